@@ -0,0 +1,110 @@
+//! 编解码热路径基准：不同负载大小的 encode/decode，打包缓冲区的流式解码，每个数据报新分配与复用缓冲池的打包发送，
+//! 以及接收路径上复制数据体与从接收缓冲切出数据体的解码
+//! 运行：cargo bench --bench codec；仅检查能否编译：cargo bench --no-run
+
+use bytes::BytesMut;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use link_rs::pool::{BufferPool, RecvArena};
+use link_rs::segment::{self, Segment, SegmentType};
+use std::hint::black_box;
+
+const PAYLOAD_SIZES: [(&str, usize); 3] = [("32B", 32), ("1KB", 1024), ("32KB", 32 * 1024)];
+const PACKED_SEGMENTS: usize = 1000;
+
+fn bench_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for (name, size) in PAYLOAD_SIZES {
+        let segment = Segment::new(SegmentType::Data, 1, vec![0xAB; size]);
+        group.throughput(Throughput::Bytes((Segment::FIXED_HEADER_LEN + size) as u64));
+        group.bench_function(name, |b| b.iter(|| black_box(&segment).encode().unwrap()));
+    }
+    group.finish();
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for (name, size) in PAYLOAD_SIZES {
+        let encoded = Segment::new(SegmentType::Data, 1, vec![0xAB; size]).encode().unwrap();
+        group.throughput(Throughput::Bytes(encoded.len() as u64));
+        group.bench_function(name, |b| b.iter(|| Segment::decode(black_box(&encoded)).unwrap()));
+    }
+    group.finish();
+}
+
+fn bench_decode_from_packed(c: &mut Criterion) {
+    // 1000 个 1KB 段紧密排列在同一个缓冲区中
+    let mut packed = BytesMut::new();
+    for seq in 0..PACKED_SEGMENTS as u64 {
+        packed.extend_from_slice(&Segment::new(SegmentType::Data, seq, vec![0xCD; 1024]).encode().unwrap());
+    }
+
+    let mut group = c.benchmark_group("decode_from");
+    group.throughput(Throughput::Bytes(packed.len() as u64));
+    group.bench_function("1000x1KB", |b| {
+        b.iter(|| {
+            let mut buf = packed.clone();
+            let mut count = 0;
+            while let Some(segment) = Segment::decode_from(&mut buf).unwrap() {
+                black_box(segment);
+                count += 1;
+            }
+            assert_eq!(count, PACKED_SEGMENTS);
+        })
+    });
+    group.finish();
+}
+
+fn bench_pack(c: &mut Criterion) {
+    // 一批 32 个 300 字节的段打包成 1200 字节以内的数据报，用完后丢弃或归还
+    let segments: Vec<_> = (0..32).map(|seq| Segment::new(SegmentType::Data, seq, vec![0xEF; 300])).collect();
+    let pool = BufferPool::with_limits(1200, 1024 * 1024);
+
+    let mut group = c.benchmark_group("pack_datagrams");
+    group.throughput(Throughput::Elements(segments.len() as u64));
+    group.bench_function("fresh", |b| b.iter(|| black_box(segment::pack_datagrams(black_box(&segments), 1200).unwrap())));
+    group.bench_function("pooled", |b| {
+        b.iter(|| {
+            for datagram in segment::pack_datagrams_with(black_box(&segments), 1200, &pool).unwrap() {
+                pool.put(black_box(datagram));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_recv_path(c: &mut Criterion) {
+    // 一个打包了 8 个 1KB 段的数据报：先写入接收空间（代替内核），再解码出全部段
+    let mut packed = BytesMut::new();
+    for seq in 0..8 {
+        packed.extend_from_slice(&Segment::new(SegmentType::Data, seq, vec![0xCD; 1024]).encode().unwrap());
+    }
+    let len = packed.len();
+
+    let mut group = c.benchmark_group("recv_path");
+    group.throughput(Throughput::Bytes(len as u64));
+    // 改动前的路径：读入固定缓冲，复制成数据报，解码时再复制数据体
+    let mut buf = vec![0u8; 65536];
+    group.bench_function("copied", |b| {
+        b.iter(|| {
+            buf[..len].copy_from_slice(black_box(&packed));
+            let mut datagram = BytesMut::from(&buf[..len]);
+            while let Some(segment) = Segment::decode_from(&mut datagram).unwrap() {
+                black_box(segment);
+            }
+        })
+    });
+    let mut arena = RecvArena::new(65536);
+    group.bench_function("zero_copy", |b| {
+        b.iter(|| {
+            arena.space()[..len].copy_from_slice(black_box(&packed));
+            let mut datagram = arena.split(len);
+            while let Some(segment) = Segment::decode_bytes(&mut datagram).unwrap() {
+                black_box(segment);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_encode, bench_decode, bench_decode_from_packed, bench_pack, bench_recv_path);
+criterion_main!(benches);
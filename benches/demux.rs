@@ -0,0 +1,85 @@
+//! 分发路径的连接表基准：8 个线程各自路由数据报（读段头取连接 ID、在 1 万条连接的表中查找），
+//! 比较所有线程共用一把锁保护的一张表（最直接的实现）与监听器现在的做法——每个分发任务独占一个分片，查找不经过锁。
+//! 两种表都只在这里构造，分片方式与监听器一致：内核按四元组把对端固定在一个套接字上，这里按连接 ID 取模
+//! 运行：cargo bench --bench demux
+
+use bytes::BytesMut;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use link_rs::segment::{self, Segment, SegmentType};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const THREADS: usize = 8;
+const CONNECTIONS: u32 = 10_000;
+const LOOKUPS: usize = 10_000; // 每个线程每轮路由的数据报数
+
+// 表中的一条连接；监听器里是连接的共享状态
+type Entry = Arc<u32>;
+
+// 每个线程要路由的数据报：只属于它的分片的连接
+fn datagrams(shard: usize) -> Vec<BytesMut> {
+    (0..CONNECTIONS)
+        .filter(|id| *id as usize % THREADS == shard)
+        .cycle()
+        .take(LOOKUPS)
+        .map(|id| Segment::builder(SegmentType::Data).conn_id(id).data_seq(1).payload(&b"x"[..]).build().unwrap().encode().unwrap())
+        .collect()
+}
+
+fn route(table: &HashMap<u32, Entry>, datagram: &[u8]) -> Option<Entry> {
+    let header = segment::parse_header(datagram).ok()?;
+    table.get(&header.conn_id).cloned()
+}
+
+// 8 个线程同时开始，返回最慢的线程完成 `iters` 轮所用的时间
+fn run(iters: u64, lookup: impl Fn(usize, &[u8]) + Sync, inputs: &[Vec<BytesMut>]) -> Duration {
+    let start = Barrier::new(THREADS + 1);
+    thread::scope(|scope| {
+        let workers: Vec<_> = inputs
+            .iter()
+            .enumerate()
+            .map(|(shard, datagrams)| {
+                let (start, lookup) = (&start, &lookup);
+                scope.spawn(move || {
+                    start.wait();
+                    let began = Instant::now();
+                    for _ in 0..iters {
+                        for datagram in datagrams {
+                            lookup(shard, datagram);
+                        }
+                    }
+                    began.elapsed()
+                })
+            })
+            .collect();
+        start.wait();
+        workers.into_iter().map(|worker| worker.join().unwrap()).max().unwrap()
+    })
+}
+
+fn bench_lookup(c: &mut Criterion) {
+    let inputs: Vec<_> = (0..THREADS).map(datagrams).collect();
+    let mut group = c.benchmark_group("demux_lookup");
+    group.throughput(Throughput::Elements((THREADS * LOOKUPS) as u64));
+
+    // 所有线程共用一张表，每次查找都要拿锁
+    let global = Mutex::new((0..CONNECTIONS).map(|id| (id, Arc::new(id))).collect::<HashMap<_, _>>());
+    group.bench_function("global_mutex", |b| {
+        b.iter_custom(|iters| run(iters, |_, datagram| drop(black_box(route(&global.lock().unwrap(), datagram))), &inputs))
+    });
+
+    // 每个线程独占自己的分片，与监听器的分发任务一样
+    let shards: Vec<HashMap<_, _>> = (0..THREADS)
+        .map(|shard| (0..CONNECTIONS).filter(|id| *id as usize % THREADS == shard).map(|id| (id, Arc::new(id))).collect())
+        .collect();
+    group.bench_function("task_owned", |b| {
+        b.iter_custom(|iters| run(iters, |shard, datagram| drop(black_box(route(&shards[shard], datagram))), &inputs))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_lookup);
+criterion_main!(benches);
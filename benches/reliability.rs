@@ -0,0 +1,176 @@
+//! 可靠性热路径基准：重传队列在大量在途段下的 ack 处理，5 万个定时器在时间轮与逐个 tokio 定时器下的登记、取消与到期，
+//! 以及重传队列与重排缓冲区在 256、4k、64k 的窗口下以环形缓冲区与有序映射为存储时的插入/确认吞吐
+//! 运行：cargo bench --bench reliability
+
+use bytes::Bytes;
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use link_rs::recv_buffer::ReceiveBuffer;
+use link_rs::retransmit::{BackoffPolicy, RetransmitQueue};
+use link_rs::ring::{Backing, MapBacked, RingBacked};
+use link_rs::segment::{Segment, SegmentType};
+use link_rs::seq::SeqNum;
+use link_rs::timer::{self, TimerWheel};
+use std::future::{Future, poll_fn};
+use std::hint::black_box;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+const IN_FLIGHT: u64 = 10_000;
+const PAYLOAD: usize = 1024;
+
+fn filled_queue() -> RetransmitQueue {
+    let now = Instant::now();
+    let mut queue = RetransmitQueue::new(Duration::from_millis(200));
+    for seq in 0..IN_FLIGHT {
+        queue.on_send(Segment::new(SegmentType::Data, seq, vec![0; PAYLOAD]), now).unwrap();
+    }
+    queue
+}
+
+fn bench_ack_processing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("retransmit_ack");
+    group.throughput(Throughput::Bytes(IN_FLIGHT * PAYLOAD as u64));
+
+    // 逐段累计确认 10k 个在途段
+    group.bench_function("cumulative_10k", |b| {
+        b.iter_batched(
+            filled_queue,
+            |mut queue| {
+                for seq in 0..IN_FLIGHT {
+                    black_box(queue.on_ack(SeqNum::new(seq)));
+                }
+                queue
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    // 逐段选择性确认
+    group.bench_function("selective_10k", |b| {
+        b.iter_batched(
+            filled_queue,
+            |mut queue| {
+                for seq in (0..IN_FLIGHT).rev() {
+                    black_box(queue.on_selective_ack(SeqNum::new(seq)));
+                }
+                queue
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+const TIMERS: u64 = 50_000;
+
+fn bench_timers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("timers_50k");
+    group.throughput(Throughput::Elements(TIMERS));
+    // 截止时间分布在 200ms 到 1.2s 之间，像一批不同时刻发出的段的 RTO；全部登记后一半在到期前被确认取消，其余逐批到期
+    group.bench_function("wheel", |b| {
+        b.iter(|| {
+            let t0 = Instant::now();
+            let mut wheel = TimerWheel::new(timer::DEFAULT_GRANULARITY);
+            let keys: Vec<_> = (0..TIMERS).map(|i| wheel.insert(t0 + Duration::from_millis(200 + i % 1000), i)).collect();
+            for key in keys.into_iter().step_by(2) {
+                black_box(wheel.cancel(key));
+            }
+            let mut fired = 0;
+            let mut now = t0;
+            while let Some(deadline) = wheel.next_deadline() {
+                now = deadline.max(now + Duration::from_millis(10));
+                fired += wheel.expire(now).len();
+            }
+            assert_eq!(fired as u64, TIMERS / 2);
+        })
+    });
+
+    // 每个段一个 tokio 定时器：登记（首次 poll）后丢弃一半，其余由运行时的定时器到期
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_time().start_paused(true).build().unwrap();
+    group.bench_function("tokio_sleep", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let start = tokio::time::Instant::now();
+                let mut sleeps: Vec<_> =
+                    (0..TIMERS).map(|i| Box::pin(tokio::time::sleep_until(start + Duration::from_millis(200 + i % 1000)))).collect();
+                poll_fn(|cx| {
+                    for sleep in &mut sleeps {
+                        let _ = sleep.as_mut().poll(cx);
+                    }
+                    Poll::Ready(())
+                })
+                .await;
+                let remaining: Vec<Pin<Box<tokio::time::Sleep>>> = sleeps.into_iter().skip(1).step_by(2).collect();
+                tokio::time::advance(Duration::from_millis(1200)).await;
+                for sleep in remaining {
+                    sleep.await;
+                }
+            })
+        })
+    });
+    group.finish();
+}
+
+const WINDOWS: [usize; 3] = [256, 4096, 65536];
+const SEGMENTS: u64 = 1 << 18;
+
+fn outgoing() -> Vec<Segment> {
+    (0..SEGMENTS).map(|seq| Segment::new(SegmentType::Data, seq, Bytes::from_static(b"payload"))).collect()
+}
+
+// 窗口填满之后每发出一个段累计确认最早的一个，在途段数保持在窗口大小
+fn send_and_ack<B: Backing>(window: usize, segments: Vec<Segment>) -> RetransmitQueue<B> {
+    let now = Instant::now();
+    let mut queue = RetransmitQueue::<B>::with_backing(Duration::from_millis(200), BackoffPolicy::default()).with_capacity(window);
+    for segment in segments {
+        let seq = segment.seq().get();
+        queue.on_send(segment, now).unwrap();
+        if let Some(oldest) = seq.checked_sub(window as u64 - 1) {
+            black_box(queue.on_ack(SeqNum::new(oldest)));
+        }
+    }
+    queue
+}
+
+// 每个窗口的第一个段最后到达：其余段在它之后的空洞后面缓存，补齐时整个窗口一起交付
+fn reorder_and_deliver<B: Backing>(window: usize) -> ReceiveBuffer<B> {
+    let mut buffer = ReceiveBuffer::<B>::with_backing(SeqNum::new(0), window);
+    let data = Bytes::from_static(b"payload");
+    for start in (0..SEGMENTS).step_by(window) {
+        for seq in (start + 1..start + window as u64).chain([start]) {
+            black_box(buffer.insert(SeqNum::new(seq), data.clone()));
+        }
+        while let Some(data) = buffer.pop_ready() {
+            black_box(data);
+        }
+    }
+    buffer
+}
+
+fn bench_backings(c: &mut Criterion) {
+    let mut group = c.benchmark_group("retransmit_window");
+    group.throughput(Throughput::Elements(SEGMENTS));
+    group.sample_size(20);
+    for window in WINDOWS {
+        group.bench_with_input(BenchmarkId::new("ring", window), &window, |b, &window| {
+            b.iter_batched(outgoing, |segments| send_and_ack::<RingBacked>(window, segments), BatchSize::LargeInput)
+        });
+        group.bench_with_input(BenchmarkId::new("map", window), &window, |b, &window| {
+            b.iter_batched(outgoing, |segments| send_and_ack::<MapBacked>(window, segments), BatchSize::LargeInput)
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("reorder_window");
+    group.throughput(Throughput::Elements(SEGMENTS));
+    group.sample_size(20);
+    for window in WINDOWS {
+        group.bench_with_input(BenchmarkId::new("ring", window), &window, |b, &window| b.iter(|| reorder_and_deliver::<RingBacked>(window)));
+        group.bench_with_input(BenchmarkId::new("map", window), &window, |b, &window| b.iter(|| reorder_and_deliver::<MapBacked>(window)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_ack_processing, bench_timers, bench_backings);
+criterion_main!(benches);
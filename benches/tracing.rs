@@ -0,0 +1,38 @@
+//! 日志开销基准：逐段事件在没有订阅者、订阅者只开到 info、以及 debug 全开时的单段成本
+//! 前两者应与不记录日志几乎相同。运行：cargo bench --bench tracing
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use link_rs::segment::{Segment, SegmentType};
+use link_rs::trace::{self, Direction};
+use std::hint::black_box;
+use std::net::SocketAddr;
+use tracing_subscriber::EnvFilter;
+
+fn trace_segment(segment: &Segment, peer: SocketAddr) {
+    if trace::enabled() {
+        trace::segment(Direction::Inbound, black_box(segment), peer);
+    }
+}
+
+fn subscriber(filter: &str) -> impl tracing::Subscriber + Send + Sync {
+    tracing_subscriber::fmt().with_env_filter(EnvFilter::new(filter)).with_writer(std::io::sink).finish()
+}
+
+fn bench_segment_event(c: &mut Criterion) {
+    let segment = Segment::new(SegmentType::Data, 42, vec![0; 1024]);
+    let peer: SocketAddr = "10.0.0.2:2".parse().unwrap();
+    let mut group = c.benchmark_group("segment_event");
+
+    group.bench_function("baseline", |b| b.iter(|| black_box(&segment)));
+    group.bench_function("no_subscriber", |b| b.iter(|| trace_segment(&segment, peer)));
+    tracing::subscriber::with_default(subscriber("info"), || {
+        group.bench_function("info_filter", |b| b.iter(|| trace_segment(&segment, peer)));
+    });
+    tracing::subscriber::with_default(subscriber("link=debug"), || {
+        group.bench_function("debug_to_sink", |b| b.iter(|| trace_segment(&segment, peer)));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_segment_event);
+criterion_main!(benches);
@@ -0,0 +1,96 @@
+//! 全双工聊天示例：一端监听、另一端连接，双方都可以随时输入并收到对方的消息。
+//!
+//!     cargo run --example chat -- listen 127.0.0.1:9000
+//!     cargo run --example chat -- connect 127.0.0.1:9000
+//!
+//! 连接以 `split` 拆成读写两半，分别交给两个任务：一个把标准输入的每一行作为一条消息发送，另一个打印收到的消息。
+//! 标准输入结束时关闭写方向，对方收到 FIN 后结束读取；两个方向都结束后以 `reunite` 合回连接，以 `close` 完成关闭。监听的一端在整个会话期间保留 `Listener`：
+//! 已接受连接的数据报经它的分发任务转交。
+
+use bytes::Bytes;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::split::{RecvHalf, SendHalf};
+use std::io::BufRead;
+use std::net::SocketAddr;
+use tokio::sync::mpsc;
+
+const USAGE: &str = "用法: chat <listen|connect> <addr:port>";
+
+// 监听时连同 `Listener` 一起返回，由调用方保留到连接关闭
+async fn open(mode: &str, addr: SocketAddr) -> Result<(Connection, Option<Listener>), LinkError> {
+    match mode {
+        "listen" => {
+            let listener = Listener::bind(addr).await?;
+            println!("在 {} 上等待对方连接", listener.local_addr()?);
+            let (connection, peer) = listener.accept().await?;
+            println!("{} 已连接", peer);
+            Ok((connection, Some(listener)))
+        }
+        _ => {
+            let connection = Connection::connect(addr).await?;
+            println!("已连接到 {}", addr);
+            Ok((connection, None))
+        }
+    }
+}
+
+// 标准输入的阻塞读取放在独立线程，读不完的一行不会拖住退出
+fn stdin_lines() -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel(16);
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if tx.blocking_send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (mode, addr) = match args.as_slice() {
+        [mode, addr] if mode == "listen" || mode == "connect" => (mode.as_str(), addr.parse::<SocketAddr>()?),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let (connection, _listener) = open(mode, addr).await?;
+    let (recv, send) = connection.split();
+
+    let mut lines = stdin_lines();
+    let (stop, mut stopped) = tokio::sync::oneshot::channel::<()>();
+    let writer = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                line = lines.recv() => match line {
+                    Some(line) => send.send(Bytes::from(line)).await?,
+                    None => break,
+                },
+                // 对方先结束时不再等待标准输入
+                _ = &mut stopped => break,
+            }
+        }
+        send.shutdown_write().await?;
+        Ok::<SendHalf, LinkError>(send)
+    });
+    let reader = tokio::spawn(async move {
+        while let Some(message) = recv.recv().await? {
+            println!("对方: {}", String::from_utf8_lossy(&message));
+        }
+        println!("对方已结束发送");
+        Ok::<RecvHalf, LinkError>(recv)
+    });
+
+    let recv = reader.await?;
+    let _ = stop.send(());
+    let send = writer.await??;
+    let connection = recv?.reunite(send)?;
+    connection.close().await?;
+    Ok(())
+}
@@ -0,0 +1,32 @@
+//! 确认帧解析的 libFuzzer 目标
+//! 不变量：任意输入、任意协商的校验算法下 `AckFrame::parse` 不 panic；它接受的确认占用的字节不超过输入，
+//! 重新编码后与输入前缀逐字节一致，且完整解码同一前缀得到同样的确认（见 `ack_frame` 模块）
+
+#![no_main]
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use link_rs::ack_frame::AckFrame;
+use link_rs::checksum::ChecksumAlgorithm;
+use link_rs::segment::Segment;
+
+fuzz_target!(|data: &[u8]| {
+    // 第一个字节选择协商的算法，其余是数据报
+    let Some((&algorithm, datagram)) = data.split_first() else {
+        return;
+    };
+    let Some(negotiated) = ChecksumAlgorithm::from_id(algorithm % 3) else {
+        return;
+    };
+    let Some((frame, len)) = AckFrame::parse(datagram, negotiated) else {
+        return;
+    };
+    assert!(len <= datagram.len());
+    assert_eq!(frame.encoded_len(), len);
+    let mut encoded = BytesMut::new();
+    frame.encode_into(&mut encoded);
+    assert_eq!(&encoded[..], &datagram[..len]);
+
+    let segment = Segment::decode(&datagram[..len]).expect("a parsed ack must decode as a segment");
+    assert_eq!(AckFrame::from(&segment), frame);
+});
@@ -0,0 +1,67 @@
+//! 段解码的 libFuzzer 目标
+//! 不变量：任意输入不 panic、不越过解码上限分配内存；成功解码的段重新编码后与输入前缀逐字节一致；
+//! 解码成功的 SACK 段总能提取出区间；只读段头的 `parse_header` 与完整解码的判断一致；
+//! 允许未知类型时（`decode_with_options`）按结构解析的 `RawSegment` 同样重新编码为输入前缀
+
+#![no_main]
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use link_rs::sack::{self, SackInfo};
+use link_rs::segment::{self, DecodeOptions, Segment};
+
+fuzz_target!(|data: &[u8]| {
+    // 1. 单段解码：成功时重新编码必须等于输入中被声明的那段前缀
+    if let Ok(segment) = Segment::decode(data) {
+        let encoded = segment.encode().expect("decoded segment must re-encode");
+        assert!(encoded.len() <= data.len());
+        assert_eq!(&encoded[..], &data[..encoded.len()]);
+
+        // 解码已校验 SACK 数据体，提取区间不会失败，且重新编码后一致
+        if let Some(sack) = SackInfo::from_segment(&segment).expect("decoded SACK must parse") {
+            if sack.ranges.len() <= sack::MAX_BLOCKS {
+                assert_eq!(&sack::encode_ranges(&sack.ranges), segment.data());
+            }
+        }
+    }
+
+    // 2. 流式解码：逐个取出紧密排列的段，每段消耗的字节必须等于其重新编码
+    let mut buf = BytesMut::from(data);
+    let mut offset = 0;
+    loop {
+        let before_len = buf.len();
+        match Segment::decode_from(&mut buf) {
+            Ok(Some(segment)) => {
+                let consumed = before_len - buf.len();
+                assert!(consumed <= Segment::MAX_SEGMENT_LEN);
+                let encoded = segment.encode().expect("decoded segment must re-encode");
+                assert_eq!(&encoded[..], &data[offset..offset + consumed]);
+                offset += consumed;
+            }
+            // 数据不足时不得消耗任何字节
+            Ok(None) => {
+                assert_eq!(buf.len(), before_len);
+                break;
+            }
+            Err(_) => break,
+        }
+    }
+
+    // 3. 只读段头：它拒绝的输入完整解码以同样的错误拒绝，两者都接受时读出的字段相同
+    match (segment::parse_header(data), Segment::decode(data)) {
+        (Ok(header), Ok(segment)) => {
+            assert_eq!(header.total_len, segment.encoded_len());
+            assert_eq!((header.segment_type, header.flags, header.stream_id), (segment.segment_type(), segment.flags(), segment.stream_id()));
+            assert_eq!((header.conn_id, header.seq), (segment.conn_id(), segment.seq()));
+        }
+        (Ok(header), Err(_)) => assert!(header.total_len <= data.len()),
+        (Err(e), decoded) => assert_eq!(decoded.err(), Some(e)),
+    }
+
+    // 4. 允许未知类型：已知类型的结果与严格模式相同，未知类型的段重新编码后同样等于输入前缀
+    if let Ok(decoded) = Segment::decode_with_options(data, DecodeOptions { allow_unknown: true }) {
+        let encoded = decoded.encode().expect("decoded segment must re-encode");
+        assert!(encoded.len() <= data.len());
+        assert_eq!(&encoded[..], &data[..encoded.len()]);
+    }
+});
@@ -0,0 +1,24 @@
+//! 段头选项区的 libFuzzer 目标
+//! 不变量：任意输入不 panic；`Options::decode` 接受的选项区不超过 `options::MAX_LEN`，原样保留输入、可以再次解码；
+//! 识别出的每个选项（未识别的被跳过）单独重新编码后解码回同一个选项
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use link_rs::options::{self, Options};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(decoded) = Options::decode(data) else {
+        return;
+    };
+    assert!(decoded.len() <= options::MAX_LEN);
+    assert_eq!(decoded.as_bytes(), data);
+    assert_eq!(Options::decode(decoded.as_bytes()).as_ref(), Ok(&decoded));
+
+    // 每个识别出的选项单独编码后解码回同一个选项；参数选项省略的字段按默认值补全，重新编码的可能比原来的长
+    for option in decoded.iter() {
+        let single = Options::new().with(option).expect("a single option fits in the option area");
+        let single = Options::decode(single.as_bytes()).expect("an encoded option must decode");
+        assert!(single.iter().eq([option]));
+    }
+});
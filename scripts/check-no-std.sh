@@ -0,0 +1,13 @@
+#!/bin/sh
+# 检查线上格式的编解码能在没有标准库的目标上构建（`#![no_std]` + `alloc`，见 Cargo.toml 的 `alloc` 特性），
+# 浏览器中的编解码（`wasm` 特性）能为 wasm32-unknown-unknown 构建，
+# 以及关闭 tokio、只用标准库时 crate 与它的测试仍能构建。需要先安装目标：
+#     rustup target add thumbv7em-none-eabihf wasm32-unknown-unknown
+set -eu
+cd "$(dirname "$0")/.."
+
+cargo build --no-default-features --features alloc --target thumbv7em-none-eabihf
+cargo clippy --no-default-features --features alloc --target thumbv7em-none-eabihf -- -D warnings
+cargo build --no-default-features --features wasm --target wasm32-unknown-unknown
+cargo clippy --no-default-features --features wasm --target wasm32-unknown-unknown -- -D warnings
+cargo build --no-default-features --features std --all-targets
@@ -0,0 +1,351 @@
+//! 接收端确认生成
+//! 确认段的 ack 字段表示累计确认："该序列号（含）之前的数据都已收到"。
+//! 按序到达采用延迟确认：每收到 `LinkConfig::ack_every_n_segments`（默认两个）段确认一次，或自第一个未确认段到达起超过
+//! `max_ack_delay` 后确认，以先到者为准。重复、超窗时立即确认；乱序与补齐空洞在 `immediate_ack_on_gap`（默认打开）时
+//! 立即确认（供快速重传使用），否则与按序段一样计数。对端以 ACK-now 选项请求时由 `Receiver` 立即确认；
+//! 上层取走数据、窗口从不足缓冲区一半涨回一半以上时立即发出窗口更新（包括从 0 打开），
+//! 更小的增长留给下一个确认捎带，避免每读一个段就发一个确认。累计点之后存在空洞时确认段附带 SACK 区间。
+//! 只根据重排缓冲区的真实状态确认，未被缓存的数据绝不会被确认。
+//! 确认以栈上的 `AckFrame` 交出（见 `ack_frame` 模块），生成确认不分配。
+
+use crate::ack_frame::AckFrame;
+use crate::config::LinkConfig;
+use crate::recv_buffer::{InsertOutcome, ReceiveBuffer};
+use crate::seq::SeqNum;
+use std::time::{Duration, Instant};
+
+/// 累计多少个按序段后不再等待定时器（`LinkConfig::ack_every_n_segments` 的默认值）
+pub const ACK_EVERY: u32 = 2;
+
+/// 延迟确认判定：有未确认的按序段，且已攒够 `every` 个或等待超过 `max_delay` 时返回 true。
+/// 纯函数，时间由调用方注入。
+pub fn should_ack(unacked: u32, every: u32, first_unacked_at: Option<Instant>, max_delay: Duration, now: Instant) -> bool {
+    match first_unacked_at {
+        Some(first) => unacked >= every || now.saturating_duration_since(first) >= max_delay,
+        None => false,
+    }
+}
+
+/// 确认生成器
+#[derive(Debug)]
+pub struct AckGenerator {
+    max_delay: Duration,                // 延迟确认最长等待时间，为 0 时每段都立即确认
+    every: u32,                         // 攒够多少个未确认段后立即确认
+    immediate_on_gap: bool,             // 乱序与补齐空洞时立即确认
+    sack: bool,                         // 有空洞时附带 SACK 区间
+    unacked: u32,                       // 尚未确认的按序段数
+    first_unacked_at: Option<Instant>,  // 第一个未确认段到达的时间
+    gap_outstanding: bool,              // 累计点之后有乱序缓存的段
+    last_acked: Option<SeqNum>,         // 最近一次发出的累计确认值
+    last_window: Option<u32>,           // 最近一次通告的窗口
+    acks_sent: u64,
+    dup_acks_sent: u64,                 // 重复确认（累计点未推进）的次数
+}
+
+impl AckGenerator {
+    pub fn new(config: &LinkConfig) -> Self {
+        Self {
+            max_delay: config.max_ack_delay,
+            every: u32::try_from(config.ack_every_n_segments).unwrap_or(u32::MAX),
+            immediate_on_gap: config.immediate_ack_on_gap,
+            sack: config.sack,
+            unacked: 0,
+            first_unacked_at: None,
+            gap_outstanding: false,
+            last_acked: None,
+            last_window: None,
+            acks_sent: 0,
+            dup_acks_sent: 0,
+        }
+    }
+
+    /// 处理一次数据段插入的结果，返回需要立即发送的确认；
+    /// 返回 None 时确认被推迟，需在 `next_deadline` 调用 `poll_timeout`
+    pub fn on_data(&mut self, outcome: InsertOutcome, buffer: &ReceiveBuffer, now: Instant) -> Option<AckFrame> {
+        let filled_gap = self.gap_outstanding;
+        self.gap_outstanding = buffer.has_gaps();
+        match outcome {
+            // 补齐空洞：立即确认跳跃后的累计点
+            InsertOutcome::Ready if filled_gap && self.immediate_on_gap => Some(self.ack_now(buffer)),
+            // 按序到达（以及不立即确认时的乱序段）：攒够段数或等待超时后再确认
+            InsertOutcome::Ready => self.delay(buffer, now),
+            InsertOutcome::Buffered if !self.immediate_on_gap => self.delay(buffer, now),
+            // 乱序、重复、超窗：立即重复确认当前累计点
+            InsertOutcome::Buffered | InsertOutcome::Duplicate | InsertOutcome::Dropped => {
+                Some(self.ack_now(buffer))
+            }
+        }
+    }
+
+    // 计入一个未确认段，攒够时立即确认
+    fn delay(&mut self, buffer: &ReceiveBuffer, now: Instant) -> Option<AckFrame> {
+        self.unacked += 1;
+        self.first_unacked_at.get_or_insert(now);
+        self.poll_timeout(buffer, now)
+    }
+
+    /// 延迟确认定时器检查：到期则返回确认
+    pub fn poll_timeout(&mut self, buffer: &ReceiveBuffer, now: Instant) -> Option<AckFrame> {
+        should_ack(self.unacked, self.every, self.first_unacked_at, self.max_delay, now).then(|| self.ack_now(buffer))
+    }
+
+    /// 上层取走数据或窗口的限制放宽后调用：上次通告的窗口不足当前窗口的一半、现在已达到一半时立即确认，让对端恢复发送。
+    /// 窗口为 1 的缓冲区从 0 打开即通告
+    pub fn on_window_update(&mut self, buffer: &ReceiveBuffer) -> Option<AckFrame> {
+        let threshold = u32::try_from((buffer.window() / 2).max(1)).unwrap_or(u32::MAX);
+        let available = u32::try_from(buffer.available()).unwrap_or(u32::MAX);
+        let opened = self.last_window.is_some_and(|last| last < threshold && available >= threshold);
+        opened.then(|| self.ack_now(buffer))
+    }
+
+    /// 调整攒够多少个按序段后立即确认；已攒够的在下一个段到达或延迟确认定时器到期时确认
+    pub fn set_every(&mut self, every: u32) {
+        self.every = every.max(1);
+    }
+
+    /// 延迟确认的到期时间；没有待确认的段时为 None
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.first_unacked_at.map(|first| first + self.max_delay)
+    }
+
+    /// 立即构造一个反映缓冲区当前状态的确认（有空洞且协商了 SACK 时附带区间），并清空延迟确认状态
+    pub fn ack_now(&mut self, buffer: &ReceiveBuffer) -> AckFrame {
+        let cumulative = buffer.cumulative_ack();
+        if self.last_acked == Some(cumulative) {
+            self.dup_acks_sent += 1;
+        }
+        self.last_acked = Some(cumulative);
+        self.acks_sent += 1;
+        self.unacked = 0;
+        self.first_unacked_at = None;
+
+        let window = u32::try_from(buffer.available()).unwrap_or(u32::MAX);
+        self.last_window = Some(window);
+        let mut frame = AckFrame::new(cumulative, window);
+        if self.sack && buffer.has_gaps() {
+            frame.sack = buffer.sack_blocks();
+        }
+        frame
+    }
+
+    pub fn last_acked(&self) -> Option<SeqNum> {
+        self.last_acked
+    }
+
+    pub fn acks_sent(&self) -> u64 {
+        self.acks_sent
+    }
+
+    pub fn dup_acks_sent(&self) -> u64 {
+        self.dup_acks_sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sack::{MAX_BLOCKS, SackInfo};
+    use crate::segment::{Segment, SegmentType};
+    use bytes::Bytes;
+
+    const DELAY: Duration = Duration::from_millis(25);
+
+    fn acker(max_ack_delay: Duration) -> AckGenerator {
+        AckGenerator::new(&LinkConfig { max_ack_delay, ..LinkConfig::default() })
+    }
+
+    // 同一时刻依次插入序列号，收集每次立即发出的 ack 值；max_delay 为 0 即逐段确认
+    fn drive(seqs: &[u64], max_delay: Duration) -> (Vec<u64>, AckGenerator) {
+        let now = Instant::now();
+        let mut buffer = ReceiveBuffer::new(SeqNum::new(1), 64);
+        let mut acker = acker(max_delay);
+        let acks = seqs
+            .iter()
+            .filter_map(|&seq| {
+                let outcome = buffer.insert(SeqNum::new(seq), Bytes::from_static(b"x"));
+                acker.on_data(outcome, &buffer, now).map(|ack| ack.ack.get())
+            })
+            .collect();
+        (acks, acker)
+    }
+
+    #[test]
+    fn test_in_order_acks() {
+        let (acks, acker) = drive(&[1, 2, 3], Duration::ZERO);
+        assert_eq!(acks, vec![1, 2, 3]);
+        assert_eq!(acker.dup_acks_sent(), 0);
+    }
+
+    #[test]
+    fn test_gap_produces_duplicate_acks() {
+        // 2 丢失：3、4、5 每次都立即重复确认 1
+        let (acks, acker) = drive(&[1, 3, 4, 5], Duration::ZERO);
+        assert_eq!(acks, vec![1, 1, 1, 1]);
+        assert_eq!(acker.dup_acks_sent(), 3);
+    }
+
+    #[test]
+    fn test_gap_attaches_sack_ranges() {
+        let mut buffer = ReceiveBuffer::new(SeqNum::new(1), 64);
+        let mut acker = acker(DELAY);
+        let now = Instant::now();
+        for seq in [1, 3, 4, 6] {
+            buffer.insert(SeqNum::new(seq), Bytes::from_static(b"x"));
+        }
+        let ack = acker.ack_now(&buffer);
+        let sack = SackInfo::from_segment(&ack.into()).unwrap().unwrap();
+        assert_eq!(sack.cumulative, SeqNum::new(1));
+        assert_eq!(sack.ranges, buffer.sack_ranges());
+
+        // 空洞补齐后恢复为普通确认
+        for seq in [2, 5] {
+            let outcome = buffer.insert(SeqNum::new(seq), Bytes::from_static(b"x"));
+            acker.on_data(outcome, &buffer, now);
+        }
+        assert_eq!(SackInfo::from_segment(&acker.ack_now(&buffer).into()), Ok(None));
+    }
+
+    #[test]
+    fn test_many_gaps_fill_every_block() {
+        let mut buffer = ReceiveBuffer::new(SeqNum::new(1), 256);
+        let mut acker = acker(DELAY);
+        for seq in (3..100).step_by(2) {
+            buffer.insert(SeqNum::new(seq), Bytes::from_static(b"x"));
+        }
+        // 一个确认能携带的区间全部用上，与按区间列表构造的确认段逐字节相同
+        let ack = acker.ack_now(&buffer);
+        assert_eq!(&ack.sack[..], &buffer.sack_ranges()[..MAX_BLOCKS]);
+        let expected = Segment::builder(SegmentType::Ack).ack(SeqNum::new(0)).window(ack.window).sack(&buffer.sack_ranges()).build().unwrap();
+        assert_eq!(Segment::from(ack).encode().unwrap(), expected.encode().unwrap());
+    }
+
+    #[test]
+    fn test_sack_off_sends_plain_acks() {
+        let mut buffer = ReceiveBuffer::new(SeqNum::new(1), 64);
+        let mut acker = AckGenerator::new(&LinkConfig { sack: false, ..LinkConfig::default() });
+        for seq in [1, 3, 4] {
+            buffer.insert(SeqNum::new(seq), Bytes::from_static(b"x"));
+        }
+        let ack = acker.ack_now(&buffer);
+        assert_eq!((ack.ack, SackInfo::from_segment(&ack.into())), (SeqNum::new(1), Ok(None)));
+    }
+
+    #[test]
+    fn test_gap_filled_jumps_cumulative_point() {
+        let (acks, _) = drive(&[1, 3, 4, 2, 5], Duration::ZERO);
+        assert_eq!(acks, vec![1, 1, 1, 4, 5]);
+    }
+
+    #[test]
+    fn test_never_acks_unbuffered_data() {
+        let mut buffer = ReceiveBuffer::new(SeqNum::new(1), 2);
+        let mut acker = acker(DELAY);
+
+        // 超出窗口被丢弃的段不会推进确认，并通告当前窗口
+        let outcome = buffer.insert(SeqNum::new(10), Bytes::from_static(b"x"));
+        assert_eq!(outcome, InsertOutcome::Dropped);
+        let ack = acker.on_data(outcome, &buffer, Instant::now()).unwrap();
+        assert_eq!(ack.ack.get(), 0);
+        assert_eq!(ack.window, 2);
+    }
+
+    #[test]
+    fn test_two_segments_coalesce_into_one_ack() {
+        let (acks, acker) = drive(&[1, 2, 3, 4], DELAY);
+        assert_eq!(acks, vec![2, 4]);
+        assert_eq!(acker.acks_sent(), 2);
+        assert_eq!(acker.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_single_segment_acked_after_delay() {
+        let t0 = Instant::now();
+        let mut buffer = ReceiveBuffer::new(SeqNum::new(1), 64);
+        let mut acker = acker(DELAY);
+
+        let outcome = buffer.insert(SeqNum::new(1), Bytes::from_static(b"x"));
+        assert!(acker.on_data(outcome, &buffer, t0).is_none());
+        assert_eq!(acker.next_deadline(), Some(t0 + DELAY));
+
+        // 定时器到期前不确认，到期后确认
+        assert!(acker.poll_timeout(&buffer, t0 + Duration::from_millis(24)).is_none());
+        assert_eq!(acker.poll_timeout(&buffer, t0 + DELAY).unwrap().ack.get(), 1);
+        assert_eq!(acker.next_deadline(), None);
+        assert!(acker.poll_timeout(&buffer, t0 + DELAY * 2).is_none());
+    }
+
+    #[test]
+    fn test_out_of_order_bypasses_delay() {
+        // 1 被延迟；3 乱序到达立即确认 1，补齐空洞的 2 也立即确认
+        let (acks, _) = drive(&[1, 3, 2], DELAY);
+        assert_eq!(acks, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_window_opening_acks_immediately() {
+        let mut buffer = ReceiveBuffer::new(SeqNum::new(1), 2);
+        let mut acker = acker(DELAY);
+        let now = Instant::now();
+        for seq in 1..=2 {
+            let outcome = buffer.insert(SeqNum::new(seq), Bytes::from_static(b"x"));
+            acker.on_data(outcome, &buffer, now);
+        }
+        assert_eq!(acker.acks_sent(), 1);
+        assert!(acker.on_window_update(&buffer).is_none());
+
+        // 上次通告窗口为 0，上层取走数据后立即通告新窗口
+        buffer.pop_ready();
+        let ack = acker.on_window_update(&buffer).unwrap();
+        assert_eq!(ack.window, 1);
+    }
+
+    #[test]
+    fn test_window_update_waits_for_half_the_buffer() {
+        let mut buffer = ReceiveBuffer::new(SeqNum::new(1), 8);
+        let mut acker = acker(DELAY);
+        let now = Instant::now();
+        for seq in 1..=6 {
+            let outcome = buffer.insert(SeqNum::new(seq), Bytes::from_static(b"x"));
+            acker.on_data(outcome, &buffer, now);
+        }
+        assert_eq!(acker.ack_now(&buffer).window, 2);
+
+        // 窗口涨到 3 还不值得单独通告，涨到缓冲区的一半（4）时立即通告，之后的增长不再单独通告
+        buffer.pop_ready();
+        assert!(acker.on_window_update(&buffer).is_none());
+        buffer.pop_ready();
+        assert_eq!(acker.on_window_update(&buffer).unwrap().window, 4);
+        buffer.pop_ready();
+        assert!(acker.on_window_update(&buffer).is_none());
+    }
+
+    #[test]
+    fn test_ack_frequency_follows_config() {
+        let config = LinkConfig { ack_every_n_segments: 8, ..LinkConfig::default() };
+        let mut buffer = ReceiveBuffer::new(SeqNum::new(1), 64);
+        let mut acker = AckGenerator::new(&config);
+        let now = Instant::now();
+        let acks: Vec<_> = (1..=64)
+            .filter_map(|seq| {
+                let outcome = buffer.insert(SeqNum::new(seq), Bytes::from_static(b"x"));
+                acker.on_data(outcome, &buffer, now).map(|ack| ack.ack.get())
+            })
+            .collect();
+        assert_eq!(acks, (1..=8).map(|n| n * 8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_gap_acks_can_be_delayed() {
+        let config = LinkConfig { immediate_ack_on_gap: false, ack_every_n_segments: 4, ..LinkConfig::default() };
+        let mut buffer = ReceiveBuffer::new(SeqNum::new(1), 64);
+        let mut acker = AckGenerator::new(&config);
+        let t0 = Instant::now();
+        // 乱序段与补齐空洞的段同样计数，攒够四个才确认；重复段仍然立即确认
+        let mut ack = |seq| acker.on_data(buffer.insert(SeqNum::new(seq), Bytes::from_static(b"x")), &buffer, t0).map(|ack| ack.ack.get());
+        assert_eq!([1, 3, 4].map(&mut ack), [None; 3]);
+        assert_eq!(ack(2), Some(4));
+        assert_eq!(ack(2), Some(4));
+        assert_eq!(ack(6), None);
+        assert_eq!(acker.next_deadline(), Some(t0 + config.max_ack_delay));
+    }
+}
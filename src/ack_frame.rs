@@ -0,0 +1,259 @@
+//! 确认帧
+//! 确认是最频繁的段：批量传输时接收端每一两个数据段就发出一个，发送端要逐个处理。`AckFrame` 是 Ack 段在栈上的表示，
+//! SACK 区间放在定长的 `SackBlocks` 中，构造、编码与解析都不分配：`encode_into` 直接写进调用方的缓冲
+//! （端点的数据报缓冲来自缓冲池），`parse` 从数据报中读出一个确认，不构造 `Segment`、不切出数据体。
+//! 接收端的确认生成（`ack` 模块）、端点的控制段打包与发送端的重传队列（`RetransmitQueue::on_ack_frame`）都以它传递确认。
+//!
+//! 线上格式就是 Ack 段（见 `segment` 模块），与同样内容的 `Segment` 的编码逐字节相同，对端无从区分。
+//! 确认帧只表示普通的确认：序列号为 0，标志位只有 SACK 与 ECE，选项区为空或只有时间戳，区间不超过 `MAX_BLOCKS` 个。
+//! 其他的 Ack 段（重新同步的请求与回应、握手的最后确认）`parse` 返回 None，照常以 `Segment` 解码。
+
+use crate::checksum::ChecksumAlgorithm;
+use crate::options::{self, Options, SegmentOption};
+use crate::sack::{BLOCK_LEN, MAX_BLOCKS, SackBlocks};
+use crate::segment::{self, Segment, SegmentFlags, SegmentType};
+use crate::seq::SeqNum;
+use bytes::{Buf, BufMut, BytesMut};
+
+// 段头中确认号的偏移：total_len(4) | type(1) | flags(1) | stream_id(2) | conn_id(4) | seq(8) 之后
+const ACK_OFFSET: usize = 4 + 1 + 1 + 2 + 4 + 8;
+
+/// 一个普通的确认
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AckFrame {
+    pub stream_id: u16,
+    pub conn_id: u32,
+    pub ack: SeqNum,                    // 累计确认点
+    pub window: u32,                    // 通告的接收窗口
+    pub ece: bool,                      // 回送收到过的 CE（ECE 标志）
+    pub timestamp: Option<(u32, u32)>,  // 时间戳选项：(value, echo)
+    pub sack: SackBlocks,               // 累计确认点之后已收到的闭区间，非空时设置 SACK 标志
+    pub checksum: ChecksumAlgorithm,    // 编码时使用的校验算法，解析时为段头中的算法
+}
+
+impl AckFrame {
+    pub fn new(ack: SeqNum, window: u32) -> Self {
+        Self { ack, window, ..Self::default() }
+    }
+
+    pub fn flags(&self) -> SegmentFlags {
+        let mut flags = SegmentFlags::empty();
+        if self.ece {
+            flags.insert(SegmentFlags::ECE);
+        }
+        if !self.sack.is_empty() {
+            flags.insert(SegmentFlags::SACK);
+        }
+        flags
+    }
+
+    /// 编码后的总长度
+    pub fn encoded_len(&self) -> usize {
+        Segment::FIXED_HEADER_LEN + self.timestamp.map_or(0, |_| options::TIMESTAMP_LEN) + self.sack.encoded_len()
+    }
+
+    /// 编码并追加到 `buf` 末尾，与 `Segment::from(self).encode_into(buf)` 写出的字节相同；
+    /// `buf` 的容量足够时不分配
+    pub fn encode_into(&self, buf: &mut BytesMut) {
+        let total_len = self.encoded_len();
+        buf.reserve(total_len);
+        let start = buf.len();
+
+        buf.put_u32(0);
+        buf.put_u8(SegmentType::Ack.into());
+        buf.put_u8(self.flags().bits());
+        buf.put_u16(self.stream_id);
+        buf.put_u32(self.conn_id);
+        buf.put_u64(0);
+        buf.put_u64(self.ack.get());
+        buf.put_u32(self.window);
+        buf.put_u8(self.checksum.id());
+        buf.put_u32(0);
+        match self.timestamp {
+            Some((value, echo)) => {
+                buf.put_u8(options::TIMESTAMP_LEN as u8);
+                buf.put_u8(options::TIMESTAMP);
+                buf.put_u8(8);
+                buf.put_u32(value);
+                buf.put_u32(echo);
+            }
+            None => buf.put_u8(0),
+        }
+        self.sack.encode_into(buf);
+
+        // 至多 16 个区间，总长度远小于 u32::MAX
+        Segment::finish(&mut buf[start..], total_len as u32, self.checksum);
+    }
+
+    /// 解析 `buf` 开头的一个段：是以 `negotiated` 算法编码、校验通过的普通确认时返回它与它占用的字节数。
+    /// 其他任何情况（不是 Ack 段、带有其他选项或标志、校验失败、数据不完整）都返回 None，由 `Segment` 照常解码并报告错误
+    pub fn parse(buf: &[u8], negotiated: ChecksumAlgorithm) -> Option<(Self, usize)> {
+        let header = segment::parse_header(buf).ok()?;
+        let plain = SegmentFlags::SACK | SegmentFlags::ECE;
+        if header.segment_type != SegmentType::Ack || header.seq.get() != 0 || header.flags.bits() & !plain.bits() != 0 {
+            return None;
+        }
+        let frame = &buf[..header.total_len];
+        let mut rest = &frame[ACK_OFFSET..];
+        let ack = SeqNum::new(rest.get_u64());
+        let window = rest.get_u32();
+        if rest.get_u8() != negotiated.id() {
+            return None;
+        }
+        rest.advance(4);
+        let options_len = usize::from(rest.get_u8());
+        let timestamp = options::timestamp_only(rest.get(..options_len)?)?;
+        let data = &rest[options_len..];
+
+        // 标志与数据体一致：带有区间时设置了 SACK 标志，反之亦然
+        if header.flags.contains(SegmentFlags::SACK) == data.is_empty() || data.len() > MAX_BLOCKS * BLOCK_LEN {
+            return None;
+        }
+        let sack = SackBlocks::decode(data).ok()?;
+        if !Segment::checksum_matches(frame, negotiated) {
+            return None;
+        }
+        let ece = header.flags.contains(SegmentFlags::ECE);
+        Some((Self { stream_id: header.stream_id, conn_id: header.conn_id, ack, window, ece, timestamp, sack, checksum: negotiated }, header.total_len))
+    }
+}
+
+/// 取出段中与确认有关的字段：确认号、窗口、ECE、时间戳与 SACK 区间，其余的选项与标志被忽略；
+/// SACK 数据体不合法时按不带区间的确认处理
+impl From<&Segment> for AckFrame {
+    fn from(segment: &Segment) -> Self {
+        let sack = match segment.flags().contains(SegmentFlags::SACK) {
+            true => SackBlocks::decode(segment.data()).unwrap_or_default(),
+            false => SackBlocks::new(),
+        };
+        Self {
+            stream_id: segment.stream_id(),
+            conn_id: segment.conn_id(),
+            ack: segment.ack(),
+            window: segment.window(),
+            ece: segment.flags().contains(SegmentFlags::ECE),
+            timestamp: segment.options().timestamp(),
+            sack,
+            checksum: segment.checksum(),
+        }
+    }
+}
+
+/// 同样内容的 Ack 段，用于需要 `Segment` 的地方（添加其他选项、记录与调试）；带区间时数据体需要分配
+impl From<&AckFrame> for Segment {
+    fn from(frame: &AckFrame) -> Self {
+        let mut builder = Segment::builder(SegmentType::Ack)
+            .stream(frame.stream_id)
+            .conn_id(frame.conn_id)
+            .ack(frame.ack)
+            .window(frame.window)
+            .checksum(frame.checksum);
+        if frame.ece {
+            builder = builder.flags(SegmentFlags::ECE);
+        }
+        if !frame.sack.is_empty() {
+            builder = builder.sack(&frame.sack);
+        }
+        if let Some((value, echo)) = frame.timestamp {
+            builder = builder.options(Options::new().with(SegmentOption::Timestamp { value, echo }).expect("a single option fits"));
+        }
+        builder.build().expect("ack segment is always valid")
+    }
+}
+
+impl From<AckFrame> for Segment {
+    fn from(frame: AckFrame) -> Self {
+        Segment::from(&frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn seq(n: u64) -> SeqNum {
+        SeqNum::new(n)
+    }
+
+    fn frames() -> Vec<AckFrame> {
+        let ranges: Vec<_> = (0..MAX_BLOCKS as u64).map(|n| (seq(100 + n * 4), seq(101 + n * 4))).collect();
+        Vec::from([
+            AckFrame::new(seq(7), 4096),
+            AckFrame { stream_id: 3, conn_id: 0xdead_beef, ece: true, checksum: ChecksumAlgorithm::XxHash32, ..AckFrame::new(seq(u64::MAX), 0) },
+            AckFrame { timestamp: Some((100, 42)), sack: SackBlocks::from(&[(seq(9), seq(12))][..]), ..AckFrame::new(seq(7), 64) },
+            AckFrame { sack: SackBlocks::from(&ranges[..]), conn_id: 5, ..AckFrame::new(seq(98), u32::MAX) },
+        ])
+    }
+
+    #[test]
+    fn test_frames_encode_like_segments() {
+        for frame in frames() {
+            let segment = Segment::from(&frame);
+            let mut encoded = BytesMut::new();
+            frame.encode_into(&mut encoded);
+            assert_eq!(encoded, segment.encode().unwrap(), "{:?}", frame);
+            assert_eq!(frame.encoded_len(), segment.encoded_len());
+
+            // 两种解码互通：段解码出的与帧解析出的相同
+            assert_eq!(Segment::decode(&encoded).unwrap(), segment);
+            assert_eq!(AckFrame::parse(&encoded, frame.checksum), Some((frame, encoded.len())));
+            assert_eq!(AckFrame::from(&segment), frame);
+        }
+    }
+
+    #[test]
+    fn test_parse_consumes_one_segment_of_a_datagram() {
+        let mut datagram = BytesMut::new();
+        for frame in frames().iter().filter(|frame| frame.checksum == ChecksumAlgorithm::default()) {
+            frame.encode_into(&mut datagram);
+        }
+        let mut rest = &datagram[..];
+        let mut parsed = Vec::new();
+        while let Some((frame, len)) = AckFrame::parse(rest, ChecksumAlgorithm::default()) {
+            parsed.push(frame);
+            rest = &rest[len..];
+        }
+        assert!(rest.is_empty());
+        assert_eq!(parsed.len(), 3);
+    }
+
+    #[test]
+    fn test_other_segments_are_left_to_the_decoder() {
+        let negotiated = ChecksumAlgorithm::default();
+        let parse = |segment: Segment| AckFrame::parse(&segment.encode().unwrap(), negotiated);
+        let ack = || Segment::builder(SegmentType::Ack).ack(seq(3)).window(10);
+        assert!(parse(ack().build().unwrap()).is_some());
+
+        // 带有其他选项、其他标志、序列号不为 0 的 Ack 段与其他类型的段
+        assert_eq!(parse(ack().options(Options::new().with(SegmentOption::Resync(None)).unwrap()).build().unwrap()), None);
+        assert_eq!(parse(ack().options(Options::new().with(SegmentOption::Timestamp { value: 1, echo: 2 }).unwrap().with(SegmentOption::Cwr).unwrap()).build().unwrap()), None);
+        assert_eq!(parse(ack().flags(SegmentFlags::CE).build().unwrap()), None);
+        assert_eq!(parse(ack().data_seq(1).build().unwrap()), None);
+        assert_eq!(parse(ack().checksum(ChecksumAlgorithm::XxHash32).build().unwrap()), None);
+        assert_eq!(parse(Segment::ping(1)), None);
+        assert_eq!(parse(ack().sack(&[]).build().unwrap()), None);
+        let many: Vec<_> = (0..MAX_BLOCKS as u64 + 1).map(|n| (seq(n * 3), seq(n * 3))).collect();
+        let mut oversized = ack().build().unwrap();
+        oversized.set_flag(SegmentFlags::SACK, true);
+        oversized.set_data(sack_payload(&many));
+        assert_eq!(parse(oversized.clone()), None);
+        assert_eq!(AckFrame::from(&oversized).sack.len(), MAX_BLOCKS);
+
+        // 损坏与不完整的确认
+        let mut encoded = ack().build().unwrap().encode().unwrap();
+        assert_eq!(AckFrame::parse(&encoded[..encoded.len() - 1], negotiated), None);
+        encoded[ACK_OFFSET] ^= 1;
+        assert_eq!(AckFrame::parse(&encoded, negotiated), None);
+        assert!(Segment::decode(&encoded).is_err());
+    }
+
+    fn sack_payload(ranges: &[(SeqNum, SeqNum)]) -> bytes::Bytes {
+        let mut payload = BytesMut::new();
+        for &(start, end) in ranges {
+            payload.put_u64(start.get());
+            payload.put_u64(end.get());
+        }
+        payload.freeze()
+    }
+}
@@ -0,0 +1,171 @@
+//! 入站对端的访问控制
+//! 监听器在来自新来源的第一个数据报上检查 `Acl`，早于解码、登记任何状态或生成任何回应：不被允许的来源静默丢弃，
+//! 计入 `ListenerStats::denied`，不回应 Rst，不向它确认这个端口上有服务。规则按 `deny` 优先：落在任何 `deny` 网段中的拒绝；
+//! 否则 `allow` 为空时允许，非空时只允许落在其中的来源。
+//! 已有的连接（包括宽限期内的旧地址与正在验证的新地址）不再检查。`Listener::set_acl` 在运行时替换规则，
+//! `Acl::evict` 决定此时不再被允许的已建立连接是被中止还是照常运行。
+//!
+//! IPv4 映射的 IPv6 地址（双栈套接字上的 IPv4 对端）按 IPv4 地址匹配。本身不做 IO。
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// 一个 IPv4 或 IPv6 网段，主机位总是为零
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// `addr` 的前 `prefix` 位；前缀超过地址长度时返回 None，主机位被清零
+    pub fn new(addr: IpAddr, prefix: u8) -> Option<Cidr> {
+        let addr = match addr {
+            IpAddr::V4(v4) if prefix <= 32 => IpAddr::V4(Ipv4Addr::from_bits(v4.to_bits() & mask_v4(prefix))),
+            IpAddr::V6(v6) if prefix <= 128 => IpAddr::V6(Ipv6Addr::from_bits(v6.to_bits() & mask_v6(prefix))),
+            _ => return None,
+        };
+        Some(Cidr { addr, prefix })
+    }
+
+    /// 只含 `addr` 一个地址的网段
+    pub fn host(addr: IpAddr) -> Cidr {
+        let prefix = if addr.is_ipv4() { 32 } else { 128 };
+        Cidr { addr, prefix }
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// `ip` 是否落在网段中；不同地址族的不匹配
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => ip.to_bits() & mask_v4(self.prefix) == net.to_bits(),
+            (IpAddr::V6(net), IpAddr::V6(ip)) => ip.to_bits() & mask_v6(self.prefix) == net.to_bits(),
+            _ => false,
+        }
+    }
+}
+
+fn mask_v4(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0)
+}
+
+fn mask_v6(prefix: u8) -> u128 {
+    u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0)
+}
+
+// `10.0.0.0/8`、`2001:db8::/32`；没有前缀时是单个地址
+impl FromStr for Cidr {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.split_once('/') {
+            Some((addr, prefix)) => {
+                let prefix = prefix.parse().map_err(|_| ())?;
+                Cidr::new(addr.parse().map_err(|_| ())?, prefix).ok_or(())
+            }
+            None => s.parse().map(Cidr::host).map_err(|_| ()),
+        }
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// 入站对端的访问控制列表
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Acl {
+    pub allow: Vec<Cidr>,   // 非空时只有落在其中的来源能开始握手
+    pub deny: Vec<Cidr>,    // 落在其中的来源一律丢弃，优先于 allow
+    pub evict: bool,        // 经 `Listener::set_acl` 换上这份规则时中止不再被允许的已建立连接，false 时它们照常运行
+}
+
+impl Acl {
+    /// 允许 `allow` 中（为空时所有）不在 `deny` 中的来源
+    pub fn new(allow: Vec<Cidr>, deny: Vec<Cidr>) -> Self {
+        Self { allow, deny, evict: false }
+    }
+
+    /// 来自 `ip` 的新来源能否开始握手
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        !self.deny.iter().any(|net| net.contains(ip)) && (self.allow.is_empty() || self.allow.iter().any(|net| net.contains(ip)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let net: Cidr = "10.1.2.3/8".parse().unwrap();
+        assert_eq!((net.addr(), net.prefix()), (ip("10.0.0.0"), 8));
+        assert_eq!(net.to_string(), "10.0.0.0/8");
+        assert_eq!("2001:db8::1/32".parse::<Cidr>().unwrap().to_string(), "2001:db8::/32");
+        assert_eq!("192.0.2.7".parse::<Cidr>().unwrap(), Cidr::new(ip("192.0.2.7"), 32).unwrap());
+        assert_eq!("::1".parse::<Cidr>().unwrap().prefix(), 128);
+        for invalid in ["10.0.0.0/33", "::/129", "10.0.0.0/", "10.0.0/8", "/8", "10.0.0.0/-1", "host/8"] {
+            assert_eq!(invalid.parse::<Cidr>(), Err(()), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_edge_prefixes() {
+        // /0 匹配同一地址族的所有地址
+        let any_v4: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any_v4.contains(ip("255.255.255.255")) && any_v4.contains(ip("0.0.0.0")));
+        assert!(!any_v4.contains(ip("::1")));
+        let any_v6: Cidr = "::/0".parse().unwrap();
+        assert!(any_v6.contains(ip("ffff::1")) && !any_v6.contains(ip("10.0.0.1")));
+
+        // /32 与 /128 只匹配一个地址
+        let host: Cidr = "192.0.2.7/32".parse().unwrap();
+        assert!(host.contains(ip("192.0.2.7")));
+        assert!(!host.contains(ip("192.0.2.6")) && !host.contains(ip("192.0.2.8")));
+        let host: Cidr = "2001:db8::7/128".parse().unwrap();
+        assert!(host.contains(ip("2001:db8::7")) && !host.contains(ip("2001:db8::6")));
+
+        // 边界两侧
+        let net: Cidr = "10.0.0.0/31".parse().unwrap();
+        assert!(net.contains(ip("10.0.0.1")) && !net.contains(ip("10.0.0.2")));
+        let net: Cidr = "2001:db8::/127".parse().unwrap();
+        assert!(net.contains(ip("2001:db8::1")) && !net.contains(ip("2001:db8::2")));
+    }
+
+    #[test]
+    fn test_mapped_v4_matches_v4_ranges() {
+        let net: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert!(net.contains(ip("::ffff:10.9.8.7")));
+        assert!(!net.contains(ip("::ffff:11.0.0.1")));
+    }
+
+    #[test]
+    fn test_deny_overrides_allow() {
+        let open = Acl::default();
+        assert!(open.permits(ip("203.0.113.1")));
+
+        let acl = Acl::new(vec!["10.0.0.0/8".parse().unwrap(), "fd00::/8".parse().unwrap()], vec!["10.66.0.0/16".parse().unwrap()]);
+        assert!(acl.permits(ip("10.1.1.1")));
+        assert!(acl.permits(ip("fd12::1")));
+        assert!(!acl.permits(ip("10.66.3.4")));
+        assert!(!acl.permits(ip("192.168.1.1")));
+
+        let deny_only = Acl::new(Vec::new(), vec!["192.0.2.0/24".parse().unwrap()]);
+        assert!(!deny_only.permits(ip("192.0.2.200")));
+        assert!(deny_only.permits(ip("198.51.100.1")));
+    }
+}
@@ -0,0 +1,256 @@
+//! 发送合并
+//! 监听器的发送任务为每条连接攒一个待发数据报：发往同一对端、段头携带同一连接 ID 的数据报首尾相接（即多段打包格式，接收端照常逐段解码），
+//! 合并后不超过 `mss`，也不超过随数据报一起交来的、对端在握手中接受的大小（连接的有效 MSS），超过时先发出已攒的部分。攒下的数据报在以下时刻发出：最早的内容已等待 `LinkConfig::batch_window`、
+//! 再追加就要超过 `mss`，或者数据报中有握手类的段（Syn、Retry、Rst，见 `is_urgent`）。
+//! `batch_window` 为 0 时不额外等待，只合并发送任务一次取出的、已在队列中积压的数据报（套接字忙时）。
+//! 同一地址上的多条连接（对端经一个套接字发起多条连接，见 `client_endpoint` 模块）各攒各的，
+//! 对端按数据报第一个段的连接 ID 分发，不同连接的段不能出现在同一个数据报里。
+//! 发送任务在放入数据报与检查到期时传入当前时刻，并按 `next_deadline` 决定睡到什么时候。
+
+use crate::segment::{self, Segment, SegmentType};
+use bytes::BytesMut;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct Pending {
+    datagram: BytesMut,
+    since: Instant,     // 最早的内容进入的时间
+}
+
+/// 按连接（对端地址与连接 ID）合并待发数据报
+#[derive(Debug)]
+pub struct Batcher {
+    mss: usize,
+    window: Duration,
+    pending: HashMap<(SocketAddr, u32), Pending>,
+    ready: Vec<(BytesMut, SocketAddr)>,
+}
+
+impl Batcher {
+    pub fn new(mss: usize, window: Duration) -> Self {
+        Self { mss, window, pending: HashMap::new(), ready: Vec::new() }
+    }
+
+    /// 加入一个发往 `to` 的数据报，`limit` 是对端接受的最大数据报；内容被合并进已攒的数据报时返回它的缓冲，可以归还缓冲池
+    pub fn push(&mut self, datagram: BytesMut, to: SocketAddr, limit: usize, now: Instant) -> Option<BytesMut> {
+        let urgent = is_urgent(&datagram);
+        let mss = self.mss.min(limit);
+        let key = (to, conn_id(&datagram));
+        let spare = match self.pending.remove(&key) {
+            Some(mut pending) if pending.datagram.len() + datagram.len() <= mss => {
+                pending.datagram.extend_from_slice(&datagram);
+                self.pending.insert(key, pending);
+                Some(datagram)
+            }
+            previous => {
+                // 放不下：先发出已攒的部分。本身就不小于 `mss` 的数据报（如路径 MTU 探测）原样发出
+                if let Some(previous) = previous {
+                    self.ready.push((previous.datagram, to));
+                }
+                if datagram.len() >= mss {
+                    self.ready.push((datagram, to));
+                    return None;
+                }
+                self.pending.insert(key, Pending { datagram, since: now });
+                None
+            }
+        };
+        if urgent && let Some(pending) = self.pending.remove(&key) {
+            self.ready.push((pending.datagram, to));
+        }
+        spare
+    }
+
+    /// 发出等待满 `batch_window` 的数据报
+    pub fn flush_due(&mut self, now: Instant) {
+        let window = self.window;
+        let ready = &mut self.ready;
+        self.pending.retain(|&(to, _), pending| {
+            let due = now >= pending.since + window;
+            if due {
+                ready.push((std::mem::take(&mut pending.datagram), to));
+            }
+            !due
+        });
+    }
+
+    /// 发出全部攒下的数据报
+    pub fn flush(&mut self) {
+        self.ready.extend(self.pending.drain().map(|((to, _), pending)| (pending.datagram, to)));
+    }
+
+    /// 取出可以发送的数据报，同一对端的按加入的顺序
+    pub fn take_ready(&mut self) -> Vec<(BytesMut, SocketAddr)> {
+        std::mem::take(&mut self.ready)
+    }
+
+    /// 同 `take_ready`，保留队列的容量：发送任务每轮都取，不必每轮分配
+    pub fn drain_ready(&mut self) -> std::vec::Drain<'_, (BytesMut, SocketAddr)> {
+        self.ready.drain(..)
+    }
+
+    /// 丢弃为 `to` 上的连接 `conn_id` 攒下、还没发出的数据报，返回它的缓冲
+    pub fn discard(&mut self, to: SocketAddr, conn_id: u32) -> Option<BytesMut> {
+        self.pending.remove(&(to, conn_id)).map(|pending| pending.datagram)
+    }
+
+    /// 最早需要调用 `flush_due` 的时间；没有攒下的数据报时为 None
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.pending.values().map(|pending| pending.since + self.window).min()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty() && self.ready.is_empty()
+    }
+}
+
+/// 数据报中是否有需要立即发送的段：握手（Syn、Retry）与 Rst
+pub fn is_urgent(datagram: &[u8]) -> bool {
+    segment_types(datagram).any(|t| matches!(t, SegmentType::Syn | SegmentType::Retry | SegmentType::Rst))
+}
+
+/// 数据报第一个段携带的连接 ID，对端按它分发；无法解析时为 0
+pub fn conn_id(datagram: &[u8]) -> u32 {
+    segment::parse_header(datagram).map_or(0, |header| header.conn_id)
+}
+
+/// 数据报中的段数
+pub fn segment_count(datagram: &[u8]) -> usize {
+    segment_types(datagram).count()
+}
+
+// 沿长度前缀逐段读出段类型；遇到不完整或不合法的部分时停止
+fn segment_types(datagram: &[u8]) -> impl Iterator<Item = SegmentType> + '_ {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        let header = datagram.get(offset..offset + Segment::FIXED_HEADER_LEN)?;
+        let total_len = u32::from_be_bytes(header[..4].try_into().expect("four bytes")) as usize;
+        if total_len < Segment::FIXED_HEADER_LEN {
+            return None;
+        }
+        offset += total_len;
+        SegmentType::from_id(header[4])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::segment::Segment;
+
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn ack(n: u64) -> BytesMut {
+        Segment::builder(SegmentType::Ack).ack(n).window(64).build().unwrap().encode().unwrap()
+    }
+
+    #[test]
+    fn test_acks_in_a_tight_loop_are_coalesced() {
+        let now = Instant::now();
+        let mut batcher = Batcher::new(1200, Duration::ZERO);
+        let mut spare = 0;
+        for n in 0..100 {
+            spare += usize::from(batcher.push(ack(n), peer(1), 1200, now).is_some());
+        }
+        batcher.flush_due(now);
+        let datagrams = batcher.take_ready();
+
+        // 100 个确认合成少数几个数据报，没有一个超过 mss，段一个不少、顺序不变
+        assert!(datagrams.len() <= 100 * ack(0).len() / 1200 + 1, "{} datagrams", datagrams.len());
+        assert!(datagrams.iter().all(|(datagram, to)| datagram.len() <= 1200 && *to == peer(1)));
+        assert_eq!(spare, 100 - datagrams.len());
+        let mut acks = Vec::new();
+        for (mut datagram, _) in datagrams {
+            while let Some(segment) = Segment::decode_from(&mut datagram).unwrap() {
+                acks.push(segment.ack().get());
+            }
+        }
+        assert_eq!(acks, (0..100).collect::<Vec<_>>());
+        assert!(batcher.is_empty());
+    }
+
+    #[test]
+    fn test_peers_are_batched_separately() {
+        let now = Instant::now();
+        let mut batcher = Batcher::new(1200, Duration::from_millis(5));
+        batcher.push(ack(1), peer(1), 1200, now);
+        batcher.push(ack(2), peer(2), 1200, now + Duration::from_millis(2));
+        batcher.push(ack(3), peer(1), 1200, now + Duration::from_millis(3));
+        assert_eq!(batcher.next_deadline(), Some(now + Duration::from_millis(5)));
+
+        // 窗口未满时不发出；只有等待满窗口的对端先发出
+        batcher.flush_due(now + Duration::from_millis(4));
+        assert!(batcher.take_ready().is_empty());
+        batcher.flush_due(now + Duration::from_millis(5));
+        let ready = batcher.take_ready();
+        assert_eq!(ready.len(), 1);
+        assert_eq!((ready[0].1, segment_count(&ready[0].0)), (peer(1), 2));
+
+        batcher.flush();
+        assert_eq!(batcher.take_ready()[0].1, peer(2));
+        assert_eq!(batcher.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_connections_to_one_peer_are_batched_separately() {
+        let now = Instant::now();
+        let mut batcher = Batcher::new(1200, Duration::from_secs(1));
+        let stamped = |n, conn_id| Segment::builder(SegmentType::Ack).conn_id(conn_id).ack(n).window(64).build().unwrap().encode().unwrap();
+        for n in 0..6 {
+            batcher.push(stamped(n, 7 + n as u32 % 2), peer(1), 1200, now);
+        }
+        // 发往同一地址的两条连接各攒一个数据报，每个数据报里只有一条连接的段
+        assert_eq!(batcher.discard(peer(1), 9), None);
+        batcher.flush();
+        let mut ready = batcher.take_ready();
+        ready.sort_by_key(|(datagram, _)| conn_id(datagram));
+        assert_eq!(ready.len(), 2);
+        for ((mut datagram, to), expected) in ready.into_iter().zip([7, 8]) {
+            assert_eq!(to, peer(1));
+            let mut acks = Vec::new();
+            while let Some(segment) = Segment::decode_from(&mut datagram).unwrap() {
+                assert_eq!(segment.conn_id(), expected);
+                acks.push(segment.ack().get());
+            }
+            assert_eq!(acks, (0..6).filter(|n| 7 + *n as u32 % 2 == expected).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_urgent_and_large_datagrams_are_not_held() {
+        let now = Instant::now();
+        let mut batcher = Batcher::new(1200, Duration::from_secs(1));
+        batcher.push(ack(1), peer(1), 1200, now);
+        // Rst 与之前攒下的确认一起立即发出
+        let rst = Segment::builder(SegmentType::Rst).build().unwrap().encode().unwrap();
+        assert!(is_urgent(&rst) && !is_urgent(&ack(1)));
+        batcher.push(rst, peer(1), 1200, now);
+        let ready = batcher.take_ready();
+        assert_eq!((ready.len(), segment_count(&ready[0].0)), (1, 2));
+
+        // 不小于 mss 的数据报原样发出，不与攒下的内容合并
+        batcher.push(ack(2), peer(1), 1200, now);
+        batcher.push(Segment::probe(7, 1200).encode().unwrap(), peer(1), 1200, now);
+        let ready = batcher.take_ready();
+        assert_eq!(ready.iter().map(|(datagram, _)| segment_count(datagram)).collect::<Vec<_>>(), vec![1, 1]);
+        assert!(batcher.is_empty());
+    }
+
+    #[test]
+    fn test_merges_stay_within_the_peer_limit() {
+        let now = Instant::now();
+        let mut batcher = Batcher::new(1200, Duration::from_secs(1));
+        // 对端只接受两个确认大小的数据报
+        let limit = 2 * ack(0).len();
+        for n in 0..5 {
+            batcher.push(ack(n), peer(1), limit, now);
+        }
+        batcher.flush();
+        let ready = batcher.take_ready();
+        assert_eq!(ready.iter().map(|(datagram, _)| segment_count(datagram)).collect::<Vec<_>>(), vec![2, 2, 1]);
+    }
+}
@@ -0,0 +1,401 @@
+use bytes::Bytes;
+use link_rs::cli;
+use link_rs::client::{self, BenchOptions, BenchReport, ClientOptions, Reply};
+use link_rs::config::{self, LinkConfig};
+use link_rs::diagnostics::{self, Diagnosis, Report, SelftestOptions};
+use link_rs::ping::{PingEvent, PingOptions, PingSummary};
+use link_rs::segment::Segment;
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str = "\
+用法: link-client <addr:port> [选项]
+      link-client bench <addr:port> [选项]
+      link-client ping <addr:port> [选项]
+      link-client selftest <addr:port> [选项]
+
+向服务器（protocol 模式）发送消息并等待回显，逐条输出序号与往返时间；有消息没有收到回应时以非零状态退出。
+bench 在指定时长内让发送窗口保持满载，之后输出有效吞吐量、发送段数、重传率与 RTT。
+ping 以 Ping 段探测往返时间，逐个输出结果，最后输出 RTT 的最小/平均/最大/标准差与丢失率；丢失率超过阈值时以非零状态退出。
+selftest 检查到回显服务器的网络路径：握手、突发探测、填充到满长的段与一段空闲，输出诊断，并以它的退出码退出：
+0 正常，3 没有 UDP 连通性，4 路径 MTU 不足，5 高丢包，6 NAT 很快更换映射。
+
+选项:
+    --message <text>        发送的消息 [默认: hello]
+    --count <n>             发送的条数 [默认: 1]
+    --interval <dur>        收到回应后等待多久再发送下一条，如 10ms、1s [默认: 1s]
+    --timeout <dur>         等待每条回应的最长时间 [默认: 5s]
+
+ping 选项:
+    --count <n>             探测的个数 [默认: 10]
+    --interval <dur>        相邻探测的间隔，不等待回应 [默认: 1s]
+    --timeout <dur>         每个探测等待 Pong 的最长时间 [默认: 1s]
+    --max-loss <percent>    允许的最大丢失率 [默认: 0]
+
+selftest 选项:
+    --padded <bytes>        填充段的字节数 [默认: 1200]
+    --burst <n>             突发的探测个数 [默认: 50]
+    --timeout <dur>         等待回显与探测回应的最长时间 [默认: 3s]
+    --max-loss <percent>    突发探测允许的最大丢失率 [默认: 10]
+    --idle <dur>            空闲的时长，0 跳过这一步 [默认: 30s]
+
+bench 选项:
+    --duration <dur>        测试时长 [默认: 10s]
+    --payload <bytes>       每条消息的字节数 [默认: 1200]
+    --streams <n>           并发的连接数，结果合计 [默认: 1]
+
+共用选项:
+    --dual-stack            目标是 IPv4 映射地址时使用双栈套接字
+    --max-payload <bytes>   单个数据报的最大数据体 [默认: 1168]
+    --recv-buffer <bytes>   接收缓冲区大小 [默认: 65536]
+    --chaos <spec>          注入故障，如 loss=0.05,delay=20ms±10ms
+    --config <file.toml>    从 TOML 文件读取参数；LINK_* 环境变量覆盖文件，命令行选项覆盖两者
+    -h, --help              显示本帮助";
+
+/// 运行哪种测试
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Echo(ClientOptions),
+    Bench(BenchOptions),
+    Ping { options: PingOptions, max_loss: f64 },  // max_loss 为百分比
+    Selftest(SelftestOptions),
+}
+
+/// 解析后的命令行参数
+#[derive(Debug)]
+struct Args {
+    target: SocketAddr,
+    mode: Mode,
+    config: LinkConfig,
+}
+
+// 以 `config` 为基础解析命令行参数（不含程序名）；`--help` 返回 None
+fn parse_args(args: impl IntoIterator<Item = String>, config: LinkConfig) -> Result<Option<Args>, String> {
+    let mut target = None;
+    let mut args = args.into_iter().peekable();
+    let mut mode = match args.next_if(|arg| arg == "bench" || arg == "ping" || arg == "selftest").as_deref() {
+        Some("bench") => Mode::Bench(BenchOptions::default()),
+        Some("selftest") => Mode::Selftest(SelftestOptions::default()),
+        Some(_) => Mode::Ping { options: PingOptions::default(), max_loss: 0.0 },
+        None => Mode::Echo(ClientOptions::default()),
+    };
+    let mut config = config;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} requires a value", arg));
+        match (arg.as_str(), &mut mode) {
+            ("-h" | "--help", _) => return Ok(None),
+            ("--message", Mode::Echo(options)) => options.message = Bytes::from(value()?),
+            ("--count", Mode::Echo(options)) => {
+                let value = value()?;
+                options.count = value.parse().map_err(|_| format!("invalid count '{}', expected a non-negative integer", value))?;
+            }
+            ("--interval", Mode::Echo(options)) => {
+                let value = value()?;
+                options.interval = config::parse_duration(&value).ok_or_else(|| format!("invalid interval '{}', expected e.g. 10ms or 1s", value))?;
+            }
+            ("--timeout", Mode::Echo(options)) => {
+                let value = value()?;
+                options.timeout = config::parse_duration(&value).ok_or_else(|| format!("invalid timeout '{}', expected e.g. 500ms or 5s", value))?;
+            }
+            ("--count", Mode::Ping { options, .. }) => {
+                let value = value()?;
+                options.count = value.parse().map_err(|_| format!("invalid count '{}', expected a non-negative integer", value))?;
+            }
+            ("--interval", Mode::Ping { options, .. }) => {
+                let value = value()?;
+                options.interval = config::parse_duration(&value).ok_or_else(|| format!("invalid interval '{}', expected e.g. 10ms or 1s", value))?;
+            }
+            ("--timeout", Mode::Ping { options, .. }) => {
+                let value = value()?;
+                options.timeout = config::parse_duration(&value).ok_or_else(|| format!("invalid timeout '{}', expected e.g. 500ms or 5s", value))?;
+            }
+            ("--max-loss", Mode::Ping { max_loss, .. }) => {
+                let value = value()?;
+                *max_loss = value
+                    .trim_end_matches('%')
+                    .parse()
+                    .ok()
+                    .filter(|percent| (0.0..=100.0).contains(percent))
+                    .ok_or_else(|| format!("invalid max loss '{}', expected a percentage in 0..=100", value))?;
+            }
+            ("--padded", Mode::Selftest(options)) => {
+                let value = value()?;
+                options.padded = value
+                    .parse()
+                    .ok()
+                    .filter(|padded| (Segment::FIXED_HEADER_LEN + 1..=config::MAX_DATAGRAM).contains(padded))
+                    .ok_or_else(|| format!("invalid padded size '{}', expected {}..={} bytes", value, Segment::FIXED_HEADER_LEN + 1, config::MAX_DATAGRAM))?;
+            }
+            ("--burst", Mode::Selftest(options)) => {
+                let value = value()?;
+                options.burst = value
+                    .parse()
+                    .ok()
+                    .filter(|burst| *burst > 0)
+                    .ok_or_else(|| format!("invalid burst '{}', expected a positive integer", value))?;
+            }
+            ("--timeout", Mode::Selftest(options)) => {
+                let value = value()?;
+                options.timeout = config::parse_duration(&value).ok_or_else(|| format!("invalid timeout '{}', expected e.g. 500ms or 5s", value))?;
+            }
+            ("--max-loss", Mode::Selftest(options)) => {
+                let value = value()?;
+                options.max_loss = value
+                    .trim_end_matches('%')
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|percent| (0.0..=100.0).contains(percent))
+                    .ok_or_else(|| format!("invalid max loss '{}', expected a percentage in 0..=100", value))?
+                    / 100.0;
+            }
+            ("--idle", Mode::Selftest(options)) => {
+                let value = value()?;
+                options.idle = config::parse_duration(&value).ok_or_else(|| format!("invalid idle '{}', expected e.g. 0s or 30s", value))?;
+            }
+            ("--duration", Mode::Bench(options)) => {
+                let value = value()?;
+                options.duration = config::parse_duration(&value)
+                    .filter(|duration| !duration.is_zero())
+                    .ok_or_else(|| format!("invalid duration '{}', expected e.g. 200ms or 10s", value))?;
+            }
+            ("--payload", Mode::Bench(options)) => {
+                let value = value()?;
+                let max = config::MAX_DATAGRAM - Segment::FIXED_HEADER_LEN;
+                options.payload = value
+                    .parse()
+                    .ok()
+                    .filter(|payload| (1..=max).contains(payload))
+                    .ok_or_else(|| format!("invalid payload '{}', expected 1..={} bytes", value, max))?;
+            }
+            ("--streams", Mode::Bench(options)) => {
+                let value = value()?;
+                options.streams = value
+                    .parse()
+                    .ok()
+                    .filter(|streams| *streams > 0)
+                    .ok_or_else(|| format!("invalid streams '{}', expected a positive integer", value))?;
+            }
+            (other, _) if other.starts_with('-') => {
+                if !cli::config_flag(&mut config, other, &mut value)? {
+                    return Err(format!("unknown argument '{}'", other));
+                }
+            }
+            (other, _) if target.is_none() => {
+                target = Some(other.parse().map_err(|_| format!("invalid target address '{}', expected addr:port", other))?);
+            }
+            (other, _) => return Err(format!("unexpected argument '{}'", other)),
+        }
+    }
+    config.validate().map_err(|e| e.to_string())?;
+    let target = target.ok_or("missing target address")?;
+    Ok(Some(Args { target, mode, config }))
+}
+
+fn print_reply(target: SocketAddr, reply: &Reply) {
+    println!("来自 {} 的回应: seq={} bytes={} rtt={:.3}ms", target, reply.seq, reply.message.len(), reply.rtt.as_secs_f64() * 1000.0);
+}
+
+fn millis(rtt: Option<Duration>) -> f64 {
+    rtt.unwrap_or_default().as_secs_f64() * 1000.0
+}
+
+fn print_bench(target: SocketAddr, report: &BenchReport) {
+    println!(
+        "{} 个连接到 {}，{:.2}s：有效吞吐量 {:.2} Mbit/s（确认 {} 字节）",
+        report.connections.len(),
+        target,
+        report.elapsed.as_secs_f64(),
+        report.goodput() * 8.0 / 1e6,
+        report.bytes_acked()
+    );
+    println!(
+        "发送 {} 段，重传 {} 段（{:.2}%）",
+        report.segments_sent(),
+        report.segments_retransmitted(),
+        report.retransmission_rate() * 100.0
+    );
+    match report.srtt() {
+        Some((min, mean, max)) => println!(
+            "srtt 最小/平均/最大 = {:.3}/{:.3}/{:.3}ms",
+            millis(Some(min)),
+            millis(Some(mean)),
+            millis(Some(max))
+        ),
+        None => println!("没有 RTT 样本"),
+    }
+}
+
+fn print_probe(target: SocketAddr, event: &PingEvent) {
+    match event {
+        PingEvent::Reply { seq, rtt } => println!("来自 {} 的 Pong: seq={} rtt={:.3}ms", target, seq, millis(Some(*rtt))),
+        PingEvent::Timeout { seq } => println!("seq={} 超时", seq),
+        PingEvent::Late { seq, rtt } => println!("seq={} 的 Pong 在超时后到达: rtt={:.3}ms", seq, millis(Some(*rtt))),
+    }
+}
+
+fn print_ping(summary: &PingSummary) {
+    println!(
+        "已发送 {} 个探测，收到 {} 个回应（另有 {} 个超时后到达），丢失 {:.1}%",
+        summary.sent,
+        summary.received,
+        summary.late,
+        summary.loss() * 100.0
+    );
+    if summary.received > 0 {
+        println!(
+            "rtt 最小/平均/最大/标准差 = {:.3}/{:.3}/{:.3}/{:.3}ms",
+            millis(summary.min_rtt),
+            millis(summary.mean_rtt),
+            millis(summary.max_rtt),
+            millis(summary.stddev_rtt)
+        );
+    }
+}
+
+fn print_selftest(target: SocketAddr, options: &SelftestOptions, report: &Report) {
+    match report.handshake {
+        Some(elapsed) => println!("握手: 完成，用时 {:.3}ms", millis(Some(elapsed))),
+        None => println!("握手: {} 没有回应", target),
+    }
+    if let Some(loss) = report.loss {
+        println!("突发 {} 个探测: 丢失 {:.1}%，平均 rtt {:.3}ms", options.burst, loss * 100.0, millis(report.burst_rtt));
+    }
+    match (report.padded_rtt, report.diagnosis) {
+        (Some(rtt), _) => println!("{} 字节的段: 往返 {:.3}ms", options.padded, millis(Some(rtt))),
+        (None, Diagnosis::PathMtu { .. }) => println!("{} 字节的段: {:?} 内没有回显", options.padded, options.timeout),
+        (None, _) => {}
+    }
+    match report.diagnosis {
+        Diagnosis::Healthy => println!("诊断: {}", report.diagnosis),
+        _ => eprintln!("诊断: {}", report.diagnosis),
+    }
+}
+
+async fn run_echo(target: SocketAddr, options: &ClientOptions, config: LinkConfig) -> ExitCode {
+    match client::run(target, options, config, |reply| print_reply(target, reply)).await {
+        Ok(summary) => {
+            println!(
+                "已发送 {} 条，收到 {} 条回应，丢失 {} 条；rtt 最小/平均/最大 = {:.3}/{:.3}/{:.3}ms",
+                summary.sent,
+                summary.replied,
+                summary.lost(),
+                millis(summary.min_rtt),
+                millis(summary.mean_rtt()),
+                millis(summary.max_rtt)
+            );
+            if summary.lost() == 0 && summary.sent == options.count { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+        }
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let raw: Vec<String> = std::env::args().skip(1).collect();
+    let args = match cli::load_config(&raw).and_then(|config| parse_args(raw, config)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("错误: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+
+    match args.mode {
+        Mode::Echo(options) => run_echo(args.target, &options, args.config).await,
+        Mode::Ping { options, max_loss } => match client::ping(args.target, &options, args.config, |event| print_probe(args.target, event)).await {
+            Ok(summary) => {
+                print_ping(&summary);
+                if summary.loss() * 100.0 <= max_loss { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+            }
+            Err(e) => {
+                eprintln!("错误: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+        Mode::Selftest(options) => match diagnostics::selftest(args.target, &options, args.config).await {
+            Ok(report) => {
+                print_selftest(args.target, &options, &report);
+                ExitCode::from(report.diagnosis.exit_code())
+            }
+            Err(e) => {
+                eprintln!("错误: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+        Mode::Bench(options) => match client::bench(args.target, &options, args.config).await {
+            Ok(report) => {
+                print_bench(args.target, &report);
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("错误: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()), LinkConfig::default())
+    }
+
+    #[test]
+    fn test_options() {
+        let args = parse(&["127.0.0.1:8080", "--message", "hi", "--count", "100", "--interval", "10ms", "--max-payload", "500"]).unwrap().unwrap();
+        assert_eq!(args.target, "127.0.0.1:8080".parse().unwrap());
+        let Mode::Echo(options) = args.mode else { panic!("expected echo mode") };
+        assert_eq!((options.message, options.count, options.interval), (Bytes::from("hi"), 100, Duration::from_millis(10)));
+        assert_eq!(options.timeout, ClientOptions::default().timeout);
+        assert_eq!(args.config.mss, 500 + Segment::FIXED_HEADER_LEN);
+        assert!(parse(&["--help"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_subcommand_options() {
+        let args = parse(&["bench", "127.0.0.1:8080", "--duration", "200ms", "--streams", "4"]).unwrap().unwrap();
+        assert_eq!(args.mode, Mode::Bench(BenchOptions { duration: Duration::from_millis(200), streams: 4, ..BenchOptions::default() }));
+        assert!(parse(&["bench", "127.0.0.1:1", "--streams", "0"]).unwrap_err().contains("invalid streams"));
+        assert!(parse(&["bench", "127.0.0.1:1", "--payload", "0"]).unwrap_err().contains("invalid payload"));
+        assert!(parse(&["bench", "127.0.0.1:1", "--duration", "0s"]).unwrap_err().contains("invalid duration"));
+        let args = parse(&["ping", "127.0.0.1:8080", "--count", "3", "--timeout", "200ms", "--max-loss", "50%"]).unwrap().unwrap();
+        let options = PingOptions { count: 3, timeout: Duration::from_millis(200), ..PingOptions::default() };
+        assert_eq!(args.mode, Mode::Ping { options, max_loss: 50.0 });
+        assert!(parse(&["ping", "127.0.0.1:1", "--max-loss", "101"]).unwrap_err().contains("invalid max loss"));
+        let args = parse(&["selftest", "127.0.0.1:8080", "--padded", "1400", "--max-loss", "5%", "--idle", "0s"]).unwrap().unwrap();
+        let options = SelftestOptions { padded: 1400, max_loss: 0.05, idle: Duration::ZERO, ..SelftestOptions::default() };
+        assert_eq!(args.mode, Mode::Selftest(options));
+        assert!(parse(&["selftest", "127.0.0.1:1", "--padded", "10"]).unwrap_err().contains("invalid padded size"));
+        assert!(parse(&["selftest", "127.0.0.1:1", "--burst", "0"]).unwrap_err().contains("invalid burst"));
+
+        // 选项只属于各自的模式
+        assert!(parse(&["bench", "127.0.0.1:1", "--count", "3"]).unwrap_err().contains("unknown argument"));
+        assert!(parse(&["127.0.0.1:1", "--streams", "3"]).unwrap_err().contains("unknown argument"));
+        assert!(parse(&["ping", "127.0.0.1:1", "--message", "hi"]).unwrap_err().contains("unknown argument"));
+        assert!(parse(&["selftest", "127.0.0.1:1", "--count", "3"]).unwrap_err().contains("unknown argument"));
+    }
+
+    #[test]
+    fn test_malformed_arguments() {
+        assert!(parse(&[]).unwrap_err().contains("missing target"));
+        assert!(parse(&["localhost"]).unwrap_err().contains("invalid target address"));
+        assert!(parse(&["127.0.0.1:1", "127.0.0.1:2"]).unwrap_err().contains("unexpected argument"));
+        assert!(parse(&["127.0.0.1:1", "--interval", "10"]).unwrap_err().contains("invalid interval"));
+        assert!(parse(&["127.0.0.1:1", "--count", "-1"]).unwrap_err().contains("invalid count"));
+        assert!(parse(&["127.0.0.1:1", "--verbose"]).unwrap_err().contains("unknown argument"));
+    }
+}
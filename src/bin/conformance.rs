@@ -0,0 +1,174 @@
+use link_rs::cli;
+use link_rs::config::LinkConfig;
+use link_rs::conformance::{self, Peer, SuiteConfig};
+use link_rs::segment::Segment;
+use link_rs::socket;
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str = "\
+用法: link-conformance <addr:port> [选项]
+      link-conformance --listen <addr:port> [选项]
+
+对另一个实现运行互通检查：握手、版本协商、MSS 限制、按序交付、注入丢包下的恢复与正常关闭，逐项报告，
+未通过的检查附上相关段的 hexdump；任何一项未通过时以非零状态退出。
+对端必须把收到的每条消息原样发回，并在本端关闭时完成 FIN 交换。
+
+选项:
+    --listen <addr:port>        在这个地址等待对端连接，而不是连接到对端
+    --messages <n>              按序交付检查发送的消息数 [默认: 1000]
+    --lossy-messages <n>        丢包恢复检查发送的消息数 [默认: 200]
+    --loss <p>                  丢包恢复检查在两个方向上注入的丢包率 [默认: 0.1]
+    --seed <n>                  丢包判定的随机数种子 [默认: 1]
+    --advertised-mss <bytes>    握手中通告给对端的 MSS，同时是接收缓冲区大小 [默认: 600]
+    --timeout <secs>            每项检查的最长时间 [默认: 30]
+    --dual-stack                目标是 IPv4 映射地址时使用双栈套接字
+    --max-payload <bytes>       单个数据报的最大数据体 [默认: 1168]
+    --config <file.toml>        从 TOML 文件读取参数；LINK_* 环境变量覆盖文件，命令行选项覆盖两者
+    -h, --help                  显示本帮助";
+
+/// 解析后的命令行参数
+#[derive(Debug)]
+struct Args {
+    peer: Peer,
+    listen: Option<SocketAddr>,
+    suite: SuiteConfig,
+}
+
+fn parse_count(arg: &str, value: String) -> Result<usize, String> {
+    value.parse::<usize>().ok().filter(|count| *count >= 1).ok_or_else(|| format!("invalid {} '{}', expected a positive integer", arg, value))
+}
+
+// 以 `config` 为基础解析命令行参数（不含程序名）；`--help` 返回 None
+fn parse_args(args: impl IntoIterator<Item = String>, config: LinkConfig) -> Result<Option<Args>, String> {
+    let mut target = None;
+    let mut listen = None;
+    let mut suite = SuiteConfig { link: config, ..SuiteConfig::default() };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} requires a value", arg));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--listen" => {
+                let value = value()?;
+                listen = Some(value.parse().map_err(|_| format!("invalid listen address '{}', expected addr:port", value))?);
+            }
+            "--messages" => suite.messages = parse_count(&arg, value()?)?,
+            "--lossy-messages" => suite.lossy_messages = parse_count(&arg, value()?)?,
+            "--loss" => {
+                let value = value()?;
+                suite.loss = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|loss| *loss > 0.0 && *loss < 1.0)
+                    .ok_or_else(|| format!("invalid loss rate '{}', expected a probability between 0 and 1", value))?;
+            }
+            "--seed" => {
+                let value = value()?;
+                suite.seed = value.parse().map_err(|_| format!("invalid seed '{}', expected an unsigned integer", value))?;
+            }
+            "--advertised-mss" => {
+                let value = value()?;
+                suite.advertised_mss = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|mss| *mss > Segment::FIXED_HEADER_LEN)
+                    .ok_or_else(|| format!("invalid advertised mss '{}', expected more than {} bytes", value, Segment::FIXED_HEADER_LEN))?;
+            }
+            "--timeout" => {
+                let value = value()?;
+                let secs = value.parse::<u64>().ok().filter(|secs| *secs >= 1).ok_or_else(|| format!("invalid timeout '{}', expected whole seconds", value))?;
+                suite.step_timeout = Duration::from_secs(secs);
+            }
+            other if other.starts_with('-') => {
+                if !cli::config_flag(&mut suite.link, other, &mut value)? {
+                    return Err(format!("unknown argument '{}'", other));
+                }
+            }
+            other if target.is_none() => {
+                target = Some(other.parse().map_err(|_| format!("invalid target address '{}', expected addr:port", other))?);
+            }
+            other => return Err(format!("unexpected argument '{}'", other)),
+        }
+    }
+    suite.link.validate().map_err(|e| e.to_string())?;
+    let peer = match (target, listen) {
+        (Some(target), None) => Peer::Server(target),
+        (None, Some(_)) => Peer::Client,
+        (Some(_), Some(_)) => return Err("give either a target address or --listen, not both".into()),
+        (None, None) => return Err("missing target address or --listen".into()),
+    };
+    Ok(Some(Args { peer, listen, suite }))
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let raw: Vec<String> = std::env::args().skip(1).collect();
+    let args = match cli::load_config(&raw).and_then(|config| parse_args(raw, config)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("错误: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+
+    let local = match (args.listen, args.peer) {
+        (Some(listen), _) => listen,
+        (None, Peer::Server(target)) if target.is_ipv6() => SocketAddr::from(([0u16; 8], 0)),
+        (None, _) => SocketAddr::from(([0u8; 4], 0)),
+    };
+    let transport = match socket::bind(local, &args.suite.link).await {
+        Ok(transport) => transport,
+        Err(e) => {
+            eprintln!("错误: 无法绑定 {}: {}", local, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match args.peer {
+        Peer::Server(target) => println!("连接到 {} 运行互通检查", target),
+        Peer::Client => println!("在 {} 等待对端连接", local),
+    }
+    let report = conformance::run(transport, args.peer, args.suite).await;
+    println!("{}", report);
+    match report.passed() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()), LinkConfig::default())
+    }
+
+    #[test]
+    fn test_arguments() {
+        let args = parse(&["127.0.0.1:9000", "--messages", "50", "--loss", "0.2", "--timeout", "5"]).unwrap().unwrap();
+        assert_eq!(args.peer, Peer::Server("127.0.0.1:9000".parse().unwrap()));
+        assert_eq!((args.suite.messages, args.suite.loss, args.suite.step_timeout), (50, 0.2, Duration::from_secs(5)));
+        assert_eq!(args.suite.advertised_mss, SuiteConfig::default().advertised_mss);
+
+        let args = parse(&["--listen", "0.0.0.0:9000", "--advertised-mss", "900", "--max-payload", "500"]).unwrap().unwrap();
+        assert_eq!((args.peer, args.listen), (Peer::Client, Some("0.0.0.0:9000".parse().unwrap())));
+        assert_eq!((args.suite.advertised_mss, args.suite.link.mss), (900, 500 + Segment::FIXED_HEADER_LEN));
+        assert!(parse(&["--help"]).unwrap().is_none());
+
+        assert!(parse(&[]).unwrap_err().contains("missing target address"));
+        assert!(parse(&["127.0.0.1:1", "--listen", "0.0.0.0:1"]).unwrap_err().contains("not both"));
+        assert!(parse(&["127.0.0.1:1", "--loss", "1.5"]).unwrap_err().contains("invalid loss rate"));
+        assert!(parse(&["127.0.0.1:1", "--messages", "0"]).unwrap_err().contains("invalid --messages"));
+        assert!(parse(&["127.0.0.1:1", "--advertised-mss", "10"]).unwrap_err().contains("invalid advertised mss"));
+        assert!(parse(&["127.0.0.1:1", "extra"]).unwrap_err().contains("unexpected argument"));
+    }
+}
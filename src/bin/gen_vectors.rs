@@ -0,0 +1,220 @@
+//! 重新生成 `tests/vectors/` 下的线上格式测试向量：`cargo run --bin gen-vectors`
+//! 只在有意改变线上格式时运行，之后用 `git diff tests/vectors` 逐字节检查变化，并在变更说明中写明与已部署对端的兼容性。
+//! 合法向量由构造器构造并编码，清单记录期望的各字段；非法向量从合法向量的编码改动而来，清单记录解码返回的错误。
+
+use link_rs::checksum::ChecksumAlgorithm;
+use link_rs::close_codes::CloseCode;
+use link_rs::options::{Options, SegmentOption};
+use link_rs::segment::{Segment, SegmentFlags, SegmentType};
+use link_rs::seq::SeqNum;
+use link_rs::stats::PeerStats;
+use std::time::Duration;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::process::ExitCode;
+
+/// 清单中使用的标志名
+const FLAGS: [(&str, SegmentFlags); 8] = [
+    ("ACK", SegmentFlags::ACK),
+    ("SACK", SegmentFlags::SACK),
+    ("SEALED", SegmentFlags::SEALED),
+    ("AUTH", SegmentFlags::AUTH),
+    ("TOKEN", SegmentFlags::TOKEN),
+    ("CE", SegmentFlags::CE),
+    ("ECE", SegmentFlags::ECE),
+    ("UNRELIABLE", SegmentFlags::UNRELIABLE),
+];
+
+const CONN_ID: u32 = 0x0102_0304;
+
+// 一个向量：合法向量带构造出的段，非法向量只有字节
+struct Vector {
+    name: &'static str,
+    description: &'static str,
+    bytes: Vec<u8>,
+    segment: Option<Segment>,
+}
+
+fn valid(name: &'static str, description: &'static str, segment: Segment) -> Vector {
+    let bytes = segment.encode().expect("vector segment encodes").to_vec();
+    Vector { name, description, bytes, segment: Some(segment) }
+}
+
+fn invalid(name: &'static str, description: &'static str, bytes: Vec<u8>) -> Vector {
+    Vector { name, description, bytes, segment: None }
+}
+
+fn options(list: &[SegmentOption]) -> Options {
+    list.iter().fold(Options::new(), |options, &option| options.with(option).expect("vector options fit"))
+}
+
+fn data(seq: u64) -> link_rs::segment::SegmentBuilder {
+    Segment::builder(SegmentType::Data).conn_id(CONN_ID).data_seq(seq)
+}
+
+fn vectors() -> Vec<Vector> {
+    let token: Vec<u8> = (0..Segment::RETRY_TOKEN_LEN as u8).collect();
+    let trailer: Vec<u8> = (0..Segment::AUTH_TRAILER_LEN as u8).map(|b| b ^ 0xA5).collect();
+    let timestamp = SegmentOption::Timestamp { value: 0x1111_2222, echo: 0x3333_4444 };
+    let build = |builder: link_rs::segment::SegmentBuilder| builder.build().expect("vector segment is valid");
+    let offer = [ChecksumAlgorithm::Crc32c, ChecksumAlgorithm::XxHash32];
+    let peer_stats = PeerStats {
+        uptime: Duration::from_secs(3600),
+        srtt: Some(Duration::from_micros(25_000)),
+        cwnd: 10,
+        bytes_sent: 1 << 20,
+        bytes_received: 4096,
+        segments_retransmitted: 2,
+    };
+
+    let mut vectors = vec![
+        valid("data", "Data 段：流 0，数据体 hello，默认 crc32c 校验", build(data(1).payload(&b"hello"[..]))),
+        valid("data_piggyback_ack", "捎带确认的 Data 段：ACK 标志，确认号与窗口有效，流 3", build(data(7).stream(3).ack(41).window(64).payload(&b"reply"[..]))),
+        valid("data_xxhash32", "以 xxhash32 校验的 Data 段", build(data(2).checksum(ChecksumAlgorithm::XxHash32).payload(&b"xx"[..]))),
+        valid("data_no_checksum", "不校验的 Data 段：校验和字段为 0", build(data(3).checksum(ChecksumAlgorithm::NoChecksum).payload(&b"raw"[..]))),
+        valid("data_ce", "途中被标记 CE 的 Data 段：CE 位不参与校验和", build(data(4).flags(SegmentFlags::CE).payload(&b"marked"[..]))),
+        valid("data_cwr_timestamp", "降窗后的 Data 段：CWR 与时间戳选项", build(data(5).options(options(&[SegmentOption::Cwr, timestamp])).payload(&b"cwr"[..]))),
+        valid("data_sealed", "加密的 Data 段：SEALED 标志，数据体是密文与标签，解码不解密", build(data(6).flags(SegmentFlags::SEALED).payload(vec![0xC3; 24]))),
+        valid("data_empty", "空数据体的 Data 段：零窗口探测", build(data(8))),
+        valid("data_ack_now", "填满发送窗口的 Data 段：请求立即确认的 ACK-now 选项", build(data(10).options(options(&[SegmentOption::AckNow])).payload(&b"last"[..]))),
+        valid("data_unreliable", "不可靠的 Data 段：UNRELIABLE 标志，序列号属于不可靠消息自己的空间", build(data(0).flags(SegmentFlags::UNRELIABLE).payload(&b"sample"[..]))),
+        valid("ack", "Ack 段：确认号 41，窗口 64", build(Segment::builder(SegmentType::Ack).conn_id(CONN_ID).ack(41).window(64))),
+        valid(
+            "ack_sack",
+            "带 SACK 的 Ack 段：数据体为 [45, 47] 与 [50, 50] 两个闭区间",
+            build(Segment::builder(SegmentType::Ack).conn_id(CONN_ID).ack(41).window(64).sack(&[(SeqNum::new(45), SeqNum::new(47)), (SeqNum::new(50), SeqNum::new(50))])),
+        ),
+        valid("ack_ece", "回送拥塞信号的 Ack 段：ECE 标志", build(Segment::builder(SegmentType::Ack).conn_id(CONN_ID).ack(9).window(32).flags(SegmentFlags::ECE))),
+        valid(
+            "ack_auth",
+            "完成握手的 Ack 段：AUTH 标志，数据体是 64 字节认证尾部",
+            build(Segment::builder(SegmentType::Ack).conn_id(CONN_ID).ack(5001).window(64).flags(SegmentFlags::AUTH).payload(trailer.clone())),
+        ),
+        valid(
+            "syn",
+            "Syn 段：接受 crc32c 与 xxhash32，MSS、SACK-permitted 与时间戳选项",
+            build(Segment::builder(SegmentType::Syn).data_seq(1000).offer(&offer).options(options(&[SegmentOption::Mss(1400), SegmentOption::SackPermitted, timestamp]))),
+        ),
+        valid("syn_ack", "SYN-ACK：分配连接 ID，确认对端的 ISN", build(Segment::builder(SegmentType::Syn).conn_id(CONN_ID).data_seq(5000).ack(1000).window(64).offer(&offer[..1]))),
+        valid("syn_token", "带回 Retry 令牌的 Syn 段：TOKEN 标志，算法列表后是 24 字节令牌", build(Segment::builder(SegmentType::Syn).data_seq(1000).offer(&offer).retry_token(&token))),
+        valid(
+            "syn_auth",
+            "认证的 Syn 段：AUTH 标志，算法列表后是 64 字节认证尾部",
+            build(Segment::builder(SegmentType::Syn).data_seq(1000).flags(SegmentFlags::AUTH).payload([&[ChecksumAlgorithm::Crc32c.id()][..], &trailer].concat())),
+        ),
+        valid("retry", "Retry 段：确认号是被要求重试的 SYN 的序列号，数据体是令牌", build(Segment::builder(SegmentType::Retry).ack(1000).window(0).payload(token.clone()))),
+        valid("ping", "携带 nonce 的保活探测", Segment::ping(0x0102_0304_0506_0708)),
+        valid("pong", "对同一 nonce 的回应", Segment::pong(0x0102_0304_0506_0708)),
+        valid("stats_request", "携带 nonce 的统计查询", Segment::stats_request(0x0102_0304_0506_0708)),
+        valid("stats_reply", "统计查询的回应：nonce 之后是版本 1 的统计", Segment::stats_reply(0x0102_0304_0506_0708, &peer_stats.encode())),
+        valid("path_challenge", "验证对端新地址的 8 字节令牌", Segment::path_challenge(0x1122_3344_5566_7788)),
+        valid("path_response", "原样回送令牌的回应", Segment::path_response(0x1122_3344_5566_7788)),
+        valid("skip", "Skip 段：跳过序列号 42..=44，数据体是最后一个序列号", Segment::skip(SeqNum::new(42), SeqNum::new(44))),
+        valid("new_conn_id", "NewConnId 段：占用序列号 99，数据体是新的连接 ID", {
+            let mut rotation = Segment::new_conn_id(SeqNum::new(99), 0xCAFE_F00D);
+            rotation.set_conn_id(CONN_ID);
+            rotation
+        }),
+        valid("fin", "Fin 段：占用序列号 99", build(Segment::builder(SegmentType::Fin).conn_id(CONN_ID).data_seq(99))),
+        valid("rst", "Rst 段", build(Segment::builder(SegmentType::Rst).conn_id(CONN_ID))),
+        valid(
+            "rst_error",
+            "因协议违规复位：携带错误码选项",
+            build(Segment::builder(SegmentType::Rst).conn_id(CONN_ID).options(options(&[SegmentOption::Error(CloseCode::PROTOCOL_ERROR)]))),
+        ),
+    ];
+
+    // 未识别的选项类型被保留，重新编码后不变
+    let mut unknown = build(data(9).payload(&b"future"[..]));
+    unknown.set_options(Options::decode(&[99, 2, 0xAB, 0xCD, 2, 2, 0x05, 0x78]).expect("unknown option kinds are skipped"));
+    vectors.push(valid("data_unknown_option", "带未识别选项（类型 99）与 MSS 选项的 Data 段", unknown));
+
+    // 非法向量：从合法编码改动而来
+    let base = vectors[0].bytes.clone();
+    let patched = |offset: usize, value: u8| {
+        let mut bytes = base.clone();
+        bytes[offset] = value;
+        bytes
+    };
+    let mut corrupted = base.clone();
+    *corrupted.last_mut().expect("payload") ^= 0x01;
+    let mut long = base.clone();
+    long[..4].copy_from_slice(&(base.len() as u32 + 1).to_be_bytes());
+    let mut reserved = vectors.iter().find(|vector| vector.name == "ack").expect("ack vector").bytes.clone();
+    reserved[5] = 0x80;
+    let mut sack = vectors.iter().find(|vector| vector.name == "ack_sack").expect("sack vector").bytes.clone();
+    sack.truncate(sack.len() - 6);
+    let sack_len = sack.len() as u32;
+    sack[..4].copy_from_slice(&sack_len.to_be_bytes());
+    let mut fin = vectors.iter().find(|vector| vector.name == "fin").expect("fin vector").bytes.clone();
+    fin.push(0x00);
+    let fin_len = fin.len() as u32;
+    fin[..4].copy_from_slice(&fin_len.to_be_bytes());
+    vectors.extend([
+        invalid("bad_checksum", "数据体的一位在途中翻转", corrupted),
+        invalid("bad_length_short", "声明的总长度小于固定头部", patched(3, 10)),
+        invalid("bad_length_long", "声明的总长度超过数据报", long),
+        invalid("truncated", "不足长度前缀的数据报", base[..2].to_vec()),
+        invalid("reserved_flag", "Ack 段设置了最高标志位：它只在 Data 段上表示 UNRELIABLE", reserved),
+        invalid("unknown_type", "未知的段类型 14", patched(4, 14)),
+        invalid("unknown_checksum", "未知的校验算法 id 7", patched(32, 7)),
+        invalid("bad_option", "选项区长度越过段的末尾", patched(37, 200)),
+        invalid("bad_sack", "SACK 数据体不是 16 字节区间的整数倍", sack),
+        invalid("unexpected_payload", "Fin 段携带了一个字节的数据体", fin),
+    ]);
+    vectors
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn quoted(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+fn manifest(vectors: &[Vector]) -> String {
+    let mut out = String::from("# 由 `cargo run --bin gen-vectors` 生成，不要手工修改；格式见 README.md\n");
+    for vector in vectors {
+        let _ = writeln!(out, "\n[[vector]]\nname = {}\ndescription = {}\nfile = \"{}.hex\"", quoted(vector.name), quoted(vector.description), vector.name);
+        let Some(segment) = &vector.segment else {
+            let error = Segment::decode(&vector.bytes).expect_err("invalid vector must not decode");
+            let _ = writeln!(out, "error = {}", quoted(&format!("{:?}", error)));
+            continue;
+        };
+        let flags: Vec<_> = FLAGS.iter().filter(|(_, flag)| segment.flags().contains(*flag)).map(|(name, _)| quoted(name)).collect();
+        let options: Vec<_> = segment.options().iter().map(|option| quoted(&format!("{:?}", option))).collect();
+        let _ = writeln!(out, "type = \"{:?}\"", segment.segment_type());
+        let _ = writeln!(out, "flags = [{}]", flags.join(", "));
+        let _ = writeln!(out, "stream_id = {}\nconn_id = {}", segment.stream_id(), segment.conn_id());
+        let _ = writeln!(out, "seq = {}\nack = {}\nwindow = {}", segment.seq().get(), segment.ack().get(), segment.window());
+        let _ = writeln!(out, "checksum = \"{}\"", segment.checksum());
+        let _ = writeln!(out, "options = [{}]\noptions_hex = \"{}\"", options.join(", "), hex(segment.options().as_bytes()));
+        let _ = writeln!(out, "payload = \"{}\"", hex(segment.data()));
+    }
+    out
+}
+
+// 每行 16 字节，首行是注释
+fn hex_file(vector: &Vector) -> String {
+    let mut out = format!("# {}: {}\n", vector.name, vector.description);
+    for line in vector.bytes.chunks(16) {
+        let _ = writeln!(out, "{}", line.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" "));
+    }
+    out
+}
+
+fn main() -> ExitCode {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/vectors");
+    let vectors = vectors();
+    let mut files = vec![("manifest.toml".to_string(), manifest(&vectors))];
+    files.extend(vectors.iter().map(|vector| (format!("{}.hex", vector.name), hex_file(vector))));
+    for (name, content) in files {
+        if let Err(e) = std::fs::write(dir.join(&name), content) {
+            eprintln!("gen-vectors: cannot write {}: {}", dir.join(&name).display(), e);
+            return ExitCode::FAILURE;
+        }
+    }
+    println!("wrote {} vectors to {}", vectors.len(), dir.display());
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,110 @@
+use link_rs::inspect;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+用法: link-inspect <file> [选项]
+
+解码 pcap 文件（`link_rs --capture` 或 tcpdump 写出的）或 `link_rs --record` 的记录文件中的数据报，每个段输出一行：
+距第一个数据报的时间、地址、方向（给出端口时）、段类型、序列号、确认号、标志与数据体长度。
+无法解析的段输出解码错误，不中止。
+
+选项:
+    -p, --port <port>       只解码来自或发往这个 UDP 端口的数据报，并据此标出方向
+    -v, --verbose           在每个段之后输出它的原始字节
+    --json                  每个段输出一行 JSON（需要 serde 特性）
+    -h, --help              显示本帮助";
+
+/// 解析后的命令行参数
+#[derive(Debug, PartialEq)]
+struct Args {
+    file: PathBuf,
+    port: Option<u16>,
+    verbose: bool,
+    json: bool,
+}
+
+// 解析命令行参数（不含程序名）；`--help` 返回 None
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut file = None;
+    let mut port = None;
+    let mut verbose = false;
+    let mut json = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-p" | "--port" => {
+                let value = args.next().ok_or_else(|| format!("{} requires a value", arg))?;
+                port = Some(value.parse::<u16>().map_err(|_| format!("invalid port '{}'", value))?);
+            }
+            "-v" | "--verbose" => verbose = true,
+            "--json" if cfg!(feature = "serde") => json = true,
+            "--json" => return Err("--json requires the serde feature".to_string()),
+            other if other.starts_with('-') => return Err(format!("unknown argument '{}'", other)),
+            other if file.is_none() => file = Some(PathBuf::from(other)),
+            other => return Err(format!("unexpected argument '{}'", other)),
+        }
+    }
+    let file = file.ok_or("missing capture file")?;
+    Ok(Some(Args { file, port, verbose, json }))
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("错误: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let entries = match std::fs::read(&args.file).map_err(|e| e.to_string()).and_then(|file| inspect::inspect(&file, args.port).map_err(|e| e.to_string())) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("错误: 无法读取 {}: {}", args.file.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+    for entry in &entries {
+        #[cfg(feature = "serde")]
+        if args.json {
+            println!("{}", entry.to_json(args.verbose));
+            continue;
+        }
+        match args.verbose {
+            true => println!("{}{}\n", entry, entry.hexdump()),
+            false => println!("{}", entry),
+        }
+    }
+    if !args.json {
+        let failed = entries.iter().filter(|entry| entry.segment.is_err()).count();
+        eprintln!("{} 个段，{} 个无法解析", entries.len() - failed, failed);
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_arguments() {
+        let args = parse(&["session.pcap", "--port", "7000", "-v"]).unwrap().unwrap();
+        assert_eq!(args, Args { file: PathBuf::from("session.pcap"), port: Some(7000), verbose: true, json: false });
+        assert_eq!(parse(&["session.rec"]).unwrap().unwrap().port, None);
+        assert!(parse(&["--help"]).unwrap().is_none());
+        assert_eq!(parse(&["a.pcap", "--json"]).map(|args| args.unwrap().json), if cfg!(feature = "serde") { Ok(true) } else { Err("--json requires the serde feature".to_string()) });
+
+        assert!(parse(&[]).unwrap_err().contains("missing capture file"));
+        assert!(parse(&["a.pcap", "--port", "70000"]).unwrap_err().contains("invalid port"));
+        assert!(parse(&["a.pcap", "b.pcap"]).unwrap_err().contains("unexpected argument"));
+    }
+}
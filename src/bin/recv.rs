@@ -0,0 +1,158 @@
+use link_rs::cli;
+use link_rs::config::LinkConfig;
+use link_rs::listener::Listener;
+use link_rs::transfer::{self, Progress, TransferError};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str = "\
+用法: link-recv <output-dir> --bind <addr:port> [选项]
+
+等待一个 link-send 连接，把它发来的文件写入输出目录；SHA-256 校验不通过时删除文件并以非零状态退出。
+
+选项:
+    --bind <addr:port>      监听地址
+    --dual-stack            在 [::] 上同时接受 IPv4 与 IPv6 连接
+    --max-payload <bytes>   单个数据报的最大数据体 [默认: 1168]
+    --recv-buffer <bytes>   接收缓冲区大小 [默认: 65536]
+    --chaos <spec>          注入故障，如 loss=0.05,delay=20ms±10ms
+    --config <file.toml>    从 TOML 文件读取参数；LINK_* 环境变量覆盖文件，命令行选项覆盖两者
+    -h, --help              显示本帮助";
+
+/// 解析后的命令行参数
+#[derive(Debug)]
+struct Args {
+    dir: PathBuf,
+    bind: SocketAddr,
+    config: LinkConfig,
+}
+
+// 以 `config` 为基础解析命令行参数（不含程序名）；`--help` 返回 None
+fn parse_args(args: impl IntoIterator<Item = String>, config: LinkConfig) -> Result<Option<Args>, String> {
+    let mut dir = None;
+    let mut bind = None;
+    let mut config = config;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} requires a value", arg));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--bind" => {
+                let value = value()?;
+                bind = Some(value.parse().map_err(|_| format!("invalid bind address '{}', expected addr:port", value))?);
+            }
+            other if other.starts_with('-') => {
+                if !cli::config_flag(&mut config, other, &mut value)? {
+                    return Err(format!("unknown argument '{}'", other));
+                }
+            }
+            other if dir.is_none() => dir = Some(PathBuf::from(other)),
+            other => return Err(format!("unexpected argument '{}'", other)),
+        }
+    }
+    config.validate().map_err(|e| e.to_string())?;
+    let dir = dir.ok_or("missing output directory")?;
+    let bind = bind.ok_or("missing --bind address")?;
+    Ok(Some(Args { dir, bind, config }))
+}
+
+fn mib(bytes: f64) -> f64 {
+    bytes / (1024.0 * 1024.0)
+}
+
+fn print_progress(progress: Progress) {
+    let percent = if progress.size == 0 { 100.0 } else { progress.bytes as f64 * 100.0 / progress.size as f64 };
+    println!(
+        "已接收 {:.1}/{:.1} MiB ({:.0}%)，{:.2} MiB/s",
+        mib(progress.bytes as f64),
+        mib(progress.size as f64),
+        percent,
+        mib(progress.bytes as f64 / progress.elapsed.as_secs_f64())
+    );
+}
+
+async fn receive(args: Args) -> Result<transfer::Transferred, TransferError> {
+    if !args.dir.is_dir() {
+        return Err(TransferError::Io(format!("{} is not a directory", args.dir.display())));
+    }
+    let listener = Listener::bind_with(args.bind, args.config).await?;
+    println!("在 {} 上等待发送方", listener.local_addr()?);
+    let (connection, peer) = listener.accept().await?;
+    println!("{} 已连接", peer);
+    let received = transfer::recv_file(&connection, &args.dir, print_progress).await;
+    // 失败时判定已经发出（或连接已断开），关闭的结果不再重要
+    match received {
+        Ok(received) => {
+            // 发送方收到判定后先关闭；本端等到它的 FIN 再关闭，双方的 close 都不会等一个已经退出的对端
+            while connection.recv().await?.is_some() {}
+            // 判定已经送达，丢包下关闭超时不影响结果
+            if let Err(e) = connection.close().await {
+                tracing::warn!(error = %e, "close after the transfer failed");
+            }
+            Ok(received)
+        }
+        Err(e) => {
+            let _ = connection.close().await;
+            Err(e)
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let raw: Vec<String> = std::env::args().skip(1).collect();
+    let args = match cli::load_config(&raw).and_then(|config| parse_args(raw, config)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("错误: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+
+    match receive(args).await {
+        Ok(received) => {
+            println!(
+                "已接收 {}：{} 字节，用时 {:.2}s，{:.2} MiB/s，SHA-256 校验通过",
+                received.path.display(),
+                received.bytes,
+                received.elapsed.as_secs_f64(),
+                mib(received.throughput())
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()), LinkConfig::default())
+    }
+
+    #[test]
+    fn test_arguments() {
+        let args = parse(&["out", "--bind", "0.0.0.0:9000", "--dual-stack"]).unwrap().unwrap();
+        assert_eq!((args.dir, args.bind), (PathBuf::from("out"), "0.0.0.0:9000".parse().unwrap()));
+        assert!(args.config.dual_stack);
+        assert!(parse(&["--help"]).unwrap().is_none());
+
+        assert!(parse(&["--bind", "0.0.0.0:9000"]).unwrap_err().contains("missing output directory"));
+        assert!(parse(&["out"]).unwrap_err().contains("missing --bind"));
+        assert!(parse(&["out", "--bind", "9000"]).unwrap_err().contains("invalid bind address"));
+        assert!(parse(&["out", "other", "--bind", "0.0.0.0:9000"]).unwrap_err().contains("unexpected argument"));
+    }
+}
@@ -0,0 +1,120 @@
+use link_rs::cli;
+use link_rs::config::LinkConfig;
+use link_rs::replay::{self, ReplayEvent};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str = "\
+用法: link-replay <file> [选项]
+
+把 `link_rs --record` 记录的数据报按原来的相对时间重放给内存网络上的一个新监听器，输出它一侧的事件：
+接受的连接、交付的消息、对端关闭与连接错误。有连接出错时以非零状态退出。
+
+选项:
+    --speed <n>             按 n 倍速重放，inf 表示不等待 [默认: 1]
+    --max-payload <bytes>   单个数据报的最大数据体 [默认: 1168]
+    --config <file.toml>    从 TOML 文件读取参数；LINK_* 环境变量覆盖文件，命令行选项覆盖两者
+    -h, --help              显示本帮助";
+
+/// 解析后的命令行参数
+#[derive(Debug)]
+struct Args {
+    file: PathBuf,
+    speed: f64,
+    config: LinkConfig,
+}
+
+// 以 `config` 为基础解析命令行参数（不含程序名）；`--help` 返回 None
+fn parse_args(args: impl IntoIterator<Item = String>, config: LinkConfig) -> Result<Option<Args>, String> {
+    let mut file = None;
+    let mut speed = 1.0;
+    let mut config = config;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} requires a value", arg));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--speed" => {
+                let value = value()?;
+                speed = match value.parse::<f64>() {
+                    Ok(speed) if speed > 0.0 => speed,
+                    _ => return Err(format!("invalid speed '{}', expected a positive number", value)),
+                };
+            }
+            other if other.starts_with('-') => {
+                if !cli::config_flag(&mut config, other, &mut value)? {
+                    return Err(format!("unknown argument '{}'", other));
+                }
+            }
+            other if file.is_none() => file = Some(PathBuf::from(other)),
+            other => return Err(format!("unexpected argument '{}'", other)),
+        }
+    }
+    config.validate().map_err(|e| e.to_string())?;
+    let file = file.ok_or("missing record file")?;
+    Ok(Some(Args { file, speed, config }))
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let raw: Vec<String> = std::env::args().skip(1).collect();
+    let args = match cli::load_config(&raw).and_then(|config| parse_args(raw, config)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("错误: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+
+    let records = match replay::read(&args.file).await {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("错误: 无法读取 {}: {}", args.file.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+    println!("重放 {} 个数据报", records.len());
+    match replay::replay(&records, args.config, args.speed).await {
+        Ok(events) => {
+            for event in &events {
+                println!("{}", event);
+            }
+            match events.iter().any(|event| matches!(event, ReplayEvent::Failed(..))) {
+                true => ExitCode::FAILURE,
+                false => ExitCode::SUCCESS,
+            }
+        }
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()), LinkConfig::default())
+    }
+
+    #[test]
+    fn test_arguments() {
+        let args = parse(&["session.rec", "--speed", "inf"]).unwrap().unwrap();
+        assert_eq!((args.file, args.speed), (PathBuf::from("session.rec"), f64::INFINITY));
+        assert_eq!(parse(&["session.rec"]).unwrap().unwrap().speed, 1.0);
+        assert!(parse(&["--help"]).unwrap().is_none());
+
+        assert!(parse(&[]).unwrap_err().contains("missing record file"));
+        assert!(parse(&["session.rec", "--speed", "0"]).unwrap_err().contains("invalid speed"));
+        assert!(parse(&["a.rec", "b.rec"]).unwrap_err().contains("unexpected argument"));
+    }
+}
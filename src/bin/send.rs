@@ -0,0 +1,141 @@
+use link_rs::cli;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::transfer::{self, Progress, TransferError};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str = "\
+用法: link-send <file> <addr:port> [选项]
+
+把文件发送给 link-recv：先发送文件名、大小与 SHA-256，再分块发送内容，接收方校验通过后才算成功。
+
+选项:
+    --dual-stack            目标是 IPv4 映射地址时使用双栈套接字
+    --max-payload <bytes>   单个数据报的最大数据体 [默认: 1168]
+    --recv-buffer <bytes>   接收缓冲区大小 [默认: 65536]
+    --chaos <spec>          注入故障，如 loss=0.05,delay=20ms±10ms
+    --config <file.toml>    从 TOML 文件读取参数；LINK_* 环境变量覆盖文件，命令行选项覆盖两者
+    -h, --help              显示本帮助";
+
+/// 解析后的命令行参数
+#[derive(Debug)]
+struct Args {
+    file: PathBuf,
+    target: SocketAddr,
+    config: LinkConfig,
+}
+
+// 以 `config` 为基础解析命令行参数（不含程序名）；`--help` 返回 None
+fn parse_args(args: impl IntoIterator<Item = String>, config: LinkConfig) -> Result<Option<Args>, String> {
+    let mut file = None;
+    let mut target = None;
+    let mut config = config;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} requires a value", arg));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            other if other.starts_with('-') => {
+                if !cli::config_flag(&mut config, other, &mut value)? {
+                    return Err(format!("unknown argument '{}'", other));
+                }
+            }
+            other if file.is_none() => file = Some(PathBuf::from(other)),
+            other if target.is_none() => {
+                target = Some(other.parse().map_err(|_| format!("invalid target address '{}', expected addr:port", other))?);
+            }
+            other => return Err(format!("unexpected argument '{}'", other)),
+        }
+    }
+    config.validate().map_err(|e| e.to_string())?;
+    let file = file.ok_or("missing file")?;
+    let target = target.ok_or("missing target address")?;
+    Ok(Some(Args { file, target, config }))
+}
+
+fn mib(bytes: f64) -> f64 {
+    bytes / (1024.0 * 1024.0)
+}
+
+fn print_progress(progress: Progress) {
+    let percent = if progress.size == 0 { 100.0 } else { progress.bytes as f64 * 100.0 / progress.size as f64 };
+    println!(
+        "已发送 {:.1}/{:.1} MiB ({:.0}%)，{:.2} MiB/s",
+        mib(progress.bytes as f64),
+        mib(progress.size as f64),
+        percent,
+        mib(progress.bytes as f64 / progress.elapsed.as_secs_f64())
+    );
+}
+
+async fn send(args: Args) -> Result<transfer::Transferred, TransferError> {
+    let connection = Connection::connect_with(args.target, args.config).await?;
+    let sent = transfer::send_file(&connection, &args.file, print_progress).await?;
+    // 接收方已经确认校验通过，丢包下关闭超时不影响结果
+    if let Err(e) = connection.close().await {
+        tracing::warn!(error = %e, "close after the transfer failed");
+    }
+    Ok(sent)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let raw: Vec<String> = std::env::args().skip(1).collect();
+    let args = match cli::load_config(&raw).and_then(|config| parse_args(raw, config)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("错误: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+
+    let target = args.target;
+    match send(args).await {
+        Ok(sent) => {
+            println!(
+                "已发送 {} 到 {}：{} 字节，用时 {:.2}s，{:.2} MiB/s，接收方校验通过",
+                sent.path.display(),
+                target,
+                sent.bytes,
+                sent.elapsed.as_secs_f64(),
+                mib(sent.throughput())
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()), LinkConfig::default())
+    }
+
+    #[test]
+    fn test_arguments() {
+        let args = parse(&["data.bin", "127.0.0.1:9000", "--max-payload", "500"]).unwrap().unwrap();
+        assert_eq!((args.file, args.target), (PathBuf::from("data.bin"), "127.0.0.1:9000".parse().unwrap()));
+        assert_eq!(args.config.mss, 500 + link_rs::segment::Segment::FIXED_HEADER_LEN);
+        assert!(parse(&["--help"]).unwrap().is_none());
+
+        assert!(parse(&[]).unwrap_err().contains("missing file"));
+        assert!(parse(&["data.bin"]).unwrap_err().contains("missing target"));
+        assert!(parse(&["data.bin", "localhost"]).unwrap_err().contains("invalid target address"));
+        assert!(parse(&["data.bin", "127.0.0.1:1", "extra"]).unwrap_err().contains("unexpected argument"));
+    }
+}
@@ -0,0 +1,214 @@
+//! 阻塞的客户端
+//! `blocking` 特性提供不需要异步运行时的 `Connection`，供同步的命令行工具使用：标准库的 `UdpSocket` 直接驱动
+//! 不做 IO 的协议核心（`endpoint` 模块），定时器以 `Instant` 计算，接收以套接字的读超时等到最近的截止时间。
+//! 握手（SYN 的重传计划与总超时）、RTO 重传、确认与 FIN 交换都与异步的连接相同，对端就是普通的 `Listener`/`Server`。
+//!
+//! 没有后台任务：协议只在 `send`、`recv` 与 `close` 阻塞期间推进，两次调用之间到达的段留在套接字的接收缓冲中，
+//! 到期的重传与延迟确认也等到下一次调用时才处理。调用间隔超过 `idle_timeout` 时对端会以空闲回收连接。
+//! 收发不经过 `LinkConfig::faults` 与 `capture`。
+
+use crate::config::LinkConfig;
+use crate::connection::{handshake_datagram, jittered};
+use crate::endpoint::{ConnectionCore, Handshake, MAIN_STREAM, Opener, fresh_isn};
+use crate::error::{self, LinkError};
+use crate::params::TransportParameters;
+use crate::pool::BufferPool;
+use crate::segment::{self, Segment};
+use crate::stats::ConnectionStats;
+use bytes::{Bytes, BytesMut};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker, ready};
+use std::time::{Duration, Instant};
+
+/// 一条阻塞的客户端连接
+#[derive(Debug)]
+pub struct Connection {
+    socket: UdpSocket,
+    core: ConnectionCore,
+    buf: Vec<u8>,
+    linger: Duration,
+}
+
+impl Connection {
+    /// 连接到 `addr`；解析出多个地址时依次尝试，返回最后一个地址的错误。
+    /// 参数未通过 `LinkConfig::validate` 时返回 `LinkError::Config`，握手未能完成时返回 `ConnectTimeout`
+    pub fn connect(addr: impl ToSocketAddrs, config: LinkConfig) -> Result<Connection, LinkError> {
+        config.validate()?;
+        let mut last = LinkError::Io { kind: io::ErrorKind::InvalidInput, message: "no addresses to connect to".to_string() };
+        for remote in addr.to_socket_addrs()? {
+            match Self::connect_addr(remote, &config) {
+                Ok(connection) => return Ok(connection),
+                Err(e) => last = e,
+            }
+        }
+        Err(last)
+    }
+
+    fn connect_addr(remote: SocketAddr, config: &LinkConfig) -> Result<Connection, LinkError> {
+        let any: SocketAddr = if remote.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+        let socket = UdpSocket::bind(any)?;
+        socket.connect(remote)?;
+        let mut buf = vec![0u8; config.recv_buffer];
+        let handshake = match handshake(&socket, &mut buf, config) {
+            Err(LinkError::Protocol(_)) => handshake(&socket, &mut buf, config),
+            result => result,
+        }?;
+        let core = ConnectionCore::new(handshake, config, remote, Arc::new(BufferPool::new(config)), Instant::now());
+        Ok(Connection { socket, core, buf, linger: config.linger })
+    }
+
+    /// 发送一条消息（一个段），发送队列已满时阻塞到窗口打开；消息放不进对端通告的 MSS 时返回 `MessageTooLarge`
+    pub fn send(&mut self, data: &[u8]) -> Result<(), LinkError> {
+        let mut data = Some(Bytes::copy_from_slice(data));
+        self.drive(None, |core, cx, now| core.poll_send(MAIN_STREAM, cx, &mut data, now)).map(|sent| sent.expect("no deadline"))
+    }
+
+    /// 接收下一条消息，至多等待 `timeout`：超时返回 `kind` 为 `TimedOut` 的 `LinkError::Io`，
+    /// 对端关闭写方向后返回 `Closed`，连接失败时返回失败的原因
+    pub fn recv(&mut self, timeout: Duration) -> Result<Vec<u8>, LinkError> {
+        let deadline = Instant::now() + timeout;
+        match self.drive(Some(deadline), |core, cx, now| core.poll_recv(MAIN_STREAM, cx, now))? {
+            Some(Some(data)) => Ok(data.to_vec()),
+            Some(None) => Err(LinkError::Closed),
+            None => Err(io::Error::new(io::ErrorKind::TimedOut, "recv timed out").into()),
+        }
+    }
+
+    /// 优雅关闭：等待已发送的数据全部被确认、FIN 交换完成，与异步的 `close` 相同；
+    /// `linger` 内未能完成时以 Rst 终止并返回 `CloseTimedOut`
+    pub fn close(mut self) -> Result<(), LinkError> {
+        self.core.begin_close();
+        let deadline = Instant::now() + self.linger;
+        let closed = self.drive(Some(deadline), |core, cx, now| {
+            ready!(core.poll_shutdown(MAIN_STREAM, cx, now))?;
+            core.poll_peer_fin(cx, now)
+        })?;
+        if closed.is_none() {
+            self.core.reset(LinkError::CloseTimedOut);
+            self.transmit();
+            return Err(LinkError::CloseTimedOut);
+        }
+        Ok(())
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, LinkError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.core.peer_addr()
+    }
+
+    /// 握手时与对端协商出的参数
+    pub fn negotiated(&self) -> TransportParameters {
+        self.core.negotiated()
+    }
+
+    /// 流 0 的统计
+    pub fn stats(&self) -> ConnectionStats {
+        ConnectionStats { local_addr: self.socket.local_addr().ok(), peer_addr: Some(self.core.peer_addr()), ..self.core.stats() }
+    }
+
+    // 推进协议直到 `poll` 就绪或到达 `deadline`（返回 None）：发出待发送的数据报，
+    // 在套接字上等到最近的定时器或 `deadline`，把收到的数据报与到期的定时器交给协议核心
+    fn drive<T>(
+        &mut self,
+        deadline: Option<Instant>,
+        mut poll: impl FnMut(&mut ConnectionCore, &mut Context<'_>, Instant) -> Poll<Result<T, LinkError>>,
+    ) -> Result<Option<T>, LinkError> {
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            let polled = poll(&mut self.core, &mut cx, Instant::now());
+            self.transmit();
+            if let Poll::Ready(result) = polled {
+                return result.map(Some);
+            }
+            if self.core.is_terminated() {
+                return Err(self.core.error().cloned().unwrap_or(LinkError::Closed));
+            }
+            let now = Instant::now();
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                return Ok(None);
+            }
+            let wake = [self.core.next_deadline(), deadline].into_iter().flatten().min();
+            // 读超时不能为零，已经到期的定时器至少等 1ms
+            let wait = wake.map(|wake| wake.saturating_duration_since(now).max(Duration::from_millis(1)));
+            self.socket.set_read_timeout(wait)?;
+            match self.socket.recv(&mut self.buf) {
+                // 截断的数据报可能在末尾解析出更短的段，整个丢弃
+                Ok(len) if !segment::is_truncated(&self.buf[..len]) => {
+                    self.core.handle_datagram(Instant::now(), Bytes::copy_from_slice(&self.buf[..len]));
+                }
+                Ok(_) => {}
+                Err(e) if error::is_fatal(&e) => self.core.fail(e.into()),
+                // 超时，或 ICMP 不可达等只影响单个数据报的错误
+                Err(_) => {}
+            }
+            self.core.handle_timeout(Instant::now());
+        }
+    }
+
+    // 发出协议核心中全部待发送的数据报；发送失败等同于丢包，由重传处理。过大的数据报交还协议核心，
+    // 它降低有效 MSS、重新分片后接着发出
+    fn transmit(&mut self) {
+        while let Some((datagram, _)) = self.core.poll_transmit() {
+            if let Err(e) = self.socket.send(&datagram)
+                && error::is_too_large(&e)
+            {
+                self.core.on_datagram_too_large(&datagram, Instant::now());
+            }
+        }
+    }
+}
+
+// 客户端握手，重传计划与总超时同异步的连接
+fn handshake(socket: &UdpSocket, buf: &mut [u8], config: &LinkConfig) -> Result<Handshake, LinkError> {
+    let start = Instant::now();
+    let give_up = start + config.handshake_timeout;
+    let mut opener = Opener::new(fresh_isn(), config)?;
+    let mut interval = config.syn_retry_initial.min(config.max_rto);
+    let mut attempts = 0;
+    for _ in 0..=config.syn_max_retries {
+        send_ignoring_refused(socket, &handshake_datagram(&opener, &opener.retransmission())?, config.mss)?;
+        attempts += 1;
+        let retransmit_at = (Instant::now() + jittered(interval, config.syn_retry_jitter)).min(give_up);
+        interval = (interval * 2).min(config.max_rto);
+
+        while let Some(wait) = retransmit_at.checked_duration_since(Instant::now()).filter(|wait| !wait.is_zero()) {
+            socket.set_read_timeout(Some(wait))?;
+            // 对端端口未打开时 ICMP 不可达会表现为接收错误，继续等待直到超时
+            let len = match socket.recv(buf) {
+                Ok(len) => len,
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+                Err(e) if error::is_fatal(&e) => return Err(e.into()),
+                Err(_) => continue,
+            };
+            if segment::is_truncated(&buf[..len]) {
+                continue;
+            }
+            let mut datagram = BytesMut::from(&buf[..len]);
+            while let Ok(Some(segment)) = Segment::decode_from(&mut datagram) {
+                if let Some(reply) = opener.on_segment(&segment)? {
+                    send_ignoring_refused(socket, &handshake_datagram(&opener, &reply)?, config.mss)?;
+                }
+                if opener.is_established() {
+                    return Ok(opener.finish());
+                }
+            }
+        }
+        if Instant::now() >= give_up {
+            break;
+        }
+    }
+    Err(LinkError::ConnectTimeout { attempts, elapsed: start.elapsed() })
+}
+
+// 发送握手段；对端端口未打开导致的拒绝视同丢包，过大的握手数据报（如 0-RTT 数据）报告为 `DatagramTooLarge`
+fn send_ignoring_refused(socket: &UdpSocket, datagram: &[u8], mss: usize) -> Result<(), LinkError> {
+    match socket.send(datagram) {
+        Err(e) if e.kind() != io::ErrorKind::ConnectionRefused => Err(error::send_error(e, datagram.len(), mss)),
+        _ => Ok(()),
+    }
+}
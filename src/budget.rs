@@ -0,0 +1,351 @@
+//! 内存预算
+//! 在内存有限的环境中（如 128MB 的容器），重排缓冲区、攒着的分片、发送队列与墓碑无论怎样组合都不能耗尽内存。
+//! `MemoryBudget` 是一个监听器的所有连接共用的账本：持有缓冲的部分各以一个 `Charge` 登记自己当前占用的字节数，
+//! 按 `GRANULE` 向上取整，取整后的值变化时才以 relaxed 原子操作更新合计，`Charge` 被丢弃时归还。计数是近似的：
+//! 重排缓冲区、分片与发送队列按数据体计，共享的底层存储与各种小对象不计。
+//!
+//! 每条连接有一个 `MemoryAccount`，按部分（`Component`）分别记账，合计同时计入全局。开户时先记下 `CONNECTION_OVERHEAD`
+//! 的基础占用，全局上限因此同时限制了连接数：`MemoryBudget::account` 在全局额度放不下它时返回 None，监听器以
+//! `SERVER_BUSY` 拒绝这次握手（见 `listener` 模块）。不经监听器建立的连接（客户端）有一个不计入任何账本的账户，
+//! 只受 `LinkConfig::connection_memory_limit` 约束。
+//!
+//! 超出限制时的处理在连接中（见 `ConnectionCore`）：占用超出连接的上限时从最早开始的一条丢起攒着的分片；
+//! 各个流通告的接收窗口与发送队列的上限不超过剩余额度（连接的与全局的较小者）分给它的部分，全局额度紧张时
+//! 所有连接的窗口一起收小，对端随之放慢发送。为了总能推进，窗口至少留一个段，连接的占用因此至多超出上限一个段。
+//! 即使发送队列清空也放不下的写入以 `LinkError::MemoryLimit` 拒绝，消息没有入队，连接不受影响。
+//!
+//! `MemoryBudget::top_consumers` 按占用从多到少列出各条连接与它的分项（`Listener::memory_consumers`），
+//! 合计与上限见 `MetricsSnapshot::memory_used` 与 `memory_limit`。
+
+use crate::config::LinkConfig;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+/// 记账的粒度（字节）：登记的占用按它向上取整，取整后的值不变时不碰原子计数
+pub const GRANULE: usize = 4096;
+
+/// 一条连接的基础占用（字节）：协议状态、定时器与暂存缓冲等不随流量增减的部分
+pub const CONNECTION_OVERHEAD: usize = 16 * 1024;
+
+/// 连接中占用内存的部分
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Component {
+    Connection,     // 基础占用，见 `CONNECTION_OVERHEAD`
+    Reorder,        // 各个流的重排缓冲区
+    Reassembly,     // 攒着的分片与提前取出、尚未读走的无序消息
+    Send,           // 各个流的发送队列：暂存的写入与已发送未确认的数据体
+}
+
+impl Component {
+    /// 部分数
+    pub const COUNT: usize = 4;
+
+    /// 全部部分，按声明顺序
+    pub const ALL: [Component; Self::COUNT] = [Component::Connection, Component::Reorder, Component::Reassembly, Component::Send];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+fn round_up(bytes: usize) -> usize {
+    bytes.checked_next_multiple_of(GRANULE).unwrap_or(usize::MAX)
+}
+
+// 一条连接的分项占用，由它的账户更新，账本的登记表只留弱引用
+#[derive(Debug)]
+struct Usage {
+    peer: SocketAddr,
+    conn_id: u32,
+    components: [AtomicUsize; Component::COUNT],
+}
+
+/// 一项登记的占用：`set` 改为新的字节数，被丢弃时归还
+#[derive(Debug)]
+pub struct Charge {
+    budget: Option<Arc<MemoryBudget>>,
+    usage: Option<(Arc<Usage>, Component)>,
+    charged: usize,     // 取整后已计入的字节数
+}
+
+impl Charge {
+    // 不计入任何账本，只记下自己的占用
+    fn detached() -> Self {
+        Self { budget: None, usage: None, charged: 0 }
+    }
+
+    /// 登记的占用改为 `bytes`，取整后与之前相同时什么也不做
+    pub fn set(&mut self, bytes: usize) {
+        let rounded = round_up(bytes);
+        if rounded == self.charged {
+            return;
+        }
+        let update = |counter: &AtomicUsize| match rounded > self.charged {
+            true => counter.fetch_add(rounded - self.charged, Ordering::Relaxed),
+            false => counter.fetch_sub(self.charged - rounded, Ordering::Relaxed),
+        };
+        if let Some(budget) = &self.budget {
+            update(&budget.used);
+        }
+        if let Some((usage, component)) = &self.usage {
+            update(&usage.components[component.index()]);
+        }
+        self.charged = rounded;
+    }
+
+    /// 取整后计入的字节数
+    pub fn charged(&self) -> usize {
+        self.charged
+    }
+}
+
+impl Drop for Charge {
+    fn drop(&mut self) {
+        self.set(0);
+    }
+}
+
+/// 一条连接在账本中的占用（见 `MemoryBudget::top_consumers`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryConsumer {
+    pub peer: SocketAddr,       // 开户时的对端地址
+    pub conn_id: u32,           // 开户时的连接 ID，轮换后不变
+    pub used: usize,
+    pub components: [usize; Component::COUNT],  // 按 `Component::ALL` 的顺序
+}
+
+impl MemoryConsumer {
+    /// 某一部分的占用
+    pub fn get(&self, component: Component) -> usize {
+        self.components[component.index()]
+    }
+}
+
+/// 监听器的所有连接共用的内存账本
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit: Option<usize>,
+    connection_limit: Option<usize>,
+    used: AtomicUsize,
+    accounts: Mutex<Vec<Weak<Usage>>>,  // 开过户的连接，已关闭的在登记表扩容前清理
+}
+
+impl MemoryBudget {
+    /// 以 `memory_limit` 为全局上限、`connection_memory_limit` 为每个账户的上限
+    pub fn new(config: &LinkConfig) -> Self {
+        Self { limit: config.memory_limit, connection_limit: config.connection_memory_limit, used: AtomicUsize::new(0), accounts: Mutex::new(Vec::new()) }
+    }
+
+    /// 当前合计的占用
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// 全局的剩余额度，不限时为 None
+    pub fn available(&self) -> Option<usize> {
+        self.limit.map(|limit| limit.saturating_sub(self.used()))
+    }
+
+    /// 全局额度能否再容纳 `bytes`
+    pub fn has_room(&self, bytes: usize) -> bool {
+        self.available().is_none_or(|available| available >= bytes)
+    }
+
+    /// 为一条新连接开户并记下基础占用；全局额度放不下时返回 None
+    pub fn account(self: &Arc<Self>, peer: SocketAddr, conn_id: u32) -> Option<MemoryAccount> {
+        // 先记账再检查：多个分发任务同时开户时不会一起挤过上限
+        let before = self.used.fetch_add(CONNECTION_OVERHEAD, Ordering::Relaxed);
+        if self.limit.is_some_and(|limit| before.saturating_add(CONNECTION_OVERHEAD) > limit) {
+            self.used.fetch_sub(CONNECTION_OVERHEAD, Ordering::Relaxed);
+            return None;
+        }
+        let usage = Arc::new(Usage { peer, conn_id, components: Default::default() });
+        usage.components[Component::Connection.index()].store(CONNECTION_OVERHEAD, Ordering::Relaxed);
+        {
+            let mut accounts = self.accounts.lock().expect("memory accounts poisoned");
+            if accounts.len() == accounts.capacity() {
+                accounts.retain(|usage| usage.strong_count() > 0);
+            }
+            accounts.push(Arc::downgrade(&usage));
+        }
+        let mut charges = Component::ALL.map(|component| Charge { budget: Some(self.clone()), usage: Some((usage.clone(), component)), charged: 0 });
+        charges[Component::Connection.index()].charged = CONNECTION_OVERHEAD;
+        Some(MemoryAccount { budget: Some(self.clone()), limit: self.connection_limit, charges })
+    }
+
+    /// 只计入全局合计的一项占用，如监听器的墓碑表
+    pub fn charge(self: &Arc<Self>) -> Charge {
+        Charge { budget: Some(self.clone()), usage: None, charged: 0 }
+    }
+
+    /// 占用最多的 `n` 条连接，从多到少
+    pub fn top_consumers(&self, n: usize) -> Vec<MemoryConsumer> {
+        let accounts = self.accounts.lock().expect("memory accounts poisoned");
+        let mut consumers: Vec<_> = accounts
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(|usage| {
+                let components = usage.components.each_ref().map(|counter| counter.load(Ordering::Relaxed));
+                MemoryConsumer { peer: usage.peer, conn_id: usage.conn_id, used: components.iter().sum(), components }
+            })
+            .collect();
+        consumers.sort_by(|a, b| b.used.cmp(&a.used).then(a.conn_id.cmp(&b.conn_id)));
+        consumers.truncate(n);
+        consumers
+    }
+}
+
+/// 一条连接的账户：各部分的占用与连接的上限
+#[derive(Debug)]
+pub struct MemoryAccount {
+    budget: Option<Arc<MemoryBudget>>,
+    limit: Option<usize>,
+    charges: [Charge; Component::COUNT],
+}
+
+impl MemoryAccount {
+    /// 不计入任何账本的账户，只受 `limit` 约束；同样记下基础占用
+    pub fn detached(limit: Option<usize>) -> Self {
+        let mut charges = Component::ALL.map(|_| Charge::detached());
+        charges[Component::Connection.index()].set(CONNECTION_OVERHEAD);
+        Self { budget: None, limit, charges }
+    }
+
+    /// 某一部分的占用改为 `bytes`
+    pub fn set(&mut self, component: Component, bytes: usize) {
+        self.charges[component.index()].set(bytes);
+    }
+
+    /// 某一部分取整后的占用
+    pub fn get(&self, component: Component) -> usize {
+        self.charges[component.index()].charged()
+    }
+
+    /// 各部分合计的占用
+    pub fn used(&self) -> usize {
+        self.charges.iter().map(Charge::charged).sum()
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// 占用超出了连接的上限
+    pub fn over_limit(&self) -> bool {
+        self.limit.is_some_and(|limit| self.used() > limit)
+    }
+
+    /// 剩余额度：连接的与全局的较小者，都不限时为 None
+    pub fn room(&self) -> Option<usize> {
+        let own = self.limit.map(|limit| limit.saturating_sub(self.used()));
+        let global = self.budget.as_ref().and_then(|budget| budget.available());
+        match (own, global) {
+            (Some(own), Some(global)) => Some(own.min(global)),
+            (own, global) => own.or(global),
+        }
+    }
+
+    /// `component` 清空之后的剩余额度能否容纳 `bytes`
+    pub fn fits(&self, component: Component, bytes: usize) -> bool {
+        self.room().is_none_or(|room| room.saturating_add(self.get(component)) >= bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(limit: Option<usize>, connection_limit: Option<usize>) -> Arc<MemoryBudget> {
+        Arc::new(MemoryBudget::new(&LinkConfig { memory_limit: limit, connection_memory_limit: connection_limit, ..LinkConfig::default() }))
+    }
+
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::from(([10, 0, 0, 2], port))
+    }
+
+    #[test]
+    fn test_charges_round_up_and_return_on_drop() {
+        let budget = budget(None, None);
+        let mut charge = budget.charge();
+        charge.set(1);
+        assert_eq!((charge.charged(), budget.used()), (GRANULE, GRANULE));
+        // 同一个粒度之内的变化不改合计
+        charge.set(GRANULE);
+        assert_eq!(budget.used(), GRANULE);
+        charge.set(GRANULE + 1);
+        assert_eq!(budget.used(), 2 * GRANULE);
+        charge.set(0);
+        assert_eq!(budget.used(), 0);
+        charge.set(3 * GRANULE);
+        drop(charge);
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[test]
+    fn test_global_limit_refuses_new_accounts() {
+        let budget = budget(Some(3 * CONNECTION_OVERHEAD + GRANULE), None);
+        let accounts: Vec<_> = (1..=3).map(|port| budget.account(peer(port), u32::from(port)).unwrap()).collect();
+        assert_eq!(budget.used(), 3 * CONNECTION_OVERHEAD);
+        assert!(budget.account(peer(4), 4).is_none());
+        assert_eq!(budget.used(), 3 * CONNECTION_OVERHEAD);
+        assert_eq!(accounts[0].room(), Some(GRANULE));
+
+        // 关闭一条连接腾出它的额度
+        drop(accounts);
+        assert_eq!(budget.used(), 0);
+        assert!(budget.account(peer(4), 4).is_some());
+    }
+
+    #[test]
+    fn test_room_is_the_smaller_of_connection_and_global() {
+        let budget = budget(Some(4 * CONNECTION_OVERHEAD), Some(2 * CONNECTION_OVERHEAD));
+        let mut account = budget.account(peer(1), 1).unwrap();
+        assert_eq!(account.room(), Some(CONNECTION_OVERHEAD));
+        account.set(Component::Send, CONNECTION_OVERHEAD - 100);
+        assert_eq!(account.room(), Some(0));
+        assert!(!account.over_limit());
+        // 发送队列清空后放得下，超出连接上限的放不下
+        assert!(account.fits(Component::Send, CONNECTION_OVERHEAD));
+        assert!(!account.fits(Component::Send, CONNECTION_OVERHEAD + 1));
+        account.set(Component::Reassembly, 1);
+        assert!(account.over_limit());
+
+        account.set(Component::Reassembly, 0);
+        account.set(Component::Send, 0);
+        assert_eq!(account.room(), Some(CONNECTION_OVERHEAD));
+
+        // 全局额度更紧时以它为准
+        let mut other = budget.account(peer(2), 2).unwrap();
+        other.set(Component::Reorder, 2 * CONNECTION_OVERHEAD - GRANULE);
+        assert_eq!((account.room(), budget.available()), (Some(GRANULE), Some(GRANULE)));
+        drop(other);
+        assert_eq!(account.room(), Some(CONNECTION_OVERHEAD));
+
+        let detached = MemoryAccount::detached(None);
+        assert_eq!((detached.used(), detached.room()), (CONNECTION_OVERHEAD, None));
+    }
+
+    #[test]
+    fn test_top_consumers_lists_the_largest_first() {
+        let budget = budget(None, None);
+        let mut accounts: Vec<_> = (1..=4).map(|port| budget.account(peer(port), u32::from(port)).unwrap()).collect();
+        accounts[2].set(Component::Reassembly, 10 * GRANULE);
+        accounts[0].set(Component::Reorder, 2 * GRANULE);
+        accounts[0].set(Component::Send, GRANULE);
+        let top = budget.top_consumers(2);
+        assert_eq!(top.iter().map(|consumer| consumer.conn_id).collect::<Vec<_>>(), vec![3, 1]);
+        assert_eq!(top[0].used, CONNECTION_OVERHEAD + 10 * GRANULE);
+        assert_eq!((top[1].get(Component::Reorder), top[1].get(Component::Send), top[1].peer), (2 * GRANULE, GRANULE, peer(1)));
+        assert_eq!(budget.used(), 4 * CONNECTION_OVERHEAD + 13 * GRANULE);
+
+        // 关闭的连接不再列出
+        accounts.remove(2);
+        assert_eq!(budget.top_consumers(8).len(), 3);
+        assert_eq!(budget.top_consumers(1)[0].conn_id, 1);
+    }
+}
@@ -0,0 +1,277 @@
+//! 数据报抓包
+//! `LinkConfig::capture` 设置后，监听器、客户端连接与回显服务器把收发的每个数据报交给其中的 `CaptureSink`。
+//! 数据路径上只取时间戳并复制数据报；`PcapWriter` 把记录经有界队列交给单独的写入任务，队列满时丢弃并计数，
+//! 不会让收发等待磁盘。
+//!
+//! 文件是纳秒时间戳的 pcap，链路类型 LINKTYPE_RAW：每个记录是补出的 IP 与 UDP 头加原始数据报，Wireshark
+//! 可以直接按地址与端口过滤。两端都是 IPv4（含映射地址）时写 IPv4 头，否则写 IPv6 头。写入出错时记录一条
+//! warn 之后不再写入，不影响收发。
+
+use crate::trace::Direction;
+use bytes::Bytes;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::{mpsc, oneshot};
+
+/// pcap 链路类型：没有链路层头的 IP 包
+const LINKTYPE_RAW: u32 = 101;
+
+/// 写入任务的队列长度（记录数）
+const CAPTURE_QUEUE: usize = 4096;
+
+/// 每个记录最长的字节数：IPv6 头、UDP 头与最大的数据报
+const SNAPLEN: u32 = 40 + 8 + 65535;
+
+/// 收发的数据报的去处
+pub trait CaptureSink: Send + Sync + 'static {
+    /// 在收到或发出数据报时同步调用，不应阻塞
+    fn record(&self, direction: Direction, local: SocketAddr, peer: SocketAddr, datagram: &[u8]);
+}
+
+/// `LinkConfig` 中的抓包钩子
+#[derive(Clone)]
+pub struct Capture(Arc<dyn CaptureSink>);
+
+impl Capture {
+    pub fn new(sink: Arc<dyn CaptureSink>) -> Self {
+        Self(sink)
+    }
+
+    /// 绑定到一个本地地址，供单个套接字的收发使用
+    pub fn tap(&self, local: SocketAddr) -> Tap {
+        Tap { capture: self.clone(), local }
+    }
+}
+
+impl fmt::Debug for Capture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Capture")
+    }
+}
+
+/// 绑定了本地地址的抓包钩子
+#[derive(Debug, Clone)]
+pub struct Tap {
+    capture: Capture,
+    local: SocketAddr,
+}
+
+impl Tap {
+    pub fn record(&self, direction: Direction, peer: SocketAddr, datagram: &[u8]) {
+        self.capture.0.record(direction, self.local, peer, datagram);
+    }
+}
+
+enum Command {
+    Record { at: SystemTime, direction: Direction, local: SocketAddr, peer: SocketAddr, datagram: Bytes },
+    Flush(oneshot::Sender<()>),
+}
+
+/// 把数据报写入 pcap 文件的 `CaptureSink`
+#[derive(Debug)]
+pub struct PcapWriter {
+    tx: mpsc::Sender<Command>,
+    dropped: AtomicU64,
+}
+
+impl PcapWriter {
+    /// 创建（覆盖）文件，写入文件头并启动写入任务
+    pub async fn create(path: impl AsRef<Path>) -> io::Result<PcapWriter> {
+        let mut file = BufWriter::new(tokio::fs::File::create(path).await?);
+        file.write_all(&file_header()).await?;
+        file.flush().await?;
+        let (tx, rx) = mpsc::channel(CAPTURE_QUEUE);
+        tokio::spawn(write_loop(file, rx));
+        Ok(PcapWriter { tx, dropped: AtomicU64::new(0) })
+    }
+
+    /// 等待此前交给它的记录全部写入文件
+    pub async fn flush(&self) {
+        let (done, flushed) = oneshot::channel();
+        if self.tx.send(Command::Flush(done)).await.is_ok() {
+            let _ = flushed.await;
+        }
+    }
+
+    /// 队列已满而丢弃的记录数
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl CaptureSink for PcapWriter {
+    fn record(&self, direction: Direction, local: SocketAddr, peer: SocketAddr, datagram: &[u8]) {
+        let command = Command::Record { at: SystemTime::now(), direction, local, peer, datagram: Bytes::copy_from_slice(datagram) };
+        if self.tx.try_send(command).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// 写入任务：队列取空时刷新缓冲，出错后只回应 flush
+async fn write_loop(mut file: BufWriter<tokio::fs::File>, mut rx: mpsc::Receiver<Command>) {
+    let mut failed = false;
+    while let Some(command) = rx.recv().await {
+        let result = match command {
+            Command::Flush(done) if failed => {
+                let _ = done.send(());
+                continue;
+            }
+            _ if failed => continue,
+            Command::Record { at, direction, local, peer, datagram } => {
+                let (src, dst) = match direction {
+                    Direction::Inbound => (peer, local),
+                    Direction::Outbound => (local, peer),
+                };
+                let result = file.write_all(&record(at, src, dst, &datagram)).await;
+                if result.is_ok() && rx.is_empty() { file.flush().await } else { result }
+            }
+            Command::Flush(done) => {
+                let result = file.flush().await;
+                let _ = done.send(());
+                result
+            }
+        };
+        if let Err(e) = result {
+            tracing::warn!(error = %e, "capture file write failed, no further datagrams are recorded");
+            failed = true;
+        }
+    }
+    let _ = file.flush().await;
+}
+
+pub(crate) fn file_header() -> [u8; 24] {
+    let mut header = [0u8; 24];
+    header[0..4].copy_from_slice(&0xa1b2_3c4du32.to_le_bytes());   // 纳秒时间戳
+    header[4..6].copy_from_slice(&2u16.to_le_bytes());
+    header[6..8].copy_from_slice(&4u16.to_le_bytes());
+    header[16..20].copy_from_slice(&SNAPLEN.to_le_bytes());
+    header[20..24].copy_from_slice(&LINKTYPE_RAW.to_le_bytes());
+    header
+}
+
+// 一个 pcap 记录：记录头、IP 头、UDP 头与数据报
+pub(crate) fn record(at: SystemTime, src: SocketAddr, dst: SocketAddr, datagram: &[u8]) -> Vec<u8> {
+    let packet = packet(src, dst, datagram);
+    let since = at.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut out = Vec::with_capacity(16 + packet.len());
+    out.extend_from_slice(&(since.as_secs() as u32).to_le_bytes());
+    out.extend_from_slice(&since.subsec_nanos().to_le_bytes());
+    out.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+    out.extend_from_slice(&packet);
+    out
+}
+
+pub(crate) fn packet(src: SocketAddr, dst: SocketAddr, datagram: &[u8]) -> Vec<u8> {
+    let udp_len = (8 + datagram.len()) as u16;
+    let mut udp = Vec::with_capacity(8 + datagram.len());
+    udp.extend_from_slice(&src.port().to_be_bytes());
+    udp.extend_from_slice(&dst.port().to_be_bytes());
+    udp.extend_from_slice(&udp_len.to_be_bytes());
+    udp.extend_from_slice(&[0, 0]);
+    udp.extend_from_slice(datagram);
+
+    let (src_ip, dst_ip) = (canonical(src.ip()), canonical(dst.ip()));
+    let mut packet = Vec::with_capacity(40 + udp.len());
+    match (src_ip, dst_ip) {
+        (IpAddr::V4(s), IpAddr::V4(d)) => {
+            let udp_sum = checksum(&[&s.octets(), &d.octets(), &[0, 17], &udp_len.to_be_bytes(), &udp]);
+            udp[6..8].copy_from_slice(&nonzero(udp_sum).to_be_bytes());
+            let mut ip = [0u8; 20];
+            ip[0] = 0x45;
+            ip[2..4].copy_from_slice(&(20 + udp_len).to_be_bytes());
+            ip[6] = 0x40;   // 不分片
+            ip[8] = 64;
+            ip[9] = 17;
+            ip[12..16].copy_from_slice(&s.octets());
+            ip[16..20].copy_from_slice(&d.octets());
+            let ip_sum = checksum(&[&ip]);
+            ip[10..12].copy_from_slice(&ip_sum.to_be_bytes());
+            packet.extend_from_slice(&ip);
+        }
+        _ => {
+            let (s, d) = (v6(src_ip), v6(dst_ip));
+            let udp_sum = checksum(&[&s.octets(), &d.octets(), &u32::from(udp_len).to_be_bytes(), &[0, 0, 0, 17], &udp]);
+            udp[6..8].copy_from_slice(&nonzero(udp_sum).to_be_bytes());
+            let mut ip = [0u8; 40];
+            ip[0] = 0x60;
+            ip[4..6].copy_from_slice(&udp_len.to_be_bytes());
+            ip[6] = 17;
+            ip[7] = 64;
+            ip[8..24].copy_from_slice(&s.octets());
+            ip[24..40].copy_from_slice(&d.octets());
+            packet.extend_from_slice(&ip);
+        }
+    }
+    packet.extend_from_slice(&udp);
+    packet
+}
+
+// 双栈套接字上的 IPv4 对端以映射地址出现
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        v4 => v4,
+    }
+}
+
+fn v6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+        IpAddr::V6(v6) => v6,
+    }
+}
+
+// UDP 校验和为 0 表示未计算，算出 0 时写作全 1
+fn nonzero(sum: u16) -> u16 {
+    if sum == 0 { 0xffff } else { sum }
+}
+
+// 互联网校验和：各部分依次拼接后按 16 位反码求和
+fn checksum(parts: &[&[u8]]) -> u16 {
+    let mut sum = 0u32;
+    let mut odd = None;
+    for &byte in parts.iter().flat_map(|part| part.iter()) {
+        match odd.take() {
+            None => odd = Some(byte),
+            Some(high) => sum += u32::from(u16::from_be_bytes([high, byte])),
+        }
+    }
+    if let Some(high) = odd {
+        sum += u32::from(high) << 8;
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ipv4_record_checksums() {
+        let src: SocketAddr = "10.0.0.1:1000".parse().unwrap();
+        let dst: SocketAddr = "[::ffff:10.0.0.2]:2000".parse().unwrap();
+        let packet = packet(src, dst, b"hello");
+        assert_eq!(packet.len(), 20 + 8 + 5);
+        assert_eq!(packet[0], 0x45);
+        assert_eq!(&packet[16..20], &[10, 0, 0, 2]);
+        // 校验和正确时整个头部的反码和为 0
+        assert_eq!(checksum(&[&packet[..20]]), 0);
+        let udp = &packet[20..];
+        assert_eq!(checksum(&[&packet[12..20], &[0, 17], &udp[4..6], udp]), 0);
+
+        let v6 = super::packet("[::1]:1".parse().unwrap(), dst, b"x");
+        assert_eq!((v6.len(), v6[0] >> 4), (40 + 8 + 1, 6));
+        assert_eq!(&v6[24..40], &Ipv6Addr::from([0, 0, 0, 0, 0, 0xffff, 0x0a00, 0x0002]).octets());
+    }
+}
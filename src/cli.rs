@@ -0,0 +1,73 @@
+//! 命令行的共用部分
+//! 服务器、客户端与文件收发各个程序都接受的连接参数选项：`load_config` 读取 `--config` 指定的 TOML 文件与 `LINK_*`
+//! 环境变量，`config_flag` 再以命令行选项覆盖其中的值（见 `config` 模块）。错误信息直接给用户看。
+
+use crate::config::{LinkConfig, MAX_DATAGRAM};
+use crate::cookie::SynCookies;
+use crate::segment::Segment;
+use std::time::Duration;
+
+/// 读取 `--config` 指定的文件（没有时取默认值），再以 `LINK_*` 环境变量覆盖
+pub fn load_config(args: &[String]) -> Result<LinkConfig, String> {
+    let config = match args.iter().position(|arg| arg == "--config") {
+        Some(at) => {
+            let path = args.get(at + 1).ok_or("--config requires a value")?;
+            LinkConfig::from_file(path).map_err(|e| e.to_string())?
+        }
+        None => LinkConfig::default(),
+    };
+    config.with_env().map_err(|e| e.to_string())
+}
+
+/// 把一个连接参数选项写进 `config`，需要值时调用 `value` 取下一个参数；`arg` 不是这类选项时返回 Ok(false)
+pub fn config_flag(config: &mut LinkConfig, arg: &str, value: &mut dyn FnMut() -> Result<String, String>) -> Result<bool, String> {
+    match arg {
+        "--dual-stack" => config.dual_stack = true,
+        "--max-payload" => {
+            let value = value()?;
+            let max = MAX_DATAGRAM - Segment::FIXED_HEADER_LEN;
+            let payload = value
+                .parse::<usize>()
+                .ok()
+                .filter(|payload| (1..=max).contains(payload))
+                .ok_or_else(|| format!("invalid max payload '{}', expected 1..={} bytes", value, max))?;
+            config.mss = payload + Segment::FIXED_HEADER_LEN;
+        }
+        "--recv-buffer" => {
+            let value = value()?;
+            config.recv_buffer = value
+                .parse::<usize>()
+                .ok()
+                .filter(|size| *size >= Segment::FIXED_HEADER_LEN)
+                .ok_or_else(|| format!("invalid receive buffer '{}', expected at least {} bytes", value, Segment::FIXED_HEADER_LEN))?;
+        }
+        "--workers" => {
+            let value = value()?;
+            config.workers = value
+                .parse::<usize>()
+                .ok()
+                .filter(|workers| *workers >= 1)
+                .ok_or_else(|| format!("invalid worker count '{}', expected a positive integer", value))?;
+        }
+        "--syn-cookies" => {
+            config.syn_cookies = match value()?.as_str() {
+                "never" => SynCookies::Never,
+                "overflow" => SynCookies::Overflow,
+                "always" => SynCookies::Always,
+                other => return Err(format!("invalid syn cookie mode '{}', expected never, overflow or always", other)),
+            };
+        }
+        "--metrics-interval" => {
+            let value = value()?;
+            let secs = value.parse::<u64>().map_err(|_| format!("invalid metrics interval '{}', expected whole seconds", value))?;
+            config.metrics_interval = Duration::from_secs(secs);
+        }
+        "--chaos" => config.faults = Some(value()?.parse()?),
+        // 已由 load_config 读取
+        "--config" => {
+            value()?;
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
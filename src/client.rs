@@ -0,0 +1,226 @@
+//! 测试客户端
+//! `run` 连接到目标，按间隔发送同一条消息并等待回应（服务端以 `EchoHandler` 原样发回），逐条报告往返时间。
+//! 重传与确认由连接完成：一条消息在重传次数耗尽后仍未送达时连接失败，`run` 返回该错误；
+//! 回应在 `timeout` 内没有到达时不再发送后续消息，`Summary` 中记为丢失。`src/bin/client.rs` 是它的命令行入口。
+//!
+//! `bench` 测量吞吐量：在 `duration` 内持续发送生成的数据，让发送队列一直是满的（回显的数据读出后丢弃），
+//! 到期时每个连接取一次 `ConnectionStats`，报告中的数字全部来自这些快照。
+//! `ping` 在连接上以 Ping 段探测往返时间（见 `ping::Pinger`），不经过数据通道。
+
+use crate::config::LinkConfig;
+use crate::connection::Connection;
+use crate::error::LinkError;
+use crate::ping::{PingEvent, PingOptions, PingSummary, Pinger};
+use crate::stats::ConnectionStats;
+use bytes::Bytes;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// 发送什么、发送多少次
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientOptions {
+    pub message: Bytes,
+    pub count: usize,
+    pub interval: Duration, // 收到上一条回应后等待多久再发送下一条
+    pub timeout: Duration,  // 等待每条回应的最长时间
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self { message: Bytes::from_static(b"hello"), count: 1, interval: Duration::from_secs(1), timeout: Duration::from_secs(5) }
+    }
+}
+
+/// 一条消息的回应
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reply {
+    pub seq: usize,     // 消息的序号，从 0 开始
+    pub rtt: Duration,  // 从交给连接到收到回应
+    pub message: Bytes,
+}
+
+/// 一次运行的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub sent: usize,
+    pub replied: usize,
+    pub min_rtt: Option<Duration>,
+    pub max_rtt: Option<Duration>,
+    pub total_rtt: Duration,
+}
+
+impl Summary {
+    /// 没有收到回应的消息数
+    pub fn lost(&self) -> usize {
+        self.sent - self.replied
+    }
+
+    pub fn mean_rtt(&self) -> Option<Duration> {
+        (self.replied > 0).then(|| self.total_rtt / self.replied as u32)
+    }
+
+    fn record(&mut self, rtt: Duration) {
+        self.replied += 1;
+        self.total_rtt += rtt;
+        self.min_rtt = Some(self.min_rtt.map_or(rtt, |min| min.min(rtt)));
+        self.max_rtt = Some(self.max_rtt.map_or(rtt, |max| max.max(rtt)));
+    }
+}
+
+/// 连接 `target` 发送 `options.count` 条消息，每收到一条回应调用一次 `on_reply`，最后关闭连接
+pub async fn run(target: SocketAddr, options: &ClientOptions, config: LinkConfig, mut on_reply: impl FnMut(&Reply)) -> Result<Summary, LinkError> {
+    let connection = Connection::connect_with(target, config).await?;
+    let mut summary = Summary::default();
+    for seq in 0..options.count {
+        if seq > 0 {
+            tokio::time::sleep(options.interval).await;
+        }
+        let sent_at = Instant::now();
+        connection.send(options.message.clone()).await?;
+        summary.sent += 1;
+        let message = match tokio::time::timeout(options.timeout, connection.recv()).await {
+            Ok(received) => received?.ok_or(LinkError::Closed)?,
+            Err(_) => {
+                tracing::warn!(seq, "no reply within the timeout");
+                break;
+            }
+        };
+        let reply = Reply { seq, rtt: sent_at.elapsed(), message };
+        summary.record(reply.rtt);
+        on_reply(&reply);
+    }
+    connection.close().await?;
+    Ok(summary)
+}
+
+/// 连接 `target` 发送 `options.count` 个 Ping 探测，每个探测有结果时调用一次 `on_event`，最后关闭连接
+pub async fn ping(target: SocketAddr, options: &PingOptions, config: LinkConfig, on_event: impl FnMut(&PingEvent)) -> Result<PingSummary, LinkError> {
+    let connection = Connection::connect_with(target, config).await?;
+    let summary = Pinger::new(&connection).run(options, on_event).await?;
+    // 探测结果已经确定；丢包严重时关闭可能等到 linger 超时
+    if let Err(e) = connection.close().await {
+        tracing::warn!(error = %e, "close after probing failed");
+    }
+    Ok(summary)
+}
+
+/// 吞吐量测试的参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchOptions {
+    pub duration: Duration,
+    pub payload: usize, // 每条消息的字节数
+    pub streams: usize, // 并发的连接数
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self { duration: Duration::from_secs(10), payload: 1200, streams: 1 }
+    }
+}
+
+/// 吞吐量测试的结果：每个连接在到期时的统计快照
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchReport {
+    pub elapsed: Duration,
+    pub connections: Vec<ConnectionStats>,
+}
+
+impl BenchReport {
+    /// 被对端确认的数据体字节数
+    pub fn bytes_acked(&self) -> u64 {
+        self.connections.iter().map(|stats| stats.sender.bytes_acked).sum()
+    }
+
+    /// 有效吞吐量（字节每秒）：被确认的数据体字节数除以测试时长
+    pub fn goodput(&self) -> f64 {
+        self.bytes_acked() as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    pub fn segments_sent(&self) -> u64 {
+        self.connections.iter().map(|stats| stats.sender.segments_sent).sum()
+    }
+
+    pub fn segments_retransmitted(&self) -> u64 {
+        self.connections.iter().map(|stats| stats.sender.segments_retransmitted).sum()
+    }
+
+    /// 重传段数占首次发出段数的比例
+    pub fn retransmission_rate(&self) -> f64 {
+        self.segments_retransmitted() as f64 / self.segments_sent().max(1) as f64
+    }
+
+    /// 各连接平滑 RTT 的最小值、平均值与最大值；没有任何连接得到 RTT 样本时为 None
+    pub fn srtt(&self) -> Option<(Duration, Duration, Duration)> {
+        let samples: Vec<Duration> = self.connections.iter().filter_map(|stats| stats.srtt).collect();
+        let min = *samples.iter().min()?;
+        let max = *samples.iter().max()?;
+        Some((min, samples.iter().sum::<Duration>() / samples.len() as u32, max))
+    }
+}
+
+/// 对 `target` 打开 `options.streams` 个连接并发地发送 `options.duration`，汇总各连接的统计
+pub async fn bench(target: SocketAddr, options: &BenchOptions, config: LinkConfig) -> Result<BenchReport, LinkError> {
+    let mut connections = Vec::with_capacity(options.streams);
+    for _ in 0..options.streams {
+        connections.push(Connection::connect_with(target, config.clone()).await?);
+    }
+    let payload = Bytes::from((0..options.payload).map(|i| i as u8).collect::<Vec<u8>>());
+    let deadline = Instant::now() + options.duration;
+    let runs: Vec<_> = connections
+        .into_iter()
+        .map(|connection| tokio::spawn(saturate(connection, payload.clone(), deadline)))
+        .collect();
+    let mut report = BenchReport { elapsed: options.duration, connections: Vec::with_capacity(options.streams) };
+    for run in runs {
+        // 任务不会被取消，只有 panic 会让 await 出错
+        let stats = run.await.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))?;
+        report.connections.push(stats);
+    }
+    Ok(report)
+}
+
+// 到期前不停地发送并丢弃回显的数据，到期时取统计快照再关闭连接
+async fn saturate(connection: Connection, payload: Bytes, deadline: Instant) -> Result<ConnectionStats, LinkError> {
+    let sending = async {
+        loop {
+            if let Err(e) = connection.send(payload.clone()).await {
+                return e;
+            }
+        }
+    };
+    let draining = async {
+        loop {
+            match connection.recv().await {
+                Ok(Some(_)) => {}
+                Ok(None) => return LinkError::Closed,
+                Err(e) => return e,
+            }
+        }
+    };
+    // 两者都只在连接失败时结束
+    let failed = tokio::time::timeout_at(deadline, async {
+        tokio::select! {
+            e = sending => e,
+            e = draining => e,
+        }
+    });
+    if let Ok(e) = failed.await {
+        return Err(e);
+    }
+    let stats = connection.stats();
+    // 队列里剩下的数据不计入结果。半关闭期间继续读出回显：停止读取时对端的发送会被本端未读的数据卡住，
+    // 它也就不再读取本端的数据，close 只能等到 linger 超时
+    let drained = async {
+        while connection.recv().await?.is_some() {}
+        Ok(())
+    };
+    let finished = tokio::try_join!(connection.shutdown_write(), drained);
+    if let Err(e) = finished {
+        tracing::debug!(error = %e, "shutdown after the benchmark failed");
+    }
+    if let Err(e) = connection.close().await {
+        tracing::debug!(error = %e, "close after the benchmark failed");
+    }
+    Ok(stats)
+}
@@ -0,0 +1,415 @@
+//! 单套接字的客户端端点
+//! `Connection::connect` 为每条连接绑定一个套接字；要连接很多服务器的进程会因此耗尽端口与文件描述符，
+//! 在一些 NAT 后面还会为每条连接占用一个映射。`ClientEndpoint` 只绑定一个本地套接字，`connect` 从它发起任意多条连接，
+//! 结构与监听器的服务端相同：分发任务是唯一调用 `recv_from` 的任务，按来源地址与段头中的连接 ID 把数据报原样
+//! 交给对应连接的驱动任务；所有连接发出的数据报经同一个发送任务合并后发出（见 `listener::send_loop`），
+//! 收发共用一个缓冲池，每次唤醒后同样取走已经排队的数据报（见 `listener` 模块）。每条连接仍有自己的驱动任务与定时器，一条连接的关闭、失败或被丢弃不影响同一端点上的其他连接。
+//!
+//! 握手期间连接还没有 ID：SYN-ACK 与 Retry 按它确认的 ISN 交给对应的握手，之后携带 SYN-ACK 分配的连接 ID 的段
+//! 在握手登记为连接之前也交给它。不带连接 ID 的 Rst（拒绝握手）交给发往这个对端的所有握手；
+//! 已建立的连接不接受它，它们靠超时发现对端已不存在，一条连接收到的 Rst 不会中止同一对端上的其他连接。
+//! 不属于任何连接与握手的数据报丢弃。连接 ID 轮换后（见 `rotation` 模块）新的 ID 随即登记，
+//! 旧 ID 在 `LinkConfig::conn_id_grace` 内仍能找到连接。
+//!
+//! 端点句柄被丢弃后，分发任务在最后一条连接结束时退出，套接字随之关闭；`shutdown` 立即中止所有连接（向对端发出 Rst）
+//! 并关闭端点，进行中的 `connect` 以 `Closed` 失败。多条连接连到同一个监听器时，监听器以连接 ID 区分同一地址上的它们。
+//! 端点不接受入站连接，也不支持 0-RTT 数据与同时打开。
+
+use crate::batch::Batcher;
+use crate::capture::Tap;
+use crate::config::LinkConfig;
+use crate::connection::{self, Connection, HandshakePath, Notice, Outgoing, Outlet, Reaper, Shared, INBOUND_QUEUE};
+use crate::error::{self, LinkError};
+use crate::listener;
+use crate::metrics::{Metrics, MetricsSnapshot};
+use crate::pool::{BufferPool, RecvArena};
+use crate::segment::{self, Segment, SegmentType};
+use crate::seq::SeqNum;
+#[cfg(feature = "tokio")]
+use crate::socket;
+use crate::socket::LinkSocket;
+use crate::trace::{self, Direction, Role};
+use crate::transport::Transport;
+use bytes::Bytes;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::time::Instant;
+#[cfg(feature = "tokio")]
+use tokio::net::ToSocketAddrs;
+use tokio::sync::{mpsc, watch};
+use tracing::Instrument;
+
+/// 经一个本地套接字发起多条连接的客户端端点
+#[derive(Debug)]
+pub struct ClientEndpoint {
+    socket: Arc<LinkSocket>,
+    local: SocketAddr,
+    config: LinkConfig,
+    commands: mpsc::UnboundedSender<Command>,
+    reaper: Reaper,             // 交给每条连接，驱动任务退出时经它通知分发任务
+    out: mpsc::Sender<Outgoing>,
+    pool: Arc<BufferPool>,
+    metrics: Arc<Metrics>,
+    tap: Option<Tap>,           // 握手段直接写套接字，在这里记录
+    live: Arc<AtomicUsize>,     // 分发任务登记着的连接数
+    closed: watch::Receiver<()>,    // 分发任务退出时发送端被丢弃
+}
+
+// 句柄交给分发任务的请求
+#[derive(Debug)]
+enum Command {
+    // 开始一次以 `isn` 为 ISN 的握手，对端的回应经 `inbox` 转交
+    Expect { remote: SocketAddr, isn: SeqNum, inbox: mpsc::Sender<Bytes> },
+    // 以 `isn` 开始的握手已完成：登记连接，此后它的数据报直接交给它
+    Register { shared: Arc<Shared>, isn: SeqNum },
+    Shutdown,
+}
+
+impl ClientEndpoint {
+    /// 以默认参数绑定本地地址
+    #[cfg(feature = "tokio")]
+    pub async fn bind(local: impl ToSocketAddrs) -> Result<ClientEndpoint, LinkError> {
+        Self::bind_with(local, LinkConfig::default()).await
+    }
+
+    /// 按 `config` 的套接字选项绑定本地地址；参数未通过 `LinkConfig::validate` 时返回 `LinkError::Config`
+    #[cfg(feature = "tokio")]
+    pub async fn bind_with(local: impl ToSocketAddrs, config: LinkConfig) -> Result<ClientEndpoint, LinkError> {
+        config.validate()?;
+        let socket = socket::bind(local, &config).await?;
+        Self::start(LinkSocket::new(socket, &config), config)
+    }
+
+    /// 在调用方提供的传输（如 `transport::MemoryTransport`）上发起连接
+    pub fn with_transport(transport: impl Transport, config: LinkConfig) -> Result<ClientEndpoint, LinkError> {
+        config.validate()?;
+        Self::start(LinkSocket::new(transport, &config), config)
+    }
+
+    fn start(socket: LinkSocket, config: LinkConfig) -> Result<ClientEndpoint, LinkError> {
+        let socket = Arc::new(socket);
+        let local = socket.local_addr()?;
+        let tap = |config: &LinkConfig| config.capture.as_ref().map(|capture| capture.tap(local));
+        let metrics = Arc::new(Metrics::default());
+        let pool = Arc::new(BufferPool::new(&config));
+        // 发送任务在所有连接与句柄都放下队列后自行退出
+        let (out, outgoing) = mpsc::channel(listener::OUTBOUND_QUEUE);
+        let batcher = Batcher::new(config.mss, config.batch_window);
+        tokio::spawn(listener::send_loop(socket.clone(), outgoing, batcher, metrics.clone(), tap(&config), pool.clone()));
+        let (commands, requests) = mpsc::unbounded_channel();
+        let (reaper, reaped) = mpsc::unbounded_channel();
+        let (done, closed) = watch::channel(());
+        let live = Arc::new(AtomicUsize::new(0));
+        let demux = Demux {
+            socket: socket.clone(),
+            config: config.clone(),
+            tap: tap(&config),
+            metrics: metrics.clone(),
+            requests,
+            reaped,
+            connections: HashMap::new(),
+            retiring: VecDeque::new(),
+            pending: HashMap::new(),
+            live: live.clone(),
+            orphaned: false,
+            _done: done,
+        };
+        tokio::spawn(demux.run().instrument(tracing::info_span!("client_endpoint", %local)));
+        let tap = tap(&config);
+        Ok(ClientEndpoint { socket, local, config, commands, reaper, out, pool, metrics, tap, live, closed })
+    }
+
+    /// 从端点的套接字连接到 `remote`。握手的重传与超时同 `Connection::connect_over`；
+    /// 端点已关闭时返回 `Closed`
+    pub async fn connect(&self, remote: SocketAddr) -> Result<Connection, LinkError> {
+        let span = trace::connection_span(Role::Client, remote);
+        let start = tokio::time::Instant::now();
+        let attempts = AtomicU32::new(0);
+        let mut path = Relayed { socket: &self.socket, commands: &self.commands, inbox: None, isn: None };
+        let config = &self.config;
+        let handshake = async {
+            match connection::handshake(&mut path, self.tap.as_ref(), remote, config, None, &attempts, start).await {
+                Err(LinkError::Protocol(_)) => connection::handshake(&mut path, self.tap.as_ref(), remote, config, None, &attempts, start).await,
+                result => result,
+            }
+        };
+        // 总超时到期时无论握手进行到哪一步都放弃
+        let handshake = tokio::time::timeout_at(start + config.handshake_timeout, handshake.instrument(trace::handshake_span(&span)))
+            .await
+            .unwrap_or_else(|_| Err(LinkError::ConnectTimeout { attempts: attempts.load(Ordering::Relaxed), elapsed: start.elapsed() }))?;
+        let (Some(mut inbox), Some(isn)) = (path.inbox.take(), path.isn) else {
+            return Err(LinkError::Closed);
+        };
+        let outlet = Outlet::Channel { tx: self.out.clone(), local: self.local, pool: self.pool.clone() };
+        let connection = Connection::establish(outlet, remote, config, handshake, span, Some(self.reaper.clone()), Some(self.metrics.clone()));
+        let shared = connection.shared().clone();
+        // 登记之前没有数据报交给连接、调用方也还拿不到它，驱动任务不会先于登记退出
+        self.commands.send(Command::Register { shared: shared.clone(), isn }).map_err(|_| LinkError::Closed)?;
+        // 分发任务登记连接时放下这次握手的队列：登记之前转交给握手的数据报交给连接
+        while let Some(datagram) = inbox.recv().await {
+            shared.deliver(datagram);
+        }
+        // 登记之前端点就关闭了
+        if self.commands.is_closed() {
+            return Err(LinkError::Closed);
+        }
+        Ok(connection)
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, LinkError> {
+        Ok(self.local)
+    }
+
+    /// 端点上还在运行的连接数
+    pub fn connections(&self) -> usize {
+        self.live.load(Ordering::Relaxed)
+    }
+
+    /// 端点及其连接的数据路径计数器
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// 立即关闭端点：中止所有连接（驱动任务向对端发出 Rst），进行中的 `connect` 以 `Closed` 失败，等分发任务退出后返回
+    pub async fn shutdown(&self) {
+        let _ = self.commands.send(Command::Shutdown);
+        let mut closed = self.closed.clone();
+        while closed.changed().await.is_ok() {}
+    }
+}
+
+// 端点上一次握手的收发：握手段直接写共用的套接字，对端的回应由分发任务转交
+struct Relayed<'a> {
+    socket: &'a LinkSocket,
+    commands: &'a mpsc::UnboundedSender<Command>,
+    inbox: Option<mpsc::Receiver<Bytes>>,
+    isn: Option<SeqNum>,
+}
+
+impl HandshakePath for Relayed<'_> {
+    fn socket(&self) -> &LinkSocket {
+        self.socket
+    }
+
+    // 重新握手时换一个队列：前一次握手的登记随旧队列被丢弃而失效
+    fn expect(&mut self, remote: SocketAddr, isn: SeqNum) -> Result<(), LinkError> {
+        let (inbox, rx) = mpsc::channel(INBOUND_QUEUE);
+        self.commands.send(Command::Expect { remote, isn, inbox }).map_err(|_| LinkError::Closed)?;
+        self.inbox = Some(rx);
+        self.isn = Some(isn);
+        Ok(())
+    }
+
+    // 来源、抓包与截断已由分发任务处理；队列关闭说明端点已关闭
+    async fn recv(&mut self, _: SocketAddr, _: Option<&Tap>) -> Result<Option<Bytes>, LinkError> {
+        let Some(inbox) = self.inbox.as_mut() else {
+            return Err(LinkError::Closed);
+        };
+        inbox.recv().await.map(Some).ok_or(LinkError::Closed)
+    }
+}
+
+// 进行中的握手
+#[derive(Debug)]
+struct Pending {
+    isn: SeqNum,
+    conn_id: u32,                   // SYN-ACK 分配的连接 ID，收到之前为 0
+    inbox: mpsc::Sender<Bytes>,
+}
+
+// 分发任务：端点上唯一读套接字的任务，连接表与握手表只在这里读写
+struct Demux {
+    socket: Arc<LinkSocket>,
+    config: LinkConfig,
+    tap: Option<Tap>,
+    metrics: Arc<Metrics>,
+    requests: mpsc::UnboundedReceiver<Command>,
+    reaped: mpsc::UnboundedReceiver<Notice>,
+    connections: HashMap<(SocketAddr, u32), Arc<Shared>>,  // 按对端地址与本端的连接 ID 登记
+    retiring: VecDeque<((SocketAddr, u32), Instant)>,      // 轮换下来的连接 ID 与它移出连接表的时间，按时间排列
+    pending: HashMap<SocketAddr, Vec<Pending>>,
+    live: Arc<AtomicUsize>,
+    orphaned: bool,                 // 句柄已被丢弃，不会再有新的握手
+    _done: watch::Sender<()>,
+}
+
+impl Demux {
+    async fn run(mut self) {
+        let mut arena = RecvArena::new(self.config.recv_buffer);
+        let mut handled = 0;    // 上次让出执行权以来处理的数据报数
+        loop {
+            // 先处理句柄的请求与连接的通知：登记总在同一条连接的 Reaped 之前
+            tokio::select! {
+                biased;
+                command = self.requests.recv(), if !self.orphaned => match command {
+                    Some(Command::Expect { remote, isn, inbox }) => {
+                        self.pending.entry(remote).or_default().push(Pending { isn, conn_id: 0, inbox });
+                    }
+                    Some(Command::Register { shared, isn }) => self.register(shared, isn),
+                    Some(Command::Shutdown) => {
+                        self.close(LinkError::Closed, true);
+                        return;
+                    }
+                    None => self.orphaned = true,
+                },
+                // 发送端由句柄与各连接持有；句柄被丢弃且没有连接时下面的检查让任务退出
+                Some(notice) = self.reaped.recv() => match notice {
+                    Notice::Reaped(shared) => self.reap(&shared),
+                    Notice::Rotated { shared, old, new } => self.rekey(shared, old, new),
+                    // 端点不接续导出的连接
+                    Notice::Adopt { .. } => {}
+                },
+                received = self.socket.recv_from(arena.space()) => {
+                    // ICMP 不可达报告的接收错误忽略；套接字失效时所有连接以这个错误失败
+                    let (len, from) = match received {
+                        Ok(received) => received,
+                        Err(e) if error::is_fatal(&e) => {
+                            tracing::error!(error = %e, "client endpoint socket failed");
+                            self.close(e.into(), false);
+                            return;
+                        }
+                        Err(_) => continue,
+                    };
+                    let mut drained = 1;
+                    self.receive(arena.split(len), from);
+                    // 接着取走已经排队的数据报，与监听器相同
+                    while handled + drained < self.config.recv_batch {
+                        let Ok((len, from)) = self.socket.try_recv_from(arena.space()) else {
+                            break;
+                        };
+                        drained += 1;
+                        self.receive(arena.split(len), from);
+                    }
+                    handled += drained;
+                    self.metrics.on_drained(drained);
+                }
+            }
+            self.pending.retain(|_, handshakes| {
+                handshakes.retain(|pending| !pending.inbox.is_closed());
+                !handshakes.is_empty()
+            });
+            self.retire_conn_ids(connection::now());
+            if self.orphaned && self.connections.is_empty() && self.pending.is_empty() {
+                return;
+            }
+            if handled >= self.config.recv_batch {
+                handled = 0;
+                tokio::task::yield_now().await;
+            }
+        }
+    }
+
+    fn receive(&mut self, datagram: Bytes, from: SocketAddr) {
+        self.metrics.on_received(datagram.len());
+        if let Some(tap) = &self.tap {
+            tap.record(Direction::Inbound, from, &datagram);
+        }
+        if segment::is_truncated(&datagram) {
+            self.metrics.on_truncated();
+            tracing::warn!(peer = %from, len = datagram.len(), "dropping truncated datagram");
+        } else {
+            self.route(datagram, from);
+        }
+    }
+
+    // 数据报原样交给连接或握手；一个数据报里的段属于同一条连接，只看第一个段
+    fn route(&mut self, datagram: Bytes, from: SocketAddr) {
+        let conn_id = segment::parse_header(&datagram).map_or(0, |header| header.conn_id);
+        if let Some(shared) = self.connections.get(&(from, conn_id)) {
+            let delivered = shared.deliver(datagram);
+            self.metrics.on_routed(delivered);
+            return;
+        }
+        let Some(handshakes) = self.pending.get(&from) else {
+            tracing::trace!(peer = %from, conn_id, "dropping datagram for no connection");
+            return;
+        };
+        // 握手已收到 SYN-ACK、还没登记为连接
+        if conn_id != 0
+            && let Some(pending) = handshakes.iter().find(|pending| pending.conn_id == conn_id)
+        {
+            let _ = pending.inbox.try_send(datagram);
+            return;
+        }
+        let Ok(Some(segment)) = Segment::decode_bytes(&mut datagram.clone()) else {
+            return;
+        };
+        if trace::enabled() {
+            trace::segment(Direction::Inbound, &segment, from);
+        }
+        let handshakes = self.pending.get_mut(&from).expect("checked above");
+        match segment.segment_type() {
+            // 拒绝握手的 Rst 不知道是哪一次握手，交给发往这个对端的所有握手
+            SegmentType::Rst if segment.conn_id() == 0 => {
+                for pending in handshakes.iter() {
+                    let _ = pending.inbox.try_send(datagram.clone());
+                }
+            }
+            // SYN-ACK 与 Retry 确认 SYN 的 ISN
+            SegmentType::Syn | SegmentType::Retry => {
+                if let Some(pending) = handshakes.iter_mut().find(|pending| pending.conn_id == 0 && pending.isn == segment.ack()) {
+                    pending.conn_id = segment.conn_id();
+                    let _ = pending.inbox.try_send(datagram);
+                }
+            }
+            _ => tracing::trace!(peer = %from, conn_id, "dropping datagram for no connection"),
+        }
+    }
+
+    // 握手完成：放下它的队列，连接此后按 ID 找到
+    fn register(&mut self, shared: Arc<Shared>, isn: SeqNum) {
+        let remote = shared.peer_addr();
+        if let Some(handshakes) = self.pending.get_mut(&remote) {
+            handshakes.retain(|pending| pending.isn != isn);
+        }
+        self.connections.insert((remote, shared.conn_id()), shared);
+        self.live.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // 驱动任务已退出：移出它的所有 ID（包括宽限期内的旧 ID）
+    fn reap(&mut self, shared: &Arc<Shared>) {
+        let before = self.connections.len();
+        self.connections.retain(|_, other| !Arc::ptr_eq(other, shared));
+        if self.connections.len() < before {
+            self.live.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    fn rekey(&mut self, shared: Arc<Shared>, old: u32, new: u32) {
+        let remote = shared.peer_addr();
+        match self.connections.get(&(remote, new)) {
+            Some(other) if !Arc::ptr_eq(other, &shared) => tracing::debug!(old, new, "new connection id is taken, not registering it"),
+            _ => {
+                self.connections.insert((remote, new), shared);
+            }
+        }
+        self.retiring.push_back(((remote, old), connection::now() + self.config.conn_id_grace));
+    }
+
+    // 宽限期已过的旧连接 ID 移出连接表；连接又换回了这个 ID 时保留
+    fn retire_conn_ids(&mut self, now: Instant) {
+        while let Some(&(key, until)) = self.retiring.front()
+            && until <= now
+        {
+            self.retiring.pop_front();
+            if self.connections.get(&key).is_some_and(|shared| shared.conn_id() != key.1) {
+                self.connections.remove(&key);
+            }
+        }
+    }
+
+    // 端点关闭：进行中的握手随队列关闭以 `Closed` 失败，连接被中止（`reset` 向对端发出 Rst）或以套接字的错误失败
+    fn close(&mut self, error: LinkError, reset: bool) {
+        self.requests.close();
+        self.pending.clear();
+        for (_, shared) in self.connections.drain() {
+            match reset {
+                true => shared.reset(error.clone()),
+                false => shared.fail(error.clone()),
+            }
+        }
+        self.live.store(0, Ordering::Relaxed);
+    }
+}
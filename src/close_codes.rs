@@ -0,0 +1,172 @@
+//! 关闭码
+//! 连接以 Rst 结束或被拒绝时，原因以一个 16 位的码随 Rst 的错误码选项发给对端（见 `options` 模块），
+//! 复位连接、拒绝握手、按策略拒绝与拒绝重新同步共用这一张表，不各自定义数字。码分为两个区：
+//! `0..APPLICATION` 是传输层的码，由本 crate 定义，下面列出的是已分配的；`APPLICATION` 及以上留给应用，
+//! 本 crate 原样收发，不解释（见 `Connection::reset`）。
+//!
+//! 线上的错误码字段小于 256 时占 1 字节，与只认识 1 字节错误码的旧版本兼容，传输层的码都在这个范围内；
+//! 更大的码占 2 字节（大端），旧版本把携带它的段当作格式错误丢弃，等同于 Rst 丢失，它们靠超时发现连接已结束。
+//! 收到的码转换为对应的 `LinkError` 变体交给应用：本版本不认识的传输层码成为 `LinkError::Aborted`，
+//! 应用的码成为 `LinkError::Application`，两者都保留原始的码，`LinkError::close_code` 读出。
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// 连接结束或被拒绝的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CloseCode(pub u16);
+
+impl CloseCode {
+    /// 没有错误
+    pub const NORMAL: CloseCode = CloseCode(0);
+    /// 对端违反了协议（如 SYN-ACK 确认了错误的序列号、引用了从未打开的流）
+    pub const PROTOCOL_ERROR: CloseCode = CloseCode(1);
+    /// 服务器的连接数已达上限（`LinkConfig::max_connections`、`per_ip_limit`），拒绝这次握手
+    pub const SERVER_BUSY: CloseCode = CloseCode(2);
+    /// 监听器的 `ParamPolicy` 拒绝了客户端在握手中提出的参数
+    pub const PARAMETER_ERROR: CloseCode = CloseCode(3);
+    /// 重新同步请求的起点早于发送方仍保留的最早数据，随 resync-head 回应，不复位连接
+    pub const RESYNC_REFUSED: CloseCode = CloseCode(4);
+    /// 双方的协议版本不兼容
+    pub const VERSION_MISMATCH: CloseCode = CloseCode(5);
+    /// 握手未通过认证：监听器配置了预共享密钥，而 SYN 没有签名
+    pub const AUTH_FAILED: CloseCode = CloseCode(6);
+    /// 数据段超过了本端在握手中通告的 MSS
+    pub const MSS_VIOLATION: CloseCode = CloseCode(7);
+    /// 连续多个保活探测未得到回应，本端判定对端失联
+    pub const KEEPALIVE_TIMEOUT: CloseCode = CloseCode(8);
+    /// 应用区的第一个码
+    pub const APPLICATION: CloseCode = CloseCode(0x4000);
+
+    /// 已分配的传输层的码
+    pub const NAMED: [CloseCode; 9] = [
+        CloseCode::NORMAL,
+        CloseCode::PROTOCOL_ERROR,
+        CloseCode::SERVER_BUSY,
+        CloseCode::PARAMETER_ERROR,
+        CloseCode::RESYNC_REFUSED,
+        CloseCode::VERSION_MISMATCH,
+        CloseCode::AUTH_FAILED,
+        CloseCode::MSS_VIOLATION,
+        CloseCode::KEEPALIVE_TIMEOUT,
+    ];
+
+    /// 应用区的第 `n` 个码；超出应用区时为 None
+    pub fn application(n: u16) -> Option<CloseCode> {
+        CloseCode::APPLICATION.0.checked_add(n).map(CloseCode)
+    }
+
+    pub fn is_application(self) -> bool {
+        self >= CloseCode::APPLICATION
+    }
+
+    pub fn is_transport(self) -> bool {
+        !self.is_application()
+    }
+
+    /// 已分配的传输层的码的符号名
+    pub fn name(self) -> Option<&'static str> {
+        let name = match self {
+            CloseCode::NORMAL => "NORMAL",
+            CloseCode::PROTOCOL_ERROR => "PROTOCOL_ERROR",
+            CloseCode::SERVER_BUSY => "SERVER_BUSY",
+            CloseCode::PARAMETER_ERROR => "PARAMETER_ERROR",
+            CloseCode::RESYNC_REFUSED => "RESYNC_REFUSED",
+            CloseCode::VERSION_MISMATCH => "VERSION_MISMATCH",
+            CloseCode::AUTH_FAILED => "AUTH_FAILED",
+            CloseCode::MSS_VIOLATION => "MSS_VIOLATION",
+            CloseCode::KEEPALIVE_TIMEOUT => "KEEPALIVE_TIMEOUT",
+            _ => return None,
+        };
+        Some(name)
+    }
+
+    /// 线上的错误码字段：小于 256 时 1 字节，否则 2 字节大端
+    pub fn to_wire(self) -> Vec<u8> {
+        match u8::try_from(self.0) {
+            Ok(code) => vec![code],
+            Err(_) => self.0.to_be_bytes().to_vec(),
+        }
+    }
+
+    /// 解析错误码字段；长度不是 1 或 2 字节时为 None
+    pub fn from_wire(value: &[u8]) -> Option<CloseCode> {
+        match *value {
+            [code] => Some(CloseCode(u16::from(code))),
+            [high, low] => Some(CloseCode(u16::from_be_bytes([high, low]))),
+            _ => None,
+        }
+    }
+}
+
+impl From<u16> for CloseCode {
+    fn from(code: u16) -> Self {
+        CloseCode(code)
+    }
+}
+
+impl From<CloseCode> for u16 {
+    fn from(code: CloseCode) -> Self {
+        code.0
+    }
+}
+
+/// 已分配的码显示符号名，应用的码显示为 `APPLICATION(0x4001)`，其余为 `UNKNOWN(0x0009)`
+impl fmt::Display for CloseCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None if self.is_application() => write!(f, "APPLICATION({:#06x})", self.0),
+            None => write!(f, "UNKNOWN({:#06x})", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{Options, SegmentOption};
+
+    // 作为 Rst 的错误码选项编码后再解码
+    fn over_the_wire(code: CloseCode) -> CloseCode {
+        let options = Options::new().with(SegmentOption::Error(code)).unwrap();
+        Options::decode(options.as_bytes()).unwrap().error().unwrap()
+    }
+
+    #[test]
+    fn test_named_codes_roundtrip_over_the_wire() {
+        for code in CloseCode::NAMED {
+            assert!(code.is_transport() && code.name().is_some());
+            assert_eq!(code.to_wire(), [code.0 as u8]);
+            assert_eq!(over_the_wire(code), code);
+            assert_eq!(code.to_string(), code.name().unwrap());
+        }
+        assert_eq!(CloseCode::MSS_VIOLATION.to_string(), "MSS_VIOLATION");
+    }
+
+    #[test]
+    fn test_unknown_and_application_codes_are_kept() {
+        // 本版本不认识的传输层码原样保留
+        let unknown = CloseCode(0x0123);
+        assert!(unknown.is_transport() && unknown.name().is_none());
+        assert_eq!(unknown.to_wire(), [0x01, 0x23]);
+        assert_eq!(over_the_wire(unknown), unknown);
+        assert_eq!(over_the_wire(CloseCode(200)), CloseCode(200));
+        assert_eq!(unknown.to_string(), "UNKNOWN(0x0123)");
+
+        let app = CloseCode::application(7).unwrap();
+        assert_eq!(app, CloseCode(0x4007));
+        assert!(app.is_application() && !app.is_transport());
+        assert_eq!(over_the_wire(app), app);
+        assert_eq!(over_the_wire(CloseCode(u16::MAX)), CloseCode(u16::MAX));
+        assert_eq!(app.to_string(), "APPLICATION(0x4007)");
+        assert_eq!(CloseCode::application(0xC000), None);
+        assert_eq!(CloseCode::from_wire(&[]), None);
+        assert_eq!(CloseCode::from_wire(&[1, 2, 3]), None);
+    }
+}
@@ -0,0 +1,642 @@
+//! 连接参数
+//! 所有可调参数集中在 `LinkConfig`，各组件从这里读取默认值。
+//!
+//! 参数也可以从 TOML 文件（`from_file`）与 `LINK_*` 环境变量（`with_env`）读取，后者覆盖前者：
+//! 键名即字段名，环境变量为大写加前缀（`min_rto` 对应 `LINK_MIN_RTO`）；时长写作 `200ms` 或 `10s`，
+//! 拥塞控制写作 `reno` 或 `nocc:<段数>`，校验算法写作逗号分隔的 `crc32c`、`xxhash32` 或 `none`，故障注入写作 `fault` 模块的描述文本，预共享密钥（`crypto` 特性）写作 64 个十六进制字符。`capture` 与 `observer` 只能在代码中设置。
+//! 读取后与监听器、客户端连接创建时都经过 `validate`，不合理的组合返回 `ConfigError`。
+
+use crate::ack;
+use crate::acl::Cidr;
+use crate::budget;
+use crate::capture::Capture;
+use crate::checksum::ChecksumAlgorithm;
+use crate::congestion::CongestionAlgorithm;
+use crate::cookie::SynCookies;
+#[cfg(feature = "crypto")]
+use crate::crypto::PresharedKey;
+use crate::fault::FaultConfig;
+use crate::observer::Observer;
+use crate::segment::Segment;
+use crate::timer;
+use crate::watermarks::Watermarks;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 单个 UDP 数据报的最大数据体（IPv4）
+pub const MAX_DATAGRAM: usize = 65507;
+
+/// `mss` 的默认值；对端在握手中没有通告 MSS 时，发往它的数据报也不超过这个保守的大小
+pub const DEFAULT_MSS: usize = 1200;
+
+/// 环境变量的前缀
+const ENV_PREFIX: &str = "LINK_";
+
+/// 读取或校验参数失败
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    Io { path: PathBuf, message: String },  // 无法读取配置文件
+    Syntax(String),                         // 不是合法的 TOML
+    UnknownKey(String),                     // 没有这个参数（含拼写错误）
+    InvalidValue { key: String, value: String, expected: &'static str },
+    Invalid(String),                        // 各项单独合法，组合起来不合理
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, message } => write!(f, "cannot read config file {}: {}", path.display(), message),
+            ConfigError::Syntax(message) => write!(f, "invalid config file: {}", message),
+            ConfigError::UnknownKey(key) => write!(f, "unknown config key '{}'", key),
+            ConfigError::InvalidValue { key, value, expected } => write!(f, "invalid value '{}' for {}, expected {}", value, key, expected),
+            ConfigError::Invalid(reason) => write!(f, "invalid config: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// `set` 拒绝一个键值的原因，由调用方按来源（文件的键或环境变量名）组成 `ConfigError`
+enum Rejected {
+    Unknown,
+    Expected(&'static str),
+}
+
+/// 连接级参数
+#[derive(Debug, Clone)]
+pub struct LinkConfig {
+    pub send_window: usize,         // 本地配置的最大在途段数
+    pub send_buffer: usize,         // 每个流的发送队列上限（字节）：等待窗口与已发送未确认的数据之和
+    pub send_watermarks: Option<Watermarks>,    // 设置时流 0 的发送队列（字节）越过这对水位时通知订阅者（见 `watermarks` 模块）
+    pub max_message: usize,         // `Connection::send_msg` 接受的最大消息（字节），更大的消息在发送时以 `MessageTooLarge` 拒绝
+    pub reassembly_budget: usize,   // 一个连接的各个流上攒着、尚未收齐的分片消息合计的字节上限，超出时丢弃最早开始的一条；比它大的消息收不齐
+    pub max_partial_messages: usize,    // 一个连接上同时尚未收齐的分片消息条数上限（每个流至多一条），超出时同样丢弃最早开始的
+    pub reassembly_timeout: Duration,   // 分片消息从第一片被取出起收齐的期限，过期的被丢弃
+    pub initial_cwnd: usize,        // 初始拥塞窗口（段数）
+    pub congestion: CongestionAlgorithm,  // 拥塞控制算法
+    pub checksums: Vec<ChecksumAlgorithm>, // 本端接受的校验算法，按偏好排列，握手时与对端协商出一个（见 `checksum` 模块）
+    pub recv_window: usize,         // 接收端重排缓冲区容量（段数），即通告窗口上限
+    pub recv_watermarks: Option<Watermarks>,    // 设置时流 0 的重排缓冲区（段数）越过这对水位时通知订阅者
+    pub min_rto: Duration,          // RTO 下限
+    pub max_rto: Duration,          // RTO（含退避）上限
+    pub max_retries: u32,           // 连续超时重传上限，超过后判定对端不可达
+    pub max_ack_delay: Duration,    // 延迟确认的最长等待时间
+    pub ack_every_n_segments: usize,    // 攒够多少个按序段后不等延迟定时器立即确认（见 `ack` 模块）
+    pub immediate_ack_on_gap: bool, // 乱序段与补齐空洞的段立即确认（供快速重传与 SACK 使用），关闭时同样按延迟确认
+    pub sack: bool,                 // 有空洞时确认附带 SACK 区间，握手时与对端协商，任一方关闭即不附带（见 `params` 模块）
+    pub timer_granularity: Duration, // 连接定时器时间轮的刻度（见 `timer` 模块），只影响定时器的分布，不改变到期时间
+    pub mss: usize,                 // 单个数据报的最大字节数，小写入合并到该大小后立即发送；路径 MTU 探测的起点。不超过对端在握手中通告的 MSS
+    pub max_mss: Option<usize>,     // 设置时建立后探测路径 MTU，有效 MSS 在 mss 与它之间（见 `pmtu` 模块），套接字设置 DF
+    pub pmtu_interval: Duration,    // 探测结束后多久重新确认路径 MTU
+    pub recv_buffer: usize,         // 单次接收的缓冲区大小（字节），放不下的数据报被截断，计数后丢弃；握手中作为 MSS 通告给对端
+    pub buffer_pool: usize,         // 每个套接字的数据报缓冲池最多保留的字节数（见 `pool` 模块），为 0 时每个数据报单独分配
+    pub scratch_high_water: usize,  // 每个任务的编码暂存缓冲超过这个容量后，闲置一段时间即释放回初始大小（见 `pool::Scratch`）
+    pub recv_batch: usize,          // 监听器的分发任务与连接的驱动、读取任务连续处理这么多个数据报后让出执行权，不饿死同一运行时上的其他任务
+    pub batch_window: Duration,     // 监听器合并发往同一对端的数据报时最多等待多久（见 `batch` 模块），为 0 时只合并已积压的
+    pub pacing: bool,               // 按 cwnd/SRTT 算出的速率逐个放出新数据段（见 `pacing` 模块），关闭时窗口打开即整窗发出
+    pub pacing_gain: f64,           // 发送速率相对 cwnd/SRTT 的倍数
+    pub dual_stack: bool,           // 绑定 IPv6 地址的套接字同时接收 IPv4 对端（IPV6_V6ONLY=false）
+    pub so_rcvbuf: Option<usize>,   // 设置时请求的套接字接收缓冲区（SO_RCVBUF，字节），内核可能调整（见 `socket` 模块）
+    pub so_sndbuf: Option<usize>,   // 设置时请求的套接字发送缓冲区（SO_SNDBUF，字节）
+    pub dscp: Option<u8>,           // 设置时发出的数据报带这个 DSCP（0..=63，即 IP_TOS/IPV6_TCLASS 的高 6 位）
+    pub metrics_interval: Duration, // 服务器输出一行指标摘要的间隔，为 0 时不输出
+    pub decode_error_warn_rate: f64,    // 某一类解码错误的速率（次/秒）超过它时指标摘要以 warn 级别输出并列出这些种类，为 0 时从不告警
+    pub reject_log: usize,          // 监听器保留最近多少个无法解析的数据报供诊断（见 `rejects` 模块），为 0 时不保留
+    pub capture: Option<Capture>,   // 收发的每个数据报交给它，例如写入 pcap 文件（见 `capture` 模块）
+    pub faults: Option<FaultConfig>,    // 在套接字与协议之间注入丢包、复制、乱序与延迟（见 `fault` 模块）
+    pub observer: Option<Observer>, // 连接的生命周期与重传等事件交给它（见 `observer` 模块）
+    pub workers: usize,             // 监听器的接收套接字数，大于 1 时以 SO_REUSEPORT 绑定同一地址，不支持的平台只用一个
+    pub nodelay: bool,              // 关闭小写入合并（Nagle），每次写入立即发送
+    pub nagle_delay: Duration,      // 合并缓冲的最长等待时间
+    pub keepalive_interval: Duration,   // 多久没有收到任何段后发送存活探测
+    pub path_keepalive_interval: Duration,  // 多久没有发出任何段后发送路径探测，保持 NAT 映射；0 表示不发送
+    pub keepalive_failures: u32,    // 连续多少个探测未回应后判定对端失联
+    pub backlog: usize,             // 监听器允许的半开握手数，也是待 accept 队列的容量
+    pub max_connections: Option<usize>, // 监听器（所有接收套接字合计）已建立连接数的上限，达到时以 `SERVER_BUSY` 的 Rst 拒绝新的握手，None 时不限
+    pub per_ip_limit: Option<usize>,    // 来自同一 IP 的已建立连接数的上限，拒绝方式同上，None 时不限
+    pub memory_limit: Option<usize>,    // 监听器的连接（含墓碑）合计占用的内存上限（字节），放不下新连接时以 `SERVER_BUSY` 拒绝握手、已有连接收小接收窗口（见 `budget` 模块），None 时不限
+    pub connection_memory_limit: Option<usize>, // 一条连接的重排缓冲、攒着的分片与发送队列合计的内存上限（字节），超出时先丢弃分片、收小接收窗口，放不下的写入以 `MemoryLimit` 拒绝，None 时不限
+    pub allow: Vec<Cidr>,           // 非空时监听器只与来自这些网段的新来源握手，其余静默丢弃（见 `acl` 模块）
+    pub deny: Vec<Cidr>,            // 监听器静默丢弃来自这些网段的新来源，优先于 allow
+    pub syn_cookies: SynCookies,    // 何时以无状态的 cookie 回应 SYN，不登记半开握手
+    pub retry_threshold: Option<usize>, // 半开握手数达到它时以 Retry 要求对端先验证地址（见 `retry` 模块），None 时从不要求
+    pub retry_token_lifetime: Duration, // Retry 令牌的有效期
+    pub handshake_timeout: Duration,    // 握手的最长时间：客户端 connect 的总超时，也是服务端半开握手的保留时间
+    pub syn_retry_initial: Duration,    // 客户端第一次重传 SYN 之前等待的时间，之后每次加倍，不超过 max_rto
+    pub syn_max_retries: u32,       // 客户端重传 SYN 的最多次数，最后一次重传之后再等一个间隔仍无回应则放弃
+    pub syn_retry_jitter: f64,      // 每个 SYN 重传间隔在 ±这个比例内随机浮动，避免大量客户端同步重试；0 时严格按指数退避
+    pub path_validation_timeout: Duration,  // 对端出现在新地址后等待它回应 PathChallenge 的时间，超过后放弃新地址（见 `listener` 模块）
+    pub conn_id_grace: Duration,    // 连接 ID 轮换后旧 ID 仍被接受的时间，吸收途中乱序的段，之后退役（见 `rotation` 模块）
+    pub conn_id_rotation_bytes: Option<u64>,    // 设置时每收发这么多数据体字节自动轮换一次本端的连接 ID，None 时只手动轮换
+    pub rotate_conn_id_on_migration: bool,  // 监听器在对端迁移到新地址后轮换连接的 ID，观察者无法把两个地址上的流量关联起来
+    pub accept_early_data: bool,    // 监听器接受随 SYN 到达的 0-RTT 数据（见 `Connection::connect_with_data`），关闭时忽略，由客户端在握手后重发
+    pub linger: Duration,           // close 等待数据送达与 FIN 握手的最长时间，超过后以 Rst 终止
+    pub idle_timeout: Duration,     // 多久没有收到任何段后回收连接（以 Rst 通知对端）
+    pub drain_timeout: Duration,    // 连接结束后监听器保留墓碑、吸收迟到重传的时间，默认 2 倍 max_rto
+    pub max_tombstones: usize,      // 监听器同时保留的墓碑数上限
+    pub shutdown_timeout: Duration, // 服务器关闭时等待各连接完成 FIN 交换的最长时间，之后中止余下的连接
+    #[cfg(feature = "crypto")]
+    pub psk: Option<PresharedKey>,  // 设置时加密数据段，双方必须使用同一个密钥（见 `crypto` 模块）
+}
+
+impl Default for LinkConfig {
+    fn default() -> Self {
+        Self {
+            send_window: 64,
+            send_buffer: 256 * 1024,
+            send_watermarks: None,
+            max_message: 16 * 1024 * 1024,
+            reassembly_budget: 32 * 1024 * 1024,
+            max_partial_messages: 64,
+            reassembly_timeout: Duration::from_secs(30),
+            initial_cwnd: 10,
+            congestion: CongestionAlgorithm::Reno,
+            checksums: vec![ChecksumAlgorithm::Crc32c, ChecksumAlgorithm::XxHash32],
+            recv_window: 64,
+            recv_watermarks: None,
+            min_rto: Duration::from_millis(200),
+            max_rto: Duration::from_secs(60),
+            max_retries: 8,
+            max_ack_delay: Duration::from_millis(25),
+            ack_every_n_segments: ack::ACK_EVERY as usize,
+            immediate_ack_on_gap: true,
+            sack: true,
+            timer_granularity: timer::DEFAULT_GRANULARITY,
+            mss: DEFAULT_MSS,
+            max_mss: None,
+            pmtu_interval: Duration::from_secs(600),
+            recv_buffer: 64 * 1024,
+            buffer_pool: 4 * 1024 * 1024,
+            scratch_high_water: 256 * 1024,
+            recv_batch: 64,
+            batch_window: Duration::ZERO,
+            pacing: true,
+            pacing_gain: 1.25,
+            dual_stack: false,
+            so_rcvbuf: None,
+            so_sndbuf: None,
+            dscp: None,
+            metrics_interval: Duration::from_secs(10),
+            decode_error_warn_rate: 1.0,
+            reject_log: 64,
+            capture: None,
+            faults: None,
+            observer: None,
+            workers: 1,
+            nodelay: false,
+            nagle_delay: Duration::from_millis(5),
+            keepalive_interval: Duration::from_secs(15),
+            path_keepalive_interval: Duration::from_secs(15),
+            keepalive_failures: 3,
+            backlog: 128,
+            max_connections: None,
+            per_ip_limit: None,
+            memory_limit: None,
+            connection_memory_limit: None,
+            allow: Vec::new(),
+            deny: Vec::new(),
+            syn_cookies: SynCookies::Never,
+            retry_threshold: None,
+            retry_token_lifetime: Duration::from_secs(10),
+            handshake_timeout: Duration::from_secs(10),
+            syn_retry_initial: Duration::from_secs(1),
+            syn_max_retries: 6,
+            syn_retry_jitter: 0.1,
+            path_validation_timeout: Duration::from_secs(3),
+            conn_id_grace: Duration::from_secs(2),
+            conn_id_rotation_bytes: None,
+            rotate_conn_id_on_migration: false,
+            accept_early_data: false,
+            linger: Duration::from_secs(10),
+            idle_timeout: Duration::from_secs(300),
+            drain_timeout: Duration::from_secs(120),
+            max_tombstones: 4096,
+            shutdown_timeout: Duration::from_secs(15),
+            #[cfg(feature = "crypto")]
+            psk: None,
+        }
+    }
+}
+
+impl LinkConfig {
+    /// 从 TOML 文件读取，未出现的参数取默认值
+    pub fn from_file(path: impl AsRef<Path>) -> Result<LinkConfig, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Io { path: path.to_path_buf(), message: e.to_string() })?;
+        Self::from_toml(&text)
+    }
+
+    /// 从 TOML 文本读取，未出现的参数取默认值
+    pub fn from_toml(text: &str) -> Result<LinkConfig, ConfigError> {
+        let table: toml::Table = text.parse().map_err(|e: toml::de::Error| ConfigError::Syntax(e.message().to_string()))?;
+        let mut config = LinkConfig::default();
+        for (key, value) in &table {
+            let value = match value {
+                toml::Value::String(s) => s.clone(),
+                toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => value.to_string(),
+                _ => return Err(ConfigError::InvalidValue { key: key.clone(), value: value.to_string(), expected: "a string, number or boolean" }),
+            };
+            config.set(key, &value).map_err(|rejected| rejection(key, &value, rejected))?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 默认值加上 `LINK_*` 环境变量
+    pub fn from_env() -> Result<LinkConfig, ConfigError> {
+        LinkConfig::default().with_env()
+    }
+
+    /// 以 `LINK_*` 环境变量覆盖已有的值
+    pub fn with_env(self) -> Result<LinkConfig, ConfigError> {
+        self.with_vars(std::env::vars())
+    }
+
+    fn with_vars(mut self, vars: impl IntoIterator<Item = (String, String)>) -> Result<LinkConfig, ConfigError> {
+        for (name, value) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            self.set(&key.to_ascii_lowercase(), &value).map_err(|rejected| rejection(&name, &value, rejected))?;
+        }
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// 拒绝不合理的组合
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |reason: String| Err(ConfigError::Invalid(reason));
+        if self.min_rto > self.max_rto {
+            return invalid(format!("min_rto {:?} exceeds max_rto {:?}", self.min_rto, self.max_rto));
+        }
+        if self.min_rto.is_zero() {
+            return invalid("min_rto must be positive".to_string());
+        }
+        if self.timer_granularity.is_zero() {
+            return invalid("timer_granularity must be positive".to_string());
+        }
+        if self.recv_batch == 0 {
+            return invalid("recv_batch must be positive".to_string());
+        }
+        // 确认延迟超过 RTO 下限时，对端会在确认发出前超时重传
+        if self.max_ack_delay >= self.min_rto {
+            return invalid(format!("max_ack_delay {:?} must be below min_rto {:?}", self.max_ack_delay, self.min_rto));
+        }
+        if self.mss <= Segment::FIXED_HEADER_LEN || self.mss > MAX_DATAGRAM {
+            return invalid(format!("mss {} must leave room for a payload after the {}-byte header and fit in {} bytes", self.mss, Segment::FIXED_HEADER_LEN, MAX_DATAGRAM));
+        }
+        if let Some(max_mss) = self.max_mss
+            && (max_mss < self.mss || max_mss > self.recv_buffer.min(MAX_DATAGRAM))
+        {
+            return invalid(format!("max_mss {} must lie between mss {} and recv_buffer {}", max_mss, self.mss, self.recv_buffer.min(MAX_DATAGRAM)));
+        }
+        if self.recv_buffer < Segment::FIXED_HEADER_LEN {
+            return invalid(format!("recv_buffer {} cannot hold a {}-byte header", self.recv_buffer, Segment::FIXED_HEADER_LEN));
+        }
+        for (field, value) in [
+            ("send_window", self.send_window),
+            ("recv_window", self.recv_window),
+            ("initial_cwnd", self.initial_cwnd),
+            ("ack_every_n_segments", self.ack_every_n_segments),
+            ("workers", self.workers),
+            ("max_partial_messages", self.max_partial_messages),
+        ] {
+            if value == 0 {
+                return invalid(format!("{} must be positive", field));
+            }
+        }
+        if let Some(dscp) = self.dscp
+            && dscp > 63
+        {
+            return invalid(format!("dscp {} must be within 0..=63", dscp));
+        }
+        for (field, limit) in [("memory_limit", self.memory_limit), ("connection_memory_limit", self.connection_memory_limit)] {
+            if let Some(limit) = limit
+                && limit <= budget::CONNECTION_OVERHEAD
+            {
+                return invalid(format!("{} {} must exceed the {}-byte overhead of a connection", field, limit, budget::CONNECTION_OVERHEAD));
+            }
+        }
+        if self.conn_id_rotation_bytes == Some(0) {
+            return invalid("conn_id_rotation_bytes must be positive".to_string());
+        }
+        if self.syn_retry_initial.is_zero() {
+            return invalid("syn_retry_initial must be positive".to_string());
+        }
+        if !(0.0..1.0).contains(&self.syn_retry_jitter) {
+            return invalid(format!("syn_retry_jitter {} must be within 0..1", self.syn_retry_jitter));
+        }
+        if !(self.pacing_gain.is_finite() && self.pacing_gain > 0.0) {
+            return invalid(format!("pacing_gain {} must be a positive number", self.pacing_gain));
+        }
+        if !(self.decode_error_warn_rate.is_finite() && self.decode_error_warn_rate >= 0.0) {
+            return invalid(format!("decode_error_warn_rate {} must be a non-negative number", self.decode_error_warn_rate));
+        }
+        if self.checksums.is_empty() {
+            return invalid("checksums must list at least one algorithm".to_string());
+        }
+        if self.checksums.len() > Segment::MAX_CHECKSUM_OFFER {
+            return invalid(format!("checksums lists {} algorithms, at most {} fit in a SYN", self.checksums.len(), Segment::MAX_CHECKSUM_OFFER));
+        }
+        if let CongestionAlgorithm::NoCc { window: 0 } = self.congestion {
+            return invalid("nocc window must be positive".to_string());
+        }
+        for (field, marks, limit) in [("send_watermarks", self.send_watermarks, self.send_buffer), ("recv_watermarks", self.recv_watermarks, self.recv_window)] {
+            if let Some(marks) = marks
+                && (marks.low >= marks.high || marks.high > limit)
+            {
+                return invalid(format!("{} {} must have low below high and high within {}", field, marks, limit));
+            }
+        }
+        if let Some(faults) = &self.faults
+            && ![faults.loss, faults.duplicate, faults.reorder, faults.ce].iter().all(|p| (0.0..=1.0).contains(p))
+        {
+            return invalid("fault probabilities must be within 0..=1".to_string());
+        }
+        Ok(())
+    }
+
+    // 按键名设置一项；值的写法与 TOML 中的字符串相同
+    fn set(&mut self, key: &str, value: &str) -> Result<(), Rejected> {
+        fn number<T: std::str::FromStr>(value: &str) -> Result<T, Rejected> {
+            value.parse().map_err(|_| Rejected::Expected("a non-negative integer"))
+        }
+        fn cidrs(value: &str) -> Result<Vec<Cidr>, Rejected> {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|net| !net.is_empty())
+                .map(str::parse)
+                .collect::<Result<_, ()>>()
+                .map_err(|_| Rejected::Expected("a list such as 10.0.0.0/8,fd00::/8"))
+        }
+        fn boolean(value: &str) -> Result<bool, Rejected> {
+            value.parse().map_err(|_| Rejected::Expected("true or false"))
+        }
+        fn duration(value: &str) -> Result<Duration, Rejected> {
+            parse_duration(value).ok_or(Rejected::Expected("a duration such as 200ms or 10s"))
+        }
+        fn watermarks(value: &str) -> Result<Option<Watermarks>, Rejected> {
+            match value {
+                "off" => Ok(None),
+                _ => Ok(Some(value.parse().map_err(|_| Rejected::Expected("off or low..high such as 16384..49152"))?)),
+            }
+        }
+        fn optional<T: std::str::FromStr>(value: &str) -> Result<Option<T>, Rejected> {
+            match value {
+                "off" => Ok(None),
+                _ => Ok(Some(value.parse().map_err(|_| Rejected::Expected("off or a non-negative integer"))?)),
+            }
+        }
+        match key {
+            "send_window" => self.send_window = number(value)?,
+            "send_buffer" => self.send_buffer = number(value)?,
+            "send_watermarks" => self.send_watermarks = watermarks(value)?,
+            "max_message" => self.max_message = number(value)?,
+            "reassembly_budget" => self.reassembly_budget = number(value)?,
+            "max_partial_messages" => self.max_partial_messages = number(value)?,
+            "reassembly_timeout" => self.reassembly_timeout = duration(value)?,
+            "initial_cwnd" => self.initial_cwnd = number(value)?,
+            "congestion" => {
+                self.congestion = match value.split_once(':') {
+                    None if value == "reno" => CongestionAlgorithm::Reno,
+                    Some(("nocc", window)) => CongestionAlgorithm::NoCc { window: number(window)? },
+                    _ => return Err(Rejected::Expected("reno or nocc:<window>")),
+                }
+            }
+            "checksums" => {
+                self.checksums = value
+                    .split(',')
+                    .map(|name| name.trim().parse())
+                    .collect::<Result<_, ()>>()
+                    .map_err(|_| Rejected::Expected("a list such as crc32c,xxhash32,none"))?
+            }
+            "recv_window" => self.recv_window = number(value)?,
+            "recv_watermarks" => self.recv_watermarks = watermarks(value)?,
+            "min_rto" => self.min_rto = duration(value)?,
+            "max_rto" => self.max_rto = duration(value)?,
+            "max_retries" => self.max_retries = number(value)?,
+            "max_ack_delay" => self.max_ack_delay = duration(value)?,
+            "ack_every_n_segments" => self.ack_every_n_segments = number(value)?,
+            "immediate_ack_on_gap" => self.immediate_ack_on_gap = boolean(value)?,
+            "sack" => self.sack = boolean(value)?,
+            "timer_granularity" => self.timer_granularity = duration(value)?,
+            "mss" => self.mss = number(value)?,
+            "max_mss" => self.max_mss = optional(value)?,
+            "pmtu_interval" => self.pmtu_interval = duration(value)?,
+            "recv_buffer" => self.recv_buffer = number(value)?,
+            "buffer_pool" => self.buffer_pool = number(value)?,
+            "scratch_high_water" => self.scratch_high_water = number(value)?,
+            "recv_batch" => self.recv_batch = number(value)?,
+            "batch_window" => self.batch_window = duration(value)?,
+            "pacing" => self.pacing = boolean(value)?,
+            "pacing_gain" => self.pacing_gain = value.parse().map_err(|_| Rejected::Expected("a number such as 1.25"))?,
+            "dual_stack" => self.dual_stack = boolean(value)?,
+            "so_rcvbuf" => self.so_rcvbuf = optional(value)?,
+            "so_sndbuf" => self.so_sndbuf = optional(value)?,
+            "dscp" => self.dscp = optional(value)?,
+            "metrics_interval" => self.metrics_interval = duration(value)?,
+            "decode_error_warn_rate" => self.decode_error_warn_rate = value.parse().map_err(|_| Rejected::Expected("a number such as 1.0"))?,
+            "reject_log" => self.reject_log = number(value)?,
+            "faults" => self.faults = Some(value.parse().map_err(|_| Rejected::Expected("a fault spec such as loss=0.05,delay=20ms"))?),
+            "workers" => self.workers = number(value)?,
+            "nodelay" => self.nodelay = boolean(value)?,
+            "nagle_delay" => self.nagle_delay = duration(value)?,
+            "keepalive_interval" => self.keepalive_interval = duration(value)?,
+            "path_keepalive_interval" => self.path_keepalive_interval = duration(value)?,
+            "keepalive_failures" => self.keepalive_failures = number(value)?,
+            "backlog" => self.backlog = number(value)?,
+            "max_connections" => self.max_connections = optional(value)?,
+            "per_ip_limit" => self.per_ip_limit = optional(value)?,
+            "memory_limit" => self.memory_limit = optional(value)?,
+            "connection_memory_limit" => self.connection_memory_limit = optional(value)?,
+            "allow" => self.allow = cidrs(value)?,
+            "deny" => self.deny = cidrs(value)?,
+            "syn_cookies" => {
+                self.syn_cookies = match value {
+                    "never" => SynCookies::Never,
+                    "overflow" => SynCookies::Overflow,
+                    "always" => SynCookies::Always,
+                    _ => return Err(Rejected::Expected("never, overflow or always")),
+                }
+            }
+            "retry_threshold" => self.retry_threshold = optional(value)?,
+            "retry_token_lifetime" => self.retry_token_lifetime = duration(value)?,
+            "handshake_timeout" => self.handshake_timeout = duration(value)?,
+            "syn_retry_initial" => self.syn_retry_initial = duration(value)?,
+            "syn_max_retries" => self.syn_max_retries = number(value)?,
+            "syn_retry_jitter" => self.syn_retry_jitter = value.parse().map_err(|_| Rejected::Expected("a number such as 0.1"))?,
+            "path_validation_timeout" => self.path_validation_timeout = duration(value)?,
+            "conn_id_grace" => self.conn_id_grace = duration(value)?,
+            "conn_id_rotation_bytes" => self.conn_id_rotation_bytes = optional(value)?,
+            "rotate_conn_id_on_migration" => self.rotate_conn_id_on_migration = boolean(value)?,
+            "accept_early_data" => self.accept_early_data = boolean(value)?,
+            "linger" => self.linger = duration(value)?,
+            "idle_timeout" => self.idle_timeout = duration(value)?,
+            "drain_timeout" => self.drain_timeout = duration(value)?,
+            "max_tombstones" => self.max_tombstones = number(value)?,
+            "shutdown_timeout" => self.shutdown_timeout = duration(value)?,
+            #[cfg(feature = "crypto")]
+            "psk" => self.psk = Some(value.parse().map_err(|_| Rejected::Expected("64 hexadecimal characters"))?),
+            _ => return Err(Rejected::Unknown),
+        }
+        Ok(())
+    }
+}
+
+fn rejection(key: &str, value: &str, rejected: Rejected) -> ConfigError {
+    match rejected {
+        Rejected::Unknown => ConfigError::UnknownKey(key.to_string()),
+        Rejected::Expected(expected) => ConfigError::InvalidValue { key: key.to_string(), value: value.to_string(), expected },
+    }
+}
+
+/// 带 ms 或 s 单位的整数时长，如 `200ms`、`10s`
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    if let Some(ms) = value.strip_suffix("ms") {
+        ms.parse().ok().map(Duration::from_millis)
+    } else {
+        value.strip_suffix('s')?.parse().ok().map(Duration::from_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parse_toml() {
+        let config = LinkConfig::from_toml(
+            r#"
+            min_rto = "300ms"
+            max_rto = "30s"
+            mss = 1400
+            nodelay = true
+            congestion = "nocc:32"
+            checksums = "xxhash32, none"
+            syn_cookies = "overflow"
+            retry_threshold = 16
+            max_mss = 9000
+            faults = "loss=0.1,seed=3"
+            "#,
+        )
+        .unwrap();
+        assert_eq!((config.min_rto, config.max_rto), (Duration::from_millis(300), Duration::from_secs(30)));
+        assert_eq!((config.mss, config.nodelay, config.syn_cookies), (1400, true, SynCookies::Overflow));
+        assert_eq!(config.congestion, CongestionAlgorithm::NoCc { window: 32 });
+        assert_eq!(config.retry_threshold, Some(16));
+        assert_eq!(LinkConfig::from_toml("retry_threshold = \"off\"").unwrap().retry_threshold, None);
+        assert_eq!(LinkConfig::from_toml("buffer_pool = 0").unwrap().buffer_pool, 0);
+        assert_eq!(LinkConfig::from_toml("scratch_high_water = 65536").unwrap().scratch_high_water, 65536);
+        assert_eq!(LinkConfig::from_toml("recv_batch = 1").unwrap().recv_batch, 1);
+        let limits = LinkConfig::from_toml("max_connections = 1000\nper_ip_limit = 8").unwrap();
+        let acl = LinkConfig::from_toml("allow = \"10.0.0.0/8, fd00::/8\"\ndeny = \"\"").unwrap();
+        assert_eq!(acl.allow, vec!["10.0.0.0/8".parse().unwrap(), "fd00::/8".parse().unwrap()]);
+        assert!(acl.deny.is_empty());
+        assert!(matches!(LinkConfig::from_toml("deny = \"10.0.0.0/40\""), Err(ConfigError::InvalidValue { key, .. }) if key == "deny"));
+        assert_eq!((limits.max_connections, limits.per_ip_limit, LinkConfig::default().max_connections), (Some(1000), Some(8), None));
+        let memory = LinkConfig::from_toml("memory_limit = 134217728\nconnection_memory_limit = \"off\"").unwrap();
+        assert_eq!((memory.memory_limit, memory.connection_memory_limit), (Some(128 << 20), None));
+        assert_eq!(LinkConfig::from_toml("path_validation_timeout = \"500ms\"").unwrap().path_validation_timeout, Duration::from_millis(500));
+        let rotation = LinkConfig::from_toml("conn_id_grace = \"500ms\"\nconn_id_rotation_bytes = 1048576\nrotate_conn_id_on_migration = true").unwrap();
+        assert_eq!((rotation.conn_id_grace, rotation.conn_id_rotation_bytes, rotation.rotate_conn_id_on_migration), (Duration::from_millis(500), Some(1 << 20), true));
+        assert_eq!(LinkConfig::from_toml("batch_window = \"2ms\"").unwrap().batch_window, Duration::from_millis(2));
+        let syn = LinkConfig::from_toml("syn_retry_initial = \"250ms\"\nsyn_max_retries = 3\nsyn_retry_jitter = 0.0").unwrap();
+        assert_eq!((syn.syn_retry_initial, syn.syn_max_retries, syn.syn_retry_jitter), (Duration::from_millis(250), 3, 0.0));
+        assert_eq!(LinkConfig::from_toml("timer_granularity = \"1ms\"").unwrap().timer_granularity, Duration::from_millis(1));
+        let acks = LinkConfig::from_toml("ack_every_n_segments = 8\nimmediate_ack_on_gap = false\nsack = false").unwrap();
+        assert_eq!((acks.ack_every_n_segments, acks.immediate_ack_on_gap, acks.sack), (8, false, false));
+        let paced = LinkConfig::from_toml("pacing = false\npacing_gain = 2.0").unwrap();
+        assert_eq!((paced.pacing, paced.pacing_gain, LinkConfig::default().pacing), (false, 2.0, true));
+        assert_eq!(LinkConfig::from_toml("decode_error_warn_rate = 0").unwrap().decode_error_warn_rate, 0.0);
+        let marks = LinkConfig::from_toml("send_watermarks = \"16384..49152\"\nrecv_watermarks = \"off\"").unwrap();
+        assert_eq!((marks.send_watermarks, marks.recv_watermarks), (Some(Watermarks::new(16384, 49152)), None));
+        assert!(matches!(LinkConfig::from_toml("recv_watermarks = 48"), Err(ConfigError::InvalidValue { key, .. }) if key == "recv_watermarks"));
+        assert_eq!((config.max_mss, LinkConfig::default().max_mss), (Some(9000), None));
+        let tuned = LinkConfig::from_toml("so_rcvbuf = 262144\nso_sndbuf = \"off\"\ndscp = 46").unwrap();
+        assert_eq!((tuned.so_rcvbuf, tuned.so_sndbuf, tuned.dscp), (Some(262144), None, Some(46)));
+        assert_eq!(config.checksums, vec![ChecksumAlgorithm::XxHash32, ChecksumAlgorithm::NoChecksum]);
+        assert!(matches!(LinkConfig::from_toml("checksums = \"crc64\""), Err(ConfigError::InvalidValue { key, .. }) if key == "checksums"));
+        assert_eq!(config.faults.map(|faults| (faults.loss, faults.seed)), Some((0.1, 3)));
+        // 未出现的参数取默认值
+        assert_eq!(config.send_window, LinkConfig::default().send_window);
+
+        assert!(matches!(LinkConfig::from_toml("mss = "), Err(ConfigError::Syntax(_))));
+        assert_eq!(LinkConfig::from_toml("msss = 1400").unwrap_err(), ConfigError::UnknownKey("msss".to_string()));
+        assert!(matches!(LinkConfig::from_toml("min_rto = 300"), Err(ConfigError::InvalidValue { key, .. }) if key == "min_rto"));
+        assert!(matches!(LinkConfig::from_toml("mss = -1"), Err(ConfigError::InvalidValue { .. })));
+        assert!(matches!(LinkConfig::from_toml("mss = [1]"), Err(ConfigError::InvalidValue { .. })));
+        assert!(matches!(LinkConfig::from_file("/nonexistent/link.toml"), Err(ConfigError::Io { .. })));
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_parse_psk() {
+        let hex = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+        let config = LinkConfig::from_toml(&format!("psk = \"{}\"", hex)).unwrap();
+        assert_eq!(config.psk, Some(hex.parse().unwrap()));
+        assert!(matches!(LinkConfig::from_toml("psk = \"00ff\""), Err(ConfigError::InvalidValue { key, .. }) if key == "psk"));
+        assert_eq!(LinkConfig::default().psk, None);
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let file = LinkConfig::from_toml("mss = 1400\nlinger = \"5s\"").unwrap();
+        let config = file.with_vars(vars(&[("LINK_MSS", "1000"), ("LINK_DUAL_STACK", "true"), ("PATH", "/bin"), ("LINKER", "x")])).unwrap();
+        assert_eq!(config.mss, 1000);
+        assert!(config.dual_stack);
+        // 环境变量中没有的值保留文件中的设置
+        assert_eq!(config.linger, Duration::from_secs(5));
+
+        let unknown = LinkConfig::default().with_vars(vars(&[("LINK_MSSS", "1000")])).unwrap_err();
+        assert_eq!(unknown, ConfigError::UnknownKey("LINK_MSSS".to_string()));
+        let invalid = LinkConfig::default().with_vars(vars(&[("LINK_NODELAY", "yes")])).unwrap_err();
+        assert!(invalid.to_string().contains("LINK_NODELAY"), "{}", invalid);
+    }
+
+    #[test]
+    fn test_validation_rules() {
+        let rejects = |config: LinkConfig, needle: &str| {
+            let error = config.validate().unwrap_err();
+            assert!(error.to_string().contains(needle), "{} does not mention {}", error, needle);
+        };
+        assert!(LinkConfig::default().validate().is_ok());
+        rejects(LinkConfig { min_rto: Duration::from_secs(2), max_rto: Duration::from_secs(1), ..LinkConfig::default() }, "exceeds max_rto");
+        rejects(LinkConfig { min_rto: Duration::ZERO, max_ack_delay: Duration::ZERO, ..LinkConfig::default() }, "min_rto must be positive");
+        rejects(LinkConfig { timer_granularity: Duration::ZERO, ..LinkConfig::default() }, "timer_granularity must be positive");
+        rejects(LinkConfig { max_ack_delay: Duration::from_millis(200), ..LinkConfig::default() }, "max_ack_delay");
+        rejects(LinkConfig { mss: Segment::FIXED_HEADER_LEN, ..LinkConfig::default() }, "mss");
+        rejects(LinkConfig { mss: MAX_DATAGRAM + 1, ..LinkConfig::default() }, "mss");
+        rejects(LinkConfig { recv_buffer: 16, ..LinkConfig::default() }, "recv_buffer");
+        rejects(LinkConfig { max_mss: Some(1000), ..LinkConfig::default() }, "max_mss");
+        rejects(LinkConfig { conn_id_rotation_bytes: Some(0), ..LinkConfig::default() }, "conn_id_rotation_bytes");
+        rejects(LinkConfig { dscp: Some(64), ..LinkConfig::default() }, "dscp");
+        rejects(LinkConfig { memory_limit: Some(1024), ..LinkConfig::default() }, "memory_limit");
+        rejects(LinkConfig { connection_memory_limit: Some(budget::CONNECTION_OVERHEAD), ..LinkConfig::default() }, "connection_memory_limit");
+        rejects(LinkConfig { max_mss: Some(9000), recv_buffer: 4096, ..LinkConfig::default() }, "max_mss");
+        rejects(LinkConfig { send_window: 0, ..LinkConfig::default() }, "send_window");
+        rejects(LinkConfig { recv_window: 0, ..LinkConfig::default() }, "recv_window");
+        rejects(LinkConfig { initial_cwnd: 0, ..LinkConfig::default() }, "initial_cwnd");
+        rejects(LinkConfig { ack_every_n_segments: 0, ..LinkConfig::default() }, "ack_every_n_segments");
+        rejects(LinkConfig { workers: 0, ..LinkConfig::default() }, "workers");
+        rejects(LinkConfig { recv_batch: 0, ..LinkConfig::default() }, "recv_batch");
+        rejects(LinkConfig { congestion: CongestionAlgorithm::NoCc { window: 0 }, ..LinkConfig::default() }, "nocc");
+        rejects(LinkConfig { pacing_gain: 0.0, ..LinkConfig::default() }, "pacing_gain");
+        rejects(LinkConfig { syn_retry_initial: Duration::ZERO, ..LinkConfig::default() }, "syn_retry_initial");
+        rejects(LinkConfig { syn_retry_jitter: 1.0, ..LinkConfig::default() }, "syn_retry_jitter");
+        rejects(LinkConfig { pacing_gain: f64::NAN, ..LinkConfig::default() }, "pacing_gain");
+        rejects(LinkConfig { decode_error_warn_rate: -1.0, ..LinkConfig::default() }, "decode_error_warn_rate");
+        rejects(LinkConfig { checksums: Vec::new(), ..LinkConfig::default() }, "checksums");
+        rejects(LinkConfig { checksums: vec![ChecksumAlgorithm::Crc32c; Segment::MAX_CHECKSUM_OFFER + 1], ..LinkConfig::default() }, "checksums");
+        rejects(LinkConfig { send_watermarks: Some(Watermarks::new(4096, 4096)), ..LinkConfig::default() }, "send_watermarks");
+        rejects(LinkConfig { recv_watermarks: Some(Watermarks::new(16, 65)), ..LinkConfig::default() }, "recv_watermarks");
+        rejects(LinkConfig { faults: Some(FaultConfig { loss: 2.0, ..FaultConfig::default() }), ..LinkConfig::default() }, "fault");
+        // 读取时同样校验
+        assert!(matches!(LinkConfig::from_toml("min_rto = \"2s\"\nmax_rto = \"1s\""), Err(ConfigError::Invalid(_))));
+    }
+}
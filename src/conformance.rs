@@ -0,0 +1,566 @@
+//! 与另一个实现的互通检查
+//! `run` 在调用方提供的传输上连接到对端（`Peer::Server`）或等待对端连接（`Peer::Client`），依次运行一组脚本化的检查：
+//! 握手、版本协商（校验算法与传输参数）、MSS 限制、1000 条消息的按序交付、注入丢包下的恢复与正常关闭，
+//! 每项给出通过与否，未通过时附上相关的段。`src/bin/conformance.rs` 是它的命令行入口，`tests/conformance.rs`
+//! 以本 crate 自己的回显服务器运行同一组检查。
+//!
+//! 对端的约定：把收到的数据原样发回（字节流意义上，分段方式不限），本端关闭时完成 FIN 交换。
+//! 检查不重新实现协议：连接本身由协议核心驱动，判定依据是 `LinkConfig::capture` 记下的数据报、
+//! `LinkConfig::observer` 报告的事件与连接协商出的结果；丢包由套接字与协议之间的 `FaultyTransport` 注入，
+//! 两个方向各自计数。通告给对端的 MSS 取 `SuiteConfig::advertised_mss`，它同时是接收缓冲区的大小，
+//! 对端发来更大的数据报时会被截断并在 MSS 检查中报告。
+
+use crate::capture::{Capture, CaptureSink};
+use crate::checksum;
+use crate::config::LinkConfig;
+use crate::connection::Connection;
+use crate::error::LinkError;
+use crate::fault::{FaultConfig, FaultControl, FaultyTransport};
+use crate::listener::Listener;
+use crate::observer::{ConnectionObserver, Observation, Observer};
+use crate::options;
+use crate::rejects::hexdump;
+use crate::segment::{self, Segment, SegmentError, SegmentFlags, SegmentType};
+use crate::seq::SeqNum;
+use crate::state::ConnState;
+use crate::trace::Direction;
+use crate::transport::Transport;
+use bytes::Bytes;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::{Instant, timeout};
+
+/// 每项未通过的检查最多附上的段数（取最近的）
+const MAX_EVIDENCE: usize = 8;
+
+/// 对端的角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Peer {
+    Server(SocketAddr), // 连接到对端的回显服务器
+    Client,             // 在传输上等待对端连接，接受第一个连接
+}
+
+/// 检查的参数
+#[derive(Debug, Clone)]
+pub struct SuiteConfig {
+    pub link: LinkConfig,           // 本端的连接参数；recv_buffer、nodelay、capture 与 observer 由检查设置
+    pub advertised_mss: usize,      // 握手中通告给对端的 MSS（字节）
+    pub messages: usize,            // 按序交付检查发送的消息数
+    pub lossy_messages: usize,      // 丢包恢复检查发送的消息数
+    pub loss: f64,                  // 丢包恢复检查在两个方向上注入的丢包率
+    pub seed: u64,                  // 丢包判定的随机数种子
+    pub step_timeout: Duration,     // 每项检查的最长时间
+}
+
+impl Default for SuiteConfig {
+    fn default() -> Self {
+        Self {
+            link: LinkConfig::default(),
+            advertised_mss: 600,
+            messages: 1000,
+            lossy_messages: 200,
+            loss: 0.1,
+            seed: 1,
+            step_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// 一项检查的结果
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    Passed,
+    Failed { reason: String, evidence: Vec<Evidence> },
+    Skipped(String),    // 前面的检查失败，这一项无法进行
+}
+
+/// 一项检查
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+}
+
+/// 未通过的检查附上的一个段：收发方向、对端、解码后的摘要与原始字节
+#[derive(Debug, Clone)]
+pub struct Evidence {
+    pub direction: Direction,
+    pub peer: SocketAddr,
+    pub summary: String,
+    pub bytes: Bytes,
+}
+
+/// 各项检查按运行顺序排列
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// 每一项都通过；跳过的检查不算通过
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| matches!(check.outcome, Outcome::Passed))
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|check| !matches!(check.outcome, Outcome::Passed))
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            match &check.outcome {
+                Outcome::Passed => writeln!(f, "PASS {}", check.name)?,
+                Outcome::Skipped(reason) => writeln!(f, "SKIP {}: {}", check.name, reason)?,
+                Outcome::Failed { reason, evidence } => {
+                    writeln!(f, "FAIL {}: {}", check.name, reason)?;
+                    for segment in evidence {
+                        let direction = match segment.direction {
+                            Direction::Inbound => "from",
+                            Direction::Outbound => "to",
+                        };
+                        write!(f, "  {} {}: {} ({} bytes)", direction, segment.peer, segment.summary, segment.bytes.len())?;
+                        hexdump(f, &segment.bytes)?;
+                        writeln!(f)?;
+                    }
+                }
+            }
+        }
+        let passed = self.checks.iter().filter(|check| matches!(check.outcome, Outcome::Passed)).count();
+        write!(f, "{}/{} checks passed", passed, self.checks.len())
+    }
+}
+
+// 记下收发的每个数据报与连接报告的事件
+#[derive(Debug, Default)]
+struct Recorder {
+    datagrams: Mutex<Vec<(Direction, SocketAddr, Bytes)>>,
+    observations: Mutex<Vec<Observation>>,
+}
+
+impl Recorder {
+    fn observe(&self, observation: Observation) {
+        self.observations.lock().expect("recorder poisoned").push(observation);
+    }
+
+    fn observed(&self, matches: &impl Fn(&Observation) -> bool) -> usize {
+        self.observations.lock().expect("recorder poisoned").iter().filter(|observation| matches(observation)).count()
+    }
+}
+
+impl CaptureSink for Recorder {
+    fn record(&self, direction: Direction, _local: SocketAddr, peer: SocketAddr, datagram: &[u8]) {
+        self.datagrams.lock().expect("recorder poisoned").push((direction, peer, Bytes::copy_from_slice(datagram)));
+    }
+}
+
+impl ConnectionObserver for Recorder {
+    fn on_established(&self, _peer: SocketAddr) {
+        self.observe(Observation::Established);
+    }
+
+    fn on_close(&self, _peer: SocketAddr, reason: Option<&LinkError>) {
+        self.observe(Observation::Closed(reason.cloned()));
+    }
+
+    fn on_state_change(&self, old: ConnState, new: ConnState) {
+        self.observe(Observation::StateChange { old, new });
+    }
+
+    fn on_retransmit(&self, seq: SeqNum, attempt: u32) {
+        self.observe(Observation::Retransmit { seq, attempt });
+    }
+
+    fn on_rto_expired(&self, backoff: Duration) {
+        self.observe(Observation::RtoExpired { backoff });
+    }
+}
+
+// 数据报中的一个段，解码失败时是从失败处起的余下部分
+struct Piece {
+    direction: Direction,
+    peer: SocketAddr,
+    bytes: Bytes,
+    segment: Result<Segment, SegmentError>,
+}
+
+impl Piece {
+    fn evidence(&self) -> Evidence {
+        let summary = match &self.segment {
+            Ok(segment) => format!(
+                "{:?} conn_id={} seq={} ack={} flags={:#04x} checksum={} data={}",
+                segment.segment_type(),
+                segment.conn_id(),
+                segment.seq(),
+                segment.ack(),
+                segment.flags().bits(),
+                segment.checksum(),
+                segment.data().len()
+            ),
+            Err(e) => format!("undecodable: {}", e),
+        };
+        Evidence { direction: self.direction, peer: self.peer, summary, bytes: self.bytes.clone() }
+    }
+
+    fn is(&self, segment_type: SegmentType) -> bool {
+        matches!(&self.segment, Ok(segment) if segment.segment_type() == segment_type)
+    }
+}
+
+// 沿长度前缀把数据报切成段，与接收路径一样解码
+fn split(direction: Direction, peer: SocketAddr, datagram: &Bytes) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut rest = datagram.clone();
+    while !rest.is_empty() {
+        let before = rest.clone();
+        match Segment::decode_bytes(&mut rest) {
+            Ok(Some(segment)) => {
+                let bytes = before.slice(..before.len() - rest.len());
+                pieces.push(Piece { direction, peer, bytes, segment: Ok(segment) });
+            }
+            Ok(None) => {
+                pieces.push(Piece { direction, peer, bytes: before, segment: Err(SegmentError::TooShort) });
+                break;
+            }
+            Err(e) => {
+                pieces.push(Piece { direction, peer, bytes: before, segment: Err(e) });
+                break;
+            }
+        }
+    }
+    pieces
+}
+
+fn evidence<'a>(pieces: impl IntoIterator<Item = &'a Piece>) -> Vec<Evidence> {
+    let all: Vec<Evidence> = pieces.into_iter().map(Piece::evidence).collect();
+    all[all.len().saturating_sub(MAX_EVIDENCE)..].to_vec()
+}
+
+fn failed(reason: impl Into<String>, evidence: Vec<Evidence>) -> Outcome {
+    Outcome::Failed { reason: reason.into(), evidence }
+}
+
+// 第 `index` 条消息：可读的前缀与随序号变化的长度，数据体大小覆盖从几个字节到接近一个段
+fn message(label: &str, index: usize) -> Bytes {
+    let mut message = format!("{} {:05} ", label, index).into_bytes();
+    message.extend((0..index * 37 % 251).map(|i| b'a' + (i % 26) as u8));
+    Bytes::from(message)
+}
+
+struct Suite {
+    config: SuiteConfig,
+    recorder: Arc<Recorder>,
+    faults: FaultControl,
+    peer: Option<SocketAddr>,   // 连接建立后只看与这个对端之间的数据报
+}
+
+impl Suite {
+    // 至今记下的数据报数，之后的 `pieces` 可以从这里开始看
+    fn mark(&self) -> usize {
+        self.recorder.datagrams.lock().expect("recorder poisoned").len()
+    }
+
+    fn datagrams(&self, direction: Direction, since: usize) -> Vec<(SocketAddr, Bytes)> {
+        let datagrams = self.recorder.datagrams.lock().expect("recorder poisoned");
+        datagrams[since..]
+            .iter()
+            .filter(|(dir, peer, _)| *dir == direction && self.peer.is_none_or(|ours| ours == *peer))
+            .map(|(_, peer, datagram)| (*peer, datagram.clone()))
+            .collect()
+    }
+
+    fn pieces(&self, direction: Direction, since: usize) -> Vec<Piece> {
+        self.datagrams(direction, since).iter().flat_map(|(peer, datagram)| split(direction, *peer, datagram)).collect()
+    }
+
+    // 对端发起或回应的握手段：我们连接时是它的 SYN-ACK，对端连接时是它的 SYN
+    fn peer_handshake(&self) -> Option<Piece> {
+        self.pieces(Direction::Inbound, 0).into_iter().find(|piece| piece.is(SegmentType::Syn))
+    }
+
+    // 等待观察者收到满足 `matches` 的事件：事件由驱动任务在对应的调用返回前后交给观察者
+    async fn observe(&self, matches: impl Fn(&Observation) -> bool) -> bool {
+        let deadline = Instant::now() + self.config.step_timeout;
+        while self.recorder.observed(&matches) == 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        true
+    }
+
+    // 逐条发出 `messages`，同时读回对端发回的字节流，与发出的逐字节比较；失败时说明停在第几条消息
+    async fn echo(&self, connection: &Connection, messages: &[Bytes]) -> Result<(), String> {
+        let stream = Bytes::from(messages.concat());
+        let starts: Vec<usize> = messages.iter().scan(0, |offset, message| Some(std::mem::replace(offset, *offset + message.len()))).collect();
+        let at = |offset: usize| starts.partition_point(|&start| start <= offset).saturating_sub(1);
+        let received = AtomicUsize::new(0);
+        let send = async {
+            for (index, message) in messages.iter().enumerate() {
+                connection.send(message.clone()).await.map_err(|e| format!("send of message {} failed: {}", index, e))?;
+            }
+            Ok(())
+        };
+        let receive = async {
+            loop {
+                let offset = received.load(Ordering::Relaxed);
+                if offset == stream.len() {
+                    return Ok(());
+                }
+                let chunk = match connection.recv().await {
+                    Ok(Some(chunk)) => chunk,
+                    Ok(None) => return Err(format!("peer closed after echoing {} of {} messages", at(offset), messages.len())),
+                    Err(e) => return Err(format!("connection failed after {} of {} messages were echoed: {}", at(offset), messages.len(), e)),
+                };
+                let end = offset + chunk.len();
+                if end > stream.len() || chunk != stream[offset..end] {
+                    return Err(format!("echo of message {} is out of order or altered", at(offset)));
+                }
+                received.store(end, Ordering::Relaxed);
+            }
+        };
+        match timeout(self.config.step_timeout, async { tokio::try_join!(send, receive) }).await {
+            Ok(result) => result.map(|_| ()),
+            Err(_) => Err(format!("only {} of {} messages echoed within {:?}", at(received.load(Ordering::Relaxed)), messages.len(), self.config.step_timeout)),
+        }
+    }
+
+    // 握手完成，双方都走到 Established；对端的握手段格式正确并带有 MSS，SYN-ACK 确认的是我们的 SYN
+    async fn handshake(&self, connection: &Connection, initiated: bool) -> Outcome {
+        let inbound = self.pieces(Direction::Inbound, 0);
+        if !self.observe(|observation| *observation == Observation::Established).await {
+            return failed("the connection never reported Established", evidence(&inbound));
+        }
+        let Some(handshake) = self.peer_handshake() else {
+            return failed("no handshake segment arrived from the peer", evidence(&inbound));
+        };
+        let Ok(segment) = &handshake.segment else { unreachable!("peer_handshake only returns decoded segments") };
+        if segment.options().mss().is_none() {
+            return failed("the peer's handshake segment carries no MSS option", vec![handshake.evidence()]);
+        }
+        if initiated {
+            let ours = self.pieces(Direction::Outbound, 0).into_iter().find(|piece| piece.is(SegmentType::Syn));
+            let Some(Ok(syn)) = ours.map(|piece| piece.segment) else {
+                return failed("no SYN of ours was recorded", Vec::new());
+            };
+            if !segment.flags().contains(SegmentFlags::ACK) {
+                return failed("the peer answered our SYN with a SYN that does not acknowledge it", vec![handshake.evidence()]);
+            }
+            if segment.ack() != syn.seq() {
+                return failed(format!("the SYN-ACK acknowledges {} instead of our initial sequence number {}", segment.ack(), syn.seq()), vec![handshake.evidence()]);
+            }
+        } else if segment.flags().contains(SegmentFlags::ACK) {
+            return failed("the peer opened with a SYN that carries ACK", vec![handshake.evidence()]);
+        }
+        if connection.peer_addr() != handshake.peer {
+            return failed(format!("the connection is with {} but the handshake came from {}", connection.peer_addr(), handshake.peer), vec![handshake.evidence()]);
+        }
+        Outcome::Passed
+    }
+
+    // 对端按协商规则选出校验算法（发起方的偏好中第一个双方都接受的），带上可解码的传输参数；
+    // 握手之后对端的每个段都以选定的算法编码
+    async fn negotiation(&self, connection: &Connection, initiated: bool) -> Outcome {
+        let Some(handshake) = self.peer_handshake() else {
+            return failed("no handshake segment arrived from the peer", Vec::new());
+        };
+        let Ok(segment) = &handshake.segment else { unreachable!("peer_handshake only returns decoded segments") };
+        let (theirs, ours) = (segment.checksum_offer(), &self.config.link.checksums);
+        if theirs.is_empty() {
+            return failed("the peer's handshake segment offers no checksum algorithm", vec![handshake.evidence()]);
+        }
+        let expected = match initiated {
+            true => checksum::negotiate(ours, &theirs),
+            false => checksum::negotiate(&theirs, ours),
+        };
+        let Some(expected) = expected else {
+            return failed(format!("no checksum algorithm in common: we accept {:?}, the peer offers {:?}", ours, theirs), vec![handshake.evidence()]);
+        };
+        if initiated && segment.checksum() != expected {
+            return failed(format!("the SYN-ACK is encoded with {} but the negotiated algorithm is {}", segment.checksum(), expected), vec![handshake.evidence()]);
+        }
+        if connection.checksum() != expected {
+            return failed(format!("the connection uses {} but the negotiated algorithm is {}", connection.checksum(), expected), vec![handshake.evidence()]);
+        }
+        if segment.options().params().is_none() {
+            return failed("the peer's handshake segment carries no transport parameters", vec![handshake.evidence()]);
+        }
+
+        let mark = self.mark();
+        if let Err(reason) = self.echo(connection, &[message("negotiation", 0)]).await {
+            return failed(reason, evidence(&self.pieces(Direction::Inbound, mark)));
+        }
+        let mismatched: Vec<Piece> = self
+            .datagrams(Direction::Inbound, 0)
+            .into_iter()
+            .flat_map(|(peer, datagram)| split(Direction::Inbound, peer, &datagram))
+            .filter(|piece| !piece.is(SegmentType::Syn) && Segment::decode_bytes_with(&mut piece.bytes.clone(), expected).is_err())
+            .collect();
+        if !mismatched.is_empty() {
+            return failed(format!("{} segments after the handshake are not encoded with the negotiated {}", mismatched.len(), expected), evidence(&mismatched));
+        }
+        Outcome::Passed
+    }
+
+    // 一串刚好放满我们通告的 MSS 的消息原样回来，对端发来的数据报没有一个超过通告的 MSS（接收缓冲与它一样大，
+    // 超过的会被截断）；我们发出的段同样不超过对端通告的 MSS
+    async fn mss(&self, connection: &Connection) -> Outcome {
+        let advertised = self.config.advertised_mss;
+        let handshake = self.peer_handshake();
+        let peer_mss = handshake.as_ref().and_then(|piece| piece.segment.as_ref().ok()).and_then(|segment| segment.options().mss());
+        if let Some(peer_mss) = peer_mss
+            && connection.mss() > usize::from(peer_mss)
+        {
+            return failed(format!("we send {}-byte segments although the peer advertised {}", connection.mss(), peer_mss), handshake.iter().map(Piece::evidence).collect());
+        }
+        // 与协议核心一样为段头、最长的选项区与密封标签留出余量；对端通告的 MSS 更小时按它，否则我们发不出去
+        let overhead = Segment::FIXED_HEADER_LEN + options::MAX_LEN + self.tag_len();
+        let limit = peer_mss.map_or(advertised, |peer_mss| advertised.min(usize::from(peer_mss)));
+        let full = limit.saturating_sub(overhead).max(1);
+        let messages: Vec<Bytes> = (0..16).map(|index| Bytes::from((0..full).map(|i| ((index + i) % 251) as u8).collect::<Vec<u8>>())).collect();
+        let mark = self.mark();
+        if let Err(reason) = self.echo(connection, &messages).await {
+            return failed(reason, evidence(&self.pieces(Direction::Inbound, mark)));
+        }
+        let oversized: Vec<Piece> = self
+            .datagrams(Direction::Inbound, 0)
+            .into_iter()
+            .filter(|(_, datagram)| datagram.len() > advertised || segment::is_truncated(datagram))
+            .flat_map(|(peer, datagram)| split(Direction::Inbound, peer, &datagram))
+            .collect();
+        if !oversized.is_empty() {
+            return failed(format!("the peer sent datagrams larger than the {} bytes we advertised", advertised), evidence(&oversized));
+        }
+        Outcome::Passed
+    }
+
+    fn tag_len(&self) -> usize {
+        #[cfg(feature = "crypto")]
+        if self.config.link.psk.is_some() {
+            return crate::crypto::TAG_LEN;
+        }
+        0
+    }
+
+    // 逐条发出的消息按发送顺序原样回来
+    async fn delivery(&self, connection: &Connection) -> Outcome {
+        let messages: Vec<Bytes> = (0..self.config.messages).map(|index| message("message", index)).collect();
+        let mark = self.mark();
+        match self.echo(connection, &messages).await {
+            Ok(()) => Outcome::Passed,
+            Err(reason) => failed(reason, evidence(&self.pieces(Direction::Inbound, mark))),
+        }
+    }
+
+    // 两个方向都注入丢包：消息仍全部按序回来，我们重传了丢失的段，对端补上了被丢弃的段
+    async fn loss_recovery(&self, connection: &Connection) -> Outcome {
+        let messages: Vec<Bytes> = (0..self.config.lossy_messages).map(|index| message("lossy", index)).collect();
+        let (mark, before) = (self.mark(), self.faults.dropped());
+        let retransmitted = |recorder: &Recorder| recorder.observed(&|observation| matches!(observation, Observation::Retransmit { .. }));
+        let retransmits = retransmitted(&self.recorder);
+        self.faults.set(&FaultConfig { loss: self.config.loss, seed: self.config.seed, ..FaultConfig::default() });
+        let echoed = self.echo(connection, &messages).await;
+        self.faults.set(&FaultConfig::default());
+        let after = self.faults.dropped();
+        let dropped = (after.0 - before.0, after.1 - before.1);
+
+        let inbound = || evidence(&self.pieces(Direction::Inbound, mark));
+        if let Err(reason) = echoed {
+            return failed(format!("{} ({} outbound and {} inbound datagrams dropped)", reason, dropped.0, dropped.1), inbound());
+        }
+        if dropped.0 == 0 || dropped.1 == 0 {
+            return failed(format!("the fault injector dropped {} outbound and {} inbound datagrams; raise the loss rate or the message count", dropped.0, dropped.1), Vec::new());
+        }
+        if retransmitted(&self.recorder) == retransmits {
+            return failed(format!("{} of our datagrams were dropped but nothing was retransmitted", dropped.0), inbound());
+        }
+        Outcome::Passed
+    }
+
+    // close 在期限内完成 FIN 交换：对端回应了 Fin，连接以正常关闭结束
+    async fn close(&self, connection: Connection) -> Outcome {
+        let mark = self.mark();
+        let inbound = || evidence(&self.pieces(Direction::Inbound, mark));
+        match timeout(self.config.step_timeout, connection.close()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return failed(format!("close failed: {}", e), inbound()),
+            Err(_) => return failed(format!("close did not finish within {:?}", self.config.step_timeout), inbound()),
+        }
+        if !self.observe(|observation| matches!(observation, Observation::Closed(_))).await {
+            return failed("the connection never reported that it closed", inbound());
+        }
+        if self.recorder.observed(&|observation| matches!(observation, Observation::Closed(Some(_)))) > 0 {
+            return failed("the connection reported an error on close", inbound());
+        }
+        if !self.pieces(Direction::Inbound, mark).iter().any(|piece| piece.is(SegmentType::Fin)) {
+            return failed("the peer never sent a Fin", inbound());
+        }
+        Outcome::Passed
+    }
+}
+
+const HANDSHAKE: &str = "handshake";
+const NEGOTIATION: &str = "version negotiation";
+const MSS: &str = "mss enforcement";
+const DELIVERY: &str = "in-order delivery";
+const LOSS_RECOVERY: &str = "loss recovery";
+const CLOSE: &str = "graceful close";
+
+/// 在 `transport` 上与 `peer` 运行全部检查。`transport` 的本地地址就是检查使用的地址；
+/// 握手失败时其余各项跳过，其他检查失败时继续运行后面的检查
+pub async fn run(transport: impl Transport, peer: Peer, config: SuiteConfig) -> Report {
+    let recorder = Arc::new(Recorder::default());
+    let mut link = config.link.clone();
+    link.recv_buffer = config.advertised_mss;
+    link.nodelay = true;
+    link.capture = Some(Capture::new(recorder.clone()));
+    link.observer = Some(Observer::new(recorder.clone()));
+    let transport = FaultyTransport::new(transport, &FaultConfig::default(), link.recv_buffer);
+    let mut suite = Suite { config, recorder, faults: transport.control(), peer: None };
+    let step = suite.config.step_timeout;
+
+    let mut report = Report::default();
+    let mut _listener = None;   // 对端连接时，接受的连接只在监听器存活期间可用
+    let established = match peer {
+        Peer::Server(addr) => match timeout(step, Connection::connect_over(transport, addr, link)).await {
+            Ok(result) => result.map_err(|e| format!("connect failed: {}", e)),
+            Err(_) => Err(format!("no handshake within {:?}", step)),
+        },
+        Peer::Client => match Listener::with_transport(transport, link) {
+            Ok(listener) => {
+                let accepted = timeout(step, listener.accept()).await;
+                _listener = Some(listener);
+                match accepted {
+                    Ok(result) => result.map(|(connection, _)| connection).map_err(|e| format!("accept failed: {}", e)),
+                    Err(_) => Err(format!("no peer connected within {:?}", step)),
+                }
+            }
+            Err(e) => Err(format!("cannot listen: {}", e)),
+        },
+    };
+    let connection = match established {
+        Ok(connection) => connection,
+        Err(reason) => {
+            report.checks.push(Check { name: HANDSHAKE, outcome: failed(reason, evidence(&suite.pieces(Direction::Inbound, 0))) });
+            for name in [NEGOTIATION, MSS, DELIVERY, LOSS_RECOVERY, CLOSE] {
+                report.checks.push(Check { name, outcome: Outcome::Skipped("no connection was established".into()) });
+            }
+            return report;
+        }
+    };
+    suite.peer = Some(connection.peer_addr());
+    let initiated = matches!(peer, Peer::Server(_));
+
+    report.checks.push(Check { name: HANDSHAKE, outcome: suite.handshake(&connection, initiated).await });
+    report.checks.push(Check { name: NEGOTIATION, outcome: suite.negotiation(&connection, initiated).await });
+    report.checks.push(Check { name: MSS, outcome: suite.mss(&connection).await });
+    report.checks.push(Check { name: DELIVERY, outcome: suite.delivery(&connection).await });
+    report.checks.push(Check { name: LOSS_RECOVERY, outcome: suite.loss_recovery(&connection).await });
+    report.checks.push(Check { name: CLOSE, outcome: suite.close(connection).await });
+    report
+}
@@ -0,0 +1,246 @@
+//! 拥塞控制
+//! 发送端只通过 `CongestionControl` trait 与算法交互，内置两种实现，由 `LinkConfig::congestion` 选择：
+//! `Reno` 风格的 AIMD：拥塞窗口（cwnd，段数）在慢启动阶段每确认一个段加一（每 RTT 翻倍），
+//! 达到 ssthresh 后进入拥塞避免，每确认一整个窗口加一（每 RTT 加一）。
+//! RTO 超时视为严重拥塞：ssthresh 减半、cwnd 回到 1 重新慢启动；快速重传只把 cwnd 减半。
+//! 对端回送的显式拥塞信号（ECE）等同于一次快速重传，但没有段需要重传；发送端保证每 RTT 最多通知一次。
+//! `NoCc`：固定窗口，忽略所有确认与丢包事件，适合独占的低延迟链路。
+//! 每次确认得到的投递速率样本（见 `rate` 模块）经 `on_rate_sample` 交给算法，两种内置算法都不使用它，
+//! 基于速率的算法可以据此设置窗口与发送节奏。
+
+use crate::rate::RateSample;
+use std::fmt;
+use std::time::Duration;
+
+/// 丢包后 ssthresh 的下限（段数）
+pub const MIN_SSTHRESH: usize = 2;
+
+/// 丢包事件的类型（RTO 超时单独通过 `on_rto` 通知）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LossKind {
+    FastRetransmit, // 重复确认触发的快速重传
+}
+
+/// 拥塞控制算法。窗口以段为单位；对象安全，发送端以 `Box<dyn CongestionControl>` 持有。
+pub trait CongestionControl: fmt::Debug + Send {
+    /// 新确认了 `segments` 个段；`rtt` 为本次确认得到的有效 RTT 样本（Karn 算法丢弃的为 None）
+    fn on_ack(&mut self, segments: usize, rtt: Option<Duration>);
+
+    /// 检测到丢包
+    fn on_loss(&mut self, kind: LossKind);
+
+    /// 重传定时器超时
+    fn on_rto(&mut self);
+
+    /// 对端回送了途中的拥塞标记（ECE），没有发生丢包
+    fn on_ecn(&mut self);
+
+    /// 一次确认得到的投递速率样本，在同一确认的 `on_ack` 之前调用；应用受限的样本（`app_limited`）可能低于路径的容量
+    fn on_rate_sample(&mut self, _sample: RateSample) {}
+
+    /// 当前允许的在途段数
+    fn window(&self) -> usize;
+
+    /// 慢启动阈值，没有该概念的算法返回 usize::MAX
+    fn ssthresh(&self) -> usize {
+        usize::MAX
+    }
+}
+
+/// 内置算法的选择，`LinkConfig` 据此为每个连接构造实例
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CongestionAlgorithm {
+    #[default]
+    Reno,
+    NoCc { window: usize }, // 固定窗口（段数）
+}
+
+impl CongestionAlgorithm {
+    /// 构造算法实例；`initial_cwnd` 只对 Reno 有效
+    pub fn build(self, initial_cwnd: usize) -> Box<dyn CongestionControl> {
+        match self {
+            CongestionAlgorithm::Reno => Box::new(Reno::new(initial_cwnd)),
+            CongestionAlgorithm::NoCc { window } => Box::new(NoCc::new(window)),
+        }
+    }
+}
+
+/// Reno 拥塞窗口状态机
+#[derive(Debug, Clone)]
+pub struct Reno {
+    cwnd: usize,        // 拥塞窗口（段数）
+    ssthresh: usize,    // 慢启动阈值，初始无上限
+    acked_in_ca: usize, // 拥塞避免阶段累计确认的段数，满一个窗口时 cwnd 加一
+}
+
+impl Reno {
+    /// `initial_cwnd`：初始拥塞窗口（段数）
+    pub fn new(initial_cwnd: usize) -> Self {
+        Self {
+            cwnd: initial_cwnd.max(1),
+            ssthresh: usize::MAX,
+            acked_in_ca: 0,
+        }
+    }
+
+    pub fn cwnd(&self) -> usize {
+        self.cwnd
+    }
+
+    pub fn in_slow_start(&self) -> bool {
+        self.cwnd < self.ssthresh
+    }
+}
+
+impl CongestionControl for Reno {
+    fn on_ack(&mut self, segments: usize, _rtt: Option<Duration>) {
+        if self.in_slow_start() {
+            self.cwnd = self.cwnd.saturating_add(segments).min(self.ssthresh);
+            return;
+        }
+        self.acked_in_ca += segments;
+        while self.acked_in_ca >= self.cwnd {
+            self.acked_in_ca -= self.cwnd;
+            self.cwnd += 1;
+        }
+    }
+
+    fn on_loss(&mut self, kind: LossKind) {
+        match kind {
+            LossKind::FastRetransmit => {
+                self.ssthresh = (self.cwnd / 2).max(MIN_SSTHRESH);
+                self.cwnd = self.ssthresh;
+                self.acked_in_ca = 0;
+            }
+        }
+    }
+
+    fn on_rto(&mut self) {
+        self.ssthresh = (self.cwnd / 2).max(MIN_SSTHRESH);
+        self.cwnd = 1;
+        self.acked_in_ca = 0;
+    }
+
+    fn on_ecn(&mut self) {
+        self.on_loss(LossKind::FastRetransmit);
+    }
+
+    fn window(&self) -> usize {
+        self.cwnd
+    }
+
+    fn ssthresh(&self) -> usize {
+        self.ssthresh
+    }
+}
+
+/// 固定窗口，不做拥塞控制
+#[derive(Debug, Clone)]
+pub struct NoCc {
+    window: usize,
+}
+
+impl NoCc {
+    pub fn new(window: usize) -> Self {
+        Self { window }
+    }
+}
+
+impl CongestionControl for NoCc {
+    fn on_ack(&mut self, _segments: usize, _rtt: Option<Duration>) {}
+
+    fn on_loss(&mut self, _kind: LossKind) {}
+
+    fn on_rto(&mut self) {}
+
+    fn on_ecn(&mut self) {}
+
+    fn window(&self) -> usize {
+        self.window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 脚本化事件，返回每个事件之后的 cwnd
+    enum Event {
+        Ack(usize),
+        FastRetransmit,
+        Rto,
+        Ecn,
+    }
+
+    fn trajectory(cc: &mut dyn CongestionControl, events: &[Event]) -> Vec<usize> {
+        events
+            .iter()
+            .map(|event| {
+                match event {
+                    Event::Ack(n) => cc.on_ack(*n, None),
+                    Event::FastRetransmit => cc.on_loss(LossKind::FastRetransmit),
+                    Event::Rto => cc.on_rto(),
+                    Event::Ecn => cc.on_ecn(),
+                }
+                cc.window()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_slow_start_then_rto() {
+        let mut reno = Reno::new(2);
+        // 每 RTT 确认整个窗口：2 -> 4 -> 8 -> 16
+        let cwnds = trajectory(&mut reno, &[Event::Ack(2), Event::Ack(4), Event::Ack(8)]);
+        assert_eq!(cwnds, vec![4, 8, 16]);
+
+        // 超时：ssthresh = 8，重新从 1 慢启动，到 8 后转入拥塞避免
+        let cwnds = trajectory(
+            &mut reno,
+            &[Event::Rto, Event::Ack(1), Event::Ack(2), Event::Ack(4), Event::Ack(1)],
+        );
+        assert_eq!(cwnds, vec![1, 2, 4, 8, 8]);
+        assert_eq!(reno.ssthresh(), 8);
+        assert!(!reno.in_slow_start());
+    }
+
+    #[test]
+    fn test_congestion_avoidance_is_additive() {
+        let mut reno = Reno::new(10);
+        reno.on_loss(LossKind::FastRetransmit);
+        assert_eq!((reno.window(), reno.ssthresh()), (5, 5));
+
+        // 每确认一整个窗口只加一
+        let cwnds = trajectory(&mut reno, &[Event::Ack(5), Event::Ack(6), Event::Ack(3), Event::Ack(4)]);
+        assert_eq!(cwnds, vec![6, 7, 7, 8]);
+
+        // 快速重传减半，不低于下限
+        let cwnds = trajectory(&mut reno, &[Event::FastRetransmit, Event::FastRetransmit, Event::FastRetransmit]);
+        assert_eq!(cwnds, vec![4, 2, 2]);
+    }
+
+    #[test]
+    fn test_implementations_diverge_on_same_trace() {
+        let trace = [Event::Ack(4), Event::Ack(8), Event::FastRetransmit, Event::Ack(6), Event::Rto, Event::Ack(1)];
+
+        let mut reno = CongestionAlgorithm::Reno.build(4);
+        assert_eq!(trajectory(reno.as_mut(), &trace), vec![8, 16, 8, 8, 1, 2]);
+
+        // 固定窗口对确认与丢包都不做反应
+        let mut nocc = CongestionAlgorithm::NoCc { window: 32 }.build(4);
+        assert_eq!(trajectory(nocc.as_mut(), &trace), vec![32; 6]);
+        assert_eq!(nocc.ssthresh(), usize::MAX);
+    }
+
+    #[test]
+    fn test_ecn_halves_window() {
+        let mut reno = Reno::new(4);
+        // 慢启动中收到拥塞信号：与快速重传一样减半并转入拥塞避免
+        let cwnds = trajectory(&mut reno, &[Event::Ack(4), Event::Ack(8), Event::Ecn, Event::Ack(8)]);
+        assert_eq!(cwnds, vec![8, 16, 8, 9]);
+        assert_eq!(reno.ssthresh(), 8);
+
+        let mut nocc = CongestionAlgorithm::NoCc { window: 32 }.build(4);
+        assert_eq!(trajectory(nocc.as_mut(), &[Event::Ecn]), vec![32]);
+    }
+}
@@ -0,0 +1,1869 @@
+//! 可靠连接
+//! `Connection` 是共享状态的句柄：协议状态（发送端、接收端、保活与状态机）是不做 IO 的 `endpoint::ConnectionCore`，
+//! 放在一把锁后面。
+//! 每个连接有自己的驱动任务，负责解码入站数据报、处理定时器（重传、延迟确认、保活）并发出响应：
+//! 监听器的分发任务（服务端）、连接自己的读取任务（客户端，`connect` 创建）或 `ClientEndpoint` 的分发任务
+//! （多条客户端连接共用一个套接字，见 `client_endpoint` 模块）只把原始数据报
+//! 经有界队列转交给它，队列已满时丢弃（等同于丢包），一个处理缓慢的连接不会拖住其他连接。
+//! 这些任务连续处理 `LinkConfig::recv_batch` 个数据报后主动让出执行权：内存传输与积压的套接字总有数据报可读，
+//! 单线程运行时上一条被灌满的连接不会饿死其他连接。
+//! 锁内只做纯计算，数据报由协议核心打包，释放锁之后再写套接字，不会在持锁时等待 IO。
+//! `send`/`recv` 在同一次轮询内完成状态变更，产生的段放进发件箱由驱动任务发出，
+//! 因此两者都是取消安全的：被丢弃的 `recv` 不会取走消息，被丢弃的 `send` 不会入队。`send_timeout`/`recv_timeout`
+//! 与 `*_deadline` 变体在此之上限时，到期时以 `LinkError::Timeout` 返回，连接状态与从未调用过一样。
+//! 它们与 `close` 都包装同名的 `poll_*` 方法，供手写的 `Future` 直接轮询。
+//! 连接失败时挂起的 `send`/`recv` 立即被唤醒并返回错误。读写分别在两个任务中进行时可以 `split` 成独占的两半（见 `split` 模块）。
+//! `futures` 特性下连接同时是流 0 消息的 `Stream` 与 `Sink`，`Sink` 一次缓存一条 `start_send` 交出的消息。
+//! 连接句柄被丢弃时驱动与读取任务随之退出（TIME_WAIT 中的连接等定时器到期后再退出）。
+//!
+//! 关闭写方向时先等已发送的数据全部被确认，再发出占用一个序列号的 FIN；FIN 被确认由发送端的重传队列判定，
+//! 之后以 `Action::FinAcked` 通知状态机。双方的 FIN 都被处理后进入 TIME_WAIT，`TIME_WAIT` 到期后关闭。
+//! `close` 把这一过程连同对端 FIN 的等待一起限制在 `linger` 之内；`shutdown_write` 只关闭写方向，
+//! 读方向继续交付对端的数据，对端的 FIN 到达后 `recv` 返回 `None`。
+//! `reset` 不经过这一过程，立即以携带应用关闭码的 Rst 结束连接，对端以 `LinkError::Application` 得到这个码（见 `close_codes` 模块）。
+//!
+//! 流 0 是连接自身（`send`/`recv`/`close`）；`open_stream`/`accept_stream` 打开的附加流各有独立的序列号空间、
+//! 发送窗口与重排缓冲区，段头的流 ID 区分它们。附加流的 Data/Ack/Fin 不经过连接状态机，
+//! 一个流的丢包重传与关闭不影响其他流。客户端使用奇数流 ID，服务端使用偶数流 ID；对端的新流在其第一个段
+//! 到达时登记并交给 `accept_stream`，引用本端从未打开的流视为协议错误。
+//!
+//! 客户端握手：发送携带新 ISN 的 SYN，从 `syn_retry_initial` 起按带抖动的指数退避重传，至多 `syn_max_retries` 次；
+//! 重传用尽，或超过 `handshake_timeout`（无论握手进行到哪一步）时以 `ConnectTimeout` 失败，`ConnectOptions` 可以逐次覆盖这三个参数。
+//! SYN-ACK 确认了错误的序列号时换一个 ISN 重试一次，仍然错误则以协议错误失败；收到 Rst 即被拒绝，Rst 携带的关闭码决定失败的原因。
+//! 监听器以 Retry 要求验证地址时，立即重发带回令牌的 SYN。
+//! `connect_with_data` 在每个 SYN 之后打包 0-RTT 数据段（本端的第一个数据段），建立之后再以同一个序列号照常发送，
+//! 对端是否接受了 0-RTT 数据都只交付一次。
+//! SYN-ACK 携带服务端分配的连接 ID，此后每个发出的段都打上它；对端地址可由监听器在迁移时更新。
+//! `rotate_conn_id` 把本端的连接 ID 换成新的（见 `rotation` 模块），服务端的连接换用后经 `Notice::Rotated`
+//! 告诉监听器，监听器据此更新连接表。
+//! 两个客户端同时互相连接（`connect_from` 绑定约定的端口）时双方的 SYN 交错：收到对端的 SYN 后回应 SYN-ACK，
+//! 收到对端的 SYN-ACK 或确认后建立，双方得到同一条连接（见 `Opener`）。
+//! 配置了预共享密钥时握手段都经过签名并交换双方的 nonce（见 `crypto` 模块），未通过认证的握手段视同丢失；
+//! 最后的确认丢失时对端重发 SYN-ACK，连接以同一个签名的确认回应。
+//!
+//! 设置了 `LinkConfig::max_mss` 时建立后即开始探测路径 MTU（见 `pmtu` 模块），探测以连接当前的 RTO 为超时；
+//! 有效 MSS 变化后各个流的合并、数据报打包与字节流的切分都按新的大小进行。
+//!
+//! `query_peer_stats` 以 StatsRequest 查询对端一侧的连接统计，按 nonce 匹配回应；对端每秒至多回应一次
+//! （见 `endpoint::STATS_REPLY_INTERVAL`），查询或回应丢失与被限速的查询都在 `STATS_QUERY_TIMEOUT` 后报告超时。
+//! `resync` 让重新开始读取的接收方告诉对端不必再重传积压的数据：对端从请求的位置（通常是它的最新位置）开始交付，
+//! 丢弃之前还没被确认的数据，本端丢弃回应的位置之前尚未读取的数据；回应丢失时在 `RESYNC_TIMEOUT` 后报告超时，可以再次请求。
+
+use crate::capture::Tap;
+use crate::checksum::ChecksumAlgorithm;
+use crate::close_codes::CloseCode;
+use crate::config::{ConfigError, LinkConfig};
+use crate::endpoint::{ConnectionCore, Event, Handshake, MAIN_STREAM, Opener, fresh_isn};
+use crate::error::{self, LinkError};
+use crate::gaps::{GapEvent, GapEvents};
+use crate::metrics::Metrics;
+use crate::observer::Observer;
+use crate::params::TransportParameters;
+use crate::pool::{BufferPool, RecvArena};
+use crate::receiver::TaggedMessage;
+use crate::segment::{self, Segment};
+use crate::sender::SendOptions;
+#[cfg(feature = "tokio")]
+use crate::socket;
+#[cfg(feature = "tokio")]
+use crate::tcp;
+use crate::socket::LinkSocket;
+use crate::split::{self, RecvHalf, SendHalf};
+use crate::state::ConnState;
+use crate::segment::SegmentError;
+use crate::seq::SeqNum;
+use crate::session::{SessionError, SessionState};
+use crate::stats::{ConnectionStats, PeerStats, StatsCell};
+use crate::stream::{LinkStream, LinkStreamIo};
+use crate::trace::{self, Direction, Role};
+use crate::tuning::{ConnOption, OptionName};
+use crate::transport::Transport;
+use crate::watermarks::WatermarkEvents;
+use bytes::{Bytes, BytesMut};
+use std::future::{pending, poll_fn};
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+#[cfg(feature = "futures")]
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::task::{Context, Poll, ready};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, Notify};
+#[cfg(feature = "tokio")]
+use tokio::net::ToSocketAddrs;
+use tokio::task::JoinHandle;
+#[cfg(feature = "tokio")]
+use tokio::task::JoinSet;
+use tracing::{Instrument, Span};
+
+/// 驱动层的当前时间；经由 tokio 取得，测试中可用 `tokio::time::pause` 控制
+pub(crate) fn now() -> Instant {
+    tokio::time::Instant::now().into_std()
+}
+
+/// 每个连接待处理的入站数据报队列长度
+pub(crate) const INBOUND_QUEUE: usize = 256;
+
+/// 对端解析出多个地址时，前一个地址的握手没有结果多久之后开始尝试下一个
+pub const CONNECT_STAGGER: Duration = Duration::from_millis(250);
+
+/// `query_peer_stats` 等待回应的时间
+pub const STATS_QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// `resync` 等待回应的时间
+pub const RESYNC_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 客户端套接字的绑定选项与握手的耐心，见 `Connection::connect_with_options`；握手参数为 None 时取 `LinkConfig` 中的值
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectOptions {
+    pub local_addr: Option<SocketAddr>, // 绑定的本地地址（源地址），端口为 0 时由系统分配；None 时绑定与对端同一地址族的任意地址
+    pub interface: Option<String>,      // 只经这个网络接口收发（SO_BINDTODEVICE，仅 Linux 与 Android）
+    pub syn_retry_interval_initial: Option<Duration>,   // 第一次重传 SYN 之前等待的时间，覆盖 `LinkConfig::syn_retry_initial`
+    pub syn_max_retries: Option<u32>,   // SYN 的最多重传次数，覆盖 `LinkConfig::syn_max_retries`
+    pub overall_timeout: Option<Duration>,  // connect 的总超时，覆盖 `LinkConfig::handshake_timeout`
+    pub transport: TransportKind,       // 经 UDP 还是 TCP 连接
+}
+
+/// 客户端使用的传输，见 `ConnectOptions::transport`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransportKind {
+    #[default]
+    Udp,
+    Tcp,    // 经 `tcp::TcpTransport` 连接，对端必须以 `Listener::bind_tcp` 监听同一端口；不使用 local_addr 与 interface
+    Auto,   // 先经 UDP 握手，超时（`ConnectTimeout`）后改经 TCP 连接同一地址，适合可能屏蔽 UDP 的网络
+}
+
+impl ConnectOptions {
+    // 以设置了的握手参数覆盖 `config`
+    #[cfg(feature = "tokio")]
+    fn configure(&self, mut config: LinkConfig) -> LinkConfig {
+        if let Some(initial) = self.syn_retry_interval_initial {
+            config.syn_retry_initial = initial;
+        }
+        if let Some(retries) = self.syn_max_retries {
+            config.syn_max_retries = retries;
+        }
+        if let Some(timeout) = self.overall_timeout {
+            config.handshake_timeout = timeout;
+        }
+        config
+    }
+}
+
+/// 一条已建立的可靠连接
+#[derive(Debug)]
+pub struct Connection {
+    shared: Arc<Shared>,
+    driver: JoinHandle<()>,
+    reader: Option<JoinHandle<()>>,     // 客户端连接独占套接字时的读取任务
+    #[cfg(feature = "futures")]
+    sink: Option<Bytes>,                // `Sink::start_send` 交出、尚未进入发送队列的消息
+}
+
+impl Connection {
+    /// 以默认参数连接到 `remote`
+    #[cfg(feature = "tokio")]
+    pub async fn connect(remote: impl ToSocketAddrs) -> Result<Connection, LinkError> {
+        Self::connect_with(remote, LinkConfig::default()).await
+    }
+
+    /// 解析 `remote`（地址或 `host:port`），为每个地址绑定同一地址族的临时端口并握手，返回第一个建立的连接。
+    /// 各地址按解析结果的顺序尝试：前一个失败时立即、没有结果时过 `CONNECT_STAGGER` 开始下一个，已开始的握手继续进行。
+    /// 只解析出一个地址时返回它的错误，多个地址都失败时返回 `ConnectFailed`，列出每个地址及其错误
+    #[cfg(feature = "tokio")]
+    pub async fn connect_with(remote: impl ToSocketAddrs, config: LinkConfig) -> Result<Connection, LinkError> {
+        config.validate()?;
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host(remote).await?.collect();
+        match addrs[..] {
+            [] => Err(io::Error::new(io::ErrorKind::InvalidInput, "could not resolve to any address").into()),
+            [remote] => Self::connect_with_options(remote, config, ConnectOptions::default()).await,
+            _ => Self::connect_any(addrs, config).await,
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn connect_any(addrs: Vec<SocketAddr>, config: LinkConfig) -> Result<Connection, LinkError> {
+        let mut remaining = addrs.into_iter();
+        let mut attempts = JoinSet::new();
+        let mut failures = Vec::new();
+        let start = |attempts: &mut JoinSet<_>, remote: SocketAddr| {
+            let config = config.clone();
+            attempts.spawn(async move { (remote, Self::connect_with_options(remote, config, ConnectOptions::default()).await) });
+        };
+        start(&mut attempts, remaining.next().expect("at least two addresses"));
+        loop {
+            // 返回时 JoinSet 被丢弃，仍在进行的握手随之中止
+            tokio::select! {
+                Some(joined) = attempts.join_next() => {
+                    let (remote, result) = joined.expect("connect attempt panicked");
+                    match result {
+                        Ok(connection) => return Ok(connection),
+                        Err(e) => failures.push((remote, e)),
+                    }
+                    match remaining.next() {
+                        Some(next) => start(&mut attempts, next),
+                        None if attempts.is_empty() => return Err(LinkError::ConnectFailed(failures)),
+                        None => {}
+                    }
+                }
+                _ = tokio::time::sleep(CONNECT_STAGGER), if remaining.len() > 0 => {
+                    start(&mut attempts, remaining.next().expect("checked above"));
+                }
+            }
+        }
+    }
+
+    /// 从指定的本地地址连接；两端互相 `connect_from` 对方的地址时按同时打开建立同一条连接
+    #[cfg(feature = "tokio")]
+    pub async fn connect_from(local: SocketAddr, remote: SocketAddr, config: LinkConfig) -> Result<Connection, LinkError> {
+        Self::connect_with_options(remote, config, ConnectOptions { local_addr: Some(local), ..ConnectOptions::default() }).await
+    }
+
+    /// 按 `options` 绑定客户端套接字后握手，多宿主机上以此选择出口的源地址或网络接口，或为这一次连接调整 SYN 重传与总超时。
+    /// 本地地址不属于本机时返回 `AddrNotLocal`，平台不支持绑定接口时返回 `InterfaceUnsupported`，
+    /// 参数未通过 `LinkConfig::validate` 时返回 `LinkError::Config`
+    #[cfg(feature = "tokio")]
+    pub async fn connect_with_options(remote: SocketAddr, config: LinkConfig, options: ConnectOptions) -> Result<Connection, LinkError> {
+        let config = options.configure(config);
+        config.validate()?;
+        match options.transport {
+            TransportKind::Udp => {}
+            TransportKind::Tcp => return Self::connect_tcp(remote, config).await,
+            TransportKind::Auto => {
+                let udp = ConnectOptions { transport: TransportKind::Udp, ..options };
+                return match Box::pin(Self::connect_with_options(remote, config.clone(), udp)).await {
+                    Err(LinkError::ConnectTimeout { attempts, .. }) => {
+                        tracing::debug!(%remote, attempts, "udp handshake timed out, falling back to tcp");
+                        Self::connect_tcp(remote, config).await
+                    }
+                    result => result,
+                };
+            }
+        }
+        let any: SocketAddr = if remote.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+        let local = options.local_addr.unwrap_or(any);
+        let socket = socket::bind_client(local, remote, &config, options.interface.as_deref())?;
+        socket.connect(remote).await?;
+        Self::open(LinkSocket::new(socket, &config), remote, config, None).await
+    }
+
+    // 经 TCP 连接，重传与拥塞控制改为直通（见 `tcp::stream_config`）
+    #[cfg(feature = "tokio")]
+    async fn connect_tcp(remote: SocketAddr, config: LinkConfig) -> Result<Connection, LinkError> {
+        let config = tcp::stream_config(config);
+        let transport = tcp::TcpTransport::connect(remote).await?;
+        Self::open(LinkSocket::new(transport, &config), remote, config, None).await
+    }
+
+    /// 连接到 `remote`，`data` 作为 0-RTT 数据与 SYN 同在一个数据报中发出：对端打开了 `accept_early_data` 时
+    /// 它在握手完成之前就被交付，省去一个往返；否则在建立之后照常发送。两种情况下对端都只收到一次，
+    /// 之后的 `send` 排在它之后。0-RTT 数据在对端验证地址之前就被处理，可能被重放，只应携带幂等的请求。
+    /// 放不进一个握手数据报时返回 `MessageTooLarge`；配置了预共享密钥时总是在建立之后发送
+    #[cfg(feature = "tokio")]
+    pub async fn connect_with_data(remote: SocketAddr, config: LinkConfig, data: Bytes) -> Result<Connection, LinkError> {
+        config.validate()?;
+        let any: SocketAddr = if remote.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+        let socket = socket::bind_client(any, remote, &config, None)?;
+        socket.connect(remote).await?;
+        Self::open(LinkSocket::new(socket, &config), remote, config, Some(data)).await
+    }
+
+    /// 经调用方提供的传输（如 `transport::MemoryTransport`）连接到 `remote`；连接独占这个传输，
+    /// 只处理来自 `remote` 的数据报
+    pub async fn connect_over(transport: impl Transport, remote: SocketAddr, config: LinkConfig) -> Result<Connection, LinkError> {
+        config.validate()?;
+        Self::open(LinkSocket::new(transport, &config), remote, config, None).await
+    }
+
+    /// 经调用方提供的传输连接，携带 0-RTT 数据，见 `connect_with_data`
+    pub async fn connect_over_with_data(transport: impl Transport, remote: SocketAddr, config: LinkConfig, data: Bytes) -> Result<Connection, LinkError> {
+        config.validate()?;
+        Self::open(LinkSocket::new(transport, &config), remote, config, Some(data)).await
+    }
+
+    async fn open(socket: LinkSocket, remote: SocketAddr, config: LinkConfig, early: Option<Bytes>) -> Result<Connection, LinkError> {
+        let socket = Arc::new(socket);
+        let tap = match &config.capture {
+            Some(capture) => Some(capture.tap(socket.local_addr()?)),
+            None => None,
+        };
+
+        let span = trace::connection_span(Role::Client, remote);
+        let start = tokio::time::Instant::now();
+        let attempts = AtomicU32::new(0);
+        let mut path = Exclusive { socket: &socket, buf: vec![0u8; config.recv_buffer] };
+        let handshake = async {
+            match handshake(&mut path, tap.as_ref(), remote, &config, early.as_ref(), &attempts, start).await {
+                Err(LinkError::Protocol(_)) => handshake(&mut path, tap.as_ref(), remote, &config, early.as_ref(), &attempts, start).await,
+                result => result,
+            }
+        };
+        // 总超时到期时无论握手进行到哪一步都放弃
+        let handshake = tokio::time::timeout_at(start + config.handshake_timeout, handshake.instrument(trace::handshake_span(&span)))
+            .await
+            .unwrap_or_else(|_| Err(LinkError::ConnectTimeout { attempts: attempts.load(Ordering::Relaxed), elapsed: start.elapsed() }))?;
+        let pool = Arc::new(BufferPool::new(&config));
+        let mut connection = Self::establish(Outlet::Udp { socket, tap, pool }, remote, &config, handshake, span.clone(), None, None);
+        connection.reader = Some(tokio::spawn(read_loop(connection.shared.clone()).instrument(span)));
+        // 0-RTT 数据总是作为第一个数据段再发一次：对端已经收到时序列号相同，作为重复段丢弃
+        if let Some(data) = early {
+            connection.send(data).await?;
+        }
+        Ok(connection)
+    }
+
+    /// 握手完成后构造连接并启动驱动任务，驱动任务在连接的 span 中运行；数据段从双方各自的 ISN + 1 开始编号
+    pub(crate) fn establish(
+        outlet: Outlet,
+        peer: SocketAddr,
+        config: &LinkConfig,
+        handshake: Handshake,
+        span: Span,
+        reaper: Option<Reaper>,
+        metrics: Option<Arc<Metrics>>,
+    ) -> Self {
+        let core = ConnectionCore::new(handshake, config, peer, outlet.pool().clone(), now());
+        Self::start(outlet, core, config, span, reaper, metrics)
+    }
+
+    /// 从导出的状态接续连接并启动驱动任务（见 `session` 模块），驱动任务随即发出接续时的确认
+    pub(crate) fn adopt(
+        outlet: Outlet,
+        state: SessionState,
+        config: &LinkConfig,
+        span: Span,
+        reaper: Option<Reaper>,
+        metrics: Option<Arc<Metrics>>,
+    ) -> Result<Self, SessionError> {
+        let core = ConnectionCore::restore(state, config, outlet.pool().clone(), now())?;
+        let connection = Self::start(outlet, core, config, span, reaper, metrics);
+        connection.shared.timer.notify_one();
+        Ok(connection)
+    }
+
+    fn start(outlet: Outlet, core: ConnectionCore, config: &LinkConfig, span: Span, reaper: Option<Reaper>, metrics: Option<Arc<Metrics>>) -> Self {
+        let (inbound_tx, inbound_rx) = mpsc::channel(INBOUND_QUEUE);
+        let (conn_id, checksum, peer_isn) = (core.conn_id(), core.checksum(), core.peer_isn());
+        let stats = StatsCell::default();
+        stats.publish(&core.stats());
+        let shared = Arc::new(Shared {
+            core: Mutex::new(core),
+            local: outlet.local_addr().ok(),
+            outlet,
+            conn_id: AtomicU32::new(conn_id),
+            checksum,
+            peer_isn,
+            linger: config.linger,
+            recv_buffer: config.recv_buffer,
+            recv_batch: config.recv_batch,
+            timer: Notify::new(),
+            done: Notify::new(),
+            inbound: inbound_tx,
+            reaper,
+            metrics,
+            observer: config.observer.clone(),
+            stats,
+            pongs: Mutex::new(None),
+            gaps: Mutex::new(None),
+            stats_queries: Mutex::new(HashMap::new()),
+            resyncs: Mutex::new(Vec::new()),
+            next_stats_query: AtomicU64::new(1),
+            byte_stream: AtomicBool::new(false),
+            exported: AtomicBool::new(false),
+        });
+        span.record("conn_id", conn_id);
+        let driver = tokio::spawn(drive(shared.clone(), inbound_rx).instrument(span));
+        Self {
+            shared,
+            driver,
+            reader: None,
+            #[cfg(feature = "futures")]
+            sink: None,
+        }
+    }
+
+    /// 分发任务与字节流适配使用的共享状态
+    pub(crate) fn shared(&self) -> &Arc<Shared> {
+        &self.shared
+    }
+
+    /// 对端的当前地址；服务端的连接在对端迁移后返回新地址
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.shared.peer_addr()
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, LinkError> {
+        self.shared.outlet.local_addr()
+    }
+
+    /// 握手时与对端协商出的校验算法
+    pub fn checksum(&self) -> ChecksumAlgorithm {
+        self.shared.checksum
+    }
+
+    /// 握手时与对端协商出的参数（见 `params` 模块）；确认频率与保活间隔经 `set_option` 调整后报告本端现在使用的值
+    pub fn negotiated(&self) -> TransportParameters {
+        self.shared.lock().negotiated()
+    }
+
+    /// 在连接运行期间调整一项参数（见 `tuning` 模块）：在连接的锁内一步完成，驱动任务随即按新的值计时与发送。
+    /// 与 `rotate_conn_id`、`close` 一样不经控制消息转交驱动任务：驱动任务处理入站段与定时器时同样持有这把锁，
+    /// 调整不会与它交错；直接在调用处完成，错误可以同步返回，返回后 `get_option` 立即读到新的值。
+    /// MSS 与校验算法由握手决定，返回 `NotAdjustable`；取值超出范围时返回 `Config`；两者都不影响连接
+    pub fn set_option(&self, option: ConnOption) -> Result<(), LinkError> {
+        self.shared.set_option(option)
+    }
+
+    /// 参数 `name` 的当前值
+    pub fn get_option(&self, name: OptionName) -> ConnOption {
+        self.shared.lock().get_option(name)
+    }
+
+    /// 对端发给本端的段当前携带的连接 ID；`rotate_conn_id` 之后对端确认时改变
+    pub fn conn_id(&self) -> u32 {
+        self.shared.conn_id()
+    }
+
+    /// 轮换本端的连接 ID，返回新的 ID：对端确认后它发来的段改用新的 ID，旧 ID 在 `LinkConfig::conn_id_grace`
+    /// 后退役（见 `rotation` 模块）。上一次轮换还没被确认时返回那一次的 ID；连接失败或已开始关闭时返回错误
+    pub fn rotate_conn_id(&self) -> Result<u32, LinkError> {
+        self.shared.rotate_conn_id()
+    }
+
+    /// 服务端：握手由随 SYN 到达的 0-RTT 数据完成（见 `connect_with_data`），`recv` 交付的第一条消息就是它。
+    /// 它在对端地址得到验证之前被接受，可能是重放的数据报
+    pub fn accepted_early_data(&self) -> bool {
+        self.shared.lock().accepted_early_data()
+    }
+
+    /// 半关闭：交出暂存的写入，等已发送的数据全部被确认后发送 FIN，在 FIN 被确认时完成。
+    /// 之后 `send` 返回 `WriteClosed`，`recv` 照常交付对端的数据，直到对端关闭后返回 `None`。
+    pub async fn shutdown_write(&self) -> Result<(), LinkError> {
+        poll_fn(|cx| self.shared.poll_shutdown(MAIN_STREAM, cx)).await
+    }
+
+    /// 优雅关闭：等待已发送的数据全部被确认，发送 FIN 并等待它被确认与对端的 FIN，确认后完成。
+    /// 整个过程受 `LinkConfig::linger` 限制，超时则向对端发送 Rst 并返回 `CloseTimedOut`。
+    /// 关闭开始后 `send` 一律返回 `Closed`；关闭期间收到的数据被丢弃。
+    pub async fn close(self) -> Result<(), LinkError> {
+        let deadline = tokio::time::Instant::now() + self.shared.linger;
+        match tokio::time::timeout_at(deadline, poll_fn(|cx| self.poll_close(cx))).await {
+            Ok(result) => result,
+            Err(_) => {
+                self.shared.lock().reset(LinkError::CloseTimedOut);
+                self.shared.flush().await;
+                Err(LinkError::CloseTimedOut)
+            }
+        }
+    }
+
+    /// 以 `error` 中止连接：立即向对端发出 Rst，不等待已发送的数据被确认
+    pub(crate) async fn abort(&self, error: LinkError) {
+        self.shared.lock().reset(error);
+        self.shared.flush().await;
+    }
+
+    /// 以应用区的关闭码 `code` 中止连接：立即向对端发出携带它的 Rst，不等待已发送的数据被确认。
+    /// 之后两端的操作都以 `LinkError::Application(code)` 失败。`code` 不在应用区时返回 `Config`，连接不受影响
+    pub async fn reset(&self, code: CloseCode) -> Result<(), LinkError> {
+        if !code.is_application() {
+            return Err(ConfigError::InvalidValue { key: "close_code".to_string(), value: code.to_string(), expected: "an application close code" }.into());
+        }
+        self.abort(LinkError::Application(code)).await;
+        Ok(())
+    }
+
+    /// `close` 的轮询形式，不受 `linger` 限制（需要时由调用方限时）：第一次轮询开始关闭，
+    /// FIN 被确认且对端的 FIN 到达后就绪。未就绪时登记本次轮询的 waker，取代之前登记的
+    pub fn poll_close(&self, cx: &mut Context<'_>) -> Poll<Result<(), LinkError>> {
+        self.shared.lock().begin_close();
+        ready!(self.shared.poll_shutdown(MAIN_STREAM, cx))?;
+        self.shared.poll_peer_fin(cx)
+    }
+
+    /// 当前的有效 MSS：`LinkConfig::mss`，打开路径 MTU 探测时随探测结果变化
+    pub fn mss(&self) -> usize {
+        self.shared.mss()
+    }
+
+    pub fn state(&self) -> ConnState {
+        self.shared.lock().state()
+    }
+
+    /// 流 0 上已交给连接、尚未被对端累计确认的消息，按发送顺序。连接失败后可以据此在新连接上重发，
+    /// 其中一些可能已经到达对端（只是确认丢失），重发是至少一次的
+    pub fn unacknowledged(&self) -> Vec<Bytes> {
+        self.shared.lock().main.sender.unacknowledged()
+    }
+
+    /// 流 0 的统计快照：驱动任务发布的原子变量，只为读出对端的当前地址短暂取锁，反映驱动任务最近处理完的事件
+    pub fn stats(&self) -> ConnectionStats {
+        self.shared.stats.load(self.shared.local, self.shared.peer_addr())
+    }
+
+    /// 向对端查询它一侧的连接统计（流 0）：`STATS_QUERY_TIMEOUT` 内没有回应时返回 `StatsTimedOut`，
+    /// 回应中的统计无法解码时返回 `Segment(InvalidStats)`
+    pub async fn query_peer_stats(&self) -> Result<PeerStats, LinkError> {
+        let nonce = self.shared.next_stats_query.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.shared.stats_queries().insert(nonce, tx);
+        let sent = self.shared.lock().stats_request(nonce);
+        if let Err(e) = sent {
+            self.shared.stats_queries().remove(&nonce);
+            return Err(e);
+        }
+        self.shared.flush().await;
+        let reply = tokio::time::timeout(STATS_QUERY_TIMEOUT, rx).await;
+        self.shared.stats_queries().remove(&nonce);
+        match reply {
+            Ok(Ok(stats)) => Ok(stats?),
+            _ => Err(LinkError::StatsTimedOut),
+        }
+    }
+
+    /// 请求对端从 `from` 开始发送流 0 的数据，None 表示从对端的最新位置开始：对端放弃之前还没被确认的数据，
+    /// 本端丢弃之前尚未读取的消息，返回对端开始交付的序列号（起点在消息中间时是这条消息之后）。起点早于对端仍保留的
+    /// 最早数据时返回 `ResyncRefused`，`RESYNC_TIMEOUT` 内没有回应时返回 `ResyncTimedOut`；同时进行的请求共享一个回应
+    pub async fn resync(&self, from: Option<SeqNum>) -> Result<SeqNum, LinkError> {
+        let (tx, rx) = oneshot::channel();
+        self.shared.resyncs().push(tx);
+        let sent = self.shared.lock().resync(from);
+        let reply = match sent {
+            Ok(()) => {
+                self.shared.flush().await;
+                tokio::time::timeout(RESYNC_TIMEOUT, rx).await.map_err(|_| LinkError::ResyncTimedOut)
+            }
+            Err(e) => {
+                drop(rx);
+                Err(e)
+            }
+        };
+        // 超时或没能发出的请求不再等待
+        self.shared.resyncs().retain(|waiter| !waiter.is_closed());
+        reply?.unwrap_or(Err(LinkError::ResyncTimedOut))
+    }
+
+    /// 把消息放进发送队列，在队列容纳它时完成（不等待确认）；队列中等待窗口与已发送未确认的数据
+    /// 超过 `LinkConfig::send_buffer` 时等待，连接失败或不再允许发送时返回错误
+    pub async fn send(&self, data: Bytes) -> Result<(), LinkError> {
+        poll_fn(|cx| self.poll_send(cx, &data)).await
+    }
+
+    /// `send` 的轮询形式：发送队列容纳 `data` 时放入并就绪，否则登记本次轮询的 waker（取代之前登记的）、
+    /// 在队列腾出空间时唤醒。未就绪时不保留 `data`，被唤醒后以同一条消息再次轮询；
+    /// 虚假唤醒后的轮询只是再次返回 `Pending`
+    pub fn poll_send(&self, cx: &mut Context<'_>, data: &Bytes) -> Poll<Result<(), LinkError>> {
+        self.message_mode()?;
+        self.shared.poll_send(MAIN_STREAM, cx, &mut Some(data.clone()), SendOptions::default())
+    }
+
+    /// 以 `options` 发送一条消息，其余同 `send`；`ordered: false` 的消息仍可靠，但完整到达即交付，
+    /// 不等在它之前发出的消息。设置了 `ttl` 的消息过期时被放弃，对端跳过它（计入对端的 `ReceiverStats::skipped`）
+    pub async fn send_with(&self, data: Bytes, options: SendOptions) -> Result<(), LinkError> {
+        self.message_mode()?;
+        let mut data = Some(data);
+        poll_fn(|cx| self.shared.poll_send(MAIN_STREAM, cx, &mut data, options)).await
+    }
+
+    /// 发送一条任意大小的消息：放不进一个段时分片发送，对端的 `recv`/`recv_msg` 收齐后作为一条消息交付。
+    /// 超过 `LinkConfig::max_message` 的消息返回 `MessageTooLarge`；发送队列按整条消息等待，其余同 `send`
+    pub async fn send_msg(&self, data: Bytes) -> Result<(), LinkError> {
+        self.message_mode()?;
+        let mut data = Some(data);
+        poll_fn(|cx| self.shared.poll_send_message(MAIN_STREAM, cx, &mut data, SendOptions::default())).await
+    }
+
+    /// 以 `options` 分片发送一条消息，其余同 `send_msg`；分片消息总是按序交付，设置了 `ttl` 时过期的消息整条放弃
+    pub async fn send_msg_with(&self, data: Bytes, options: SendOptions) -> Result<(), LinkError> {
+        self.message_mode()?;
+        let mut data = Some(data);
+        poll_fn(|cx| self.shared.poll_send_message(MAIN_STREAM, cx, &mut data, options)).await
+    }
+
+    /// 限时的 `send`：`timeout` 内发送队列没能容纳消息时返回 `Timeout`，消息没有入队，可以原样重试
+    pub async fn send_timeout(&self, data: Bytes, timeout: Duration) -> Result<(), LinkError> {
+        self.send_deadline(data, tokio::time::Instant::now() + timeout).await
+    }
+
+    /// 在 `deadline` 之前完成的 `send`，其余同 `send_timeout`；期限已过时仍先尝试一次，队列有空间就入队
+    pub async fn send_deadline(&self, data: Bytes, deadline: tokio::time::Instant) -> Result<(), LinkError> {
+        tokio::time::timeout_at(deadline, self.send(data)).await.unwrap_or(Err(LinkError::Timeout))
+    }
+
+    /// 不等待的 `send`：发送队列已满时返回 `WouldBlock`
+    pub fn try_send(&self, data: Bytes) -> Result<(), LinkError> {
+        self.message_mode()?;
+        self.shared.try_send(MAIN_STREAM, data)
+    }
+
+    /// 等待下一个按序到达的消息；已到达的消息先于连接错误交付，对端关闭写方向后返回 `None`
+    pub async fn recv(&self) -> Result<Option<Bytes>, LinkError> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// 限时的 `recv`：`timeout` 内没有消息到达时返回 `Timeout`，之后到达的消息留给下一次读取
+    pub async fn recv_timeout(&self, timeout: Duration) -> Result<Option<Bytes>, LinkError> {
+        self.recv_deadline(tokio::time::Instant::now() + timeout).await
+    }
+
+    /// 在 `deadline` 之前完成的 `recv`，其余同 `recv_timeout`；期限已过时仍交付已经到达的消息
+    pub async fn recv_deadline(&self, deadline: tokio::time::Instant) -> Result<Option<Bytes>, LinkError> {
+        tokio::time::timeout_at(deadline, self.recv()).await.unwrap_or(Err(LinkError::Timeout))
+    }
+
+    /// `recv` 的轮询形式：有按序到达的消息时取出并就绪，对端关闭写方向后就绪为 `None`；
+    /// 否则登记本次轮询的 waker（取代之前登记的），在数据、FIN 或连接错误到达时唤醒
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Result<Option<Bytes>, LinkError>> {
+        self.message_mode()?;
+        self.shared.poll_recv(MAIN_STREAM, cx)
+    }
+
+    /// 等待下一条完整的消息，对端以 `send_msg` 分片发送的消息收齐后才交付；对端关闭写方向后返回 `Closed`
+    pub async fn recv_msg(&self) -> Result<Bytes, LinkError> {
+        poll_fn(|cx| self.poll_recv(cx)).await?.ok_or(LinkError::Closed)
+    }
+
+    /// 同 `recv_msg`，连同对端以 `SendOptions::tag` 附上的标签一起交付；没有标签的消息（包括不认识 tag 选项的旧版本对端发来的）为 None
+    pub async fn recv_msg_tagged(&self) -> Result<(Option<u32>, Bytes), LinkError> {
+        self.message_mode()?;
+        poll_fn(|cx| self.shared.poll_recv_tagged(MAIN_STREAM, cx)).await?.ok_or(LinkError::Closed)
+    }
+
+    /// 不可靠地发送一条消息（见 `unreliable` 模块）：不重传、不等待，对端以 `recv_unreliable` 接收；
+    /// 等待发出的不可靠消息过多时返回 `WouldBlock`
+    pub fn send_unreliable(&self, data: Bytes) -> Result<(), LinkError> {
+        let mut core = self.shared.lock();
+        core.send_unreliable(data)?;
+        self.shared.wake_driver(&core);
+        Ok(())
+    }
+
+    /// 等待下一条到达的不可靠消息，按到达顺序交付；对端关闭写方向或连接结束后返回 `None`
+    pub async fn recv_unreliable(&self) -> Result<Option<Bytes>, LinkError> {
+        poll_fn(|cx| self.shared.lock().poll_recv_unreliable(cx)).await
+    }
+
+    /// 此后收到的 Pong 转交给返回的通道，取代之前的订阅（见 `ping::Pinger`）
+    pub(crate) fn subscribe_pongs(&self) -> mpsc::UnboundedReceiver<(u64, Instant)> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        *self.shared.pongs.lock().expect("pong subscriber poisoned") = Some(tx);
+        rx
+    }
+
+    /// 订阅流 0 的重排缓冲区中空洞的出现与结束（见 `gaps` 模块），取代之前的订阅；按序交付不受影响
+    pub fn subscribe_gaps(&self) -> GapEvents {
+        let (tx, rx) = mpsc::unbounded_channel();
+        *self.shared.gaps.lock().expect("gap subscriber poisoned") = Some(tx);
+        self.shared.lock().track_gaps();
+        GapEvents::new(rx)
+    }
+
+    /// 订阅流 0 的发送队列与重排缓冲区越过 `LinkConfig::send_watermarks`/`recv_watermarks` 的事件（见 `watermarks` 模块），
+    /// 取代之前的订阅；已在高水位之上的队列先报告一次 `High`
+    pub fn subscribe_watermarks(&self) -> WatermarkEvents {
+        self.shared.lock().subscribe_watermarks(now())
+    }
+
+    /// 流 0 的发送队列当前的字节数：暂存的写入与已发送未确认的数据体，`send_buffer` 限制的就是它
+    pub fn send_queue_len(&self) -> usize {
+        self.shared.lock().send_queue_len()
+    }
+
+    /// 流 0 的重排缓冲区当前的段数：已收到、尚未被读出的段，`recv_window` 限制的就是它
+    pub fn recv_buffer_len(&self) -> usize {
+        self.shared.lock().recv_buffer_len()
+    }
+
+    /// 立即发送一个携带 `nonce` 的 Ping，不经过发送队列；连接已失败或已关闭时返回错误
+    pub(crate) async fn send_ping(&self, nonce: u64) -> Result<(), LinkError> {
+        self.shared.lock().ping(nonce)?;
+        self.shared.flush().await;
+        Ok(())
+    }
+
+    /// 打开一个新的流：客户端分配奇数 ID，服务端分配偶数 ID。不需要往返，对端在收到这个流的第一个段时得知它；
+    /// 本端的流 ID 用完时返回 `StreamsExhausted`
+    pub fn open_stream(&self) -> Result<LinkStream, LinkError> {
+        let id = self.shared.lock().open_stream()?;
+        Ok(LinkStream::new(self.shared.clone(), id))
+    }
+
+    /// 等待对端打开的下一个流
+    pub async fn accept_stream(&self) -> Result<LinkStream, LinkError> {
+        let id = poll_fn(|cx| self.shared.lock().poll_accept(cx)).await?;
+        Ok(LinkStream::new(self.shared.clone(), id))
+    }
+
+    /// 拆成各自独占的读写两半（见 `split` 模块），分别交给读任务与写任务；丢弃 `SendHalf` 时关闭写方向
+    pub fn split(self) -> (RecvHalf, SendHalf) {
+        split::split(self)
+    }
+
+    /// 停下连接并导出它的状态，供另一个进程以 `Listener::adopt` 接续（见 `session` 模块）。先停下驱动与读取任务：
+    /// 此后本端不再确认对端的任何段，也不发出 Rst，监听器接受的连接在监听器中留下不回应的墓碑，交接期间对端的段被静默丢弃。
+    /// 连接不能导出时返回 `Session(NotExportable)`，连接不受影响；停下的过程中才变得不能导出时（如对端的 FIN 恰好到达）
+    /// 同样返回它，连接已经停下
+    pub async fn export_state(mut self) -> Result<SessionState, LinkError> {
+        self.shared.lock().exportable()?;
+        // 驱动任务退出时监听器据此立墓碑，须在停下之前标记
+        self.shared.exported.store(true, Ordering::Relaxed);
+        self.driver.abort();
+        let _ = (&mut self.driver).await;
+        if let Some(reader) = &mut self.reader {
+            reader.abort();
+            let _ = reader.await;
+        }
+        Ok(self.shared.lock().export()?)
+    }
+
+    /// 转换为字节流（见 `stream` 模块），供 `AsyncRead`/`AsyncWrite` 的使用方；之后经 `LinkStreamIo::get_ref`
+    /// 按消息收发主流返回 `ByteStreamMode`，附加流不受影响
+    pub fn into_byte_stream(self) -> LinkStreamIo {
+        self.shared.byte_stream.store(true, Ordering::Relaxed);
+        LinkStreamIo::new(self)
+    }
+
+    // 主流的字节被字节流读走一半时，按消息读出的只是残片
+    fn message_mode(&self) -> Result<(), LinkError> {
+        match self.shared.byte_stream.load(Ordering::Relaxed) {
+            true => Err(LinkError::ByteStreamMode),
+            false => Ok(()),
+        }
+    }
+}
+
+/// 按序交付流 0 的消息，对端关闭写方向后结束；连接错误作为一项交付
+#[cfg(feature = "futures")]
+impl futures_core::Stream for Connection {
+    type Item = Result<Bytes, LinkError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_recv(cx).map(Result::transpose)
+    }
+}
+
+/// 在流 0 上以 `send_msg` 发送消息：`poll_ready` 等发送队列容纳上一条消息，`poll_flush` 等已发送的数据全部被确认，
+/// `poll_close` 同 `shutdown_write`，之后仍可作为 `Stream` 读到对端关闭
+#[cfg(feature = "futures")]
+impl futures_sink::Sink<Bytes> for Connection {
+    type Error = LinkError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), LinkError>> {
+        self.get_mut().poll_sink(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Bytes) -> Result<(), LinkError> {
+        self.message_mode()?;
+        self.get_mut().sink = Some(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), LinkError>> {
+        let this = self.get_mut();
+        ready!(this.poll_sink(cx))?;
+        this.shared.poll_flush(MAIN_STREAM, cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), LinkError>> {
+        let this = self.get_mut();
+        ready!(this.poll_sink(cx))?;
+        this.shared.poll_shutdown(MAIN_STREAM, cx)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl Connection {
+    // 把 `start_send` 交出的消息放进发送队列；被拒绝的消息随错误丢弃
+    fn poll_sink(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), LinkError>> {
+        if self.sink.is_none() {
+            return Poll::Ready(Ok(()));
+        }
+        let sent = ready!(self.shared.poll_send_message(MAIN_STREAM, cx, &mut self.sink, SendOptions::default()));
+        self.sink = None;
+        Poll::Ready(sent)
+    }
+}
+
+impl Drop for Connection {
+    // TIME_WAIT 中的连接留给驱动任务处理完迟到的 FIN，到期后驱动与读取任务自行退出
+    fn drop(&mut self) {
+        if self.shared.lock().state() == ConnState::TimeWait {
+            return;
+        }
+        self.driver.abort();
+        if let Some(reader) = &self.reader {
+            reader.abort();
+        }
+    }
+}
+
+/// 连接发出数据报的去处，以及收发数据报所用的缓冲池
+#[derive(Debug)]
+pub(crate) enum Outlet {
+    // 客户端连接独占的套接字
+    Udp { socket: Arc<LinkSocket>, tap: Option<Tap>, pool: Arc<BufferPool> },
+    // 交给单一的发送任务（监听器），或测试中转发到对端的任务；随数据报交出连接的有效 MSS，
+    // 发送任务合并数据报时不超过它（见 `batch` 模块），以及发出它的连接，数据报过大时交还给它。缓冲由接收方归还
+    Channel { tx: mpsc::Sender<Outgoing>, local: SocketAddr, pool: Arc<BufferPool> },
+}
+
+/// 交给发送任务的数据报：目的地址、发出它的连接当时的有效 MSS，与发出它的连接（监听器自己的回应为 None）
+pub(crate) type Outgoing = (BytesMut, SocketAddr, usize, Option<Weak<Shared>>);
+
+impl Outlet {
+    fn local_addr(&self) -> Result<SocketAddr, LinkError> {
+        match self {
+            Outlet::Udp { socket, .. } => Ok(socket.local_addr()?),
+            Outlet::Channel { local, .. } => Ok(*local),
+        }
+    }
+
+    fn pool(&self) -> &Arc<BufferPool> {
+        match self {
+            Outlet::Udp { pool, .. } | Outlet::Channel { pool, .. } => pool,
+        }
+    }
+
+    // 除数据报过大（交还给 `origin` 重新分片）之外，发送失败等同于丢包
+    async fn send(&self, datagram: BytesMut, peer: SocketAddr, mss: usize, origin: &Arc<Shared>) {
+        match self {
+            Outlet::Udp { socket, tap, pool } => {
+                match socket.send_to(&datagram, peer).await {
+                    Ok(_) => {
+                        if let Some(tap) = tap {
+                            tap.record(Direction::Outbound, peer, &datagram);
+                        }
+                    }
+                    Err(e) if error::is_too_large(&e) => {
+                        origin.on_too_large(&datagram);
+                    }
+                    Err(_) => {}
+                }
+                pool.put(datagram);
+            }
+            Outlet::Channel { tx, .. } => {
+                let _ = tx.send((datagram, peer, mss, Some(Arc::downgrade(origin)))).await;
+            }
+        }
+    }
+}
+
+/// 交给监听器分发任务的通知，经同一个通道按发生的顺序到达
+#[derive(Debug)]
+pub(crate) enum Notice {
+    /// 驱动任务退出（连接终止或句柄被丢弃），监听器据此把它移出连接表
+    Reaped(Arc<Shared>),
+    /// 连接的 ID 从 `old` 换成了 `new`，监听器登记新的 ID，宽限期后移除旧的
+    Rotated { shared: Arc<Shared>, old: u32, new: u32 },
+    /// 监听器接续导出的连接（`Listener::adopt`），登记后经 `reply` 交还
+    Adopt { state: Box<SessionState>, reply: oneshot::Sender<Result<Connection, LinkError>> },
+}
+
+/// 监听器接收 `Notice` 的通道
+pub(crate) type Reaper = mpsc::UnboundedSender<Notice>;
+
+/// 连接的共享状态
+#[derive(Debug)]
+pub(crate) struct Shared {
+    core: Mutex<ConnectionCore>,
+    local: Option<SocketAddr>,  // 建立连接时读出的本地地址
+    outlet: Outlet,
+    conn_id: AtomicU32,         // 与协议状态中的本端 ID 相同，监听器读取时不必取锁
+    checksum: ChecksumAlgorithm,
+    peer_isn: SeqNum,           // 对端在握手中选的 ISN
+    linger: Duration,
+    recv_buffer: usize,
+    recv_batch: usize,  // 驱动与读取任务连续处理这么多个数据报后让出执行权
+    timer: Notify,  // 入站段可能让定时器提前，提醒驱动任务重新计算
+    done: Notify,   // 驱动任务退出，读取任务随之结束
+    inbound: mpsc::Sender<Bytes>,   // 交给驱动任务处理的入站数据报
+    reaper: Option<Reaper>,
+    metrics: Option<Arc<Metrics>>,  // 监听器接受的连接累加到监听器的指标
+    observer: Option<Observer>,     // 驱动任务把连接的事件交给它（见 `observer` 模块）
+    stats: StatsCell,           // 驱动任务发布的统计快照
+    pongs: Mutex<Option<mpsc::UnboundedSender<(u64, Instant)>>>,  // `Pinger` 订阅时，收到的 Pong 的 nonce 与到达时间
+    gaps: Mutex<Option<mpsc::UnboundedSender<GapEvent>>>,         // `subscribe_gaps` 订阅时，流 0 的空洞事件
+    stats_queries: Mutex<HashMap<u64, oneshot::Sender<Result<PeerStats, SegmentError>>>>,  // 等待回应的统计查询，按 nonce
+    next_stats_query: AtomicU64,
+    resyncs: Mutex<Vec<oneshot::Sender<Result<SeqNum, LinkError>>>>,  // 等待回应的重新同步请求
+    byte_stream: AtomicBool,    // 已转换为字节流，主流不再按消息收发
+    exported: AtomicBool,       // 状态已导出、驱动任务已停下（见 `session` 模块）
+}
+
+impl Shared {
+    pub(crate) fn lock(&self) -> MutexGuard<'_, ConnectionCore> {
+        self.core.lock().expect("connection state poisoned")
+    }
+
+    fn stats_queries(&self) -> MutexGuard<'_, HashMap<u64, oneshot::Sender<Result<PeerStats, SegmentError>>>> {
+        self.stats_queries.lock().expect("stats queries poisoned")
+    }
+
+    /// 连接的状态已被导出：监听器为它留下不回应的墓碑
+    pub(crate) fn is_exported(&self) -> bool {
+        self.exported.load(Ordering::Relaxed)
+    }
+
+    fn resyncs(&self) -> MutexGuard<'_, Vec<oneshot::Sender<Result<SeqNum, LinkError>>>> {
+        self.resyncs.lock().expect("resyncs poisoned")
+    }
+
+    // 有待发送的段时提醒驱动任务
+    fn wake_driver(&self, core: &ConnectionCore) {
+        if core.has_transmit() {
+            self.timer.notify_one();
+        }
+    }
+
+    /// 窗口有空位时取走 `data` 交给流 `id` 的可靠层；`data` 只在返回 `Ready(Ok)` 时被取走
+    pub(crate) fn poll_send(
+        &self,
+        id: u16,
+        cx: &mut Context<'_>,
+        data: &mut Option<Bytes>,
+        options: SendOptions,
+    ) -> Poll<Result<(), LinkError>> {
+        let sent = self.lock().poll_send_with(id, cx, data, options, now());
+        // 新数据可能启动了重传或合并定时器
+        if sent.is_ready() {
+            self.timer.notify_one();
+        }
+        sent
+    }
+
+    /// 分片发送一条消息，见 `ConnectionCore::poll_send_message_with`
+    pub(crate) fn poll_send_message(
+        &self,
+        id: u16,
+        cx: &mut Context<'_>,
+        data: &mut Option<Bytes>,
+        options: SendOptions,
+    ) -> Poll<Result<(), LinkError>> {
+        let sent = self.lock().poll_send_message_with(id, cx, data, options, now());
+        if sent.is_ready() {
+            self.timer.notify_one();
+        }
+        sent
+    }
+
+    /// 不等待的发送：发送队列已满时返回 `WouldBlock`，其余错误同 `poll_send`
+    pub(crate) fn try_send(&self, id: u16, data: Bytes) -> Result<(), LinkError> {
+        self.lock().try_send(id, data, now())?;
+        self.timer.notify_one();
+        Ok(())
+    }
+
+    /// 取出流 `id` 的下一个按序到达的消息，对端关闭这个流的写方向后返回 `None`
+    pub(crate) fn poll_recv(&self, id: u16, cx: &mut Context<'_>) -> Poll<Result<Option<Bytes>, LinkError>> {
+        let mut core = self.lock();
+        let received = core.poll_recv(id, cx, now());
+        self.wake_driver(&core);
+        received
+    }
+
+    /// 同 `poll_recv`，连同消息的标签一起取出
+    pub(crate) fn poll_recv_tagged(&self, id: u16, cx: &mut Context<'_>) -> Poll<Result<Option<TaggedMessage>, LinkError>> {
+        let mut core = self.lock();
+        let received = core.poll_recv_tagged(id, cx, now());
+        self.wake_driver(&core);
+        received
+    }
+
+    /// 交出流 `id` 合并缓冲中的写入，等待全部已发送的数据被确认
+    pub(crate) fn poll_flush(&self, id: u16, cx: &mut Context<'_>) -> Poll<Result<(), LinkError>> {
+        let mut core = self.lock();
+        let flushed = core.poll_flush(id, cx, now());
+        self.wake_driver(&core);
+        flushed
+    }
+
+    /// 关闭流 `id` 的写方向：数据全部确认后发送 FIN，等待 FIN 被确认；从第一次轮询起不再接受新的发送
+    pub(crate) fn poll_shutdown(&self, id: u16, cx: &mut Context<'_>) -> Poll<Result<(), LinkError>> {
+        let mut core = self.lock();
+        let shutdown = core.poll_shutdown(id, cx, now());
+        self.wake_driver(&core);
+        shutdown
+    }
+
+    /// 等待对端的 FIN，见 `ConnectionCore::poll_peer_fin`
+    fn poll_peer_fin(&self, cx: &mut Context<'_>) -> Poll<Result<(), LinkError>> {
+        let mut core = self.lock();
+        let fin = core.poll_peer_fin(cx, now());
+        self.wake_driver(&core);
+        fin
+    }
+
+    /// 见 `ConnectionCore::set_priority`
+    pub(crate) fn set_priority(&self, id: u16, priority: u8) {
+        self.lock().set_priority(id, priority);
+    }
+
+    /// 附加流的句柄被丢弃：关闭写方向，之后到达的数据直接丢弃
+    pub(crate) fn abandon_stream(&self, id: u16) {
+        self.lock().abandon_stream(id, now());
+        self.timer.notify_one();
+    }
+
+    pub(crate) fn mss(&self) -> usize {
+        self.lock().mss()
+    }
+
+    pub(crate) fn peer_addr(&self) -> SocketAddr {
+        self.lock().peer_addr()
+    }
+
+    pub(crate) fn conn_id(&self) -> u32 {
+        self.conn_id.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn peer_isn(&self) -> SeqNum {
+        self.peer_isn
+    }
+
+    /// 轮换本端的连接 ID，见 `ConnectionCore::rotate_conn_id`
+    pub(crate) fn rotate_conn_id(&self) -> Result<u32, LinkError> {
+        let id = self.lock().rotate_conn_id(now())?;
+        self.timer.notify_one();
+        Ok(id)
+    }
+
+    /// 见 `ConnectionCore::set_option`
+    pub(crate) fn set_option(&self, option: ConnOption) -> Result<(), LinkError> {
+        self.lock().set_option(option, now())?;
+        self.timer.notify_one();
+        Ok(())
+    }
+
+    /// 以 `error` 中止连接，由驱动任务向对端发出 Rst
+    pub(crate) fn reset(&self, error: LinkError) {
+        self.lock().reset(error);
+        self.timer.notify_one();
+    }
+
+    /// 传输已无法收发：以 `error` 中止连接，见 `ConnectionCore::fail`
+    pub(crate) fn fail(&self, error: LinkError) {
+        self.lock().fail(error);
+        self.timer.notify_one();
+    }
+
+    /// 对端迁移到新地址：之后发出的段都发往 `peer`
+    pub(crate) fn rebind(&self, peer: SocketAddr) {
+        self.lock().rebind(peer);
+    }
+
+    /// 来自陌生地址的段能否证明它属于这条连接（连接 ID 由调用方核对）
+    pub(crate) fn accepts_migration(&self, segment: &Segment) -> bool {
+        self.lock().accepts_migration(segment)
+    }
+
+    /// 见 `ConnectionCore::final_ack`
+    pub(crate) fn final_ack(&self) -> Option<Segment> {
+        self.lock().final_ack()
+    }
+
+    /// 连接失败的原因
+    pub(crate) fn error(&self) -> Option<LinkError> {
+        self.lock().error().cloned()
+    }
+
+    // 驱动任务处理一个入站数据报
+    fn on_datagram(self: &Arc<Self>, datagram: Bytes) {
+        let events = self.lock().handle_datagram(now(), datagram);
+        self.dispatch(events);
+    }
+
+    // 协议核心报告的事件：Pong 与空洞事件转交给订阅者，统计回应交给等待它的查询，重新同步的回应交给所有等待它的请求，解码失败计入指标，新的连接 ID 告诉监听器；
+    // 连接的结束由驱动任务在下一轮发现
+    fn dispatch(self: &Arc<Self>, events: Vec<Event>) {
+        for event in events {
+            match event {
+                Event::Pong { nonce, at } => {
+                    if let Some(pongs) = &*self.pongs.lock().expect("pong subscriber poisoned") {
+                        let _ = pongs.send((nonce, at));
+                    }
+                }
+                // 查询已超时的迟到回应被丢弃
+                Event::StatsReply { nonce, stats } => {
+                    if let Some(query) = self.stats_queries().remove(&nonce) {
+                        let _ = query.send(stats);
+                    }
+                }
+                Event::Resynced(result) => {
+                    for waiter in self.resyncs().drain(..) {
+                        let _ = waiter.send(result.clone());
+                    }
+                }
+                Event::DecodeFailed(e) => {
+                    if let Some(metrics) = &self.metrics {
+                        metrics.on_decode_error(&e, self.peer_addr());
+                    }
+                    trace::decode_failed(&e, self.peer_addr(), self.conn_id());
+                }
+                Event::ConnIdChanged { old, new } => {
+                    self.conn_id.store(new, Ordering::Relaxed);
+                    if let Some(reaper) = &self.reaper {
+                        let _ = reaper.send(Notice::Rotated { shared: self.clone(), old, new });
+                    }
+                }
+                Event::Gap(gap) => {
+                    if let Some(gaps) = &*self.gaps.lock().expect("gap subscriber poisoned") {
+                        let _ = gaps.send(gap);
+                    }
+                }
+                // 空洞不会再有变化，订阅者读完已有的事件后得到 None
+                Event::Failed(_) | Event::Closed => {
+                    self.gaps.lock().expect("gap subscriber poisoned").take();
+                }
+                Event::StreamOpened(_) | Event::MssChanged(_) => {}
+            }
+        }
+    }
+
+    /// 发出的数据报被本机以过大拒绝：交给协议核心降低有效 MSS、重新分片，再唤醒驱动任务发出；返回新的有效 MSS
+    pub(crate) fn on_too_large(self: &Arc<Self>, datagram: &[u8]) -> usize {
+        let (events, mss) = {
+            let mut core = self.lock();
+            (core.on_datagram_too_large(datagram, now()), core.mss())
+        };
+        self.dispatch(events);
+        self.timer.notify_one();
+        mss
+    }
+
+    /// 把来自对端的数据报交给驱动任务；队列已满时丢弃并返回 false
+    pub(crate) fn deliver(&self, datagram: Bytes) -> bool {
+        self.inbound.try_send(datagram).is_ok()
+    }
+
+    // 发出协议核心中全部待发送的数据报；发送失败等同于丢包，由重传处理。锁不跨越发送
+    async fn flush(self: &Arc<Self>) {
+        while self.transmit().await {}
+    }
+
+    // 发出下一个待发送的数据报；没有时返回 false
+    async fn transmit(self: &Arc<Self>) -> bool {
+        let (datagram, mss) = {
+            let mut core = self.lock();
+            (core.poll_transmit(), core.mss())
+        };
+        let Some((datagram, peer)) = datagram else {
+            return false;
+        };
+        self.outlet.send(datagram, peer, mss, self).await;
+        true
+    }
+}
+
+/// 客户端握手的收发：握手段直接写套接字，对端的回应从独占的套接字读出，或由 `ClientEndpoint` 的分发任务转交（见 `client_endpoint` 模块）
+pub(crate) trait HandshakePath {
+    fn socket(&self) -> &LinkSocket;
+
+    /// 以 `isn` 开始一次握手；共用套接字时分发任务据此认出确认了它的 SYN-ACK
+    fn expect(&mut self, remote: SocketAddr, isn: SeqNum) -> Result<(), LinkError>;
+
+    /// 下一个来自 `remote` 的完整数据报；接收出错或来源不符时为 None，调用方接着等待
+    async fn recv(&mut self, remote: SocketAddr, tap: Option<&Tap>) -> Result<Option<Bytes>, LinkError>;
+}
+
+// 客户端独占的套接字：来源、抓包与截断在这里处理
+struct Exclusive<'a> {
+    socket: &'a LinkSocket,
+    buf: Vec<u8>,
+}
+
+impl HandshakePath for Exclusive<'_> {
+    fn socket(&self) -> &LinkSocket {
+        self.socket
+    }
+
+    fn expect(&mut self, _: SocketAddr, _: SeqNum) -> Result<(), LinkError> {
+        Ok(())
+    }
+
+    async fn recv(&mut self, remote: SocketAddr, tap: Option<&Tap>) -> Result<Option<Bytes>, LinkError> {
+        // 对端端口未打开时 ICMP 不可达会表现为接收错误，继续等待直到超时
+        // 连接过的 UDP 套接字由内核过滤来源，其他传输在这里过滤
+        let Ok((len, from)) = self.socket.recv_from(&mut self.buf).await else {
+            return Ok(None);
+        };
+        if from != remote {
+            return Ok(None);
+        }
+        if let Some(tap) = tap {
+            tap.record(Direction::Inbound, remote, &self.buf[..len]);
+        }
+        if segment::is_truncated(&self.buf[..len]) {
+            return Ok(None);
+        }
+        Ok(Some(Bytes::copy_from_slice(&self.buf[..len])))
+    }
+}
+
+// 客户端握手：`attempts` 累计按重传计划发出的 SYN 数，重传用尽时以自 `start` 起的时间报告超时
+pub(crate) async fn handshake(
+    path: &mut impl HandshakePath,
+    tap: Option<&Tap>,
+    remote: SocketAddr,
+    config: &LinkConfig,
+    early: Option<&Bytes>,
+    attempts: &AtomicU32,
+    start: tokio::time::Instant,
+) -> Result<Handshake, LinkError> {
+    let isn = fresh_isn();
+    let mut opener = Opener::new(isn, config)?;
+    if let Some(data) = early {
+        opener = opener.with_early_data(data.clone(), config)?;
+    }
+    path.expect(remote, isn)?;
+    let mut interval = config.syn_retry_initial.min(config.max_rto);
+    for _ in 0..=config.syn_max_retries {
+        send_ignoring_refused(path.socket(), tap, remote, &handshake_datagram(&opener, &opener.retransmission())?, config.mss).await?;
+        attempts.fetch_add(1, Ordering::Relaxed);
+        let retransmit_at = tokio::time::Instant::now() + jittered(interval, config.syn_retry_jitter);
+        interval = (interval * 2).min(config.max_rto);
+
+        while let Ok(received) = tokio::time::timeout_at(retransmit_at, path.recv(remote, tap)).await {
+            let Some(mut datagram) = received? else {
+                continue;
+            };
+            while let Ok(Some(segment)) = Segment::decode_bytes(&mut datagram) {
+                let reply = opener.on_segment(&segment)?;
+                if let Some(reply) = reply {
+                    send_ignoring_refused(path.socket(), tap, remote, &handshake_datagram(&opener, &reply)?, config.mss).await?;
+                }
+                if opener.is_established() {
+                    return Ok(opener.finish());
+                }
+            }
+        }
+    }
+    Err(LinkError::ConnectTimeout { attempts: attempts.load(Ordering::Relaxed), elapsed: start.elapsed() })
+}
+
+// 在 interval 的 1 ± jitter 倍之间均匀分布
+pub(crate) fn jittered(interval: Duration, jitter: f64) -> Duration {
+    if jitter == 0.0 {
+        return interval;
+    }
+    let mut random = [0u8; 4];
+    getrandom::fill(&mut random).expect("operating system random source unavailable");
+    let unit = f64::from(u32::from_be_bytes(random)) / f64::from(u32::MAX);
+    interval.mul_f64(1.0 - jitter + 2.0 * jitter * unit)
+}
+
+// 握手段的数据报：SYN 之后打包 0-RTT 数据段
+pub(crate) fn handshake_datagram(opener: &Opener, segment: &Segment) -> Result<BytesMut, LinkError> {
+    let mut datagram = segment.encode()?;
+    if let Some(early) = opener.early_data(segment) {
+        early.encode_into(&mut datagram)?;
+    }
+    Ok(datagram)
+}
+
+// 发送握手段；对端端口未打开导致的拒绝视同丢包，过大的握手数据报（如 0-RTT 数据）报告为 `DatagramTooLarge`
+async fn send_ignoring_refused(socket: &LinkSocket, tap: Option<&Tap>, remote: SocketAddr, datagram: &[u8], mss: usize) -> Result<(), LinkError> {
+    match socket.send_to(datagram, remote).await {
+        Err(e) if e.kind() != io::ErrorKind::ConnectionRefused => Err(error::send_error(e, datagram.len(), mss)),
+        Err(_) => Ok(()),
+        Ok(_) => {
+            if let Some(tap) = tap {
+                tap.record(Direction::Outbound, remote, datagram);
+            }
+            Ok(())
+        }
+    }
+}
+
+// 客户端连接的读取任务：把独占套接字上收到的段交给连接处理
+async fn read_loop(shared: Arc<Shared>) {
+    let mut arena = RecvArena::new(shared.recv_buffer);
+    let mut handled = 0;
+    loop {
+        if handled >= shared.recv_batch {
+            handled = 0;
+            tokio::task::yield_now().await;
+        }
+        let Outlet::Udp { socket, tap, .. } = &shared.outlet else {
+            return;
+        };
+        let received = tokio::select! {
+            received = socket.recv_from(arena.space()) => received,
+            _ = shared.done.notified() => return,
+        };
+        let (len, from) = match received {
+            Ok(received) => received,
+            // 套接字失效或 TCP 流已关闭，之后再也收不到对端的段
+            Err(e) if error::is_fatal(&e) => {
+                shared.fail(e.into());
+                return;
+            }
+            Err(_) => continue,
+        };
+        // 接着取走已经排队的数据报，一次唤醒清空突发到达的积压
+        let mut drained = 1;
+        receive(&shared, &mut arena, len, from, tap.as_ref());
+        while handled + drained < shared.recv_batch {
+            let Ok((len, from)) = socket.try_recv_from(arena.space()) else {
+                break;
+            };
+            drained += 1;
+            receive(&shared, &mut arena, len, from, tap.as_ref());
+        }
+        handled += drained;
+    }
+}
+
+// 刚读入 `arena` 的数据报：只收对端发来的
+fn receive(shared: &Shared, arena: &mut RecvArena, len: usize, from: SocketAddr, tap: Option<&Tap>) {
+    if from != shared.peer_addr() {
+        return;
+    }
+    let datagram = arena.split(len);
+    if let Some(tap) = tap {
+        tap.record(Direction::Inbound, from, &datagram);
+    }
+    // 截断的数据报可能在末尾解析出更短的段，整个丢弃
+    if segment::is_truncated(&datagram) {
+        return;
+    }
+    shared.deliver(datagram);
+}
+
+// 连接的驱动任务：把入站数据报与到期的定时器交给协议核心并发出它产生的数据报，
+// 睡到最近的截止时间，或被 send/recv 提前唤醒后重新计算。连接开始关闭后的各轮在关闭阶段的子 span 中运行
+async fn drive(shared: Arc<Shared>, mut inbound: mpsc::Receiver<Bytes>) {
+    let _reap = Reap(shared.clone());
+    let mut retransmitted = 0;  // 已累加到监听器指标的重传段数
+    let mut close: Option<Span> = None;
+    loop {
+        if close.is_none() && shared.lock().state().is_closing() {
+            close = Some(trace::close_span(&Span::current()));
+        }
+        let step = step(&shared, &mut inbound, &mut retransmitted);
+        let running = match &close {
+            Some(span) => step.instrument(span.clone()).await,
+            None => step.await,
+        };
+        if !running {
+            return;
+        }
+    }
+}
+
+// 驱动任务的一轮；连接结束后返回 false
+async fn step(shared: &Arc<Shared>, inbound: &mut mpsc::Receiver<Bytes>, retransmitted: &mut u64) -> bool {
+    let (deadline, observations, peer) = {
+        let mut core = shared.lock();
+        let stats = core.stats();
+        shared.stats.publish(&stats);
+        if let Some(metrics) = &shared.metrics {
+            metrics.on_retransmitted(stats.sender.segments_retransmitted - *retransmitted);
+            *retransmitted = stats.sender.segments_retransmitted;
+        }
+        let observations = if shared.observer.is_some() { core.take_observations() } else { Vec::new() };
+        let deadline = if core.is_terminated() {
+            match core.error() {
+                Some(error) => tracing::debug!(%error, "connection failed"),
+                None => tracing::debug!("connection closed"),
+            }
+            None
+        } else {
+            Some(core.next_deadline())
+        };
+        (deadline, observations, core.peer_addr())
+    };
+    // 观察者的回调在锁外调用
+    if let Some(observer) = &shared.observer {
+        observer.notify(peer, observations);
+    }
+    let Some(deadline) = deadline else {
+        shared.done.notify_one();
+        return false;
+    };
+    let sleep = async {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+            None => pending().await,
+        }
+    };
+    tokio::select! {
+        _ = sleep => {
+            let events = shared.lock().handle_timeout(now());
+            shared.dispatch(events);
+        }
+        _ = shared.timer.notified() => {}
+        Some(datagram) = inbound.recv() => shared.on_datagram(datagram),
+    }
+    // 每发一个数据报之前先处理已经到达的数据报：发送很慢时，它们引起的确认与 Pong 不必等排队的数据发完。
+    // 连续处理满 `recv_batch` 个时先让出执行权，持续涌入的数据报不会饿死同一运行时上的其他任务
+    let mut budget = shared.recv_batch;
+    loop {
+        while let Ok(datagram) = inbound.try_recv() {
+            shared.on_datagram(datagram);
+            budget -= 1;
+            if budget == 0 {
+                budget = shared.recv_batch;
+                tokio::task::yield_now().await;
+            }
+        }
+        if !shared.transmit().await {
+            return true;
+        }
+    }
+}
+
+// 驱动任务结束时（包括被中止）把连接交还给监听器
+struct Reap(Arc<Shared>);
+
+impl Drop for Reap {
+    fn drop(&mut self) {
+        if let Some(reaper) = &self.0.reaper {
+            let _ = reaper.send(Notice::Reaped(self.0.clone()));
+        }
+    }
+}
+
+/// 测试用的内存直连
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use crate::seq::SeqNum;
+    use crate::state::{Action, StateMachine};
+
+    fn established() -> StateMachine {
+        let mut state = StateMachine::new();
+        state.on_action(Action::Connect).unwrap();
+        state.on_syn_ack().unwrap();
+        state
+    }
+
+    // 把一端发出的数据报交给另一端，`drop` 返回 true 的数据报被丢弃
+    async fn pump(mut rx: mpsc::Receiver<Outgoing>, to: Arc<Shared>, mut drop: impl FnMut(&[u8]) -> bool) {
+        while let Some((datagram, ..)) = rx.recv().await {
+            if !drop(&datagram) {
+                to.deliver(datagram.freeze());
+            }
+        }
+    }
+
+    // 通过内存通道直连的一对已建立连接；`drop_every` 非零时丢弃每个方向上每第 N 个数据报
+    pub(crate) fn memory_pair(drop_every: usize) -> (Connection, Connection) {
+        memory_pair_with(LinkConfig::default(), drop_every)
+    }
+
+    pub(crate) fn memory_pair_with(config: LinkConfig, drop_every: usize) -> (Connection, Connection) {
+        let mut count = 0;
+        memory_pair_lossy(config, move |_| {
+            count += 1;
+            drop_every != 0 && count % drop_every == 0
+        })
+    }
+
+    // 每个方向各用一份 `drop` 决定丢弃哪些数据报
+    pub(crate) fn memory_pair_lossy(
+        config: LinkConfig,
+        drop: impl FnMut(&[u8]) -> bool + Clone + Send + 'static,
+    ) -> (Connection, Connection) {
+        let (isn_a, isn_b) = (SeqNum::new(1000), SeqNum::new(5000));
+        // 两端配置相同，各自通告的 MSS 也相同
+        let peer_mss = Some(crate::endpoint::advertised_mss(&config));
+        let handshake = |local_isn, peer_isn, initiator| Handshake {
+            state: established(),
+            local_isn,
+            peer_isn,
+            conn_id: 7,
+            initiator,
+            checksum: ChecksumAlgorithm::default(),
+            peer_mss,
+            early_data: false,
+            params: crate::endpoint::local_params(&config, true),
+            #[cfg(feature = "crypto")]
+            nonces: None,
+            #[cfg(feature = "crypto")]
+            reauth: None,
+        };
+        let (handshake_a, handshake_b) = (handshake(isn_a, isn_b, true), handshake(isn_b, isn_a, false));
+        memory_pair_from(&config, handshake_a, handshake_b, drop)
+    }
+
+    // 用给定的握手结果建立一对内存直连的连接
+    pub(crate) fn memory_pair_from(
+        config: &LinkConfig,
+        handshake_a: Handshake,
+        handshake_b: Handshake,
+        drop: impl FnMut(&[u8]) -> bool + Clone + Send + 'static,
+    ) -> (Connection, Connection) {
+        let (addr_a, addr_b) = ("10.0.0.1:1".parse().unwrap(), "10.0.0.2:2".parse().unwrap());
+        let (tx_a, rx_a) = mpsc::channel(INBOUND_QUEUE);
+        let (tx_b, rx_b) = mpsc::channel(INBOUND_QUEUE);
+        let pool = Arc::new(BufferPool::new(config));
+        let span_a = trace::connection_span(Role::Client, addr_b);
+        let span_b = trace::connection_span(Role::Server, addr_a);
+        let a = Connection::establish(Outlet::Channel { tx: tx_a, local: addr_a, pool: pool.clone() }, addr_b, config, handshake_a, span_a, None, None);
+        let b = Connection::establish(Outlet::Channel { tx: tx_b, local: addr_b, pool }, addr_a, config, handshake_b, span_b, None, None);
+        tokio::spawn(pump(rx_a, b.shared().clone(), drop.clone()));
+        tokio::spawn(pump(rx_b, a.shared().clone(), drop));
+        (a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{memory_pair, memory_pair_from, memory_pair_with};
+    use super::*;
+    use crate::endpoint::TIME_WAIT;
+    use crate::segment::SegmentType;
+    use crate::seq::SeqNum;
+    use tokio::time::timeout;
+
+    #[test]
+    fn test_syn_jitter_stays_within_bounds() {
+        let interval = Duration::from_millis(400);
+        assert_eq!(jittered(interval, 0.0), interval);
+        for _ in 0..100 {
+            let delay = jittered(interval, 0.25);
+            assert!(delay >= Duration::from_millis(300) && delay <= Duration::from_millis(500), "{:?}", delay);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_thousand_messages_over_lossy_link() {
+        let (a, b) = memory_pair(7);
+        let send = async {
+            for i in 0..1000 {
+                a.send(Bytes::from(format!("m{}", i))).await.unwrap();
+            }
+        };
+        let recv = async {
+            let mut received = Vec::new();
+            for _ in 0..1000 {
+                received.push(b.recv().await.unwrap().unwrap());
+            }
+            received
+        };
+        let ((), received) = tokio::join!(send, recv);
+
+        let expected: Vec<Bytes> = (0..1000).map(|i| Bytes::from(format!("m{}", i))).collect();
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reset_wakes_parked_send_and_recv() {
+        // 全部丢包：发送窗口很快填满，接收端也等不到数据
+        let (a, b) = memory_pair(1);
+        while timeout(Duration::from_millis(10), a.send(Bytes::from_static(b"x"))).await.is_ok() {}
+
+        let rst = Segment::builder(SegmentType::Rst).build().unwrap().encode().unwrap().freeze();
+        let reset = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(a.shared().deliver(rst.clone()));
+            assert!(b.shared().deliver(rst));
+        };
+        let (sent, received, ()) = tokio::join!(a.send(Bytes::from_static(b"y")), b.recv(), reset);
+        assert_eq!(sent, Err(LinkError::Reset));
+        assert_eq!(received, Err(LinkError::Reset));
+        assert_eq!(a.send(Bytes::from_static(b"z")).await, Err(LinkError::Reset));
+        assert_eq!(a.state(), ConnState::Aborted);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_buffered_data_before_error() {
+        let (a, b) = memory_pair(0);
+        a.send(Bytes::from_static(b"last")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let rst = Segment::builder(SegmentType::Rst).build().unwrap();
+        assert!(b.shared().deliver(rst.encode().unwrap().freeze()));
+        assert_eq!(b.recv().await.unwrap().unwrap(), Bytes::from_static(b"last"));
+        assert_eq!(b.recv().await, Err(LinkError::Reset));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_received_payload_is_not_copied() {
+        // 记下每个送达的数据报所在的存储范围
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let ranges = received.clone();
+        let (a, b) = testing::memory_pair_lossy(LinkConfig::default(), move |datagram: &[u8]| {
+            let start = datagram.as_ptr() as usize;
+            ranges.lock().unwrap().push(start..start + datagram.len());
+            false
+        });
+        a.send(Bytes::from_static(b"zero copy")).await.unwrap();
+
+        // 交给应用的数据体就是数据报中的那段字节
+        let message = b.recv().await.unwrap().unwrap();
+        assert_eq!(message, Bytes::from_static(b"zero copy"));
+        let ptr = message.as_ptr() as usize;
+        assert!(received.lock().unwrap().iter().any(|range| range.contains(&ptr) && range.contains(&(ptr + message.len() - 1))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancelled_recv_loses_nothing() {
+        let (a, b) = memory_pair(0);
+        assert!(timeout(Duration::from_millis(50), b.recv()).await.is_err());
+
+        a.send(Bytes::from_static(b"after")).await.unwrap();
+        assert_eq!(b.recv().await.unwrap().unwrap(), Bytes::from_static(b"after"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_close_delivers_data_in_flight() {
+        let (a, b) = memory_pair(7);
+        let (a_shared, b_shared) = (a.shared().clone(), b.shared().clone());
+        // 不超过接收窗口：对端在 close 开始后才读取
+        for i in 0..50 {
+            a.send(Bytes::from(format!("m{}", i))).await.unwrap();
+        }
+        let peer = async {
+            for i in 0..50 {
+                assert_eq!(b.recv().await.unwrap().unwrap(), Bytes::from(format!("m{}", i)));
+            }
+            assert_eq!(b.recv().await, Ok(None));
+            b.close().await
+        };
+        let (closed, peer_closed) = tokio::join!(a.close(), peer);
+        closed.unwrap();
+        peer_closed.unwrap();
+
+        // 先关闭的一方经过 TIME_WAIT，另一方在 FIN 被确认后直接关闭
+        assert_eq!(a_shared.lock().state(), ConnState::TimeWait);
+        assert_eq!(b_shared.lock().state(), ConnState::Closed);
+        tokio::time::sleep(TIME_WAIT * 2).await;
+        assert_eq!(a_shared.lock().state(), ConnState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_close_times_out_without_fin_ack() {
+        let linger = Duration::from_secs(1);
+        let (a, _b) = memory_pair_with(LinkConfig { linger, ..LinkConfig::default() }, 1);
+        let shared = a.shared().clone();
+
+        let started = tokio::time::Instant::now();
+        assert_eq!(a.close().await, Err(LinkError::CloseTimedOut));
+        assert_eq!(started.elapsed(), linger);
+        assert_eq!(shared.lock().state(), ConnState::Aborted);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrent_close_from_both_sides() {
+        let (a, b) = memory_pair(5);
+        let (a_shared, b_shared) = (a.shared().clone(), b.shared().clone());
+        a.send(Bytes::from_static(b"from a")).await.unwrap();
+        b.send(Bytes::from_static(b"from b")).await.unwrap();
+
+        let (closed_a, closed_b) = tokio::join!(a.close(), b.close());
+        closed_a.unwrap();
+        closed_b.unwrap();
+        assert_eq!(a_shared.lock().state(), ConnState::TimeWait);
+        assert_eq!(b_shared.lock().state(), ConnState::TimeWait);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_send_fails_once_close_started() {
+        let (a, b) = memory_pair(0);
+        let shared = a.shared().clone();
+        let mut late = Some(Bytes::from_static(b"late"));
+        let peer = async {
+            assert_eq!(b.recv().await, Ok(None));
+            b.close().await
+        };
+        // join! 按顺序轮询：close 先开始，随后的发送必然失败
+        let (closed, sent, peer_closed) = tokio::join!(a.close(), poll_fn(|cx| shared.poll_send(MAIN_STREAM, cx, &mut late, SendOptions::default())), peer);
+        assert_eq!(sent, Err(LinkError::Closed));
+        closed.unwrap();
+        peer_closed.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_half_close_keeps_receiving() {
+        let (a, b) = memory_pair(3);
+        let (a_shared, b_shared) = (a.shared().clone(), b.shared().clone());
+        a.send(Bytes::from_static(b"request")).await.unwrap();
+        a.shutdown_write().await.unwrap();
+        assert_eq!(a.send(Bytes::from_static(b"late")).await, Err(LinkError::WriteClosed));
+
+        // 对端读到请求与流结束后继续回应，最后关闭
+        let peer = async {
+            assert_eq!(b.recv().await.unwrap().unwrap(), Bytes::from_static(b"request"));
+            assert_eq!(b.recv().await, Ok(None));
+            for i in 0..10 {
+                b.send(Bytes::from(format!("r{}", i))).await.unwrap();
+            }
+            b.close().await
+        };
+        let local = async {
+            for i in 0..10 {
+                assert_eq!(a.recv().await.unwrap().unwrap(), Bytes::from(format!("r{}", i)));
+            }
+            assert_eq!(a.recv().await, Ok(None));
+        };
+        let (peer_closed, ()) = tokio::join!(peer, local);
+        peer_closed.unwrap();
+
+        assert_eq!(b_shared.lock().state(), ConnState::Closed);
+        tokio::time::sleep(TIME_WAIT * 2).await;
+        assert_eq!(a_shared.lock().state(), ConnState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_streams_close_independently() {
+        let (a, b) = memory_pair(0);
+        let first = a.open_stream().unwrap();
+        let second = a.open_stream().unwrap();
+        assert_eq!((first.id(), second.id()), (1, 3));
+        first.send(Bytes::from_static(b"one")).await.unwrap();
+        second.send(Bytes::from_static(b"two")).await.unwrap();
+        let (first_b, second_b) = (b.accept_stream().await.unwrap(), b.accept_stream().await.unwrap());
+        assert_eq!(first_b.recv().await.unwrap().unwrap(), Bytes::from_static(b"one"));
+        assert_eq!(second_b.recv().await.unwrap().unwrap(), Bytes::from_static(b"two"));
+
+        // 双方关闭第一个流：它被回收，第二个流与连接本身照常工作
+        first.shutdown_write().await.unwrap();
+        assert_eq!(first_b.recv().await, Ok(None));
+        first_b.shutdown_write().await.unwrap();
+        assert_eq!(first.recv().await, Ok(None));
+        assert!(!a.shared().lock().streams.contains_key(&1));
+        assert!(!b.shared().lock().streams.contains_key(&1));
+
+        second.send(Bytes::from_static(b"still open")).await.unwrap();
+        assert_eq!(second_b.recv().await.unwrap().unwrap(), Bytes::from_static(b"still open"));
+        a.send(Bytes::from_static(b"main")).await.unwrap();
+        assert_eq!(b.recv().await.unwrap().unwrap(), Bytes::from_static(b"main"));
+        assert_eq!(a.state(), ConnState::Established);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unopened_local_stream_is_protocol_error() {
+        let (a, _b) = memory_pair(0);
+        // a 是发起方，奇数流只能由它打开
+        let forged = Segment::builder(SegmentType::Data).stream(9).data_seq(0u64).payload("forged").build().unwrap();
+        assert!(a.shared().deliver(forged.encode().unwrap().freeze()));
+        assert!(matches!(a.recv().await, Err(LinkError::Protocol(_))));
+        assert_eq!(a.state(), ConnState::Aborted);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stream_ids_exhausted() {
+        let (a, b) = memory_pair(0);
+        a.shared().lock().next_local_stream = u32::from(u16::MAX);
+        assert_eq!(a.open_stream().unwrap().id(), u16::MAX);
+        assert_eq!(a.open_stream().unwrap_err(), LinkError::StreamsExhausted);
+        // 服务端的流 ID 为偶数，与客户端互不影响
+        assert_eq!(b.open_stream().unwrap().id(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_send_parks_when_queue_full() {
+        // 对端收不到任何数据报，也就从不确认：发送在队列达到上限时挂起，排队的字节数不再增长
+        let config = LinkConfig { send_buffer: 8 * 1024, ..LinkConfig::default() };
+        let (a, _b) = memory_pair_with(config, 1);
+        let message = Bytes::from(vec![0u8; 1000]);
+        for _ in 0..8 {
+            a.send(message.clone()).await.unwrap();
+        }
+        assert!(timeout(Duration::from_millis(100), a.send(message.clone())).await.is_err());
+        assert_eq!(a.try_send(message.clone()), Err(LinkError::WouldBlock));
+
+        let queued = a.shared().lock().main.sender.queued_bytes();
+        assert_eq!(queued, 8000);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(a.try_send(message), Err(LinkError::WouldBlock));
+        assert_eq!(a.shared().lock().main.sender.queued_bytes(), queued);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_try_send_delivers() {
+        let (a, b) = memory_pair(0);
+        a.try_send(Bytes::from_static(b"now")).unwrap();
+        assert_eq!(b.recv().await.unwrap().unwrap(), Bytes::from_static(b"now"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stats_over_lossy_link() {
+        let (a, b) = memory_pair(7);
+        let message = Bytes::from(vec![7u8; 100]);
+        let send = async {
+            for _ in 0..500 {
+                a.send(message.clone()).await.unwrap();
+            }
+            a.shutdown_write().await.unwrap();
+        };
+        let recv = async { while b.recv().await.unwrap().is_some() {} };
+        tokio::join!(send, recv);
+        // 让驱动任务处理完最后的确认并发布快照
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let (sent, received) = (a.stats(), b.stats());
+        assert_eq!(sent.local_addr, Some("10.0.0.1:1".parse().unwrap()));
+        assert_eq!(sent.peer_addr, Some("10.0.0.2:2".parse().unwrap()));
+        assert!(sent.srtt.is_some());
+        assert_eq!((sent.in_flight, sent.send_queue_bytes), (0, 0));
+
+        // 丢包引起重传，重传又在对端产生重复段与乱序段
+        assert!(sent.sender.segments_retransmitted > 0);
+        assert!(sent.sender.duplicate_acks > 0);
+        assert!(received.receiver.duplicates_received + received.receiver.out_of_order_received > 0);
+        assert!(sent.fast_retransmits + sent.timeouts > 0);
+
+        // 数据全部送达并被确认：发出的字节数（含重传）不少于被确认的字节数，后者恰为全部数据
+        assert_eq!(sent.sender.segments_sent, 500);
+        assert_eq!(sent.sender.bytes_acked, 500 * 100);
+        assert_eq!(received.receiver.bytes_received, 500 * 100);
+        assert!(sent.sender.bytes_sent > sent.sender.bytes_acked);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_simultaneous_open() {
+        // 双方的 SYN 同时发出、在路上交错：各自收到对端的 SYN，回应 SYN-ACK，再收到对端的 SYN-ACK
+        let config = LinkConfig::default();
+        let (isn_a, isn_b) = (SeqNum::new(9000), SeqNum::new(3000));
+        let mut a = Opener::new(isn_a, &config).unwrap();
+        let mut b = Opener::new(isn_b, &config).unwrap();
+        let (syn_a, syn_b) = (a.retransmission(), b.retransmission());
+        let syn_ack_a = a.on_segment(&syn_b).unwrap().unwrap();
+        let syn_ack_b = b.on_segment(&syn_a).unwrap().unwrap();
+        assert!(syn_ack_a.is_ack_bearing() && syn_ack_b.is_ack_bearing());
+        assert_eq!(a.retransmission(), syn_ack_a);
+        let ack_a = a.on_segment(&syn_ack_b).unwrap().unwrap();
+        let ack_b = b.on_segment(&syn_ack_a).unwrap().unwrap();
+        assert!(a.is_established() && b.is_established());
+        // 握手之后迟到的确认不再产生回应
+        assert_eq!(a.on_segment(&ack_b).unwrap(), None);
+        assert_eq!(b.on_segment(&ack_a).unwrap(), None);
+
+        // 双方对序列号的看法一致，恰好一方作为发起方
+        let (a, b) = (a.finish(), b.finish());
+        assert_eq!((a.local_isn, a.peer_isn), (isn_a, isn_b));
+        assert_eq!((b.local_isn, b.peer_isn), (isn_b, isn_a));
+        assert!(a.initiator && !b.initiator);
+        assert_eq!(a.state.state(), ConnState::Established);
+
+        let (a, b) = memory_pair_from(&config, a, b, |_| false);
+        a.send(Bytes::from_static(b"from a")).await.unwrap();
+        b.send(Bytes::from_static(b"from b")).await.unwrap();
+        assert_eq!(b.recv().await.unwrap().unwrap(), Bytes::from_static(b"from a"));
+        assert_eq!(a.recv().await.unwrap().unwrap(), Bytes::from_static(b"from b"));
+        // 流 ID 的奇偶互不冲突
+        assert_eq!(a.open_stream().unwrap().id() % 2, 1);
+        assert_eq!(b.open_stream().unwrap().id() % 2, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_simultaneous_open_with_lost_syn() {
+        // b 的 SYN 丢失：a 从 SynSent 直接收到 b 的 SYN-ACK，b 等到 a 的确认
+        let config = LinkConfig::default();
+        let mut a = Opener::new(SeqNum::new(10), &config).unwrap();
+        let mut b = Opener::new(SeqNum::new(20), &config).unwrap();
+        let syn_ack_b = b.on_segment(&a.retransmission()).unwrap().unwrap();
+        let ack_a = a.on_segment(&syn_ack_b).unwrap().unwrap();
+        assert!(a.is_established() && !b.is_established());
+        assert_eq!(b.on_segment(&ack_a).unwrap(), None);
+        assert!(b.is_established());
+
+        let (a, b) = (a.finish(), b.finish());
+        assert_eq!((a.peer_isn, b.peer_isn), (SeqNum::new(20), SeqNum::new(10)));
+        assert!(!a.initiator && b.initiator);
+    }
+}
@@ -0,0 +1,177 @@
+//! 无状态握手（SYN cookie）
+//! 监听器以 cookie 回应 SYN 时不登记半开握手：SYN-ACK 的序列号（本端 ISN）由时间片与 HMAC 组成，
+//! 高 8 位是发出时的时间片（秒）的低 8 位，低 56 位是以服务端密钥对 (对端地址, 对端 ISN, 连接 ID, 时间片)
+//! 计算的 HMAC-SHA256 的前 7 字节。完成握手的段确认这个序列号并带回对端 ISN（最后的确认在序列号上携带
+//! 客户端 ISN，第一个数据段的序列号是 ISN + 1），重新计算通过、且时间片在 `handshake_timeout` 之内时才建立连接。
+//!
+//! 密钥每隔 `SECRET_LIFETIME` 轮换一次，上一代密钥保留一代的宽限期，轮换前发出的 cookie 仍可验证。
+//! 最后的确认与第一个数据段都丢失时，后续数据段无法还原对端 ISN，握手失败。
+//! 签发与验证的时刻由监听器在处理 SYN 与握手的最后一个确认时传入，密钥轮换也按这个时刻推进。
+
+use crate::config::LinkConfig;
+use crate::segment::{Segment, SegmentType};
+use crate::seq::SeqNum;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+/// 密钥的轮换周期
+pub const SECRET_LIFETIME: Duration = Duration::from_secs(120);
+
+/// cookie 中时间片的长度
+const SLOT: Duration = Duration::from_secs(1);
+
+/// 低 56 位是 HMAC
+const MAC_BITS: u32 = 56;
+
+/// 监听器何时以 cookie 回应 SYN
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SynCookies {
+    #[default]
+    Never,      // 总是登记半开握手，backlog 已满时丢弃 SYN
+    Overflow,   // backlog 已满时改用 cookie
+    Always,     // 从不登记半开握手
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 签发与验证 cookie 的密钥
+#[derive(Debug)]
+pub struct CookieJar {
+    current: [u8; 32],
+    previous: Option<[u8; 32]>, // 上一代密钥，轮换后再保留一代
+    rotated_at: Instant,
+    start: Instant,             // 第 0 个时间片的起点
+    window: u64,                // cookie 的有效时间片数
+}
+
+fn fresh_secret() -> [u8; 32] {
+    let mut secret = [0u8; 32];
+    getrandom::fill(&mut secret).expect("operating system random source unavailable");
+    secret
+}
+
+impl CookieJar {
+    pub fn new(config: &LinkConfig, now: Instant) -> Self {
+        // 时间片只保留低 8 位，窗口不超过一圈
+        let window = config.handshake_timeout.as_secs().saturating_add(1).min(255);
+        Self { current: fresh_secret(), previous: None, rotated_at: now, start: now, window }
+    }
+
+    /// 为 `peer` 的 SYN 签发 cookie，作为 SYN-ACK 的序列号
+    pub fn issue(&mut self, peer: SocketAddr, peer_isn: SeqNum, conn_id: u32, now: Instant) -> SeqNum {
+        self.rotate(now);
+        let slot = self.slot(now);
+        let mac = mac(&self.current, peer, peer_isn, conn_id, slot);
+        SeqNum::new(((slot & 0xFF) << MAC_BITS) | mac)
+    }
+
+    /// 校验完成握手的段：通过时返回 (本端 ISN, 对端 ISN)
+    pub fn validate(&mut self, segment: &Segment, peer: SocketAddr, now: Instant) -> Option<(SeqNum, SeqNum)> {
+        let peer_isn = match segment.segment_type() {
+            SegmentType::Ack => segment.seq(),
+            SegmentType::Data if segment.stream_id() == 0 => segment.seq().wrapping_sub(1),
+            _ => return None,
+        };
+        if segment.conn_id() == 0 {
+            return None;
+        }
+        self.rotate(now);
+        let cookie = segment.ack();
+        let current = self.slot(now);
+        let age = (current & 0xFF).wrapping_sub(cookie.get() >> MAC_BITS) & 0xFF;
+        if age > self.window || age > current {
+            return None;
+        }
+        let slot = current - age;
+        let expected = cookie.get() & ((1 << MAC_BITS) - 1);
+        let valid = std::iter::once(&self.current)
+            .chain(self.previous.as_ref())
+            .any(|secret| mac(secret, peer, peer_isn, segment.conn_id(), slot) == expected);
+        valid.then_some((cookie, peer_isn))
+    }
+
+    // 到期时轮换；闲置超过两个周期时上一代密钥也已过期
+    fn rotate(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.rotated_at);
+        if elapsed < SECRET_LIFETIME {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.current, fresh_secret());
+        self.previous = (elapsed < SECRET_LIFETIME * 2).then_some(previous);
+        self.rotated_at = now;
+    }
+
+    fn slot(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.start).as_secs() / SLOT.as_secs()
+    }
+}
+
+// HMAC 的前 7 字节；IPv4 地址按映射后的 IPv6 形式参与计算，双栈套接字上两种写法得到同一个 cookie
+fn mac(secret: &[u8; 32], peer: SocketAddr, peer_isn: SeqNum, conn_id: u32, slot: u64) -> u64 {
+    let ip = match peer.ip() {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    };
+    let mut hmac = HmacSha256::new_from_slice(secret).expect("hmac accepts any key length");
+    hmac.update(&ip.octets());
+    hmac.update(&peer.port().to_be_bytes());
+    hmac.update(&peer_isn.get().to_be_bytes());
+    hmac.update(&conn_id.to_be_bytes());
+    hmac.update(&slot.to_be_bytes());
+    let tag = hmac.finalize().into_bytes();
+    let mut bytes = [0u8; 8];
+    bytes[1..].copy_from_slice(&tag[..7]);
+    u64::from_be_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ack(conn_id: u32, isn: u64, cookie: SeqNum) -> Segment {
+        Segment::builder(SegmentType::Ack).conn_id(conn_id).data_seq(isn).ack(cookie).window(64).build().unwrap()
+    }
+
+    #[test]
+    fn test_cookie_round_trip() {
+        let t0 = Instant::now();
+        let mut jar = CookieJar::new(&LinkConfig::default(), t0);
+        let peer: SocketAddr = "10.0.0.2:2".parse().unwrap();
+        let cookie = jar.issue(peer, SeqNum::new(100), 7, t0);
+
+        let later = t0 + Duration::from_secs(3);
+        assert_eq!(jar.validate(&ack(7, 100, cookie), peer, later), Some((cookie, SeqNum::new(100))));
+        // 第一个数据段的序列号是 ISN + 1
+        let data = Segment::builder(SegmentType::Data).conn_id(7).data_seq(101u64).ack(cookie).payload("x").build().unwrap();
+        assert_eq!(jar.validate(&data, peer, later), Some((cookie, SeqNum::new(100))));
+
+        // 任何一项不符都不通过
+        assert_eq!(jar.validate(&ack(8, 100, cookie), peer, later), None);
+        assert_eq!(jar.validate(&ack(7, 101, cookie), peer, later), None);
+        assert_eq!(jar.validate(&ack(7, 100, cookie), "10.0.0.2:3".parse().unwrap(), later), None);
+        assert_eq!(jar.validate(&ack(7, 100, cookie.wrapping_add(1)), peer, later), None);
+        // 超出 handshake_timeout
+        assert_eq!(jar.validate(&ack(7, 100, cookie), peer, t0 + Duration::from_secs(30)), None);
+    }
+
+    #[test]
+    fn test_secret_rotation_grace() {
+        let t0 = Instant::now();
+        let config = LinkConfig { handshake_timeout: Duration::from_secs(200), ..LinkConfig::default() };
+        let mut jar = CookieJar::new(&config, t0);
+        let peer: SocketAddr = "[::1]:9".parse().unwrap();
+        let cookie = jar.issue(peer, SeqNum::new(5), 3, t0 + SECRET_LIFETIME - Duration::from_secs(1));
+
+        // 轮换后上一代密钥签发的 cookie 仍然有效
+        let rotated = t0 + SECRET_LIFETIME + Duration::from_secs(1);
+        assert!(jar.validate(&ack(3, 5, cookie), peer, rotated).is_some());
+        // 再轮换一次后失效，尽管仍在时间窗口内
+        let mut jar = CookieJar::new(&config, t0);
+        let cookie = jar.issue(peer, SeqNum::new(5), 3, t0 + SECRET_LIFETIME - Duration::from_secs(1));
+        jar.rotate(t0 + SECRET_LIFETIME);
+        jar.rotate(t0 + SECRET_LIFETIME * 2);
+        assert!(jar.validate(&ack(3, 5, cookie), peer, t0 + SECRET_LIFETIME * 2).is_none());
+    }
+}
@@ -0,0 +1,485 @@
+//! 握手认证与数据段加密（`crypto` 特性）
+//! 双方配置同一个预共享密钥（`LinkConfig::psk`）时，握手段与数据段都受它保护，预共享密钥本身从不直接用作密钥：
+//!
+//! 握手：SYN、SYN-ACK 与完成握手的确认设置 AUTH 标志，数据体末尾附 `nonce(16) | echo(16) | tag(32)`。
+//! nonce 是发送方为这次握手随机选择的，echo 回送对端的 nonce（SYN 为全 0），tag 是以握手密钥
+//! HKDF-SHA256(psk, "link-rs handshake") 对段头（type 到 checksum_id）与尾部之前的数据体计算的 HMAC-SHA256。
+//! 不持有预共享密钥的一方无法伪造其中任何一个段，也就无法完成握手；篡改 nonce 或段头的段无法通过认证。
+//!
+//! 流量密钥：握手完成后双方由 HKDF-SHA256(salt = 发起方 nonce | 响应方 nonce | 连接 ID, ikm = psk) 分别展开
+//! 两个方向的密钥，每条连接、每个方向的密钥都不同。此后 Data 段与 NewConnId 段的数据体以 ChaCha20-Poly1305 加密，设置 SEALED 标志；
+//! 段头是关联数据，篡改密文或段头都无法通过认证，解码返回 `SegmentError::DecryptFailed`。其他控制段不加密，
+//! 不持有密钥的一方因而无法注入新的连接 ID。
+//! 密钥材料（预共享密钥、握手密钥、展开的流量密钥与 cipher 的内部状态）在丢弃时清零。
+//!
+//! nonce 为 `conn_id(4) | stream_id(2) | seq 的低 48 位(6)`。数据段不捎带确认，同一序列号的重传段头与数据体
+//! 都相同，得到同一密文，不构成 nonce 复用；连接 ID 轮换之后重传的段换了 nonce，密文不同，明文仍是同一个，同样不构成复用。
+//! NewConnId 与数据段共用流 0 的序列号，两者的 nonce 不会重复。每个流以第一个加密的序列号为起点，最多加密 `NONCE_LIMIT` 个序列号，
+//! 再往后低 48 位将回绕、与已用过的 nonce 重复：此时 `Sealer::seal` 返回 `LinkError::NonceExhausted`，连接以 Rst 关闭。
+//! 密文比明文多 `TAG_LEN` 字节，消息连同标签仍须放进对端的 `recv_buffer`。
+
+use crate::error::LinkError;
+use crate::segment::{Segment, SegmentError, SegmentType};
+use crate::seq::SeqNum;
+use crate::segment::SegmentFlags;
+use bytes::Bytes;
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use zeroize::{Zeroize, Zeroizing};
+
+/// 预共享密钥与会话密钥的长度
+pub const KEY_LEN: usize = 32;
+
+/// 认证标签的长度
+pub const TAG_LEN: usize = 16;
+
+/// 握手 nonce 的长度
+pub const NONCE_LEN: usize = 16;
+
+// 握手段认证尾部中 HMAC 标签的长度
+const MAC_LEN: usize = 32;
+
+/// 每个流在一组密钥下可加密的序列号数：nonce 只容纳序列号的低 48 位
+pub const NONCE_LIMIT: u64 = 1 << 48;
+
+// HKDF 展开时的标签：握手密钥与两个方向的流量密钥
+const HANDSHAKE_LABEL: &[u8] = b"link-rs handshake";
+const INITIATOR_LABEL: &[u8] = b"link-rs initiator";
+const RESPONDER_LABEL: &[u8] = b"link-rs responder";
+
+/// 预共享密钥；配置中写作 64 个十六进制字符
+#[derive(Clone, PartialEq, Eq)]
+pub struct PresharedKey([u8; KEY_LEN]);
+
+impl PresharedKey {
+    pub const fn new(bytes: [u8; KEY_LEN]) -> Self {
+        Self(bytes)
+    }
+
+    /// 以系统随机源生成
+    pub fn random() -> Self {
+        let mut bytes = [0u8; KEY_LEN];
+        getrandom::fill(&mut bytes).expect("operating system random source unavailable");
+        Self(bytes)
+    }
+}
+
+impl Drop for PresharedKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+// 不输出密钥
+impl fmt::Debug for PresharedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PresharedKey(..)")
+    }
+}
+
+impl FromStr for PresharedKey {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        if s.len() != 2 * KEY_LEN || !s.is_ascii() {
+            return Err(());
+        }
+        let mut bytes = [0u8; KEY_LEN];
+        for (byte, pair) in bytes.iter_mut().zip(s.as_bytes().chunks_exact(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| ())?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| ())?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+/// 一端为一次握手随机选择的 nonce
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HandshakeNonce([u8; NONCE_LEN]);
+
+impl HandshakeNonce {
+    pub const fn new(bytes: [u8; NONCE_LEN]) -> Self {
+        Self(bytes)
+    }
+
+    /// 以系统随机源生成
+    pub fn random() -> Self {
+        let mut bytes = [0u8; NONCE_LEN];
+        getrandom::fill(&mut bytes).expect("operating system random source unavailable");
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; NONCE_LEN] {
+        &self.0
+    }
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+// 以 HKDF 展开一个 32 字节的密钥
+fn expand(hkdf: &Hkdf<Sha256>, label: &[u8]) -> Zeroizing<[u8; KEY_LEN]> {
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    hkdf.expand(label, key.as_mut()).expect("32 bytes is a valid HKDF output length");
+    key
+}
+
+/// 签名与验证握手段的密钥，只由预共享密钥派生
+pub struct HandshakeAuth {
+    key: Zeroizing<[u8; KEY_LEN]>,
+}
+
+impl HandshakeAuth {
+    pub fn new(psk: &PresharedKey) -> Self {
+        Self { key: expand(&Hkdf::<Sha256>::new(None, &psk.0), HANDSHAKE_LABEL) }
+    }
+
+    fn mac(&self, segment: &Segment, signed: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.key.as_ref()).expect("hmac accepts any key length");
+        mac.update(&segment.authenticated_header());
+        mac.update(signed);
+        mac
+    }
+
+    /// 设置 AUTH 标志并在数据体末尾追加认证尾部：`nonce` 是本端的，`echo` 是回送的对端 nonce（SYN 为全 0）。
+    /// 段头字段（连接 ID、校验算法等）须已写好，之后不能再改
+    pub fn sign(&self, segment: &mut Segment, nonce: HandshakeNonce, echo: HandshakeNonce) {
+        segment.set_flag(SegmentFlags::AUTH, true);
+        let mut data = Vec::with_capacity(segment.data().len() + Segment::AUTH_TRAILER_LEN);
+        data.extend_from_slice(segment.data());
+        data.extend_from_slice(&nonce.0);
+        data.extend_from_slice(&echo.0);
+        let tag = self.mac(segment, &data).finalize().into_bytes();
+        data.extend_from_slice(&tag);
+        segment.set_data(Bytes::from(data));
+    }
+
+    /// 验证认证尾部，返回 (对端的 nonce, 它回送的 nonce)；尾部缺失或不符时返回 `AuthFailed`
+    pub fn verify(&self, segment: &Segment) -> Result<(HandshakeNonce, HandshakeNonce), SegmentError> {
+        let trailer = segment.auth_trailer().ok_or(SegmentError::AuthFailed)?;
+        let signed = &segment.data()[..segment.data().len() - MAC_LEN];
+        self.mac(segment, signed).verify_slice(&trailer[2 * NONCE_LEN..]).map_err(|_| SegmentError::AuthFailed)?;
+        let nonce = trailer[..NONCE_LEN].try_into().expect("nonce length");
+        let echo = trailer[NONCE_LEN..2 * NONCE_LEN].try_into().expect("nonce length");
+        Ok((HandshakeNonce(nonce), HandshakeNonce(echo)))
+    }
+}
+
+impl fmt::Debug for HandshakeAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HandshakeAuth(..)")
+    }
+}
+
+/// 握手完成后派生本端的加密与解密状态：`initiator` 为本端是否发起了握手，
+/// `initiator_nonce` 与 `responder_nonce` 是双方在握手中交换的 nonce
+pub fn session(
+    psk: &PresharedKey,
+    initiator: bool,
+    initiator_nonce: HandshakeNonce,
+    responder_nonce: HandshakeNonce,
+    conn_id: u32,
+) -> (Sealer, Unsealer) {
+    let mut salt = [0u8; 2 * NONCE_LEN + 4];
+    salt[..NONCE_LEN].copy_from_slice(&initiator_nonce.0);
+    salt[NONCE_LEN..2 * NONCE_LEN].copy_from_slice(&responder_nonce.0);
+    salt[2 * NONCE_LEN..].copy_from_slice(&conn_id.to_be_bytes());
+    let hkdf = Hkdf::<Sha256>::new(Some(&salt), &psk.0);
+    let initiator_key = ChaCha20Poly1305::new(&Key::from(*expand(&hkdf, INITIATOR_LABEL)));
+    let responder_key = ChaCha20Poly1305::new(&Key::from(*expand(&hkdf, RESPONDER_LABEL)));
+    let (seal, open) = if initiator { (initiator_key, responder_key) } else { (responder_key, initiator_key) };
+    (Sealer { cipher: seal, origins: HashMap::new(), limit: NONCE_LIMIT }, Unsealer { cipher: open })
+}
+
+// 加密的段类型
+fn is_protected(segment_type: SegmentType) -> bool {
+    matches!(segment_type, SegmentType::Data | SegmentType::NewConnId)
+}
+
+fn nonce(segment: &Segment) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[..4].copy_from_slice(&segment.conn_id().to_be_bytes());
+    nonce[4..6].copy_from_slice(&segment.stream_id().to_be_bytes());
+    nonce[6..].copy_from_slice(&segment.seq().get().to_be_bytes()[2..]);
+    Nonce::from(nonce)
+}
+
+/// 加密本端发出的数据段
+pub struct Sealer {
+    cipher: ChaCha20Poly1305,
+    origins: HashMap<u16, SeqNum>,  // 每个流第一个加密的序列号
+    limit: u64,
+}
+
+impl Sealer {
+    /// 改变每个流可加密的序列号数，测试中以很小的值触发耗尽
+    pub fn with_limit(mut self, limit: u64) -> Self {
+        self.limit = limit.min(NONCE_LIMIT);
+        self
+    }
+
+    /// 流第一个加密的序列号，接续的连接由它继续计算 nonce 的用量（见 `session` 模块）
+    pub(crate) fn origin(&self, stream_id: u16) -> Option<SeqNum> {
+        self.origins.get(&stream_id).copied()
+    }
+
+    pub(crate) fn set_origin(&mut self, stream_id: u16, origin: SeqNum) {
+        self.origins.insert(stream_id, origin);
+    }
+
+    /// 加密数据段与 NewConnId 段的数据体并设置 SEALED 标志，其他段不变；连接 ID 等段头字段须已写好
+    pub fn seal(&mut self, segment: &mut Segment) -> Result<(), LinkError> {
+        if !is_protected(segment.segment_type()) {
+            return Ok(());
+        }
+        let origin = *self.origins.entry(segment.stream_id()).or_insert(segment.seq());
+        if segment.seq().get().wrapping_sub(origin.get()) >= self.limit {
+            return Err(LinkError::NonceExhausted { stream_id: segment.stream_id() });
+        }
+        segment.set_flag(SegmentFlags::SEALED, true);
+        let aad = segment.authenticated_header();
+        let sealed = self
+            .cipher
+            .encrypt(&nonce(segment), Payload { msg: segment.data(), aad: &aad })
+            .expect("payload within the cipher's length limit");
+        segment.set_data(Bytes::from(sealed));
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Sealer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sealer").field("origins", &self.origins).field("limit", &self.limit).finish_non_exhaustive()
+    }
+}
+
+/// 解密对端发来的数据段
+pub struct Unsealer {
+    cipher: ChaCha20Poly1305,
+}
+
+impl Unsealer {
+    /// 返回明文的数据段与 NewConnId 段（清除 SEALED 标志），其他段原样返回；未加密的这两种段与认证失败都返回 `DecryptFailed`
+    pub fn open(&self, mut segment: Segment) -> Result<Segment, SegmentError> {
+        if !is_protected(segment.segment_type()) {
+            return match segment.is_sealed() {
+                true => Err(SegmentError::DecryptFailed),
+                false => Ok(segment),
+            };
+        }
+        if !segment.is_sealed() {
+            return Err(SegmentError::DecryptFailed);
+        }
+        let aad = segment.authenticated_header();
+        let plain = self
+            .cipher
+            .decrypt(&nonce(&segment), Payload { msg: segment.data(), aad: &aad })
+            .map_err(|_| SegmentError::DecryptFailed)?;
+        segment.set_flag(SegmentFlags::SEALED, false);
+        segment.set_data(Bytes::from(plain));
+        Ok(segment)
+    }
+}
+
+impl fmt::Debug for Unsealer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Unsealer").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::ChecksumAlgorithm;
+
+    const PSK: PresharedKey = PresharedKey::new([7; KEY_LEN]);
+
+    // 客户端（发起方）与服务端的会话
+    fn pair() -> ((Sealer, Unsealer), (Sealer, Unsealer)) {
+        let (client_nonce, server_nonce) = (HandshakeNonce::new([1; NONCE_LEN]), HandshakeNonce::new([2; NONCE_LEN]));
+        (session(&PSK, true, client_nonce, server_nonce, 9), session(&PSK, false, client_nonce, server_nonce, 9))
+    }
+
+    fn data(seq: u64, payload: &'static [u8], checksum: ChecksumAlgorithm) -> Segment {
+        let mut segment = Segment::builder(SegmentType::Data).data_seq(seq).conn_id(9).payload(payload).build().unwrap();
+        segment.set_checksum(checksum);
+        segment
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let ((mut client, client_unsealer), (mut server, server_unsealer)) = pair();
+        let mut segment = data(1001, b"attack at dawn", ChecksumAlgorithm::Crc32c);
+        client.seal(&mut segment).unwrap();
+        assert!(segment.is_sealed());
+        assert_eq!(segment.data().len(), b"attack at dawn".len() + TAG_LEN);
+        assert!(!segment.data().windows(6).any(|w| w == b"attack"));
+
+        let encoded = segment.encode().unwrap();
+        let opened = Segment::decode_sealed(&encoded, ChecksumAlgorithm::Crc32c, &server_unsealer).unwrap();
+        assert_eq!(opened, data(1001, b"attack at dawn", ChecksumAlgorithm::Crc32c));
+
+        // 两个方向的密钥不同：自己封装的段自己打不开
+        assert_eq!(Segment::decode_sealed(&encoded, ChecksumAlgorithm::Crc32c, &client_unsealer), Err(SegmentError::DecryptFailed));
+        // 另一个方向照常
+        let mut reply = data(5001, b"ack", ChecksumAlgorithm::Crc32c);
+        server.seal(&mut reply).unwrap();
+        assert_eq!(client_unsealer.open(reply).unwrap().data().as_ref(), b"ack");
+
+        // 控制段不加密，未加密的数据段被拒绝
+        let mut ack = Segment::builder(SegmentType::Ack).ack(1002u64).build().unwrap();
+        client.seal(&mut ack).unwrap();
+        assert_eq!(server_unsealer.open(ack.clone()), Ok(ack));
+        assert_eq!(server_unsealer.open(data(1002, b"plain", ChecksumAlgorithm::Crc32c)), Err(SegmentError::DecryptFailed));
+    }
+
+    #[test]
+    fn test_tampered_ciphertext_and_header() {
+        let ((mut client, _), (_, server_unsealer)) = pair();
+        // 不校验的段：篡改后只有认证标签能发现
+        let mut segment = data(1001, b"transfer 100", ChecksumAlgorithm::NoChecksum);
+        client.seal(&mut segment).unwrap();
+        let encoded = segment.encode().unwrap();
+        assert!(Segment::decode_sealed(&encoded, ChecksumAlgorithm::NoChecksum, &server_unsealer).is_ok());
+
+        let mut ciphertext = encoded.clone();
+        ciphertext[Segment::FIXED_HEADER_LEN + 2] ^= 0x01;
+        assert_eq!(Segment::decode_sealed(&ciphertext, ChecksumAlgorithm::NoChecksum, &server_unsealer), Err(SegmentError::DecryptFailed));
+
+        let mut tag = encoded.clone();
+        let last = tag.len() - 1;
+        tag[last] ^= 0x80;
+        assert_eq!(Segment::decode_sealed(&tag, ChecksumAlgorithm::NoChecksum, &server_unsealer), Err(SegmentError::DecryptFailed));
+
+        // 段头是关联数据：改动序列号（nonce 随之改变）或窗口（只在关联数据中）都无法通过认证
+        for offset in [12 + 7, 28] {
+            let mut header = encoded.clone();
+            header[offset] ^= 0x01;
+            assert_eq!(Segment::decode_sealed(&header, ChecksumAlgorithm::NoChecksum, &server_unsealer), Err(SegmentError::DecryptFailed), "offset {}", offset);
+        }
+        // 途中的拥塞标记例外
+        let mut marked = encoded.clone();
+        crate::segment::mark_ce(&mut marked);
+        assert!(Segment::decode_sealed(&marked, ChecksumAlgorithm::NoChecksum, &server_unsealer).unwrap().flags().contains(SegmentFlags::CE));
+    }
+
+    #[test]
+    fn test_nonce_exhaustion() {
+        let ((client, _), (_, server_unsealer)) = pair();
+        let mut client = client.with_limit(4);
+        // 重传同一序列号不消耗额度，得到同一密文
+        let mut first = data(1001, b"0", ChecksumAlgorithm::Crc32c);
+        let mut again = first.clone();
+        client.seal(&mut first).unwrap();
+        client.seal(&mut again).unwrap();
+        assert_eq!(first, again);
+        for seq in 1002..1005 {
+            let mut segment = data(seq, b"n", ChecksumAlgorithm::Crc32c);
+            client.seal(&mut segment).unwrap();
+            assert!(server_unsealer.open(segment).is_ok());
+        }
+        let mut segment = data(1005, b"n", ChecksumAlgorithm::Crc32c);
+        assert_eq!(client.seal(&mut segment), Err(LinkError::NonceExhausted { stream_id: 0 }));
+        assert!(!segment.is_sealed());
+        // 额度按流计算
+        let mut other = data(0, b"s", ChecksumAlgorithm::Crc32c);
+        other.set_stream_id(1);
+        client.seal(&mut other).unwrap();
+    }
+
+    #[test]
+    fn test_new_conn_id_is_sealed() {
+        let ((mut client, _), (_, server_unsealer)) = pair();
+        let mut rotation = Segment::new_conn_id(SeqNum::new(1001), 0x0102_0304);
+        rotation.set_conn_id(9);
+        client.seal(&mut rotation).unwrap();
+        assert!(rotation.is_sealed());
+        assert_eq!(rotation.data().len(), 4 + TAG_LEN);
+        let encoded = rotation.encode().unwrap();
+        let opened = Segment::decode_sealed(&encoded, ChecksumAlgorithm::default(), &server_unsealer).unwrap();
+        assert_eq!(opened.issued_conn_id(), Some(0x0102_0304));
+
+        // 没有密钥的一方注入的明文 NewConnId 被拒绝
+        let mut forged = Segment::new_conn_id(SeqNum::new(1002), 0xbad);
+        forged.set_conn_id(9);
+        assert_eq!(server_unsealer.open(forged), Err(SegmentError::DecryptFailed));
+    }
+
+    #[test]
+    fn test_connections_get_distinct_keys() {
+        // 同一个预共享密钥、同一个连接 ID，只有握手 nonce 不同
+        let (mut first, first_unsealer) = session(&PSK, true, HandshakeNonce::random(), HandshakeNonce::random(), 9);
+        let (mut second, second_unsealer) = session(&PSK, true, HandshakeNonce::random(), HandshakeNonce::random(), 9);
+        let (mut a, mut b) = (data(1001, b"same plaintext", ChecksumAlgorithm::Crc32c), data(1001, b"same plaintext", ChecksumAlgorithm::Crc32c));
+        first.seal(&mut a).unwrap();
+        second.seal(&mut b).unwrap();
+        assert_ne!(a.data(), b.data());
+        assert_eq!(first_unsealer.open(a.clone()), Err(SegmentError::DecryptFailed));
+        assert_eq!(second_unsealer.open(b.clone()), Err(SegmentError::DecryptFailed));
+
+        // 连接 ID 同样参与派生
+        let nonces = (HandshakeNonce::new([1; NONCE_LEN]), HandshakeNonce::new([2; NONCE_LEN]));
+        let (mut other, _) = session(&PSK, true, nonces.0, nonces.1, 10);
+        let ((mut same, _), (_, server_unsealer)) = pair();
+        let (mut c, mut d) = (data(1001, b"x", ChecksumAlgorithm::Crc32c), data(1001, b"x", ChecksumAlgorithm::Crc32c));
+        same.seal(&mut c).unwrap();
+        other.seal(&mut d).unwrap();
+        assert_ne!(c.data(), d.data());
+        assert!(server_unsealer.open(c).is_ok());
+    }
+
+    #[test]
+    fn test_handshake_auth() {
+        let auth = HandshakeAuth::new(&PSK);
+        let client_nonce = HandshakeNonce::random();
+        let mut syn = Segment::builder(SegmentType::Syn).data_seq(1000u64).offer(&[ChecksumAlgorithm::XxHash32]).build().unwrap();
+        auth.sign(&mut syn, client_nonce, HandshakeNonce::default());
+        assert_eq!(syn.checksum_offer(), vec![ChecksumAlgorithm::XxHash32]);
+        let encoded = syn.encode().unwrap();
+        let decoded = Segment::decode(&encoded).unwrap();
+        assert_eq!(auth.verify(&decoded), Ok((client_nonce, HandshakeNonce::default())));
+
+        // 篡改 nonce、回送的 nonce、标签或算法列表都无法通过认证（途中篡改时校验和会先发现，攻击者可以重算它）
+        let nonce_at = decoded.data().len() - Segment::AUTH_TRAILER_LEN;
+        for offset in [nonce_at, nonce_at + 5, nonce_at + NONCE_LEN + 3, decoded.data().len() - 1, 0] {
+            let mut data = decoded.data().to_vec();
+            data[offset] ^= 0x01;
+            let mut tampered = decoded.clone();
+            tampered.set_data(Bytes::from(data));
+            assert_eq!(auth.verify(&tampered), Err(SegmentError::AuthFailed), "offset {}", offset);
+        }
+        // 段头同样被签名
+        let mut header = decoded.clone();
+        header.set_conn_id(77);
+        assert_eq!(auth.verify(&header), Err(SegmentError::AuthFailed));
+
+        // 另一个预共享密钥签名的段、没有认证尾部的段
+        let other = HandshakeAuth::new(&PresharedKey::new([8; KEY_LEN]));
+        assert_eq!(other.verify(&decoded), Err(SegmentError::AuthFailed));
+        let plain = Segment::builder(SegmentType::Syn).data_seq(1000u64).build().unwrap();
+        assert_eq!(auth.verify(&plain), Err(SegmentError::AuthFailed));
+
+        // SYN-ACK 回送客户端的 nonce，完成握手的确认也可以只携带尾部
+        let server_nonce = HandshakeNonce::random();
+        let mut ack = Segment::builder(SegmentType::Ack).conn_id(9).data_seq(1000u64).ack(5000u64).build().unwrap();
+        auth.sign(&mut ack, client_nonce, server_nonce);
+        let ack = Segment::decode(&ack.encode().unwrap()).unwrap();
+        assert_eq!(auth.verify(&ack), Ok((client_nonce, server_nonce)));
+    }
+
+    #[test]
+    fn test_parse_key() {
+        let hex = "00112233445566778899aabbccddeeff00112233445566778899AABBCCDDEEFF";
+        let key: PresharedKey = hex.parse().unwrap();
+        assert_eq!(key.0[..4], [0x00, 0x11, 0x22, 0x33]);
+        assert_eq!(key.0[31], 0xFF);
+        assert!(hex[1..].parse::<PresharedKey>().is_err());
+        assert!(hex.replace('0', "g").parse::<PresharedKey>().is_err());
+        assert_eq!(format!("{:?}", key), "PresharedKey(..)");
+    }
+}
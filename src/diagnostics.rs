@@ -0,0 +1,219 @@
+//! 启动自检：验证本机到服务器的网络路径
+//! 部署在静默丢弃 UDP 的防火墙、MTU 过小的隧道或映射很快过期的 NAT 后面时，连接的表现都只是"卡住"。
+//! `selftest` 连接到回显服务器（`server::EchoHandler`），按顺序走一遍固定的步骤，给出一个 `Diagnosis`：
+//!
+//! 1. 握手：`LinkConfig::handshake_timeout` 内没有得到任何回应，诊断为没有 UDP 连通性。
+//! 2. 连续发出 `burst` 个 Ping 段（见 `ping` 模块），按没有得到回应的比例估计丢包率；Ping 不重传，也不排在数据后面。
+//! 3. 填充到 `padded` 字节的一个段经回显往返，两个方向各经过一次这么大的数据报；小的探测有回应而它没有，诊断为路径 MTU 不足。
+//! 4. 空闲 `idle`，期间只有保活探测（`keepalive_interval` 与 `path_keepalive_interval`），最后再往返一条消息。
+//!    对端在新地址上验证本端（PathChallenge，见 `listener` 模块）或以 Rst 中止连接说明 NAT 换了映射，
+//!    此前静默的时长是映射存活时间的上界。
+//!
+//! 发现问题之后不再进行后面的步骤。`src/bin/client.rs` 的 `selftest` 子命令是命令行入口，以 `Diagnosis::exit_code` 退出；
+//! `Server::selftest` 经回环地址检查服务器自己。
+
+use crate::config::LinkConfig;
+use crate::connection::Connection;
+use crate::error::LinkError;
+use crate::ping::{PingOptions, Pinger};
+use crate::segment::Segment;
+use crate::state::ConnState;
+use crate::transport::Transport;
+use bytes::Bytes;
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::time::{Instant, sleep, timeout};
+
+/// 空闲期间查看连接统计的间隔
+const IDLE_POLL: Duration = Duration::from_millis(100);
+
+/// 突发探测相邻两个的间隔：探测与 Pong 各自成为一个数据报，不被合并成大数据报而整批丢失
+const BURST_SPACING: Duration = Duration::from_millis(10);
+
+/// 自检的各个步骤的参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelftestOptions {
+    pub padded: usize,      // 填充后的段的字节数（段头加数据）
+    pub timeout: Duration,  // 等待回显与突发探测的回应的时间
+    pub burst: usize,       // 突发的 Ping 段数
+    pub max_loss: f64,      // 突发探测允许的丢失率（0..=1），超过时诊断为高丢包
+    pub idle: Duration,     // 空闲的时长，0 表示跳过这一步
+}
+
+impl Default for SelftestOptions {
+    fn default() -> Self {
+        Self { padded: 1200, timeout: Duration::from_secs(3), burst: 50, max_loss: 0.1, idle: Duration::from_secs(30) }
+    }
+}
+
+/// 自检的结论
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Diagnosis {
+    Healthy,
+    NoConnectivity,                     // 握手没有得到任何回应
+    PathMtu { below: usize },           // 这么大的数据报到不了对端，或对端的回应到不了本端
+    HighLoss { loss: f64 },             // 突发探测的丢失率（0..=1）
+    NatRebinding { after: Duration },   // 静默这么久之后本端的地址在对端看来变了
+}
+
+impl Diagnosis {
+    /// 命令行的退出码：正常为 0，每种问题各不相同（1 与 2 留给运行错误与参数错误）
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Diagnosis::Healthy => 0,
+            Diagnosis::NoConnectivity => 3,
+            Diagnosis::PathMtu { .. } => 4,
+            Diagnosis::HighLoss { .. } => 5,
+            Diagnosis::NatRebinding { .. } => 6,
+        }
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnosis::Healthy => write!(f, "healthy: full-size datagrams get through both ways and the address survives idle periods"),
+            Diagnosis::NoConnectivity => write!(f, "no UDP connectivity: the handshake got no answer (is a firewall dropping UDP?)"),
+            Diagnosis::PathMtu { below } => write!(
+                f, "path MTU below {} bytes: small segments get through but {}-byte datagrams do not (lower --max-payload)",
+                below, below
+            ),
+            Diagnosis::HighLoss { loss } => write!(f, "high loss ({:.0}%): probes sent in a burst went unanswered", loss * 100.0),
+            Diagnosis::NatRebinding { after } => write!(
+                f, "NAT rebinds after ~{}s: the address changed after that long without traffic (keep keepalive intervals below it)",
+                after.as_secs_f64().round()
+            ),
+        }
+    }
+}
+
+/// 自检的测量与结论；没有进行或没有结果的测量为 None
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Report {
+    pub handshake: Option<Duration>,    // 握手用时
+    pub padded_rtt: Option<Duration>,   // 填充段经回显的往返时间
+    pub loss: Option<f64>,              // 突发探测的丢失率
+    pub burst_rtt: Option<Duration>,    // 突发探测的平均 RTT
+    pub diagnosis: Diagnosis,
+}
+
+/// 连接 `target` 上的回显服务器做一次自检，最后关闭连接。握手被拒绝等不属于诊断的失败返回错误
+#[cfg(feature = "tokio")]
+pub async fn selftest(target: SocketAddr, options: &SelftestOptions, config: LinkConfig) -> Result<Report, LinkError> {
+    run(Connection::connect_with(target, config), options).await
+}
+
+/// 在调用方提供的传输上自检，见 `Connection::connect_over`；其余同 `selftest`
+pub async fn selftest_over(transport: impl Transport, target: SocketAddr, options: &SelftestOptions, config: LinkConfig) -> Result<Report, LinkError> {
+    run(Connection::connect_over(transport, target, config), options).await
+}
+
+async fn run(connect: impl Future<Output = Result<Connection, LinkError>>, options: &SelftestOptions) -> Result<Report, LinkError> {
+    let mut report = Report { handshake: None, padded_rtt: None, loss: None, burst_rtt: None, diagnosis: Diagnosis::Healthy };
+    let started = Instant::now();
+    let connection = match connect.await {
+        Ok(connection) => connection,
+        Err(e) if unanswered(&e) => {
+            report.diagnosis = Diagnosis::NoConnectivity;
+            return Ok(report);
+        }
+        Err(e) => return Err(e),
+    };
+    report.handshake = Some(started.elapsed());
+    let diagnosis = check(&connection, options, &mut report).await;
+    // 结论已经确定；路径有问题时关闭可能一直等不到确认
+    if timeout(options.timeout, connection.close()).await.is_err() {
+        tracing::debug!("close after the selftest timed out");
+    }
+    report.diagnosis = diagnosis?;
+    Ok(report)
+}
+
+// 握手之后的步骤，返回结论
+async fn check(connection: &Connection, options: &SelftestOptions, report: &mut Report) -> Result<Diagnosis, LinkError> {
+    // 突发探测在填充段之前：到不了对端的填充段会一直重传，与之合并进同一个数据报的探测也随之丢失
+    let burst = PingOptions { count: options.burst, interval: BURST_SPACING, timeout: options.timeout };
+    let summary = Pinger::new(connection).run(&burst, |_| {}).await?;
+    report.loss = Some(summary.loss());
+    report.burst_rtt = summary.mean_rtt;
+    if summary.loss() > options.max_loss {
+        return Ok(Diagnosis::HighLoss { loss: summary.loss() });
+    }
+
+    let padded = Bytes::from(vec![0x5a; options.padded.saturating_sub(Segment::FIXED_HEADER_LEN)]);
+    let sent_at = Instant::now();
+    connection.send(padded.clone()).await?;
+    match timeout(options.timeout, connection.recv()).await {
+        Err(_) => return Ok(Diagnosis::PathMtu { below: options.padded }),
+        Ok(received) => {
+            if received?.ok_or(LinkError::Closed)? != padded {
+                return Err(LinkError::Protocol("selftest needs an echo server: the reply differs from the padded segment".to_string()));
+            }
+            report.padded_rtt = Some(sent_at.elapsed());
+        }
+    }
+    if options.idle.is_zero() {
+        return Ok(Diagnosis::Healthy);
+    }
+
+    // 空闲：只有保活探测。NAT 换了映射时，对端在新地址上收到探测后发来 PathChallenge；
+    // 探测无从校验而不能触发迁移时，对端以 Rst 回应陌生地址，连接随之中止
+    let challenged = connection.stats().path_challenges;
+    let rebound = || connection.stats().path_challenges > challenged || connection.state() == ConnState::Aborted;
+    let quiet = Instant::now();
+    let deadline = quiet + options.idle;
+    while Instant::now() < deadline {
+        sleep(IDLE_POLL.min(deadline - Instant::now())).await;
+        if rebound() {
+            return Ok(Diagnosis::NatRebinding { after: quiet.elapsed() });
+        }
+    }
+    // 没有保活时由这条消息换出新的映射
+    let echoed = timeout(options.timeout, async {
+        connection.send(Bytes::from_static(b"selftest")).await?;
+        connection.recv().await
+    })
+    .await;
+    match echoed {
+        Err(_) | Ok(Err(LinkError::Reset)) => Ok(Diagnosis::NatRebinding { after: options.idle }),
+        Ok(_) if rebound() => Ok(Diagnosis::NatRebinding { after: options.idle }),
+        Ok(received) => received?.ok_or(LinkError::Closed).map(|_| Diagnosis::Healthy),
+    }
+}
+
+// 握手没有得到任何回应，而不是被拒绝
+fn unanswered(e: &LinkError) -> bool {
+    match e {
+        LinkError::ConnectTimeout { .. } => true,
+        LinkError::ConnectFailed(attempts) => attempts.iter().all(|(_, e)| unanswered(e)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnoses_have_distinct_exit_codes() {
+        let diagnoses = [
+            Diagnosis::Healthy,
+            Diagnosis::NoConnectivity,
+            Diagnosis::PathMtu { below: 1200 },
+            Diagnosis::HighLoss { loss: 0.3 },
+            Diagnosis::NatRebinding { after: Duration::from_millis(4900) },
+        ];
+        let mut codes: Vec<_> = diagnoses.iter().map(Diagnosis::exit_code).collect();
+        assert_eq!(codes[0], 0);
+        codes.dedup();
+        assert_eq!(codes.len(), diagnoses.len());
+        assert!(diagnoses[2].to_string().starts_with("path MTU below 1200 bytes"));
+        assert!(diagnoses[3].to_string().starts_with("high loss (30%)"));
+        assert!(diagnoses[4].to_string().starts_with("NAT rebinds after ~5s"));
+
+        assert!(unanswered(&LinkError::ConnectTimeout { attempts: 3, elapsed: Duration::from_secs(10) }));
+        assert!(!unanswered(&LinkError::ConnectFailed(vec![("127.0.0.1:1".parse().unwrap(), LinkError::Refused)])));
+    }
+}
@@ -0,0 +1,2868 @@
+//! 不做 IO 的连接协议核心
+//! `ConnectionCore` 持有一条已建立连接的全部协议状态：各个流的发送端与接收端、状态机、保活与路径 MTU 探测，
+//! 不依赖 tokio 与套接字。调用方把收到的数据报交给 `handle_datagram`（已自行解码的段交给 `handle_segment`），
+//! 在 `next_deadline` 到达时调用 `handle_timeout`，再用 `poll_transmit` 取出要发送的数据报；
+//! 每个入口都显式接收当前时间，测试可以用假时钟驱动，`connection` 模块的驱动任务只负责把套接字与定时器泵进来。
+//! 处理过程中驱动层需要知道的事情（Pong、统计查询的回应、新流、连接 ID 的轮换、空洞事件、解码失败、连接结束）以 `Event` 返回；
+//! 设置了观察者时，建立、状态迁移与结束连同各流发送端的重传等事件由 `take_observations` 取出（见 `observer` 模块）。
+//! 对端的统计查询（StatsRequest）以流 0 的统计回应，每条连接每 `STATS_REPLY_INTERVAL` 至多回应一次，
+//! 回应比查询大，不限速时伪造源地址的查询可以把连接变成放大流量的反射点。
+//! 重新同步（`resync`）只作用于流 0：请求与回应都是携带选项的确认段（见 `options` 模块），对端的发送端放弃起点之前的数据
+//! （见 `Sender::resync`），本端的接收端丢弃回应的位置之前尚未交付的数据；没有请求在等待时到达的回应被忽略。
+//!
+//! 应用数据的收发有两种形式：`send_data`/`recv_data` 立即返回，`poll_*` 未就绪时登记调用方的 waker，
+//! 之后的 `handle_*` 让它就绪时唤醒。发出的段在 `poll_transmit` 时才打上连接 ID 与校验算法、
+//! 配置了密钥时加密，并按当前的有效 MSS 打包成数据报。控制段（数据段以外的一切：确认、FIN、Ping/Pong 与探测等）
+//! 另排一队，先于排队的数据发出，数据的数据报还有余量时捎带上它们；连续 `CONTROL_BURST` 个控制数据报之后
+//! 让一个数据的数据报先走，源源不断的控制段饿不死数据。接收端产生的确认不构造 `Segment`：以栈上的 `AckFrame`（见 `ack_frame` 模块）
+//! 排在控制段之前，直接编码进数据报；入站的流 0 的普通确认同样直接解析，经 `Sender::on_ack_frame` 交给发送端，稳定状态下确认的收发都不分配。数据段按流分别打包，各个流的数据报按优先级轮流发出（见 `schedule` 模块）。
+//! 驱动层发送时本机报告数据报过大（EMSGSIZE）的，把它交回 `on_datagram_too_large`：有效 MSS 降到它之下，
+//! 其中还没发出过的数据按新的大小重新分片发送（见 `Sender::rewind`），而不是等重传原样再失败。
+//!
+//! 客户端握手同样不做 IO（`Opener`），完成后的 `Handshake` 用来构造 `ConnectionCore`；握手中协商出的参数（见 `params` 模块）
+//! 覆盖本端配置中的确认频率、保活间隔与 SACK。
+//! 服务端的半开握手（cookie、Retry 与连接表）仍由 `listener` 模块处理。
+//!
+//! 连接 ID 的轮换（`rotate_conn_id`，或按 `LinkConfig::conn_id_rotation_bytes` 自动进行）见 `rotation` 模块：
+//! 入站段在交给状态机之前核对连接 ID，携带已退役 ID 的被丢弃。
+//!
+//! 连接的内存占用记在它的 `MemoryAccount` 中（见 `budget` 模块）：与分片重组的限制在同样的时机按当前的缓冲登记各部分，
+//! 超出连接的上限时丢弃攒着的分片，再把剩余额度平分给各个流的接收窗口与发送队列。监听器接受的连接由 `set_memory_account`
+//! 换上计入监听器账本的账户，全局额度紧张时窗口随之收小。
+
+use crate::ack_frame::AckFrame;
+use crate::budget::{Component, MemoryAccount};
+use crate::checksum::{self, ChecksumAlgorithm};
+use crate::close_codes::CloseCode;
+use crate::config::{DEFAULT_MSS, LinkConfig, MAX_DATAGRAM};
+#[cfg(feature = "crypto")]
+use crate::crypto::{self, HandshakeAuth, HandshakeNonce, Sealer, Unsealer};
+use crate::error::LinkError;
+use crate::gaps::GapEvent;
+use crate::keepalive::{Keepalive, KeepaliveAction};
+use crate::observer::{Observation, Observations};
+use crate::options::{self, Options, SegmentOption};
+use crate::params::TransportParameters;
+use crate::pmtu::PathMtu;
+use crate::pool::{BufferPool, Scratch};
+use crate::receiver::{Receiver, TaggedMessage};
+use crate::reflect;
+use crate::recv_buffer::InsertOutcome;
+use crate::rotation::ConnIds;
+use crate::schedule::{DEFAULT_PRIORITY, Scheduler};
+use crate::segment::{self, Segment, SegmentError, SegmentFlags, SegmentType};
+use crate::sender::{SendOptions, Sender};
+use crate::seq::SeqNum;
+use crate::session::{SessionError, SessionState};
+use crate::state::{Action, ConnState, Input, InvalidTransition, Output, StateMachine, Transition};
+use crate::stats::{ConnectionStats, PeerStats};
+use crate::timer::Timers;
+use crate::trace::{self, Direction};
+use crate::tuning::{ConnOption, OptionName};
+use crate::unreliable::{self, Unreliable};
+use crate::watermarks::{WatermarkEvents, WatermarkTracker};
+use bytes::{Buf, Bytes, BytesMut};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker, ready};
+use std::time::{Duration, Instant};
+
+/// TIME_WAIT 的持续时间：足够吸收对端重传的 FIN
+pub(crate) const TIME_WAIT: Duration = Duration::from_secs(2);
+
+/// 两次回应对端统计查询的最小间隔，间隔内的查询被忽略
+pub const STATS_REPLY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 连接自身的流 ID
+pub const MAIN_STREAM: u16 = 0;
+
+/// 有数据排队时，连续优先发出的控制数据报至多这么多个，之后发出一个数据的数据报
+pub const CONTROL_BURST: usize = 4;
+
+/// 附加流的序列号空间都从这里开始（连接的 ISN 已保护了握手）
+const STREAM_ISN: SeqNum = SeqNum::new(0);
+
+/// 为新的握手生成初始序列号：取自操作系统的随机源，知道地址的旁路攻击者猜不出序列号，无法盲注入段
+pub(crate) fn fresh_isn() -> SeqNum {
+    let mut isn = [0u8; 8];
+    getrandom::fill(&mut isn).expect("operating system random source unavailable");
+    SeqNum::new(u64::from_be_bytes(isn))
+}
+
+/// 本端在握手中通告的 MSS：能接收的最大数据报，即不被截断的 `recv_buffer`
+pub(crate) fn advertised_mss(config: &LinkConfig) -> usize {
+    config.recv_buffer.min(MAX_DATAGRAM)
+}
+
+// 握手段携带的 MSS 与参数选项；`mss` 不超过 `MAX_DATAGRAM`，总能放进两个字节
+fn handshake_options(mss: usize, params: &TransportParameters) -> Options {
+    Options::new()
+        .with(SegmentOption::Mss(u16::try_from(mss).unwrap_or(u16::MAX)))
+        .and_then(|options| options.with(SegmentOption::Params(*params)))
+        .expect("mss and params fit")
+}
+
+/// 本端在握手中提出的参数（见 `params` 模块）；`early_data` 是本端是否接受 0-RTT 数据，发起方总是允许
+pub(crate) fn local_params(config: &LinkConfig, early_data: bool) -> TransportParameters {
+    TransportParameters {
+        ack_every: u16::try_from(config.ack_every_n_segments).unwrap_or(u16::MAX),
+        keepalive_interval: config.keepalive_interval.max(Duration::from_millis(1)),
+        sack: config.sack,
+        early_data,
+        checksum: config.checksums[0],
+    }
+}
+
+/// 与对端在握手段中携带的参数合并出生效的参数，校验算法取协商出的 `checksum`；对端没有携带参数（旧版本）时按本端的
+pub(crate) fn negotiate(local: &TransportParameters, segment: &Segment, checksum: ChecksumAlgorithm) -> TransportParameters {
+    let peer = segment.options().params().unwrap_or(*local);
+    TransportParameters { checksum, ..local.merge(&peer) }
+}
+
+/// 对端在握手段中通告的 MSS，没有通告时为 None；放不下段头的通告是协议错误
+pub(crate) fn peer_mss(segment: &Segment) -> Result<Option<usize>, LinkError> {
+    match segment.options().mss().map(usize::from) {
+        Some(mss) if mss <= Segment::FIXED_HEADER_LEN => Err(LinkError::Protocol(format!("peer advertised an mss of {} bytes", mss))),
+        mss => Ok(mss),
+    }
+}
+
+/// 告知对端连接因 `code` 结束、或以它拒绝握手的 Rst；没有码时不携带错误码选项
+pub(crate) fn rst(code: Option<CloseCode>) -> Segment {
+    let mut rst = Segment::builder(SegmentType::Rst).build().expect("rst segment is always valid");
+    if let Some(code) = code {
+        rst.set_options(Options::new().with(SegmentOption::Error(code)).expect("a single option fits"));
+    }
+    rst
+}
+
+/// 握手的结果：进入 Established 的状态机、双方的 ISN、服务端分配的连接 ID 与本端的角色
+#[derive(Debug)]
+pub struct Handshake {
+    pub(crate) state: StateMachine,
+    pub(crate) local_isn: SeqNum,
+    pub(crate) peer_isn: SeqNum,
+    pub(crate) conn_id: u32,
+    pub(crate) initiator: bool,     // 本端发起了握手（客户端）
+    pub(crate) checksum: ChecksumAlgorithm, // 协商出的校验算法
+    pub(crate) peer_mss: Option<usize>,     // 对端在握手中通告的 MSS
+    pub(crate) early_data: bool,    // 服务端：握手由随 SYN 到达的 0-RTT 数据完成
+    pub(crate) params: TransportParameters, // 与对端协商出的参数
+    #[cfg(feature = "crypto")]
+    pub(crate) nonces: Option<(HandshakeNonce, HandshakeNonce)>,    // 配置了密钥时双方的握手 nonce：（发起方, 响应方）
+    #[cfg(feature = "crypto")]
+    pub(crate) reauth: Option<Segment>,     // 发起方签名的最后确认，对端重发 SYN-ACK 时原样重发
+}
+
+// 握手的结果中导出连接状态（见 `session` 模块）还需要、构造连接之后不再用到的部分
+#[derive(Debug, Clone, Copy)]
+struct Origin {
+    peer_isn: SeqNum,
+    conn_id: u32,               // 握手分配的连接 ID，参与会话密钥的派生
+    peer_mss: Option<usize>,
+    #[cfg(feature = "crypto")]
+    nonces: Option<(HandshakeNonce, HandshakeNonce)>,
+}
+
+/// `ConnectionCore` 处理入站数据与定时器时产生的、驱动层需要知道的事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// 收到一个不属于路径 MTU 探测的 Pong：它的 nonce 与到达时间（见 `ping::Pinger`）
+    Pong { nonce: u64, at: Instant },
+    /// 收到统计查询的回应：查询的 nonce 与解码出的对端统计，统计无法解码时为错误
+    StatsReply { nonce: u64, stats: Result<PeerStats, SegmentError> },
+    /// 对端回应了重新同步：开始交付的序列号，拒绝时为 `ResyncRefused`
+    Resynced(Result<SeqNum, LinkError>),
+    /// 对端打开了流，等待 `poll_accept` 取走
+    StreamOpened(u16),
+    /// 路径 MTU 探测或本机报告数据报过大（`on_datagram_too_large`）改变了有效 MSS
+    MssChanged(usize),
+    /// 对端确认了本端发出的 NewConnId：之后它发来的段携带 `new`，`old` 在宽限期后退役
+    ConnIdChanged { old: u32, new: u32 },
+    /// 流 0 的重排缓冲区中空洞出现或结束（`track_gaps` 之后，见 `gaps` 模块）
+    Gap(GapEvent),
+    /// 数据报中有无法解码的部分，它之后的内容被丢弃
+    DecodeFailed(SegmentError),
+    /// 连接失败，只报告一次
+    Failed(LinkError),
+    /// 连接正常结束（FIN 交换完成，TIME_WAIT 已到期），只报告一次
+    Closed,
+}
+
+// 一个流的可靠传输状态：流 0 的在 `ConnectionCore::main`，附加流的在 `ConnectionCore::streams`
+#[derive(Debug)]
+pub(crate) struct StreamState {
+    pub(crate) sender: Sender,
+    pub(crate) receiver: Receiver,
+    write_closed: bool,         // 写方向已关闭（半关闭或 close），读方向不受影响
+    abandoned: bool,            // 附加流的句柄已丢弃，到达的数据直接丢弃
+    priority: u8,               // 与其他流分享链路的权重（见 `schedule` 模块）
+}
+
+impl StreamState {
+    fn new(config: &LinkConfig) -> Self {
+        Self {
+            sender: Sender::new(STREAM_ISN, config),
+            receiver: Receiver::new(STREAM_ISN, config),
+            write_closed: false,
+            abandoned: false,
+            priority: DEFAULT_PRIORITY,
+        }
+    }
+
+    // 本端的 FIN 已被确认，对端的 FIN 之前的数据也已全部交付
+    fn is_done(&self) -> bool {
+        self.sender.fin_acked() && self.receiver.is_finished()
+    }
+}
+
+// 为流产生的段打上流 ID
+fn tagged(id: u16, segments: impl IntoIterator<Item = Segment>) -> impl Iterator<Item = Segment> {
+    segments.into_iter().map(move |mut segment| {
+        segment.set_stream_id(id);
+        segment
+    })
+}
+
+/// 一条已建立连接的协议状态机，不做 IO、不读时钟
+#[derive(Debug)]
+pub struct ConnectionCore {
+    state: StateMachine,
+    pub(crate) main: StreamState,
+    pub(crate) streams: HashMap<u16, StreamState>,
+    initiator: bool,
+    pub(crate) next_local_stream: u32,     // 本端下一个可分配的流 ID（超出 u16 即用尽）
+    next_peer_stream: u32,      // 对端下一个新流的最小 ID，更小的 ID 都已打开过
+    accept_queue: VecDeque<u16>,    // 对端打开、尚未被 accept_stream 取走的流
+    accept_waker: Option<Waker>,
+    unreliable: Unreliable,     // 流 0 上不可靠消息的编号、去重与未读队列（见 `unreliable` 模块）
+    keepalive: Keepalive,
+    local_isn: SeqNum,
+    origin: Origin,
+    early_data: bool,           // 见 `Handshake::early_data`
+    unconfirmed: Option<SeqNum>,    // 由 0-RTT 数据建立、还没收到对端握手之后的段时为对端 ISN：重传的 SYN 说明 SYN-ACK 丢失了
+    peer: SocketAddr,           // 对端的当前地址，迁移时由监听器更新
+    ids: ConnIds,               // 两个方向的连接 ID（见 `rotation` 模块）
+    issued: Option<u32>,        // 已发给对端、还没被确认的新连接 ID
+    rotated_bytes: u64,         // 上一次自动轮换时流 0 已收发的数据体字节数
+    stale_conn_id: u64,         // 携带已退役连接 ID 而被丢弃的段数
+    path_challenges: u64,       // 收到的 PathChallenge 数
+    checksum: ChecksumAlgorithm,    // 发出的段以它编码，入站段必须以它编码
+    params: TransportParameters,    // 握手时协商出的参数
+    #[cfg(feature = "crypto")]
+    sealer: Option<Sealer>,     // 配置了预共享密钥时加密发出的数据段
+    #[cfg(feature = "crypto")]
+    unsealer: Option<Unsealer>,
+    pool: Arc<BufferPool>,      // 打包数据报所用的缓冲
+    scratch: Scratch,           // 拼合分片消息的暂存缓冲
+    outbox: Vec<Segment>,       // 已产生、尚未打包的段
+    datagrams: Scheduler,       // 已按流打包、等待 `poll_transmit` 轮流取走的数据的数据报（见 `schedule` 模块）
+    control: VecDeque<Segment>, // 已打好标签（及加密）、等待发出的控制段，先于 `datagrams`
+    acks: VecDeque<AckFrame>,   // 接收端产生、等待发出的确认，已打好流 ID，先于 `control`
+    control_burst: usize,       // 有数据排队时已连续发出的控制数据报数
+    events: Vec<Event>,
+    reported: bool,             // 连接的结束已报告
+    time_wait: Option<Instant>, // TIME_WAIT 结束的时间
+    closing: bool,              // close 已开始，不再接受新的发送
+    last_received: Instant,     // 最近一次收到对端的段
+    established: Instant,       // 连接建立的时间，统计回应中的 uptime 从这里算起
+    last_stats_reply: Option<Instant>,  // 最近一次回应对端统计查询的时间
+    resyncing: bool,            // 发出了重新同步请求，还没有收到回应
+    config: LinkConfig,         // 新的附加流沿用连接的参数，`mss` 是当前的有效 MSS
+    max_segment: usize,         // 本端通告的 MSS：对端的数据段不能超过它
+    too_large: bool,            // 本机拒绝过过大的数据报（见 `on_datagram_too_large`）：之后 `send` 放不进有效 MSS 的消息也要分片
+    max_payload: usize,         // 对端通告的 MSS 留出段头、选项区与加密标签后，一条消息的最大字节数
+    overhead: usize,            // 段头、选项区与加密标签至多占用的字节数
+    pmtu: Option<PathMtu>,      // 设置了 `max_mss` 时的路径 MTU 探测
+    timers: Timers<Timer>,      // 以下各项的截止时间，驱动层只需等到其中最早的一个
+    #[cfg(feature = "crypto")]
+    reauth: Option<Segment>,    // 见 `Handshake::reauth`
+    error: Option<LinkError>,   // 连接失败的原因（粘滞）
+    observations: Observations,
+    close_observed: bool,       // 连接的结束已记给观察者
+    messages_discarded: u64,    // 超出重组限制或过期而被丢弃的未收齐消息数（所有流）
+    memory: MemoryAccount,      // 各部分的内存占用与额度（见 `budget` 模块）
+    watermarks: WatermarkTracker,   // 流 0 的发送队列与重排缓冲区的水位（见 `watermarks` 模块）
+}
+
+// 登记在连接时间轮中的定时器；各部分自己判断是否到期，时间轮只决定到期时轮询哪些部分
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Timer {
+    Stream(u16),    // 流的发送端（重传、坚持探测、合并）与接收端（延迟确认）中最早的一个
+    Keepalive,
+    PathMtu,
+    TimeWait,
+    Idle,
+    Scratch,        // 暂存缓冲闲置后释放
+}
+
+
+impl ConnectionCore {
+    /// 握手完成后构造连接的协议状态；数据段从双方各自的 ISN + 1 开始编号，发出的数据报以 `pool` 中的缓冲打包。
+    /// 有效 MSS 是 `config.mss` 与双方通告的 MSS 中最小的一个，对端没有通告时按 `DEFAULT_MSS`；路径 MTU 探测同样不超过对端的通告
+    pub fn new(handshake: Handshake, config: &LinkConfig, peer: SocketAddr, pool: Arc<BufferPool>, now: Instant) -> Self {
+        let max_segment = advertised_mss(config);
+        let peer_mss = handshake.peer_mss.unwrap_or(DEFAULT_MSS);
+        let limit = peer_mss.min(max_segment);
+        #[cfg(feature = "crypto")]
+        let tag = if config.psk.is_some() { crypto::TAG_LEN } else { 0 };
+        #[cfg(not(feature = "crypto"))]
+        let tag = 0;
+        let overhead = Segment::FIXED_HEADER_LEN + options::MAX_LEN + tag;
+        let max_payload = peer_mss.saturating_sub(overhead);
+        let params = handshake.params;
+        let origin = Origin {
+            peer_isn: handshake.peer_isn,
+            conn_id: handshake.conn_id,
+            peer_mss: handshake.peer_mss,
+            #[cfg(feature = "crypto")]
+            nonces: handshake.nonces,
+        };
+        let config = &LinkConfig {
+            mss: config.mss.min(limit),
+            max_mss: config.max_mss.map(|max| max.min(peer_mss)),
+            ack_every_n_segments: usize::from(params.ack_every),
+            keepalive_interval: params.keepalive_interval,
+            sack: params.sack,
+            ..config.clone()
+        };
+        let main = StreamState {
+            sender: Sender::new(handshake.local_isn.wrapping_add(1), config),
+            receiver: Receiver::new(handshake.peer_isn.wrapping_add(1), config),
+            write_closed: false,
+            abandoned: false,
+            priority: DEFAULT_PRIORITY,
+        };
+        #[cfg(feature = "crypto")]
+        let (sealer, unsealer) = match (&config.psk, handshake.nonces) {
+            (Some(psk), Some((initiator_nonce, responder_nonce))) => {
+                let (sealer, unsealer) = crypto::session(psk, handshake.initiator, initiator_nonce, responder_nonce, handshake.conn_id);
+                (Some(sealer), Some(unsealer))
+            }
+            _ => (None, None),
+        };
+        let mut observations = Observations::new(config);
+        if handshake.state.state() == ConnState::Established {
+            observations.push(Observation::Established);
+        }
+        Self {
+            state: handshake.state,
+            main,
+            streams: HashMap::new(),
+            initiator: handshake.initiator,
+            next_local_stream: if handshake.initiator { 1 } else { 2 },
+            next_peer_stream: if handshake.initiator { 2 } else { 1 },
+            accept_queue: VecDeque::new(),
+            accept_waker: None,
+            unreliable: Unreliable::new(config.recv_window),
+            keepalive: Keepalive::new(config, now),
+            local_isn: handshake.local_isn,
+            origin,
+            early_data: handshake.early_data,
+            unconfirmed: handshake.early_data.then_some(handshake.peer_isn),
+            peer,
+            ids: ConnIds::new(handshake.conn_id, config.conn_id_grace),
+            issued: None,
+            rotated_bytes: 0,
+            stale_conn_id: 0,
+            path_challenges: 0,
+            checksum: handshake.checksum,
+            params,
+            #[cfg(feature = "crypto")]
+            sealer,
+            #[cfg(feature = "crypto")]
+            unsealer,
+            pool,
+            scratch: Scratch::new(config.scratch_high_water),
+            outbox: Vec::new(),
+            datagrams: Scheduler::new(),
+            control: VecDeque::new(),
+            acks: VecDeque::new(),
+            control_burst: 0,
+            events: Vec::new(),
+            reported: false,
+            time_wait: None,
+            closing: false,
+            last_received: now,
+            established: now,
+            last_stats_reply: None,
+            resyncing: false,
+            config: config.clone(),
+            max_segment,
+            too_large: false,
+            max_payload,
+            overhead,
+            pmtu: PathMtu::new(config, now),
+            timers: Timers::new(config.timer_granularity),
+            #[cfg(feature = "crypto")]
+            reauth: handshake.reauth,
+            error: None,
+            observations,
+            close_observed: false,
+            messages_discarded: 0,
+            memory: MemoryAccount::detached(config.connection_memory_limit),
+            watermarks: WatermarkTracker::new(config.send_watermarks, config.recv_watermarks),
+        }
+    }
+
+    /// 从另一个进程导出的状态接续连接（见 `session` 模块）：已发送未确认的段在 RTO 到期后重传，尚未发出的写入照常发送，
+    /// 发件箱中先放一个确认，让对端尽快重传交接期间没有得到确认的段。状态中的 nonce 与本端是否配置了预共享密钥不一致时
+    /// 返回 `KeyMismatch`
+    pub fn restore(state: SessionState, config: &LinkConfig, pool: Arc<BufferPool>, now: Instant) -> Result<Self, SessionError> {
+        #[cfg(feature = "crypto")]
+        let nonces = match (state.nonces, &config.psk) {
+            (Some((initiator, responder)), Some(_)) => Some((HandshakeNonce::new(initiator), HandshakeNonce::new(responder))),
+            (None, None) => None,
+            _ => return Err(SessionError::KeyMismatch),
+        };
+        #[cfg(not(feature = "crypto"))]
+        if state.nonces.is_some() {
+            return Err(SessionError::KeyMismatch);
+        }
+        let handshake = Handshake {
+            state: StateMachine::established(),
+            local_isn: state.local_isn,
+            peer_isn: state.peer_isn,
+            conn_id: state.handshake_id,
+            initiator: state.initiator,
+            checksum: state.checksum,
+            peer_mss: state.peer_mss,
+            early_data: false,
+            params: state.params,
+            #[cfg(feature = "crypto")]
+            nonces,
+            #[cfg(feature = "crypto")]
+            reauth: None,
+        };
+        let mut core = Self::new(handshake, config, state.peer, pool, now);
+        core.ids = ConnIds::resume(state.local_id, state.remote_id, config.conn_id_grace);
+        core.next_local_stream = state.next_local_stream;
+        core.next_peer_stream = state.next_peer_stream;
+        #[cfg(feature = "crypto")]
+        if let (Some(sealer), Some(origin)) = (&mut core.sealer, state.origin) {
+            sealer.set_origin(MAIN_STREAM, origin);
+        }
+        core.main.sender = Sender::restore(state.sender, &core.config, now);
+        core.main.receiver = Receiver::restore(state.receiver, &core.config, now);
+        let ack = core.main.receiver.ack_frame();
+        core.push_ack(MAIN_STREAM, [ack]);
+        let pending = core.main.sender.flush(now).unwrap_or_default();
+        core.outbox.extend(pending);
+        Ok(core)
+    }
+
+    /// 连接能否导出：处于 Established、没有开始关闭、没有附加流、没有进行中的连接 ID 轮换，也没有收到对端的 FIN
+    pub fn exportable(&self) -> Result<(), SessionError> {
+        let reason = if self.state.state() != ConnState::Established || self.closing || self.error.is_some() {
+            "the connection is not established"
+        } else if self.main.write_closed {
+            "the write side is shut down"
+        } else if !self.streams.is_empty() {
+            "additional streams are open"
+        } else if self.issued.is_some() {
+            "a connection id rotation is in progress"
+        } else if self.main.receiver.fin_received() {
+            "the peer has closed its side"
+        } else {
+            return Ok(());
+        };
+        Err(SessionError::NotExportable(reason))
+    }
+
+    /// 导出连接的可迁移状态（见 `session` 模块），之后这个协议状态不应再处理任何段；不能导出时返回 `NotExportable`
+    pub fn export(&self) -> Result<SessionState, SessionError> {
+        self.exportable()?;
+        #[cfg(feature = "crypto")]
+        let (nonces, origin) = (
+            self.origin.nonces.map(|(initiator, responder)| (*initiator.as_bytes(), *responder.as_bytes())),
+            self.sealer.as_ref().and_then(|sealer| sealer.origin(MAIN_STREAM)),
+        );
+        #[cfg(not(feature = "crypto"))]
+        let (nonces, origin) = (None, None);
+        Ok(SessionState {
+            peer: self.peer,
+            initiator: self.initiator,
+            local_isn: self.local_isn,
+            peer_isn: self.origin.peer_isn,
+            handshake_id: self.origin.conn_id,
+            local_id: self.ids.local(),
+            remote_id: self.ids.remote(),
+            checksum: self.checksum,
+            peer_mss: self.origin.peer_mss,
+            params: self.params,
+            next_local_stream: self.next_local_stream,
+            next_peer_stream: self.next_peer_stream,
+            nonces,
+            origin,
+            sender: self.main.sender.export(),
+            receiver: self.main.receiver.export(),
+        })
+    }
+
+    /// 处理来自对端的一个数据报（可能打包了多个段）；遇到无法解析的部分时丢弃剩余内容。
+    /// 数据体与数据报共享存储
+    pub fn handle_datagram(&mut self, now: Instant, mut datagram: Bytes) -> Vec<Event> {
+        loop {
+            // 流 0 的普通确认直接解析，其余的段（以及不完整、损坏的部分）照常解码
+            if let Some((frame, len)) = AckFrame::parse(&datagram, self.checksum)
+                && frame.stream_id == MAIN_STREAM
+            {
+                datagram.advance(len);
+                self.receive_ack(&frame, now);
+                continue;
+            }
+            match self.decode_from(&mut datagram) {
+                Ok(Some(segment)) => self.receive(&segment, now),
+                Ok(None) => break,
+                Err(e) => {
+                    self.events.push(Event::DecodeFailed(e));
+                    break;
+                }
+            }
+        }
+        self.limit_reassembly(now);
+        self.limit_memory();
+        self.rotate_if_due(now);
+        self.note_outbound(now);
+        self.check_watermarks(now);
+        self.take_events()
+    }
+
+    /// 处理一个已解码、已解密的段
+    pub fn handle_segment(&mut self, now: Instant, segment: Segment) -> Vec<Event> {
+        self.receive(&segment, now);
+        self.limit_reassembly(now);
+        self.limit_memory();
+        self.rotate_if_due(now);
+        self.note_outbound(now);
+        self.check_watermarks(now);
+        self.take_events()
+    }
+
+    /// 处理到期的定时器；`now` 早于 `next_deadline` 时什么也不做
+    pub fn handle_timeout(&mut self, now: Instant) -> Vec<Event> {
+        let out = self.on_timeout(now);
+        self.outbox.extend(out);
+        self.limit_reassembly(now);
+        self.limit_memory();
+        self.rotate_if_due(now);
+        self.note_outbound(now);
+        self.check_watermarks(now);
+        self.take_events()
+    }
+
+    // 分片重组的限制：超过 `reassembly_timeout` 还没收齐的消息被丢弃；各个流攒着的分片合计超出
+    // `reassembly_budget` 字节或 `max_partial_messages` 条时，从最早开始的一条丢起，直到回到限制之内。
+    // 在取出数据、收到数据报与定时器到期之后检查，过期的消息至多晚一个定时器周期被丢弃
+    fn limit_reassembly(&mut self, now: Instant) {
+        let streams = std::iter::once((MAIN_STREAM, &self.main)).chain(self.streams.iter().map(|(id, stream)| (*id, stream)));
+        let mut partials: Vec<(Instant, usize, u16)> =
+            streams.filter_map(|(id, stream)| stream.receiver.partial().map(|(since, bytes)| (since, bytes, id))).collect();
+        if partials.is_empty() {
+            return;
+        }
+        partials.sort_unstable();
+        let mut bytes: usize = partials.iter().map(|(_, len, _)| len).sum();
+        let mut count = partials.len();
+        for (since, len, id) in partials {
+            let expired = now.saturating_duration_since(since) >= self.config.reassembly_timeout;
+            if !expired && bytes <= self.config.reassembly_budget && count <= self.config.max_partial_messages {
+                break;
+            }
+            if let Some(stream) = self.stream_mut(id) {
+                stream.receiver.discard_partial();
+            }
+            tracing::debug!(stream = id, bytes = len, expired, "discarding an incomplete message");
+            bytes -= len;
+            count -= 1;
+            self.messages_discarded += 1;
+        }
+    }
+
+    // 连接的内存限制（见 `budget` 模块）：按当前的缓冲登记各部分的占用，超出连接的上限时从最早开始的一条丢起攒着的分片，
+    // 再把剩余额度平分给各个流：接收窗口至多再容纳分到的字节数（按有效 MSS 换算成段数，至少一个段，总能推进），发送队列至多再增长同样多
+    fn limit_memory(&mut self) {
+        self.charge_memory();
+        while self.memory.over_limit() {
+            let streams = std::iter::once((MAIN_STREAM, &self.main)).chain(self.streams.iter().map(|(id, stream)| (*id, stream)));
+            let Some((_, id)) = streams.filter_map(|(id, stream)| stream.receiver.partial().map(|(since, _)| (since, id))).min() else {
+                break;
+            };
+            if let Some(stream) = self.stream_mut(id) {
+                stream.receiver.discard_partial();
+            }
+            tracing::debug!(stream = id, used = self.memory.used(), "discarding an incomplete message over the memory limit");
+            self.messages_discarded += 1;
+            self.charge_memory();
+        }
+        let share = self.memory.room().map(|room| room / (2 * (1 + self.streams.len())));
+        let segment = self.config.mss;
+        let limit = |stream: &mut StreamState| {
+            let (window, queue) = match share {
+                Some(share) => ((stream.receiver.buffer().len() + share / segment).max(1), stream.sender.queued_bytes() + share),
+                None => (usize::MAX, usize::MAX),
+            };
+            stream.sender.set_queue_limit(queue);
+            stream.receiver.set_window_limit(window)
+        };
+        if let Some(update) = limit(&mut self.main) {
+            self.push_ack(MAIN_STREAM, [update]);
+        }
+        let mut updates = Vec::new();
+        for (id, stream) in &mut self.streams {
+            if let Some(update) = limit(stream) {
+                updates.push((*id, update));
+            }
+        }
+        for (id, update) in updates {
+            self.push_ack(id, [update]);
+        }
+    }
+
+    // 按各个流当前缓冲的数据体登记内存占用
+    fn charge_memory(&mut self) {
+        let (mut reorder, mut reassembly, mut send) = (0, 0, 0);
+        for stream in std::iter::once(&self.main).chain(self.streams.values()) {
+            reorder += stream.receiver.buffer().bytes();
+            reassembly += stream.receiver.held_bytes();
+            send += stream.sender.queued_bytes();
+        }
+        self.memory.set(Component::Reorder, reorder);
+        self.memory.set(Component::Reassembly, reassembly);
+        self.memory.set(Component::Send, send);
+    }
+
+    /// 换上计入监听器账本的内存账户（见 `budget` 模块），随即按当前的缓冲登记占用
+    pub fn set_memory_account(&mut self, account: MemoryAccount) {
+        self.memory = account;
+        self.limit_memory();
+    }
+
+    /// 连接各部分合计的内存占用，按粒度取整
+    pub fn memory_used(&self) -> usize {
+        self.memory.used()
+    }
+
+    // 即使发送队列清空也放不下的写入被拒绝：消息没有入队，连接不受影响
+    fn admits(&self, len: usize) -> Result<(), LinkError> {
+        match self.memory.fits(Component::Send, len) {
+            true => Ok(()),
+            false => Err(LinkError::MemoryLimit { len, available: self.memory.room().unwrap_or(0) + self.memory.get(Component::Send) }),
+        }
+    }
+
+    /// 取出下一个要发送的数据报与它的目的地址；没有待发送的数据时返回 `None`。
+    /// 缓冲来自构造时给出的缓冲池，发送后可以交还给它。控制段先于排队的数据，见模块文档
+    pub fn poll_transmit(&mut self) -> Option<(BytesMut, SocketAddr)> {
+        self.pack();
+        let starved = !self.datagrams.is_empty() && self.control_burst >= CONTROL_BURST;
+        if !starved && let Some(datagram) = self.pack_control() {
+            self.control_burst += usize::from(!self.datagrams.is_empty());
+            return Some((datagram, self.peer));
+        }
+        let mut datagram = self.datagrams.pop(self.config.mss)?;
+        self.control_burst = 0;
+        self.top_off(&mut datagram);
+        Some((datagram, self.peer))
+    }
+
+    /// 驱动层发送 `poll_transmit` 交出的 `datagram` 时本机报告它过大（EMSGSIZE，见 `error::is_too_large`）。
+    /// 它不超过有效 MSS 时把有效 MSS 降到它的 7/8 左右，路径 MTU 探测此后不超过失败的大小（见 `PathMtu::on_too_large`），
+    /// 其中可靠的数据段从最小的序列号起撤回，按新的 MSS 重新分片、重新编号后排队发出；撤回不了的（重传过的段、FIN 等）
+    /// 照常等待重传，以新的 MSS 单独打包。单个段或 MSS 已经降无可降仍放不下时连接以 `DatagramTooLarge` 失败。
+    /// 超过有效 MSS 的数据报（路径 MTU 探测，或 MSS 降低之前打包的）不再降低 MSS，只降低探测的上界、撤回其中的数据段。
+    /// 暂存的写入同样按新的 MSS 切开。返回产生的事件
+    pub fn on_datagram_too_large(&mut self, datagram: &[u8], now: Instant) -> Vec<Event> {
+        let size = datagram.len();
+        let limit = self.config.mss;
+        if self.is_terminated() || size == 0 {
+            return self.take_events();
+        }
+        let mss = match size > limit {
+            // 路径 MTU 探测，或 MSS 降低之前打包的数据报
+            true => {
+                if let Some(pmtu) = &mut self.pmtu {
+                    pmtu.on_too_large(size, limit, now);
+                }
+                limit
+            }
+            false => {
+                let mss = (size - size / 8).min(size - 1);
+                if mss <= self.overhead {
+                    tracing::warn!(size, limit, "datagram too large and the mss cannot be lowered further");
+                    self.abort(LinkError::DatagramTooLarge { size, limit });
+                    return self.take_events();
+                }
+                tracing::debug!(size, from = limit, to = mss, "datagram too large, lowering the mss");
+                if let Some(pmtu) = &mut self.pmtu {
+                    pmtu.on_too_large(size, mss, now);
+                }
+                self.set_mss(mss);
+                mss
+            }
+        };
+        self.too_large = true;
+        let fragment = self.fragment();
+        for stream in std::iter::once(&mut self.main).chain(self.streams.values_mut()) {
+            stream.sender.refragment(fragment);
+        }
+
+        // 按流找出其中可靠数据段的最小序列号与最大的段；加密的段只读段头
+        let mut affected: Vec<(u16, SeqNum, usize)> = Vec::new();
+        let mut rest = Bytes::copy_from_slice(datagram);
+        while let Ok(Some(segment)) = Segment::decode_bytes_with(&mut rest, self.checksum) {
+            if segment.segment_type() != SegmentType::Data || segment.flags().contains(SegmentFlags::UNRELIABLE) {
+                continue;
+            }
+            let (id, seq, len) = (segment.stream_id(), segment.seq(), segment.encoded_len());
+            match affected.iter_mut().find(|(stream, ..)| *stream == id) {
+                Some((_, first, largest)) => {
+                    *first = if seq.is_before(*first) { seq } else { *first };
+                    *largest = (*largest).max(len);
+                }
+                None => affected.push((id, seq, len)),
+            }
+        }
+        for (id, first, largest) in affected {
+            let Some(stream) = self.stream_mut(id) else {
+                continue;
+            };
+            let Some(segments) = stream.sender.rewind(first, fragment, now) else {
+                if largest > mss {
+                    self.abort(LinkError::DatagramTooLarge { size: largest, limit: mss });
+                }
+                continue;
+            };
+            // 撤回的段可能还在发件箱或已打包排队，去掉它们，以重新编号的段代替
+            let withdrawn = |segment: &Segment| segment.stream_id() == id && segment.segment_type() == SegmentType::Data && !segment.seq().is_before(first) && !segment.flags().contains(SegmentFlags::UNRELIABLE);
+            self.outbox.retain(|segment| !withdrawn(segment));
+            let checksum = self.checksum;
+            self.datagrams.retain(id, |datagram| {
+                let mut rest = datagram.clone().freeze();
+                let mut kept = BytesMut::with_capacity(datagram.len());
+                while !rest.is_empty() {
+                    let before = rest.clone();
+                    let Ok(Some(segment)) = Segment::decode_bytes_with(&mut rest, checksum) else {
+                        break;
+                    };
+                    if !withdrawn(&segment) {
+                        kept.extend_from_slice(&before[..before.len() - rest.len()]);
+                    }
+                }
+                *datagram = kept;
+                !datagram.is_empty()
+            });
+            self.push(id, segments);
+        }
+        self.take_events()
+    }
+
+    /// 是否有尚未被 `poll_transmit` 取走的数据
+    pub fn has_transmit(&self) -> bool {
+        !self.outbox.is_empty() || !self.acks.is_empty() || !self.control.is_empty() || !self.datagrams.is_empty()
+    }
+
+    /// 不等待的发送：把消息放进流 0 的发送队列，队列已满时返回 `WouldBlock`
+    pub fn send_data(&mut self, now: Instant, data: Bytes) -> Result<(), LinkError> {
+        self.try_send(MAIN_STREAM, data, now)
+    }
+
+    /// 不等待的接收：取出流 0 的下一个按序到达的消息，对端关闭写方向后返回 `Ready(Ok(None))`
+    pub fn recv_data(&mut self, now: Instant) -> Poll<Result<Option<Bytes>, LinkError>> {
+        self.poll_recv(MAIN_STREAM, &mut Context::from_waker(Waker::noop()), now)
+    }
+
+    /// 窗口有空位时取走 `data` 交给流 `id` 的可靠层；`data` 只在返回 `Ready(Ok)` 时被取走。
+    /// 本机拒绝过过大的数据报之后（见 `on_datagram_too_large`），放不进有效 MSS 的消息按它分片，同 `poll_send_message`
+    pub fn poll_send(&mut self, id: u16, cx: &mut Context<'_>, data: &mut Option<Bytes>, now: Instant) -> Poll<Result<(), LinkError>> {
+        self.poll_send_with(id, cx, data, SendOptions::default(), now)
+    }
+
+    /// 以 `options` 发送，其余同 `poll_send`
+    pub fn poll_send_with(
+        &mut self,
+        id: u16,
+        cx: &mut Context<'_>,
+        data: &mut Option<Bytes>,
+        options: SendOptions,
+        now: Instant,
+    ) -> Poll<Result<(), LinkError>> {
+        let len = data.as_ref().map_or(0, Bytes::len);
+        self.fits(len)?;
+        self.admits(len)?;
+        let fragment = self.send_fragment();
+        let stream = self.writable(id)?;
+        ready!(stream.sender.poll_write_ready(cx, len))?;
+        let data = data.take().expect("send polled after completion");
+        let segments = match fragment.filter(|fragment| len > *fragment) {
+            Some(fragment) => stream.sender.write_message_with(data, fragment, options, now)?,
+            None => stream.sender.write_with(data, options, now)?,
+        };
+        self.push(id, segments);
+        self.limit_memory();
+        self.note_outbound(now);
+        self.check_watermarks(now);
+        Poll::Ready(Ok(()))
+    }
+
+    /// 发送一条任意大小（不超过 `LinkConfig::max_message`）的消息：放不进一个段时按当前的有效 MSS 分片，
+    /// 对端收齐后作为一条消息交付；发送队列按整条消息等待，其余同 `poll_send`
+    pub fn poll_send_message(&mut self, id: u16, cx: &mut Context<'_>, data: &mut Option<Bytes>, now: Instant) -> Poll<Result<(), LinkError>> {
+        self.poll_send_message_with(id, cx, data, SendOptions::default(), now)
+    }
+
+    /// 以 `options` 分片发送，其余同 `poll_send_message`；分片消息总是按序交付
+    pub fn poll_send_message_with(
+        &mut self,
+        id: u16,
+        cx: &mut Context<'_>,
+        data: &mut Option<Bytes>,
+        options: SendOptions,
+        now: Instant,
+    ) -> Poll<Result<(), LinkError>> {
+        let len = data.as_ref().map_or(0, Bytes::len);
+        if len > self.config.max_message {
+            return Poll::Ready(Err(LinkError::MessageTooLarge { len, max: self.config.max_message }));
+        }
+        self.admits(len)?;
+        let fragment = self.fragment();
+        let stream = self.writable(id)?;
+        ready!(stream.sender.poll_write_ready(cx, len))?;
+        let data = data.take().expect("send polled after completion");
+        let segments = stream.sender.write_message_with(data, fragment, options, now)?;
+        self.push(id, segments);
+        self.limit_memory();
+        self.note_outbound(now);
+        self.check_watermarks(now);
+        Poll::Ready(Ok(()))
+    }
+
+    /// 不等待的发送：发送队列已满时返回 `WouldBlock`，其余错误同 `poll_send`
+    pub fn try_send(&mut self, id: u16, data: Bytes, now: Instant) -> Result<(), LinkError> {
+        self.fits(data.len())?;
+        self.admits(data.len())?;
+        let fragment = self.send_fragment();
+        let sender = &mut self.writable(id)?.sender;
+        let segments = match fragment.filter(|fragment| data.len() > *fragment) {
+            Some(fragment) => sender.write_message(data, fragment, now)?,
+            None => sender.write(data, now)?,
+        };
+        self.push(id, segments);
+        self.limit_memory();
+        self.note_outbound(now);
+        self.check_watermarks(now);
+        Ok(())
+    }
+
+    /// 不可靠地发送一条消息：立即放进发件箱，不重传、不占用发送窗口；等待发出的不可靠段已有
+    /// `unreliable::MAX_PENDING` 个时返回 `WouldBlock`，调用方丢弃这条消息即可。其余错误同 `try_send`
+    pub fn send_unreliable(&mut self, data: Bytes) -> Result<(), LinkError> {
+        self.fits(data.len())?;
+        self.writable(MAIN_STREAM)?;
+        let pending = self.outbox.iter().filter(|segment| segment.flags().contains(SegmentFlags::UNRELIABLE)).count();
+        if pending >= unreliable::MAX_PENDING {
+            return Err(LinkError::WouldBlock);
+        }
+        let segment = self.unreliable.segment(data);
+        self.outbox.push(segment);
+        Ok(())
+    }
+
+    /// 取出下一条到达的不可靠消息；对端不会再发送（已关闭写方向或连接已结束）且没有未读的消息时返回 `None`，
+    /// 连接失败时返回错误
+    pub fn poll_recv_unreliable(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Bytes>, LinkError>> {
+        if let Poll::Ready(data) = self.unreliable.poll_recv(cx) {
+            return Poll::Ready(Ok(Some(data)));
+        }
+        if let Some(e) = &self.error {
+            return Poll::Ready(Err(e.clone()));
+        }
+        match self.state.state().can_receive() {
+            true => Poll::Pending,
+            false => Poll::Ready(Ok(None)),
+        }
+    }
+
+    // 按当前的有效 MSS 分片时每片的数据字节数，不超过对端能接收的一个段
+    fn fragment(&self) -> usize {
+        self.config.mss.saturating_sub(self.overhead).clamp(1, self.max_payload.max(1))
+    }
+
+    // 本机拒绝过过大的数据报之后 `send` 的消息超过它时同样分片；此前一条消息总是一个段
+    fn send_fragment(&self) -> Option<usize> {
+        self.too_large.then(|| self.fragment())
+    }
+
+    // `send` 的一条消息就是一个段：放不进对端通告的 MSS 的消息不被接受，否则对端收到的是截断的数据报
+    fn fits(&self, len: usize) -> Result<(), LinkError> {
+        match len > self.max_payload {
+            true => Err(LinkError::MessageTooLarge { len, max: self.max_payload }),
+            false => Ok(()),
+        }
+    }
+
+    /// 取出流 `id` 的下一个按序到达的消息，对端关闭这个流的写方向后返回 `None`
+    pub fn poll_recv(&mut self, id: u16, cx: &mut Context<'_>, now: Instant) -> Poll<Result<Option<Bytes>, LinkError>> {
+        self.poll_recv_tagged(id, cx, now).map_ok(|message| message.map(|(_, data)| data))
+    }
+
+    /// 同 `poll_recv`，连同消息的标签（见 `SendOptions::tag`）一起取出
+    pub fn poll_recv_tagged(&mut self, id: u16, cx: &mut Context<'_>, now: Instant) -> Poll<Result<Option<TaggedMessage>, LinkError>> {
+        // 双向都已结束、移出流表的流；不经 `stream_mut`，与暂存缓冲分别借用
+        let stream = match id {
+            MAIN_STREAM => &mut self.main,
+            _ => match self.streams.get_mut(&id) {
+                Some(stream) => stream,
+                None => return Poll::Ready(Ok(None)),
+            },
+        };
+        let received = stream.receiver.poll_recv_tagged(cx, now, &mut self.scratch);
+        // 攒下的分片同样腾出了缓冲区，消息还没收齐时也要通告打开的窗口
+        if let Some(update) = stream.receiver.on_window_update() {
+            self.push_ack(id, [update]);
+        }
+        self.limit_reassembly(now);
+        self.limit_memory();
+        self.note_outbound(now);
+        self.check_watermarks(now);
+        match received {
+            Poll::Ready(data) => {
+                self.settle(id, now);
+                Poll::Ready(Ok(data))
+            }
+            Poll::Pending => match &self.error {
+                Some(e) => Poll::Ready(Err(e.clone())),
+                None => Poll::Pending,
+            },
+        }
+    }
+
+    /// 交出流 `id` 合并缓冲中的写入，等待全部已发送的数据被确认
+    pub fn poll_flush(&mut self, id: u16, cx: &mut Context<'_>, now: Instant) -> Poll<Result<(), LinkError>> {
+        if let Some(e) = &self.error {
+            return Poll::Ready(Err(e.clone()));
+        }
+        let segments = match self.stream_mut(id) {
+            Some(stream) if stream.sender.pending() > 0 => stream.sender.flush(now)?,
+            Some(_) => Vec::new(),
+            None => return Poll::Ready(Ok(())),
+        };
+        self.push(id, segments);
+        self.poll_drained(id, cx)
+    }
+
+    /// 关闭流 `id` 的写方向：数据全部确认后发送 FIN，等待 FIN 被确认；从第一次轮询起不再接受新的发送
+    pub fn poll_shutdown(&mut self, id: u16, cx: &mut Context<'_>, now: Instant) -> Poll<Result<(), LinkError>> {
+        if let Some(stream) = self.stream_mut(id) {
+            stream.write_closed = true;
+        }
+        ready!(self.poll_flush(id, cx, now))?;
+        self.shutdown(id, now)?;
+        let drained = self.poll_drained(id, cx);
+        self.settle(id, now);
+        drained
+    }
+
+    /// 等待对端的 FIN：之前的数据被读出丢弃，同时打开接收窗口，让对端的数据与 FIN 能够到达
+    pub fn poll_peer_fin(&mut self, cx: &mut Context<'_>, now: Instant) -> Poll<Result<(), LinkError>> {
+        while let Poll::Ready(data) = self.main.receiver.poll_recv(cx, now, &mut self.scratch) {
+            if let Some(update) = self.main.receiver.on_window_update() {
+                self.push_ack(MAIN_STREAM, [update]);
+            }
+            if data.is_none() {
+                return Poll::Ready(Ok(()));
+            }
+        }
+        match &self.error {
+            Some(e) => Poll::Ready(Err(e.clone())),
+            None => Poll::Pending,
+        }
+    }
+
+    /// 优雅关闭开始：此后 `send_data` 与各个流的发送一律返回 `Closed`，关闭期间收到的数据被丢弃
+    pub fn begin_close(&mut self) {
+        self.closing = true;
+    }
+
+    /// 设置流 `id` 发送时与其他流分享链路的权重，已排队的数据同样按新的权重发出；流不存在时什么也不做
+    pub fn set_priority(&mut self, id: u16, priority: u8) {
+        if let Some(stream) = self.stream_mut(id) {
+            stream.priority = priority;
+            self.datagrams.set_priority(id, priority);
+        }
+    }
+
+    /// 调整一项参数（见 `tuning` 模块）：作用于所有流的参数同时记进 `config`，之后打开的流沿用；
+    /// 暂存的写入与攒够的确认放进发件箱。握手决定的参数返回 `NotAdjustable`，连接已失败时返回连接的错误
+    pub fn set_option(&mut self, option: ConnOption, now: Instant) -> Result<(), LinkError> {
+        option.validate()?;
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        let ids: Vec<u16> = std::iter::once(MAIN_STREAM).chain(self.streams.keys().copied()).collect();
+        match option {
+            ConnOption::Nodelay(nodelay) => {
+                self.config.nodelay = nodelay;
+                for id in ids {
+                    let segments = self.stream_mut(id).expect("listed above").sender.set_nodelay(nodelay, now)?;
+                    self.push(id, segments);
+                }
+            }
+            ConnOption::Pacing(pacing) => {
+                self.config.pacing = pacing;
+                let (gain, batch_window) = (self.config.pacing_gain, self.config.batch_window);
+                for id in ids {
+                    let segments = self.stream_mut(id).expect("listed above").sender.set_pacing(pacing, gain, batch_window, now)?;
+                    self.push(id, segments);
+                }
+            }
+            ConnOption::AckEvery(every) => {
+                self.config.ack_every_n_segments = usize::from(every);
+                self.params.ack_every = every;
+                for id in ids {
+                    let ack = self.stream_mut(id).expect("listed above").receiver.set_ack_every(u32::from(every), now);
+                    self.push_ack(id, ack);
+                }
+            }
+            ConnOption::KeepaliveInterval(interval) => {
+                self.config.keepalive_interval = interval;
+                self.params.keepalive_interval = interval;
+                self.keepalive.set_interval(interval, now);
+            }
+            ConnOption::Priority(priority) => self.set_priority(MAIN_STREAM, priority),
+            ConnOption::Mss(_) | ConnOption::Checksum(_) => unreachable!("rejected by validate"),
+        }
+        Ok(())
+    }
+
+    /// 参数 `name` 的当前值（见 `tuning` 模块）
+    pub fn get_option(&self, name: OptionName) -> ConnOption {
+        match name {
+            OptionName::Nodelay => ConnOption::Nodelay(self.main.sender.nodelay()),
+            OptionName::KeepaliveInterval => ConnOption::KeepaliveInterval(self.params.keepalive_interval),
+            OptionName::AckEvery => ConnOption::AckEvery(self.params.ack_every),
+            OptionName::Pacing => ConnOption::Pacing(self.main.sender.pacing()),
+            OptionName::Priority => ConnOption::Priority(self.main.priority),
+            OptionName::Mss => ConnOption::Mss(self.mss()),
+            OptionName::Checksum => ConnOption::Checksum(self.checksum),
+        }
+    }
+
+    /// 附加流的句柄被丢弃：关闭写方向，之后到达的数据直接丢弃
+    pub fn abandon_stream(&mut self, id: u16, now: Instant) {
+        let Some(stream) = self.stream_mut(id) else {
+            return;
+        };
+        stream.write_closed = true;
+        stream.abandoned = true;
+        let segments = stream.sender.flush(now).unwrap_or_default();
+        self.push(id, segments);
+        self.settle(id, now);
+    }
+
+    /// 立即发送一个携带 `nonce` 的 Ping，不经过发送队列；连接已失败或已关闭时返回错误
+    pub fn ping(&mut self, nonce: u64) -> Result<(), LinkError> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        if self.closing || self.state.state() == ConnState::Closed {
+            return Err(LinkError::Closed);
+        }
+        self.outbox.push(Segment::ping(nonce));
+        Ok(())
+    }
+
+    /// 立即发送一个携带 `nonce` 的统计查询，回应以 `Event::StatsReply` 报告；连接已失败或已关闭时返回错误
+    pub fn stats_request(&mut self, nonce: u64) -> Result<(), LinkError> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        if self.closing || self.state.state() == ConnState::Closed {
+            return Err(LinkError::Closed);
+        }
+        self.outbox.push(Segment::stats_request(nonce));
+        Ok(())
+    }
+
+    /// 此后流 0 的重排缓冲区中空洞的出现与结束以 `Event::Gap` 报告（见 `gaps` 模块）
+    pub fn track_gaps(&mut self) {
+        self.main.receiver.track_gaps();
+    }
+
+    /// 订阅流 0 的发送队列与重排缓冲区越过水位的事件，取代之前的订阅（见 `watermarks` 模块）
+    pub fn subscribe_watermarks(&mut self, now: Instant) -> WatermarkEvents {
+        let (send, recv) = (self.send_queue_len(), self.recv_buffer_len());
+        self.watermarks.subscribe(send, recv, now)
+    }
+
+    /// 流 0 的发送队列中的字节数：暂存（含等待窗口）的写入与已发送未确认的数据体
+    pub fn send_queue_len(&self) -> usize {
+        self.main.sender.queued_bytes()
+    }
+
+    /// 流 0 的重排缓冲区中已收到、尚未被读出的段数
+    pub fn recv_buffer_len(&self) -> usize {
+        self.main.receiver.buffer().len()
+    }
+
+    // 产生了待发出的段：推迟路径探测（见 `keepalive` 模块）
+    fn note_outbound(&mut self, now: Instant) {
+        if !self.outbox.is_empty() || !self.acks.is_empty() {
+            self.keepalive.on_sent(now);
+        }
+    }
+
+    // 改变发送队列或重排缓冲区的操作之后核对水位
+    fn check_watermarks(&mut self, now: Instant) {
+        let (send, recv) = (self.send_queue_len(), self.recv_buffer_len());
+        self.watermarks.observe(send, recv, now);
+    }
+
+    /// 立即请求对端从 `from`（None 为对端的最新位置）开始发送流 0 的数据，回应以 `Event::Resynced` 报告；
+    /// 连接已失败或已关闭时返回错误
+    pub fn resync(&mut self, from: Option<SeqNum>) -> Result<(), LinkError> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        if self.closing || self.state.state() == ConnState::Closed {
+            return Err(LinkError::Closed);
+        }
+        let mut request = Segment::from(self.main.receiver.ack_frame());
+        request.set_options(Options::new().with(SegmentOption::Resync(from)).expect("a single option fits"));
+        self.outbox.push(request);
+        self.resyncing = true;
+        Ok(())
+    }
+
+    /// 以 `error` 中止连接并告知对端：尚未发出的段被丢弃，只发送一个 Rst
+    pub fn reset(&mut self, error: LinkError) {
+        let code = error.close_code();
+        self.abort(error);
+        self.datagrams.clear();
+        self.control.clear();
+        self.acks.clear();
+        self.outbox = vec![rst(code)];
+    }
+
+    /// 底层传输已无法收发（如 TCP 流被关闭）：以 `error` 中止连接，尚未发出的段被丢弃，也不再发送 Rst
+    pub fn fail(&mut self, error: LinkError) {
+        self.abort(error);
+        self.datagrams.clear();
+        self.control.clear();
+        self.acks.clear();
+        self.outbox.clear();
+    }
+
+    pub fn state(&self) -> ConnState {
+        self.state.state()
+    }
+
+    /// 见 `Connection::accepted_early_data`
+    pub fn accepted_early_data(&self) -> bool {
+        self.early_data
+    }
+
+    /// 当前的有效 MSS：`LinkConfig::mss`，打开路径 MTU 探测时随探测结果变化
+    pub fn mss(&self) -> usize {
+        self.config.mss
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// 对端迁移到新地址：之后发出的数据报都发往 `peer`
+    pub fn rebind(&mut self, peer: SocketAddr) {
+        self.peer = peer;
+    }
+
+    /// 对端发给本端的段携带的连接 ID，监听器按它分发
+    pub fn conn_id(&self) -> u32 {
+        self.ids.local()
+    }
+
+    /// 本端发出的段携带的连接 ID，对端轮换后随之改变
+    pub fn peer_conn_id(&self) -> u32 {
+        self.ids.remote()
+    }
+
+    /// 对端在握手中选的 ISN；同一地址上有多条连接时，监听器据此认出属于已建立连接的迟到 SYN
+    pub fn peer_isn(&self) -> SeqNum {
+        self.origin.peer_isn
+    }
+
+    /// 轮换本端的连接 ID：以 NewConnId 把新的 ID 发给对端，返回这个 ID；对端确认后它才生效，
+    /// 这时报告 `Event::ConnIdChanged`。上一次轮换还没被确认时返回那一次的 ID。
+    /// 连接失败、开始关闭或流 0 的写方向已关闭时返回对应的错误
+    pub fn rotate_conn_id(&mut self, now: Instant) -> Result<u32, LinkError> {
+        if let Some(id) = self.issued {
+            return Ok(id);
+        }
+        let id = self.ids.fresh();
+        let segments = self.writable(MAIN_STREAM)?.sender.issue_conn_id(id, now)?;
+        self.outbox.extend(segments);
+        self.issued = Some(id);
+        Ok(id)
+    }
+
+    // 按收发的数据量自动轮换：流 0 自上一次轮换以来收发的数据体达到 `conn_id_rotation_bytes` 时
+    fn rotate_if_due(&mut self, now: Instant) {
+        let Some(volume) = self.config.conn_id_rotation_bytes else {
+            return;
+        };
+        let bytes = self.main.sender.stats().bytes_sent + self.main.receiver.stats().bytes_received;
+        if self.issued.is_some() || bytes - self.rotated_bytes < volume || !self.state.state().can_send() {
+            return;
+        }
+        self.rotated_bytes = bytes;
+        if let Err(e) = self.rotate_conn_id(now) {
+            tracing::debug!(error = %e, "connection id rotation skipped");
+        }
+    }
+
+    // 本端发出的 NewConnId 被确认：换用新的 ID
+    fn settle_rotation(&mut self, now: Instant) {
+        if let Some(new) = self.main.sender.take_acked_conn_id() {
+            let old = self.ids.on_acked(new, now);
+            self.issued = None;
+            tracing::debug!(old, new, "connection id rotated");
+            self.events.push(Event::ConnIdChanged { old, new });
+        }
+    }
+
+    /// 握手时与对端协商出的校验算法
+    pub fn checksum(&self) -> ChecksumAlgorithm {
+        self.checksum
+    }
+
+    /// 握手时与对端协商出的参数
+    pub fn negotiated(&self) -> TransportParameters {
+        self.params
+    }
+
+    /// 连接失败的原因
+    pub fn error(&self) -> Option<&LinkError> {
+        self.error.as_ref()
+    }
+
+    /// 连接经 FIN 交换正常结束时对对端 FIN 的最后确认（打上连接 ID），监听器用它为连接立墓碑
+    pub(crate) fn final_ack(&mut self) -> Option<Segment> {
+        if self.state.state() != ConnState::Closed || self.error.is_some() {
+            return None;
+        }
+        let mut ack = Segment::from(self.main.receiver.ack_frame());
+        ack.set_conn_id(self.ids.remote());
+        ack.set_checksum(self.checksum);
+        Some(ack)
+    }
+
+    // 处理一个入站段，产生的段放进发件箱
+    fn receive(&mut self, segment: &Segment, now: Instant) {
+        if trace::enabled() {
+            trace::segment(Direction::Inbound, segment, self.peer);
+        }
+        if !self.admit(segment.conn_id(), now) {
+            return;
+        }
+        let out = self.on_segment(segment, now);
+        self.outbox.extend(out);
+        self.settle_rotation(now);
+    }
+
+    // 处理流 0 的一个普通确认，同 `receive` 与 `on_segment` 中的确认，但不构造 `Segment`
+    fn receive_ack(&mut self, frame: &AckFrame, now: Instant) {
+        if trace::enabled() {
+            trace::segment(Direction::Inbound, &Segment::from(frame), self.peer);
+        }
+        if !self.admit(frame.conn_id, now) {
+            return;
+        }
+        self.last_received = now;
+        self.unconfirmed = None;
+        if self.apply(Input::Segment(SegmentType::Ack)).is_ok() {
+            self.keepalive.on_activity(now);
+            let out = self.on_ack(frame, now);
+            self.outbox.extend(out);
+        }
+        self.settle_rotation(now);
+    }
+
+    // 核对入站段的连接 ID：携带已退役 ID 的被丢弃
+    fn admit(&mut self, conn_id: u32, now: Instant) -> bool {
+        if self.ids.accepts(conn_id, now) {
+            return true;
+        }
+        tracing::debug!(conn_id, "dropping a segment with a retired connection id");
+        self.stale_conn_id += 1;
+        false
+    }
+
+    // 取出一个段：以协商出的算法校验，配置了密钥时解密数据段
+    fn decode_from(&self, datagram: &mut Bytes) -> Result<Option<Segment>, SegmentError> {
+        #[cfg(feature = "crypto")]
+        if let Some(unsealer) = &self.unsealer {
+            return Segment::decode_bytes_sealed(datagram, self.checksum, unsealer);
+        }
+        let segment = Segment::decode_bytes_with(datagram, self.checksum)?;
+        // 没有密钥的连接打不开密文
+        if segment.as_ref().is_some_and(Segment::is_sealed) {
+            return Err(SegmentError::DecryptFailed);
+        }
+        Ok(segment)
+    }
+
+    // 把发件箱中的段打上标签（及加密），控制段排进 `control`，数据段打包成数据报；
+    // 加密失败（再发送就要复用 nonce）时中止连接，只告知对端
+    fn pack(&mut self) {
+        let mut segments = std::mem::take(&mut self.outbox);
+        if segments.is_empty() {
+            return;
+        }
+        for segment in &mut segments {
+            segment.set_conn_id(self.ids.remote());
+            segment.set_checksum(self.checksum);
+        }
+        #[cfg(feature = "crypto")]
+        if let Some(sealer) = &mut self.sealer
+            && let Err(e) = segments.iter_mut().try_for_each(|segment| sealer.seal(segment))
+        {
+            tracing::warn!(error = %e, "closing the connection");
+            let mut rst = rst(e.close_code());
+            self.abort(e);
+            rst.set_conn_id(self.ids.remote());
+            rst.set_checksum(self.checksum);
+            segments = vec![rst];
+            self.datagrams.clear();
+            self.control.clear();
+            self.acks.clear();
+        }
+        let is_data = |segment: &Segment| segment.segment_type() == SegmentType::Data;
+        if trace::enabled() {
+            for segment in segments.iter().filter(|segment| !is_data(segment)).chain(segments.iter().filter(|segment| is_data(segment))) {
+                trace::segment(Direction::Outbound, segment, self.peer);
+            }
+        }
+        // 每个流的段各自打包、保持顺序，流之间由调度器轮流发出；发件箱清空后放回，保留容量
+        let mut streams: Vec<(u16, Vec<Segment>)> = Vec::new();
+        for segment in segments.drain(..) {
+            if !is_data(&segment) {
+                self.control.push_back(segment);
+                continue;
+            }
+            let id = segment.stream_id();
+            match streams.iter_mut().find(|(stream, _)| *stream == id) {
+                Some((_, segments)) => segments.push(segment),
+                None => streams.push((id, vec![segment])),
+            }
+        }
+        self.outbox = segments;
+        for (id, segments) in streams {
+            let Ok(datagrams) = segment::pack_datagrams_with(&segments, self.config.mss, &self.pool) else {
+                continue;
+            };
+            let priority = self.stream(id).map_or(DEFAULT_PRIORITY, |stream| stream.priority);
+            for datagram in datagrams {
+                self.datagrams.push(id, priority, datagram);
+            }
+        }
+    }
+
+    // 把排队的确认与控制段打包成一个数据报；都没有时返回 None
+    fn pack_control(&mut self) -> Option<BytesMut> {
+        if self.acks.is_empty() && self.control.is_empty() {
+            return None;
+        }
+        let mut datagram = self.pool.get();
+        self.top_off(&mut datagram);
+        (!datagram.is_empty()).then_some(datagram)
+    }
+
+    // 按顺序把放得下的确认与控制段追加到数据报末尾，确认在前；空数据报至少放进一个（超过 MSS 的探测段独占一个数据报）。
+    // 确认在这里才打上连接 ID 与校验算法
+    fn top_off(&mut self, datagram: &mut BytesMut) {
+        while let Some(ack) = self.acks.front() {
+            if !datagram.is_empty() && datagram.len() + ack.encoded_len() > self.config.mss {
+                return;
+            }
+            let ack = AckFrame { conn_id: self.ids.remote(), checksum: self.checksum, ..self.acks.pop_front().expect("front ack exists") };
+            if trace::enabled() {
+                trace::segment(Direction::Outbound, &Segment::from(&ack), self.peer);
+            }
+            ack.encode_into(datagram);
+        }
+        while let Some(segment) = self.control.front() {
+            if !datagram.is_empty() && datagram.len() + segment.encoded_len() > self.config.mss {
+                break;
+            }
+            let segment = self.control.pop_front().expect("front segment exists");
+            // 本端产生的段总能编码，万一不能就丢弃它
+            if let Err(e) = segment.encode_into(datagram) {
+                tracing::debug!(error = %e, "dropping an unencodable control segment");
+            }
+        }
+    }
+
+    // 交出积累的事件；连接刚刚结束时附上结束事件
+    fn take_events(&mut self) -> Vec<Event> {
+        self.events.extend(self.main.receiver.take_gap_events().into_iter().map(Event::Gap));
+        if self.is_terminated() && !self.reported {
+            self.reported = true;
+            self.watermarks.close();
+            self.events.push(match &self.error {
+                Some(e) => Event::Failed(e.clone()),
+                None => Event::Closed,
+            });
+        }
+        std::mem::take(&mut self.events)
+    }
+
+    /// 流 0 的统计
+    pub fn stats(&self) -> ConnectionStats {
+        ConnectionStats {
+            messages_discarded: self.messages_discarded,
+            memory_used: self.memory.used(),
+            stale_conn_id: self.stale_conn_id,
+            path_challenges: self.path_challenges,
+            liveness_pings: self.keepalive.liveness_pings(),
+            path_pings: self.keepalive.path_pings(),
+            ..ConnectionStats::collect(&self.main.sender, &self.main.receiver)
+        }
+    }
+
+    fn stream(&self, id: u16) -> Option<&StreamState> {
+        match id {
+            MAIN_STREAM => Some(&self.main),
+            _ => self.streams.get(&id),
+        }
+    }
+
+    fn stream_mut(&mut self, id: u16) -> Option<&mut StreamState> {
+        match id {
+            MAIN_STREAM => Some(&mut self.main),
+            _ => self.streams.get_mut(&id),
+        }
+    }
+
+    // 发送前的检查：连接失败或关闭、流已移出、写方向已关闭时返回对应的错误
+    fn writable(&mut self, id: u16) -> Result<&mut StreamState, LinkError> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        if self.closing {
+            return Err(LinkError::Closed);
+        }
+        // 流 0 的发送受连接状态限制，附加流只受自己的写方向限制
+        let can_send = id != MAIN_STREAM || self.state.state().can_send();
+        let stream = self.stream_mut(id).ok_or(LinkError::Closed)?;
+        if stream.write_closed {
+            return Err(LinkError::WriteClosed);
+        }
+        if !can_send {
+            return Err(LinkError::Closed);
+        }
+        Ok(stream)
+    }
+
+    // 把流 `id` 产生的段放进发件箱
+    fn push(&mut self, id: u16, segments: impl IntoIterator<Item = Segment>) {
+        self.outbox.extend(tagged(id, segments));
+    }
+
+    // 把流 `id` 的接收端产生的确认排进确认队列
+    fn push_ack(&mut self, id: u16, acks: impl IntoIterator<Item = AckFrame>) {
+        self.acks.extend(acks.into_iter().map(|ack| AckFrame { stream_id: id, ..ack }));
+    }
+
+    // 流 0 的 FIN 经过连接状态机；附加流直接由发送端登记。返回是否发出了 FIN
+    fn shutdown(&mut self, id: u16, now: Instant) -> Result<bool, LinkError> {
+        if id == MAIN_STREAM {
+            if !self.state.state().can_send() {
+                return Ok(false);
+            }
+            self.close(now)?;
+            return Ok(true);
+        }
+        let Some(stream) = self.streams.get_mut(&id) else {
+            return Ok(false);
+        };
+        if stream.sender.fin_sent() {
+            return Ok(false);
+        }
+        let fin = stream.sender.fin(now)?;
+        self.push(id, [fin]);
+        Ok(true)
+    }
+
+    fn poll_drained(&mut self, id: u16, cx: &mut Context<'_>) -> Poll<Result<(), LinkError>> {
+        match self.stream_mut(id) {
+            Some(stream) => stream.sender.poll_drained(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    // 附加流的后续处理：句柄已丢弃的流读出并丢弃到达的数据，暂存的写入交出后补发 FIN；
+    // 双向都结束后移出流表
+    fn settle(&mut self, id: u16, now: Instant) {
+        if id == MAIN_STREAM {
+            return;
+        }
+        let Some(stream) = self.streams.get_mut(&id) else {
+            return;
+        };
+        let mut out = Vec::new();
+        let mut update = None;
+        if stream.abandoned {
+            let mut cx = Context::from_waker(Waker::noop());
+            while let Poll::Ready(Some(_)) = stream.receiver.poll_recv(&mut cx, now, &mut self.scratch) {}
+            update = stream.receiver.on_window_update();
+            if !stream.sender.fin_sent()
+                && stream.sender.pending() == 0
+                && let Ok(fin) = stream.sender.fin(now)
+            {
+                out.push(fin);
+            }
+        }
+        let done = stream.is_done();
+        self.push_ack(id, update);
+        self.push(id, out);
+        if done {
+            self.streams.remove(&id);
+        }
+    }
+
+    fn is_local_stream(&self, id: u16) -> bool {
+        (id % 2 == 1) == self.initiator
+    }
+
+    /// 打开一个新的流并返回它的 ID：客户端分配奇数 ID，服务端分配偶数 ID；本端的流 ID 用完时返回 `StreamsExhausted`
+    pub fn open_stream(&mut self) -> Result<u16, LinkError> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        if self.closing || !self.state.state().can_send() {
+            return Err(LinkError::Closed);
+        }
+        let id = u16::try_from(self.next_local_stream).map_err(|_| LinkError::StreamsExhausted)?;
+        self.next_local_stream += 2;
+        self.streams.insert(id, StreamState::new(&self.config));
+        Ok(id)
+    }
+
+    /// 取出对端打开的下一个流
+    pub fn poll_accept(&mut self, cx: &mut Context<'_>) -> Poll<Result<u16, LinkError>> {
+        if let Some(id) = self.accept_queue.pop_front() {
+            return Poll::Ready(Ok(id));
+        }
+        if let Some(e) = &self.error {
+            return Poll::Ready(Err(e.clone()));
+        }
+        if self.is_terminated() {
+            return Poll::Ready(Err(LinkError::Closed));
+        }
+        self.accept_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    // 处理入站段，返回需要发送的段；当前状态不接受的段被忽略
+    fn on_segment(&mut self, segment: &Segment, now: Instant) -> Vec<Segment> {
+        self.last_received = now;
+        if segment.segment_type() != SegmentType::Syn && !segment.options().early_data() {
+            self.unconfirmed = None;
+        }
+        if segment.segment_type() == SegmentType::Data && segment.encoded_len() > self.max_segment {
+            self.abort(LinkError::Protocol(format!("data segment of {} bytes exceeds the advertised mss {}", segment.encoded_len(), self.max_segment)));
+            return vec![rst(Some(CloseCode::MSS_VIOLATION))];
+        }
+        // 不可靠段不经过状态机与重排缓冲，也不确认；只属于流 0，对端不会再发送之后到达的被丢弃
+        if segment.flags().contains(SegmentFlags::UNRELIABLE) {
+            self.keepalive.on_segment(segment, now);
+            if segment.stream_id() == MAIN_STREAM && self.state.state().can_receive() {
+                self.unreliable.on_data(segment);
+            }
+            return Vec::new();
+        }
+        if segment.stream_id() != MAIN_STREAM
+            && matches!(segment.segment_type(), SegmentType::Data | SegmentType::Skip | SegmentType::Ack | SegmentType::Fin)
+        {
+            self.keepalive.on_segment(segment, now);
+            return self.on_stream_segment(segment, now);
+        }
+
+        let mut out = Vec::new();
+        let Ok(transition) = self.apply(Input::from_segment(segment)) else {
+            return out;
+        };
+        if let Some(pong) = self.keepalive.on_segment(segment, now) {
+            out.push(pong);
+        }
+
+        match segment.segment_type() {
+            SegmentType::Data => {
+                let ack = self.main.receiver.on_data(segment, now).ack;
+                self.push_ack(MAIN_STREAM, ack);
+            }
+            SegmentType::Skip => {
+                let ack = self.main.receiver.on_skip(segment).ack;
+                self.push_ack(MAIN_STREAM, ack);
+            }
+            // 连接 ID 属于整条连接，只在流 0 的序列号空间中发出
+            SegmentType::NewConnId if segment.stream_id() == MAIN_STREAM => {
+                let received = self.main.receiver.on_new_conn_id(segment);
+                if matches!(received.outcome, InsertOutcome::Ready | InsertOutcome::Buffered)
+                    && let Some(id) = segment.issued_conn_id()
+                    && self.ids.on_peer_issued(segment.seq(), id)
+                {
+                    tracing::debug!(conn_id = id, "peer issued a new connection id");
+                }
+                self.push_ack(MAIN_STREAM, received.ack);
+            }
+            SegmentType::Ack => {
+                out.extend(self.on_ack(&AckFrame::from(segment), now));
+                if let Some(from) = segment.options().resync() {
+                    out.extend(self.on_resync(from, now));
+                }
+                if let Some(head) = segment.options().resync_head() {
+                    self.on_resynced(head, segment.options().error());
+                }
+            }
+            SegmentType::Fin => {
+                self.main.receiver.on_fin(segment);
+            }
+            SegmentType::Rst => self.abort(segment.options().error().map_or(LinkError::Reset, LinkError::from)),
+            SegmentType::Pong => {
+                let probed = match (&mut self.pmtu, segment.nonce()) {
+                    (Some(pmtu), Some(nonce)) => pmtu.on_pong(nonce, now),
+                    _ => false,
+                };
+                if probed {
+                    out.extend(self.probe_path(now));
+                } else if let Some(nonce) = segment.nonce() {
+                    self.events.push(Event::Pong { nonce, at: now });
+                }
+            }
+            SegmentType::StatsRequest => {
+                let due = self.last_stats_reply.is_none_or(|last| now.saturating_duration_since(last) >= STATS_REPLY_INTERVAL);
+                if let (true, Some(nonce)) = (due, segment.nonce()) {
+                    self.last_stats_reply = Some(now);
+                    let stats = PeerStats::new(&self.stats(), now.saturating_duration_since(self.established));
+                    out.push(Segment::stats_reply(nonce, &stats.encode()));
+                }
+            }
+            SegmentType::StatsReply => {
+                if let Some(nonce) = segment.nonce() {
+                    self.events.push(Event::StatsReply { nonce, stats: PeerStats::decode(&segment.data()[8..]) });
+                }
+            }
+            // 监听器在验证本端的新地址：原样回送令牌，回应从本端当前的地址发出
+            SegmentType::PathChallenge => {
+                if let Some(token) = segment.nonce() {
+                    self.path_challenges += 1;
+                    out.push(Segment::path_response(token));
+                }
+            }
+            // 令牌由监听器在路由时核对
+            SegmentType::Syn | SegmentType::Ping | SegmentType::Retry | SegmentType::PathResponse | SegmentType::NewConnId => {}
+        }
+
+        // 订阅了空洞事件时核对流 0 的重排缓冲区（见 `gaps` 模块）
+        if matches!(segment.segment_type(), SegmentType::Data | SegmentType::Skip | SegmentType::NewConnId | SegmentType::Fin) {
+            self.main.receiver.observe_gaps(now);
+        }
+
+        for output in transition.outputs {
+            match output {
+                // 对端没有收到签名的最后确认，重发 SYN-ACK：未签名的确认不能让它完成握手
+                #[cfg(feature = "crypto")]
+                Output::SendAck if segment.segment_type() == SegmentType::Syn && self.reauth.is_some() => out.extend(self.reauth.clone()),
+                // 对端还在重传 SYN 与 0-RTT 数据：重发 SYN-ACK，确认对端的 ISN 而不是已收到的数据
+                Output::SendAck if segment.segment_type() == SegmentType::Syn && self.unconfirmed.is_some() => {
+                    let peer_isn = self.unconfirmed.expect("checked by the guard");
+                    let window = self.main.receiver.advertised_window();
+                    out.push(syn_ack(self.local_isn, peer_isn, window, self.max_segment, &self.params, &self.config.checksums, ChecksumAlgorithm::default()));
+                }
+                Output::SendAck => {
+                    let ack = self.main.receiver.ack_frame();
+                    self.push_ack(MAIN_STREAM, [ack]);
+                }
+                Output::SendSynAck => {
+                    // 校验算法在打包时由 `pack` 打上
+                    let peer_isn = self.main.receiver.buffer().cumulative_ack();
+                    let window = self.main.receiver.advertised_window();
+                    out.push(syn_ack(self.local_isn, peer_isn, window, self.max_segment, &self.params, &self.config.checksums, ChecksumAlgorithm::default()));
+                }
+                Output::ArmTimeWait => self.time_wait = Some(now + TIME_WAIT),
+                // 主动打开与关闭路径不经过入站段
+                Output::SendSyn | Output::SendFin => {}
+            }
+        }
+        out
+    }
+
+    // 流 0 的确认：交给发送端，返回重传的段与窗口打开后放出的段
+    fn on_ack(&mut self, frame: &AckFrame, now: Instant) -> Vec<Segment> {
+        let outcome = self.main.sender.on_ack_frame(frame, now);
+        let mut out = outcome.retransmit;
+        out.extend(outcome.transmit);
+        // 只看段类型无法得知 FIN 是否被确认，由发送端的重传队列判定
+        if self.main.sender.fin_acked() {
+            let _ = self.apply(Input::Action(Action::FinAcked));
+        }
+        out
+    }
+
+    // 对端请求重新同步：流 0 的发送端放弃起点之前的数据，以携带开始交付位置的确认回应，拒绝时回应仍保留的最早序列号与错误码
+    fn on_resync(&mut self, from: Option<SeqNum>, now: Instant) -> Vec<Segment> {
+        let (options, segments) = match self.main.sender.resync(from, now) {
+            Ok((head, segments)) => {
+                tracing::debug!(head = head.get(), "peer resynchronized");
+                (Options::new().with(SegmentOption::ResyncHead(head)), segments)
+            }
+            Err(oldest) => {
+                tracing::debug!(oldest = oldest.get(), "refused a resync before the oldest retained data");
+                let options = Options::new().with(SegmentOption::ResyncHead(oldest)).and_then(|options| options.with(SegmentOption::Error(CloseCode::RESYNC_REFUSED)));
+                (options, Vec::new())
+            }
+        };
+        let mut reply = Segment::from(self.main.receiver.ack_frame());
+        reply.set_options(options.expect("two options fit"));
+        let mut out = vec![reply];
+        out.extend(segments);
+        out
+    }
+
+    // 对端回应了本端的重新同步请求
+    fn on_resynced(&mut self, head: SeqNum, error: Option<CloseCode>) {
+        if !std::mem::take(&mut self.resyncing) {
+            return;
+        }
+        let result = if error == Some(CloseCode::RESYNC_REFUSED) {
+            Err(LinkError::ResyncRefused { oldest: head })
+        } else {
+            self.main.receiver.resync(head);
+            Ok(head)
+        };
+        self.events.push(Event::Resynced(result));
+    }
+
+    // 附加流的数据、确认与 FIN：不经过连接状态机，由流自己的发送端与接收端处理
+    fn on_stream_segment(&mut self, segment: &Segment, now: Instant) -> Vec<Segment> {
+        let id = segment.stream_id();
+        let mut out = Vec::new();
+        if self.is_terminated() {
+            return out;
+        }
+        if !self.streams.contains_key(&id) {
+            let local = self.is_local_stream(id);
+            let next = if local { self.next_local_stream } else { self.next_peer_stream };
+            if u32::from(id) < next {
+                // 已结束的流：它的数据与 FIN 都已收到，对端重传说明确认丢失，原样确认
+                if segment.segment_type() != SegmentType::Ack {
+                    let ack = self.closed_stream_ack(segment.seq());
+                    self.push_ack(id, [ack]);
+                }
+                return out;
+            }
+            if local {
+                out.push(rst(Some(CloseCode::PROTOCOL_ERROR)));
+                self.abort(LinkError::Protocol(format!("peer referenced stream {} that was never opened", id)));
+                return out;
+            }
+            // 确认只能针对本端发出过的数据，对端尚未打开的流不会有
+            if segment.segment_type() == SegmentType::Ack {
+                return out;
+            }
+            self.streams.insert(id, StreamState::new(&self.config));
+            self.next_peer_stream = u32::from(id) + 2;
+            self.accept_queue.push_back(id);
+            self.events.push(Event::StreamOpened(id));
+            if let Some(waker) = self.accept_waker.take() {
+                waker.wake();
+            }
+        }
+
+        let stream = self.streams.get_mut(&id).expect("stream registered above");
+        let mut emitted = Vec::new();
+        let ack = match segment.segment_type() {
+            SegmentType::Data => stream.receiver.on_data(segment, now).ack,
+            SegmentType::Skip => stream.receiver.on_skip(segment).ack,
+            SegmentType::Ack => {
+                let outcome = stream.sender.on_ack_segment(segment, now);
+                emitted.extend(outcome.retransmit);
+                emitted.extend(outcome.transmit);
+                None
+            }
+            _ => {
+                stream.receiver.on_fin(segment);
+                Some(stream.receiver.ack_frame())
+            }
+        };
+        self.push_ack(id, ack);
+        out.extend(tagged(id, emitted));
+        self.settle(id, now);
+        out
+    }
+
+    fn closed_stream_ack(&self, seq: SeqNum) -> AckFrame {
+        AckFrame::new(seq, u32::try_from(self.config.recv_window).unwrap_or(u32::MAX))
+    }
+
+    // 定时器到期：各个流的重传、合并与延迟确认，以及连接的保活
+    // 只轮询定时器到期的部分
+    fn on_timeout(&mut self, now: Instant) -> Vec<Segment> {
+        self.arm_timers();
+        let mut out = Vec::new();
+        let mut failure = None;
+        for timer in self.timers.expire(now) {
+            match timer {
+                Timer::Stream(id) => {
+                    let Some(stream) = self.stream_mut(id) else {
+                        continue;
+                    };
+                    match stream.sender.on_timeout(now) {
+                        Ok(segments) => out.extend(tagged(id, segments)),
+                        Err(e) => failure = Some(e),
+                    }
+                    let ack = stream.receiver.on_timeout(now);
+                    self.push_ack(id, ack);
+                    // 合并定时器交出最后的写入后，已丢弃句柄的流可以发送 FIN
+                    self.settle(id, now);
+                }
+                Timer::Keepalive => match self.keepalive.poll(now) {
+                    Some(KeepaliveAction::Ping(ping)) => out.push(ping),
+                    // 对端可能只是回应丢失：告知它不必再等
+                    Some(KeepaliveAction::Dead) => {
+                        let error = self.keepalive.error();
+                        out.push(rst(error.close_code()));
+                        self.abort(error);
+                    }
+                    None => {}
+                },
+                Timer::PathMtu => out.extend(self.probe_path(now)),
+                Timer::TimeWait => {
+                    self.time_wait = None;
+                    let _ = self.apply(Input::Action(Action::TimeWaitExpired));
+                }
+                // 对端长时间没有任何段到达：回收连接并告知对端
+                Timer::Idle if !self.is_terminated() => {
+                    out.push(rst(None));
+                    self.abort(LinkError::IdleTimeout);
+                }
+                Timer::Idle => {}
+                Timer::Scratch => {
+                    self.scratch.trim(now);
+                }
+            }
+        }
+        if let Some(e) = failure {
+            self.abort(e);
+        }
+        out
+    }
+
+    // 发出到期的路径 MTU 探测；有效 MSS 变化时交给各个流的发送端，之后打开的附加流也沿用它
+    fn probe_path(&mut self, now: Instant) -> Option<Segment> {
+        if self.is_terminated() {
+            return None;
+        }
+        let rto = self.main.sender.rtt().rto();
+        let pmtu = self.pmtu.as_mut()?;
+        let probe = pmtu.poll(now, rto);
+        let mss = pmtu.mss();
+        if mss != self.config.mss {
+            tracing::debug!(from = self.config.mss, to = mss, "path mtu changed");
+            self.set_mss(mss);
+        }
+        probe
+    }
+
+    // 改变有效 MSS：交给各个流的发送端，之后打开的附加流也沿用它
+    fn set_mss(&mut self, mss: usize) {
+        self.events.push(Event::MssChanged(mss));
+        self.config.mss = mss;
+        for stream in std::iter::once(&mut self.main).chain(self.streams.values_mut()) {
+            stream.sender.set_mss(mss);
+        }
+    }
+
+    // 迁移校验：数据与 FIN 必须落在所属流的接收窗口内，确认必须落在所属流已发送、未确认的范围内；
+    // 其他段不携带可校验的序列号，不能触发迁移
+    pub(crate) fn accepts_migration(&self, segment: &Segment) -> bool {
+        let Some(stream) = self.stream(segment.stream_id()) else {
+            return false;
+        };
+        match segment.segment_type() {
+            // 不可靠段的序列号不在任何接收窗口中，无从校验
+            SegmentType::Data if segment.flags().contains(SegmentFlags::UNRELIABLE) => false,
+            SegmentType::Data | SegmentType::Fin => {
+                let buffer = stream.receiver.buffer();
+                let ahead = buffer.next_deliver().distance(segment.seq());
+                u64::try_from(ahead).is_ok_and(|ahead| ahead < buffer.capacity() as u64)
+            }
+            SegmentType::Ack => {
+                let behind = segment.ack().distance(stream.sender.next_seq());
+                (1..=stream.sender.in_flight() as i64 + 1).contains(&behind)
+            }
+            _ => false,
+        }
+    }
+
+    fn idle_deadline(&self) -> Instant {
+        self.last_received + self.config.idle_timeout
+    }
+
+    /// 最早的定时器截止时间，到达时调用 `handle_timeout`；状态变化后需要重新读取
+    pub fn next_deadline(&mut self) -> Option<Instant> {
+        self.arm_timers();
+        self.timers.next_deadline()
+    }
+
+    // 按各部分当前的截止时间重新登记定时器；没有变化的不动，已移出的流撤销
+    fn arm_timers(&mut self) {
+        let streams = std::iter::once((MAIN_STREAM, &self.main)).chain(self.streams.iter().map(|(&id, stream)| (id, stream)));
+        for (id, stream) in streams {
+            let deadline = [stream.sender.next_deadline(), stream.receiver.next_deadline()].into_iter().flatten().min();
+            self.timers.set(Timer::Stream(id), deadline);
+        }
+        let removed: Vec<Timer> = self
+            .timers
+            .keys()
+            .filter(|timer| matches!(timer, Timer::Stream(id) if *id != MAIN_STREAM && !self.streams.contains_key(id)))
+            .copied()
+            .collect();
+        for timer in removed {
+            self.timers.set(timer, None);
+        }
+        let pmtu = self.pmtu.as_ref().filter(|_| !self.is_terminated()).map(PathMtu::next_deadline);
+        self.timers.set(Timer::Keepalive, self.keepalive.next_deadline());
+        self.timers.set(Timer::PathMtu, pmtu);
+        self.timers.set(Timer::TimeWait, self.time_wait);
+        self.timers.set(Timer::Idle, Some(self.idle_deadline()));
+        self.timers.set(Timer::Scratch, self.scratch.deadline());
+    }
+
+    // 本端关闭写方向：迁移状态并把 FIN 放进发件箱；调用前暂存的写入必须已交出
+    fn close(&mut self, now: Instant) -> Result<(), LinkError> {
+        let transition = self.apply(Input::Action(Action::Close)).map_err(|e| LinkError::Protocol(e.to_string()))?;
+        if transition.outputs.contains(&Output::SendFin) {
+            let fin = self.main.sender.fin(now)?;
+            self.outbox.push(fin);
+        }
+        Ok(())
+    }
+
+    // 迁移连接状态机，状态改变时记给观察者
+    fn apply(&mut self, input: Input) -> Result<Transition, InvalidTransition> {
+        let transition = self.state.apply(input)?;
+        // 等待不可靠消息的一方需要知道不会再有消息
+        if transition.from.can_receive() && !transition.to.can_receive() {
+            self.unreliable.wake();
+        }
+        if transition.from != transition.to {
+            self.observations.push(Observation::StateChange { old: transition.from, new: transition.to });
+            if transition.to == ConnState::Established {
+                self.observations.push(Observation::Established);
+            }
+        }
+        Ok(transition)
+    }
+
+    /// 取出记录下来、等待交给观察者的事件：连接自身的在前，之后是各个流的发送端的；连接刚刚结束时附上结束事件
+    pub fn take_observations(&mut self) -> Vec<Observation> {
+        let mut observations = self.observations.take();
+        for stream in std::iter::once(&mut self.main).chain(self.streams.values_mut()) {
+            observations.extend(stream.sender.take_observations());
+        }
+        if self.is_terminated() && !self.close_observed {
+            self.close_observed = true;
+            observations.push(Observation::Closed(self.error.clone()));
+        }
+        observations
+    }
+
+    // 连接失败：记录原因并唤醒所有流上挂起的发送与接收，以及等待新流的一方
+    fn abort(&mut self, error: LinkError) {
+        if self.error.is_some() {
+            return;
+        }
+        let _ = self.apply(Input::Action(Action::Abort));
+        for stream in std::iter::once(&mut self.main).chain(self.streams.values_mut()) {
+            stream.sender.abort(error.clone());
+            stream.receiver.abort();
+        }
+        if let Some(waker) = self.accept_waker.take() {
+            waker.wake();
+        }
+        self.error = Some(error);
+    }
+
+    /// 连接已关闭或已失败，不再产生任何段
+    pub fn is_terminated(&self) -> bool {
+        matches!(self.state.state(), ConnState::Closed | ConnState::Aborted)
+    }
+}
+
+/// 客户端握手的协议部分，不做 IO：调用方（如 `connection` 模块的握手）按退避重发 `retransmission`，把收到的段交给 `on_segment`。
+/// 同时打开的两端都是客户端，没有监听器分配连接 ID（对端 SYN-ACK 中的连接 ID 为 0），
+/// 双方的流 ID 奇偶由 ISN 决定：ISN 较大的一方作为发起方使用奇数流 ID，校验算法也按发起方的偏好协商。
+/// 握手段带有这次握手的随机实例 ID，被对端原样发回的握手段被忽略，不会与自己完成握手（见 `reflect` 模块）。
+#[derive(Debug)]
+pub struct Opener {
+    state: StateMachine,
+    local_isn: SeqNum,
+    peer_isn: Option<SeqNum>,   // 收到对端的 SYN 或 SYN-ACK 后得知
+    conn_id: u32,
+    window: u32,
+    mss: usize,                 // 本端通告的 MSS
+    peer_mss: Option<usize>,    // 对端在 SYN 或 SYN-ACK 中通告的 MSS
+    offer: Vec<ChecksumAlgorithm>,  // 本端接受的校验算法
+    checksum: Option<ChecksumAlgorithm>,    // 收到对端的算法列表后协商出
+    params: TransportParameters,    // 本端提出的参数
+    negotiated: Option<TransportParameters>,    // 收到对端的 SYN 或 SYN-ACK 后协商出
+    token: Option<Bytes>,       // 监听器在 Retry 中签发的地址验证令牌，此后的 SYN 都带回它
+    early: Option<Segment>,     // 随 SYN 发出的 0-RTT 数据段（见 `with_early_data`）
+    instance: u32,              // 这次握手的实例 ID，识别被反射回来的握手段（见 `reflect` 模块）
+    #[cfg(feature = "crypto")]
+    auth: Option<(HandshakeAuth, HandshakeNonce)>,  // 配置了密钥时：签名握手段的密钥与本端的 nonce
+    #[cfg(feature = "crypto")]
+    peer_nonce: Option<HandshakeNonce>,     // 收到对端第一个通过认证的握手段后得知
+}
+
+impl Opener {
+    pub fn new(local_isn: SeqNum, config: &LinkConfig) -> Result<Self, LinkError> {
+        let mut state = StateMachine::new();
+        state.on_action(Action::Connect).map_err(|e| LinkError::Protocol(e.to_string()))?;
+        let window = u32::try_from(config.recv_window).unwrap_or(u32::MAX);
+        let offer = config.checksums.clone();
+        Ok(Self {
+            state,
+            local_isn,
+            peer_isn: None,
+            conn_id: 0,
+            window,
+            mss: advertised_mss(config),
+            peer_mss: None,
+            offer,
+            checksum: None,
+            params: local_params(config, true),
+            negotiated: None,
+            token: None,
+            early: None,
+            instance: reflect::fresh_instance(),
+            #[cfg(feature = "crypto")]
+            auth: config.psk.as_ref().map(|psk| (HandshakeAuth::new(psk), HandshakeNonce::random())),
+            #[cfg(feature = "crypto")]
+            peer_nonce: None,
+        })
+    }
+
+    /// 随 SYN 发出 0-RTT 数据：它是本端的第一个数据段（ISN + 1），与每次发出的 SYN 打包在同一个数据报中。
+    /// 数据报（连同 Retry 令牌）放不进 `config.mss` 时返回 `MessageTooLarge`。配置了密钥时不发出：
+    /// 握手完成之前没有流量密钥，数据只能在建立之后发送
+    pub fn with_early_data(mut self, data: Bytes, config: &LinkConfig) -> Result<Self, LinkError> {
+        let reserved = self.retransmission().encoded_len() + Segment::RETRY_TOKEN_LEN + Segment::FIXED_HEADER_LEN + options::MAX_LEN;
+        let max = config.mss.saturating_sub(reserved);
+        if data.len() > max {
+            return Err(LinkError::MessageTooLarge { len: data.len(), max });
+        }
+        #[cfg(feature = "crypto")]
+        if self.auth.is_some() {
+            return Ok(self);
+        }
+        let early = Segment::builder(SegmentType::Data)
+            .data_seq(self.local_isn.wrapping_add(1))
+            .options(Options::new().with(SegmentOption::EarlyData).expect("a single option fits"))
+            .checksum(self.offer[0])
+            .payload(data)
+            .build()
+            .expect("early data segment is always valid");
+        self.early = Some(early);
+        Ok(self)
+    }
+
+    /// 与 `segment` 打包在同一个数据报中的 0-RTT 数据段：只跟随 SYN，收到对端的 SYN 或 SYN-ACK 之后不再发出
+    pub fn early_data(&self, segment: &Segment) -> Option<&Segment> {
+        let syn = Input::from_segment(segment) == Input::Segment(SegmentType::Syn);
+        self.early.as_ref().filter(|_| syn && self.peer_isn.is_none())
+    }
+
+    // 为握手段打上实例 ID；配置了密钥时再签名：携带本端的 nonce 并回显对端的（尚未得知时为全零）
+    fn sign(&self, mut segment: Segment) -> Segment {
+        reflect::stamp(&mut segment, self.instance);
+        #[cfg(feature = "crypto")]
+        if let Some((auth, nonce)) = &self.auth {
+            auth.sign(&mut segment, *nonce, self.peer_nonce.unwrap_or_default());
+        }
+        segment
+    }
+
+    // 配置了密钥时校验对端的握手段：标签正确、对端的 nonce 不变，SYN-ACK 与确认还须回显本端的 nonce
+    #[cfg(feature = "crypto")]
+    fn authenticates(&self, segment: &Segment) -> bool {
+        let Some((auth, nonce)) = &self.auth else {
+            return true;
+        };
+        let Ok((peer_nonce, echo)) = auth.verify(segment) else {
+            return false;
+        };
+        // 同时打开时对端的 SYN 还不知道本端的 nonce
+        let fresh = Input::from_segment(segment) == Input::Segment(SegmentType::Syn) && echo == HandshakeNonce::default();
+        self.peer_nonce.is_none_or(|known| known == peer_nonce) && (echo == *nonce || fresh)
+    }
+
+    // 最后的确认在序列号上携带本端 ISN，以 cookie 回应的监听器据此还原握手（见 `cookie` 模块）；
+    // 它同样通告本端的 MSS 与协商出的参数，因为 cookie 不记录 SYN 中的选项
+    fn ack(&self, peer_isn: SeqNum, checksum: ChecksumAlgorithm) -> Segment {
+        self.sign(
+            Segment::builder(SegmentType::Ack)
+                .conn_id(self.conn_id)
+                .data_seq(self.local_isn)
+                .ack(peer_isn)
+                .window(self.window)
+                .options(handshake_options(self.mss, self.negotiated.as_ref().unwrap_or(&self.params)))
+                .checksum(checksum)
+                .build()
+                .expect("ack segment is always valid"),
+        )
+    }
+
+    /// 重传定时器到期时发出的段：SynSent 时是 SYN（以本端最偏好的算法编码），同时打开进入 SynReceived 后是 SYN-ACK
+    pub fn retransmission(&self) -> Segment {
+        let segment = match (self.peer_isn, self.checksum) {
+            (Some(peer_isn), Some(checksum)) => syn_ack(self.local_isn, peer_isn, self.window, self.mss, self.negotiated.as_ref().unwrap_or(&self.params), &self.offer, checksum),
+            _ => {
+                let syn = Segment::builder(SegmentType::Syn)
+                    .data_seq(self.local_isn)
+                    .options(handshake_options(self.mss, &self.params))
+                    .offer(&self.offer)
+                    .checksum(self.offer[0]);
+                match &self.token {
+                    Some(token) => syn.retry_token(token),
+                    None => syn,
+                }
+                .build()
+                .expect("syn segment is always valid")
+            }
+        };
+        self.sign(segment)
+    }
+
+    /// 处理一个握手期间收到的段，返回需要立即发出的回应；与握手无关的段被忽略。
+    /// 收到 Rst 即被拒绝（不携带错误码时为 `Refused`，否则按关闭码，如 `SERVER_BUSY` 时为 `ServerBusy`），SYN-ACK 确认了错误的序列号或换了 ISN 时以协议错误失败，对端不接受本端的任何校验算法时失败。
+    pub fn on_segment(&mut self, segment: &Segment) -> Result<Option<Segment>, LinkError> {
+        // 本端的 SYN 被原样发回（例如对端是原始回显服务器）：不能当作对端的 SYN 与自己同时打开
+        if reflect::is_reflected(segment, self.instance) {
+            tracing::debug!(kind = ?segment.segment_type(), "ignoring a reflected handshake segment");
+            return Ok(None);
+        }
+        // 监听器要求验证地址：立即以带回令牌的 SYN 重试。每次握手只接受一个确认了本端 ISN 的 Retry，
+        // 令牌仍不被接受时 SYN 照常重传直到超时，不会与监听器来回交换
+        if segment.segment_type() == SegmentType::Retry {
+            if self.token.is_some() || self.peer_isn.is_some() || segment.ack() != self.local_isn || segment.data().len() != Segment::RETRY_TOKEN_LEN {
+                return Ok(None);
+            }
+            self.token = Some(segment.data().clone());
+            return Ok(Some(self.retransmission()));
+        }
+        let input = Input::from_segment(segment);
+        match input {
+            Input::Segment(SegmentType::Rst) => {
+                return Err(match segment.options().error() {
+                    None | Some(CloseCode::NORMAL) => LinkError::Refused,
+                    Some(code) => code.into(),
+                });
+            }
+            Input::SynAck if segment.ack() != self.local_isn => {
+                return Err(LinkError::Protocol(format!(
+                    "SYN-ACK acknowledges {} but our ISN is {}",
+                    segment.ack(), self.local_isn
+                )));
+            }
+            Input::SynAck | Input::Segment(SegmentType::Syn) => {
+                if let Some(peer_isn) = self.peer_isn
+                    && peer_isn != segment.seq()
+                {
+                    return Err(LinkError::Protocol(format!("peer ISN changed from {} to {}", peer_isn, segment.seq())));
+                }
+            }
+            // 同时打开：对端已收到本端的 SYN-ACK，以确认完成握手
+            Input::Segment(SegmentType::Ack) if self.peer_isn.is_some() && segment.ack() == self.local_isn => {}
+            _ => return Ok(None),
+        }
+        // 配置了密钥时未通过认证的握手段被当作丢失：伪造的 SYN-ACK 不能让连接失败，握手最终超时
+        #[cfg(feature = "crypto")]
+        if !self.authenticates(segment) {
+            tracing::debug!(kind = ?segment.segment_type(), "ignoring an unauthenticated handshake segment");
+            return Ok(None);
+        }
+        if matches!(input, Input::SynAck | Input::Segment(SegmentType::Syn)) {
+            let peer = segment.checksum_offer();
+            let initiator = segment.conn_id() != 0 || self.local_isn.get() > segment.seq().get();
+            let checksum = match initiator {
+                true => checksum::negotiate(&self.offer, &peer),
+                false => checksum::negotiate(&peer, &self.offer),
+            };
+            let checksum = checksum.ok_or(LinkError::NoCommonChecksum)?;
+            self.checksum = Some(checksum);
+            self.peer_mss = peer_mss(segment)?;
+            self.negotiated = Some(negotiate(&self.params, segment, checksum));
+        }
+        let Ok(transition) = self.state.apply(input) else {
+            return Ok(None);
+        };
+        match input {
+            Input::SynAck => {
+                self.peer_isn = Some(segment.seq());
+                self.conn_id = segment.conn_id();
+            }
+            Input::Segment(SegmentType::Syn) => self.peer_isn = Some(segment.seq()),
+            _ => {}
+        }
+        #[cfg(feature = "crypto")]
+        if let Some((auth, _)) = &self.auth {
+            self.peer_nonce = auth.verify(segment).ok().map(|(peer_nonce, _)| peer_nonce);
+        }
+        let peer_isn = self.peer_isn.expect("peer ISN known after a handshake transition");
+        let checksum = self.checksum.expect("checksum negotiated with the peer ISN");
+        let reply = transition.outputs.iter().find_map(|output| match output {
+            Output::SendAck => Some(self.ack(peer_isn, checksum)),
+            Output::SendSynAck => Some(self.retransmission()),
+            _ => None,
+        });
+        Ok(reply)
+    }
+
+    pub fn is_established(&self) -> bool {
+        self.state.state() == ConnState::Established
+    }
+
+    /// 握手完成后的结果
+    pub fn finish(self) -> Handshake {
+        let peer_isn = self.peer_isn.expect("handshake finished without the peer ISN");
+        // 监听器分配的连接 ID 非零，对端是监听器时本端总是发起方
+        let initiator = self.conn_id != 0 || self.local_isn.get() > peer_isn.get();
+        let checksum = self.checksum.expect("handshake finished without a checksum algorithm");
+        let params = self.negotiated.expect("parameters negotiated with the checksum");
+        #[cfg(feature = "crypto")]
+        let (nonces, reauth) = match (&self.auth, self.peer_nonce) {
+            (Some((_, nonce)), Some(peer_nonce)) => {
+                let nonces = if initiator { (*nonce, peer_nonce) } else { (peer_nonce, *nonce) };
+                (Some(nonces), initiator.then(|| self.ack(peer_isn, checksum)))
+            }
+            _ => (None, None),
+        };
+        Handshake {
+            state: self.state,
+            local_isn: self.local_isn,
+            peer_isn,
+            conn_id: self.conn_id,
+            initiator,
+            checksum,
+            peer_mss: self.peer_mss,
+            early_data: false,
+            params,
+            #[cfg(feature = "crypto")]
+            nonces,
+            #[cfg(feature = "crypto")]
+            reauth,
+        }
+    }
+}
+
+/// 构造 SYN-ACK：携带本端 ISN、通告的 MSS、协商出的参数与接受的校验算法，确认对端的 ISN，以协商出的 `checksum` 编码
+pub(crate) fn syn_ack(local_isn: SeqNum, peer_isn: SeqNum, window: u32, mss: usize, params: &TransportParameters, offer: &[ChecksumAlgorithm], checksum: ChecksumAlgorithm) -> Segment {
+    Segment::builder(SegmentType::Syn)
+        .data_seq(local_isn)
+        .ack(peer_isn)
+        .window(window)
+        .options(handshake_options(mss, params))
+        .offer(offer)
+        .checksum(checksum)
+        .build()
+        .expect("syn-ack segment is always valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 假时钟下的一对协议核心：`now` 只由测试推进，数据报在两者之间直接传递
+    struct Pair {
+        a: ConnectionCore,
+        b: ConnectionCore,
+        now: Instant,
+        events: (Vec<Event>, Vec<Event>),
+    }
+
+    impl Pair {
+        fn new(config: LinkConfig) -> Pair {
+            Self::with_isns(config, SeqNum::new(9000), SeqNum::new(3000))
+        }
+
+        // 以同时打开完成不做 IO 的握手：ISN 较大的 a 作为发起方
+        fn with_isns(config: LinkConfig, isn_a: SeqNum, isn_b: SeqNum) -> Pair {
+            let mut opener_a = Opener::new(isn_a, &config).unwrap();
+            let mut opener_b = Opener::new(isn_b, &config).unwrap();
+            let syn_ack_b = opener_b.on_segment(&opener_a.retransmission()).unwrap().unwrap();
+            let ack_a = opener_a.on_segment(&syn_ack_b).unwrap().unwrap();
+            assert_eq!(opener_b.on_segment(&ack_a).unwrap(), None);
+
+            let now = Instant::now();
+            let pool = Arc::new(BufferPool::new(&config));
+            let (addr_a, addr_b) = ("10.0.0.1:1".parse().unwrap(), "10.0.0.2:2".parse().unwrap());
+            let a = ConnectionCore::new(opener_a.finish(), &config, addr_b, pool.clone(), now);
+            let b = ConnectionCore::new(opener_b.finish(), &config, addr_a, pool, now);
+            Pair { a, b, now, events: (Vec::new(), Vec::new()) }
+        }
+
+        // 双方来回交换数据报直到都没有可发送的；`drop` 返回 true 的数据报被丢弃
+        fn exchange(&mut self, drop: &mut impl FnMut(&[u8]) -> bool) {
+            loop {
+                let mut moved = false;
+                while let Some((datagram, to)) = self.a.poll_transmit() {
+                    assert_eq!(to, "10.0.0.2:2".parse().unwrap());
+                    moved = true;
+                    if !drop(&datagram) {
+                        self.events.1.extend(self.b.handle_datagram(self.now, datagram.freeze()));
+                    }
+                }
+                while let Some((datagram, _)) = self.b.poll_transmit() {
+                    moved = true;
+                    if !drop(&datagram) {
+                        self.events.0.extend(self.a.handle_datagram(self.now, datagram.freeze()));
+                    }
+                }
+                if !moved {
+                    return;
+                }
+            }
+        }
+
+        // 时间推进到双方最早的截止时间，处理到期的定时器
+        fn advance(&mut self) {
+            let deadline = [self.a.next_deadline(), self.b.next_deadline()].into_iter().flatten().min().expect("a timer is armed");
+            self.now = self.now.max(deadline);
+            self.events.0.extend(self.a.handle_timeout(self.now));
+            self.events.1.extend(self.b.handle_timeout(self.now));
+        }
+    }
+
+    fn noop() -> Context<'static> {
+        Context::from_waker(Waker::noop())
+    }
+
+    #[test]
+    fn test_messages_arrive_in_order_over_lossy_exchange() {
+        let mut pair = Pair::new(LinkConfig::default());
+        let mut count = 0;
+        let mut drop = |_: &[u8]| {
+            count += 1;
+            count % 5 == 0
+        };
+        for i in 0..200 {
+            pair.a.send_data(pair.now, Bytes::from(format!("m{}", i))).unwrap();
+        }
+        let mut received = Vec::new();
+        for _ in 0..10_000 {
+            pair.exchange(&mut drop);
+            while let Poll::Ready(message) = pair.b.recv_data(pair.now) {
+                received.push(message.unwrap().unwrap());
+            }
+            if received.len() == 200 {
+                break;
+            }
+            pair.advance();
+        }
+        let expected: Vec<Bytes> = (0..200).map(|i| Bytes::from(format!("m{}", i))).collect();
+        assert_eq!(received, expected);
+        assert!(pair.a.stats().sender.segments_retransmitted > 0);
+    }
+
+    #[test]
+    fn test_transfer_across_sequence_wrap() {
+        // 双方的数据段都从 ISN + 1 开始编号，10 个段跨过 u64::MAX 回绕到 0
+        let mut pair = Pair::with_isns(LinkConfig { nodelay: true, ..LinkConfig::default() }, SeqNum::new(u64::MAX - 2), SeqNum::new(u64::MAX - 5));
+        let mut sent = 0;
+        // 丢弃第三个数据报：重传与乱序缓冲同样跨过回绕
+        let mut drop = |_: &[u8]| {
+            sent += 1;
+            sent == 3
+        };
+        for i in 0..10 {
+            pair.a.send_data(pair.now, Bytes::from(format!("a{}", i))).unwrap();
+            pair.b.send_data(pair.now, Bytes::from(format!("b{}", i))).unwrap();
+        }
+        let (mut at_a, mut at_b) = (Vec::new(), Vec::new());
+        for _ in 0..100 {
+            pair.exchange(&mut drop);
+            while let Poll::Ready(message) = pair.b.recv_data(pair.now) {
+                at_b.push(message.unwrap().unwrap());
+            }
+            while let Poll::Ready(message) = pair.a.recv_data(pair.now) {
+                at_a.push(message.unwrap().unwrap());
+            }
+            if at_a.len() == 10 && at_b.len() == 10 && pair.a.stats().in_flight == 0 && pair.b.stats().in_flight == 0 {
+                break;
+            }
+            pair.advance();
+        }
+        assert_eq!(at_b, (0..10).map(|i| Bytes::from(format!("a{}", i))).collect::<Vec<_>>());
+        assert_eq!(at_a, (0..10).map(|i| Bytes::from(format!("b{}", i))).collect::<Vec<_>>());
+        // 全部确认，没有段被当作回绕前的旧段而滞留
+        assert_eq!((pair.a.stats().in_flight, pair.b.stats().in_flight), (0, 0));
+        assert_eq!((pair.a.stats().send_queue_bytes, pair.b.stats().send_queue_bytes), (0, 0));
+        assert!(pair.a.stats().sender.segments_retransmitted + pair.b.stats().sender.segments_retransmitted > 0);
+    }
+
+    #[test]
+    fn test_fresh_isns_differ() {
+        assert_ne!(fresh_isn(), fresh_isn());
+    }
+
+    #[test]
+    fn test_rto_retransmits_exactly_at_deadline() {
+        let mut pair = Pair::new(LinkConfig { nodelay: true, ..LinkConfig::default() });
+        let rto = pair.a.stats().rto;
+        let sent_at = pair.now;
+        pair.a.send_data(pair.now, Bytes::from_static(b"lost once")).unwrap();
+        pair.exchange(&mut |_| true);
+
+        assert_eq!(pair.a.next_deadline(), Some(sent_at + rto));
+        // 截止时间之前什么也不发生
+        pair.events.0.extend(pair.a.handle_timeout(sent_at + rto - Duration::from_millis(1)));
+        assert!(!pair.a.has_transmit());
+        pair.advance();
+        assert_eq!(pair.now, sent_at + rto);
+        pair.exchange(&mut |_| false);
+        assert_eq!(pair.b.recv_data(pair.now), Poll::Ready(Ok(Some(Bytes::from_static(b"lost once")))));
+        assert_eq!(pair.a.stats().timeouts, 1);
+    }
+
+    #[test]
+    fn test_close_exchanges_fins_and_reports_closed_once() {
+        let mut pair = Pair::new(LinkConfig::default());
+        pair.a.send_data(pair.now, Bytes::from_static(b"bye")).unwrap();
+        let mut shutdown = pair.a.poll_shutdown(MAIN_STREAM, &mut noop(), pair.now);
+        for _ in 0..100 {
+            if shutdown.is_ready() {
+                break;
+            }
+            pair.exchange(&mut |_| false);
+            shutdown = pair.a.poll_shutdown(MAIN_STREAM, &mut noop(), pair.now);
+            if !pair.a.has_transmit() && shutdown.is_pending() {
+                pair.advance();
+            }
+        }
+        assert_eq!(shutdown, Poll::Ready(Ok(())));
+        assert_eq!(pair.b.recv_data(pair.now), Poll::Ready(Ok(Some(Bytes::from_static(b"bye")))));
+        assert_eq!(pair.b.recv_data(pair.now), Poll::Ready(Ok(None)));
+
+        // 对端随后关闭：它在 FIN 被确认后直接关闭，先关闭的一方经过 TIME_WAIT
+        assert_eq!(pair.b.poll_shutdown(MAIN_STREAM, &mut noop(), pair.now), Poll::Pending);
+        pair.exchange(&mut |_| false);
+        assert_eq!(pair.b.state(), ConnState::Closed);
+        assert_eq!(pair.a.state(), ConnState::TimeWait);
+        while !pair.a.is_terminated() {
+            pair.advance();
+        }
+        assert_eq!(pair.a.state(), ConnState::Closed);
+        assert_eq!(pair.events.0.iter().filter(|event| **event == Event::Closed).count(), 1);
+        assert_eq!(pair.events.1.iter().filter(|event| **event == Event::Closed).count(), 1);
+        assert!(pair.a.final_ack().is_some());
+        assert_eq!(pair.a.poll_transmit(), None);
+    }
+
+    #[test]
+    fn test_keepalive_failure_reported_once() {
+        let config = LinkConfig { keepalive_interval: Duration::from_secs(1), keepalive_failures: 2, ..LinkConfig::default() };
+        let mut pair = Pair::new(config);
+        let started = pair.now;
+        // b 的回应全部丢失
+        while !pair.a.is_terminated() {
+            while pair.b.poll_transmit().is_some() {}
+            pair.now = pair.a.next_deadline().unwrap();
+            pair.events.0.extend(pair.a.handle_timeout(pair.now));
+            while let Some((datagram, _)) = pair.a.poll_transmit() {
+                let _ = pair.b.handle_datagram(pair.now, datagram.freeze());
+            }
+        }
+        assert_eq!(pair.now - started, Duration::from_secs(3));
+        assert_eq!(pair.events.0, vec![Event::Failed(LinkError::KeepaliveTimeout { unanswered: 2 })]);
+        assert_eq!(pair.a.send_data(pair.now, Bytes::from_static(b"late")), Err(LinkError::KeepaliveTimeout { unanswered: 2 }));
+        assert!(pair.a.handle_timeout(pair.now + Duration::from_secs(60)).is_empty());
+    }
+
+    #[test]
+    fn test_undecodable_datagram_is_reported() {
+        let mut pair = Pair::new(LinkConfig::default());
+        let events = pair.a.handle_datagram(pair.now, Bytes::from_static(&[0, 0, 0, 3, 1]));
+        assert_eq!(events, vec![Event::DecodeFailed(SegmentError::InvalidTotalLen(3, 5))]);
+        assert_eq!(pair.a.state(), ConnState::Established);
+    }
+
+    #[test]
+    fn test_missing_mss_option_falls_back_to_default() {
+        let config = LinkConfig { mss: 1400, ..LinkConfig::default() };
+        let mut opener = Opener::new(SeqNum::new(10), &config).unwrap();
+        assert_eq!(opener.retransmission().options().mss(), Some(MAX_DATAGRAM as u16));
+        // 不通告 MSS 的对端
+        let syn_ack = Segment::builder(SegmentType::Syn).conn_id(7).data_seq(500).ack(10).window(64).offer(&config.checksums).build().unwrap();
+        let ack = opener.on_segment(&syn_ack).unwrap().unwrap();
+        assert_eq!(ack.options().mss(), Some(MAX_DATAGRAM as u16));
+        let handshake = opener.finish();
+        assert_eq!(handshake.peer_mss, None);
+
+        let mut core = ConnectionCore::new(handshake, &config, "10.0.0.2:2".parse().unwrap(), Arc::new(BufferPool::new(&config)), Instant::now());
+        assert_eq!(core.mss(), DEFAULT_MSS);
+        let max = DEFAULT_MSS - Segment::FIXED_HEADER_LEN - options::MAX_LEN;
+        assert_eq!(core.send_data(Instant::now(), Bytes::from(vec![0; max + 1])), Err(LinkError::MessageTooLarge { len: max + 1, max }));
+        assert_eq!(core.send_data(Instant::now(), Bytes::from(vec![0; max])), Ok(()));
+    }
+
+    #[test]
+    fn test_unusable_mss_option_fails_the_handshake() {
+        let config = LinkConfig::default();
+        let mut opener = Opener::new(SeqNum::new(10), &config).unwrap();
+        let syn_ack = Segment::builder(SegmentType::Syn)
+            .conn_id(7)
+            .data_seq(500)
+            .ack(10)
+            .window(64)
+            .options(Options::new().with(SegmentOption::Mss(Segment::FIXED_HEADER_LEN as u16)).unwrap())
+            .offer(&config.checksums)
+            .build()
+            .unwrap();
+        assert!(matches!(opener.on_segment(&syn_ack), Err(LinkError::Protocol(_))));
+    }
+
+    #[test]
+    fn test_oversized_data_segment_resets_with_mss_violation() {
+        let mut pair = Pair::new(LinkConfig { recv_buffer: 2048, ..LinkConfig::default() });
+        let oversized = Segment::builder(SegmentType::Data)
+            .conn_id(pair.a.conn_id())
+            .data_seq(pair.a.local_isn.wrapping_add(1))
+            .checksum(pair.a.checksum())
+            .payload(vec![0; 2048])
+            .build()
+            .unwrap();
+        let events = pair.b.handle_datagram(pair.now, oversized.encode().unwrap().freeze());
+        assert!(matches!(&events[..], [Event::Failed(LinkError::Protocol(reason))] if reason.contains("exceeds the advertised mss 2048")));
+        assert_eq!(pair.b.recv_data(pair.now), Poll::Ready(Err(pair.b.error().unwrap().clone())));
+
+        // 对端收到携带错误码的 Rst
+        let (rst, _) = pair.b.poll_transmit().unwrap();
+        let rst = Segment::decode(&rst).unwrap();
+        assert_eq!((rst.segment_type(), rst.options().error()), (SegmentType::Rst, Some(CloseCode::MSS_VIOLATION)));
+        let events = pair.a.handle_datagram(pair.now, rst.encode().unwrap().freeze());
+        assert!(matches!(&events[..], [Event::Failed(LinkError::Aborted(CloseCode::MSS_VIOLATION))]));
+    }
+
+    #[test]
+    fn test_rst_carries_the_close_code_to_the_application() {
+        // 不认识的传输层码与应用的码都原样交给应用
+        for code in [CloseCode(0x0123), CloseCode::application(9).unwrap()] {
+            let mut pair = Pair::new(LinkConfig::default());
+            let mut reset = rst(Some(code));
+            reset.set_conn_id(pair.a.conn_id());
+            reset.set_checksum(pair.a.checksum());
+            let events = pair.b.handle_datagram(pair.now, reset.encode().unwrap().freeze());
+            assert!(matches!(&events[..], [Event::Failed(e)] if e.close_code() == Some(code)), "{:?}", events);
+        }
+
+        // 本端中止连接时 Rst 携带错误对应的码，没有对应的码时不携带
+        let mut pair = Pair::new(LinkConfig::default());
+        pair.a.reset(LinkError::Application(CloseCode::APPLICATION));
+        let (reset, _) = pair.a.poll_transmit().unwrap();
+        assert_eq!(Segment::decode(&reset).unwrap().options().error(), Some(CloseCode::APPLICATION));
+        let mut pair = Pair::new(LinkConfig::default());
+        pair.a.reset(LinkError::Denied);
+        let (reset, _) = pair.a.poll_transmit().unwrap();
+        assert_eq!(Segment::decode(&reset).unwrap().options().error(), None);
+    }
+
+    // 对端发来的一片：`seq` 是它在流中的序列号，`more` 时不是消息的最后一片
+    fn fragment(id: u16, seq: SeqNum, len: usize, more: bool) -> Segment {
+        let options = if more { Options::new().with(SegmentOption::More).unwrap() } else { Options::new() };
+        Segment::builder(SegmentType::Data).stream(id).data_seq(seq).options(options).payload(vec![0; len]).build().unwrap()
+    }
+
+    #[test]
+    fn test_orphan_first_fragments_stay_within_the_budget() {
+        let config = LinkConfig { reassembly_budget: 4096, max_partial_messages: 4, ..LinkConfig::default() };
+        let mut pair = Pair::new(config);
+        // 对端在 10 个流上各发出一条消息的第一片，永远不发剩下的
+        for id in (1..20).step_by(2) {
+            pair.now += Duration::from_millis(1);
+            pair.b.handle_segment(pair.now, fragment(id, STREAM_ISN, 1000, true));
+            assert!(pair.b.poll_recv(id, &mut noop(), pair.now).is_pending());
+            let partials: Vec<_> = pair.b.streams.values().filter_map(|stream| stream.receiver.partial()).collect();
+            assert!(partials.len() <= 4 && partials.iter().map(|(_, len)| len).sum::<usize>() <= 4096, "{:?}", partials);
+        }
+        assert_eq!(pair.b.stats().messages_discarded, 6);
+
+        // 流 0 上一条不断追加的消息：先挤掉更早开始的消息，自己超出字节预算后被丢弃，它之后的消息照常交付
+        let mut seq = pair.b.main.receiver.buffer().next_expected();
+        pair.now += Duration::from_millis(1);
+        for discarded in 7..=11 {
+            pair.b.handle_segment(pair.now, fragment(MAIN_STREAM, seq, 1000, true));
+            seq = seq.wrapping_add(1);
+            assert!(pair.b.recv_data(pair.now).is_pending());
+            assert_eq!(pair.b.stats().messages_discarded, discarded);
+        }
+        assert_eq!(pair.b.main.receiver.partial(), None);
+        pair.b.handle_segment(pair.now, fragment(MAIN_STREAM, seq, 10, false));
+        pair.b.handle_segment(pair.now, fragment(MAIN_STREAM, seq.wrapping_add(1), 3, false));
+        assert_eq!(pair.b.recv_data(pair.now), Poll::Ready(Ok(Some(Bytes::from(vec![0; 3])))));
+    }
+
+    #[test]
+    fn test_slow_message_completes_before_the_timeout() {
+        let config = LinkConfig { reassembly_timeout: Duration::from_secs(10), ..LinkConfig::default() };
+        let mut pair = Pair::new(config);
+        let mut seq = pair.b.main.receiver.buffer().next_expected();
+        let mut next = || {
+            let current = seq;
+            seq = seq.wrapping_add(1);
+            current
+        };
+
+        // 三片间隔 4 秒到达，在期限之内收齐
+        pair.b.handle_segment(pair.now, fragment(MAIN_STREAM, next(), 100, true));
+        assert!(pair.b.recv_data(pair.now).is_pending());
+        pair.now += Duration::from_secs(4);
+        pair.b.handle_segment(pair.now, fragment(MAIN_STREAM, next(), 100, true));
+        assert!(pair.b.recv_data(pair.now).is_pending());
+        pair.now += Duration::from_secs(4);
+        pair.b.handle_segment(pair.now, fragment(MAIN_STREAM, next(), 100, false));
+        assert_eq!(pair.b.recv_data(pair.now), Poll::Ready(Ok(Some(Bytes::from(vec![0; 300])))));
+
+        // 下一条过了期限还没收齐：被丢弃，迟到的最后一片不会交付
+        pair.b.handle_segment(pair.now, fragment(MAIN_STREAM, next(), 100, true));
+        assert!(pair.b.recv_data(pair.now).is_pending());
+        pair.now += Duration::from_secs(10);
+        pair.b.handle_timeout(pair.now);
+        assert_eq!(pair.b.stats().messages_discarded, 1);
+        pair.b.handle_segment(pair.now, fragment(MAIN_STREAM, next(), 100, false));
+        assert!(pair.b.recv_data(pair.now).is_pending());
+        pair.b.handle_segment(pair.now, fragment(MAIN_STREAM, next(), 7, false));
+        assert_eq!(pair.b.recv_data(pair.now), Poll::Ready(Ok(Some(Bytes::from(vec![0; 7])))));
+    }
+
+    #[test]
+    fn test_stats_replies_are_rate_limited() {
+        let mut pair = Pair::new(LinkConfig { nodelay: true, ..LinkConfig::default() });
+        let replies = |events: &mut Vec<Event>| -> Vec<(u64, PeerStats)> {
+            events
+                .drain(..)
+                .filter_map(|event| match event {
+                    Event::StatsReply { nonce, stats } => Some((nonce, stats.unwrap())),
+                    _ => None,
+                })
+                .collect()
+        };
+        pair.a.send_data(pair.now, Bytes::from_static(b"hello")).unwrap();
+        // 查询是控制段，会赶在排队的数据之前；先让数据到达
+        pair.exchange(&mut |_| false);
+        pair.now += Duration::from_millis(250);
+        pair.a.stats_request(1).unwrap();
+        pair.exchange(&mut |_| false);
+        let first = replies(&mut pair.events.0);
+        assert_eq!(first.len(), 1);
+        let (nonce, stats) = first[0];
+        assert_eq!((nonce, stats.uptime, stats.bytes_received, stats.bytes_sent), (1, Duration::from_millis(250), 5, 0));
+
+        // 间隔内的查询得不到回应，间隔过后恢复
+        pair.now += STATS_REPLY_INTERVAL / 2;
+        pair.a.stats_request(2).unwrap();
+        pair.exchange(&mut |_| false);
+        assert!(replies(&mut pair.events.0).is_empty());
+        pair.now += STATS_REPLY_INTERVAL / 2;
+        pair.a.stats_request(3).unwrap();
+        pair.exchange(&mut |_| false);
+        assert_eq!(replies(&mut pair.events.0).iter().map(|(nonce, _)| *nonce).collect::<Vec<_>>(), vec![3]);
+    }
+
+    // 数据报中各段的类型
+    fn kinds(core: &ConnectionCore, datagram: BytesMut) -> Vec<SegmentType> {
+        let mut buf = datagram;
+        std::iter::from_fn(|| Segment::decode_from_with(&mut buf, core.checksum).unwrap()).map(|segment| segment.segment_type()).collect()
+    }
+
+    #[test]
+    fn test_control_segments_overtake_queued_data() {
+        let mut pair = Pair::new(LinkConfig { nodelay: true, ..LinkConfig::default() });
+        for _ in 0..4 {
+            pair.a.send_data(pair.now, Bytes::from(vec![7; 1000])).unwrap();
+        }
+        pair.a.ping(1).unwrap();
+        let (datagram, _) = pair.a.poll_transmit().unwrap();
+        assert_eq!(kinds(&pair.a, datagram), [SegmentType::Ping]);
+        // 数据的数据报有余量时捎带控制段
+        pair.a.ping(2).unwrap();
+        pair.a.control_burst = CONTROL_BURST;
+        let (datagram, _) = pair.a.poll_transmit().unwrap();
+        assert_eq!(kinds(&pair.a, datagram), [SegmentType::Data, SegmentType::Ping]);
+        pair.exchange(&mut |_| false);
+        for _ in 0..4 {
+            assert_eq!(pair.b.recv_data(pair.now), Poll::Ready(Ok(Some(Bytes::from(vec![7; 1000])))));
+        }
+    }
+
+    #[test]
+    fn test_control_bursts_do_not_starve_data() {
+        let mut pair = Pair::new(LinkConfig { nodelay: true, ..LinkConfig::default() });
+        for _ in 0..2 {
+            pair.a.send_data(pair.now, Bytes::from(vec![7; 1000])).unwrap();
+        }
+        let mut sent = Vec::new();
+        for nonce in 0..12 {
+            pair.a.ping(nonce).unwrap();
+            let (datagram, _) = pair.a.poll_transmit().unwrap();
+            sent.push(kinds(&pair.a, datagram).contains(&SegmentType::Data));
+        }
+        // 每 `CONTROL_BURST` 个控制数据报之后发出一个数据的数据报；数据发完后只剩控制段
+        let data: Vec<usize> = sent.iter().enumerate().filter(|(_, data)| **data).map(|(i, _)| i).collect();
+        assert_eq!(data, [CONTROL_BURST, 2 * CONTROL_BURST + 1]);
+    }
+
+    #[test]
+    fn test_malformed_stats_reply_is_a_decode_error() {
+        let mut pair = Pair::new(LinkConfig::default());
+        let mut reply = Segment::stats_reply(5, &[1, 0, 0]);
+        reply.set_conn_id(pair.b.conn_id());
+        let events = pair.a.handle_segment(pair.now, reply);
+        assert_eq!(events, vec![Event::StatsReply { nonce: 5, stats: Err(SegmentError::InvalidStats { version: 1, len: 3 }) }]);
+        assert_eq!(pair.a.state(), ConnState::Established);
+    }
+
+    #[test]
+    fn test_resync_skips_the_backlog() {
+        let mut pair = Pair::new(LinkConfig { nodelay: true, ..LinkConfig::default() });
+        // b 收到但没有读取 m0；之后的 m1、m2 发出时链路中断
+        pair.a.send_data(pair.now, Bytes::from_static(b"m0")).unwrap();
+        pair.exchange(&mut |_| false);
+        pair.a.send_data(pair.now, Bytes::from_static(b"m1")).unwrap();
+        pair.a.send_data(pair.now, Bytes::from_static(b"m2")).unwrap();
+        pair.exchange(&mut |_| true);
+
+        pair.b.resync(None).unwrap();
+        pair.exchange(&mut |_| false);
+        let head = pair.a.main.sender.next_seq();
+        assert_eq!(pair.events.1, vec![Event::Resynced(Ok(head))]);
+        assert_eq!(pair.a.main.sender.in_flight(), 0);
+        assert_eq!(pair.b.recv_data(pair.now), Poll::Pending);
+        pair.a.send_data(pair.now, Bytes::from_static(b"m3")).unwrap();
+        pair.exchange(&mut |_| false);
+        assert_eq!(pair.b.recv_data(pair.now), Poll::Ready(Ok(Some(Bytes::from_static(b"m3")))));
+
+        // 已被确认的数据不再保留；没有请求在等待时到达的回应被忽略
+        pair.events.1.clear();
+        pair.b.resync(Some(head.wrapping_sub(1))).unwrap();
+        pair.exchange(&mut |_| false);
+        let oldest = head.wrapping_add(1);
+        assert_eq!(pair.events.1, vec![Event::Resynced(Err(LinkError::ResyncRefused { oldest }))]);
+        let mut reply = Segment::from(pair.a.main.receiver.ack_frame());
+        reply.set_options(Options::new().with(SegmentOption::ResyncHead(oldest)).unwrap());
+        reply.set_conn_id(pair.a.conn_id());
+        assert!(pair.b.handle_segment(pair.now, reply).is_empty());
+    }
+
+    #[test]
+    fn test_rotation_mid_transfer_retires_the_old_id_after_the_grace() {
+        let grace = Duration::from_secs(2);
+        let mut pair = Pair::new(LinkConfig { nodelay: true, conn_id_grace: grace, ..LinkConfig::default() });
+        let old = pair.b.conn_id();
+        pair.a.send_data(pair.now, Bytes::from_static(b"m0")).unwrap();
+        pair.exchange(&mut |_| false);
+        // m1 在途中被耽搁，仍携带旧 ID
+        pair.a.send_data(pair.now, Bytes::from_static(b"m1")).unwrap();
+        let (delayed, _) = pair.a.poll_transmit().unwrap();
+
+        let new = pair.b.rotate_conn_id(pair.now).unwrap();
+        assert_eq!(pair.b.rotate_conn_id(pair.now), Ok(new));
+        pair.exchange(&mut |_| false);
+        assert!(pair.events.1.contains(&Event::ConnIdChanged { old, new }));
+        assert_eq!((pair.b.conn_id(), pair.a.peer_conn_id(), pair.a.conn_id()), (new, new, old));
+
+        // 宽限期内迟到的旧 ID 段照常接受，消息按序交付
+        pair.a.send_data(pair.now, Bytes::from_static(b"m2")).unwrap();
+        pair.exchange(&mut |_| false);
+        pair.events.1.extend(pair.b.handle_datagram(pair.now, delayed.freeze()));
+        for expected in [&b"m0"[..], b"m1", b"m2"] {
+            assert_eq!(pair.b.recv_data(pair.now), Poll::Ready(Ok(Some(Bytes::from_static(expected)))));
+        }
+
+        // 宽限期过后携带旧 ID 的段被丢弃，重传的新 ID 段照常送达
+        pair.now += grace;
+        pair.a.send_data(pair.now, Bytes::from_static(b"m3")).unwrap();
+        let (datagram, _) = pair.a.poll_transmit().unwrap();
+        let mut stale = Segment::decode(&datagram).unwrap();
+        assert_eq!(stale.conn_id(), new);
+        stale.set_conn_id(old);
+        pair.events.1.extend(pair.b.handle_segment(pair.now, stale));
+        assert_eq!(pair.b.recv_data(pair.now), Poll::Pending);
+        assert_eq!(pair.b.stats().stale_conn_id, 1);
+        let mut received = pair.b.recv_data(pair.now);
+        while received.is_pending() {
+            pair.advance();
+            pair.exchange(&mut |_| false);
+            received = pair.b.recv_data(pair.now);
+        }
+        assert_eq!(received, Poll::Ready(Ok(Some(Bytes::from_static(b"m3")))));
+        assert_eq!(pair.b.stats().stale_conn_id, 1);
+    }
+
+    #[test]
+    fn test_conn_id_rotates_after_the_configured_volume() {
+        let mut pair = Pair::new(LinkConfig { nodelay: true, conn_id_rotation_bytes: Some(100), ..LinkConfig::default() });
+        let first = pair.a.conn_id();
+        for _ in 0..3 {
+            pair.a.send_data(pair.now, Bytes::from(vec![7; 40])).unwrap();
+            pair.exchange(&mut |_| false);
+        }
+        // 双方各自按收发的数据量轮换自己的 ID，对端随之改用
+        let rotated: Vec<_> = pair.events.0.iter().chain(&pair.events.1).filter(|event| matches!(event, Event::ConnIdChanged { .. })).collect();
+        assert_eq!(rotated.len(), 2);
+        assert_ne!(pair.a.conn_id(), first);
+        assert_eq!((pair.b.peer_conn_id(), pair.a.peer_conn_id()), (pair.a.conn_id(), pair.b.conn_id()));
+    }
+
+    #[test]
+    fn test_options_adjust_the_running_connection() {
+        let mut pair = Pair::new(LinkConfig { pacing: false, ..LinkConfig::default() });
+        // 有数据在途时小写入被合并暂存，打开 nodelay 时立即交出
+        for message in ["first", "held", "held too"] {
+            pair.a.send_data(pair.now, Bytes::from_static(message.as_bytes())).unwrap();
+        }
+        let (first, _) = pair.a.poll_transmit().unwrap();
+        assert!(pair.a.poll_transmit().is_none());
+        pair.a.set_option(ConnOption::Nodelay(true), pair.now).unwrap();
+        let (held, _) = pair.a.poll_transmit().unwrap();
+        for datagram in [first, held] {
+            pair.events.1.extend(pair.b.handle_datagram(pair.now, datagram.freeze()));
+        }
+        assert_eq!((pair.a.get_option(OptionName::Nodelay), pair.a.stats().nodelay), (ConnOption::Nodelay(true), true));
+
+        // 握手决定的参数不能调整，连接不受影响
+        let mss = pair.a.mss();
+        assert_eq!(pair.a.set_option(ConnOption::Mss(mss / 2), pair.now), Err(LinkError::NotAdjustable(OptionName::Mss)));
+        assert_eq!(pair.a.get_option(OptionName::Mss), ConnOption::Mss(mss));
+        assert!(pair.a.set_option(ConnOption::AckEvery(0), pair.now).is_err());
+
+        // 协商出的参数调整后 `negotiated` 报告新的值，保活按新的间隔计时
+        pair.a.set_option(ConnOption::AckEvery(8), pair.now).unwrap();
+        pair.a.set_option(ConnOption::KeepaliveInterval(Duration::from_millis(500)), pair.now).unwrap();
+        pair.a.set_option(ConnOption::Pacing(true), pair.now).unwrap();
+        pair.a.set_option(ConnOption::Priority(3), pair.now).unwrap();
+        let negotiated = pair.a.negotiated();
+        assert_eq!((negotiated.ack_every, negotiated.keepalive_interval), (8, Duration::from_millis(500)));
+        assert!(pair.a.next_deadline().unwrap() <= pair.now + Duration::from_millis(500));
+        assert_eq!((pair.a.get_option(OptionName::Pacing), pair.a.get_option(OptionName::Priority)), (ConnOption::Pacing(true), ConnOption::Priority(3)));
+        assert_eq!(u32::from(pair.b.negotiated().ack_every), crate::ack::ACK_EVERY);
+
+        pair.exchange(&mut |_| false);
+        for expected in ["first", "held", "held too"] {
+            assert_eq!(pair.b.recv_data(pair.now), Poll::Ready(Ok(Some(Bytes::from_static(expected.as_bytes())))));
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! 连接层错误类型
+//! 编解码错误见 `segment::SegmentError`；这里描述连接生命周期中暴露给调用方的失败，
+//! 以及区分可恢复与不可恢复套接字错误的 `is_fatal`。对端以 Rst 发来的关闭码（见 `close_codes` 模块）按 `From<CloseCode>`
+//! 转换为对应的变体，`close_code` 反过来给出一个错误对应的码，本端因它中止连接时随 Rst 发给对端
+
+use crate::close_codes::CloseCode;
+use crate::config::ConfigError;
+use crate::segment::SegmentError;
+use crate::seq::SeqNum;
+use crate::session::SessionError;
+use crate::tuning::OptionName;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    PeerUnreachable { seq: SeqNum, attempts: u32 }, // 重传次数耗尽，对端不可达
+    KeepaliveTimeout { unanswered: u32 },           // 连续多个保活探测未得到回应，判定对端失联
+    Segment(SegmentError),                          // 收到无法解析的段
+    WouldBlock,                                     // 发送窗口已满，非阻塞调用无法立即完成
+    Io { kind: io::ErrorKind, message: String },    // 底层套接字错误
+    Closed,                                         // 监听器或连接已关闭
+    WriteClosed,                                    // 本端的写方向已关闭（shutdown_write），读方向仍可用
+    ConnectTimeout { attempts: u32, elapsed: Duration },    // 握手未能完成：SYN 重传次数耗尽或超过总超时，`attempts` 是发出的 SYN 数
+    Refused,                                        // 对端以不携带错误码（或携带 `NORMAL`）的 Rst 拒绝握手
+    ServerBusy,                                     // 服务器的连接数已达上限，以 `SERVER_BUSY` 的 Rst 拒绝握手，稍后可以重试
+    ParameterRejected,                              // 监听器的 `ParamPolicy` 拒绝了本端提出的握手参数，以 `PARAMETER_ERROR` 的 Rst 拒绝握手
+    AuthFailed,                                     // 监听器要求认证握手而本端没有配置密钥，以 `AUTH_FAILED` 的 Rst 拒绝握手
+    VersionMismatch,                                // 对端以 `VERSION_MISMATCH` 的 Rst 表示双方的协议版本不兼容
+    Reset,                                          // 已建立的连接被对端以不携带错误码（或携带 `NORMAL`）的 Rst 复位
+    Aborted(CloseCode),                             // 对端以没有专门变体的传输层码复位了连接，含本版本不认识的码
+    Application(CloseCode),                         // 连接以应用区的码结束（`Connection::reset`），码原样保留
+    CloseTimedOut,                                  // close 未能在 linger 时间内完成，连接已被复位
+    IdleTimeout,                                    // 超过 idle_timeout 没有收到任何段，连接被回收
+    StreamsExhausted,                               // 本端可用的流 ID 已经用完
+    Protocol(String),                               // 对端违反协议（如 SYN-ACK 确认了错误的序列号）
+    NoCommonChecksum,                               // 握手时双方接受的校验算法没有交集
+    NonceExhausted { stream_id: u16 },              // 流的加密 nonce 即将回绕，连接不能再安全地发送
+    MessageTooLarge { len: usize, max: usize },     // `send` 的消息放不进对端在握手中通告的 MSS（一条消息就是一个段，不分片），、`send_msg` 的消息超过 `LinkConfig::max_message`，或 0-RTT 数据放不进握手数据报
+    StatsTimedOut,                                  // 统计查询没有在超时内得到回应：查询或回应丢失，或被对端限速
+    ResyncRefused { oldest: SeqNum },               // 重新同步的起点早于对端仍保留的最早数据，以 `RESYNC_REFUSED` 回应；连接不受影响
+    ResyncTimedOut,                                 // 重新同步请求没有在超时内得到回应：请求或回应丢失
+    AddrNotLocal(SocketAddr),                       // 客户端要绑定的本地地址不属于本机
+    InterfaceUnsupported,                           // 当前平台不能把套接字绑定到指定的网络接口
+    ConnectFailed(Vec<(SocketAddr, LinkError)>),    // 对端解析出的每个地址都没能建立连接，按尝试顺序
+    Config(ConfigError),                            // `LinkConfig` 未通过校验
+    ByteStreamMode,                                 // 连接已转换为字节流（`into_byte_stream`），主流不能再按消息收发
+    Denied,                                         // 对端的地址不再被监听器的访问控制列表允许，连接被中止（见 `acl` 模块）
+    Session(SessionError),                          // 连接状态无法导出或接续（见 `session` 模块）
+    HandlerPanicked,                                // 服务器的 `Handler` 处理这个连接时 panic，连接被中止（见 `server` 模块）
+    Timeout,                                        // `send_timeout`/`recv_timeout` 等限时操作到期：消息没有入队，也没有消息被取走；连接不受影响
+    DatagramTooLarge { size: usize, limit: usize },  // 本机拒绝发送 `size` 字节的数据报（EMSGSIZE）；`limit` 是当时的有效 MSS，连接以它为上限也放不下时失败
+    NotAdjustable(OptionName),                      // `Connection::set_option` 要调整的参数由握手决定（见 `tuning` 模块）；连接不受影响
+    MemoryLimit { len: usize, available: usize },   // 写入即使在发送队列清空之后也超出连接的剩余内存额度（见 `budget` 模块）：消息没有入队，连接不受影响
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::PeerUnreachable { seq, attempts } => write!(
+                f, "peer unreachable: segment {} unacknowledged after {} attempts",
+                seq, attempts
+            ),
+            LinkError::KeepaliveTimeout { unanswered } => write!(
+                f, "keepalive timeout: {} consecutive pings went unanswered", unanswered
+            ),
+            LinkError::Segment(e) => write!(f, "segment error: {}", e),
+            LinkError::WouldBlock => write!(f, "operation would block: send window is full"),
+            LinkError::Io { message, .. } => write!(f, "io error: {}", message),
+            LinkError::Closed => write!(f, "connection closed"),
+            LinkError::WriteClosed => write!(f, "write side of the connection is shut down"),
+            LinkError::ConnectTimeout { attempts, elapsed } => write!(
+                f, "connect timed out: handshake incomplete after {} SYNs in {:?}", attempts, elapsed
+            ),
+            LinkError::Refused => write!(f, "connection refused by peer"),
+            LinkError::ServerBusy => write!(f, "connection refused: server busy"),
+            LinkError::ParameterRejected => write!(f, "connection refused: transport parameters rejected by the server"),
+            LinkError::AuthFailed => write!(f, "connection refused: the server requires an authenticated handshake"),
+            LinkError::VersionMismatch => write!(f, "connection refused: incompatible protocol version"),
+            LinkError::Reset => write!(f, "connection reset by peer"),
+            LinkError::Aborted(code) => write!(f, "connection reset by peer: {}", code),
+            LinkError::Application(code) => write!(f, "connection closed by the application with code {}", code),
+            LinkError::CloseTimedOut => write!(f, "close timed out: linger expired before the peer acknowledged"),
+            LinkError::IdleTimeout => write!(f, "connection evicted: nothing received within the idle timeout"),
+            LinkError::StreamsExhausted => write!(f, "no stream ids left on this connection"),
+            LinkError::Protocol(reason) => write!(f, "protocol violation: {}", reason),
+            LinkError::NoCommonChecksum => write!(f, "handshake failed: the peer accepts none of our checksum algorithms"),
+            LinkError::NonceExhausted { stream_id } => write!(
+                f, "encryption nonces exhausted on stream {}: the connection must be re-established", stream_id
+            ),
+            LinkError::MessageTooLarge { len, max } => write!(
+                f, "message of {} bytes is too large (at most {} bytes)", len, max
+            ),
+            LinkError::StatsTimedOut => write!(f, "stats query timed out: no reply from the peer"),
+            LinkError::ResyncRefused { oldest } => write!(f, "resync refused: the peer retains data only from sequence {}", oldest),
+            LinkError::ResyncTimedOut => write!(f, "resync timed out: no reply from the peer"),
+            LinkError::AddrNotLocal(addr) => write!(f, "cannot bind {}: not an address of this host", addr),
+            LinkError::InterfaceUnsupported => write!(f, "binding to a network interface is not supported on this platform"),
+            LinkError::ConnectFailed(attempts) => {
+                write!(f, "could not connect to any address:")?;
+                for (i, (addr, e)) in attempts.iter().enumerate() {
+                    write!(f, "{} {} ({})", if i == 0 { "" } else { ";" }, addr, e)?;
+                }
+                Ok(())
+            }
+            LinkError::Config(e) => write!(f, "{}", e),
+            LinkError::ByteStreamMode => write!(f, "connection is in byte-stream mode: use its AsyncRead/AsyncWrite interface"),
+            LinkError::Denied => write!(f, "connection aborted: peer address denied by the access control list"),
+            LinkError::Session(e) => write!(f, "{}", e),
+            LinkError::HandlerPanicked => write!(f, "connection aborted: the server's handler panicked"),
+            LinkError::Timeout => write!(f, "operation timed out before it could complete; nothing was queued or taken"),
+            LinkError::DatagramTooLarge { size, limit } => write!(
+                f, "datagram of {} bytes is too large for the local interface (effective mss {}): lower LinkConfig::mss", size, limit
+            ),
+            LinkError::NotAdjustable(name) => write!(f, "option {} is fixed by the handshake and cannot be adjusted", name),
+            LinkError::MemoryLimit { len, available } => write!(
+                f, "write of {} bytes exceeds the connection's memory budget ({} bytes available)", len, available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LinkError::Segment(e) => Some(e),
+            LinkError::Config(e) => Some(e),
+            LinkError::Session(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LinkError {
+    fn from(e: io::Error) -> Self {
+        LinkError::Io { kind: e.kind(), message: e.to_string() }
+    }
+}
+
+// 字节流接口（`AsyncRead`/`AsyncWrite`）只能报告 io::Error，按语义映射到最接近的错误类别
+impl From<LinkError> for io::Error {
+    fn from(e: LinkError) -> Self {
+        let kind = match &e {
+            LinkError::Io { kind, .. } => *kind,
+            LinkError::PeerUnreachable { .. }
+            | LinkError::KeepaliveTimeout { .. }
+            | LinkError::ConnectTimeout { .. }
+            | LinkError::CloseTimedOut
+            | LinkError::IdleTimeout
+            | LinkError::StatsTimedOut
+            | LinkError::ResyncTimedOut
+            | LinkError::Timeout => io::ErrorKind::TimedOut,
+            LinkError::Segment(_) | LinkError::Protocol(_) => io::ErrorKind::InvalidData,
+            LinkError::WouldBlock => io::ErrorKind::WouldBlock,
+            LinkError::Closed | LinkError::WriteClosed => io::ErrorKind::BrokenPipe,
+            LinkError::Refused | LinkError::ServerBusy | LinkError::ParameterRejected | LinkError::VersionMismatch => io::ErrorKind::ConnectionRefused,
+            LinkError::Reset | LinkError::Aborted(_) | LinkError::Application(_) => io::ErrorKind::ConnectionReset,
+            LinkError::StreamsExhausted | LinkError::NonceExhausted { .. } | LinkError::MemoryLimit { .. } => io::ErrorKind::QuotaExceeded,
+            LinkError::NoCommonChecksum | LinkError::InterfaceUnsupported | LinkError::ByteStreamMode | LinkError::NotAdjustable(_) => io::ErrorKind::Unsupported,
+            LinkError::AddrNotLocal(_) => io::ErrorKind::AddrNotAvailable,
+            LinkError::Denied | LinkError::AuthFailed => io::ErrorKind::PermissionDenied,
+            LinkError::HandlerPanicked => io::ErrorKind::ConnectionAborted,
+            LinkError::Session(SessionError::InUse) => io::ErrorKind::AddrInUse,
+            LinkError::Session(_) => io::ErrorKind::InvalidInput,
+            // 以最后一次尝试的失败归类
+            LinkError::ConnectFailed(attempts) => match attempts.last() {
+                Some((_, last)) => io::Error::from(last.clone()).kind(),
+                None => io::ErrorKind::NotConnected,
+            },
+            LinkError::Config(_) | LinkError::MessageTooLarge { .. } | LinkError::DatagramTooLarge { .. } | LinkError::ResyncRefused { .. } => io::ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, e)
+    }
+}
+
+impl LinkError {
+    /// 这个错误对应的关闭码：由对端发来的码得到的变体给出那个码；没有对应的码时为 None
+    pub fn close_code(&self) -> Option<CloseCode> {
+        let code = match self {
+            LinkError::Protocol(_) => CloseCode::PROTOCOL_ERROR,
+            LinkError::ServerBusy => CloseCode::SERVER_BUSY,
+            LinkError::ParameterRejected => CloseCode::PARAMETER_ERROR,
+            LinkError::ResyncRefused { .. } => CloseCode::RESYNC_REFUSED,
+            LinkError::VersionMismatch => CloseCode::VERSION_MISMATCH,
+            LinkError::AuthFailed => CloseCode::AUTH_FAILED,
+            LinkError::KeepaliveTimeout { .. } => CloseCode::KEEPALIVE_TIMEOUT,
+            LinkError::Aborted(code) | LinkError::Application(code) => *code,
+            _ => return None,
+        };
+        Some(code)
+    }
+}
+
+/// 对端以 `code` 复位了连接或拒绝了握手
+impl From<CloseCode> for LinkError {
+    fn from(code: CloseCode) -> Self {
+        match code {
+            _ if code.is_application() => LinkError::Application(code),
+            CloseCode::NORMAL => LinkError::Reset,
+            CloseCode::PROTOCOL_ERROR => LinkError::Protocol("peer reset the connection for a protocol violation".to_string()),
+            CloseCode::SERVER_BUSY => LinkError::ServerBusy,
+            CloseCode::PARAMETER_ERROR => LinkError::ParameterRejected,
+            CloseCode::VERSION_MISMATCH => LinkError::VersionMismatch,
+            CloseCode::AUTH_FAILED => LinkError::AuthFailed,
+            _ => LinkError::Aborted(code),
+        }
+    }
+}
+
+impl From<SegmentError> for LinkError {
+    fn from(e: SegmentError) -> Self {
+        LinkError::Segment(e)
+    }
+}
+
+impl From<ConfigError> for LinkError {
+    fn from(e: ConfigError) -> Self {
+        LinkError::Config(e)
+    }
+}
+
+impl From<SessionError> for LinkError {
+    fn from(e: SessionError) -> Self {
+        LinkError::Session(e)
+    }
+}
+
+// 描述符失效类的错误码：套接字已被关闭或不再是套接字，之后的每次调用都会失败
+#[cfg(unix)]
+const EBADF: i32 = 9;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const ENOTSOCK: i32 = 88;
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+const ENOTSOCK: i32 = 38;
+#[cfg(windows)]
+const WSAEBADF: i32 = 10009;
+#[cfg(windows)]
+const WSAENOTSOCK: i32 = 10038;
+// 数据报超过本机接口（或套接字）允许的大小
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const EMSGSIZE: i32 = 90;
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+const EMSGSIZE: i32 = 40;
+#[cfg(windows)]
+const WSAEMSGSIZE: i32 = 10040;
+// Windows 把上一个数据报引起的 ICMP 端口不可达报告为下一次接收的 WSAECONNRESET
+#[cfg(windows)]
+const WSAECONNRESET: i32 = 10054;
+
+/// 套接字错误是否无法恢复：描述符失效或绑定的地址已不可用时返回 true，之后的收发都不会成功；
+/// 只影响单个数据报的错误（对端不可达、数据报过大、缓冲区暂时不足、被信号打断等）返回 false
+pub fn is_fatal(e: &io::Error) -> bool {
+    match e.raw_os_error() {
+        #[cfg(unix)]
+        Some(EBADF | ENOTSOCK) => return true,
+        #[cfg(windows)]
+        Some(WSAEBADF | WSAENOTSOCK) => return true,
+        #[cfg(windows)]
+        Some(WSAECONNRESET) => return false,
+        _ => {}
+    }
+    matches!(e.kind(), io::ErrorKind::AddrNotAvailable | io::ErrorKind::NotConnected | io::ErrorKind::OutOfMemory)
+}
+
+/// 发送错误是否说明数据报过大（EMSGSIZE，或传输返回的 `DatagramTooLarge`）：这个数据报不会被发出，
+/// 同样大小的也不会，调用方应当缩小数据报重试（见 `ConnectionCore::on_datagram_too_large`）
+pub fn is_too_large(e: &io::Error) -> bool {
+    match e.raw_os_error() {
+        #[cfg(unix)]
+        Some(EMSGSIZE) => true,
+        #[cfg(windows)]
+        Some(WSAEMSGSIZE) => true,
+        Some(_) => false,
+        None => matches!(e.get_ref().and_then(|inner| inner.downcast_ref::<LinkError>()), Some(LinkError::DatagramTooLarge { .. })),
+    }
+}
+
+/// 把发送数据报的错误转换为 `LinkError`：过大的数据报是 `DatagramTooLarge`，`limit` 是调用方当时的有效 MSS
+pub fn send_error(e: io::Error, size: usize, limit: usize) -> LinkError {
+    match is_too_large(&e) {
+        true => LinkError::DatagramTooLarge { size, limit },
+        false => e.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_fatal() {
+        assert!(!is_fatal(&io::Error::from(io::ErrorKind::ConnectionReset)));
+        assert!(!is_fatal(&io::Error::from(io::ErrorKind::ConnectionRefused)));
+        assert!(!is_fatal(&io::Error::from(io::ErrorKind::Interrupted)));
+        assert!(is_fatal(&io::Error::from(io::ErrorKind::AddrNotAvailable)));
+        #[cfg(unix)]
+        {
+            assert!(is_fatal(&io::Error::from_raw_os_error(EBADF)));
+            assert!(is_fatal(&io::Error::from_raw_os_error(ENOTSOCK)));
+        }
+        #[cfg(target_os = "linux")]
+        {
+            // EMSGSIZE 与 ENOBUFS 只影响这一个数据报
+            assert!(!is_fatal(&io::Error::from_raw_os_error(90)));
+            assert!(!is_fatal(&io::Error::from_raw_os_error(105)));
+        }
+        #[cfg(windows)]
+        assert!(!is_fatal(&io::Error::from_raw_os_error(WSAECONNRESET)));
+    }
+
+    #[test]
+    fn test_close_codes_map_to_variants() {
+        // 除 NORMAL 外，每个已分配的码转换成错误后仍能读回
+        for code in CloseCode::NAMED.into_iter().filter(|&code| code != CloseCode::NORMAL) {
+            assert_eq!(LinkError::from(code).close_code(), Some(code), "{}", code);
+        }
+        assert_eq!(LinkError::from(CloseCode::NORMAL), LinkError::Reset);
+        assert_eq!(LinkError::from(CloseCode::SERVER_BUSY), LinkError::ServerBusy);
+        assert_eq!(LinkError::from(CloseCode::MSS_VIOLATION), LinkError::Aborted(CloseCode::MSS_VIOLATION));
+
+        // 不认识的传输层码落入 Aborted，原始的码不丢失
+        let unknown = LinkError::from(CloseCode(0x0123));
+        assert_eq!(unknown, LinkError::Aborted(CloseCode(0x0123)));
+        assert_eq!(unknown.close_code(), Some(CloseCode(0x0123)));
+        assert_eq!(io::Error::from(unknown).kind(), io::ErrorKind::ConnectionReset);
+
+        // 应用的码原样交给应用
+        let app = CloseCode::application(42).unwrap();
+        assert_eq!(LinkError::from(app), LinkError::Application(app));
+        assert_eq!(LinkError::from(app).close_code(), Some(app));
+        assert_eq!(LinkError::from(app).to_string(), "connection closed by the application with code APPLICATION(0x402a)");
+        assert_eq!((LinkError::Reset.close_code(), LinkError::IdleTimeout.close_code()), (None, None));
+    }
+
+    #[test]
+    fn test_too_large() {
+        #[cfg(unix)]
+        assert!(is_too_large(&io::Error::from_raw_os_error(EMSGSIZE)));
+        #[cfg(unix)]
+        assert!(!is_too_large(&io::Error::from_raw_os_error(EBADF)));
+        assert!(!is_too_large(&io::Error::from(io::ErrorKind::InvalidInput)));
+        // 传输以 `DatagramTooLarge` 报告时同样识别，往返后不变
+        let reported = io::Error::from(LinkError::DatagramTooLarge { size: 1500, limit: 1400 });
+        assert_eq!(reported.kind(), io::ErrorKind::InvalidInput);
+        assert!(is_too_large(&reported));
+        assert_eq!(send_error(reported, 1472, 1472), LinkError::DatagramTooLarge { size: 1472, limit: 1472 });
+        assert!(matches!(send_error(io::Error::from(io::ErrorKind::ConnectionRefused), 10, 1200), LinkError::Io { kind: io::ErrorKind::ConnectionRefused, .. }));
+        assert_eq!(
+            LinkError::DatagramTooLarge { size: 1472, limit: 1472 }.to_string(),
+            "datagram of 1472 bytes is too large for the local interface (effective mss 1472): lower LinkConfig::mss"
+        );
+    }
+}
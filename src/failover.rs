@@ -0,0 +1,257 @@
+//! 在多个后端地址之间故障切换的客户端
+//! `FailoverClient` 保持到当前地址的一条连接。连接失败（重传次数耗尽、保活超时、被复位等，由 `send`、`recv`
+//! 或 `close` 报告）时，从列表中的下一个地址开始重新连接，把旧连接上已交出、尚未被对端累计确认的消息
+//! （`Connection::unacknowledged`）依次在新连接上重发，再继续原来的操作，并调用 `on_failover` 注册的回调。
+//! 一轮地址都连接失败后按带抖动的指数退避等待再试，等待不超过 `FailoverOptions::max_backoff`；
+//! 连续 `max_rounds` 轮失败后放弃，返回最后一轮的 `ConnectFailed`。
+//!
+//! 投递是至少一次的：旧后端可能已经收到某些消息而确认丢失，切换后它们还会到达新后端。不能容忍重复的应用
+//! 应在消息中携带自己的序号并在服务端去重，`Failover::replayed` 是重发的条数。旧后端已发出、客户端尚未读到的
+//! 数据不会重发，需要时由应用在回调之后重新同步。对端正常关闭（`recv` 返回 None）不是故障，不会切换。
+
+use crate::config::LinkConfig;
+use crate::connection::Connection;
+use crate::error::LinkError;
+use crate::transport::BoxFuture;
+use bytes::Bytes;
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// 建立到一个后端的连接；默认的 `UdpConnector` 经 UDP 连接，测试可以换成内存传输
+pub trait Connector: Send + Sync + 'static {
+    fn connect(&self, remote: SocketAddr, config: LinkConfig) -> BoxFuture<'_, Result<Connection, LinkError>>;
+}
+
+/// 以 `Connection::connect_with` 连接
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UdpConnector;
+
+#[cfg(feature = "tokio")]
+impl Connector for UdpConnector {
+    fn connect(&self, remote: SocketAddr, config: LinkConfig) -> BoxFuture<'_, Result<Connection, LinkError>> {
+        Box::pin(Connection::connect_with(remote, config))
+    }
+}
+
+/// 重新连接的退避参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FailoverOptions {
+    pub initial_backoff: Duration,  // 一轮地址都连接失败后的第一次等待，之后每轮翻倍
+    pub max_backoff: Duration,      // 等待的上限；实际等待在它的一半到全部之间随机
+    pub max_rounds: u32,            // 连续多少轮都失败后放弃
+}
+
+impl Default for FailoverOptions {
+    fn default() -> Self {
+        Self { initial_backoff: Duration::from_millis(100), max_backoff: Duration::from_secs(5), max_rounds: 5 }
+    }
+}
+
+/// 一次故障切换
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failover {
+    pub from: SocketAddr,
+    pub to: SocketAddr,
+    pub error: LinkError,   // 旧连接失败的原因
+    pub replayed: usize,    // 在新连接上重发的消息数，其中一些可能已经到达旧后端
+}
+
+type FailoverCallback = Box<dyn Fn(&Failover) + Send + Sync>;
+
+/// 保持到一组后端之一的连接，故障时切换到下一个
+pub struct FailoverClient {
+    addrs: Vec<SocketAddr>,
+    config: LinkConfig,
+    options: FailoverOptions,
+    connector: Box<dyn Connector>,
+    callback: Option<FailoverCallback>,
+    current: Option<(usize, Connection)>,   // 当前连接及其地址在列表中的下标
+}
+
+impl FailoverClient {
+    /// 经 UDP 依次连接 `addrs`（不能为空）中的地址；第一次 `send`、`recv` 或 `connect` 时才建立连接
+    #[cfg(feature = "tokio")]
+    pub fn new(addrs: Vec<SocketAddr>, config: LinkConfig) -> Self {
+        Self::with_connector(addrs, config, UdpConnector)
+    }
+
+    /// 以调用方的 `Connector` 建立连接
+    pub fn with_connector(addrs: Vec<SocketAddr>, config: LinkConfig, connector: impl Connector) -> Self {
+        assert!(!addrs.is_empty(), "failover client needs at least one address");
+        Self { addrs, config, options: FailoverOptions::default(), connector: Box::new(connector), callback: None, current: None }
+    }
+
+    pub fn with_options(mut self, options: FailoverOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// 每次切换完成（新连接已建立、未确认的消息已重发）后调用；回调在调用 `send`/`recv`/`close` 的任务中执行，不能阻塞
+    pub fn on_failover(mut self, callback: impl Fn(&Failover) + Send + Sync + 'static) -> Self {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+
+    /// 当前连接的后端地址
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.current.as_ref().map(|(index, _)| self.addrs[*index])
+    }
+
+    /// 当前连接；尚未连接时为 None
+    pub fn connection(&self) -> Option<&Connection> {
+        self.current.as_ref().map(|(_, connection)| connection)
+    }
+
+    /// 尚未连接时从列表中的第一个地址开始连接
+    pub async fn connect(&mut self) -> Result<&Connection, LinkError> {
+        if self.current.is_none() {
+            self.current = Some(self.establish(0).await?);
+        }
+        Ok(self.connection().expect("connected above"))
+    }
+
+    /// 发送一条消息；连接失败时切换后在新连接上发送
+    pub async fn send(&mut self, data: Bytes) -> Result<(), LinkError> {
+        loop {
+            match self.connect().await?.send(data.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(e) if is_failure(&e) => self.fail_over(e).await?,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// 接收下一条消息；连接失败时切换后从新连接接收
+    pub async fn recv(&mut self) -> Result<Option<Bytes>, LinkError> {
+        loop {
+            match self.connect().await?.recv().await {
+                Ok(message) => return Ok(message),
+                Err(e) if is_failure(&e) => self.fail_over(e).await?,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// 等所有消息都被某个后端确认后关闭连接；等待中连接失败时照常切换并重发
+    pub async fn close(mut self) -> Result<(), LinkError> {
+        loop {
+            let Some((_, connection)) = &self.current else {
+                return Ok(());
+            };
+            match connection.shutdown_write().await {
+                Ok(()) => break,
+                Err(e) if is_failure(&e) => self.fail_over(e).await?,
+                Err(e) => return Err(e),
+            }
+        }
+        let (_, connection) = self.current.take().expect("checked above");
+        connection.close().await
+    }
+
+    // 放弃当前连接，从下一个地址开始重新连接并重发未确认的消息；重发时新连接又失败则继续切换
+    async fn fail_over(&mut self, mut error: LinkError) -> Result<(), LinkError> {
+        let (mut index, old) = self.current.take().expect("failing over from a connection");
+        let from = self.addrs[index];
+        let mut replay = old.unacknowledged();
+        drop(old);
+        loop {
+            tracing::warn!(%from, %error, unacknowledged = replay.len(), "connection failed, failing over");
+            let (next, connection) = self.establish(index + 1).await?;
+            index = next;
+            match resend(&connection, &replay).await {
+                Ok(()) => {
+                    let failover = Failover { from, to: self.addrs[index], error, replayed: replay.len() };
+                    tracing::info!(from = %failover.from, to = %failover.to, replayed = failover.replayed, "failed over");
+                    self.current = Some((index, connection));
+                    if let Some(callback) = &self.callback {
+                        callback(&failover);
+                    }
+                    return Ok(());
+                }
+                Err((sent, e)) if is_failure(&e) => {
+                    // 已在新连接上发出的部分取自它的未确认消息，其余的仍按原顺序排在后面
+                    let mut unacknowledged = connection.unacknowledged();
+                    unacknowledged.extend_from_slice(&replay[sent..]);
+                    replay = unacknowledged;
+                    error = e;
+                }
+                Err((_, e)) => return Err(e),
+            }
+        }
+    }
+
+    // 从 `start` 起循环尝试每个地址；一轮都失败后退避再试
+    async fn establish(&self, start: usize) -> Result<(usize, Connection), LinkError> {
+        let mut backoff = self.options.initial_backoff;
+        let mut round = 1;
+        loop {
+            let mut failures = Vec::new();
+            for i in 0..self.addrs.len() {
+                let index = (start + i) % self.addrs.len();
+                match self.connector.connect(self.addrs[index], self.config.clone()).await {
+                    Ok(connection) => return Ok((index, connection)),
+                    Err(e) => failures.push((self.addrs[index], e)),
+                }
+            }
+            if round >= self.options.max_rounds {
+                return Err(LinkError::ConnectFailed(failures));
+            }
+            tokio::time::sleep(jittered(backoff)).await;
+            backoff = (backoff * 2).min(self.options.max_backoff);
+            round += 1;
+        }
+    }
+}
+
+impl fmt::Debug for FailoverClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FailoverClient").field("addrs", &self.addrs).field("peer", &self.peer_addr()).finish_non_exhaustive()
+    }
+}
+
+// 连接本身已不可用、应当切换的错误；其余（消息过大、写方向已关闭等）是调用方的问题，原样返回
+fn is_failure(e: &LinkError) -> bool {
+    matches!(
+        e,
+        LinkError::PeerUnreachable { .. }
+            | LinkError::KeepaliveTimeout { .. }
+            | LinkError::Reset
+            | LinkError::IdleTimeout
+            | LinkError::CloseTimedOut
+            | LinkError::Protocol(_)
+            | LinkError::NonceExhausted { .. }
+            | LinkError::Io { .. }
+    )
+}
+
+// 依次重发；失败时返回已成功交给新连接的条数
+async fn resend(connection: &Connection, messages: &[Bytes]) -> Result<(), (usize, LinkError)> {
+    for (sent, message) in messages.iter().enumerate() {
+        connection.send(message.clone()).await.map_err(|e| (sent, e))?;
+    }
+    Ok(())
+}
+
+// 在 backoff 的一半到全部之间均匀分布，避免多个客户端同时重连
+fn jittered(backoff: Duration) -> Duration {
+    let mut random = [0u8; 4];
+    getrandom::fill(&mut random).expect("operating system random source unavailable");
+    let unit = f64::from(u32::from_be_bytes(random)) / f64::from(u32::MAX);
+    backoff / 2 + backoff.mul_f64(unit / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jitter_stays_within_half_to_full_backoff() {
+        let backoff = Duration::from_millis(400);
+        for _ in 0..100 {
+            let delay = jittered(backoff);
+            assert!(delay >= backoff / 2 && delay <= backoff, "{:?}", delay);
+        }
+    }
+}
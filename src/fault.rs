@@ -0,0 +1,546 @@
+//! 故障注入
+//! `FaultyTransport` 包在任意 `Transport`（UDP 套接字或内存端点）外面，自身也是 `Transport`，按 `FaultConfig` 的概率丢弃、复制、延迟与乱序收发的数据报，
+//! 或者像途经拥塞的路由器那样给其中的数据段打上 CE 标记（`segment::mark_ce`），
+//! 收与发两个方向各有独立的随机数序列，由同一个种子派生，同样的种子与流量得到同样的决定。
+//! `LinkConfig::faults` 设置后监听器与客户端连接的传输都经过它，测试也可以直接包装一个传输。
+//! `FaultConfig` 也可以从文本解析，如 `loss=0.05,dup=0.01,reorder=0.02,ce=0.1,delay=20ms±10ms,seed=7`。
+//!
+//! 两种路径本身的缺陷不是概率性的：`mtu` 之上的数据报在两个方向都被静默丢弃（路径 MTU 黑洞）；`nat` 把本端放在
+//! 一个 NAT 后面看对端（`Nat`）——对端超过这么久没有发来数据报，映射过期，下一个数据报换一个源端口到达，
+//! 发往旧端口的数据报被丢弃。它们用来复现自检的诊断（见 `diagnostics` 模块）。
+//!
+//! 每个数据报的去向由 `Faults::plan` 决定（不做 IO，时间由调用方注入）：丢弃时没有副本；复制时有两个副本；
+//! 每个副本在 `delay` 加上 ±`jitter` 内均匀分布的抖动之后放出；乱序的副本再多等 `REORDER_DELAY`，
+//! 期间之后的数据报越过它。每个方向有一个任务按放出时间（相同时按到达顺序）依次发出或交给 `recv_from`。
+//! `FaultyTransport::control` 返回的 `FaultControl` 可以在运行中替换注入的故障并读出丢弃的计数，
+//! 例如只在一致性测试的丢包阶段打开丢包（见 `conformance` 模块）。
+
+use crate::config;
+use crate::error;
+use crate::segment;
+use crate::transport::{self, BoxFuture, Transport};
+use bytes::Bytes;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::io;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::{Mutex, mpsc};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// 乱序的副本额外等待的时间
+pub const REORDER_DELAY: Duration = Duration::from_millis(5);
+
+/// 收到、尚未被 `recv_from` 取走的数据报上限，超过时丢弃（等同于内核接收缓冲区已满）
+const INBOUND_QUEUE: usize = 1024;
+
+/// 注入的故障：各项概率在 0..=1 之间，对每个数据报独立判定
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultConfig {
+    pub loss: f64,
+    pub duplicate: f64,
+    pub reorder: f64,
+    pub ce: f64,            // 数据报中的数据段被标记 CE 的概率
+    pub delay: Duration,
+    pub jitter: Duration,   // 延迟在 delay ± jitter 内均匀分布
+    pub mtu: Option<usize>, // 超过这么多字节的数据报被丢弃，两个方向都是
+    pub nat: Option<Duration>,  // 对端经过的 NAT 的映射在对端静默这么久后过期
+    pub seed: u64,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        Self { loss: 0.0, duplicate: 0.0, reorder: 0.0, ce: 0.0, delay: Duration::ZERO, jitter: Duration::ZERO, mtu: None, nat: None, seed: 1 }
+    }
+}
+
+// 逗号分隔的 key=value，未给出的项不注入
+impl FromStr for FaultConfig {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut faults = FaultConfig::default();
+        for item in spec.split(',').filter(|item| !item.is_empty()) {
+            let (key, value) = item.split_once('=').ok_or_else(|| format!("invalid fault '{}', expected key=value", item))?;
+            let probability = || {
+                value.parse::<f64>().ok().filter(|p| (0.0..=1.0).contains(p)).ok_or_else(|| format!("invalid {} '{}', expected a probability in 0..=1", key, value))
+            };
+            match key {
+                "loss" => faults.loss = probability()?,
+                "dup" => faults.duplicate = probability()?,
+                "reorder" => faults.reorder = probability()?,
+                "ce" => faults.ce = probability()?,
+                "delay" => {
+                    let invalid = || format!("invalid delay '{}', expected e.g. 20ms or 20ms±10ms", value);
+                    let (delay, jitter) = value.split_once('±').or_else(|| value.split_once("+-")).unwrap_or((value, "0ms"));
+                    faults.delay = config::parse_duration(delay).ok_or_else(invalid)?;
+                    faults.jitter = config::parse_duration(jitter).ok_or_else(invalid)?;
+                }
+                "mtu" => {
+                    let mtu = value.parse().ok().filter(|mtu| *mtu > 0).ok_or_else(|| format!("invalid mtu '{}', expected a positive number of bytes", value))?;
+                    faults.mtu = Some(mtu);
+                }
+                "nat" => {
+                    let timeout = config::parse_duration(value).filter(|timeout| !timeout.is_zero()).ok_or_else(|| format!("invalid nat '{}', expected e.g. 30s", value))?;
+                    faults.nat = Some(timeout);
+                }
+                "seed" => faults.seed = value.parse().map_err(|_| format!("invalid seed '{}', expected an integer", value))?,
+                other => return Err(format!("unknown fault '{}', expected loss, dup, reorder, ce, delay, mtu, nat or seed", other)),
+            }
+        }
+        Ok(faults)
+    }
+}
+
+/// 可复现的伪随机数（SplitMix64），不用于任何安全用途
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // [0, 1) 内的均匀分布
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.unit() < probability
+    }
+}
+
+/// 一个方向的故障判定
+#[derive(Debug, Clone)]
+pub struct Faults {
+    config: FaultConfig,
+    rng: Rng,
+    dropped: u64,
+}
+
+impl Faults {
+    /// `stream` 区分同一种子派生的不同方向
+    pub fn new(config: &FaultConfig, stream: u64) -> Self {
+        let mut rng = Rng(config.seed ^ stream.wrapping_mul(0xd1b5_4a32_d192_ed03));
+        rng.next();
+        Self { config: config.clone(), rng, dropped: 0 }
+    }
+
+    /// 在 `now` 到达的 `len` 字节的数据报各副本的放出时间；空表示丢弃。超过 `mtu` 的数据报不消耗随机数
+    pub fn plan(&mut self, len: usize, now: Instant) -> Vec<Instant> {
+        if self.config.mtu.is_some_and(|mtu| len > mtu) {
+            self.dropped += 1;
+            return Vec::new();
+        }
+        if self.rng.chance(self.config.loss) {
+            self.dropped += 1;
+            return Vec::new();
+        }
+        let copies = if self.rng.chance(self.config.duplicate) { 2 } else { 1 };
+        (0..copies)
+            .map(|_| {
+                let jitter = self.config.jitter.mul_f64(self.rng.unit() * 2.0);
+                let mut at = (now + self.config.delay + jitter).checked_sub(self.config.jitter).unwrap_or(now).max(now);
+                if self.rng.chance(self.config.reorder) {
+                    at += REORDER_DELAY;
+                }
+                at
+            })
+            .collect()
+    }
+
+    /// 没有被丢弃的数据报是否途经拥塞、需要标记 CE；`ce` 为 0 时不消耗随机数，其余判定的序列不变
+    pub fn congests(&mut self) -> bool {
+        self.rng.chance(self.config.ce)
+    }
+
+    /// 至今判定丢弃的数据报数
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+/// 对端前面的 NAT（`FaultConfig::nat`）：记下每个对端的真实地址当前映射到的外部地址。对端静默超过 `timeout` 后映射过期，
+/// 它的下一个数据报换一个外部端口到达；本端发往当前外部地址的数据报送到对端的真实地址，发往过期外部地址的被丢弃
+#[derive(Debug, Default)]
+pub struct Nat {
+    timeout: Option<Duration>,
+    mappings: HashMap<SocketAddr, (SocketAddr, Instant)>,   // 真实地址 → 外部地址与最近一次发来数据报的时间
+    rebinds: u16,
+}
+
+impl Nat {
+    /// `timeout` 为 None 时地址原样通过
+    pub fn new(timeout: Option<Duration>) -> Self {
+        Self { timeout, ..Self::default() }
+    }
+
+    /// 对端从 `from` 在 `now` 发来的数据报在本端看来的来源地址；第一个映射就是真实地址
+    pub fn inbound(&mut self, from: SocketAddr, now: Instant) -> SocketAddr {
+        let Some(timeout) = self.timeout else {
+            return from;
+        };
+        let (external, seen) = self.mappings.entry(from).or_insert((from, now));
+        if now.duration_since(*seen) >= timeout {
+            self.rebinds = self.rebinds.wrapping_add(1);
+            *external = SocketAddr::new(from.ip(), from.port().wrapping_add(self.rebinds));
+        }
+        *seen = now;
+        *external
+    }
+
+    /// 本端发往 `to` 的数据报实际送达的地址；`to` 是映射过的对端已经过期的外部地址时返回 None
+    pub fn outbound(&self, to: SocketAddr) -> Option<SocketAddr> {
+        if self.timeout.is_none() {
+            return Some(to);
+        }
+        if let Some((real, _)) = self.mappings.iter().find(|(_, (external, _))| *external == to) {
+            return Some(*real);
+        }
+        match self.mappings.keys().any(|real| real.ip() == to.ip()) {
+            true => None,
+            false => Some(to),
+        }
+    }
+
+    /// 至今重新映射的次数
+    pub fn rebinds(&self) -> u16 {
+        self.rebinds
+    }
+}
+
+/// 运行中替换 `FaultyTransport` 注入的故障，两个方向一起生效；已经判定、尚未放出的数据报不受影响
+#[derive(Debug, Clone)]
+pub struct FaultControl(Arc<([StdMutex<Faults>; 2], StdMutex<Nat>)>);  // 发出与收到两个方向，下标即 `Faults::new` 的 stream；两个方向共用的 NAT
+
+impl FaultControl {
+    fn new(config: &FaultConfig) -> Self {
+        Self(Arc::new(([StdMutex::new(Faults::new(config, 0)), StdMutex::new(Faults::new(config, 1))], StdMutex::new(Nat::new(config.nat)))))
+    }
+
+    fn direction(&self, stream: usize) -> MutexGuard<'_, Faults> {
+        self.0.0[stream].lock().expect("fault state poisoned")
+    }
+
+    fn nat(&self) -> MutexGuard<'_, Nat> {
+        self.0.1.lock().expect("fault state poisoned")
+    }
+
+    /// 换成 `config` 描述的故障，随机数序列由它的种子重新派生，NAT 的映射清空；丢弃的计数累计保留
+    pub fn set(&self, config: &FaultConfig) {
+        for stream in 0..2 {
+            let mut faults = self.direction(stream);
+            let dropped = faults.dropped;
+            *faults = Faults::new(config, stream as u64);
+            faults.dropped = dropped;
+        }
+        *self.nat() = Nat::new(config.nat);
+    }
+
+    /// NAT 至今重新映射对端的次数
+    pub fn rebinds(&self) -> u16 {
+        self.nat().rebinds()
+    }
+
+    /// 至今丢弃的数据报数：（发出的，收到的）
+    pub fn dropped(&self) -> (u64, u64) {
+        (self.direction(0).dropped, self.direction(1).dropped)
+    }
+}
+
+// 按判定给数据报的副本打上 CE 标记
+fn marked(faults: &mut Faults, datagram: &[u8]) -> Bytes {
+    if faults.congests() {
+        let mut datagram = datagram.to_vec();
+        segment::mark_ce(&mut datagram);
+        return Bytes::from(datagram);
+    }
+    Bytes::copy_from_slice(datagram)
+}
+
+// 放出时间、到达序号（相同放出时间时保持到达顺序）、数据报与对端
+type Pending = Reverse<(Instant, u64, Bytes, SocketAddr)>;
+
+/// 注入故障的传输
+#[derive(Debug)]
+pub struct FaultyTransport<T> {
+    socket: Arc<T>,
+    control: FaultControl,
+    sent: AtomicU64,    // 发出的副本的序号，放出时间相同时按它排序
+    outgoing: mpsc::UnboundedSender<Pending>,
+    incoming: Mutex<mpsc::Receiver<io::Result<(Bytes, SocketAddr)>>>,
+    tasks: [JoinHandle<()>; 2],
+}
+
+impl<T: Transport> FaultyTransport<T> {
+    /// `recv_buffer` 是单次接收的缓冲区大小，与 `LinkConfig::recv_buffer` 相同
+    pub fn new(socket: T, config: &FaultConfig, recv_buffer: usize) -> Self {
+        let socket = Arc::new(socket);
+        let (outgoing, pending) = mpsc::unbounded_channel();
+        let (tx, incoming) = mpsc::channel(INBOUND_QUEUE);
+        let control = FaultControl::new(config);
+        let sender = tokio::spawn(send_loop(socket.clone(), pending));
+        let receiver = tokio::spawn(recv_loop(socket.clone(), control.clone(), recv_buffer, tx));
+        Self {
+            socket,
+            control,
+            sent: AtomicU64::new(0),
+            outgoing,
+            incoming: Mutex::new(incoming),
+            tasks: [sender, receiver],
+        }
+    }
+}
+
+impl<T: Transport> Transport for FaultyTransport<T> {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// 总是立即成功，与交给内核之后在网络上丢失一样；真正发送时的错误被忽略
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        let sent = self.plan(buf, target);
+        Box::pin(async move { sent })
+    }
+
+    /// 放不下的数据报与 UDP 套接字一样被截断
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        Box::pin(async move {
+            let received = self.incoming.lock().await.recv().await;
+            let (datagram, from) = received.unwrap_or_else(|| Err(io::Error::from(io::ErrorKind::NotConnected)))?;
+            Ok((transport::copy_truncated(&datagram, buf), from))
+        })
+    }
+
+    /// 只取已经放出的数据报，还在延迟中的不提前交出
+    fn try_recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let mut incoming = self.incoming.try_lock().map_err(|_| io::Error::from(io::ErrorKind::WouldBlock))?;
+        let received = incoming.try_recv().map_err(|e| match e {
+            mpsc::error::TryRecvError::Empty => io::Error::from(io::ErrorKind::WouldBlock),
+            mpsc::error::TryRecvError::Disconnected => io::Error::from(io::ErrorKind::NotConnected),
+        })?;
+        let (datagram, from) = received?;
+        Ok((transport::copy_truncated(&datagram, buf), from))
+    }
+}
+
+impl<T> FaultyTransport<T> {
+    /// 运行中替换注入的故障、读出丢弃计数的句柄
+    pub fn control(&self) -> FaultControl {
+        self.control.clone()
+    }
+
+    // 判定发出的数据报的去向，把它的副本交给发出方向的任务；持有判定的锁直到副本排好序号
+    fn plan(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        let translated = self.control.nat().outbound(target);
+        let mut outbound = self.control.direction(0);
+        let Some(target) = translated else {
+            outbound.dropped += 1;
+            return Ok(buf.len());
+        };
+        let plan = outbound.plan(buf.len(), Instant::now());
+        if plan.is_empty() {
+            return Ok(buf.len());
+        }
+        let datagram = marked(&mut outbound, buf);
+        for at in plan {
+            let sent = self.sent.fetch_add(1, Ordering::Relaxed);
+            let _ = self.outgoing.send(Reverse((at, sent, datagram.clone(), target)));
+        }
+        Ok(buf.len())
+    }
+}
+
+impl<T> Drop for FaultyTransport<T> {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+// 发出方向：按放出时间依次发送
+async fn send_loop<T: Transport>(socket: Arc<T>, mut pending: mpsc::UnboundedReceiver<Pending>) {
+    let mut queue = BinaryHeap::new();
+    loop {
+        let next = queue.peek().map(|Reverse((at, ..)): &Pending| *at);
+        tokio::select! {
+            item = pending.recv() => match item {
+                Some(item) => queue.push(item),
+                None => return,
+            },
+            () = sleep_until(next) => {
+                let Some(Reverse((_, _, datagram, target))) = queue.pop() else { continue };
+                let _ = socket.send_to(&datagram, target).await;
+            }
+        }
+    }
+}
+
+// 收到方向：判定后按放出时间依次交给 recv_from；套接字失效时把错误交给它并退出
+async fn recv_loop<T: Transport>(socket: Arc<T>, control: FaultControl, recv_buffer: usize, incoming: mpsc::Sender<io::Result<(Bytes, SocketAddr)>>) {
+    let mut buf = vec![0u8; recv_buffer];
+    let mut queue = BinaryHeap::new();
+    let mut arrivals = 0u64;
+    loop {
+        let next = queue.peek().map(|Reverse((at, ..)): &Pending| *at);
+        tokio::select! {
+            received = socket.recv_from(&mut buf) => match received {
+                Ok((len, from)) => {
+                    let from = control.nat().inbound(from, Instant::now());
+                    let mut faults = control.direction(1);
+                    let plan = faults.plan(len, Instant::now());
+                    if plan.is_empty() {
+                        continue;
+                    }
+                    let datagram = marked(&mut faults, &buf[..len]);
+                    drop(faults);
+                    for at in plan {
+                        arrivals += 1;
+                        queue.push(Reverse((at, arrivals, datagram.clone(), from)));
+                    }
+                }
+                Err(e) if error::is_fatal(&e) => {
+                    let _ = incoming.send(Err(e)).await;
+                    return;
+                }
+                Err(e) => {
+                    let _ = incoming.try_send(Err(e));
+                }
+            },
+            () = sleep_until(next) => {
+                let Some(Reverse((_, _, datagram, from))) = queue.pop() else { continue };
+                let _ = incoming.try_send(Ok((datagram, from)));
+            }
+        }
+    }
+}
+
+async fn sleep_until(at: Option<Instant>) {
+    match at {
+        Some(at) => tokio::time::sleep_until(at).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MemoryTransport;
+
+    fn plans(config: &FaultConfig, stream: u64, now: Instant) -> Vec<Vec<Instant>> {
+        let mut faults = Faults::new(config, stream);
+        (0..10_000).map(|_| faults.plan(100, now)).collect()
+    }
+
+    #[test]
+    fn test_plan_rates_and_reproducibility() {
+        let config = FaultConfig { loss: 0.05, duplicate: 0.01, reorder: 0.02, ce: 0.0, delay: Duration::from_millis(20), jitter: Duration::from_millis(10), mtu: None, nat: None, seed: 7 };
+        let now = Instant::now();
+        let plan = plans(&config, 0, now);
+        assert_eq!(plan, plans(&config, 0, now));
+        assert_ne!(plan, plans(&config, 1, now));
+
+        let lost = plan.iter().filter(|copies| copies.is_empty()).count();
+        let duplicated = plan.iter().filter(|copies| copies.len() == 2).count();
+        assert!((400..600).contains(&lost), "{} lost", lost);
+        assert!((50..150).contains(&duplicated), "{} duplicated", duplicated);
+        let delays: Vec<_> = plan.iter().flatten().map(|at| *at - now).collect();
+        assert!(delays.iter().all(|delay| (Duration::from_millis(10)..=Duration::from_millis(30) + REORDER_DELAY).contains(delay)));
+        let reordered = delays.iter().filter(|delay| **delay > Duration::from_millis(30)).count();
+        assert!(reordered > 0 && reordered < 400, "{} reordered", reordered);
+
+        // 不注入任何故障时每个数据报立即放出
+        assert!(plans(&FaultConfig::default(), 0, now).iter().all(|copies| copies == &[now]));
+
+        // 超过 MTU 的数据报总被丢弃，放得下的照常判定
+        let mut faults = Faults::new(&FaultConfig { mtu: Some(1200), ..FaultConfig::default() }, 0);
+        assert!(faults.plan(1201, now).is_empty());
+        assert_eq!(faults.plan(1200, now), [now]);
+        assert_eq!(faults.dropped(), 1);
+    }
+
+    #[test]
+    fn test_nat_rebinds_a_silent_peer() {
+        let t0 = Instant::now();
+        let (peer, other) = ("10.0.0.2:5000".parse().unwrap(), "10.0.0.3:5000".parse().unwrap());
+        let mut nat = Nat::new(Some(Duration::from_secs(2)));
+        assert_eq!(nat.inbound(peer, t0), peer);
+        assert_eq!(nat.inbound(peer, t0 + Duration::from_secs(1)), peer);
+        assert_eq!(nat.outbound(peer), Some(peer));
+
+        // 静默到期：换一个外部端口，旧端口不再通向对端；与它无关的地址原样通过
+        let rebound = nat.inbound(peer, t0 + Duration::from_secs(3));
+        assert_eq!((rebound.ip(), rebound.port() != peer.port()), (peer.ip(), true));
+        assert_eq!(nat.outbound(rebound), Some(peer));
+        assert_eq!(nat.outbound(peer), None);
+        assert_eq!(nat.outbound(other), Some(other));
+        assert_eq!(nat.rebinds(), 1);
+
+        let mut open = Nat::new(None);
+        assert_eq!(open.inbound(peer, t0 + Duration::from_secs(60)), peer);
+        assert_eq!(open.outbound(peer), Some(peer));
+    }
+
+    #[tokio::test]
+    async fn test_control_switches_faults_at_runtime() {
+        let (near, far) = MemoryTransport::pair(16, 16);
+        let (near_addr, far_addr) = (near.local_addr().unwrap(), far.local_addr().unwrap());
+        let faulty = FaultyTransport::new(near, &FaultConfig::default(), 2048);
+        let control = faulty.control();
+        let mut buf = [0u8; 16];
+
+        // 打开丢包：两个方向各丢一个，等收到方向判定完再关掉
+        control.set(&FaultConfig { loss: 1.0, ..FaultConfig::default() });
+        faulty.send_to(b"lost", far_addr).await.unwrap();
+        far.send_to(b"lost", near_addr).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while control.dropped() != (1, 1) {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        control.set(&FaultConfig::default());
+        faulty.send_to(b"kept", far_addr).await.unwrap();
+        far.send_to(b"kept", near_addr).await.unwrap();
+        let (len, _) = far.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"kept");
+        let (len, _) = faulty.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"kept");
+        // 丢弃计数跨过替换累计
+        assert_eq!(control.dropped(), (1, 1));
+    }
+
+    #[test]
+    fn test_parse_spec() {
+        let faults: FaultConfig = "loss=0.05,dup=0.01,reorder=0.02,ce=0.5,delay=20ms±10ms,seed=9".parse().unwrap();
+        assert_eq!(
+            faults,
+            FaultConfig { loss: 0.05, duplicate: 0.01, reorder: 0.02, ce: 0.5, delay: Duration::from_millis(20), jitter: Duration::from_millis(10), mtu: None, nat: None, seed: 9 }
+        );
+        let faults: FaultConfig = "delay=1s+-5ms".parse().unwrap();
+        assert_eq!((faults.loss, faults.delay, faults.jitter), (0.0, Duration::from_secs(1), Duration::from_millis(5)));
+
+        let faults: FaultConfig = "mtu=1100,nat=30s".parse().unwrap();
+        assert_eq!((faults.mtu, faults.nat), (Some(1100), Some(Duration::from_secs(30))));
+
+        assert!("loss=1.5".parse::<FaultConfig>().unwrap_err().contains("invalid loss"));
+        assert!("mtu=0".parse::<FaultConfig>().unwrap_err().contains("invalid mtu"));
+        assert!("nat=0s".parse::<FaultConfig>().unwrap_err().contains("invalid nat"));
+        assert!("ce=-1".parse::<FaultConfig>().unwrap_err().contains("invalid ce"));
+        assert!("delay=20".parse::<FaultConfig>().unwrap_err().contains("invalid delay"));
+        assert!("drop=0.1".parse::<FaultConfig>().unwrap_err().contains("unknown fault"));
+        assert!("loss".parse::<FaultConfig>().unwrap_err().contains("expected key=value"));
+    }
+}
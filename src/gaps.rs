@@ -0,0 +1,170 @@
+//! 接收端空洞事件
+//! 对延迟敏感的上层（例如游戏服务器在数据缺失时先做客户端预测）想在重排缓冲区出现空洞时立即得知，而不是等重传补齐后
+//! 才看到数据。`Connection::subscribe_gaps` 之后，流 0 的接收端每处理完一个占用序列号的段（数据、Skip、NewConnId、FIN）就核对
+//! 重排缓冲区中的空洞：新出现的空洞报告一次 `Opened`，之后它的每个序列号都收到或被占住时报告一次 `Filled`，
+//! 其中有序列号被发送方以 Skip 放弃时报告 `Abandoned`。按序交付不受影响，事件只是旁路的通知。
+//!
+//! 空洞以首次发现时缺失的序列号区间标识：同一空洞之后的乱序段只会让它缩小或分裂，不产生新的事件，
+//! 一连串落在同一个空洞之后的乱序段只报告一次；分裂出的各部分全部补齐时才报告结束。新的空洞只出现在已收到的最大序列号之后。
+//! 订阅时已经存在的空洞在下一个段到达时报告。附加流不报告空洞。
+//!
+//! 事件经无界通道交给订阅者（`GapEvents`），再次订阅取代之前的订阅；连接结束后 `GapEvents::next` 返回 None。
+
+use crate::recv_buffer::ReceiveBuffer;
+use crate::seq::SeqNum;
+use std::collections::HashSet;
+use std::future::poll_fn;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+/// 流 0 重排缓冲区中的空洞事件，区间都是闭区间 `start..=end`，`at` 是发现变化的时间
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapEvent {
+    /// 出现了新的空洞：这段序列号还没有收到，它之后的已经收到
+    Opened { start: SeqNum, end: SeqNum, at: Instant },
+    /// 空洞的序列号全部收到（重传或迟到的乱序段）
+    Filled { start: SeqNum, end: SeqNum, at: Instant },
+    /// 空洞中有序列号被发送方放弃（Skip），不会再补齐
+    Abandoned { start: SeqNum, end: SeqNum, at: Instant },
+}
+
+impl GapEvent {
+    /// 事件所指的空洞：首次发现时缺失的序列号区间
+    pub fn range(&self) -> (SeqNum, SeqNum) {
+        match *self {
+            GapEvent::Opened { start, end, .. } | GapEvent::Filled { start, end, .. } | GapEvent::Abandoned { start, end, .. } => (start, end),
+        }
+    }
+}
+
+/// 订阅者持有的空洞事件流（见 `Connection::subscribe_gaps`）
+#[derive(Debug)]
+pub struct GapEvents {
+    rx: mpsc::UnboundedReceiver<GapEvent>,
+}
+
+impl GapEvents {
+    pub(crate) fn new(rx: mpsc::UnboundedReceiver<GapEvent>) -> Self {
+        Self { rx }
+    }
+
+    /// 等待下一个事件；连接结束或订阅被取代后返回 None
+    pub async fn next(&mut self) -> Option<GapEvent> {
+        poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<GapEvent>> {
+        self.rx.poll_recv(cx)
+    }
+
+    /// 不等待地取出下一个已到达的事件
+    pub fn try_next(&mut self) -> Option<GapEvent> {
+        self.rx.try_recv().ok()
+    }
+}
+
+#[cfg(feature = "futures")]
+impl futures_core::Stream for GapEvents {
+    type Item = GapEvent;
+
+    fn poll_next(self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<GapEvent>> {
+        self.get_mut().rx.poll_recv(cx)
+    }
+}
+
+/// 接收端记录的、尚未结束的空洞与待交出的事件
+#[derive(Debug, Default)]
+pub(crate) struct GapTracker {
+    open: Vec<(SeqNum, SeqNum)>,
+    events: Vec<GapEvent>,
+}
+
+impl GapTracker {
+    /// 核对重排缓冲区当前的空洞：`skipped` 是 Skip 占住、尚未按序读到的序列号
+    pub(crate) fn update(&mut self, buffer: &ReceiveBuffer, skipped: &HashSet<SeqNum>, now: Instant) {
+        let holes = buffer.holes();
+        let events = &mut self.events;
+        self.open.retain(|&(start, end)| {
+            if holes.iter().any(|&hole| overlaps(hole, (start, end))) {
+                return true;
+            }
+            let mut seq = start;
+            let mut abandoned = false;
+            while !seq.is_after(end) && !abandoned {
+                abandoned = skipped.contains(&seq);
+                seq = seq.wrapping_add(1);
+            }
+            events.push(match abandoned {
+                true => GapEvent::Abandoned { start, end, at: now },
+                false => GapEvent::Filled { start, end, at: now },
+            });
+            false
+        });
+        for hole in holes {
+            if !self.open.iter().any(|&gap| overlaps(hole, gap)) {
+                self.events.push(GapEvent::Opened { start: hole.0, end: hole.1, at: now });
+                self.open.push(hole);
+            }
+        }
+    }
+
+    pub(crate) fn take_events(&mut self) -> Vec<GapEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+fn overlaps(a: (SeqNum, SeqNum), b: (SeqNum, SeqNum)) -> bool {
+    !a.1.is_before(b.0) && !a.0.is_after(b.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn seq(n: u64) -> SeqNum {
+        SeqNum::new(n)
+    }
+
+    fn insert(buffer: &mut ReceiveBuffer, tracker: &mut GapTracker, n: u64, now: Instant) {
+        buffer.insert(seq(n), Bytes::from_static(b"x"));
+        tracker.update(buffer, &HashSet::new(), now);
+    }
+
+    #[test]
+    fn test_burst_behind_one_gap_opens_it_once() {
+        let now = Instant::now();
+        let mut buffer = ReceiveBuffer::new(seq(0), 64);
+        let mut tracker = GapTracker::default();
+        insert(&mut buffer, &mut tracker, 0, now);
+        for n in 3..8 {
+            insert(&mut buffer, &mut tracker, n, now);
+        }
+        assert_eq!(tracker.take_events(), vec![GapEvent::Opened { start: seq(1), end: seq(2), at: now }]);
+        // 空洞分裂不产生事件，全部补齐时才结束
+        insert(&mut buffer, &mut tracker, 2, now);
+        assert!(tracker.take_events().is_empty());
+        insert(&mut buffer, &mut tracker, 1, now);
+        assert_eq!(tracker.take_events(), vec![GapEvent::Filled { start: seq(1), end: seq(2), at: now }]);
+    }
+
+    #[test]
+    fn test_separate_gaps_and_abandonment() {
+        let now = Instant::now();
+        let mut buffer = ReceiveBuffer::new(seq(0), 64);
+        let mut tracker = GapTracker::default();
+        insert(&mut buffer, &mut tracker, 1, now);
+        insert(&mut buffer, &mut tracker, 3, now);
+        assert_eq!(
+            tracker.take_events(),
+            vec![GapEvent::Opened { start: seq(0), end: seq(0), at: now }, GapEvent::Opened { start: seq(2), end: seq(2), at: now }]
+        );
+        // 发送方放弃了序列号 0
+        buffer.insert(seq(0), Bytes::new());
+        tracker.update(&buffer, &HashSet::from([seq(0)]), now);
+        assert_eq!(tracker.take_events(), vec![GapEvent::Abandoned { start: seq(0), end: seq(0), at: now }]);
+        insert(&mut buffer, &mut tracker, 2, now);
+        assert_eq!(tracker.take_events(), vec![GapEvent::Filled { start: seq(2), end: seq(2), at: now }]);
+    }
+}
@@ -0,0 +1,531 @@
+//! 抓包与记录文件的离线解码
+//! `link-inspect` 的库部分：读入 pcap 文件（`capture` 模块写出的，或 tcpdump 写出的）或 `replay` 模块的记录文件，
+//! 取出其中的 UDP 数据报，逐个段以 `DecodeOptions::allow_unknown` 解码，每个段得到一个 `Entry`。
+//!
+//! pcap 接受微秒与纳秒时间戳、两种字节序，链路类型 RAW、Ethernet（含一层 802.1Q 标签）、BSD 环回与 Linux cooked（SLL/SLL2）；
+//! 其中不是 UDP 的包与 IP 分片被跳过。pcapng 需要先以 `editcap -F pcap` 转换。记录文件只记入站方向，它的每个数据报都是 `Inbound`；
+//! pcap 中的方向只在给出端口时可以判断：发往这个端口的是 `Inbound`，从它发出的是 `Outbound`。时间戳取距文件中第一个数据报的时间。
+//!
+//! 一个数据报可能打包了多个段，与监听器一样依次解码；无法解析的部分作为一个带错误的 `Entry` 保留下来（数据报的其余内容），
+//! 不会中止整个文件。文件本身无法解析（格式未知、记录截断）时返回 `InspectError`。
+
+use crate::replay::{self, RecordError};
+use crate::segment::{DecodeOptions, Decoded, Segment, SegmentError, SegmentFlags};
+use crate::options::SegmentOption;
+use crate::trace::Direction;
+use bytes::{Bytes, BytesMut};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+// 链路类型（https://www.tcpdump.org/linktypes.html）
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LOOP: u32 = 108;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+/// 文件无法解析
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InspectError {
+    UnknownFormat,                  // 既不是 pcap 也不是记录文件
+    Pcapng,                         // pcapng 文件，需要先转换为 pcap
+    UnsupportedLinkType(u32),       // 不认识的 pcap 链路类型
+    Truncated { offset: usize },    // 从这个偏移开始的 pcap 记录不完整
+    Record(RecordError),            // 记录文件无法解析
+}
+
+impl fmt::Display for InspectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InspectError::UnknownFormat => write!(f, "neither a pcap nor a datagram record file"),
+            InspectError::Pcapng => write!(f, "pcapng is not supported, convert it with `editcap -F pcap`"),
+            InspectError::UnsupportedLinkType(link_type) => write!(f, "unsupported pcap link type {}", link_type),
+            InspectError::Truncated { offset } => write!(f, "pcap record at offset {} is truncated", offset),
+            InspectError::Record(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for InspectError {}
+
+impl From<RecordError> for InspectError {
+    fn from(e: RecordError) -> Self {
+        InspectError::Record(e)
+    }
+}
+
+/// 文件中的一个 UDP 数据报
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Datagram {
+    pub at: Duration,       // 距文件中第一个数据报的时间
+    pub from: SocketAddr,
+    pub to: SocketAddr,
+    pub inbound: bool,      // 记录文件中的数据报，已知是入站方向
+    pub payload: Bytes,
+}
+
+/// 一个段，或数据报中无法解析的其余部分
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub at: Duration,
+    pub from: SocketAddr,
+    pub to: SocketAddr,
+    pub direction: Option<Direction>,
+    pub bytes: Bytes,       // 这个段的原始字节；出错时是数据报中从这里开始的其余内容
+    pub segment: Result<Decoded, SegmentError>,
+}
+
+/// 读出文件中的全部 UDP 数据报，按文件中的顺序
+pub fn datagrams(file: &[u8]) -> Result<Vec<Datagram>, InspectError> {
+    match file.get(..4) {
+        Some(magic) if magic == replay::MAGIC => {
+            let mut at = Duration::ZERO;
+            Ok(replay::decode(file)?
+                .into_iter()
+                .map(|recorded| {
+                    at += recorded.delay;
+                    Datagram { at, from: recorded.from, to: recorded.to, inbound: true, payload: recorded.datagram }
+                })
+                .collect())
+        }
+        Some([0x0a, 0x0d, 0x0d, 0x0a]) => Err(InspectError::Pcapng),
+        Some(_) => pcap(file),
+        None => Err(InspectError::UnknownFormat),
+    }
+}
+
+/// 解码文件中与 `port` 有关的（为 None 时是全部）数据报，每个段一个 `Entry`
+pub fn inspect(file: &[u8], port: Option<u16>) -> Result<Vec<Entry>, InspectError> {
+    let datagrams = datagrams(file)?;
+    Ok(datagrams
+        .iter()
+        .filter(|datagram| port.is_none_or(|port| datagram.from.port() == port || datagram.to.port() == port))
+        .flat_map(|datagram| decode(datagram, port))
+        .collect())
+}
+
+/// 依次解码一个数据报中的段，遇到无法解析的部分时以它结束
+pub fn decode(datagram: &Datagram, port: Option<u16>) -> Vec<Entry> {
+    let direction = match port {
+        _ if datagram.inbound => Some(Direction::Inbound),
+        Some(port) if datagram.to.port() == port => Some(Direction::Inbound),
+        Some(_) => Some(Direction::Outbound),
+        None => None,
+    };
+    let entry = |bytes: Bytes, segment| Entry { at: datagram.at, from: datagram.from, to: datagram.to, direction, bytes, segment };
+    let options = DecodeOptions { allow_unknown: true };
+    let mut entries = Vec::new();
+    let mut rest = BytesMut::from(&datagram.payload[..]);
+    let mut offset = 0;
+    while !rest.is_empty() {
+        let remaining = datagram.payload.slice(offset..);
+        match Segment::decode_from_with_options(&mut rest, options) {
+            Ok(Some(decoded)) => {
+                let len = remaining.len() - rest.len();
+                entries.push(entry(remaining.slice(..len), Ok(decoded)));
+                offset += len;
+            }
+            Ok(None) => {
+                entries.push(entry(remaining, Err(SegmentError::TooShort)));
+                break;
+            }
+            Err(e) => {
+                entries.push(entry(remaining, Err(e)));
+                break;
+            }
+        }
+    }
+    entries
+}
+
+impl Entry {
+    /// 段的原始字节，`hexdump -C` 的格式，每行之前换行
+    pub fn hexdump(&self) -> String {
+        let mut out = String::new();
+        let _ = crate::rejects::hexdump(&mut out, &self.bytes);
+        out
+    }
+
+    /// 一行 JSON；`verbose` 时带上段的原始字节（十六进制）
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self, verbose: bool) -> String {
+        #[derive(serde::Serialize)]
+        struct Json {
+            at_us: u64,
+            from: SocketAddr,
+            to: SocketAddr,
+            direction: Option<&'static str>,
+            #[serde(flatten)]
+            header: Option<Header>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            error: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            bytes: Option<String>,
+        }
+        let json = Json {
+            at_us: u64::try_from(self.at.as_micros()).unwrap_or(u64::MAX),
+            from: self.from,
+            to: self.to,
+            direction: self.direction.map(direction),
+            header: self.segment.as_ref().ok().map(Header::of),
+            error: self.segment.as_ref().err().map(|e| e.to_string()),
+            bytes: verbose.then(|| self.bytes.iter().map(|byte| format!("{:02x}", byte)).collect()),
+        };
+        serde_json::to_string(&json).expect("entry serializes")
+    }
+}
+
+/// 一行摘要：时间戳、地址与方向，之后是段头字段与数据体长度，或解码错误
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>11.6} {} > {} {:<3}", self.at.as_secs_f64(), self.from, self.to, self.direction.map_or("-", direction))?;
+        match &self.segment {
+            Ok(decoded) => {
+                let header = Header::of(decoded);
+                write!(f, " {} seq={} ack={} flags={} conn={} len={}", header.kind, header.seq, header.ack, header.flags, header.conn_id, header.len)?;
+                if header.stream_id != 0 {
+                    write!(f, " stream={}", header.stream_id)?;
+                }
+                if !header.options.is_empty() {
+                    write!(f, " [{}]", header.options.join(" "))?;
+                }
+                Ok(())
+            }
+            Err(e) => write!(f, " error: {} ({} bytes)", e, self.bytes.len()),
+        }
+    }
+}
+
+fn direction(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Inbound => "in",
+        Direction::Outbound => "out",
+    }
+}
+
+// 已知与未知类型的段共有的段头字段，以显示用的形式
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct Header {
+    kind: String,
+    seq: u64,
+    ack: u64,
+    flags: String,
+    conn_id: u32,
+    stream_id: u16,
+    options: Vec<String>,
+    len: usize,
+}
+
+impl Header {
+    fn of(decoded: &Decoded) -> Header {
+        match decoded {
+            Decoded::Known(segment) => Header {
+                kind: format!("{:?}", segment.segment_type()),
+                seq: segment.seq().get(),
+                ack: segment.ack().get(),
+                flags: flags(segment.flags().bits()),
+                conn_id: segment.conn_id(),
+                stream_id: segment.stream_id(),
+                options: segment.options().iter().map(option).collect(),
+                len: segment.data().len(),
+            },
+            Decoded::Unknown(raw) => Header {
+                kind: format!("Unknown({})", raw.type_id()),
+                seq: raw.seq().get(),
+                ack: raw.ack().get(),
+                flags: flags(raw.flag_bits()),
+                conn_id: raw.conn_id(),
+                stream_id: raw.stream_id(),
+                options: raw.options().iter().map(option).collect(),
+                len: raw.data().len(),
+            },
+        }
+    }
+}
+
+// 标志位的名字，以 `|` 连接；未定义的位以十六进制列出，没有标志时为 `-`
+fn flags(bits: u8) -> String {
+    const NAMES: [(SegmentFlags, &str); 8] = [
+        (SegmentFlags::ACK, "ACK"),
+        (SegmentFlags::SACK, "SACK"),
+        (SegmentFlags::SEALED, "SEALED"),
+        (SegmentFlags::AUTH, "AUTH"),
+        (SegmentFlags::TOKEN, "TOKEN"),
+        (SegmentFlags::CE, "CE"),
+        (SegmentFlags::ECE, "ECE"),
+        (SegmentFlags::UNRELIABLE, "UNRELIABLE"),
+    ];
+    match bits {
+        0 => "-".to_string(),
+        _ => NAMES.iter().filter(|(flag, _)| bits & flag.bits() != 0).map(|(_, name)| name.to_string()).collect::<Vec<_>>().join("|"),
+    }
+}
+
+fn option(option: SegmentOption) -> String {
+    match option {
+        SegmentOption::Timestamp { value, echo } => format!("ts={}/{}", value, echo),
+        SegmentOption::Mss(mss) => format!("mss={}", mss),
+        SegmentOption::SackPermitted => "sack-permitted".to_string(),
+        SegmentOption::Cwr => "cwr".to_string(),
+        SegmentOption::Error(code) => format!("error={}", code),
+        SegmentOption::AckNow => "ack-now".to_string(),
+        SegmentOption::Unordered => "unordered".to_string(),
+        SegmentOption::More => "more".to_string(),
+        SegmentOption::EarlyData => "early-data".to_string(),
+        SegmentOption::Params(params) => format!(
+            "params(ack_every={} keepalive={}ms sack={} early_data={} checksum={})",
+            params.ack_every, params.keepalive_interval.as_millis(), params.sack, params.early_data, params.checksum
+        ),
+        SegmentOption::Resync(Some(from)) => format!("resync={}", from),
+        SegmentOption::Resync(None) => "resync".to_string(),
+        SegmentOption::ResyncHead(head) => format!("resync-head={}", head),
+        SegmentOption::Instance(id) => format!("instance={:#010x}", id),
+        SegmentOption::Tag(tag) => format!("tag={}", tag),
+    }
+}
+
+// pcap 文件：文件头之后逐个记录，每个记录是一个链路层帧
+fn pcap(file: &[u8]) -> Result<Vec<Datagram>, InspectError> {
+    let magic: [u8; 4] = file[..4].try_into().expect("4 bytes");
+    let (little, nanos) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+        (0xa1b2_c3d4, _) => (true, false),
+        (0xa1b2_3c4d, _) => (true, true),
+        (_, 0xa1b2_c3d4) => (false, false),
+        (_, 0xa1b2_3c4d) => (false, true),
+        _ => return Err(InspectError::UnknownFormat),
+    };
+    let field = |at: usize| -> Option<u32> {
+        let bytes: [u8; 4] = file.get(at..at + 4)?.try_into().expect("4 bytes");
+        Some(if little { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    };
+    // 高位是 FCS 长度等附加信息
+    let link_type = field(20).ok_or(InspectError::Truncated { offset: 0 })? & 0xffff;
+    if !matches!(link_type, LINKTYPE_NULL | LINKTYPE_ETHERNET | LINKTYPE_RAW | LINKTYPE_LOOP | LINKTYPE_LINUX_SLL | LINKTYPE_IPV4 | LINKTYPE_IPV6 | LINKTYPE_LINUX_SLL2) {
+        return Err(InspectError::UnsupportedLinkType(link_type));
+    }
+    let mut datagrams = Vec::new();
+    let mut first = None;
+    let mut offset = 24;
+    while offset < file.len() {
+        let truncated = InspectError::Truncated { offset };
+        let (secs, frac, captured) = match (field(offset), field(offset + 4), field(offset + 8)) {
+            (Some(secs), Some(frac), Some(captured)) if file.len() >= offset + 16 => (secs, frac, captured as usize),
+            _ => return Err(truncated),
+        };
+        let frame = file.get(offset + 16..offset + 16 + captured).ok_or(truncated)?;
+        offset += 16 + captured;
+        let Some((from, to, payload)) = ip(link_payload(link_type, frame)) else {
+            continue;
+        };
+        let at = Duration::from_secs(u64::from(secs)) + if nanos { Duration::from_nanos(u64::from(frac)) } else { Duration::from_micros(u64::from(frac)) };
+        let first = *first.get_or_insert(at);
+        datagrams.push(Datagram { at: at.saturating_sub(first), from, to, inbound: false, payload: Bytes::copy_from_slice(payload) });
+    }
+    Ok(datagrams)
+}
+
+// 去掉链路层头，剩下 IP 包；不是 IP 的帧返回空
+fn link_payload(link_type: u32, frame: &[u8]) -> &[u8] {
+    let ethertype = |at: usize| frame.get(at..at + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+    let ip_after = |at: usize, ethertype: Option<u16>| match ethertype {
+        Some(ETHERTYPE_IPV4 | ETHERTYPE_IPV6) => frame.get(at..).unwrap_or_default(),
+        _ => &[],
+    };
+    match link_type {
+        LINKTYPE_ETHERNET => match ethertype(12) {
+            Some(ETHERTYPE_VLAN) => ip_after(18, ethertype(16)),
+            other => ip_after(14, other),
+        },
+        LINKTYPE_LINUX_SLL => ip_after(16, ethertype(14)),
+        LINKTYPE_LINUX_SLL2 => ip_after(20, ethertype(0)),
+        // 主机字节序的地址族，IP 版本由包本身区分
+        LINKTYPE_NULL | LINKTYPE_LOOP => frame.get(4..).unwrap_or_default(),
+        _ => frame,
+    }
+}
+
+// IP 包中的 UDP 数据报：(源, 目的, 负载)；不是 UDP、IP 分片或头部截断时返回 None。负载截断到抓取的长度
+fn ip(packet: &[u8]) -> Option<(SocketAddr, SocketAddr, &[u8])> {
+    let (src, dst, udp) = match packet.first()? >> 4 {
+        4 => {
+            let header_len = usize::from(packet[0] & 0x0f) * 4;
+            let fragment = u16::from_be_bytes([*packet.get(6)?, *packet.get(7)?]);
+            // MF 位或非零的片偏移
+            if *packet.get(9)? != 17 || fragment & 0x3fff != 0 || header_len < 20 {
+                return None;
+            }
+            let total = usize::from(u16::from_be_bytes([packet[2], packet[3]]));
+            let src = IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(packet.get(12..16)?).ok()?));
+            let dst = IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(packet.get(16..20)?).ok()?));
+            (src, dst, packet.get(header_len..total.min(packet.len()))?)
+        }
+        6 => {
+            if *packet.get(6)? != 17 {
+                return None;
+            }
+            let src = IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(packet.get(8..24)?).ok()?));
+            let dst = IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(packet.get(24..40)?).ok()?));
+            (src, dst, &packet[40..])
+        }
+        _ => return None,
+    };
+    let port = |at: usize| udp.get(at..at + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+    let (src_port, dst_port, len) = (port(0)?, port(2)?, usize::from(port(4)?));
+    let payload = udp.get(8..len.clamp(8, udp.len()))?;
+    Some((SocketAddr::new(src, src_port), SocketAddr::new(dst, dst_port), payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture;
+    use crate::checksum::ChecksumAlgorithm;
+    use crate::options::Options;
+    use crate::replay::Recorded;
+    use crate::segment::SegmentType;
+    use std::time::UNIX_EPOCH;
+
+    fn client() -> SocketAddr {
+        "10.0.0.2:5000".parse().unwrap()
+    }
+
+    fn server() -> SocketAddr {
+        "10.0.0.1:7000".parse().unwrap()
+    }
+
+    // 一次握手、一个数据段与它的确认、一个打包了两个段的数据报、一个新版本的未知类型段与一个损坏的段
+    fn exchange() -> Vec<(SocketAddr, SocketAddr, Vec<u8>)> {
+        let encode = |segment: &Segment| segment.encode().unwrap().to_vec();
+        let options = Options::new().with(SegmentOption::Mss(1200)).unwrap().with(SegmentOption::SackPermitted).unwrap();
+        let syn = Segment::builder(SegmentType::Syn).data_seq(100).options(options).build().unwrap();
+        let syn_ack = Segment::builder(SegmentType::Syn).data_seq(900).ack(101).conn_id(7).build().unwrap();
+        let ack = Segment::builder(SegmentType::Ack).data_seq(101).ack(901).window(64).conn_id(7).build().unwrap();
+        let data = Segment::builder(SegmentType::Data).data_seq(101).ack(901).conn_id(7).payload(&b"hello"[..]).build().unwrap();
+        let data_ack = Segment::builder(SegmentType::Ack).data_seq(901).ack(102).window(63).conn_id(7).build().unwrap();
+        let mut ping = Segment::ping(42);
+        ping.set_conn_id(7);
+        let fin = Segment::builder(SegmentType::Fin).data_seq(102).conn_id(7).build().unwrap();
+        // 不计算校验和的段改了类型字节仍然完好
+        let mut unknown = encode(&Segment::builder(SegmentType::Data).data_seq(103).conn_id(7).checksum(ChecksumAlgorithm::NoChecksum).payload(&b"v2"[..]).build().unwrap());
+        unknown[4] = 200;
+        let mut corrupt = encode(&data);
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xff;
+        vec![
+            (client(), server(), encode(&syn)),
+            (server(), client(), encode(&syn_ack)),
+            (client(), server(), encode(&ack)),
+            (client(), server(), encode(&data)),
+            (server(), client(), encode(&data_ack)),
+            (client(), server(), [encode(&ping), encode(&fin)].concat()),
+            (client(), server(), unknown),
+            (client(), server(), corrupt),
+            // 其他端口的流量被过滤掉
+            ("10.0.0.3:6000".parse().unwrap(), "10.0.0.1:8000".parse().unwrap(), encode(&ping)),
+        ]
+    }
+
+    fn pcap_file(datagrams: &[(SocketAddr, SocketAddr, Vec<u8>)]) -> Vec<u8> {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut file = capture::file_header().to_vec();
+        for (i, (from, to, datagram)) in datagrams.iter().enumerate() {
+            file.extend_from_slice(&capture::record(start + Duration::from_micros(1500 * i as u64), *from, *to, datagram));
+        }
+        file
+    }
+
+    fn summary(entries: &[Entry]) -> String {
+        entries.iter().map(|entry| format!("{}\n", entry)).collect()
+    }
+
+    #[test]
+    fn test_pcap_summary_snapshot() {
+        let entries = inspect(&pcap_file(&exchange()), Some(7000)).unwrap();
+        assert_eq!(
+            summary(&entries),
+            "   0.000000 10.0.0.2:5000 > 10.0.0.1:7000 in  Syn seq=100 ack=0 flags=- conn=0 len=0 [mss=1200 sack-permitted]\n\
+             \x20  0.001500 10.0.0.1:7000 > 10.0.0.2:5000 out Syn seq=900 ack=101 flags=ACK conn=7 len=0\n\
+             \x20  0.003000 10.0.0.2:5000 > 10.0.0.1:7000 in  Ack seq=101 ack=901 flags=- conn=7 len=0\n\
+             \x20  0.004500 10.0.0.2:5000 > 10.0.0.1:7000 in  Data seq=101 ack=901 flags=ACK conn=7 len=5\n\
+             \x20  0.006000 10.0.0.1:7000 > 10.0.0.2:5000 out Ack seq=901 ack=102 flags=- conn=7 len=0\n\
+             \x20  0.007500 10.0.0.2:5000 > 10.0.0.1:7000 in  Ping seq=0 ack=0 flags=- conn=7 len=8\n\
+             \x20  0.007500 10.0.0.2:5000 > 10.0.0.1:7000 in  Fin seq=102 ack=0 flags=- conn=7 len=0\n\
+             \x20  0.009000 10.0.0.2:5000 > 10.0.0.1:7000 in  Unknown(200) seq=103 ack=0 flags=- conn=7 len=2\n\
+             \x20  0.010500 10.0.0.2:5000 > 10.0.0.1:7000 in  error: checksum mismatch: declared 0x9f2fd7e2 but computed 0x325284b3 (43 bytes)\n"
+        );
+    }
+
+    #[test]
+    fn test_record_file_is_inbound() {
+        let mut file = replay::file_header().to_vec();
+        for (i, (from, to, datagram)) in exchange().into_iter().filter(|(_, to, _)| *to == server()).take(3).enumerate() {
+            Recorded { delay: Duration::from_millis(10 * i as u64), from, to, datagram: datagram.into() }.encode(&mut file);
+        }
+        let entries = inspect(&file, None).unwrap();
+        assert_eq!(
+            summary(&entries),
+            "   0.000000 10.0.0.2:5000 > 10.0.0.1:7000 in  Syn seq=100 ack=0 flags=- conn=0 len=0 [mss=1200 sack-permitted]\n\
+             \x20  0.010000 10.0.0.2:5000 > 10.0.0.1:7000 in  Ack seq=101 ack=901 flags=- conn=7 len=0\n\
+             \x20  0.030000 10.0.0.2:5000 > 10.0.0.1:7000 in  Data seq=101 ack=901 flags=ACK conn=7 len=5\n"
+        );
+        file.truncate(file.len() - 1);
+        assert!(matches!(inspect(&file, None), Err(InspectError::Record(RecordError::Truncated { .. }))));
+    }
+
+    #[test]
+    fn test_tcpdump_ethernet_capture() {
+        // tcpdump 的默认格式：大端序、微秒时间戳、Ethernet 帧；ARP 与 IPv4 分片被跳过
+        let mut file = vec![0xa1, 0xb2, 0xc3, 0xd4, 0, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0, 0, 0, 1];
+        let mut frame = |micros: u32, ethertype: u16, packet: &[u8]| {
+            let frame = [&[0u8; 12][..], &ethertype.to_be_bytes(), packet].concat();
+            for field in [1_700_000_000, micros, frame.len() as u32, frame.len() as u32] {
+                file.extend_from_slice(&u32::to_be_bytes(field));
+            }
+            file.extend_from_slice(&frame);
+        };
+        let ping = Segment::ping(9).encode().unwrap();
+        let v6: (SocketAddr, SocketAddr) = ("[fd00::2]:5000".parse().unwrap(), "[fd00::1]:7000".parse().unwrap());
+        frame(250, 0x0806, &[0; 28]);
+        frame(500, ETHERTYPE_IPV4, &capture::packet(client(), server(), &ping));
+        let mut fragment = capture::packet(client(), server(), &ping);
+        fragment[6] = 0x20;
+        frame(600, ETHERTYPE_IPV4, &fragment);
+        frame(750, ETHERTYPE_IPV6, &capture::packet(v6.1, v6.0, &ping));
+        let entries = inspect(&file, None).unwrap();
+        assert_eq!(
+            summary(&entries),
+            "   0.000000 10.0.0.2:5000 > 10.0.0.1:7000 -   Ping seq=0 ack=0 flags=- conn=0 len=8\n\
+             \x20  0.000250 [fd00::1]:7000 > [fd00::2]:5000 -   Ping seq=0 ack=0 flags=- conn=0 len=8\n"
+        );
+        assert!(entries[0].hexdump().starts_with("\n00000000  00 00 00 2e 03 00"), "{}", entries[0].hexdump());
+
+        file[20..24].copy_from_slice(&147u32.to_be_bytes());
+        assert_eq!(inspect(&file, None), Err(InspectError::UnsupportedLinkType(147)));
+        assert_eq!(inspect(&[0x0a, 0x0d, 0x0d, 0x0a, 0, 0], None), Err(InspectError::Pcapng));
+        assert_eq!(inspect(b"GIF89a", None), Err(InspectError::UnknownFormat));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_lines() {
+        let entries = inspect(&pcap_file(&exchange()), Some(7000)).unwrap();
+        let first: serde_json::Value = serde_json::from_str(&entries[0].to_json(false)).unwrap();
+        assert_eq!(first["kind"], "Syn");
+        assert_eq!((&first["from"], &first["direction"], &first["seq"]), (&serde_json::json!("10.0.0.2:5000"), &serde_json::json!("in"), &serde_json::json!(100)));
+        assert_eq!(first["options"], serde_json::json!(["mss=1200", "sack-permitted"]));
+        assert!(first.get("bytes").is_none() && first.get("error").is_none());
+
+        let last: serde_json::Value = serde_json::from_str(&entries.last().unwrap().to_json(true)).unwrap();
+        assert!(last["error"].as_str().unwrap().starts_with("checksum mismatch"));
+        assert_eq!(last["bytes"].as_str().unwrap().len(), entries.last().unwrap().bytes.len() * 2);
+        assert!(last.get("kind").is_none());
+    }
+}
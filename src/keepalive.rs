@@ -0,0 +1,307 @@
+//! 保活与对端失联检测
+//! 两件事分开计时。对端是否还活着看接收：超过 `keepalive_interval` 没有收到任何段时发送携带新 nonce 的存活探测；
+//! 每收到一个段（数据、确认，不只是 Pong）都重置这个计时与失败计数。连续 `keepalive_failures` 个存活探测都没有
+//! 等到任何段时判定对端失联，连接应以 `LinkError::KeepaliveTimeout` 终止挂起的操作（见 `Sender::abort`）。
+//! 路径上的 NAT 映射是否保持看发送：超过 `path_keepalive_interval` 没有发出任何段时发送一个路径探测，
+//! 持续发送数据的连接因此不需要它；路径探测不计入失败计数，失联只由接收计时判定。两种探测分别计数。
+//! `Keepalive` 本身不做 IO、时间由调用方注入；`drive` 用 tokio 定时器驱动它，可在测试中用
+//! `tokio::time::pause` 模拟时间流逝。
+
+use crate::config::LinkConfig;
+use crate::error::LinkError;
+use crate::segment::{Segment, SegmentType};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// 保活定时器到期时需要执行的动作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeepaliveAction {
+    Ping(Segment),  // 发送该探测段
+    Dead,           // 对端失联
+}
+
+/// 保活状态机
+#[derive(Debug)]
+pub struct Keepalive {
+    interval: Duration,
+    max_failures: u32,
+    deadline: Instant,      // 下一次存活探测（或判定失联）的时间
+    path_interval: Duration,    // 为零时不发送路径探测
+    path_deadline: Instant,     // 下一次路径探测的时间：上次发出段之后一个 `path_interval`
+    unanswered: u32,        // 自上次收到段以来发出的存活探测数
+    next_nonce: u64,
+    last_nonce: Option<u64>,    // 最近一次探测的 nonce
+    liveness_pings: u64,
+    path_pings: u64,
+    dead: bool,
+}
+
+impl Keepalive {
+    pub fn new(config: &LinkConfig, now: Instant) -> Self {
+        Self {
+            interval: config.keepalive_interval,
+            max_failures: config.keepalive_failures,
+            deadline: now + config.keepalive_interval,
+            path_interval: config.path_keepalive_interval,
+            path_deadline: now + config.path_keepalive_interval,
+            unanswered: 0,
+            next_nonce: 1,
+            last_nonce: None,
+            liveness_pings: 0,
+            path_pings: 0,
+            dead: false,
+        }
+    }
+
+    /// 发出了段：推迟路径探测
+    pub fn on_sent(&mut self, now: Instant) {
+        self.path_deadline = now + self.path_interval;
+    }
+
+    /// 收到了对端的消息（不必是完整的段，例如直接解析的确认）：重置空闲计时与失败计数
+    pub fn on_activity(&mut self, now: Instant) {
+        if self.dead {
+            return;
+        }
+        self.deadline = now + self.interval;
+        self.unanswered = 0;
+    }
+
+    /// 收到任意段：重置空闲计时与失败计数；返回需要回应的 Pong（收到的是 Ping 时）
+    pub fn on_segment(&mut self, segment: &Segment, now: Instant) -> Option<Segment> {
+        if self.dead {
+            return None;
+        }
+        self.on_activity(now);
+        match segment.segment_type() {
+            SegmentType::Ping => segment.nonce().map(Segment::pong),
+            _ => None,
+        }
+    }
+
+    /// 定时器检查：接收空闲超时则发送存活探测，存活探测次数耗尽则判定失联（只报告一次）；
+    /// 否则发送空闲超时时发送路径探测。两者同时到期时只发一个存活探测，它同样保持了路径
+    pub fn poll(&mut self, now: Instant) -> Option<KeepaliveAction> {
+        if self.dead {
+            return None;
+        }
+        if now >= self.deadline {
+            if self.unanswered >= self.max_failures {
+                self.dead = true;
+                return Some(KeepaliveAction::Dead);
+            }
+            self.unanswered += 1;
+            self.liveness_pings += 1;
+            self.deadline = now + self.interval;
+            return Some(self.ping(now));
+        }
+        if !self.path_interval.is_zero() && now >= self.path_deadline {
+            self.path_pings += 1;
+            return Some(self.ping(now));
+        }
+        None
+    }
+
+    fn ping(&mut self, now: Instant) -> KeepaliveAction {
+        let nonce = self.next_nonce;
+        self.next_nonce = self.next_nonce.wrapping_add(1);
+        self.last_nonce = Some(nonce);
+        self.on_sent(now);
+        KeepaliveAction::Ping(Segment::ping(nonce))
+    }
+
+    /// 调整存活探测的间隔，从现在起按新的间隔计时
+    pub fn set_interval(&mut self, interval: Duration, now: Instant) {
+        self.interval = interval;
+        self.deadline = now + interval;
+    }
+
+    /// 下一次需要调用 `poll` 的时间；已判定失联时为 None
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let path = (!self.path_interval.is_zero()).then_some(self.path_deadline);
+        (!self.dead).then(|| path.map_or(self.deadline, |path| path.min(self.deadline)))
+    }
+
+    /// 自上次收到段以来未得到回应的存活探测数
+    pub fn unanswered(&self) -> u32 {
+        self.unanswered
+    }
+
+    /// 对端沉默而发出的存活探测总数
+    pub fn liveness_pings(&self) -> u64 {
+        self.liveness_pings
+    }
+
+    /// 本端沉默而发出的路径探测总数
+    pub fn path_pings(&self) -> u64 {
+        self.path_pings
+    }
+
+    pub fn last_nonce(&self) -> Option<u64> {
+        self.last_nonce
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.dead
+    }
+
+    /// 失联时应交给挂起操作的错误
+    pub fn error(&self) -> LinkError {
+        LinkError::KeepaliveTimeout { unanswered: self.max_failures }
+    }
+}
+
+/// 用 tokio 定时器驱动保活：`incoming` 中的每个段都会重置接收计时（收到 Ping 时经 `outgoing` 回应 Pong，
+/// 同时推迟路径探测），需要探测时经 `outgoing` 发出 Ping。`incoming` 关闭时正常返回，判定失联时返回 `KeepaliveTimeout`。
+pub async fn drive(
+    mut keepalive: Keepalive,
+    mut incoming: mpsc::Receiver<Segment>,
+    outgoing: mpsc::Sender<Segment>,
+) -> Result<(), LinkError> {
+    loop {
+        let Some(deadline) = keepalive.next_deadline() else {
+            return Err(keepalive.error());
+        };
+        tokio::select! {
+            segment = incoming.recv() => {
+                let Some(segment) = segment else {
+                    return Ok(());
+                };
+                let now = tokio::time::Instant::now().into_std();
+                if let Some(pong) = keepalive.on_segment(&segment, now) {
+                    // 发送端关闭说明连接已在拆除，不再回应
+                    let _ = outgoing.send(pong).await;
+                    keepalive.on_sent(now);
+                }
+            }
+            _ = tokio::time::sleep_until(deadline.into()) => {
+                let now = tokio::time::Instant::now().into_std();
+                match keepalive.poll(now) {
+                    Some(KeepaliveAction::Ping(ping)) => {
+                        let _ = outgoing.send(ping).await;
+                    }
+                    Some(KeepaliveAction::Dead) => return Err(keepalive.error()),
+                    None => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sender::Sender;
+    use crate::seq::SeqNum;
+    use bytes::Bytes;
+
+    const INTERVAL: Duration = Duration::from_secs(15);
+
+    fn spawn_drive() -> (mpsc::Sender<Segment>, mpsc::Receiver<Segment>, tokio::task::JoinHandle<Result<(), LinkError>>) {
+        let keepalive = Keepalive::new(&LinkConfig::default(), tokio::time::Instant::now().into_std());
+        let (incoming_tx, incoming_rx) = mpsc::channel(16);
+        let (outgoing_tx, outgoing_rx) = mpsc::channel(16);
+        let task = tokio::spawn(drive(keepalive, incoming_rx, outgoing_tx));
+        (incoming_tx, outgoing_rx, task)
+    }
+
+    #[test]
+    fn test_any_segment_resets_idle_clock() {
+        let t0 = Instant::now();
+        // 只看接收计时
+        let mut keepalive = Keepalive::new(&LinkConfig { path_keepalive_interval: Duration::ZERO, ..LinkConfig::default() }, t0);
+        assert!(keepalive.poll(t0 + INTERVAL - Duration::from_millis(1)).is_none());
+
+        // 数据段同样重置计时
+        keepalive.on_segment(&Segment::new(SegmentType::Data, 1, vec![1]), t0 + Duration::from_secs(10));
+        assert!(keepalive.poll(t0 + INTERVAL).is_none());
+        assert!(matches!(keepalive.poll(t0 + Duration::from_secs(25)), Some(KeepaliveAction::Ping(_))));
+
+        // 对端的 Ping 得到同 nonce 的 Pong
+        let pong = keepalive.on_segment(&Segment::ping(42), t0 + Duration::from_secs(26)).unwrap();
+        assert_eq!((pong.segment_type(), pong.nonce()), (SegmentType::Pong, Some(42)));
+        assert_eq!(keepalive.unanswered(), 0);
+    }
+
+    #[test]
+    fn test_path_pings_follow_outbound_silence() {
+        let t0 = Instant::now();
+        let config = LinkConfig { keepalive_interval: Duration::from_secs(30), path_keepalive_interval: Duration::from_secs(10), ..LinkConfig::default() };
+        let mut keepalive = Keepalive::new(&config, t0);
+        let at = |secs| t0 + Duration::from_secs(secs);
+
+        // 不断发出数据、收到确认：两种探测都不需要
+        for secs in 1..=60 {
+            keepalive.on_sent(at(secs));
+            keepalive.on_segment(&Segment::new(SegmentType::Ack, 1, vec![]), at(secs));
+            assert!(keepalive.poll(at(secs)).is_none());
+        }
+        assert_eq!(keepalive.next_deadline(), Some(at(70)));
+
+        // 本端沉默、对端仍在发来数据：只有路径探测，不计入失败计数
+        for secs in [70, 80, 90] {
+            keepalive.on_segment(&Segment::new(SegmentType::Data, 1, vec![1]), at(secs));
+            assert!(matches!(keepalive.poll(at(secs)), Some(KeepaliveAction::Ping(_))));
+        }
+        assert_eq!((keepalive.path_pings(), keepalive.liveness_pings(), keepalive.unanswered()), (3, 0, 0));
+
+        // 对端沉默、本端仍在发送：存活探测照常发出，耗尽后判定失联，路径探测一个也没有
+        for secs in 91..=300 {
+            keepalive.on_sent(at(secs));
+            if let Some(KeepaliveAction::Dead) = keepalive.poll(at(secs)) {
+                assert_eq!(secs, 90 + 30 * 4);
+                break;
+            }
+        }
+        assert!(keepalive.is_dead());
+        assert_eq!((keepalive.path_pings(), keepalive.liveness_pings()), (3, 3));
+
+        // 路径探测可以关闭
+        let config = LinkConfig { path_keepalive_interval: Duration::ZERO, ..config };
+        let mut keepalive = Keepalive::new(&config, t0);
+        assert_eq!(keepalive.next_deadline(), Some(at(30)));
+        assert!(keepalive.poll(at(20)).is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_peer_recovers_after_missed_pings() {
+        let start = tokio::time::Instant::now();
+        let (incoming, mut outgoing, task) = spawn_drive();
+
+        // 前两个探测没有回应，各相隔一个间隔且 nonce 不同
+        let first = outgoing.recv().await.unwrap();
+        assert_eq!(start.elapsed(), INTERVAL);
+        let second = outgoing.recv().await.unwrap();
+        assert_eq!(start.elapsed(), INTERVAL * 2);
+        assert_ne!(first.nonce(), second.nonce());
+
+        // 第三个探测得到回应，失败计数清零，之后又要等一个完整间隔才再次探测
+        let third = outgoing.recv().await.unwrap();
+        incoming.send(Segment::pong(third.nonce().unwrap())).await.unwrap();
+        let fourth = outgoing.recv().await.unwrap();
+        assert_eq!(fourth.segment_type(), SegmentType::Ping);
+        assert_eq!(start.elapsed(), INTERVAL * 4);
+        assert!(!task.is_finished());
+
+        drop(incoming);
+        assert_eq!(task.await.unwrap(), Ok(()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dead_peer_fails_pending_sends() {
+        let start = tokio::time::Instant::now();
+        let (_incoming, mut outgoing, task) = spawn_drive();
+
+        // 三个探测都没有回应：再过一个间隔后判定失联
+        for _ in 0..3 {
+            assert_eq!(outgoing.recv().await.unwrap().segment_type(), SegmentType::Ping);
+        }
+        let error = task.await.unwrap().unwrap_err();
+        assert_eq!(error, LinkError::KeepaliveTimeout { unanswered: 3 });
+        assert_eq!(start.elapsed(), INTERVAL * 4);
+
+        let mut sender = Sender::new(SeqNum::new(1), &LinkConfig::default());
+        sender.abort(error.clone());
+        assert_eq!(sender.send(Bytes::from_static(b"x"), Instant::now()), Err(error));
+    }
+}
@@ -1 +1,147 @@
-pub mod segment;
\ No newline at end of file
+//! 不可靠网络之上的可靠传输层。`std` 特性（默认）提供完整的连接、监听器与服务器；关闭时 crate 以 `#![no_std]` 构建，
+//! `alloc` 特性下只保留线上格式的编解码：段、段类型与标志位、选项、握手参数、SACK 与确认帧、序列号与校验算法，供嵌入式的对端复用。
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod ack;
+#[cfg(feature = "alloc")]
+pub mod ack_frame;
+#[cfg(feature = "std")]
+pub mod acl;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod budget;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "std")]
+pub mod capture;
+#[cfg(feature = "alloc")]
+pub mod checksum;
+#[cfg(feature = "std")]
+pub mod cli;
+#[cfg(feature = "tokio")]
+pub mod client;
+#[cfg(feature = "std")]
+pub mod client_endpoint;
+#[cfg(feature = "alloc")]
+pub mod close_codes;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod conformance;
+#[cfg(feature = "std")]
+pub mod connection;
+#[cfg(feature = "std")]
+pub mod congestion;
+#[cfg(feature = "std")]
+pub mod cookie;
+#[cfg(feature = "crypto")]
+pub mod crypto;
+#[cfg(feature = "std")]
+pub mod diagnostics;
+#[cfg(feature = "std")]
+pub mod endpoint;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod failover;
+#[cfg(feature = "std")]
+pub mod fault;
+#[cfg(feature = "std")]
+pub mod gaps;
+#[cfg(feature = "std")]
+pub mod inspect;
+#[cfg(feature = "std")]
+pub mod keepalive;
+#[cfg(feature = "std")]
+pub mod listener;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "tokio")]
+pub mod multicast;
+#[cfg(feature = "std")]
+pub mod observer;
+#[cfg(feature = "alloc")]
+pub mod options;
+#[cfg(feature = "std")]
+pub mod pacing;
+#[cfg(feature = "alloc")]
+pub mod params;
+#[cfg(feature = "std")]
+pub mod ping;
+#[cfg(feature = "std")]
+pub mod pmtu;
+#[cfg(feature = "std")]
+pub mod pool;
+#[cfg(feature = "std")]
+pub mod rate;
+#[cfg(feature = "std")]
+pub mod receiver;
+#[cfg(feature = "std")]
+pub mod recv_buffer;
+#[cfg(feature = "std")]
+pub mod reflect;
+#[cfg(feature = "std")]
+pub mod rejects;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub mod retransmit;
+#[cfg(feature = "std")]
+pub mod retry;
+#[cfg(feature = "std")]
+pub mod ring;
+#[cfg(feature = "std")]
+pub mod rotation;
+#[cfg(feature = "std")]
+pub mod rtt;
+#[cfg(feature = "alloc")]
+pub mod sack;
+#[cfg(feature = "std")]
+pub mod schedule;
+#[cfg(feature = "alloc")]
+pub mod segment;
+#[cfg(feature = "std")]
+pub mod sender;
+#[cfg(feature = "std")]
+pub mod server;
+#[cfg(feature = "std")]
+pub mod session;
+#[cfg(feature = "std")]
+pub mod socket;
+#[cfg(feature = "std")]
+pub mod split;
+#[cfg(feature = "alloc")]
+pub mod seq;
+#[cfg(feature = "std")]
+pub mod state;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod stream;
+#[cfg(feature = "tokio")]
+pub mod tcp;
+#[cfg(feature = "std")]
+pub mod timer;
+#[cfg(feature = "std")]
+pub mod tombstone;
+#[cfg(feature = "std")]
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod transport;
+#[cfg(feature = "std")]
+pub mod transfer;
+#[cfg(feature = "std")]
+pub mod tuning;
+#[cfg(feature = "std")]
+pub mod unreliable;
+#[cfg(feature = "std")]
+pub mod util;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "std")]
+pub mod watermarks;
@@ -0,0 +1,1596 @@
+//! 监听器
+//! `Listener` 独占 UDP 套接字。分发任务是唯一调用 `recv_from` 的任务，按来源地址把数据报路由到对应的连接：
+//! 已建立连接的数据报不解码，原样经有界队列交给该连接的驱动任务，队列已满时丢弃，不会阻塞接收；
+//! 所有连接发出的数据报经同一个队列交给唯一的发送任务，它把发往同一对端的数据报合并到 `mss` 以内再发出（见 `batch` 模块）。
+//! 未知地址的 SYN 建立半开握手并回应 SYN-ACK，握手完成（收到确认或数据）后生成新的 `Connection`
+//! 交给 `accept`；之后来自该地址的数据报都交给这个连接处理。半开握手数受 `LinkConfig::backlog` 限制，
+//! 超过 `handshake_timeout` 仍未完成的握手会被清理。`LinkConfig::syn_cookies` 允许时（总是，或 backlog 已满时）
+//! 改以无状态的 cookie 回应 SYN，不登记任何状态，完成握手的段通过校验后才建立连接（见 `cookie` 模块）。
+//! 半开握手数达到 `LinkConfig::retry_threshold` 时先以 Retry 验证对端地址，带回有效令牌的 SYN 才继续（见 `retry` 模块）。未知地址发来的非 SYN 段以 Rst 回应，无法解析的数据报丢弃并计数；
+//! 已建立的连接数达到 `LinkConfig::max_connections`、或来自同一 IP 的达到 `per_ip_limit` 时，新的 SYN 以携带
+//! `CloseCode::SERVER_BUSY` 的 Rst 回应，不登记任何状态。所有接收套接字共用一份计数，握手完成时检查与登记在同一把锁下进行，
+//! 此时才超出上限的握手（SYN 之后其他握手抢先完成，或以 cookie 完成）同样以它拒绝；连接的 IP 按建立时的地址计，迁移后不变。
+//! 所有连接与墓碑的内存占用计入同一个 `MemoryBudget`（见 `budget` 模块）：全局额度放不下又一条连接时，SYN 与完成握手的段
+//! 同样以 `SERVER_BUSY` 拒绝，已建立的连接收小通告的窗口；`memory_consumers` 列出占用最多的连接。
+//! 超出 `LinkConfig::recv_buffer` 而被截断的数据报在路由前识别，单独计数后丢弃。
+//! 分发任务每次被唤醒后以 `try_recv_from` 接着取走已经排队的数据报，至多凑满 `LinkConfig::recv_batch` 个，
+//! 每个都同样经过截断检查与访问控制；每次取走的个数记入 `MetricsSnapshot::drained`。
+//! 已建立的连接超过 `idle_timeout` 没有收到任何段时由它自己的驱动任务判定空闲并以 Rst 终止，
+//! 驱动任务退出时（包括连接句柄被丢弃）把连接交还给分发任务移出连接表，不需要扫描整张表。
+//! 同一地址上可以有多条连接（例如来自同一个 `ClientEndpoint`，见 `client_endpoint` 模块）：它们以段头的连接 ID 区分，
+//! 还没有 ID 的握手段按 SYN 的 ISN 找到对应的半开握手或连接。
+//! 监听器发出的握手与回应段都带有它的实例 ID，对端原样发回的这类段在分发前丢弃并计入 `MetricsSnapshot::reflected`（见 `reflect` 模块）。
+//!
+//! 每个连接在 SYN-ACK 中分配一个连接 ID。陌生地址与墓碑的查找只读段头（`segment::parse_header`），不做完整解码；
+//! 陌生地址发来的数据报若第一个段携带已知的连接 ID，
+//! 且序列号通过该连接的校验（`Shared::accepts_migration`），视为对端可能迁移了（例如 NAT 重新映射）。
+//! 知道连接 ID 的路径外攻击者同样能做到这一点，因此新地址先要通过验证：监听器向它发出携带 8 字节随机令牌的
+//! PathChallenge，`path_validation_timeout` 内从该地址收到原样回送令牌的 PathResponse 才提交迁移。验证期间来自新地址的段
+//! 照常交给连接，连接发出的段仍然发往已验证的旧地址；超时未得到回应时放弃新地址并计入 `ListenerStats::unvalidated`，
+//! 之后再从那里到达的段重新开始验证。每条连接同时至多验证 `MAX_PATH_CANDIDATES` 个新地址。
+//! 提交时连接表在分发任务内一步改为以新地址索引，连接之后的段发往新地址；旧地址在 `MIGRATION_GRACE`
+//! 内仍被接受（迁移前已在途的段），但不会把连接迁回去。`LinkConfig::rotate_conn_id_on_migration` 打开时提交迁移的同时
+//! 轮换连接的 ID，新地址上的流量与旧地址上的不再携带同一个 ID。
+//!
+//! 连接的 ID 轮换后（见 `rotation` 模块）连接经 `Notice::Rotated` 通知分发任务：连接表以新的 ID 登记它，旧 ID 在
+//! `LinkConfig::conn_id_grace` 内仍能找到它（迁移中乱序的段），之后移出连接表。新的 ID 已被另一条连接占用时不登记，
+//! 这条连接仍可按地址找到，只是迁移时无法按 ID 找回。
+//!
+//! `LinkConfig::workers` 大于 1 时以 SO_REUSEPORT 绑定多个接收套接字（见 `socket` 模块），每个套接字有自己的
+//! 分发任务、发送任务与连接表，完成握手的连接汇入同一个 accept 队列。连接表按套接字分片而不是共享：内核按四元组
+//! 把对端固定在一个套接字上，分片后每条数据报只经过一个任务、不需要跨核加锁，代价是分片之间互不知道对方的连接——
+//! 迁移后的新地址可能散列到另一个套接字，在那里是陌生地址，以 Rst 回应，多套接字时连接迁移不可用。
+//! 每张连接表只由它的分发任务读写，路由数据报时的查找不经过任何锁；连接的登记与移除（握手完成、驱动任务退出、
+//! 访问控制列表驱逐）同样在这个任务内进行，路由看到的总是一致的连接表。`worker_stats` 按分片读出各自的统计；
+//! `benches/demux.rs` 比较 8 个线程在 1 万条连接上路由时，共用一把锁的表与各自独占的分片的吞吐。
+//!
+//! 经 FIN 交换正常结束的连接移出连接表后留下墓碑（见 `tombstone` 模块）：`drain_timeout` 内携带它的连接 ID、
+//! 来自它的对端地址的段不再路由，重传的 FIN 以最后的确认回应，其余段被丢弃；墓碑期间它的连接 ID 不会重新分配。
+//! 导出了状态的连接（见 `session` 模块）同样留下墓碑，但不回应任何段，交接期间对端不会收到 Rst；
+//! `Listener::adopt` 在另一个进程的监听器中接续它，连接表按导出时的地址与 ID 登记。
+//!
+//! `bind_tcp` 在 TCP 上监听（见 `tcp` 模块）：每条接受的流以对端地址区分，路由、握手与连接表与 UDP 完全相同。
+//!
+//! SYN 中客户端提出的连接参数（见 `params` 模块）与本端的合并，每项取更保守的一个，结果随 SYN-ACK 告知客户端。
+//! `set_param_policy` 登记的 `ParamPolicy` 先审查客户端提出的参数：它可以收紧参数，也可以拒绝握手，
+//! 被拒绝的 SYN（以及以 cookie 完成的确认）以携带 `CloseCode::PARAMETER_ERROR` 的 Rst 回应，不登记任何状态。
+//!
+//! `LinkConfig::accept_early_data` 打开时，与 SYN 同在一个数据报中的 0-RTT 数据段（见 `Connection::connect_with_data`）
+//! 直接完成半开握手并随新连接交付；关闭时、配置了密钥时，或 SYN 没有登记半开握手时，它被忽略而不回应。
+//!
+//! 配置了预共享密钥时（`crypto` 特性）SYN 必须通过认证：未签名的以 Rst 拒绝，标签错误的丢弃并计入 `decrypt`；
+//! 回应的 SYN-ACK 携带本端的 nonce，半开握手只由签名的确认完成，先到的数据段换来重发的 SYN-ACK。
+//!
+//! `LinkConfig::allow`/`deny` 组成的访问控制列表（见 `acl` 模块）在来自新来源的数据报到达时最先检查，早于墓碑、迁移与解码：
+//! 不被允许的来源静默丢弃并计入 `ListenerStats::denied`，不登记状态也不回应。`set_acl` 经 watch 通道把新的规则交给
+//! 所有分发任务：不再被允许的半开握手被放弃，`Acl::evict` 时不再被允许的已建立连接以 `LinkError::Denied` 中止。
+//!
+//! `stop_accepting` 之后新的 SYN 与此时才完成的握手都以 Rst 拒绝，已建立的连接照常路由；
+//! 待 accept 队列中已有的连接仍可取出，取完后 `accept` 返回 `Closed`。
+
+use crate::acl::Acl;
+use crate::budget::{Charge, MemoryBudget, MemoryConsumer, CONNECTION_OVERHEAD};
+use crate::batch::{self, Batcher};
+use crate::capture::Tap;
+use crate::checksum::{self, ChecksumAlgorithm};
+use crate::close_codes::CloseCode;
+use crate::config::LinkConfig;
+use crate::connection::{self, Connection, Notice, Outgoing, Outlet, Reaper, Shared};
+use crate::cookie::{CookieJar, SynCookies};
+#[cfg(feature = "crypto")]
+use crate::crypto::{HandshakeAuth, HandshakeNonce};
+use crate::endpoint::{self, Handshake};
+use crate::error::{self, LinkError};
+use crate::params::{ParamPolicy, TransportParameters};
+use crate::retry::RetryTokens;
+use crate::metrics::{DecodeErrorReport, Metrics, MetricsSnapshot};
+use crate::pool::{BufferPool, RecvArena};
+use crate::reflect;
+use crate::rejects::{Reject, RejectLog};
+use crate::segment::{self, Segment, SegmentError, SegmentType};
+use crate::seq::SeqNum;
+use crate::session::{SessionError, SessionState};
+#[cfg(feature = "tokio")]
+use crate::socket;
+use crate::socket::{LinkSocket, SocketInfo};
+#[cfg(feature = "tokio")]
+use crate::tcp;
+use crate::transport::Transport;
+use crate::stats::ListenerStats;
+use crate::state::{ConnState, StateMachine};
+use crate::tombstone::{self, Tombstones};
+use crate::trace::{self, Direction, Role};
+use bytes::{Bytes, BytesMut};
+use std::collections::{HashMap, VecDeque};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, Weak};
+use std::time::{Duration, Instant};
+#[cfg(feature = "tokio")]
+use tokio::net::ToSocketAddrs;
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+/// 接受入站连接的监听器
+#[derive(Debug)]
+pub struct Listener {
+    socket: Arc<LinkSocket>,    // 第一个接收套接字，所有套接字绑定在同一地址
+    incoming: Mutex<mpsc::Receiver<(Connection, SocketAddr)>>,
+    workers: Vec<Worker>,
+    metrics: Arc<Metrics>,
+    rejects: Arc<RejectLog>,    // 所有分发任务共用
+    accepting: Arc<AtomicBool>, // 所有分发任务共用，stop_accepting 后为 false
+    occupancy: Arc<Occupancy>,  // 所有分发任务共用
+    budget: Arc<MemoryBudget>,  // 所有分发任务共用
+    acl: watch::Sender<Arc<Acl>>,   // 所有分发任务订阅
+    policy: watch::Sender<Policy>,  // 所有分发任务订阅
+    socket_info: Option<SocketInfo>,    // 绑定 UDP 套接字时读出的生效选项
+}
+
+// 审查握手参数的策略，None 时照单全收
+#[derive(Clone, Default)]
+struct Policy(Option<Arc<dyn ParamPolicy>>);
+
+impl fmt::Debug for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.is_some() { "Policy(..)" } else { "Policy(None)" })
+    }
+}
+
+// 一个接收套接字与它的分发任务
+#[derive(Debug)]
+struct Worker {
+    stats: Arc<StdMutex<ListenerStats>>,
+    demux: JoinHandle<()>,
+    notices: Reaper,
+}
+
+/// 发送任务的队列长度（数据报）
+pub(crate) const OUTBOUND_QUEUE: usize = 1024;
+
+/// 发送任务记着的对端数超过它（及上一次清理后的两倍）时清理已结束的连接
+const ORIGINS_PRUNE: usize = 1024;
+
+/// 迁移后旧地址仍被接受的时间
+const MIGRATION_GRACE: Duration = Duration::from_secs(2);
+
+/// 每条连接同时验证的新地址数上限，超出时来自其他新地址的段不触发迁移
+const MAX_PATH_CANDIDATES: usize = 4;
+
+impl Listener {
+    /// 以默认参数绑定地址并开始接受握手
+    #[cfg(feature = "tokio")]
+    pub async fn bind(addr: impl ToSocketAddrs) -> Result<Listener, LinkError> {
+        Self::bind_with(addr, LinkConfig::default()).await
+    }
+
+    /// `config.workers` 大于 1 且平台支持 SO_REUSEPORT 时绑定多个接收套接字，见 `workers`；
+    /// 参数未通过 `LinkConfig::validate` 时返回 `LinkError::Config`
+    #[cfg(feature = "tokio")]
+    pub async fn bind_with(addr: impl ToSocketAddrs, config: LinkConfig) -> Result<Listener, LinkError> {
+        config.validate()?;
+        let sockets = socket::bind_workers(addr, &config).await?;
+        let info = socket::socket_info(&sockets[0]);
+        let sockets = sockets.into_iter().map(|socket| LinkSocket::new(socket, &config)).collect();
+        Self::start(sockets, config, Some(info))
+    }
+
+    /// 经 TCP 监听 `addr`（见 `tcp` 模块），供 UDP 被屏蔽的客户端以 `TransportKind::Tcp` 或 `Auto` 连接；
+    /// 重传与拥塞控制改为直通（`tcp::stream_config`），只有一个分发任务
+    #[cfg(feature = "tokio")]
+    pub async fn bind_tcp(addr: impl ToSocketAddrs, config: LinkConfig) -> Result<Listener, LinkError> {
+        let config = tcp::stream_config(config);
+        config.validate()?;
+        let transport = tcp::TcpListenerTransport::bind(addr).await?;
+        Self::start(vec![LinkSocket::new(transport, &config)], config, None)
+    }
+
+    /// 在调用方提供的传输（如 `transport::MemoryTransport`）上接受握手；只有一个分发任务，`config.workers` 不起作用
+    pub fn with_transport(transport: impl Transport, config: LinkConfig) -> Result<Listener, LinkError> {
+        config.validate()?;
+        let socket = LinkSocket::new(transport, &config);
+        Self::start(vec![socket], config, None)
+    }
+
+    fn start(sockets: Vec<LinkSocket>, config: LinkConfig, socket_info: Option<SocketInfo>) -> Result<Listener, LinkError> {
+        let sockets: Vec<_> = sockets.into_iter().map(Arc::new).collect();
+        let (tx, rx) = mpsc::channel(config.backlog.max(1));
+        let metrics = Arc::new(Metrics::default());
+        let rejects = Arc::new(RejectLog::new(config.reject_log));
+        let accepting = Arc::new(AtomicBool::new(true));
+        let occupancy = Arc::new(Occupancy::new(&config));
+        let budget = Arc::new(MemoryBudget::new(&config));
+        let (acl, _) = watch::channel(Arc::new(Acl::new(config.allow.clone(), config.deny.clone())));
+        let (policy, _) = watch::channel(Policy::default());
+        let instance = reflect::fresh_instance();
+        let mut workers = Vec::with_capacity(sockets.len());
+        for socket in &sockets {
+            let socket = socket.clone();
+            let local = socket.local_addr()?;
+            let stats = Arc::new(StdMutex::new(ListenerStats::default()));
+            // 发送任务在所有连接与分发任务都放下队列后自行退出，监听器关闭后仍在使用的连接照常发送
+            let (out, outgoing) = mpsc::channel(OUTBOUND_QUEUE);
+            let tap = config.capture.as_ref().map(|capture| capture.tap(local));
+            // 每个接收套接字一个缓冲池，由分发任务、它的连接与发送任务共用
+            let pool = Arc::new(BufferPool::new(&config));
+            let batcher = Batcher::new(config.mss, config.batch_window);
+            tokio::spawn(send_loop(socket.clone(), outgoing, batcher, metrics.clone(), tap, pool.clone()));
+            let span = tracing::info_span!("listener", %local);
+            let common = Common {
+                stats: stats.clone(),
+                metrics: metrics.clone(),
+                rejects: rejects.clone(),
+                accepting: accepting.clone(),
+                occupancy: occupancy.clone(),
+                budget: budget.clone(),
+                acl: acl.subscribe(),
+                policy: policy.subscribe(),
+                instance,
+            };
+            let demux = Demux::new(socket, local, out, pool, config.clone(), tx.clone(), common);
+            let notices = demux.reaper.clone();
+            let demux = tokio::spawn(demux.run().instrument(span));
+            workers.push(Worker { stats, demux, notices });
+        }
+        let socket = sockets[0].clone();
+        Ok(Listener { socket, incoming: Mutex::new(rx), workers, metrics, rejects, accepting, occupancy, budget, acl, policy, socket_info })
+    }
+
+    /// 等待下一个完成握手的连接。连接的入站数据报由监听器的分发任务转交，
+    /// 监听器被丢弃后已接受的连接不再收到任何段，监听器应与它接受的连接活得一样久
+    pub async fn accept(&self) -> Result<(Connection, SocketAddr), LinkError> {
+        self.incoming.lock().await.recv().await.ok_or(LinkError::Closed)
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, LinkError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// 接续另一个进程以 `Connection::export_state` 导出的连接（见 `session` 模块），连接直接交还给调用方而不经过 `accept`。
+    /// 监听器须绑定在导出时的本地地址上，并以相同的 `psk` 配置；对端地址或连接 ID 已被占用时返回 `Session(InUse)`，
+    /// 连接数达到上限时返回 `ServerBusy`。多个接收套接字时连接登记在第一个套接字上，对端的段须由内核散列到那里
+    pub async fn adopt(&self, state: SessionState) -> Result<Connection, LinkError> {
+        let (reply, adopted) = oneshot::channel();
+        self.workers[0].notices.send(Notice::Adopt { state: Box::new(state), reply }).map_err(|_| LinkError::Closed)?;
+        adopted.await.map_err(|_| LinkError::Closed)?
+    }
+
+    /// 接收套接字上生效的缓冲区大小与 DSCP（见 `socket` 模块）；调用方提供的传输没有套接字选项，返回 None
+    pub fn socket_info(&self) -> Option<SocketInfo> {
+        self.socket_info
+    }
+
+    /// 不再接受新的握手；已建立的连接不受影响。待 accept 队列中的连接取完后 `accept` 返回 `Closed`
+    pub async fn stop_accepting(&self) {
+        self.accepting.store(false, Ordering::Relaxed);
+        self.incoming.lock().await.close();
+    }
+
+    /// 换上新的访问控制列表，此后来自新来源的数据报按它检查；不再被允许的半开握手被放弃，
+    /// `acl.evict` 时不再被允许的已建立连接以 Rst 中止，否则照常运行
+    pub fn set_acl(&self, acl: Acl) {
+        self.acl.send_replace(Arc::new(acl));
+    }
+
+    /// 当前生效的访问控制列表
+    pub fn acl(&self) -> Arc<Acl> {
+        self.acl.borrow().clone()
+    }
+
+    /// 以 `policy` 审查此后的握手中客户端提出的参数（见 `params` 模块）：返回收紧后的参数，或拒绝握手
+    pub fn set_param_policy(&self, policy: impl ParamPolicy) {
+        self.policy.send_replace(Policy(Some(Arc::new(policy))));
+    }
+
+    /// 连接表的当前规模与累计回收数，多个接收套接字时为各自之和
+    pub fn stats(&self) -> ListenerStats {
+        self.worker_stats().into_iter().fold(ListenerStats::default(), |mut total, stats| {
+            total += stats;
+            total
+        })
+    }
+
+    /// 监听器及其连接的数据路径计数器
+    pub fn metrics(&self) -> MetricsSnapshot {
+        MetricsSnapshot { memory_used: self.budget.used(), memory_limit: self.budget.limit(), ..self.metrics.snapshot() }
+    }
+
+    /// 内存占用最多的 `n` 条连接与它们的分项（见 `budget` 模块），从多到少
+    pub fn memory_consumers(&self, n: usize) -> Vec<MemoryConsumer> {
+        self.budget.top_consumers(n)
+    }
+
+    /// 计入 `max_connections` 与 `per_ip_limit` 的已建立连接数：合计与来自 `ip` 的
+    pub fn occupancy(&self, ip: IpAddr) -> (usize, usize) {
+        self.occupancy.get(ip)
+    }
+
+    pub(crate) fn shared_metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// 最近无法解析的数据报，从旧到新，最多 `LinkConfig::reject_log` 条（见 `rejects` 模块）
+    pub fn recent_rejects(&self) -> Vec<Reject> {
+        self.rejects.recent()
+    }
+
+    /// 监听器及其连接遇到的解码错误按种类与来源的汇总（见 `metrics` 模块）
+    pub fn decode_error_report(&self) -> DecodeErrorReport {
+        self.metrics.decode_report()
+    }
+    /// 每个接收套接字各自的连接表统计
+    pub fn worker_stats(&self) -> Vec<ListenerStats> {
+        self.workers.iter().map(|worker| *worker.stats.lock().expect("listener stats poisoned")).collect()
+    }
+
+    /// 实际绑定的接收套接字数；平台不支持 SO_REUSEPORT 时为 1，可能少于 `LinkConfig::workers`
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        for worker in &self.workers {
+            worker.demux.abort();
+        }
+    }
+}
+
+// 尚未完成的握手
+struct HalfOpen {
+    state: StateMachine,
+    local_isn: SeqNum,
+    peer_isn: SeqNum,
+    conn_id: u32,
+    checksum: ChecksumAlgorithm,    // 与对端协商出的校验算法
+    peer_mss: Option<usize>,        // 对端通告的 MSS
+    params: TransportParameters,    // 与对端协商出的参数
+    auth: SynAuth,
+    started_at: Instant,
+}
+
+impl HalfOpen {
+    // SYN-ACK 携带为这个连接分配的连接 ID 与监听器的实例 ID
+    fn syn_ack(&self, window: u32, config: &LinkConfig, instance: u32) -> Segment {
+        let mut reply = endpoint::syn_ack(self.local_isn, self.peer_isn, window, endpoint::advertised_mss(config), &self.params, &config.checksums, self.checksum);
+        reply.set_conn_id(self.conn_id);
+        reflect::stamp(&mut reply, instance);
+        self.auth.sign(&mut reply);
+        reply
+    }
+}
+
+enum Peer {
+    HalfOpen(HalfOpen),
+    Open(Arc<Shared>),
+}
+
+impl Peer {
+    // 握手完成前（连接 ID 为 0）的段是否属于它：重传的 SYN 带着同一个 ISN，随 SYN 到达的 0-RTT 数据是 ISN + 1
+    fn started_with(&self, kind: SegmentType, seq: SeqNum) -> bool {
+        match self {
+            Peer::HalfOpen(handshake) => seq == handshake.peer_isn || (kind != SegmentType::Syn && seq == handshake.peer_isn.wrapping_add(1)),
+            Peer::Open(shared) => kind == SegmentType::Syn && shared.peer_isn() == seq,
+        }
+    }
+
+    fn is_open(&self, shared: &Arc<Shared>) -> bool {
+        matches!(self, Peer::Open(open) if Arc::ptr_eq(open, shared))
+    }
+}
+
+// 连接表：按对端地址索引。对端经一个套接字发起多条连接时（见 `client_endpoint` 模块）同一地址上有多个登记项，
+// 由 `Demux::find` 按段头区分；通常每个地址只有一个
+#[derive(Default)]
+struct PeerTable {
+    entries: HashMap<SocketAddr, Vec<Peer>>,
+    len: usize,
+}
+
+impl PeerTable {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn contains(&self, addr: &SocketAddr) -> bool {
+        self.entries.contains_key(addr)
+    }
+
+    fn at(&self, addr: SocketAddr) -> &[Peer] {
+        self.entries.get(&addr).map_or(&[], Vec::as_slice)
+    }
+
+    fn get_mut(&mut self, addr: SocketAddr, index: usize) -> Option<&mut Peer> {
+        self.entries.get_mut(&addr)?.get_mut(index)
+    }
+
+    fn insert(&mut self, addr: SocketAddr, peer: Peer) {
+        self.entries.entry(addr).or_default().push(peer);
+        self.len += 1;
+    }
+
+    fn remove(&mut self, addr: SocketAddr, index: usize) -> Option<Peer> {
+        let peers = self.entries.get_mut(&addr)?;
+        if index >= peers.len() {
+            return None;
+        }
+        let peer = peers.remove(index);
+        if peers.is_empty() {
+            self.entries.remove(&addr);
+        }
+        self.len -= 1;
+        Some(peer)
+    }
+
+    // 已建立的连接 `shared` 在 `addr` 上的位置
+    fn position(&self, addr: SocketAddr, shared: &Arc<Shared>) -> Option<usize> {
+        self.at(addr).iter().position(|peer| peer.is_open(shared))
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(SocketAddr, &Peer) -> bool) {
+        self.entries.retain(|&addr, peers| {
+            peers.retain(|peer| keep(addr, peer));
+            !peers.is_empty()
+        });
+        self.len = self.entries.values().map(Vec::len).sum();
+    }
+}
+
+// 正在验证的新地址：等待带回 `token` 的 PathResponse
+struct Candidate {
+    shared: Arc<Shared>,
+    token: u64,
+    deadline: Instant,
+}
+
+// 计入 `max_connections` 与 `per_ip_limit` 的已建立连接，所有分发任务共用
+#[derive(Debug)]
+struct Occupancy {
+    max_connections: Option<usize>,
+    per_ip_limit: Option<usize>,
+    counts: StdMutex<Counts>,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+impl Occupancy {
+    fn new(config: &LinkConfig) -> Self {
+        Self { max_connections: config.max_connections, per_ip_limit: config.per_ip_limit, counts: StdMutex::default() }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Counts> {
+        self.counts.lock().expect("listener occupancy poisoned")
+    }
+
+    fn get(&self, ip: IpAddr) -> (usize, usize) {
+        let counts = self.lock();
+        (counts.total, counts.per_ip.get(&ip).copied().unwrap_or(0))
+    }
+
+    // 再来自 `ip` 的一个连接是否超出上限
+    fn admits(&self, counts: &Counts, ip: IpAddr) -> bool {
+        self.max_connections.is_none_or(|max| counts.total < max)
+            && self.per_ip_limit.is_none_or(|max| counts.per_ip.get(&ip).copied().unwrap_or(0) < max)
+    }
+
+    // SYN 到达时的检查，不登记
+    fn has_room(&self, ip: IpAddr) -> bool {
+        self.admits(&self.lock(), ip)
+    }
+
+    // 握手完成时在同一把锁下检查并登记；超出上限时返回 false
+    fn try_admit(&self, ip: IpAddr) -> bool {
+        let mut counts = self.lock();
+        if !self.admits(&counts, ip) {
+            return false;
+        }
+        counts.total += 1;
+        *counts.per_ip.entry(ip).or_default() += 1;
+        true
+    }
+
+    fn release(&self, ip: IpAddr) {
+        let mut counts = self.lock();
+        counts.total -= 1;
+        if let Some(count) = counts.per_ip.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                counts.per_ip.remove(&ip);
+            }
+        }
+    }
+}
+
+// 分发任务与监听器共享的状态：统计属于单个分发任务，其余由所有分发任务共用
+struct Common {
+    stats: Arc<StdMutex<ListenerStats>>,   // 这个分发任务自己的统计，由监听器汇总
+    metrics: Arc<Metrics>,
+    rejects: Arc<RejectLog>,
+    accepting: Arc<AtomicBool>,
+    occupancy: Arc<Occupancy>,
+    budget: Arc<MemoryBudget>,
+    acl: watch::Receiver<Arc<Acl>>,
+    policy: watch::Receiver<Policy>,
+    instance: u32,  // 监听器的实例 ID，所有接收套接字共用（见 `reflect` 模块）
+}
+
+// 唯一的发送任务：每次取出队列中积压的全部数据报交给 `batcher` 合并；发送失败等同于丢包，发出后把缓冲还给池。
+// 本机以过大拒绝的数据报交还给最近向那个对端发送、携带同一连接 ID 的连接，由它降低有效 MSS、重新分片（见 `Shared::on_too_large`）；
+// 在此之前它已交来的数据报（按旧的 MSS 打包，可能带着被撤回的段）一律丢弃，直到它按新的 MSS 交来第一个数据报。
+// 同一地址上的其他连接不受影响。`client_endpoint` 模块的发送任务也是它
+pub(crate) async fn send_loop(
+    socket: Arc<LinkSocket>,
+    mut outgoing: mpsc::Receiver<Outgoing>,
+    mut batcher: Batcher,
+    metrics: Arc<Metrics>,
+    tap: Option<Tap>,
+    pool: Arc<BufferPool>,
+) {
+    let mut open = true;
+    let mut origins: HashMap<(SocketAddr, u32), Weak<Shared>> = HashMap::new();
+    let mut prune_at = ORIGINS_PRUNE;
+    let mut shrunk: HashMap<(SocketAddr, u32), usize> = HashMap::new();    // 对端与连接 ID，交还数据报后连接的有效 MSS
+    let mut ready = Vec::new();
+    while open || !batcher.is_empty() {
+        let deadline = batcher.next_deadline().map(tokio::time::Instant::from_std);
+        tokio::select! {
+            received = outgoing.recv(), if open => match received {
+                Some(first) => {
+                    // 已在队列中积压的一并取出，合并进各自连接的数据报
+                    let mut next = Some(first);
+                    while let Some((datagram, to, mss, origin)) = next {
+                        next = outgoing.try_recv().ok();
+                        if let Some(origin) = origin {
+                            let key = (to, batch::conn_id(&datagram));
+                            if let Some(&limit) = shrunk.get(&key) {
+                                if mss > limit {
+                                    pool.put(datagram);
+                                    continue;
+                                }
+                                shrunk.remove(&key);
+                            }
+                            origins.insert(key, origin);
+                        }
+                        if let Some(spare) = batcher.push(datagram, to, mss, connection::now()) {
+                            pool.put(spare);
+                        }
+                    }
+                    // 已结束的连接不再需要记着
+                    if origins.len() >= prune_at {
+                        origins.retain(|_, origin| origin.strong_count() > 0);
+                        shrunk.retain(|key, _| origins.contains_key(key));
+                        prune_at = (origins.len() * 2).max(ORIGINS_PRUNE);
+                    }
+                }
+                // 所有连接与分发任务都已放下队列：发出攒下的数据报后退出
+                None => open = false,
+            },
+            _ = tokio::time::sleep_until(deadline.unwrap_or_else(tokio::time::Instant::now)), if deadline.is_some() => {}
+        }
+        if open {
+            batcher.flush_due(connection::now());
+        } else {
+            batcher.flush();
+        }
+        ready.extend(batcher.drain_ready());
+        for (datagram, to) in ready.drain(..) {
+            let key = (to, batch::conn_id(&datagram));
+            if shrunk.contains_key(&key) {
+                pool.put(datagram);
+                continue;
+            }
+            match socket.send_to(&datagram, to).await {
+                Ok(_) => {
+                    metrics.on_sent(datagram.len(), batch::segment_count(&datagram));
+                    if let Some(tap) = &tap {
+                        tap.record(Direction::Outbound, to, &datagram);
+                    }
+                }
+                Err(e) if error::is_too_large(&e) => {
+                    if let Some(origin) = origins.get(&key).and_then(Weak::upgrade) {
+                        shrunk.insert(key, origin.on_too_large(&datagram));
+                        if let Some(stale) = batcher.discard(to, key.1) {
+                            pool.put(stale);
+                        }
+                    }
+                }
+                Err(_) => {}
+            }
+            pool.put(datagram);
+        }
+    }
+}
+
+// 分发任务的状态
+// 认证的握手：签名用的密钥与双方的 nonce；没有配置密钥（或未启用 `crypto` 特性）时为空，不签名也不校验
+#[derive(Debug, Clone, Default)]
+struct SynAuth {
+    #[cfg(feature = "crypto")]
+    keyed: Option<(Arc<HandshakeAuth>, HandshakeNonce, HandshakeNonce)>,   // 密钥、客户端与本端的 nonce
+}
+
+impl SynAuth {
+    // 为 SYN-ACK 签名：携带本端的 nonce，回显客户端的
+    #[cfg_attr(not(feature = "crypto"), allow(unused_variables))]
+    fn sign(&self, reply: &mut Segment) {
+        #[cfg(feature = "crypto")]
+        if let Some((auth, client, server)) = &self.keyed {
+            auth.sign(reply, *server, *client);
+        }
+    }
+
+    // 能否推进半开握手：重传的 SYN 携带同一个 nonce，确认还须回显本端的；Rst 不签名
+    #[cfg_attr(not(feature = "crypto"), allow(unused_variables))]
+    fn authenticates(&self, segment: &Segment) -> bool {
+        #[cfg(feature = "crypto")]
+        if let Some((auth, client, server)) = &self.keyed {
+            return match (segment.segment_type(), auth.verify(segment)) {
+                (SegmentType::Rst, _) => true,
+                (SegmentType::Syn, Ok((nonce, _))) => nonce == *client,
+                (SegmentType::Ack, Ok((nonce, echo))) => nonce == *client && echo == *server,
+                _ => false,
+            };
+        }
+        true
+    }
+
+    // 交给连接派生流量密钥的 nonce：（发起方, 响应方）
+    #[cfg(feature = "crypto")]
+    fn nonces(&self) -> Option<(HandshakeNonce, HandshakeNonce)> {
+        self.keyed.as_ref().map(|(_, client, server)| (*client, *server))
+    }
+}
+
+struct Demux {
+    socket: Arc<LinkSocket>,
+    local: SocketAddr,
+    out: mpsc::Sender<Outgoing>,
+    pool: Arc<BufferPool>,
+    config: LinkConfig,
+    accept_tx: mpsc::Sender<(Connection, SocketAddr)>,
+    peers: PeerTable,
+    by_id: HashMap<u32, Arc<Shared>>,                   // 已建立的连接按连接 ID 索引
+    aliases: HashMap<SocketAddr, (Arc<Shared>, Instant)>, // 迁移前的旧地址与其失效时间
+    candidates: HashMap<SocketAddr, Candidate>,         // 正在验证的新地址
+    retiring: VecDeque<(u32, Instant)>,                 // 轮换下来的连接 ID 与它移出 `by_id` 的时间，按时间排列
+    tombstones: Tombstones,     // 已结束的连接
+    cookies: CookieJar,
+    retry: RetryTokens,
+    #[cfg(feature = "crypto")]
+    auth: Option<Arc<HandshakeAuth>>,   // 配置了密钥时认证握手
+    half_open: usize,
+    reaper: Reaper,
+    reaped: mpsc::UnboundedReceiver<Notice>,
+    stats: Arc<StdMutex<ListenerStats>>,
+    evicted: u64,
+    dropped: u64,
+    migrated: u64,
+    unvalidated: u64,
+    malformed: u64,
+    truncated: u64,
+    metrics: Arc<Metrics>,  // 所有接收套接字共用
+    rejects: Arc<RejectLog>,
+    accepting: Arc<AtomicBool>,
+    occupancy: Arc<Occupancy>,
+    admitted: HashMap<u32, IpAddr>, // 计入 `occupancy` 的连接（按连接 ID）与它建立时的 IP
+    budget: Arc<MemoryBudget>,
+    tombstone_memory: Charge,   // 墓碑表在 `budget` 中的占用
+    acl: watch::Receiver<Arc<Acl>>,
+    policy: watch::Receiver<Policy>,
+    denied: u64,
+    instance: u32,
+}
+
+impl Demux {
+    fn new(
+        socket: Arc<LinkSocket>,
+        local: SocketAddr,
+        out: mpsc::Sender<Outgoing>,
+        pool: Arc<BufferPool>,
+        config: LinkConfig,
+        accept_tx: mpsc::Sender<(Connection, SocketAddr)>,
+        common: Common,
+    ) -> Self {
+        let Common { stats, metrics, rejects, accepting, occupancy, budget, acl, policy, instance } = common;
+        let (reaper, reaped) = mpsc::unbounded_channel();
+        let tombstones = Tombstones::new(&config);
+        let cookies = CookieJar::new(&config, connection::now());
+        let retry = RetryTokens::new(&config, connection::now());
+        #[cfg(feature = "crypto")]
+        let auth = config.psk.as_ref().map(|psk| Arc::new(HandshakeAuth::new(psk)));
+        Self {
+            socket,
+            local,
+            out,
+            pool,
+            config,
+            accept_tx,
+            peers: PeerTable::default(),
+            by_id: HashMap::new(),
+            aliases: HashMap::new(),
+            candidates: HashMap::new(),
+            retiring: VecDeque::new(),
+            tombstones,
+            cookies,
+            retry,
+            #[cfg(feature = "crypto")]
+            auth,
+            half_open: 0,
+            reaper,
+            reaped,
+            stats,
+            evicted: 0,
+            dropped: 0,
+            migrated: 0,
+            unvalidated: 0,
+            malformed: 0,
+            truncated: 0,
+            metrics,
+            rejects,
+            accepting,
+            occupancy,
+            admitted: HashMap::new(),
+            tombstone_memory: budget.charge(),
+            budget,
+            acl,
+            policy,
+            denied: 0,
+            instance,
+        }
+    }
+
+    async fn run(mut self) {
+        let mut arena = RecvArena::new(self.config.recv_buffer);
+        let tap = self.config.capture.as_ref().map(|capture| capture.tap(self.local));
+        let mut handled = 0;    // 上次让出执行权以来处理的数据报数
+        loop {
+            tokio::select! {
+                received = self.socket.recv_from(arena.space()) => {
+                    // 部分平台会把对端的 ICMP 不可达报告为接收错误，忽略即可；套接字失效时分发任务退出，accept 返回 Closed
+                    let (len, from) = match received {
+                        Ok(received) => received,
+                        Err(e) if error::is_fatal(&e) => {
+                            tracing::error!(error = %e, "listener socket failed");
+                            return;
+                        }
+                        Err(_) => continue,
+                    };
+                    let mut drained = 1;
+                    self.receive(arena.split(len), from, tap.as_ref());
+                    // 不再等待，取走此刻已经排队的数据报：突发到达时积压在这一次唤醒里清空，内核的接收缓冲区不会在两次唤醒之间溢出
+                    while handled + drained < self.config.recv_batch {
+                        match self.socket.try_recv_from(arena.space()) {
+                            Ok((len, from)) => {
+                                drained += 1;
+                                self.receive(arena.split(len), from, tap.as_ref());
+                            }
+                            // 没有排队的数据报；其他错误留给下一次 recv_from 报告
+                            Err(_) => break,
+                        }
+                    }
+                    handled += drained;
+                    self.metrics.on_drained(drained);
+                }
+                // reaper 由自己持有，通道不会关闭
+                Some(notice) = self.reaped.recv() => match notice {
+                    Notice::Reaped(shared) => self.reap(shared),
+                    Notice::Rotated { shared, old, new } => self.rekey(shared, old, new),
+                    Notice::Adopt { state, reply } => {
+                        let _ = reply.send(self.adopt(*state));
+                    }
+                },
+                // 发送端由监听器持有，监听器被丢弃时分发任务随之中止
+                Ok(()) = self.acl.changed() => self.apply_acl(),
+            }
+            self.tombstones.sweep(connection::now());
+            self.tombstone_memory.set(self.tombstones.len() * tombstone::FOOTPRINT);
+            self.retire_conn_ids(connection::now());
+            self.expire_candidates(connection::now());
+            self.publish_stats();
+            // 积压的套接字与内存传输总有数据报可读，recv_from 从不挂起：处理满一批后主动让出执行权
+            if handled >= self.config.recv_batch {
+                handled = 0;
+                tokio::task::yield_now().await;
+            }
+        }
+    }
+
+    // 一个收到的数据报：截断检查、访问控制、墓碑，然后交给连接或由监听器自己解码。
+    // 每次唤醒等到的与随后取走的数据报都经过这里
+    fn receive(&mut self, mut datagram: Bytes, from: SocketAddr, tap: Option<&Tap>) {
+        let len = datagram.len();
+        self.metrics.on_received(len);
+        if let Some(tap) = tap {
+            tap.record(Direction::Inbound, from, &datagram);
+        }
+        if segment::is_truncated(&datagram) {
+            // 超出接收缓冲区的数据报：剩下的前缀不可信，不交给任何连接
+            self.truncated += 1;
+            self.metrics.on_truncated();
+            self.rejects.record(from, &SegmentError::TooShort, &datagram);
+            tracing::warn!(peer = %from, len, "dropping truncated datagram");
+        } else if !self.admits(from) {
+            // 不被允许的新来源：不解码也不回应
+            self.denied += 1;
+            tracing::trace!(peer = %from, "dropping datagram from a denied source");
+        } else if self.absorb(&datagram, from) {
+            // 已结束连接的迟到段
+            self.metrics.on_absorbed();
+        } else if let Some(shared) = self.route(&datagram, from) {
+            // 数据报与接收缓冲共享存储，连接从中切出数据体
+            let delivered = shared.deliver(datagram);
+            self.dropped += u64::from(!delivered);
+            self.metrics.on_routed(delivered);
+        } else {
+            // 一个数据报可能打包了多个段；遇到无法解析的部分（含未知段类型、截断）时丢弃剩余内容并计数
+            let conn_id = segment::parse_header(&datagram).map_or(0, |header| header.conn_id);
+            let raw = datagram.clone();
+            loop {
+                match Segment::decode_bytes(&mut datagram) {
+                    Ok(Some(segment)) => {
+                        if trace::enabled() {
+                            trace::segment(Direction::Inbound, &segment, from);
+                        }
+                        // 本监听器发出、又被对端原样发回的段：回应它只会让环路继续
+                        if reflect::is_reflected(&segment, self.instance) {
+                            self.metrics.on_reflected();
+                            tracing::debug!(peer = %from, kind = ?segment.segment_type(), "dropping a reflected segment");
+                            continue;
+                        }
+                        self.dispatch(segment, from);
+                    }
+                    Ok(None) if datagram.is_empty() => {
+                        self.metrics.on_handled(None, from);
+                        break;
+                    }
+                    Ok(None) => {
+                        self.malformed += 1;
+                        self.metrics.on_incomplete(from);
+                        self.rejects.record(from, &SegmentError::TooShort, &raw);
+                        break;
+                    }
+                    Err(e) => {
+                        self.malformed += 1;
+                        self.metrics.on_handled(Some(&e), from);
+                        self.rejects.record(from, &e, &raw);
+                        trace::decode_failed(&e, from, conn_id);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // 已有连接或握手的地址不再检查；新来源须经访问控制列表允许
+    fn admits(&self, from: SocketAddr) -> bool {
+        self.peers.contains(&from)
+            || self.aliases.contains_key(&from)
+            || self.candidates.contains_key(&from)
+            || self.acl.borrow().permits(from.ip())
+    }
+
+    // 换上了新的访问控制列表：放弃不再被允许的半开握手，`evict` 时中止不再被允许的已建立连接，
+    // 连接的驱动任务发出 Rst 后经 `Notice::Reaped` 移出连接表
+    fn apply_acl(&mut self) {
+        let acl = self.acl.borrow_and_update().clone();
+        let mut forgotten = 0;
+        self.peers.retain(|addr, peer| match peer {
+            _ if acl.permits(addr.ip()) => true,
+            Peer::HalfOpen(_) => {
+                forgotten += 1;
+                false
+            }
+            Peer::Open(shared) => {
+                if acl.evict {
+                    tracing::info!(peer = %addr, conn_id = shared.conn_id(), "connection denied by the new access control list");
+                    shared.reset(LinkError::Denied);
+                }
+                true
+            }
+        });
+        self.half_open -= forgotten;
+    }
+
+    // 携带墓碑中连接 ID、来自该连接对端的数据报：重传的 FIN 以最后的确认回应，其余段丢弃
+    fn absorb(&self, datagram: &[u8], from: SocketAddr) -> bool {
+        if self.tombstones.is_empty() {
+            return false;
+        }
+        let Some(tombstone) =
+            segment::parse_header(datagram).ok().and_then(|header| self.tombstones.lookup(header.conn_id, from, connection::now()))
+        else {
+            return false;
+        };
+        let mut datagram = BytesMut::from(datagram);
+        while let Ok(Some(segment)) = Segment::decode_from(&mut datagram) {
+            if let Some(reply) = tombstone.on_segment(&segment) {
+                self.send(&reply, from);
+            }
+        }
+        true
+    }
+
+    // 来自 `from`、段头如此的段在连接表中所属的登记项：携带连接 ID 的段按 ID 找（已建立的连接经 `by_id`，
+    // 含轮换后宽限期内的旧 ID），握手完成前不带 ID 的段按 SYN 的 ISN 找（见 `Peer::started_with`）。
+    // 地址上只有一个登记项时，除了另一次握手的 SYN，不带 ID 或 ID 不认识的段（如重放的会话）都属于它
+    fn find(&self, from: SocketAddr, conn_id: u32, kind: SegmentType, seq: SeqNum) -> Option<usize> {
+        let peers = self.peers.at(from);
+        if conn_id != 0
+            && let Some(index) = match self.by_id.get(&conn_id) {
+                Some(shared) => peers.iter().position(|peer| peer.is_open(shared)),
+                None => peers.iter().position(|peer| matches!(peer, Peer::HalfOpen(handshake) if handshake.conn_id == conn_id)),
+            }
+        {
+            return Some(index);
+        }
+        if peers.len() == 1 && kind != SegmentType::Syn {
+            return Some(0);
+        }
+        peers.iter().position(|peer| peer.started_with(kind, seq))
+    }
+
+    // 数据报所属的已建立连接：当前地址、宽限期内的旧地址、正在验证的新地址，或通过校验的迁移
+    fn route(&mut self, datagram: &[u8], from: SocketAddr) -> Option<Arc<Shared>> {
+        if self.peers.contains(&from) {
+            let found = match segment::parse_header(datagram) {
+                Ok(header) => self.find(from, header.conn_id, header.segment_type, header.seq),
+                // 无法解析的数据报交给地址上唯一的连接，由它计入解码错误
+                Err(_) => (self.peers.at(from).len() == 1).then_some(0),
+            };
+            match found.map(|index| &self.peers.at(from)[index]) {
+                Some(Peer::Open(shared)) => return Some(shared.clone()),
+                // 半开握手的段逐段处理
+                Some(Peer::HalfOpen(_)) => return None,
+                // 新握手的 SYN，或不属于这个地址上任何连接的段
+                None => {}
+            }
+        }
+        match self.aliases.get(&from) {
+            Some((shared, until)) if connection::now() < *until => return Some(shared.clone()),
+            Some(_) => {
+                self.aliases.remove(&from);
+            }
+            None => {}
+        }
+        if let Some(shared) = self.validate_path(datagram, from) {
+            return Some(shared);
+        }
+        self.migrate(datagram, from)
+    }
+
+    // 陌生地址：第一个段携带已知的连接 ID 且通过校验时，向新地址发出 PathChallenge 开始验证，数据报照常交给连接。
+    // 只有连接 ID 已知时才完整解码
+    fn migrate(&mut self, datagram: &[u8], from: SocketAddr) -> Option<Arc<Shared>> {
+        let header = segment::parse_header(datagram).ok()?;
+        let shared = self.by_id.get(&header.conn_id)?.clone();
+        let segment = Segment::decode_from(&mut BytesMut::from(datagram)).ok()??;
+        if !shared.accepts_migration(&segment) {
+            return None;
+        }
+        if self.candidates.values().filter(|candidate| Arc::ptr_eq(&candidate.shared, &shared)).count() >= MAX_PATH_CANDIDATES {
+            tracing::debug!(conn_id = header.conn_id, new = %from, "too many unvalidated addresses");
+            return None;
+        }
+
+        let mut token = [0u8; 8];
+        getrandom::fill(&mut token).expect("operating system random source unavailable");
+        let token = u64::from_be_bytes(token);
+        let mut challenge = Segment::path_challenge(token);
+        challenge.set_conn_id(header.conn_id);
+        challenge.set_checksum(segment.checksum());
+        self.send(&challenge, from);
+        let deadline = connection::now() + self.config.path_validation_timeout;
+        self.candidates.insert(from, Candidate { shared: shared.clone(), token, deadline });
+        tracing::debug!(conn_id = header.conn_id, old = %shared.peer_addr(), new = %from, "validating a new peer address");
+        Some(shared)
+    }
+
+    // 正在验证的新地址：带回令牌的 PathResponse 提交迁移。数据报无论如何都交给连接
+    fn validate_path(&mut self, datagram: &[u8], from: SocketAddr) -> Option<Arc<Shared>> {
+        if self.candidates.is_empty() {
+            return None;
+        }
+        self.expire_candidates(connection::now());
+        let candidate = self.candidates.get(&from)?;
+        let (shared, token) = (candidate.shared.clone(), candidate.token);
+        let mut datagram = BytesMut::from(datagram);
+        let validated = std::iter::from_fn(|| Segment::decode_from(&mut datagram).ok().flatten())
+            .any(|segment| segment.segment_type() == SegmentType::PathResponse && segment.nonce() == Some(token));
+        if validated {
+            self.commit_migration(&shared, from);
+        }
+        Some(shared)
+    }
+
+    // 新地址通过验证：连接表改为以它索引，连接之后的段发往它；这条连接的其他候选地址一并放弃
+    fn commit_migration(&mut self, shared: &Arc<Shared>, from: SocketAddr) {
+        let now = connection::now();
+        self.candidates.retain(|_, candidate| !Arc::ptr_eq(&candidate.shared, shared));
+        self.aliases.retain(|_, (_, until)| now < *until);
+        let old = shared.peer_addr();
+        if let Some(peer) = self.peers.position(old, shared).and_then(|index| self.peers.remove(old, index)) {
+            self.aliases.insert(old, (shared.clone(), now + MIGRATION_GRACE));
+            self.peers.insert(from, peer);
+        }
+        shared.rebind(from);
+        self.migrated += 1;
+        tracing::info!(conn_id = shared.conn_id(), %old, new = %from, "peer migrated");
+        if self.config.rotate_conn_id_on_migration
+            && let Err(e) = shared.rotate_conn_id()
+        {
+            tracing::debug!(conn_id = shared.conn_id(), error = %e, "connection id rotation skipped");
+        }
+    }
+
+    // 连接换用了新的 ID：以它登记连接，旧 ID 在宽限期后移除；占用的计数跟着换到新的 ID 上
+    fn rekey(&mut self, shared: Arc<Shared>, old: u32, new: u32) {
+        if let Some(ip) = self.admitted.remove(&old) {
+            self.admitted.insert(new, ip);
+        }
+        match self.by_id.get(&new) {
+            Some(other) if !Arc::ptr_eq(other, &shared) => tracing::debug!(old, new, "new connection id is taken, not registering it"),
+            _ => {
+                self.by_id.insert(new, shared);
+            }
+        }
+        self.retiring.push_back((old, connection::now() + self.config.conn_id_grace));
+    }
+
+    // 宽限期已过的旧连接 ID 移出连接表；连接又换回了这个 ID 时保留
+    fn retire_conn_ids(&mut self, now: Instant) {
+        while let Some(&(old, until)) = self.retiring.front()
+            && until <= now
+        {
+            self.retiring.pop_front();
+            if self.by_id.get(&old).is_some_and(|shared| shared.conn_id() != old) {
+                self.by_id.remove(&old);
+            }
+        }
+    }
+
+    // 放弃超时未通过验证的新地址
+    fn expire_candidates(&mut self, now: Instant) {
+        if self.candidates.is_empty() {
+            return;
+        }
+        let before = self.candidates.len();
+        self.candidates.retain(|addr, candidate| {
+            let pending = now < candidate.deadline;
+            if !pending {
+                tracing::warn!(conn_id = candidate.shared.conn_id(), new = %addr, "peer address failed validation");
+            }
+            pending
+        });
+        self.unvalidated += (before - self.candidates.len()) as u64;
+    }
+
+    // 连接的驱动任务已退出：移出连接表（同一地址上可能还有别的连接，只移除同一个连接）
+    fn reap(&mut self, shared: Arc<Shared>) {
+        let addr = shared.peer_addr();
+        if let Some(index) = self.peers.position(addr, &shared) {
+            self.peers.remove(addr, index);
+            let evicted = shared.error() == Some(LinkError::IdleTimeout);
+            if evicted {
+                self.evicted += 1;
+                tracing::debug!(peer = %addr, conn_id = shared.conn_id(), "idle connection evicted");
+            }
+            self.metrics.on_connection_closed(evicted);
+        }
+        if self.by_id.get(&shared.conn_id()).is_some_and(|current| Arc::ptr_eq(current, &shared)) {
+            self.by_id.remove(&shared.conn_id());
+        }
+        self.aliases.retain(|_, (alias, _)| !Arc::ptr_eq(alias, &shared));
+        self.candidates.retain(|_, candidate| !Arc::ptr_eq(&candidate.shared, &shared));
+        if let Some(ip) = self.admitted.remove(&shared.conn_id()) {
+            self.occupancy.release(ip);
+        }
+        // 迁出的连接：交接期间对端的段静默丢弃，不以 Rst 回应
+        if shared.is_exported() {
+            self.tombstones.insert(shared.conn_id(), addr, None, connection::now());
+        } else if let Some(ack) = shared.final_ack() {
+            self.tombstones.insert(shared.conn_id(), addr, Some(ack), connection::now());
+        }
+    }
+
+    fn publish_stats(&self) {
+        let stats = ListenerStats {
+            connections: self.peers.len() - self.half_open,
+            half_open: self.half_open,
+            evicted: self.evicted,
+            dropped: self.dropped,
+            migrated: self.migrated,
+            unvalidated: self.unvalidated,
+            malformed: self.malformed,
+            truncated: self.truncated,
+            denied: self.denied,
+            tombstones: self.tombstones.len(),
+            tombstones_evicted: self.tombstones.evicted(),
+        };
+        *self.stats.lock().expect("listener stats poisoned") = stats;
+    }
+
+    fn dispatch(&mut self, segment: Segment, from: SocketAddr) {
+        let window = self.window();
+        let early_data = self.accepts_early_data();
+        let index = self.find(from, segment.conn_id(), segment.segment_type(), segment.seq());
+        // 携带未知连接 ID（例如轮换后没能登记的 ID）、来自只有一条连接的地址的段交给这条连接，由它决定是否接受
+        let lone = match (index, self.peers.at(from)) {
+            (None, [Peer::Open(shared)]) => Some(shared.clone()),
+            _ => None,
+        };
+        match index.and_then(|index| self.peers.get_mut(from, index)) {
+            // 握手在同一个数据报里完成，之后的段交给新连接
+            Some(Peer::Open(shared)) => {
+                if let Ok(datagram) = segment.encode() {
+                    shared.deliver(datagram.freeze());
+                }
+            }
+            Some(Peer::HalfOpen(handshake)) => {
+                // 重传的 SYN 以对端最偏好的算法编码，其余的段必须以协商出的算法编码
+                if segment.segment_type() != SegmentType::Syn && segment.checksum() != handshake.checksum {
+                    return;
+                }
+                // 随 SYN 到达的 0-RTT 数据完成握手，不接受时忽略，客户端在握手完成后重发它
+                if segment.options().early_data() && !early_data {
+                    return;
+                }
+                // 认证的握手只由签名的段推进；先到的数据说明最后的确认丢失了，重发 SYN-ACK 让对端重发它
+                if !handshake.auth.authenticates(&segment) {
+                    if matches!(segment.segment_type(), SegmentType::Data | SegmentType::Ping | SegmentType::Pong) {
+                        let reply = handshake.syn_ack(window, &self.config, self.instance);
+                        self.send(&reply, from);
+                    }
+                    return;
+                }
+                let Ok(transition) = handshake.state.on_segment(segment.segment_type()) else {
+                    return;
+                };
+                match transition.to {
+                    // 重传的 SYN：SYN-ACK 丢失，重新回应
+                    ConnState::SynReceived => {
+                        let reply = handshake.syn_ack(window, &self.config, self.instance);
+                        self.send(&reply, from);
+                    }
+                    ConnState::Established => self.complete(from, index.expect("found above"), segment),
+                    // 握手期间对端就放弃了
+                    _ => self.forget(from, index.expect("found above")),
+                }
+            }
+            None if segment.segment_type() == SegmentType::Syn && self.accepting.load(Ordering::Relaxed) => self.open(segment, from),
+            // SYN 没有登记半开握手（以 Retry 或 cookie 回应、backlog 已满、停止接受）：0-RTT 数据等客户端重发，
+            // 未验证的地址换不来任何回应
+            None if segment.options().early_data() => {}
+            // 以 cookie 回应过的握手在这里完成
+            None if self.config.syn_cookies != SynCookies::Never
+                && let Some((local_isn, peer_isn)) = self.cookies.validate(&segment, from, connection::now()) =>
+            {
+                self.complete_stateless(from, segment, local_isn, peer_isn);
+            }
+            None if let Some(shared) = &lone => {
+                if let Ok(datagram) = segment.encode() {
+                    shared.deliver(datagram.freeze());
+                }
+            }
+            // 不属于任何连接的段（包括停止接受后的 SYN）：告知对端连接不存在（不回应 Rst 本身，避免互相复位）。
+            // Rst 以该段的校验算法编码，对端的连接只接受协商出的算法
+            None if segment.segment_type() != SegmentType::Rst => {
+                let rst = Segment::builder(SegmentType::Rst).checksum(segment.checksum()).build().expect("rst segment is always valid");
+                self.send(&rst, from);
+            }
+            None => {}
+        }
+    }
+
+    // 收到新的 SYN：在半开握手数允许时登记并回应 SYN-ACK；否则按 `syn_cookies` 以 cookie 回应，或丢弃让对端重试。
+    // 没有共同的校验算法时仍回应 SYN-ACK（不登记任何状态），对端据其中的算法列表报告握手失败
+    fn open(&mut self, syn: Segment, from: SocketAddr) {
+        let now = connection::now();
+        self.expire_handshakes(now);
+        if !self.validate_address(&syn, from, now) {
+            return;
+        }
+        let Some(auth) = self.authenticate_syn(&syn, from) else {
+            return;
+        };
+        if !self.occupancy.has_room(from.ip()) || !self.budget.has_room(CONNECTION_OVERHEAD) {
+            self.refuse_busy(syn.checksum(), from);
+            return;
+        }
+        let Ok(peer_mss) = endpoint::peer_mss(&syn) else {
+            tracing::debug!(peer = %from, mss = ?syn.options().mss(), "refusing a SYN with an unusable mss");
+            self.refuse(CloseCode::PROTOCOL_ERROR, syn.checksum(), from);
+            return;
+        };
+        let Some(checksum) = checksum::negotiate(&syn.checksum_offer(), &self.config.checksums) else {
+            tracing::warn!(peer = %from, offered = ?syn.checksum_offer(), "no checksum algorithm in common");
+            let params = endpoint::local_params(&self.config, self.accepts_early_data());
+            let mut reply = endpoint::syn_ack(SeqNum::new(0), syn.seq(), self.window(), endpoint::advertised_mss(&self.config), &params, &self.config.checksums, self.config.checksums[0]);
+            reply.set_conn_id(self.fresh_conn_id());
+            reflect::stamp(&mut reply, self.instance);
+            auth.sign(&mut reply);
+            self.send(&reply, from);
+            return;
+        };
+        let Some(params) = self.negotiate(&syn, from, checksum) else {
+            self.refuse_params(syn.checksum(), from);
+            return;
+        };
+        let full = self.half_open >= self.config.backlog;
+        match self.config.syn_cookies {
+            SynCookies::Always => return self.open_stateless(syn, from, checksum, params, auth, now),
+            SynCookies::Overflow if full => return self.open_stateless(syn, from, checksum, params, auth, now),
+            _ if full => return,
+            _ => {}
+        }
+
+        let mut state = StateMachine::new();
+        if state.on_segment(SegmentType::Syn).is_err() {
+            return;
+        }
+        let handshake = HalfOpen {
+            state,
+            local_isn: endpoint::fresh_isn(),
+            peer_isn: syn.seq(),
+            conn_id: self.fresh_conn_id(),
+            checksum,
+            peer_mss,
+            params,
+            auth,
+            started_at: now,
+        };
+        let reply = handshake.syn_ack(self.window(), &self.config, self.instance);
+        self.peers.insert(from, Peer::HalfOpen(handshake));
+        self.half_open += 1;
+        self.send(&reply, from);
+    }
+
+    // 以 cookie 回应：本端 ISN 编码了握手的全部信息，不登记任何状态
+    fn open_stateless(&mut self, syn: Segment, from: SocketAddr, checksum: ChecksumAlgorithm, params: TransportParameters, auth: SynAuth, now: Instant) {
+        let conn_id = self.fresh_conn_id();
+        let local_isn = self.cookies.issue(from, syn.seq(), conn_id, now);
+        let mut reply = endpoint::syn_ack(local_isn, syn.seq(), self.window(), endpoint::advertised_mss(&self.config), &params, &self.config.checksums, checksum);
+        reply.set_conn_id(conn_id);
+        reflect::stamp(&mut reply, self.instance);
+        auth.sign(&mut reply);
+        self.send(&reply, from);
+    }
+
+    // 半开握手数达到 `retry_threshold` 时先验证对端地址：没有令牌的 SYN 换来一个 Retry，令牌无效的 SYN 被丢弃。
+    // 返回 false 时 SYN 已被处理
+    fn validate_address(&self, syn: &Segment, from: SocketAddr, now: Instant) -> bool {
+        if self.config.retry_threshold.is_none_or(|threshold| self.half_open < threshold) {
+            return true;
+        }
+        match syn.retry_token() {
+            Some(token) if self.retry.validate(token, from, now) => true,
+            Some(_) => {
+                tracing::debug!(peer = %from, "dropping a SYN with an invalid retry token");
+                self.metrics.on_invalid_token();
+                false
+            }
+            None => {
+                let retry = Segment::builder(SegmentType::Retry)
+                    .ack(syn.seq())
+                    .checksum(syn.checksum())
+                    .payload(self.retry.issue(from, now).to_vec())
+                    .build()
+                    .expect("retry segment is always valid");
+                self.metrics.on_retry();
+                self.send(&retry, from);
+                false
+            }
+        }
+    }
+
+    // 配置了密钥时校验新的 SYN 并为这次握手生成本端的 nonce。未签名的 SYN 来自没有密钥的对端，以 `AUTH_FAILED` 的 Rst 拒绝；
+    // 标签不对的 SYN 被篡改或伪造，丢弃并计数。返回 None 时 SYN 已被处理
+    #[cfg_attr(not(feature = "crypto"), allow(unused_variables))]
+    fn authenticate_syn(&self, syn: &Segment, from: SocketAddr) -> Option<SynAuth> {
+        #[cfg(feature = "crypto")]
+        if let Some(auth) = &self.auth {
+            return match auth.verify(syn) {
+                Ok((client, _)) => Some(SynAuth { keyed: Some((auth.clone(), client, HandshakeNonce::random())) }),
+                Err(_) if syn.auth_trailer().is_none() => {
+                    tracing::warn!(peer = %from, "refusing an unauthenticated handshake");
+                    self.refuse(CloseCode::AUTH_FAILED, syn.checksum(), from);
+                    None
+                }
+                Err(e) => {
+                    self.metrics.on_decode_error(&e, from);
+                    trace::decode_failed(&e, from, syn.conn_id());
+                    None
+                }
+            };
+        }
+        Some(SynAuth::default())
+    }
+
+    // 配置了密钥时以 cookie 完成的握手必须是签名的确认，双方的 nonce 取自它（cookie 不记录 nonce）。
+    // 因此数据段不能代替丢失的确认：对端重传数据直到超时，不像有状态的握手那样以 SYN-ACK 提示它重发确认
+    #[cfg_attr(not(feature = "crypto"), allow(unused_variables))]
+    fn authenticate_completion(&self, segment: &Segment) -> Option<SynAuth> {
+        #[cfg(feature = "crypto")]
+        if let Some(auth) = &self.auth {
+            return match auth.verify(segment) {
+                Ok((client, server)) if segment.segment_type() == SegmentType::Ack => Some(SynAuth { keyed: Some((auth.clone(), client, server)) }),
+                _ => None,
+            };
+        }
+        Some(SynAuth::default())
+    }
+
+    // cookie 通过校验：还原握手后与有状态的握手一样完成，协商出的校验算法、对端的 MSS 与参数取自完成握手的段（cookie 不记录它们，
+    // 只要求是本端接受的算法；以数据段完成时没有 MSS 与参数选项，按默认值与本端的参数）。期间连接 ID 已被占用时以 Rst 拒绝
+    fn complete_stateless(&mut self, from: SocketAddr, segment: Segment, local_isn: SeqNum, peer_isn: SeqNum) {
+        let Some(auth) = self.authenticate_completion(&segment) else {
+            return;
+        };
+        let conn_id = segment.conn_id();
+        let checksum = segment.checksum();
+        let Ok(peer_mss) = endpoint::peer_mss(&segment) else {
+            self.refuse(CloseCode::PROTOCOL_ERROR, checksum, from);
+            return;
+        };
+        let Some(params) = self.negotiate(&segment, from, checksum) else {
+            self.refuse_params(checksum, from);
+            return;
+        };
+        let mut state = StateMachine::new();
+        let established = state.on_segment(SegmentType::Syn).is_ok()
+            && state.on_segment(segment.segment_type()).is_ok_and(|transition| transition.to == ConnState::Established);
+        if !established
+            || !self.config.checksums.contains(&checksum)
+            || self.by_id.contains_key(&conn_id)
+            || self.tombstones.contains(conn_id, connection::now())
+        {
+            let rst = Segment::builder(SegmentType::Rst).checksum(checksum).build().expect("rst segment is always valid");
+            self.send(&rst, from);
+            return;
+        }
+        let handshake = HalfOpen { state, local_isn, peer_isn, conn_id, checksum, peer_mss, params, auth, started_at: connection::now() };
+        self.establish(from, handshake, segment);
+    }
+
+    // 握手完成：生成连接交给 accept，完成握手的段（可能是数据）交给新连接处理
+    fn complete(&mut self, from: SocketAddr, index: usize, segment: Segment) {
+        let Some(Peer::HalfOpen(handshake)) = self.peers.remove(from, index) else {
+            return;
+        };
+        self.half_open -= 1;
+        self.establish(from, handshake, segment);
+    }
+
+    fn establish(&mut self, from: SocketAddr, handshake: HalfOpen, segment: Segment) {
+        if !self.accepting.load(Ordering::Relaxed) {
+            let rst = Segment::builder(SegmentType::Rst).build().expect("rst segment is always valid");
+            self.send(&rst, from);
+            return;
+        }
+
+        let conn_id = handshake.conn_id;
+        if !self.occupancy.try_admit(from.ip()) {
+            self.refuse_busy(handshake.checksum, from);
+            return;
+        }
+        let Some(account) = self.budget.account(from, conn_id) else {
+            self.occupancy.release(from.ip());
+            self.refuse_busy(handshake.checksum, from);
+            return;
+        };
+        let span = trace::connection_span(Role::Server, from);
+        let connection = Connection::establish(
+            Outlet::Channel { tx: self.out.clone(), local: self.local, pool: self.pool.clone() },
+            from,
+            &self.config,
+            Handshake {
+                state: handshake.state,
+                local_isn: handshake.local_isn,
+                peer_isn: handshake.peer_isn,
+                conn_id,
+                initiator: false,
+                checksum: handshake.checksum,
+                peer_mss: handshake.peer_mss,
+                early_data: segment.options().early_data(),
+                params: handshake.params,
+                #[cfg(feature = "crypto")]
+                nonces: handshake.auth.nonces(),
+                #[cfg(feature = "crypto")]
+                reauth: None,
+            },
+            span.clone(),
+            Some(self.reaper.clone()),
+            Some(self.metrics.clone()),
+        );
+        let shared = connection.shared().clone();
+        shared.lock().set_memory_account(account);
+        // 待 accept 队列已满时放弃这个连接，对端的数据得不到确认，最终超时
+        if self.accept_tx.try_send((connection, from)).is_err() {
+            tracing::warn!(peer = %from, "accept queue full, abandoning connection");
+            self.occupancy.release(from.ip());
+            return;
+        }
+        self.admitted.insert(conn_id, from.ip());
+        trace::handshake_span(&span).in_scope(|| tracing::debug!(peer = %from, conn_id, "connection established"));
+        self.metrics.on_connection_opened();
+        if let Ok(datagram) = segment.encode() {
+            shared.deliver(datagram.freeze());
+        }
+        self.by_id.insert(conn_id, shared.clone());
+        self.peers.insert(from, Peer::Open(shared));
+    }
+
+    // 接续导出的连接：与握手完成时一样登记，只是不经过 accept 队列
+    fn adopt(&mut self, state: SessionState) -> Result<Connection, LinkError> {
+        let (from, conn_id) = (state.peer_addr(), state.conn_id());
+        if self.peers.contains(&from) || self.by_id.contains_key(&conn_id) || self.tombstones.contains(conn_id, connection::now()) {
+            return Err(SessionError::InUse.into());
+        }
+        if !self.occupancy.try_admit(from.ip()) {
+            return Err(LinkError::ServerBusy);
+        }
+        let Some(account) = self.budget.account(from, conn_id) else {
+            self.occupancy.release(from.ip());
+            return Err(LinkError::ServerBusy);
+        };
+        let span = trace::connection_span(Role::Server, from);
+        let outlet = Outlet::Channel { tx: self.out.clone(), local: self.local, pool: self.pool.clone() };
+        let connection = match Connection::adopt(outlet, state, &self.config, span.clone(), Some(self.reaper.clone()), Some(self.metrics.clone())) {
+            Ok(connection) => connection,
+            Err(e) => {
+                self.occupancy.release(from.ip());
+                return Err(e.into());
+            }
+        };
+        let shared = connection.shared().clone();
+        shared.lock().set_memory_account(account);
+        self.admitted.insert(conn_id, from.ip());
+        span.in_scope(|| tracing::info!(peer = %from, conn_id, "connection adopted"));
+        self.metrics.on_connection_opened();
+        self.by_id.insert(conn_id, shared.clone());
+        self.peers.insert(from, Peer::Open(shared));
+        Ok(connection)
+    }
+
+    // 配置了密钥时握手段都须签名，未签名的 0-RTT 数据不能完成握手
+    fn accepts_early_data(&self) -> bool {
+        #[cfg(feature = "crypto")]
+        if self.config.psk.is_some() {
+            return false;
+        }
+        self.config.accept_early_data
+    }
+
+    // 与客户端在握手段中提出的参数协商：先经 `ParamPolicy` 审查，审查的结果再与本端的、客户端提出的合并，
+    // 策略只能让参数更保守。对端没有携带参数（旧版本）时按本端的参数审查。返回 None 时策略拒绝了握手
+    fn negotiate(&self, segment: &Segment, from: SocketAddr, checksum: ChecksumAlgorithm) -> Option<TransportParameters> {
+        let local = endpoint::local_params(&self.config, self.accepts_early_data());
+        let proposed = segment.options().params().unwrap_or(local);
+        let policy = self.policy.borrow().0.clone();
+        let reviewed = match policy {
+            Some(policy) => policy.review(from, &proposed)?,
+            None => proposed,
+        };
+        Some(TransportParameters { checksum, ..local.merge(&reviewed).merge(&proposed) })
+    }
+
+    // 参数被策略拒绝：以 `PARAMETER_ERROR` 的 Rst 拒绝握手，Rst 以握手段的校验算法编码
+    fn refuse_params(&self, checksum: ChecksumAlgorithm, from: SocketAddr) {
+        tracing::debug!(peer = %from, "refusing a handshake: transport parameters rejected");
+        self.refuse(CloseCode::PARAMETER_ERROR, checksum, from);
+    }
+
+    // 连接数已达上限：以 `SERVER_BUSY` 的 Rst 拒绝握手，Rst 以握手段的校验算法编码
+    fn refuse_busy(&self, checksum: ChecksumAlgorithm, from: SocketAddr) {
+        tracing::debug!(peer = %from, "refusing a handshake: server busy");
+        self.metrics.on_busy();
+        self.refuse(CloseCode::SERVER_BUSY, checksum, from);
+    }
+
+    // 以携带 `code` 的 Rst 拒绝握手，Rst 以握手段的校验算法编码
+    fn refuse(&self, code: CloseCode, checksum: ChecksumAlgorithm, from: SocketAddr) {
+        let mut rst = endpoint::rst(Some(code));
+        rst.set_checksum(checksum);
+        self.send(&rst, from);
+    }
+
+    fn forget(&mut self, from: SocketAddr, index: usize) {
+        if let Some(Peer::HalfOpen(_)) = self.peers.remove(from, index) {
+            self.half_open -= 1;
+        }
+    }
+
+    // 清理超时未完成的半开握手
+    fn expire_handshakes(&mut self, now: Instant) {
+        let timeout = self.config.handshake_timeout;
+        let before = self.peers.len();
+        self.peers.retain(|_, peer| match peer {
+            Peer::HalfOpen(handshake) => now.saturating_duration_since(handshake.started_at) < timeout,
+            Peer::Open(_) => true,
+        });
+        self.half_open -= before - self.peers.len();
+    }
+
+    // 非零且未被已建立连接或墓碑占用的连接 ID；0 表示握手尚未完成
+    fn fresh_conn_id(&self) -> u32 {
+        let now = connection::now();
+        loop {
+            let id = RandomState::new().build_hasher().finish() as u32;
+            if id != 0 && !self.by_id.contains_key(&id) && !self.tombstones.contains(id, now) {
+                return id;
+            }
+        }
+    }
+
+    fn window(&self) -> u32 {
+        u32::try_from(self.config.recv_window).unwrap_or(u32::MAX)
+    }
+
+    // 握手与复位回应经发送任务发出；队列已满时丢弃，由对端重试，接收循环不等待。
+    // 未签名的段在这里打上实例 ID，签名的 SYN-ACK 在签名之前已经打上
+    fn send(&self, segment: &Segment, to: SocketAddr) {
+        let mut stamped;
+        let segment = match segment.options().instance().is_none() && segment.auth_trailer().is_none() {
+            true => {
+                stamped = segment.clone();
+                reflect::stamp(&mut stamped, self.instance);
+                &stamped
+            }
+            false => segment,
+        };
+        if trace::enabled() {
+            trace::segment(Direction::Outbound, segment, to);
+        }
+        let mut datagram = self.pool.get();
+        if segment.encode_into(&mut datagram).is_ok() {
+            let _ = self.out.try_send((datagram, to, self.config.mss, None));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::segment::SegmentType;
+    use crate::transport::MemoryTransport;
+
+    #[tokio::test]
+    async fn test_send_loop_coalesces_queued_acks() {
+        let (server, client) = MemoryTransport::pair(256, 256);
+        let to = client.local_addr().unwrap();
+        let config = LinkConfig::default();
+        let metrics = Arc::new(Metrics::default());
+        let pool = Arc::new(BufferPool::new(&config));
+
+        // 100 个确认在发送任务取出之前就已排队
+        let (out, outgoing) = mpsc::channel(OUTBOUND_QUEUE);
+        for n in 0..100u64 {
+            let ack = Segment::builder(SegmentType::Ack).ack(n).window(64).build().unwrap();
+            out.try_send((ack.encode().unwrap(), to, config.mss, None)).unwrap();
+        }
+        drop(out);
+        let socket = Arc::new(LinkSocket::new(server, &config));
+        send_loop(socket, outgoing, Batcher::new(config.mss, config.batch_window), metrics.clone(), None, pool).await;
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.segments_sent, 100);
+        assert!(snapshot.datagrams_sent <= 5, "{} datagrams", snapshot.datagrams_sent);
+        assert!(snapshot.datagrams_per_segment() <= 0.05);
+        let mut buf = vec![0u8; 65536];
+        let mut acks = 0;
+        for _ in 0..snapshot.datagrams_sent {
+            let (len, _) = client.recv_from(&mut buf).await.unwrap();
+            assert!(len <= config.mss);
+            acks += batch::segment_count(&buf[..len]);
+        }
+        assert_eq!(acks, 100);
+    }
+}
@@ -1,19 +1,520 @@
-use tokio::net::UdpSocket;
+use link_rs::capture::{Capture, CaptureSink, PcapWriter, Tap};
+use link_rs::cli;
+use link_rs::config::LinkConfig;
+use link_rs::error;
+use link_rs::fault::FaultyTransport;
+use link_rs::pool::Scratch;
+use link_rs::reflect::{self, RepeatLimiter};
+use link_rs::replay::RecordWriter;
+use link_rs::server::{DiscardHandler, EchoHandler, Handler, Server};
+use link_rs::socket;
+use link_rs::trace::Direction;
+use link_rs::transport::Transport;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::task::JoinSet;
+use tracing::Instrument;
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str = "\
+用法: link_rs [选项]
+
+选项:
+    --bind <addr:port>      监听地址，IPv6 地址写作 [::1]:8080 [默认: 127.0.0.1:8080]
+    --dual-stack            绑定 IPv6 地址时同时接收 IPv4 对端
+    --mode <mode>           echo: 原样回显 UDP 数据报；protocol: 以连接协议回显消息；discard: 以连接协议接收并丢弃消息，
+                            用于压测 [默认: protocol]
+    --max-payload <bytes>   单个数据报的最大数据体 [默认: 1168]
+    --recv-buffer <bytes>   接收缓冲区大小，放不下的数据报被截断、计数后丢弃 [默认: 65536]
+    --echo-repeat-limit <n> echo 模式下同一个数据报每秒回显给同一个对端的次数上限，超出的丢弃，0 表示不限 [默认: 64]
+    --workers <n>           以 SO_REUSEPORT 绑定的接收套接字数，不支持的平台只用一个 [默认: 1]
+    --syn-cookies <mode>    never、overflow（半开握手数满时）或 always，以无状态的 cookie 回应 SYN [默认: never]
+    --metrics-interval <s>  协议模式下输出指标摘要的间隔（秒），0 表示不输出 [默认: 10]
+    --capture <file.pcap>   把收发的每个数据报写入 pcap 文件，退出时刷新
+    --record <file>         把收到的每个数据报追加到记录文件，供 link-replay 重放；不能与 --capture 同时使用
+    --chaos <spec>          注入故障，如 loss=0.05,dup=0.01,reorder=0.02,delay=20ms±10ms,seed=7；收发两个方向各自判定；
+                            mtu=1100 丢弃更大的数据报，nat=30s 在对端静默这么久后更换它的地址（模拟 NAT）
+    --config <file.toml>    从 TOML 文件读取参数；LINK_* 环境变量覆盖文件，命令行选项覆盖两者
+    -h, --help              显示本帮助";
+
+/// 服务器的运行方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Echo,       // 不经连接协议，直接回显数据报
+    Protocol,   // 接受连接，回显每条消息
+    Discard,    // 接受连接，丢弃每条消息
+}
+
+/// 解析后的命令行参数
+#[derive(Debug)]
+struct Args {
+    bind: SocketAddr,
+    mode: Mode,
+    config: LinkConfig,  // --max-payload 折算为 mss
+    capture: Option<PathBuf>,
+    record: Option<PathBuf>,
+    echo_repeat_limit: u32,  // 见 `reflect::RepeatLimiter`
+}
+
+// 以 `config` 为基础解析命令行参数（不含程序名）；`--help` 返回 None
+fn parse_args(args: impl IntoIterator<Item = String>, config: LinkConfig) -> Result<Option<Args>, String> {
+    let mut parsed = Args { bind: SocketAddr::from(([127, 0, 0, 1], 8080)), mode: Mode::Protocol, config, capture: None, record: None, echo_repeat_limit: reflect::DEFAULT_REPEAT_LIMIT };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} requires a value", arg));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--bind" => {
+                let value = value()?;
+                parsed.bind = value.parse().map_err(|_| format!("invalid bind address '{}', expected addr:port", value))?;
+            }
+            "--mode" => {
+                parsed.mode = match value()?.as_str() {
+                    "echo" => Mode::Echo,
+                    "protocol" => Mode::Protocol,
+                    "discard" => Mode::Discard,
+                    other => return Err(format!("invalid mode '{}', expected echo, protocol or discard", other)),
+                };
+            }
+            "--capture" => parsed.capture = Some(PathBuf::from(value()?)),
+            "--record" => parsed.record = Some(PathBuf::from(value()?)),
+            "--echo-repeat-limit" => {
+                let value = value()?;
+                parsed.echo_repeat_limit = value.parse().map_err(|_| format!("invalid echo repeat limit '{}', expected a count", value))?;
+            }
+            other => {
+                if !cli::config_flag(&mut parsed.config, other, &mut value)? {
+                    return Err(format!("unknown argument '{}'", other));
+                }
+            }
+        }
+    }
+    if parsed.capture.is_some() && parsed.record.is_some() {
+        return Err("--capture and --record cannot be combined".to_string());
+    }
+    parsed.config.validate().map_err(|e| e.to_string())?;
+    Ok(Some(parsed))
+}
+
+// 回显循环使用的数据报套接字，测试中替换为注入错误的实现
+trait DatagramSocket {
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)>;
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize>;
+}
+
+// UDP 套接字与包在它外面的 `FaultyTransport`
+impl<T: Transport> DatagramSocket for T {
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        Transport::recv_from(self, buf).await
+    }
+
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        Transport::send_to(self, buf, target).await
+    }
+}
+
+// 原样回显收到的数据报。同一个数据报反复回显给同一个对端的次数受 `repeats` 限制，
+// 两台互相指向的回显服务器（或把数据报发回来的对端）之间的环路在超出上限后断开
+struct Echo<S> {
+    socket: S,
+    scratch: Scratch,   // 任务的暂存缓冲，数据报读入其中后原地回显，不逐个分配
+    recv_buffer: usize,
+    tap: Option<Tap>,
+    repeats: RepeatLimiter,
+    errors: u64,    // 被记录后跳过的单个数据报错误
+    too_large: u64, // 其中本机以过大拒绝回显的数据报
+    mss: usize,     // 报告过大的数据报时给出的有效 MSS
+    truncated: u64, // 填满接收缓冲区、可能被截断而未回显的数据报
+    suppressed: u64,    // 超出重复回显上限而丢弃的数据报
+}
+
+impl<S: DatagramSocket> Echo<S> {
+    fn new(socket: S, config: &LinkConfig, tap: Option<Tap>, repeat_limit: u32) -> Self {
+        Self {
+            socket,
+            scratch: Scratch::new(config.scratch_high_water),
+            recv_buffer: config.recv_buffer,
+            tap,
+            repeats: RepeatLimiter::new(repeat_limit, reflect::DEFAULT_REPEAT_WINDOW),
+            errors: 0,
+            too_large: 0,
+            mss: config.mss,
+            truncated: 0,
+            suppressed: 0,
+        }
+    }
+
+    // 一直回显，直到遇到无法恢复的套接字错误
+    async fn run(&mut self) -> io::Error {
+        loop {
+            if let Err(e) = self.serve_one().await {
+                return e;
+            }
+        }
+    }
+
+    // 处理一个数据报；单个数据报的错误记录后返回 Ok，只有无法恢复的错误返回 Err
+    async fn serve_one(&mut self) -> io::Result<()> {
+        let now = Instant::now();
+        self.scratch.trim(now);
+        let (len, peer) = match self.socket.recv_from(self.scratch.space(self.recv_buffer, now)).await {
+            Ok(received) => received,
+            Err(e) => return self.skip(e, None),
+        };
+        let datagram = &self.scratch.space(self.recv_buffer, now)[..len];
+        if let Some(tap) = &self.tap {
+            tap.record(Direction::Inbound, peer, datagram);
+        }
+        // 原始数据报没有长度前缀，只能把填满缓冲区的数据报视为被截断
+        if len == self.recv_buffer {
+            self.truncated += 1;
+            tracing::warn!(%peer, truncated = self.truncated, "数据报超出接收缓冲区，已丢弃");
+            return Ok(());
+        }
+        if !self.repeats.admit(peer, datagram, now) {
+            self.suppressed += 1;
+            // 环路中每个窗口都有一批被丢弃，按 2 的幂次记录
+            if self.suppressed.is_power_of_two() {
+                tracing::warn!(%peer, suppressed = self.suppressed, "同一个数据报回显过于频繁，可能形成了环路，已丢弃");
+            }
+            return Ok(());
+        }
+        match self.socket.send_to(datagram, peer).await {
+            Ok(_) => {
+                if let Some(tap) = &self.tap {
+                    tap.record(Direction::Outbound, peer, datagram);
+                }
+                Ok(())
+            }
+            // 原样回显不能分片：报告数据报的大小，由发送方缩小它
+            Err(e) if error::is_too_large(&e) => {
+                self.errors += 1;
+                self.too_large += 1;
+                let e = error::send_error(e, len, self.mss);
+                tracing::warn!(%peer, errors = self.errors, error = %e, "回显失败");
+                Ok(())
+            }
+            Err(e) => self.skip(e, Some(peer)),
+        }
+    }
+
+    fn skip(&mut self, e: io::Error, peer: Option<SocketAddr>) -> io::Result<()> {
+        if error::is_fatal(&e) {
+            return Err(e);
+        }
+        self.errors += 1;
+        match peer {
+            Some(peer) => tracing::warn!(%peer, errors = self.errors, error = %e, "发送失败"),
+            // 接收错误不带来源地址（如 Windows 上上一个对端的 ICMP 不可达）
+            None => tracing::warn!(errors = self.errors, error = %e, "接收失败"),
+        }
+        Ok(())
+    }
+}
+
+// 实际绑定的套接字少于请求的数量时提示，服务照常运行
+fn warn_workers(requested: usize, bound: usize) {
+    if bound < requested {
+        tracing::warn!(requested, bound, "当前平台不支持 SO_REUSEPORT 分流，以较少的接收套接字运行");
+    }
+}
+
+// 每个接收套接字一个回显任务，任一套接字失效或收到退出信号时退出
+async fn run_echo(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    let sockets = socket::bind_workers(args.bind, &args.config).await?;
+    tracing::info!(local = %sockets[0].local_addr()?, "UDP 回显服务器启动");
+    warn_workers(args.config.workers, sockets.len());
+    let mut echoes = JoinSet::new();
+    for socket in sockets {
+        let local = socket.local_addr()?;
+        let tap = args.config.capture.as_ref().map(|capture| capture.tap(local));
+        match &args.config.faults {
+            Some(faults) => {
+                let mut echo = Echo::new(FaultyTransport::new(socket, faults, args.config.recv_buffer), &args.config, tap, args.echo_repeat_limit);
+                echoes.spawn(async move { echo.run().await });
+            }
+            None => {
+                let mut echo = Echo::new(socket, &args.config, tap, args.echo_repeat_limit);
+                echoes.spawn(async move { echo.run().await });
+            }
+        }
+    }
+    tokio::select! {
+        fatal = echoes.join_next() => Err(fatal.expect("at least one socket is bound")?.into()),
+        () = shutdown_signal() => Ok(()),
+    }
+}
+
+async fn run_protocol(args: Args, handler: impl Handler) -> Result<(), Box<dyn std::error::Error>> {
+    let requested = args.config.workers;
+    let server = Server::builder().config(args.config).handler(handler).bind(args.bind).await?;
+    tracing::info!(local = %server.local_addr()?, "服务器启动");
+    warn_workers(requested, server.listener().workers());
+    let run = server.run();
+    tokio::pin!(run);
+    tokio::select! {
+        // 只有监听器失效时提前结束
+        result = &mut run => {
+            result?;
+            return Ok(());
+        }
+        () = shutdown_signal() => {}
+    }
+    tracing::info!("收到退出信号，开始关闭");
+    server.shutdown();
+    run.await?;
+    Ok(())
+}
+
+// ctrl-c，以及 unix 上的 SIGTERM
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let socket = UdpSocket::bind("127.0.0.1:8080").await?;
-    println!("异步UDP服务器启动");
+    let raw: Vec<String> = std::env::args().skip(1).collect();
+    let mut args = match cli::load_config(&raw).and_then(|config| parse_args(raw, config)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("错误: {}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    // RUST_LOG 控制输出，例如 RUST_LOG=link=debug 打开逐段事件；未设置时只输出 info 及以上
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+
+    let writer = match (&args.capture, &args.record) {
+        (Some(path), _) => Some(Writer::Pcap(Arc::new(PcapWriter::create(path).await.map_err(|e| format!("cannot create capture file {}: {}", path.display(), e))?))),
+        (_, Some(path)) => Some(Writer::Record(Arc::new(RecordWriter::create(path).await.map_err(|e| format!("cannot create record file {}: {}", path.display(), e))?))),
+        _ => None,
+    };
+    if let Some(writer) = &writer {
+        args.config.capture = Some(Capture::new(writer.sink()));
+    }
+
+    let span = tracing::info_span!("server", bind = %args.bind, mode = ?args.mode);
+    let result = match args.mode {
+        Mode::Echo => run_echo(args).instrument(span).await,
+        Mode::Protocol => run_protocol(args, EchoHandler).instrument(span).await,
+        Mode::Discard => run_protocol(args, DiscardHandler).instrument(span).await,
+    };
+    if let Some(writer) = writer {
+        let dropped = writer.flush().await;
+        if dropped > 0 {
+            tracing::warn!(dropped, "抓包队列已满，部分数据报未写入");
+        }
+    }
+    result
+}
 
-    let mut buf = [0u8; 1024];
+// --capture 或 --record 的写入器
+enum Writer {
+    Pcap(Arc<PcapWriter>),
+    Record(Arc<RecordWriter>),
+}
 
-    loop {
-        // 异步recv_from：非阻塞
-        let (len, src_addr) = socket.recv_from(&mut buf).await?;
-        let msg = String::from_utf8_lossy(&buf[..len]);
-        println!("收到: {} from {}", msg, src_addr);
+impl Writer {
+    fn sink(&self) -> Arc<dyn CaptureSink> {
+        match self {
+            Writer::Pcap(writer) => writer.clone(),
+            Writer::Record(writer) => writer.clone(),
+        }
+    }
+
+    // 等待记录全部写入，返回队列已满而丢弃的记录数
+    async fn flush(&self) -> u64 {
+        match self {
+            Writer::Pcap(writer) => {
+                writer.flush().await;
+                writer.dropped()
+            }
+            Writer::Record(writer) => {
+                writer.flush().await;
+                writer.dropped()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use link_rs::cookie::SynCookies;
+    use link_rs::segment::Segment;
+    use link_rs::transport::MemoryNetwork;
+    use std::time::Duration;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+
+    type Script = VecDeque<io::Result<(&'static [u8], SocketAddr)>>;
+
+    // 按脚本返回接收结果的套接字；脚本用完后报告描述符失效
+    struct Scripted {
+        incoming: RefCell<Script>,
+        sent: RefCell<Vec<(Vec<u8>, SocketAddr)>>,
+        unreachable: SocketAddr,    // 发往这个地址时报告数据报过大
+    }
+
+    impl DatagramSocket for Scripted {
+        async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+            let (datagram, from) = self.incoming.borrow_mut().pop_front().unwrap_or_else(|| Err(io::Error::from_raw_os_error(9)))?;
+            // 与内核一样截断放不下的部分
+            let len = datagram.len().min(buf.len());
+            buf[..len].copy_from_slice(&datagram[..len]);
+            Ok((len, from))
+        }
+
+        async fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+            if target == self.unreachable {
+                // EMSGSIZE
+                return Err(io::Error::from_raw_os_error(90));
+            }
+            self.sent.borrow_mut().push((buf.to_vec(), target));
+            Ok(buf.len())
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_echo_survives_transient_errors() {
+        let (a, b) = ("10.0.0.2:2".parse().unwrap(), "10.0.0.3:3".parse().unwrap());
+        let socket = Scripted {
+            incoming: RefCell::new(VecDeque::from([
+                Ok((&b"one"[..], a)),
+                Err(io::Error::from(io::ErrorKind::ConnectionReset)),
+                Ok((&b"too big"[..], b)),
+                Ok((&[0u8; 2048][..], a)),
+                Ok((&b"two"[..], a)),
+            ])),
+            sent: RefCell::default(),
+            unreachable: b,
+        };
+        let mut echo = Echo::new(socket, &LinkConfig { recv_buffer: 1024, ..LinkConfig::default() }, None, reflect::DEFAULT_REPEAT_LIMIT);
+        let fatal = echo.run().await;
+        assert_eq!(fatal.raw_os_error(), Some(9));
+        assert_eq!(echo.errors, 2);
+        assert_eq!(echo.too_large, 1);
+        assert_eq!(echo.truncated, 1);
+        assert_eq!(*echo.socket.sent.borrow(), vec![(b"one".to_vec(), a), (b"two".to_vec(), a)]);
+    }
+
+    #[tokio::test]
+    async fn test_echo_loop_dies_out() {
+        let network = MemoryNetwork::new();
+        let (a_addr, b_addr): (SocketAddr, SocketAddr) = ("10.0.0.2:2".parse().unwrap(), "10.0.0.3:3".parse().unwrap());
+        let config = LinkConfig::default();
+        let mut a = Echo::new(network.bind(a_addr).unwrap(), &config, None, 8);
+        let mut b = Echo::new(network.bind(b_addr).unwrap(), &config, None, 8);
+        // 两台回显服务器互相指向：一个数据报在它们之间来回回显
+        DatagramSocket::send_to(&a.socket, b"loop", b_addr).await.unwrap();
+        let mut rounds = 0;
+        loop {
+            let mut progressed = false;
+            for echo in [&mut b, &mut a] {
+                if let Ok(served) = tokio::time::timeout(Duration::from_millis(50), echo.serve_one()).await {
+                    served.unwrap();
+                    progressed = true;
+                }
+            }
+            if !progressed {
+                break;
+            }
+            rounds += 1;
+            assert!(rounds <= 20, "the echo loop never died out");
+        }
+        // b 先收到数据报，第 9 次时超出上限，环路断开
+        assert_eq!((b.suppressed, a.suppressed), (1, 0));
+        assert_eq!(rounds, 9);
+    }
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()), LinkConfig::default())
+    }
+
+    #[test]
+    fn test_defaults() {
+        let args = parse(&[]).unwrap().unwrap();
+        assert_eq!(args.bind, "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(args.mode, Mode::Protocol);
+        assert_eq!(args.config.mss, LinkConfig::default().mss);
+        assert!(!args.config.dual_stack);
+    }
+
+    #[test]
+    fn test_values_flow_into_config() {
+        let args = parse(&["--bind", "0.0.0.0:9000", "--mode", "echo", "--max-payload", "500", "--recv-buffer", "1024"]).unwrap().unwrap();
+        assert_eq!(args.config.recv_buffer, 1024);
+        assert_eq!(args.bind, "0.0.0.0:9000".parse().unwrap());
+        assert_eq!(args.mode, Mode::Echo);
+        assert_eq!(parse(&["--mode", "discard"]).unwrap().unwrap().mode, Mode::Discard);
+        assert_eq!(args.config.mss, 500 + Segment::FIXED_HEADER_LEN);
+        let args = parse(&["--bind", "[::]:9000", "--dual-stack"]).unwrap().unwrap();
+        assert_eq!(args.bind, "[::]:9000".parse().unwrap());
+        assert!(args.config.dual_stack);
+        assert_eq!(parse(&["--workers", "4"]).unwrap().unwrap().config.workers, 4);
+        assert_eq!(parse(&["--syn-cookies", "overflow"]).unwrap().unwrap().config.syn_cookies, SynCookies::Overflow);
+        assert_eq!(parse(&["--capture", "out.pcap"]).unwrap().unwrap().capture, Some(PathBuf::from("out.pcap")));
+        assert_eq!(parse(&["--record", "session.rec"]).unwrap().unwrap().record, Some(PathBuf::from("session.rec")));
+        assert!(parse(&["--record", "session.rec", "--capture", "out.pcap"]).unwrap_err().contains("cannot be combined"));
+        assert!(parse(&["--metrics-interval", "0"]).unwrap().unwrap().config.metrics_interval.is_zero());
+        let faults = parse(&["--chaos", "loss=0.05,seed=9"]).unwrap().unwrap().config.faults.unwrap();
+        assert_eq!((faults.loss, faults.seed), (0.05, 9));
+        assert!(parse(&["--mode", "protocol", "--help"]).unwrap().is_none());
+        assert_eq!(parse(&[]).unwrap().unwrap().echo_repeat_limit, reflect::DEFAULT_REPEAT_LIMIT);
+        assert_eq!(parse(&["--echo-repeat-limit", "0"]).unwrap().unwrap().echo_repeat_limit, 0);
+        // 命令行选项覆盖文件与环境变量中的值，其余保留
+        let base = LinkConfig { linger: Duration::from_secs(3), ..LinkConfig::default() };
+        let args = parse_args(["--workers", "2", "--config", "ignored.toml"].map(String::from), base).unwrap().unwrap();
+        assert_eq!((args.config.workers, args.config.linger), (2, Duration::from_secs(3)));
+    }
 
-        // 异步send_to
-        socket.send_to(&buf[..len], src_addr).await?;
+    #[test]
+    fn test_malformed_arguments() {
+        assert!(parse(&["--bind", "localhost"]).unwrap_err().contains("invalid bind address"));
+        assert!(parse(&["--bind", "127.0.0.1:99999"]).is_err());
+        assert!(parse(&["--bind", "::1:8080"]).is_err());
+        assert!(parse(&["--bind"]).unwrap_err().contains("requires a value"));
+        assert!(parse(&["--mode", "tcp"]).unwrap_err().contains("invalid mode"));
+        assert!(parse(&["--max-payload", "0"]).is_err());
+        assert!(parse(&["--max-payload", "70000"]).is_err());
+        assert!(parse(&["--max-payload", "abc"]).is_err());
+        assert!(parse(&["--recv-buffer", "16"]).unwrap_err().contains("invalid receive buffer"));
+        assert!(parse(&["--workers", "0"]).unwrap_err().contains("invalid worker count"));
+        assert!(parse(&["--syn-cookies", "yes"]).unwrap_err().contains("invalid syn cookie mode"));
+        assert!(parse(&["--metrics-interval", "1.5"]).unwrap_err().contains("invalid metrics interval"));
+        assert!(parse(&["--chaos", "loss=1.5"]).unwrap_err().contains("invalid loss"));
+        assert!(parse(&["--echo-repeat-limit", "-1"]).unwrap_err().contains("invalid echo repeat limit"));
+        assert!(cli::load_config(&["--config".to_string()]).unwrap_err().contains("requires a value"));
+        assert!(cli::load_config(&["--config".to_string(), "/nonexistent.toml".to_string()]).unwrap_err().contains("cannot read config file"));
+        let contradictory = LinkConfig { max_ack_delay: Duration::from_secs(1), ..LinkConfig::default() };
+        assert!(parse_args(Vec::new(), contradictory).unwrap_err().contains("max_ack_delay"));
+        assert!(parse(&["--port", "80"]).unwrap_err().contains("unknown argument"));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,548 @@
+//! 服务器级指标
+//! `Metrics` 是一组原子计数器（relaxed），由监听器的分发与发送任务、以及它接受的连接在数据路径上直接累加，
+//! 同一个监听器的所有接收套接字共用一份。`snapshot` 逐项读出，`MetricsSnapshot::summary` 把两次快照之差
+//! 折算成速率，供定期的摘要日志使用。
+//!
+//! 分发任务收到的每个数据报恰好落入一类：截断、被墓碑吸收、交给连接、连接的入站队列已满而丢弃、
+//! 由监听器自己解码处理（握手与 Rst），或监听器无法解析（计入 `listener_decode_errors`）。被反射回来的本监听器的段
+//! 在解码后丢弃，所在的数据报仍计入 handled，段数另计于 `reflected`。
+//!
+//! 解码错误按 `SegmentError` 的变体逐个计数（`DecodeErrorKind`），同时按来源地址记在一张有界的表中：
+//! 至多 `PEER_TABLE` 个对端，满了以后淘汰最久没有出错的一个。`Metrics::decode_report` 汇总成 `DecodeErrorReport`，
+//! 列出各类错误的次数与出错最多的 `REPORT_PEERS` 个对端，与第三方实现互通出问题时据此找到是哪一类错误、来自谁。
+//!
+//! 接收任务每次被唤醒后不只处理等到的那个数据报，还把此刻已经排队的一并取走（至多 `LinkConfig::recv_batch` 个），
+//! 每次取走的个数记入直方图 `DrainHistogram`：突发到达时它偏向大的桶，说明一次唤醒就清空了积压。
+
+use crate::segment::SegmentError;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 按来源记录解码错误的对端数上限
+pub const PEER_TABLE: usize = 128;
+
+/// `DecodeErrorReport` 列出的对端数
+pub const REPORT_PEERS: usize = 16;
+
+/// `DrainHistogram` 的桶数
+pub const DRAIN_BUCKETS: usize = 8;
+
+/// 解码错误的种类，与 `SegmentError` 的变体一一对应
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DecodeErrorKind {
+    TooShort,
+    InvalidTotalLen,
+    UnknownFrameType,
+    TotalLenOverflow,
+    TotalLenTooLarge,
+    ReservedFlags,
+    InvalidSack,
+    UnknownChecksum,
+    UnexpectedChecksum,
+    ChecksumMismatch,
+    DecryptFailed,
+    AuthFailed,
+    BadOption,
+    UnexpectedPayload,
+    InvalidStats,
+}
+
+impl DecodeErrorKind {
+    /// 种类数
+    pub const COUNT: usize = 15;
+
+    /// 全部种类，按声明顺序
+    pub const ALL: [DecodeErrorKind; Self::COUNT] = [
+        DecodeErrorKind::TooShort,
+        DecodeErrorKind::InvalidTotalLen,
+        DecodeErrorKind::UnknownFrameType,
+        DecodeErrorKind::TotalLenOverflow,
+        DecodeErrorKind::TotalLenTooLarge,
+        DecodeErrorKind::ReservedFlags,
+        DecodeErrorKind::InvalidSack,
+        DecodeErrorKind::UnknownChecksum,
+        DecodeErrorKind::UnexpectedChecksum,
+        DecodeErrorKind::ChecksumMismatch,
+        DecodeErrorKind::DecryptFailed,
+        DecodeErrorKind::AuthFailed,
+        DecodeErrorKind::BadOption,
+        DecodeErrorKind::UnexpectedPayload,
+        DecodeErrorKind::InvalidStats,
+    ];
+
+    pub fn of(error: &SegmentError) -> Self {
+        match error {
+            SegmentError::TooShort => DecodeErrorKind::TooShort,
+            SegmentError::InvalidTotalLen(..) => DecodeErrorKind::InvalidTotalLen,
+            SegmentError::UnknownFrameType(_) => DecodeErrorKind::UnknownFrameType,
+            SegmentError::TotalLenOverflow(_) => DecodeErrorKind::TotalLenOverflow,
+            SegmentError::TotalLenTooLarge(..) => DecodeErrorKind::TotalLenTooLarge,
+            SegmentError::ReservedFlags(_) => DecodeErrorKind::ReservedFlags,
+            SegmentError::InvalidSack(_) => DecodeErrorKind::InvalidSack,
+            SegmentError::UnknownChecksum(_) => DecodeErrorKind::UnknownChecksum,
+            SegmentError::UnexpectedChecksum { .. } => DecodeErrorKind::UnexpectedChecksum,
+            SegmentError::ChecksumMismatch { .. } => DecodeErrorKind::ChecksumMismatch,
+            SegmentError::DecryptFailed => DecodeErrorKind::DecryptFailed,
+            SegmentError::AuthFailed => DecodeErrorKind::AuthFailed,
+            SegmentError::BadOption => DecodeErrorKind::BadOption,
+            SegmentError::UnexpectedPayload { .. } => DecodeErrorKind::UnexpectedPayload,
+            SegmentError::InvalidStats { .. } => DecodeErrorKind::InvalidStats,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+// 非零的计数，按次数从多到少，次数相同时按种类的声明顺序
+fn nonzero(counts: &[u64; DecodeErrorKind::COUNT]) -> Vec<(DecodeErrorKind, u64)> {
+    let mut kinds: Vec<_> = DecodeErrorKind::ALL.into_iter().map(|kind| (kind, counts[kind.index()])).filter(|&(_, n)| n > 0).collect();
+    kinds.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    kinds
+}
+
+/// 按错误类别统计的解码失败次数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeErrors {
+    pub too_short: u64,         // 不足一个段头，或最后一个段不完整
+    pub invalid_length: u64,    // 长度前缀小于段头或超过解码上限
+    pub unknown_type: u64,
+    pub reserved_flags: u64,
+    pub invalid_sack: u64,
+    pub bad_option: u64,        // 段头选项区格式错误
+    pub unexpected_payload: u64, // 数据体长度不符合段类型的规则
+    pub checksum: u64,          // 校验和不符、未知的校验算法，或不是连接协商出的算法
+    pub decrypt: u64,           // 加密的数据段或握手段未通过认证，或加密与否与连接不符
+    kinds: [u64; DecodeErrorKind::COUNT],   // 逐个种类的计数，上面的各类由它们归并
+}
+
+impl DecodeErrors {
+    fn from_kinds(kinds: [u64; DecodeErrorKind::COUNT]) -> Self {
+        let count = |kind: DecodeErrorKind| kinds[kind.index()];
+        DecodeErrors {
+            too_short: count(DecodeErrorKind::TooShort),
+            invalid_length: count(DecodeErrorKind::InvalidTotalLen) + count(DecodeErrorKind::TotalLenOverflow) + count(DecodeErrorKind::TotalLenTooLarge),
+            unknown_type: count(DecodeErrorKind::UnknownFrameType),
+            reserved_flags: count(DecodeErrorKind::ReservedFlags),
+            invalid_sack: count(DecodeErrorKind::InvalidSack),
+            bad_option: count(DecodeErrorKind::BadOption),
+            unexpected_payload: count(DecodeErrorKind::UnexpectedPayload) + count(DecodeErrorKind::InvalidStats),
+            checksum: count(DecodeErrorKind::UnknownChecksum) + count(DecodeErrorKind::UnexpectedChecksum) + count(DecodeErrorKind::ChecksumMismatch),
+            decrypt: count(DecodeErrorKind::DecryptFailed) + count(DecodeErrorKind::AuthFailed),
+            kinds,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.kinds.iter().sum()
+    }
+
+    /// 某一种错误的次数
+    pub fn get(&self, kind: DecodeErrorKind) -> u64 {
+        self.kinds[kind.index()]
+    }
+
+    /// 出现过的种类与次数，按次数从多到少
+    pub fn by_kind(&self) -> Vec<(DecodeErrorKind, u64)> {
+        nonzero(&self.kinds)
+    }
+
+    /// 自 `previous` 以来 `elapsed` 内速率超过 `rate`（次/秒）的种类与它们的速率
+    pub fn above_rate(&self, previous: &DecodeErrors, elapsed: Duration, rate: f64) -> Vec<(DecodeErrorKind, f64)> {
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        DecodeErrorKind::ALL
+            .into_iter()
+            .map(|kind| (kind, self.get(kind).saturating_sub(previous.get(kind)) as f64 / secs))
+            .filter(|&(_, observed)| observed > rate)
+            .collect()
+    }
+}
+
+/// 一个对端的解码错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PeerDecodeErrors {
+    pub peer: SocketAddr,
+    pub total: u64,
+    pub kinds: Vec<(DecodeErrorKind, u64)>, // 按次数从多到少
+}
+
+/// 解码错误的分类汇总（见 `Metrics::decode_report`）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DecodeErrorReport {
+    pub total: u64,
+    pub kinds: Vec<(DecodeErrorKind, u64)>, // 出现过的种类，按次数从多到少
+    pub peers: Vec<PeerDecodeErrors>,       // 出错最多的对端，至多 `REPORT_PEERS` 个；只含仍在表中的对端
+    pub tracked_peers: usize,               // 表中的对端数，至多 `PEER_TABLE`
+}
+
+// 按来源记录的解码错误，至多 `PEER_TABLE` 个对端，满了以后淘汰最久没有出错的
+#[derive(Debug, Default)]
+struct PeerErrors {
+    peers: HashMap<SocketAddr, PeerEntry>,
+    tick: u64,  // 每记录一次加一，作为最近出错的时刻
+}
+
+#[derive(Debug)]
+struct PeerEntry {
+    kinds: [u64; DecodeErrorKind::COUNT],
+    last: u64,
+}
+
+impl PeerErrors {
+    fn record(&mut self, peer: SocketAddr, kind: DecodeErrorKind) {
+        self.tick += 1;
+        if self.peers.len() >= PEER_TABLE
+            && !self.peers.contains_key(&peer)
+            && let Some(&oldest) = self.peers.iter().min_by_key(|(_, entry)| entry.last).map(|(peer, _)| peer)
+        {
+            self.peers.remove(&oldest);
+        }
+        let entry = self.peers.entry(peer).or_insert(PeerEntry { kinds: [0; DecodeErrorKind::COUNT], last: 0 });
+        entry.kinds[kind.index()] += 1;
+        entry.last = self.tick;
+    }
+
+    fn top(&self) -> Vec<PeerDecodeErrors> {
+        let mut peers: Vec<_> = self
+            .peers
+            .iter()
+            .map(|(&peer, entry)| PeerDecodeErrors { peer, total: entry.kinds.iter().sum(), kinds: nonzero(&entry.kinds) })
+            .collect();
+        peers.sort_by(|a, b| b.total.cmp(&a.total).then(a.peer.cmp(&b.peer)));
+        peers.truncate(REPORT_PEERS);
+        peers
+    }
+}
+
+/// 接收任务每次唤醒取走的数据报数的分布：第 i 个桶统计 [2^i, 2^(i+1)) 个的唤醒次数，最后一个桶不设上限
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrainHistogram {
+    pub buckets: [u64; DRAIN_BUCKETS],
+    pub datagrams: u64,     // 各次唤醒取走的数据报总数
+}
+
+impl DrainHistogram {
+    /// 一次取走 `drained` 个（至少 1）落入的桶
+    pub fn bucket(drained: usize) -> usize {
+        (drained.max(1).ilog2() as usize).min(DRAIN_BUCKETS - 1)
+    }
+
+    /// 第 `index` 个桶的下界
+    pub fn lower_bound(index: usize) -> usize {
+        1 << index
+    }
+
+    /// 唤醒次数
+    pub fn wakeups(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// 平均每次唤醒取走的数据报数；还没有唤醒过时为 0
+    pub fn mean(&self) -> f64 {
+        match self.wakeups() {
+            0 => 0.0,
+            wakeups => self.datagrams as f64 / wakeups as f64,
+        }
+    }
+}
+
+/// 某一时刻的指标
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub datagrams_received: u64,
+    pub bytes_received: u64,
+    pub datagrams_sent: u64,
+    pub bytes_sent: u64,
+    pub segments_sent: u64,         // 发出的数据报中的段数（见 `datagrams_per_segment`）
+    pub truncated: u64,
+    pub absorbed: u64,              // 已结束连接的迟到段
+    pub delivered: u64,             // 交给已建立连接的数据报
+    pub dropped: u64,               // 连接的入站队列已满而丢弃的数据报
+    pub handled: u64,               // 由监听器解码处理的数据报
+    pub reflected: u64,             // 其中携带本监听器实例 ID、被反射回来而丢弃的段（见 `reflect` 模块）
+    pub listener_decode_errors: u64, // 监听器无法解析的数据报
+    pub decode_errors: DecodeErrors, // 监听器与连接遇到的全部解码错误
+    pub active_connections: u64,
+    pub evictions: u64,
+    pub retransmissions: u64,       // 连接重传的段数
+    pub retries: u64,               // 要求对端验证地址的 Retry 段
+    pub invalid_tokens: u64,        // 令牌过期、被篡改或来自其他地址而被丢弃的 SYN
+    pub busy_refusals: u64,         // 因 `max_connections`、`per_ip_limit` 或 `memory_limit` 以 `SERVER_BUSY` 拒绝的握手
+    pub drained: DrainHistogram,    // 分发任务每次唤醒取走的数据报数
+    pub memory_used: usize,         // 连接与墓碑计入内存预算的合计（见 `budget` 模块），只有监听器填写
+    pub memory_limit: Option<usize>, // `LinkConfig::memory_limit`
+}
+
+impl MetricsSnapshot {
+    /// 发送合并的效果：平均每个段占用的数据报数，越小合并得越多；还没有发送时为 0
+    pub fn datagrams_per_segment(&self) -> f64 {
+        if self.segments_sent == 0 {
+            return 0.0;
+        }
+        self.datagrams_sent as f64 / self.segments_sent as f64
+    }
+
+    /// 一行摘要：累计值与自 `previous` 以来 `elapsed` 内的速率
+    pub fn summary(&self, previous: &MetricsSnapshot, elapsed: Duration) -> String {
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        let rate = |now: u64, before: u64| now.saturating_sub(before) as f64 / secs;
+        format!(
+            "rx {} dgrams ({:.1}/s, {:.1} KB/s), tx {} dgrams ({:.1}/s, {:.1} KB/s), {} active, {} evicted, \
+             {} retransmits ({:.1}/s), {} decode errors, {} truncated, {} dropped",
+            self.datagrams_received,
+            rate(self.datagrams_received, previous.datagrams_received),
+            rate(self.bytes_received, previous.bytes_received) / 1024.0,
+            self.datagrams_sent,
+            rate(self.datagrams_sent, previous.datagrams_sent),
+            rate(self.bytes_sent, previous.bytes_sent) / 1024.0,
+            self.active_connections,
+            self.evictions,
+            self.retransmissions,
+            rate(self.retransmissions, previous.retransmissions),
+            self.decode_errors.total(),
+            self.truncated,
+            self.dropped,
+        )
+    }
+}
+
+/// 服务器级的原子计数器
+#[derive(Debug, Default)]
+pub struct Metrics {
+    datagrams_received: AtomicU64,
+    bytes_received: AtomicU64,
+    datagrams_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    segments_sent: AtomicU64,
+    truncated: AtomicU64,
+    absorbed: AtomicU64,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+    handled: AtomicU64,
+    reflected: AtomicU64,
+    listener_decode_errors: AtomicU64,
+    decode_kinds: [AtomicU64; DecodeErrorKind::COUNT],
+    decode_peers: Mutex<PeerErrors>,
+    active_connections: AtomicU64,
+    evictions: AtomicU64,
+    retransmissions: AtomicU64,
+    retries: AtomicU64,
+    invalid_tokens: AtomicU64,
+    busy_refusals: AtomicU64,
+    drain_buckets: [AtomicU64; DRAIN_BUCKETS],
+    drained: AtomicU64,
+}
+
+fn add(counter: &AtomicU64, n: u64) {
+    counter.fetch_add(n, Ordering::Relaxed);
+}
+
+impl Metrics {
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        MetricsSnapshot {
+            datagrams_received: load(&self.datagrams_received),
+            bytes_received: load(&self.bytes_received),
+            datagrams_sent: load(&self.datagrams_sent),
+            bytes_sent: load(&self.bytes_sent),
+            segments_sent: load(&self.segments_sent),
+            truncated: load(&self.truncated),
+            absorbed: load(&self.absorbed),
+            delivered: load(&self.delivered),
+            dropped: load(&self.dropped),
+            handled: load(&self.handled),
+            reflected: load(&self.reflected),
+            listener_decode_errors: load(&self.listener_decode_errors),
+            decode_errors: DecodeErrors::from_kinds(self.decode_kinds.each_ref().map(load)),
+            active_connections: load(&self.active_connections),
+            evictions: load(&self.evictions),
+            retransmissions: load(&self.retransmissions),
+            retries: load(&self.retries),
+            invalid_tokens: load(&self.invalid_tokens),
+            busy_refusals: load(&self.busy_refusals),
+            drained: DrainHistogram { buckets: self.drain_buckets.each_ref().map(load), datagrams: load(&self.drained) },
+            memory_used: 0,
+            memory_limit: None,
+        }
+    }
+
+    pub(crate) fn on_received(&self, len: usize) {
+        add(&self.datagrams_received, 1);
+        add(&self.bytes_received, len as u64);
+    }
+
+    pub(crate) fn on_sent(&self, len: usize, segments: usize) {
+        add(&self.datagrams_sent, 1);
+        add(&self.bytes_sent, len as u64);
+        add(&self.segments_sent, segments as u64);
+    }
+
+    /// 一次唤醒取走了 `drained` 个数据报
+    pub(crate) fn on_drained(&self, drained: usize) {
+        add(&self.drain_buckets[DrainHistogram::bucket(drained)], 1);
+        add(&self.drained, drained as u64);
+    }
+
+    pub(crate) fn on_truncated(&self) {
+        add(&self.truncated, 1);
+    }
+
+    pub(crate) fn on_absorbed(&self) {
+        add(&self.absorbed, 1);
+    }
+
+    pub(crate) fn on_retry(&self) {
+        add(&self.retries, 1);
+    }
+
+    pub(crate) fn on_invalid_token(&self) {
+        add(&self.invalid_tokens, 1);
+    }
+
+    pub(crate) fn on_busy(&self) {
+        add(&self.busy_refusals, 1);
+    }
+
+    pub(crate) fn on_reflected(&self) {
+        add(&self.reflected, 1);
+    }
+
+    /// 交给连接的数据报；`accepted` 为 false 表示连接的入站队列已满
+    pub(crate) fn on_routed(&self, accepted: bool) {
+        add(if accepted { &self.delivered } else { &self.dropped }, 1);
+    }
+
+    /// 监听器自己解码的数据报；`error` 为解析失败的原因，`from` 是数据报的来源
+    pub(crate) fn on_handled(&self, error: Option<&SegmentError>, from: SocketAddr) {
+        match error {
+            None => add(&self.handled, 1),
+            Some(error) => {
+                add(&self.listener_decode_errors, 1);
+                self.on_decode_error(error, from);
+            }
+        }
+    }
+
+    /// 最后一个段不完整（数据报在段中间结束）
+    pub(crate) fn on_incomplete(&self, from: SocketAddr) {
+        add(&self.listener_decode_errors, 1);
+        self.on_decode_error(&SegmentError::TooShort, from);
+    }
+
+    pub(crate) fn on_decode_error(&self, error: &SegmentError, from: SocketAddr) {
+        let kind = DecodeErrorKind::of(error);
+        add(&self.decode_kinds[kind.index()], 1);
+        self.decode_peers.lock().expect("decode error table poisoned").record(from, kind);
+    }
+
+    /// 解码错误按种类与来源的汇总
+    pub fn decode_report(&self) -> DecodeErrorReport {
+        let errors = self.snapshot().decode_errors;
+        let peers = self.decode_peers.lock().expect("decode error table poisoned");
+        DecodeErrorReport { total: errors.total(), kinds: errors.by_kind(), peers: peers.top(), tracked_peers: peers.peers.len() }
+    }
+
+    pub(crate) fn on_connection_opened(&self) {
+        add(&self.active_connections, 1);
+    }
+
+    pub(crate) fn on_connection_closed(&self, evicted: bool) {
+        self.active_connections.fetch_sub(1, Ordering::Relaxed);
+        add(&self.evictions, u64::from(evicted));
+    }
+
+    pub(crate) fn on_retransmitted(&self, segments: u64) {
+        add(&self.retransmissions, segments);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_rates() {
+        let metrics = Metrics::default();
+        let before = metrics.snapshot();
+        for _ in 0..20 {
+            metrics.on_received(1024);
+            metrics.on_routed(true);
+        }
+        metrics.on_handled(Some(&SegmentError::UnknownFrameType(9)), "10.0.0.9:9".parse().unwrap());
+        metrics.on_received(10);
+        metrics.on_retransmitted(4);
+
+        let now = metrics.snapshot();
+        assert_eq!(now.decode_errors.unknown_type, 1);
+        assert_eq!(now.delivered + now.listener_decode_errors, now.datagrams_received);
+        let summary = now.summary(&before, Duration::from_secs(2));
+        assert!(summary.starts_with("rx 21 dgrams (10.5/s, 10.0 KB/s)"), "{}", summary);
+        assert!(summary.contains("4 retransmits (2.0/s), 1 decode errors"), "{}", summary);
+    }
+
+    #[test]
+    fn test_drain_histogram_buckets() {
+        let metrics = Metrics::default();
+        for drained in [1, 1, 2, 3, 4, 64, 100, 500] {
+            metrics.on_drained(drained);
+        }
+        let drained = metrics.snapshot().drained;
+        assert_eq!(drained.buckets, [2, 2, 1, 0, 0, 0, 2, 1]);
+        assert_eq!((drained.wakeups(), drained.datagrams), (8, 675));
+        assert_eq!(DrainHistogram::lower_bound(DrainHistogram::bucket(100)), 64);
+        assert_eq!(DrainHistogram::bucket(0), 0);
+        assert_eq!(DrainHistogram::default().mean(), 0.0);
+    }
+
+    #[test]
+    fn test_decode_errors_by_kind() {
+        let metrics = Metrics::default();
+        let peer: SocketAddr = "10.0.0.2:2".parse().unwrap();
+        let before = metrics.snapshot();
+        for _ in 0..30 {
+            metrics.on_decode_error(&SegmentError::ChecksumMismatch { declared: 1, computed: 2 }, peer);
+        }
+        metrics.on_decode_error(&SegmentError::TotalLenOverflow(1 << 33), peer);
+        metrics.on_incomplete(peer);
+
+        let now = metrics.snapshot().decode_errors;
+        assert_eq!((now.total(), now.checksum, now.invalid_length, now.too_short), (32, 30, 1, 1));
+        assert_eq!(now.get(DecodeErrorKind::ChecksumMismatch), 30);
+        assert_eq!(now.by_kind(), [(DecodeErrorKind::ChecksumMismatch, 30), (DecodeErrorKind::TooShort, 1), (DecodeErrorKind::TotalLenOverflow, 1)]);
+        // 10 秒内 30 次是 3/s，另外两类低于 1/s
+        assert_eq!(now.above_rate(&before.decode_errors, Duration::from_secs(10), 1.0), [(DecodeErrorKind::ChecksumMismatch, 3.0)]);
+        assert!(now.above_rate(&before.decode_errors, Duration::from_secs(10), 5.0).is_empty());
+    }
+
+    #[test]
+    fn test_peer_table_evicts_the_least_recent() {
+        let metrics = Metrics::default();
+        let peer = |n: usize| SocketAddr::from(([10, 0, (n >> 8) as u8, n as u8], 1000));
+        // 第一个对端错误最多，但在表满之后最久没有出错，被淘汰
+        for _ in 0..10 {
+            metrics.on_decode_error(&SegmentError::BadOption, peer(0));
+        }
+        for n in 1..PEER_TABLE {
+            metrics.on_decode_error(&SegmentError::BadOption, peer(n));
+        }
+        metrics.on_decode_error(&SegmentError::BadOption, peer(1));
+        metrics.on_decode_error(&SegmentError::AuthFailed, peer(PEER_TABLE));
+
+        let report = metrics.decode_report();
+        assert_eq!(report.total, 10 + PEER_TABLE as u64 + 1);
+        assert_eq!(report.tracked_peers, PEER_TABLE);
+        assert_eq!(report.peers.len(), REPORT_PEERS);
+        assert!(report.peers.iter().all(|entry| entry.peer != peer(0)));
+        assert_eq!((report.peers[0].peer, report.peers[0].total), (peer(1), 2));
+        assert_eq!(report.kinds, [(DecodeErrorKind::BadOption, 10 + PEER_TABLE as u64), (DecodeErrorKind::AuthFailed, 1)]);
+    }
+}
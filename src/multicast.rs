@@ -0,0 +1,285 @@
+//! 组播收发
+//! 一对多的遥测分发：`MulticastSender` 把每条消息编码为一个 Data 段发往组播组，`MulticastReceiver` 加入组并接收
+//! 任何发送方的 Data 段。组播没有连接与握手，也不做确认与重传（不能向组确认），丢失的段不会补发；
+//! 接收端按 (发送方地址, 序列号) 去重，每个发送方保留最近 `REPLAY_WINDOW` 个序列号，更早的段与重复的段一样丢弃。
+//! 落后超过窗口很多的序列号视为发送方重启（新的随机起始序列号），从它重新开始。发送方表是一张 `util::ExpiringMap`：
+//! `SENDER_TIMEOUT` 内没有发来段的发送方被忘掉，表满时忘掉最久没有发来段的一个。
+//!
+//! 接收套接字以 SO_REUSEADDR 绑定组播端口，同一主机上的多个接收端可以加入同一个组。发送端的 TTL（IPv6 的跳数
+//! 限制）、出接口与是否回送本机由 `MulticastConfig` 设置。
+
+use crate::config::LinkConfig;
+use crate::error::{self, LinkError};
+use crate::segment::{self, Segment, SegmentType};
+use crate::seq::SeqNum;
+use bytes::{Bytes, BytesMut};
+use socket2::{Domain, Protocol, Socket, Type};
+use crate::util::ExpiringMap;
+use std::collections::VecDeque;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+/// 每个发送方记住的最近序列号个数
+pub const REPLAY_WINDOW: u64 = 64;
+
+/// 同时跟踪的发送方上限，超过时忘掉最久没有发来段的一个
+const MAX_SENDERS: usize = 1024;
+
+/// 发送方这么久没有发来段就被忘掉，之后它的段从新的去重窗口开始
+pub const SENDER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 发送端的组播选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulticastConfig {
+    pub ttl: u32,               // IPv4 TTL 或 IPv6 跳数限制，默认 1（只到本地网段）
+    pub loopback: bool,         // 本机加入了组的接收端也收到（IP_MULTICAST_LOOP）
+    pub interface_v4: Ipv4Addr, // IPv4 出接口的地址，UNSPECIFIED 时由系统选择
+    pub interface_v6: u32,      // IPv6 出接口的索引，0 时由系统选择
+}
+
+impl Default for MulticastConfig {
+    fn default() -> Self {
+        Self { ttl: 1, loopback: true, interface_v4: Ipv4Addr::UNSPECIFIED, interface_v6: 0 }
+    }
+}
+
+/// 一个发送方最近收到的序列号：`highest` 与其前 `REPLAY_WINDOW - 1` 个，第 i 位表示 `highest - i`
+#[derive(Debug, Clone, Copy)]
+struct ReplayWindow {
+    highest: SeqNum,
+    seen: u64,
+}
+
+impl ReplayWindow {
+    fn new(seq: SeqNum) -> Self {
+        Self { highest: seq, seen: 1 }
+    }
+
+    // 第一次见到 `seq` 时返回 true
+    fn accept(&mut self, seq: SeqNum) -> bool {
+        let ahead = self.highest.distance(seq);
+        if ahead > 0 {
+            let shift = ahead as u64;
+            self.seen = if shift >= REPLAY_WINDOW { 1 } else { (self.seen << shift) | 1 };
+            self.highest = seq;
+            return true;
+        }
+        let behind = ahead.unsigned_abs();
+        if behind >= REPLAY_WINDOW * 4 {
+            // 远远落后：发送方以新的起始序列号重启
+            *self = Self::new(seq);
+            return true;
+        }
+        if behind >= REPLAY_WINDOW || self.seen & (1 << behind) != 0 {
+            return false;
+        }
+        self.seen |= 1 << behind;
+        true
+    }
+}
+
+/// 组播组的接收端
+#[derive(Debug)]
+pub struct MulticastReceiver {
+    socket: UdpSocket,
+    buf: Vec<u8>,
+    pending: VecDeque<(Segment, SocketAddr)>,   // 一个数据报打包的多个段
+    senders: ExpiringMap<SocketAddr, ReplayWindow>, // 每次发来段都刷新，`SENDER_TIMEOUT` 后过期
+    duplicates: u64,
+    malformed: u64,
+}
+
+impl MulticastReceiver {
+    /// 在 `interface`（接口地址，UNSPECIFIED 由系统选择）上加入 IPv4 组 `group`，接收发往 `port` 的段
+    pub fn join_v4(group: Ipv4Addr, port: u16, interface: Ipv4Addr, config: &LinkConfig) -> Result<Self, LinkError> {
+        if !group.is_multicast() {
+            return Err(not_multicast(group.into()));
+        }
+        let socket = bind_group(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)))?;
+        socket.join_multicast_v4(&group, &interface)?;
+        Ok(Self::new(UdpSocket::from_std(socket.into())?, config))
+    }
+
+    /// 在索引为 `interface` 的接口（0 由系统选择）上加入 IPv6 组 `group`
+    pub fn join_v6(group: Ipv6Addr, port: u16, interface: u32, config: &LinkConfig) -> Result<Self, LinkError> {
+        if !group.is_multicast() {
+            return Err(not_multicast(group.into()));
+        }
+        let socket = bind_group(SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)))?;
+        socket.join_multicast_v6(&group, interface)?;
+        Ok(Self::new(UdpSocket::from_std(socket.into())?, config))
+    }
+
+    fn new(socket: UdpSocket, config: &LinkConfig) -> Self {
+        Self {
+            socket,
+            buf: vec![0u8; config.recv_buffer],
+            pending: VecDeque::new(),
+            senders: ExpiringMap::new(MAX_SENDERS),
+            duplicates: 0,
+            malformed: 0,
+        }
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, LinkError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// 下一个没有见过的 Data 段与它的发送方；其他类型的段、无法解析与截断的数据报被丢弃并计数
+    pub async fn recv(&mut self) -> Result<(Segment, SocketAddr), LinkError> {
+        loop {
+            if let Some(received) = self.pending.pop_front() {
+                return Ok(received);
+            }
+            let (len, from) = match self.socket.recv_from(&mut self.buf).await {
+                Ok(received) => received,
+                Err(e) if error::is_fatal(&e) => return Err(e.into()),
+                Err(_) => continue,
+            };
+            let datagram = &self.buf[..len];
+            if segment::is_truncated(datagram) {
+                self.malformed += 1;
+                continue;
+            }
+            let now = Instant::now();
+            let mut datagram = BytesMut::from(datagram);
+            loop {
+                match Segment::decode_from(&mut datagram) {
+                    Ok(Some(segment)) if segment.segment_type() == SegmentType::Data => {
+                        if self.is_new(from, segment.seq(), now) {
+                            self.pending.push_back((segment, from));
+                        } else {
+                            self.duplicates += 1;
+                        }
+                    }
+                    Ok(Some(_)) => self.malformed += 1,
+                    Ok(None) if datagram.is_empty() => break,
+                    Ok(None) | Err(_) => {
+                        self.malformed += 1;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// 重复或早于去重窗口而丢弃的段数
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+
+    /// 不是 Data 段、无法解析或被截断而丢弃的段数
+    pub fn malformed(&self) -> u64 {
+        self.malformed
+    }
+
+    fn is_new(&mut self, from: SocketAddr, seq: SeqNum, now: Instant) -> bool {
+        let Some(window) = self.senders.get_mut(&from, now) else {
+            self.senders.insert(from, ReplayWindow::new(seq), SENDER_TIMEOUT, now);
+            return true;
+        };
+        let new = window.accept(seq);
+        self.senders.refresh(&from, SENDER_TIMEOUT, now);
+        new
+    }
+}
+
+/// 向组播组发送 Data 段
+#[derive(Debug)]
+pub struct MulticastSender {
+    socket: UdpSocket,
+    group: SocketAddr,
+    next_seq: AtomicU64,
+}
+
+impl MulticastSender {
+    /// 绑定与 `group` 同一地址族的临时端口；起始序列号随机，重启后的接收端不会把新的段当作重复
+    pub fn bind(group: SocketAddr, config: &MulticastConfig) -> Result<Self, LinkError> {
+        if !group.ip().is_multicast() {
+            return Err(not_multicast(group.ip()));
+        }
+        let local = match group {
+            SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        };
+        let socket = Socket::new(Domain::for_address(local), Type::DGRAM, Some(Protocol::UDP))?;
+        match group {
+            SocketAddr::V4(_) => {
+                socket.set_multicast_ttl_v4(config.ttl)?;
+                socket.set_multicast_loop_v4(config.loopback)?;
+                socket.set_multicast_if_v4(&config.interface_v4)?;
+            }
+            SocketAddr::V6(_) => {
+                socket.set_multicast_hops_v6(config.ttl)?;
+                socket.set_multicast_loop_v6(config.loopback)?;
+                socket.set_multicast_if_v6(config.interface_v6)?;
+            }
+        }
+        socket.set_nonblocking(true)?;
+        socket.bind(&local.into())?;
+        let mut isn = [0u8; 8];
+        getrandom::fill(&mut isn).expect("operating system random source unavailable");
+        Ok(Self { socket: UdpSocket::from_std(socket.into())?, group, next_seq: AtomicU64::new(u64::from_be_bytes(isn)) })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, LinkError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// 把 `payload` 作为一个 Data 段发往组，返回它的序列号；不等待、也不会得到任何确认
+    pub async fn send(&self, payload: impl Into<Bytes>) -> Result<SeqNum, LinkError> {
+        let seq = SeqNum::new(self.next_seq.fetch_add(1, Ordering::Relaxed));
+        let segment = Segment::builder(SegmentType::Data)
+            .data_seq(seq)
+            .payload(payload)
+            .build()
+            .expect("data segment is always valid");
+        self.socket.send_to(&segment.encode()?, self.group).await?;
+        Ok(seq)
+    }
+}
+
+fn bind_group(addr: SocketAddr) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    Ok(socket)
+}
+
+fn not_multicast(ip: IpAddr) -> LinkError {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a multicast address", ip)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_window() {
+        let base = SeqNum::new(u64::MAX - 2);
+        let mut window = ReplayWindow::new(base);
+        assert!(!window.accept(base));
+        // 跨越回绕、乱序到达的段各接受一次
+        assert!(window.accept(base.wrapping_add(3)));
+        assert!(window.accept(base.wrapping_add(1)));
+        assert!(!window.accept(base.wrapping_add(1)));
+        assert!(!window.accept(base.wrapping_add(3)));
+        assert!(window.accept(base.wrapping_add(2)));
+
+        // 早于窗口的段被丢弃，远远落后的视为发送方重启
+        let far = base.wrapping_add(REPLAY_WINDOW + 10);
+        assert!(window.accept(far));
+        assert!(!window.accept(base.wrapping_add(5)));
+        assert!(window.accept(far.wrapping_sub(REPLAY_WINDOW - 1)));
+        let restarted = far.wrapping_sub(REPLAY_WINDOW * 10);
+        assert!(window.accept(restarted));
+        assert!(!window.accept(restarted));
+    }
+}
@@ -0,0 +1,141 @@
+//! 连接生命周期与可靠传输事件的回调
+//! `LinkConfig::observer` 设置后，连接把建立、状态迁移、重传、RTO 到期、零窗口停顿、RTT 样本与结束
+//! 报告给其中的 `ConnectionObserver`，供嵌入方把它们导出到自己的遥测系统。
+//!
+//! 协议核心与发送端不直接调用回调：它们把事件记为 `Observation`，连接的驱动任务每处理完一个事件就取出
+//! （`ConnectionCore::take_observations`）并依次调用回调，调用发生在驱动任务中、连接的锁之外。
+//! 回调是同步的，阻塞它就阻塞了这条连接的收发与定时器，耗时的处理应转交给别的任务（例如放进通道）。
+//! 没有设置观察者时不记录任何事件。
+
+use crate::config::LinkConfig;
+use crate::error::LinkError;
+use crate::seq::SeqNum;
+use crate::state::ConnState;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 连接事件的接收方；各方法默认什么也不做，只需实现关心的那些。回调不能阻塞
+pub trait ConnectionObserver: Send + Sync + 'static {
+    /// 连接进入 `Established`
+    fn on_established(&self, _peer: SocketAddr) {}
+
+    /// 连接结束：正常关闭时 `reason` 为 None，失败时是连接的错误。只报告一次
+    fn on_close(&self, _peer: SocketAddr, _reason: Option<&LinkError>) {}
+
+    /// 状态机的一次迁移（只报告状态改变的迁移）
+    fn on_state_change(&self, _old: ConnState, _new: ConnState) {}
+
+    /// 重传了序列号为 `seq` 的段，`attempt` 是它的第几次重传（从 1 开始），超时、快速重传与 SACK 空洞重传都报告
+    fn on_retransmit(&self, _seq: SeqNum, _attempt: u32) {}
+
+    /// 重传定时器到期，`backoff` 是退避之后的 RTO
+    fn on_rto_expired(&self, _backoff: Duration) {}
+
+    /// 对端通告了零窗口，发送停顿，开始坚持探测
+    fn on_window_stalled(&self) {}
+
+    /// 一个被采用的 RTT 样本（按 Karn 算法排除了重传段的确认）
+    fn on_rtt_sample(&self, _rtt: Duration) {}
+}
+
+/// `LinkConfig` 中的观察者钩子
+#[derive(Clone)]
+pub struct Observer(Arc<dyn ConnectionObserver>);
+
+impl Observer {
+    pub fn new(observer: Arc<dyn ConnectionObserver>) -> Self {
+        Self(observer)
+    }
+
+    /// 依次调用各事件对应的回调；自己驱动 `ConnectionCore` 的调用方用它转交 `take_observations` 的结果
+    pub fn notify(&self, peer: SocketAddr, observations: Vec<Observation>) {
+        for observation in observations {
+            match observation {
+                Observation::Established => self.0.on_established(peer),
+                Observation::Closed(reason) => self.0.on_close(peer, reason.as_ref()),
+                Observation::StateChange { old, new } => self.0.on_state_change(old, new),
+                Observation::Retransmit { seq, attempt } => self.0.on_retransmit(seq, attempt),
+                Observation::RtoExpired { backoff } => self.0.on_rto_expired(backoff),
+                Observation::WindowStalled => self.0.on_window_stalled(),
+                Observation::RttSample(rtt) => self.0.on_rtt_sample(rtt),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Observer")
+    }
+}
+
+/// 记录下来、等待驱动任务交给观察者的事件，各项对应 `ConnectionObserver` 的同名回调
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Observation {
+    Established,
+    Closed(Option<LinkError>),
+    StateChange { old: ConnState, new: ConnState },
+    Retransmit { seq: SeqNum, attempt: u32 },
+    RtoExpired { backoff: Duration },
+    WindowStalled,
+    RttSample(Duration),
+}
+
+/// 事件的缓冲：没有设置观察者时丢弃
+#[derive(Debug, Default)]
+pub(crate) struct Observations(Option<Vec<Observation>>);
+
+impl Observations {
+    pub(crate) fn new(config: &LinkConfig) -> Self {
+        Self(config.observer.as_ref().map(|_| Vec::new()))
+    }
+
+    pub(crate) fn push(&mut self, observation: Observation) {
+        if let Some(observations) = &mut self.0 {
+            observations.push(observation);
+        }
+    }
+
+    pub(crate) fn take(&mut self) -> Vec<Observation> {
+        self.0.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl ConnectionObserver for Recorder {
+        fn on_established(&self, peer: SocketAddr) {
+            self.0.lock().unwrap().push(format!("established {}", peer));
+        }
+
+        fn on_retransmit(&self, seq: SeqNum, attempt: u32) {
+            self.0.lock().unwrap().push(format!("retransmit {} {}", seq, attempt));
+        }
+    }
+
+    #[test]
+    fn test_observations_reach_only_overridden_callbacks() {
+        let mut observations = Observations::new(&LinkConfig::default());
+        observations.push(Observation::WindowStalled);
+        assert!(observations.take().is_empty());
+
+        let recorder = Arc::new(Recorder::default());
+        let config = LinkConfig { observer: Some(Observer::new(recorder.clone())), ..LinkConfig::default() };
+        let mut observations = Observations::new(&config);
+        observations.push(Observation::Established);
+        observations.push(Observation::RttSample(Duration::from_millis(5)));
+        observations.push(Observation::Retransmit { seq: SeqNum::new(7), attempt: 2 });
+        let peer: SocketAddr = "10.0.0.2:2".parse().unwrap();
+        config.observer.unwrap().notify(peer, observations.take());
+        assert_eq!(*recorder.0.lock().unwrap(), vec!["established 10.0.0.2:2".to_string(), "retransmit 7 2".to_string()]);
+        assert!(observations.take().is_empty());
+    }
+}
@@ -0,0 +1,439 @@
+//! 段头选项
+//! 固定头部之后是 1 字节的选项区长度，随后是选项区，再之后才是数据体。选项区由若干 `type(1) | len(1) | value(len)`
+//! 组成（TLV），`len` 只计值的长度。新的头部字段以新的选项类型加入，固定头部不变：解码时跳过未识别的类型，
+//! 旧版本照常处理段的其余部分；值越过选项区末尾、已识别的选项长度不对或选项区超过 `MAX_LEN` 时整个段以
+//! `SegmentError::BadOption` 拒绝。编码时同样受 `MAX_LEN` 限制，段头不会挤占数据体。
+//!
+//! 已识别的选项：时间戳（`value(4) | echo(4)`）、MSS（2 字节）、SACK-permitted、CWR、ACK-now、unordered、more 与 early-data
+//! （都没有值）、错误码（1 或 2 字节，见 `close_codes` 模块）。请求立即确认与无序交付用选项而不占用标志位：旧版本的对端跳过它们，照常按延迟确认、按序交付处理这个段。
+//! more 标记分片消息中不是最后一片的段；旧版本的对端会把各片当作独立的消息交付，`Connection::send_msg` 只在消息放不进一个段时分片。
+//! early-data 标记与 SYN 同在一个数据报中的 0-RTT 数据段（见 `Connection::connect_with_data`）。
+//! resync（0 或 8 字节）与 resync-head（8 字节）随确认段发出：接收方以前者请求从某个序列号（没有值时为发送方的最新位置）
+//! 开始接收，发送方以后者回应实际开始交付的位置，拒绝时同时携带错误码 `CloseCode::RESYNC_REFUSED`（见 `Connection::resync`）。
+//! instance（4 字节）是发出这个段的监听器或客户端的随机实例 ID，随握手段与监听器回应陌生地址的段发出，
+//! 收到携带自己实例 ID 的段说明自己发出的段被反射了回来（见 `reflect` 模块）。
+//! tag（4 字节）是应用附在消息上的不透明标签（见 `SendOptions::tag`），只出现在消息的第一片上；
+//! 旧版本的对端跳过它，照常交付这条消息，只是没有标签。
+//! 握手段携带的参数选项本身也是 TLV，解码时同样跳过其中未识别的参数（见 `params` 模块）。
+//! 选项区与段头的其余部分一样受校验和保护，也是加密与握手认证的关联数据。
+
+use crate::close_codes::CloseCode;
+use crate::params::TransportParameters;
+use crate::segment::SegmentError;
+use crate::seq::SeqNum;
+use alloc::vec::Vec;
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// 选项区的最大字节数
+pub const MAX_LEN: usize = 40;
+
+pub(crate) const TIMESTAMP: u8 = 1;
+const MSS: u8 = 2;
+const SACK_PERMITTED: u8 = 3;
+const CWR: u8 = 4;
+const ERROR: u8 = 5;
+const ACK_NOW: u8 = 6;
+const UNORDERED: u8 = 7;
+const MORE: u8 = 8;
+const EARLY_DATA: u8 = 9;
+const PARAMS: u8 = 10;
+const RESYNC: u8 = 11;
+const RESYNC_HEAD: u8 = 12;
+const INSTANCE: u8 = 13;
+const TAG: u8 = 14;
+
+/// 只有时间戳选项的选项区的长度
+pub(crate) const TIMESTAMP_LEN: usize = 10;
+
+/// 只有时间戳选项（或为空）的选项区：为空时是 Some(None)，有其他内容时是 None。不分配，见 `ack_frame` 模块
+pub(crate) fn timestamp_only(bytes: &[u8]) -> Option<Option<(u32, u32)>> {
+    match bytes {
+        [] => Some(None),
+        [TIMESTAMP, 8, value @ ..] if value.len() == 8 => {
+            let (value, echo) = value.split_at(4);
+            Some(Some((u32::from_be_bytes(value.try_into().ok()?), u32::from_be_bytes(echo.try_into().ok()?))))
+        }
+        _ => None,
+    }
+}
+
+/// 已识别的选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SegmentOption {
+    Timestamp { value: u32, echo: u32 },    // 发送方的时间戳与回送的对端时间戳
+    Mss(u16),                               // 发送方能接收的最大数据报（含段头，即它的 `LinkConfig::recv_buffer`），只出现在握手段上
+    SackPermitted,                          // 发送方理解 SACK
+    Cwr,                                    // 发送方已因对端回送的 ECE 降窗（congestion window reduced）
+    Error(CloseCode),                       // Rst 携带的复位原因，如 `CloseCode::PROTOCOL_ERROR`；拒绝重新同步的回应携带 `RESYNC_REFUSED`
+    AckNow,                                 // 数据段请求对端不经延迟立即确认
+    Unordered,                              // 数据段不必等待之前的空洞，完整到达即可交付给应用
+    More,                                   // 数据段是一条消息的一片，同一消息的下一片紧随其后
+    EarlyData,                              // 数据段随 SYN 发出，对端可能还不知道这个连接
+    Params(TransportParameters),            // 发送方提出的或协商出的连接参数，只出现在握手段上
+    Resync(Option<SeqNum>),                 // 确认段请求对端从这个序列号开始发送，None 表示从对端的最新位置开始
+    ResyncHead(SeqNum),                     // 回应 resync：发送方开始交付的序列号，拒绝时是它仍保留的最早序列号
+    Instance(u32),                          // 发送方的实例 ID，用来识别被反射回来的段
+    Tag(u32),                               // 数据段是一条带标签的消息的第一片，值是应用给的标签
+}
+
+impl SegmentOption {
+    /// 线上的选项类型
+    pub fn kind(&self) -> u8 {
+        match self {
+            SegmentOption::Timestamp { .. } => TIMESTAMP,
+            SegmentOption::Mss(_) => MSS,
+            SegmentOption::SackPermitted => SACK_PERMITTED,
+            SegmentOption::Cwr => CWR,
+            SegmentOption::Error(_) => ERROR,
+            SegmentOption::AckNow => ACK_NOW,
+            SegmentOption::Unordered => UNORDERED,
+            SegmentOption::More => MORE,
+            SegmentOption::EarlyData => EARLY_DATA,
+            SegmentOption::Params(_) => PARAMS,
+            SegmentOption::Resync(_) => RESYNC,
+            SegmentOption::ResyncHead(_) => RESYNC_HEAD,
+            SegmentOption::Instance(_) => INSTANCE,
+            SegmentOption::Tag(_) => TAG,
+        }
+    }
+
+    fn value(&self) -> Vec<u8> {
+        match *self {
+            SegmentOption::Timestamp { value, echo } => [value.to_be_bytes(), echo.to_be_bytes()].concat(),
+            SegmentOption::Mss(mss) => mss.to_be_bytes().to_vec(),
+            SegmentOption::Error(code) => code.to_wire(),
+            SegmentOption::Params(params) => params.encode(),
+            SegmentOption::Resync(from) => from.map(|seq| seq.get().to_be_bytes().to_vec()).unwrap_or_default(),
+            SegmentOption::ResyncHead(head) => head.get().to_be_bytes().to_vec(),
+            SegmentOption::Instance(id) | SegmentOption::Tag(id) => id.to_be_bytes().to_vec(),
+            SegmentOption::SackPermitted
+            | SegmentOption::Cwr
+            | SegmentOption::AckNow
+            | SegmentOption::Unordered
+            | SegmentOption::More
+            | SegmentOption::EarlyData => Vec::new(),
+        }
+    }
+
+    // 未识别的类型返回 Ok(None)，已识别但长度不对时返回 Err
+    fn parse(kind: u8, value: &[u8]) -> Result<Option<Self>, SegmentError> {
+        let option = match (kind, value.len()) {
+            (TIMESTAMP, 8) => SegmentOption::Timestamp {
+                value: u32::from_be_bytes(value[..4].try_into().expect("four bytes")),
+                echo: u32::from_be_bytes(value[4..].try_into().expect("four bytes")),
+            },
+            (MSS, 2) => SegmentOption::Mss(u16::from_be_bytes(value.try_into().expect("two bytes"))),
+            (SACK_PERMITTED, 0) => SegmentOption::SackPermitted,
+            (CWR, 0) => SegmentOption::Cwr,
+            (ERROR, 1 | 2) => SegmentOption::Error(CloseCode::from_wire(value).expect("one or two bytes")),
+            (ACK_NOW, 0) => SegmentOption::AckNow,
+            (UNORDERED, 0) => SegmentOption::Unordered,
+            (MORE, 0) => SegmentOption::More,
+            (EARLY_DATA, 0) => SegmentOption::EarlyData,
+            (PARAMS, _) => SegmentOption::Params(TransportParameters::decode(value)?),
+            (RESYNC, 0) => SegmentOption::Resync(None),
+            (RESYNC, 8) => SegmentOption::Resync(Some(SeqNum::new(u64::from_be_bytes(value.try_into().expect("eight bytes"))))),
+            (RESYNC_HEAD, 8) => SegmentOption::ResyncHead(SeqNum::new(u64::from_be_bytes(value.try_into().expect("eight bytes")))),
+            (INSTANCE, 4) => SegmentOption::Instance(u32::from_be_bytes(value.try_into().expect("four bytes"))),
+            (TAG, 4) => SegmentOption::Tag(u32::from_be_bytes(value.try_into().expect("four bytes"))),
+            (TIMESTAMP | MSS | SACK_PERMITTED | CWR | ERROR | ACK_NOW | UNORDERED | MORE | EARLY_DATA | RESYNC | RESYNC_HEAD | INSTANCE | TAG, _) => {
+                return Err(SegmentError::BadOption);
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(option))
+    }
+}
+
+/// 选项区放不下新的选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptionsFull {
+    pub needed: usize,  // 加入后选项区的字节数
+}
+
+impl fmt::Display for OptionsFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "header options need {} bytes but at most {} fit", self.needed, MAX_LEN)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OptionsFull {}
+
+/// 编码后的选项区：按加入的顺序排列，保留解码时遇到的未识别选项
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Options {
+    bytes: Vec<u8>,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加一个选项；放不下时返回错误，选项区不变
+    pub fn push(&mut self, option: SegmentOption) -> Result<(), OptionsFull> {
+        let value = option.value();
+        let needed = self.bytes.len() + 2 + value.len();
+        if needed > MAX_LEN {
+            return Err(OptionsFull { needed });
+        }
+        self.bytes.push(option.kind());
+        self.bytes.push(value.len() as u8);
+        self.bytes.extend_from_slice(&value);
+        Ok(())
+    }
+
+    /// 构造器形式的 `push`
+    pub fn with(mut self, option: SegmentOption) -> Result<Self, OptionsFull> {
+        self.push(option)?;
+        Ok(self)
+    }
+
+    /// 解码选项区并校验每个选项的长度
+    pub fn decode(bytes: &[u8]) -> Result<Self, SegmentError> {
+        if bytes.len() > MAX_LEN {
+            return Err(SegmentError::BadOption);
+        }
+        let mut rest = bytes;
+        while let [kind, len, tail @ ..] = rest {
+            let value = tail.get(..usize::from(*len)).ok_or(SegmentError::BadOption)?;
+            SegmentOption::parse(*kind, value)?;
+            rest = &tail[value.len()..];
+        }
+        // 只剩一个字节：有类型没有长度
+        if !rest.is_empty() {
+            return Err(SegmentError::BadOption);
+        }
+        Ok(Self { bytes: bytes.to_vec() })
+    }
+
+    /// 已识别的选项，跳过未识别的类型
+    pub fn iter(&self) -> impl Iterator<Item = SegmentOption> + '_ {
+        let mut rest = &self.bytes[..];
+        core::iter::from_fn(move || {
+            while let [kind, len, tail @ ..] = rest {
+                // 反序列化得到的选项区未经校验，越界时停止
+                let Some(value) = tail.get(..usize::from(*len)) else {
+                    break;
+                };
+                rest = &tail[value.len()..];
+                if let Ok(Some(option)) = SegmentOption::parse(*kind, value) {
+                    return Some(option);
+                }
+            }
+            None
+        })
+    }
+
+    /// 时间戳选项：(value, echo)
+    pub fn timestamp(&self) -> Option<(u32, u32)> {
+        self.iter().find_map(|option| match option {
+            SegmentOption::Timestamp { value, echo } => Some((value, echo)),
+            _ => None,
+        })
+    }
+
+    pub fn mss(&self) -> Option<u16> {
+        self.iter().find_map(|option| match option {
+            SegmentOption::Mss(mss) => Some(mss),
+            _ => None,
+        })
+    }
+
+    pub fn sack_permitted(&self) -> bool {
+        self.iter().any(|option| option == SegmentOption::SackPermitted)
+    }
+
+    pub fn cwr(&self) -> bool {
+        self.iter().any(|option| option == SegmentOption::Cwr)
+    }
+
+    pub fn ack_now(&self) -> bool {
+        self.iter().any(|option| option == SegmentOption::AckNow)
+    }
+
+    pub fn unordered(&self) -> bool {
+        self.iter().any(|option| option == SegmentOption::Unordered)
+    }
+
+    pub fn more(&self) -> bool {
+        self.iter().any(|option| option == SegmentOption::More)
+    }
+
+    pub fn early_data(&self) -> bool {
+        self.iter().any(|option| option == SegmentOption::EarlyData)
+    }
+
+    /// 握手段携带的连接参数；旧版本的对端不携带
+    pub fn params(&self) -> Option<TransportParameters> {
+        self.iter().find_map(|option| match option {
+            SegmentOption::Params(params) => Some(params),
+            _ => None,
+        })
+    }
+
+    /// 确认段携带的重新同步请求：外层的 None 表示没有请求，内层的 None 表示从最新位置开始
+    pub fn resync(&self) -> Option<Option<SeqNum>> {
+        self.iter().find_map(|option| match option {
+            SegmentOption::Resync(from) => Some(from),
+            _ => None,
+        })
+    }
+
+    /// 重新同步的回应
+    pub fn resync_head(&self) -> Option<SeqNum> {
+        self.iter().find_map(|option| match option {
+            SegmentOption::ResyncHead(head) => Some(head),
+            _ => None,
+        })
+    }
+
+    /// 发送方的实例 ID
+    pub fn instance(&self) -> Option<u32> {
+        self.iter().find_map(|option| match option {
+            SegmentOption::Instance(id) => Some(id),
+            _ => None,
+        })
+    }
+
+    /// 消息的标签，只在消息的第一片上
+    pub fn tag(&self) -> Option<u32> {
+        self.iter().find_map(|option| match option {
+            SegmentOption::Tag(tag) => Some(tag),
+            _ => None,
+        })
+    }
+
+    /// Rst 或拒绝重新同步的回应携带的错误码
+    pub fn error(&self) -> Option<CloseCode> {
+        self.iter().find_map(|option| match option {
+            SegmentOption::Error(code) => Some(code),
+            _ => None,
+        })
+    }
+
+    /// 选项区的字节数
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// 线上的选项区
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_roundtrip() {
+        let options = Options::new()
+            .with(SegmentOption::Mss(1200))
+            .and_then(|options| options.with(SegmentOption::Timestamp { value: 7, echo: 3 }))
+            .and_then(|options| options.with(SegmentOption::SackPermitted))
+            .and_then(|options| options.with(SegmentOption::Error(CloseCode::PROTOCOL_ERROR)))
+            .and_then(|options| options.with(SegmentOption::AckNow))
+            .and_then(|options| options.with(SegmentOption::Unordered))
+            .and_then(|options| options.with(SegmentOption::More))
+            .and_then(|options| options.with(SegmentOption::EarlyData))
+            .unwrap();
+        assert_eq!(options.len(), 4 + 10 + 2 + 3 + 2 + 2 + 2 + 2);
+        let decoded = Options::decode(options.as_bytes()).unwrap();
+        assert_eq!(decoded, options);
+        assert_eq!((decoded.mss(), decoded.timestamp(), decoded.sack_permitted()), (Some(1200), Some((7, 3)), true));
+        assert_eq!((decoded.error(), decoded.ack_now(), decoded.unordered()), (Some(CloseCode::PROTOCOL_ERROR), true, true));
+        assert!(decoded.more() && decoded.early_data());
+        assert_eq!(Options::new().iter().count(), 0);
+        assert!(!Options::new().sack_permitted() && !Options::new().ack_now() && !Options::new().unordered());
+    }
+
+    #[test]
+    fn test_resync_options_roundtrip() {
+        let latest = Options::new().with(SegmentOption::Resync(None)).unwrap();
+        assert_eq!((latest.len(), latest.resync(), latest.resync_head()), (2, Some(None), None));
+        let options = Options::new()
+            .with(SegmentOption::Resync(Some(SeqNum::new(42))))
+            .and_then(|options| options.with(SegmentOption::ResyncHead(SeqNum::new(u64::MAX))))
+            .and_then(|options| options.with(SegmentOption::Error(CloseCode::RESYNC_REFUSED)))
+            .unwrap();
+        let decoded = Options::decode(options.as_bytes()).unwrap();
+        assert_eq!(decoded.resync(), Some(Some(SeqNum::new(42))));
+        assert_eq!((decoded.resync_head(), decoded.error()), (Some(SeqNum::new(u64::MAX)), Some(CloseCode::RESYNC_REFUSED)));
+        assert_eq!(Options::new().resync(), None);
+    }
+
+    #[test]
+    fn test_instance_option_roundtrip() {
+        let options = Options::new().with(SegmentOption::Instance(0xDEAD_BEEF)).unwrap();
+        assert_eq!(options.as_bytes(), [INSTANCE, 4, 0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(Options::decode(options.as_bytes()).unwrap().instance(), Some(0xDEAD_BEEF));
+        assert_eq!(Options::new().instance(), None);
+    }
+
+    #[test]
+    fn test_tag_option_roundtrip() {
+        let options = Options::new().with(SegmentOption::More).and_then(|options| options.with(SegmentOption::Tag(7))).unwrap();
+        assert_eq!(options.as_bytes(), [MORE, 0, TAG, 4, 0, 0, 0, 7]);
+        let decoded = Options::decode(options.as_bytes()).unwrap();
+        assert_eq!((decoded.tag(), decoded.more()), (Some(7), true));
+        assert_eq!(Options::new().tag(), None);
+    }
+
+    #[test]
+    fn test_unknown_options_are_skipped() {
+        // 类型 99 的选项夹在两个已识别的选项之间
+        let raw = [MSS, 2, 0x04, 0xB0, 99, 3, 1, 2, 3, SACK_PERMITTED, 0];
+        let options = Options::decode(&raw).unwrap();
+        assert_eq!(options.iter().collect::<Vec<_>>(), vec![SegmentOption::Mss(1200), SegmentOption::SackPermitted]);
+        assert_eq!(options.as_bytes(), raw);
+    }
+
+    #[test]
+    fn test_malformed_options_are_rejected() {
+        for raw in [
+            &[MSS, 4, 0, 1][..],            // 值越过选项区末尾
+            &[99, 1],                       // 未识别的类型同样不能越界
+            &[SACK_PERMITTED, 0, MSS],      // 只剩类型
+            &[MSS, 3, 0, 1, 2],             // 已识别的类型长度不对
+            &[TIMESTAMP, 4, 0, 0, 0, 1],
+            &[CWR, 1, 0],
+            &[ERROR, 0],
+            &[ERROR, 3, 0x40, 0, 1],
+            &[ACK_NOW, 1, 0],
+            &[UNORDERED, 1, 0],
+            &[MORE, 1, 0],
+            &[EARLY_DATA, 1, 0],
+            &[RESYNC, 4, 0, 0, 0, 1],
+            &[RESYNC_HEAD, 0],
+            &[INSTANCE, 2, 0, 1],
+            &[TAG, 8, 0, 0, 0, 0, 0, 0, 0, 1],
+            &[99, 40, 0],
+        ] {
+            assert_eq!(Options::decode(raw), Err(SegmentError::BadOption), "{:?}", raw);
+        }
+        assert_eq!(Options::decode(&[0; MAX_LEN + 2]), Err(SegmentError::BadOption));
+    }
+
+    #[test]
+    fn test_options_are_capped() {
+        let mut options = Options::new();
+        for _ in 0..4 {
+            options.push(SegmentOption::Timestamp { value: 1, echo: 2 }).unwrap();
+        }
+        // 已有 40 字节
+        assert_eq!(options.push(SegmentOption::SackPermitted), Err(OptionsFull { needed: MAX_LEN + 2 }));
+        assert_eq!(options.len(), MAX_LEN);
+    }
+}
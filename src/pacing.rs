@@ -0,0 +1,136 @@
+//! 发送节奏
+//! 窗口一打开就整窗发出会在瓶颈处形成突发排队。`Pacer` 按拥塞窗口与平滑 RTT 算出的速率放出新数据段：
+//! 速率 = `LinkConfig::pacing_gain` × cwnd × mss / srtt（字节/秒），一个长度为 `len` 的段之后间隔 `len / 速率`。
+//! 实现为令牌桶：以该速率填充，桶中不欠令牌时放行一个段并扣除它的长度（允许欠一个段），欠下的令牌按速率补齐后再放行下一个。
+//! 桶的容量即最大突发：`PACING_QUANTUM` 个 MSS，与 `PACING_HORIZON` 或 `LinkConfig::batch_window`（取大）内按速率能发出的字节数取大——
+//! 定时器精度之内的段一次放出，监听器合并发送时一个合并窗口内的段也一次放出，不会让攒着的数据报等待逐个放行。
+//! 尚无 RTT 样本时不限速。只作用于新数据段，确认、握手、探测与重传不经过它。本身不做 IO，时间由调用方注入。
+
+use std::time::{Duration, Instant};
+
+/// 桶容量的下限（MSS 个数）
+pub const PACING_QUANTUM: usize = 2;
+
+/// 桶容量至少覆盖的时长，与定时器的实际精度相当
+pub const PACING_HORIZON: Duration = Duration::from_millis(2);
+
+// 欠下不足一个字节的令牌视为已补齐，避免浮点误差让到期的放行再等一轮
+const TOLERANCE: f64 = 1.0;
+
+/// 新数据段的令牌桶
+#[derive(Debug, Clone)]
+pub struct Pacer {
+    gain: f64,
+    horizon: Duration,      // 桶容量按速率覆盖的时长
+    rate: Option<f64>,      // 字节/秒，None 时不限速
+    capacity: f64,          // 桶容量（字节）
+    tokens: f64,            // 可为负：欠下的令牌
+    refilled: Option<Instant>,  // 上一次填充的时间
+}
+
+impl Pacer {
+    /// `batch_window` 大于 `PACING_HORIZON` 时，桶容量覆盖一个合并窗口
+    pub fn new(gain: f64, batch_window: Duration) -> Self {
+        Self { gain, horizon: batch_window.max(PACING_HORIZON), rate: None, capacity: 0.0, tokens: 0.0, refilled: None }
+    }
+
+    /// 按当前拥塞窗口（段数）、MSS 与平滑 RTT 更新速率；srtt 未知或为 0 时不限速
+    pub fn set_rate(&mut self, cwnd: usize, mss: usize, srtt: Option<Duration>, now: Instant) {
+        self.refill(now);
+        let rate = srtt.filter(|srtt| !srtt.is_zero()).map(|srtt| self.gain * (cwnd * mss) as f64 / srtt.as_secs_f64());
+        let capacity = rate.map_or(0.0, |rate| ((PACING_QUANTUM * mss) as f64).max(rate * self.horizon.as_secs_f64()));
+        if self.rate.is_none() {
+            // 刚开始限速：从满桶开始
+            self.tokens = capacity;
+        }
+        self.rate = rate;
+        self.capacity = capacity;
+        self.tokens = self.tokens.min(capacity);
+    }
+
+    /// 当前速率（字节/秒），不限速时为 None
+    pub fn rate(&self) -> Option<f64> {
+        self.rate
+    }
+
+    /// 现在能否放行一个段
+    pub fn can_send(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.rate.is_none() || self.tokens > -TOLERANCE
+    }
+
+    /// 放行了一个编码后长度为 `len` 的段
+    pub fn on_send(&mut self, len: usize, now: Instant) {
+        self.refill(now);
+        if self.rate.is_some() {
+            self.tokens -= len as f64;
+        }
+    }
+
+    /// 下一次能放行的时间；现在就能放行或不限速时为 None
+    pub fn next_release(&mut self, now: Instant) -> Option<Instant> {
+        self.refill(now);
+        let rate = self.rate?;
+        (self.tokens <= -TOLERANCE).then(|| now + Duration::from_secs_f64((-self.tokens - TOLERANCE / 2.0) / rate))
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let (Some(rate), Some(refilled)) = (self.rate, self.refilled) {
+            let elapsed = now.saturating_duration_since(refilled).as_secs_f64();
+            self.tokens = (self.tokens + rate * elapsed).min(self.capacity);
+        }
+        self.refilled = Some(self.refilled.map_or(now, |refilled| refilled.max(now)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_releases_at_computed_interval() {
+        let now = Instant::now();
+        let mut pacer = Pacer::new(1.25, Duration::ZERO);
+        assert!(pacer.can_send(now));
+        // 10 段窗口、100ms RTT：150000 字节/秒，1200 字节的段间隔 8ms
+        pacer.set_rate(10, 1200, Some(Duration::from_millis(100)), now);
+        assert_eq!(pacer.rate(), Some(150_000.0));
+
+        // 满桶放出一个放行量，之后每 8ms 一个
+        let mut released = Vec::new();
+        let mut at = now;
+        while released.len() < 6 {
+            if pacer.can_send(at) {
+                pacer.on_send(1200, at);
+                released.push(at - now);
+            } else {
+                at = pacer.next_release(at).expect("pacer is in debt");
+            }
+        }
+        let ms = |d: &Duration| d.as_secs_f64() * 1000.0;
+        assert_eq!(released[..3].iter().map(ms).collect::<Vec<_>>(), vec![0.0, 0.0, 0.0]);
+        for pair in released[2..].windows(2) {
+            assert!((ms(&(pair[1] - pair[0])) - 8.0).abs() < 0.01, "{:?}", released);
+        }
+    }
+
+    #[test]
+    fn test_capacity_covers_batch_window() {
+        let now = Instant::now();
+        let mut pacer = Pacer::new(1.0, Duration::from_millis(10));
+        pacer.set_rate(100, 1000, Some(Duration::from_millis(100)), now);
+        // 1MB/s 下 10ms 的合并窗口内能发出 10000 字节：整整一批不必逐个等待
+        let mut burst = 0;
+        while pacer.can_send(now) {
+            pacer.on_send(1000, now);
+            burst += 1;
+        }
+        assert_eq!(burst, 11);
+
+        // 没有 RTT 样本时不限速
+        let mut idle = Pacer::new(1.0, Duration::ZERO);
+        idle.set_rate(10, 1000, None, now);
+        idle.on_send(1_000_000, now);
+        assert!(idle.can_send(now) && idle.next_release(now).is_none());
+    }
+}
@@ -0,0 +1,158 @@
+//! 握手参数协商
+//! MSS 之外，连接的若干参数在握手时由双方商定，而不是各自假定：SYN 与 SYN-ACK 携带一个参数选项（见 `options` 模块），
+//! 它的值由若干 `id(1) | len(1) | value(len)` 的参数组成，与选项区同样是 TLV。每个参数的生效值取双方中更保守的一个：
+//! 确认频率（攒够多少个按序段后立即确认）与保活间隔取较小者，SACK 与 0-RTT 只有双方都允许时才启用。
+//! 解码时跳过未识别的参数，较新的版本加入的参数不影响旧版本；缺少的参数不构成约束，按另一方的值。
+//! 对端完全没有携带参数选项（旧版本）时各项按本端的值，与引入协商之前的行为相同。
+//!
+//! 校验算法仍由 SYN 数据体中的算法列表协商（见 `checksum` 模块），参数中的算法是发送方最偏好的（SYN）或协商出的
+//! （SYN-ACK 与最后的确认），只供监听器的 `ParamPolicy` 审查，不参与取值。以 cookie 回应的监听器不记录 SYN 中的参数，
+//! 客户端在完成握手的确认中再次携带协商结果，与 MSS 相同。
+
+use crate::checksum::ChecksumAlgorithm;
+use crate::segment::SegmentError;
+use alloc::vec::Vec;
+use core::net::SocketAddr;
+use core::time::Duration;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const ACK_EVERY: u8 = 1;
+const KEEPALIVE: u8 = 2;
+const SACK: u8 = 3;
+const EARLY_DATA: u8 = 4;
+const CHECKSUM: u8 = 5;
+
+/// 参数选项的值的字节数
+pub const ENCODED_LEN: usize = (2 + 2) + (2 + 4) + (2 + 1) * 3;
+
+/// 握手时商定的连接参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TransportParameters {
+    pub ack_every: u16,                 // 攒够多少个按序段后立即确认（`LinkConfig::ack_every_n_segments`），至少为 1
+    pub keepalive_interval: Duration,   // 多久没有收到任何段后发送保活探测，线上以毫秒计，不为 0
+    pub sack: bool,                     // 确认携带 SACK 区间（`LinkConfig::sack`）
+    pub early_data: bool,               // 接受随 SYN 到达的 0-RTT 数据（`LinkConfig::accept_early_data`，客户端总是允许）
+    pub checksum: ChecksumAlgorithm,    // 发送方最偏好的或协商出的校验算法，不参与取值
+}
+
+impl TransportParameters {
+    // 解码时缺少的参数：不构成约束
+    const UNCONSTRAINED: TransportParameters = TransportParameters {
+        ack_every: u16::MAX,
+        keepalive_interval: Duration::from_millis(u32::MAX as u64),
+        sack: true,
+        early_data: true,
+        checksum: ChecksumAlgorithm::Crc32c,
+    };
+
+    /// 与对端的参数合并：每项取更保守的一个，校验算法保留本端的
+    pub fn merge(&self, peer: &TransportParameters) -> TransportParameters {
+        TransportParameters {
+            ack_every: self.ack_every.min(peer.ack_every),
+            keepalive_interval: self.keepalive_interval.min(peer.keepalive_interval),
+            sack: self.sack && peer.sack,
+            early_data: self.early_data && peer.early_data,
+            checksum: self.checksum,
+        }
+    }
+
+    /// 参数选项的值
+    pub fn encode(&self) -> Vec<u8> {
+        let keepalive = u32::try_from(self.keepalive_interval.as_millis()).unwrap_or(u32::MAX);
+        let mut value = Vec::with_capacity(ENCODED_LEN);
+        value.extend_from_slice(&[ACK_EVERY, 2]);
+        value.extend_from_slice(&self.ack_every.to_be_bytes());
+        value.extend_from_slice(&[KEEPALIVE, 4]);
+        value.extend_from_slice(&keepalive.to_be_bytes());
+        value.extend_from_slice(&[SACK, 1, u8::from(self.sack)]);
+        value.extend_from_slice(&[EARLY_DATA, 1, u8::from(self.early_data)]);
+        value.extend_from_slice(&[CHECKSUM, 1, self.checksum.id()]);
+        value
+    }
+
+    /// 解码参数选项的值：跳过未识别的参数与未识别的校验算法；值越界、已识别的参数长度不对，
+    /// 或确认频率、保活间隔为 0 时返回 `BadOption`
+    pub fn decode(value: &[u8]) -> Result<Self, SegmentError> {
+        let mut params = Self::UNCONSTRAINED;
+        let mut rest = value;
+        while let [id, len, tail @ ..] = rest {
+            let value = tail.get(..usize::from(*len)).ok_or(SegmentError::BadOption)?;
+            rest = &tail[value.len()..];
+            match (*id, value) {
+                (ACK_EVERY, &[high, low]) if u16::from_be_bytes([high, low]) > 0 => params.ack_every = u16::from_be_bytes([high, low]),
+                (KEEPALIVE, &[a, b, c, d]) if u32::from_be_bytes([a, b, c, d]) > 0 => {
+                    params.keepalive_interval = Duration::from_millis(u64::from(u32::from_be_bytes([a, b, c, d])));
+                }
+                (SACK, &[flag @ (0 | 1)]) => params.sack = flag == 1,
+                (EARLY_DATA, &[flag @ (0 | 1)]) => params.early_data = flag == 1,
+                (CHECKSUM, &[id]) => params.checksum = ChecksumAlgorithm::from_id(id).unwrap_or(params.checksum),
+                (ACK_EVERY | KEEPALIVE | SACK | EARLY_DATA | CHECKSUM, _) => return Err(SegmentError::BadOption),
+                _ => {}
+            }
+        }
+        if !rest.is_empty() {
+            return Err(SegmentError::BadOption);
+        }
+        Ok(params)
+    }
+}
+
+/// 监听器审查客户端在握手中提出的参数（见 `Listener::set_param_policy`）
+pub trait ParamPolicy: Send + Sync + 'static {
+    /// 返回调整后的参数，None 拒绝这次握手：监听器以携带 `CloseCode::PARAMETER_ERROR` 的 Rst 回应。
+    /// 调整只能更保守：结果仍与客户端提出的与本端的合并。对端没有携带参数（旧版本）时 `proposed` 是本端的参数
+    fn review(&self, peer: SocketAddr, proposed: &TransportParameters) -> Option<TransportParameters>;
+}
+
+impl<F> ParamPolicy for F
+where
+    F: Fn(SocketAddr, &TransportParameters) -> Option<TransportParameters> + Send + Sync + 'static,
+{
+    fn review(&self, peer: SocketAddr, proposed: &TransportParameters) -> Option<TransportParameters> {
+        self(peer, proposed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(ack_every: u16, keepalive_secs: u64, sack: bool, early_data: bool) -> TransportParameters {
+        TransportParameters { ack_every, keepalive_interval: Duration::from_secs(keepalive_secs), sack, early_data, checksum: ChecksumAlgorithm::XxHash32 }
+    }
+
+    #[test]
+    fn test_params_roundtrip() {
+        let local = params(4, 30, true, false);
+        let encoded = local.encode();
+        assert_eq!(encoded.len(), ENCODED_LEN);
+        assert_eq!(TransportParameters::decode(&encoded), Ok(local));
+    }
+
+    #[test]
+    fn test_merge_takes_the_conservative_side() {
+        let client = params(2, 30, true, true);
+        let server = params(8, 10, false, true);
+        let merged = client.merge(&server);
+        assert_eq!((merged.ack_every, merged.keepalive_interval, merged.sack, merged.early_data), (2, Duration::from_secs(10), false, true));
+        assert_eq!(merged.checksum, client.checksum);
+        assert_eq!(server.merge(&client).checksum, server.checksum);
+    }
+
+    #[test]
+    fn test_unknown_and_missing_params() {
+        // 未识别的参数被跳过，缺少的参数不约束对端
+        let value = [0x7F, 3, 1, 2, 3, ACK_EVERY, 2, 0, 1];
+        let decoded = TransportParameters::decode(&value).unwrap();
+        assert_eq!(decoded.ack_every, 1);
+        let local = params(2, 15, true, false);
+        assert_eq!(local.merge(&decoded), TransportParameters { ack_every: 1, ..local });
+
+        for bad in [&[ACK_EVERY, 2, 0, 0][..], &[KEEPALIVE, 2, 0, 1], &[SACK, 1, 2], &[SACK, 3, 1], &[EARLY_DATA]] {
+            assert_eq!(TransportParameters::decode(bad), Err(SegmentError::BadOption), "{:?}", bad);
+        }
+    }
+}
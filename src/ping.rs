@@ -0,0 +1,240 @@
+//! 连接上的往返时间探测
+//! `Pinger` 在一个已建立的连接上按间隔发送 Ping 段，用 Pong 回带的 nonce 匹配探测，报告每个探测的 RTT。
+//! 每个探测各自超时，不影响之后的探测按时发出；超时之后才到的 Pong 记为迟到，不改变结果。
+//! 探测的 nonce 最高位为 1，与保活探测（从 1 开始递增）不会混淆。
+//!
+//! 匹配逻辑在不做 IO 的 `ProbeTracker` 中，时间由调用方注入；`Pinger::run` 用 tokio 定时器驱动它。
+
+use crate::connection::Connection;
+use crate::error::LinkError;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// 探测 nonce 的命名空间
+const PROBE_NONCE: u64 = 1 << 63;
+
+/// 探测的次数与节奏
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PingOptions {
+    pub count: usize,
+    pub interval: Duration, // 相邻两次探测的间隔，与是否收到回应无关
+    pub timeout: Duration,  // 每个探测等待 Pong 的最长时间
+}
+
+impl Default for PingOptions {
+    fn default() -> Self {
+        Self { count: 10, interval: Duration::from_secs(1), timeout: Duration::from_secs(1) }
+    }
+}
+
+/// 一个探测的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PingEvent {
+    Reply { seq: usize, rtt: Duration },
+    Timeout { seq: usize },
+    Late { seq: usize, rtt: Duration }, // 超时之后到达的 Pong
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Probe {
+    Pending { sent: Instant, deadline: Instant },
+    Answered,
+    TimedOut { sent: Instant },
+}
+
+/// 发出的探测与它们的状态
+#[derive(Debug)]
+pub struct ProbeTracker {
+    timeout: Duration,
+    probes: Vec<Probe>,
+    rtts: Vec<Duration>,
+    late: usize,
+}
+
+impl ProbeTracker {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout, probes: Vec::new(), rtts: Vec::new(), late: 0 }
+    }
+
+    /// 登记一个新探测，返回它的序号与应放进 Ping 的 nonce
+    pub fn on_send(&mut self, now: Instant) -> (usize, u64) {
+        let seq = self.probes.len();
+        self.probes.push(Probe::Pending { sent: now, deadline: now + self.timeout });
+        (seq, PROBE_NONCE | seq as u64)
+    }
+
+    /// 收到 Pong：不是本端探测的 nonce 与重复的回应返回 None
+    pub fn on_pong(&mut self, nonce: u64, now: Instant) -> Option<PingEvent> {
+        if nonce & PROBE_NONCE == 0 {
+            return None;
+        }
+        let seq = usize::try_from(nonce & !PROBE_NONCE).ok()?;
+        let probe = self.probes.get_mut(seq)?;
+        match *probe {
+            Probe::Pending { sent, .. } => {
+                *probe = Probe::Answered;
+                let rtt = now.saturating_duration_since(sent);
+                self.rtts.push(rtt);
+                Some(PingEvent::Reply { seq, rtt })
+            }
+            Probe::TimedOut { sent } => {
+                *probe = Probe::Answered;
+                self.late += 1;
+                Some(PingEvent::Late { seq, rtt: now.saturating_duration_since(sent) })
+            }
+            Probe::Answered => None,
+        }
+    }
+
+    /// 把到期的探测标记为超时，按序号返回
+    pub fn expire(&mut self, now: Instant) -> Vec<PingEvent> {
+        let mut events = Vec::new();
+        for (seq, probe) in self.probes.iter_mut().enumerate() {
+            if let Probe::Pending { sent, deadline } = *probe
+                && deadline <= now
+            {
+                *probe = Probe::TimedOut { sent };
+                events.push(PingEvent::Timeout { seq });
+            }
+        }
+        events
+    }
+
+    /// 最早的未决探测的超时时间；没有未决探测时为 None
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.probes
+            .iter()
+            .filter_map(|probe| match probe {
+                Probe::Pending { deadline, .. } => Some(*deadline),
+                _ => None,
+            })
+            .min()
+    }
+
+    pub fn summary(&self) -> PingSummary {
+        PingSummary::new(self.probes.len(), &self.rtts, self.late)
+    }
+}
+
+/// 一轮探测的汇总
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PingSummary {
+    pub sent: usize,
+    pub received: usize, // 超时之前得到回应的探测数
+    pub late: usize,     // 超时之后才得到回应的探测数，计入丢失
+    pub min_rtt: Option<Duration>,
+    pub mean_rtt: Option<Duration>,
+    pub max_rtt: Option<Duration>,
+    pub stddev_rtt: Option<Duration>,
+}
+
+impl PingSummary {
+    fn new(sent: usize, rtts: &[Duration], late: usize) -> Self {
+        let mut summary = Self { sent, received: rtts.len(), late, ..Self::default() };
+        if rtts.is_empty() {
+            return summary;
+        }
+        let mean = rtts.iter().map(Duration::as_secs_f64).sum::<f64>() / rtts.len() as f64;
+        let variance = rtts.iter().map(|rtt| (rtt.as_secs_f64() - mean).powi(2)).sum::<f64>() / rtts.len() as f64;
+        summary.min_rtt = rtts.iter().min().copied();
+        summary.max_rtt = rtts.iter().max().copied();
+        summary.mean_rtt = Some(Duration::from_secs_f64(mean));
+        summary.stddev_rtt = Some(Duration::from_secs_f64(variance.sqrt()));
+        summary
+    }
+
+    /// 没有在超时之前得到回应的比例
+    pub fn loss(&self) -> f64 {
+        if self.sent == 0 {
+            return 0.0;
+        }
+        (self.sent - self.received) as f64 / self.sent as f64
+    }
+}
+
+/// 在一个连接上探测 RTT；同一连接同时只应有一个 `Pinger`，新建的会接管 Pong 的转交
+#[derive(Debug)]
+pub struct Pinger<'a> {
+    connection: &'a Connection,
+    pongs: mpsc::UnboundedReceiver<(u64, Instant)>,
+}
+
+impl<'a> Pinger<'a> {
+    pub fn new(connection: &'a Connection) -> Self {
+        Self { connection, pongs: connection.subscribe_pongs() }
+    }
+
+    /// 发送 `options.count` 个探测，每个探测有结果（回应、超时或迟到的回应）时调用一次 `on_event`，
+    /// 所有探测都得到回应或超时后返回；连接失败时返回该错误
+    pub async fn run(&mut self, options: &PingOptions, mut on_event: impl FnMut(&PingEvent)) -> Result<PingSummary, LinkError> {
+        let mut tracker = ProbeTracker::new(options.timeout);
+        let mut sent = 0;
+        let mut next_send = tokio::time::Instant::now();
+        while sent < options.count || tracker.next_deadline().is_some() {
+            let deadline = tracker.next_deadline();
+            tokio::select! {
+                _ = tokio::time::sleep_until(next_send), if sent < options.count => {
+                    let (_, nonce) = tracker.on_send(now());
+                    self.connection.send_ping(nonce).await?;
+                    sent += 1;
+                    next_send += options.interval;
+                }
+                _ = sleep_until(deadline), if deadline.is_some() => {
+                    tracker.expire(now()).iter().for_each(&mut on_event);
+                }
+                pong = self.pongs.recv() => {
+                    let Some((nonce, at)) = pong else {
+                        return Err(LinkError::Closed);
+                    };
+                    if let Some(event) = tracker.on_pong(nonce, at) {
+                        on_event(&event);
+                    }
+                }
+            }
+        }
+        Ok(tracker.summary())
+    }
+}
+
+fn now() -> Instant {
+    tokio::time::Instant::now().into_std()
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker_matches_late_and_foreign_pongs() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut tracker = ProbeTracker::new(Duration::from_millis(100));
+        let (_, first) = tracker.on_send(start);
+        let (_, second) = tracker.on_send(ms(10));
+        assert_eq!(tracker.next_deadline(), Some(ms(100)));
+
+        assert_eq!(tracker.on_pong(second, ms(30)), Some(PingEvent::Reply { seq: 1, rtt: Duration::from_millis(20) }));
+        assert_eq!(tracker.on_pong(second, ms(31)), None);
+        // 保活探测的 nonce 与不存在的序号被忽略
+        assert_eq!(tracker.on_pong(1, ms(40)), None);
+        assert_eq!(tracker.on_pong(PROBE_NONCE | 7, ms(40)), None);
+
+        assert!(tracker.expire(ms(99)).is_empty());
+        assert_eq!(tracker.expire(ms(100)), vec![PingEvent::Timeout { seq: 0 }]);
+        assert_eq!(tracker.next_deadline(), None);
+        assert_eq!(tracker.on_pong(first, ms(150)), Some(PingEvent::Late { seq: 0, rtt: Duration::from_millis(150) }));
+        assert_eq!(tracker.on_pong(first, ms(160)), None);
+
+        let summary = tracker.summary();
+        assert_eq!((summary.sent, summary.received, summary.late), (2, 1, 1));
+        assert_eq!(summary.loss(), 0.5);
+        assert_eq!((summary.min_rtt, summary.stddev_rtt), (Some(Duration::from_millis(20)), Some(Duration::ZERO)));
+    }
+}
@@ -0,0 +1,273 @@
+//! 路径 MTU 探测（简化的 DPLPMTUD）
+//! 连接建立后在 `LinkConfig::mss`（已知可用的起点）与 `max_mss` 之间二分搜索：每一步发送一个填充到候选大小的 Ping
+//! （`Segment::probe`），收到回带同一 nonce 的 Pong 即说明这个大小的数据报能够到达，有效 MSS 随之提高；
+//! 连续 `PROBE_ATTEMPTS` 个探测都没有回应时把搜索上界降到候选大小之下。上下界相差不到 `RESOLUTION` 时搜索结束，
+//! 此后每隔 `pmtu_interval` 复核一次：先以当前大小探测，通过则重新向上搜索，失败则说明路径变窄，
+//! 退回起点重新搜索。本机以 EMSGSIZE 拒绝发送的大小（`on_too_large`）是之后搜索与复核的上限，
+//! 已验证的大小不小于它时随之降低。
+//!
+//! 探测不进入重传队列也不占用拥塞窗口，丢失只影响搜索本身，数据照常以已验证的 MSS 发送。
+//! 探测的 nonce 以 `PROBE_NONCE` 为前缀，与保活探测（从 1 开始递增）和 `ping::Pinger`（最高位为 1）都不会混淆。
+//! 本身不做 IO，时间与超时由调用方注入。
+
+use crate::config::LinkConfig;
+use crate::segment::Segment;
+use std::time::{Duration, Instant};
+
+/// 候选大小连续失败多少次后判定过大
+pub const PROBE_ATTEMPTS: u32 = 2;
+
+/// 上下界相差不到它（字节）时搜索结束
+pub const RESOLUTION: usize = 16;
+
+/// 探测 nonce 的命名空间
+const PROBE_NONCE: u64 = 1 << 62;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Search,     // 二分搜索中
+    Validated,  // 搜索结束，等待复核
+    Confirm,    // 复核当前大小
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Probe {
+    nonce: u64,
+    size: usize,
+    attempts: u32,
+}
+
+/// 一条连接的路径 MTU 探测状态
+#[derive(Debug)]
+pub struct PathMtu {
+    base: usize,        // 起点，也是路径变窄时退回的大小
+    max: usize,
+    mss: usize,         // 已验证的大小，即有效 MSS
+    ceiling: usize,     // 当前的搜索上界
+    phase: Phase,
+    probe: Option<Probe>,   // 等待回应的探测
+    deadline: Instant,      // 下一次需要调用 `poll` 的时间
+    interval: Duration,
+    next_nonce: u64,
+}
+
+impl PathMtu {
+    /// 没有设置 `max_mss`（或它不大于 `mss`）时不探测，返回 None
+    pub fn new(config: &LinkConfig, now: Instant) -> Option<Self> {
+        let max = config.max_mss.filter(|&max| max > config.mss)?;
+        Some(Self {
+            base: config.mss,
+            max,
+            mss: config.mss,
+            ceiling: max,
+            phase: Phase::Search,
+            probe: None,
+            deadline: now,
+            interval: config.pmtu_interval,
+            next_nonce: PROBE_NONCE,
+        })
+    }
+
+    /// 当前的有效 MSS
+    pub fn mss(&self) -> usize {
+        self.mss
+    }
+
+    /// 搜索已结束，`mss` 是已验证的大小
+    pub fn is_validated(&self) -> bool {
+        self.phase == Phase::Validated
+    }
+
+    /// 定时器检查：返回需要发出的探测。`timeout` 是等待回应的时间，通常取连接当前的 RTO
+    pub fn poll(&mut self, now: Instant, timeout: Duration) -> Option<Segment> {
+        if now < self.deadline {
+            return None;
+        }
+        if let Some(probe) = &mut self.probe {
+            if probe.attempts < PROBE_ATTEMPTS {
+                probe.attempts += 1;
+                self.deadline = now + timeout;
+                return Some(Segment::probe(probe.nonce, probe.size));
+            }
+            let size = probe.size;
+            self.probe = None;
+            self.on_lost(size);
+        }
+        let size = self.next_size(now)?;
+        let nonce = self.next_nonce;
+        self.next_nonce = self.next_nonce.wrapping_add(1) | PROBE_NONCE;
+        self.probe = Some(Probe { nonce, size, attempts: 1 });
+        self.deadline = now + timeout;
+        Some(Segment::probe(nonce, size))
+    }
+
+    /// 收到 Pong：是等待中的探测的回应时记下结果并返回 true，下一个探测随即可以发出
+    pub fn on_pong(&mut self, nonce: u64, now: Instant) -> bool {
+        let Some(probe) = self.probe.filter(|probe| probe.nonce == nonce) else {
+            return false;
+        };
+        self.probe = None;
+        match self.phase {
+            // 当前大小仍然可用，看看路径是否变宽
+            Phase::Confirm => {
+                self.phase = Phase::Search;
+                self.ceiling = self.max;
+            }
+            _ => self.mss = self.mss.max(probe.size),
+        }
+        self.deadline = now;
+        true
+    }
+
+    /// 本机拒绝发送 `size` 字节的数据报（EMSGSIZE）：这是本机接口的上限，之后的搜索与复核都不超过它。
+    /// 已验证的大小不小于它时降到 `fallback`，起点随之降低；等待回应的探测不小于它时作废，立即重新搜索
+    pub fn on_too_large(&mut self, size: usize, fallback: usize, now: Instant) {
+        let limit = size.saturating_sub(1);
+        self.max = self.max.min(limit);
+        self.ceiling = self.ceiling.min(limit);
+        if self.mss >= size {
+            self.mss = fallback.min(limit);
+            self.base = self.base.min(self.mss);
+            self.phase = Phase::Search;
+            self.deadline = now;
+        }
+        if self.probe.is_some_and(|probe| probe.size >= size) {
+            self.probe = None;
+            self.phase = Phase::Search;
+            self.deadline = now;
+        }
+    }
+
+    /// 下一次需要调用 `poll` 的时间
+    pub fn next_deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    fn on_lost(&mut self, size: usize) {
+        if self.phase == Phase::Confirm {
+            // 路径变窄：已验证的大小不再可靠，从起点重新搜索
+            self.mss = self.base;
+            self.phase = Phase::Search;
+        }
+        self.ceiling = size - 1;
+    }
+
+    // 下一个候选大小；搜索结束时安排复核并返回 None
+    fn next_size(&mut self, now: Instant) -> Option<usize> {
+        if self.phase == Phase::Validated {
+            // 复核时间到：当前大小高于起点时先确认它，否则直接重新向上搜索
+            if self.mss > self.base {
+                self.phase = Phase::Confirm;
+                return Some(self.mss);
+            }
+            self.phase = Phase::Search;
+            self.ceiling = self.max;
+        }
+        if self.ceiling < self.mss + RESOLUTION {
+            self.phase = Phase::Validated;
+            self.deadline = now + self.interval;
+            return None;
+        }
+        Some(self.mss + (self.ceiling - self.mss).div_ceil(2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RTO: Duration = Duration::from_millis(200);
+
+    fn config() -> LinkConfig {
+        LinkConfig { mss: 1200, max_mss: Some(9000), ..LinkConfig::default() }
+    }
+
+    // 模拟一条 MTU 为 `mtu` 的路径，直到搜索结束；返回经过的时间
+    fn converge(pmtu: &mut PathMtu, mtu: usize, mut now: Instant) -> Instant {
+        for _ in 0..100 {
+            match pmtu.poll(now, RTO) {
+                Some(probe) => {
+                    let len = probe.encode().unwrap().len();
+                    if len <= mtu {
+                        assert!(pmtu.on_pong(probe.nonce().unwrap(), now));
+                    } else {
+                        now += RTO;
+                    }
+                }
+                None if pmtu.is_validated() => return now,
+                None => now = pmtu.next_deadline(),
+            }
+        }
+        panic!("probing did not converge");
+    }
+
+    #[test]
+    fn test_search_converges_below_path_mtu() {
+        let t0 = Instant::now();
+        for mtu in [1200, 1500, 4000, 8999, 9000, 20000] {
+            let mut pmtu = PathMtu::new(&config(), t0).unwrap();
+            converge(&mut pmtu, mtu, t0);
+            let expected = mtu.min(9000);
+            assert!(pmtu.mss() <= expected && pmtu.mss() + RESOLUTION > expected, "mtu {}: mss {}", mtu, pmtu.mss());
+        }
+        assert!(PathMtu::new(&LinkConfig::default(), t0).is_none());
+    }
+
+    #[test]
+    fn test_single_loss_is_retried() {
+        let t0 = Instant::now();
+        let mut pmtu = PathMtu::new(&config(), t0).unwrap();
+        let first = pmtu.poll(t0, RTO).unwrap();
+        assert!(pmtu.poll(t0 + RTO / 2, RTO).is_none());
+        // 第一次没有回应，同一个候选大小再试一次，第二次得到回应
+        let retry = pmtu.poll(t0 + RTO, RTO).unwrap();
+        assert_eq!((retry.nonce(), retry.data().len()), (first.nonce(), first.data().len()));
+        assert!(pmtu.on_pong(retry.nonce().unwrap(), t0 + RTO));
+        assert_eq!(pmtu.mss(), first.encode().unwrap().len());
+        // 迟到或未知的回应被忽略
+        assert!(!pmtu.on_pong(retry.nonce().unwrap(), t0 + RTO));
+        assert!(!pmtu.on_pong(7, t0 + RTO));
+    }
+
+    #[test]
+    fn test_local_limit_caps_the_search() {
+        let t0 = Instant::now();
+        let mut pmtu = PathMtu::new(&config(), t0).unwrap();
+        converge(&mut pmtu, 4000, t0);
+        assert!(pmtu.mss() > 3000);
+
+        // 本机接口只允许 1400 字节：已验证的大小降到给出的值，之后的搜索不再越过它
+        pmtu.on_too_large(1401, 1300, t0);
+        assert_eq!(pmtu.mss(), 1300);
+        converge(&mut pmtu, 4000, t0);
+        assert!(pmtu.mss() <= 1400 && pmtu.mss() + RESOLUTION > 1400, "mss {}", pmtu.mss());
+
+        // 过大的探测作废，已验证的大小不受影响
+        let mut pmtu = PathMtu::new(&config(), t0).unwrap();
+        let probe = pmtu.poll(t0, RTO).unwrap();
+        let size = probe.encode().unwrap().len();
+        pmtu.on_too_large(size, 1000, t0);
+        assert_eq!(pmtu.mss(), 1200);
+        assert!(!pmtu.on_pong(probe.nonce().unwrap(), t0));
+        assert!(pmtu.poll(t0, RTO).unwrap().encode().unwrap().len() < size);
+    }
+
+    #[test]
+    fn test_revalidation_tracks_path_changes() {
+        let t0 = Instant::now();
+        let mut pmtu = PathMtu::new(&config(), t0).unwrap();
+        let now = converge(&mut pmtu, 4000, t0);
+        let validated = pmtu.mss();
+        assert!(pmtu.poll(now, RTO).is_none());
+        assert_eq!(pmtu.next_deadline(), now + config().pmtu_interval);
+
+        // 路径变窄：复核失败后退回起点，重新搜索出新的大小
+        let recheck = pmtu.next_deadline();
+        let now = converge(&mut pmtu, 2000, recheck);
+        assert!(pmtu.mss() <= 2000 && pmtu.mss() + RESOLUTION > 2000, "mss {}", pmtu.mss());
+
+        // 路径变宽：复核通过后向上搜索
+        converge(&mut pmtu, 6000, now + config().pmtu_interval);
+        assert!(pmtu.mss() > validated && pmtu.mss() <= 6000, "mss {}", pmtu.mss());
+    }
+}
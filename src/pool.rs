@@ -0,0 +1,486 @@
+//! 数据报缓冲池
+//! 高包速下，每个数据报编码与接收时各一次的 `BytesMut` 分配是主要开销。`BufferPool` 发放容量不小于标准大小
+//! （最大数据报长度，`LinkConfig::max_mss` 或 `mss`）的空缓冲，用完后以 `put` 归还：清空后用 `BytesMut::try_reclaim`
+//! 收回整块存储——从它切出的 `Bytes`/`BytesMut` 都已丢弃时不需要重新分配；仍有句柄引用它时直接丢弃，由最后一个句柄释放。
+//! 保留的缓冲总容量不超过 `LinkConfig::buffer_pool`，容量超过标准大小两倍的缓冲（承载了大消息）也不保留。
+//! 池空时 `get` 当场分配，从不等待。池分为 `SHARDS` 个分片，每个线程固定使用其中一个，减少锁竞争。
+//!
+//! 接收方向用 `RecvArena`：数据报直接读入一大块缓冲的空闲部分，按实际长度 `split_to` 后 `freeze` 成 `Bytes`，
+//! `Segment::decode_bytes` 再从中切出数据体，从套接字到 `Connection::recv` 不复制数据体。剩余空间放不下一个最大数据报时
+//! 换下一块：之前切出的句柄都已丢弃时收回原来的存储，否则先看最近换下的 `RETIRED_BLOCKS` 块中有没有已无人引用的，
+//! 都没有时才分配新的一块。句柄在上层停留得久一些（等待交付、作为回显留在重传队列）时，几块存储轮流使用，不再分配。
+//!
+//! 大小不定的内容（拼合分片消息、回显的数据报）写入每个任务一个的 `Scratch`，写完 `split` 切出，需要时再 `freeze`。
+//! 切出的句柄都已丢弃时下次写入收回原来的存储，稳定状态下不再分配；放不下时容量按两倍增长。一次突发（如大消息）
+//! 把容量撑过 `LinkConfig::scratch_high_water` 后，闲置 `SCRATCH_QUIET` 即释放，内存回到突发之前的水平。
+//! 仍被引用的存储同样换下、轮流收回；只有放不下时才翻倍。
+
+use crate::config::LinkConfig;
+use crate::segment::{Segment, SegmentError};
+use bytes::{Bytes, BytesMut};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// 分片数
+pub const SHARDS: usize = 8;
+
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // 线程首次使用缓冲池时轮流分配
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % SHARDS;
+}
+
+/// 缓冲池统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub hits: u64,          // 由池中的缓冲满足的 `get`
+    pub misses: u64,        // 池空、当场分配的 `get`
+    pub discarded: u64,     // 归还时仍被引用、过大或超出上限而丢弃的缓冲
+    pub retained: usize,    // 池中缓冲的总容量（字节）
+}
+
+/// 可复用的数据报缓冲
+#[derive(Debug)]
+pub struct BufferPool {
+    buffer_size: usize,
+    max_retained: usize,
+    retained: AtomicUsize,
+    shards: [Mutex<Vec<BytesMut>>; SHARDS],
+    hits: AtomicU64,
+    misses: AtomicU64,
+    discarded: AtomicU64,
+}
+
+impl BufferPool {
+    /// 标准大小取 `max_mss`（未设置时 `mss`），保留上限为 `buffer_pool`
+    pub fn new(config: &LinkConfig) -> Self {
+        Self::with_limits(config.max_mss.unwrap_or(config.mss), config.buffer_pool)
+    }
+
+    /// `max_retained` 为 0 时不保留任何缓冲，每次 `get` 都当场分配
+    pub fn with_limits(buffer_size: usize, max_retained: usize) -> Self {
+        Self {
+            buffer_size,
+            max_retained,
+            retained: AtomicUsize::new(0),
+            shards: Default::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            discarded: AtomicU64::new(0),
+        }
+    }
+
+    /// 取一个空缓冲，容量不小于标准大小
+    pub fn get(&self) -> BytesMut {
+        let reused = self.shard().lock().expect("buffer pool poisoned").pop();
+        match reused {
+            Some(buf) => {
+                self.retained.fetch_sub(buf.capacity(), Ordering::Relaxed);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                buf
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                BytesMut::with_capacity(self.buffer_size)
+            }
+        }
+    }
+
+    /// 归还缓冲；不能在不分配的情况下收回标准大小，或池已满时丢弃
+    pub fn put(&self, mut buf: BytesMut) {
+        buf.clear();
+        // 收回后才是整块存储的容量
+        let reclaimed = buf.try_reclaim(self.buffer_size);
+        let capacity = buf.capacity();
+        let reserved = reclaimed
+            && capacity <= 2 * self.buffer_size
+            && self
+                .retained
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |retained| {
+                    Some(retained + capacity).filter(|&total| total <= self.max_retained)
+                })
+                .is_ok();
+        if reserved {
+            self.shard().lock().expect("buffer pool poisoned").push(buf);
+        } else {
+            self.discarded.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// 标准大小（字节）
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            discarded: self.discarded.load(Ordering::Relaxed),
+            retained: self.retained.load(Ordering::Relaxed),
+        }
+    }
+
+    fn shard(&self) -> &Mutex<Vec<BytesMut>> {
+        &self.shards[SHARD.with(|shard| *shard)]
+    }
+}
+
+/// 每块接收缓冲能容纳的最大数据报个数
+pub const ARENA_DATAGRAMS: usize = 4;
+
+/// 接收缓冲与暂存缓冲各自保留的换下的存储块数
+pub const RETIRED_BLOCKS: usize = 2;
+
+// 换下时仍被切出的句柄引用的存储块，句柄都丢弃后可以原样收回
+#[derive(Debug, Default)]
+struct Retired(VecDeque<BytesMut>);
+
+impl Retired {
+    // 取出一块已无人引用、容量不小于 `size` 的存储
+    fn reclaim(&mut self, size: usize) -> Option<BytesMut> {
+        let index = self.0.iter_mut().position(|block| block.try_reclaim(size))?;
+        self.0.remove(index)
+    }
+
+    // 换下一块存储；已有 `RETIRED_BLOCKS` 块时放弃最早的一块，由它最后的句柄释放
+    fn retire(&mut self, mut block: BytesMut) {
+        block.clear();
+        if self.0.len() == RETIRED_BLOCKS {
+            self.0.pop_front();
+        }
+        self.0.push_back(block);
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// 接收缓冲：数据报读入同一块存储，切出的 `Bytes` 与它共享
+#[derive(Debug)]
+pub struct RecvArena {
+    buf: BytesMut,
+    retired: Retired,
+    datagram: usize,    // 最大数据报长度，即 `LinkConfig::recv_buffer`
+    blocks: u64,        // 填充过的块数（含收回的）
+}
+
+impl RecvArena {
+    pub fn new(datagram: usize) -> Self {
+        Self { buf: BytesMut::new(), retired: Retired::default(), datagram, blocks: 0 }
+    }
+
+    /// 下一个数据报的接收空间，长度为最大数据报长度
+    pub fn space(&mut self) -> &mut [u8] {
+        if self.buf.len() < self.datagram {
+            // 剩余部分已初始化，但放不下一个最大数据报：收回或换一块
+            let size = self.datagram * ARENA_DATAGRAMS;
+            self.buf.clear();
+            if !self.buf.try_reclaim(size) {
+                let block = self.retired.reclaim(size).unwrap_or_else(|| BytesMut::with_capacity(size));
+                self.retired.retire(std::mem::replace(&mut self.buf, block));
+            }
+            self.buf.resize(self.buf.capacity(), 0);
+            self.blocks += 1;
+        }
+        &mut self.buf[..self.datagram]
+    }
+
+    /// 把刚读入 `space` 的 `len` 字节切出为一个数据报
+    pub fn split(&mut self, len: usize) -> Bytes {
+        self.buf.split_to(len).freeze()
+    }
+
+    /// 填充过的块数
+    pub fn blocks(&self) -> u64 {
+        self.blocks
+    }
+}
+
+/// 暂存缓冲超过高水位后，闲置多久释放
+pub const SCRATCH_QUIET: Duration = Duration::from_secs(1);
+
+// 暂存缓冲的最小容量
+const SCRATCH_MIN: usize = 4096;
+
+/// 任务的编码暂存缓冲：内容写入后切出，存储在句柄都丢弃后复用
+#[derive(Debug)]
+pub struct Scratch {
+    buf: BytesMut,
+    retired: Retired,
+    size: usize,            // 当前这块存储的大小
+    high_water: usize,
+    busy: Option<Instant>,  // 最近一次需要超过高水位的容量的时间
+}
+
+impl Scratch {
+    pub fn new(high_water: usize) -> Self {
+        Self { buf: BytesMut::new(), retired: Retired::default(), size: 0, high_water, busy: None }
+    }
+
+    /// 保证还能写入 `additional` 字节：先收回切出后已无人引用的存储，仍被引用时换一块同样大小的，
+    /// 放不下时容量至少翻倍。已写入、尚未切出的内容随之搬过去
+    pub fn reserve(&mut self, additional: usize, now: Instant) {
+        let needed = self.buf.len() + additional;
+        if needed > self.high_water {
+            self.busy = Some(now);
+        }
+        if self.buf.try_reclaim(additional) {
+            return;
+        }
+        if needed > self.size {
+            // 换下的块都小了，不再保留；当前这块由它最后的句柄释放
+            self.size = needed.max(2 * self.size).max(SCRATCH_MIN);
+            self.retired.clear();
+            let mut block = BytesMut::with_capacity(self.size);
+            block.extend_from_slice(&self.buf);
+            self.buf = block;
+            return;
+        }
+        let mut block = self.retired.reclaim(self.size).unwrap_or_else(|| BytesMut::with_capacity(self.size));
+        block.extend_from_slice(&self.buf);
+        self.retired.retire(std::mem::replace(&mut self.buf, block));
+    }
+
+    /// 追加内容
+    pub fn extend(&mut self, data: &[u8], now: Instant) {
+        self.reserve(data.len(), now);
+        self.buf.extend_from_slice(data);
+    }
+
+    /// 把一个段编码到末尾
+    pub fn encode(&mut self, segment: &Segment, now: Instant) -> Result<(), SegmentError> {
+        self.reserve(segment.encoded_len(), now);
+        segment.encode_into(&mut self.buf)
+    }
+
+    /// 长度为 `len` 的可写空间，已初始化，供直接接收数据报；随后以 `split_to` 切出实际写入的部分
+    pub fn space(&mut self, len: usize, now: Instant) -> &mut [u8] {
+        if self.buf.len() < len {
+            self.reserve(len - self.buf.len(), now);
+            self.buf.resize(len, 0);
+        }
+        &mut self.buf[..len]
+    }
+
+    /// 切出已写入的全部内容
+    pub fn split(&mut self) -> BytesMut {
+        self.buf.split()
+    }
+
+    /// 切出开头的 `len` 字节（`space` 中写入的部分），其余留作下次的空间
+    pub fn split_to(&mut self, len: usize) -> BytesMut {
+        self.buf.split_to(len)
+    }
+
+    /// 容量超过高水位且已闲置 `SCRATCH_QUIET` 时释放存储；仍被切出的句柄引用的部分由最后一个句柄释放
+    pub fn trim(&mut self, now: Instant) -> bool {
+        match self.deadline() {
+            Some(deadline) if now >= deadline => {
+                self.buf = BytesMut::new();
+                self.retired.clear();
+                self.size = 0;
+                self.busy = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 需要调用 `trim` 的时间；容量没有超过高水位时为 None
+    pub fn deadline(&self) -> Option<Instant> {
+        self.busy.filter(|_| self.size > self.high_water).map(|busy| busy + SCRATCH_QUIET)
+    }
+
+    /// 当前这块存储的大小（字节）
+    pub fn capacity(&self) -> usize {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffers_are_reused() {
+        let pool = BufferPool::with_limits(1500, 64 * 1024);
+        let mut buf = pool.get();
+        assert!(buf.capacity() >= 1500);
+        buf.extend_from_slice(&[7; 1000]);
+        let ptr = buf.as_ptr();
+        pool.put(buf);
+        assert_eq!(pool.stats().retained, 1500);
+
+        // 归还的缓冲被清空后再次发出，不重新分配
+        let again = pool.get();
+        assert!(again.is_empty());
+        assert_eq!(again.as_ptr(), ptr);
+        assert_eq!((pool.stats().hits, pool.stats().misses, pool.stats().retained), (1, 1, 0));
+    }
+
+    #[test]
+    fn test_split_buffers_return_once_released() {
+        let pool = BufferPool::with_limits(1500, 64 * 1024);
+        let mut buf = pool.get();
+        buf.extend_from_slice(&[1; 600]);
+        let frame = buf.split_to(100).freeze();
+
+        // 切出的句柄还在：收回需要分配，丢弃
+        pool.put(buf);
+        assert_eq!(pool.stats().discarded, 1);
+        drop(frame);
+
+        let mut buf = pool.get();
+        buf.extend_from_slice(&[1; 600]);
+        let frame = buf.split_to(100);
+        drop(frame);
+        pool.put(buf);
+        assert_eq!(pool.stats().retained, 1500);
+        assert_eq!(pool.get().capacity(), 1500);
+    }
+
+    #[test]
+    fn test_retained_memory_is_capped() {
+        let pool = BufferPool::with_limits(1000, 2500);
+        let buffers: Vec<_> = (0..4).map(|_| pool.get()).collect();
+        for buf in buffers {
+            pool.put(buf);
+        }
+        // 只保留两个，其余丢弃
+        assert_eq!((pool.stats().retained, pool.stats().discarded), (2000, 2));
+
+        // 过大的缓冲不保留
+        pool.put(BytesMut::with_capacity(5000));
+        assert_eq!(pool.stats().discarded, 3);
+
+        // 上限为 0：从不保留，池空时当场分配
+        let off = BufferPool::with_limits(1000, 0);
+        off.put(off.get());
+        assert_eq!((off.stats().retained, off.stats().misses), (0, 1));
+        assert!(off.get().capacity() >= 1000);
+    }
+
+    #[test]
+    fn test_arena_splits_share_one_block() {
+        let mut arena = RecvArena::new(1000);
+        let space = arena.space();
+        let base = space.as_ptr() as usize;
+        space[..3].copy_from_slice(b"abc");
+        let first = arena.split(3);
+        arena.space()[..2].copy_from_slice(b"de");
+        let second = arena.split(2);
+        assert_eq!((&first[..], &second[..]), (&b"abc"[..], &b"de"[..]));
+        // 连续的数据报紧挨着落在同一块存储里
+        assert_eq!((first.as_ptr() as usize, second.as_ptr() as usize), (base, base + 3));
+        assert_eq!(arena.blocks(), 1);
+
+        // 块用尽时仍有句柄引用它：换一块新的
+        for _ in 0..ARENA_DATAGRAMS {
+            arena.space();
+            drop(arena.split(1000));
+        }
+        assert_eq!(arena.blocks(), 2);
+        assert_ne!(arena.space().as_ptr() as usize, base);
+
+        // 句柄都已丢弃：收回原来的存储
+        drop((first, second));
+        let mut arena = RecvArena::new(1000);
+        let base = arena.space().as_ptr() as usize;
+        for _ in 0..ARENA_DATAGRAMS {
+            arena.space();
+            drop(arena.split(1000));
+        }
+        assert_eq!(arena.space().as_ptr() as usize, base);
+        assert_eq!(arena.blocks(), 2);
+    }
+
+    #[test]
+    fn test_arena_recycles_retired_blocks() {
+        let mut arena = RecvArena::new(1000);
+        // 每块的最后一个数据报在换块时还被引用：换下的块在句柄丢弃后轮流收回，不再分配
+        let mut last: Option<Bytes> = None;
+        let mut starts = std::collections::HashSet::new();
+        for _ in 0..10 {
+            starts.insert(arena.space().as_ptr() as usize);
+            let mut block: Vec<Bytes> = (0..ARENA_DATAGRAMS).map(|_| {
+                arena.space();
+                arena.split(1000)
+            }).collect();
+            arena.space();
+            last = block.pop();
+        }
+        drop(last);
+        assert_eq!(arena.blocks(), 11);
+        assert_eq!(starts.len(), RETIRED_BLOCKS + 1);
+    }
+
+    #[test]
+    fn test_scratch_reuses_and_grows_geometrically() {
+        let now = Instant::now();
+        let mut scratch = Scratch::new(64 * 1024);
+        scratch.extend(&[1; 100], now);
+        let base = scratch.split().as_ptr() as usize;
+        assert_eq!(scratch.capacity(), SCRATCH_MIN);
+        // 接着写在同一块存储里
+        scratch.extend(&[2; 200], now);
+        let frame = scratch.split().freeze();
+        assert_eq!(frame.as_ptr() as usize, base + 100);
+
+        // 放不下且仍被引用：另分配一块，大小翻倍
+        scratch.extend(&[3; 5000], now);
+        assert_eq!(scratch.capacity(), 2 * SCRATCH_MIN);
+        drop(frame);
+        let segment = Segment::builder(crate::segment::SegmentType::Ack).ack(9u64).window(64).build().unwrap();
+        scratch.encode(&segment, now).unwrap();
+        let encoded = scratch.split();
+        assert_eq!(&encoded[5000..], &segment.encode().unwrap()[..]);
+
+        // 切出的句柄都已丢弃：收回整块存储，不再分配
+        let block = encoded.as_ptr() as usize;
+        drop(encoded);
+        scratch.extend(&[4; 2 * SCRATCH_MIN], now);
+        let pinned = scratch.split();
+        assert_eq!(pinned.as_ptr() as usize, block);
+
+        // 仍被引用时换一块同样大小的；引用丢弃后换下的块轮流收回
+        scratch.extend(&[5; 100], now);
+        let second = scratch.split();
+        assert_ne!(second.as_ptr() as usize, block);
+        assert_eq!(scratch.capacity(), 2 * SCRATCH_MIN);
+        drop(pinned);
+        scratch.extend(&[6; 2 * SCRATCH_MIN - 50], now);
+        assert_eq!(scratch.split().as_ptr() as usize, block);
+    }
+
+    #[test]
+    fn test_scratch_trims_after_a_quiet_period() {
+        let now = Instant::now();
+        let mut scratch = Scratch::new(16 * 1024);
+        scratch.extend(&[1; 1000], now);
+        drop(scratch.split());
+        // 没有超过高水位：不释放
+        assert_eq!(scratch.deadline(), None);
+        assert!(!scratch.trim(now + SCRATCH_QUIET * 10));
+
+        scratch.extend(&vec![2; 100 * 1024], now);
+        drop(scratch.split());
+        assert_eq!(scratch.capacity(), 100 * 1024);
+        assert_eq!(scratch.deadline(), Some(now + SCRATCH_QUIET));
+        // 闲置期间再次用到大容量，重新计时
+        scratch.reserve(50 * 1024, now + SCRATCH_QUIET / 2);
+        assert!(!scratch.trim(now + SCRATCH_QUIET));
+        assert!(scratch.trim(now + SCRATCH_QUIET * 2));
+        assert_eq!((scratch.capacity(), scratch.deadline()), (0, None));
+
+        // 接收空间：切出写入的部分，其余留作下次
+        let space = scratch.space(1500, now);
+        space[..3].copy_from_slice(b"abc");
+        assert_eq!(&scratch.split_to(3)[..], b"abc");
+        assert_eq!(scratch.space(1500, now).len(), 1500);
+    }
+}
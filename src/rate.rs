@@ -0,0 +1,263 @@
+//! 投递速率估计
+//! 按 BBR 的做法从确认中采样连接实际送达数据的速率：每个段（重）发出时记下连接当时已送达的字节数、
+//! 最近一次送达的时间与所在采样区间开始发送的时间（`DeliverySnapshot`，随段保存在重传队列中）；
+//! 它被确认时，从那时起新送达的字节数除以区间长度就是一个样本（`RateSample`）。区间取发送间隔与确认间隔中较长的一个，
+//! 确认被压缩成一批到达时不会高估。带宽估计是最近 `BANDWIDTH_WINDOW` 个往返轮次中样本的最大值：
+//! 一轮从某次确认开始，到在它之后发出的段被确认为止。
+//!
+//! 发送方没有更多数据、窗口还有空位时连接处于应用受限期，直到此刻在途的数据全部送达；期间发出的段得到的样本
+//! 被标记 `app_limited`，它们反映的是应用写入的速度而不是路径的容量，只在不低于当前估计时采纳，不会把估计拉低。
+//! 发送与送达的时刻由重传队列在登记段与处理确认时传入，与 RTT 采样用同一个时刻。
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// 带宽估计保留样本的往返轮次数
+pub const BANDWIDTH_WINDOW: u64 = 10;
+
+/// 一个段发出时连接的投递状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeliverySnapshot {
+    delivered: u64,             // 连接已送达的字节数
+    delivered_at: Instant,      // 最近一次送达的时间
+    first_sent_at: Instant,     // 所在采样区间第一个段的发送时间
+    sent_at: Instant,
+    app_limited: bool,          // 在应用受限期内发出
+}
+
+impl DeliverySnapshot {
+    /// 段（重）发出的时间
+    pub fn sent_at(&self) -> Instant {
+        self.sent_at
+    }
+
+    /// 段是否在应用受限期内发出
+    pub fn is_app_limited(&self) -> bool {
+        self.app_limited
+    }
+}
+
+/// 一次确认得到的投递速率样本
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateSample {
+    pub delivered: u64,         // 区间内送达的字节数
+    pub interval: Duration,     // 区间长度：发送间隔与确认间隔中较长的一个
+    pub rtt: Duration,          // 区间最后一个段从（重）发出到被确认的时间
+    pub app_limited: bool,      // 区间内发送方受应用限制，速率可能低于路径的容量
+}
+
+impl RateSample {
+    /// 每秒送达的字节数；区间为零时为 None
+    pub fn bytes_per_sec(&self) -> Option<u64> {
+        if self.interval.is_zero() {
+            return None;
+        }
+        u64::try_from(u128::from(self.delivered) * 1_000_000_000 / self.interval.as_nanos()).ok()
+    }
+}
+
+/// 连接的投递状态与带宽估计
+#[derive(Debug, Clone)]
+pub struct DeliveryRate {
+    delivered: u64,
+    delivered_at: Option<Instant>,
+    first_sent_at: Option<Instant>,
+    app_limited_until: u64,     // 非零时应用受限期持续到送达的字节数超过它
+    round: u64,                 // 已开始的往返轮次数
+    round_end: u64,             // 发出时已送达这么多字节的段被确认即开始下一轮
+    max_filter: VecDeque<(u64, u64)>,   // (轮次, 字节每秒)，速率从前往后递减，队首是窗口内的最大值
+}
+
+impl Default for DeliveryRate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeliveryRate {
+    pub fn new() -> Self {
+        Self {
+            delivered: 0,
+            delivered_at: None,
+            first_sent_at: None,
+            app_limited_until: 0,
+            round: 0,
+            round_end: 0,
+            max_filter: VecDeque::new(),
+        }
+    }
+
+    /// 记录一个在 `now` 发出的段，`in_flight_bytes` 是发出前在途的字节数；没有在途数据时开始新的采样区间
+    pub fn on_send(&mut self, in_flight_bytes: usize, now: Instant) -> DeliverySnapshot {
+        if in_flight_bytes == 0 {
+            self.first_sent_at = Some(now);
+            self.delivered_at = Some(now);
+        }
+        DeliverySnapshot {
+            delivered: self.delivered,
+            delivered_at: *self.delivered_at.get_or_insert(now),
+            first_sent_at: *self.first_sent_at.get_or_insert(now),
+            sent_at: now,
+            app_limited: self.app_limited_until != 0,
+        }
+    }
+
+    /// 发送方没有更多数据、窗口还有空位：应用受限期持续到在途的 `in_flight_bytes` 全部送达
+    pub fn on_app_limited(&mut self, in_flight_bytes: usize) {
+        self.app_limited_until = (self.delivered + in_flight_bytes as u64).max(1);
+    }
+
+    /// 在 `now` 新送达了 `bytes` 字节，`newest` 是其中最后发出的段的快照；返回得到的样本
+    pub fn on_delivered(&mut self, bytes: usize, newest: Option<DeliverySnapshot>, now: Instant) -> Option<RateSample> {
+        if bytes == 0 {
+            return None;
+        }
+        self.delivered += bytes as u64;
+        self.delivered_at = Some(now);
+        if self.app_limited_until != 0 && self.delivered > self.app_limited_until {
+            self.app_limited_until = 0;
+        }
+        let snapshot = newest?;
+        if snapshot.delivered >= self.round_end {
+            self.round += 1;
+            self.round_end = self.delivered;
+        }
+        // 之后的样本从这个段的发送时间算起
+        self.first_sent_at = Some(snapshot.sent_at);
+
+        let send_interval = snapshot.sent_at.saturating_duration_since(snapshot.first_sent_at);
+        let ack_interval = now.saturating_duration_since(snapshot.delivered_at);
+        let sample = RateSample {
+            delivered: self.delivered - snapshot.delivered,
+            interval: send_interval.max(ack_interval),
+            rtt: now.saturating_duration_since(snapshot.sent_at),
+            app_limited: snapshot.app_limited,
+        };
+        self.update(&sample);
+        Some(sample)
+    }
+
+    // 把样本放进窗口最大值滤波器；应用受限的样本低于当前估计时不采纳
+    fn update(&mut self, sample: &RateSample) {
+        let Some(rate) = sample.bytes_per_sec() else {
+            return;
+        };
+        if sample.app_limited && rate < self.bandwidth() {
+            return;
+        }
+        while self.max_filter.front().is_some_and(|&(round, _)| round + BANDWIDTH_WINDOW <= self.round) {
+            self.max_filter.pop_front();
+        }
+        while self.max_filter.back().is_some_and(|&(_, max)| max <= rate) {
+            self.max_filter.pop_back();
+        }
+        self.max_filter.push_back((self.round, rate));
+    }
+
+    /// 带宽估计（字节每秒），还没有样本时为 0
+    pub fn bandwidth(&self) -> u64 {
+        self.max_filter.front().map_or(0, |&(_, rate)| rate)
+    }
+
+    /// 连接已送达的字节数
+    pub fn delivered(&self) -> u64 {
+        self.delivered
+    }
+
+    /// 当前是否处于应用受限期
+    pub fn is_app_limited(&self) -> bool {
+        self.app_limited_until != 0
+    }
+
+    /// 已开始的往返轮次数
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MSS: usize = 1000;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    // 容量为每毫秒一个段的链路，单程 `delay`：每毫秒发出一段，按序确认
+    fn saturate(rate: &mut DeliveryRate, t0: Instant, delay: Duration, segments: usize) -> Vec<RateSample> {
+        let mut in_flight = VecDeque::new();
+        let mut samples = Vec::new();
+        for i in 0..segments {
+            let now = t0 + ms(i as u64);
+            while in_flight.front().is_some_and(|(acked_at, _)| *acked_at <= now) {
+                let (acked_at, snapshot) = in_flight.pop_front().unwrap();
+                samples.extend(rate.on_delivered(MSS, Some(snapshot), acked_at));
+            }
+            let snapshot = rate.on_send(in_flight.len() * MSS, now);
+            in_flight.push_back((now + delay * 2 + ms(1), snapshot));
+        }
+        samples
+    }
+
+    #[test]
+    fn test_estimate_tracks_the_bottleneck() {
+        let mut rate = DeliveryRate::new();
+        let samples = saturate(&mut rate, Instant::now(), ms(5), 500);
+        assert!(samples.len() > 400);
+        // 每毫秒 1000 字节
+        let bandwidth = rate.bandwidth();
+        assert!((900_000..=1_100_000).contains(&bandwidth), "{}", bandwidth);
+        assert!(rate.round() > 10);
+        assert!(samples.iter().all(|sample| !sample.app_limited && sample.rtt == ms(11)));
+    }
+
+    #[test]
+    fn test_ack_compression_does_not_inflate_the_estimate() {
+        let mut rate = DeliveryRate::new();
+        let t0 = Instant::now();
+        // 每毫秒发出一段，第一个段在 51ms 时被确认，其余的确认积压到 60ms 一起到达
+        let first = rate.on_send(0, t0);
+        let queued: Vec<_> = (1..=50).map(|i| rate.on_send(i * MSS, t0 + ms(i as u64))).collect();
+        rate.on_delivered(MSS, Some(first), t0 + ms(51));
+        let last = rate.on_send(50 * MSS, t0 + ms(52));
+        for snapshot in queued {
+            rate.on_delivered(MSS, Some(snapshot), t0 + ms(60));
+        }
+        let sample = rate.on_delivered(MSS, Some(last), t0 + ms(60)).unwrap();
+        // 确认间隔只有 9ms，按较长的发送间隔计算，速率仍是每毫秒不到一个段
+        assert_eq!((sample.delivered, sample.interval), (51 * MSS as u64, ms(52)));
+        assert!(sample.bytes_per_sec().unwrap() < 1_000_000);
+        assert!(rate.bandwidth() < 1_000_000, "{}", rate.bandwidth());
+    }
+
+    #[test]
+    fn test_app_limited_samples_do_not_lower_the_estimate() {
+        let mut rate = DeliveryRate::new();
+        let t0 = Instant::now();
+        saturate(&mut rate, t0, ms(5), 200);
+        let bandwidth = rate.bandwidth();
+
+        // 应用每 50ms 才写一个段：样本远低于估计，被标记为应用受限且不采纳
+        let mut now = t0 + ms(1000);
+        for _ in 0..20 {
+            rate.on_app_limited(0);
+            let snapshot = rate.on_send(0, now);
+            let sample = rate.on_delivered(MSS, Some(snapshot), now + ms(11)).unwrap();
+            assert!(sample.app_limited);
+            assert!(sample.bytes_per_sec().unwrap() < bandwidth / 10);
+            now += ms(50);
+        }
+        assert_eq!(rate.bandwidth(), bandwidth);
+
+        // 同样低速但不受应用限制的样本在窗口滑过之后取代旧的估计
+        for _ in 0..=BANDWIDTH_WINDOW {
+            let snapshot = rate.on_send(0, now);
+            rate.on_delivered(MSS, Some(snapshot), now + ms(11));
+            now += ms(50);
+        }
+        assert!(!rate.is_app_limited());
+        assert!(rate.bandwidth() < bandwidth / 10, "{}", rate.bandwidth());
+    }
+}
@@ -0,0 +1,795 @@
+//! 连接的接收端
+//! 数据段经重排缓冲区按序交付；重复段（已交付或已缓存）被丢弃但仍会触发确认，
+//! 让发送方得知这次重传是多余的。按序段的确认可能被延迟，由连接任务在
+//! `next_deadline` 调用 `on_timeout` 发出。接收统计在这里累计。
+//! 对端的 FIN 以空数据体占用重排缓冲区中的一个序列号，它之前的数据全部交付后 `poll_recv` 报告流结束。
+//! 收到带 CE 标志的数据段后，之后的每个确认都设置 ECE，直到收到携带 CWR 选项的数据段。
+//! 携带 ACK-now 选项的数据段不经延迟立即确认。
+//! 携带 unordered 选项的数据段落在空洞之后时不等空洞补齐，立即从重排缓冲区取出（`ReceiveBuffer::take_early`），
+//! 先于按序的数据由 `poll_recv` 交付；按序到达的照常排队。
+//! 携带 more 选项的段是一条消息中不是最后一片的部分：按序取出后先攒着，收到最后一片时拼成整条消息交付，
+//! 交付给上层的永远是完整的消息。攒着的分片不占重排缓冲区，接收窗口照常打开，它们的内存由连接按
+//! `LinkConfig::reassembly_budget` 等限制（见 `ConnectionCore`）：被丢弃的消息已取出的分片都已确认、不会重传，
+//! 这条消息剩下的分片到达后同样丢弃，直到它的最后一片；序列号与确认不受影响，之后的消息照常交付。
+//! 携带 tag 选项的段是一条带标签的消息的第一片：标签按序列号记下，随这条消息一起由 `poll_recv_tagged` 交付，
+//! 各个流有各自的接收端，交错到达的消息不会拿到别的消息的标签。
+//! Skip 段表示发送方放弃了一段序列号上过期的消息（见 `sender` 模块）：其中还没收到的序列号以空段占住，累计确认
+//! 照常越过；按序读到这段序列号时连同已攒下的分片一起丢弃，之后是新的消息。每段空缺在 `ReceiverStats::skipped`
+//! 中只计一次，重传的 Skip 不会重复计数。
+//! 本端请求的重新同步得到回应后（见 `Connection::resync`），对端开始交付的位置之前尚未交付的数据全部丢弃：已缓存的按序读到时丢弃，
+//! 对端放弃的部分由它的 Skip 占住，攒下的分片与提前取出的无序消息一并丢弃，之后从那个位置开始是新的消息。
+//! 对端的 NewConnId 同样以空段占用一个序列号（见 `rotation` 模块），按序读到时直接越过，不影响分片的重组。
+//! 打开 `track_gaps` 后 `observe_gaps` 核对重排缓冲区中空洞的出现与结束（见 `gaps` 模块）。
+
+use crate::ack::AckGenerator;
+use crate::ack_frame::AckFrame;
+use crate::config::LinkConfig;
+use crate::gaps::{GapEvent, GapTracker};
+use crate::pool::Scratch;
+use crate::recv_buffer::{InsertOutcome, ReceiveBuffer};
+use crate::segment::{Segment, SegmentFlags};
+use crate::seq::SeqNum;
+use crate::session::{Entry, ReceiverState};
+use bytes::Bytes;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::task::{Context, Poll, Waker};
+use std::time::Instant;
+
+/// 接收端统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReceiverStats {
+    pub segments_received: u64,     // 收到的数据段总数（含重复）
+    pub bytes_received: u64,        // 收到的数据体字节数（不含重复）
+    pub duplicates_received: u64,   // 重复段数
+    pub out_of_order_received: u64, // 乱序到达并被缓存的段数
+    pub dropped: u64,               // 超出接收窗口被丢弃的段数
+    pub ce_received: u64,           // 带拥塞标记（CE）到达的段数
+    pub skipped: u64,               // 发送方放弃、按序读到时跳过的空缺数
+}
+
+/// 连同第一片上的标签（见 `SendOptions::tag`）交付的消息，没有标签时为 None
+pub type TaggedMessage = (Option<u32>, Bytes);
+
+/// 处理一个数据段的结果
+#[derive(Debug, Clone)]
+pub struct Received {
+    pub outcome: InsertOutcome,
+    pub ack: Option<AckFrame>,      // 需要立即发出的确认
+}
+
+/// 接收端状态
+#[derive(Debug)]
+pub struct Receiver {
+    buffer: ReceiveBuffer,
+    acker: AckGenerator,
+    stats: ReceiverStats,
+    recv_waker: Option<Waker>,  // 等待数据的接收方
+    early: VecDeque<TaggedMessage>, // 提前取出、尚未交付的无序消息及其标签
+    continued: HashSet<SeqNum>, // 已缓存、携带 more 选项的段
+    tags: HashMap<SeqNum, u32>, // 已缓存、携带 tag 选项的段的标签
+    fragments: Vec<Bytes>,      // 已按序取出、尚未收齐的消息的各片
+    tag: Option<u32>,           // 尚未收齐的消息的标签
+    partial: Option<(Instant, usize)>,  // 尚未收齐的消息取出第一片的时间与已攒下的字节数
+    discarding: bool,           // 当前消息已被丢弃，剩下的分片取出即丢弃
+    skipped: HashSet<SeqNum>,   // Skip 覆盖、尚未按序读到的序列号
+    gaps: HashSet<SeqNum>,      // 其中每段空缺的第一个序列号
+    markers: HashSet<SeqNum>,   // NewConnId 占用、尚未按序读到的序列号
+    floor: Option<SeqNum>,      // 重新同步后对端开始交付的位置，之前的数据按序读到时丢弃
+    fin: Option<SeqNum>,        // 对端 FIN 的序列号
+    finished: bool,             // FIN 之前的数据已全部交付
+    ece_pending: bool,          // 确认需要回送 ECE
+    holes: Option<GapTracker>,  // 订阅了空洞事件时记录尚未结束的空洞
+}
+
+impl Receiver {
+    /// `initial_seq`：期望的第一个数据段序列号
+    pub fn new(initial_seq: SeqNum, config: &LinkConfig) -> Self {
+        Self {
+            buffer: ReceiveBuffer::new(initial_seq, config.recv_window),
+            acker: AckGenerator::new(config),
+            stats: ReceiverStats::default(),
+            recv_waker: None,
+            early: VecDeque::new(),
+            continued: HashSet::new(),
+            tags: HashMap::new(),
+            fragments: Vec::new(),
+            tag: None,
+            partial: None,
+            discarding: false,
+            skipped: HashSet::new(),
+            gaps: HashSet::new(),
+            markers: HashSet::new(),
+            floor: None,
+            fin: None,
+            finished: false,
+            ece_pending: false,
+            holes: None,
+        }
+    }
+
+    /// 此后 `observe_gaps` 记录空洞事件
+    pub fn track_gaps(&mut self) {
+        self.holes.get_or_insert_with(GapTracker::default);
+    }
+
+    /// 处理完一个占用序列号的段后核对重排缓冲区中的空洞；没有打开 `track_gaps` 时什么也不做
+    pub fn observe_gaps(&mut self, now: Instant) {
+        if let Some(holes) = &mut self.holes {
+            holes.update(&self.buffer, &self.skipped, now);
+        }
+    }
+
+    /// 取出记录下的空洞事件
+    pub fn take_gap_events(&mut self) -> Vec<GapEvent> {
+        self.holes.as_mut().map(GapTracker::take_events).unwrap_or_default()
+    }
+
+    /// 处理一个数据段并交给确认生成器决定确认；重复段不会被交付，但同样会被确认。
+    pub fn on_data(&mut self, segment: &Segment, now: Instant) -> Received {
+        self.stats.segments_received += 1;
+        // 同时带 CWR 与 CE 的段：对端已响应之前的信号，这是新的一次
+        if segment.options().cwr() {
+            self.ece_pending = false;
+        }
+        if segment.flags().contains(SegmentFlags::CE) {
+            self.stats.ce_received += 1;
+            self.ece_pending = true;
+        }
+
+        let outcome = self.buffer.insert(segment.seq(), segment.data().clone());
+        if matches!(outcome, InsertOutcome::Ready | InsertOutcome::Buffered) {
+            if segment.options().more() {
+                self.continued.insert(segment.seq());
+            }
+            if let Some(tag) = segment.options().tag() {
+                self.tags.insert(segment.seq(), tag);
+            }
+        }
+        match outcome {
+            InsertOutcome::Ready => {
+                self.stats.bytes_received += segment.data().len() as u64;
+                self.wake();
+            }
+            InsertOutcome::Buffered => {
+                self.stats.bytes_received += segment.data().len() as u64;
+                self.stats.out_of_order_received += 1;
+                if segment.options().unordered()
+                    && !self.below_floor(segment.seq())
+                    && let Some(data) = self.buffer.take_early(segment.seq())
+                {
+                    self.early.push_back((self.tags.remove(&segment.seq()), data));
+                    self.wake();
+                }
+            }
+            InsertOutcome::Duplicate => self.stats.duplicates_received += 1,
+            InsertOutcome::Dropped => self.stats.dropped += 1,
+        }
+
+        let ack = match self.acker.on_data(outcome, &self.buffer, now) {
+            None if segment.options().ack_now() => Some(self.acker.ack_now(&self.buffer)),
+            ack => ack,
+        };
+        let ack = ack.map(|ack| self.echo(ack));
+        Received { outcome, ack }
+    }
+
+    /// 处理对端的 Skip：`seq..=end` 中尚未交付的序列号标为跳过，还没收到的以空段占住；超出接收窗口的部分忽略，
+    /// 等待重传的 Skip。确认立即发出，让发送方停止重传 Skip
+    pub fn on_skip(&mut self, segment: &Segment) -> Received {
+        let mut outcome = InsertOutcome::Duplicate;
+        let mut gap = None;
+        if let Some(end) = segment.skip_end() {
+            // 已交付的部分不必再看，伪造的 Skip 也不会让这里遍历很长的区间
+            let mut seq = segment.seq();
+            if seq.is_before(self.buffer.next_deliver()) {
+                seq = self.buffer.next_deliver();
+            }
+            while !seq.is_after(end) {
+                let inserted = self.buffer.insert(seq, Bytes::new());
+                if inserted == InsertOutcome::Dropped {
+                    break;
+                }
+                if inserted == InsertOutcome::Ready || outcome == InsertOutcome::Duplicate {
+                    outcome = inserted;
+                }
+                if self.buffer.holds(seq) && self.skipped.insert(seq) {
+                    gap.get_or_insert(seq);
+                }
+                seq = seq.wrapping_add(1);
+            }
+        }
+        if let Some(seq) = gap {
+            self.gaps.insert(seq);
+        }
+        if outcome == InsertOutcome::Ready {
+            self.wake();
+        }
+        let ack = self.acker.ack_now(&self.buffer);
+        Received { outcome, ack: Some(self.echo(ack)) }
+    }
+
+    /// 处理对端的 NewConnId：以空段占住它的序列号，按序读到时越过；超出窗口时忽略，等待重传。
+    /// 确认立即发出，对端收到确认后才改用新的 ID
+    pub fn on_new_conn_id(&mut self, segment: &Segment) -> Received {
+        let outcome = self.buffer.insert(segment.seq(), Bytes::new());
+        if matches!(outcome, InsertOutcome::Ready | InsertOutcome::Buffered) {
+            self.markers.insert(segment.seq());
+        }
+        if outcome == InsertOutcome::Ready {
+            self.wake();
+        }
+        let ack = self.acker.ack_now(&self.buffer);
+        Received { outcome, ack: Some(self.echo(ack)) }
+    }
+
+    /// 处理对端的 FIN：登记流结束的位置，超出窗口时忽略等待重传；确认由连接的状态迁移发出
+    pub fn on_fin(&mut self, segment: &Segment) -> InsertOutcome {
+        let outcome = self.buffer.insert(segment.seq(), Bytes::new());
+        match outcome {
+            InsertOutcome::Dropped => {}
+            InsertOutcome::Ready => {
+                self.fin = Some(segment.seq());
+                self.wake();
+            }
+            InsertOutcome::Buffered | InsertOutcome::Duplicate => self.fin = Some(segment.seq()),
+        }
+        outcome
+    }
+
+    /// 对端回应了重新同步，从 `head` 开始交付：之前尚未交付的数据都不再交给上层，攒下的分片与提前取出的无序消息一并丢弃
+    pub fn resync(&mut self, head: SeqNum) {
+        if self.buffer.next_deliver().is_before(head) {
+            self.early.clear();
+            self.discard_partial();
+            self.floor = Some(head);
+            self.wake();
+        }
+    }
+
+    fn below_floor(&self, seq: SeqNum) -> bool {
+        self.floor.is_some_and(|floor| seq.is_before(floor))
+    }
+
+    /// 对端的流已结束且数据全部交付
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// 取出下一个按序就绪的数据
+    pub fn pop_ready(&mut self) -> Option<Bytes> {
+        self.buffer.pop_ready()
+    }
+
+    /// 延迟确认定时器到期时调用，返回需要发送的确认
+    pub fn on_timeout(&mut self, now: Instant) -> Option<AckFrame> {
+        self.acker.poll_timeout(&self.buffer, now).map(|ack| self.echo(ack))
+    }
+
+    /// 上层取走数据后调用，接收窗口从 0 打开时返回窗口更新确认
+    pub fn on_window_update(&mut self) -> Option<AckFrame> {
+        self.acker.on_window_update(&self.buffer).map(|ack| self.echo(ack))
+    }
+
+    /// 按连接的内存额度限制接收窗口（见 `budget` 模块）：至多缓存 `limit` 个段，`usize::MAX` 时不限；
+    /// 窗口因放宽而从不足一半打开时返回窗口更新确认
+    pub fn set_window_limit(&mut self, limit: usize) -> Option<AckFrame> {
+        if self.buffer.limit() == limit {
+            return None;
+        }
+        self.buffer.set_limit(limit);
+        self.on_window_update()
+    }
+
+    /// 调整确认频率：攒够 `every` 个按序段后立即确认；已经攒够时返回需要发送的确认
+    pub fn set_ack_every(&mut self, every: u32, now: Instant) -> Option<AckFrame> {
+        self.acker.set_every(every);
+        self.on_timeout(now)
+    }
+
+    /// 下一次需要调用 `on_timeout` 的时间
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.acker.next_deadline()
+    }
+
+    /// 等待下一条就绪的消息：提前取出的无序消息在前，之后按序；对端的流结束后返回 None，
+    /// FIN 之前没有收齐的分片消息被丢弃。分片消息在连接的 `scratch` 中拼合
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>, now: Instant, scratch: &mut Scratch) -> Poll<Option<Bytes>> {
+        self.poll_recv_tagged(cx, now, scratch).map(|message| message.map(|(_, data)| data))
+    }
+
+    /// 同 `poll_recv`，连同消息第一片上的标签一起交付
+    pub fn poll_recv_tagged(&mut self, cx: &mut Context<'_>, now: Instant, scratch: &mut Scratch) -> Poll<Option<TaggedMessage>> {
+        if let Some(message) = self.early.pop_front() {
+            return Poll::Ready(Some(message));
+        }
+        loop {
+            if !self.finished && self.fin == Some(self.buffer.next_deliver()) && self.buffer.pop_ready().is_some() {
+                self.finished = true;
+            }
+            if self.finished {
+                return Poll::Ready(None);
+            }
+            let seq = self.buffer.next_deliver();
+            if self.floor.is_some() && !self.below_floor(seq) {
+                self.floor = None;
+            }
+            let tag = self.tags.remove(&seq);
+            match self.buffer.pop_ready() {
+                Some(_) if self.markers.remove(&seq) => {}
+                // 被放弃的消息：攒下的分片一起丢弃，之后是新的消息
+                Some(_) if self.skipped.remove(&seq) => {
+                    if self.gaps.remove(&seq) {
+                        self.stats.skipped += 1;
+                    }
+                    self.continued.remove(&seq);
+                    self.fragments.clear();
+                    self.tag = None;
+                    self.partial = None;
+                    self.discarding = false;
+                }
+                // 重新同步之前的数据：起点落在消息中间时这条消息剩下的分片同样丢弃
+                Some(_) if self.discarding || self.below_floor(seq) => self.discarding = self.continued.remove(&seq),
+                Some(data) if self.continued.remove(&seq) => {
+                    if self.fragments.is_empty() {
+                        self.tag = tag;
+                    }
+                    self.partial.get_or_insert((now, 0)).1 += data.len();
+                    self.fragments.push(data);
+                }
+                Some(data) if self.fragments.is_empty() => return Poll::Ready(Some((tag, data))),
+                Some(data) => {
+                    self.fragments.push(data);
+                    for fragment in self.fragments.drain(..) {
+                        scratch.extend(&fragment, now);
+                    }
+                    let message = scratch.split().freeze();
+                    self.partial = None;
+                    return Poll::Ready(Some((self.tag.take(), message)));
+                }
+                None => {
+                    self.recv_waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+
+    /// 尚未收齐的消息：取出第一片的时间与已攒下的字节数
+    pub fn partial(&self) -> Option<(Instant, usize)> {
+        self.partial
+    }
+
+    /// 已取出、还没交给上层的数据体字节数：攒下的分片与提前取出的无序消息
+    pub fn held_bytes(&self) -> usize {
+        self.partial.map_or(0, |(_, bytes)| bytes) + self.early.iter().map(|(_, data)| data.len()).sum::<usize>()
+    }
+
+    /// 丢弃尚未收齐的消息，它剩下的分片到达后同样丢弃
+    pub fn discard_partial(&mut self) {
+        if self.partial.take().is_some() {
+            self.fragments.clear();
+            self.tag = None;
+            self.discarding = true;
+        }
+    }
+
+    /// 连接失败：唤醒等待数据的接收方，让它观察到连接错误
+    pub fn abort(&mut self) {
+        self.wake();
+    }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.recv_waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// 立即以当前累计确认点与剩余窗口构造确认
+    pub fn ack_frame(&mut self) -> AckFrame {
+        let ack = self.acker.ack_now(&self.buffer);
+        self.echo(ack)
+    }
+
+    // 有待回送的拥塞标记时在确认上设置 ECE
+    fn echo(&self, mut ack: AckFrame) -> AckFrame {
+        ack.ece = self.ece_pending;
+        ack
+    }
+
+    /// 通告给对端的接收窗口（剩余可缓存段数）
+    pub fn advertised_window(&self) -> u32 {
+        u32::try_from(self.buffer.available()).unwrap_or(u32::MAX)
+    }
+
+    pub fn buffer(&self) -> &ReceiveBuffer {
+        &self.buffer
+    }
+
+    pub fn stats(&self) -> ReceiverStats {
+        self.stats
+    }
+
+    /// 导出可迁移的状态（见 `session` 模块）；调用方已确认对端的 FIN 还没有到达
+    pub(crate) fn export(&self) -> ReceiverState {
+        let entries = self.buffer.entries().map(|(seq, data, taken)| Entry {
+            seq,
+            data: data.clone(),
+            more: self.continued.contains(&seq),
+            skipped: self.skipped.contains(&seq),
+            gap: self.gaps.contains(&seq),
+            marker: self.markers.contains(&seq),
+            taken,
+            tag: self.tags.get(&seq).copied(),
+        });
+        ReceiverState {
+            next_deliver: self.buffer.next_deliver(),
+            floor: self.floor,
+            discarding: self.discarding,
+            entries: entries.collect(),
+            early: self.early.iter().cloned().collect(),
+            fragments: self.fragments.clone(),
+            tag: self.tag,
+        }
+    }
+
+    /// 从导出的状态重建接收端；攒下的分片从 `now` 起重新计算重组超时
+    pub(crate) fn restore(state: ReceiverState, config: &LinkConfig, now: Instant) -> Self {
+        let mut receiver = Self::new(state.next_deliver, config);
+        // 提前取出的段先放回：此时它们之前都是空洞，取出之后只留下空位，其余的段补上空洞时按序越过它们
+        for entry in state.entries.iter().filter(|entry| entry.taken) {
+            receiver.buffer.insert(entry.seq, entry.data.clone());
+            receiver.buffer.take_early(entry.seq);
+        }
+        for entry in state.entries {
+            if !entry.taken {
+                receiver.buffer.insert(entry.seq, entry.data);
+            }
+            let marks = [(entry.more, &mut receiver.continued), (entry.skipped, &mut receiver.skipped), (entry.gap, &mut receiver.gaps), (entry.marker, &mut receiver.markers)];
+            for (set, marked) in marks {
+                if set {
+                    marked.insert(entry.seq);
+                }
+            }
+            if let Some(tag) = entry.tag {
+                receiver.tags.insert(entry.seq, tag);
+            }
+        }
+        receiver.early = state.early.into();
+        if !state.fragments.is_empty() {
+            receiver.partial = Some((now, state.fragments.iter().map(Bytes::len).sum()));
+        }
+        receiver.fragments = state.fragments;
+        receiver.tag = state.tag;
+        receiver.discarding = state.discarding;
+        receiver.floor = state.floor;
+        receiver
+    }
+
+    /// 对端的 FIN 已经到达（可能还在空洞之后）
+    pub(crate) fn fin_received(&self) -> bool {
+        self.fin.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{Options, SegmentOption};
+    use crate::segment::SegmentType;
+
+    fn receiver() -> Receiver {
+        Receiver::new(SeqNum::new(0), &LinkConfig { recv_window: 16, ..LinkConfig::default() })
+    }
+
+    fn data(seq: u64) -> Segment {
+        Segment::new(SegmentType::Data, seq, seq.to_be_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_duplicate_of_buffered_head() {
+        let now = Instant::now();
+        let mut receiver = receiver();
+
+        // seq 0 已按序到达但尚未被应用取走，此时它位于重排缓冲区头部
+        assert_eq!(receiver.on_data(&data(0), now).outcome, InsertOutcome::Ready);
+        let dup = receiver.on_data(&data(0), now);
+        assert_eq!(dup.outcome, InsertOutcome::Duplicate);
+        // 重复段仍然产生确认
+        assert_eq!(dup.ack.unwrap().ack.get(), 0);
+
+        // 只交付一次
+        assert!(receiver.pop_ready().is_some());
+        assert!(receiver.pop_ready().is_none());
+        assert_eq!(receiver.stats().duplicates_received, 1);
+    }
+
+    #[test]
+    fn test_unordered_segment_skips_the_hole() {
+        let now = Instant::now();
+        let mut receiver = receiver();
+        let unordered = |seq: u64| {
+            let mut segment = data(seq);
+            segment.set_options(Options::new().with(SegmentOption::Unordered).unwrap());
+            segment
+        };
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut scratch = Scratch::new(LinkConfig::default().scratch_high_water);
+
+        // 0 丢失：无序的 1 立即交付，按序的 2 等待
+        receiver.on_data(&unordered(1), now);
+        receiver.on_data(&data(2), now);
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Ready(Some(data(1).data().clone())));
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Pending);
+        // 1 的重传不再交付
+        assert_eq!(receiver.on_data(&unordered(1), now).outcome, InsertOutcome::Duplicate);
+
+        receiver.on_data(&data(0), now);
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Ready(Some(data(0).data().clone())));
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Ready(Some(data(2).data().clone())));
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Pending);
+        assert_eq!(receiver.buffer().cumulative_ack(), SeqNum::new(2));
+    }
+
+    #[test]
+    fn test_fragments_are_delivered_as_one_message() {
+        let now = Instant::now();
+        let mut receiver = receiver();
+        let fragment = |seq: u64, more: bool| {
+            let mut segment = data(seq);
+            if more {
+                segment.set_options(Options::new().with(SegmentOption::More).unwrap());
+            }
+            segment
+        };
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut scratch = Scratch::new(LinkConfig::default().scratch_high_water);
+
+        // 0 是独立的消息，1..=3 是一条消息的三片，2 迟到
+        receiver.on_data(&fragment(0, false), now);
+        receiver.on_data(&fragment(1, true), now);
+        receiver.on_data(&fragment(3, false), now);
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Ready(Some(data(0).data().clone())));
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Pending);
+        // 已取出的第一片腾出了缓冲区
+        assert_eq!(receiver.buffer().len(), 1);
+
+        receiver.on_data(&fragment(2, true), now);
+        let message: Bytes = (1..=3u64).flat_map(u64::to_be_bytes).collect();
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Ready(Some(message)));
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Pending);
+    }
+
+    #[test]
+    fn test_tags_follow_their_messages() {
+        let now = Instant::now();
+        let mut receiver = receiver();
+        let segment = |seq: u64, options: &[SegmentOption]| {
+            let mut segment = data(seq);
+            segment.set_options(options.iter().fold(Options::new(), |options, option| options.with(*option).unwrap()));
+            segment
+        };
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut scratch = Scratch::new(LinkConfig::default().scratch_high_water);
+
+        // 0 带标签；1..=2 是带标签的分片消息，只有第一片携带；3 没有标签；5 是带标签的无序消息，越过空洞 4
+        receiver.on_data(&segment(5, &[SegmentOption::Unordered, SegmentOption::Tag(50)]), now);
+        receiver.on_data(&segment(2, &[]), now);
+        receiver.on_data(&segment(0, &[SegmentOption::Tag(10)]), now);
+        receiver.on_data(&segment(1, &[SegmentOption::More, SegmentOption::Tag(11)]), now);
+        receiver.on_data(&segment(3, &[]), now);
+        let fragmented: Bytes = (1..=2u64).flat_map(u64::to_be_bytes).collect();
+        for expected in [(Some(50), data(5).data().clone()), (Some(10), data(0).data().clone()), (Some(11), fragmented), (None, data(3).data().clone())] {
+            assert_eq!(receiver.poll_recv_tagged(&mut cx, now, &mut scratch), Poll::Ready(Some(expected)));
+        }
+        assert_eq!(receiver.poll_recv_tagged(&mut cx, now, &mut scratch), Poll::Pending);
+
+        // 被丢弃的分片消息的标签不会落到下一条消息上
+        receiver.on_data(&segment(4, &[SegmentOption::More, SegmentOption::Tag(40)]), now);
+        assert_eq!(receiver.poll_recv_tagged(&mut cx, now, &mut scratch), Poll::Pending);
+        receiver.discard_partial();
+        receiver.on_data(&segment(6, &[]), now);
+        receiver.on_data(&segment(7, &[]), now);
+        assert_eq!(receiver.poll_recv_tagged(&mut cx, now, &mut scratch), Poll::Ready(Some((None, data(7).data().clone()))));
+        assert!(receiver.tags.is_empty());
+    }
+
+    #[test]
+    fn test_discarded_message_skips_its_remaining_fragments() {
+        let now = Instant::now();
+        let mut receiver = receiver();
+        let fragment = |seq: u64, more: bool| {
+            let mut segment = data(seq);
+            if more {
+                segment.set_options(Options::new().with(SegmentOption::More).unwrap());
+            }
+            segment
+        };
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut scratch = Scratch::new(LinkConfig::default().scratch_high_water);
+
+        // 0..=2 是一条消息，取出两片后被丢弃；3 是下一条
+        receiver.on_data(&fragment(0, true), now);
+        receiver.on_data(&fragment(1, true), now);
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Pending);
+        assert_eq!(receiver.partial(), Some((now, 16)));
+        receiver.discard_partial();
+        assert_eq!(receiver.partial(), None);
+
+        receiver.on_data(&fragment(2, false), now);
+        receiver.on_data(&fragment(3, false), now);
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Ready(Some(data(3).data().clone())));
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Pending);
+        assert_eq!(receiver.buffer().cumulative_ack(), SeqNum::new(3));
+    }
+
+    #[test]
+    fn test_duplicate_after_delivery() {
+        let now = Instant::now();
+        let mut receiver = receiver();
+        receiver.on_data(&data(0), now);
+        receiver.on_data(&data(1), now);
+        assert!(receiver.pop_ready().is_some());
+        assert!(receiver.pop_ready().is_some());
+
+        // 原始段已交付给应用后，重传的副本到达
+        let dup = receiver.on_data(&data(0), now);
+        assert_eq!(dup.outcome, InsertOutcome::Duplicate);
+        assert_eq!(dup.ack.unwrap().ack.get(), 1);
+        assert!(receiver.pop_ready().is_none());
+
+        let stats = receiver.stats();
+        assert_eq!(stats.segments_received, 3);
+        assert_eq!(stats.duplicates_received, 1);
+        assert_eq!(stats.bytes_received, 16);
+    }
+
+    #[test]
+    fn test_fin_ends_stream_after_earlier_data() {
+        let now = Instant::now();
+        let mut receiver = receiver();
+        let waker = std::task::Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        let mut scratch = Scratch::new(LinkConfig::default().scratch_high_water);
+
+        // FIN 先于 seq 1 到达：必须等空洞补齐、数据交付之后才报告结束
+        receiver.on_data(&data(0), now);
+        let fin = Segment::builder(SegmentType::Fin).data_seq(2u64).build().unwrap();
+        assert_eq!(receiver.on_fin(&fin), InsertOutcome::Buffered);
+        assert!(receiver.poll_recv(&mut cx, now, &mut scratch).is_ready());
+        assert!(receiver.poll_recv(&mut cx, now, &mut scratch).is_pending());
+
+        receiver.on_data(&data(1), now);
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Ready(Some(Bytes::from(1u64.to_be_bytes().to_vec()))));
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Ready(None));
+        assert!(receiver.is_finished());
+        // FIN 本身也被累计确认
+        assert_eq!(receiver.ack_frame().ack.get(), 2);
+    }
+
+    #[test]
+    fn test_ce_echoed_until_cwr() {
+        let now = Instant::now();
+        let mut receiver = receiver();
+        // 按序段的确认可能被延迟，逐段取立即确认
+        let mut ece = |segment: Segment| {
+            receiver.on_data(&segment, now);
+            receiver.ack_frame().ece
+        };
+        assert!(!ece(data(0)));
+
+        let mut marked = data(1);
+        marked.set_flag(SegmentFlags::CE, true);
+        assert!(ece(marked));
+        // 之后没有标记的段的确认同样回送，直到对端表明已经降窗
+        assert!(ece(data(2)));
+
+        let mut cwr = data(3);
+        cwr.set_options(crate::options::Options::new().with(crate::options::SegmentOption::Cwr).unwrap());
+        assert!(!ece(cwr));
+        assert!(!ece(data(4)));
+        assert_eq!(receiver.stats().ce_received, 1);
+    }
+
+    #[test]
+    fn test_skip_discards_the_abandoned_message_once() {
+        let now = Instant::now();
+        let mut receiver = receiver();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut scratch = Scratch::new(LinkConfig::default().scratch_high_water);
+        // 被放弃的消息：第一片 0 已到达，1 与 2 丢失；之后的消息 3 等在空洞之后
+        let mut first = data(0);
+        first.set_options(Options::new().with(SegmentOption::More).unwrap());
+        receiver.on_data(&first, now);
+        receiver.on_data(&data(3), now);
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Pending);
+
+        let skip = Segment::skip(SeqNum::new(0), SeqNum::new(2));
+        let received = receiver.on_skip(&skip);
+        assert_eq!(received.outcome, InsertOutcome::Ready);
+        assert_eq!(received.ack.unwrap().ack.get(), 3);
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Ready(Some(data(3).data().clone())));
+        assert_eq!(receiver.stats().skipped, 1);
+
+        // 重传的 Skip 只被确认
+        assert_eq!(receiver.on_skip(&skip).outcome, InsertOutcome::Duplicate);
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Pending);
+        assert_eq!(receiver.stats().skipped, 1);
+    }
+
+    #[test]
+    fn test_resync_discards_everything_before_the_head() {
+        let now = Instant::now();
+        let mut receiver = receiver();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut scratch = Scratch::new(LinkConfig::default().scratch_high_water);
+        // 0 已缓存未读，1 是一条消息的第一片；2 丢失，对端从 4 开始交付，放弃的 2、3 由 Skip 占住
+        let mut first = data(1);
+        first.set_options(Options::new().with(SegmentOption::More).unwrap());
+        receiver.on_data(&data(0), now);
+        receiver.on_data(&first, now);
+        receiver.resync(SeqNum::new(4));
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Pending);
+        receiver.on_skip(&Segment::skip(SeqNum::new(2), SeqNum::new(3)));
+        receiver.on_data(&data(4), now);
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Ready(Some(data(4).data().clone())));
+        assert_eq!(receiver.buffer().cumulative_ack().get(), 4);
+
+        // 已经读过起点时什么也不丢
+        receiver.on_data(&data(5), now);
+        receiver.resync(SeqNum::new(5));
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Ready(Some(data(5).data().clone())));
+    }
+
+    #[test]
+    fn test_new_conn_id_does_not_split_a_message() {
+        let now = Instant::now();
+        let mut receiver = receiver();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut scratch = Scratch::new(LinkConfig::default().scratch_high_water);
+        let mut first = data(0);
+        first.set_options(Options::new().with(SegmentOption::More).unwrap());
+        receiver.on_data(&first, now);
+        receiver.on_data(&data(1), now);
+
+        // 两条消息之间的 NewConnId 按序读到时被越过
+        let received = receiver.on_new_conn_id(&Segment::new_conn_id(SeqNum::new(2), 9));
+        assert_eq!((received.outcome, received.ack.unwrap().ack.get()), (InsertOutcome::Ready, 2));
+        receiver.on_data(&data(3), now);
+        let message: Bytes = (0..=1u64).flat_map(u64::to_be_bytes).collect();
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Ready(Some(message)));
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Ready(Some(data(3).data().clone())));
+        assert_eq!(receiver.poll_recv(&mut cx, now, &mut scratch), Poll::Pending);
+
+        // 重传的 NewConnId 只被确认
+        assert_eq!(receiver.on_new_conn_id(&Segment::new_conn_id(SeqNum::new(2), 9)).outcome, InsertOutcome::Duplicate);
+    }
+
+    #[test]
+    fn test_ack_now_bypasses_the_delay() {
+        let now = Instant::now();
+        let mut receiver = receiver();
+        let mut urgent = data(0);
+        urgent.set_options(crate::options::Options::new().with(crate::options::SegmentOption::AckNow).unwrap());
+        assert_eq!(receiver.on_data(&urgent, now).ack.map(|ack| ack.ack.get()), Some(0));
+        // 请求只对这一个段有效：下一个按序段照常延迟
+        assert!(receiver.on_data(&data(1), now).ack.is_none());
+        assert!(receiver.next_deadline().is_some());
+    }
+
+    #[test]
+    fn test_window_limit_shrinks_and_reopens() {
+        let now = Instant::now();
+        let mut receiver = receiver();
+        assert!(receiver.set_window_limit(2).is_none());
+        assert_eq!(receiver.ack_frame().window, 2);
+        // 窗口外的段被丢弃，等对端重传
+        assert_eq!(receiver.on_data(&data(3), now).outcome, InsertOutcome::Dropped);
+        assert_eq!(receiver.on_data(&data(1), now).outcome, InsertOutcome::Buffered);
+        assert_eq!(receiver.advertised_window(), 1);
+
+        // 放宽后窗口打开到一半以上，立即通告
+        let update = receiver.set_window_limit(usize::MAX).unwrap();
+        assert_eq!(update.window, 15);
+        assert!(receiver.set_window_limit(usize::MAX).is_none());
+    }
+}
@@ -0,0 +1,411 @@
+//! 接收端重排缓冲区
+//! UDP 会乱序到达，数据段在这里按序列号缓存，严格按序交付给上层，
+//! 同时为确认生成器提供累计确认点与 SACK 区间。无序交付的段可以在空洞补齐之前由 `take_early` 提前取出，
+//! 它仍占着自己的位置（累计确认、SACK 与接收窗口都不变），按序交付到这里时被跳过。
+//! 内部以相对初始序列号的偏移量索引（单调递增、不会回绕），对外只暴露 `SeqNum`；缓存的段放在容量为接收窗口的环形缓冲区中
+//! （见 `ring` 模块），`ReceiveBuffer<MapBacked>` 换成有序映射，行为相同。
+
+use crate::ring::{Backing, RingBacked, SeqStore};
+use crate::sack::SackBlocks;
+use crate::seq::SeqNum;
+use bytes::Bytes;
+
+/// `ReceiveBuffer::insert` 的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertOutcome {
+    Ready,      // 补齐了连续序列，`pop_ready` 可以取出数据
+    Buffered,   // 乱序到达，已缓存等待前面的空洞
+    Duplicate,  // 已交付或已缓存过的序列号
+    Dropped,    // 超出接收窗口（容量），直接丢弃
+}
+
+// 一个已收到、尚未交付的段
+#[derive(Debug)]
+struct Pending {
+    data: Bytes,
+    early: bool,    // 已提前取出，只留下空位
+}
+
+/// 重排缓冲区：按序号缓存数据段，按序交付
+#[derive(Debug)]
+pub struct ReceiveBuffer<B: Backing = RingBacked> {
+    origin: SeqNum,                 // 初始序列号，偏移量 0
+    next_deliver: u64,              // 下一个交付给上层的偏移量
+    cum_next: u64,                  // 第一个尚未连续收到的偏移量（累计确认点 + 1）
+    pending: B::Store<Pending>,     // 已收到但尚未交付的段，按偏移量索引（含已连续、待取出的部分）
+    capacity: usize,                // 最多缓存的段数（接收窗口）
+    limit: usize,                   // `set_limit` 收小的窗口，不限时为 usize::MAX
+    bytes: usize,                   // 缓存的段合计的数据字节数，提前取出的不计
+}
+
+impl ReceiveBuffer {
+    /// `next_seq`：期望收到的第一个数据段序列号；`capacity`：最多缓存的段数
+    pub fn new(next_seq: SeqNum, capacity: usize) -> Self {
+        Self::with_backing(next_seq, capacity)
+    }
+}
+
+impl<B: Backing> ReceiveBuffer<B> {
+    /// 同 `new`，内部存储由 `B` 选择
+    pub fn with_backing(next_seq: SeqNum, capacity: usize) -> Self {
+        Self {
+            origin: next_seq,
+            next_deliver: 0,
+            cum_next: 0,
+            pending: B::Store::with_capacity(capacity),
+            capacity,
+            limit: usize::MAX,
+            bytes: 0,
+        }
+    }
+
+    /// 插入一个数据段
+    pub fn insert(&mut self, seq: SeqNum, data: Bytes) -> InsertOutcome {
+        // 已交付，或已缓存的段视为重复
+        let distance = self.seq_at(self.next_deliver).distance(seq);
+        let Ok(distance) = u64::try_from(distance) else {
+            return InsertOutcome::Duplicate;
+        };
+        let offset = self.next_deliver + distance;
+        if self.pending.contains(offset) {
+            return InsertOutcome::Duplicate;
+        }
+        // 窗口为 [next_deliver, next_deliver + window)，超出的最新段直接丢弃
+        if distance >= self.window() as u64 {
+            return InsertOutcome::Dropped;
+        }
+
+        self.bytes += data.len();
+        self.pending.insert(offset, Pending { data, early: false });
+        if offset != self.cum_next {
+            return InsertOutcome::Buffered;
+        }
+
+        // 补齐空洞后累计确认点向前推进，吸收所有已缓存的连续段
+        while self.pending.contains(self.cum_next) {
+            self.cum_next += 1;
+        }
+        self.skip_early();
+        InsertOutcome::Ready
+    }
+
+    /// 取出下一个按序就绪的数据，没有时返回 None
+    pub fn pop_ready(&mut self) -> Option<Bytes> {
+        if self.next_deliver == self.cum_next {
+            return None;
+        }
+        let pending = self.pending.remove(self.next_deliver)?;
+        self.bytes -= pending.data.len();
+        self.next_deliver += 1;
+        self.skip_early();
+        Some(pending.data)
+    }
+
+    /// 提前取出已缓存在空洞之后的 `seq`；它不在空洞之后（按序交付即可）或已经取出过时返回 None
+    pub fn take_early(&mut self, seq: SeqNum) -> Option<Bytes> {
+        let offset = self.next_deliver + u64::try_from(self.seq_at(self.next_deliver).distance(seq)).ok()?;
+        if offset <= self.cum_next {
+            return None;
+        }
+        let pending = self.pending.get_mut(offset).filter(|pending| !pending.early)?;
+        pending.early = true;
+        self.bytes -= pending.data.len();
+        Some(std::mem::take(&mut pending.data))
+    }
+
+    /// `seq` 已收到、尚未交付，也没有被提前取出
+    pub fn holds(&self, seq: SeqNum) -> bool {
+        let Ok(distance) = u64::try_from(self.seq_at(self.next_deliver).distance(seq)) else {
+            return false;
+        };
+        let offset = self.next_deliver + distance;
+        self.pending.get(offset).is_some_and(|pending| !pending.early)
+    }
+
+    // 按序交付的位置越过已提前取出的段
+    fn skip_early(&mut self) {
+        while self.next_deliver < self.cum_next && self.pending.get(self.next_deliver).is_some_and(|pending| pending.early) {
+            self.pending.remove(self.next_deliver);
+            self.next_deliver += 1;
+        }
+    }
+
+    /// 累计确认点：该序列号（含）之前的数据都已收到
+    pub fn cumulative_ack(&self) -> SeqNum {
+        self.seq_at(self.cum_next).wrapping_sub(1)
+    }
+
+    /// 期望收到的下一个序列号
+    pub fn next_expected(&self) -> SeqNum {
+        self.seq_at(self.cum_next)
+    }
+
+    /// 下一个交付给上层的序列号
+    pub fn next_deliver(&self) -> SeqNum {
+        self.seq_at(self.next_deliver)
+    }
+
+    /// 累计确认点之后是否还有已缓存的段（即存在空洞）
+    pub fn has_gaps(&self) -> bool {
+        self.pending.next_key(self.cum_next, u64::MAX).is_some()
+    }
+
+    /// 累计确认点之后已收到的段组成的闭区间 `(start, end)`（按序列号先后排列）
+    pub fn sack_ranges(&self) -> Vec<(SeqNum, SeqNum)> {
+        let mut ranges: Vec<(u64, u64)> = Vec::new();
+        for (offset, _) in self.pending.range(self.cum_next, u64::MAX) {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == offset => *end = offset,
+                _ => ranges.push((offset, offset)),
+            }
+        }
+        ranges.into_iter().map(|(start, end)| (self.seq_at(start), self.seq_at(end))).collect()
+    }
+
+    /// 同 `sack_ranges`，只取前 `MAX_BLOCKS` 个区间（一个确认能携带的全部），不分配
+    pub fn sack_blocks(&self) -> SackBlocks {
+        let mut blocks = SackBlocks::new();
+        let mut current: Option<(u64, u64)> = None;
+        for (offset, _) in self.pending.range(self.cum_next, u64::MAX) {
+            match &mut current {
+                Some((_, end)) if *end + 1 == offset => *end = offset,
+                _ => {
+                    if let Some((start, end)) = current.replace((offset, offset))
+                        && !blocks.push(self.seq_at(start), self.seq_at(end))
+                    {
+                        return blocks;
+                    }
+                }
+            }
+        }
+        if let Some((start, end)) = current {
+            blocks.push(self.seq_at(start), self.seq_at(end));
+        }
+        blocks
+    }
+
+    /// 累计确认点之后尚未收到、其后已有段到达的序列号组成的闭区间 `(start, end)`（按序列号先后排列）
+    pub fn holes(&self) -> Vec<(SeqNum, SeqNum)> {
+        let mut holes = Vec::new();
+        let mut next = self.cum_next;
+        for (offset, _) in self.pending.range(self.cum_next, u64::MAX) {
+            if offset > next {
+                holes.push((self.seq_at(next), self.seq_at(offset - 1)));
+            }
+            next = offset + 1;
+        }
+        holes
+    }
+
+    /// 已收到、尚未交付的段，按序列号先后：序列号、数据与是否已被提前取出（只留下空位）
+    pub fn entries(&self) -> impl Iterator<Item = (SeqNum, &Bytes, bool)> + '_ {
+        self.pending.range(0, u64::MAX).map(|(offset, pending)| (self.seq_at(offset), &pending.data, pending.early))
+    }
+
+    /// 当前缓存的段数（含就绪未取出的）
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// 缓存的段合计的数据字节数（含就绪未取出的），提前取出的不计
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// 剩余可缓存的段数，用于通告接收窗口
+    pub fn available(&self) -> usize {
+        self.window().saturating_sub(self.pending.len())
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// 当前的接收窗口（段数）：容量与 `set_limit` 的限制中较小的一个
+    pub fn window(&self) -> usize {
+        self.capacity.min(self.limit)
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// 把接收窗口收小到 `limit` 个段（不超过容量），`usize::MAX` 时恢复；已缓存的段不受影响，之后落在窗口之外的段被丢弃
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    fn seq_at(&self, offset: u64) -> SeqNum {
+        self.origin.wrapping_add(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ring::MapBacked;
+
+    fn payload(seq: u64) -> Bytes {
+        Bytes::from(seq.to_be_bytes().to_vec())
+    }
+
+    fn seq(n: u64) -> SeqNum {
+        SeqNum::new(n)
+    }
+
+    // 同一组测试分别在环形缓冲区与有序映射上运行，两者的行为必须一致
+    macro_rules! backing_tests {
+        ($backing:ty) => {
+            use super::*;
+
+            fn buffer(next_seq: SeqNum, capacity: usize) -> ReceiveBuffer<$backing> {
+                ReceiveBuffer::with_backing(next_seq, capacity)
+            }
+
+            #[test]
+            fn test_reorder_1_3_2() {
+                let mut buf = buffer(seq(1), 16);
+
+                assert_eq!(buf.insert(seq(1), payload(1)), InsertOutcome::Ready);
+                assert_eq!(buf.insert(seq(3), payload(3)), InsertOutcome::Buffered);
+                assert_eq!(buf.pop_ready(), Some(payload(1)));
+                // 3 在空洞之后，不能提前交付
+                assert_eq!(buf.pop_ready(), None);
+                assert_eq!(buf.cumulative_ack(), seq(1));
+                assert_eq!(buf.sack_ranges(), vec![(seq(3), seq(3))]);
+                assert_eq!(buf.holes(), vec![(seq(2), seq(2))]);
+
+                assert_eq!(buf.insert(seq(2), payload(2)), InsertOutcome::Ready);
+                assert_eq!(buf.cumulative_ack(), seq(3));
+                assert_eq!(buf.pop_ready(), Some(payload(2)));
+                assert_eq!(buf.pop_ready(), Some(payload(3)));
+                assert_eq!(buf.pop_ready(), None);
+                assert!(buf.sack_ranges().is_empty());
+                assert!(buf.holes().is_empty());
+            }
+
+            #[test]
+            fn test_early_segment_is_skipped_in_order() {
+                let mut buf = buffer(seq(0), 16);
+                buf.insert(seq(2), payload(2));
+                buf.insert(seq(3), payload(3));
+                assert_eq!(buf.bytes(), 16);
+                assert_eq!(buf.take_early(seq(3)), Some(payload(3)));
+                assert_eq!(buf.take_early(seq(3)), None);
+                // 提前取出的段仍被确认与计入窗口，重复到达时不再交付
+                assert_eq!(buf.sack_ranges(), vec![(seq(2), seq(3))]);
+                assert_eq!(buf.available(), 14);
+                assert_eq!(buf.bytes(), 8);
+                assert_eq!(buf.insert(seq(3), payload(3)), InsertOutcome::Duplicate);
+
+                buf.insert(seq(0), payload(0));
+                assert_eq!(buf.take_early(seq(0)), None);
+                assert_eq!(buf.insert(seq(1), payload(1)), InsertOutcome::Ready);
+                assert_eq!(buf.cumulative_ack(), seq(3));
+                assert_eq!([buf.pop_ready(), buf.pop_ready(), buf.pop_ready()], [Some(payload(0)), Some(payload(1)), Some(payload(2))]);
+                assert_eq!(buf.pop_ready(), None);
+                assert_eq!(buf.next_deliver(), seq(4));
+                assert!(buf.is_empty());
+                assert_eq!(buf.bytes(), 0);
+            }
+
+            #[test]
+            fn test_duplicate_of_delivered_seq() {
+                let mut buf = buffer(seq(0), 16);
+                buf.insert(seq(0), payload(0));
+                assert_eq!(buf.pop_ready(), Some(payload(0)));
+
+                // 已交付的序列号再次到达，不应被再次交付
+                assert_eq!(buf.insert(seq(0), payload(0)), InsertOutcome::Duplicate);
+                assert_eq!(buf.pop_ready(), None);
+
+                // 已缓存（未交付）的乱序段重复到达
+                buf.insert(seq(2), payload(2));
+                assert_eq!(buf.insert(seq(2), payload(2)), InsertOutcome::Duplicate);
+                assert_eq!(buf.len(), 1);
+            }
+
+            #[test]
+            fn test_gap_never_fills() {
+                let mut buf = buffer(seq(0), 16);
+                buf.insert(seq(0), payload(0));
+                for n in 2..6 {
+                    assert_eq!(buf.insert(seq(n), payload(n)), InsertOutcome::Buffered);
+                }
+
+                assert_eq!(buf.pop_ready(), Some(payload(0)));
+                // seq 1 一直缺失：之后的数据全部滞留，累计确认点停在 0
+                assert_eq!(buf.pop_ready(), None);
+                assert_eq!(buf.cumulative_ack(), seq(0));
+                assert_eq!(buf.sack_ranges(), vec![(seq(2), seq(5))]);
+                assert_eq!(&buf.sack_blocks()[..], &buf.sack_ranges()[..]);
+                assert_eq!(buf.len(), 4);
+            }
+
+            #[test]
+            fn test_capacity_overflow_drops_newest() {
+                let mut buf = buffer(seq(0), 4);
+                // 窗口为 [0, 4)：1..=3 乱序缓存
+                for n in 1..4 {
+                    assert_eq!(buf.insert(seq(n), payload(n)), InsertOutcome::Buffered);
+                }
+                // 超出窗口的新段被丢弃，缓冲区不增长
+                assert_eq!(buf.insert(seq(4), payload(4)), InsertOutcome::Dropped);
+                assert_eq!(buf.insert(seq(100), payload(100)), InsertOutcome::Dropped);
+                assert_eq!(buf.len(), 3);
+                assert_eq!(buf.available(), 1);
+
+                // 空洞补齐并交付后窗口前移，之前被丢弃的段可以重新接收
+                assert_eq!(buf.insert(seq(0), payload(0)), InsertOutcome::Ready);
+                while buf.pop_ready().is_some() {}
+                assert_eq!(buf.insert(seq(4), payload(4)), InsertOutcome::Ready);
+            }
+
+            #[test]
+            fn test_limit_shrinks_the_window() {
+                let mut buf = buffer(seq(0), 16);
+                buf.insert(seq(5), payload(5));
+                buf.set_limit(3);
+                assert_eq!((buf.window(), buf.available()), (3, 2));
+                // 已缓存的段留着，窗口外的新段被丢弃
+                assert_eq!(buf.insert(seq(3), payload(3)), InsertOutcome::Dropped);
+                assert_eq!(buf.insert(seq(2), payload(2)), InsertOutcome::Buffered);
+                assert_eq!(buf.len(), 2);
+                buf.set_limit(usize::MAX);
+                assert_eq!((buf.window(), buf.available()), (16, 14));
+                assert_eq!(buf.insert(seq(3), payload(3)), InsertOutcome::Buffered);
+            }
+
+            #[test]
+            fn test_sequence_wraps_around_max() {
+                let start = seq(u64::MAX - 1);
+                let mut buf = buffer(start, 16);
+
+                // u64::MAX - 1, u64::MAX, 0, 1 按序列号算术连续
+                assert_eq!(buf.insert(start.wrapping_add(2), payload(0)), InsertOutcome::Buffered);
+                assert_eq!(buf.sack_ranges(), vec![(seq(0), seq(0))]);
+                assert_eq!(buf.insert(start, payload(1)), InsertOutcome::Ready);
+                assert_eq!(buf.insert(start.wrapping_add(1), payload(2)), InsertOutcome::Ready);
+                assert_eq!(buf.cumulative_ack(), seq(0));
+                assert_eq!(buf.next_expected(), seq(1));
+
+                // 回绕前的旧序列号是重复段
+                while buf.pop_ready().is_some() {}
+                assert_eq!(buf.insert(start, payload(1)), InsertOutcome::Duplicate);
+                assert_eq!(buf.insert(seq(1), payload(3)), InsertOutcome::Ready);
+            }
+        };
+    }
+
+    mod ring {
+        backing_tests!(RingBacked);
+    }
+
+    mod map {
+        backing_tests!(MapBacked);
+    }
+}
@@ -0,0 +1,160 @@
+//! 反射流量的识别与抑制
+//! 对端把收到的数据报原样发回（配置错误的转发、两台互相指向的服务器、原始回显模式的服务器）时，两端会无休止地来回传递同一批段。
+//! 监听器与客户端的握手各自生成一个随机的实例 ID，随自己发出的握手段以及监听器回应陌生地址的段（Rst、Retry）一起发出
+//! （`options::SegmentOption::Instance`），收到携带自己实例 ID 的段就知道是被反射回来的：监听器丢弃并计入
+//! `MetricsSnapshot::reflected`，握手中的客户端忽略它，不会与自己的 SYN 完成一次"同时打开"。已建立连接的段不打标记，
+//! 它们携带连接 ID，反射回来时不会被任何连接接受。
+//!
+//! 实例 ID 属于单个监听器或单次握手，而不是整个进程：同一进程中的客户端连向自己的监听器、两个互相连接的进程内服务器，
+//! 各自的段都带着对方不认识的 ID，不会被误判。对端不会发出与本端相同的 ID（32 位随机数碰撞的概率可以忽略）。
+//!
+//! 原始回显模式不解析数据报，无法识别反射；`RepeatLimiter` 改为限制同一个数据体在 `window` 内回显给同一个对端的次数，
+//! 两台互相指向的回显服务器之间的数据报在超过上限后被丢弃，环路随之消失。正常的对端很少在短时间内反复发送完全相同的数据报。
+
+use crate::options::SegmentOption;
+use crate::segment::Segment;
+use crate::util::ExpiringMap;
+use std::hash::{BuildHasher, RandomState};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// 原始回显在一个窗口内把同一个数据体回显给同一个对端的默认次数上限
+pub const DEFAULT_REPEAT_LIMIT: u32 = 64;
+
+/// 默认的计数窗口
+pub const DEFAULT_REPEAT_WINDOW: Duration = Duration::from_secs(1);
+
+/// `RepeatLimiter` 同时记录的（对端, 数据体）数上限，超出时淘汰窗口最早结束的记录
+const MAX_TRACKED: usize = 4096;
+
+/// 新的实例 ID，非零
+pub(crate) fn fresh_instance() -> u32 {
+    loop {
+        let mut random = [0u8; 4];
+        getrandom::fill(&mut random).expect("operating system random source unavailable");
+        let id = u32::from_be_bytes(random);
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+/// 在段头选项中打上实例 ID；已经带有实例 ID 或选项区放不下时保持原样。签名的握手段须在签名之前打上
+pub(crate) fn stamp(segment: &mut Segment, instance: u32) {
+    if segment.options().instance().is_some() {
+        return;
+    }
+    let mut options = segment.options().clone();
+    if options.push(SegmentOption::Instance(instance)).is_ok() {
+        segment.set_options(options);
+    }
+}
+
+/// 段是否由 `instance` 发出后被反射了回来
+pub(crate) fn is_reflected(segment: &Segment, instance: u32) -> bool {
+    segment.options().instance() == Some(instance)
+}
+
+/// 限制同一个数据体在一个窗口内回显给同一个对端的次数。数据体以进程内随机密钥散列，外部无法构造碰撞
+#[derive(Debug)]
+pub struct RepeatLimiter {
+    limit: u32,
+    window: Duration,
+    hasher: RandomState,
+    seen: ExpiringMap<(SocketAddr, u64), u32>,  // 窗口内已回显的次数，窗口结束时过期
+}
+
+impl RepeatLimiter {
+    /// `limit` 为 0 时不限制
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self { limit, window, hasher: RandomState::new(), seen: ExpiringMap::new(MAX_TRACKED) }
+    }
+
+    /// 能否把 `payload` 回显给 `peer`；允许时计入一次
+    pub fn admit(&mut self, peer: SocketAddr, payload: &[u8], now: Instant) -> bool {
+        if self.limit == 0 {
+            return true;
+        }
+        let key = (peer, self.hasher.hash_one(payload));
+        match self.seen.get_mut(&key, now) {
+            Some(count) if *count >= self.limit => false,
+            Some(count) => {
+                *count += 1;
+                true
+            }
+            // 第一次见到或上一个窗口已结束：从这里开始一个新窗口。表满时被淘汰的记录重新计数，最坏情况下环路多持续一个窗口
+            None => {
+                self.seen.insert(key, 1, self.window, now);
+                true
+            }
+        }
+    }
+
+    /// 正在记录的（对端, 数据体）数
+    pub fn tracked(&self) -> usize {
+        self.seen.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+    use crate::segment::SegmentType;
+
+    #[test]
+    fn test_stamp_and_detect() {
+        let ours = fresh_instance();
+        let mut rst = Segment::builder(SegmentType::Rst).build().unwrap();
+        stamp(&mut rst, ours);
+        assert!(is_reflected(&rst, ours));
+        assert!(!is_reflected(&rst, ours.wrapping_add(1)));
+        // 已有的实例 ID 不被覆盖
+        stamp(&mut rst, ours.wrapping_add(1));
+        assert_eq!(rst.options().instance(), Some(ours));
+
+        let decoded = Segment::decode(&rst.encode().unwrap()).unwrap();
+        assert!(is_reflected(&decoded, ours));
+    }
+
+    #[test]
+    fn test_stamp_skips_full_options() {
+        let mut options = Options::new();
+        while options.push(SegmentOption::Mss(1200)).is_ok() {}
+        let mut segment = Segment::builder(SegmentType::Ack).options(options.clone()).build().unwrap();
+        stamp(&mut segment, 7);
+        assert_eq!(segment.options(), &options);
+    }
+
+    #[test]
+    fn test_repeat_limiter_window() {
+        let now = Instant::now();
+        let peer: SocketAddr = "10.0.0.1:9000".parse().unwrap();
+        let other: SocketAddr = "10.0.0.2:9000".parse().unwrap();
+        let mut limiter = RepeatLimiter::new(3, Duration::from_secs(1));
+        for _ in 0..3 {
+            assert!(limiter.admit(peer, b"ping", now));
+        }
+        assert!(!limiter.admit(peer, b"ping", now));
+        // 其他数据体与其他对端各自计数
+        assert!(limiter.admit(peer, b"pong", now));
+        assert!(limiter.admit(other, b"ping", now));
+        // 窗口过后重新计数
+        assert!(limiter.admit(peer, b"ping", now + Duration::from_secs(1)));
+
+        let mut unlimited = RepeatLimiter::new(0, Duration::from_secs(1));
+        assert!((0..1000).all(|_| unlimited.admit(peer, b"ping", now)));
+        assert_eq!(unlimited.tracked(), 0);
+    }
+
+    #[test]
+    fn test_repeat_limiter_bounded() {
+        let now = Instant::now();
+        let peer: SocketAddr = "10.0.0.1:9000".parse().unwrap();
+        let mut limiter = RepeatLimiter::new(1, Duration::from_millis(10));
+        for n in 0..MAX_TRACKED as u32 * 2 {
+            assert!(limiter.admit(peer, &n.to_be_bytes(), now + Duration::from_micros(u64::from(n))));
+        }
+        assert!(limiter.tracked() <= MAX_TRACKED);
+    }
+}
@@ -0,0 +1,164 @@
+//! 被拒数据报的诊断记录
+//! 监听器把无法解析的数据报（含被截断的，记为 `SegmentError::TooShort`）记入一个固定容量的环形缓冲区（`LinkConfig::reject_log` 条，为 0 时不记录）：来源、时间、
+//! 解析错误与数据报的前 `PREFIX` 字节。槽位在创建时分配好，记录一条只复制字节前缀，满了以后覆盖最旧的一条。
+//! `Server::recent_rejects` 读出当前内容；服务器关闭时（unix 上收到 SIGUSR1 时也一样）以十六进制转储写入日志。
+
+use crate::segment::SegmentError;
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// 每条记录保存的数据报前缀长度（字节）
+pub const PREFIX: usize = 128;
+
+/// 一个被拒的数据报
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reject {
+    pub from: SocketAddr,
+    pub at: SystemTime,
+    pub error: SegmentError,
+    pub len: usize,         // 数据报的完整长度
+    prefix: [u8; PREFIX],
+    kept: usize,            // prefix 中有效的字节数
+}
+
+impl Reject {
+    const EMPTY: Reject = Reject {
+        from: SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        at: SystemTime::UNIX_EPOCH,
+        error: SegmentError::TooShort,
+        len: 0,
+        prefix: [0; PREFIX],
+        kept: 0,
+    };
+
+    /// 数据报的前 `PREFIX` 字节（较短的数据报为全部）
+    pub fn bytes(&self) -> &[u8] {
+        &self.prefix[..self.kept]
+    }
+}
+
+/// 首行是来源、时间与错误，其后每 16 字节一行，格式同 `hexdump -C`
+impl fmt::Display for Reject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let at = self.at.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+        write!(f, "{} at {}.{:06}: {} ({} bytes", self.from, at.as_secs(), at.subsec_micros(), self.error, self.len)?;
+        match self.kept < self.len {
+            true => write!(f, ", first {} shown)", self.kept)?,
+            false => write!(f, ")")?,
+        }
+        hexdump(f, self.bytes())
+    }
+}
+
+/// 以 `hexdump -C` 的格式写出 `bytes`，每 16 字节一行，每行之前换行
+pub(crate) fn hexdump(f: &mut impl fmt::Write, bytes: &[u8]) -> fmt::Result {
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        write!(f, "\n{:08x} ", row * 16)?;
+        for column in 0..16 {
+            if column == 8 {
+                write!(f, " ")?;
+            }
+            match chunk.get(column) {
+                Some(byte) => write!(f, " {:02x}", byte)?,
+                None => write!(f, "   ")?,
+            }
+        }
+        let text: String = chunk.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }).collect();
+        write!(f, "  |{}|", text)?;
+    }
+    Ok(())
+}
+
+/// 固定容量的被拒数据报环形缓冲区，由监听器的各个分发任务共用
+#[derive(Debug)]
+pub struct RejectLog {
+    ring: Mutex<Ring>,
+}
+
+#[derive(Debug)]
+struct Ring {
+    slots: Vec<Reject>,
+    next: usize,    // 下一条写入的槽位
+    len: usize,     // 已写入的槽位数
+}
+
+impl RejectLog {
+    /// 预先分配 `capacity` 个槽位
+    pub fn new(capacity: usize) -> Self {
+        Self { ring: Mutex::new(Ring { slots: vec![Reject::EMPTY; capacity], next: 0, len: 0 }) }
+    }
+
+    /// 记录一个被拒的数据报，覆盖最旧的一条；容量为 0 时什么也不做
+    pub fn record(&self, from: SocketAddr, error: &SegmentError, datagram: &[u8]) {
+        let mut ring = self.ring.lock().expect("reject log poisoned");
+        let capacity = ring.slots.len();
+        if capacity == 0 {
+            return;
+        }
+        let next = ring.next;
+        let slot = &mut ring.slots[next];
+        let kept = datagram.len().min(PREFIX);
+        slot.from = from;
+        slot.at = SystemTime::now();
+        slot.error = error.clone();
+        slot.len = datagram.len();
+        slot.prefix[..kept].copy_from_slice(&datagram[..kept]);
+        slot.kept = kept;
+        ring.next = (next + 1) % capacity;
+        ring.len = (ring.len + 1).min(capacity);
+    }
+
+    /// 当前保存的记录，从旧到新
+    pub fn recent(&self) -> Vec<Reject> {
+        let ring = self.ring.lock().expect("reject log poisoned");
+        let start = (ring.next + ring.slots.len() - ring.len) % ring.slots.len().max(1);
+        (0..ring.len).map(|i| ring.slots[(start + i) % ring.slots.len()].clone()).collect()
+    }
+}
+
+/// 以 warn 级别逐条写入日志，每条一个事件；没有记录时什么也不写
+pub fn dump(rejects: &[Reject]) {
+    if rejects.is_empty() {
+        return;
+    }
+    tracing::warn!(count = rejects.len(), "recent rejected datagrams");
+    for reject in rejects {
+        tracing::warn!("{}", reject);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port)
+    }
+
+    #[test]
+    fn test_ring_keeps_the_newest_entries() {
+        let log = RejectLog::new(2);
+        for port in 1..=3 {
+            log.record(addr(port), &SegmentError::UnknownFrameType(port as u8), &[port as u8; 200]);
+        }
+        let recent = log.recent();
+        assert_eq!(recent.iter().map(|reject| reject.from.port()).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(recent[1].bytes(), &[3; PREFIX][..]);
+        assert_eq!(recent[1].len, 200);
+        assert!(RejectLog::new(0).recent().is_empty());
+    }
+
+    #[test]
+    fn test_display_is_a_hexdump() {
+        let log = RejectLog::new(4);
+        log.record(addr(9), &SegmentError::TooShort, b"0123456789abcdefXY\x00");
+        let text = log.recent()[0].to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("127.0.0.1:9 at "), "{}", lines[0]);
+        assert!(lines[0].ends_with("(19 bytes)"), "{}", lines[0]);
+        assert_eq!(lines[1], "00000000  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  |0123456789abcdef|");
+        assert_eq!(lines[2], "00000010  58 59 00                                          |XY.|");
+    }
+}
@@ -0,0 +1,356 @@
+//! 数据报记录与重放
+//! `RecordWriter` 是一个 `CaptureSink`：设为 `LinkConfig::capture` 后把收到的每个数据报（只记入站方向）连同
+//! 距上一个记录的时间、来源与目的地址追加到记录文件。与 `PcapWriter` 一样经有界队列交给单独的写入任务，
+//! 队列满时丢弃并计数，收发不等待磁盘。
+//!
+//! 文件格式（整数都是大端序）：文件头 `magic "LKRP"(4) | version(1)`，之后逐个记录
+//! `delay_us(8) | from | to | len(4) | datagram`，地址编码为 `family(1，4 或 6) | ip(4 或 16) | port(2)`。
+//! 第一个记录的 delay 为 0。
+//!
+//! `replay` 在内存网络上启动一个监听器，绑定在记录的目的地址，从各个记录的来源地址按原来的相对时间
+//! （可按 `speed` 加速）发出记录的数据报，返回监听器一侧发生的事件：接受的连接、交付的消息、对端关闭与连接错误。
+//! 监听器的 ISN 与连接 ID 是新生成的，与记录时不同；握手与数据段不依赖它们，对端对本端数据的确认则对不上，
+//! 重放适合检查对端发来的数据如何被处理。
+
+use crate::capture::CaptureSink;
+use crate::config::LinkConfig;
+use crate::error::LinkError;
+use crate::listener::Listener;
+use crate::trace::Direction;
+use crate::transport::{MemoryNetwork, MemoryTransport, Transport};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::{mpsc, oneshot};
+
+/// 记录文件的魔数
+pub const MAGIC: [u8; 4] = *b"LKRP";
+
+/// 本端写入的格式版本
+pub const VERSION: u8 = 1;
+
+/// 写入任务的队列长度（记录数）
+const RECORD_QUEUE: usize = 4096;
+
+/// 重放完最后一个数据报之后，多久没有新的事件即结束
+pub const SETTLE: Duration = Duration::from_millis(500);
+
+/// 记录文件中的一个数据报
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recorded {
+    pub delay: Duration,    // 距上一个记录的时间
+    pub from: SocketAddr,
+    pub to: SocketAddr,
+    pub datagram: Bytes,
+}
+
+/// 记录文件无法解析
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated { offset: usize },    // 从这个偏移开始的记录不完整
+    BadAddress { offset: usize },   // 地址族既不是 4 也不是 6
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::BadMagic => write!(f, "not a datagram record file"),
+            RecordError::UnsupportedVersion(version) => write!(f, "unsupported record file version {}", version),
+            RecordError::Truncated { offset } => write!(f, "record at offset {} is truncated", offset),
+            RecordError::BadAddress { offset } => write!(f, "record at offset {} has an unknown address family", offset),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl Recorded {
+    pub(crate) fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&u64::try_from(self.delay.as_micros()).unwrap_or(u64::MAX).to_be_bytes());
+        encode_addr(self.from, out);
+        encode_addr(self.to, out);
+        out.extend_from_slice(&(self.datagram.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.datagram);
+    }
+}
+
+fn encode_addr(addr: SocketAddr, out: &mut Vec<u8>) {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            out.push(4);
+            out.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            out.push(6);
+            out.extend_from_slice(&ip.octets());
+        }
+    }
+    out.extend_from_slice(&addr.port().to_be_bytes());
+}
+
+/// 文件头
+pub fn file_header() -> [u8; 5] {
+    let mut header = [0u8; 5];
+    header[..4].copy_from_slice(&MAGIC);
+    header[4] = VERSION;
+    header
+}
+
+/// 解析整个记录文件
+pub fn decode(bytes: &[u8]) -> Result<Vec<Recorded>, RecordError> {
+    if bytes.len() < 5 || bytes[..4] != MAGIC {
+        return Err(RecordError::BadMagic);
+    }
+    if bytes[4] != VERSION {
+        return Err(RecordError::UnsupportedVersion(bytes[4]));
+    }
+    let mut records = Vec::new();
+    let mut offset = 5;
+    while offset < bytes.len() {
+        let mut reader = Reader { bytes, at: offset };
+        let truncated = RecordError::Truncated { offset };
+        let delay = Duration::from_micros(u64::from_be_bytes(reader.take(8).ok_or(truncated.clone())?.try_into().expect("8 bytes")));
+        let from = reader.addr(offset)?;
+        let to = reader.addr(offset)?;
+        let len = u32::from_be_bytes(reader.take(4).ok_or(truncated.clone())?.try_into().expect("4 bytes")) as usize;
+        let datagram = Bytes::copy_from_slice(reader.take(len).ok_or(truncated)?);
+        records.push(Recorded { delay, from, to, datagram });
+        offset = reader.at;
+    }
+    Ok(records)
+}
+
+/// 读出并解析记录文件
+pub async fn read(path: impl AsRef<Path>) -> io::Result<Vec<Recorded>> {
+    let bytes = tokio::fs::read(path).await?;
+    decode(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Option<&[u8]> {
+        let taken = self.bytes.get(self.at..self.at.checked_add(len)?)?;
+        self.at += len;
+        Some(taken)
+    }
+
+    fn addr(&mut self, offset: usize) -> Result<SocketAddr, RecordError> {
+        let truncated = RecordError::Truncated { offset };
+        let ip = match self.take(1).ok_or(truncated.clone())?[0] {
+            4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(self.take(4).ok_or(truncated.clone())?).expect("4 bytes"))),
+            6 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(self.take(16).ok_or(truncated.clone())?).expect("16 bytes"))),
+            _ => return Err(RecordError::BadAddress { offset }),
+        };
+        let port = u16::from_be_bytes(self.take(2).ok_or(truncated)?.try_into().expect("2 bytes"));
+        Ok(SocketAddr::new(ip, port))
+    }
+}
+
+enum Command {
+    Record { at: Instant, from: SocketAddr, to: SocketAddr, datagram: Bytes },
+    Flush(oneshot::Sender<()>),
+}
+
+/// 把收到的数据报追加到记录文件的 `CaptureSink`
+#[derive(Debug)]
+pub struct RecordWriter {
+    tx: mpsc::Sender<Command>,
+    dropped: AtomicU64,
+}
+
+impl RecordWriter {
+    /// 创建（覆盖）文件，写入文件头并启动写入任务
+    pub async fn create(path: impl AsRef<Path>) -> io::Result<RecordWriter> {
+        let mut file = BufWriter::new(tokio::fs::File::create(path).await?);
+        file.write_all(&file_header()).await?;
+        file.flush().await?;
+        let (tx, rx) = mpsc::channel(RECORD_QUEUE);
+        tokio::spawn(write_loop(file, rx));
+        Ok(RecordWriter { tx, dropped: AtomicU64::new(0) })
+    }
+
+    /// 等待此前交给它的记录全部写入文件
+    pub async fn flush(&self) {
+        let (done, flushed) = oneshot::channel();
+        if self.tx.send(Command::Flush(done)).await.is_ok() {
+            let _ = flushed.await;
+        }
+    }
+
+    /// 队列已满而丢弃的记录数
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl CaptureSink for RecordWriter {
+    fn record(&self, direction: Direction, local: SocketAddr, peer: SocketAddr, datagram: &[u8]) {
+        if direction != Direction::Inbound {
+            return;
+        }
+        let command = Command::Record { at: Instant::now(), from: peer, to: local, datagram: Bytes::copy_from_slice(datagram) };
+        if self.tx.try_send(command).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// 写入任务：记录之间的时间在这里由到达时间算出；队列取空时刷新缓冲，出错后只回应 flush
+async fn write_loop(mut file: BufWriter<tokio::fs::File>, mut rx: mpsc::Receiver<Command>) {
+    let mut failed = false;
+    let mut last: Option<Instant> = None;
+    let mut buf = Vec::new();
+    while let Some(command) = rx.recv().await {
+        let result = match command {
+            Command::Flush(done) if failed => {
+                let _ = done.send(());
+                continue;
+            }
+            _ if failed => continue,
+            Command::Record { at, from, to, datagram } => {
+                let delay = last.map_or(Duration::ZERO, |last| at.saturating_duration_since(last));
+                last = Some(at);
+                buf.clear();
+                Recorded { delay, from, to, datagram }.encode(&mut buf);
+                let result = file.write_all(&buf).await;
+                if result.is_ok() && rx.is_empty() { file.flush().await } else { result }
+            }
+            Command::Flush(done) => {
+                let result = file.flush().await;
+                let _ = done.send(());
+                result
+            }
+        };
+        if let Err(e) = result {
+            tracing::warn!(error = %e, "record file write failed, no further datagrams are recorded");
+            failed = true;
+        }
+    }
+    let _ = file.flush().await;
+}
+
+/// 重放时监听器一侧发生的事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayEvent {
+    Accepted(SocketAddr),
+    Message(SocketAddr, Bytes),     // 连接交付的一条消息
+    Finished(SocketAddr),           // 对端关闭了写方向
+    Failed(SocketAddr, LinkError),
+}
+
+impl fmt::Display for ReplayEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayEvent::Accepted(peer) => write!(f, "{} accepted", peer),
+            ReplayEvent::Message(peer, data) => write!(f, "{} message of {} bytes: {:?}", peer, data.len(), String::from_utf8_lossy(&data[..data.len().min(64)])),
+            ReplayEvent::Finished(peer) => write!(f, "{} finished", peer),
+            ReplayEvent::Failed(peer, e) => write!(f, "{} failed: {}", peer, e),
+        }
+    }
+}
+
+/// 把记录的数据报按原来的相对时间发给一个新的监听器（以 `config` 为参数），返回它一侧的事件。
+/// `speed` 大于 1 时按比例压缩记录之间的间隔，`f64::INFINITY` 时不等待；只重放发往第一个记录的目的地址的数据报
+pub async fn replay(records: &[Recorded], config: LinkConfig, speed: f64) -> Result<Vec<ReplayEvent>, LinkError> {
+    let Some(first) = records.first() else {
+        return Ok(Vec::new());
+    };
+    let target = first.to;
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(target)?, config)?;
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let accepting = tokio::spawn(accept_loop(listener, tx));
+
+    let mut peers: HashMap<SocketAddr, MemoryTransport> = HashMap::new();
+    for record in records {
+        if speed.is_finite() && speed > 0.0 {
+            tokio::time::sleep(record.delay.div_f64(speed)).await;
+        }
+        if record.to != target {
+            continue;
+        }
+        let peer = match peers.entry(record.from) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => entry.insert(network.bind(record.from)?),
+        };
+        peer.send_to(&record.datagram, target).await?;
+        tokio::task::yield_now().await;
+    }
+
+    let mut events = Vec::new();
+    while let Ok(Some(event)) = tokio::time::timeout(SETTLE, rx.recv()).await {
+        events.push(event);
+    }
+    accepting.abort();
+    Ok(events)
+}
+
+// 接受重放出的连接，每个连接读到结束或出错
+async fn accept_loop(listener: Listener, tx: mpsc::UnboundedSender<ReplayEvent>) {
+    while let Ok((connection, peer)) = listener.accept().await {
+        let _ = tx.send(ReplayEvent::Accepted(peer));
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            loop {
+                let event = match connection.recv().await {
+                    Ok(Some(data)) => ReplayEvent::Message(peer, data),
+                    Ok(None) => ReplayEvent::Finished(peer),
+                    Err(e) => ReplayEvent::Failed(peer, e),
+                };
+                let done = !matches!(event, ReplayEvent::Message(..));
+                if tx.send(event).is_err() || done {
+                    return;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<Recorded> {
+        vec![
+            Recorded { delay: Duration::ZERO, from: "10.0.0.2:5000".parse().unwrap(), to: "10.0.0.1:7000".parse().unwrap(), datagram: Bytes::from_static(b"one") },
+            Recorded { delay: Duration::from_micros(1500), from: "[::1]:9".parse().unwrap(), to: "[::2]:10".parse().unwrap(), datagram: Bytes::new() },
+        ]
+    }
+
+    #[test]
+    fn test_records_round_trip() {
+        let mut file = file_header().to_vec();
+        for record in records() {
+            record.encode(&mut file);
+        }
+        assert_eq!(decode(&file).unwrap(), records());
+
+        // 截断在第二个记录中间
+        let second = 5 + 8 + 7 + 7 + 4 + 3;
+        assert_eq!(decode(&file[..file.len() - 1]), Err(RecordError::Truncated { offset: second }));
+        assert_eq!(decode(&file[..5]).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn test_header_is_checked() {
+        assert_eq!(decode(b"PCAP\x01"), Err(RecordError::BadMagic));
+        assert_eq!(decode(b"LKR"), Err(RecordError::BadMagic));
+        assert_eq!(decode(b"LKRP\x02"), Err(RecordError::UnsupportedVersion(2)));
+        let mut file = file_header().to_vec();
+        file.extend_from_slice(&[0; 8]);
+        file.push(5);
+        assert_eq!(decode(&file), Err(RecordError::BadAddress { offset: 5 }));
+    }
+}
@@ -0,0 +1,782 @@
+//! 发送端重传队列
+//! 记录每个已发送、尚未确认的数据段及其发送时间，超过 RTO 未被确认时交还给调用方重发。
+//! 时间由调用方注入（`now` 参数），连接任务用 tokio 定时器驱动 `next_deadline()`，测试可用任意时钟。
+//! 每个段的重传定时器登记在时间轮中（见 `timer` 模块），确认时 O(1) 取消，找最近的截止时间不需要遍历在途段。
+//! 连续超时按指数退避（RTO 翻倍至上限），超过重试次数后队列进入失败状态并返回 `PeerUnreachable`。
+//! 内部以相对第一个发送段的偏移量索引，序列号回绕不影响排序；在途段放在容量为发送窗口的环形缓冲区中（见 `ring` 模块），
+//! `RetransmitQueue<MapBacked>` 换成有序映射，行为相同。
+//! SACK 覆盖的段被标记为已收到，不再计入在途、也不会在超时后重传，但在累计确认越过之前仍保留；
+//! 空洞之上已有 `DUP_ACK_THRESHOLD` 个段被 SACK 时判定它丢失（RFC 6675 的 DupThresh，与三个重复确认对应），
+//! 排队等待 `poll_lost` 重传；只被一两个段越过的空洞可能只是乱序，等待后续的 SACK 或 RTO。
+//! 发送方放弃过期的消息时以 `abandon` 移出它还没被确认的段，它们不再重传、不再计入在途。
+//! 每个段（重）发出时记下连接的投递状态（见 `rate` 模块），确认时把其中最后发出的段的快照交给 `sample_rate` 采样投递速率。
+
+use crate::ack_frame::AckFrame;
+use crate::error::LinkError;
+use crate::rate::{DeliveryRate, DeliverySnapshot, RateSample};
+use crate::ring::{Backing, RingBacked, SeqStore};
+use crate::sack::SackInfo;
+use crate::segment::Segment;
+use crate::sender::DUP_ACK_THRESHOLD;
+use crate::seq::SeqNum;
+use crate::timer::{self, Timers};
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
+// 队列中的一个未确认段
+#[derive(Debug)]
+struct Entry {
+    segment: Segment,
+    len: usize,             // 数据体字节数（计入在途字节）
+    sent_at: Instant,       // 最近一次（重）发送时间
+    retransmits: u32,       // 已重传次数
+    sacked: bool,           // 已被 SACK 确认
+    lost_marked: bool,      // 已被判定丢失（排队中或已快速重传），超时重传前不再重复判定
+    delivery: DeliverySnapshot, // 最近一次（重）发送时连接的投递状态
+}
+
+/// 一次确认的处理结果，供 RTT 采样与拥塞控制使用
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Acked {
+    pub segments: usize,            // 新确认的段数（含新被 SACK 的段）
+    pub bytes: usize,               // 新确认的字节数
+    pub newest_sent_at: Option<Instant>,  // 被确认的最大序列号段的最近发送时间
+    pub newest_retransmitted: bool, // 该段是否被重传过（Karn 算法据此丢弃样本）
+    pub advanced: bool,             // 累计确认点是否推进
+    pub lost: usize,                // 本次 SACK 新判定丢失的段数
+    pub delivery: Option<DeliverySnapshot>, // 新确认的段中最后（重）发出的那个发出时的投递状态
+}
+
+impl Acked {
+    // 保留最后（重）发出的段的快照
+    fn note_delivery(&mut self, delivery: DeliverySnapshot) {
+        if self.delivery.is_none_or(|newest| delivery.sent_at() >= newest.sent_at()) {
+            self.delivery = Some(delivery);
+        }
+    }
+}
+
+/// 超时退避策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackoffPolicy {
+    pub max_rto: Duration,  // 退避后 RTO 的上限
+    pub max_retries: u32,   // 连续超时重传的最大次数，超过后判定对端不可达
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        Self {
+            max_rto: Duration::from_secs(60),
+            max_retries: 8,
+        }
+    }
+}
+
+/// 重传队列：按序列号索引在途的数据段
+#[derive(Debug)]
+pub struct RetransmitQueue<B: Backing = RingBacked> {
+    origin: Option<SeqNum>,     // 第一个发送的序列号，偏移量 0
+    entries: B::Store<Entry>,   // 按偏移量索引
+    highest_sent: Option<u64>,  // 已发送的最大偏移量，用于过滤确认未发送数据的 ack
+    highest_sacked: Option<u64>,// 被 SACK 的最大偏移量
+    sacked: usize,              // 已被 SACK、尚未累计确认的段数
+    lost: BTreeSet<u64>,        // 判定丢失、等待重传的偏移量
+    timers: Timers<u64>,        // 未被 SACK 的段的下一次重传时间，按偏移量
+    rto: Duration,
+    in_flight_bytes: usize,
+    policy: BackoffPolicy,
+    consecutive_timeouts: u32,  // 连续超时次数，任何有效确认都会清零
+    failure: Option<LinkError>, // 进入失败状态后的错误（粘滞）
+    rate: DeliveryRate,
+}
+
+impl RetransmitQueue {
+    pub fn new(rto: Duration) -> Self {
+        Self::with_policy(rto, BackoffPolicy::default())
+    }
+
+    pub fn with_policy(rto: Duration, policy: BackoffPolicy) -> Self {
+        Self::with_backing(rto, policy)
+    }
+}
+
+impl<B: Backing> RetransmitQueue<B> {
+    /// 同 `with_policy`，内部存储由 `B` 选择
+    pub fn with_backing(rto: Duration, policy: BackoffPolicy) -> Self {
+        Self {
+            origin: None,
+            entries: B::Store::with_capacity(0),
+            highest_sent: None,
+            highest_sacked: None,
+            sacked: 0,
+            lost: BTreeSet::new(),
+            timers: Timers::new(timer::DEFAULT_GRANULARITY),
+            rto,
+            in_flight_bytes: 0,
+            policy,
+            consecutive_timeouts: 0,
+            failure: None,
+            rate: DeliveryRate::new(),
+        }
+    }
+
+    /// 重传定时器所在时间轮的刻度，应在发送第一个段之前设置
+    pub fn with_granularity(mut self, granularity: Duration) -> Self {
+        self.timers = Timers::new(granularity);
+        self
+    }
+
+    /// 预计同时在途的段数（发送窗口），按它预留存储；超出时按需扩容。应在发送第一个段之前设置
+    pub fn with_capacity(mut self, window: usize) -> Self {
+        self.entries = B::Store::with_capacity(window);
+        self
+    }
+
+    /// 更新 RTO，只影响之后（重新）安排的定时器
+    pub fn set_rto(&mut self, rto: Duration) {
+        self.rto = rto;
+    }
+
+    pub fn rto(&self) -> Duration {
+        self.rto
+    }
+
+    /// 记录一个刚发出的数据段；队列已失败时返回失败原因
+    pub fn on_send(&mut self, segment: Segment, now: Instant) -> Result<(), LinkError> {
+        if let Some(e) = &self.failure {
+            return Err(e.clone());
+        }
+        let seq = segment.seq();
+        let origin = *self.origin.get_or_insert(seq);
+        // 早于第一个发送段的序列号不属于本队列管理的范围
+        let Ok(offset) = u64::try_from(origin.distance(seq)) else {
+            return Ok(());
+        };
+        let len = segment.data().len();
+        let entry = Entry {
+            segment,
+            len,
+            sent_at: now,
+            retransmits: 0,
+            sacked: false,
+            lost_marked: false,
+            delivery: self.rate.on_send(self.in_flight_bytes, now),
+        };
+
+        if let Some(old) = self.entries.insert(offset, entry) {
+            self.forget(offset, &old);
+        }
+        self.timers.set(offset, Some(now + self.rto));
+        self.in_flight_bytes += len;
+        self.highest_sent = Some(self.highest_sent.map_or(offset, |h| h.max(offset)));
+        Ok(())
+    }
+
+    // 序列号对应的偏移量；早于第一个发送段时为 None
+    fn offset(&self, seq: SeqNum) -> Option<u64> {
+        u64::try_from(self.origin?.distance(seq)).ok()
+    }
+
+    /// 累计确认：移除序列号不大于 `ack` 的所有段。
+    /// 确认尚未发送的数据属于非法 ack，直接忽略。
+    pub fn on_ack(&mut self, ack: SeqNum) -> Acked {
+        let Some(ack) = self.offset(ack) else {
+            return Acked::default();
+        };
+        match self.highest_sent {
+            Some(highest) if ack <= highest => {}
+            _ => return Acked::default(),
+        }
+
+        // 逐个从头部取出
+        let mut result = Acked::default();
+        while let Some(offset) = self.entries.next_key(0, ack) {
+            let entry = self.entries.remove(offset).expect("next_key returns a live offset");
+            // 已被 SACK 的段在当时已经计入
+            if !entry.sacked {
+                result.segments += 1;
+                result.bytes += entry.len;
+                result.note_delivery(entry.delivery);
+            }
+            self.forget(offset, &entry);
+            result.newest_sent_at = Some(entry.sent_at);
+            result.newest_retransmitted = entry.retransmits > 0;
+            result.advanced = true;
+            self.consecutive_timeouts = 0;
+        }
+        result
+    }
+
+    /// 处理一个 SACK 确认：先按累计确认点移除段，再标记区间覆盖的段为已收到，
+    /// 并把之上已有 `DUP_ACK_THRESHOLD` 个段被 SACK、尚未判定过的空洞排入丢失队列
+    pub fn on_sack(&mut self, sack: &SackInfo) -> Acked {
+        self.on_ranges(sack.cumulative, &sack.ranges)
+    }
+
+    /// 处理一个确认帧：不带 SACK 区间时同 `on_ack`，否则同 `on_sack`，不需要构造 `SackInfo`
+    pub fn on_ack_frame(&mut self, frame: &AckFrame) -> Acked {
+        if frame.sack.is_empty() {
+            self.on_ack(frame.ack)
+        } else {
+            self.on_ranges(frame.ack, &frame.sack)
+        }
+    }
+
+    fn on_ranges(&mut self, cumulative: SeqNum, ranges: &[(SeqNum, SeqNum)]) -> Acked {
+        let mut result = self.on_ack(cumulative);
+        let Some(highest_sent) = self.highest_sent else {
+            return result;
+        };
+
+        let mut newest: Option<(u64, Instant, bool)> = None;
+        for &(start, end) in ranges {
+            // 区间在第一个发送段之前或晚于已发送数据的部分被忽略
+            let Some(end) = self.offset(end).map(|end| end.min(highest_sent)) else {
+                continue;
+            };
+            let start = self.offset(start).unwrap_or(0);
+            if start > end {
+                continue;
+            }
+            let mut next = Some(start);
+            while let Some(offset) = next.and_then(|from| self.entries.next_key(from, end)) {
+                next = offset.checked_add(1);
+                let entry = self.entries.get_mut(offset).expect("next_key returns a live offset");
+                if entry.sacked {
+                    continue;
+                }
+                entry.sacked = true;
+                self.timers.set(offset, None);
+                self.sacked += 1;
+                self.in_flight_bytes -= entry.len;
+                self.lost.remove(&offset);
+                result.segments += 1;
+                result.bytes += entry.len;
+                result.note_delivery(entry.delivery);
+                if newest.is_none_or(|(n, _, _)| offset > n) {
+                    newest = Some((offset, entry.sent_at, entry.retransmits > 0));
+                }
+            }
+            self.highest_sacked = Some(self.highest_sacked.map_or(end, |h| h.max(end)));
+        }
+        if let Some((_, sent_at, retransmitted)) = newest {
+            self.consecutive_timeouts = 0;
+            if !result.advanced {
+                result.newest_sent_at = Some(sent_at);
+                result.newest_retransmitted = retransmitted;
+            }
+        }
+
+        // 从最高 SACK 段往下数已 SACK 的段，越过的够多时空洞视为丢失
+        if let Some(highest) = self.highest_sacked {
+            let mut sacked_above = 0;
+            let mut next = Some(highest);
+            while let Some(offset) = next.and_then(|to| self.entries.prev_key(0, to)) {
+                next = offset.checked_sub(1);
+                let entry = self.entries.get_mut(offset).expect("prev_key returns a live offset");
+                if entry.sacked {
+                    sacked_above += 1;
+                } else if sacked_above >= DUP_ACK_THRESHOLD && !entry.lost_marked {
+                    entry.lost_marked = true;
+                    self.lost.insert(offset);
+                    result.lost += 1;
+                }
+            }
+        }
+        result
+    }
+
+    /// 取出最多 `max` 个判定丢失的段用于重传（按序列号先后），并重新安排它们的定时器
+    pub fn poll_lost(&mut self, now: Instant, max: usize) -> Vec<Segment> {
+        let rto = self.backoff_rto();
+        let mut resend = Vec::new();
+        while resend.len() < max
+            && let Some(offset) = self.lost.pop_first()
+        {
+            if let Some(entry) = self.entries.get_mut(offset) {
+                entry.sent_at = now;
+                entry.delivery = self.rate.on_send(self.in_flight_bytes, now);
+                self.timers.set(offset, Some(now + rto));
+                entry.retransmits += 1;
+                resend.push(entry.segment.clone());
+            }
+        }
+        resend
+    }
+
+    /// 队列中序列号为 `seq` 的段已重传的次数；不在队列中时为 None
+    pub fn retransmits(&self, seq: SeqNum) -> Option<u32> {
+        self.entries.get(self.offset(seq)?).map(|entry| entry.retransmits)
+    }
+
+    /// 判定丢失、等待重传的段（按序列号先后）
+    pub fn lost(&self) -> impl Iterator<Item = SeqNum> + '_ {
+        self.lost.iter().filter_map(|&offset| self.entries.get(offset)).map(|e| e.segment.seq())
+    }
+
+    // 段离开队列时撤销它在各项计数中的贡献
+    fn forget(&mut self, offset: u64, entry: &Entry) {
+        if entry.sacked {
+            self.sacked -= 1;
+        } else {
+            self.in_flight_bytes -= entry.len;
+        }
+        self.lost.remove(&offset);
+        self.timers.set(offset, None);
+    }
+
+    /// 放弃 `first..=last` 中尚未被累计确认的段，返回其中最小的序列号；区间中没有未确认的段，
+    /// 或者剩下的都已被 SACK（对端已经全部收到）时什么也不做，返回 None
+    pub fn abandon(&mut self, first: SeqNum, last: SeqNum) -> Option<SeqNum> {
+        let start = self.offset(first).unwrap_or(0);
+        let end = self.offset(last)?;
+        if start > end || self.entries.range(start, end).all(|(_, entry)| entry.sacked) {
+            return None;
+        }
+        let offsets: Vec<u64> = self.entries.range(start, end).map(|(offset, _)| offset).collect();
+        let lowest = self.entries.get(offsets[0]).expect("offset collected from the entries").segment.seq();
+        for offset in offsets {
+            let entry = self.entries.remove(offset).expect("offset collected from the entries");
+            self.forget(offset, &entry);
+        }
+        Some(lowest)
+    }
+
+    /// 撤回从 `first` 起的全部段，按序列号先后返回：它们登记后其实没有发出（如发送时本机报告数据报过大），
+    /// 调用方以同样的序列号重新编号发送。其中有重传过或被 SACK 的段（对端可能已经收到）时什么也不做，返回 None
+    pub fn withdraw(&mut self, first: SeqNum) -> Option<Vec<Segment>> {
+        let start = self.offset(first)?;
+        if self.entries.range(start, u64::MAX).any(|(_, entry)| entry.retransmits > 0 || entry.sacked) {
+            return None;
+        }
+        let offsets: Vec<u64> = self.entries.range(start, u64::MAX).map(|(offset, _)| offset).collect();
+        let mut segments = Vec::with_capacity(offsets.len());
+        for offset in offsets {
+            let entry = self.entries.remove(offset).expect("offset collected from the entries");
+            self.forget(offset, &entry);
+            segments.push(entry.segment);
+        }
+        self.highest_sent = self.highest_sent.filter(|&highest| highest < start).or(start.checked_sub(1));
+        Some(segments)
+    }
+
+    /// 以一次确认的结果采样投递速率，`now` 是确认到达的时间；没有新确认数据时为 None
+    pub fn sample_rate(&mut self, acked: &Acked, now: Instant) -> Option<RateSample> {
+        self.rate.on_delivered(acked.bytes, acked.delivery, now)
+    }
+
+    /// 发送方没有更多数据、窗口还有空位：在此之前发出的数据送达之前的样本被标记为应用受限
+    pub fn on_app_limited(&mut self) {
+        self.rate.on_app_limited(self.in_flight_bytes);
+    }
+
+    /// 投递状态与带宽估计
+    pub fn delivery_rate(&self) -> &DeliveryRate {
+        &self.rate
+    }
+
+    /// 选择性确认单个段，未知序列号忽略
+    pub fn on_selective_ack(&mut self, seq: SeqNum) -> Acked {
+        let Some(offset) = self.offset(seq) else {
+            return Acked::default();
+        };
+        match self.entries.remove(offset) {
+            Some(entry) => {
+                self.forget(offset, &entry);
+                self.consecutive_timeouts = 0;
+                Acked {
+                    segments: 1,
+                    bytes: entry.len,
+                    newest_sent_at: Some(entry.sent_at),
+                    newest_retransmitted: entry.retransmits > 0,
+                    delivery: Some(entry.delivery),
+                    ..Acked::default()
+                }
+            }
+            None => Acked::default(),
+        }
+    }
+
+    /// 取出所有已超时的段用于重发，并为它们重新安排下一次超时（每个 RTO 周期最多一次）。
+    /// 每次超时事件使退避后的 RTO 翻倍；连续超时超过 `max_retries` 次时进入失败状态。
+    /// 已被 SACK 的段不会被重传。
+    pub fn poll_expired(&mut self, now: Instant) -> Result<Vec<Segment>, LinkError> {
+        if let Some(e) = &self.failure {
+            return Err(e.clone());
+        }
+
+        let mut expired = self.timers.expire(now);
+        expired.sort_unstable();
+        let Some(oldest) = expired.first().and_then(|&offset| self.entries.get(offset)) else {
+            return Ok(Vec::new());
+        };
+
+        if self.consecutive_timeouts >= self.policy.max_retries {
+            let e = LinkError::PeerUnreachable { seq: oldest.segment.seq(), attempts: oldest.retransmits + 1 };
+            self.failure = Some(e.clone());
+            return Err(e);
+        }
+        self.consecutive_timeouts += 1;
+
+        let rto = self.backoff_rto();
+        let mut resend = Vec::with_capacity(expired.len());
+        for offset in expired {
+            let Some(e) = self.entries.get_mut(offset) else {
+                continue;
+            };
+            // 超时重传后允许之后的 SACK 再次判定丢失
+            self.lost.remove(&offset);
+            e.lost_marked = false;
+            e.sent_at = now;
+            e.delivery = self.rate.on_send(self.in_flight_bytes, now);
+            e.retransmits += 1;
+            resend.push(e.segment.clone());
+            self.timers.set(offset, Some(now + rto));
+        }
+        Ok(resend)
+    }
+
+    /// 快速重传：立即重发最早的未确认段并重新安排其定时器，不计入连续超时。
+    /// 该段被标记为已重传，之后对它的确认不会用于 RTT 采样（Karn 算法）；
+    /// 已被 SACK 或已判定丢失的段不会重复重发。
+    pub fn retransmit_oldest(&mut self, now: Instant) -> Option<Segment> {
+        let rto = self.backoff_rto();
+        let offset = self.entries.first_key()?;
+        let entry = self.entries.get_mut(offset).expect("first_key returns a live offset");
+        if entry.sacked || entry.lost_marked {
+            return None;
+        }
+        entry.lost_marked = true;
+        entry.sent_at = now;
+        entry.delivery = self.rate.on_send(self.in_flight_bytes, now);
+        entry.retransmits += 1;
+        self.timers.set(offset, Some(now + rto));
+        Some(entry.segment.clone())
+    }
+
+    /// 当前退避后的 RTO：rto * 2^连续超时次数，不超过上限
+    pub fn backoff_rto(&self) -> Duration {
+        let factor = 1u32.checked_shl(self.consecutive_timeouts).unwrap_or(u32::MAX);
+        self.rto.saturating_mul(factor).min(self.policy.max_rto.max(self.rto))
+    }
+
+    /// 连续超时次数
+    pub fn consecutive_timeouts(&self) -> u32 {
+        self.consecutive_timeouts
+    }
+
+    /// 失败原因（重试耗尽或被 `fail` 终止后为 Some）
+    pub fn failure(&self) -> Option<&LinkError> {
+        self.failure.as_ref()
+    }
+
+    /// 因外部原因（如保活超时）进入失败状态；已有的失败原因不会被覆盖
+    pub fn fail(&mut self, error: LinkError) {
+        self.failure.get_or_insert(error);
+    }
+
+    /// 最近的重传时间点，连接任务据此设置定时器
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.timers.next_deadline()
+    }
+
+    /// 仍在网络中的段数：不含已被 SACK 与判定丢失、等待重传的段
+    pub fn in_flight(&self) -> usize {
+        self.entries.len() - self.sacked - self.lost.len()
+    }
+
+    /// 已发送未确认、也未被 SACK 的数据体字节数
+    pub fn in_flight_bytes(&self) -> usize {
+        self.in_flight_bytes
+    }
+
+    /// 尚未被累计确认的段（含已被 SACK 的），按序列号先后
+    pub fn unacknowledged(&self) -> impl Iterator<Item = &Segment> + '_ {
+        self.entries.range(0, u64::MAX).map(|(_, entry)| &entry.segment)
+    }
+
+    /// 队列中保留的段数（含已被 SACK 的段）
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, seq: SeqNum) -> bool {
+        self.offset(seq).is_some_and(|offset| self.entries.contains(offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ring::MapBacked;
+    use crate::segment::SegmentType;
+
+    const RTO: Duration = Duration::from_millis(200);
+
+    fn data(seq: u64, len: usize) -> Segment {
+        Segment::new(SegmentType::Data, seq, vec![0; len])
+    }
+
+    // 同一组测试分别在环形缓冲区与有序映射上运行，两者的行为必须一致
+    macro_rules! backing_tests {
+        ($backing:ty) => {
+            use super::*;
+
+            fn queue(policy: BackoffPolicy) -> RetransmitQueue<$backing> {
+                RetransmitQueue::with_backing(RTO, policy)
+            }
+
+            #[test]
+            fn test_ack_before_timeout_removes_entry() {
+                let t0 = Instant::now();
+                let mut queue = queue(BackoffPolicy::default());
+                queue.on_send(data(1, 100), t0).unwrap();
+                queue.on_send(data(2, 50), t0).unwrap();
+                assert_eq!(queue.in_flight_bytes(), 150);
+
+                let acked = queue.on_ack(SeqNum::new(1));
+                assert_eq!((acked.segments, acked.bytes), (1, 100));
+                assert_eq!(acked.newest_sent_at, Some(t0));
+                assert!(!acked.newest_retransmitted);
+                assert_eq!(queue.in_flight_bytes(), 50);
+                assert!(!queue.contains(SeqNum::new(1)));
+
+                // 被确认的段超时后也不会再被重发
+                let resend = queue.poll_expired(t0 + RTO).unwrap();
+                assert_eq!(resend.iter().map(|s| s.seq().get()).collect::<Vec<_>>(), vec![2]);
+            }
+
+            #[test]
+            fn test_timeout_resends_once_per_rto() {
+                let t0 = Instant::now();
+                let mut queue = queue(BackoffPolicy::default());
+                queue.on_send(data(7, 10), t0).unwrap();
+
+                assert!(queue.poll_expired(t0 + RTO / 2).unwrap().is_empty());
+                assert_eq!(queue.next_deadline(), Some(t0 + RTO));
+
+                let t1 = t0 + RTO;
+                assert_eq!(queue.poll_expired(t1).unwrap().len(), 1);
+                // 同一个（退避后的）RTO 周期内不会重复交还
+                assert!(queue.poll_expired(t1).unwrap().is_empty());
+                assert!(queue.poll_expired(t1 + RTO).unwrap().is_empty());
+                // 退避后周期为 2*RTO
+                let t2 = t1 + RTO * 2;
+                assert_eq!(queue.poll_expired(t2).unwrap().len(), 1);
+                assert_eq!(queue.in_flight_bytes(), 10);
+
+                // 重传过的段被确认时如实报告，发送时间为最近一次重发
+                let acked = queue.on_ack(SeqNum::new(7));
+                assert!(acked.newest_retransmitted);
+                assert_eq!(acked.newest_sent_at, Some(t2));
+            }
+
+            #[test]
+            fn test_backoff_doubles_until_max() {
+                let policy = BackoffPolicy { max_rto: Duration::from_millis(1000), max_retries: 8 };
+                let mut queue = queue(policy);
+                let mut now = Instant::now();
+                queue.on_send(data(1, 10), now).unwrap();
+
+                // 每次超时后下一次到期间隔：400, 800, 1000(封顶), 1000...
+                let mut gaps = Vec::new();
+                for _ in 0..5 {
+                    now = queue.next_deadline().unwrap();
+                    assert_eq!(queue.poll_expired(now).unwrap().len(), 1);
+                    gaps.push(queue.next_deadline().unwrap() - now);
+                }
+                let ms: Vec<u128> = gaps.iter().map(|d| d.as_millis()).collect();
+                assert_eq!(ms, vec![400, 800, 1000, 1000, 1000]);
+
+                // 任何有效确认清零连续超时次数，RTO 回到基准值
+                queue.on_send(data(2, 10), now).unwrap();
+                queue.on_selective_ack(SeqNum::new(2));
+                assert_eq!(queue.consecutive_timeouts(), 0);
+                assert_eq!(queue.backoff_rto(), RTO);
+            }
+
+            #[test]
+            fn test_retry_limit_surfaces_failure() {
+                let policy = BackoffPolicy { max_rto: Duration::from_secs(60), max_retries: 3 };
+                let mut queue = queue(policy);
+                let t0 = Instant::now();
+                queue.on_send(data(5, 10), t0).unwrap();
+
+                for _ in 0..3 {
+                    let now = queue.next_deadline().unwrap();
+                    assert_eq!(queue.poll_expired(now).unwrap().len(), 1);
+                }
+
+                // 第 4 次超时：重试耗尽
+                let now = queue.next_deadline().unwrap();
+                let expected = LinkError::PeerUnreachable { seq: SeqNum::new(5), attempts: 4 };
+                assert_eq!(queue.poll_expired(now), Err(expected.clone()));
+                assert_eq!(queue.failure(), Some(&expected));
+
+                // 失败是粘滞的：之后的发送调用方会拿到同一个错误，而不是被默默丢弃
+                assert_eq!(queue.on_send(data(6, 10), now), Err(expected));
+            }
+
+            #[test]
+            fn test_unknown_acks_ignored() {
+                let t0 = Instant::now();
+                let mut queue = queue(BackoffPolicy::default());
+                queue.on_send(data(1, 10), t0).unwrap();
+                queue.on_send(data(2, 10), t0).unwrap();
+
+                // 确认尚未发送的数据：忽略，不应清空队列
+                assert_eq!(queue.on_ack(SeqNum::new(100)), Acked::default());
+                assert_eq!(queue.len(), 2);
+                // 选择性确认未知段
+                assert_eq!(queue.on_selective_ack(SeqNum::new(42)), Acked::default());
+
+                assert_eq!(queue.on_selective_ack(SeqNum::new(2)).bytes, 10);
+                assert_eq!(queue.on_ack(SeqNum::new(2)).bytes, 10);
+                assert!(queue.is_empty());
+                assert_eq!(queue.in_flight_bytes(), 0);
+            }
+
+            #[test]
+            fn test_sequence_wraps_around_max() {
+                let t0 = Instant::now();
+                let mut queue = queue(BackoffPolicy::default());
+                let start = SeqNum::new(u64::MAX - 1);
+                for i in 0..4 {
+                    queue.on_send(data(start.wrapping_add(i).get(), 10), t0).unwrap();
+                }
+
+                // 确认 u64::MAX 之后的 0：前三个段（含回绕前的两个）被移除
+                assert_eq!(queue.on_ack(SeqNum::new(0)).segments, 3);
+                assert!(queue.contains(SeqNum::new(1)));
+                // 回绕前的旧确认被忽略
+                assert_eq!(queue.on_ack(start), Acked::default());
+                assert_eq!(queue.len(), 1);
+            }
+
+            #[test]
+            fn test_sack_queues_only_gaps() {
+                let t0 = Instant::now();
+                let mut queue = queue(BackoffPolicy::default());
+                for seq in 1..=12 {
+                    queue.on_send(data(seq, 10), t0).unwrap();
+                }
+
+                // 5 与 9 丢失：累计确认到 4，SACK 6..=8 与 10；9 之上只有一个段被 SACK，可能只是乱序
+                let sack = SackInfo {
+                    cumulative: SeqNum::new(4),
+                    ranges: vec![(SeqNum::new(6), SeqNum::new(8)), (SeqNum::new(10), SeqNum::new(10))],
+                };
+                let acked = queue.on_sack(&sack);
+                assert_eq!((acked.segments, acked.lost), (8, 1));
+                assert_eq!(queue.lost().map(SeqNum::get).collect::<Vec<_>>(), vec![5]);
+
+                // 又有两个段越过 9：同样判定丢失
+                let sack = SackInfo {
+                    cumulative: SeqNum::new(4),
+                    ranges: vec![(SeqNum::new(6), SeqNum::new(8)), (SeqNum::new(10), SeqNum::new(12))],
+                };
+                let acked = queue.on_sack(&sack);
+                assert_eq!((acked.segments, acked.lost), (2, 1));
+                assert_eq!(queue.lost().map(SeqNum::get).collect::<Vec<_>>(), vec![5, 9]);
+                // SACK 过的段仍保留，但不计入在途
+                assert_eq!(queue.len(), 8);
+                assert_eq!(queue.in_flight(), 0);
+                assert_eq!(queue.in_flight_bytes(), 20);
+
+                // 同一个 SACK 重复到达不会重复排队
+                assert_eq!(queue.on_sack(&sack).lost, 0);
+                let resend = queue.poll_lost(t0, usize::MAX);
+                assert_eq!(resend.iter().map(|s| s.seq().get()).collect::<Vec<_>>(), vec![5, 9]);
+                assert_eq!(queue.in_flight(), 2);
+
+                // 之后的超时只重传 5 与 9，不会重传已被 SACK 的段
+                let expired = queue.poll_expired(t0 + RTO * 4).unwrap();
+                assert_eq!(expired.iter().map(|s| s.seq().get()).collect::<Vec<_>>(), vec![5, 9]);
+
+                // 累计确认越过后全部移除
+                assert_eq!(queue.on_ack(SeqNum::new(12)).segments, 2);
+                assert!(queue.is_empty());
+                assert_eq!(queue.in_flight_bytes(), 0);
+            }
+
+            #[test]
+            fn test_abandon_removes_the_unacked_part_of_a_range() {
+                let t0 = Instant::now();
+                let mut queue = queue(BackoffPolicy::default());
+                for seq in 1..=6 {
+                    queue.on_send(data(seq, 10), t0).unwrap();
+                }
+                queue.on_ack(SeqNum::new(2));
+
+                // 3..=4 仍在队列中：移出它们，不再重传
+                assert_eq!(queue.abandon(SeqNum::new(1), SeqNum::new(4)), Some(SeqNum::new(3)));
+                assert_eq!((queue.len(), queue.in_flight_bytes()), (2, 20));
+                let expired = queue.poll_expired(t0 + RTO).unwrap();
+                assert_eq!(expired.iter().map(|s| s.seq().get()).collect::<Vec<_>>(), vec![5, 6]);
+
+                // 全部已被 SACK 的区间不放弃，对端已经收齐
+                let sack = SackInfo { cumulative: SeqNum::new(2), ranges: vec![(SeqNum::new(5), SeqNum::new(6))] };
+                queue.on_sack(&sack);
+                assert_eq!(queue.abandon(SeqNum::new(5), SeqNum::new(6)), None);
+                assert_eq!(queue.abandon(SeqNum::new(1), SeqNum::new(2)), None);
+                assert_eq!(queue.len(), 2);
+            }
+
+            #[test]
+            fn test_withdraw_only_segments_never_resent() {
+                let t0 = Instant::now();
+                let mut queue = queue(BackoffPolicy::default());
+                for seq in 1..=6 {
+                    queue.on_send(data(seq, 10), t0).unwrap();
+                }
+                queue.on_ack(SeqNum::new(2));
+
+                // 撤回 4..=6 后以同样的序列号重新登记，确认照常处理
+                let withdrawn = queue.withdraw(SeqNum::new(4)).unwrap();
+                assert_eq!(withdrawn.iter().map(|s| s.seq().get()).collect::<Vec<_>>(), vec![4, 5, 6]);
+                assert_eq!((queue.len(), queue.in_flight_bytes()), (1, 10));
+                assert_eq!(queue.on_ack(SeqNum::new(4)), Acked::default());
+                queue.on_send(data(4, 5), t0).unwrap();
+                assert_eq!(queue.on_ack(SeqNum::new(4)).segments, 2);
+
+                // 重传过的段可能已经到达对端，不能撤回
+                queue.on_send(data(5, 10), t0).unwrap();
+                queue.poll_expired(t0 + RTO).unwrap();
+                assert_eq!(queue.withdraw(SeqNum::new(5)), None);
+                assert_eq!(queue.withdraw(SeqNum::new(9)), Some(Vec::new()));
+                assert_eq!(queue.len(), 1);
+            }
+
+            #[test]
+            fn test_span_beyond_the_reserved_window() {
+                // 被 SACK 的段迟迟不被累计确认时在途的跨度超过预留的容量：段不会丢失或被覆盖
+                let t0 = Instant::now();
+                let mut queue = queue(BackoffPolicy::default()).with_capacity(64);
+                for seq in 1..=300 {
+                    queue.on_send(data(seq, 10), t0).unwrap();
+                }
+                let sack = SackInfo { cumulative: SeqNum::new(1), ranges: vec![(SeqNum::new(3), SeqNum::new(300))] };
+                assert_eq!(queue.on_sack(&sack).segments, 299);
+                assert_eq!((queue.len(), queue.in_flight_bytes()), (299, 10));
+                let resend = queue.poll_lost(t0, usize::MAX);
+                assert_eq!(resend.iter().map(|s| s.seq().get()).collect::<Vec<_>>(), vec![2]);
+                assert_eq!(queue.unacknowledged().map(|s| s.seq().get()).collect::<Vec<_>>(), (2..=300).collect::<Vec<_>>());
+
+                assert_eq!(queue.on_ack(SeqNum::new(300)).segments, 1);
+                assert!(queue.is_empty());
+            }
+        };
+    }
+
+    mod ring {
+        backing_tests!(RingBacked);
+    }
+
+    mod map {
+        backing_tests!(MapBacked);
+    }
+}
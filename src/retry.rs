@@ -0,0 +1,117 @@
+//! 地址验证（Retry）
+//! 半开握手数达到 `LinkConfig::retry_threshold` 时，监听器不以 SYN-ACK 回应没有令牌的 SYN，而是回应一个
+//! Retry 段，数据体是不透明的令牌；客户端在重发的 SYN 中带回它（设置 TOKEN 标志，见 `segment` 模块），
+//! 令牌通过验证的 SYN 才进入正常的握手。伪造源地址的一方收不到 Retry，也就无法让监听器登记状态或回应 SYN-ACK。
+//!
+//! 令牌是 `issued(8) | mac(16)`：签发时刻（自签发者创建起的毫秒数）与以服务端密钥对 (对端地址, 签发时刻)
+//! 计算的 HMAC-SHA256 的前 16 字节。令牌只对签发时的地址有效，超过 `retry_token_lifetime` 后失效；
+//! 带着失效令牌的 SYN 被丢弃，不再回应新的 Retry（客户端每次握手只接受一个 Retry）。
+//! 签发与验证的时刻由监听器在处理 SYN 时传入，令牌中的签发时刻相对签发者创建的时刻计算。
+
+use crate::config::LinkConfig;
+use crate::segment::Segment;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+/// 令牌中 HMAC 的字节数
+const MAC_LEN: usize = 16;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 签发与验证 Retry 令牌的密钥
+#[derive(Debug)]
+pub struct RetryTokens {
+    secret: [u8; 32],
+    start: Instant,     // 签发时刻的起点
+    lifetime: Duration,
+}
+
+impl RetryTokens {
+    pub fn new(config: &LinkConfig, now: Instant) -> Self {
+        let mut secret = [0u8; 32];
+        getrandom::fill(&mut secret).expect("operating system random source unavailable");
+        Self { secret, start: now, lifetime: config.retry_token_lifetime }
+    }
+
+    /// 为 `peer` 签发令牌，作为 Retry 段的数据体
+    pub fn issue(&self, peer: SocketAddr, now: Instant) -> [u8; Segment::RETRY_TOKEN_LEN] {
+        let issued = now.saturating_duration_since(self.start).as_millis() as u64;
+        let mut token = [0u8; Segment::RETRY_TOKEN_LEN];
+        token[..8].copy_from_slice(&issued.to_be_bytes());
+        token[8..].copy_from_slice(&self.mac(peer, issued).finalize().into_bytes()[..MAC_LEN]);
+        token
+    }
+
+    /// 令牌是否由本端为 `peer` 签发且尚未过期
+    pub fn validate(&self, token: &[u8], peer: SocketAddr, now: Instant) -> bool {
+        let Ok(token) = <&[u8; Segment::RETRY_TOKEN_LEN]>::try_from(token) else {
+            return false;
+        };
+        let issued = u64::from_be_bytes(token[..8].try_into().expect("eight bytes"));
+        let age = now.saturating_duration_since(self.start).as_millis() as u64;
+        match age.checked_sub(issued) {
+            Some(age) if Duration::from_millis(age) <= self.lifetime => self.mac(peer, issued).verify_truncated_left(&token[8..]).is_ok(),
+            _ => false,
+        }
+    }
+
+    // IPv4 地址按映射后的 IPv6 形式参与计算，双栈套接字上两种写法得到同一个令牌
+    fn mac(&self, peer: SocketAddr, issued: u64) -> HmacSha256 {
+        let ip = match peer.ip() {
+            IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+            IpAddr::V6(ip) => ip,
+        };
+        let mut hmac = HmacSha256::new_from_slice(&self.secret).expect("hmac accepts any key length");
+        hmac.update(&ip.octets());
+        hmac.update(&peer.port().to_be_bytes());
+        hmac.update(&issued.to_be_bytes());
+        hmac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_round_trip() {
+        let t0 = Instant::now();
+        let tokens = RetryTokens::new(&LinkConfig::default(), t0);
+        let peer: SocketAddr = "10.0.0.2:2".parse().unwrap();
+        let token = tokens.issue(peer, t0 + Duration::from_millis(5));
+        assert!(tokens.validate(&token, peer, t0 + Duration::from_secs(1)));
+        assert!(tokens.validate(&token, "[::ffff:10.0.0.2]:2".parse().unwrap(), t0 + Duration::from_secs(1)));
+
+        // 被篡改、截短，或另一个签发者的令牌
+        let mut tampered = token;
+        tampered[3] ^= 1;
+        assert!(!tokens.validate(&tampered, peer, t0 + Duration::from_secs(1)));
+        assert!(!tokens.validate(&token[..20], peer, t0 + Duration::from_secs(1)));
+        assert!(!RetryTokens::new(&LinkConfig::default(), t0).validate(&token, peer, t0 + Duration::from_secs(1)));
+        // 签发时刻在未来
+        assert!(!tokens.validate(&token, peer, t0));
+    }
+
+    #[test]
+    fn test_expired_token() {
+        let t0 = Instant::now();
+        let config = LinkConfig { retry_token_lifetime: Duration::from_secs(2), ..LinkConfig::default() };
+        let tokens = RetryTokens::new(&config, t0);
+        let peer: SocketAddr = "10.0.0.2:2".parse().unwrap();
+        let token = tokens.issue(peer, t0);
+        assert!(tokens.validate(&token, peer, t0 + Duration::from_secs(2)));
+        assert!(!tokens.validate(&token, peer, t0 + Duration::from_millis(2001)));
+    }
+
+    #[test]
+    fn test_token_bound_to_address() {
+        let t0 = Instant::now();
+        let tokens = RetryTokens::new(&LinkConfig::default(), t0);
+        let token = tokens.issue("10.0.0.2:2".parse().unwrap(), t0);
+        for other in ["10.0.0.2:3", "10.0.0.3:2", "[::1]:2"] {
+            assert!(!tokens.validate(&token, other.parse().unwrap(), t0), "{}", other);
+        }
+    }
+}
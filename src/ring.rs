@@ -0,0 +1,360 @@
+//! 以偏移量索引的环形缓冲区
+//! 重排缓冲区与重传队列都以相对初始序列号的偏移量（单调递增、不会回绕）索引，存活的偏移量通常落在一个不超过窗口的跨度内。
+//! `SeqRing` 把它们放进容量为窗口向上取 2 的幂的环形缓冲区，以 `offset % capacity` 定位槽位，另以占用位图记录哪些槽位有值：
+//! 插入、删除与查找都是 O(1)，按偏移量先后找下一个（上一个）存活的段逐个位图字扫描，不为每个段单独分配。
+//!
+//! 环覆盖 `[base, base + capacity)`，移除最前面的段时 `base` 随之前移。插入落在这个范围之外时先把 `base` 移到最小的存活偏移量，
+//! 仍然放不下（跨度超过容量：窗口被放大，或被 SACK 的段在累计确认越过之前一直保留）时走慢路径：
+//! 扩容到能容纳整个跨度的 2 的幂，逐个搬移存活的段。因此窗口之外的偏移量不会被静默覆盖或丢弃，只是变慢；容量从不缩小。
+//!
+//! `SeqStore` 是两者共用的接口，`BTreeMap<u64, T>` 也实现了它。使用方以 `Backing` 选择实现（默认 `RingBacked`），
+//! 单元测试对两种实现各跑一遍，保证行为一致；`benches/reliability.rs` 比较两者的吞吐。
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// 以 u64 偏移量为键、按偏移量有序的存储
+pub trait SeqStore<T> {
+    /// `capacity` 是预计同时存活的跨度（窗口）；超出时按需扩容，不是上限
+    fn with_capacity(capacity: usize) -> Self;
+
+    fn get(&self, offset: u64) -> Option<&T>;
+
+    fn get_mut(&mut self, offset: u64) -> Option<&mut T>;
+
+    /// 插入，返回被替换的旧值
+    fn insert(&mut self, offset: u64, value: T) -> Option<T>;
+
+    fn remove(&mut self, offset: u64) -> Option<T>;
+
+    fn len(&self) -> usize;
+
+    /// `from..=to` 中最小的存活偏移量
+    fn next_key(&self, from: u64, to: u64) -> Option<u64>;
+
+    /// `from..=to` 中最大的存活偏移量
+    fn prev_key(&self, from: u64, to: u64) -> Option<u64>;
+
+    fn contains(&self, offset: u64) -> bool {
+        self.get(offset).is_some()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 最小的存活偏移量
+    fn first_key(&self) -> Option<u64> {
+        self.next_key(0, u64::MAX)
+    }
+
+    /// `from..=to` 中存活的段，按偏移量先后
+    fn range<'a>(&'a self, from: u64, to: u64) -> impl Iterator<Item = (u64, &'a T)>
+    where
+        T: 'a,
+    {
+        let mut at = Some(from);
+        std::iter::from_fn(move || {
+            let offset = self.next_key(at?, to)?;
+            at = offset.checked_add(1);
+            Some((offset, self.get(offset).expect("next_key returns a live offset")))
+        })
+    }
+}
+
+/// 选择 `ReceiveBuffer` 与 `RetransmitQueue` 内部存储的实现
+pub trait Backing: fmt::Debug {
+    type Store<T: fmt::Debug>: SeqStore<T> + fmt::Debug;
+}
+
+/// 环形缓冲区（默认）
+#[derive(Debug)]
+pub struct RingBacked;
+
+impl Backing for RingBacked {
+    type Store<T: fmt::Debug> = SeqRing<T>;
+}
+
+/// 有序映射，作为对照的参考实现
+#[derive(Debug)]
+pub struct MapBacked;
+
+impl Backing for MapBacked {
+    type Store<T: fmt::Debug> = BTreeMap<u64, T>;
+}
+
+/// 最小容量：一个位图字
+const MIN_CAPACITY: usize = 64;
+
+/// 以 `offset % capacity` 索引、带占用位图的环形缓冲区
+#[derive(Debug)]
+pub struct SeqRing<T> {
+    slots: Vec<Option<T>>,
+    occupied: Vec<u64>, // 每个槽位一位
+    base: u64,          // 环覆盖 [base, base + capacity)
+    len: usize,
+}
+
+impl<T> SeqRing<T> {
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn mask(&self) -> u64 {
+        self.slots.len() as u64 - 1
+    }
+
+    fn index(&self, offset: u64) -> usize {
+        (offset & self.mask()) as usize
+    }
+
+    // 环覆盖的最后一个偏移量
+    fn last(&self) -> u64 {
+        self.base.saturating_add(self.mask())
+    }
+
+    fn covers(&self, offset: u64) -> bool {
+        offset.wrapping_sub(self.base) < self.slots.len() as u64
+    }
+
+    fn set(&mut self, index: usize, occupied: bool) {
+        match occupied {
+            true => self.occupied[index / 64] |= 1 << (index % 64),
+            false => self.occupied[index / 64] &= !(1 << (index % 64)),
+        }
+    }
+
+    // 让环覆盖 `offset`：先把 base 移到最小的存活偏移量，仍然放不下时扩容
+    fn make_room(&mut self, offset: u64) {
+        if self.len == 0 {
+            self.base = offset;
+            return;
+        }
+        if self.covers(offset) {
+            return;
+        }
+        let first = self.next_key(self.base, self.last()).expect("a non-empty ring has a live offset");
+        let last = self.prev_key(first, self.last()).expect("a non-empty ring has a live offset");
+        let (low, high) = (first.min(offset), last.max(offset));
+        let span = high - low + 1;
+        if span <= self.slots.len() as u64 {
+            self.base = low;
+            return;
+        }
+        self.grow(low, span);
+    }
+
+    // 慢路径：扩容到能容纳 `span` 的 2 的幂，存活的段按新的容量重新放置
+    fn grow(&mut self, low: u64, span: u64) {
+        let capacity = usize::try_from(span).ok().and_then(usize::checked_next_power_of_two).expect("sequence span exceeds the address space");
+        let mut slots: Vec<Option<T>> = (0..capacity).map(|_| None).collect();
+        let mut occupied = vec![0u64; capacity.div_ceil(64)];
+        let (base, mask) = (self.base, self.mask());
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if let Some(value) = slot.take() {
+                let offset = base + ((index as u64).wrapping_sub(base) & mask);
+                let at = (offset & (capacity as u64 - 1)) as usize;
+                occupied[at / 64] |= 1 << (at % 64);
+                slots[at] = Some(value);
+            }
+        }
+        self.slots = slots;
+        self.occupied = occupied;
+        self.base = low;
+    }
+}
+
+impl<T> SeqStore<T> for SeqRing<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(MIN_CAPACITY).next_power_of_two();
+        Self { slots: (0..capacity).map(|_| None).collect(), occupied: vec![0; capacity.div_ceil(64)], base: 0, len: 0 }
+    }
+
+    fn get(&self, offset: u64) -> Option<&T> {
+        match self.covers(offset) {
+            true => self.slots[self.index(offset)].as_ref(),
+            false => None,
+        }
+    }
+
+    fn get_mut(&mut self, offset: u64) -> Option<&mut T> {
+        match self.covers(offset) {
+            true => {
+                let index = self.index(offset);
+                self.slots[index].as_mut()
+            }
+            false => None,
+        }
+    }
+
+    fn insert(&mut self, offset: u64, value: T) -> Option<T> {
+        self.make_room(offset);
+        let index = self.index(offset);
+        let old = self.slots[index].replace(value);
+        if old.is_none() {
+            self.set(index, true);
+            self.len += 1;
+        }
+        old
+    }
+
+    fn remove(&mut self, offset: u64) -> Option<T> {
+        if !self.covers(offset) {
+            return None;
+        }
+        let index = self.index(offset);
+        let old = self.slots[index].take()?;
+        self.set(index, false);
+        self.len -= 1;
+        // 按序移除（累计确认、按序交付）时环跟着前移，之后的扫描不必越过已经清空的槽位
+        if offset == self.base {
+            self.base += 1;
+        }
+        Some(old)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn next_key(&self, from: u64, to: u64) -> Option<u64> {
+        if self.len == 0 {
+            return None;
+        }
+        let mut at = from.max(self.base);
+        let to = to.min(self.last());
+        while at <= to {
+            let index = self.index(at);
+            // 同一个位图字中 index 之后的位对应紧随 at 之后的偏移量；容量小于 64 时高出容量的位从不置位
+            let bits = self.occupied[index / 64] >> (index % 64);
+            if bits != 0 {
+                let found = at + u64::from(bits.trailing_zeros());
+                return (found <= to).then_some(found);
+            }
+            at += (64 - index % 64).min(self.slots.len() - index) as u64;
+        }
+        None
+    }
+
+    fn prev_key(&self, from: u64, to: u64) -> Option<u64> {
+        if self.len == 0 || to < self.base {
+            return None;
+        }
+        let from = from.max(self.base);
+        let mut at = to.min(self.last());
+        while at >= from {
+            let index = self.index(at);
+            // 同一个位图字中 index 之前的位对应紧挨 at 之前的偏移量
+            let bits = self.occupied[index / 64] << (63 - index % 64);
+            if bits != 0 {
+                let found = at - u64::from(bits.leading_zeros());
+                return (found >= from).then_some(found);
+            }
+            at = at.checked_sub((index % 64 + 1) as u64)?;
+        }
+        None
+    }
+}
+
+impl<T> SeqStore<T> for BTreeMap<u64, T> {
+    fn with_capacity(_: usize) -> Self {
+        BTreeMap::new()
+    }
+
+    fn get(&self, offset: u64) -> Option<&T> {
+        BTreeMap::get(self, &offset)
+    }
+
+    fn get_mut(&mut self, offset: u64) -> Option<&mut T> {
+        BTreeMap::get_mut(self, &offset)
+    }
+
+    fn insert(&mut self, offset: u64, value: T) -> Option<T> {
+        BTreeMap::insert(self, offset, value)
+    }
+
+    fn remove(&mut self, offset: u64) -> Option<T> {
+        BTreeMap::remove(self, &offset)
+    }
+
+    fn len(&self) -> usize {
+        BTreeMap::len(self)
+    }
+
+    fn next_key(&self, from: u64, to: u64) -> Option<u64> {
+        (from <= to).then(|| self.range(from..=to).next().map(|(&offset, _)| offset)).flatten()
+    }
+
+    fn prev_key(&self, from: u64, to: u64) -> Option<u64> {
+        (from <= to).then(|| self.range(from..=to).next_back().map(|(&offset, _)| offset)).flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 同一串操作在环与映射上的结果一致
+    fn agree(ring: &SeqRing<u64>, map: &BTreeMap<u64, u64>, probes: &[(u64, u64)]) {
+        assert_eq!(SeqStore::len(ring), SeqStore::len(map));
+        for &(from, to) in probes {
+            assert_eq!(ring.next_key(from, to), map.next_key(from, to), "next_key({}, {})", from, to);
+            assert_eq!(ring.prev_key(from, to), map.prev_key(from, to), "prev_key({}, {})", from, to);
+        }
+        let collected: Vec<_> = SeqStore::range(ring, 0, u64::MAX).map(|(offset, &value)| (offset, value)).collect();
+        let expected: Vec<_> = map.iter().map(|(&offset, &value)| (offset, value)).collect();
+        assert_eq!(collected, expected);
+    }
+
+    #[test]
+    fn test_ring_matches_map() {
+        let mut ring = SeqRing::with_capacity(64);
+        let mut map = BTreeMap::new();
+        let probes = [(0, u64::MAX), (0, 59), (61, 65), (64, 64), (63, 119), (119, 63), (65, 1000), (150, 250)];
+        // 跨过环尾回到开头
+        for offset in [60, 62, 63, 64, 66, 70, 120] {
+            assert_eq!(SeqStore::insert(&mut ring, offset, offset), None);
+            SeqStore::insert(&mut map, offset, offset);
+        }
+        agree(&ring, &map, &probes);
+        assert_eq!(SeqStore::insert(&mut ring, 63, 630), Some(63));
+        SeqStore::insert(&mut map, 63, 630);
+        for offset in [60, 64, 65] {
+            assert_eq!(SeqStore::remove(&mut ring, offset), SeqStore::remove(&mut map, offset));
+        }
+        agree(&ring, &map, &probes);
+
+        // 跨度超出容量：扩容后原有的段不丢失，扫描跨过多个位图字
+        for offset in [200, 199, 130] {
+            SeqStore::insert(&mut ring, offset, offset);
+            SeqStore::insert(&mut map, offset, offset);
+        }
+        assert_eq!(ring.capacity(), 256);
+        agree(&ring, &map, &probes);
+
+        // 前面的段被移出后 base 前移，不必扩容
+        for offset in [62, 63, 66, 70, 120] {
+            assert_eq!(SeqStore::remove(&mut ring, offset), SeqStore::remove(&mut map, offset));
+        }
+        SeqStore::insert(&mut ring, 380, 380);
+        SeqStore::insert(&mut map, 380, 380);
+        assert_eq!(ring.capacity(), 256);
+        agree(&ring, &map, &probes);
+        assert_eq!(ring.first_key(), Some(130));
+    }
+
+    #[test]
+    fn test_ring_slides_over_a_long_run() {
+        let mut ring = SeqRing::with_capacity(64);
+        for offset in 0..10_000u64 {
+            SeqStore::insert(&mut ring, offset, offset);
+            if offset >= 48 {
+                assert_eq!(SeqStore::remove(&mut ring, offset - 48), Some(offset - 48));
+            }
+        }
+        // 存活的跨度从未超过窗口，容量不变
+        assert_eq!(ring.capacity(), 64);
+        assert_eq!(SeqStore::len(&ring), 48);
+        assert_eq!((ring.first_key(), ring.prev_key(0, u64::MAX)), (Some(10_000 - 48), Some(9_999)));
+    }
+}
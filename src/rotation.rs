@@ -0,0 +1,146 @@
+//! 连接 ID 轮换
+//! 连接 ID 一直不变时，途中的观察者可以据此把对端在不同网络上的流量关联到同一条连接。ID 分两个方向：
+//! `local` 是对端发给本端的段携带的 ID（服务端的监听器据此分发），`remote` 是本端发出的段携带的 ID，
+//! 握手完成时两者都是服务端在 SYN-ACK 中分配的那一个。任何一方都可以轮换自己的 `local`：
+//!
+//! 1. 本端随机选出新的 ID，以 NewConnId 段告诉对端。它像 FIN 一样占用流 0 的一个序列号、登记重传，
+//!    配置了密钥时和数据段一样加密（见 `crypto` 模块）：不在接收窗口内或无法通过认证的 NewConnId 无法注入；
+//! 2. 对端按序列号收下并确认它，之后发出的段改用新的 ID；重复的与比已采用的更早的 NewConnId 被忽略；
+//! 3. 本端收到确认后以新的 ID 作为 `local`。旧 ID 在 `LinkConfig::conn_id_grace` 内仍被接受，吸收途中乱序的段，
+//!    之后退役：携带它的段被丢弃，计入 `ConnectionStats::stale_conn_id`。监听器同样在宽限期后把旧 ID 移出连接表。
+//!
+//! 其他 ID（握手阶段的 0，或对端已经采用、本端还没收到确认的新 ID）照常接受；退役的 ID 只记最近的 `RETIRED_LIMIT` 个。
+//! 宽限期按协议核心处理确认与入站段时传入的时刻计算，与连接的其他定时器用同一个时钟。
+
+use crate::seq::SeqNum;
+use std::collections::VecDeque;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+/// 记住的退役 ID 个数上限
+pub const RETIRED_LIMIT: usize = 16;
+
+/// 一条连接两个方向的连接 ID
+#[derive(Debug)]
+pub struct ConnIds {
+    local: u32,
+    remote: u32,
+    previous: VecDeque<(u32, Instant)>, // 换下来、还在宽限期内的本端 ID 与宽限期结束的时间
+    retired: VecDeque<u32>,     // 宽限期已过的本端 ID，最早的在前
+    applied: Option<SeqNum>,    // 最近一次采用的对端 NewConnId 的序列号
+    grace: Duration,
+}
+
+impl ConnIds {
+    /// 握手分配的 `conn_id`，旧 ID 在轮换后 `grace` 内仍被接受
+    pub fn new(conn_id: u32, grace: Duration) -> Self {
+        Self { local: conn_id, remote: conn_id, previous: VecDeque::new(), retired: VecDeque::new(), applied: None, grace }
+    }
+
+    /// 接续导出的连接（见 `session` 模块）：两个方向当前的 ID，换下来的旧 ID 不随之迁移
+    pub fn resume(local: u32, remote: u32, grace: Duration) -> Self {
+        Self { local, remote, ..Self::new(local, grace) }
+    }
+
+    /// 对端发给本端的段应携带的 ID
+    pub fn local(&self) -> u32 {
+        self.local
+    }
+
+    /// 本端发出的段携带的 ID
+    pub fn remote(&self) -> u32 {
+        self.remote
+    }
+
+    /// 随机选出一个新的本端 ID：非零，不同于当前、宽限期内与已退役的 ID
+    pub fn fresh(&self) -> u32 {
+        loop {
+            let id = RandomState::new().build_hasher().finish() as u32;
+            if id != 0 && id != self.local && !self.previous.iter().any(|&(old, _)| old == id) && !self.retired.contains(&id) {
+                return id;
+            }
+        }
+    }
+
+    /// 本端发出的 NewConnId 被确认：`conn_id` 成为本端的 ID，返回被换下的旧 ID
+    pub fn on_acked(&mut self, conn_id: u32, now: Instant) -> u32 {
+        let old = std::mem::replace(&mut self.local, conn_id);
+        self.previous.push_back((old, now + self.grace));
+        old
+    }
+
+    /// 对端以序列号 `seq` 发来的 NewConnId：比已采用的更新时之后的段改用 `conn_id`，返回是否采用
+    pub fn on_peer_issued(&mut self, seq: SeqNum, conn_id: u32) -> bool {
+        if self.applied.is_some_and(|applied| !seq.is_after(applied)) {
+            return false;
+        }
+        self.applied = Some(seq);
+        self.remote = conn_id;
+        true
+    }
+
+    /// 入站段携带的 `conn_id` 能否接受；宽限期已过的旧 ID 在这里退役
+    pub fn accepts(&mut self, conn_id: u32, now: Instant) -> bool {
+        while let Some(&(old, until)) = self.previous.front()
+            && until <= now
+        {
+            self.previous.pop_front();
+            if self.retired.len() == RETIRED_LIMIT {
+                self.retired.pop_front();
+            }
+            self.retired.push_back(old);
+        }
+        conn_id == self.local || !self.retired.contains(&conn_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_old_id_is_accepted_until_the_grace_ends() {
+        let now = Instant::now();
+        let mut ids = ConnIds::new(7, Duration::from_secs(2));
+        let new = ids.fresh();
+        assert!(new != 0 && new != 7);
+        // 确认到达之前，对端可能已经改用新的 ID
+        assert!(ids.accepts(new, now));
+
+        assert_eq!(ids.on_acked(new, now), 7);
+        assert_eq!((ids.local(), ids.remote()), (new, 7));
+        assert!(ids.accepts(7, now + Duration::from_secs(1)));
+        assert!(!ids.accepts(7, now + Duration::from_secs(2)));
+        assert!(ids.accepts(new, now + Duration::from_secs(2)));
+        // 握手阶段的段不受影响
+        assert!(ids.accepts(0, now + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_peer_ids_are_applied_in_sequence_order() {
+        let mut ids = ConnIds::new(7, Duration::from_secs(2));
+        assert!(ids.on_peer_issued(SeqNum::new(20), 9));
+        // 更早发出、迟到的 NewConnId 与重传都不会把 ID 换回去
+        assert!(!ids.on_peer_issued(SeqNum::new(10), 8));
+        assert!(!ids.on_peer_issued(SeqNum::new(20), 9));
+        assert_eq!((ids.local(), ids.remote()), (7, 9));
+        assert!(ids.on_peer_issued(SeqNum::new(21), 11));
+        assert_eq!(ids.remote(), 11);
+    }
+
+    #[test]
+    fn test_retired_ids_are_bounded() {
+        let mut now = Instant::now();
+        let mut ids = ConnIds::new(1, Duration::ZERO);
+        for id in 2..=RETIRED_LIMIT as u32 + 2 {
+            ids.on_acked(id, now);
+            now += Duration::from_millis(1);
+            ids.accepts(id, now);
+        }
+        // 最早退役的 ID 被忘记，之后的仍被拒绝
+        assert!(ids.accepts(1, now));
+        assert!(!ids.accepts(2, now));
+        assert!(!ids.accepts(RETIRED_LIMIT as u32 + 1, now));
+    }
+}
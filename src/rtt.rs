@@ -0,0 +1,184 @@
+//! RTT 估计与重传超时（RFC 6298）
+//! SRTT / RTTVAR 平滑、RTO 上下限钳制、超时退避，以及 Karn 算法：
+//! 重传过的段被确认时无法判断对应哪次发送，除非时间戳回显能明确标识，否则丢弃该样本。
+
+use std::time::Duration;
+
+// RFC 6298 的平滑系数：alpha = 1/8，beta = 1/4，K = 4
+const K: u32 = 4;
+
+/// 时钟粒度 G，RTO 中的方差项至少取该值
+const CLOCK_GRANULARITY: Duration = Duration::from_millis(1);
+
+/// RTT 估计器
+#[derive(Debug, Clone)]
+pub struct RttEstimator {
+    srtt: Option<Duration>,     // 平滑 RTT，尚无样本时为 None
+    rttvar: Duration,           // RTT 方差
+    base_rto: Duration,         // 由 SRTT/RTTVAR 计算出的 RTO（未退避）
+    backoff: u32,               // 连续超时的退避指数，RTO = base_rto * 2^backoff
+    min_rto: Duration,
+    max_rto: Duration,
+    latest: Option<Duration>,   // 最近一个被采纳的样本
+    min: Option<Duration>,      // 被采纳的样本中最小的一个
+}
+
+impl RttEstimator {
+    /// RFC 6298 建议的初始 RTO
+    pub const INITIAL_RTO: Duration = Duration::from_secs(1);
+
+    pub fn new(min_rto: Duration, max_rto: Duration) -> Self {
+        Self {
+            srtt: None,
+            rttvar: Duration::ZERO,
+            base_rto: Self::INITIAL_RTO.clamp(min_rto, max_rto),
+            backoff: 0,
+            min_rto,
+            max_rto,
+            latest: None,
+            min: None,
+        }
+    }
+
+    /// 采纳一个 RTT 样本并更新 SRTT / RTTVAR / RTO，同时清除超时退避
+    pub fn on_sample(&mut self, rtt: Duration) {
+        match self.srtt {
+            // 第一个样本：SRTT = R，RTTVAR = R/2
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2;
+            }
+            // RTTVAR = 3/4 * RTTVAR + 1/4 * |SRTT - R|，SRTT = 7/8 * SRTT + 1/8 * R
+            Some(srtt) => {
+                let delta = srtt.abs_diff(rtt);
+                self.rttvar = (self.rttvar * 3 + delta) / 4;
+                self.srtt = Some((srtt * 7 + rtt) / 8);
+            }
+        }
+
+        let srtt = self.srtt.unwrap_or(rtt);
+        self.base_rto = (srtt + (self.rttvar * K).max(CLOCK_GRANULARITY)).clamp(self.min_rto, self.max_rto);
+        self.backoff = 0;
+        self.latest = Some(rtt);
+        self.min = Some(self.min.map_or(rtt, |min| min.min(rtt)));
+    }
+
+    /// 某个段被确认时调用：按 Karn 算法决定是否采纳样本，返回是否采纳。
+    /// `retransmitted`：该段被重传过；`ts_echo`：确认中的时间戳回显能唯一对应某次发送。
+    pub fn on_segment_acked(&mut self, rtt: Duration, retransmitted: bool, ts_echo: bool) -> bool {
+        if retransmitted && !ts_echo {
+            return false;
+        }
+        self.on_sample(rtt);
+        true
+    }
+
+    /// 重传超时：RTO 翻倍（不超过上限），直到下一个有效样本
+    pub fn on_timeout(&mut self) {
+        if self.rto() < self.max_rto {
+            self.backoff += 1;
+        }
+    }
+
+    /// 当前 RTO（含退避，钳制在 [min_rto, max_rto]）
+    pub fn rto(&self) -> Duration {
+        let factor = 1u32.checked_shl(self.backoff).unwrap_or(u32::MAX);
+        self.base_rto.saturating_mul(factor).clamp(self.min_rto, self.max_rto)
+    }
+
+    pub fn srtt(&self) -> Option<Duration> {
+        self.srtt
+    }
+
+    pub fn rttvar(&self) -> Duration {
+        self.rttvar
+    }
+
+    pub fn latest(&self) -> Option<Duration> {
+        self.latest
+    }
+
+    /// 连接以来最小的 RTT 样本，近似没有排队时的传播时延
+    pub fn min_rtt(&self) -> Option<Duration> {
+        self.min
+    }
+
+    /// 当前退避次数
+    pub fn backoff(&self) -> u32 {
+        self.backoff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    fn estimator() -> RttEstimator {
+        RttEstimator::new(ms(10), Duration::from_secs(60))
+    }
+
+    #[test]
+    fn test_known_sample_sequence() {
+        let mut rtt = estimator();
+        assert_eq!(rtt.rto(), Duration::from_secs(1));
+
+        // 第一个样本：SRTT=100，RTTVAR=50，RTO=100+4*50=300
+        rtt.on_sample(ms(100));
+        assert_eq!(rtt.srtt(), Some(ms(100)));
+        assert_eq!(rtt.rttvar(), ms(50));
+        assert_eq!(rtt.rto(), ms(300));
+
+        // 第二个样本 200：RTTVAR=(3*50+100)/4=62.5，SRTT=(7*100+200)/8=112.5，RTO=112.5+250=362.5
+        rtt.on_sample(ms(200));
+        assert_eq!(rtt.rttvar(), Duration::from_micros(62_500));
+        assert_eq!(rtt.srtt(), Some(Duration::from_micros(112_500)));
+        assert_eq!(rtt.rto(), Duration::from_micros(362_500));
+    }
+
+    #[test]
+    fn test_rto_clamped_to_bounds() {
+        let mut rtt = RttEstimator::new(ms(200), Duration::from_secs(2));
+        // 极小的 RTT 也不会低于下限
+        rtt.on_sample(Duration::from_micros(10));
+        assert_eq!(rtt.rto(), ms(200));
+        // 极大的 RTT 不会超过上限
+        rtt.on_sample(Duration::from_secs(30));
+        assert_eq!(rtt.rto(), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_backoff_and_collapse() {
+        let mut rtt = estimator();
+        rtt.on_sample(ms(100));
+        assert_eq!(rtt.rto(), ms(300));
+
+        rtt.on_timeout();
+        assert_eq!(rtt.rto(), ms(600));
+        rtt.on_timeout();
+        assert_eq!(rtt.rto(), ms(1200));
+
+        // 下一个有效样本让 RTO 回落到计算值
+        rtt.on_sample(ms(100));
+        assert_eq!(rtt.backoff(), 0);
+        assert!(rtt.rto() < ms(600));
+    }
+
+    #[test]
+    fn test_karn_excludes_retransmitted_samples() {
+        let mut rtt = estimator();
+        rtt.on_sample(ms(100));
+        let before = (rtt.srtt(), rtt.rttvar(), rtt.rto());
+
+        // 重传段的确认（样本含糊，可能对应第一次发送）不得污染估计
+        assert!(!rtt.on_segment_acked(ms(5000), true, false));
+        assert_eq!((rtt.srtt(), rtt.rttvar(), rtt.rto()), before);
+
+        // 有时间戳回显时可以明确对应，样本被采纳
+        assert!(rtt.on_segment_acked(ms(120), true, true));
+        assert_ne!(rtt.srtt(), before.0);
+    }
+}
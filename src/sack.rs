@@ -0,0 +1,222 @@
+//! 选择性确认（SACK）
+//! 接收端在累计确认点之后存在空洞时，把已收到的段区间附在 Ack 段的数据体中：
+//! 每个区间为 `start(8) | end(8)` 的闭区间（大端序），按序列号先后排列，最多 `MAX_BLOCKS` 个。
+//! SACK 只是建议性的：发送端据此避免重传对端已有的数据，但在累计确认越过之前仍保留这些段。
+//! 确认的热路径（见 `ack_frame` 模块）以定长的 `SackBlocks` 存放区间，不分配。
+
+use crate::segment::{Segment, SegmentError, SegmentFlags, SegmentType};
+use crate::seq::SeqNum;
+use alloc::vec::Vec;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use core::fmt;
+use core::ops::Deref;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// 单个区间在线上的长度
+pub const BLOCK_LEN: usize = 16;
+
+/// 一个 Ack 段最多携带的区间数，超出的（序列号最大的）区间被省略
+pub const MAX_BLOCKS: usize = 16;
+
+/// 一个 SACK 确认携带的信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SackInfo {
+    pub cumulative: SeqNum,             // 累计确认点
+    pub ranges: Vec<(SeqNum, SeqNum)>,  // 累计确认点之后已收到的闭区间
+}
+
+impl SackInfo {
+    /// 从确认段中提取 SACK 信息；不是携带 SACK 的 Ack 段时返回 None
+    pub fn from_segment(segment: &Segment) -> Result<Option<Self>, SegmentError> {
+        if segment.segment_type() != SegmentType::Ack || !segment.flags().contains(SegmentFlags::SACK) {
+            return Ok(None);
+        }
+        let ranges = decode_ranges(segment.data())?;
+        Ok(Some(Self { cumulative: segment.ack(), ranges }))
+    }
+
+    /// 区间中序列号最大的一端
+    pub fn highest(&self) -> Option<SeqNum> {
+        self.ranges.iter().map(|&(_, end)| end).reduce(|a, b| if b.is_after(a) { b } else { a })
+    }
+}
+
+/// 至多 `MAX_BLOCKS` 个区间的定长列表，放在栈上，不分配
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SackBlocks {
+    ranges: [(SeqNum, SeqNum); MAX_BLOCKS],
+    len: usize,
+}
+
+impl SackBlocks {
+    pub const fn new() -> Self {
+        Self { ranges: [(SeqNum::new(0), SeqNum::new(0)); MAX_BLOCKS], len: 0 }
+    }
+
+    /// 追加一个区间；已满时返回 false，区间被省略
+    pub fn push(&mut self, start: SeqNum, end: SeqNum) -> bool {
+        let Some(slot) = self.ranges.get_mut(self.len) else {
+            return false;
+        };
+        *slot = (start, end);
+        self.len += 1;
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == MAX_BLOCKS
+    }
+
+    /// 同 `decode_ranges`，超过 `MAX_BLOCKS` 的区间被省略
+    pub fn decode(data: &[u8]) -> Result<Self, SegmentError> {
+        if !data.len().is_multiple_of(BLOCK_LEN) {
+            return Err(SegmentError::InvalidSack(data.len()));
+        }
+        let mut blocks = Self::new();
+        for mut block in data.chunks_exact(BLOCK_LEN).take(MAX_BLOCKS) {
+            blocks.push(SeqNum::new(block.get_u64()), SeqNum::new(block.get_u64()));
+        }
+        Ok(blocks)
+    }
+
+    /// 编码后的长度
+    pub fn encoded_len(&self) -> usize {
+        self.len * BLOCK_LEN
+    }
+
+    /// 同 `encode_ranges`，写进 `buf`
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        for &(start, end) in self.iter() {
+            buf.put_u64(start.get());
+            buf.put_u64(end.get());
+        }
+    }
+}
+
+impl Default for SackBlocks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for SackBlocks {
+    type Target = [(SeqNum, SeqNum)];
+
+    fn deref(&self) -> &Self::Target {
+        &self.ranges[..self.len]
+    }
+}
+
+impl fmt::Debug for SackBlocks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// 取前 `MAX_BLOCKS` 个区间，其余的被省略（同 `encode_ranges`）
+impl From<&[(SeqNum, SeqNum)]> for SackBlocks {
+    fn from(ranges: &[(SeqNum, SeqNum)]) -> Self {
+        let mut blocks = Self::new();
+        for &(start, end) in ranges.iter().take(MAX_BLOCKS) {
+            blocks.push(start, end);
+        }
+        blocks
+    }
+}
+
+/// 编码区间列表，超过 `MAX_BLOCKS` 的部分被截断
+pub fn encode_ranges(ranges: &[(SeqNum, SeqNum)]) -> Bytes {
+    let ranges = &ranges[..ranges.len().min(MAX_BLOCKS)];
+    let mut buf = BytesMut::with_capacity(ranges.len() * BLOCK_LEN);
+    for &(start, end) in ranges {
+        buf.put_u64(start.get());
+        buf.put_u64(end.get());
+    }
+    buf.freeze()
+}
+
+/// 解码区间列表；长度不是 `BLOCK_LEN` 的整数倍时返回错误
+pub fn decode_ranges(mut data: &[u8]) -> Result<Vec<(SeqNum, SeqNum)>, SegmentError> {
+    if !data.len().is_multiple_of(BLOCK_LEN) {
+        return Err(SegmentError::InvalidSack(data.len()));
+    }
+    let mut ranges = Vec::with_capacity(data.len() / BLOCK_LEN);
+    while data.has_remaining() {
+        let start = SeqNum::new(data.get_u64());
+        let end = SeqNum::new(data.get_u64());
+        ranges.push((start, end));
+    }
+    Ok(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seq(n: u64) -> SeqNum {
+        SeqNum::new(n)
+    }
+
+    #[test]
+    fn test_sack_roundtrip_through_segment() {
+        let ranges = vec![(seq(6), seq(8)), (seq(10), seq(10))];
+        let segment = Segment::builder(SegmentType::Ack)
+            .ack(seq(4))
+            .window(32)
+            .sack(&ranges)
+            .build()
+            .unwrap();
+
+        let decoded = Segment::decode(&segment.encode().unwrap()).unwrap();
+        let info = SackInfo::from_segment(&decoded).unwrap().unwrap();
+        assert_eq!(info, SackInfo { cumulative: seq(4), ranges });
+        assert_eq!(info.highest(), Some(seq(10)));
+    }
+
+    #[test]
+    fn test_plain_ack_has_no_sack() {
+        let segment = Segment::builder(SegmentType::Ack).ack(seq(4)).build().unwrap();
+        assert_eq!(SackInfo::from_segment(&segment), Ok(None));
+    }
+
+    #[test]
+    fn test_truncated_sack_rejected() {
+        let segment = Segment::builder(SegmentType::Ack).ack(seq(1)).sack(&[(seq(3), seq(4))]).build().unwrap();
+        let mut encoded = segment.encode().unwrap();
+        // 去掉最后一个字节并修正总长度
+        encoded.truncate(encoded.len() - 1);
+        let total_len = encoded.len() as u32;
+        encoded[..4].copy_from_slice(&total_len.to_be_bytes());
+        assert_eq!(Segment::decode(&encoded), Err(SegmentError::InvalidSack(BLOCK_LEN - 1)));
+
+        assert_eq!(decode_ranges(&[0; 17]), Err(SegmentError::InvalidSack(17)));
+    }
+
+    #[test]
+    fn test_blocks_keep_the_first_max_blocks() {
+        let ranges: Vec<_> = (0..20).map(|n| (seq(n * 10), seq(n * 10 + 5))).collect();
+        let mut blocks = SackBlocks::from(&ranges[..]);
+        assert!(blocks.is_full());
+        assert_eq!(&blocks[..], &ranges[..MAX_BLOCKS]);
+        assert!(!blocks.push(seq(500), seq(501)));
+        blocks.clear();
+        assert!(blocks.push(seq(7), seq(8)));
+        assert_eq!(&blocks[..], &[(seq(7), seq(8))]);
+        assert_eq!(format!("{:?}", blocks), format!("{:?}", [(seq(7), seq(8))]));
+
+        // 与区间列表的编码相同
+        let encoded = encode_ranges(&ranges);
+        assert_eq!(SackBlocks::decode(&encoded), Ok(SackBlocks::from(&ranges[..])));
+        let mut buf = Vec::new();
+        SackBlocks::from(&ranges[..]).encode_into(&mut buf);
+        assert_eq!((&buf[..], buf.len()), (&encoded[..], SackBlocks::from(&ranges[..]).encoded_len()));
+        assert_eq!(SackBlocks::decode(&[0; 17]), Err(SegmentError::InvalidSack(17)));
+    }
+}
@@ -0,0 +1,207 @@
+//! 流间的发送调度
+//! 各个流的数据段按流分别打包成数据报排队，`poll_transmit` 以按字节计的差额轮询（DRR）在有数据排队的流之间轮流取出：
+//! 每轮到一个流，它的额度增加与权重成正比的字节数，额度够付队首的数据报时发出它，不够时轮到下一个流。
+//! 一个流一次放出整窗的数据时，其他流新写入的段至多等它一轮的额度，而不是排在整个队列之后；
+//! 只有一个流有数据时它独占链路，调度不浪费容量。
+//!
+//! 权重就是流的优先级（`LinkStream::set_priority`），默认为 `DEFAULT_PRIORITY`，每轮一个 MSS；
+//! 优先级翻倍时每轮可以发出的字节数翻倍。0 按 1 计。控制段不经过调度，先于排队的数据发出（见 `endpoint` 模块）。
+
+use bytes::BytesMut;
+use std::collections::{HashMap, VecDeque};
+
+/// 流的默认优先级
+pub const DEFAULT_PRIORITY: u8 = 8;
+
+// 一个流排队的数据报与本轮余下的额度（字节）
+#[derive(Debug, Default)]
+struct Flow {
+    queue: VecDeque<BytesMut>,
+    deficit: usize,
+    priority: u8,
+}
+
+/// 按流排队、轮流发出的数据的数据报
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    flows: HashMap<u16, Flow>,
+    active: VecDeque<u16>,  // 有数据报排队的流，队首是当前轮到的流
+    credited: bool,         // 队首的流本轮已补充过额度
+    len: usize,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 把流 `id` 的数据报排在它自己的队列末尾
+    pub fn push(&mut self, id: u16, priority: u8, datagram: BytesMut) {
+        let flow = self.flows.entry(id).or_insert_with(|| Flow { priority, ..Flow::default() });
+        if flow.queue.is_empty() {
+            self.active.push_back(id);
+        }
+        flow.priority = priority;
+        flow.queue.push_back(datagram);
+        self.len += 1;
+    }
+
+    /// 调整已在排队的流的优先级，从下一次补充额度起生效
+    pub fn set_priority(&mut self, id: u16, priority: u8) {
+        if let Some(flow) = self.flows.get_mut(&id) {
+            flow.priority = priority;
+        }
+    }
+
+    /// 取出下一个数据报；`mss` 是每轮按默认优先级补充的额度
+    pub fn pop(&mut self, mss: usize) -> Option<BytesMut> {
+        loop {
+            let id = *self.active.front()?;
+            let flow = self.flows.get_mut(&id).expect("active flows are registered");
+            if !self.credited {
+                flow.deficit += quantum(mss, flow.priority);
+                self.credited = true;
+            }
+            let head = flow.queue.front().expect("active flows have queued datagrams").len();
+            if flow.deficit >= head {
+                flow.deficit -= head;
+                let datagram = flow.queue.pop_front();
+                // 队列空了就退出轮转，余下的额度不留到下次
+                if flow.queue.is_empty() {
+                    self.flows.remove(&id);
+                    self.active.pop_front();
+                    self.credited = false;
+                }
+                self.len -= 1;
+                return datagram;
+            }
+            // 本轮的额度不够付队首的数据报：轮到下一个流
+            self.active.rotate_left(1);
+            self.credited = false;
+        }
+    }
+
+    /// 就地修改流 `id` 排队的数据报，`keep` 返回 false 的移出队列
+    pub fn retain(&mut self, id: u16, keep: impl FnMut(&mut BytesMut) -> bool) {
+        let Some(flow) = self.flows.get_mut(&id) else {
+            return;
+        };
+        let before = flow.queue.len();
+        flow.queue.retain_mut(keep);
+        self.len -= before - flow.queue.len();
+        if flow.queue.is_empty() {
+            self.flows.remove(&id);
+            if self.active.front() == Some(&id) {
+                self.credited = false;
+            }
+            self.active.retain(|&active| active != id);
+        }
+    }
+
+    /// 排队的数据报数
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.flows.clear();
+        self.active.clear();
+        self.credited = false;
+        self.len = 0;
+    }
+}
+
+// 以 `priority` 为权重每轮补充的额度
+fn quantum(mss: usize, priority: u8) -> usize {
+    (mss * usize::from(priority.max(1)) / usize::from(DEFAULT_PRIORITY)).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MSS: usize = 1000;
+
+    fn datagram(id: u16, len: usize) -> BytesMut {
+        let mut datagram = BytesMut::zeroed(len);
+        datagram[..2].copy_from_slice(&id.to_be_bytes());
+        datagram
+    }
+
+    // 依次取出的数据报所属的流
+    fn drain(scheduler: &mut Scheduler) -> Vec<u16> {
+        std::iter::from_fn(|| scheduler.pop(MSS)).map(|datagram| u16::from_be_bytes([datagram[0], datagram[1]])).collect()
+    }
+
+    #[test]
+    fn test_streams_take_turns() {
+        let mut scheduler = Scheduler::new();
+        for _ in 0..100 {
+            scheduler.push(1, DEFAULT_PRIORITY, datagram(1, MSS));
+        }
+        // 排在整窗数据之后写入的流下一轮就能发出
+        scheduler.push(3, DEFAULT_PRIORITY, datagram(3, 100));
+        scheduler.push(5, DEFAULT_PRIORITY, datagram(5, MSS));
+        assert_eq!(scheduler.len(), 102);
+        let order = drain(&mut scheduler);
+        assert_eq!(order[..4], [1, 3, 5, 1]);
+        assert_eq!(order.len(), 102);
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn test_retain_edits_one_flow() {
+        let mut scheduler = Scheduler::new();
+        for _ in 0..3 {
+            scheduler.push(1, DEFAULT_PRIORITY, datagram(1, 100));
+            scheduler.push(2, DEFAULT_PRIORITY, datagram(2, 100));
+        }
+        // 流 1 的数据报截短一半、丢掉最后一个；流 2 不受影响
+        let mut seen = 0;
+        scheduler.retain(1, |datagram| {
+            seen += 1;
+            datagram.truncate(50);
+            seen < 3
+        });
+        assert_eq!(scheduler.len(), 5);
+        assert_eq!(drain(&mut scheduler), [1, 1, 2, 2, 2]);
+
+        scheduler.push(3, DEFAULT_PRIORITY, datagram(3, 100));
+        scheduler.retain(3, |_| false);
+        scheduler.retain(4, |_| false);
+        assert!(scheduler.is_empty());
+        assert_eq!(scheduler.pop(1200), None);
+    }
+
+    #[test]
+    fn test_priority_weights_the_share() {
+        let mut scheduler = Scheduler::new();
+        for _ in 0..300 {
+            scheduler.push(1, DEFAULT_PRIORITY, datagram(1, MSS));
+            scheduler.push(2, DEFAULT_PRIORITY * 2, datagram(2, MSS));
+            scheduler.push(3, DEFAULT_PRIORITY / 2, datagram(3, MSS));
+        }
+        // 三个流都还有数据时按 2:4:1 分配
+        let order = drain(&mut scheduler);
+        let window = &order[..210];
+        let share = |id| window.iter().filter(|&&stream| stream == id).count();
+        assert_eq!((share(1), share(2), share(3)), (60, 120, 30));
+    }
+
+    #[test]
+    fn test_small_datagrams_share_bytes_not_turns() {
+        let mut scheduler = Scheduler::new();
+        for _ in 0..100 {
+            scheduler.push(1, DEFAULT_PRIORITY, datagram(1, MSS));
+            scheduler.push(2, DEFAULT_PRIORITY, datagram(2, MSS / 4));
+        }
+        // 每轮一个 MSS：四个小数据报对一个大数据报
+        let order = drain(&mut scheduler);
+        let window = &order[..50];
+        assert_eq!(window.iter().filter(|&&stream| stream == 2).count(), 40);
+    }
+}
@@ -1,16 +1,68 @@
 //! L4 协议段的编码和解码
-//! 支持数据帧、确认帧、同步帧
+//! 支持数据帧、确认帧、同步帧、结束帧、复位帧、保活帧（Ping/Pong）、统计查询帧（StatsRequest/StatsReply）、
+//! 路径验证帧（PathChallenge/PathResponse）、跳过帧（Skip）与连接 ID 轮换帧（NewConnId）
 //! 高效将结构化数据序列化为字节缓冲区，并从接收到的字节缓冲区中反序列化回结构化数据
+//!
+//! 线上格式（大端序）：
+//! `total_len(4) | type(1) | flags(1) | stream_id(2) | conn_id(4) | seq(8) | ack(8) | window(4) | checksum_id(1) | checksum(4) | options_len(1) | options | data`
+//!
+//! `conn_id` 由服务端在 SYN-ACK 中分配，此后双方的每个段都携带它，对端地址变化后据此找回连接；
+//! 握手完成前为 0；之后任何一方都可以以 NewConnId 换成新的连接 ID（见 `rotation` 模块）。`checksum` 以 `checksum_id` 指定的算法覆盖它之前的头部与它之后的全部内容，见 `checksum` 模块。
+//! `options` 是 `options_len` 字节的 TLV 选项区，未识别的选项被跳过，见 `options` 模块。
+//! 标志中的 CE 位不参与校验和与认证，拥塞点可以就地标记 Data 段（`mark_ce`）；接收端在确认上以 ECE 回送，
+//! 发送端降窗后在下一个数据段上带出 CWR 选项，见 `sender` 与 `receiver` 模块。
+//! 最高的 UNRELIABLE 位只用在 Data 段上，标记不可靠消息（见 `unreliable` 模块），其他段上它仍是保留位。
+//!
+//! 设置了 SACK 标志的 Ack 段，数据体为若干 `start(8) | end(8)` 闭区间，见 `sack` 模块；
+//! Ping/Pong 段的数据体为 8 字节的 nonce，Pong 原样回送对应 Ping 的 nonce；StatsRequest 同样恰为 8 字节的 nonce，
+//! StatsReply 回送这个 nonce，其后是带版本号的连接统计（见 `stats::PeerStats`）；
+//! PathChallenge 是监听器发往对端新地址的 8 字节随机令牌，PathResponse 原样回送它（见 `listener` 模块）；
+//! Skip 段的数据体为 8 字节的序列号 `end`：发送方放弃了 `seq..=end` 上过期的消息，对端不再等待它们（见 `sender` 模块）；
+//! NewConnId 段的数据体为 4 字节的新连接 ID，它像 FIN 一样占用发送方的一个序列号，直到被确认（见 `rotation` 模块），
+//! 连接配置了密钥时它像 Data 段一样加密，设置 SEALED 标志，数据体另有 `SEAL_TAG_LEN` 字节的认证标签；
+//! Syn 段（含 SYN-ACK）的数据体为发送方接受的校验算法 id，按偏好排列，为空时视为只接受默认算法；
+//! 设置了 SEALED 标志的 Data 段，数据体为密文与认证标签；设置了 AUTH 标志的握手段（Syn、完成握手的 Ack），
+//! 数据体末尾是 `nonce(16) | echo(16) | tag(32)` 的认证尾部，不计入校验算法列表。两者见 `crypto` 模块（`crypto` 特性）；
+//! Retry 段的数据体为监听器签发的 24 字节地址验证令牌，确认号是被要求重试的 SYN 的序列号；设置了 TOKEN 标志的 Syn 段
+//! 在算法列表之后（认证尾部之前）带回这个令牌，同样不计入算法列表。见 `retry` 模块
+//!
+//! 端点以严格模式解码，未知的类型字节是错误。转发与记录工具可以用 `DecodeOptions::allow_unknown` 解码，
+//! 新版本对端的未知类型的段只按结构解析为 `RawSegment`，重新编码后逐字节不变
 
+use crate::checksum::ChecksumAlgorithm;
+use crate::options::Options;
+#[cfg(feature = "std")]
+use crate::pool::BufferPool;
+#[cfg(feature = "crypto")]
+use crate::crypto::Unsealer;
+use crate::sack;
+use crate::seq::SeqNum;
+use alloc::vec;
+use alloc::vec::Vec;
 use bytes::{BytesMut, BufMut, Buf, Bytes};
-use std::fmt;
+use core::fmt;
+use core::ops::{BitOr, Range};
 
-#[derive(Debug)]
-enum SegmentError {
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SegmentError {
     TooShort,                       // 缓冲区长度不足
     InvalidTotalLen(u32, usize),    // 总长度不合法（声明的长度，实际缓冲区长度）
     UnknownFrameType(u8),           // 未知的帧类型
     TotalLenOverflow(usize),        // 总长度超过 u32 最大值（4字节上限）
+    TotalLenTooLarge(u32, usize),   // 声明的总长度超过解码上限（声明的长度，上限）
+    ReservedFlags(u8),              // 标志位中包含未定义的保留位
+    InvalidSack(usize),             // SACK 数据体长度不是区间长度的整数倍
+    UnknownChecksum(u8),            // 未知的校验算法 id
+    UnexpectedChecksum { negotiated: ChecksumAlgorithm, found: ChecksumAlgorithm }, // 不是连接协商出的校验算法
+    ChecksumMismatch { declared: u32, computed: u32 },  // 校验和不符，段在途中损坏
+    DecryptFailed,                  // 密文或段头被篡改、密钥不符，或加密的连接收到明文数据段（及其反面）
+    AuthFailed,                     // 握手段的认证尾部缺失或不符：对端不持有同一个预共享密钥，或段被篡改
+    BadOption,                      // 选项区越过段的末尾，或其中的选项越过选项区、已识别的选项长度不对
+    UnexpectedPayload { segment_type: SegmentType, len: usize },    // 数据体长度不符合该类型段的规则（见 `Segment::check_payload`）
+    InvalidStats { version: u8, len: usize },   // StatsReply 中的统计版本号为 0，或短于该版本的长度（见 `stats::PeerStats`）
 }
 
 impl fmt::Display for SegmentError {
@@ -26,158 +78,2094 @@ impl fmt::Display for SegmentError {
                 f, "total length {} exceeds u32 maximum ({}), cannot encode",
                 len, u32::MAX
             ),
+            SegmentError::TotalLenTooLarge(declared, limit) => write!(
+                f, "declared total length {} exceeds decode limit {}",
+                declared, limit
+            ),
+            SegmentError::ReservedFlags(bits) => write!(f, "reserved flag bits set: {:#04x}", bits),
+            SegmentError::InvalidSack(len) => write!(
+                f, "SACK payload length {} is not a multiple of {}",
+                len, sack::BLOCK_LEN
+            ),
+            SegmentError::UnknownChecksum(id) => write!(f, "unknown checksum algorithm id: {}", id),
+            SegmentError::UnexpectedChecksum { negotiated, found } => write!(
+                f, "segment checksummed with {} but the connection negotiated {}",
+                found, negotiated
+            ),
+            SegmentError::ChecksumMismatch { declared, computed } => write!(
+                f, "checksum mismatch: declared {:#010x} but computed {:#010x}",
+                declared, computed
+            ),
+            SegmentError::DecryptFailed => write!(f, "sealed payload failed authentication"),
+            SegmentError::AuthFailed => write!(f, "handshake segment failed authentication"),
+            SegmentError::BadOption => write!(f, "malformed header options"),
+            SegmentError::UnexpectedPayload { segment_type, len } => write!(
+                f, "{:?} segment must not carry a {}-byte payload",
+                segment_type, len
+            ),
+            SegmentError::InvalidStats { version, len } => write!(
+                f, "malformed stats reply: version {} with {} bytes of statistics",
+                version, len
+            ),
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for SegmentError {}
+
 // 帧类型（L4 控制/数据标识）
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum SegmentType {
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SegmentType {
     Data = 0,
     Ack = 1,
     Syn = 2,
+    Ping = 3,
+    Pong = 4,
+    Fin = 5,
+    Rst = 6,
+    Retry = 7,
+    StatsRequest = 8,
+    StatsReply = 9,
+    PathChallenge = 10,
+    PathResponse = 11,
+    Skip = 12,
+    NewConnId = 13,
+}
+
+impl SegmentType {
+    /// 未识别的类型字节返回 None，见 `TryFrom<u8>`
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::try_from(id).ok()
+    }
+}
+
+/// 线上的类型字节
+impl From<SegmentType> for u8 {
+    fn from(segment_type: SegmentType) -> u8 {
+        segment_type as u8
+    }
+}
+
+/// 解码使用的类型字节映射，未识别的类型返回 `UnknownFrameType`
+impl TryFrom<u8> for SegmentType {
+    type Error = SegmentError;
+
+    fn try_from(id: u8) -> Result<Self, SegmentError> {
+        match id {
+            0 => Ok(SegmentType::Data),
+            1 => Ok(SegmentType::Ack),
+            2 => Ok(SegmentType::Syn),
+            3 => Ok(SegmentType::Ping),
+            4 => Ok(SegmentType::Pong),
+            5 => Ok(SegmentType::Fin),
+            6 => Ok(SegmentType::Rst),
+            7 => Ok(SegmentType::Retry),
+            8 => Ok(SegmentType::StatsRequest),
+            9 => Ok(SegmentType::StatsReply),
+            10 => Ok(SegmentType::PathChallenge),
+            11 => Ok(SegmentType::PathResponse),
+            12 => Ok(SegmentType::Skip),
+            13 => Ok(SegmentType::NewConnId),
+            _ => Err(SegmentError::UnknownFrameType(id)),
+        }
+    }
+}
+
+/// 段标志位（1 字节位图），未定义的位必须为 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SegmentFlags(u8);
+
+impl SegmentFlags {
+    /// ack / window 字段有效（Ack 段隐含，数据段捎带确认时显式设置）
+    pub const ACK: SegmentFlags = SegmentFlags(0b0000_0001);
+    /// Ack 段的数据体携带 SACK 区间
+    pub const SACK: SegmentFlags = SegmentFlags(0b0000_0010);
+    /// Data 段的数据体已加密
+    pub const SEALED: SegmentFlags = SegmentFlags(0b0000_0100);
+    /// 握手段的数据体末尾附有认证尾部
+    pub const AUTH: SegmentFlags = SegmentFlags(0b0000_1000);
+    /// Syn 段的数据体携带 Retry 令牌
+    pub const TOKEN: SegmentFlags = SegmentFlags(0b0001_0000);
+    /// Data 段途经的拥塞点标记的拥塞信号（congestion experienced），不参与校验和与认证，途中可以直接设置
+    pub const CE: SegmentFlags = SegmentFlags(0b0010_0000);
+    /// 确认段回送收到过的 CE（ECN echo），直到对端以 CWR 选项表明已经降窗
+    pub const ECE: SegmentFlags = SegmentFlags(0b0100_0000);
+    /// Data 段不可靠：不重传、不确认、不经重排缓冲（见 `unreliable` 模块）；只能出现在 Data 段上，其他段上仍是保留位
+    pub const UNRELIABLE: SegmentFlags = SegmentFlags(0b1000_0000);
+
+    // 当前版本在任何段上都有定义的位
+    const KNOWN: u8 = Self::ACK.0 | Self::SACK.0 | Self::SEALED.0 | Self::AUTH.0 | Self::TOKEN.0 | Self::CE.0 | Self::ECE.0;
+
+    pub const fn empty() -> Self {
+        SegmentFlags(0)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// 从原始位构造；包含保留位时返回 None。不知道段类型，UNRELIABLE 也视为保留位
+    pub const fn from_bits(bits: u8) -> Option<Self> {
+        if bits & !Self::KNOWN == 0 {
+            Some(SegmentFlags(bits))
+        } else {
+            None
+        }
+    }
+
+    /// 从 `segment_type` 类型的段上的原始位构造；包含这种段的保留位时返回 None
+    pub const fn from_bits_for(segment_type: SegmentType, bits: u8) -> Option<Self> {
+        let known = match segment_type {
+            SegmentType::Data => Self::KNOWN | Self::UNRELIABLE.0,
+            _ => Self::KNOWN,
+        };
+        if bits & !known == 0 {
+            Some(SegmentFlags(bits))
+        } else {
+            None
+        }
+    }
+
+    pub const fn contains(self, other: SegmentFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: SegmentFlags) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: SegmentFlags) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for SegmentFlags {
+    type Output = SegmentFlags;
+
+    fn bitor(self, rhs: SegmentFlags) -> SegmentFlags {
+        SegmentFlags(self.0 | rhs.0)
+    }
 }
 
 // L4 传输段（Segment）
-#[derive(Debug, Clone)]
-struct Segment {
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Segment {
     segment_type: SegmentType,
-    seq: u64,               // u64序列号（有序性重传检测）
+    flags: SegmentFlags,
+    stream_id: u16,         // 多路复用的流标识
+    conn_id: u32,           // 连接 ID，握手前为 0
+    seq: SeqNum,            // 序列号（有序性重传检测）
+    ack: SeqNum,            // 确认号，仅在确认类段上有意义
+    window: u32,            // 通告的接收窗口，仅在确认类段上有意义
+    checksum: ChecksumAlgorithm,    // 编码时使用的校验算法，解码时为段头中的算法
+    #[cfg_attr(feature = "serde", serde(default))]
+    options: Options,
+    #[cfg_attr(feature = "serde", serde(with = "payload_serde"))]
     data: Bytes,            // 改用 Bytes 避免拷贝，提升性能
 }
 
 impl Segment {
-    fn new(segment_type: SegmentType, seq: u64, data: Vec<u8>) -> Self {
+    /// `data` 可以是 `Bytes`、`Vec<u8>` 或 `&'static [u8]`：已经持有 `Bytes` 的调用方（例如转发收到的载荷）不必拷贝
+    pub fn new(segment_type: SegmentType, seq: impl Into<SeqNum>, data: impl Into<Bytes>) -> Self {
         Self {
             segment_type,
-            seq,
-            data: Bytes::from(data), // Vec<u8> 转 Bytes（零拷贝）
+            flags: SegmentFlags::empty(),
+            stream_id: 0,
+            conn_id: 0,
+            seq: seq.into(),
+            ack: SeqNum::default(),
+            window: 0,
+            checksum: ChecksumAlgorithm::default(),
+            options: Options::new(),
+            data: data.into(),
+        }
+    }
+
+    /// 载荷为常量的段，不分配内存
+    pub fn from_static(segment_type: SegmentType, seq: impl Into<SeqNum>, data: &'static [u8]) -> Self {
+        Self::new(segment_type, seq, Bytes::from_static(data))
+    }
+
+    /// 携带 nonce 的保活探测段
+    pub fn ping(nonce: u64) -> Self {
+        Self::new(SegmentType::Ping, 0, nonce.to_be_bytes().to_vec())
+    }
+
+    /// 对 nonce 为 `nonce` 的 Ping 的回应
+    pub fn pong(nonce: u64) -> Self {
+        Self::new(SegmentType::Pong, 0, nonce.to_be_bytes().to_vec())
+    }
+
+    /// 编码后恰为 `size` 字节的 Ping：nonce 之后以零填充，用于路径 MTU 探测（见 `pmtu` 模块）
+    pub fn probe(nonce: u64, size: usize) -> Self {
+        let mut data = vec![0u8; size.saturating_sub(Self::FIXED_HEADER_LEN).max(8)];
+        data[..8].copy_from_slice(&nonce.to_be_bytes());
+        Self::new(SegmentType::Ping, 0, data)
+    }
+
+    /// 请求对端的连接统计
+    pub fn stats_request(nonce: u64) -> Self {
+        Self::new(SegmentType::StatsRequest, 0, nonce.to_be_bytes().to_vec())
+    }
+
+    /// 对 nonce 为 `nonce` 的统计查询的回应，`stats` 是 `PeerStats::encode` 的结果
+    pub fn stats_reply(nonce: u64, stats: &[u8]) -> Self {
+        let mut data = nonce.to_be_bytes().to_vec();
+        data.extend_from_slice(stats);
+        Self::new(SegmentType::StatsReply, 0, data)
+    }
+
+    /// 以随机的 `token` 验证对端的新地址
+    pub fn path_challenge(token: u64) -> Self {
+        Self::new(SegmentType::PathChallenge, 0, token.to_be_bytes().to_vec())
+    }
+
+    /// 对令牌为 `token` 的 PathChallenge 的回应
+    pub fn path_response(token: u64) -> Self {
+        Self::new(SegmentType::PathResponse, 0, token.to_be_bytes().to_vec())
+    }
+
+    /// 告诉对端跳过 `first..=last`：发送方放弃了这些序列号上的消息
+    pub fn skip(first: SeqNum, last: SeqNum) -> Self {
+        Self::new(SegmentType::Skip, first, last.get().to_be_bytes().to_vec())
+    }
+
+    /// Skip 段跳过的最后一个序列号；其他段为 None
+    pub fn skip_end(&self) -> Option<SeqNum> {
+        if self.segment_type != SegmentType::Skip {
+            return None;
+        }
+        Some(SeqNum::new(u64::from_be_bytes(self.data[..].try_into().ok()?)))
+    }
+
+    /// 以序列号 `seq` 通知对端：之后的段改用连接 ID `conn_id`
+    pub fn new_conn_id(seq: SeqNum, conn_id: u32) -> Self {
+        Self::new(SegmentType::NewConnId, seq, conn_id.to_be_bytes().to_vec())
+    }
+
+    /// NewConnId 段通知的新连接 ID；其他段为 None
+    pub fn issued_conn_id(&self) -> Option<u32> {
+        if self.segment_type != SegmentType::NewConnId {
+            return None;
+        }
+        Some(u32::from_be_bytes(self.data[..].try_into().ok()?))
+    }
+
+    /// Ping/Pong、统计查询与路径验证段携带的 nonce（令牌）；其他段或数据体不是 8 字节时为 None（填充过的 Ping 与 StatsReply 取前 8 字节）
+    pub fn nonce(&self) -> Option<u64> {
+        let bytes = match self.segment_type {
+            SegmentType::Ping | SegmentType::StatsReply => self.data.get(..8)?,
+            SegmentType::Pong | SegmentType::StatsRequest | SegmentType::PathChallenge | SegmentType::PathResponse => &self.data[..],
+            _ => return None,
+        };
+        Some(u64::from_be_bytes(bytes.try_into().ok()?))
+    }
+
+    /// 以构造器方式创建段，适用于需要设置 ack / window / 流 ID 等可选字段的场景
+    pub fn builder(segment_type: SegmentType) -> SegmentBuilder {
+        SegmentBuilder::new(segment_type)
+    }
+
+    // 测试专用：直接以 Bytes 构造，避免生成器里的额外拷贝
+    #[cfg(test)]
+    pub(crate) fn from_parts(segment_type: SegmentType, seq: impl Into<SeqNum>, data: Bytes) -> Self {
+        Self { data, ..Self::new(segment_type, seq, Vec::new()) }
+    }
+
+    /// 段类型
+    pub fn segment_type(&self) -> SegmentType {
+        self.segment_type
+    }
+
+    /// 序列号
+    pub fn seq(&self) -> SeqNum {
+        self.seq
+    }
+
+    /// 标志位
+    pub fn flags(&self) -> SegmentFlags {
+        self.flags
+    }
+
+    /// 流 ID
+    pub fn stream_id(&self) -> u16 {
+        self.stream_id
+    }
+
+    /// 附加流的发送端与接收端不区分流，段发出前由连接打上流 ID
+    pub fn set_stream_id(&mut self, stream_id: u16) {
+        self.stream_id = stream_id;
+    }
+
+    /// 连接 ID
+    pub fn conn_id(&self) -> u32 {
+        self.conn_id
+    }
+
+    /// 发出前由连接打上自己的连接 ID
+    pub fn set_conn_id(&mut self, conn_id: u32) {
+        self.conn_id = conn_id;
+    }
+
+    /// 校验算法
+    pub fn checksum(&self) -> ChecksumAlgorithm {
+        self.checksum
+    }
+
+    /// 发出前由连接打上协商出的校验算法
+    pub fn set_checksum(&mut self, checksum: ChecksumAlgorithm) {
+        self.checksum = checksum;
+    }
+
+    /// 段头选项
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// 发出前替换段头选项
+    pub fn set_options(&mut self, options: Options) {
+        self.options = options;
+    }
+
+    /// Syn 段中对端接受的校验算法，按偏好排列；未识别的 id 被跳过，数据体为空时只有默认算法
+    pub fn checksum_offer(&self) -> Vec<ChecksumAlgorithm> {
+        let mut offer = &self.data[..self.trailer_start()];
+        if self.retry_token().is_some() {
+            offer = &offer[..offer.len() - Self::RETRY_TOKEN_LEN];
+        }
+        if offer.is_empty() {
+            return vec![ChecksumAlgorithm::default()];
+        }
+        offer.iter().filter_map(|&id| ChecksumAlgorithm::from_id(id)).collect()
+    }
+
+    /// 认证尾部的长度：nonce、回送的对端 nonce 与标签
+    pub const AUTH_TRAILER_LEN: usize = 16 + 16 + 32;
+
+    /// 设置了 AUTH 标志时数据体末尾的认证尾部；数据体不够长时为 None
+    pub fn auth_trailer(&self) -> Option<&[u8]> {
+        let start = self.data.len().checked_sub(Self::AUTH_TRAILER_LEN)?;
+        self.flags.contains(SegmentFlags::AUTH).then(|| &self.data[start..])
+    }
+
+    /// 加密的段（Data、NewConnId）在数据体末尾附加的认证标签的长度
+    pub const SEAL_TAG_LEN: usize = 16;
+
+    /// Retry 令牌的长度
+    pub const RETRY_TOKEN_LEN: usize = 8 + 16;
+
+    /// 设置了 TOKEN 标志的 Syn 段带回的 Retry 令牌；数据体不够长时为 None
+    pub fn retry_token(&self) -> Option<&[u8]> {
+        let end = self.trailer_start();
+        let start = end.checked_sub(Self::RETRY_TOKEN_LEN)?;
+        self.flags.contains(SegmentFlags::TOKEN).then(|| &self.data[start..end])
+    }
+
+    /// Syn 段的校验算法列表最多的 id 个数
+    pub const MAX_CHECKSUM_OFFER: usize = 16;
+
+    // 各类型段的数据体规则，编码与解码时都检查：Data 不限；Ack 为空，带 SACK 时是 SACK 区间（区间长度另见 `InvalidSack`），
+    // 带 AUTH 时另加认证尾部；Syn 是至多 `MAX_CHECKSUM_OFFER` 个算法 id，之后依标志是 Retry 令牌与认证尾部；
+    // Ping 至少是 8 字节的 nonce（路径 MTU 探测在其后填充）；Pong、StatsRequest、路径验证段与 Skip 恰为 8 字节；
+    // NewConnId 恰为 4 字节（加密时另加标签）；StatsReply 至少是 nonce 与版本号；
+    // Fin 与 Rst 为空；Retry 恰为一个令牌
+    fn check_payload(segment_type: SegmentType, flags: SegmentFlags, len: usize) -> Result<(), SegmentError> {
+        let trailer = if flags.contains(SegmentFlags::AUTH) { Self::AUTH_TRAILER_LEN } else { 0 };
+        let token = if flags.contains(SegmentFlags::TOKEN) { Self::RETRY_TOKEN_LEN } else { 0 };
+        let tag = if flags.contains(SegmentFlags::SEALED) { Self::SEAL_TAG_LEN } else { 0 };
+        let fits = match segment_type {
+            SegmentType::Data => true,
+            SegmentType::Ack => len.checked_sub(trailer).is_some_and(|rest| rest == 0 || flags.contains(SegmentFlags::SACK)),
+            SegmentType::Syn => len.checked_sub(trailer + token).is_some_and(|offer| offer <= Self::MAX_CHECKSUM_OFFER),
+            SegmentType::Ping => len >= 8,
+            SegmentType::Pong | SegmentType::StatsRequest | SegmentType::PathChallenge | SegmentType::PathResponse | SegmentType::Skip => len == 8,
+            SegmentType::NewConnId => len == 4 + tag,
+            SegmentType::StatsReply => len > 8,
+            SegmentType::Fin | SegmentType::Rst => len == 0,
+            SegmentType::Retry => len == Self::RETRY_TOKEN_LEN,
+        };
+        if !fits {
+            return Err(SegmentError::UnexpectedPayload { segment_type, len });
+        }
+        Ok(())
+    }
+
+    // 认证尾部（没有时为数据体末尾）的起点
+    fn trailer_start(&self) -> usize {
+        self.data.len() - self.auth_trailer().map_or(0, <[u8]>::len)
+    }
+
+    /// 确认号（仅当 `is_ack_bearing()` 时有意义）
+    pub fn ack(&self) -> SeqNum {
+        self.ack
+    }
+
+    /// 通告窗口（仅当 `is_ack_bearing()` 时有意义）
+    pub fn window(&self) -> u32 {
+        self.window
+    }
+
+    /// 数据体
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+
+    /// 数据体是否为密文
+    pub fn is_sealed(&self) -> bool {
+        self.flags.contains(SegmentFlags::SEALED)
+    }
+
+    // 加密、解密与认证后替换数据体并设置或清除相应的标志
+    #[cfg(any(feature = "crypto", test))]
+    pub(crate) fn set_flag(&mut self, flag: SegmentFlags, on: bool) {
+        if on {
+            self.flags.insert(flag);
+        } else {
+            self.flags.remove(flag);
         }
     }
 
-    // 头部固定长度：4(total_len) + 1(type) + 8(seq) = 13 字节（移除了冗余的 len 字段）
-    const FIXED_HEADER_LEN: usize = 4 + 1 + 8;
+    #[cfg(any(feature = "crypto", test))]
+    pub(crate) fn set_data(&mut self, data: Bytes) {
+        self.data = data;
+    }
+
+    // 段头中总长度与校验和之外的字段（type 到 checksum_id），与线上的字节相同；加密时作为关联数据
+    pub(crate) fn header_fields(&self) -> [u8; Self::CHECKSUM_OFFSET - 4] {
+        let mut fields = [0u8; Self::CHECKSUM_OFFSET - 4];
+        let mut buf = &mut fields[..];
+        buf.put_u8(self.segment_type.into());
+        buf.put_u8(self.flags.bits());
+        buf.put_u16(self.stream_id);
+        buf.put_u32(self.conn_id);
+        buf.put_u64(self.seq.get());
+        buf.put_u64(self.ack.get());
+        buf.put_u32(self.window);
+        buf.put_u8(self.checksum.id());
+        fields
+    }
+
+    // 加密与握手认证的关联数据：`header_fields`（除去 CE）之后接选项区长度与选项区
+    #[cfg(feature = "crypto")]
+    pub(crate) fn authenticated_header(&self) -> Vec<u8> {
+        let mut header = self.header_fields().to_vec();
+        header[1] &= !SegmentFlags::CE.bits();
+        header.push(self.options.len() as u8);
+        header.extend_from_slice(self.options.as_bytes());
+        header
+    }
+
+    /// 是否携带确认信息：Ack 段本身，或设置了 ACK 标志的捎带确认
+    pub fn is_ack_bearing(&self) -> bool {
+        self.segment_type == SegmentType::Ack || self.flags.contains(SegmentFlags::ACK)
+    }
+
+    // 头部固定长度：4(total_len) + 1(type) + 1(flags) + 2(stream_id) + 4(conn_id) + 8(seq) + 8(ack) + 4(window)
+    // + 1(checksum_id) + 4(checksum) + 1(options_len) = 38 字节，不含选项区
+    pub const FIXED_HEADER_LEN: usize = 4 + 1 + 1 + 2 + 4 + 8 + 8 + 4 + 1 + 4 + 1;
+
+    // 校验和字段的偏移，它之前的头部与它之后的全部内容参与计算
+    const CHECKSUM_OFFSET: usize = Self::FIXED_HEADER_LEN - 5;
+
+    // flags 字节的偏移
+    const FLAGS_OFFSET: usize = 5;
+
+    // 参与校验和计算的头部：校验和之前的字节，CE 位视为 0
+    fn checksummed_header(buf: &[u8]) -> [u8; Self::CHECKSUM_OFFSET] {
+        let mut header: [u8; Self::CHECKSUM_OFFSET] = buf[..Self::CHECKSUM_OFFSET].try_into().expect("fixed header");
+        header[Self::FLAGS_OFFSET] &= !SegmentFlags::CE.bits();
+        header
+    }
+
+    /// 段头的长度：固定头部加上选项区
+    pub fn header_len(&self) -> usize {
+        Self::FIXED_HEADER_LEN + self.options.len()
+    }
+
+    /// 流式解码时单个段允许声明的最大总长度，防止恶意长度前缀导致无界缓冲
+    pub const MAX_SEGMENT_LEN: usize = 1024 * 1024;
+
+    /// 编码后的总长度
+    pub fn encoded_len(&self) -> usize {
+        self.header_len() + self.data.len()
+    }
 
     // 编码：Segment -> Result<BytesMut, SegmentError>（返回 Result 处理溢出）
-    fn encode(&self) -> Result<BytesMut, SegmentError> {
-        let data_len = self.data.len();
-        let total_len = Self::FIXED_HEADER_LEN + data_len;
+    pub fn encode(&self) -> Result<BytesMut, SegmentError> {
+        // 精准预分配内存
+        let mut buf = BytesMut::with_capacity(self.encoded_len());
+        self.encode_into(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// 编码并追加到 `buf` 末尾，`buf` 中已有的内容不变；与缓冲池（`pool` 模块）配合避免每段一次分配
+    pub fn encode_into(&self, buf: &mut BytesMut) -> Result<(), SegmentError> {
+        Self::check_payload(self.segment_type, self.flags, self.data.len())?;
+        let total_len = self.encoded_len();
 
         // 将 total_len（usize）安全转为 u32（避免溢出和类型不匹配）
         let total_len_u32 = u32::try_from(total_len)
             .map_err(|_| SegmentError::TotalLenOverflow(total_len))?;
 
-        // 精准预分配内存（用 usize 类型的 total_len，内存分配需要 usize）
-        let mut buf = BytesMut::with_capacity(total_len);
+        buf.reserve(total_len);
+        let start = buf.len();
 
         // 1. 写入总长度占位（4字节）
         buf.put_u32(0);
-        // 2. 写入段类型（u8）
-        buf.put_u8(self.segment_type as u8);
-        // 3. 写入序列号（u64，大端序）
-        buf.put_u64(self.seq);
-        // 4. 写入数据体
+        // 2. 写入段类型、标志位、流 ID、连接 ID、序列号、确认号、窗口与校验算法
+        buf.put_slice(&self.header_fields());
+        // 3. 写入校验和占位
+        buf.put_u32(0);
+        // 4. 写入选项区（构造时已限制在 `options::MAX_LEN` 之内）与数据体
+        buf.put_u8(self.options.len() as u8);
+        buf.put_slice(self.options.as_bytes());
         buf.put_slice(&self.data);
 
+        Self::finish(&mut buf[start..], total_len_u32, self.checksum);
+        Ok(())
+    }
+
+    // 为编码好的 `frame` 写入总长度与以 `checksum` 算出的校验和（编码时两者先写为占位）
+    pub(crate) fn finish(frame: &mut [u8], total_len: u32, checksum: ChecksumAlgorithm) {
         // 用 u32 转 4 字节大端序（与目标切片长度一致）
-        buf[0..4].copy_from_slice(&total_len_u32.to_be_bytes());
+        frame[0..4].copy_from_slice(&total_len.to_be_bytes());
+        // 长度写入后才能计算校验和
+        let computed = checksum.implementation().compute(&Self::checksummed_header(frame), &frame[Self::CHECKSUM_OFFSET + 4..]);
+        frame[Self::CHECKSUM_OFFSET..Self::CHECKSUM_OFFSET + 4].copy_from_slice(&computed.to_be_bytes());
+    }
 
-        Ok(buf)
+    // 完整的一个段 `frame` 以 `checksum` 算出的校验和与段头中声明的相符
+    pub(crate) fn checksum_matches(frame: &[u8], checksum: ChecksumAlgorithm) -> bool {
+        let declared = u32::from_be_bytes(frame[Self::CHECKSUM_OFFSET..Self::CHECKSUM_OFFSET + 4].try_into().expect("four bytes"));
+        checksum.implementation().compute(&Self::checksummed_header(frame), &frame[Self::CHECKSUM_OFFSET + 4..]) == declared
     }
 
-    // 解码：&[u8] -> Result<Segment, SegmentError>
-    fn decode(buf: &[u8]) -> Result<Self, SegmentError> {
-        if buf.len() < 4 {
-            return Err(SegmentError::TooShort);
-        }
+    // 解码：&[u8] -> Result<Segment, SegmentError>，以段头中的算法校验
+    pub fn decode(buf: &[u8]) -> Result<Self, SegmentError> {
+        Self::decode_checked(buf, None)
+    }
 
-        let mut slice = &buf[..];
-        let total_len_declared = slice.get_u32() as usize; // 读取 4 字节 u32，转 usize 方便计算
+    /// 解码并要求段以协商出的 `negotiated` 算法编码、校验通过
+    pub fn decode_with(buf: &[u8], negotiated: ChecksumAlgorithm) -> Result<Self, SegmentError> {
+        Self::decode_checked(buf, Some(negotiated))
+    }
 
-        // 校验：总长度不能超过缓冲区实际长度，且至少包含固定头部
-        if total_len_declared > buf.len() || total_len_declared < Self::FIXED_HEADER_LEN {
-            return Err(SegmentError::InvalidTotalLen(
-                total_len_declared as u32,
-                buf.len()
-            ));
-        }
+    fn decode_checked(buf: &[u8], negotiated: Option<ChecksumAlgorithm>) -> Result<Self, SegmentError> {
+        Self::decode_parts(buf, negotiated, |payload| Bytes::copy_from_slice(&buf[payload]))
+    }
 
-        // 读取段类型
-        let segment_type = match slice.get_u8() {
-            0 => SegmentType::Data,
-            1 => SegmentType::Ack,
-            2 => SegmentType::Syn,
-            t => return Err(SegmentError::UnknownFrameType(t)),
-        };
+    // 解析并校验一个段；数据体由 `data` 按它在 `buf` 中的范围取得（复制，或从共享的 `Bytes` 切出）
+    fn decode_parts(buf: &[u8], negotiated: Option<ChecksumAlgorithm>, data: impl FnOnce(Range<usize>) -> Bytes) -> Result<Self, SegmentError> {
+        // 长度、段类型、标志位、流 ID、连接 ID 与序列号，与 `parse_header` 的校验一致
+        let SegmentHeader { total_len: total_len_declared, segment_type, flags, stream_id, conn_id, seq } = parse_header(buf)?;
+        let mut slice = &buf[SegmentHeader::LEN..];
 
-        // 读取序列号
-        let seq = slice.get_u64();
+        // 读取确认号与窗口
+        let ack = SeqNum::new(slice.get_u64());
+        let window = slice.get_u32();
 
-        // 读取数据体（长度 = 声明的总长度 - 固定头部长度）
-        let data_len = total_len_declared - Self::FIXED_HEADER_LEN;
-        let data = Bytes::copy_from_slice(&slice[..data_len]);
+        // 读取校验算法与校验和
+        let checksum_id = slice.get_u8();
+        let checksum = ChecksumAlgorithm::from_id(checksum_id).ok_or(SegmentError::UnknownChecksum(checksum_id))?;
+        let declared = slice.get_u32();
+
+        // 读取选项区，其后是数据体（长度 = 声明的总长度 - 固定头部长度 - 选项区长度）
+        let options_len = usize::from(slice.get_u8());
+        let data_len = (total_len_declared - Self::FIXED_HEADER_LEN).checked_sub(options_len).ok_or(SegmentError::BadOption)?;
+        let options = Options::decode(&slice[..options_len])?;
+        if segment_type == SegmentType::Ack && flags.contains(SegmentFlags::SACK) && !data_len.is_multiple_of(sack::BLOCK_LEN) {
+            return Err(SegmentError::InvalidSack(data_len));
+        }
+        Self::check_payload(segment_type, flags, data_len)?;
+        let payload_start = buf.len() - slice.len() + options_len;
+
+        // 校验：算法必须是协商出的那个，校验和必须相符（覆盖选项区与数据体）
+        if let Some(negotiated) = negotiated
+            && negotiated != checksum
+        {
+            return Err(SegmentError::UnexpectedChecksum { negotiated, found: checksum });
+        }
+        let computed = checksum.implementation().compute(&Self::checksummed_header(buf), &buf[Self::CHECKSUM_OFFSET + 4..total_len_declared]);
+        if computed != declared {
+            return Err(SegmentError::ChecksumMismatch { declared, computed });
+        }
+        let data = data(payload_start..payload_start + data_len);
 
         Ok(Self {
-            segment_type,   
+            segment_type,
+            flags,
+            stream_id,
+            conn_id,
             seq,
+            ack,
+            window,
+            checksum,
+            options,
             data,
         })
     }
+
+    /// 流式解码：从累积缓冲区头部取出一个完整段
+    /// 数据不足时返回 Ok(None) 且不消耗缓冲区；成功时消耗该段占用的字节。
+    /// 适用于一个缓冲区中紧密排列多个段（打包发送、流式传输）的场景。
+    pub fn decode_from(buf: &mut BytesMut) -> Result<Option<Self>, SegmentError> {
+        Self::decode_from_checked(buf, None)
+    }
+
+    /// 同 `decode_from`，并要求段以协商出的 `negotiated` 算法编码（见 `decode_with`）
+    pub fn decode_from_with(buf: &mut BytesMut, negotiated: ChecksumAlgorithm) -> Result<Option<Self>, SegmentError> {
+        Self::decode_from_checked(buf, Some(negotiated))
+    }
+
+    fn decode_from_checked(buf: &mut BytesMut, negotiated: Option<ChecksumAlgorithm>) -> Result<Option<Self>, SegmentError> {
+        let Some(total_len) = Self::frame_len(buf, Self::MAX_SEGMENT_LEN)? else {
+            return Ok(None);
+        };
+        let frame = buf.split_to(total_len);
+        Self::decode_checked(&frame, negotiated).map(Some)
+    }
+
+    /// 同 `decode_from`，但数据体直接从 `buf` 切出，与它共享存储，不复制。
+    /// 接收路径以它解码从接收缓冲切出的数据报，数据体原样到达 `Connection::recv`
+    pub fn decode_bytes(buf: &mut Bytes) -> Result<Option<Self>, SegmentError> {
+        Self::decode_bytes_checked(buf, None)
+    }
+
+    /// 同 `decode_bytes`，并要求段以协商出的 `negotiated` 算法编码（见 `decode_with`）
+    pub fn decode_bytes_with(buf: &mut Bytes, negotiated: ChecksumAlgorithm) -> Result<Option<Self>, SegmentError> {
+        Self::decode_bytes_checked(buf, Some(negotiated))
+    }
+
+    fn decode_bytes_checked(buf: &mut Bytes, negotiated: Option<ChecksumAlgorithm>) -> Result<Option<Self>, SegmentError> {
+        let Some(total_len) = Self::frame_len(buf, Self::MAX_SEGMENT_LEN)? else {
+            return Ok(None);
+        };
+        let frame = buf.split_to(total_len);
+        Self::decode_parts(&frame, negotiated, |payload| frame.slice(payload)).map(Some)
+    }
+
+    /// 流式解码的分帧：缓冲区开头完整段的长度，数据不足时返回 None。
+    /// 长度前缀小于固定头部或超过 `max_len` 时立即报错，不再等待更多数据；`decode_from` 系列与 TCP 传输（见 `tcp` 模块）共用它
+    pub fn frame_len(buf: &[u8], max_len: usize) -> Result<Option<usize>, SegmentError> {
+        if buf.len() < 4 {
+            return Ok(None);
+        }
+
+        let total_len_declared = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        let total_len = total_len_declared as usize;
+
+        // 长度前缀本身就不合法时立即报错，不再等待更多数据
+        if total_len < Self::FIXED_HEADER_LEN {
+            return Err(SegmentError::InvalidTotalLen(total_len_declared, buf.len()));
+        }
+        if total_len > max_len {
+            return Err(SegmentError::TotalLenTooLarge(total_len_declared, max_len));
+        }
+        if buf.len() < total_len {
+            return Ok(None);
+        }
+        Ok(Some(total_len))
+    }
+
+    /// 同 `decode_with`，并以 `unsealer` 解密数据段：返回明文，认证失败或数据段未加密时返回 `DecryptFailed`
+    #[cfg(feature = "crypto")]
+    pub fn decode_sealed(buf: &[u8], negotiated: ChecksumAlgorithm, unsealer: &Unsealer) -> Result<Self, SegmentError> {
+        unsealer.open(Self::decode_checked(buf, Some(negotiated))?)
+    }
+
+    /// 同 `decode_from_with`，并以 `unsealer` 解密数据段（见 `decode_sealed`）
+    #[cfg(feature = "crypto")]
+    pub fn decode_from_sealed(buf: &mut BytesMut, negotiated: ChecksumAlgorithm, unsealer: &Unsealer) -> Result<Option<Self>, SegmentError> {
+        Self::decode_from_checked(buf, Some(negotiated))?.map(|segment| unsealer.open(segment)).transpose()
+    }
+
+    /// 同 `decode_bytes_with`，并以 `unsealer` 解密数据段；明文写入新的缓冲，只有未加密的段是零复制的
+    #[cfg(feature = "crypto")]
+    pub fn decode_bytes_sealed(buf: &mut Bytes, negotiated: ChecksumAlgorithm, unsealer: &Unsealer) -> Result<Option<Self>, SegmentError> {
+        Self::decode_bytes_checked(buf, Some(negotiated))?.map(|segment| unsealer.open(segment)).transpose()
+    }
+
+    /// 按 `options` 解码：允许未知类型时，类型字节未识别的段按结构解析为 `Decoded::Unknown`，否则同 `decode`
+    pub fn decode_with_options(buf: &[u8], options: DecodeOptions) -> Result<Decoded, SegmentError> {
+        if options.allow_unknown && buf.len() > 4 && SegmentType::from_id(buf[4]).is_none() {
+            return RawSegment::decode(buf).map(Decoded::Unknown);
+        }
+        Self::decode(buf).map(Decoded::Known)
+    }
+
+    /// 同 `decode_from`，按 `options` 解码（见 `decode_with_options`）
+    pub fn decode_from_with_options(buf: &mut BytesMut, options: DecodeOptions) -> Result<Option<Decoded>, SegmentError> {
+        let Some(total_len) = Self::frame_len(buf, Self::MAX_SEGMENT_LEN)? else {
+            return Ok(None);
+        };
+        let frame = buf.split_to(total_len);
+        Self::decode_with_options(&frame, options).map(Some)
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// 同 `Segment::decode`：解码缓冲区开头的一个段，数据体复制出来
+impl TryFrom<&[u8]> for Segment {
+    type Error = SegmentError;
 
-    #[test]
-    fn test_encode_decode() {
-        // 1. 构造段
-        let segment = Segment::new(SegmentType::Syn, 12345, vec![0x11, 0x22, 0x33]);
+    fn try_from(buf: &[u8]) -> Result<Self, SegmentError> {
+        Segment::decode(buf)
+    }
+}
 
-        // 2. 编码（处理 Result）
-        let encoded = segment.encode().unwrap();
+/// 解码开头的一个段，数据体从 `buf` 中切出、不复制；其余同 `Segment::decode`
+impl TryFrom<Bytes> for Segment {
+    type Error = SegmentError;
 
-        // 3. 解码
-        let decoded = Segment::decode(&encoded).unwrap();
+    fn try_from(buf: Bytes) -> Result<Self, SegmentError> {
+        Segment::decode_parts(&buf, None, |payload| buf.slice(payload))
+    }
+}
 
-        // 4. 验证
-        assert_eq!(decoded.segment_type, SegmentType::Syn);
-        assert_eq!(decoded.seq, 12345);
-        assert_eq!(decoded.data, Bytes::from(vec![0x11, 0x22, 0x33]));
+/// 同 `Segment::encode`
+impl TryFrom<&Segment> for Bytes {
+    type Error = SegmentError;
+
+    fn try_from(segment: &Segment) -> Result<Self, SegmentError> {
+        segment.encode().map(BytesMut::freeze)
     }
+}
 
-    #[test]
-    fn test_decode_invalid_type() {
-        // 构造一个段类型为 3 的非法数据
-        let mut buf = BytesMut::new();
-        buf.put_u32(13); // 总长度 = 固定头部长度（13），无数据
-        buf.put_u8(3);   // 非法类型
-        buf.put_u64(0);  // 序列号
+impl TryFrom<Segment> for Bytes {
+    type Error = SegmentError;
 
-        let result = Segment::decode(&buf);
-        assert!(matches!(result, Err(SegmentError::UnknownFrameType(3))));
+    fn try_from(segment: Segment) -> Result<Self, SegmentError> {
+        Bytes::try_from(&segment)
     }
+}
 
-    #[test]
-    fn test_decode_invalid_total_len() {
-        // 总长度声明为 100，但实际缓冲区只有 13 字节
-        let mut buf = BytesMut::new();
-        buf.put_u32(100); // 非法总长度
-        buf.put_u8(0);
-        buf.put_u64(0);
+/// 解码模式；默认是端点使用的严格模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DecodeOptions {
+    /// 保留未知类型的段而不是返回 `UnknownFrameType`，供转发与记录工具透传新版本对端的段
+    pub allow_unknown: bool,
+}
 
-        let result = Segment::decode(&buf);
-        assert!(matches!(result, Err(SegmentError::InvalidTotalLen(100, 13))));
+/// 按 `DecodeOptions` 解码的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decoded {
+    Known(Segment),
+    Unknown(RawSegment),
+}
+
+impl Decoded {
+    /// 重新编码；未知类型的段与原始字节逐字节相同
+    pub fn encode(&self) -> Result<BytesMut, SegmentError> {
+        let mut buf = BytesMut::with_capacity(self.encoded_len());
+        self.encode_into(&mut buf)?;
+        Ok(buf)
     }
 
-    #[test]
-    fn test_encode_total_len_overflow() {
-        // 构造超大数据（超过 u32::MAX 长度）
-        let big_data = vec![0; (u32::MAX as usize) + 1]; // 数据长度 = 4294967296（u32最大值+1）
-        let segment = Segment::new(SegmentType::Data, 0, big_data);
+    /// 编码并追加到 `buf` 末尾
+    pub fn encode_into(&self, buf: &mut BytesMut) -> Result<(), SegmentError> {
+        match self {
+            Decoded::Known(segment) => segment.encode_into(buf),
+            Decoded::Unknown(raw) => raw.encode_into(buf),
+        }
+    }
 
-        // 编码应返回溢出错误
-        let result = segment.encode();
-        assert!(matches!(result, Err(SegmentError::TotalLenOverflow(_))));
+    /// 编码后的总长度
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Decoded::Known(segment) => segment.encoded_len(),
+            Decoded::Unknown(raw) => raw.encoded_len(),
+        }
     }
-}
\ No newline at end of file
+}
+
+/// 类型字节未识别的段：只按线上格式的结构解析（长度、段头字段、选项区、数据体）并校验校验和，
+/// 标志原样保留（新类型可能使用保留位），不解释数据体。重新编码与原始字节逐字节相同
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RawSegment {
+    type_id: u8,
+    flag_bits: u8,
+    stream_id: u16,
+    conn_id: u32,
+    seq: SeqNum,
+    ack: SeqNum,
+    window: u32,
+    checksum: ChecksumAlgorithm,
+    #[cfg_attr(feature = "serde", serde(default))]
+    options: Options,
+    #[cfg_attr(feature = "serde", serde(with = "payload_serde"))]
+    data: Bytes,
+}
+
+impl RawSegment {
+    fn decode(buf: &[u8]) -> Result<Self, SegmentError> {
+        if buf.len() < 4 {
+            return Err(SegmentError::TooShort);
+        }
+        let mut slice = buf;
+        let total_len = slice.get_u32() as usize;
+        if total_len > buf.len() || total_len < Segment::FIXED_HEADER_LEN {
+            return Err(SegmentError::InvalidTotalLen(total_len as u32, buf.len()));
+        }
+        let type_id = slice.get_u8();
+        let flag_bits = slice.get_u8();
+        let stream_id = slice.get_u16();
+        let conn_id = slice.get_u32();
+        let seq = SeqNum::new(slice.get_u64());
+        let ack = SeqNum::new(slice.get_u64());
+        let window = slice.get_u32();
+        let checksum_id = slice.get_u8();
+        let checksum = ChecksumAlgorithm::from_id(checksum_id).ok_or(SegmentError::UnknownChecksum(checksum_id))?;
+        let declared = slice.get_u32();
+        let options_len = usize::from(slice.get_u8());
+        let data_len = (total_len - Segment::FIXED_HEADER_LEN).checked_sub(options_len).ok_or(SegmentError::BadOption)?;
+        let options = Options::decode(&slice[..options_len])?;
+        let computed = checksum.implementation().compute(&Segment::checksummed_header(buf), &buf[Segment::CHECKSUM_OFFSET + 4..total_len]);
+        if computed != declared {
+            return Err(SegmentError::ChecksumMismatch { declared, computed });
+        }
+        let payload_start = total_len - data_len;
+        let data = Bytes::copy_from_slice(&buf[payload_start..total_len]);
+        Ok(Self { type_id, flag_bits, stream_id, conn_id, seq, ack, window, checksum, options, data })
+    }
+
+    /// 段头中的类型字节
+    pub fn type_id(&self) -> u8 {
+        self.type_id
+    }
+
+    /// 段头中的标志字节，含保留位
+    pub fn flag_bits(&self) -> u8 {
+        self.flag_bits
+    }
+
+    /// 流 ID
+    pub fn stream_id(&self) -> u16 {
+        self.stream_id
+    }
+
+    /// 连接 ID
+    pub fn conn_id(&self) -> u32 {
+        self.conn_id
+    }
+
+    /// 序列号
+    pub fn seq(&self) -> SeqNum {
+        self.seq
+    }
+
+    /// 确认号字段
+    pub fn ack(&self) -> SeqNum {
+        self.ack
+    }
+
+    /// 窗口字段
+    pub fn window(&self) -> u32 {
+        self.window
+    }
+
+    /// 校验算法
+    pub fn checksum(&self) -> ChecksumAlgorithm {
+        self.checksum
+    }
+
+    /// 段头选项，未识别的选项原样保留
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// 数据体
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+
+    /// 编码后的总长度
+    pub fn encoded_len(&self) -> usize {
+        Segment::FIXED_HEADER_LEN + self.options.len() + self.data.len()
+    }
+
+    /// 编码并追加到 `buf` 末尾
+    pub fn encode_into(&self, buf: &mut BytesMut) -> Result<(), SegmentError> {
+        let total_len = self.encoded_len();
+        let total_len_u32 = u32::try_from(total_len).map_err(|_| SegmentError::TotalLenOverflow(total_len))?;
+        buf.reserve(total_len);
+        let start = buf.len();
+        buf.put_u32(total_len_u32);
+        buf.put_u8(self.type_id);
+        buf.put_u8(self.flag_bits);
+        buf.put_u16(self.stream_id);
+        buf.put_u32(self.conn_id);
+        buf.put_u64(self.seq.get());
+        buf.put_u64(self.ack.get());
+        buf.put_u32(self.window);
+        buf.put_u8(self.checksum.id());
+        buf.put_u32(0);
+        buf.put_u8(self.options.len() as u8);
+        buf.put_slice(self.options.as_bytes());
+        buf.put_slice(&self.data);
+
+        let frame = &mut buf[start..];
+        let checksum = self.checksum.implementation().compute(&Segment::checksummed_header(frame), &frame[Segment::CHECKSUM_OFFSET + 4..]);
+        frame[Segment::CHECKSUM_OFFSET..Segment::CHECKSUM_OFFSET + 4].copy_from_slice(&checksum.to_be_bytes());
+        Ok(())
+    }
+}
+
+/// 数据报是否被截断：沿长度前缀逐段前进，最后一个段声明的长度超出了数据报的末尾。
+/// 接收缓冲区小于数据报时内核丢弃超出的部分，剩下的前缀可能仍是格式正确的更短的段，必须在解码前识别。
+/// 长度前缀本身不合法时返回 false，交给解码报告
+pub fn is_truncated(datagram: &[u8]) -> bool {
+    let mut offset = 0;
+    while offset < datagram.len() {
+        let Some(prefix) = datagram.get(offset..offset + 4) else {
+            return true;
+        };
+        let total_len = u32::from_be_bytes(prefix.try_into().expect("four bytes")) as usize;
+        if !(Segment::FIXED_HEADER_LEN..=Segment::MAX_SEGMENT_LEN).contains(&total_len) {
+            return false;
+        }
+        offset += total_len;
+    }
+    offset > datagram.len()
+}
+
+/// 在编码后的数据报中给每个 Data 段设置 CE 标志，返回标记的段数；供整形或转发的组件（以及测试中的故障注入）
+/// 模拟拥塞点，不需要重新计算校验和。遇到不合法的长度前缀时停止
+pub fn mark_ce(datagram: &mut [u8]) -> usize {
+    let mut offset = 0;
+    let mut marked = 0;
+    while let Some(header) = datagram.get_mut(offset..offset + Segment::FIXED_HEADER_LEN) {
+        let total_len = u32::from_be_bytes(header[..4].try_into().expect("four bytes")) as usize;
+        if total_len < Segment::FIXED_HEADER_LEN {
+            break;
+        }
+        if header[4] == SegmentType::Data as u8 {
+            header[Segment::FLAGS_OFFSET] |= SegmentFlags::CE.bits();
+            marked += 1;
+        }
+        offset += total_len;
+    }
+    marked
+}
+
+/// 分发数据报所需的段头字段，由 `parse_header` 读出
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentHeader {
+    pub total_len: usize,           // 声明的总长度，不超过缓冲区且至少包含固定头部
+    pub segment_type: SegmentType,
+    pub flags: SegmentFlags,
+    pub stream_id: u16,
+    pub conn_id: u32,
+    pub seq: SeqNum,
+}
+
+impl SegmentHeader {
+    // 这些字段占用的线上字节：total_len 到 seq
+    const LEN: usize = 4 + 1 + 1 + 2 + 4 + 8;
+}
+
+/// 只解析缓冲区头部第一个段的段头，不校验校验和、不解析选项区与数据体，供分发数据报时按连接 ID、
+/// 序列号找到处理它的连接；完整的校验留给真正消费它的连接。线上格式没有版本字段，段类型即是格式的检验：
+/// 长度前缀不合法、未知类型或有保留标志位时返回的错误与 `Segment::decode` 相同，返回 Ok 不代表能解码成功
+pub fn parse_header(buf: &[u8]) -> Result<SegmentHeader, SegmentError> {
+    if buf.len() < 4 {
+        return Err(SegmentError::TooShort);
+    }
+
+    let mut slice = buf;
+    let total_len = slice.get_u32() as usize; // 读取 4 字节 u32，转 usize 方便计算
+
+    // 校验：总长度不能超过缓冲区实际长度，且至少包含固定头部
+    if total_len > buf.len() || total_len < Segment::FIXED_HEADER_LEN {
+        return Err(SegmentError::InvalidTotalLen(total_len as u32, buf.len()));
+    }
+
+    // 读取段类型
+    let type_id = slice.get_u8();
+    let segment_type = SegmentType::try_from(type_id)?;
+
+    // 读取标志位（保留位必须为 0）、流 ID、连接 ID 与序列号
+    let flag_bits = slice.get_u8();
+    let flags = SegmentFlags::from_bits_for(segment_type, flag_bits).ok_or(SegmentError::ReservedFlags(flag_bits))?;
+    let stream_id = slice.get_u16();
+    let conn_id = slice.get_u32();
+    let seq = SeqNum::new(slice.get_u64());
+    Ok(SegmentHeader { total_len, segment_type, flags, stream_id, conn_id, seq })
+}
+
+/// 把多个段首尾相接打包成数据报，每个数据报不超过 `mss` 字节（单个超过 `mss` 的段独占一个数据报）；
+/// 段不会被拆分，接收端用 `Segment::decode_from` 逐个取出
+pub fn pack_datagrams(segments: &[Segment], mss: usize) -> Result<Vec<BytesMut>, SegmentError> {
+    pack(segments, mss, BytesMut::new)
+}
+
+/// 同 `pack_datagrams`，数据报的缓冲取自 `pool`，发出后应归还
+#[cfg(feature = "std")]
+pub fn pack_datagrams_with(segments: &[Segment], mss: usize, pool: &BufferPool) -> Result<Vec<BytesMut>, SegmentError> {
+    pack(segments, mss, || pool.get())
+}
+
+fn pack(segments: &[Segment], mss: usize, mut fresh: impl FnMut() -> BytesMut) -> Result<Vec<BytesMut>, SegmentError> {
+    let mut datagrams: Vec<BytesMut> = Vec::new();
+    for segment in segments {
+        match datagrams.last_mut() {
+            Some(last) if last.len() + segment.encoded_len() <= mss => segment.encode_into(last)?,
+            _ => {
+                let mut datagram = fresh();
+                segment.encode_into(&mut datagram)?;
+                datagrams.push(datagram);
+            }
+        }
+    }
+    Ok(datagrams)
+}
+
+/// 构造段时违反字段组合约束
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    WindowWithoutAck(SegmentType),  // 非确认类段上设置了窗口
+    PayloadOnControl(SegmentType),  // 控制段携带了数据体（携带 SACK 区间的 Ack 段、Ping/Pong、统计查询与 Syn 除外）
+    SackOnNonAck(SegmentType),      // 非 Ack 段上设置了 SACK
+    UnreliableOnNonData(SegmentType),   // 非 Data 段上设置了 UNRELIABLE
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::WindowWithoutAck(t) => write!(
+                f, "window is only valid on ack-bearing segments, got {:?} without ack", t
+            ),
+            BuildError::PayloadOnControl(t) => write!(f, "control segment {:?} must not carry payload", t),
+            BuildError::SackOnNonAck(t) => write!(f, "SACK is only valid on Ack segments, got {:?}", t),
+            BuildError::UnreliableOnNonData(t) => write!(f, "UNRELIABLE is only valid on Data segments, got {:?}", t),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildError {}
+
+/// 段构造器：按名字设置可选字段，`build()` 时统一校验
+#[derive(Debug, Clone)]
+pub struct SegmentBuilder {
+    segment_type: SegmentType,
+    flags: SegmentFlags,
+    stream_id: u16,
+    conn_id: u32,
+    seq: SeqNum,
+    ack: Option<SeqNum>,
+    window: Option<u32>,
+    checksum: ChecksumAlgorithm,
+    options: Options,
+    payload: Bytes,
+}
+
+impl SegmentBuilder {
+    pub fn new(segment_type: SegmentType) -> Self {
+        Self {
+            segment_type,
+            flags: SegmentFlags::empty(),
+            stream_id: 0,
+            conn_id: 0,
+            seq: SeqNum::default(),
+            ack: None,
+            window: None,
+            checksum: ChecksumAlgorithm::default(),
+            options: Options::new(),
+            payload: Bytes::new(),
+        }
+    }
+
+    /// 本段的序列号
+    pub fn data_seq(mut self, seq: impl Into<SeqNum>) -> Self {
+        self.seq = seq.into();
+        self
+    }
+
+    /// 确认号；在非 Ack 段上设置时会自动打上 ACK 标志（捎带确认）
+    pub fn ack(mut self, ack: impl Into<SeqNum>) -> Self {
+        self.ack = Some(ack.into());
+        self
+    }
+
+    /// 通告窗口，只允许出现在确认类段上
+    pub fn window(mut self, window: u32) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    pub fn stream(mut self, stream_id: u16) -> Self {
+        self.stream_id = stream_id;
+        self
+    }
+
+    pub fn conn_id(mut self, conn_id: u32) -> Self {
+        self.conn_id = conn_id;
+        self
+    }
+
+    pub fn payload(mut self, payload: impl Into<Bytes>) -> Self {
+        self.payload = payload.into();
+        self
+    }
+
+    /// SACK 区间，编码进 Ack 段的数据体并设置 SACK 标志；只允许出现在 Ack 段上
+    pub fn sack(mut self, ranges: &[(SeqNum, SeqNum)]) -> Self {
+        self.flags.insert(SegmentFlags::SACK);
+        self.payload = sack::encode_ranges(ranges);
+        self
+    }
+
+    /// 编码时使用的校验算法
+    pub fn checksum(mut self, checksum: ChecksumAlgorithm) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Syn 段接受的校验算法，按偏好编码进数据体
+    pub fn offer(mut self, algorithms: &[ChecksumAlgorithm]) -> Self {
+        self.payload = algorithms.iter().map(|algorithm| algorithm.id()).collect();
+        self
+    }
+
+    /// 段头选项（见 `options::Options`，构造时已限制长度）
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// 在 `offer` 设置的算法列表之后追加 Retry 令牌并设置 TOKEN 标志
+    pub fn retry_token(mut self, token: &[u8]) -> Self {
+        self.payload = [&self.payload[..], token].concat().into();
+        self.flags.insert(SegmentFlags::TOKEN);
+        self
+    }
+
+    /// 追加标志位（与 `ack()` 自动设置的标志合并）
+    pub fn flags(mut self, flags: SegmentFlags) -> Self {
+        self.flags.insert(flags);
+        self
+    }
+
+    pub fn build(self) -> Result<Segment, BuildError> {
+        let mut flags = self.flags;
+        if self.ack.is_some() && self.segment_type != SegmentType::Ack {
+            flags.insert(SegmentFlags::ACK);
+        }
+
+        let ack_bearing = self.segment_type == SegmentType::Ack || flags.contains(SegmentFlags::ACK);
+        if self.window.is_some() && !ack_bearing {
+            return Err(BuildError::WindowWithoutAck(self.segment_type));
+        }
+        let sack_carrier = flags.contains(SegmentFlags::SACK);
+        if sack_carrier && self.segment_type != SegmentType::Ack {
+            return Err(BuildError::SackOnNonAck(self.segment_type));
+        }
+        if flags.contains(SegmentFlags::UNRELIABLE) && self.segment_type != SegmentType::Data {
+            return Err(BuildError::UnreliableOnNonData(self.segment_type));
+        }
+        let payload_allowed = matches!(self.segment_type, SegmentType::Data | SegmentType::Ping | SegmentType::Pong | SegmentType::Syn | SegmentType::Retry
+            | SegmentType::StatsRequest | SegmentType::StatsReply | SegmentType::PathChallenge | SegmentType::PathResponse | SegmentType::Skip
+            | SegmentType::NewConnId);
+        let auth_carrier = self.segment_type == SegmentType::Ack && flags.contains(SegmentFlags::AUTH);
+        if !payload_allowed && !sack_carrier && !auth_carrier && !self.payload.is_empty() {
+            return Err(BuildError::PayloadOnControl(self.segment_type));
+        }
+
+        Ok(Segment {
+            segment_type: self.segment_type,
+            flags,
+            stream_id: self.stream_id,
+            conn_id: self.conn_id,
+            seq: self.seq,
+            ack: self.ack.unwrap_or_default(),
+            window: self.window.unwrap_or(0),
+            checksum: self.checksum,
+            options: self.options,
+            data: self.payload,
+        })
+    }
+}
+
+// 数据体的 serde 表示：人类可读格式（JSON/YAML）用 base64 字符串，二进制格式（bincode）用原始字节
+#[cfg(feature = "serde")]
+mod payload_serde {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use bytes::Bytes;
+    use serde::de::{self, Visitor};
+    use serde::{Deserializer, Serializer};
+    use std::fmt;
+
+    pub fn serialize<S: Serializer>(data: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&STANDARD.encode(data))
+        } else {
+            serializer.serialize_bytes(data)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(Base64Visitor)
+        } else {
+            deserializer.deserialize_byte_buf(RawVisitor)
+        }
+    }
+
+    struct Base64Visitor;
+
+    impl Visitor<'_> for Base64Visitor {
+        type Value = Bytes;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a base64 encoded payload")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Bytes, E> {
+            STANDARD.decode(v).map(Bytes::from).map_err(E::custom)
+        }
+    }
+
+    struct RawVisitor;
+
+    impl<'de> Visitor<'de> for RawVisitor {
+        type Value = Bytes;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("raw payload bytes")
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Bytes, E> {
+            Ok(Bytes::copy_from_slice(v))
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Bytes, E> {
+            Ok(Bytes::from(v))
+        }
+
+        // 部分格式会把字节作为 u8 序列传入
+        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Bytes, A::Error> {
+            let mut buf = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(b) = seq.next_element::<u8>()? {
+                buf.push(b);
+            }
+            Ok(Bytes::from(buf))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        // 1. 构造段
+        let segment = Segment::new(SegmentType::Syn, 12345, vec![0x11, 0x22, 0x33]);
+
+        // 2. 编码（处理 Result）
+        let encoded = segment.encode().unwrap();
+
+        // 3. 解码
+        let decoded = Segment::decode(&encoded).unwrap();
+
+        // 4. 验证
+        assert_eq!(decoded.segment_type, SegmentType::Syn);
+        assert_eq!(decoded.seq, SeqNum::new(12345));
+        assert_eq!(decoded.data, Bytes::from(vec![0x11, 0x22, 0x33]));
+    }
+
+    #[test]
+    fn test_decode_invalid_type() {
+        // 构造一个段类型为 0xFF 的非法数据
+        let mut buf = BytesMut::new();
+        buf.put_u32(38); // 总长度 = 固定头部长度（38），无数据
+        buf.put_u8(0xFF); // 非法类型
+        buf.put_u8(0);   // 标志位
+        buf.put_u16(0);  // 流 ID
+        buf.put_u32(0);  // 连接 ID
+        buf.put_u64(0);  // 序列号
+        buf.put_u64(0);  // 确认号
+        buf.put_u32(0);  // 窗口
+        buf.put_u8(0);   // 校验算法
+        buf.put_u32(0);  // 校验和
+        buf.put_u8(0);   // 选项区长度
+
+        let result = Segment::decode(&buf);
+        assert!(matches!(result, Err(SegmentError::UnknownFrameType(0xFF))));
+    }
+
+    #[test]
+    fn test_segment_type_byte_conversions() {
+        for id in 0..=u8::MAX {
+            match SegmentType::try_from(id) {
+                Ok(segment_type) => assert_eq!(u8::from(segment_type), id),
+                Err(e) => assert_eq!(e, SegmentError::UnknownFrameType(id)),
+            }
+            assert_eq!(SegmentType::from_id(id), SegmentType::try_from(id).ok());
+        }
+        assert_eq!(SegmentType::try_from(13), Ok(SegmentType::NewConnId));
+        assert_eq!(SegmentType::try_from(14), Err(SegmentError::UnknownFrameType(14)));
+
+        // 解码对类型字节的判断与 `TryFrom<u8>` 一致
+        let encoded = Segment::new(SegmentType::Fin, 1, vec![]).encode().unwrap();
+        for id in 0..=u8::MAX {
+            let mut buf = encoded.clone();
+            buf[4] = id;
+            match (SegmentType::try_from(id), parse_header(&buf)) {
+                (Ok(segment_type), Ok(header)) => assert_eq!(header.segment_type, segment_type),
+                (Err(expected), Err(found)) => assert_eq!(found, expected),
+                (expected, found) => panic!("type byte {}: {:?} vs {:?}", id, expected, found.map(|header| header.segment_type)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_segment_byte_conversions() {
+        let segment = Segment::new(SegmentType::Data, 7, vec![1, 2, 3]);
+        let bytes = Bytes::try_from(&segment).unwrap();
+        assert_eq!(bytes, segment.encode().unwrap());
+        assert_eq!(Bytes::try_from(segment.clone()).unwrap(), bytes);
+
+        let decoded: Segment = bytes.as_ref().try_into().unwrap();
+        assert_eq!(decoded, segment);
+        let shared = Segment::try_from(bytes.clone()).unwrap();
+        assert_eq!(shared, segment);
+        // 数据体与输入共用存储
+        assert_eq!(shared.data().as_ptr(), bytes[bytes.len() - 3..].as_ptr());
+
+        // 解码失败：截断、类型未知
+        assert!(matches!(Segment::try_from(&bytes[..3]), Err(SegmentError::TooShort)));
+        assert!(matches!(Segment::try_from(bytes.slice(..bytes.len() - 1)), Err(SegmentError::InvalidTotalLen(..))));
+        let mut unknown = BytesMut::from(&bytes[..]);
+        unknown[4] = 0xEE;
+        assert_eq!(Segment::try_from(unknown.freeze()), Err(SegmentError::UnknownFrameType(0xEE)));
+        // 编码失败：数据体不符合段类型的规则
+        let fin = Segment::new(SegmentType::Fin, 7, vec![1]);
+        let expected = SegmentError::UnexpectedPayload { segment_type: SegmentType::Fin, len: 1 };
+        assert_eq!(Bytes::try_from(&fin), Err(expected.clone()));
+        assert_eq!(Bytes::try_from(fin), Err(expected));
+    }
+
+    #[test]
+    fn test_decode_invalid_total_len() {
+        // 总长度声明为 100，但实际缓冲区只有 38 字节
+        let mut buf = BytesMut::new();
+        buf.put_u32(100); // 非法总长度
+        buf.put_u8(0);
+        buf.put_u8(0);
+        buf.put_u16(0);
+        buf.put_u32(0);
+        buf.put_u64(0);
+        buf.put_u64(0);
+        buf.put_u32(0);
+        buf.put_u8(0);
+        buf.put_u32(0);
+        buf.put_u8(0);
+
+        let result = Segment::decode(&buf);
+        assert!(matches!(result, Err(SegmentError::InvalidTotalLen(100, 38))));
+    }
+
+    #[test]
+    fn test_encode_total_len_overflow() {
+        // 构造超大数据（超过 u32::MAX 长度）
+        let big_data = vec![0; (u32::MAX as usize) + 1]; // 数据长度 = 4294967296（u32最大值+1）
+        let segment = Segment::new(SegmentType::Data, 0, big_data);
+
+        // 编码应返回溢出错误
+        let result = segment.encode();
+        assert!(matches!(result, Err(SegmentError::TotalLenOverflow(_))));
+    }
+
+    #[test]
+    fn test_decode_reserved_flags() {
+        // 最高位只在 Data 段上是 UNRELIABLE
+        let mut encoded = Segment::new(SegmentType::Ack, 1, vec![]).encode().unwrap();
+        encoded[5] = 0x80; // flags 字节位于 total_len 与 type 之后
+
+        let result = Segment::decode(&encoded);
+        assert!(matches!(result, Err(SegmentError::ReservedFlags(0x80))));
+    }
+
+    #[test]
+    fn test_unreliable_flag_only_on_data() {
+        let segment = Segment::builder(SegmentType::Data).data_seq(3).flags(SegmentFlags::UNRELIABLE).payload(&b"sample"[..]).build().unwrap();
+        let decoded = Segment::decode(&segment.encode().unwrap()).unwrap();
+        assert!(decoded.flags().contains(SegmentFlags::UNRELIABLE));
+        assert_eq!(decoded, segment);
+
+        let result = Segment::builder(SegmentType::Ping).flags(SegmentFlags::UNRELIABLE).build();
+        assert_eq!(result.unwrap_err(), BuildError::UnreliableOnNonData(SegmentType::Ping));
+    }
+
+    #[test]
+    fn test_builder_full_data_segment() {
+        let segment = Segment::builder(SegmentType::Data)
+            .data_seq(7)
+            .ack(3)
+            .window(65535)
+            .stream(5)
+            .payload(&b"payload"[..])
+            .build()
+            .unwrap();
+
+        // 数据段上设置 ack 会自动打上 ACK 标志
+        assert!(segment.flags().contains(SegmentFlags::ACK));
+        assert!(segment.is_ack_bearing());
+        assert_eq!(
+            (segment.seq().get(), segment.ack().get(), segment.window(), segment.stream_id()),
+            (7, 3, 65535, 5)
+        );
+        assert_eq!(segment.data(), &Bytes::from_static(b"payload"));
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_combinations() {
+        // 纯数据段（无 ack）上设置窗口
+        let result = Segment::builder(SegmentType::Data).window(10).build();
+        assert_eq!(result, Err(BuildError::WindowWithoutAck(SegmentType::Data)));
+
+        // 控制段携带数据体（Syn 的数据体是校验算法列表）
+        let result = Segment::builder(SegmentType::Fin).payload(vec![1]).build();
+        assert_eq!(result, Err(BuildError::PayloadOnControl(SegmentType::Fin)));
+
+        // SACK 只能出现在 Ack 段上
+        let result = Segment::builder(SegmentType::Data).sack(&[]).build();
+        assert_eq!(result, Err(BuildError::SackOnNonAck(SegmentType::Data)));
+    }
+
+    #[test]
+    fn test_builder_matches_hand_constructed() {
+        let built = Segment::builder(SegmentType::Data)
+            .data_seq(12345)
+            .payload(vec![0x11, 0x22, 0x33])
+            .build()
+            .unwrap();
+        let manual = Segment::new(SegmentType::Data, 12345, vec![0x11, 0x22, 0x33]);
+
+        assert_eq!(built, manual);
+        assert_eq!(built.encode().unwrap(), manual.encode().unwrap());
+        assert_eq!(Segment::decode(&built.encode().unwrap()).unwrap(), manual);
+
+        // 带 ack / window 的 Ack 段编解码往返
+        let ack = Segment::builder(SegmentType::Ack).ack(99).window(4096).build().unwrap();
+        assert_eq!(Segment::decode(&ack.encode().unwrap()).unwrap(), ack);
+    }
+
+    #[test]
+    fn test_decode_from_packed_buffer() {
+        // 两个段紧密排列，外加第三个段的前半部分
+        let a = Segment::new(SegmentType::Data, 1, vec![1, 2, 3]);
+        let b = Segment::new(SegmentType::Ack, 2, vec![]);
+        let c = Segment::new(SegmentType::Data, 3, vec![9; 10]).encode().unwrap();
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&a.encode().unwrap());
+        buf.extend_from_slice(&b.encode().unwrap());
+        buf.extend_from_slice(&c[..c.len() - 1]);
+
+        assert_eq!(Segment::decode_from(&mut buf).unwrap(), Some(a));
+        assert_eq!(Segment::decode_from(&mut buf).unwrap(), Some(b));
+        // 不完整的段：返回 None，且不消耗字节
+        assert_eq!(Segment::decode_from(&mut buf).unwrap(), None);
+        assert_eq!(buf.len(), c.len() - 1);
+
+        buf.extend_from_slice(&c[c.len() - 1..]);
+        assert_eq!(Segment::decode_from(&mut buf).unwrap().unwrap().seq, SeqNum::new(3));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_ping_pong_carry_nonce() {
+        let ping = Segment::ping(0xDEAD_BEEF);
+        let decoded = Segment::decode(&ping.encode().unwrap()).unwrap();
+        assert_eq!(decoded.segment_type(), SegmentType::Ping);
+        assert_eq!(decoded.nonce(), Some(0xDEAD_BEEF));
+        assert_eq!(Segment::pong(7).nonce(), Some(7));
+
+        // 非保活段或数据体长度不对时没有 nonce
+        assert_eq!(Segment::new(SegmentType::Data, 1, 7u64.to_be_bytes().to_vec()).nonce(), None);
+        assert_eq!(Segment::new(SegmentType::Ping, 0, vec![1, 2]).nonce(), None);
+        assert_eq!(Segment::new(SegmentType::Pong, 0, vec![0; 9]).nonce(), None);
+
+        // 探测段填充到指定大小，nonce 仍可读出
+        let probe = Segment::probe(9, 1500);
+        assert_eq!(probe.encode().unwrap().len(), 1500);
+        assert_eq!(probe.nonce(), Some(9));
+
+        // 统计查询的回应在 nonce 之后带出统计
+        assert_eq!(Segment::stats_request(3).nonce(), Some(3));
+        let reply = Segment::stats_reply(3, &[1, 2, 3]);
+        assert_eq!((reply.nonce(), &reply.data()[8..]), (Some(3), &[1, 2, 3][..]));
+    }
+
+    #[test]
+    fn test_skip_carries_the_abandoned_range() {
+        let skip = Segment::skip(SeqNum::new(u64::MAX), SeqNum::new(2));
+        let decoded = Segment::decode(&skip.encode().unwrap()).unwrap();
+        assert_eq!((decoded.segment_type(), decoded.seq(), decoded.skip_end()), (SegmentType::Skip, SeqNum::new(u64::MAX), Some(SeqNum::new(2))));
+        assert_eq!(Segment::new(SegmentType::Data, 1, 7u64.to_be_bytes().to_vec()).skip_end(), None);
+    }
+
+    #[test]
+    fn test_new_conn_id_carries_the_id() {
+        let rotation = Segment::new_conn_id(SeqNum::new(42), 0xdead_beef);
+        let decoded = Segment::decode(&rotation.encode().unwrap()).unwrap();
+        assert_eq!((decoded.segment_type(), decoded.seq(), decoded.issued_conn_id()), (SegmentType::NewConnId, SeqNum::new(42), Some(0xdead_beef)));
+        assert_eq!(Segment::ping(1).issued_conn_id(), None);
+    }
+
+    #[test]
+    fn test_pack_datagrams_keeps_segments_whole() {
+        // 每段 38 + 10 = 48 字节，100 字节的数据报放得下两个段
+        let segments: Vec<_> = (1..=5).map(|seq| Segment::new(SegmentType::Data, seq, vec![0; 10])).collect();
+        let datagrams = pack_datagrams(&segments, 100).unwrap();
+        assert_eq!(datagrams.iter().map(|d| d.len()).collect::<Vec<_>>(), vec![96, 96, 48]);
+
+        let mut unpacked = Vec::new();
+        for mut datagram in datagrams {
+            while let Some(segment) = Segment::decode_from(&mut datagram).unwrap() {
+                unpacked.push(segment);
+            }
+        }
+        assert_eq!(unpacked, segments);
+
+        // 超过 mss 的段独占一个数据报
+        let big = Segment::new(SegmentType::Data, 6, vec![0; 200]);
+        assert_eq!(pack_datagrams(&[big], 100).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_encode_into_appends() {
+        let first = Segment::new(SegmentType::Data, 1, vec![1; 10]);
+        let second = Segment::builder(SegmentType::Ack).ack(1u64).window(8).build().unwrap();
+        let mut buf = BytesMut::with_capacity(100);
+        first.encode_into(&mut buf).unwrap();
+        second.encode_into(&mut buf).unwrap();
+        assert_eq!(buf.len(), first.encoded_len() + second.encoded_len());
+        // 与逐个编码的结果相同
+        assert_eq!(&buf[..first.encoded_len()], &first.encode().unwrap()[..]);
+        assert_eq!(Segment::decode_from(&mut buf).unwrap(), Some(first));
+        assert_eq!(Segment::decode_from(&mut buf).unwrap(), Some(second));
+    }
+
+    #[test]
+    fn test_decode_bytes_slices_payload() {
+        let first = Segment::new(SegmentType::Data, 1, vec![1; 10]);
+        let second = Segment::new(SegmentType::Data, 2, vec![2; 20]);
+        let mut buf = BytesMut::new();
+        first.encode_into(&mut buf).unwrap();
+        second.encode_into(&mut buf).unwrap();
+        let mut datagram = buf.freeze();
+        let base = datagram.as_ptr() as usize;
+
+        // 与复制的解码结果相同，数据体指向数据报内部
+        let decoded = Segment::decode_bytes(&mut datagram).unwrap().unwrap();
+        assert_eq!(decoded, first);
+        assert_eq!(decoded.data().as_ptr() as usize, base + first.encoded_len() - 10);
+        let decoded = Segment::decode_bytes_with(&mut datagram, ChecksumAlgorithm::Crc32c).unwrap().unwrap();
+        assert_eq!(decoded, second);
+        assert_eq!(decoded.data().as_ptr() as usize, base + first.encoded_len() + second.encoded_len() - 20);
+        assert!(datagram.is_empty());
+        assert_eq!(Segment::decode_bytes(&mut datagram).unwrap(), None);
+    }
+
+    #[test]
+    fn test_new_accepts_any_payload() {
+        static PAYLOAD: &[u8] = b"payload";
+        let from_bytes = Segment::new(SegmentType::Data, 1, Bytes::from_static(PAYLOAD));
+        let from_vec = Segment::new(SegmentType::Data, 1, PAYLOAD.to_vec());
+        let from_slice = Segment::new(SegmentType::Data, 1, PAYLOAD);
+        let from_static = Segment::from_static(SegmentType::Data, 1, PAYLOAD);
+        assert_eq!(from_bytes, from_vec);
+        assert_eq!(from_slice, from_vec);
+        assert_eq!(from_static, from_vec);
+        // 常量载荷直接引用静态数据
+        assert_eq!(from_static.data().as_ptr(), PAYLOAD.as_ptr());
+        assert_eq!(from_bytes.data().as_ptr(), PAYLOAD.as_ptr());
+    }
+
+    #[test]
+    fn test_forwarded_payload_shares_the_allocation() {
+        let mut datagram = Segment::new(SegmentType::Data, 1, vec![9; 100]).encode().unwrap().freeze();
+        let received = Segment::decode_bytes(&mut datagram).unwrap().unwrap();
+
+        // 以收到的载荷构造新段转发，不复制数据
+        let forwarded = Segment::builder(SegmentType::Data).data_seq(7u64).payload(received.data().clone()).build().unwrap();
+        assert_eq!(forwarded.data().as_ptr(), received.data().as_ptr());
+        let forwarded = Segment::new(SegmentType::Data, 7, received.data().slice(10..));
+        assert_eq!(forwarded.data().as_ptr(), received.data()[10..].as_ptr());
+        // 编码时只拷贝进数据报缓冲区一次
+        let mut buf = BytesMut::new();
+        forwarded.encode_into(&mut buf).unwrap();
+        assert_eq!(Segment::decode(&buf).unwrap(), forwarded);
+    }
+
+    #[test]
+    fn test_mark_ce_keeps_checksum_valid() {
+        let ack = Segment::builder(SegmentType::Ack).ack(1u64).build().unwrap();
+        let segments = vec![Segment::new(SegmentType::Data, 1, vec![0; 10]), ack.clone(), Segment::new(SegmentType::Data, 2, vec![0; 10])];
+        let mut datagram = pack_datagrams(&segments, 1500).unwrap().remove(0);
+        // 只标记数据段，校验和不需要重新计算
+        assert_eq!(mark_ce(&mut datagram), 2);
+        let mut unpacked = Vec::new();
+        while let Some(segment) = Segment::decode_from(&mut datagram).unwrap() {
+            unpacked.push(segment);
+        }
+        assert!(unpacked[0].flags().contains(SegmentFlags::CE) && unpacked[2].flags().contains(SegmentFlags::CE));
+        assert_eq!(unpacked[1], ack);
+
+        // 其余的标志位仍受校验和保护
+        let mut encoded = Segment::new(SegmentType::Data, 1, vec![0; 10]).encode().unwrap();
+        encoded[5] |= SegmentFlags::ECE.bits();
+        assert!(matches!(Segment::decode(&encoded), Err(SegmentError::ChecksumMismatch { .. })));
+    }
+
+    #[test]
+    fn test_is_truncated() {
+        let segments: Vec<_> = (1..=2).map(|seq| Segment::new(SegmentType::Data, seq, vec![0; 10])).collect();
+        let datagram = pack_datagrams(&segments, 100).unwrap().remove(0);
+        assert!(!is_truncated(&datagram));
+        // 截在第二个段的数据体中或长度前缀中
+        assert!(is_truncated(&datagram[..60]));
+        assert!(is_truncated(&datagram[..50]));
+        // 恰好截在段边界时无法从内容识别，剩下的段本身是完整的
+        assert!(!is_truncated(&datagram[..48]));
+        // 不合法的长度前缀交给解码报告
+        assert!(!is_truncated(&[0, 0, 0, 1, 0, 0]));
+        assert!(!is_truncated(b"garbage"));
+    }
+
+    #[test]
+    fn test_checksum_detects_corruption() {
+        let segment = Segment::builder(SegmentType::Data).data_seq(9).ack(4).window(64).payload(&b"payload"[..]).build().unwrap();
+        for algorithm in [ChecksumAlgorithm::Crc32c, ChecksumAlgorithm::XxHash32] {
+            let mut segment = segment.clone();
+            segment.set_checksum(algorithm);
+            let encoded = segment.encode().unwrap();
+            assert_eq!(Segment::decode_with(&encoded, algorithm).unwrap(), segment);
+            // 头部（seq）、数据体与校验和字段本身的任一位翻转都被发现
+            for at in [14, Segment::FIXED_HEADER_LEN + 2, Segment::CHECKSUM_OFFSET + 3] {
+                let mut corrupted = encoded.clone();
+                corrupted[at] ^= 0x01;
+                assert!(
+                    matches!(Segment::decode(&corrupted), Err(SegmentError::ChecksumMismatch { .. })),
+                    "{} missed a flipped bit at {}", algorithm, at
+                );
+            }
+        }
+
+        // 不校验时损坏无法发现：数据体被翻转一位的段照常解码
+        let mut segment = segment.clone();
+        segment.set_checksum(ChecksumAlgorithm::NoChecksum);
+        let mut encoded = segment.encode().unwrap();
+        encoded[Segment::FIXED_HEADER_LEN + 2] ^= 0x01;
+        assert_eq!(&Segment::decode(&encoded).unwrap().data()[..], b"paxload");
+    }
+
+    #[test]
+    fn test_decode_with_requires_negotiated_algorithm() {
+        let mut segment = Segment::new(SegmentType::Data, 1, b"x".to_vec());
+        segment.set_checksum(ChecksumAlgorithm::XxHash32);
+        let encoded = segment.encode().unwrap();
+        assert_eq!(
+            Segment::decode_with(&encoded, ChecksumAlgorithm::Crc32c),
+            Err(SegmentError::UnexpectedChecksum { negotiated: ChecksumAlgorithm::Crc32c, found: ChecksumAlgorithm::XxHash32 })
+        );
+        let mut buf = encoded.clone();
+        assert!(Segment::decode_from_with(&mut buf, ChecksumAlgorithm::XxHash32).unwrap().is_some());
+
+        // 未知的算法 id
+        let mut unknown = encoded.clone();
+        unknown[Segment::CHECKSUM_OFFSET - 1] = 0x7F;
+        assert_eq!(Segment::decode(&unknown), Err(SegmentError::UnknownChecksum(0x7F)));
+
+        // Syn 的数据体是校验算法列表
+        let syn = Segment::builder(SegmentType::Syn).offer(&[ChecksumAlgorithm::XxHash32, ChecksumAlgorithm::NoChecksum]).build().unwrap();
+        let syn = Segment::decode(&syn.encode().unwrap()).unwrap();
+        assert_eq!(syn.checksum_offer(), vec![ChecksumAlgorithm::XxHash32, ChecksumAlgorithm::NoChecksum]);
+        assert_eq!(Segment::new(SegmentType::Syn, 1, vec![]).checksum_offer(), vec![ChecksumAlgorithm::Crc32c]);
+    }
+
+    #[test]
+    fn test_auth_trailer() {
+        // 认证尾部不计入校验算法列表
+        let mut payload = vec![ChecksumAlgorithm::XxHash32.id()];
+        payload.extend_from_slice(&[0xAB; Segment::AUTH_TRAILER_LEN]);
+        let syn = Segment::builder(SegmentType::Syn).flags(SegmentFlags::AUTH).payload(payload.clone()).build().unwrap();
+        let syn = Segment::decode(&syn.encode().unwrap()).unwrap();
+        assert_eq!(syn.checksum_offer(), vec![ChecksumAlgorithm::XxHash32]);
+        assert_eq!(syn.auth_trailer(), Some(&[0xAB; Segment::AUTH_TRAILER_LEN][..]));
+        // 没有 AUTH 标志时整个数据体都是算法列表
+        let plain = Segment::builder(SegmentType::Syn).payload(payload).build().unwrap();
+        assert_eq!(plain.auth_trailer(), None);
+        assert_eq!(plain.checksum_offer(), vec![ChecksumAlgorithm::XxHash32]);
+
+        // 完成握手的 Ack 可以只携带认证尾部，太短的尾部视为缺失
+        let ack = Segment::builder(SegmentType::Ack).ack(1u64).flags(SegmentFlags::AUTH).payload(vec![1; Segment::AUTH_TRAILER_LEN]).build().unwrap();
+        assert_eq!(ack.auth_trailer().map(<[u8]>::len), Some(Segment::AUTH_TRAILER_LEN));
+        let short = Segment::builder(SegmentType::Ack).flags(SegmentFlags::AUTH).payload(vec![1; 8]).build().unwrap();
+        assert_eq!(short.auth_trailer(), None);
+        assert_eq!(
+            Segment::builder(SegmentType::Fin).flags(SegmentFlags::AUTH).payload(vec![1; 8]).build(),
+            Err(BuildError::PayloadOnControl(SegmentType::Fin))
+        );
+    }
+
+    #[test]
+    fn test_retry_token() {
+        let token = [0x5A; Segment::RETRY_TOKEN_LEN];
+        let syn = Segment::builder(SegmentType::Syn).offer(&[ChecksumAlgorithm::XxHash32]).retry_token(&token).build().unwrap();
+        let syn = Segment::decode(&syn.encode().unwrap()).unwrap();
+        assert_eq!(syn.retry_token(), Some(&token[..]));
+        assert_eq!(syn.checksum_offer(), vec![ChecksumAlgorithm::XxHash32]);
+
+        // 令牌位于认证尾部之前
+        let mut payload = syn.data().to_vec();
+        payload.extend_from_slice(&[0xAB; Segment::AUTH_TRAILER_LEN]);
+        let signed = Segment::builder(SegmentType::Syn).flags(SegmentFlags::AUTH | SegmentFlags::TOKEN).payload(payload).build().unwrap();
+        assert_eq!(signed.retry_token(), Some(&token[..]));
+        assert_eq!(signed.checksum_offer(), vec![ChecksumAlgorithm::XxHash32]);
+
+        let short = Segment::builder(SegmentType::Syn).flags(SegmentFlags::TOKEN).payload(vec![1; 8]).build().unwrap();
+        assert_eq!(short.retry_token(), None);
+        assert_eq!(Segment::builder(SegmentType::Syn).offer(&[ChecksumAlgorithm::Crc32c]).build().unwrap().retry_token(), None);
+    }
+
+    #[test]
+    fn test_header_options() {
+        use crate::options::SegmentOption;
+
+        // 没有选项时选项区长度为 0
+        let plain = Segment::new(SegmentType::Data, 1, b"x".to_vec());
+        let encoded = plain.encode().unwrap();
+        assert_eq!(encoded[Segment::FIXED_HEADER_LEN - 1], 0);
+        assert_eq!(plain.header_len(), Segment::FIXED_HEADER_LEN);
+        assert!(Segment::decode(&encoded).unwrap().options().is_empty());
+
+        // 多个选项与数据体一起往返
+        let options = Options::new()
+            .with(SegmentOption::Timestamp { value: 100, echo: 42 })
+            .and_then(|options| options.with(SegmentOption::Mss(1400)))
+            .and_then(|options| options.with(SegmentOption::SackPermitted))
+            .unwrap();
+        let syn = Segment::builder(SegmentType::Syn).options(options.clone()).offer(&[ChecksumAlgorithm::XxHash32]).build().unwrap();
+        let encoded = syn.encode().unwrap();
+        assert_eq!(encoded.len(), syn.header_len() + syn.data().len());
+        let decoded = Segment::decode(&encoded).unwrap();
+        assert_eq!(decoded, syn);
+        assert_eq!(decoded.options().timestamp(), Some((100, 42)));
+        assert_eq!(decoded.options().mss(), Some(1400));
+        assert!(decoded.options().sack_permitted());
+        assert_eq!(decoded.checksum_offer(), vec![ChecksumAlgorithm::XxHash32]);
+
+        // 未识别的选项被跳过，段照常解码，选项原样保留
+        let unknown = Options::decode(&[200, 2, 0xAA, 0xBB, 2, 2, 0x05, 0x00]).unwrap();
+        let segment = Segment::builder(SegmentType::Ack).ack(3).options(unknown.clone()).build().unwrap();
+        let decoded = Segment::decode(&segment.encode().unwrap()).unwrap();
+        assert_eq!(decoded.options(), &unknown);
+        assert_eq!(decoded.options().mss(), Some(0x500));
+
+        // 选项的长度越过选项区末尾
+        let mut segment = Segment::builder(SegmentType::Ack).ack(3).options(Options::new().with(SegmentOption::Mss(1)).unwrap()).build().unwrap();
+        segment.set_checksum(ChecksumAlgorithm::NoChecksum);
+        let mut malformed = segment.encode().unwrap();
+        malformed[Segment::FIXED_HEADER_LEN + 1] = 3;
+        assert_eq!(Segment::decode(&malformed), Err(SegmentError::BadOption));
+        // 选项区长度超过段的剩余部分
+        let mut malformed = segment.encode().unwrap();
+        malformed[Segment::FIXED_HEADER_LEN - 1] = 5;
+        assert_eq!(Segment::decode(&malformed), Err(SegmentError::BadOption));
+    }
+
+    #[test]
+    fn test_decode_from_rejects_huge_length() {
+        let mut buf = BytesMut::new();
+        buf.put_u32(u32::MAX);
+        buf.put_u8(0);
+
+        let result = Segment::decode_from(&mut buf);
+        assert!(matches!(result, Err(SegmentError::TotalLenTooLarge(u32::MAX, _))));
+    }
+
+    // `parse_header` 与完整解码不会在段头的有效性上分歧：能解码的段头字段相同，段头无效时两者返回同一个错误
+    fn assert_header_agrees(buf: &[u8]) {
+        match (parse_header(buf), Segment::decode(buf)) {
+            (Ok(header), Ok(segment)) => {
+                assert_eq!(header.total_len, buf.len());
+                assert_eq!((header.segment_type, header.flags, header.stream_id), (segment.segment_type, segment.flags, segment.stream_id));
+                assert_eq!((header.conn_id, header.seq), (segment.conn_id, segment.seq));
+            }
+            (Ok(_), Err(_)) => {}
+            (Err(e), decoded) => assert_eq!(decoded, Err(e), "{:02x?}", buf),
+        }
+    }
+
+    #[test]
+    fn test_parse_header_agrees_with_decode() {
+        let corpus = [
+            Segment::builder(SegmentType::Data).conn_id(7).stream(2).data_seq(u64::MAX).ack(3).window(8).payload(&b"data"[..]).build().unwrap(),
+            Segment::builder(SegmentType::Ack).conn_id(7).ack(9).window(64).sack(&[(SeqNum::new(11), SeqNum::new(12))]).build().unwrap(),
+            Segment::builder(SegmentType::Syn).data_seq(1000).offer(&[ChecksumAlgorithm::Crc32c]).build().unwrap(),
+            Segment::ping(5),
+            Segment::new(SegmentType::Fin, 4, Vec::new()),
+        ];
+        for segment in corpus {
+            let encoded = segment.encode().unwrap();
+            let header = parse_header(&encoded).unwrap();
+            assert_eq!((header.segment_type, header.conn_id, header.seq), (segment.segment_type, segment.conn_id, segment.seq));
+            // 每个截断与每一位翻转
+            for len in 0..=encoded.len() {
+                assert_header_agrees(&encoded[..len]);
+            }
+            for at in 0..encoded.len() * 8 {
+                let mut corrupted = encoded.to_vec();
+                corrupted[at / 8] ^= 1 << (at % 8);
+                assert_header_agrees(&corrupted);
+            }
+        }
+        // 只读段头：数据体与校验和的损坏留给完整解码
+        let mut corrupted = Segment::new(SegmentType::Data, 1, b"x".to_vec()).encode().unwrap();
+        corrupted[Segment::FIXED_HEADER_LEN] ^= 0x01;
+        assert!(parse_header(&corrupted).is_ok());
+        assert!(matches!(Segment::decode(&corrupted), Err(SegmentError::ChecksumMismatch { .. })));
+    }
+
+    #[test]
+    fn test_payload_rules_per_type() {
+        let token = Segment::RETRY_TOKEN_LEN;
+        let trailer = Segment::AUTH_TRAILER_LEN;
+        let offer = Segment::MAX_CHECKSUM_OFFER;
+        // 每种类型（与标志）允许的边界长度，以及多（或少）一个字节的违规长度
+        let cases = [
+            (SegmentType::Ack, SegmentFlags::empty(), 0, 1),
+            (SegmentType::Ack, SegmentFlags::AUTH, trailer, trailer + 1),
+            (SegmentType::Ack, SegmentFlags::AUTH, trailer, trailer - 1),
+            (SegmentType::Syn, SegmentFlags::empty(), offer, offer + 1),
+            (SegmentType::Syn, SegmentFlags::TOKEN, token + offer, token + offer + 1),
+            (SegmentType::Syn, SegmentFlags::TOKEN | SegmentFlags::AUTH, token + trailer, token + trailer - 1),
+            (SegmentType::Ping, SegmentFlags::empty(), 8, 7),
+            (SegmentType::Pong, SegmentFlags::empty(), 8, 9),
+            (SegmentType::Pong, SegmentFlags::empty(), 8, 7),
+            (SegmentType::StatsRequest, SegmentFlags::empty(), 8, 9),
+            (SegmentType::StatsReply, SegmentFlags::empty(), 9, 8),
+            (SegmentType::PathChallenge, SegmentFlags::empty(), 8, 9),
+            (SegmentType::PathResponse, SegmentFlags::empty(), 8, 7),
+            (SegmentType::Skip, SegmentFlags::empty(), 8, 0),
+            (SegmentType::NewConnId, SegmentFlags::empty(), 4, 8),
+            (SegmentType::Fin, SegmentFlags::empty(), 0, 1),
+            (SegmentType::Rst, SegmentFlags::empty(), 0, 1),
+            (SegmentType::Retry, SegmentFlags::empty(), token, token + 1),
+            (SegmentType::Retry, SegmentFlags::empty(), token, token - 1),
+        ];
+        for (segment_type, flags, valid, invalid) in cases {
+            let with_len = |len: usize| {
+                let mut segment = Segment::new(segment_type, 1, vec![0; len]);
+                segment.flags = flags;
+                segment
+            };
+            let segment = with_len(valid);
+            let encoded = segment.encode().unwrap();
+            assert_eq!(Segment::decode(&encoded).unwrap(), segment);
+
+            // 编码时拒绝，手工拼出的同样的段解码时也拒绝
+            let expected = Err(SegmentError::UnexpectedPayload { segment_type, len: invalid });
+            assert_eq!(with_len(invalid).encode().map(|_| ()), expected);
+            let mut smuggled = BytesMut::from(&encoded[..Segment::FIXED_HEADER_LEN]);
+            smuggled.resize(Segment::FIXED_HEADER_LEN + invalid, 0);
+            let total_len = smuggled.len() as u32;
+            smuggled[..4].copy_from_slice(&total_len.to_be_bytes());
+            assert_eq!(Segment::decode(&smuggled).map(|_| ()), expected, "{:?} {:?}", segment_type, flags);
+        }
+        // Data 段与填充过的 Ping 不限长度
+        assert!(Segment::new(SegmentType::Data, 1, vec![0; 3000]).encode().is_ok());
+        assert!(Segment::probe(7, 1400).encode().is_ok());
+    }
+
+    // 把编码改成类型字节为 `type_id`、标志字节为 `flag_bits` 的段，并重新计算校验和
+    fn retyped(segment: &Segment, type_id: u8, flag_bits: u8) -> BytesMut {
+        let mut encoded = segment.encode().unwrap();
+        encoded[4] = type_id;
+        encoded[Segment::FLAGS_OFFSET] = flag_bits;
+        let checksum = segment.checksum().implementation().compute(&Segment::checksummed_header(&encoded), &encoded[Segment::CHECKSUM_OFFSET + 4..]);
+        encoded[Segment::CHECKSUM_OFFSET..Segment::CHECKSUM_OFFSET + 4].copy_from_slice(&checksum.to_be_bytes());
+        encoded
+    }
+
+    #[test]
+    fn test_unknown_type_roundtrips_when_allowed() {
+        let mut segment = Segment::builder(SegmentType::Data).conn_id(7).data_seq(99).ack(3).window(64).payload(&b"from v2"[..]).build().unwrap();
+        segment.set_options(Options::decode(&[99, 2, 0xAB, 0xCD]).unwrap());
+        let encoded = retyped(&segment, 42, 0x80 | SegmentFlags::ACK.bits());
+        let allow = DecodeOptions { allow_unknown: true };
+
+        let Decoded::Unknown(raw) = Segment::decode_with_options(&encoded, allow).unwrap() else {
+            panic!("unknown type decoded as a known segment");
+        };
+        assert_eq!((raw.type_id(), raw.flag_bits(), raw.conn_id()), (42, 0x81, 7));
+        assert_eq!((raw.seq(), raw.ack(), raw.window()), (SeqNum::new(99), SeqNum::new(3), 64));
+        assert_eq!((&raw.data()[..], raw.options().as_bytes()), (&b"from v2"[..], &[99, 2, 0xAB, 0xCD][..]));
+        assert_eq!(Decoded::Unknown(raw).encode().unwrap(), encoded);
+
+        // 严格模式（默认）照旧拒绝；已知类型仍解码为 `Segment`
+        assert_eq!(Segment::decode(&encoded), Err(SegmentError::UnknownFrameType(42)));
+        assert_eq!(Segment::decode_with_options(&encoded, DecodeOptions::default()), Err(SegmentError::UnknownFrameType(42)));
+        let known = segment.encode().unwrap();
+        assert_eq!(Segment::decode_with_options(&known, allow).unwrap(), Decoded::Known(segment.clone()));
+
+        // 结构上的错误与损坏依然报告
+        let mut corrupted = encoded.clone();
+        corrupted[Segment::FIXED_HEADER_LEN + 5] ^= 0x01;
+        assert!(matches!(Segment::decode_with_options(&corrupted, allow), Err(SegmentError::ChecksumMismatch { .. })));
+
+        // 打包的数据报中已知与未知类型的段逐个取出，原样转发
+        let mut datagram = BytesMut::new();
+        datagram.extend_from_slice(&encoded);
+        datagram.extend_from_slice(&known);
+        let mut forwarded = BytesMut::new();
+        while let Some(decoded) = Segment::decode_from_with_options(&mut datagram, allow).unwrap() {
+            decoded.encode_into(&mut forwarded).unwrap();
+        }
+        assert_eq!(forwarded, [&encoded[..], &known[..]].concat());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_json_roundtrip() {
+        let segment = Segment::new(SegmentType::Data, u64::MAX, b"hello".to_vec());
+
+        // JSON 中数据体应为 base64 字符串
+        let json = serde_json::to_string(&segment).unwrap();
+        assert!(json.contains("\"aGVsbG8=\""));
+
+        let back: Segment = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.segment_type, segment.segment_type);
+        assert_eq!(back.seq, segment.seq);
+        assert_eq!(back.data, segment.data);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_bincode_roundtrip() {
+        let segment = Segment::new(SegmentType::Ping, 42, vec![0u8, 1, 2, 255, 0, 1, 2, 255]);
+
+        let raw = bincode::serialize(&segment).unwrap();
+        let back: Segment = bincode::deserialize(&raw).unwrap();
+        assert_eq!(back.segment_type, segment.segment_type);
+        assert_eq!(back.seq, segment.seq);
+        assert_eq!(back.data, segment.data);
+
+        // 解码后的段重新编码应与原始编码逐字节一致
+        assert_eq!(back.encode().unwrap(), segment.encode().unwrap());
+    }
+}
+
+// 生成式测试：随机段的编解码往返、任意输入不 panic、截断必然报错
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const MAX_PAYLOAD: usize = 64 * 1024;
+
+    fn arb_segment_type() -> impl Strategy<Value = SegmentType> {
+        prop_oneof![
+            Just(SegmentType::Data),
+            Just(SegmentType::Ack),
+            Just(SegmentType::Syn),
+            Just(SegmentType::Ping),
+            Just(SegmentType::Pong),
+            Just(SegmentType::Fin),
+            Just(SegmentType::Rst),
+            Just(SegmentType::Retry),
+            Just(SegmentType::StatsRequest),
+            Just(SegmentType::StatsReply),
+            Just(SegmentType::PathChallenge),
+            Just(SegmentType::PathResponse),
+            Just(SegmentType::Skip),
+            Just(SegmentType::NewConnId),
+        ]
+    }
+
+    // 把随机数据体截短或补齐到该类型段允许的长度（见 `Segment::check_payload`）
+    fn fit_payload(segment_type: SegmentType, mut data: Vec<u8>) -> Vec<u8> {
+        let len = match segment_type {
+            SegmentType::Data => data.len(),
+            SegmentType::Ack | SegmentType::Fin | SegmentType::Rst => 0,
+            SegmentType::Syn => data.len().min(Segment::MAX_CHECKSUM_OFFER),
+            SegmentType::Ping => data.len().max(8),
+            SegmentType::Pong | SegmentType::StatsRequest | SegmentType::PathChallenge | SegmentType::PathResponse | SegmentType::Skip => 8,
+            SegmentType::NewConnId => 4,
+            SegmentType::StatsReply => data.len().max(9),
+            SegmentType::Retry => Segment::RETRY_TOKEN_LEN,
+        };
+        data.resize(len, 0);
+        data
+    }
+
+    // Arbitrary 风格的段生成器：类型随机、seq 覆盖整个 u64、数据体 0..64KB（按类型截短或补齐），头部其余字段与校验算法同样随机
+    fn arb_segment() -> impl Strategy<Value = Segment> {
+        (
+            arb_segment_type(),
+            any::<u64>(),
+            proptest::collection::vec(any::<u8>(), 0..MAX_PAYLOAD),
+            (any::<bool>(), any::<u16>(), any::<u32>(), any::<u64>(), any::<u32>()),
+            0..3u8,
+        )
+            .prop_map(|(t, seq, data, (ack_flag, stream_id, conn_id, ack, window), checksum)| {
+                let mut segment = Segment::from_parts(t, seq, Bytes::from(fit_payload(t, data)));
+                if ack_flag {
+                    segment.flags.insert(SegmentFlags::ACK);
+                }
+                segment.stream_id = stream_id;
+                segment.conn_id = conn_id;
+                segment.ack = SeqNum::new(ack);
+                segment.window = window;
+                segment.checksum = ChecksumAlgorithm::from_id(checksum).expect("known id");
+                segment
+            })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(128))]
+
+        #[test]
+        fn prop_encode_decode_roundtrip(segment in arb_segment()) {
+            let encoded = segment.encode().unwrap();
+            let decoded = Segment::decode(&encoded).unwrap();
+            prop_assert_eq!(decoded, segment);
+        }
+
+        #[test]
+        fn prop_decode_never_panics(buf in proptest::collection::vec(any::<u8>(), 0..MAX_PAYLOAD)) {
+            // 只要求不 panic，成功或失败均可
+            let _ = Segment::decode(&buf);
+        }
+
+        #[test]
+        fn prop_parse_header_agrees_with_decode(segment in arb_segment(), at in any::<prop::sample::Index>(), byte in any::<u8>()) {
+            // 随机改写段头中的一个字节
+            let mut encoded = segment.encode().unwrap().to_vec();
+            let at = at.index(SegmentHeader::LEN);
+            encoded[at] = byte;
+            match (parse_header(&encoded), Segment::decode(&encoded)) {
+                (Ok(header), Ok(decoded)) => prop_assert_eq!((header.conn_id, header.seq), (decoded.conn_id, decoded.seq)),
+                (Ok(_), Err(_)) => {}
+                (Err(e), decoded) => prop_assert_eq!(decoded, Err(e)),
+            }
+        }
+
+        #[test]
+        fn prop_truncated_encoding_is_error(segment in arb_segment(), cut in any::<prop::sample::Index>()) {
+            let encoded = segment.encode().unwrap();
+            // 截断位置取 [0, len)，即至少少 1 字节
+            let at = cut.index(encoded.len());
+            prop_assert!(Segment::decode(&encoded[..at]).is_err());
+        }
+    }
+}
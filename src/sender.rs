@@ -0,0 +1,1753 @@
+//! 连接的发送端
+//! 分配序列号、登记重传队列、维护 RTT 估计，并用滑动窗口限制在途段数：
+//! 窗口 = min(拥塞窗口, 对端通告窗口, 本地配置窗口)，窗口满时发送方通过 `poll_send_ready` 挂起，
+//! 确认到达、窗口打开后被唤醒。同一累计确认值在有在途数据时重复到达三次即快速重传
+//! 最早的未确认段，无需等待 RTO；携带 SACK 的确认则只重传空洞（之上已有三个段被 SACK，受拥塞窗口限制）。
+//! 每个丢失恢复期（直到累计确认越过丢包时已发送的最大序列号）只通知一次拥塞控制。
+//! 设置了 ECE 的确认表示途中出现了拥塞标记，同样按恢复期（这里约等于一个 RTT）最多降窗一次，
+//! 已在丢失恢复中时不再重复降窗；之后的第一个新数据段携带 CWR 选项，让对端停止回送。
+//! 对端通告零窗口时停止发送数据，并启动坚持定时器：到期后发送探测段（重复已确认的序列号、空数据体），
+//! 对端会以携带当前窗口的确认回应，避免窗口更新确认丢失导致双方永久等待。探测不进重传队列，
+//! 窗口关闭期间（包括打开窗口的那个确认）的重复确认也不计入快速重传，探测因而不会被当成丢包。
+//! 小写入合并（Nagle）：`write` 在有在途数据时把写入暂存，待攒够一个 MSS、确认到达或合并定时器到期后
+//! 一次性交出。每次写入仍是独立的段，合并只发生在数据报层面（`segment::pack_datagrams` 把多个完整的段
+//! 首尾相接放进同一个数据报，对端用 `Segment::decode_from` 逐个拆出），因此消息边界永远不会被改变。
+//! `nodelay` 关闭该行为（连接运行期间可经 `Connection::set_option` 切换，见 `tuning` 模块）。
+//! 发送队列：`write` 不受窗口限制，窗口已满时写入暂存在合并缓冲中等待确认；暂存与在途数据的字节数之和
+//! 受 `LinkConfig::send_buffer` 限制，满时 `write` 返回 `WouldBlock`，发送方通过 `poll_write_ready` 挂起；设置了连接的内存上限时
+//! 队列还受连接分给它的额度限制（`set_queue_limit`，见 `budget` 模块）。
+//! 发送节奏：`LinkConfig::pacing` 打开时，`flush` 交出的新数据段还要经过 `Pacer`，按 cwnd/SRTT 算出的速率逐个放出，
+//! 暂时不能放行的写入留在合并缓冲中，由节奏定时器（`next_deadline`）到期时的 `on_timeout` 交出。`send` 不受节奏限制，
+//! 但同样消耗令牌。
+//! 填满发送窗口的数据段携带 ACK-now 选项：之后要等确认才能继续发送，对端不应再延迟确认它。
+//! 以 `SendOptions { ordered: false, .. }` 写入的消息携带 unordered 选项，对端不等之前的空洞即可交付它（见 `receiver` 模块），
+//! 它在发送端与其他数据段完全一样：占用序列号、登记重传、受窗口限制。
+//! `write_message` 把放不进一个段的消息切成若干片一次写入，除最后一片外都携带 more 选项，对端据此重组（见 `receiver` 模块）；
+//! 各片连续占用序列号，同时写入的其他消息不会插在中间。
+//! 设置了 `SendOptions::tag` 的消息只在第一片上携带 tag 选项，对端收齐后连同标签交付（见 `receiver` 模块）；
+//! 重传的段原样携带它，暂存写入被重新分片（`refragment`、`rewind`）时标签留在新的第一片上。
+//! 部分可靠：设置了 `SendOptions::ttl` 的消息写入之后超过 TTL 还没有全部被确认时被放弃。还没发出的部分直接丢弃；
+//! 已发出、未被确认的段移出重传队列，由一个 Skip 段占住其中最小的序列号、告诉对端跳过到这条消息已发出的最后一片，
+//! 对端的重排缓冲区因此不会一直等待不再重传的数据。Skip 像数据段一样登记到重传队列，直到被确认；
+//! 已被 SACK、对端已经收齐的消息不放弃。
+//! 每次确认从重传队列采样投递速率（见 `rate` 模块），样本交给拥塞控制的 `on_rate_sample`；
+//! 处理完一个确认、交出全部暂存的写入后窗口仍有空位时标记应用受限，这段时间的样本不会拉低带宽估计。
+//! 设置了观察者时，重传、RTO 到期、零窗口停顿与 RTT 样本记进 `Observations`，由连接取出（见 `observer` 模块）。
+//! FIN 像数据段一样占用一个序列号并登记到重传队列，它被累计确认即表示之前的数据全部送达。
+//! NewConnId 同样占用序列号、登记重传，不受窗口限制；分片消息发到一半时推迟到最后一片之后发出，
+//! 它在对端被确认后才算生效（见 `rotation` 模块）。
+//! 本身不做 IO，时间与唤醒由连接任务驱动，控制段不受窗口限制。
+
+use crate::ack_frame::AckFrame;
+use crate::config::LinkConfig;
+use crate::congestion::{CongestionControl, LossKind};
+use crate::error::LinkError;
+use crate::observer::{Observation, Observations};
+use crate::retransmit::{Acked, BackoffPolicy, RetransmitQueue};
+use crate::rtt::RttEstimator;
+use crate::options::{Options, SegmentOption};
+use crate::pacing::Pacer;
+use crate::rate::DeliveryRate;
+use crate::segment::{Segment, SegmentType};
+use crate::seq::SeqNum;
+use crate::session::{Pending, SenderState};
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// 触发快速重传所需的重复确认次数
+pub const DUP_ACK_THRESHOLD: u32 = 3;
+
+/// 发送端统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SenderStats {
+    pub segments_sent: u64,         // 首次发出的数据段数（不含重传）
+    pub bytes_sent: u64,            // 发出的数据体字节数（含重传）
+    pub bytes_acked: u64,           // 被累计确认或 SACK 确认的数据体字节数
+    pub segments_retransmitted: u64,    // 超时、快速重传与 SACK 空洞重传的段数
+    pub duplicate_acks: u64,        // 累计确认点未推进、仍有在途数据时到达的确认数
+    pub ecn_reductions: u64,        // 因对端回送的 ECE 降窗的次数
+    pub expired: u64,               // TTL 到期、还没有全部被确认而被放弃的消息数
+}
+
+/// 单条消息的发送选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendOptions {
+    pub ordered: bool,  // 为 false 时对端完整收到即交付，不等之前的消息；仍然可靠，丢失时重传
+    pub ttl: Option<Duration>,  // 写入之后这么久还没有全部被确认时放弃这条消息，见模块文档
+    pub tag: Option<u32>,   // 随消息送达对端的不透明标签，对端以 `Connection::recv_msg_tagged` 取出
+}
+
+impl Default for SendOptions {
+    fn default() -> Self {
+        Self { ordered: true, ttl: None, tag: None }
+    }
+}
+
+impl SendOptions {
+    /// 有效期为 `ttl` 的有序消息
+    pub fn ttl(ttl: Duration) -> Self {
+        Self { ttl: Some(ttl), ..Self::default() }
+    }
+
+    /// 带标签 `tag` 的有序消息
+    pub fn tag(tag: u32) -> Self {
+        Self { tag: Some(tag), ..Self::default() }
+    }
+}
+
+// 设置了 TTL、可能还没有全部被确认的消息
+#[derive(Debug)]
+struct Expiring {
+    deadline: Instant,
+    sent: Option<(SeqNum, SeqNum)>, // 已发出的第一片与最后一片的序列号
+    complete: bool,             // 最后一片已经发出
+}
+
+/// `Sender::on_ack` 的处理结果
+#[derive(Debug, Clone, Default)]
+pub struct AckOutcome {
+    pub acked: Acked,
+    pub retransmit: Vec<Segment>,   // 需立即重发的段（重复确认触发的快速重传或 SACK 空洞）
+    pub transmit: Vec<Segment>,     // 确认到达后从合并缓冲交出的新数据段
+}
+
+/// 发送端状态
+#[derive(Debug)]
+pub struct Sender {
+    next_seq: SeqNum,
+    last_ack: SeqNum,           // 最近一次收到的累计确认值
+    dup_acks: u32,              // 累计确认点未推进的连续重复确认次数
+    recovery_point: Option<SeqNum>, // 丢失恢复期的结束点，累计确认越过前不再重复降窗
+    ecn_point: Option<SeqNum>,  // 上一次因 ECE 降窗时已发送的最大序列号，累计确认越过前忽略 ECE
+    cwr_pending: bool,          // 下一个新数据段需要携带 CWR 选项
+    queue: RetransmitQueue,
+    rtt: RttEstimator,
+    cc: Box<dyn CongestionControl>, // 拥塞控制算法，只通过 trait 访问
+    configured_window: usize,   // 本地配置窗口（段数）
+    peer_window: usize,         // 对端通告窗口（段数）
+    persist_deadline: Option<Instant>,  // 零窗口时下一次发送探测的时间
+    persist_probes: u32,        // 已发送的探测次数，用于退避
+    max_rto: Duration,
+    send_waker: Option<Waker>,  // 因窗口或发送队列已满而挂起的发送方
+    drain_waker: Option<Waker>, // 等待全部数据被确认的一方
+    fin_seq: Option<SeqNum>,    // 已发送的 FIN 的序列号
+    more_sent: bool,            // 最近发出的数据段带 more 选项，消息还没发完
+    deferred_conn_id: Option<u32>,  // 等待当前消息发完再发出的新连接 ID
+    conn_id_seq: Option<(SeqNum, u32)>, // 已发出、等待确认的 NewConnId 的序列号与 ID
+    fast_retransmits: u64,
+    timeouts: u64,              // 触发了重传的 RTO 超时事件数
+    stats: SenderStats,
+    nodelay: bool,              // 关闭小写入合并
+    mss: usize,
+    nagle_delay: Duration,
+    send_buffer: usize,         // 发送队列上限（字节）
+    queue_limit: usize,         // 连接的内存额度分给发送队列的上限（见 `budget` 模块），不限时为 usize::MAX
+    pending: VecDeque<(Bytes, SendOptions, bool, Option<Instant>)>,  // 等待合并或等待窗口的写入，每项对应一个段；第三项表示同一消息还有下一片，第四项是 TTL 到期的时间
+    pending_bytes: usize,       // 暂存写入编码后的总长度
+    nagle_deadline: Option<Instant>,    // 合并缓冲的强制发送时间
+    pacer: Option<Pacer>,       // 关闭发送节奏时为 None
+    pacing_deadline: Option<Instant>,   // 暂存的写入下一次可以放行的时间
+    expiring: VecDeque<Expiring>,   // 设置了 TTL 的消息，按写入顺序
+    observations: Observations,
+}
+
+impl Sender {
+    /// `initial_seq`：第一个数据段使用的序列号；拥塞控制算法按 `config.congestion` 构造
+    pub fn new(initial_seq: SeqNum, config: &LinkConfig) -> Self {
+        Self::with_congestion(initial_seq, config, config.congestion.build(config.initial_cwnd))
+    }
+
+    /// 使用自定义的拥塞控制算法
+    pub fn with_congestion(initial_seq: SeqNum, config: &LinkConfig, cc: Box<dyn CongestionControl>) -> Self {
+        let rtt = RttEstimator::new(config.min_rto, config.max_rto);
+        let policy = BackoffPolicy { max_rto: config.max_rto, max_retries: config.max_retries };
+        Self {
+            next_seq: initial_seq,
+            last_ack: initial_seq.wrapping_sub(1),
+            dup_acks: 0,
+            recovery_point: None,
+            ecn_point: None,
+            cwr_pending: false,
+            queue: RetransmitQueue::with_policy(rtt.rto(), policy).with_granularity(config.timer_granularity).with_capacity(config.send_window),
+            rtt,
+            cc,
+            configured_window: config.send_window,
+            peer_window: usize::MAX,
+            persist_deadline: None,
+            persist_probes: 0,
+            max_rto: config.max_rto,
+            send_waker: None,
+            drain_waker: None,
+            fin_seq: None,
+            more_sent: false,
+            deferred_conn_id: None,
+            conn_id_seq: None,
+            fast_retransmits: 0,
+            timeouts: 0,
+            stats: SenderStats::default(),
+            nodelay: config.nodelay,
+            mss: config.mss,
+            nagle_delay: config.nagle_delay,
+            send_buffer: config.send_buffer,
+            queue_limit: usize::MAX,
+            pending: VecDeque::new(),
+            pending_bytes: 0,
+            nagle_deadline: None,
+            pacer: config.pacing.then(|| Pacer::new(config.pacing_gain, config.batch_window)),
+            pacing_deadline: None,
+            expiring: VecDeque::new(),
+            observations: Observations::new(config),
+        }
+    }
+
+    /// 当前有效发送窗口（段数）
+    pub fn window(&self) -> usize {
+        self.cc.window().min(self.peer_window).min(self.configured_window)
+    }
+
+    /// 在途（已发送未确认）段数
+    pub fn in_flight(&self) -> usize {
+        self.queue.in_flight()
+    }
+
+    pub fn in_flight_bytes(&self) -> usize {
+        self.queue.in_flight_bytes()
+    }
+
+    /// 窗口是否还有空位
+    pub fn can_send(&self) -> bool {
+        self.in_flight() < self.window()
+    }
+
+    /// 调整本地配置窗口，变大时唤醒挂起的发送方
+    pub fn set_send_window(&mut self, window: usize) {
+        self.configured_window = window;
+        self.wake_if_open();
+    }
+
+    /// 更新对端通告的窗口
+    pub fn set_peer_window(&mut self, window: usize) {
+        self.peer_window = window;
+        self.wake_if_open();
+    }
+
+    /// 对端通告的窗口
+    pub fn peer_window(&self) -> usize {
+        self.peer_window
+    }
+
+    /// 处理对端的确认段，同 `on_ack_frame`
+    pub fn on_ack_segment(&mut self, segment: &Segment, now: Instant) -> AckOutcome {
+        self.on_ack_frame(&AckFrame::from(segment), now)
+    }
+
+    /// 处理对端的确认：先处理累计确认与 SACK 区间，再采用其中通告的窗口；
+    /// 窗口为 0 时启动坚持定时器，窗口打开时取消
+    pub fn on_ack_frame(&mut self, frame: &AckFrame, now: Instant) -> AckOutcome {
+        // 先于累计确认处理：越过 `ecn_point` 的那个确认仍属于已经响应过的拥塞信号
+        if frame.ece {
+            self.on_ecn();
+        }
+        // 异常的 SACK 数据体（只可能来自本地构造的段，解码时已校验）在转换时丢弃，按普通确认处理
+        let mut outcome = if frame.sack.is_empty() {
+            // 零窗口探测引出的确认累计点不变，不是丢包信号
+            let window_open = frame.window > 0 && self.peer_window > 0;
+            let acked = self.queue.on_ack(frame.ack);
+            self.process_ack(frame.ack, acked, now, window_open)
+        } else {
+            let acked = self.queue.on_ack_frame(frame);
+            self.process_ack(frame.ack, acked, now, false)
+        };
+        let window = usize::try_from(frame.window).unwrap_or(usize::MAX);
+        self.set_peer_window(window);
+        if window > 0 {
+            self.persist_deadline = None;
+            self.persist_probes = 0;
+        } else if self.persist_deadline.is_none() {
+            self.persist_deadline = Some(now + self.rtt.rto());
+            self.observations.push(Observation::WindowStalled);
+        }
+
+        // SACK 判定丢失的段在窗口允许的范围内重传
+        let budget = self.window().saturating_sub(self.in_flight());
+        let lost = self.queue.poll_lost(now, budget);
+        self.fast_retransmits += lost.len() as u64;
+        self.count_retransmits(&lost);
+        outcome.retransmit.extend(lost);
+        // 重传优先，剩余窗口再交出合并缓冲
+        outcome.transmit = self.flush_pending(now);
+        self.check_app_limited();
+        self.settle_expiring();
+        self.wake_if_drained();
+        outcome
+    }
+
+    /// 等待窗口出现空位；连接已失败时返回错误
+    pub fn poll_send_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), LinkError>> {
+        if let Some(e) = self.queue.failure() {
+            return Poll::Ready(Err(e.clone()));
+        }
+        if self.can_send() {
+            return Poll::Ready(Ok(()));
+        }
+        // 每次轮询都登记最新的 waker，旧的被替换
+        self.send_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// 等待发送队列容纳 `len` 字节；队列为空时总能容纳，超过上限的单个写入不会永远等待
+    pub fn poll_write_ready(&mut self, cx: &mut Context<'_>, len: usize) -> Poll<Result<(), LinkError>> {
+        if let Some(e) = self.queue.failure() {
+            return Poll::Ready(Err(e.clone()));
+        }
+        if self.has_room(len) {
+            return Poll::Ready(Ok(()));
+        }
+        self.send_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// 发送队列中的字节数：暂存（含等待窗口）的写入与已发送未确认的数据体
+    pub fn queued_bytes(&self) -> usize {
+        self.pending_bytes - self.pending.len() * Segment::FIXED_HEADER_LEN + self.queue.in_flight_bytes()
+    }
+
+    fn has_room(&self, len: usize) -> bool {
+        let queued = self.queued_bytes();
+        queued == 0 || queued + len <= self.queue_capacity()
+    }
+
+    // 发送队列的有效上限：`send_buffer` 与内存额度中较小的一个
+    fn queue_capacity(&self) -> usize {
+        self.send_buffer.min(self.queue_limit)
+    }
+
+    /// 按连接的内存额度限制发送队列（见 `budget` 模块）：至多 `limit` 字节，`usize::MAX` 时不限；放宽时唤醒挂起的发送方
+    pub fn set_queue_limit(&mut self, limit: usize) {
+        if self.queue_limit != limit {
+            self.queue_limit = limit;
+            self.wake_if_open();
+        }
+    }
+
+    /// 为数据分配序列号并登记到重传队列，返回待发送的段。
+    /// 窗口已满时返回 `WouldBlock`，调用方应先等待 `poll_send_ready`。
+    pub fn send(&mut self, data: Bytes, now: Instant) -> Result<Segment, LinkError> {
+        self.send_with(data, SendOptions::default(), now)
+    }
+
+    /// 以 `options` 发送一个数据段，其余同 `send`
+    pub fn send_with(&mut self, data: Bytes, send: SendOptions, now: Instant) -> Result<Segment, LinkError> {
+        if let Some(e) = self.queue.failure() {
+            return Err(e.clone());
+        }
+        if !self.can_send() {
+            return Err(LinkError::WouldBlock);
+        }
+        self.track(send, now);
+        self.send_fragment(data, send, false, now)
+    }
+
+    // 登记设置了 TTL 的消息，返回它到期的时间
+    fn track(&mut self, options: SendOptions, now: Instant) -> Option<Instant> {
+        let deadline = now + options.ttl?;
+        self.expiring.push_back(Expiring { deadline, sent: None, complete: false });
+        Some(deadline)
+    }
+
+    // `more`：这是一条消息中不是最后一片的段
+    fn send_fragment(&mut self, data: Bytes, send: SendOptions, more: bool, now: Instant) -> Result<Segment, LinkError> {
+        if let Some(e) = self.queue.failure() {
+            return Err(e.clone());
+        }
+        if !self.can_send() {
+            return Err(LinkError::WouldBlock);
+        }
+
+        let mut options = Options::new();
+        if self.cwr_pending {
+            options = options.with(SegmentOption::Cwr).expect("four empty options fit");
+        }
+        if self.in_flight() + 1 >= self.window() {
+            options = options.with(SegmentOption::AckNow).expect("four empty options fit");
+        }
+        if !send.ordered {
+            options = options.with(SegmentOption::Unordered).expect("four empty options fit");
+        }
+        if more {
+            options = options.with(SegmentOption::More).expect("four empty options fit");
+        }
+        if let Some(tag) = send.tag {
+            options = options.with(SegmentOption::Tag(tag)).expect("four empty options and a tag fit");
+        }
+        let builder = Segment::builder(SegmentType::Data).data_seq(self.next_seq).payload(data).options(options);
+        let segment = builder.build().expect("plain data segment is always valid");
+        self.queue.on_send(segment.clone(), now)?;
+        if let Some(pacer) = &mut self.pacer {
+            pacer.on_send(segment.encoded_len(), now);
+        }
+        // 各片按写入顺序发出：这一片属于第一条还没发完的消息
+        if send.ttl.is_some()
+            && let Some(message) = self.expiring.iter_mut().find(|message| !message.complete)
+        {
+            let first = message.sent.map_or(self.next_seq, |(first, _)| first);
+            message.sent = Some((first, self.next_seq));
+            message.complete = !more;
+        }
+        self.cwr_pending = false;
+        self.more_sent = more;
+        self.next_seq = self.next_seq.wrapping_add(1);
+        self.stats.segments_sent += 1;
+        self.stats.bytes_sent += segment.data().len() as u64;
+        Ok(segment)
+    }
+
+    /// 带小写入合并的发送：没有在途数据、关闭了合并或暂存达到 MSS 时返回窗口允许发送的段
+    /// （用 `segment::pack_datagrams` 打包），否则暂存写入并返回空列表，之后由确认到达（`AckOutcome::transmit`）或 `on_timeout` 交出。
+    /// 发送队列已满时返回 `WouldBlock`。
+    pub fn write(&mut self, data: Bytes, now: Instant) -> Result<Vec<Segment>, LinkError> {
+        self.write_with(data, SendOptions::default(), now)
+    }
+
+    /// 以 `options` 写入一条消息，其余同 `write`
+    pub fn write_with(&mut self, data: Bytes, options: SendOptions, now: Instant) -> Result<Vec<Segment>, LinkError> {
+        if let Some(e) = self.queue.failure() {
+            return Err(e.clone());
+        }
+        if !self.has_room(data.len()) {
+            return Err(LinkError::WouldBlock);
+        }
+        let expires = self.track(options, now);
+        self.enqueue(data, options, false, expires, now)
+    }
+
+    /// 把消息按 `fragment` 字节切片后一次写入（空消息是一个空段），其余同 `write`；
+    /// 发送队列按整条消息检查，队列为空时总能写入
+    pub fn write_message(&mut self, data: Bytes, fragment: usize, now: Instant) -> Result<Vec<Segment>, LinkError> {
+        self.write_message_with(data, fragment, SendOptions::default(), now)
+    }
+
+    /// 以 `options` 分片写入一条消息，其余同 `write_message`；分片消息总是按序交付，`ordered` 被忽略，
+    /// TTL 到期时整条消息一起放弃
+    pub fn write_message_with(&mut self, mut data: Bytes, fragment: usize, options: SendOptions, now: Instant) -> Result<Vec<Segment>, LinkError> {
+        if let Some(e) = self.queue.failure() {
+            return Err(e.clone());
+        }
+        if !self.has_room(data.len()) {
+            return Err(LinkError::WouldBlock);
+        }
+        let mut options = SendOptions { ordered: true, ..options };
+        let expires = self.track(options, now);
+        let mut ready = Vec::new();
+        while data.len() > fragment {
+            let piece = data.split_to(fragment);
+            ready.extend(self.enqueue(piece, options, true, expires, now)?);
+            // 标签只随第一片
+            options.tag = None;
+        }
+        ready.extend(self.enqueue(data, options, false, expires, now)?);
+        Ok(ready)
+    }
+
+    fn enqueue(&mut self, data: Bytes, options: SendOptions, more: bool, expires: Option<Instant>, now: Instant) -> Result<Vec<Segment>, LinkError> {
+        // 加入本次写入会超过 MSS 时，先交出已暂存的部分，保证合并出的数据报不超过 MSS
+        let len = Segment::FIXED_HEADER_LEN + data.len();
+        let mut ready = Vec::new();
+        if !self.pending.is_empty() && self.pending_bytes + len > self.mss {
+            ready = self.flush(now)?;
+        }
+        self.pending_bytes += len;
+        self.pending.push_back((data, options, more, expires));
+        if self.nodelay || self.in_flight() == 0 || self.pending_bytes >= self.mss {
+            ready.extend(self.flush(now)?);
+        } else {
+            self.nagle_deadline.get_or_insert(now + self.nagle_delay);
+        }
+        Ok(ready)
+    }
+
+    /// 在窗口与发送节奏允许的范围内交出暂存的写入；窗口不足时剩余部分等待下一次确认，节奏不允许时等待节奏定时器
+    pub fn flush(&mut self, now: Instant) -> Result<Vec<Segment>, LinkError> {
+        self.nagle_deadline = None;
+        self.pacing_deadline = None;
+        if let Some(pacer) = &mut self.pacer {
+            pacer.set_rate(self.cc.window(), self.mss, self.rtt.srtt(), now);
+        }
+        let mut segments = Vec::new();
+        while self.can_send() && !self.pending.is_empty() {
+            if let Some(pacer) = &mut self.pacer
+                && !pacer.can_send(now)
+            {
+                self.pacing_deadline = pacer.next_release(now);
+                break;
+            }
+            let (data, options, more, _) = self.pending.pop_front().expect("pending is not empty");
+            self.pending_bytes -= Segment::FIXED_HEADER_LEN + data.len();
+            segments.push(self.send_fragment(data, options, more, now)?);
+        }
+        segments.extend(self.release_conn_id(now));
+        Ok(segments)
+    }
+
+    // 确认到达时交出暂存的写入；连接未失败时 flush 不会出错
+    fn flush_pending(&mut self, now: Instant) -> Vec<Segment> {
+        if self.pending.is_empty() {
+            return Vec::new();
+        }
+        self.flush(now).unwrap_or_default()
+    }
+
+    /// 为 FIN 分配序列号并登记到重传队列；暂存的写入必须先全部交出，否则返回 `WouldBlock`
+    pub fn fin(&mut self, now: Instant) -> Result<Segment, LinkError> {
+        if let Some(e) = self.queue.failure() {
+            return Err(e.clone());
+        }
+        if !self.pending.is_empty() {
+            return Err(LinkError::WouldBlock);
+        }
+        let segment = Segment::builder(SegmentType::Fin)
+            .data_seq(self.next_seq)
+            .build()
+            .expect("fin segment is always valid");
+        self.queue.on_send(segment.clone(), now)?;
+        self.fin_seq = Some(self.next_seq);
+        self.next_seq = self.next_seq.wrapping_add(1);
+        Ok(segment)
+    }
+
+    /// 发出携带新连接 ID 的 NewConnId 段：分配序列号并登记到重传队列，不受窗口限制。
+    /// 分片消息发到一半时推迟到最后一片之后，这时返回空列表；上一个 NewConnId 还没被确认时返回 `WouldBlock`
+    pub fn issue_conn_id(&mut self, conn_id: u32, now: Instant) -> Result<Vec<Segment>, LinkError> {
+        if let Some(e) = self.queue.failure() {
+            return Err(e.clone());
+        }
+        if self.deferred_conn_id.is_some() || self.conn_id_seq.is_some() {
+            return Err(LinkError::WouldBlock);
+        }
+        self.deferred_conn_id = Some(conn_id);
+        Ok(self.release_conn_id(now).into_iter().collect())
+    }
+
+    // 当前消息已经发完时发出推迟的 NewConnId
+    fn release_conn_id(&mut self, now: Instant) -> Option<Segment> {
+        if self.more_sent {
+            return None;
+        }
+        let conn_id = self.deferred_conn_id.take()?;
+        let segment = Segment::new_conn_id(self.next_seq, conn_id);
+        // 队列已失败时不再发出，失败由之后的 `poll_expired` 报告
+        self.queue.on_send(segment.clone(), now).ok()?;
+        self.conn_id_seq = Some((self.next_seq, conn_id));
+        self.next_seq = self.next_seq.wrapping_add(1);
+        Some(segment)
+    }
+
+    /// 已发出的 NewConnId 被对端确认时取出其中的 ID，每个只返回一次
+    pub fn take_acked_conn_id(&mut self) -> Option<u32> {
+        let (seq, conn_id) = self.conn_id_seq?;
+        if self.queue.contains(seq) {
+            return None;
+        }
+        self.conn_id_seq = None;
+        Some(conn_id)
+    }
+
+    /// 是否已发送 FIN
+    pub fn fin_sent(&self) -> bool {
+        self.fin_seq.is_some()
+    }
+
+    /// 已发送的 FIN 是否已被确认
+    pub fn fin_acked(&self) -> bool {
+        self.fin_seq.is_some_and(|seq| !self.queue.contains(seq))
+    }
+
+    /// 全部写入都已发出并被确认（暂存与在途都为空）
+    pub fn is_drained(&self) -> bool {
+        self.pending.is_empty() && self.queue.is_empty()
+    }
+
+    /// 等待全部写入被确认；连接已失败时返回错误
+    pub fn poll_drained(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), LinkError>> {
+        if let Some(e) = self.queue.failure() {
+            return Poll::Ready(Err(e.clone()));
+        }
+        if self.is_drained() {
+            return Poll::Ready(Ok(()));
+        }
+        self.drain_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// 开关小写入合并；打开 nodelay 时立即交出暂存的写入
+    pub fn set_nodelay(&mut self, nodelay: bool, now: Instant) -> Result<Vec<Segment>, LinkError> {
+        self.nodelay = nodelay;
+        if nodelay { self.flush(now) } else { Ok(Vec::new()) }
+    }
+
+    pub fn nodelay(&self) -> bool {
+        self.nodelay
+    }
+
+    /// 开关发送节奏（速率按 `gain` 与 `batch_window`，见 `pacing` 模块）；关闭时立即交出等待节奏放行的写入，
+    /// 打开时从还没有速率的令牌桶开始，下一次交出写入时按当前的 cwnd/SRTT 限速
+    pub fn set_pacing(&mut self, pacing: bool, gain: f64, batch_window: Duration, now: Instant) -> Result<Vec<Segment>, LinkError> {
+        if pacing == self.pacer.is_some() {
+            return Ok(Vec::new());
+        }
+        self.pacer = pacing.then(|| Pacer::new(gain, batch_window));
+        if self.pacing_deadline.is_some() { self.flush(now) } else { Ok(Vec::new()) }
+    }
+
+    pub fn pacing(&self) -> bool {
+        self.pacer.is_some()
+    }
+
+    /// 调整合并的目标大小，路径 MTU 探测改变有效 MSS 时调用；已暂存的写入在下一次写入或确认时按新的大小交出
+    pub fn set_mss(&mut self, mss: usize) {
+        self.mss = mss;
+    }
+
+    pub fn mss(&self) -> usize {
+        self.mss
+    }
+
+    /// 有效 MSS 降低之后（见 `ConnectionCore::on_datagram_too_large`）把暂存写入中超过 `fragment` 字节的切开，
+    /// 除最后一片外带 more 选项，由对端拼回一条消息
+    pub fn refragment(&mut self, fragment: usize) {
+        if self.pending.iter().all(|(data, ..)| data.len() <= fragment) {
+            return;
+        }
+        let mut writes = Vec::with_capacity(self.pending.len());
+        for write in self.pending.drain(..) {
+            split(write, fragment, &mut writes);
+        }
+        self.pending.extend(writes);
+        self.pending_bytes = self.pending.iter().map(|(data, ..)| Segment::FIXED_HEADER_LEN + data.len()).sum();
+    }
+
+    /// 从 `first` 起的段其实没能发出（本机报告数据报过大，见 `ConnectionCore::on_datagram_too_large`）：撤回它们，
+    /// 各条消息的数据按 `fragment` 字节重新分片（除最后一片外带 more 选项），放回暂存写入的最前面，从 `first` 起重新编号，
+    /// 返回窗口允许发送的段。其中有重传过、被 SACK 的段或不是数据段（FIN、NewConnId、Skip），或者有设置了 TTL 的消息时
+    /// 什么也不做，返回 None：这些段照常由重传原样发出
+    pub fn rewind(&mut self, first: SeqNum, fragment: usize, now: Instant) -> Option<Vec<Segment>> {
+        if self.queue.failure().is_some() || !self.expiring.is_empty() {
+            return None;
+        }
+        if self.queue.unacknowledged().any(|segment| !segment.seq().is_before(first) && segment.segment_type() != SegmentType::Data) {
+            return None;
+        }
+        let previous = self.queue.unacknowledged().take_while(|segment| segment.seq().is_before(first)).last().filter(|segment| segment.seq() == first.wrapping_sub(1));
+        let more_sent = previous.is_some_and(|segment| segment.options().more());
+        let withdrawn = self.queue.withdraw(first)?;
+        if withdrawn.is_empty() {
+            return None;
+        }
+
+        // 逐条消息拼回原来的数据（前一段带 more 的属于同一条消息），再按新的大小切开
+        let mut writes = Vec::new();
+        let mut message: Vec<Bytes> = Vec::new();
+        let mut tag = None;
+        for segment in &withdrawn {
+            self.cwr_pending |= segment.options().cwr();
+            self.stats.segments_sent -= 1;
+            self.stats.bytes_sent -= segment.data().len() as u64;
+            // 撤回的第一段可能是之前已发出的消息的后续分片，不带标签
+            if message.is_empty() {
+                tag = segment.options().tag();
+            }
+            message.push(segment.data().clone());
+            let more = segment.options().more();
+            if more && segment.seq() != withdrawn[withdrawn.len() - 1].seq() {
+                continue;
+            }
+            let options = SendOptions { ordered: !segment.options().unordered(), ttl: None, tag };
+            let data = match message.len() {
+                1 => message.pop().expect("one piece"),
+                _ => Bytes::from(std::mem::take(&mut message).concat()),
+            };
+            message.clear();
+            split((data, options, more, None), fragment, &mut writes);
+        }
+        for write in writes.into_iter().rev() {
+            self.pending_bytes += Segment::FIXED_HEADER_LEN + write.0.len();
+            self.pending.push_front(write);
+        }
+        self.next_seq = first;
+        self.more_sent = more_sent;
+        Some(self.flush(now).unwrap_or_default())
+    }
+
+    /// 合并缓冲中暂存的写入数
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// 处理累计确认：移除已确认段、按 Karn 算法采样 RTT、打开窗口；
+    /// 累计点未推进时计数重复确认，达到阈值时返回需快速重传的段
+    pub fn on_ack(&mut self, ack: SeqNum, now: Instant) -> AckOutcome {
+        let acked = self.queue.on_ack(ack);
+        let mut outcome = self.process_ack(ack, acked, now, true);
+        outcome.transmit = self.flush_pending(now);
+        self.check_app_limited();
+        self.settle_expiring();
+        self.wake_if_drained();
+        outcome
+    }
+
+    // 确认的公共处理：RTT 采样、重复确认计数、拥塞控制与唤醒
+    fn process_ack(&mut self, ack: SeqNum, acked: Acked, now: Instant, count_dup_acks: bool) -> AckOutcome {
+        let mut rtt_sample = None;
+        if let Some(sent_at) = acked.newest_sent_at {
+            let sample = now.saturating_duration_since(sent_at);
+            if self.rtt.on_segment_acked(sample, acked.newest_retransmitted, false) {
+                self.queue.set_rto(self.rtt.rto());
+                rtt_sample = Some(sample);
+                self.observations.push(Observation::RttSample(sample));
+            }
+        }
+        if let Some(sample) = self.queue.sample_rate(&acked, now) {
+            self.cc.on_rate_sample(sample);
+        }
+
+        self.stats.bytes_acked += acked.bytes as u64;
+        let mut retransmit = Vec::new();
+        if acked.advanced {
+            // 累计点推进：重新计数，避免乱序造成的误触发
+            self.last_ack = ack;
+            self.dup_acks = 0;
+            if self.recovery_point.is_some_and(|point| !point.is_after(ack)) {
+                self.recovery_point = None;
+            }
+            if self.ecn_point.is_some_and(|point| !point.is_after(ack)) {
+                self.ecn_point = None;
+            }
+        } else if ack == self.last_ack && !self.queue.is_empty() {
+            self.stats.duplicate_acks += 1;
+            if count_dup_acks {
+                self.dup_acks += 1;
+                if self.dup_acks == DUP_ACK_THRESHOLD
+                    && let Some(segment) = self.queue.retransmit_oldest(now)
+                {
+                    retransmit.push(segment);
+                    self.fast_retransmits += 1;
+                    self.on_loss();
+                }
+            }
+        }
+        self.count_retransmits(&retransmit);
+        if acked.lost > 0 {
+            self.on_loss();
+        }
+        if acked.segments > 0 {
+            self.cc.on_ack(acked.segments, rtt_sample);
+            self.wake_if_open();
+        }
+        AckOutcome { acked, retransmit, transmit: Vec::new() }
+    }
+
+    // 没有等待发送的数据、窗口还有空位：发送速度受应用限制
+    fn check_app_limited(&mut self) {
+        if self.pending.is_empty() && self.can_send() {
+            self.queue.on_app_limited();
+        }
+    }
+
+    fn count_retransmits(&mut self, segments: &[Segment]) {
+        self.stats.segments_retransmitted += segments.len() as u64;
+        self.stats.bytes_sent += segments.iter().map(|s| s.data().len() as u64).sum::<u64>();
+        for segment in segments {
+            let attempt = self.queue.retransmits(segment.seq()).unwrap_or(1);
+            self.observations.push(Observation::Retransmit { seq: segment.seq(), attempt });
+        }
+    }
+
+    // 检测到丢包：每个恢复期只通知一次拥塞控制
+    fn on_loss(&mut self) {
+        if self.recovery_point.is_none() {
+            self.cc.on_loss(LossKind::FastRetransmit);
+            self.recovery_point = Some(self.next_seq.wrapping_sub(1));
+        }
+    }
+
+    // 对端回送了拥塞标记：每个窗口最多降窗一次，丢失恢复已经降过窗时只回应 CWR
+    fn on_ecn(&mut self) {
+        if self.ecn_point.is_some() {
+            return;
+        }
+        if self.recovery_point.is_none() {
+            self.cc.on_ecn();
+            self.stats.ecn_reductions += 1;
+        }
+        self.ecn_point = Some(self.next_seq.wrapping_sub(1));
+        self.cwr_pending = true;
+    }
+
+    /// 已交给发送端、尚未被对端累计确认的数据，按写入顺序：重传队列中的数据段在前，暂存的写入在后。
+    /// 被 SACK 的段也在其中，对端没有把它们交付给应用之前连接就可能失败；分片消息的各片分别列出
+    pub fn unacknowledged(&self) -> Vec<Bytes> {
+        let sent = self.queue.unacknowledged().filter(|segment| segment.segment_type() == SegmentType::Data);
+        sent.map(|segment| segment.data().clone()).chain(self.pending.iter().map(|(data, ..)| data.clone())).collect()
+    }
+
+    /// 导出可迁移的状态（见 `session` 模块）；调用方已确认没有未确认的 FIN 与 NewConnId
+    pub(crate) fn export(&self) -> SenderState {
+        SenderState {
+            next_seq: self.next_seq,
+            last_ack: self.last_ack,
+            peer_window: self.peer_window,
+            more_sent: self.more_sent,
+            in_flight: self.queue.unacknowledged().cloned().collect(),
+            pending: self.pending.iter().map(|(data, options, more, _)| Pending { data: data.clone(), ordered: options.ordered, more: *more, tag: options.tag }).collect(),
+        }
+    }
+
+    /// 从导出的状态重建发送端：已发送未确认的段从 `now` 起重新计时，RTO 到期后重传；拥塞控制与 RTT 估计从头开始
+    pub(crate) fn restore(state: SenderState, config: &LinkConfig, now: Instant) -> Self {
+        let mut sender = Self::new(state.next_seq, config);
+        sender.last_ack = state.last_ack;
+        sender.peer_window = state.peer_window;
+        sender.more_sent = state.more_sent;
+        for segment in state.in_flight {
+            sender.queue.on_send(segment, now).expect("a fresh retransmit queue has not failed");
+        }
+        sender.pending = state.pending.into_iter().map(|pending| (pending.data, SendOptions { ordered: pending.ordered, ttl: None, tag: pending.tag }, pending.more, None)).collect();
+        sender.pending_bytes = sender.pending.iter().map(|(data, ..)| Segment::FIXED_HEADER_LEN + data.len()).sum();
+        sender
+    }
+
+    /// 对端请求从 `from`（None 为最新位置）开始接收：放弃之前还没被累计确认的数据，返回开始交付的序列号与要发出的段
+    /// （让对端跳过放弃部分的 Skip，以及不必再等当前消息发完的 NewConnId）。起点早于仍保留的最早数据时拒绝，
+    /// 错误中是仍保留的最早序列号；已被累计确认的数据不再保留。起点落在分片消息中间时推到这条消息之后，越过下一个
+    /// 待分配的序列号时按最新位置处理；已发出、未被确认的 FIN 与 NewConnId 不放弃，开始交付的位置最晚是它们。
+    /// 只有从下一个待分配的序列号开始交付时才丢弃暂存的写入：最新位置丢弃全部，否则只丢弃发到一半的消息剩下的分片
+    pub fn resync(&mut self, from: Option<SeqNum>, now: Instant) -> Result<(SeqNum, Vec<Segment>), SeqNum> {
+        let oldest = self.last_ack.wrapping_add(1);
+        if from.is_some_and(|from| from.is_before(oldest)) {
+            return Err(oldest);
+        }
+        let latest = from.is_none_or(|from| from.is_after(self.next_seq));
+        let mut head = if latest { self.next_seq } else { from.expect("checked by latest") };
+        // 重传队列按序列号排列：前一段还有下一片时逐段后移
+        for segment in self.queue.unacknowledged() {
+            if head != self.next_seq && segment.seq() == head.wrapping_sub(1) && segment.options().more() {
+                head = head.wrapping_add(1);
+            }
+        }
+        let pinned = [self.fin_seq, self.conn_id_seq.map(|(seq, _)| seq)].into_iter().flatten().filter(|&seq| self.queue.contains(seq));
+        for seq in pinned {
+            if seq.is_before(head) {
+                head = seq;
+            }
+        }
+
+        let mut segments = Vec::new();
+        if head.is_after(oldest)
+            && let Some(lowest) = self.queue.abandon(oldest, head.wrapping_sub(1))
+        {
+            let skip = Segment::skip(lowest, head.wrapping_sub(1));
+            if self.queue.on_send(skip.clone(), now).is_ok() {
+                segments.push(skip);
+            }
+        }
+        if head == self.next_seq {
+            let dropped = match (latest, self.more_sent) {
+                (true, _) => self.pending.len(),
+                (false, true) => self.pending.iter().position(|(_, _, more, _)| !more).map_or(self.pending.len(), |last| last + 1),
+                (false, false) => 0,
+            };
+            self.pending.drain(..dropped);
+            self.pending_bytes = self.pending.iter().map(|(data, ..)| Segment::FIXED_HEADER_LEN + data.len()).sum();
+            self.more_sent = false;
+            segments.extend(self.release_conn_id(now));
+        }
+        let queued = !self.pending.is_empty();
+        self.expiring.retain(|message| message.sent.map_or(queued, |(_, last)| !last.is_before(head)));
+        self.wake_if_open();
+        self.wake_if_drained();
+        Ok((head, segments))
+    }
+
+    /// 连接被判定失败（如保活超时）：之后的发送都返回该错误，并唤醒挂起的发送方
+    pub fn abort(&mut self, error: LinkError) {
+        self.queue.fail(error);
+        self.wake_all();
+    }
+
+    /// 当前的连续重复确认次数
+    pub fn dup_acks(&self) -> u32 {
+        self.dup_acks
+    }
+
+    /// 处理重传、坚持、合并与节奏定时器到期，返回需要发送的段（重传段、零窗口探测及合并的新数据）；
+    /// 重试耗尽时返回错误并唤醒挂起的发送方
+    pub fn on_timeout(&mut self, now: Instant) -> Result<Vec<Segment>, LinkError> {
+        // 先放弃过期的消息，它们的段不必再重传
+        let skips = self.expire(now);
+        match self.queue.poll_expired(now) {
+            Ok(mut resend) => {
+                resend.extend(skips);
+                if !resend.is_empty() {
+                    self.rtt.on_timeout();
+                    self.cc.on_rto();
+                    self.timeouts += 1;
+                    self.observations.push(Observation::RtoExpired { backoff: self.queue.backoff_rto() });
+                    self.count_retransmits(&resend);
+                }
+                if let Some(probe) = self.poll_persist(now) {
+                    resend.push(probe);
+                }
+                if [self.nagle_deadline, self.pacing_deadline].into_iter().flatten().any(|deadline| now >= deadline) {
+                    resend.extend(self.flush(now)?);
+                }
+                Ok(resend)
+            }
+            Err(e) => {
+                self.wake_all();
+                Err(e)
+            }
+        }
+    }
+
+    /// 下一次需要调用 `on_timeout` 的时间
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let expiry = self.expiring.iter().map(|message| message.deadline).min();
+        [self.queue.next_deadline(), self.persist_deadline, self.nagle_deadline, self.pacing_deadline, expiry].into_iter().flatten().min()
+    }
+
+    // TTL 到期的消息：丢弃还没发出的部分，已发出、未被确认的部分换成一个 Skip 段；返回要发出的 Skip 段
+    fn expire(&mut self, now: Instant) -> Vec<Segment> {
+        if !self.expiring.iter().any(|message| message.deadline <= now) {
+            return Vec::new();
+        }
+        let queued = self.pending.len();
+        self.pending.retain(|(.., expires)| expires.is_none_or(|deadline| now < deadline));
+        if self.pending.len() < queued {
+            self.pending_bytes = self.pending.iter().map(|(data, ..)| Segment::FIXED_HEADER_LEN + data.len()).sum();
+        }
+        let (expired, live): (Vec<_>, Vec<_>) = std::mem::take(&mut self.expiring).into_iter().partition(|message| message.deadline <= now);
+        self.expiring = live.into();
+
+        let mut skips = Vec::new();
+        for message in expired {
+            // 放弃了发到一半的消息，推迟的 NewConnId 不必再等它的最后一片
+            if message.sent.is_some() && !message.complete {
+                self.more_sent = false;
+            }
+            let mut abandoned = !message.complete;
+            if let Some((first, last)) = message.sent
+                && let Some(lowest) = self.queue.abandon(first, last)
+            {
+                let skip = Segment::skip(lowest, last);
+                // 队列已失败时不再发出，失败由之后的 `poll_expired` 报告
+                if self.queue.on_send(skip.clone(), now).is_ok() {
+                    skips.push(skip);
+                }
+                abandoned = true;
+            }
+            if abandoned {
+                self.stats.expired += 1;
+            }
+        }
+        skips.extend(self.release_conn_id(now));
+        self.wake_if_open();
+        self.wake_if_drained();
+        skips
+    }
+
+    // 最早的几条设置了 TTL 的消息已经全部被累计确认时不再跟踪
+    fn settle_expiring(&mut self) {
+        while self.expiring.front().is_some_and(|message| message.complete && message.sent.is_some_and(|(_, last)| !last.is_after(self.last_ack))) {
+            self.expiring.pop_front();
+        }
+    }
+
+    // 坚持定时器到期时构造零窗口探测段，并按指数退避安排下一次探测
+    fn poll_persist(&mut self, now: Instant) -> Option<Segment> {
+        let deadline = self.persist_deadline?;
+        if now < deadline {
+            return None;
+        }
+        self.persist_probes += 1;
+        let factor = 1u32.checked_shl(self.persist_probes).unwrap_or(u32::MAX);
+        let interval = self.rtt.rto().saturating_mul(factor).min(self.max_rto);
+        self.persist_deadline = Some(now + interval);
+
+        // 已确认的序列号在对端是重复段，会被立即确认并携带当前窗口
+        let probe = Segment::builder(SegmentType::Data)
+            .data_seq(self.last_ack)
+            .build()
+            .expect("empty data segment is always valid");
+        Some(probe)
+    }
+
+    /// 取出记录下来、等待交给观察者的事件
+    pub(crate) fn take_observations(&mut self) -> Vec<Observation> {
+        self.observations.take()
+    }
+
+    /// 下一个待分配的序列号
+    pub fn next_seq(&self) -> SeqNum {
+        self.next_seq
+    }
+
+    pub fn rtt(&self) -> &RttEstimator {
+        &self.rtt
+    }
+
+    /// 投递速率与带宽估计
+    pub fn delivery_rate(&self) -> &DeliveryRate {
+        self.queue.delivery_rate()
+    }
+
+    pub fn congestion(&self) -> &dyn CongestionControl {
+        self.cc.as_ref()
+    }
+
+    /// 快速重传次数
+    pub fn fast_retransmits(&self) -> u64 {
+        self.fast_retransmits
+    }
+
+    /// 触发重传的 RTO 超时次数
+    pub fn timeouts(&self) -> u64 {
+        self.timeouts
+    }
+
+    pub fn stats(&self) -> SenderStats {
+        self.stats
+    }
+
+    fn wake_if_drained(&mut self) {
+        if self.is_drained()
+            && let Some(waker) = self.drain_waker.take()
+        {
+            waker.wake();
+        }
+    }
+
+    // 连接失败：所有等待方都要观察到错误
+    fn wake_all(&mut self) {
+        for waker in [self.send_waker.take(), self.drain_waker.take()].into_iter().flatten() {
+            waker.wake();
+        }
+    }
+
+    fn wake_if_open(&mut self) {
+        if (self.can_send() || self.queued_bytes() < self.queue_capacity())
+            && let Some(waker) = self.send_waker.take()
+        {
+            waker.wake();
+        }
+    }
+}
+
+// 把一项暂存写入按 `fragment` 字节切开放进 `writes`；切开的消息同分片消息一样按序交付
+fn split((mut data, options, more, expires): (Bytes, SendOptions, bool, Option<Instant>), fragment: usize, writes: &mut Vec<(Bytes, SendOptions, bool, Option<Instant>)>) {
+    if data.len() <= fragment {
+        writes.push((data, options, more, expires));
+        return;
+    }
+    let mut options = SendOptions { ordered: true, ..options };
+    while data.len() > fragment {
+        writes.push((data.split_to(fragment), options, true, expires));
+        options.tag = None;
+    }
+    writes.push((data, options, more, expires));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::congestion::CongestionAlgorithm;
+    use crate::receiver::Receiver;
+    use crate::segment::SegmentFlags;
+    use crate::stats::ConnectionStats;
+    use std::future::poll_fn;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    fn sender(window: usize) -> Sender {
+        Sender::new(SeqNum::new(1), &LinkConfig { send_window: window, ..LinkConfig::default() })
+    }
+
+    #[test]
+    fn test_window_filling_segment_requests_an_immediate_ack() {
+        let now = Instant::now();
+        let mut sender = sender(3);
+        let requested: Vec<_> = (0..3).map(|_| sender.send(Bytes::from_static(b"x"), now).unwrap().options().ack_now()).collect();
+        assert_eq!(requested, vec![false, false, true]);
+    }
+
+    #[test]
+    fn test_window_bounds_in_flight() {
+        let now = Instant::now();
+        let mut sender = sender(3);
+        for seq in 1..=3 {
+            assert_eq!(sender.send(Bytes::from_static(b"x"), now).unwrap().seq().get(), seq);
+        }
+        assert_eq!(sender.in_flight(), 3);
+        assert!(matches!(sender.send(Bytes::from_static(b"x"), now), Err(LinkError::WouldBlock)));
+
+        // 对端通告的窗口更小时取较小值
+        sender.on_ack(SeqNum::new(3), now);
+        sender.set_peer_window(1);
+        assert_eq!(sender.window(), 1);
+        sender.send(Bytes::new(), now).unwrap();
+        assert!(!sender.can_send());
+    }
+
+    #[tokio::test]
+    async fn test_full_window_parks_until_ack() {
+        let now = Instant::now();
+        let sender = Arc::new(Mutex::new(sender(2)));
+        for _ in 0..2 {
+            sender.lock().unwrap().send(Bytes::from_static(b"data"), now).unwrap();
+        }
+
+        // 第三次发送需要等待窗口
+        let waiting = {
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                poll_fn(|cx| sender.lock().unwrap().poll_send_ready(cx)).await.unwrap();
+                sender.lock().unwrap().send(Bytes::from_static(b"data"), Instant::now()).unwrap().seq().get()
+            })
+        };
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+
+        // 确认第一个段，窗口打开，挂起的发送完成
+        sender.lock().unwrap().on_ack(SeqNum::new(1), now);
+        let seq = tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap();
+        assert_eq!(seq, 3);
+        assert_eq!(sender.lock().unwrap().in_flight(), 2);
+    }
+
+    #[test]
+    fn test_rtt_sampled_from_acks() {
+        let t0 = Instant::now();
+        let mut sender = sender(8);
+        sender.send(Bytes::from_static(b"a"), t0).unwrap();
+
+        sender.on_ack(SeqNum::new(1), t0 + Duration::from_millis(40));
+        assert_eq!(sender.rtt().srtt(), Some(Duration::from_millis(40)));
+    }
+
+    #[test]
+    fn test_fast_retransmit_after_three_dup_acks() {
+        let t0 = Instant::now();
+        let config = LinkConfig::default();
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        let mut receiver = Receiver::new(SeqNum::new(1), &config);
+
+        // 模拟传输：发送 1..=6，丢弃 2，其余按序送达；收集接收端立即发出的确认
+        let mut acks = Vec::new();
+        for _ in 1..=6 {
+            let segment = sender.send(Bytes::from_static(b"data"), t0).unwrap();
+            if segment.seq().get() == 2 {
+                continue;
+            }
+            acks.extend(receiver.on_data(&segment, t0).ack);
+        }
+        // 1 的确认被延迟后由乱序段 3 捎带，之后 4、5、6 各产生一个重复确认
+        assert_eq!(acks.iter().map(|a| a.ack.get()).collect::<Vec<_>>(), vec![1, 1, 1, 1]);
+
+        let now = t0 + Duration::from_millis(10);
+        assert!(sender.on_ack(acks[0].ack, now).retransmit.is_empty());
+        assert!(sender.on_ack(acks[1].ack, now).retransmit.is_empty());
+        assert!(sender.on_ack(acks[2].ack, now).retransmit.is_empty());
+        // 第三个重复确认：远早于 RTO 就重发了丢失的 2
+        assert!(now < sender.next_deadline().unwrap());
+        let resend = sender.on_ack(acks[3].ack, now).retransmit.pop().unwrap();
+        assert_eq!(resend.seq().get(), 2);
+        // 之后的重复确认不再重复触发
+        assert!(sender.on_ack(SeqNum::new(1), now).retransmit.is_empty());
+
+        // 重传段补齐空洞，累计确认跳到 6，重复确认计数清零
+        let ack = receiver.on_data(&resend, now).ack.unwrap();
+        assert_eq!(ack.ack.get(), 6);
+        let outcome = sender.on_ack(ack.ack, now + Duration::from_millis(10));
+        assert_eq!(outcome.acked.segments, 5);
+        assert_eq!(sender.dup_acks(), 0);
+        // Karn：最新被确认的 6 没有重传过，RTT 样本有效；重传段本身不会被采样
+        assert_eq!(sender.rtt().latest(), Some(Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn test_cwnd_limits_window() {
+        let t0 = Instant::now();
+        let mut sender = sender(64);
+        let mut receiver = Receiver::new(SeqNum::new(1), &LinkConfig::default());
+
+        // 初始拥塞窗口 10 段，小于本地配置窗口
+        for _ in 0..10 {
+            sender.send(Bytes::from_static(b"x"), t0).unwrap();
+        }
+        assert!(matches!(sender.send(Bytes::new(), t0), Err(LinkError::WouldBlock)));
+
+        // 全部超时：重新慢启动，cwnd 回到 1
+        let resend = sender.on_timeout(sender.next_deadline().unwrap()).unwrap();
+        assert_eq!(resend.len(), 10);
+        let stats = ConnectionStats::collect(&sender, &receiver);
+        assert_eq!((stats.cwnd, stats.ssthresh, stats.timeouts), (1, 5, 1));
+
+        // 确认全部 10 段后 cwnd 按慢启动增长到 ssthresh
+        sender.on_ack(SeqNum::new(10), t0 + Duration::from_secs(2));
+        receiver.on_data(&resend[0], t0);
+        let stats = ConnectionStats::collect(&sender, &receiver);
+        assert_eq!((stats.cwnd, stats.send_window, stats.in_flight), (5, 5, 0));
+        assert_eq!(stats.receiver.segments_received, 1);
+    }
+
+    #[test]
+    fn test_ece_reduces_window_once_per_rtt() {
+        let t0 = Instant::now();
+        let mut sender = sender(64);
+        let ece = |ack: u64| Segment::builder(SegmentType::Ack).ack(ack).window(64).flags(SegmentFlags::ECE).build().unwrap();
+        for _ in 0..10 {
+            sender.send(Bytes::from_static(b"x"), t0).unwrap();
+        }
+
+        // 第一个 ECE 把窗口减半；同一个窗口内后续的 ECE（包括越过降窗点的那个）都被忽略
+        sender.on_ack_segment(&ece(2), t0);
+        assert_eq!((sender.congestion().window(), sender.congestion().ssthresh()), (5, 5));
+        sender.on_ack_segment(&ece(4), t0);
+        sender.on_ack_segment(&ece(10), t0);
+        assert_eq!(sender.congestion().ssthresh(), 5);
+        assert_eq!(sender.stats().ecn_reductions, 1);
+
+        // 降窗后的第一个新数据段带出 CWR
+        let cwr = sender.send(Bytes::from_static(b"x"), t0).unwrap();
+        assert!(cwr.options().cwr());
+        assert!(!sender.send(Bytes::from_static(b"x"), t0).unwrap().options().cwr());
+
+        // 降窗点之后发出的段再次被标记：新的一次降窗
+        sender.on_ack_segment(&ece(11), t0);
+        assert_eq!(sender.stats().ecn_reductions, 2);
+        assert_eq!(sender.stats().segments_retransmitted, 0);
+    }
+
+    // 记下收到的速率样本，窗口固定
+    #[derive(Debug)]
+    struct Recording(Arc<Mutex<Vec<crate::rate::RateSample>>>);
+
+    impl CongestionControl for Recording {
+        fn on_ack(&mut self, _segments: usize, _rtt: Option<Duration>) {}
+        fn on_loss(&mut self, _kind: LossKind) {}
+        fn on_rto(&mut self) {}
+        fn on_ecn(&mut self) {}
+        fn on_rate_sample(&mut self, sample: crate::rate::RateSample) {
+            self.0.lock().unwrap().push(sample);
+        }
+        fn window(&self) -> usize {
+            8
+        }
+    }
+
+    #[test]
+    fn test_rate_samples_reach_the_congestion_control() {
+        let t0 = Instant::now();
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let config = LinkConfig { nodelay: true, ..LinkConfig::default() };
+        let mut sender = Sender::with_congestion(SeqNum::new(1), &config, Box::new(Recording(samples.clone())));
+        // 一次写满窗口，写入全部交出后窗口已满：不是应用受限
+        let segments = sender.write_message(Bytes::from(vec![0; 8000]), 1000, t0).unwrap();
+        assert_eq!(segments.len(), 8);
+        assert!(!sender.delivery_rate().is_app_limited());
+
+        // 10ms 后确认前四个段
+        sender.on_ack(SeqNum::new(4), t0 + Duration::from_millis(10));
+        let sample = samples.lock().unwrap().pop().unwrap();
+        assert_eq!((sample.delivered, sample.interval, sample.app_limited), (4000, Duration::from_millis(10), false));
+        assert_eq!(sample.bytes_per_sec(), Some(400_000));
+        assert_eq!(sender.delivery_rate().bandwidth(), 400_000);
+        let stats = ConnectionStats::collect(&sender, &Receiver::new(SeqNum::new(1), &LinkConfig::default()));
+        assert_eq!((stats.delivery_rate_bps, stats.min_rtt), (3_200_000, Some(Duration::from_millis(10))));
+
+        // 确认之后没有更多数据：之后只写一个小消息，这段时间的样本被标记为应用受限，不拉低估计
+        sender.on_ack(SeqNum::new(8), t0 + Duration::from_millis(20));
+        assert!(!samples.lock().unwrap().pop().unwrap().app_limited);
+        sender.write(Bytes::from_static(b"x"), t0 + Duration::from_millis(100)).unwrap();
+        assert!(sender.delivery_rate().is_app_limited());
+        sender.on_ack(SeqNum::new(9), t0 + Duration::from_millis(110));
+        let sample = samples.lock().unwrap().pop().unwrap();
+        assert!(sample.app_limited);
+        assert_eq!(sender.delivery_rate().bandwidth(), 400_000);
+    }
+
+    #[test]
+    fn test_fixed_window_ignores_loss() {
+        let t0 = Instant::now();
+        let config = LinkConfig { congestion: CongestionAlgorithm::NoCc { window: 4 }, ..LinkConfig::default() };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        for _ in 0..4 {
+            sender.send(Bytes::from_static(b"x"), t0).unwrap();
+        }
+        assert!(!sender.can_send());
+
+        // 超时后窗口不缩小，确认后立即可以继续发满 4 段
+        sender.on_timeout(sender.next_deadline().unwrap()).unwrap();
+        sender.on_ack(SeqNum::new(4), t0 + Duration::from_secs(2));
+        assert_eq!(sender.window(), 4);
+        assert_eq!(sender.congestion().ssthresh(), usize::MAX);
+    }
+
+    #[test]
+    fn test_peer_window_bounds_sending() {
+        let now = Instant::now();
+        let config = LinkConfig { recv_window: 3, ..LinkConfig::default() };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        let mut receiver = Receiver::new(SeqNum::new(1), &config);
+
+        // 接收端通告 3 段窗口
+        sender.on_ack_frame(&receiver.ack_frame(), now);
+        assert_eq!(sender.peer_window(), 3);
+
+        // 尝试发送 10 段：每轮只能发出窗口允许的段数，应用读取后窗口打开，其余数据继续流动
+        let mut pending: Vec<u8> = (0..10).rev().collect();
+        let mut rounds = Vec::new();
+        let mut delivered = Vec::new();
+        while !pending.is_empty() {
+            let mut sent = 0;
+            while sender.can_send() && let Some(i) = pending.pop() {
+                receiver.on_data(&sender.send(Bytes::from(vec![i]), now).unwrap(), now);
+                sent += 1;
+            }
+            rounds.push(sent);
+
+            // 确认到达但应用尚未读取：发满的窗口关闭
+            sender.on_ack_frame(&receiver.ack_frame(), now);
+            assert_eq!(sender.peer_window(), 3 - sent);
+            if sent == 3 {
+                assert!(matches!(sender.send(Bytes::new(), now), Err(LinkError::WouldBlock)));
+            }
+
+            while let Some(data) = receiver.pop_ready() {
+                delivered.push(data[0]);
+            }
+            if let Some(update) = receiver.on_window_update() {
+                sender.on_ack_frame(&update, now);
+            }
+        }
+        assert_eq!(rounds, vec![3, 3, 3, 1]);
+        assert_eq!(delivered, (0..10).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_zero_window_probe_recovers_lost_update() {
+        let t0 = Instant::now();
+        let config = LinkConfig { recv_window: 1, ..LinkConfig::default() };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        let mut receiver = Receiver::new(SeqNum::new(1), &config);
+
+        let segment = sender.send(Bytes::from_static(b"x"), t0).unwrap();
+        receiver.on_data(&segment, t0);
+        sender.on_ack_frame(&receiver.ack_frame(), t0);
+        assert_eq!(sender.peer_window(), 0);
+
+        // 应用读取后发出的窗口更新丢失
+        receiver.pop_ready().unwrap();
+        assert!(receiver.on_window_update().is_some());
+        assert!(!sender.can_send());
+
+        // 坚持定时器到期，探测段引出携带新窗口的确认
+        let deadline = sender.next_deadline().unwrap();
+        let probes = sender.on_timeout(deadline).unwrap();
+        assert_eq!(probes.len(), 1);
+        assert!(probes[0].data().is_empty());
+        let reply = receiver.on_data(&probes[0], deadline).ack.unwrap();
+        assert_eq!(reply.window, 1);
+        sender.on_ack_frame(&reply, deadline);
+        assert!(sender.can_send());
+        assert_eq!(sender.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_zero_window_probe_replies_are_not_losses() {
+        let t0 = Instant::now();
+        let config = LinkConfig { recv_window: 1, ..LinkConfig::default() };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        let mut receiver = Receiver::new(SeqNum::new(1), &config);
+
+        // 第二个段超出接收窗口被丢弃，仍在途
+        for _ in 0..2 {
+            let segment = sender.send(Bytes::from_static(b"x"), t0).unwrap();
+            if let Some(ack) = receiver.on_data(&segment, t0).ack {
+                sender.on_ack_frame(&ack, t0);
+            }
+        }
+        assert_eq!((sender.peer_window(), sender.in_flight()), (0, 1));
+        let cwnd = sender.congestion().window();
+
+        // 每个探测都引出累计点不变的确认，攒够三个也不触发快速重传（这里不驱动重传定时器）
+        for _ in 0..4 {
+            let deadline = sender.persist_deadline.unwrap();
+            let probe = sender.poll_persist(deadline).unwrap();
+            let reply = receiver.on_data(&probe, deadline).ack.unwrap();
+            assert!(sender.on_ack_frame(&reply, deadline).retransmit.is_empty());
+        }
+        assert_eq!((sender.fast_retransmits(), sender.congestion().window()), (0, cwnd));
+    }
+
+    #[test]
+    fn test_sack_retransmits_only_gaps() {
+        let t0 = Instant::now();
+        let config = LinkConfig::default();
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        let mut receiver = Receiver::new(SeqNum::new(1), &config);
+
+        // 发送 1..=12，丢弃 5 与 9；接收端对乱序段立即回复带 SACK 的确认
+        let mut retransmitted = Vec::new();
+        for _ in 1..=12 {
+            let segment = sender.send(Bytes::from_static(b"data"), t0).unwrap();
+            if matches!(segment.seq().get(), 5 | 9) {
+                continue;
+            }
+            if let Some(ack) = receiver.on_data(&segment, t0).ack {
+                retransmitted.extend(sender.on_ack_frame(&ack, t0).retransmit);
+            }
+        }
+        assert_eq!(retransmitted.iter().map(|s| s.seq().get()).collect::<Vec<_>>(), vec![5, 9]);
+        assert_eq!(sender.fast_retransmits(), 2);
+        // 两个空洞属于同一个恢复期，只降一次窗：判定 5 丢失时（8 被 SACK）cwnd 已慢启动到 16
+        assert_eq!(sender.congestion().ssthresh(), 8);
+
+        // 超时也不会重发已被 SACK 的段
+        let expired = sender.on_timeout(sender.next_deadline().unwrap()).unwrap();
+        assert_eq!(expired.iter().map(|s| s.seq().get()).collect::<Vec<_>>(), vec![5, 9]);
+
+        for segment in &retransmitted {
+            if let Some(ack) = receiver.on_data(segment, t0).ack {
+                sender.on_ack_frame(&ack, t0);
+            }
+        }
+        assert_eq!(sender.in_flight(), 0);
+        assert_eq!(receiver.buffer().cumulative_ack(), SeqNum::new(12));
+    }
+
+    // 同一时刻写入 n 个 10 字节的小消息，不回确认，最后等合并定时器到期；返回数据报数与全部段
+    fn tiny_writes(n: usize, nodelay: bool) -> (usize, Vec<Segment>) {
+        let config = LinkConfig {
+            congestion: CongestionAlgorithm::NoCc { window: 64 },
+            nodelay,
+            ..LinkConfig::default()
+        };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        let t0 = Instant::now();
+        let mut batches = Vec::new();
+        for _ in 0..n {
+            batches.push(sender.write(Bytes::from_static(b"0123456789"), t0).unwrap());
+        }
+        if let Some(deadline) = sender.next_deadline().filter(|&d| d < t0 + config.min_rto) {
+            batches.push(sender.on_timeout(deadline).unwrap());
+        }
+        assert_eq!(sender.pending(), 0);
+
+        let mut datagrams = 0;
+        for batch in &batches {
+            let packed = crate::segment::pack_datagrams(batch, config.mss).unwrap();
+            assert!(packed.iter().all(|d| d.len() <= config.mss));
+            datagrams += packed.len();
+        }
+        (datagrams, batches.into_iter().flatten().collect())
+    }
+
+    #[test]
+    fn test_payload_is_not_copied_before_encoding() {
+        let t0 = Instant::now();
+        let config = LinkConfig { nodelay: true, ..LinkConfig::default() };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        let data = Bytes::from(vec![3; 250]);
+        // 交出的段与登记重传的副本都指向调用方的 Bytes
+        let segment = sender.send(data.clone(), t0).unwrap();
+        assert_eq!(segment.data().as_ptr(), data.as_ptr());
+        assert_eq!(sender.unacknowledged()[0].as_ptr(), data.as_ptr());
+        let segments = sender.write(data.clone(), t0).unwrap();
+        assert_eq!(segments[0].data().as_ptr(), data.as_ptr());
+        // 分片是原缓冲区的切片
+        let segments = sender.write_message(data.clone(), 100, t0).unwrap();
+        let offsets: Vec<_> = segments.iter().map(|segment| segment.data().as_ptr() as usize - data.as_ptr() as usize).collect();
+        assert_eq!(offsets, [0, 100, 200]);
+    }
+
+    #[test]
+    fn test_nagle_coalesces_tiny_writes() {
+        // 第一次写入立即发送；之后每 28 个（28 * 42 = 1176 字节）凑满一个数据报，余下的由定时器交出
+        let (datagrams, segments) = tiny_writes(50, false);
+        assert_eq!(datagrams, 3);
+        // 每次写入仍是独立的段，顺序与消息边界不变
+        assert_eq!(segments.len(), 50);
+        assert!(segments.iter().enumerate().all(|(i, s)| s.seq().get() == i as u64 + 1 && s.data().len() == 10));
+
+        let (datagrams, segments) = tiny_writes(50, true);
+        assert_eq!(datagrams, 50);
+        assert_eq!(segments.len(), 50);
+    }
+
+    #[test]
+    fn test_ack_flushes_pending_writes() {
+        let t0 = Instant::now();
+        let mut sender = sender(64);
+        assert_eq!(sender.write(Bytes::from_static(b"a"), t0).unwrap().len(), 1);
+        assert!(sender.write(Bytes::from_static(b"b"), t0).unwrap().is_empty());
+        assert!(sender.write(Bytes::from_static(b"c"), t0).unwrap().is_empty());
+        assert_eq!(sender.pending(), 2);
+
+        // 确认到达时一次交出暂存的写入
+        let outcome = sender.on_ack(SeqNum::new(1), t0 + Duration::from_millis(1));
+        assert_eq!(outcome.transmit.iter().map(|s| s.seq().get()).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(sender.pending(), 0);
+
+        // 打开 nodelay 后立即交出
+        assert!(sender.write(Bytes::from_static(b"d"), t0).unwrap().is_empty());
+        assert_eq!(sender.set_nodelay(true, t0).unwrap().len(), 1);
+        assert_eq!(sender.write(Bytes::from_static(b"e"), t0).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_unacknowledged_covers_sent_and_pending_writes() {
+        let t0 = Instant::now();
+        let mut sender = sender(64);
+        for data in [&b"a"[..], b"b", b"c"] {
+            sender.write(Bytes::from_static(data), t0).unwrap();
+        }
+        // a 已发出未确认，b、c 暂存
+        assert_eq!(sender.unacknowledged(), vec![Bytes::from_static(b"a"), Bytes::from_static(b"b"), Bytes::from_static(b"c")]);
+        sender.on_ack(SeqNum::new(1), t0 + Duration::from_millis(1));
+        assert_eq!(sender.unacknowledged(), vec![Bytes::from_static(b"b"), Bytes::from_static(b"c")]);
+        sender.on_ack(SeqNum::new(3), t0 + Duration::from_millis(2));
+        assert!(sender.unacknowledged().is_empty());
+    }
+
+    #[test]
+    fn test_message_is_split_into_fragments() {
+        let t0 = Instant::now();
+        let config = LinkConfig { nodelay: true, ..LinkConfig::default() };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        let message: Bytes = (0..250u8).collect();
+        let segments = sender.write_message(message.clone(), 100, t0).unwrap();
+        // 除最后一片外都携带 more，拼起来是原消息
+        assert_eq!(segments.iter().map(|segment| segment.data().len()).collect::<Vec<_>>(), [100, 100, 50]);
+        assert_eq!(segments.iter().map(|segment| segment.options().more()).collect::<Vec<_>>(), [true, true, false]);
+        assert_eq!(segments.iter().flat_map(|segment| segment.data().to_vec()).collect::<Bytes>(), message);
+
+        // 放得进一片的消息与空消息都是一个不带 more 的段
+        for len in [100, 0] {
+            let segments = sender.write_message(Bytes::from(vec![0; len]), 100, t0).unwrap();
+            assert_eq!(segments.len(), 1);
+            assert!(!segments[0].options().more());
+        }
+    }
+
+    #[test]
+    fn test_rewind_refragments_unsent_segments() {
+        let t0 = Instant::now();
+        let config = LinkConfig { nodelay: true, ..LinkConfig::default() };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        let message: Bytes = (0..250u8).collect();
+        sender.write(Bytes::from_static(b"first"), t0).unwrap();
+        assert_eq!(sender.write_message(message.clone(), 100, t0).unwrap().len(), 3);
+        sender.write_with(Bytes::from_static(b"loose"), SendOptions { ordered: false, ..SendOptions::default() }, t0).unwrap();
+
+        // 消息的第二片起没能发出：撤回后按 60 字节重新切开，从 3 起重新编号，之后的消息保持原样
+        let segments = sender.rewind(SeqNum::new(3), 60, t0).unwrap();
+        assert_eq!(segments.iter().map(|segment| segment.seq().get()).collect::<Vec<_>>(), (3..=6).collect::<Vec<_>>());
+        assert_eq!(segments.iter().map(|segment| segment.data().len()).collect::<Vec<_>>(), [60, 60, 30, 5]);
+        assert_eq!(segments.iter().map(|segment| segment.options().more()).collect::<Vec<_>>(), [true, true, false, false]);
+        assert!(segments[3].options().unordered());
+        let rebuilt: Bytes = segments[..3].iter().flat_map(|segment| segment.data().to_vec()).collect();
+        assert_eq!(rebuilt, message.slice(100..));
+        assert_eq!(sender.next_seq(), SeqNum::new(7));
+        assert_eq!((sender.in_flight(), sender.stats().segments_sent), (6, 6));
+
+        // 重传过的段可能已经到达对端，原样等待重传
+        sender.on_timeout(t0 + Duration::from_secs(5)).unwrap();
+        assert_eq!(sender.rewind(SeqNum::new(6), 10, t0), None);
+        assert_eq!(sender.next_seq(), SeqNum::new(7));
+    }
+
+    #[test]
+    fn test_refragment_splits_pending_writes() {
+        let t0 = Instant::now();
+        let config = LinkConfig { send_window: 1, nodelay: true, ..LinkConfig::default() };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        sender.write(Bytes::from_static(b"sent"), t0).unwrap();
+        sender.write_with(Bytes::from(vec![1; 150]), SendOptions { ordered: false, ..SendOptions::default() }, t0).unwrap();
+        sender.write(Bytes::from(vec![2; 40]), t0).unwrap();
+
+        // 暂存的 150 字节切成两片，按序交付；放得下的写入不动
+        sender.refragment(100);
+        assert_eq!(sender.pending(), 3);
+        let segments = sender.on_ack(SeqNum::new(1), t0).transmit;
+        assert_eq!(segments.len(), 1);
+        assert_eq!((segments[0].data().len(), segments[0].options().more(), segments[0].options().unordered()), (100, true, false));
+        let segments: Vec<_> = (2..=3).flat_map(|ack| sender.on_ack(SeqNum::new(ack), t0).transmit).collect();
+        assert_eq!(segments.iter().map(|segment| (segment.data().len(), segment.options().more())).collect::<Vec<_>>(), [(50, false), (40, false)]);
+    }
+
+    #[test]
+    fn test_tag_stays_on_the_first_fragment() {
+        let t0 = Instant::now();
+        let config = LinkConfig { nodelay: true, ..LinkConfig::default() };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        let tags = |segments: &[Segment]| segments.iter().map(|segment| segment.options().tag()).collect::<Vec<_>>();
+        let segments = sender.write_message_with(Bytes::from(vec![3; 250]), 100, SendOptions::tag(7), t0).unwrap();
+        assert_eq!(tags(&segments), [Some(7), None, None]);
+        let segments = sender.write_with(Bytes::from_static(b"next"), SendOptions { ordered: false, ..SendOptions::tag(8) }, t0).unwrap();
+        assert_eq!(tags(&segments), [Some(8)]);
+        assert!(segments[0].options().unordered());
+
+        // 重传原样携带标签
+        let retransmitted = sender.on_timeout(t0 + Duration::from_secs(5)).unwrap();
+        assert_eq!(tags(&retransmitted[..1]), [Some(7)]);
+
+        // 撤回后重新切开：标签留在新的第一片上，从消息中间撤回的部分不带标签
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        sender.write_message_with(Bytes::from(vec![3; 250]), 100, SendOptions::tag(7), t0).unwrap();
+        sender.write_with(Bytes::from(vec![4; 90]), SendOptions::tag(9), t0).unwrap();
+        let segments = sender.rewind(SeqNum::new(2), 60, t0).unwrap();
+        assert_eq!(tags(&segments), [None, None, None, Some(9), None]);
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        sender.write_message_with(Bytes::from(vec![3; 250]), 100, SendOptions::tag(7), t0).unwrap();
+        assert_eq!(tags(&sender.rewind(SeqNum::new(1), 125, t0).unwrap()), [Some(7), None]);
+    }
+
+    #[test]
+    fn test_expired_message_is_replaced_by_a_skip() {
+        let t0 = Instant::now();
+        let ttl = Duration::from_millis(100);
+        let config = LinkConfig { send_window: 2, nodelay: true, ..LinkConfig::default() };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        // 三片的消息：窗口只放得下前两片，第三片暂存
+        let sent = sender.write_message_with(Bytes::from(vec![7; 250]), 100, SendOptions::ttl(ttl), t0).unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sender.pending(), 1);
+        assert_eq!(sender.next_deadline(), Some(t0 + ttl));
+
+        // 到期：暂存的一片丢弃，已发出的两片由一个 Skip 占住
+        let out = sender.on_timeout(t0 + ttl).unwrap();
+        assert_eq!(out.len(), 1);
+        assert_eq!((out[0].segment_type(), out[0].seq(), out[0].skip_end()), (SegmentType::Skip, SeqNum::new(1), Some(SeqNum::new(2))));
+        assert_eq!((sender.pending(), sender.in_flight()), (0, 1));
+        assert!(sender.unacknowledged().is_empty());
+        assert_eq!(sender.stats().expired, 1);
+
+        // Skip 像数据段一样超时重传
+        let retransmitted = sender.on_timeout(t0 + ttl + sender.rtt().rto()).unwrap();
+        assert_eq!(retransmitted.iter().map(Segment::segment_type).collect::<Vec<_>>(), [SegmentType::Skip]);
+        // 对端越过整段空缺后确认 2
+        sender.on_ack(SeqNum::new(2), t0 + ttl * 20);
+        assert!(sender.is_drained());
+        assert_eq!(sender.write(Bytes::from_static(b"next"), t0 + ttl * 20).unwrap()[0].seq(), SeqNum::new(3));
+        assert_eq!(sender.stats().expired, 1);
+    }
+
+    #[test]
+    fn test_new_conn_id_waits_for_the_last_fragment() {
+        let t0 = Instant::now();
+        let config = LinkConfig { send_window: 2, nodelay: true, ..LinkConfig::default() };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        // 窗口只放得下前两片：NewConnId 推迟到第三片之后，不插进消息中间
+        assert_eq!(sender.write_message(Bytes::from(vec![7; 250]), 100, t0).unwrap().len(), 2);
+        assert!(sender.issue_conn_id(9, t0).unwrap().is_empty());
+        assert_eq!(sender.issue_conn_id(10, t0), Err(LinkError::WouldBlock));
+
+        let outcome = sender.on_ack(SeqNum::new(1), t0);
+        let kinds: Vec<_> = outcome.transmit.iter().map(|segment| (segment.segment_type(), segment.seq())).collect();
+        assert_eq!(kinds, [(SegmentType::Data, SeqNum::new(3)), (SegmentType::NewConnId, SeqNum::new(4))]);
+        assert_eq!(outcome.transmit[1].issued_conn_id(), Some(9));
+
+        // 被确认之后才交出
+        sender.on_ack(SeqNum::new(3), t0);
+        assert_eq!(sender.take_acked_conn_id(), None);
+        sender.on_ack(SeqNum::new(4), t0);
+        assert_eq!(sender.take_acked_conn_id(), Some(9));
+        assert_eq!(sender.take_acked_conn_id(), None);
+        assert!(sender.is_drained());
+    }
+
+    #[test]
+    fn test_resync_to_latest_abandons_the_backlog() {
+        let t0 = Instant::now();
+        let config = LinkConfig { send_window: 2, nodelay: true, ..LinkConfig::default() };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        for _ in 0..4 {
+            sender.write(Bytes::from_static(b"old"), t0).unwrap();
+        }
+        sender.on_ack(SeqNum::new(1), t0);
+        assert_eq!((sender.in_flight(), sender.pending()), (2, 1));
+
+        // 在途的 2、3 由一个 Skip 占住，暂存的写入丢弃，之后从 4 开始
+        let (head, out) = sender.resync(None, t0).unwrap();
+        assert_eq!(head, SeqNum::new(4));
+        assert_eq!(out.len(), 1);
+        assert_eq!((out[0].segment_type(), out[0].seq(), out[0].skip_end()), (SegmentType::Skip, SeqNum::new(2), Some(SeqNum::new(3))));
+        assert_eq!(sender.pending(), 0);
+        assert!(sender.unacknowledged().is_empty());
+        sender.on_ack(SeqNum::new(3), t0);
+        assert_eq!(sender.write(Bytes::from_static(b"new"), t0).unwrap()[0].seq(), head);
+
+        // 已被累计确认的数据不再保留
+        assert_eq!(sender.resync(Some(SeqNum::new(2)), t0), Err(SeqNum::new(4)));
+        assert_eq!(sender.in_flight(), 1);
+    }
+
+    #[test]
+    fn test_resync_moves_past_a_split_message() {
+        let t0 = Instant::now();
+        let config = LinkConfig { send_window: 2, nodelay: true, ..LinkConfig::default() };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        // 三片的消息发出前两片，第三片暂存：从第二片开始的请求推到整条消息之后，第三片一起丢弃
+        sender.write_message(Bytes::from(vec![7; 250]), 100, t0).unwrap();
+        sender.write(Bytes::from_static(b"after"), t0).unwrap();
+        let (head, out) = sender.resync(Some(SeqNum::new(2)), t0).unwrap();
+        assert_eq!(head, SeqNum::new(3));
+        assert_eq!((out[0].seq(), out[0].skip_end()), (SeqNum::new(1), Some(SeqNum::new(2))));
+        assert_eq!(sender.unacknowledged(), [Bytes::from_static(b"after")]);
+
+        // 已发出的 FIN 不放弃
+        sender.on_ack(SeqNum::new(2), t0);
+        sender.flush(t0).unwrap();
+        let fin = sender.fin(t0).unwrap().seq();
+        assert_eq!(sender.resync(None, t0).unwrap().0, fin);
+        assert!(sender.unacknowledged().is_empty());
+    }
+
+    #[test]
+    fn test_acked_message_does_not_expire() {
+        let t0 = Instant::now();
+        let mut sender = sender(8);
+        let ttl = Duration::from_millis(100);
+        sender.send_with(Bytes::from_static(b"fresh"), SendOptions::ttl(ttl), t0).unwrap();
+        sender.on_ack(SeqNum::new(1), t0 + Duration::from_millis(10));
+        assert_eq!(sender.next_deadline(), None);
+        assert!(sender.on_timeout(t0 + ttl).unwrap().is_empty());
+        assert_eq!(sender.stats().expired, 0);
+    }
+
+    #[test]
+    fn test_send_buffer_bounds_queued_bytes() {
+        // 窗口 2 段、队列 300 字节：窗口外的写入暂存等待确认，队列满后写入返回 WouldBlock
+        let t0 = Instant::now();
+        let config = LinkConfig { send_window: 2, send_buffer: 300, nodelay: true, ..LinkConfig::default() };
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        let chunk = Bytes::from(vec![0u8; 100]);
+        assert_eq!(sender.write(chunk.clone(), t0).unwrap().len(), 1);
+        assert_eq!(sender.write(chunk.clone(), t0).unwrap().len(), 1);
+        assert!(sender.write(chunk.clone(), t0).unwrap().is_empty());
+        assert_eq!(sender.queued_bytes(), 300);
+        assert_eq!(sender.write(chunk.clone(), t0), Err(LinkError::WouldBlock));
+
+        // 确认腾出窗口，暂存的写入发出，队列随之缩小
+        let outcome = sender.on_ack(SeqNum::new(1), t0 + Duration::from_millis(1));
+        assert_eq!(outcome.transmit.len(), 1);
+        assert_eq!(sender.queued_bytes(), 200);
+        // 内存额度比 send_buffer 更紧时以它为准，放宽后照常写入
+        sender.set_queue_limit(250);
+        assert_eq!(sender.write(chunk.clone(), t0), Err(LinkError::WouldBlock));
+        sender.set_queue_limit(usize::MAX);
+        assert!(sender.write(chunk, t0).is_ok());
+    }
+}
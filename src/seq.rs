@@ -0,0 +1,123 @@
+//! 序列号
+//! `SeqNum` 按序列号算术（RFC 1982）比较：两个值的前后关系由它们在环上的有符号距离决定，
+//! 跨越 `u64::MAX` 回绕时依然正确。刻意不实现 `Ord`/`PartialOrd`，直接用 `<` 比较序列号无法编译，
+//! 只能使用 `is_before`/`is_after`/`distance`。
+
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// 线格式上的 u64 序列号
+///
+/// 没有实现 `PartialOrd`，原始比较无法编译：
+///
+/// ```compile_fail
+/// use link_rs::seq::SeqNum;
+/// let _ = SeqNum::new(1) < SeqNum::new(2);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct SeqNum(u64);
+
+impl SeqNum {
+    /// 可比较的最大距离：相距恰好 2^63 的两个值前后关系无定义
+    pub const MAX_DISTANCE: u64 = (1 << 63) - 1;
+
+    pub const fn new(value: u64) -> Self {
+        Self(value)
+    }
+
+    /// 线格式上的原始值
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    pub const fn wrapping_add(self, n: u64) -> Self {
+        Self(self.0.wrapping_add(n))
+    }
+
+    pub const fn wrapping_sub(self, n: u64) -> Self {
+        Self(self.0.wrapping_sub(n))
+    }
+
+    /// 从 `self` 到 `other` 的有符号距离：`other` 在后为正，在前为负
+    pub const fn distance(self, other: SeqNum) -> i64 {
+        other.0.wrapping_sub(self.0) as i64
+    }
+
+    /// `self` 是否在 `other` 之前
+    pub const fn is_before(self, other: SeqNum) -> bool {
+        self.distance(other) > 0
+    }
+
+    /// `self` 是否在 `other` 之后
+    pub const fn is_after(self, other: SeqNum) -> bool {
+        other.is_before(self)
+    }
+}
+
+impl From<u64> for SeqNum {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+impl From<SeqNum> for u64 {
+    fn from(seq: SeqNum) -> Self {
+        seq.0
+    }
+}
+
+impl fmt::Display for SeqNum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordering_without_wrap() {
+        let a = SeqNum::new(10);
+        let b = SeqNum::new(20);
+        assert!(a.is_before(b));
+        assert!(b.is_after(a));
+        assert_eq!(a.distance(b), 10);
+        assert_eq!(b.distance(a), -10);
+    }
+
+    #[test]
+    fn test_ordering_straddles_max() {
+        let before = SeqNum::new(u64::MAX - 1);
+        let after = before.wrapping_add(3);
+        assert_eq!(after.get(), 1);
+        assert!(before.is_before(after));
+        assert!(after.is_after(before));
+        assert!(!after.is_before(before));
+        assert_eq!(before.distance(after), 3);
+        assert_eq!(after.wrapping_sub(3), before);
+    }
+
+    #[test]
+    fn test_equal_values() {
+        let a = SeqNum::new(u64::MAX);
+        assert!(!a.is_before(a));
+        assert!(!a.is_after(a));
+        assert_eq!(a.distance(a), 0);
+    }
+
+    #[test]
+    fn test_maximum_distance() {
+        let a = SeqNum::new(5);
+        let far = a.wrapping_add(SeqNum::MAX_DISTANCE);
+        assert!(a.is_before(far));
+        assert_eq!(a.distance(far), i64::MAX);
+
+        // 超过最大距离后关系翻转：环上反方向更近
+        let beyond = a.wrapping_add(SeqNum::MAX_DISTANCE + 2);
+        assert!(a.is_after(beyond));
+    }
+}
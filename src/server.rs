@@ -0,0 +1,644 @@
+//! 消息服务器
+//! `Server` 在监听器之上为每个接受的连接起一个会话任务：先调用 `Handler::on_connect`，再按到达顺序把每条消息交给
+//! `Handler::on_message`，上一条处理完才交付下一条，回应由处理函数经 `ConnectionHandle::send` 发出；连接结束后调用
+//! `Handler::on_disconnect` 并告知结局。握手、确认与重传由连接完成，无法解析的数据报由监听器丢弃并计数。
+//! 自带两种处理方式：原样发回的 `EchoHandler` 与丢弃一切的 `DiscardHandler`（用于压测）。
+//!
+//! 处理函数中的 panic 在会话任务内被捕获，只中止这一个连接：向对端发出 Rst，本端的结局为 `LinkError::HandlerPanicked`，
+//! 服务器与其他连接不受影响。`LinkConfig::metrics_interval` 非零时
+//! 另起一个任务，按这个间隔以 info 级别输出一行指标摘要（见 `metrics` 模块）；期间有某一类解码错误的速率超过
+//! `LinkConfig::decode_error_warn_rate` 时改以 warn 级别输出，并附上这些种类与它们的速率。
+//!
+//! `shutdown` 经 watch 通道通知 `run` 与所有会话：监听器停止接受握手，每个会话放弃等待下一条消息，以 `close`
+//! 向对端发送 FIN 并等待关闭完成（正在执行的处理函数先执行完）；`LinkConfig::shutdown_timeout` 内仍未结束的会话被中止（连接随之丢弃，
+//! 对端收不到 FIN），之后 `run` 返回各类会话的计数。对端在关闭期间发来的消息不再交给 `Handler`。
+//!
+//! 监听器保留最近被拒的数据报（见 `rejects` 模块），`recent_rejects` 读出它们；`run` 返回前以及 unix 上
+//! 收到 SIGUSR1 时把它们以十六进制转储写入日志。
+
+use crate::acl::Acl;
+use crate::config::LinkConfig;
+use crate::error::LinkError;
+use crate::connection::Connection;
+#[cfg(feature = "tokio")]
+use crate::diagnostics::{self, Report, SelftestOptions};
+use crate::listener::Listener;
+use crate::metrics::{DecodeErrorReport, Metrics, MetricsSnapshot};
+#[cfg(feature = "tokio")]
+use crate::multicast::MulticastReceiver;
+use crate::rejects::{self, Reject};
+use crate::socket::SocketInfo;
+use crate::transport::Transport;
+use bytes::Bytes;
+use std::any::Any;
+use std::future::{Future, poll_fn};
+use std::net::SocketAddr;
+#[cfg(feature = "tokio")]
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+#[cfg(feature = "tokio")]
+use tokio::net::ToSocketAddrs;
+use tokio::sync::watch;
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::Instrument;
+
+/// 服务器对每个连接的处理，所有连接共用同一个实例；实现时可以直接写 `async fn`
+pub trait Handler: Send + Sync + 'static {
+    /// 连接建立后、交付第一条消息之前调用一次
+    fn on_connect(&self, _conn: &ConnectionHandle) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+
+    /// 按到达顺序处理每条消息，返回后才交付下一条
+    fn on_message(&self, conn: &ConnectionHandle, message: Bytes) -> impl Future<Output = ()> + Send;
+
+    /// 连接结束后调用一次：`outcome` 为 Ok 表示完成了 FIN 交换
+    fn on_disconnect(&self, _peer: SocketAddr, _outcome: &Result<(), LinkError>) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+}
+
+/// 默认的处理方式：原样发回
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EchoHandler;
+
+impl Handler for EchoHandler {
+    async fn on_message(&self, conn: &ConnectionHandle, message: Bytes) {
+        tracing::debug!(len = message.len(), "echo");
+        // 发送失败时连接已经失败，下一次 recv 结束会话
+        let _ = conn.send(message).await;
+    }
+}
+
+/// 丢弃收到的一切，不发回任何消息；只测量接收路径
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiscardHandler;
+
+impl Handler for DiscardHandler {
+    async fn on_message(&self, _conn: &ConnectionHandle, _message: Bytes) {}
+}
+
+/// 处理函数看到的连接：发送回应，或经 `connection` 使用连接的其余接口。接收由会话负责
+#[derive(Debug)]
+pub struct ConnectionHandle {
+    connection: Connection,
+}
+
+impl ConnectionHandle {
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.connection.peer_addr()
+    }
+
+    /// 发送一条消息，见 `Connection::send`
+    pub async fn send(&self, message: Bytes) -> Result<(), LinkError> {
+        self.connection.send(message).await
+    }
+
+    /// 发送一条可能超过 MSS 的消息，见 `Connection::send_msg`
+    pub async fn send_msg(&self, message: Bytes) -> Result<(), LinkError> {
+        self.connection.send_msg(message).await
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+}
+
+type BoxFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+// `Handler` 的对象安全形式，服务器以 `Arc<dyn ErasedHandler>` 持有处理函数
+trait ErasedHandler: Send + Sync + 'static {
+    fn on_connect<'a>(&'a self, conn: &'a ConnectionHandle) -> BoxFuture<'a>;
+    fn on_message<'a>(&'a self, conn: &'a ConnectionHandle, message: Bytes) -> BoxFuture<'a>;
+    fn on_disconnect<'a>(&'a self, peer: SocketAddr, outcome: &'a Result<(), LinkError>) -> BoxFuture<'a>;
+}
+
+impl<H: Handler> ErasedHandler for H {
+    fn on_connect<'a>(&'a self, conn: &'a ConnectionHandle) -> BoxFuture<'a> {
+        Box::pin(Handler::on_connect(self, conn))
+    }
+
+    fn on_message<'a>(&'a self, conn: &'a ConnectionHandle, message: Bytes) -> BoxFuture<'a> {
+        Box::pin(Handler::on_message(self, conn, message))
+    }
+
+    fn on_disconnect<'a>(&'a self, peer: SocketAddr, outcome: &'a Result<(), LinkError>) -> BoxFuture<'a> {
+        Box::pin(Handler::on_disconnect(self, peer, outcome))
+    }
+}
+
+/// 关闭时各会话的结局
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Drained {
+    pub closed: usize,      // 完成 FIN 交换
+    pub failed: usize,      // 连接已失败，或 close 超过 linger 以 Rst 结束
+    pub aborted: usize,     // shutdown_timeout 到期时仍未结束而被中止
+}
+
+/// 把每个连接的消息交给同一个 `Handler` 的服务器
+pub struct Server {
+    listener: Listener,
+    handler: Arc<dyn ErasedHandler>,
+    shutdown: watch::Sender<bool>,
+    config: LinkConfig,
+    summary: Option<JoinHandle<()>>,
+}
+
+impl Server {
+    /// 逐项设置参数与处理函数，默认为 `LinkConfig::default()` 与 `EchoHandler`
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder { config: LinkConfig::default(), handler: Arc::new(EchoHandler) }
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn bind(addr: impl ToSocketAddrs, config: LinkConfig, handler: impl Handler) -> Result<Server, LinkError> {
+        let listener = Listener::bind_with(addr, config.clone()).await?;
+        Ok(Self::serve(listener, config, Arc::new(handler)))
+    }
+
+    /// 在调用方提供的传输上服务，见 `Listener::with_transport`
+    pub fn with_transport(transport: impl Transport, config: LinkConfig, handler: impl Handler) -> Result<Server, LinkError> {
+        let listener = Listener::with_transport(transport, config.clone())?;
+        Ok(Self::serve(listener, config, Arc::new(handler)))
+    }
+
+    fn serve(listener: Listener, config: LinkConfig, handler: Arc<dyn ErasedHandler>) -> Server {
+        let summary = Summary::new(&config);
+        let summary = (!summary.interval.is_zero()).then(|| tokio::spawn(summarize(listener.shared_metrics(), summary)));
+        let (shutdown, _) = watch::channel(false);
+        Server { listener, handler, shutdown, config, summary }
+    }
+
+    /// 加入 IPv4 组播组，接收任何发送方发往 `port` 的 Data 段；组播没有连接，返回单独的接收端（见 `multicast` 模块）
+    #[cfg(feature = "tokio")]
+    pub fn bind_multicast(group: Ipv4Addr, port: u16, interface: Ipv4Addr, config: LinkConfig) -> Result<MulticastReceiver, LinkError> {
+        MulticastReceiver::join_v4(group, port, interface, &config)
+    }
+
+    /// `bind_multicast` 的 IPv6 版本，`interface` 是接口索引
+    #[cfg(feature = "tokio")]
+    pub fn bind_multicast_v6(group: Ipv6Addr, port: u16, interface: u32, config: LinkConfig) -> Result<MulticastReceiver, LinkError> {
+        MulticastReceiver::join_v6(group, port, interface, &config)
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, LinkError> {
+        self.listener.local_addr()
+    }
+
+    /// 经回环地址对自己做一次自检（见 `diagnostics` 模块），检查本机的防火墙与套接字选项是否放行这个端口；
+    /// 需要 `run` 正在另一个任务中运行，且处理函数原样发回消息（`EchoHandler`）。监听在通配地址上时连接同族的回环地址
+    #[cfg(feature = "tokio")]
+    pub async fn selftest(&self, options: &SelftestOptions) -> Result<Report, LinkError> {
+        let mut target = self.local_addr()?;
+        if target.ip().is_unspecified() {
+            target.set_ip(match target {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        // 故障只注入在服务端一侧，免得每个数据报经过两次
+        diagnostics::selftest(target, options, LinkConfig { faults: None, ..self.config.clone() }).await
+    }
+
+    pub fn listener(&self) -> &Listener {
+        &self.listener
+    }
+
+    /// 接收套接字上生效的选项，见 `Listener::socket_info`
+    pub fn socket_info(&self) -> Option<SocketInfo> {
+        self.listener.socket_info()
+    }
+
+    /// 数据路径计数器的当前值
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.listener.metrics()
+    }
+
+    /// 解码错误按种类与来源的汇总，可序列化（见 `metrics::DecodeErrorReport`）
+    pub fn decode_error_report(&self) -> DecodeErrorReport {
+        self.listener.decode_error_report()
+    }
+
+    /// 监听器最近无法解析的数据报，从旧到新（见 `Listener::recent_rejects`）
+    pub fn recent_rejects(&self) -> Vec<Reject> {
+        self.listener.recent_rejects()
+    }
+
+    /// 在运行中替换访问控制列表，已建立的连接是否保留由 `acl.evict` 决定（见 `Listener::set_acl`）
+    pub fn set_acl(&self, acl: Acl) {
+        self.listener.set_acl(acl);
+    }
+
+    /// 开始关闭，立即返回；`run` 关闭完所有会话后返回。在 `run` 之前调用时 `run` 直接进入关闭
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// 接受连接并处理消息，每个连接一个会话任务，慢速的对端不会拖住其他连接。
+    /// `shutdown` 后关闭所有会话并返回它们的结局；监听器失效时返回错误
+    pub async fn run(&self) -> Result<Drained, LinkError> {
+        let mut stop = self.shutdown.subscribe();
+        let mut sessions = JoinSet::new();
+        let mut dump = DumpSignal::install();
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    let (connection, peer) = accepted?;
+                    self.spawn_session(&mut sessions, connection, peer);
+                }
+                // 回收已结束的会话；集合为空时这一分支本轮不参与
+                Some(_) = sessions.join_next() => {}
+                () = dump.recv() => rejects::dump(&self.recent_rejects()),
+                _ = stop.wait_for(|stop| *stop) => break,
+            }
+        }
+
+        tracing::info!(sessions = sessions.len(), "shutting down");
+        self.listener.stop_accepting().await;
+        // 停止前已完成握手、仍在队列中的连接一并关闭
+        while let Ok((connection, peer)) = self.listener.accept().await {
+            self.spawn_session(&mut sessions, connection, peer);
+        }
+        let deadline = tokio::time::Instant::now() + self.config.shutdown_timeout;
+        let mut drained = Drained::default();
+        loop {
+            match tokio::time::timeout_at(deadline, sessions.join_next()).await {
+                Ok(Some(Ok(true))) => drained.closed += 1,
+                Ok(Some(_)) => drained.failed += 1,
+                Ok(None) => break,
+                Err(_) => {
+                    drained.aborted = sessions.len();
+                    sessions.shutdown().await;
+                    break;
+                }
+            }
+        }
+        rejects::dump(&self.recent_rejects());
+        tracing::info!(closed = drained.closed, failed = drained.failed, aborted = drained.aborted, "shutdown complete");
+        Ok(drained)
+    }
+
+    fn spawn_session(&self, sessions: &mut JoinSet<bool>, connection: Connection, peer: SocketAddr) {
+        let span = tracing::info_span!("session", %peer);
+        sessions.spawn(session(connection, peer, self.handler.clone(), self.shutdown.subscribe()).instrument(span));
+    }
+}
+
+/// `Server::builder` 返回的构造器
+pub struct ServerBuilder {
+    config: LinkConfig,
+    handler: Arc<dyn ErasedHandler>,
+}
+
+impl ServerBuilder {
+    pub fn config(mut self, config: LinkConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn handler(mut self, handler: impl Handler) -> Self {
+        self.handler = Arc::new(handler);
+        self
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn bind(self, addr: impl ToSocketAddrs) -> Result<Server, LinkError> {
+        let listener = Listener::bind_with(addr, self.config.clone()).await?;
+        Ok(Server::serve(listener, self.config, self.handler))
+    }
+
+    /// 在调用方提供的传输上服务，见 `Listener::with_transport`
+    pub fn with_transport(self, transport: impl Transport) -> Result<Server, LinkError> {
+        let listener = Listener::with_transport(transport, self.config.clone())?;
+        Ok(Server::serve(listener, self.config, self.handler))
+    }
+}
+
+// 一个连接的会话：处理消息直到对端关闭、连接失败或服务器关闭，之后以 close 结束；处理函数 panic 时以 Rst 中止。
+// 返回是否正常关闭
+async fn session(connection: Connection, peer: SocketAddr, handler: Arc<dyn ErasedHandler>, mut stop: watch::Receiver<bool>) -> bool {
+    tracing::info!("connection accepted");
+    let conn = ConnectionHandle { connection };
+    let failure = 'serve: {
+        if caught(handler.on_connect(&conn)).await.is_err() {
+            break 'serve Some(LinkError::HandlerPanicked);
+        }
+        loop {
+            let message = tokio::select! {
+                message = conn.connection.recv() => message,
+                _ = stop.wait_for(|stop| *stop) => break None,
+            };
+            match message {
+                Ok(Some(message)) => {
+                    if caught(handler.on_message(&conn, message)).await.is_err() {
+                        break Some(LinkError::HandlerPanicked);
+                    }
+                }
+                // 对端关闭了写方向
+                Ok(None) => break None,
+                Err(e) => break Some(e),
+            }
+        }
+    };
+    let outcome = match failure {
+        None => conn.connection.close().await,
+        Some(LinkError::HandlerPanicked) => {
+            conn.connection.abort(LinkError::HandlerPanicked).await;
+            Err(LinkError::HandlerPanicked)
+        }
+        Some(e) => Err(e),
+    };
+    tracing::info!(clean = outcome.is_ok(), "connection finished");
+    let _ = caught(handler.on_disconnect(peer, &outcome)).await;
+    outcome.is_ok()
+}
+
+// 执行处理函数返回的 future，其中的 panic 被捕获、记入日志后返回 Err
+async fn caught(mut future: BoxFuture<'_>) -> Result<(), ()> {
+    poll_fn(|cx| match catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+        Ok(poll) => poll.map(Ok),
+        Err(panic) => {
+            tracing::error!(panic = panic_message(&*panic), "handler panicked, aborting the connection");
+            Poll::Ready(Err(()))
+        }
+    })
+    .await
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(message), _) => message,
+        (_, Some(message)) => message,
+        _ => "non-string payload",
+    }
+}
+
+// 请求转储被拒数据报的信号：unix 上为 SIGUSR1，其他平台或无法安装处理函数时从不触发
+struct DumpSignal {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl DumpSignal {
+    fn install() -> Self {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let signal = signal(SignalKind::user_defined1())
+                .inspect_err(|e| tracing::debug!(error = %e, "cannot install SIGUSR1 handler"))
+                .ok();
+            Self { signal }
+        }
+        #[cfg(not(unix))]
+        Self {}
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = &mut self.signal
+            && signal.recv().await.is_some()
+        {
+            return;
+        }
+        std::future::pending().await
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Some(summary) = &self.summary {
+            summary.abort();
+        }
+    }
+}
+
+// 指标摘要的输出间隔与解码错误的告警速率
+#[derive(Debug, Clone, Copy)]
+struct Summary {
+    interval: Duration,
+    warn_rate: f64,
+}
+
+impl Summary {
+    fn new(config: &LinkConfig) -> Self {
+        Self { interval: config.metrics_interval, warn_rate: config.decode_error_warn_rate }
+    }
+}
+
+// 定期输出一行指标摘要，速率按两次输出之间的实际间隔计算
+async fn summarize(metrics: Arc<Metrics>, summary: Summary) {
+    let mut ticker = tokio::time::interval(summary.interval);
+    ticker.tick().await;
+    let mut previous = (metrics.snapshot(), tokio::time::Instant::now());
+    loop {
+        ticker.tick().await;
+        let now = (metrics.snapshot(), tokio::time::Instant::now());
+        let elapsed = now.1 - previous.1;
+        let line = now.0.summary(&previous.0, elapsed);
+        let noisy = now.0.decode_errors.above_rate(&previous.0.decode_errors, elapsed, summary.warn_rate);
+        match noisy.is_empty() || summary.warn_rate == 0.0 {
+            true => tracing::info!("{}", line),
+            false => {
+                let kinds: Vec<_> = noisy.iter().map(|(kind, rate)| format!("{:?} {:.1}/s", kind, rate)).collect();
+                tracing::warn!("{}; decode errors above {}/s: {}", line, summary.warn_rate, kinds.join(", "));
+            }
+        }
+        previous = now;
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::connection::Connection;
+    use crate::segment::{Segment, SegmentError, SegmentType};
+    use crate::seq::SeqNum;
+    use bytes::BytesMut;
+    use tokio::net::UdpSocket;
+    use tokio::time::timeout;
+
+    async fn recv_segment(socket: &UdpSocket) -> Segment {
+        let mut buf = vec![0u8; 65535];
+        let len = timeout(Duration::from_secs(5), socket.recv(&mut buf)).await.unwrap().unwrap();
+        Segment::decode_from(&mut BytesMut::from(&buf[..len])).unwrap().unwrap()
+    }
+
+    // 手写的客户端：握手、发送三条消息，检查累计确认与回显段的序列号
+    async fn hand_built_client(server: SocketAddr) {
+        const ISN: u64 = 100;
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(server).await.unwrap();
+
+        let syn = Segment::builder(SegmentType::Syn).data_seq(ISN).build().unwrap();
+        socket.send(&syn.encode().unwrap()).await.unwrap();
+        let syn_ack = recv_segment(&socket).await;
+        assert_eq!(syn_ack.segment_type(), SegmentType::Syn);
+        assert_eq!(syn_ack.ack(), SeqNum::new(ISN));
+        let conn_id = syn_ack.conn_id();
+        let ack = Segment::builder(SegmentType::Ack).conn_id(conn_id).ack(syn_ack.seq()).window(64).build().unwrap();
+        socket.send(&ack.encode().unwrap()).await.unwrap();
+
+        // 陌生地址发来的无法解析的数据报被丢弃并计数，不影响连接
+        let stranger = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        stranger.send_to(b"garbage that is not a segment at all", server).await.unwrap();
+        for (i, message) in ["a", "b", "c"].into_iter().enumerate() {
+            let data = Segment::builder(SegmentType::Data).conn_id(conn_id).data_seq(ISN + 1 + i as u64).payload(message).build().unwrap();
+            socket.send(&data.encode().unwrap()).await.unwrap();
+        }
+
+        // 确认单调推进到最后一条消息；每条消息以服务端自己的序列号回显
+        let (mut acked, mut echoed) = (SeqNum::new(ISN), Vec::new());
+        while acked != SeqNum::new(ISN + 3) || echoed.len() < 3 {
+            let segment = recv_segment(&socket).await;
+            if segment.segment_type() == SegmentType::Data {
+                assert_eq!(segment.seq(), SeqNum::new(syn_ack.seq().get() + 1 + echoed.len() as u64));
+                echoed.push(segment.data().clone());
+                let ack = Segment::builder(SegmentType::Ack).conn_id(conn_id).ack(segment.seq()).window(64).build().unwrap();
+                socket.send(&ack.encode().unwrap()).await.unwrap();
+            }
+            if segment.is_ack_bearing() {
+                assert!(segment.ack().get() >= acked.get() && segment.ack().get() <= ISN + 3);
+                acked = segment.ack();
+            }
+        }
+        assert_eq!(echoed, vec![Bytes::from_static(b"a"), Bytes::from_static(b"b"), Bytes::from_static(b"c")]);
+    }
+
+    #[tokio::test]
+    async fn test_protocol_mode_acks_and_echoes() {
+        let server = Server::bind("127.0.0.1:0", LinkConfig::default(), EchoHandler).await.unwrap();
+        tokio::select! {
+            result = server.run() => panic!("server stopped: {:?}", result),
+            () = hand_built_client(server.local_addr().unwrap()) => {}
+        }
+        assert_eq!(server.listener().stats().malformed, 1);
+    }
+
+    #[tokio::test]
+    async fn test_recent_rejects_keep_the_malformed_datagrams() {
+        let server = Server::bind("127.0.0.1:0", LinkConfig::default(), EchoHandler).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let stranger = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        // 类型未知且长于记录的前缀、长度前缀小于段头、在段中间结束（截断）
+        let mut unknown = Segment::new(SegmentType::Data, 1, vec![7; 300]).encode().unwrap().to_vec();
+        unknown[4] = 0xEE;
+        let datagrams = [unknown, vec![0, 0, 0, 3, 9, 9], vec![0, 0, 0, 40, 1, 2]];
+        for datagram in &datagrams {
+            stranger.send_to(datagram, addr).await.unwrap();
+        }
+        let rejects = timeout(Duration::from_secs(5), async {
+            loop {
+                let rejects = server.recent_rejects();
+                if rejects.len() == datagrams.len() {
+                    return rejects;
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let errors: Vec<_> = rejects.iter().map(|reject| reject.error.clone()).collect();
+        assert_eq!(errors, [SegmentError::UnknownFrameType(0xEE), SegmentError::InvalidTotalLen(3, 6), SegmentError::TooShort]);
+        assert!(rejects.iter().all(|reject| reject.from == stranger.local_addr().unwrap()));
+        assert_eq!((rejects[0].len, rejects[0].bytes()), (datagrams[0].len(), &datagrams[0][..rejects::PREFIX]));
+        for (reject, datagram) in rejects.iter().zip(&datagrams).skip(1) {
+            assert_eq!((reject.len, reject.bytes()), (datagram.len(), &datagram[..]));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode_error_report_breaks_down_by_kind_and_peer() {
+        let server = Server::bind("127.0.0.1:0", LinkConfig::default(), EchoHandler).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let mut unknown = Segment::new(SegmentType::Data, 1, vec![7; 16]).encode().unwrap().to_vec();
+        unknown[4] = 0xEE;
+        let mut corrupted = Segment::new(SegmentType::Data, 1, vec![7; 16]).encode().unwrap().to_vec();
+        *corrupted.last_mut().unwrap() ^= 0xFF;
+        let short_length = vec![0, 0, 0, 3, 9, 9];
+        let (first, second) = (UdpSocket::bind("127.0.0.1:0").await.unwrap(), UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        for datagram in [&unknown, &unknown, &corrupted] {
+            first.send_to(datagram, addr).await.unwrap();
+        }
+        for datagram in [&short_length, &unknown] {
+            second.send_to(datagram, addr).await.unwrap();
+        }
+        let report = timeout(Duration::from_secs(5), async {
+            loop {
+                let report = server.decode_error_report();
+                if report.total == 5 {
+                    return report;
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        use crate::metrics::DecodeErrorKind::*;
+        assert_eq!(report.kinds, [(UnknownFrameType, 3), (InvalidTotalLen, 1), (ChecksumMismatch, 1)]);
+        assert_eq!(report.tracked_peers, 2);
+        let peers: Vec<_> = report.peers.iter().map(|peer| (peer.peer, peer.total, peer.kinds.clone())).collect();
+        assert_eq!(
+            peers,
+            [
+                (first.local_addr().unwrap(), 3, vec![(UnknownFrameType, 2), (ChecksumMismatch, 1)]),
+                (second.local_addr().unwrap(), 2, vec![(InvalidTotalLen, 1), (UnknownFrameType, 1)]),
+            ]
+        );
+        // 分类计数与按类别归并的计数一致
+        let metrics = server.metrics();
+        assert_eq!((metrics.decode_errors.unknown_type, metrics.decode_errors.checksum, metrics.decode_errors.invalid_length), (3, 1, 1));
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&report).unwrap();
+            assert_eq!(serde_json::from_str::<DecodeErrorReport>(&json).unwrap(), report);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metrics_add_up() {
+        let server = Server::bind("127.0.0.1:0", LinkConfig::default(), EchoHandler).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let client = async {
+            let connection = Connection::connect(addr).await.unwrap();
+            for i in 0..50 {
+                connection.send(Bytes::from(format!("m{}", i))).await.unwrap();
+                assert_eq!(connection.recv().await.unwrap().unwrap(), Bytes::from(format!("m{}", i)));
+            }
+            // 一个类型未知，另一个在段中间结束、按截断计数
+            let stranger = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let mut unknown = Segment::new(SegmentType::Data, 1, vec![]).encode().unwrap();
+            unknown[4] = 0xEE;
+            stranger.send_to(&unknown, addr).await.unwrap();
+            stranger.send_to(&[0, 0, 0, 40, 1, 2], addr).await.unwrap();
+            while server.metrics().listener_decode_errors + server.metrics().truncated < 2 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            connection
+        };
+        let connection = tokio::select! {
+            result = server.run() => panic!("server stopped: {:?}", result),
+            connection = timeout(Duration::from_secs(10), client) => connection.unwrap(),
+        };
+
+        let metrics = server.metrics();
+        // 每个收到的数据报恰好落入一类
+        assert_eq!(
+            metrics.datagrams_received,
+            metrics.truncated + metrics.absorbed + metrics.delivered + metrics.dropped + metrics.handled + metrics.listener_decode_errors
+        );
+        assert!(metrics.delivered >= 50);
+        assert!(metrics.handled >= 1);      // 握手
+        assert_eq!(metrics.decode_errors.unknown_type, 1);
+        assert_eq!(metrics.truncated, 1);
+        assert_eq!(metrics.active_connections, 1);
+        assert!(metrics.datagrams_sent >= 51);
+        assert!(metrics.bytes_received > 0 && metrics.bytes_sent > 0);
+        drop(connection);
+    }
+}
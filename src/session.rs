@@ -0,0 +1,516 @@
+//! 连接状态的导出与接续
+//! 零停机部署时新进程接手旧进程已建立的连接，而不是复位每一个客户端：旧进程以 `Connection::export_state` 停下连接的驱动任务
+//! （此后不再确认对端的任何段，导出的状态与对端看到的不会分叉）并导出 `SessionState`，编码后交给新进程；
+//! 新进程以 `Listener::adopt` 从它重建连接并继续收发。套接字本身（文件描述符）的交接留给调用方，新进程的监听器
+//! 绑定在同一地址上（或接过旧进程的套接字）即可。对端只会看到一次短暂的停顿：交接期间发来的段没有得到确认，重传后由新进程处理。
+//!
+//! 状态包含两端的 ISN、握手分配的连接 ID 与两个方向当前的 ID、对端地址与 MSS、协商出的校验算法与参数，流 0 的发送端
+//! （累计确认点、已发送未确认的段、尚未发出的写入）与接收端（已收到未交付的段、攒下的分片与提前取出的无序消息）。
+//! 配置了预共享密钥时（`crypto` 特性）还包含握手交换的双方 nonce：会话密钥由它们与预共享密钥重新派生，状态本身不含密钥，
+//! 新进程须配置同一个预共享密钥（接续时只能察觉有没有配置，配置了另一个密钥时对端的段都无法解密而被丢弃）。RTT、拥塞窗口与统计不迁移，接续的连接从慢启动重新开始；TTL 不迁移，未确认的消息不再过期；
+//! 未读的不可靠消息被丢弃。
+//!
+//! 只有处于 Established、没有附加流、没有进行中的连接 ID 轮换、也没有收到对端 FIN 的连接可以导出，否则返回 `NotExportable`。
+//!
+//! 编码以版本号开头，之后是大端序的定长字段与带长度前缀的变长部分。版本号为 0 或高于 `SESSION_VERSION` 的状态
+//! 无法解读：新版本的进程可以接续旧版本导出的连接，反之不行。版本 2 加入了消息的标签（见 `SendOptions::tag`），
+//! 版本 1 的状态照常解读，其中的消息都没有标签。
+
+use crate::checksum::ChecksumAlgorithm;
+use crate::params::TransportParameters;
+use crate::receiver::TaggedMessage;
+use crate::segment::Segment;
+use crate::seq::SeqNum;
+use bytes::Bytes;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// 本端编码的状态版本
+pub const SESSION_VERSION: u8 = 2;
+
+// 握手 nonce 的字节数（见 `crypto::NONCE_LEN`），不启用 `crypto` 特性时同样能解读含 nonce 的状态
+const NONCE_LEN: usize = 16;
+
+/// 导出或接续连接状态失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionError {
+    NotExportable(&'static str),    // 连接当前不能导出（见模块文档），附原因
+    UnsupportedVersion(u8),         // 版本号为 0 或高于 `SESSION_VERSION`
+    Truncated,                      // 编码在某个字段中间结束
+    Malformed,                      // 字段的值不合法（未知的地址族或校验算法、无法解码的段等），或末尾有多余的字节
+    KeyMismatch,                    // 状态是否含有 nonce 与本端是否配置了预共享密钥不一致
+    InUse,                          // 监听器上已有同一对端地址或同一连接 ID 的连接
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::NotExportable(reason) => write!(f, "connection cannot be exported: {}", reason),
+            SessionError::UnsupportedVersion(version) => write!(f, "unsupported session state version {}", version),
+            SessionError::Truncated => write!(f, "session state is truncated"),
+            SessionError::Malformed => write!(f, "session state is malformed"),
+            SessionError::KeyMismatch => write!(f, "session state and local configuration disagree on the pre-shared key"),
+            SessionError::InUse => write!(f, "the listener already has a connection with this peer or connection id"),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+/// 一条已建立连接的可迁移状态，由 `Connection::export_state` 导出、`Listener::adopt` 接续
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionState {
+    pub(crate) peer: SocketAddr,
+    pub(crate) initiator: bool,
+    pub(crate) local_isn: SeqNum,
+    pub(crate) peer_isn: SeqNum,
+    pub(crate) handshake_id: u32,       // 握手分配的连接 ID，参与会话密钥的派生
+    pub(crate) local_id: u32,           // 对端发来的段当前携带的 ID
+    pub(crate) remote_id: u32,          // 本端发出的段当前携带的 ID
+    pub(crate) checksum: ChecksumAlgorithm,
+    pub(crate) peer_mss: Option<usize>,
+    pub(crate) params: TransportParameters,
+    pub(crate) next_local_stream: u32,
+    pub(crate) next_peer_stream: u32,
+    pub(crate) nonces: Option<([u8; NONCE_LEN], [u8; NONCE_LEN])>,  // 握手 nonce：（发起方, 响应方）
+    pub(crate) origin: Option<SeqNum>,  // 流 0 第一个加密的序列号，nonce 用量从它算起
+    pub(crate) sender: SenderState,
+    pub(crate) receiver: ReceiverState,
+}
+
+/// 流 0 发送端的可迁移部分
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SenderState {
+    pub(crate) next_seq: SeqNum,
+    pub(crate) last_ack: SeqNum,
+    pub(crate) peer_window: usize,
+    pub(crate) more_sent: bool,
+    pub(crate) in_flight: Vec<Segment>,     // 已发送、尚未被累计确认的段，按序列号
+    pub(crate) pending: Vec<Pending>,       // 尚未发出的写入，按写入顺序
+}
+
+/// 尚未发出的一段写入
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Pending {
+    pub(crate) data: Bytes,
+    pub(crate) ordered: bool,
+    pub(crate) more: bool,      // 同一消息还有下一片
+    pub(crate) tag: Option<u32>,
+}
+
+/// 流 0 接收端的可迁移部分
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ReceiverState {
+    pub(crate) next_deliver: SeqNum,
+    pub(crate) floor: Option<SeqNum>,
+    pub(crate) discarding: bool,
+    pub(crate) entries: Vec<Entry>,     // 已收到、尚未交付的段，按序列号
+    pub(crate) early: Vec<TaggedMessage>,   // 提前取出、尚未交付的无序消息及其标签
+    pub(crate) fragments: Vec<Bytes>,   // 已按序取出、尚未收齐的消息的各片
+    pub(crate) tag: Option<u32>,        // 尚未收齐的消息的标签
+}
+
+/// 重排缓冲区中的一个段及接收端对它的标记
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Entry {
+    pub(crate) seq: SeqNum,
+    pub(crate) data: Bytes,
+    pub(crate) more: bool,      // 携带 more 选项
+    pub(crate) skipped: bool,   // Skip 覆盖的空位
+    pub(crate) gap: bool,       // 一段空缺的第一个序列号
+    pub(crate) marker: bool,    // NewConnId 占用的空位
+    pub(crate) taken: bool,     // 已被提前取出，只留下空位
+    pub(crate) tag: Option<u32>,    // 携带 tag 选项
+}
+
+// 标志位
+const INITIATOR: u8 = 0x01;
+const NONCES: u8 = 0x02;
+const ORIGIN: u8 = 0x04;
+const MORE_SENT: u8 = 0x08;
+const FLOOR: u8 = 0x10;
+const DISCARDING: u8 = 0x20;
+const PARTIAL_TAG: u8 = 0x40;
+
+const ORDERED: u8 = 0x01;
+const MORE: u8 = 0x02;
+const SKIPPED: u8 = 0x04;
+const GAP: u8 = 0x08;
+const MARKER: u8 = 0x10;
+const TAKEN: u8 = 0x20;
+const TAGGED: u8 = 0x40;
+
+impl SessionState {
+    /// 对端的地址
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// 对端发来的段当前携带的连接 ID
+    pub fn conn_id(&self) -> u32 {
+        self.local_id
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Writer(Vec::new());
+        let flags = flag(self.initiator, INITIATOR)
+            | flag(self.nonces.is_some(), NONCES)
+            | flag(self.origin.is_some(), ORIGIN)
+            | flag(self.sender.more_sent, MORE_SENT)
+            | flag(self.receiver.floor.is_some(), FLOOR)
+            | flag(self.receiver.discarding, DISCARDING)
+            | flag(self.receiver.tag.is_some(), PARTIAL_TAG);
+        out.u8(SESSION_VERSION);
+        out.u8(flags);
+        match self.peer.ip() {
+            IpAddr::V4(ip) => {
+                out.u8(4);
+                out.0.extend_from_slice(&ip.octets());
+            }
+            IpAddr::V6(ip) => {
+                out.u8(6);
+                out.0.extend_from_slice(&ip.octets());
+            }
+        }
+        out.0.extend_from_slice(&self.peer.port().to_be_bytes());
+        out.u64(self.local_isn.get());
+        out.u64(self.peer_isn.get());
+        out.u32(self.handshake_id);
+        out.u32(self.local_id);
+        out.u32(self.remote_id);
+        out.u8(self.checksum.id());
+        out.u64(self.peer_mss.map_or(0, |mss| mss as u64));
+        out.bytes(&self.params.encode());
+        out.u32(self.next_local_stream);
+        out.u32(self.next_peer_stream);
+        if let Some((initiator, responder)) = &self.nonces {
+            out.0.extend_from_slice(initiator);
+            out.0.extend_from_slice(responder);
+        }
+        if let Some(origin) = self.origin {
+            out.u64(origin.get());
+        }
+
+        let sender = &self.sender;
+        out.u64(sender.next_seq.get());
+        out.u64(sender.last_ack.get());
+        out.u64(u64::try_from(sender.peer_window).unwrap_or(u64::MAX));
+        out.u32(sender.in_flight.len() as u32);
+        for segment in &sender.in_flight {
+            out.bytes(&segment.encode().expect("queued segments were encodable when sent"));
+        }
+        out.u32(sender.pending.len() as u32);
+        for pending in &sender.pending {
+            out.u8(flag(pending.ordered, ORDERED) | flag(pending.more, MORE) | flag(pending.tag.is_some(), TAGGED));
+            out.tag(pending.tag);
+            out.bytes(&pending.data);
+        }
+
+        let receiver = &self.receiver;
+        out.u64(receiver.next_deliver.get());
+        if let Some(floor) = receiver.floor {
+            out.u64(floor.get());
+        }
+        out.u32(receiver.entries.len() as u32);
+        for entry in &receiver.entries {
+            out.u64(entry.seq.get());
+            out.u8(
+                flag(entry.more, MORE) | flag(entry.skipped, SKIPPED) | flag(entry.gap, GAP) | flag(entry.marker, MARKER) | flag(entry.taken, TAKEN) | flag(entry.tag.is_some(), TAGGED),
+            );
+            out.tag(entry.tag);
+            out.bytes(&entry.data);
+        }
+        out.u32(receiver.early.len() as u32);
+        for (tag, data) in &receiver.early {
+            out.u8(flag(tag.is_some(), TAGGED));
+            out.tag(*tag);
+            out.bytes(data);
+        }
+        out.u32(receiver.fragments.len() as u32);
+        for data in &receiver.fragments {
+            out.bytes(data);
+        }
+        out.tag(receiver.tag);
+        out.0
+    }
+
+    /// 版本号为 0 或高于 `SESSION_VERSION` 时返回 `UnsupportedVersion`，在字段中间结束时返回 `Truncated`
+    pub fn decode(bytes: &[u8]) -> Result<Self, SessionError> {
+        let mut input = Reader(bytes);
+        let version = input.u8()?;
+        if version == 0 || version > SESSION_VERSION {
+            return Err(SessionError::UnsupportedVersion(version));
+        }
+        let flags = input.u8()?;
+        let ip = match input.u8()? {
+            4 => IpAddr::V4(Ipv4Addr::from(input.array::<4>()?)),
+            6 => IpAddr::V6(Ipv6Addr::from(input.array::<16>()?)),
+            _ => return Err(SessionError::Malformed),
+        };
+        let peer = SocketAddr::new(ip, u16::from_be_bytes(input.array()?));
+        let local_isn = input.seq()?;
+        let peer_isn = input.seq()?;
+        let handshake_id = input.u32()?;
+        let local_id = input.u32()?;
+        let remote_id = input.u32()?;
+        let checksum = ChecksumAlgorithm::from_id(input.u8()?).ok_or(SessionError::Malformed)?;
+        let peer_mss = match input.u64()? {
+            0 => None,
+            mss => Some(usize::try_from(mss).map_err(|_| SessionError::Malformed)?),
+        };
+        let params = TransportParameters::decode(input.bytes()?).map_err(|_| SessionError::Malformed)?;
+        let next_local_stream = input.u32()?;
+        let next_peer_stream = input.u32()?;
+        let nonces = match flags & NONCES {
+            0 => None,
+            _ => Some((input.array()?, input.array()?)),
+        };
+        let origin = if flags & ORIGIN != 0 { Some(input.seq()?) } else { None };
+
+        let next_seq = input.seq()?;
+        let last_ack = input.seq()?;
+        let peer_window = usize::try_from(input.u64()?).unwrap_or(usize::MAX);
+        let in_flight = (0..input.u32()?)
+            .map(|_| Segment::decode(input.bytes()?).map_err(|_| SessionError::Malformed))
+            .collect::<Result<_, _>>()?;
+        let pending = (0..input.u32()?)
+            .map(|_| {
+                let flags = input.u8()?;
+                let tag = input.tag(flags)?;
+                Ok(Pending { data: Bytes::copy_from_slice(input.bytes()?), ordered: flags & ORDERED != 0, more: flags & MORE != 0, tag })
+            })
+            .collect::<Result<_, SessionError>>()?;
+        let sender = SenderState { next_seq, last_ack, peer_window, more_sent: flags & MORE_SENT != 0, in_flight, pending };
+
+        let next_deliver = input.seq()?;
+        let floor = if flags & FLOOR != 0 { Some(input.seq()?) } else { None };
+        let entries = (0..input.u32()?)
+            .map(|_| {
+                let seq = input.seq()?;
+                let marks = input.u8()?;
+                let tag = input.tag(marks)?;
+                Ok(Entry {
+                    seq,
+                    data: Bytes::copy_from_slice(input.bytes()?),
+                    more: marks & MORE != 0,
+                    skipped: marks & SKIPPED != 0,
+                    gap: marks & GAP != 0,
+                    marker: marks & MARKER != 0,
+                    taken: marks & TAKEN != 0,
+                    tag,
+                })
+            })
+            .collect::<Result<_, SessionError>>()?;
+        // 版本 1 的无序消息没有标志字节
+        let early = match version {
+            1 => input.list()?.into_iter().map(|data| (None, data)).collect(),
+            _ => (0..input.u32()?)
+                .map(|_| {
+                    let marks = input.u8()?;
+                    Ok((input.tag(marks)?, Bytes::copy_from_slice(input.bytes()?)))
+                })
+                .collect::<Result<_, SessionError>>()?,
+        };
+        let fragments = input.list()?;
+        let tag = if flags & PARTIAL_TAG != 0 { Some(input.u32()?) } else { None };
+        let receiver = ReceiverState { next_deliver, floor, discarding: flags & DISCARDING != 0, entries, early, fragments, tag };
+        if !input.0.is_empty() {
+            return Err(SessionError::Malformed);
+        }
+        Ok(Self {
+            peer,
+            initiator: flags & INITIATOR != 0,
+            local_isn,
+            peer_isn,
+            handshake_id,
+            local_id,
+            remote_id,
+            checksum,
+            peer_mss,
+            params,
+            next_local_stream,
+            next_peer_stream,
+            nonces,
+            origin,
+            sender,
+            receiver,
+        })
+    }
+}
+
+fn flag(set: bool, bit: u8) -> u8 {
+    if set { bit } else { 0 }
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    // 标签由所在项的标志位表明有无，没有时不占字节
+    fn tag(&mut self, tag: Option<u32>) {
+        if let Some(tag) = tag {
+            self.u32(tag);
+        }
+    }
+
+    // 以 4 字节长度为前缀
+    fn bytes(&mut self, value: &[u8]) {
+        self.u32(value.len() as u32);
+        self.0.extend_from_slice(value);
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SessionError> {
+        if self.0.len() < len {
+            return Err(SessionError::Truncated);
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], SessionError> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8, SessionError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, SessionError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, SessionError> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    fn seq(&mut self) -> Result<SeqNum, SessionError> {
+        Ok(SeqNum::new(self.u64()?))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], SessionError> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn list(&mut self) -> Result<Vec<Bytes>, SessionError> {
+        (0..self.u32()?).map(|_| Ok(Bytes::copy_from_slice(self.bytes()?))).collect()
+    }
+
+    // `marks` 中有 `TAGGED` 时读出标签
+    fn tag(&mut self, marks: u8) -> Result<Option<u32>, SessionError> {
+        if marks & TAGGED == 0 {
+            return Ok(None);
+        }
+        self.u32().map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::segment::SegmentType;
+
+    fn state() -> SessionState {
+        let data = Segment::builder(SegmentType::Data).data_seq(SeqNum::new(101)).payload(Bytes::from_static(b"in flight")).build().unwrap();
+        SessionState {
+            peer: "[2001:db8::1]:4000".parse().unwrap(),
+            initiator: false,
+            local_isn: SeqNum::new(100),
+            peer_isn: SeqNum::new(u64::MAX - 1),
+            handshake_id: 7,
+            local_id: 9,
+            remote_id: 7,
+            checksum: ChecksumAlgorithm::Crc32c,
+            peer_mss: Some(1200),
+            params: TransportParameters {
+                ack_every: 2,
+                keepalive_interval: std::time::Duration::from_secs(15),
+                sack: true,
+                early_data: false,
+                checksum: ChecksumAlgorithm::Crc32c,
+            },
+            next_local_stream: 2,
+            next_peer_stream: 1,
+            nonces: Some(([1; NONCE_LEN], [2; NONCE_LEN])),
+            origin: Some(SeqNum::new(101)),
+            sender: SenderState {
+                next_seq: SeqNum::new(102),
+                last_ack: SeqNum::new(100),
+                peer_window: usize::MAX,
+                more_sent: true,
+                in_flight: vec![data],
+                pending: vec![Pending { data: Bytes::from_static(b"rest"), ordered: true, more: false, tag: Some(3) }],
+            },
+            receiver: ReceiverState {
+                next_deliver: SeqNum::new(u64::MAX),
+                floor: None,
+                discarding: false,
+                entries: vec![
+                    Entry { seq: SeqNum::new(0), data: Bytes::from_static(b"late"), more: true, skipped: false, gap: false, marker: false, taken: false, tag: None },
+                    Entry { seq: SeqNum::new(2), data: Bytes::new(), more: false, skipped: true, gap: true, marker: false, taken: false, tag: None },
+                    Entry { seq: SeqNum::new(3), data: Bytes::from_static(b"tagged"), more: false, skipped: false, gap: false, marker: false, taken: false, tag: Some(u32::MAX) },
+                ],
+                early: vec![(None, Bytes::from_static(b"unordered")), (Some(9), Bytes::from_static(b"tagged unordered"))],
+                fragments: vec![Bytes::from_static(b"first half")],
+                tag: Some(5),
+            },
+        }
+    }
+
+    #[test]
+    fn test_session_state_roundtrip() {
+        let state = state();
+        assert_eq!(SessionState::decode(&state.encode()), Ok(state.clone()));
+        let plain = SessionState { nonces: None, origin: None, peer: "10.0.0.2:5000".parse().unwrap(), ..state };
+        assert_eq!(SessionState::decode(&plain.encode()), Ok(plain));
+    }
+
+    #[test]
+    fn test_decodes_version_1_states() {
+        // 版本 1 的无序消息没有标志字节，其余部分在没有标签时与版本 2 相同
+        let mut state = state();
+        state.sender.pending[0].tag = None;
+        state.receiver.entries.truncate(2);
+        state.receiver.early.clear();
+        state.receiver.tag = None;
+        let mut encoded = state.encode();
+        encoded[0] = 1;
+        assert_eq!(SessionState::decode(&encoded), Ok(state.clone()));
+
+        let tail = 4 + 4 + b"first half".len();
+        let at = encoded.len() - tail - 4;
+        encoded.splice(at..at + 4, [0, 0, 0, 1, 0, 0, 0, 2, b'h', b'i']);
+        state.receiver.early.push((None, Bytes::from_static(b"hi")));
+        assert_eq!(SessionState::decode(&encoded), Ok(state));
+    }
+
+    #[test]
+    fn test_session_state_rejects_unknown_versions_and_truncation() {
+        let mut encoded = state().encode();
+        for len in [0, 1, 20, encoded.len() - 1] {
+            assert_eq!(SessionState::decode(&encoded[..len]), Err(SessionError::Truncated));
+        }
+        encoded.push(0);
+        assert_eq!(SessionState::decode(&encoded), Err(SessionError::Malformed));
+        encoded[0] = 0;
+        assert_eq!(SessionState::decode(&encoded), Err(SessionError::UnsupportedVersion(0)));
+        encoded[0] = SESSION_VERSION + 1;
+        assert_eq!(SessionState::decode(&encoded), Err(SessionError::UnsupportedVersion(SESSION_VERSION + 1)));
+    }
+}
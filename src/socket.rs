@@ -0,0 +1,312 @@
+//! UDP 套接字的创建
+//! 地址解析后依次尝试绑定，第一个成功的地址生效。`LinkConfig::dual_stack` 打开时，绑定在 IPv6 地址上的套接字
+//! 关闭 IPV6_V6ONLY，同一个套接字也接收 IPv4 对端，它们的地址以 IPv4 映射的 IPv6 地址（`[::ffff:a.b.c.d]`）出现，
+//! 回应也发往这个地址；平台不允许时绑定失败。关闭时 IPv6 套接字只接收 IPv6，不依赖各平台不同的默认值。
+//!
+//! `bind_workers` 以 SO_REUSEPORT 把 `LinkConfig::workers` 个套接字绑定到同一地址，内核按四元组散列把每个对端的
+//! 数据报固定交给其中一个套接字。只在按散列分发的平台（Linux、Android）启用：其他平台的 SO_REUSEPORT
+//! 要么只把数据报交给最后绑定的套接字，要么根本没有，这时只绑定一个套接字，由调用方通过返回的数量发现。
+//!
+//! 设置了 `LinkConfig::max_mss`（路径 MTU 探测）时套接字在支持的平台（Linux、Android）上设置 DF，
+//! 超过路径 MTU 的探测被丢弃而不是分片，探测才有意义。
+//!
+//! `LinkConfig::so_rcvbuf`、`so_sndbuf` 与 `dscp` 在绑定前设置到套接字上。内核可能调整请求的缓冲区大小
+//! （Linux 把它加倍以计入簿记开销，并限制在 `net.core.rmem_max`/`wmem_max` 之内），生效值小于请求时只记一条警告；
+//! 平台不支持设置 DSCP 时同样只警告。生效的值由 `socket_info` 读出，监听器绑定时记下第一个套接字的。
+//!
+//! 监听器与客户端连接通过 `LinkSocket` 收发：它擦除了具体的 `Transport`（UDP 套接字、内存端点或调用方自己的实现），
+//! `LinkConfig::faults` 设置时传输先包进 `FaultyTransport`。绑定 UDP 套接字的部分需要 `tokio` 特性。
+
+use crate::config::LinkConfig;
+#[cfg(feature = "tokio")]
+use crate::error::LinkError;
+use crate::fault::FaultyTransport;
+use crate::transport::Transport;
+#[cfg(feature = "tokio")]
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::SocketAddr;
+#[cfg(feature = "tokio")]
+use tokio::net::{ToSocketAddrs, UdpSocket};
+
+/// 监听器与客户端连接收发数据报的传输
+#[derive(Debug)]
+pub(crate) struct LinkSocket(Box<dyn Transport>);
+
+impl LinkSocket {
+    pub(crate) fn new(transport: impl Transport, config: &LinkConfig) -> Self {
+        match &config.faults {
+            Some(faults) => LinkSocket(Box::new(FaultyTransport::new(transport, faults, config.recv_buffer))),
+            None => LinkSocket(Box::new(transport)),
+        }
+    }
+
+    pub(crate) fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    pub(crate) async fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        self.0.send_to(buf, target).await
+    }
+
+    pub(crate) async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.0.recv_from(buf).await
+    }
+
+    /// 取出一个已经排队的数据报，没有时返回 `WouldBlock`
+    pub(crate) fn try_recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.0.try_recv_from(buf)
+    }
+}
+
+/// 套接字上生效的选项；平台无法读取的项为 None
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SocketInfo {
+    pub recv_buffer: Option<usize>, // SO_RCVBUF（字节），含内核的簿记开销
+    pub send_buffer: Option<usize>, // SO_SNDBUF（字节）
+    pub dscp: Option<u8>,           // 发出数据报的 DSCP（IPv6 套接字取 IPV6_TCLASS）
+}
+
+/// 读取套接字上生效的缓冲区大小与 DSCP
+#[cfg(feature = "tokio")]
+pub fn socket_info(socket: &UdpSocket) -> SocketInfo {
+    let socket = socket2::SockRef::from(socket);
+    let ipv6 = socket.local_addr().ok().and_then(|addr| addr.as_socket()).is_some_and(|addr| addr.is_ipv6());
+    SocketInfo {
+        recv_buffer: socket.recv_buffer_size().ok(),
+        send_buffer: socket.send_buffer_size().ok(),
+        dscp: traffic_class(&socket, ipv6).ok().map(|tos| (tos >> 2) as u8),
+    }
+}
+
+/// 按 `config` 的地址族选项绑定 `addr`
+#[cfg(feature = "tokio")]
+pub async fn bind(addr: impl ToSocketAddrs, config: &LinkConfig) -> io::Result<UdpSocket> {
+    let mut last = None;
+    for addr in tokio::net::lookup_host(addr).await? {
+        match bind_one(addr, config) {
+            Ok(socket) => return Ok(socket),
+            Err(e) => last = Some(e),
+        }
+    }
+    Err(last.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "could not resolve to any address")))
+}
+
+/// 当前平台能否把同一地址上的数据报分给多个套接字
+pub const REUSE_PORT: bool = cfg!(any(target_os = "linux", target_os = "android"));
+
+/// 为监听器绑定接收套接字：支持 SO_REUSEPORT 时绑定 `config.workers` 个，否则只绑定一个。
+/// 端口为 0 时后续的套接字绑定到第一个套接字分得的端口
+#[cfg(feature = "tokio")]
+pub async fn bind_workers(addr: impl ToSocketAddrs, config: &LinkConfig) -> io::Result<Vec<UdpSocket>> {
+    let count = if REUSE_PORT { config.workers.max(1) } else { 1 };
+    if count == 1 {
+        return Ok(vec![bind(addr, config).await?]);
+    }
+    let mut last = None;
+    for addr in tokio::net::lookup_host(addr).await? {
+        let first = match bind_socket(addr, config, config.dual_stack, true) {
+            Ok(socket) => socket,
+            Err(e) => {
+                last = Some(e);
+                continue;
+            }
+        };
+        let local = first.local_addr()?;
+        let mut sockets = vec![first];
+        for _ in 1..count {
+            sockets.push(bind_socket(local, config, config.dual_stack, true)?);
+        }
+        return Ok(sockets);
+    }
+    Err(last.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "could not resolve to any address")))
+}
+
+/// 客户端连接 `remote` 使用的套接字；`remote` 是 IPv4 映射地址时总是双栈，否则它发出的数据报无法送达。
+/// 给出 `interface` 时先绑定到这个网络接口（SO_BINDTODEVICE，只有 Linux 与 Android 支持），再绑定 `local`
+#[cfg(feature = "tokio")]
+pub(crate) fn bind_client(local: SocketAddr, remote: SocketAddr, config: &LinkConfig, interface: Option<&str>) -> Result<UdpSocket, LinkError> {
+    let mapped = matches!(remote, SocketAddr::V6(remote) if remote.ip().to_ipv4_mapped().is_some());
+    let socket = configure(local, config, config.dual_stack || mapped, false)?;
+    if let Some(interface) = interface {
+        bind_device(&socket, interface)?;
+    }
+    match socket.bind(&local.into()) {
+        Err(e) if e.kind() == io::ErrorKind::AddrNotAvailable => return Err(LinkError::AddrNotLocal(local)),
+        result => result?,
+    }
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+#[cfg(feature = "tokio")]
+fn bind_one(addr: SocketAddr, config: &LinkConfig) -> io::Result<UdpSocket> {
+    bind_socket(addr, config, config.dual_stack, false)
+}
+
+#[cfg(feature = "tokio")]
+fn bind_socket(addr: SocketAddr, config: &LinkConfig, dual_stack: bool, reuse_port: bool) -> io::Result<UdpSocket> {
+    let socket = configure(addr, config, dual_stack, reuse_port)?;
+    socket.bind(&addr.into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+// 创建尚未绑定的非阻塞套接字并设置各项选项
+#[cfg(feature = "tokio")]
+fn configure(addr: SocketAddr, config: &LinkConfig, dual_stack: bool, reuse_port: bool) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    if reuse_port {
+        set_reuse_port(&socket)?;
+    }
+    if config.max_mss.is_some() {
+        set_dont_fragment(&socket, addr.is_ipv6(), dual_stack)?;
+    }
+    tune(&socket, config, addr.is_ipv6(), dual_stack)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+#[cfg(all(feature = "tokio", any(target_os = "linux", target_os = "android")))]
+fn bind_device(socket: &Socket, interface: &str) -> Result<(), LinkError> {
+    socket.bind_device(Some(interface.as_bytes())).map_err(|e| LinkError::Io {
+        kind: e.kind(),
+        message: format!("cannot bind to interface {}: {}", interface, e),
+    })
+}
+
+#[cfg(all(feature = "tokio", not(any(target_os = "linux", target_os = "android"))))]
+fn bind_device(_socket: &Socket, _interface: &str) -> Result<(), LinkError> {
+    Err(LinkError::InterfaceUnsupported)
+}
+
+// 缓冲区大小与 DSCP；内核缩小了缓冲区或平台不支持 DSCP 时只警告
+#[cfg(feature = "tokio")]
+fn tune(socket: &Socket, config: &LinkConfig, ipv6: bool, dual_stack: bool) -> io::Result<()> {
+    if let Some(requested) = config.so_rcvbuf {
+        socket.set_recv_buffer_size(requested)?;
+        let effective = socket.recv_buffer_size()?;
+        if effective < requested {
+            tracing::warn!(requested, effective, "kernel clamped the socket receive buffer");
+        }
+    }
+    if let Some(requested) = config.so_sndbuf {
+        socket.set_send_buffer_size(requested)?;
+        let effective = socket.send_buffer_size()?;
+        if effective < requested {
+            tracing::warn!(requested, effective, "kernel clamped the socket send buffer");
+        }
+    }
+    if let Some(dscp) = config.dscp
+        && let Err(e) = set_traffic_class(socket, ipv6, dual_stack, u32::from(dscp) << 2)
+    {
+        tracing::warn!(dscp, error = %e, "cannot mark datagrams with dscp");
+    }
+    Ok(())
+}
+
+// IPv4 的 TOS 与 IPv6 的流量类别；双栈套接字的 IPv4 流量由 IP 层的选项控制
+#[cfg(all(feature = "tokio", any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "freebsd")))]
+fn set_traffic_class(socket: &Socket, ipv6: bool, dual_stack: bool, tos: u32) -> io::Result<()> {
+    if ipv6 {
+        socket.set_tclass_v6(tos)?;
+    }
+    if !ipv6 || dual_stack {
+        socket.set_tos_v4(tos)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(feature = "tokio", any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "freebsd")))]
+fn traffic_class(socket: &Socket, ipv6: bool) -> io::Result<u32> {
+    if ipv6 { socket.tclass_v6() } else { socket.tos_v4() }
+}
+
+#[cfg(all(feature = "tokio", not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "freebsd"))))]
+fn set_traffic_class(_socket: &Socket, _ipv6: bool, _dual_stack: bool, _tos: u32) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(all(feature = "tokio", not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "freebsd"))))]
+fn traffic_class(_socket: &Socket, _ipv6: bool) -> io::Result<u32> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(all(feature = "tokio", any(target_os = "linux", target_os = "android")))]
+fn set_reuse_port(socket: &Socket) -> io::Result<()> {
+    socket.set_reuse_port(true)
+}
+
+// 只在 REUSE_PORT 为 true 时调用
+#[cfg(all(feature = "tokio", not(any(target_os = "linux", target_os = "android"))))]
+fn set_reuse_port(_socket: &Socket) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// 发出的数据报设置 DF，超过路径 MTU 时被丢弃而不是分片；双栈套接字的 IPv4 流量由 IP 层的选项控制
+#[cfg(all(feature = "tokio", any(target_os = "linux", target_os = "android")))]
+fn set_dont_fragment(socket: &Socket, ipv6: bool, dual_stack: bool) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let set = |level, name, value: libc::c_int| {
+        // SAFETY: 套接字在调用期间有效，选项值是一个 c_int，长度如实传入
+        let result = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                level,
+                name,
+                (&value as *const libc::c_int).cast(),
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if result == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+    };
+    if ipv6 {
+        set(libc::IPPROTO_IPV6, libc::IPV6_MTU_DISCOVER, libc::IPV6_PMTUDISC_DO)?;
+    }
+    if !ipv6 || dual_stack {
+        set(libc::IPPROTO_IP, libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_DO)?;
+    }
+    Ok(())
+}
+
+// 其他平台不设置 DF，探测仍然有效，只是超过路径 MTU 的数据报可能被分片送达
+#[cfg(all(feature = "tokio", not(any(target_os = "linux", target_os = "android"))))]
+fn set_dont_fragment(_socket: &Socket, _ipv6: bool, _dual_stack: bool) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+
+    fn ipv6_available() -> bool {
+        std::net::UdpSocket::bind("[::1]:0").is_ok()
+    }
+
+    #[tokio::test]
+    async fn test_dual_stack_receives_ipv4() {
+        if !ipv6_available() {
+            return;
+        }
+        let config = LinkConfig { dual_stack: true, ..LinkConfig::default() };
+        let Ok(server) = bind("[::]:0", &config).await else {
+            // 平台不允许双栈
+            return;
+        };
+        let port = server.local_addr().unwrap().port();
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(b"v4", ("127.0.0.1", port)).await.unwrap();
+        let mut buf = [0u8; 16];
+        let (len, from) = server.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"v4");
+        assert_eq!(from.ip().to_canonical(), client.local_addr().unwrap().ip());
+
+        // 只接收 IPv6 时 IPv4 的数据报到不了这个套接字
+        let v6only = bind("[::]:0", &LinkConfig::default()).await.unwrap();
+        let port = v6only.local_addr().unwrap().port();
+        assert!(client.send_to(b"v4", ("127.0.0.1", port)).await.is_ok());
+        assert!(tokio::time::timeout(std::time::Duration::from_millis(100), v6only.recv_from(&mut buf)).await.is_err());
+    }
+}
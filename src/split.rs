@@ -0,0 +1,118 @@
+//! 连接的读写两半
+//! `Connection::split` 把连接拆成各自独占的 `RecvHalf` 与 `SendHalf`，分别交给读任务与写任务，不需要调用方
+//! 自己包一层 `Arc`。两半共用同一个连接（及其驱动任务），最后一半被丢弃时连接随之丢弃。
+//!
+//! 丢弃 `SendHalf` 等同于 `shutdown_write`：由一个后台任务等已发送的数据全部被确认后发出 FIN，
+//! 任务持有连接直到 FIN 被确认或连接失败；`RecvHalf` 此间照常交付对端的数据。丢弃 `RecvHalf` 不影响写方向。
+//! `reunite` 把同一个连接的两半合回 `Connection`（例如用于 `close`），不是同一个连接时原样交还两半。
+
+use crate::connection::Connection;
+use crate::error::LinkError;
+use bytes::Bytes;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// 连接的读方向，见 `Connection::split`
+#[derive(Debug)]
+pub struct RecvHalf {
+    connection: Arc<Connection>,
+}
+
+/// 连接的写方向，被丢弃时关闭写方向，见 `Connection::split`
+#[derive(Debug)]
+pub struct SendHalf {
+    connection: Arc<Connection>,
+    reunited: bool,     // 由 `reunite` 合回连接时不关闭写方向
+}
+
+/// `reunite` 的两半不属于同一个连接
+#[derive(Debug)]
+pub struct ReuniteError(pub RecvHalf, pub SendHalf);
+
+impl fmt::Display for ReuniteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tried to reunite halves of different connections")
+    }
+}
+
+impl std::error::Error for ReuniteError {}
+
+pub(crate) fn split(connection: Connection) -> (RecvHalf, SendHalf) {
+    let connection = Arc::new(connection);
+    (RecvHalf { connection: connection.clone() }, SendHalf { connection, reunited: false })
+}
+
+fn reunite(recv: RecvHalf, mut send: SendHalf) -> Result<Connection, ReuniteError> {
+    if !Arc::ptr_eq(&recv.connection, &send.connection) {
+        return Err(ReuniteError(recv, send));
+    }
+    send.reunited = true;
+    drop(send);
+    Ok(Arc::into_inner(recv.connection).expect("the halves hold the only references"))
+}
+
+impl RecvHalf {
+    /// 见 `Connection::recv`
+    pub async fn recv(&self) -> Result<Option<Bytes>, LinkError> {
+        self.connection.recv().await
+    }
+
+    /// 见 `Connection::recv_msg`
+    pub async fn recv_msg(&self) -> Result<Bytes, LinkError> {
+        self.connection.recv_msg().await
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.connection.peer_addr()
+    }
+
+    /// 与同一个连接的 `SendHalf` 合回 `Connection`
+    pub fn reunite(self, send: SendHalf) -> Result<Connection, ReuniteError> {
+        reunite(self, send)
+    }
+}
+
+impl SendHalf {
+    /// 见 `Connection::send`
+    pub async fn send(&self, data: Bytes) -> Result<(), LinkError> {
+        self.connection.send(data).await
+    }
+
+    /// 见 `Connection::send_msg`
+    pub async fn send_msg(&self, data: Bytes) -> Result<(), LinkError> {
+        self.connection.send_msg(data).await
+    }
+
+    /// 见 `Connection::shutdown_write`；之后丢弃这一半不再重复关闭
+    pub async fn shutdown_write(&self) -> Result<(), LinkError> {
+        self.connection.shutdown_write().await
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.connection.peer_addr()
+    }
+
+    /// 与同一个连接的 `RecvHalf` 合回 `Connection`
+    pub fn reunite(self, recv: RecvHalf) -> Result<Connection, ReuniteError> {
+        reunite(recv, self)
+    }
+}
+
+impl Drop for SendHalf {
+    // 不在 tokio 运行时中被丢弃时无法等待确认，写方向保持打开，连接随最后一半丢弃
+    fn drop(&mut self) {
+        if self.reunited {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let connection = self.connection.clone();
+        runtime.spawn(async move {
+            if let Err(e) = connection.shutdown_write().await {
+                tracing::debug!(error = %e, "shutdown after dropping the send half failed");
+            }
+        });
+    }
+}
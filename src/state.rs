@@ -0,0 +1,404 @@
+//! 连接状态机
+//! 连接的所有阶段（握手、已建立、关闭中、已关闭）只通过 `StateMachine` 迁移：输入是收到的段类型
+//! 或本地动作，输出是新状态以及迁移要求发出的段。迁移表集中在 `transition` 一个函数里，
+//! 表里没有的 (状态, 输入) 组合一律返回 `InvalidTransition`，状态保持不变。
+//!
+//! 只看段类型无法区分"对 FIN 的确认"与普通确认，FIN 被确认由连接以 `Action::FinAcked` 告知；
+//! SYN 与 SYN-ACK 的段类型相同，携带确认的 Syn 作为 `Input::SynAck` 输入（见 `Input::from_segment`）。
+//! 同时打开：`SynSent` 状态下收到对端的 SYN（而不是 SYN-ACK）说明双方的 SYN 在路上交错，
+//! 进入 `SynReceived` 并回应 SYN-ACK，之后收到对端的 SYN-ACK 或确认即进入 `Established`。
+//! 对端的 Rst 使活跃连接进入 `Aborted`，TIME_WAIT 中的连接则直接关闭。
+
+use crate::segment::{Segment, SegmentType};
+use std::fmt;
+
+/// 连接状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnState {
+    Closed,
+    SynSent,        // 已发送 SYN，等待 SYN-ACK
+    SynReceived,    // 已回应 SYN-ACK，等待确认
+    Established,
+    FinWait,        // 本端已发送 FIN，等待对端的 FIN
+    CloseWait,      // 对端已发送 FIN，等待本端关闭
+    LastAck,        // 双方都已发送 FIN，等待本端 FIN 被确认
+    TimeWait,       // 吸收迟到的重传，定时器到期后关闭
+    Aborted,        // 异常终止（重试耗尽、保活超时、本地中止）
+}
+
+impl ConnState {
+    /// 全部状态，供表驱动测试与诊断使用
+    pub const ALL: [ConnState; 9] = [
+        ConnState::Closed,
+        ConnState::SynSent,
+        ConnState::SynReceived,
+        ConnState::Established,
+        ConnState::FinWait,
+        ConnState::CloseWait,
+        ConnState::LastAck,
+        ConnState::TimeWait,
+        ConnState::Aborted,
+    ];
+
+    /// 是否还能发送新数据
+    pub fn can_send(self) -> bool {
+        matches!(self, ConnState::Established | ConnState::CloseWait)
+    }
+
+    /// 是否还可能收到新数据
+    pub fn can_receive(self) -> bool {
+        matches!(self, ConnState::SynReceived | ConnState::Established | ConnState::FinWait)
+    }
+
+    /// 握手之后的连接是否已开始关闭（或已结束）
+    pub fn is_closing(self) -> bool {
+        !matches!(self, ConnState::SynSent | ConnState::SynReceived | ConnState::Established)
+    }
+}
+
+/// 本地动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Connect,            // 主动打开
+    Close,              // 优雅关闭
+    FinAcked,           // 本端的 FIN 已被对端确认
+    TimeWaitExpired,    // TIME_WAIT 定时器到期
+    Abort,              // 异常终止
+}
+
+impl Action {
+    pub const ALL: [Action; 5] = [Action::Connect, Action::Close, Action::FinAcked, Action::TimeWaitExpired, Action::Abort];
+}
+
+/// 状态机的输入
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Segment(SegmentType),
+    SynAck,             // 携带确认的 Syn
+    Action(Action),
+}
+
+impl Input {
+    /// 收到的段对应的输入
+    pub fn from_segment(segment: &Segment) -> Input {
+        match segment.segment_type() {
+            SegmentType::Syn if segment.is_ack_bearing() => Input::SynAck,
+            segment_type => Input::Segment(segment_type),
+        }
+    }
+}
+
+/// 迁移要求连接执行的输出
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    SendSyn,
+    SendSynAck,
+    SendAck,
+    SendFin,
+    ArmTimeWait,    // 启动 TIME_WAIT 定时器
+}
+
+/// 一次合法迁移
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub from: ConnState,
+    pub to: ConnState,
+    pub outputs: &'static [Output],
+}
+
+/// 当前状态下不允许的输入
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTransition {
+    pub state: ConnState,
+    pub input: Input,
+}
+
+impl fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid transition: {:?} in state {:?}", self.input, self.state)
+    }
+}
+
+impl std::error::Error for InvalidTransition {}
+
+/// 迁移表：返回新状态与输出，非法组合返回 None
+pub fn transition(state: ConnState, input: Input) -> Option<(ConnState, &'static [Output])> {
+    use Action::*;
+    use ConnState::*;
+    use Input::{Action as A, Segment as S, SynAck};
+    use SegmentType::{Ack, Data, Fin, NewConnId, PathChallenge, PathResponse, Ping, Pong, Rst, Skip, StatsReply, StatsRequest, Syn};
+
+    let next: (ConnState, &'static [Output]) = match (state, input) {
+        // 打开
+        (Closed, A(Connect)) => (SynSent, &[Output::SendSyn]),
+        (Closed, S(Syn)) => (SynReceived, &[Output::SendSynAck]),
+        (SynSent, SynAck) => (Established, &[Output::SendAck]),
+        (SynSent, S(Syn)) => (SynReceived, &[Output::SendSynAck]),        // 同时打开：双方的 SYN 交错
+        (SynSent, A(Close)) => (Closed, &[]),
+        (SynReceived, S(Syn)) => (SynReceived, &[Output::SendSynAck]),     // 重传的 SYN
+        (SynReceived, SynAck) => (Established, &[Output::SendAck]),        // 同时打开：对端的 SYN-ACK
+        (SynReceived, S(Ack | Data | Skip | NewConnId | Ping | Pong)) => (Established, &[]),  // 确认丢失时数据同样完成握手
+        (SynReceived, S(Fin)) => (CloseWait, &[Output::SendAck]),
+        (SynReceived, A(Close)) => (FinWait, &[Output::SendFin]),
+
+        // 已建立
+        (Established, S(Data | Skip | NewConnId | Ack | Ping | Pong | StatsRequest | StatsReply | PathChallenge | PathResponse)) => (Established, &[]),
+        (Established, SynAck) => (Established, &[Output::SendAck]),       // 重传的 SYN-ACK：本端的确认丢失
+        (Established, S(Syn)) => (Established, &[Output::SendAck]),       // 同时打开时迟到的 SYN
+        (Established, S(Fin)) => (CloseWait, &[Output::SendAck]),
+        (Established, A(Close)) => (FinWait, &[Output::SendFin]),
+
+        // 本端先关闭：仍可接收，直到对端的 FIN
+        (FinWait, S(Data | Skip | NewConnId | Ack | Ping | Pong | StatsRequest | StatsReply | PathChallenge | PathResponse)) => (FinWait, &[]),
+        (FinWait, A(FinAcked)) => (FinWait, &[]),
+        (FinWait, S(Fin)) => (TimeWait, &[Output::SendAck, Output::ArmTimeWait]),
+
+        // 对端先关闭：不会再有新数据，迟到的重传照常吸收
+        (CloseWait, S(Data | Skip | NewConnId | Ack | Ping | Pong | StatsRequest | StatsReply | PathChallenge | PathResponse)) => (CloseWait, &[]),
+        (CloseWait, S(Fin)) => (CloseWait, &[Output::SendAck]),
+        (CloseWait, A(Close)) => (LastAck, &[Output::SendFin]),
+        (LastAck, S(Data | Skip | NewConnId | Ack | Ping | Pong | StatsRequest | StatsReply | PathChallenge | PathResponse)) => (LastAck, &[]),
+        (LastAck, S(Fin)) => (LastAck, &[Output::SendAck]),
+        (LastAck, A(FinAcked)) => (Closed, &[]),
+
+        // TIME_WAIT：重复确认重传的 FIN，到期后关闭
+        (TimeWait, S(Fin)) => (TimeWait, &[Output::SendAck]),
+        (TimeWait, S(Data | Skip | NewConnId | Ack | Ping | Pong | StatsRequest | StatsReply | PathChallenge | PathResponse)) => (TimeWait, &[]),
+        (TimeWait, A(FinAcked)) => (TimeWait, &[]),
+        (TimeWait, A(TimeWaitExpired)) => (Closed, &[]),
+        (TimeWait, S(Rst)) => (Closed, &[]),
+
+        // 任何活跃状态都可以中止，对端复位同样终止连接
+        (SynSent | SynReceived | Established | FinWait | CloseWait | LastAck | TimeWait, A(Abort)) => (Aborted, &[]),
+        (SynSent | SynReceived | Established | FinWait | CloseWait | LastAck, S(Rst)) => (Aborted, &[]),
+
+        _ => return None,
+    };
+    Some(next)
+}
+
+/// 持有当前状态并校验每一次迁移
+#[derive(Debug, Clone)]
+pub struct StateMachine {
+    state: ConnState,
+}
+
+impl StateMachine {
+    pub fn new() -> Self {
+        Self { state: ConnState::Closed }
+    }
+
+    /// 接续从另一个进程导出的连接（见 `session` 模块）：直接处于 Established
+    pub fn established() -> Self {
+        Self { state: ConnState::Established }
+    }
+
+    pub fn state(&self) -> ConnState {
+        self.state
+    }
+
+    /// 收到一个段
+    pub fn on_segment(&mut self, segment_type: SegmentType) -> Result<Transition, InvalidTransition> {
+        self.apply(Input::Segment(segment_type))
+    }
+
+    /// 收到一个携带确认的 Syn（SYN-ACK）
+    pub fn on_syn_ack(&mut self) -> Result<Transition, InvalidTransition> {
+        self.apply(Input::SynAck)
+    }
+
+    /// 执行一个本地动作
+    pub fn on_action(&mut self, action: Action) -> Result<Transition, InvalidTransition> {
+        self.apply(Input::Action(action))
+    }
+
+    /// 任意输入，如 `Input::from_segment` 的结果
+    pub fn apply(&mut self, input: Input) -> Result<Transition, InvalidTransition> {
+        let from = self.state;
+        let (to, outputs) = transition(from, input).ok_or(InvalidTransition { state: from, input })?;
+        self.state = to;
+        Ok(Transition { from, to, outputs })
+    }
+}
+
+impl Default for StateMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seq::SeqNum;
+    use ConnState::*;
+
+    const SEGMENTS: [SegmentType; 14] = [
+        SegmentType::Data,
+        SegmentType::Ack,
+        SegmentType::Syn,
+        SegmentType::Ping,
+        SegmentType::Pong,
+        SegmentType::Fin,
+        SegmentType::Rst,
+        SegmentType::Retry,
+        SegmentType::StatsRequest,
+        SegmentType::StatsReply,
+        SegmentType::PathChallenge,
+        SegmentType::PathResponse,
+        SegmentType::Skip,
+        SegmentType::NewConnId,
+    ];
+
+    fn all_inputs() -> Vec<Input> {
+        SEGMENTS
+            .iter()
+            .map(|&t| Input::Segment(t))
+            .chain([Input::SynAck])
+            .chain(Action::ALL.iter().map(|&a| Input::Action(a)))
+            .collect()
+    }
+
+    fn seg(t: SegmentType) -> Input {
+        Input::Segment(t)
+    }
+
+    fn act(a: Action) -> Input {
+        Input::Action(a)
+    }
+
+    // 完整的合法迁移清单；不在清单中的组合都必须被拒绝
+    fn legal() -> Vec<(ConnState, Input, ConnState, &'static [Output])> {
+        use Output::*;
+        use SegmentType::{Ack, Data, Fin, NewConnId, Ping, Pong, Rst, Skip, Syn};
+        let mut table: Vec<(ConnState, Input, ConnState, &'static [Output])> = vec![
+            (Closed, act(Action::Connect), SynSent, &[SendSyn]),
+            (Closed, seg(Syn), SynReceived, &[SendSynAck]),
+            (SynSent, Input::SynAck, Established, &[SendAck]),
+            (SynSent, seg(Syn), SynReceived, &[SendSynAck]),
+            (SynSent, act(Action::Close), Closed, &[]),
+            (SynReceived, seg(Syn), SynReceived, &[SendSynAck]),
+            (SynReceived, Input::SynAck, Established, &[SendAck]),
+            (SynReceived, seg(Fin), CloseWait, &[SendAck]),
+            (SynReceived, act(Action::Close), FinWait, &[SendFin]),
+            (Established, Input::SynAck, Established, &[SendAck]),
+            (Established, seg(Syn), Established, &[SendAck]),
+            (Established, seg(Fin), CloseWait, &[SendAck]),
+            (Established, act(Action::Close), FinWait, &[SendFin]),
+            (FinWait, act(Action::FinAcked), FinWait, &[]),
+            (FinWait, seg(Fin), TimeWait, &[SendAck, ArmTimeWait]),
+            (CloseWait, seg(Fin), CloseWait, &[SendAck]),
+            (CloseWait, act(Action::Close), LastAck, &[SendFin]),
+            (LastAck, seg(Fin), LastAck, &[SendAck]),
+            (LastAck, act(Action::FinAcked), Closed, &[]),
+            (TimeWait, seg(Fin), TimeWait, &[SendAck]),
+            (TimeWait, act(Action::FinAcked), TimeWait, &[]),
+            (TimeWait, act(Action::TimeWaitExpired), Closed, &[]),
+            (TimeWait, seg(Rst), Closed, &[]),
+        ];
+        for t in [Data, Skip, NewConnId, Ack, Ping, Pong] {
+            table.push((SynReceived, seg(t), Established, &[]));
+            for state in [Established, FinWait, CloseWait, LastAck, TimeWait] {
+                table.push((state, seg(t), state, &[]));
+            }
+        }
+        // 统计查询与路径验证不能完成握手
+        for t in [SegmentType::StatsRequest, SegmentType::StatsReply, SegmentType::PathChallenge, SegmentType::PathResponse] {
+            for state in [Established, FinWait, CloseWait, LastAck, TimeWait] {
+                table.push((state, seg(t), state, &[]));
+            }
+        }
+        for state in [SynSent, SynReceived, Established, FinWait, CloseWait, LastAck, TimeWait] {
+            table.push((state, act(Action::Abort), Aborted, &[]));
+        }
+        for state in [SynSent, SynReceived, Established, FinWait, CloseWait, LastAck] {
+            table.push((state, seg(Rst), Aborted, &[]));
+        }
+        table
+    }
+
+    #[test]
+    fn test_every_state_input_pair() {
+        let legal = legal();
+        let mut checked = 0;
+        for state in ConnState::ALL {
+            for input in all_inputs() {
+                let expected = legal.iter().find(|(s, i, _, _)| *s == state && *i == input);
+                let mut machine = StateMachine { state };
+                let result = match input {
+                    Input::Segment(t) => machine.on_segment(t),
+                    Input::SynAck => machine.on_syn_ack(),
+                    Input::Action(a) => machine.on_action(a),
+                };
+                match expected {
+                    Some(&(_, _, to, outputs)) => {
+                        assert_eq!(result, Ok(Transition { from: state, to, outputs }), "{:?} + {:?}", state, input);
+                        assert_eq!(machine.state(), to);
+                    }
+                    None => {
+                        assert_eq!(result, Err(InvalidTransition { state, input }), "{:?} + {:?}", state, input);
+                        assert_eq!(machine.state(), state, "rejected input must not change state");
+                    }
+                }
+                checked += 1;
+            }
+        }
+        assert_eq!(checked, ConnState::ALL.len() * (SEGMENTS.len() + 1 + Action::ALL.len()));
+    }
+
+    #[test]
+    fn test_full_lifecycle() {
+        // 客户端：主动打开、主动关闭
+        let mut client = StateMachine::new();
+        assert_eq!(client.on_action(Action::Connect).unwrap().outputs, &[Output::SendSyn]);
+        assert_eq!(client.on_syn_ack().unwrap().to, Established);
+        assert_eq!(client.on_action(Action::Close).unwrap().outputs, &[Output::SendFin]);
+        client.on_action(Action::FinAcked).unwrap();
+        assert_eq!(client.on_segment(SegmentType::Fin).unwrap().to, TimeWait);
+        assert_eq!(client.on_action(Action::TimeWaitExpired).unwrap().to, Closed);
+
+        // 服务端：被动打开、被动关闭
+        let mut server = StateMachine::new();
+        assert_eq!(server.on_segment(SegmentType::Syn).unwrap().outputs, &[Output::SendSynAck]);
+        assert_eq!(server.on_segment(SegmentType::Ack).unwrap().to, Established);
+        assert_eq!(server.on_segment(SegmentType::Fin).unwrap().to, CloseWait);
+        assert!(server.state().can_send() && !server.state().can_receive());
+        assert_eq!(server.on_action(Action::Close).unwrap().to, LastAck);
+        assert_eq!(server.on_action(Action::FinAcked).unwrap().to, Closed);
+
+        let err = server.on_segment(SegmentType::Data).unwrap_err();
+        assert_eq!(err.to_string(), "invalid transition: Segment(Data) in state Closed");
+    }
+
+    #[test]
+    fn test_simultaneous_open() {
+        // 双方都已发出 SYN，各自先收到对端的 SYN，再收到对端的 SYN-ACK
+        let (mut a, mut b) = (StateMachine::new(), StateMachine::new());
+        a.on_action(Action::Connect).unwrap();
+        b.on_action(Action::Connect).unwrap();
+        for machine in [&mut a, &mut b] {
+            let transition = machine.on_segment(SegmentType::Syn).unwrap();
+            assert_eq!((transition.to, transition.outputs), (SynReceived, &[Output::SendSynAck][..]));
+        }
+        for machine in [&mut a, &mut b] {
+            assert_eq!(machine.on_syn_ack().unwrap().to, Established);
+        }
+
+        // 一方的 SYN 丢失：另一方直接从 SynSent 收到 SYN-ACK，对端以确认完成握手
+        let (mut a, mut b) = (StateMachine::new(), StateMachine::new());
+        a.on_action(Action::Connect).unwrap();
+        b.on_action(Action::Connect).unwrap();
+        b.on_segment(SegmentType::Syn).unwrap();
+        assert_eq!(a.on_syn_ack().unwrap().outputs, &[Output::SendAck]);
+        assert_eq!(b.on_segment(SegmentType::Ack).unwrap().to, Established);
+    }
+
+    #[test]
+    fn test_input_from_segment() {
+        let syn = Segment::builder(SegmentType::Syn).data_seq(1u64).build().unwrap();
+        let syn_ack = Segment::builder(SegmentType::Syn).data_seq(1u64).ack(SeqNum::new(9)).build().unwrap();
+        assert_eq!(Input::from_segment(&syn), Input::Segment(SegmentType::Syn));
+        assert_eq!(Input::from_segment(&syn_ack), Input::SynAck);
+        assert_eq!(Input::from_segment(&Segment::ping(1)), Input::Segment(SegmentType::Ping));
+    }
+}
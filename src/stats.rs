@@ -0,0 +1,386 @@
+//! 连接统计快照
+//! 汇总发送端与接收端的状态，供监控与调试使用。连接的驱动任务每处理完一个事件就把快照逐项写进
+//! `StatsCell` 的原子变量（relaxed），`Connection::stats` 只读这些原子变量，不会与数据路径争用连接的锁；
+//! 各项单独读出，同一快照中的字段之间可能相差一个事件。
+//!
+//! 对端以 StatsRequest 查询时，连接以 StatsReply 回送 `PeerStats`：快照中监控关心的几项，编码为
+//! `version(1) | uptime(8) | srtt(8) | cwnd(8) | bytes_sent(8) | bytes_received(8) | segments_retransmitted(8)`（大端序，时间以微秒计）。
+//! 新版本只在末尾追加字段，解码时读出已知的前缀，忽略其后的内容。
+
+use crate::receiver::{Receiver, ReceiverStats};
+use crate::segment::SegmentError;
+use crate::sender::{Sender, SenderStats};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 某一时刻的连接统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionStats {
+    pub local_addr: Option<SocketAddr>, // 由 `Connection::stats` 填入，直接从发送端与接收端汇总时为 None
+    pub peer_addr: Option<SocketAddr>,
+    pub cwnd: usize,                // 拥塞窗口（段数）
+    pub ssthresh: usize,            // 慢启动阈值，尚未发生丢包时为 usize::MAX
+    pub send_window: usize,         // 有效发送窗口
+    pub peer_window: usize,         // 对端通告窗口（段数），尚未收到通告时为 usize::MAX
+    pub in_flight: usize,           // 在途段数
+    pub in_flight_bytes: usize,
+    pub send_queue_bytes: usize,    // 发送队列中等待窗口与已发送未确认的字节数
+    pub srtt: Option<Duration>,
+    pub rttvar: Duration,
+    pub rto: Duration,
+    pub min_rtt: Option<Duration>,  // 连接以来最小的 RTT 样本
+    pub delivery_rate_bps: u64,     // 最近几个往返中的最大投递速率（比特每秒，只计数据体），还没有样本时为 0
+    pub fast_retransmits: u64,
+    pub timeouts: u64,              // 触发重传的 RTO 超时次数
+    pub messages_discarded: u64,    // 超出分片重组的限制或过期而被丢弃的未收齐消息数（所有流）
+    pub memory_used: usize,         // 连接各部分合计的内存占用（见 `budget` 模块），按粒度取整
+    pub stale_conn_id: u64,         // 携带已退役的连接 ID 而被丢弃的段数（见 `rotation` 模块）
+    pub liveness_pings: u64,        // 超过 `keepalive_interval` 没有收到任何段而发出的存活探测数
+    pub path_pings: u64,            // 超过 `path_keepalive_interval` 没有发出任何段而发出的路径探测数
+    pub path_challenges: u64,       // 对端在新地址上验证本端的次数：本端的地址在对端看来变了，多半是 NAT 换了映射
+    pub nodelay: bool,              // 关闭了小写入合并（`LinkConfig::nodelay`，可由 `Connection::set_option` 调整）
+    pub pacing: bool,               // 按速率放出新数据段（`LinkConfig::pacing`，同上）
+    pub sender: SenderStats,
+    pub receiver: ReceiverStats,
+}
+
+impl ConnectionStats {
+    pub fn collect(sender: &Sender, receiver: &Receiver) -> Self {
+        Self {
+            local_addr: None,
+            peer_addr: None,
+            cwnd: sender.congestion().window(),
+            ssthresh: sender.congestion().ssthresh(),
+            send_window: sender.window(),
+            peer_window: sender.peer_window(),
+            in_flight: sender.in_flight(),
+            in_flight_bytes: sender.in_flight_bytes(),
+            send_queue_bytes: sender.queued_bytes(),
+            srtt: sender.rtt().srtt(),
+            rttvar: sender.rtt().rttvar(),
+            rto: sender.rtt().rto(),
+            min_rtt: sender.rtt().min_rtt(),
+            delivery_rate_bps: sender.delivery_rate().bandwidth().saturating_mul(8),
+            fast_retransmits: sender.fast_retransmits(),
+            timeouts: sender.timeouts(),
+            messages_discarded: 0,
+            memory_used: 0,
+            stale_conn_id: 0,
+            liveness_pings: 0,
+            path_pings: 0,
+            path_challenges: 0,
+            nodelay: sender.nodelay(),
+            pacing: sender.pacing(),
+            sender: sender.stats(),
+            receiver: receiver.stats(),
+        }
+    }
+}
+
+/// 对端通过 StatsReply 报告的它一侧的连接统计（流 0）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerStats {
+    pub uptime: Duration,           // 连接建立以来的时间
+    pub srtt: Option<Duration>,
+    pub cwnd: usize,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub segments_retransmitted: u64,
+}
+
+/// 本端编码的统计版本
+pub const STATS_VERSION: u8 = 1;
+
+// 版本 1 的编码长度
+const STATS_V1_LEN: usize = 1 + 6 * 8;
+
+impl PeerStats {
+    pub fn new(stats: &ConnectionStats, uptime: Duration) -> Self {
+        Self {
+            uptime,
+            srtt: stats.srtt,
+            cwnd: stats.cwnd,
+            bytes_sent: stats.sender.bytes_sent,
+            bytes_received: stats.receiver.bytes_received,
+            segments_retransmitted: stats.sender.segments_retransmitted,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let micros = |value: Duration| u64::try_from(value.as_micros()).unwrap_or(u64::MAX);
+        let mut out = Vec::with_capacity(STATS_V1_LEN);
+        out.push(STATS_VERSION);
+        for field in [
+            micros(self.uptime),
+            // 微秒数加一，0 表示还没有样本
+            self.srtt.map_or(0, |srtt| micros(srtt).saturating_add(1)),
+            from_usize(self.cwnd),
+            self.bytes_sent,
+            self.bytes_received,
+            self.segments_retransmitted,
+        ] {
+            out.extend_from_slice(&field.to_be_bytes());
+        }
+        out
+    }
+
+    /// 版本号为 0 或短于版本 1 的长度时返回 `InvalidStats`
+    pub fn decode(bytes: &[u8]) -> Result<Self, SegmentError> {
+        let version = bytes.first().copied().unwrap_or(0);
+        if version == 0 || bytes.len() < STATS_V1_LEN {
+            return Err(SegmentError::InvalidStats { version, len: bytes.len() });
+        }
+        let field = |i: usize| {
+            let start = 1 + i * 8;
+            u64::from_be_bytes(bytes[start..start + 8].try_into().expect("field is 8 bytes"))
+        };
+        Ok(Self {
+            uptime: Duration::from_micros(field(0)),
+            srtt: field(1).checked_sub(1).map(Duration::from_micros),
+            cwnd: to_usize(field(2)),
+            bytes_sent: field(3),
+            bytes_received: field(4),
+            segments_retransmitted: field(5),
+        })
+    }
+}
+
+/// 连接统计的原子副本：驱动任务发布，任意线程读取
+#[derive(Debug, Default)]
+pub(crate) struct StatsCell {
+    cwnd: AtomicU64,
+    ssthresh: AtomicU64,
+    send_window: AtomicU64,
+    peer_window: AtomicU64,
+    in_flight: AtomicU64,
+    in_flight_bytes: AtomicU64,
+    send_queue_bytes: AtomicU64,
+    srtt: AtomicU64,            // 纳秒数加一，0 表示还没有样本
+    rttvar: AtomicU64,          // 纳秒
+    rto: AtomicU64,             // 纳秒
+    min_rtt: AtomicU64,         // 纳秒数加一，0 表示还没有样本
+    delivery_rate_bps: AtomicU64,
+    fast_retransmits: AtomicU64,
+    timeouts: AtomicU64,
+    messages_discarded: AtomicU64,
+    memory_used: AtomicU64,
+    stale_conn_id: AtomicU64,
+    liveness_pings: AtomicU64,
+    path_pings: AtomicU64,
+    path_challenges: AtomicU64,
+    nodelay: AtomicU64,         // 0 或 1
+    pacing: AtomicU64,          // 0 或 1
+    segments_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_acked: AtomicU64,
+    segments_retransmitted: AtomicU64,
+    duplicate_acks: AtomicU64,
+    ecn_reductions: AtomicU64,
+    expired: AtomicU64,
+    segments_received: AtomicU64,
+    bytes_received: AtomicU64,
+    duplicates_received: AtomicU64,
+    out_of_order_received: AtomicU64,
+    dropped: AtomicU64,
+    ce_received: AtomicU64,
+    skipped: AtomicU64,
+}
+
+// 超出 u64 的值（如 usize::MAX 的窗口）按饱和处理
+fn from_usize(value: usize) -> u64 {
+    u64::try_from(value).unwrap_or(u64::MAX)
+}
+
+fn to_usize(value: u64) -> usize {
+    usize::try_from(value).unwrap_or(usize::MAX)
+}
+
+fn from_duration(value: Duration) -> u64 {
+    u64::try_from(value.as_nanos()).unwrap_or(u64::MAX)
+}
+
+impl StatsCell {
+    pub(crate) fn publish(&self, stats: &ConnectionStats) {
+        let store = |cell: &AtomicU64, value: u64| cell.store(value, Ordering::Relaxed);
+        store(&self.cwnd, from_usize(stats.cwnd));
+        store(&self.ssthresh, from_usize(stats.ssthresh));
+        store(&self.send_window, from_usize(stats.send_window));
+        store(&self.peer_window, from_usize(stats.peer_window));
+        store(&self.in_flight, from_usize(stats.in_flight));
+        store(&self.in_flight_bytes, from_usize(stats.in_flight_bytes));
+        store(&self.send_queue_bytes, from_usize(stats.send_queue_bytes));
+        store(&self.srtt, stats.srtt.map_or(0, |srtt| from_duration(srtt).saturating_add(1)));
+        store(&self.rttvar, from_duration(stats.rttvar));
+        store(&self.rto, from_duration(stats.rto));
+        store(&self.min_rtt, stats.min_rtt.map_or(0, |min_rtt| from_duration(min_rtt).saturating_add(1)));
+        store(&self.delivery_rate_bps, stats.delivery_rate_bps);
+        store(&self.fast_retransmits, stats.fast_retransmits);
+        store(&self.timeouts, stats.timeouts);
+        store(&self.messages_discarded, stats.messages_discarded);
+        store(&self.memory_used, from_usize(stats.memory_used));
+        store(&self.stale_conn_id, stats.stale_conn_id);
+        store(&self.liveness_pings, stats.liveness_pings);
+        store(&self.path_pings, stats.path_pings);
+        store(&self.path_challenges, stats.path_challenges);
+        store(&self.nodelay, u64::from(stats.nodelay));
+        store(&self.pacing, u64::from(stats.pacing));
+        store(&self.segments_sent, stats.sender.segments_sent);
+        store(&self.bytes_sent, stats.sender.bytes_sent);
+        store(&self.bytes_acked, stats.sender.bytes_acked);
+        store(&self.segments_retransmitted, stats.sender.segments_retransmitted);
+        store(&self.duplicate_acks, stats.sender.duplicate_acks);
+        store(&self.ecn_reductions, stats.sender.ecn_reductions);
+        store(&self.expired, stats.sender.expired);
+        store(&self.segments_received, stats.receiver.segments_received);
+        store(&self.bytes_received, stats.receiver.bytes_received);
+        store(&self.duplicates_received, stats.receiver.duplicates_received);
+        store(&self.out_of_order_received, stats.receiver.out_of_order_received);
+        store(&self.dropped, stats.receiver.dropped);
+        store(&self.ce_received, stats.receiver.ce_received);
+        store(&self.skipped, stats.receiver.skipped);
+    }
+
+    pub(crate) fn load(&self, local_addr: Option<SocketAddr>, peer_addr: SocketAddr) -> ConnectionStats {
+        let load = |cell: &AtomicU64| cell.load(Ordering::Relaxed);
+        ConnectionStats {
+            local_addr,
+            peer_addr: Some(peer_addr),
+            cwnd: to_usize(load(&self.cwnd)),
+            ssthresh: to_usize(load(&self.ssthresh)),
+            send_window: to_usize(load(&self.send_window)),
+            peer_window: to_usize(load(&self.peer_window)),
+            in_flight: to_usize(load(&self.in_flight)),
+            in_flight_bytes: to_usize(load(&self.in_flight_bytes)),
+            send_queue_bytes: to_usize(load(&self.send_queue_bytes)),
+            srtt: load(&self.srtt).checked_sub(1).map(Duration::from_nanos),
+            rttvar: Duration::from_nanos(load(&self.rttvar)),
+            rto: Duration::from_nanos(load(&self.rto)),
+            min_rtt: load(&self.min_rtt).checked_sub(1).map(Duration::from_nanos),
+            delivery_rate_bps: load(&self.delivery_rate_bps),
+            fast_retransmits: load(&self.fast_retransmits),
+            timeouts: load(&self.timeouts),
+            messages_discarded: load(&self.messages_discarded),
+            memory_used: to_usize(load(&self.memory_used)),
+            stale_conn_id: load(&self.stale_conn_id),
+            liveness_pings: load(&self.liveness_pings),
+            path_pings: load(&self.path_pings),
+            path_challenges: load(&self.path_challenges),
+            nodelay: load(&self.nodelay) != 0,
+            pacing: load(&self.pacing) != 0,
+            sender: SenderStats {
+                segments_sent: load(&self.segments_sent),
+                bytes_sent: load(&self.bytes_sent),
+                bytes_acked: load(&self.bytes_acked),
+                segments_retransmitted: load(&self.segments_retransmitted),
+                duplicate_acks: load(&self.duplicate_acks),
+                ecn_reductions: load(&self.ecn_reductions),
+                expired: load(&self.expired),
+            },
+            receiver: ReceiverStats {
+                segments_received: load(&self.segments_received),
+                bytes_received: load(&self.bytes_received),
+                duplicates_received: load(&self.duplicates_received),
+                out_of_order_received: load(&self.out_of_order_received),
+                dropped: load(&self.dropped),
+                ce_received: load(&self.ce_received),
+                skipped: load(&self.skipped),
+            },
+        }
+    }
+}
+
+/// 监听器的连接表统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ListenerStats {
+    pub connections: usize,     // 连接表中已建立的连接数
+    pub half_open: usize,       // 未完成的握手数
+    pub evicted: u64,           // 因空闲超时被回收的连接数
+    pub dropped: u64,           // 连接的入站队列已满而被丢弃的数据报数
+    pub migrated: u64,          // 对端迁移到新地址的次数
+    pub unvalidated: u64,       // 新地址没有在 `path_validation_timeout` 内回应 PathChallenge、迁移被放弃的次数
+    pub malformed: u64,         // 不属于任何连接且无法解析的数据报数
+    pub truncated: u64,         // 超出接收缓冲区被截断而丢弃的数据报数
+    pub denied: u64,            // 来源不被访问控制列表允许而静默丢弃的数据报数
+    pub tombstones: usize,      // 已结束、仍在吸收迟到重传的连接数
+    pub tombstones_evicted: u64,    // 墓碑表已满、未到期就被淘汰的墓碑数（`LinkConfig::max_tombstones`）
+}
+
+// 合并多个接收套接字的统计
+impl std::ops::AddAssign for ListenerStats {
+    fn add_assign(&mut self, other: Self) {
+        self.connections += other.connections;
+        self.half_open += other.half_open;
+        self.evicted += other.evicted;
+        self.dropped += other.dropped;
+        self.migrated += other.migrated;
+        self.unvalidated += other.unvalidated;
+        self.malformed += other.malformed;
+        self.truncated += other.truncated;
+        self.denied += other.denied;
+        self.tombstones += other.tombstones;
+        self.tombstones_evicted += other.tombstones_evicted;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LinkConfig;
+    use crate::seq::SeqNum;
+    use bytes::Bytes;
+    use std::time::Instant;
+
+    #[test]
+    fn test_cell_round_trip() {
+        let config = LinkConfig::default();
+        let mut sender = Sender::new(SeqNum::new(1), &config);
+        let receiver = Receiver::new(SeqNum::new(1), &config);
+        let cell = StatsCell::default();
+        let peer: SocketAddr = "10.0.0.2:2".parse().unwrap();
+
+        // 还没有 RTT 样本，窗口为 usize::MAX 的字段原样读回
+        let stats = ConnectionStats::collect(&sender, &receiver);
+        cell.publish(&stats);
+        assert_eq!(cell.load(None, peer), ConnectionStats { peer_addr: Some(peer), ..stats });
+
+        let t0 = Instant::now();
+        sender.write(Bytes::from_static(b"hello"), t0).unwrap();
+        sender.on_ack(SeqNum::new(1), t0 + std::time::Duration::from_millis(30));
+        let stats = ConnectionStats { messages_discarded: 3, ..ConnectionStats::collect(&sender, &receiver) };
+        assert_eq!(stats.srtt, Some(std::time::Duration::from_millis(30)));
+        assert_eq!(stats.min_rtt, stats.srtt);
+        // 5 字节在 30ms 内送达
+        assert_eq!(stats.delivery_rate_bps, 5 * 1000 / 30 * 8);
+        cell.publish(&stats);
+        assert_eq!(cell.load(None, peer), ConnectionStats { peer_addr: Some(peer), ..stats });
+    }
+
+    #[test]
+    fn test_peer_stats_encoding() {
+        let stats = PeerStats {
+            uptime: Duration::from_secs(90),
+            srtt: Some(Duration::from_micros(1500)),
+            cwnd: 12,
+            bytes_sent: 1 << 40,
+            bytes_received: 7,
+            segments_retransmitted: 3,
+        };
+        let encoded = stats.encode();
+        assert_eq!((encoded.len(), encoded[0]), (STATS_V1_LEN, STATS_VERSION));
+        assert_eq!(PeerStats::decode(&encoded), Ok(stats));
+        let unsampled = PeerStats { srtt: None, ..stats };
+        assert_eq!(PeerStats::decode(&unsampled.encode()), Ok(unsampled));
+
+        // 新版本追加的字段被忽略
+        let mut newer = encoded.clone();
+        newer[0] = 2;
+        newer.extend_from_slice(&[0xFF; 16]);
+        assert_eq!(PeerStats::decode(&newer), Ok(stats));
+
+        // 截短、空与版本号为 0 的编码都是错误而不是 panic
+        for (bytes, version) in [(&encoded[..STATS_V1_LEN - 1], 1), (&[][..], 0), (&[0; STATS_V1_LEN][..], 0)] {
+            assert_eq!(PeerStats::decode(bytes), Err(SegmentError::InvalidStats { version, len: bytes.len() }));
+        }
+    }
+}
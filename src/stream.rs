@@ -0,0 +1,434 @@
+//! 字节流适配与附加流
+//! `LinkStreamIo`（`Connection::into_byte_stream`）在消息连接之上实现 `AsyncRead`/`AsyncWrite`，只有字节语义：
+//! 写入按连接当前的有效 MSS 切成数据段交给可靠层，小写入由 Nagle 合并，窗口已满时返回 `Pending`（不在本地无限缓存）；
+//! 读取按序取出数据体字节，一个段可以分多次读完，一次读取也可以跨越已经就绪的多个段，不反映对端的写入边界。
+//! `poll_flush` 等待已写入的数据全部被确认，`poll_shutdown` 在此之后发送 FIN 并等待它被确认；
+//! 对端的 FIN 之前的数据读完后读取返回 EOF。转换之后经 `get_ref` 按消息收发主流返回 `LinkError::ByteStreamMode`。
+//! `LinkStream` 是 `Connection::open_stream`/`accept_stream` 打开的附加流，收发方式与连接相同，
+//! 也以同样的方式实现字节流接口。每个流有自己的发送窗口与对端按流通告的接收窗口，窗口满了只挡住这个流的 `send`；
+//! 各个流排队的数据按优先级轮流发出（见 `schedule` 模块），一个流的批量发送不会让其他流的消息排在整窗数据之后。
+
+use crate::connection::{Connection, Shared};
+use crate::endpoint::MAIN_STREAM;
+use crate::error::LinkError;
+use crate::segment::Segment;
+use crate::sender::SendOptions;
+use bytes::{Buf, Bytes};
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, ready};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+// 单个数据段的最大数据体
+fn max_payload(mss: usize) -> usize {
+    mss.saturating_sub(Segment::FIXED_HEADER_LEN).max(1)
+}
+
+// 从流 `id` 读出字节：先读完当前段剩余的部分，`buf` 还有空间时接着读已经就绪的段，不等待；
+// 空消息不携带字节，跳过以免被误读为 EOF。EOF 与错误留给下一次读取报告
+fn poll_read_stream(
+    shared: &Shared,
+    id: u16,
+    read_buf: &mut Bytes,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+) -> Poll<io::Result<()>> {
+    while read_buf.is_empty() {
+        match ready!(shared.poll_recv(id, cx))? {
+            Some(data) => *read_buf = data,
+            None => return Poll::Ready(Ok(())),
+        }
+    }
+    loop {
+        let n = read_buf.len().min(buf.remaining());
+        buf.put_slice(&read_buf[..n]);
+        read_buf.advance(n);
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        match shared.poll_recv(id, cx) {
+            Poll::Ready(Ok(Some(data))) => *read_buf = data,
+            _ => return Poll::Ready(Ok(())),
+        }
+    }
+}
+
+// 向流 `id` 写入不超过一个数据段的字节
+// 段的大小取连接当前的有效 MSS，路径 MTU 探测可能随时改变它
+fn poll_write_stream(shared: &Shared, id: u16, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+    if buf.is_empty() {
+        return Poll::Ready(Ok(0));
+    }
+    let n = buf.len().min(max_payload(shared.mss()));
+    let mut chunk = Some(Bytes::copy_from_slice(&buf[..n]));
+    ready!(shared.poll_send(id, cx, &mut chunk, SendOptions::default()))?;
+    Poll::Ready(Ok(n))
+}
+
+/// 以字节流方式使用的连接
+#[derive(Debug)]
+pub struct LinkStreamIo {
+    connection: Connection,
+    read_buf: Bytes,    // 当前段中尚未读出的部分
+}
+
+impl LinkStreamIo {
+    pub(crate) fn new(connection: Connection) -> Self {
+        Self { connection, read_buf: Bytes::new() }
+    }
+
+    /// 底层连接，用于统计、地址与附加流；主流不能再按消息收发
+    pub fn get_ref(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// 取回底层连接（仍处于字节流模式，用于关闭）；尚未读出的字节被丢弃
+    pub fn into_inner(self) -> Connection {
+        self.connection
+    }
+}
+
+impl AsyncRead for LinkStreamIo {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        poll_read_stream(this.connection.shared(), MAIN_STREAM, &mut this.read_buf, cx, buf)
+    }
+}
+
+impl AsyncWrite for LinkStreamIo {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        poll_write_stream(self.connection.shared(), MAIN_STREAM, cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.connection.shared().poll_flush(MAIN_STREAM, cx).map_err(io::Error::from)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.connection.shared().poll_shutdown(MAIN_STREAM, cx).map_err(io::Error::from)
+    }
+}
+
+/// 连接上的一个附加流：独立的序列号空间、发送窗口与重排缓冲区，一个流的丢包与关闭不影响其他流。
+/// 可以按消息收发，也可以作为字节流使用，同一个流上不要混用两种方式（`recv` 看不到读了一半的段）。
+/// 句柄被丢弃时写方向随之关闭，之后到达的数据被丢弃；流依赖所在的连接，连接句柄被丢弃后不再工作。
+#[derive(Debug)]
+pub struct LinkStream {
+    shared: Arc<Shared>,
+    id: u16,
+    read_buf: Bytes,    // 字节流读取时当前段中尚未读出的部分
+}
+
+impl LinkStream {
+    pub(crate) fn new(shared: Arc<Shared>, id: u16) -> Self {
+        Self { shared, id, read_buf: Bytes::new() }
+    }
+
+    /// 流 ID：客户端打开的流为奇数，服务端打开的为偶数
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// 语义同 `Connection::send`，只占用这个流的发送队列与窗口
+    pub async fn send(&self, data: Bytes) -> Result<(), LinkError> {
+        let mut data = Some(data);
+        poll_fn(|cx| self.shared.poll_send(self.id, cx, &mut data, SendOptions::default())).await
+    }
+
+    /// 语义同 `Connection::send_with`
+    pub async fn send_with(&self, data: Bytes, options: SendOptions) -> Result<(), LinkError> {
+        let mut data = Some(data);
+        poll_fn(|cx| self.shared.poll_send(self.id, cx, &mut data, options)).await
+    }
+
+    /// 语义同 `Connection::try_send`
+    pub fn try_send(&self, data: Bytes) -> Result<(), LinkError> {
+        self.shared.try_send(self.id, data)
+    }
+
+    /// 语义同 `Connection::recv`：对端关闭这个流的写方向后返回 `None`
+    pub async fn recv(&self) -> Result<Option<Bytes>, LinkError> {
+        poll_fn(|cx| self.shared.poll_recv(self.id, cx)).await
+    }
+
+    /// 语义同 `Connection::recv_msg_tagged`：对端关闭这个流的写方向后返回 `Closed`；标签只属于这个流上的消息
+    pub async fn recv_msg_tagged(&self) -> Result<(Option<u32>, Bytes), LinkError> {
+        poll_fn(|cx| self.shared.poll_recv_tagged(self.id, cx)).await?.ok_or(LinkError::Closed)
+    }
+
+    /// 与同一连接上其他流分享链路的权重，默认为 `schedule::DEFAULT_PRIORITY`：几个流都有数据排队时，
+    /// 各自发出的字节数与优先级成正比（0 按 1 计）。只决定排队的数据谁先走，不影响流自己的窗口
+    pub fn set_priority(&self, priority: u8) {
+        self.shared.set_priority(self.id, priority);
+    }
+
+    /// 关闭这个流的写方向，在 FIN 被确认时完成；之后 `send` 返回 `WriteClosed`，读方向不受影响。
+    /// 双方都关闭写方向后流被回收，其他流与连接本身照常工作
+    pub async fn shutdown_write(&self) -> Result<(), LinkError> {
+        poll_fn(|cx| self.shared.poll_shutdown(self.id, cx)).await
+    }
+}
+
+impl Drop for LinkStream {
+    fn drop(&mut self) {
+        self.shared.abandon_stream(self.id);
+    }
+}
+
+impl AsyncRead for LinkStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        poll_read_stream(&this.shared, this.id, &mut this.read_buf, cx, buf)
+    }
+}
+
+impl AsyncWrite for LinkStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        poll_write_stream(&self.shared, self.id, cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.shared.poll_flush(self.id, cx).map_err(io::Error::from)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.shared.poll_shutdown(self.id, cx).map_err(io::Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LinkStream;
+    use crate::config::LinkConfig;
+    use crate::connection::testing::{memory_pair, memory_pair_lossy, memory_pair_with};
+    use crate::error::LinkError;
+    use crate::segment::{Segment, SegmentType};
+    use bytes::{Bytes, BytesMut};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::time::Instant;
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_copy_between_connections_round_trips() {
+        // a → b 经 copy 转发给 c → d，两段链路都丢包
+        let (a, b) = memory_pair(7);
+        let (c, d) = memory_pair(11);
+        let (mut a, mut b, mut c, mut d) = (a.into_byte_stream(), b.into_byte_stream(), c.into_byte_stream(), d.into_byte_stream());
+        let data = pattern(1 << 20);
+
+        let write = async {
+            a.write_all(&data).await.unwrap();
+            a.shutdown().await.unwrap();
+        };
+        let relay = async {
+            let copied = tokio::io::copy(&mut b, &mut c).await.unwrap();
+            c.shutdown().await.unwrap();
+            copied
+        };
+        let read = async {
+            let mut received = Vec::new();
+            d.read_to_end(&mut received).await.unwrap();
+            received
+        };
+        let ((), copied, received) = tokio::join!(write, relay, read);
+
+        assert_eq!(copied, data.len() as u64);
+        assert!(received == data, "payload corrupted in transit");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_partial_reads_drain_one_segment() {
+        let (a, b) = memory_pair(0);
+        let mut b = b.into_byte_stream();
+        a.send(bytes::Bytes::from_static(b"hello world")).await.unwrap();
+
+        let mut buf = [0u8; 4];
+        assert_eq!(b.read(&mut buf).await.unwrap(), 4);
+        assert_eq!(&buf, b"hell");
+        let mut rest = [0u8; 32];
+        assert_eq!(b.read(&mut rest).await.unwrap(), 7);
+        assert_eq!(&rest[..7], b"o world");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_read_spans_ready_segments() {
+        let (a, b) = memory_pair(0);
+        let mut b = b.into_byte_stream();
+        for part in [&b"hello"[..], b"", b" ", b"world"] {
+            a.send(Bytes::from_static(part)).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut buf = [0u8; 32];
+        assert_eq!(b.read(&mut buf).await.unwrap(), 11);
+        assert_eq!(&buf[..11], b"hello world");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_message_calls_fail_in_byte_stream_mode() {
+        let (a, b) = memory_pair(0);
+        let a = a.into_byte_stream();
+        let connection = a.get_ref();
+        assert_eq!(connection.send(Bytes::from_static(b"x")).await, Err(LinkError::ByteStreamMode));
+        assert_eq!(connection.send_msg(Bytes::from_static(b"x")).await, Err(LinkError::ByteStreamMode));
+        assert_eq!(connection.try_send(Bytes::from_static(b"x")), Err(LinkError::ByteStreamMode));
+        assert_eq!(connection.recv_msg().await, Err(LinkError::ByteStreamMode));
+        // 附加流照常按消息收发
+        let stream = connection.open_stream().unwrap();
+        stream.send(Bytes::from_static(b"side")).await.unwrap();
+        assert_eq!(b.accept_stream().await.unwrap().recv().await.unwrap(), Some(Bytes::from_static(b"side")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_copy_bidirectional_round_trips() {
+        // a ⇄ b 与 c ⇄ d 两条丢包的连接，b 与 c 之间以 copy_bidirectional 双向转发
+        let (a, b) = memory_pair(7);
+        let (c, d) = memory_pair(11);
+        let (mut a, mut b, mut c, mut d) = (a.into_byte_stream(), b.into_byte_stream(), c.into_byte_stream(), d.into_byte_stream());
+        let forward = pattern(300_000);
+        let backward: Vec<u8> = pattern(200_000).into_iter().rev().collect();
+
+        let relay = async { tokio::io::copy_bidirectional(&mut b, &mut c).await.unwrap() };
+        let left = async {
+            let (mut reader, mut writer) = tokio::io::split(&mut a);
+            let write = async {
+                writer.write_all(&forward).await.unwrap();
+                writer.shutdown().await.unwrap();
+            };
+            let mut received = Vec::new();
+            let ((), _) = tokio::join!(write, reader.read_to_end(&mut received));
+            received
+        };
+        let right = async {
+            let (mut reader, mut writer) = tokio::io::split(&mut d);
+            let write = async {
+                writer.write_all(&backward).await.unwrap();
+                writer.shutdown().await.unwrap();
+            };
+            let mut received = Vec::new();
+            let ((), _) = tokio::join!(write, reader.read_to_end(&mut received));
+            received
+        };
+        let (copied, at_a, at_d) = tokio::join!(relay, left, right);
+
+        assert_eq!(copied, (forward.len() as u64, backward.len() as u64));
+        assert!(at_d == forward, "forward payload corrupted in transit");
+        assert!(at_a == backward, "backward payload corrupted in transit");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_full_window_is_pending() {
+        // 全部丢包：发送队列填满后写入不再完成，也不会在本地继续缓存
+        let config = LinkConfig { send_buffer: 16 * 1024, ..LinkConfig::default() };
+        let (a, _b) = memory_pair_with(config, 1);
+        let mut a = a.into_byte_stream();
+        let chunk = [0u8; 1000];
+        let mut accepted = 0;
+        while tokio::time::timeout(std::time::Duration::from_millis(10), a.write(&chunk)).await.is_ok() {
+            accepted += 1;
+        }
+        assert!(accepted > 0);
+        assert!(accepted * chunk.len() <= 16 * 1024);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_write_after_shutdown_fails() {
+        let (a, b) = memory_pair(0);
+        let mut a = a.into_byte_stream();
+        a.write_all(b"bye").await.unwrap();
+        a.shutdown().await.unwrap();
+
+        assert!(a.write_all(b"more").await.is_err());
+        assert_eq!(b.recv().await.unwrap().unwrap(), bytes::Bytes::from_static(b"bye"));
+        assert_eq!(b.recv().await, Ok(None));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_control_stream_not_delayed_by_bulk_losses() {
+        // 只丢弃批量流（流 1）的数据：每 4 个只含它的数据段的数据报丢一个
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let (counter, mut seen) = (dropped.clone(), 0);
+        let (a, b) = memory_pair_lossy(LinkConfig::default(), move |datagram: &[u8]| {
+            let mut datagram = BytesMut::from(datagram);
+            let mut bulk_only = true;
+            while let Ok(Some(segment)) = Segment::decode_from(&mut datagram) {
+                bulk_only &= segment.stream_id() == 1 && segment.segment_type() == SegmentType::Data;
+            }
+            seen += usize::from(bulk_only);
+            let drop = bulk_only && seen.is_multiple_of(4);
+            counter.fetch_add(usize::from(drop), Ordering::Relaxed);
+            drop
+        });
+
+        let (bulk, control) = (a.open_stream().unwrap(), a.open_stream().unwrap());
+        bulk.send(Bytes::from_static(b"start")).await.unwrap();
+        control.send(Bytes::from_static(b"start")).await.unwrap();
+        let mut accepted = [b.accept_stream().await.unwrap(), b.accept_stream().await.unwrap()];
+        accepted.sort_by_key(LinkStream::id);
+        let [bulk_b, control_b] = accepted;
+        assert_eq!(bulk_b.recv().await.unwrap().unwrap(), Bytes::from_static(b"start"));
+        assert_eq!(control_b.recv().await.unwrap().unwrap(), Bytes::from_static(b"start"));
+
+        let payload = Bytes::from(vec![7u8; 1000]);
+        let bulk_send = async {
+            for _ in 0..300 {
+                bulk.send(payload.clone()).await.unwrap();
+            }
+        };
+        let bulk_recv = async {
+            for _ in 0..300 {
+                assert_eq!(bulk_b.recv().await.unwrap().unwrap(), payload);
+            }
+        };
+        let echo = async {
+            while let Ok(Some(ping)) = control_b.recv().await {
+                control_b.send(ping).await.unwrap();
+            }
+        };
+        let pings = async {
+            let mut worst = Duration::ZERO;
+            for i in 0..50 {
+                let started = Instant::now();
+                let ping = Bytes::from(format!("ping{}", i));
+                control.send(ping.clone()).await.unwrap();
+                assert_eq!(control.recv().await.unwrap().unwrap(), ping);
+                worst = worst.max(started.elapsed());
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            control.shutdown_write().await.unwrap();
+            worst
+        };
+        let ((), (), (), worst) = tokio::join!(bulk_send, bulk_recv, echo, pings);
+
+        // 批量流的每次重传至少等待 min_rto，控制流的往返远小于它
+        assert!(dropped.load(Ordering::Relaxed) > 0);
+        assert!(worst < LinkConfig::default().min_rto, "control round trip took {:?}", worst);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_link_stream_as_bytes() {
+        let (a, b) = memory_pair(5);
+        let data = pattern(100_000);
+        let mut writer = a.open_stream().unwrap();
+        let write = async {
+            writer.write_all(&data).await.unwrap();
+            writer.shutdown().await.unwrap();
+        };
+        let read = async {
+            let mut reader = b.accept_stream().await.unwrap();
+            let mut received = Vec::new();
+            reader.read_to_end(&mut received).await.unwrap();
+            received
+        };
+        let ((), received) = tokio::join!(write, read);
+        assert!(received == data, "payload corrupted in transit");
+    }
+}
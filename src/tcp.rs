@@ -0,0 +1,296 @@
+//! TCP 回退传输
+//! 屏蔽了 UDP 的网络上，同一套协议可以跑在 TCP 上：`TcpTransport`（客户端，一条 `TcpStream`）与
+//! `TcpListenerTransport`（服务端，接受任意多条）都实现 `Transport`，连接与监听器照常经过它们收发，
+//! 握手、保活、多流与消息语义对应用完全不变。
+//!
+//! 字节流按段自身的 `total_len` 前缀分帧，与流式解码共用 `Segment::frame_len`：发出的数据报（一个或多个首尾相接的完整段）原样写入流；
+//! 接收方攒够一个完整的段才把它作为一个数据报交出，读到一半的段留在缓冲中等后续的数据，`recv_from` 因而是取消安全的。
+//! `total_len` 小于段头或超过 `MAX_FRAME_LEN` 时流已无法再同步，按对端关闭处理。
+//!
+//! TCP 已经负责重传与拥塞控制，`stream_config` 把连接的这两层改为直通：固定窗口（`NoCc`），关闭发送节奏与路径 MTU 探测，
+//! RTO 不低于 `STREAM_MIN_RTO`，流短暂停顿时不会重复发出 TCP 终究会送达的段。
+//! 对端关闭流或流出错后 `recv_from` 总是返回 `NotConnected`，客户端连接随之以这个错误结束；
+//! 服务端只移除那一条流，发往已关闭的流的数据报与 UDP 一样被静默丢弃。
+
+use crate::config::LinkConfig;
+use crate::congestion::CongestionAlgorithm;
+use crate::segment::Segment;
+use crate::transport::{BoxFuture, Transport};
+use bytes::{Bytes, BytesMut};
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::{Mutex, mpsc};
+use tokio::task::{JoinHandle, JoinSet};
+
+/// 流中单个段的最大长度
+pub const MAX_FRAME_LEN: usize = 64 * 1024;
+
+/// 经 TCP 的连接的 RTO 下限
+pub const STREAM_MIN_RTO: Duration = Duration::from_secs(1);
+
+/// 每条流读出、尚未被 `recv_from` 取走的段数上限，满时停止读这条流（TCP 的流量控制随之生效）
+const INBOUND_QUEUE: usize = 1024;
+
+/// 把 `config` 的重传与拥塞控制改为适合 TCP 的直通模式
+pub fn stream_config(config: LinkConfig) -> LinkConfig {
+    LinkConfig {
+        congestion: CongestionAlgorithm::NoCc { window: config.send_window },
+        pacing: false,
+        max_mss: None,
+        min_rto: config.min_rto.max(STREAM_MIN_RTO),
+        max_rto: config.max_rto.max(STREAM_MIN_RTO),
+        ..config
+    }
+}
+
+// 对端关闭流或流出错之后的接收错误
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "stream closed")
+}
+
+// 缓冲中第一个完整的段；还不完整时为 None，长度无效时为错误。分帧与 `Segment::decode_from` 相同
+fn next_frame(buf: &mut BytesMut) -> io::Result<Option<Bytes>> {
+    match Segment::frame_len(buf, MAX_FRAME_LEN) {
+        Ok(Some(len)) => Ok(Some(buf.split_to(len).freeze())),
+        Ok(None) => Ok(None),
+        Err(error) => Err(io::Error::new(io::ErrorKind::InvalidData, error)),
+    }
+}
+
+// 读出下一个完整的段；流结束时为 None
+async fn read_frame(stream: &mut OwnedReadHalf, buf: &mut BytesMut) -> io::Result<Option<Bytes>> {
+    loop {
+        if let Some(frame) = next_frame(buf)? {
+            return Ok(Some(frame));
+        }
+        if stream.read_buf(buf).await? == 0 {
+            return Ok(None);
+        }
+    }
+}
+
+// 把段交给 `recv_from` 的缓冲区，放不下时与 UDP 一样截断
+fn deliver(frame: &[u8], buf: &mut [u8]) -> usize {
+    let len = frame.len().min(buf.len());
+    buf[..len].copy_from_slice(&frame[..len]);
+    len
+}
+
+// 读方向的状态：已读入、尚未拼成完整段的字节
+#[derive(Debug)]
+struct Reader {
+    stream: OwnedReadHalf,
+    buf: BytesMut,
+    closed: bool,
+}
+
+/// 客户端的 TCP 传输，只与建立时的对端收发
+#[derive(Debug)]
+pub struct TcpTransport {
+    local: SocketAddr,
+    peer: SocketAddr,
+    reader: Mutex<Reader>,
+    writer: Mutex<OwnedWriteHalf>,
+}
+
+impl TcpTransport {
+    /// 建立到 `remote` 的 TCP 连接
+    pub async fn connect(remote: SocketAddr) -> io::Result<Self> {
+        Self::new(TcpStream::connect(remote).await?)
+    }
+
+    /// 使用已建立的流；关闭 Nagle，段由连接自己合并
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let local = stream.local_addr()?;
+        let peer = stream.peer_addr()?;
+        let (read, write) = stream.into_split();
+        let reader = Reader { stream: read, buf: BytesMut::new(), closed: false };
+        Ok(Self { local, peer, reader: Mutex::new(reader), writer: Mutex::new(write) })
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer
+    }
+}
+
+impl Transport for TcpTransport {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.local)
+    }
+
+    /// 发往建立时的对端之外的地址的数据报被丢弃
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        Box::pin(async move {
+            if target == self.peer {
+                self.writer.lock().await.write_all(buf).await?;
+            }
+            Ok(buf.len())
+        })
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        Box::pin(async move {
+            let mut reader = self.reader.lock().await;
+            if reader.closed {
+                return Err(closed());
+            }
+            let Reader { stream, buf: pending, .. } = &mut *reader;
+            match read_frame(stream, pending).await {
+                Ok(Some(frame)) => Ok((deliver(&frame, buf), self.peer)),
+                Ok(None) | Err(_) => {
+                    reader.closed = true;
+                    Err(closed())
+                }
+            }
+        })
+    }
+}
+
+type Writers = Arc<StdMutex<HashMap<SocketAddr, Arc<Mutex<OwnedWriteHalf>>>>>;
+
+/// 服务端的 TCP 传输：接受的每条流以对端地址区分，与 UDP 套接字上的各个对端一样
+#[derive(Debug)]
+pub struct TcpListenerTransport {
+    local: SocketAddr,
+    writers: Writers,
+    incoming: Mutex<mpsc::Receiver<(Bytes, SocketAddr)>>,
+    acceptor: JoinHandle<()>,
+}
+
+impl TcpListenerTransport {
+    pub async fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let local = listener.local_addr()?;
+        let writers = Writers::default();
+        let (tx, incoming) = mpsc::channel(INBOUND_QUEUE);
+        let acceptor = tokio::spawn(accept_loop(listener, writers.clone(), tx));
+        Ok(Self { local, writers, incoming: Mutex::new(incoming), acceptor })
+    }
+}
+
+impl Drop for TcpListenerTransport {
+    fn drop(&mut self) {
+        self.acceptor.abort();
+    }
+}
+
+impl Transport for TcpListenerTransport {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.local)
+    }
+
+    /// 没有来自 `target` 的流时丢弃；写入失败时关闭这条流
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        Box::pin(async move {
+            let writer = self.writers.lock().expect("tcp writers poisoned").get(&target).cloned();
+            if let Some(writer) = writer
+                && let Err(e) = writer.lock().await.write_all(buf).await
+            {
+                self.writers.lock().expect("tcp writers poisoned").remove(&target);
+                return Err(e);
+            }
+            Ok(buf.len())
+        })
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        Box::pin(async move {
+            let (frame, from) = self.incoming.lock().await.recv().await.ok_or_else(closed)?;
+            Ok((deliver(&frame, buf), from))
+        })
+    }
+
+    fn try_recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let mut incoming = self.incoming.try_lock().map_err(|_| io::Error::from(io::ErrorKind::WouldBlock))?;
+        let (frame, from) = incoming.try_recv().map_err(|e| match e {
+            mpsc::error::TryRecvError::Empty => io::Error::from(io::ErrorKind::WouldBlock),
+            mpsc::error::TryRecvError::Disconnected => closed(),
+        })?;
+        Ok((deliver(&frame, buf), from))
+    }
+}
+
+// 接受新的流并为每条流启动读取任务；传输被丢弃时读取任务随 JoinSet 一起中止
+async fn accept_loop(listener: TcpListener, writers: Writers, incoming: mpsc::Sender<(Bytes, SocketAddr)>) {
+    let mut readers = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, peer) = match accepted {
+                    Ok(accepted) => accepted,
+                    // 文件描述符耗尽之类的错误是暂时的，稍后重试
+                    Err(e) => {
+                        tracing::warn!(error = %e, "failed to accept a tcp stream");
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let _ = stream.set_nodelay(true);
+                let (read, write) = stream.into_split();
+                writers.lock().expect("tcp writers poisoned").insert(peer, Arc::new(Mutex::new(write)));
+                readers.spawn(read_loop(read, peer, writers.clone(), incoming.clone()));
+            }
+            Some(_) = readers.join_next() => {}
+        }
+    }
+}
+
+// 一条流的读取任务：逐段交给 `recv_from`，流结束或出错时移除它
+async fn read_loop(mut stream: OwnedReadHalf, peer: SocketAddr, writers: Writers, incoming: mpsc::Sender<(Bytes, SocketAddr)>) {
+    let mut buf = BytesMut::new();
+    while let Ok(Some(frame)) = read_frame(&mut stream, &mut buf).await {
+        if incoming.send((frame, peer)).await.is_err() {
+            break;
+        }
+    }
+    writers.lock().expect("tcp writers poisoned").remove(&peer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::segment::SegmentType;
+    use bytes::{Buf, BufMut};
+
+    #[test]
+    fn test_frames_split_across_reads() {
+        let first = Segment::new(SegmentType::Data, 1, &b"hello"[..]).encode().unwrap();
+        let second = Segment::new(SegmentType::Data, 2, &b"world!"[..]).encode().unwrap();
+        let mut stream = BytesMut::new();
+        stream.put_slice(&first);
+        stream.put_slice(&second);
+
+        // 逐字节到达：只有攒够一个完整的段时才交出
+        let mut buf = BytesMut::new();
+        let mut frames = Vec::new();
+        while stream.has_remaining() {
+            buf.put_u8(stream.get_u8());
+            frames.extend(next_frame(&mut buf).unwrap());
+        }
+        assert_eq!(frames, vec![first.freeze(), second.freeze()]);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_length_is_an_error() {
+        for len in [0u32, 3, (MAX_FRAME_LEN + 1) as u32] {
+            let mut buf = BytesMut::from(&len.to_be_bytes()[..]);
+            assert_eq!(next_frame(&mut buf).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_stream_config_passes_through() {
+        let config = stream_config(LinkConfig { min_rto: Duration::from_millis(50), ..LinkConfig::default() });
+        assert_eq!(config.congestion, CongestionAlgorithm::NoCc { window: config.send_window });
+        assert_eq!((config.pacing, config.max_mss, config.min_rto), (false, None, STREAM_MIN_RTO));
+        assert_eq!(config.validate(), Ok(()));
+    }
+}
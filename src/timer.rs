@@ -0,0 +1,373 @@
+//! 分层时间轮
+//! 每条连接的定时器（每个在途段的重传、延迟确认、保活、空闲等）登记在时间轮中，驱动任务只按 `next_deadline`
+//! 睡一次，醒来后以 `expire` 成批取出到期的定时器。时间按 `granularity`（默认 `DEFAULT_GRANULARITY`）划分为刻度，
+//! 共 `LEVELS` 层、每层 `SLOTS` 个槽：第 0 层一个槽是一个刻度，上一层一个槽覆盖下一层的一整圈。
+//! 定时器放在与当前刻度最高的不同位所在的那一层，时间推进到它所在的槽时下降（cascade）到更低的层，
+//! 最终在第 0 层到期；超出最高层范围的截止时间先放在最高层的最后，推进后重新放置。
+//!
+//! 刻度只决定定时器放在哪个槽，不改变到期时间：每个定时器记录精确的截止时间，`next_deadline` 返回精确值，
+//! `expire` 只取出截止时间不晚于 `now` 的定时器，行为与逐个比较截止时间相同。
+//! 插入与取消都是 O(1)：槽内以下标索引，取消时与槽内最后一个交换后移除。`Timers` 在此之上按键管理
+//! 每个键至多一个定时器，供重传队列（键为段的偏移量）与连接（键为定时器的种类）使用。本身不做 IO，时间由调用方注入。
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// 默认刻度
+pub const DEFAULT_GRANULARITY: Duration = Duration::from_millis(10);
+
+/// 每层的槽数
+pub const SLOTS: usize = 64;
+
+/// 层数：以默认刻度约可覆盖两年
+pub const LEVELS: usize = 6;
+
+const SLOT_BITS: usize = SLOTS.trailing_zeros() as usize;
+
+// 时间轮能区分的最大刻度差
+const MAX_TICKS: u64 = (1 << (SLOT_BITS * LEVELS)) - 1;
+
+/// 定时器的句柄，取消时使用；定时器到期或取消后失效
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerKey {
+    index: usize,
+    generation: u32,
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+    deadline: Instant,
+    value: T,
+    level: usize,
+    slot: usize,
+    pos: usize,     // 在槽内的下标
+}
+
+/// 时间轮：定时器携带 `T`，到期时交还
+#[derive(Debug)]
+pub struct TimerWheel<T> {
+    granularity: Duration,
+    origin: Option<Instant>,    // 刻度 0，第一次插入时确定
+    elapsed: u64,               // 已推进到的刻度，时间轮中的定时器都不早于它
+    slots: Vec<Vec<usize>>,     // LEVELS * SLOTS 个槽，存放定时器的下标
+    occupied: [u64; LEVELS],    // 每层非空槽的位图
+    entries: Vec<Option<Entry<T>>>,
+    generations: Vec<u32>,
+    free: Vec<usize>,
+    len: usize,
+}
+
+impl<T> TimerWheel<T> {
+    pub fn new(granularity: Duration) -> Self {
+        Self {
+            granularity: granularity.max(Duration::from_micros(1)),
+            origin: None,
+            elapsed: 0,
+            slots: (0..LEVELS * SLOTS).map(|_| Vec::new()).collect(),
+            occupied: [0; LEVELS],
+            entries: Vec::new(),
+            generations: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 登记一个在 `deadline` 到期的定时器；已经过去的截止时间在下一次 `expire` 时到期
+    pub fn insert(&mut self, deadline: Instant, value: T) -> TimerKey {
+        self.origin.get_or_insert(deadline);
+        let index = self.free.pop().unwrap_or_else(|| {
+            self.entries.push(None);
+            self.generations.push(0);
+            self.entries.len() - 1
+        });
+        self.entries[index] = Some(Entry { deadline, value, level: 0, slot: 0, pos: 0 });
+        self.place(index);
+        self.len += 1;
+        TimerKey { index, generation: self.generations[index] }
+    }
+
+    /// 取消定时器，返回它携带的值；已到期或已取消时返回 None
+    pub fn cancel(&mut self, key: TimerKey) -> Option<T> {
+        if self.generations.get(key.index) != Some(&key.generation) {
+            return None;
+        }
+        self.unlink(key.index);
+        self.release(key.index).map(|entry| entry.value)
+    }
+
+    /// 最早的截止时间
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.peek().map(|(deadline, _)| deadline)
+    }
+
+    /// 最早到期的定时器，不取出
+    pub fn peek(&self) -> Option<(Instant, &T)> {
+        let (level, slot) = self.first_slot()?;
+        self.slots[level * SLOTS + slot]
+            .iter()
+            .filter_map(|&index| self.entries[index].as_ref())
+            .min_by_key(|entry| entry.deadline)
+            .map(|entry| (entry.deadline, &entry.value))
+    }
+
+    /// 取出截止时间不晚于 `now` 的全部定时器，按截止时间先后
+    pub fn expire(&mut self, now: Instant) -> Vec<T> {
+        let mut fired = Vec::new();
+        let Some(origin) = self.origin else {
+            return fired;
+        };
+        let target = self.tick(origin, now);
+        while let Some((level, slot)) = self.first_slot() {
+            let start = self.slot_start(level, slot);
+            if start > target {
+                break;
+            }
+            self.elapsed = self.elapsed.max(start);
+            let indices = std::mem::take(&mut self.slots[level * SLOTS + slot]);
+            self.occupied[level] &= !(1 << slot);
+            let mut due = Vec::new();
+            for index in indices {
+                let entry = self.entries[index].as_ref().expect("slotted timer exists");
+                if level == 0 && entry.deadline <= now {
+                    due.push(index);
+                } else {
+                    // 上层的槽下降到更低的层；第 0 层未到期的留在原处（同一刻度内稍晚的截止时间）
+                    self.place(index);
+                }
+            }
+            due.sort_by_key(|&index| self.entries[index].as_ref().map(|entry| entry.deadline));
+            fired.extend(due.into_iter().filter_map(|index| self.release(index)).map(|entry| entry.value));
+            if level == 0 && self.occupied[0] & (1 << slot) != 0 {
+                // 槽内剩下的截止时间稍晚于 `now`，当前刻度停在这里
+                return fired;
+            }
+        }
+        self.elapsed = self.elapsed.max(target);
+        fired
+    }
+
+    fn tick(&self, origin: Instant, at: Instant) -> u64 {
+        let ticks = at.saturating_duration_since(origin).as_nanos() / self.granularity.as_nanos();
+        u64::try_from(ticks).unwrap_or(u64::MAX)
+    }
+
+    // 按截止时间相对当前刻度的位置放进对应的层与槽
+    fn place(&mut self, index: usize) {
+        let origin = self.origin.expect("origin is set on first insert");
+        let deadline = self.entries[index].as_ref().expect("placed timer exists").deadline;
+        // 已过去的放在当前刻度；太远的先放在最高层能表示的最后一个刻度
+        let tick = self.tick(origin, deadline).clamp(self.elapsed, self.elapsed | MAX_TICKS);
+        let significant = 63 - ((self.elapsed ^ tick) | (SLOTS as u64 - 1)).leading_zeros() as usize;
+        let level = significant / SLOT_BITS;
+        let slot = ((tick >> (level * SLOT_BITS)) as usize) & (SLOTS - 1);
+        let bucket = &mut self.slots[level * SLOTS + slot];
+        bucket.push(index);
+        let pos = bucket.len() - 1;
+        self.occupied[level] |= 1 << slot;
+        let entry = self.entries[index].as_mut().expect("placed timer exists");
+        (entry.level, entry.slot, entry.pos) = (level, slot, pos);
+    }
+
+    // 从槽中移除：与槽内最后一个交换，更新被移动者的下标
+    fn unlink(&mut self, index: usize) {
+        let entry = self.entries[index].as_ref().expect("linked timer exists");
+        let (level, slot, pos) = (entry.level, entry.slot, entry.pos);
+        let bucket = &mut self.slots[level * SLOTS + slot];
+        bucket.swap_remove(pos);
+        if let Some(&moved) = bucket.get(pos) {
+            self.entries[moved].as_mut().expect("slotted timer exists").pos = pos;
+        }
+        if bucket.is_empty() {
+            self.occupied[level] &= !(1 << slot);
+        }
+    }
+
+    fn release(&mut self, index: usize) -> Option<Entry<T>> {
+        let entry = self.entries[index].take()?;
+        self.generations[index] = self.generations[index].wrapping_add(1);
+        self.free.push(index);
+        self.len -= 1;
+        Some(entry)
+    }
+
+    // 起始刻度最早的非空槽。每层只有当前位置及之后的槽可能非空
+    fn first_slot(&self) -> Option<(usize, usize)> {
+        (0..LEVELS)
+            .filter_map(|level| {
+                let pos = ((self.elapsed >> (level * SLOT_BITS)) as usize) & (SLOTS - 1);
+                let ahead = self.occupied[level] >> pos;
+                debug_assert_eq!(self.occupied[level] & ((1 << pos) - 1), 0, "timer behind the wheel");
+                (ahead != 0).then(|| (level, pos + ahead.trailing_zeros() as usize))
+            })
+            .min_by_key(|&(level, slot)| self.slot_start(level, slot))
+    }
+
+    fn slot_start(&self, level: usize, slot: usize) -> u64 {
+        let shift = level * SLOT_BITS;
+        let above = !((1u64 << (shift + SLOT_BITS)) - 1);
+        (self.elapsed & above) | ((slot as u64) << shift)
+    }
+}
+
+/// 按键管理的定时器：每个键至多一个，重新设置时取代之前的
+#[derive(Debug)]
+pub struct Timers<K> {
+    wheel: TimerWheel<K>,
+    armed: HashMap<K, (TimerKey, Instant)>,
+}
+
+impl<K: Copy + Eq + Hash> Timers<K> {
+    pub fn new(granularity: Duration) -> Self {
+        Self { wheel: TimerWheel::new(granularity), armed: HashMap::new() }
+    }
+
+    /// 把 `key` 的定时器设为 `deadline`，None 时取消；截止时间不变时什么也不做
+    pub fn set(&mut self, key: K, deadline: Option<Instant>) {
+        match (self.armed.get(&key), deadline) {
+            (Some(&(_, armed)), Some(deadline)) if armed == deadline => return,
+            (Some(&(timer, _)), _) => {
+                self.wheel.cancel(timer);
+                self.armed.remove(&key);
+            }
+            (None, _) => {}
+        }
+        if let Some(deadline) = deadline {
+            let timer = self.wheel.insert(deadline, key);
+            self.armed.insert(key, (timer, deadline));
+        }
+    }
+
+    /// `key` 当前的截止时间
+    pub fn deadline(&self, key: &K) -> Option<Instant> {
+        self.armed.get(key).map(|&(_, deadline)| deadline)
+    }
+
+    /// 取出到期的键，按截止时间先后
+    pub fn expire(&mut self, now: Instant) -> Vec<K> {
+        let fired = self.wheel.expire(now);
+        for key in &fired {
+            self.armed.remove(key);
+        }
+        fired
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.wheel.next_deadline()
+    }
+
+    /// 最早到期的键与它的截止时间
+    pub fn first(&self) -> Option<(K, Instant)> {
+        self.wheel.peek().map(|(deadline, key)| (*key, deadline))
+    }
+
+    /// 已设置定时器的键
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.armed.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.armed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.armed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: Duration = DEFAULT_GRANULARITY;
+
+    #[test]
+    fn test_timers_fire_in_deadline_order() {
+        let t0 = Instant::now();
+        let mut wheel = TimerWheel::new(TICK);
+        // 同一刻度内与跨刻度的截止时间，插入顺序打乱
+        for ms in [35, 3, 120, 7, 0, 64, 12] {
+            wheel.insert(t0 + Duration::from_millis(ms), ms);
+        }
+        assert_eq!(wheel.next_deadline(), Some(t0));
+        assert_eq!(wheel.expire(t0 + Duration::from_millis(5)), vec![0, 3]);
+        // 同一刻度内尚未到期的不会提前取出
+        assert_eq!(wheel.next_deadline(), Some(t0 + Duration::from_millis(7)));
+        assert_eq!(wheel.expire(t0 + Duration::from_millis(6)), Vec::<u64>::new());
+        assert_eq!(wheel.expire(t0 + Duration::from_millis(64)), vec![7, 12, 35, 64]);
+        assert_eq!(wheel.expire(t0 + Duration::from_secs(1)), vec![120]);
+        assert!(wheel.is_empty() && wheel.next_deadline().is_none());
+    }
+
+    #[test]
+    fn test_cancel_is_precise() {
+        let t0 = Instant::now();
+        let mut wheel = TimerWheel::new(TICK);
+        let keys: Vec<_> = (0..10).map(|i| wheel.insert(t0 + TICK, i)).collect();
+        // 取消槽内中间的一个：被交换过来的最后一个仍能取消
+        assert_eq!(wheel.cancel(keys[3]), Some(3));
+        assert_eq!(wheel.cancel(keys[9]), Some(9));
+        assert_eq!(wheel.cancel(keys[3]), None);
+        assert_eq!(wheel.len(), 8);
+        assert_eq!(wheel.expire(t0 + TICK).len(), 8);
+        // 已到期的句柄失效，复用的位置不会被旧句柄取消
+        let reused = wheel.insert(t0 + TICK * 2, 42);
+        assert_eq!(wheel.cancel(keys[0]), None);
+        assert_eq!(wheel.cancel(reused), Some(42));
+    }
+
+    #[test]
+    fn test_cascading_between_levels() {
+        let t0 = Instant::now();
+        let mut wheel = TimerWheel::new(TICK);
+        // 分别落在第 0、1、2、3 层，以及超出最高层范围
+        let deadlines = [TICK * 5, TICK * 100, TICK * 5000, TICK * 300_000, Duration::from_secs(100 * 365 * 86400)];
+        for (i, &after) in deadlines.iter().enumerate() {
+            wheel.insert(t0 + after, i);
+        }
+        let mut now = t0;
+        let mut fired = Vec::new();
+        while let Some(deadline) = wheel.next_deadline() {
+            assert!(deadline >= now);
+            now = deadline;
+            let batch = wheel.expire(now);
+            assert_eq!(batch.len(), 1, "exactly one timer at {:?}", now - t0);
+            fired.extend(batch);
+            assert_eq!(now - t0, deadlines[*fired.last().unwrap()]);
+        }
+        assert_eq!(fired, vec![0, 1, 2, 3, 4]);
+
+        // 推进中途插入的定时器按新的当前刻度放置
+        let mut wheel = TimerWheel::new(TICK);
+        wheel.insert(t0 + TICK * 1000, "far");
+        assert!(wheel.expire(t0 + TICK * 700).is_empty());
+        wheel.insert(t0 + TICK * 710, "near");
+        wheel.insert(t0, "past");
+        assert_eq!(wheel.expire(t0 + TICK * 700), vec!["past"]);
+        assert_eq!(wheel.next_deadline(), Some(t0 + TICK * 710));
+        assert_eq!(wheel.expire(t0 + TICK * 2000), vec!["near", "far"]);
+    }
+
+    #[test]
+    fn test_keyed_timers_replace() {
+        let t0 = Instant::now();
+        let mut timers = Timers::new(TICK);
+        timers.set("rto", Some(t0 + TICK * 20));
+        timers.set("ack", Some(t0 + TICK * 2));
+        timers.set("rto", Some(t0 + TICK * 3));
+        assert_eq!((timers.len(), timers.deadline(&"rto")), (2, Some(t0 + TICK * 3)));
+        timers.set("ack", None);
+        assert_eq!(timers.next_deadline(), Some(t0 + TICK * 3));
+        assert_eq!(timers.first(), Some(("rto", t0 + TICK * 3)));
+        assert_eq!(timers.expire(t0 + TICK * 20), vec!["rto"]);
+        assert!(timers.is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+//! 已结束连接的墓碑
+//! 连接完成 FIN 交换、从监听器的连接表移除后，在 `LinkConfig::drain_timeout` 内为它保留一个只有最后确认的墓碑，
+//! 以 (对端地址, 连接 ID) 识别迟到的段：重传的 FIN 说明对端没有收到确认，用保存的确认再回应一次；
+//! 其余迟到的数据与确认直接吞掉，不会被当作陌生地址以 Rst 回应，也不会交给复用同一地址的新连接。
+//! 墓碑存在期间它的连接 ID 不会分配给新连接。
+//! 状态被导出、交给另一个进程接续的连接（见 `session` 模块）留下没有确认的墓碑：交接期间对端的段（包括 FIN）都被吞掉，
+//! 本端既不确认也不以 Rst 回应，对端重传后由接续的进程处理。
+//!
+//! 墓碑表是一张 `util::ExpiringMap`：容量受 `LinkConfig::max_tombstones` 限制，满时淘汰最早到期的墓碑并计入 `evicted`；
+//! 过期的墓碑查找时即不再生效，`sweep` 经时间轮只移除已到期的，不需要扫描整张表。
+//! 时刻由监听器的分发任务在登记、查找与清扫时传入（`connection::now()`），测试可以任意推进。
+
+use crate::config::LinkConfig;
+use crate::segment::{Segment, SegmentType};
+use crate::util::{Eviction, ExpiringMap};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// 一个墓碑计入内存预算的字节数（见 `budget` 模块）：墓碑本身、保存的确认与表项的开销
+pub const FOOTPRINT: usize = 256;
+
+/// 一个已结束连接的墓碑
+#[derive(Debug, Clone)]
+pub struct Tombstone {
+    peer: SocketAddr,
+    ack: Option<Segment>,   // 连接结束时对对端 FIN 的确认，迁出的连接没有
+}
+
+impl Tombstone {
+    /// 属于这个墓碑的迟到段需要的回应：重传的 FIN 得到最后的确认，其余段被吞掉
+    pub fn on_segment(&self, segment: &Segment) -> Option<Segment> {
+        self.ack.clone().filter(|_| segment.segment_type() == SegmentType::Fin)
+    }
+}
+
+/// 按连接 ID 索引的墓碑表
+#[derive(Debug)]
+pub struct Tombstones {
+    entries: ExpiringMap<u32, Tombstone>,
+    drain: Duration,
+    evicted: Arc<AtomicU64>,    // 表满、未到期就被淘汰的墓碑数
+}
+
+impl Tombstones {
+    pub fn new(config: &LinkConfig) -> Self {
+        let evicted = Arc::new(AtomicU64::new(0));
+        let counter = evicted.clone();
+        let entries = ExpiringMap::new(config.max_tombstones).on_evict(move |_, _, reason| {
+            if reason == Eviction::Capacity {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        Self { entries, drain: config.drain_timeout, evicted }
+    }
+
+    /// 为刚结束的连接立一个墓碑，迁出的连接 `ack` 为 None；同一连接 ID 的旧墓碑被替换，表满时先淘汰最早到期的墓碑
+    pub fn insert(&mut self, conn_id: u32, peer: SocketAddr, ack: Option<Segment>, now: Instant) {
+        if conn_id == 0 || self.drain.is_zero() {
+            return;
+        }
+        self.entries.insert(conn_id, Tombstone { peer, ack }, self.drain, now);
+    }
+
+    /// 来自 `from`、携带 `conn_id` 的段所属的未过期墓碑
+    pub fn lookup(&self, conn_id: u32, from: SocketAddr, now: Instant) -> Option<&Tombstone> {
+        self.entries.get(&conn_id, now).filter(|tombstone| tombstone.peer == from)
+    }
+
+    /// 连接 ID 是否仍被未过期的墓碑占用
+    pub fn contains(&self, conn_id: u32, now: Instant) -> bool {
+        self.entries.contains_key(&conn_id, now)
+    }
+
+    /// 移除已过期的墓碑
+    pub fn sweep(&mut self, now: Instant) {
+        self.entries.expire(now);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 表满、未到期就被淘汰的墓碑数
+    pub fn evicted(&self) -> u64 {
+        self.evicted.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seq::SeqNum;
+
+    fn config(drain_timeout: Duration, max_tombstones: usize) -> LinkConfig {
+        LinkConfig { drain_timeout, max_tombstones, ..LinkConfig::default() }
+    }
+
+    fn ack(n: u64) -> Segment {
+        Segment::builder(SegmentType::Ack).ack(SeqNum::new(n)).window(64).build().unwrap()
+    }
+
+    #[test]
+    fn test_fin_reacked_and_data_swallowed() {
+        let t0 = Instant::now();
+        let peer: SocketAddr = "10.0.0.2:2".parse().unwrap();
+        let mut tombstones = Tombstones::new(&config(Duration::from_secs(2), 16));
+        tombstones.insert(42, peer, Some(ack(100)), t0);
+
+        let tombstone = tombstones.lookup(42, peer, t0 + Duration::from_secs(1)).unwrap();
+        let fin = Segment::builder(SegmentType::Fin).data_seq(100u64).build().unwrap();
+        assert_eq!(tombstone.on_segment(&fin).unwrap().ack(), SeqNum::new(100));
+        assert_eq!(tombstone.on_segment(&Segment::new(SegmentType::Data, 99, b"late".to_vec())), None);
+        // 连接 ID 相同但来自别的地址：不属于这个墓碑
+        assert!(tombstones.lookup(42, "10.0.0.3:3".parse().unwrap(), t0).is_none());
+    }
+
+    #[test]
+    fn test_exported_connection_swallows_everything() {
+        let t0 = Instant::now();
+        let peer: SocketAddr = "10.0.0.2:2".parse().unwrap();
+        let mut tombstones = Tombstones::new(&config(Duration::from_secs(2), 16));
+        tombstones.insert(42, peer, None, t0);
+        let tombstone = tombstones.lookup(42, peer, t0).unwrap();
+        assert_eq!(tombstone.on_segment(&Segment::builder(SegmentType::Fin).data_seq(100u64).build().unwrap()), None);
+        assert_eq!(tombstone.on_segment(&Segment::new(SegmentType::Data, 99, b"late".to_vec())), None);
+    }
+
+    #[test]
+    fn test_conn_id_reserved_until_expiry() {
+        let t0 = Instant::now();
+        let peer: SocketAddr = "10.0.0.2:2".parse().unwrap();
+        let mut tombstones = Tombstones::new(&config(Duration::from_secs(2), 16));
+        tombstones.insert(42, peer, Some(ack(100)), t0);
+        assert!(tombstones.contains(42, t0 + Duration::from_millis(1999)));
+        assert!(!tombstones.contains(42, t0 + Duration::from_secs(2)));
+        assert!(tombstones.lookup(42, peer, t0 + Duration::from_secs(2)).is_none());
+
+        // 清扫只移除已到期的，之后表为空
+        tombstones.sweep(t0 + Duration::from_millis(1990));
+        assert_eq!(tombstones.len(), 1);
+        tombstones.sweep(t0 + Duration::from_millis(2100));
+        assert!(tombstones.is_empty());
+    }
+
+    #[test]
+    fn test_sweep_after_long_gap() {
+        let t0 = Instant::now();
+        let peer: SocketAddr = "10.0.0.2:2".parse().unwrap();
+        let mut tombstones = Tombstones::new(&config(Duration::from_secs(2), 1000));
+        for i in 1..=500u32 {
+            tombstones.insert(i, peer, Some(ack(1)), t0 + Duration::from_millis(u64::from(i)));
+        }
+        // 间隔远超时间轮的一圈：一次清扫移除全部
+        tombstones.sweep(t0 + Duration::from_secs(60));
+        assert!(tombstones.is_empty());
+        assert!(tombstones.entries.next_deadline().is_none());
+        assert_eq!(tombstones.evicted(), 0);
+    }
+
+    #[test]
+    fn test_capacity_evicts_oldest() {
+        let t0 = Instant::now();
+        let peer: SocketAddr = "10.0.0.2:2".parse().unwrap();
+        let mut tombstones = Tombstones::new(&config(Duration::from_secs(2), 3));
+        for i in 1..=10u32 {
+            tombstones.insert(i, peer, Some(ack(1)), t0 + Duration::from_millis(u64::from(i) * 100));
+        }
+        assert_eq!(tombstones.len(), 3);
+        let now = t0 + Duration::from_secs(1);
+        assert_eq!((1..=10).filter(|&i| tombstones.contains(i, now)).collect::<Vec<_>>(), vec![8, 9, 10]);
+        assert_eq!(tombstones.evicted(), 7);
+    }
+}
@@ -0,0 +1,78 @@
+//! 日志事件
+//! 库只通过 `tracing` 发出事件与 span，从不安装订阅者，由使用方决定输出到哪里。逐段的 debug 事件在热路径上，
+//! 调用方先以 `enabled` 判断（`tracing::enabled!`）：没有订阅者或级别关闭时只有一次缓存的级别比较，
+//! 不会读取对端地址、也不会格式化任何字段。
+//!
+//! 每条连接有一个 `link::conn` 目标下的 `conn` span（字段 conn_id、peer、role），连接的驱动与读取任务在其中运行，
+//! 握手与关闭阶段各有一个子 span，可以按 span 字段过滤，
+//! 例如 `RUST_LOG='[conn{conn_id=42}]=debug'` 只输出这条连接的事件。
+//! 逐段事件与解码失败事件自身也带 conn_id 字段，监听器分发任务（不在任何连接的 span 中）的事件据此区分连接。
+
+use crate::segment::{Segment, SegmentError};
+use std::net::SocketAddr;
+use std::fmt;
+use tracing::{Level, Span};
+
+/// 连接 span 所在的目标
+pub const CONN_TARGET: &str = "link::conn";
+
+/// 段的方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// 连接的一方：发起握手的客户端或由监听器接受的服务端
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Client,
+    Server,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Role::Client => "client",
+            Role::Server => "server",
+        })
+    }
+}
+
+/// 一条连接的 span；客户端在握手完成前还不知道连接 ID，由 `Span::record` 补上
+pub fn connection_span(role: Role, peer: SocketAddr) -> Span {
+    tracing::info_span!(target: CONN_TARGET, "conn", conn_id = tracing::field::Empty, %peer, %role)
+}
+
+/// 连接 span 下的握手阶段
+pub fn handshake_span(connection: &Span) -> Span {
+    tracing::info_span!(target: CONN_TARGET, parent: connection, "handshake")
+}
+
+/// 连接 span 下的关闭阶段：从离开 `Established` 起到驱动任务结束
+pub fn close_span(connection: &Span) -> Span {
+    tracing::info_span!(target: CONN_TARGET, parent: connection, "close")
+}
+
+/// 逐段事件是否会被记录
+pub fn enabled() -> bool {
+    tracing::enabled!(Level::DEBUG)
+}
+
+/// 每个收发的段一条 debug 事件：连接 ID、类型、序列号、数据长度与对端
+pub fn segment(direction: Direction, segment: &Segment, peer: SocketAddr) {
+    tracing::debug!(
+        ?direction,
+        conn_id = segment.conn_id(),
+        kind = ?segment.segment_type(),
+        seq = segment.seq().get(),
+        len = segment.data().len(),
+        %peer,
+        "segment"
+    );
+}
+
+/// 无法解析的数据报：warn 级别，带上解码错误与头部中的连接 ID（头部也无法解析时为 0）
+pub fn decode_failed(error: &SegmentError, peer: SocketAddr, conn_id: u32) {
+    tracing::warn!(%peer, conn_id, %error, "dropping undecodable datagram");
+}
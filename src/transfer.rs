@@ -0,0 +1,283 @@
+//! 文件传输
+//! 在一个已建立的 `Connection` 上传一个文件：发送方先发一条头部消息（魔数、大小、SHA-256、文件名），
+//! 之后按 `CHUNK` 分块发送内容；接收方写入输出目录中的 `<文件名>.part`，边收边计算哈希，收满 `size` 字节后校验，
+//! 一致时改名为最终文件名，并回一条判定消息（`OK` 或 `BAD`），发送方收到判定后才算成功。
+//! 哈希不一致、连接中途断开或收到多余的数据时删除未完成的文件，不支持续传。
+//!
+//! 进度每隔 `PROGRESS_INTERVAL` 经回调报告一次。`src/bin/send.rs` 与 `src/bin/recv.rs` 是它的命令行入口。
+
+use crate::connection::Connection;
+use crate::error::LinkError;
+use bytes::{BufMut, Bytes, BytesMut};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::Instant;
+
+/// 头部消息的魔数
+const MAGIC: &[u8; 4] = b"LKF1";
+
+/// 每条数据消息的大小；一条消息是一个数据报，要放得进对端的 `LinkConfig::recv_buffer`（默认 64KB）
+pub const CHUNK: usize = 16 * 1024;
+
+/// 进度回调的间隔
+pub const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 文件名的最大字节数
+const MAX_NAME: usize = 255;
+
+const VERDICT_OK: &[u8] = b"OK";
+const VERDICT_BAD: &[u8] = b"BAD";
+
+/// 传输失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferError {
+    Link(LinkError),                        // 连接失败
+    Io(String),                             // 读写本地文件失败
+    Header(String),                         // 头部消息无法解析，或文件名不安全
+    Truncated { received: u64, size: u64 }, // 收满之前连接关闭
+    Oversized { size: u64 },                // 收到的数据超过头部声明的大小
+    HashMismatch,                           // 接收方校验失败
+    Rejected,                               // 接收方报告校验失败（发送方视角）
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferError::Link(e) => write!(f, "{}", e),
+            TransferError::Io(message) => write!(f, "file error: {}", message),
+            TransferError::Header(reason) => write!(f, "invalid transfer header: {}", reason),
+            TransferError::Truncated { received, size } => write!(f, "connection closed after {} of {} bytes", received, size),
+            TransferError::Oversized { size } => write!(f, "peer sent more than the announced {} bytes", size),
+            TransferError::HashMismatch => write!(f, "sha-256 of the received file does not match the header"),
+            TransferError::Rejected => write!(f, "receiver rejected the file: sha-256 mismatch"),
+        }
+    }
+}
+
+impl std::error::Error for TransferError {}
+
+impl From<LinkError> for TransferError {
+    fn from(e: LinkError) -> Self {
+        TransferError::Link(e)
+    }
+}
+
+impl From<io::Error> for TransferError {
+    fn from(e: io::Error) -> Self {
+        TransferError::Io(e.to_string())
+    }
+}
+
+/// 传输前发送的文件描述
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHeader {
+    pub name: String,
+    pub size: u64,
+    pub sha256: [u8; 32],
+}
+
+impl FileHeader {
+    pub fn encode(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(4 + 8 + 32 + self.name.len());
+        buf.put_slice(MAGIC);
+        buf.put_u64(self.size);
+        buf.put_slice(&self.sha256);
+        buf.put_slice(self.name.as_bytes());
+        buf.freeze()
+    }
+
+    pub fn decode(message: &[u8]) -> Result<FileHeader, TransferError> {
+        if message.len() < 4 + 8 + 32 || &message[..4] != MAGIC {
+            return Err(TransferError::Header("not a file header".to_string()));
+        }
+        let size = u64::from_be_bytes(message[4..12].try_into().expect("8 bytes"));
+        let sha256 = message[12..44].try_into().expect("32 bytes");
+        let name = std::str::from_utf8(&message[44..]).map_err(|_| TransferError::Header("file name is not utf-8".to_string()))?;
+        check_name(name)?;
+        Ok(FileHeader { name: name.to_string(), size, sha256 })
+    }
+}
+
+// 文件名只能是单独的一段：不含目录分隔符，也不是 `.` 或 `..`，接收方不会写到输出目录之外
+fn check_name(name: &str) -> Result<(), TransferError> {
+    let single = Path::new(name).file_name().is_some_and(|file| file == name);
+    if name.is_empty() || name.len() > MAX_NAME || !single || name.contains(['/', '\\']) {
+        return Err(TransferError::Header(format!("unsafe file name '{}'", name)));
+    }
+    Ok(())
+}
+
+/// 传输进度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub bytes: u64,
+    pub size: u64,
+    pub elapsed: Duration,
+}
+
+/// 完成的传输
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transferred {
+    pub path: PathBuf,      // 发送方是源文件，接收方是写入的文件
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl Transferred {
+    /// 平均吞吐量（字节每秒）
+    pub fn throughput(&self) -> f64 {
+        self.bytes as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+// 按间隔调用进度回调
+struct Reporter<F> {
+    started: Instant,
+    next: Instant,
+    size: u64,
+    on_progress: F,
+}
+
+impl<F: FnMut(Progress)> Reporter<F> {
+    fn new(size: u64, on_progress: F) -> Self {
+        let started = Instant::now();
+        Self { started, next: started + PROGRESS_INTERVAL, size, on_progress }
+    }
+
+    fn update(&mut self, bytes: u64) {
+        let now = Instant::now();
+        if now >= self.next {
+            self.next = now + PROGRESS_INTERVAL;
+            (self.on_progress)(Progress { bytes, size: self.size, elapsed: now - self.started });
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+/// 把 `path` 发给对端，等待接收方的判定
+pub async fn send_file(connection: &Connection, path: &Path, on_progress: impl FnMut(Progress)) -> Result<Transferred, TransferError> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| TransferError::Header(format!("{} has no usable file name", path.display())))?;
+    check_name(name)?;
+    let (size, sha256) = hash_file(path).await?;
+    connection.send(FileHeader { name: name.to_string(), size, sha256 }.encode()).await?;
+
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut reporter = Reporter::new(size, on_progress);
+    let mut sent = 0u64;
+    loop {
+        let mut chunk = BytesMut::zeroed(CHUNK);
+        let len = file.read(&mut chunk).await?;
+        if len == 0 {
+            break;
+        }
+        chunk.truncate(len);
+        connection.send(chunk.freeze()).await?;
+        sent += len as u64;
+        reporter.update(sent);
+    }
+    if sent != size {
+        return Err(TransferError::Io(format!("{} changed size while it was being sent", path.display())));
+    }
+    match connection.recv().await? {
+        Some(verdict) if verdict == VERDICT_OK => Ok(Transferred { path: path.to_path_buf(), bytes: sent, elapsed: reporter.elapsed() }),
+        Some(verdict) if verdict == VERDICT_BAD => Err(TransferError::Rejected),
+        Some(_) => Err(TransferError::Header("unexpected verdict".to_string())),
+        None => Err(TransferError::Truncated { received: sent, size }),
+    }
+}
+
+/// 从对端接收一个文件写入目录 `dir`，校验通过后回复判定
+pub async fn recv_file(connection: &Connection, dir: &Path, on_progress: impl FnMut(Progress)) -> Result<Transferred, TransferError> {
+    let header = match connection.recv().await? {
+        Some(message) => FileHeader::decode(&message)?,
+        None => return Err(TransferError::Header("connection closed before the header".to_string())),
+    };
+    let path = dir.join(&header.name);
+    let partial = dir.join(format!("{}.part", header.name));
+    let result = receive_into(connection, &header, &partial, on_progress).await;
+    let (received, elapsed) = match result {
+        Ok(received) => received,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&partial).await;
+            if e == TransferError::HashMismatch {
+                connection.send(Bytes::from_static(VERDICT_BAD)).await?;
+            }
+            return Err(e);
+        }
+    };
+    tokio::fs::rename(&partial, &path).await?;
+    connection.send(Bytes::from_static(VERDICT_OK)).await?;
+    Ok(Transferred { path, bytes: received, elapsed })
+}
+
+async fn receive_into(
+    connection: &Connection,
+    header: &FileHeader,
+    partial: &Path,
+    on_progress: impl FnMut(Progress),
+) -> Result<(u64, Duration), TransferError> {
+    let mut file = tokio::io::BufWriter::new(tokio::fs::File::create(partial).await?);
+    let mut hasher = Sha256::new();
+    let mut reporter = Reporter::new(header.size, on_progress);
+    let mut received = 0u64;
+    while received < header.size {
+        let Some(chunk) = connection.recv().await? else {
+            return Err(TransferError::Truncated { received, size: header.size });
+        };
+        received += chunk.len() as u64;
+        if received > header.size {
+            return Err(TransferError::Oversized { size: header.size });
+        }
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+        reporter.update(received);
+    }
+    file.flush().await?;
+    if hasher.finalize().as_slice() != header.sha256 {
+        return Err(TransferError::HashMismatch);
+    }
+    Ok((received, reporter.elapsed()))
+}
+
+/// 文件的大小与 SHA-256
+pub async fn hash_file(path: &Path) -> io::Result<(u64, [u8; 32])> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; CHUNK];
+    let mut size = 0u64;
+    loop {
+        let len = file.read(&mut buf).await?;
+        if len == 0 {
+            return Ok((size, hasher.finalize().into()));
+        }
+        hasher.update(&buf[..len]);
+        size += len as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trip_and_unsafe_names() {
+        let header = FileHeader { name: "report.pdf".to_string(), size: 12345, sha256: [7; 32] };
+        assert_eq!(FileHeader::decode(&header.encode()).unwrap(), header);
+        assert!(FileHeader::decode(b"LKF2").is_err());
+
+        for name in ["../etc/passwd", "a/b", "a\\b", "..", ".", ""] {
+            let header = FileHeader { name: name.to_string(), size: 1, sha256: [0; 32] };
+            assert!(matches!(FileHeader::decode(&header.encode()), Err(TransferError::Header(_))), "{} accepted", name);
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! 数据报传输
+//! `Transport` 是监听器与连接收发数据报所用的接口，形状与 UDP 套接字相同：发往一个地址、从任意地址接收，
+//! 放不下的数据报被截断。方法返回装箱的 Future，trait 是对象安全的：`Box<dyn Transport>` 与 `Arc<dyn Transport>`
+//! 本身也是传输，可以在运行时选择、层层叠加。实现只需要能在 Future 里完成收发，不限定异步运行时。
+//!
+//! tokio 的 `UdpSocket` 在 `tokio` 特性（默认打开）下实现它；`MemoryTransport` 在同一进程内以 mpsc 通道收发，
+//! 不占用端口、不经过内核，测试在没有网络的环境中也能确定地运行；`FaultyTransport` 可以包在任何一个外面。
+//! `tcp` 模块的 `TcpTransport` 与 `TcpListenerTransport` 把同一接口搬到 TCP 流上，供屏蔽了 UDP 的网络使用。
+//!
+//! `MemoryNetwork` 是内存端点的集合：`bind` 登记一个地址与它的接收队列，发往一个地址的数据报放进该地址的队列。
+//! 队列容量按端点设置，即发往它的那个方向的容量；队列已满或地址无人绑定时数据报被丢弃（与 UDP 一样发送照常成功），
+//! 前者计入发送方的 `dropped`。端点被丢弃时地址随之释放。`set_mtu` 模拟一条设置了 DF 的路径：超过它的数据报
+//! 悄无声息地消失（没有 ICMP），用于测试路径 MTU 探测。`set_filter` 让每个数据报先经过一个回调，按脚本丢弃指定的数据报
+//! 或记录收发的时间；配合 `tokio::time::pause` 可以确定地重现定时相关的行为（见 `tests/simulation.rs`）。
+
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::sync::{Mutex, mpsc};
+
+/// `Transport` 的方法返回的 Future
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// 收发数据报的传输；实现通常写成 `Box::pin(async move { ... })`
+pub trait Transport: fmt::Debug + Send + Sync + 'static {
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>>;
+
+    /// 数据报放不下时截断，返回写入的长度
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>>;
+
+    /// 不等待的接收：取出一个已经到达的数据报，没有时返回 `WouldBlock`。接收任务在每次唤醒后用它取走
+    /// 排队的数据报；默认实现总是返回 `WouldBlock`，这样的传输每次唤醒只处理 `recv_from` 的一个
+    fn try_recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let _ = buf;
+        Err(io::ErrorKind::WouldBlock.into())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Transport for tokio::net::UdpSocket {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        tokio::net::UdpSocket::local_addr(self)
+    }
+
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        Box::pin(tokio::net::UdpSocket::send_to(self, buf, target))
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        Box::pin(tokio::net::UdpSocket::recv_from(self, buf))
+    }
+
+    fn try_recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        tokio::net::UdpSocket::try_recv_from(self, buf)
+    }
+}
+
+/// 共享的传输：调用方保留一个句柄，读取 `MemoryTransport::dropped` 之类的计数
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        T::local_addr(self)
+    }
+
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        T::send_to(self, buf, target)
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        T::recv_from(self, buf)
+    }
+
+    fn try_recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        T::try_recv_from(self, buf)
+    }
+}
+
+/// 运行时选择的传输
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        T::local_addr(self)
+    }
+
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        T::send_to(self, buf, target)
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        T::recv_from(self, buf)
+    }
+
+    fn try_recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        T::try_recv_from(self, buf)
+    }
+}
+
+/// 端点接收队列的默认容量（数据报）
+pub const MEMORY_QUEUE: usize = 1024;
+
+/// 端口为 0 时分配的第一个端口
+const EPHEMERAL_PORT: u16 = 49152;
+
+type Datagram = (Bytes, SocketAddr);
+
+// 数据报、发送端与目的地址；返回 false 时丢弃
+type FilterFn = dyn FnMut(&[u8], SocketAddr, SocketAddr) -> bool + Send;
+
+struct Filter(Box<FilterFn>);
+
+impl fmt::Debug for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Filter")
+    }
+}
+
+#[derive(Debug, Default)]
+struct Endpoints {
+    queues: HashMap<SocketAddr, mpsc::Sender<Datagram>>,
+    next_port: u16,
+    mtu: Option<usize>,     // 更大的数据报被丢弃
+    filter: Option<Filter>,
+}
+
+/// 同一进程内的一组内存端点
+#[derive(Debug, Clone, Default)]
+pub struct MemoryNetwork {
+    endpoints: Arc<StdMutex<Endpoints>>,
+}
+
+impl MemoryNetwork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 以默认队列容量绑定 `addr`
+    pub fn bind(&self, addr: SocketAddr) -> io::Result<MemoryTransport> {
+        self.bind_with_capacity(addr, MEMORY_QUEUE)
+    }
+
+    /// 绑定 `addr`，发往它的数据报最多排队 `capacity` 个；端口为 0 时分配一个未用的端口，地址已被占用时返回 `AddrInUse`
+    pub fn bind_with_capacity(&self, addr: SocketAddr, capacity: usize) -> io::Result<MemoryTransport> {
+        let mut endpoints = self.endpoints.lock().expect("memory network poisoned");
+        let mut local = addr;
+        if local.port() == 0 {
+            local.set_port(endpoints.free_port(addr).ok_or_else(|| io::Error::from(io::ErrorKind::AddrNotAvailable))?);
+        }
+        if endpoints.queues.contains_key(&local) {
+            return Err(io::Error::from(io::ErrorKind::AddrInUse));
+        }
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        endpoints.queues.insert(local, tx);
+        Ok(MemoryTransport { network: self.clone(), local, incoming: Mutex::new(rx), dropped: AtomicU64::new(0) })
+    }
+
+    /// 此后超过 `mtu` 字节的数据报被丢弃，不计入 `dropped`；None 时不限制
+    pub fn set_mtu(&self, mtu: Option<usize>) {
+        self.endpoints.lock().expect("memory network poisoned").mtu = mtu;
+    }
+
+    /// 此后每个数据报（包括超过 MTU 的）先交给 `filter(datagram, from, to)`，返回 false 的被丢弃，不计入 `dropped`。
+    /// 回调在网络的锁内执行，不能再调用这个网络
+    pub fn set_filter(&self, filter: impl FnMut(&[u8], SocketAddr, SocketAddr) -> bool + Send + 'static) {
+        self.endpoints.lock().expect("memory network poisoned").filter = Some(Filter(Box::new(filter)));
+    }
+
+    /// 移除 `set_filter` 设置的回调
+    pub fn clear_filter(&self) {
+        self.endpoints.lock().expect("memory network poisoned").filter = None;
+    }
+}
+
+impl Endpoints {
+    fn free_port(&mut self, addr: SocketAddr) -> Option<u16> {
+        let range = u16::MAX - EPHEMERAL_PORT + 1;
+        for _ in 0..range {
+            let port = EPHEMERAL_PORT + self.next_port % range;
+            self.next_port = self.next_port.wrapping_add(1);
+            if !self.queues.contains_key(&SocketAddr::new(addr.ip(), port)) {
+                return Some(port);
+            }
+        }
+        None
+    }
+}
+
+/// 内存网络中的一个端点
+pub struct MemoryTransport {
+    network: MemoryNetwork,
+    local: SocketAddr,
+    incoming: Mutex<mpsc::Receiver<Datagram>>,
+    dropped: AtomicU64,
+}
+
+impl MemoryTransport {
+    /// 彼此相连的两个端点（127.0.0.1:1 与 127.0.0.1:2），`a_to_b` 与 `b_to_a` 是两个方向的队列容量
+    pub fn pair(a_to_b: usize, b_to_a: usize) -> (MemoryTransport, MemoryTransport) {
+        let network = MemoryNetwork::new();
+        let a = network.bind_with_capacity((Ipv4Addr::LOCALHOST, 1).into(), b_to_a).expect("fresh network");
+        let b = network.bind_with_capacity((Ipv4Addr::LOCALHOST, 2).into(), a_to_b).expect("fresh network");
+        (a, b)
+    }
+
+    /// 对端队列已满而丢弃的数据报数
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Debug for MemoryTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryTransport").field("local", &self.local).field("dropped", &self.dropped()).finish()
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.local)
+    }
+
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        Box::pin(async move { self.send(buf, target) })
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        Box::pin(async move {
+            // 自己的发送端登记在网络中，队列不会关闭
+            let (datagram, from) = self.incoming.lock().await.recv().await.ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+            Ok((copy_truncated(&datagram, buf), from))
+        })
+    }
+
+    // 另一个任务正在 `recv_from` 中等待时同样返回 WouldBlock，数据报留给它
+    fn try_recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let mut incoming = self.incoming.try_lock().map_err(|_| io::Error::from(io::ErrorKind::WouldBlock))?;
+        let (datagram, from) = incoming.try_recv().map_err(|e| match e {
+            mpsc::error::TryRecvError::Empty => io::ErrorKind::WouldBlock,
+            mpsc::error::TryRecvError::Disconnected => io::ErrorKind::NotConnected,
+        })?;
+        Ok((copy_truncated(&datagram, buf), from))
+    }
+}
+
+/// 把数据报复制进 `buf`，放不下的部分与 UDP 套接字一样截断，返回复制的长度
+pub(crate) fn copy_truncated(datagram: &[u8], buf: &mut [u8]) -> usize {
+    let len = datagram.len().min(buf.len());
+    buf[..len].copy_from_slice(&datagram[..len]);
+    len
+}
+
+impl MemoryTransport {
+    // 放进目的地址的队列：从不等待
+    fn send(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        let queue = {
+            let mut endpoints = self.network.endpoints.lock().expect("memory network poisoned");
+            if let Some(Filter(filter)) = &mut endpoints.filter
+                && !filter(buf, self.local, target)
+            {
+                return Ok(buf.len());
+            }
+            if endpoints.mtu.is_some_and(|mtu| buf.len() > mtu) {
+                return Ok(buf.len());
+            }
+            endpoints.queues.get(&target).cloned()
+        };
+        if let Some(queue) = queue
+            && queue.try_send((Bytes::copy_from_slice(buf), self.local)).is_err()
+        {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(buf.len())
+    }
+}
+
+impl Drop for MemoryTransport {
+    fn drop(&mut self) {
+        if let Ok(mut endpoints) = self.network.endpoints.lock() {
+            endpoints.queues.remove(&self.local);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_pair() {
+        let (a, b) = MemoryTransport::pair(2, 8);
+        let b_addr = b.local_addr().unwrap();
+        for datagram in [&b"one"[..], b"two", b"three"] {
+            assert_eq!(a.send_to(datagram, b_addr).await.unwrap(), datagram.len());
+        }
+        // 发往 b 的队列只能放两个
+        assert_eq!(a.dropped(), 1);
+        let mut buf = [0u8; 2];
+        assert_eq!(b.recv_from(&mut buf).await.unwrap(), (2, a.local_addr().unwrap()));
+        assert_eq!(&buf, b"on");
+        let mut buf = [0u8; 16];
+        assert_eq!(b.recv_from(&mut buf).await.unwrap().0, 3);
+        assert_eq!(&buf[..3], b"two");
+    }
+
+    #[tokio::test]
+    async fn test_memory_network_addresses() {
+        let network = MemoryNetwork::new();
+        let server = network.bind("10.0.0.1:9000".parse().unwrap()).unwrap();
+        assert_eq!(network.bind("10.0.0.1:9000".parse().unwrap()).unwrap_err().kind(), io::ErrorKind::AddrInUse);
+        let client = network.bind("10.0.0.2:0".parse().unwrap()).unwrap();
+        assert_eq!(client.local_addr().unwrap().port(), EPHEMERAL_PORT);
+
+        // 无人绑定的地址：数据报消失，不计入 dropped
+        assert!(client.send_to(b"lost", "10.0.0.3:1".parse().unwrap()).await.is_ok());
+        assert_eq!(client.dropped(), 0);
+
+        // 端点被丢弃后地址可以重新绑定
+        drop(server);
+        let server = network.bind("10.0.0.1:9000".parse().unwrap()).unwrap();
+        client.send_to(b"hi", server.local_addr().unwrap()).await.unwrap();
+        let mut buf = [0u8; 4];
+        assert_eq!(server.recv_from(&mut buf).await.unwrap(), (2, client.local_addr().unwrap()));
+
+        // 超过路径 MTU 的数据报消失
+        network.set_mtu(Some(3));
+        client.send_to(b"long", server.local_addr().unwrap()).await.unwrap();
+        client.send_to(b"ok", server.local_addr().unwrap()).await.unwrap();
+        assert_eq!(server.recv_from(&mut buf).await.unwrap().0, 2);
+        assert_eq!(client.dropped(), 0);
+        network.set_mtu(None);
+
+        // 过滤回调丢弃的数据报同样消失
+        network.set_filter(|datagram, _, _| datagram != b"drop");
+        client.send_to(b"drop", server.local_addr().unwrap()).await.unwrap();
+        client.send_to(b"keep", server.local_addr().unwrap()).await.unwrap();
+        assert_eq!(server.recv_from(&mut buf).await.unwrap().0, 4);
+        assert_eq!(&buf, b"keep");
+        network.clear_filter();
+        client.send_to(b"drop", server.local_addr().unwrap()).await.unwrap();
+        assert_eq!(server.recv_from(&mut buf).await.unwrap().0, 4);
+        assert_eq!(client.dropped(), 0);
+    }
+}
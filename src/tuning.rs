@@ -0,0 +1,106 @@
+//! 连接运行期间可调整的参数
+//! `LinkConfig` 中的大部分参数在连接建立时就定下了，其中只影响本端行为的几项可以在连接运行期间经
+//! `Connection::set_option` 调整（例如用户打开控制台时把批量传输的连接切换为交互模式），`get_option` 读出当前值：
+//! 小写入合并（nodelay）、存活探测间隔、确认频率、发送节奏，以及流 0 与附加流分享链路的权重。
+//! 调整在连接的锁内一步完成，与 `send`/`recv` 以及驱动任务的处理互斥，不会出现半新半旧的状态；
+//! 连接没有通往驱动任务的控制通道，调整因此不以控制消息转交驱动任务，而是在调用处完成后唤醒它，
+//! 这样不合法的调整能同步返回错误。驱动任务被唤醒后按新的值重新计时，打开 nodelay 或关闭发送节奏时暂存的写入立即交出。前四项作用于所有流，之后打开的流同样沿用。
+//!
+//! 确认频率与保活间隔是握手中协商出的参数（见 `params` 模块）：调整后 `Connection::negotiated` 报告本端现在使用的值，
+//! 对端不受影响，仍按握手时的值确认与探测。nodelay 与发送节奏反映在 `ConnectionStats` 中。
+//! MSS 与校验算法由握手决定，对端据此解析本端的段，只能读出，调整时返回 `LinkError::NotAdjustable`。
+
+use crate::checksum::ChecksumAlgorithm;
+use crate::config::ConfigError;
+use crate::error::LinkError;
+use std::fmt;
+use std::time::Duration;
+
+/// 连接的一项参数及其值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnOption {
+    Nodelay(bool),                  // 关闭小写入合并（`LinkConfig::nodelay`）
+    KeepaliveInterval(Duration),    // 多久没有收到任何段后发送存活探测，不为 0；调整时从现在起重新计时
+    AckEvery(u16),                  // 攒够多少个按序段后立即确认（`LinkConfig::ack_every_n_segments`），至少为 1
+    Pacing(bool),                   // 按 cwnd/SRTT 算出的速率逐个放出新数据段（`LinkConfig::pacing`）
+    Priority(u8),                   // 流 0 与附加流分享链路的权重（见 `schedule` 模块）
+    Mss(usize),                     // 当前的有效 MSS，握手决定
+    Checksum(ChecksumAlgorithm),    // 协商出的校验算法，握手决定
+}
+
+/// 参数的名字，`Connection::get_option` 以它指定读出哪一项
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OptionName {
+    Nodelay,
+    KeepaliveInterval,
+    AckEvery,
+    Pacing,
+    Priority,
+    Mss,
+    Checksum,
+}
+
+impl OptionName {
+    /// 连接建立后能否调整
+    pub fn is_adjustable(self) -> bool {
+        !matches!(self, OptionName::Mss | OptionName::Checksum)
+    }
+}
+
+impl fmt::Display for OptionName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OptionName::Nodelay => "nodelay",
+            OptionName::KeepaliveInterval => "keepalive_interval",
+            OptionName::AckEvery => "ack_every",
+            OptionName::Pacing => "pacing",
+            OptionName::Priority => "priority",
+            OptionName::Mss => "mss",
+            OptionName::Checksum => "checksum",
+        };
+        f.write_str(name)
+    }
+}
+
+impl ConnOption {
+    pub fn name(&self) -> OptionName {
+        match self {
+            ConnOption::Nodelay(_) => OptionName::Nodelay,
+            ConnOption::KeepaliveInterval(_) => OptionName::KeepaliveInterval,
+            ConnOption::AckEvery(_) => OptionName::AckEvery,
+            ConnOption::Pacing(_) => OptionName::Pacing,
+            ConnOption::Priority(_) => OptionName::Priority,
+            ConnOption::Mss(_) => OptionName::Mss,
+            ConnOption::Checksum(_) => OptionName::Checksum,
+        }
+    }
+
+    /// 调整前的检查：握手决定的参数返回 `NotAdjustable`，超出取值范围的返回 `Config`
+    pub fn validate(&self) -> Result<(), LinkError> {
+        let invalid = |value: String, expected| ConfigError::InvalidValue { key: self.name().to_string(), value, expected };
+        match *self {
+            _ if !self.name().is_adjustable() => Err(LinkError::NotAdjustable(self.name())),
+            ConnOption::KeepaliveInterval(interval) if interval.is_zero() => Err(invalid(format!("{:?}", interval), "a nonzero duration").into()),
+            ConnOption::AckEvery(0) => Err(invalid("0".to_string(), "at least 1").into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handshake_fixed_options_are_rejected() {
+        assert_eq!(ConnOption::Mss(1000).validate(), Err(LinkError::NotAdjustable(OptionName::Mss)));
+        assert_eq!(ConnOption::Checksum(ChecksumAlgorithm::Crc32c).validate(), Err(LinkError::NotAdjustable(OptionName::Checksum)));
+        assert!(matches!(ConnOption::AckEvery(0).validate(), Err(LinkError::Config(ConfigError::InvalidValue { .. }))));
+        assert!(matches!(ConnOption::KeepaliveInterval(Duration::ZERO).validate(), Err(LinkError::Config(_))));
+        for option in [ConnOption::Nodelay(true), ConnOption::KeepaliveInterval(Duration::from_secs(1)), ConnOption::AckEvery(1), ConnOption::Pacing(false), ConnOption::Priority(0)] {
+            assert!(option.name().is_adjustable());
+            assert_eq!(option.validate(), Ok(()));
+        }
+        assert_eq!(OptionName::KeepaliveInterval.to_string(), "keepalive_interval");
+    }
+}
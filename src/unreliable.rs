@@ -0,0 +1,149 @@
+//! 不可靠消息
+//! `Connection::send_unreliable` 发出的消息是流 0 上设置了 `SegmentFlags::UNRELIABLE` 的 Data 段：不进重传队列，
+//! 不占用发送窗口与拥塞窗口；接收端不确认、不经重排缓冲，到达即由 `recv_unreliable` 交付，丢失的就丢失了。
+//! 可靠消息仍由 `send`/`recv` 收发，两者共用连接与连接 ID，互不排队。
+//!
+//! 不可靠段的序列号是独立的空间，从 0 开始逐条递增，只用来去重：接收端记住最近 `DEDUP_WINDOW` 个序列号，
+//! 重复的与更早的段被丢弃（途中复制的数据报不会交付两次），乱序到达的照常交付。
+//! 等待发出的不可靠段至多 `MAX_PENDING` 个，更多时 `send_unreliable` 返回 `WouldBlock`，它们不能挤占可靠数据；
+//! 未读的消息至多保留 `recv_window` 条，满了丢弃最旧的一条——对这类数据，新的比旧的有用。
+//!
+//! 这里用标志位而不是选项：不认识它的对端会跳过未识别的选项，把这个段当作可靠数据放进重排缓冲；
+//! 保留位则让整个数据报以 `ReservedFlags` 被拒绝。
+
+use crate::segment::{Segment, SegmentFlags, SegmentType};
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::task::{Context, Poll, Waker};
+
+/// 等待发出的不可靠段的上限
+pub const MAX_PENDING: usize = 16;
+
+/// 接收端去重记住的序列号个数，更早的段一律丢弃
+pub const DEDUP_WINDOW: u64 = 64;
+
+/// 一条连接上不可靠消息的收发状态
+#[derive(Debug)]
+pub(crate) struct Unreliable {
+    next_seq: u64,              // 下一条发出的消息的序列号
+    highest: Option<u64>,       // 收到过的最大序列号
+    seen: u64,                  // 第 i 位：序列号 highest - i 已收到
+    queue: VecDeque<Bytes>,     // 到达、尚未被读出的消息
+    capacity: usize,
+    waker: Option<Waker>,
+}
+
+impl Unreliable {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self { next_seq: 0, highest: None, seen: 0, queue: VecDeque::new(), capacity: capacity.max(1), waker: None }
+    }
+
+    /// 为 `data` 构造下一个不可靠段
+    pub(crate) fn segment(&mut self, data: Bytes) -> Segment {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        Segment::builder(SegmentType::Data)
+            .data_seq(seq)
+            .flags(SegmentFlags::UNRELIABLE)
+            .payload(data)
+            .build()
+            .expect("unreliable data segment is always valid")
+    }
+
+    /// 收到一个不可靠段；返回它是否被交付（重复与过旧的段不交付）
+    pub(crate) fn on_data(&mut self, segment: &Segment) -> bool {
+        if !self.accept(segment.seq().get()) {
+            return false;
+        }
+        if self.queue.len() == self.capacity {
+            self.queue.pop_front();
+        }
+        self.queue.push_back(segment.data().clone());
+        self.wake();
+        true
+    }
+
+    // 去重窗口：新的最大序列号让窗口前移，窗口内的每个序列号只接受一次
+    fn accept(&mut self, seq: u64) -> bool {
+        let Some(highest) = self.highest else {
+            self.highest = Some(seq);
+            self.seen = 1;
+            return true;
+        };
+        if seq > highest {
+            let shift = seq - highest;
+            self.seen = if shift >= DEDUP_WINDOW { 0 } else { self.seen << shift };
+            self.seen |= 1;
+            self.highest = Some(seq);
+            return true;
+        }
+        let behind = highest - seq;
+        if behind >= DEDUP_WINDOW || self.seen & (1 << behind) != 0 {
+            return false;
+        }
+        self.seen |= 1 << behind;
+        true
+    }
+
+    /// 取出最早的未读消息，没有时登记 waker
+    pub(crate) fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Bytes> {
+        match self.queue.pop_front() {
+            Some(data) => Poll::Ready(data),
+            None => {
+                self.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// 唤醒等待消息的接收方：有新消息，或连接不会再收到消息
+    pub(crate) fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn received(unreliable: &mut Unreliable) -> Vec<Bytes> {
+        let mut cx = Context::from_waker(Waker::noop());
+        std::iter::from_fn(|| match unreliable.poll_recv(&mut cx) {
+            Poll::Ready(data) => Some(data),
+            Poll::Pending => None,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_duplicates_and_stale_segments_are_dropped() {
+        let mut sender = Unreliable::new(16);
+        let segments: Vec<Segment> = (0..100u8).map(|i| sender.segment(Bytes::from(vec![i]))).collect();
+        assert!(segments.iter().all(|segment| segment.flags().contains(SegmentFlags::UNRELIABLE)));
+
+        let mut receiver = Unreliable::new(256);
+        // 乱序到达的照常交付，重复的只交付一次
+        for i in [1, 0, 1, 3, 2, 3] {
+            receiver.on_data(&segments[i]);
+        }
+        assert_eq!(received(&mut receiver), [1u8, 0, 3, 2].map(|i| Bytes::from(vec![i])));
+
+        // 落在去重窗口之外的旧段不再交付
+        assert!(receiver.on_data(&segments[99]));
+        assert!(!receiver.on_data(&segments[4]));
+        assert!(receiver.on_data(&segments[99 - DEDUP_WINDOW as usize + 1]));
+        assert!(!receiver.on_data(&segments[99]));
+    }
+
+    #[test]
+    fn test_full_queue_drops_oldest() {
+        let mut sender = Unreliable::new(4);
+        let mut receiver = Unreliable::new(4);
+        for i in 0..10u8 {
+            receiver.on_data(&sender.segment(Bytes::from(vec![i])));
+        }
+        assert_eq!(received(&mut receiver), [6u8, 7, 8, 9].map(|i| Bytes::from(vec![i])));
+    }
+}
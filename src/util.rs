@@ -0,0 +1,278 @@
+//! 有容量上限、条目各自过期的表
+//! 监听器的墓碑（`tombstone`）、组播接收端按发送方的去重窗口（`multicast`）、原始回显的重复计数（`reflect::RepeatLimiter`）
+//! 都按对端（或对端与数据体）建立条目，条目数随不同对端的数量增长。`ExpiringMap` 把它们共同的限制放在一处：
+//! 每个条目插入时给定存活时间，过期的条目在查找时即视为不存在（惰性）；截止时间登记在 `timer::Timers` 中，
+//! `expire` 成批移除已到期的条目（周期清扫），不需要扫描整张表。表满时插入新键先淘汰最早到期的条目。
+//! 插入与查找都是均摊 O(1)。因过期或容量被移除的条目交给 `on_evict` 登记的回调，拥有者据此更新计数；`remove` 主动移除的不经过回调。
+//! 本身不做 IO 也不加锁，时间由调用方注入；多个任务共用时由拥有者加锁。
+
+use crate::timer::{DEFAULT_GRANULARITY, Timers};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// 条目被移除的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eviction {
+    Expired,    // 存活时间已过
+    Capacity,   // 表满时为新键让位，此时它是最早到期的条目
+}
+
+type Callback<K, V> = Box<dyn FnMut(K, V, Eviction) + Send>;
+
+/// 容量至多 `capacity` 个条目、每个条目在各自的截止时间过期的表
+pub struct ExpiringMap<K, V> {
+    entries: HashMap<K, (V, Instant)>,  // 值与截止时间
+    timers: Timers<K>,
+    capacity: usize,
+    on_evict: Option<Callback<K, V>>,
+}
+
+impl<K: Copy + Eq + Hash, V> ExpiringMap<K, V> {
+    /// 容量为 0 的表什么也不保存
+    pub fn new(capacity: usize) -> Self {
+        Self::with_granularity(capacity, DEFAULT_GRANULARITY)
+    }
+
+    /// 以 `granularity` 为时间轮的刻度，见 `timer::TimerWheel`
+    pub fn with_granularity(capacity: usize, granularity: Duration) -> Self {
+        Self { entries: HashMap::new(), timers: Timers::new(granularity), capacity, on_evict: None }
+    }
+
+    /// 登记条目因过期或容量被移除时的回调
+    pub fn on_evict(mut self, callback: impl FnMut(K, V, Eviction) + Send + 'static) -> Self {
+        self.on_evict = Some(Box::new(callback));
+        self
+    }
+
+    /// 插入在 `now + ttl` 过期的条目，返回同一个键尚未过期的旧值。先移除已到期的条目，表仍满时淘汰最早到期的一个
+    pub fn insert(&mut self, key: K, value: V, ttl: Duration, now: Instant) -> Option<V> {
+        self.expire(now);
+        if self.capacity == 0 {
+            return None;
+        }
+        if !self.entries.contains_key(&key)
+            && self.entries.len() >= self.capacity
+            && let Some((oldest, _)) = self.timers.first()
+        {
+            self.evict(oldest, Eviction::Capacity);
+        }
+        let deadline = now + ttl;
+        self.timers.set(key, Some(deadline));
+        self.entries.insert(key, (value, deadline)).map(|(old, _)| old)
+    }
+
+    /// 尚未过期的值
+    pub fn get(&self, key: &K, now: Instant) -> Option<&V> {
+        self.entries.get(key).filter(|(_, deadline)| now < *deadline).map(|(value, _)| value)
+    }
+
+    /// 尚未过期的值；已过期的条目顺带移除
+    pub fn get_mut(&mut self, key: &K, now: Instant) -> Option<&mut V> {
+        let deadline = self.entries.get(key).map(|&(_, deadline)| deadline)?;
+        if deadline <= now {
+            self.evict(*key, Eviction::Expired);
+            return None;
+        }
+        self.entries.get_mut(key).map(|(value, _)| value)
+    }
+
+    pub fn contains_key(&self, key: &K, now: Instant) -> bool {
+        self.get(key, now).is_some()
+    }
+
+    /// 把尚未过期的条目的截止时间改为 `now + ttl`；条目不存在或已过期时返回 false
+    pub fn refresh(&mut self, key: &K, ttl: Duration, now: Instant) -> bool {
+        if self.get_mut(key, now).is_none() {
+            return false;
+        }
+        let deadline = now + ttl;
+        self.timers.set(*key, Some(deadline));
+        if let Some((_, at)) = self.entries.get_mut(key) {
+            *at = deadline;
+        }
+        true
+    }
+
+    /// 主动移除，不经过回调
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.timers.set(*key, None);
+        self.entries.remove(key).map(|(value, _)| value)
+    }
+
+    /// 移除截止时间不晚于 `now` 的条目，按截止时间先后交给回调，返回移除的个数
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let expired = self.timers.expire(now);
+        for key in &expired {
+            self.evict(*key, Eviction::Expired);
+        }
+        expired.len()
+    }
+
+    /// 最早的截止时间，拥有者据此安排下一次 `expire`
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.timers.next_deadline()
+    }
+
+    /// 条目数，包括已过期、尚未被移除的
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn evict(&mut self, key: K, reason: Eviction) {
+        self.timers.set(key, None);
+        if let Some((value, _)) = self.entries.remove(&key)
+            && let Some(callback) = &mut self.on_evict
+        {
+            callback(key, value, reason);
+        }
+    }
+}
+
+impl<K, V> fmt::Debug for ExpiringMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExpiringMap").field("len", &self.entries.len()).field("capacity", &self.capacity).finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    // 回调记下的 (键, 值, 原因)
+    type Log<V> = Arc<Mutex<Vec<(u32, V, Eviction)>>>;
+
+    fn recorded<V: Send + 'static>(map: ExpiringMap<u32, V>) -> (ExpiringMap<u32, V>, Log<V>) {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let sink = log.clone();
+        (map.on_evict(move |key, value, reason| sink.lock().unwrap().push((key, value, reason))), log)
+    }
+
+    #[test]
+    fn test_entries_expire_under_fake_clock() {
+        let t0 = Instant::now();
+        let (mut map, log) = recorded(ExpiringMap::new(16));
+        map.insert(1, "short", MS * 100, t0);
+        map.insert(2, "long", MS * 300, t0);
+        assert_eq!(map.next_deadline(), Some(t0 + MS * 100));
+
+        // 截止时间一到查找即失效，清扫之前仍占着位置
+        assert_eq!(map.get(&1, t0 + MS * 99), Some(&"short"));
+        assert_eq!(map.get(&1, t0 + MS * 100), None);
+        assert!(!map.contains_key(&1, t0 + MS * 100) && map.len() == 2);
+        // 可变查找顺带移除过期的条目
+        assert!(map.get_mut(&1, t0 + MS * 100).is_none());
+        assert_eq!((map.len(), map.next_deadline()), (1, Some(t0 + MS * 300)));
+
+        // 刷新推迟截止时间；清扫只移除到期的
+        assert!(map.refresh(&2, MS * 300, t0 + MS * 200));
+        assert!(!map.refresh(&1, MS * 300, t0 + MS * 200));
+        assert_eq!(map.expire(t0 + MS * 450), 0);
+        assert_eq!(map.get(&2, t0 + MS * 450), Some(&"long"));
+        assert_eq!(map.expire(t0 + MS * 500), 1);
+        assert!(map.is_empty() && map.next_deadline().is_none());
+        assert_eq!(*log.lock().unwrap(), vec![(1, "short", Eviction::Expired), (2, "long", Eviction::Expired)]);
+
+        // 替换返回未过期的旧值，主动移除不经过回调
+        map.insert(3, "old", MS * 100, t0 + MS * 500);
+        assert_eq!(map.insert(3, "new", MS * 100, t0 + MS * 550), Some("old"));
+        assert_eq!(map.insert(3, "newer", MS * 100, t0 + MS * 700), None);
+        assert_eq!(map.remove(&3), Some("newer"));
+        assert_eq!(map.expire(t0 + MS * 1000), 0);
+        assert_eq!(log.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_capacity_evicts_earliest_deadline() {
+        let t0 = Instant::now();
+        let (mut map, log) = recorded(ExpiringMap::new(3));
+        // 存活时间各不相同：淘汰按截止时间而不是插入顺序
+        map.insert(1, (), MS * 500, t0);
+        map.insert(2, (), MS * 100, t0);
+        map.insert(3, (), MS * 300, t0);
+        map.insert(4, (), MS * 400, t0);
+        // 替换已有的键不淘汰
+        map.insert(4, (), MS * 50, t0 + MS * 10);
+        // 刷新过的条目排到后面
+        assert!(map.refresh(&3, MS * 1000, t0 + MS * 20));
+        map.insert(5, (), MS * 500, t0 + MS * 20);
+        map.insert(6, (), MS * 500, t0 + MS * 30);
+        assert_eq!(map.len(), 3);
+        let evicted: Vec<_> = log.lock().unwrap().iter().map(|&(key, _, reason)| (key, reason)).collect();
+        assert_eq!(evicted, vec![(2, Eviction::Capacity), (4, Eviction::Capacity), (1, Eviction::Capacity)]);
+        assert!([3, 5, 6].iter().all(|key| map.contains_key(key, t0 + MS * 30)));
+
+        let mut empty = ExpiringMap::new(0);
+        assert_eq!(empty.insert(1, (), MS, t0), None);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_demux_and_sweeper() {
+        // 分发路径插入与查找，清扫路径推进共用的假时钟并移除到期的条目
+        const KEYS: u32 = 20_000;
+        const CAPACITY: usize = 256;
+        let t0 = Instant::now();
+        let clock = Arc::new(AtomicU64::new(0));
+        let expired = Arc::new(AtomicU64::new(0));
+        let evicted = Arc::new(AtomicU64::new(0));
+        let counters = (expired.clone(), evicted.clone());
+        let map = Arc::new(Mutex::new(ExpiringMap::<u32, Instant>::new(CAPACITY).on_evict(move |_, _, reason| {
+            let counter = if reason == Eviction::Expired { &counters.0 } else { &counters.1 };
+            counter.fetch_add(1, Ordering::Relaxed);
+        })));
+
+        let demux = thread::spawn({
+            let (map, clock) = (map.clone(), clock.clone());
+            move || {
+                for key in 0..KEYS {
+                    let now = t0 + MS * clock.load(Ordering::Relaxed) as u32;
+                    let mut map = map.lock().unwrap();
+                    // 值是截止时间：查找从不返回已过期的条目
+                    map.insert(key, now + MS * 20, MS * 20, now);
+                    for probe in key.saturating_sub(64)..=key {
+                        if let Some(&deadline) = map.get(&probe, now) {
+                            assert!(now < deadline);
+                        }
+                    }
+                    assert!(map.len() <= CAPACITY);
+                }
+            }
+        });
+        let sweeper = thread::spawn({
+            let (map, clock) = (map.clone(), clock.clone());
+            move || {
+                while !demux.is_finished() {
+                    let now = clock.fetch_add(1, Ordering::Relaxed) + 1;
+                    map.lock().unwrap().expire(t0 + MS * now as u32);
+                    thread::yield_now();
+                }
+                demux.join().unwrap();
+            }
+        });
+        sweeper.join().unwrap();
+
+        let mut map = map.lock().unwrap();
+        let remaining = map.len() as u64;
+        assert!(remaining <= CAPACITY as u64);
+        // 每个条目恰好离开一次
+        assert_eq!(expired.load(Ordering::Relaxed) + evicted.load(Ordering::Relaxed) + remaining, u64::from(KEYS));
+        map.expire(t0 + Duration::from_secs(3600));
+        assert!(map.is_empty());
+        assert_eq!(expired.load(Ordering::Relaxed) + evicted.load(Ordering::Relaxed), u64::from(KEYS));
+    }
+}
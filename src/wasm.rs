@@ -0,0 +1,70 @@
+//! 浏览器中的编解码
+//! `wasm` 特性以 wasm-bindgen 导出段的编解码，网页可以直接解码经 WebSocket 转来的记录，与端点使用同一份代码。
+//! 只依赖 `alloc`，不需要 tokio 与套接字，可以关闭默认特性为 `wasm32-unknown-unknown` 构建：
+//!     cargo rustc --lib --crate-type cdylib --release --target wasm32-unknown-unknown --no-default-features --features wasm
+//! 再以 `wasm-bindgen --target web` 生成 JS 绑定。段类型以名字（`Data`、`Ack`、`Syn`……，与 `SegmentType` 的变体同名）表示，
+//! 序列号是 BigInt，数据体是 `Uint8Array`。
+
+use crate::segment::{Segment, SegmentType};
+use crate::seq::SeqNum;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use wasm_bindgen::prelude::*;
+
+/// 解码出的段
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct JsSegment {
+    segment: Segment,
+}
+
+#[wasm_bindgen]
+impl JsSegment {
+    /// 段类型的名字
+    #[wasm_bindgen(getter = type)]
+    pub fn type_name(&self) -> String {
+        format!("{:?}", self.segment.segment_type())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn seq(&self) -> u64 {
+        self.segment.seq().get()
+    }
+
+    /// 标志位的原始值，见 `SegmentFlags`
+    #[wasm_bindgen(getter)]
+    pub fn flags(&self) -> u8 {
+        self.segment.flags().bits()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn payload(&self) -> Vec<u8> {
+        self.segment.data().to_vec()
+    }
+}
+
+impl JsSegment {
+    pub fn segment(&self) -> &Segment {
+        &self.segment
+    }
+}
+
+// 按名字查找段类型
+fn segment_type(name: &str) -> Option<SegmentType> {
+    (0..=u8::MAX).filter_map(SegmentType::from_id).find(|segment_type| format!("{:?}", segment_type) == name)
+}
+
+/// 解码一个完整的段（严格模式，与端点相同）
+#[wasm_bindgen(js_name = decodeSegment)]
+pub fn decode_segment(js_bytes: &[u8]) -> Result<JsSegment, JsError> {
+    Segment::decode(js_bytes).map(|segment| JsSegment { segment }).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// 以类型名、序列号与数据体编码一个段，其余字段取默认值
+#[wasm_bindgen(js_name = encodeSegment)]
+pub fn encode_segment(segment_type_name: &str, seq: u64, payload: &[u8]) -> Result<Vec<u8>, JsError> {
+    let segment_type = segment_type(segment_type_name).ok_or_else(|| JsError::new(&format!("unknown segment type '{}'", segment_type_name)))?;
+    let segment = Segment::new(segment_type, SeqNum::new(seq), payload.to_vec());
+    segment.encode().map(|encoded| encoded.to_vec()).map_err(|e| JsError::new(&e.to_string()))
+}
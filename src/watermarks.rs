@@ -0,0 +1,218 @@
+//! 发送队列与重排缓冲区的水位事件
+//! 建在连接之上的代理需要在 `send` 开始等待之前停止读上游、在接收端积压时放慢下游。`LinkConfig::send_watermarks`
+//! 与 `recv_watermarks` 各给出一对水位：流 0 的发送队列（字节，与 `send_buffer` 同一口径：暂存的写入与已发送未确认的数据体）
+//! 与重排缓冲区（段数，与 `recv_window` 同一口径：已收到、尚未被读出的段）升到 `high` 及以上时越过高水位，
+//! 之后降到 `low` 及以下时越过低水位。两者之间的波动不产生事件，高低水位事件总是交替出现。
+//!
+//! 协议核心在每次改变这两个量的操作（发送、读出、处理数据报与定时器）之后立即核对，越过时把事件直接放进订阅者的无界通道：
+//! 越过之后很快又退回，也会在通道中留下两个事件，不会因为订阅者还没来得及读而合并或丢失。
+//! `Connection::subscribe_watermarks` 取代之前的订阅；订阅时已经在高水位之上的队列先报告一次 `High`。
+//! 连接结束后 `WatermarkEvents::next` 读完已有的事件返回 None。只想轮询的调用方用 `Connection::send_queue_len`
+//! 与 `recv_buffer_len` 直接读出当前值。
+
+use std::fmt;
+use std::future::poll_fn;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+/// 一对水位，`low < high`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watermarks {
+    pub low: usize,
+    pub high: usize,
+}
+
+impl Watermarks {
+    pub fn new(low: usize, high: usize) -> Self {
+        Self { low, high }
+    }
+}
+
+impl fmt::Display for Watermarks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.low, self.high)
+    }
+}
+
+// `low..high`，如 `16384..49152`
+impl FromStr for Watermarks {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (low, high) = value.split_once("..").ok_or(())?;
+        Ok(Self::new(low.trim().parse().map_err(|_| ())?, high.trim().parse().map_err(|_| ())?))
+    }
+}
+
+/// 哪一个队列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Queue {
+    Send,       // 流 0 的发送队列（字节）
+    Receive,    // 流 0 的重排缓冲区（段数）
+}
+
+/// 越过了哪一个水位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Level {
+    High,
+    Low,
+}
+
+/// 一次越过：`len` 是越过时队列的占用，`at` 是发现越过的时间
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatermarkEvent {
+    pub queue: Queue,
+    pub level: Level,
+    pub len: usize,
+    pub at: Instant,
+}
+
+/// 订阅者持有的水位事件流（见 `Connection::subscribe_watermarks`）
+#[derive(Debug)]
+pub struct WatermarkEvents {
+    rx: mpsc::UnboundedReceiver<WatermarkEvent>,
+}
+
+impl WatermarkEvents {
+    /// 等待下一个事件；连接结束或订阅被取代后返回 None
+    pub async fn next(&mut self) -> Option<WatermarkEvent> {
+        poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<WatermarkEvent>> {
+        self.rx.poll_recv(cx)
+    }
+
+    /// 不等待地取出下一个已到达的事件
+    pub fn try_next(&mut self) -> Option<WatermarkEvent> {
+        self.rx.try_recv().ok()
+    }
+}
+
+#[cfg(feature = "futures")]
+impl futures_core::Stream for WatermarkEvents {
+    type Item = WatermarkEvent;
+
+    fn poll_next(self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<WatermarkEvent>> {
+        self.get_mut().rx.poll_recv(cx)
+    }
+}
+
+// 一个队列的水位与它当前是否在高水位之上
+#[derive(Debug)]
+struct Edge {
+    marks: Option<Watermarks>,
+    high: bool,
+}
+
+impl Edge {
+    fn new(marks: Option<Watermarks>) -> Self {
+        Self { marks, high: false }
+    }
+
+    // 占用变为 `len` 后越过的水位
+    fn observe(&mut self, len: usize) -> Option<Level> {
+        let marks = self.marks?;
+        match self.high {
+            false if len >= marks.high => {
+                self.high = true;
+                Some(Level::High)
+            }
+            true if len <= marks.low => {
+                self.high = false;
+                Some(Level::Low)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// 协议核心持有的两个队列的水位状态；没有订阅者时照常跟踪，事件被丢弃
+#[derive(Debug)]
+pub(crate) struct WatermarkTracker {
+    send: Edge,
+    recv: Edge,
+    subscriber: Option<mpsc::UnboundedSender<WatermarkEvent>>,
+}
+
+impl WatermarkTracker {
+    pub(crate) fn new(send: Option<Watermarks>, recv: Option<Watermarks>) -> Self {
+        Self { send: Edge::new(send), recv: Edge::new(recv), subscriber: None }
+    }
+
+    /// 取代之前的订阅；已在高水位之上的队列先报告一次 `High`，占用取当前值
+    pub(crate) fn subscribe(&mut self, send: usize, recv: usize, now: Instant) -> WatermarkEvents {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for (queue, edge, len) in [(Queue::Send, &self.send, send), (Queue::Receive, &self.recv, recv)] {
+            if edge.high {
+                let _ = tx.send(WatermarkEvent { queue, level: Level::High, len, at: now });
+            }
+        }
+        self.subscriber = Some(tx);
+        WatermarkEvents { rx }
+    }
+
+    /// 核对两个队列的当前占用，越过水位时交给订阅者
+    pub(crate) fn observe(&mut self, send: usize, recv: usize, now: Instant) {
+        for (queue, edge, len) in [(Queue::Send, &mut self.send, send), (Queue::Receive, &mut self.recv, recv)] {
+            if let Some(level) = edge.observe(len)
+                && let Some(subscriber) = &self.subscriber
+            {
+                let _ = subscriber.send(WatermarkEvent { queue, level, len, at: now });
+            }
+        }
+    }
+
+    /// 连接结束：订阅者读完已有的事件后得到 None
+    pub(crate) fn close(&mut self) {
+        self.subscriber = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(events: &mut WatermarkEvents) -> Vec<(Queue, Level, usize)> {
+        std::iter::from_fn(|| events.try_next()).map(|event| (event.queue, event.level, event.len)).collect()
+    }
+
+    #[test]
+    fn test_crossings_are_edge_triggered() {
+        let now = Instant::now();
+        let mut tracker = WatermarkTracker::new(Some(Watermarks::new(10, 100)), Some(Watermarks::new(2, 8)));
+        let mut events = tracker.subscribe(0, 0, now);
+        // 在两个水位之间波动、在高水位之上继续上升都不产生事件
+        for send in [50, 99, 100, 150, 200, 60, 11, 100, 10, 5, 0] {
+            tracker.observe(send, 0, now);
+        }
+        assert_eq!(levels(&mut events), [(Queue::Send, Level::High, 100), (Queue::Send, Level::Low, 10)]);
+
+        // 越过之后立即退回：两个事件都留在通道中
+        tracker.observe(0, 8, now);
+        tracker.observe(0, 1, now);
+        tracker.observe(120, 9, now);
+        assert_eq!(levels(&mut events), [(Queue::Receive, Level::High, 8), (Queue::Receive, Level::Low, 1), (Queue::Send, Level::High, 120), (Queue::Receive, Level::High, 9)]);
+
+        // 重新订阅时先报告仍在高水位之上的队列
+        let mut again = tracker.subscribe(130, 9, now);
+        assert_eq!(levels(&mut again), [(Queue::Send, Level::High, 130), (Queue::Receive, Level::High, 9)]);
+        assert!(events.try_next().is_none());
+        tracker.close();
+        assert!(again.try_next().is_none());
+    }
+
+    #[test]
+    fn test_unconfigured_queue_is_silent() {
+        let now = Instant::now();
+        let mut tracker = WatermarkTracker::new(None, Some(Watermarks::new(2, 8)));
+        let mut events = tracker.subscribe(0, 0, now);
+        tracker.observe(usize::MAX, 0, now);
+        assert!(events.try_next().is_none());
+        assert_eq!("16384..49152".parse(), Ok(Watermarks::new(16384, 49152)));
+        assert_eq!(Watermarks::new(1, 2).to_string(), "1..2");
+        assert!("16384".parse::<Watermarks>().is_err());
+    }
+}
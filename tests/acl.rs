@@ -0,0 +1,114 @@
+//! 访问控制集成测试：监听器与连接跑在 `MemoryNetwork` 上，客户端从指定的地址连接；
+//! 不被允许的来源收不到任何回应，运行中换上新的规则时已建立的连接按 `evict` 保留或被中止
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use bytes::Bytes;
+use common::{accept, server_addr};
+use link_rs::acl::Acl;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::server::{EchoHandler, Server};
+use link_rs::transport::MemoryNetwork;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::timeout;
+
+fn quick() -> LinkConfig {
+    LinkConfig { handshake_timeout: Duration::from_millis(300), ..LinkConfig::default() }
+}
+
+async fn connect(network: &MemoryNetwork, ip: &str) -> Result<Connection, LinkError> {
+    common::connect(network, format!("{}:0", ip).parse().unwrap(), quick()).await
+}
+
+#[tokio::test]
+async fn test_denied_source_gets_no_answer() {
+    let network = MemoryNetwork::new();
+    // 服务端发出的每个数据报的目的地址
+    let replies = Arc::new(Mutex::new(Vec::new()));
+    network.set_filter({
+        let replies = replies.clone();
+        move |_, from, to| {
+            if from == server_addr() {
+                replies.lock().unwrap().push(to);
+            }
+            true
+        }
+    });
+    let config = LinkConfig { allow: vec!["10.0.0.0/24".parse().unwrap()], deny: vec!["10.0.0.66".parse().unwrap()], ..LinkConfig::default() };
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config).unwrap();
+
+    for outside in ["10.0.1.2", "10.0.0.66"] {
+        let result = connect(&network, outside).await;
+        assert!(matches!(result, Err(LinkError::ConnectTimeout { .. })), "{}: {:?}", outside, result.map(|_| ()));
+    }
+    // 没有 SYN-ACK，也没有 Rst
+    assert!(replies.lock().unwrap().is_empty(), "{:?}", replies.lock().unwrap());
+    let stats = listener.stats();
+    assert!(stats.denied >= 2, "{:?}", stats);
+    assert_eq!((stats.half_open, stats.connections), (0, 0));
+
+    let client = connect(&network, "10.0.0.2").await.unwrap();
+    let server = accept(&listener).await;
+    client.send(Bytes::from_static(b"inside")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"inside")));
+}
+
+#[tokio::test]
+async fn test_reload_keeps_established_connections() {
+    let network = MemoryNetwork::new();
+    let server = Arc::new(Server::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default(), EchoHandler).unwrap());
+    let running = tokio::spawn({
+        let server = server.clone();
+        async move { server.run().await }
+    });
+    let client = connect(&network, "10.0.0.2").await.unwrap();
+    client.send(Bytes::from_static(b"before")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), client.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"before")));
+
+    // 允许之后又拒绝这个网段：新的来源被挡住，已建立的连接照常回显
+    server.set_acl(Acl::new(Vec::new(), vec!["10.0.0.0/24".parse().unwrap()]));
+    assert!(matches!(connect(&network, "10.0.0.3").await, Err(LinkError::ConnectTimeout { .. })));
+    client.send(Bytes::from_static(b"after")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), client.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"after")));
+    assert!(server.listener().stats().denied > 0);
+    assert_eq!(server.listener().stats().connections, 1);
+
+    // 再放开：新的来源可以连接
+    server.set_acl(Acl::default());
+    let other = connect(&network, "10.0.0.3").await.unwrap();
+    other.send(Bytes::from_static(b"again")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), other.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"again")));
+    running.abort();
+}
+
+#[tokio::test]
+async fn test_reload_evicts_denied_connections() {
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap();
+    let denied = connect(&network, "10.0.0.2").await.unwrap();
+    let denied_server = accept(&listener).await;
+    let kept = connect(&network, "10.0.9.2").await.unwrap();
+    let kept_server = accept(&listener).await;
+
+    listener.set_acl(Acl { deny: vec!["10.0.0.0/24".parse().unwrap()], evict: true, ..Acl::default() });
+    // 服务端一侧以 Denied 结束，客户端收到 Rst
+    assert_eq!(timeout(Duration::from_secs(5), denied_server.recv()).await.unwrap(), Err(LinkError::Denied));
+    assert_eq!(timeout(Duration::from_secs(5), denied.recv()).await.unwrap(), Err(LinkError::Reset));
+    timeout(Duration::from_secs(5), async {
+        while listener.stats().connections > 1 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("evicted connection stayed in the table");
+
+    // 其他网段的连接不受影响
+    kept.send(Bytes::from_static(b"kept")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), kept_server.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"kept")));
+    assert_eq!(listener.acl().deny.len(), 1);
+}
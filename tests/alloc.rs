@@ -0,0 +1,307 @@
+//! 热路径分配测试：以计数的全局分配器驱动一对 `ConnectionCore` 回显消息。稳定状态下数据报缓冲来自缓冲池、
+//! 分片消息在连接的暂存缓冲中拼合，回显 N 条消息的缓冲大小的分配次数与 N 无关；大消息的突发把暂存缓冲撑过高水位后，
+//! 闲置 `SCRATCH_QUIET` 即释放，占用的内存回到突发之前的水平。确认以栈上的 `AckFrame` 编码与解析，
+//! 稳定状态下收发普通确认与带 SACK 区间的确认都不分配
+
+use bytes::Bytes;
+use link_rs::config::LinkConfig;
+use link_rs::endpoint::{ConnectionCore, Opener};
+use link_rs::pool::{BufferPool, RecvArena, SCRATCH_QUIET};
+use link_rs::seq::SeqNum;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+// 按字节对齐、不小于它的分配是缓冲（数据报、接收块、拼合的消息）；段与定时器的队列按 8 字节对齐，是簿记
+const BUFFER_SIZE: usize = 512;
+
+// 突发中的大消息
+const JUMBO: usize = 1024 * 1024;
+
+thread_local! {
+    static BUFFERS: Cell<u64> = const { Cell::new(0) };    // 本线程缓冲大小的分配次数
+    static LIVE: Cell<isize> = const { Cell::new(0) };     // 本线程分配、尚未释放的字节数
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) }; // 本线程所有的分配次数
+}
+
+// 按线程计数，并行运行的其他测试不影响结果
+struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        note(layout, 0, layout.size());
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = LIVE.try_with(|live| live.set(live.get() - layout.size() as isize));
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        note(layout, layout.size(), new_size);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+fn note(layout: Layout, old: usize, new: usize) {
+    if layout.align() == 1 && new >= BUFFER_SIZE {
+        let _ = BUFFERS.try_with(|buffers| buffers.set(buffers.get() + 1));
+    }
+    let _ = LIVE.try_with(|live| live.set(live.get() + new as isize - old as isize));
+    let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+}
+
+fn allocations() -> u64 {
+    ALLOCATIONS.with(Cell::get)
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn buffers() -> u64 {
+    BUFFERS.with(Cell::get)
+}
+
+fn live() -> isize {
+    LIVE.with(Cell::get)
+}
+
+// 直接相连的一对协议状态：数据报经接收块交给对端，发出后把缓冲还给池
+struct Pair {
+    a: ConnectionCore,
+    b: ConnectionCore,
+    pool: Arc<BufferPool>,
+    arena: RecvArena,
+    now: Instant,
+}
+
+impl Pair {
+    fn new(config: &LinkConfig) -> Self {
+        let mut opener_a = Opener::new(SeqNum::new(9000), config).unwrap();
+        let mut opener_b = Opener::new(SeqNum::new(3000), config).unwrap();
+        let syn_ack = opener_b.on_segment(&opener_a.retransmission()).unwrap().unwrap();
+        let ack = opener_a.on_segment(&syn_ack).unwrap().unwrap();
+        opener_b.on_segment(&ack).unwrap();
+        let now = Instant::now();
+        let pool = Arc::new(BufferPool::new(config));
+        let a = ConnectionCore::new(opener_a.finish(), config, "10.0.0.2:2".parse().unwrap(), pool.clone(), now);
+        let b = ConnectionCore::new(opener_b.finish(), config, "10.0.0.1:1".parse().unwrap(), pool.clone(), now);
+        Self { a, b, pool, arena: RecvArena::new(config.recv_buffer), now }
+    }
+
+    fn deliver(from: &mut ConnectionCore, to: &mut ConnectionCore, pool: &BufferPool, arena: &mut RecvArena, now: Instant) {
+        while let Some((datagram, _)) = from.poll_transmit() {
+            arena.space()[..datagram.len()].copy_from_slice(&datagram);
+            let received = arena.split(datagram.len());
+            pool.put(datagram);
+            to.handle_datagram(now, received);
+        }
+    }
+
+    // 交换一轮数据报：b 回显收到的每条消息，a 收到的回显交给 `echoed`；时钟前进 1ms
+    fn step(&mut self, echoed: &mut impl FnMut(Bytes)) {
+        let cx = &mut Context::from_waker(Waker::noop());
+        Self::deliver(&mut self.a, &mut self.b, &self.pool, &mut self.arena, self.now);
+        while let Poll::Ready(Ok(Some(message))) = self.b.poll_recv(0, cx, self.now) {
+            let mut data = Some(message);
+            while self.b.poll_send_message(0, cx, &mut data, self.now).is_pending() {
+                Self::deliver(&mut self.b, &mut self.a, &self.pool, &mut self.arena, self.now);
+                Self::deliver(&mut self.a, &mut self.b, &self.pool, &mut self.arena, self.now);
+            }
+        }
+        Self::deliver(&mut self.b, &mut self.a, &self.pool, &mut self.arena, self.now);
+        while let Poll::Ready(Ok(Some(message))) = self.a.poll_recv(0, cx, self.now) {
+            echoed(message);
+        }
+        self.now += Duration::from_millis(1);
+        self.a.handle_timeout(self.now);
+        self.b.handle_timeout(self.now);
+    }
+
+    // 发出 `messages` 条消息并等到全部回显，返回回显的字节数
+    fn echo(&mut self, messages: usize, message: &Bytes) -> usize {
+        let cx = &mut Context::from_waker(Waker::noop());
+        let (mut sent, mut received, mut bytes) = (0, 0, 0);
+        while received < messages {
+            if sent < messages {
+                let mut data = Some(message.clone());
+                if self.a.poll_send_message(0, cx, &mut data, self.now).is_ready() {
+                    sent += 1;
+                }
+            }
+            self.step(&mut |echoed| {
+                assert_eq!(echoed.len(), message.len());
+                received += 1;
+                bytes += echoed.len();
+            });
+        }
+        bytes
+    }
+
+    // 把 b 排队的确认逐个交给 a，返回 b 编码、a 处理它们时的分配次数，以及确认的个数
+    fn return_acks(&mut self) -> (u64, usize) {
+        let (mut allocated, mut acks) = (0, 0);
+        loop {
+            let before = allocations();
+            let Some((datagram, _)) = self.b.poll_transmit() else {
+                return (allocated, acks);
+            };
+            allocated += allocations() - before;
+            self.arena.space()[..datagram.len()].copy_from_slice(&datagram);
+            let ack = self.arena.split(datagram.len());
+            self.pool.put(datagram);
+            let before = allocations();
+            self.a.handle_datagram(self.now, ack);
+            allocated += allocations() - before;
+            acks += 1;
+        }
+    }
+
+    // 推进时钟，让空闲的定时器（暂存缓冲的释放等）到期
+    fn idle(&mut self, period: Duration) {
+        let until = self.now + period;
+        while self.now < until {
+            self.step(&mut |_| panic!("nothing is in flight"));
+        }
+    }
+}
+
+#[test]
+fn test_steady_state_echo_allocates_no_buffers() {
+    let config = LinkConfig::default();
+    let mut pair = Pair::new(&config);
+    let small = Bytes::from(vec![7u8; 100]);
+    let fragmented = Bytes::from(vec![9u8; 3 * config.mss]);
+    // 预热：缓冲池、接收块与暂存缓冲达到稳定的大小
+    pair.echo(200, &small);
+    pair.echo(50, &fragmented);
+
+    let mut counts = Vec::new();
+    for n in [100, 1000] {
+        let before = buffers();
+        assert_eq!(pair.echo(n, &small), n * small.len());
+        pair.echo(n / 10, &fragmented);
+        counts.push(buffers() - before);
+    }
+    // 回显的消息数增加十倍，缓冲的分配次数不随之增长
+    assert!(counts.iter().all(|&count| count <= 4), "buffer allocations for 110 and 1100 echoes: {:?}", counts);
+}
+
+// 稳定状态下发出几条 1 MiB 的消息，它们在两端的暂存缓冲中拼合；返回突发使内存增长的峰值，以及闲置之后仍多占的字节数
+fn jumbo_burst(scratch_high_water: usize) -> (isize, isize) {
+    let config = LinkConfig { buffer_pool: 64 * 1024, scratch_high_water, ..LinkConfig::default() };
+    let mut pair = Pair::new(&config);
+    let small = Bytes::from(vec![7u8; 100]);
+    // 预热到稳定状态：接收块已轮换过，换下的块计入基线
+    pair.echo(500, &Bytes::from(vec![9u8; 3 * config.mss]));
+    pair.idle(SCRATCH_QUIET * 2);
+    pair.echo(100, &small);
+    let baseline = live();
+
+    let jumbo = Bytes::from(vec![3u8; JUMBO]);
+    let cx = &mut Context::from_waker(Waker::noop());
+    let (mut peak, mut pending, mut received) = (baseline, 4, 0);
+    while received < 4 {
+        if pending > 0 {
+            let mut data = Some(jumbo.clone());
+            if pair.a.poll_send_message(0, cx, &mut data, pair.now).is_ready() {
+                pending -= 1;
+            }
+        }
+        pair.step(&mut |echoed| {
+            assert_eq!(echoed, jumbo);
+            received += 1;
+        });
+        peak = peak.max(live());
+    }
+    drop(jumbo);
+
+    // 闲置一段时间后小消息照常回显
+    pair.idle(SCRATCH_QUIET * 2);
+    pair.echo(100, &small);
+    (peak - baseline, live() - baseline)
+}
+
+#[test]
+fn test_memory_returns_to_baseline_after_a_jumbo_burst() {
+    let (grown, residue) = jumbo_burst(64 * 1024);
+    assert!(grown > 4 * JUMBO as isize, "burst grew memory by only {} bytes", grown);
+    // 两端的暂存缓冲都已释放，留下的只有各个队列扩大后的容量
+    assert!(residue < grown / 8, "{} of {} bytes still held after the burst", residue, grown);
+    // 高水位之上从不释放时，两端各自留着至少一条大消息大小的暂存缓冲
+    let (_, kept) = jumbo_burst(usize::MAX);
+    assert!(kept - residue >= 3 * JUMBO as isize / 2, "releasing the scratch buffers saved only {} bytes", kept - residue);
+}
+
+// a 发出 `messages` 个段，一段一个数据报，除第 `late` 个以外交给 b，b 的确认交给 a；迟到的那个随后补上，
+// 两次都统计 b 编码确认、a 处理确认时的分配次数。返回分配次数与确认的个数
+fn ack_round(pair: &mut Pair, messages: usize, late: Option<usize>) -> (u64, usize) {
+    let cx = &mut Context::from_waker(Waker::noop());
+    // 两个放不进同一个数据报
+    let message = Bytes::from(vec![5u8; pair.a.mss() * 2 / 3]);
+    // 先送走 a 还没发出的确认（回显之后剩下的）
+    Pair::deliver(&mut pair.a, &mut pair.b, &pair.pool, &mut pair.arena, pair.now);
+    for _ in 0..messages {
+        let mut data = Some(message.clone());
+        assert!(pair.a.poll_send_message(0, cx, &mut data, pair.now).is_ready());
+    }
+    let (mut held, mut index) = (None, 0);
+    while let Some((datagram, _)) = pair.a.poll_transmit() {
+        if late == Some(index) {
+            held = Some(datagram);
+        } else {
+            pair.arena.space()[..datagram.len()].copy_from_slice(&datagram);
+            let received = pair.arena.split(datagram.len());
+            pair.pool.put(datagram);
+            pair.b.handle_datagram(pair.now, received);
+        }
+        index += 1;
+    }
+    assert_eq!(index, messages, "{:?}", pair.a.stats());
+    let (mut allocated, mut acks) = pair.return_acks();
+    if let Some(datagram) = held {
+        pair.arena.space()[..datagram.len()].copy_from_slice(&datagram);
+        let received = pair.arena.split(datagram.len());
+        pair.pool.put(datagram);
+        pair.b.handle_datagram(pair.now, received);
+        let (more, filled) = pair.return_acks();
+        (allocated, acks) = (allocated + more, acks + filled);
+    }
+    let mut received = 0;
+    while let Poll::Ready(Ok(Some(_))) = pair.b.poll_recv(0, cx, pair.now) {
+        received += 1;
+    }
+    assert_eq!(received, messages);
+    pair.now += Duration::from_millis(1);
+    (allocated, acks)
+}
+
+#[test]
+fn test_acks_are_sent_and_processed_without_allocating() {
+    // 每次写入立即发出，每个数据段立即确认：确认逐个往返
+    let config = LinkConfig { nodelay: true, ack_every_n_segments: 1, ..LinkConfig::default() };
+    let mut pair = Pair::new(&config);
+    pair.echo(200, &Bytes::from(vec![7u8; 100]));
+    for _ in 0..20 {
+        ack_round(&mut pair, 3, None);
+        ack_round(&mut pair, 3, Some(0));
+    }
+
+    let (mut plain, mut sacked) = ((0, 0), (0, 0));
+    let duplicates = pair.a.stats().sender.duplicate_acks;
+    for _ in 0..50 {
+        let (allocated, acks) = ack_round(&mut pair, 3, None);
+        plain = (plain.0 + allocated, plain.1 + acks);
+        // 空洞之上只有两个段，不足以判定丢失，SACK 确认不引发快速重传
+        let (allocated, acks) = ack_round(&mut pair, 3, Some(0));
+        sacked = (sacked.0 + allocated, sacked.1 + acks);
+    }
+    // 确认数据报可能装着几个确认
+    assert!(plain.1 >= 50 && sacked.1 >= 100, "{:?} {:?}", plain, sacked);
+    assert!(pair.a.stats().sender.duplicate_acks > duplicates, "no ack carried SACK blocks");
+    assert_eq!(pair.a.stats().sender.segments_retransmitted, 0);
+    assert_eq!((plain.0, sacked.0), (0, 0), "allocations while sending and processing plain and SACK acks");
+}
@@ -0,0 +1,108 @@
+//! 阻塞客户端集成测试：同步的 `blocking::Connection` 在本机 UDP 上连接异步的服务器，
+//! 服务器跑在测试自己建立的 tokio 运行时里；客户端一侧没有任何异步代码
+#![cfg(all(feature = "blocking", feature = "tokio"))]
+
+use bytes::Bytes;
+use link_rs::blocking::Connection;
+use link_rs::config::LinkConfig;
+use link_rs::error::LinkError;
+use link_rs::fault::FaultConfig;
+use link_rs::listener::Listener;
+use link_rs::server::{EchoHandler, Server};
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap()
+}
+
+// 在 `runtime` 中运行回显服务器
+fn echo_server(runtime: &Runtime, config: LinkConfig) -> SocketAddr {
+    runtime.block_on(async {
+        let server = Server::bind("127.0.0.1:0", config, EchoHandler).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move { server.run().await });
+        addr
+    })
+}
+
+#[test]
+fn test_blocking_client_talks_to_the_async_server() {
+    let runtime = runtime();
+    let addr = echo_server(&runtime, LinkConfig::default());
+
+    let mut client = Connection::connect(addr, LinkConfig::default()).unwrap();
+    assert_eq!(client.peer_addr(), addr);
+    for i in 0..5u8 {
+        client.send(&[i; 100]).unwrap();
+    }
+    for i in 0..5u8 {
+        assert_eq!(client.recv(Duration::from_secs(5)).unwrap(), vec![i; 100]);
+    }
+    assert_eq!(client.stats().sender.segments_sent, 5);
+    client.close().unwrap();
+}
+
+#[test]
+fn test_recv_times_out() {
+    let runtime = runtime();
+    let addr = echo_server(&runtime, LinkConfig::default());
+
+    let mut client = Connection::connect(addr, LinkConfig::default()).unwrap();
+    let started = Instant::now();
+    let result = client.recv(Duration::from_millis(200));
+    assert!(matches!(result, Err(LinkError::Io { kind: io::ErrorKind::TimedOut, .. })), "{:?}", result);
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    // 超时之后连接照常可用
+    client.send(b"still there").unwrap();
+    assert_eq!(client.recv(Duration::from_secs(5)).unwrap(), b"still there");
+}
+
+#[test]
+fn test_lost_segments_are_retransmitted() {
+    let runtime = runtime();
+    // 服务端双向丢包：客户端的数据段与服务端的确认都会丢失
+    let faults = FaultConfig { loss: 0.2, seed: 7, ..FaultConfig::default() };
+    let config = LinkConfig { min_rto: Duration::from_millis(50), syn_retry_initial: Duration::from_millis(100), ..LinkConfig::default() };
+    let addr = echo_server(&runtime, LinkConfig { faults: Some(faults), ..config.clone() });
+
+    let mut client = Connection::connect(addr, config).unwrap();
+    for i in 0..20u8 {
+        client.send(&[i; 200]).unwrap();
+        assert_eq!(client.recv(Duration::from_secs(10)).unwrap(), vec![i; 200]);
+    }
+    assert!(client.stats().sender.segments_retransmitted > 0, "{:?}", client.stats());
+}
+
+#[test]
+fn test_peer_close_ends_recv() {
+    let runtime = runtime();
+    let listener = runtime.block_on(Listener::bind("127.0.0.1:0")).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let closing = runtime.spawn(async move {
+        let (server, _) = listener.accept().await.unwrap();
+        server.send(Bytes::from_static(b"bye")).await.unwrap();
+        server.close().await
+    });
+
+    let mut client = Connection::connect(addr, LinkConfig::default()).unwrap();
+    assert_eq!(client.recv(Duration::from_secs(5)).unwrap(), b"bye");
+    assert_eq!(client.recv(Duration::from_secs(5)), Err(LinkError::Closed));
+    client.close().unwrap();
+    runtime.block_on(closing).unwrap().unwrap();
+}
+
+#[test]
+fn test_connect_times_out_against_dead_port() {
+    let dead = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let config = LinkConfig { syn_retry_initial: Duration::from_millis(50), syn_max_retries: 2, syn_retry_jitter: 0.0, ..LinkConfig::default() };
+
+    // SYN 在 0、50、150ms 发出，350ms 时放弃
+    let result = Connection::connect(dead, config);
+    let Err(LinkError::ConnectTimeout { attempts: 3, elapsed }) = result else {
+        panic!("{:?}", result.map(|_| ()));
+    };
+    assert!(elapsed >= Duration::from_millis(350) && elapsed < Duration::from_secs(2), "{:?}", elapsed);
+}
@@ -0,0 +1,98 @@
+//! 内存预算集成测试：对端发来一条收不齐的大消息，接收方攒着的分片受 `connection_memory_limit` 约束，
+//! 超出时整条丢弃，之后的消息照常交付；全局的 `memory_limit` 放不下又一条连接时新的握手以 `SERVER_BUSY` 拒绝，
+//! 已有的连接照常收发
+
+mod common;
+
+use bytes::Bytes;
+use common::{accept, addr, connect, server_addr};
+use link_rs::budget::{Component, CONNECTION_OVERHEAD, GRANULE};
+use link_rs::config::LinkConfig;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::transport::MemoryNetwork;
+use std::time::Duration;
+use tokio::time::timeout;
+
+// 客户端的握手超时：被拒绝的握手不必等满默认的超时
+fn quick() -> LinkConfig {
+    LinkConfig { handshake_timeout: Duration::from_secs(2), ..LinkConfig::default() }
+}
+
+#[tokio::test]
+async fn test_reassembly_is_capped_by_the_connection_limit() {
+    const LIMIT: usize = 64 * 1024;
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { connection_memory_limit: Some(LIMIT), ..LinkConfig::default() };
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config).unwrap();
+    let client = connect(&network, addr(2, 5000), quick()).await.unwrap();
+    let server = accept(&listener).await;
+
+    // 远大于上限的消息：分片攒到上限即整条丢弃，剩下的分片到达后同样丢弃
+    let sending = tokio::spawn(async move {
+        client.send_msg(Bytes::from(vec![7u8; 1024 * 1024])).await.unwrap();
+        client.send_msg(Bytes::from_static(b"after the flood")).await.unwrap();
+        client
+    });
+    let received = timeout(Duration::from_secs(20), async {
+        let mut recv = Box::pin(server.recv());
+        loop {
+            tokio::select! {
+                received = &mut recv => break received,
+                _ = tokio::time::sleep(Duration::from_millis(1)) => {
+                    assert!(server.stats().memory_used <= LIMIT + GRANULE, "{}", server.stats().memory_used);
+                    for consumer in listener.memory_consumers(1) {
+                        assert!(consumer.used <= LIMIT + GRANULE, "{consumer:?}");
+                    }
+                }
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(received.unwrap(), Some(Bytes::from_static(b"after the flood")));
+    let _client = sending.await.unwrap();
+
+    let stats = server.stats();
+    assert!(stats.messages_discarded >= 1);
+    assert!(stats.memory_used >= CONNECTION_OVERHEAD);
+    let consumers = listener.memory_consumers(4);
+    assert_eq!(consumers.len(), 1);
+    assert_eq!(consumers[0].get(Component::Connection), CONNECTION_OVERHEAD);
+}
+
+#[tokio::test]
+async fn test_global_limit_refuses_the_next_connection() {
+    const N: usize = 3;
+    let network = MemoryNetwork::new();
+    let limit = N * CONNECTION_OVERHEAD + 15 * 1024;
+    let config = LinkConfig { memory_limit: Some(limit), ..LinkConfig::default() };
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config).unwrap();
+
+    let mut pairs = Vec::new();
+    for host in 0..N {
+        let client = connect(&network, addr(2 + host as u8, 5000), quick()).await.unwrap();
+        pairs.push((client, accept(&listener).await));
+    }
+    let error = connect(&network, addr(2 + N as u8, 5000), quick()).await.unwrap_err();
+    assert_eq!(error, LinkError::ServerBusy);
+    let metrics = listener.metrics();
+    assert_eq!(metrics.busy_refusals, 1);
+    assert_eq!(metrics.memory_limit, Some(limit));
+    assert!(metrics.memory_used >= N * CONNECTION_OVERHEAD && metrics.memory_used <= limit, "{}", metrics.memory_used);
+
+    // 剩余额度放不下的消息被拒绝，连接不受影响
+    let (_, server) = &pairs[0];
+    let error = server.send_msg(Bytes::from(vec![0u8; 32 * 1024])).await.unwrap_err();
+    assert!(matches!(error, LinkError::MemoryLimit { len: 32768, available } if available < 16 * 1024), "{error:?}");
+
+    // 收小了窗口的连接照常双向收发
+    for (i, (client, server)) in pairs.iter().enumerate() {
+        let request = Bytes::from(format!("request {i}"));
+        client.send(request.clone()).await.unwrap();
+        assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), Some(request));
+        let reply = Bytes::from(vec![i as u8; 4 * 1024]);
+        server.send_msg(reply.clone()).await.unwrap();
+        assert_eq!(timeout(Duration::from_secs(5), client.recv()).await.unwrap().unwrap(), Some(reply));
+    }
+}
@@ -0,0 +1,53 @@
+//! 突发接收集成测试：分发任务开始运行之前就排在内存传输中的 100 个数据报在一到两次唤醒内全部处理完，
+//! 随后取走的数据报与等到的第一个一样经过截断检查与访问控制
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use common::server_addr;
+use link_rs::config::LinkConfig;
+use link_rs::listener::Listener;
+use link_rs::segment::{Segment, SegmentType};
+use link_rs::transport::{MemoryNetwork, Transport};
+use std::time::Duration;
+use tokio::time::timeout;
+
+const BURST: usize = 100;
+
+#[tokio::test]
+async fn test_queued_burst_is_drained_in_few_wakeups() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { recv_buffer: 1024, deny: vec!["10.0.0.66".parse().unwrap()], ..LinkConfig::default() };
+    assert!(config.recv_batch > BURST / 2);
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config).unwrap();
+    let allowed = network.bind("10.0.0.2:5000".parse().unwrap()).unwrap();
+    let denied = network.bind("10.0.0.66:5000".parse().unwrap()).unwrap();
+
+    // 内存传输的发送从不挂起：分发任务在下面第一次让出执行权之前不会运行
+    let oversized = Segment::builder(SegmentType::Data).data_seq(1).payload(vec![0u8; 2048]).build().unwrap().encode().unwrap();
+    for i in 0..BURST {
+        match i % 10 {
+            0 => allowed.send_to(&oversized, server_addr()).await.unwrap(),
+            5 => denied.send_to(&[0xff; 8], server_addr()).await.unwrap(),
+            _ => allowed.send_to(&[0xff; 8], server_addr()).await.unwrap(),
+        };
+    }
+    assert_eq!(listener.metrics().datagrams_received, 0);
+
+    timeout(Duration::from_secs(5), async {
+        while listener.metrics().datagrams_received < BURST as u64 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .unwrap();
+    let metrics = listener.metrics();
+    assert_eq!(metrics.drained.datagrams, BURST as u64);
+    assert!(metrics.drained.wakeups() <= 2, "{:?}", metrics.drained);
+    assert!(metrics.drained.mean() >= (BURST / 2) as f64);
+
+    // 每个取走的数据报都经过了截断检查与访问控制
+    let stats = listener.stats();
+    assert_eq!((stats.truncated, stats.denied, stats.malformed), (10, 10, 80));
+    assert_eq!(metrics.truncated, 10);
+}
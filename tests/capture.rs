@@ -0,0 +1,75 @@
+//! 抓包集成测试：监听器与客户端共用一个 pcap 文件，一次短交换后重新解析文件，
+//! 每个记录的长度与其中的 IP/UDP 头一致，时间戳不递减，握手的段按发生顺序出现
+#![cfg(feature = "tokio")]
+
+use bytes::Bytes;
+use link_rs::capture::{Capture, PcapWriter};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::timeout;
+
+// 记录头之后的内容：(时间戳纳秒, 源端口, 目的端口, UDP 负载)
+fn parse(file: &[u8]) -> Vec<(u128, u16, u16, Vec<u8>)> {
+    assert_eq!(&file[..4], &0xa1b2_3c4du32.to_le_bytes());
+    assert_eq!(u32::from_le_bytes(file[20..24].try_into().unwrap()), 101);
+    let mut records = Vec::new();
+    let mut rest = &file[24..];
+    while !rest.is_empty() {
+        let field = |at: usize| u32::from_le_bytes(rest[at..at + 4].try_into().unwrap());
+        let (secs, nanos, incl, orig) = (field(0), field(4), field(8) as usize, field(12) as usize);
+        assert_eq!(incl, orig);
+        let packet = &rest[16..16 + incl];
+        // 环回地址都是 IPv4
+        assert_eq!(packet[0], 0x45);
+        assert_eq!(u16::from_be_bytes([packet[2], packet[3]]) as usize, incl);
+        let udp = &packet[20..];
+        assert_eq!(u16::from_be_bytes([udp[4], udp[5]]) as usize, udp.len());
+        let port = |at: usize| u16::from_be_bytes([udp[at], udp[at + 1]]);
+        records.push((u128::from(secs) * 1_000_000_000 + u128::from(nanos), port(0), port(2), udp[8..].to_vec()));
+        rest = &rest[16 + incl..];
+    }
+    records
+}
+
+#[tokio::test]
+async fn test_capture_records_exchange() {
+    let path = std::env::temp_dir().join(format!("link-rs-capture-{}.pcap", std::process::id()));
+    let writer = Arc::new(PcapWriter::create(&path).await.unwrap());
+    let config = LinkConfig { capture: Some(Capture::new(writer.clone())), ..Default::default() };
+
+    let listener = Listener::bind_with("127.0.0.1:0", config.clone()).await.unwrap();
+    let server_port = listener.local_addr().unwrap().port();
+    let exchange = async {
+        let client = Connection::connect_with(listener.local_addr().unwrap(), config).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        client.send(Bytes::from_static(b"ping")).await.unwrap();
+        assert_eq!(server.recv().await.unwrap().unwrap(), Bytes::from_static(b"ping"));
+        server.send(Bytes::from_static(b"pong")).await.unwrap();
+        assert_eq!(client.recv().await.unwrap().unwrap(), Bytes::from_static(b"pong"));
+        client.local_addr().unwrap().port()
+    };
+    let client_port = timeout(Duration::from_secs(5), exchange).await.unwrap();
+    writer.flush().await;
+
+    let file = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let records = parse(&file);
+    assert!(records.windows(2).all(|pair| pair[0].0 <= pair[1].0), "timestamps go backwards");
+    // 每个数据报被发送方与接收方各记录一次
+    for (_, src, dst, _) in &records {
+        assert!((*src, *dst) == (client_port, server_port) || (*src, *dst) == (server_port, client_port));
+    }
+    assert!(records.len().is_multiple_of(2) && records.len() >= 8, "{} records", records.len());
+
+    // 握手：客户端发出的 SYN 先于服务端收到它，SYN-ACK 随后
+    let syn = 2;
+    assert_eq!((records[0].1, records[0].3[4]), (client_port, syn));
+    assert_eq!((records[1].1, records[1].3[4]), (client_port, syn));
+    assert_eq!((records[2].1, records[2].3[4]), (server_port, syn));
+    let payloads = |needle: &[u8]| records.iter().filter(|record| record.3.windows(4).any(|window| window == needle)).count();
+    assert_eq!((payloads(b"ping"), payloads(b"pong")), (2, 2));
+    assert_eq!(writer.dropped(), 0);
+}
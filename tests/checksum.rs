@@ -0,0 +1,134 @@
+//! 校验算法集成测试：握手按发起方的偏好协商出双方都接受的算法（含以 cookie 回应的握手），
+//! 没有共同的算法时握手失败；途中损坏的段被校验发现并丢弃，由重传补齐，每种算法都一样
+
+mod common;
+
+use bytes::Bytes;
+use common::server_addr;
+use link_rs::checksum::ChecksumAlgorithm::{self, Crc32c, NoChecksum, XxHash32};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::cookie::SynCookies;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::segment::Segment;
+use link_rs::transport::{BoxFuture, MemoryNetwork, MemoryTransport, Transport};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::timeout;
+
+fn accepting(checksums: &[ChecksumAlgorithm]) -> LinkConfig {
+    LinkConfig { checksums: checksums.to_vec(), ..LinkConfig::default() }
+}
+
+fn client(network: &MemoryNetwork) -> MemoryTransport {
+    network.bind("10.0.0.2:0".parse().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_negotiates_initiator_preference() {
+    for syn_cookies in [SynCookies::Never, SynCookies::Always] {
+        for (offered, accepted, expected) in [
+            (vec![XxHash32, Crc32c], vec![Crc32c, XxHash32], XxHash32),
+            (vec![NoChecksum, Crc32c], vec![Crc32c], Crc32c),
+            (vec![NoChecksum], vec![XxHash32, NoChecksum], NoChecksum),
+        ] {
+            let network = MemoryNetwork::new();
+            let config = LinkConfig { syn_cookies, ..accepting(&accepted) };
+            let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config).unwrap();
+            let connection = Connection::connect_over(client(&network), server_addr(), accepting(&offered)).await.unwrap();
+            let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+            assert_eq!((connection.checksum(), server.checksum()), (expected, expected), "{:?} offered to {:?}", offered, accepted);
+
+            connection.send(Bytes::from_static(b"hello")).await.unwrap();
+            assert_eq!(server.recv().await.unwrap(), Some(Bytes::from_static(b"hello")));
+            server.send(Bytes::from_static(b"back")).await.unwrap();
+            assert_eq!(connection.recv().await.unwrap(), Some(Bytes::from_static(b"back")));
+            let (closed, _) = tokio::join!(connection.close(), async {
+                assert_eq!(server.recv().await.unwrap(), None);
+                server.close().await.unwrap();
+            });
+            closed.unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_no_common_algorithm_fails_the_handshake() {
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), accepting(&[XxHash32])).unwrap();
+    let result = Connection::connect_over(client(&network), server_addr(), accepting(&[Crc32c, NoChecksum])).await;
+    assert_eq!(result.unwrap_err(), LinkError::NoCommonChecksum);
+    // 监听器没有为失败的握手留下任何状态
+    assert!(timeout(Duration::from_millis(200), listener.accept()).await.is_err());
+    assert_eq!(listener.stats().half_open, 0);
+}
+
+// 翻转每第 `every` 个携带数据体的数据报中数据体的一位
+#[derive(Debug)]
+struct Corrupting {
+    inner: MemoryTransport,
+    every: u64,
+    seen: AtomicU64,
+    corrupted: AtomicU64,
+}
+
+impl Transport for Corrupting {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        Box::pin(async move {
+            if buf.len() > Segment::FIXED_HEADER_LEN && self.seen.fetch_add(1, Ordering::Relaxed) % self.every == self.every - 1 {
+                let mut corrupted = buf.to_vec();
+                corrupted[Segment::FIXED_HEADER_LEN] ^= 0x10;
+                self.corrupted.fetch_add(1, Ordering::Relaxed);
+                return self.inner.send_to(&corrupted, target).await;
+            }
+            self.inner.send_to(buf, target).await
+        })
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        self.inner.recv_from(buf)
+    }
+}
+
+#[tokio::test]
+async fn test_corrupted_segments_are_dropped_and_retransmitted() {
+    for algorithm in [Crc32c, XxHash32] {
+        let network = MemoryNetwork::new();
+        let config = accepting(&[algorithm]);
+        let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config.clone()).unwrap();
+        let transport = std::sync::Arc::new(Corrupting { inner: client(&network), every: 5, seen: AtomicU64::new(0), corrupted: AtomicU64::new(0) });
+        let connection = Connection::connect_over(transport.clone(), server_addr(), config).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+
+        let messages: Vec<Bytes> = (0..50u8).map(|i| Bytes::from(vec![i; 100])).collect();
+        let received = timeout(Duration::from_secs(30), async {
+            let sending = async {
+                for message in &messages {
+                    connection.send(message.clone()).await.unwrap();
+                }
+                connection.close().await.unwrap();
+            };
+            let receiving = async {
+                let mut received = Vec::new();
+                while let Some(message) = server.recv().await.unwrap() {
+                    received.push(message);
+                }
+                server.close().await.unwrap();
+                received
+            };
+            tokio::join!(sending, receiving).1
+        })
+        .await
+        .expect("transfer did not finish");
+        assert_eq!(received, messages, "{} let a corrupted payload through", algorithm);
+        assert!(transport.corrupted.load(Ordering::Relaxed) > 0);
+        assert_eq!(listener.metrics().decode_errors.checksum, transport.corrupted.load(Ordering::Relaxed));
+    }
+}
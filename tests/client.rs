@@ -0,0 +1,61 @@
+//! 客户端集成测试：在同一进程中启动回显服务器，客户端逻辑收到每条消息的回应，吞吐量测试得到非零的有效吞吐量；没有服务器时返回错误
+#![cfg(feature = "tokio")]
+
+use bytes::Bytes;
+use link_rs::client::{self, BenchOptions, ClientOptions};
+use link_rs::config::LinkConfig;
+use link_rs::server::{EchoHandler, Server};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_client_gets_every_reply() {
+    let server = Arc::new(Server::bind("127.0.0.1:0", LinkConfig::default(), EchoHandler).await.unwrap());
+    let addr = server.local_addr().unwrap();
+    let running = tokio::spawn({
+        let server = server.clone();
+        async move { server.run().await }
+    });
+
+    let options = ClientOptions { message: Bytes::from_static(b"ping"), count: 20, interval: Duration::from_millis(1), ..ClientOptions::default() };
+    let mut seqs = Vec::new();
+    let summary = client::run(addr, &options, LinkConfig::default(), |reply| {
+        assert_eq!(reply.message, options.message);
+        seqs.push(reply.seq);
+    })
+    .await
+    .unwrap();
+    assert_eq!(seqs, (0..20).collect::<Vec<_>>());
+    assert_eq!((summary.sent, summary.replied, summary.lost()), (20, 20, 0));
+    assert!(summary.min_rtt <= summary.mean_rtt() && summary.mean_rtt() <= summary.max_rtt);
+
+    server.shutdown();
+    running.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_client_fails_without_server() {
+    let unused = std::net::UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let config = LinkConfig { handshake_timeout: Duration::from_millis(300), ..LinkConfig::default() };
+    assert!(client::run(unused, &ClientOptions::default(), config, |_| panic!("no reply expected")).await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_bench_reports_goodput() {
+    let server = Arc::new(Server::bind("127.0.0.1:0", LinkConfig::default(), EchoHandler).await.unwrap());
+    let addr = server.local_addr().unwrap();
+    let running = tokio::spawn({
+        let server = server.clone();
+        async move { server.run().await }
+    });
+
+    let options = BenchOptions { duration: Duration::from_millis(200), streams: 2, ..BenchOptions::default() };
+    let report = client::bench(addr, &options, LinkConfig::default()).await.unwrap();
+    assert_eq!(report.connections.len(), 2);
+    assert!(report.goodput() > 0.0 && report.goodput().is_finite(), "goodput {}", report.goodput());
+    assert!(report.segments_sent() > 0);
+    assert!(report.srtt().is_some());
+
+    server.shutdown();
+    running.await.unwrap().unwrap();
+}
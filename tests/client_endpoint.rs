@@ -0,0 +1,144 @@
+//! 客户端端点集成测试：一个本地套接字上的 50 条并发连接连到同一个监听器，服务端看到的对端地址都相同，
+//! 各自的消息不串线；关闭或丢弃其中一些不影响其余的；句柄被丢弃后套接字在最后一条连接结束时释放；
+//! `shutdown` 中止连接，之后的 `connect` 以 `Closed` 失败
+
+mod common;
+
+use bytes::Bytes;
+use common::{client_addr, server_addr};
+use futures::future::join_all;
+use link_rs::client_endpoint::ClientEndpoint;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::transport::MemoryNetwork;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::timeout;
+
+const CONNECTIONS: usize = 50;
+
+// 把收到的每条消息原样发回，直到对端关闭
+fn echo(listener: Arc<Listener>) {
+    tokio::spawn(async move {
+        while let Ok((connection, peer)) = listener.accept().await {
+            assert_eq!(peer, client_addr());
+            assert_eq!(connection.peer_addr(), client_addr());
+            tokio::spawn(async move {
+                while let Ok(Some(message)) = connection.recv().await {
+                    if connection.send(message).await.is_err() {
+                        return;
+                    }
+                }
+                let _ = connection.close().await;
+            });
+        }
+    });
+}
+
+async fn round_trip(connection: &Connection, message: String) {
+    connection.send(Bytes::from(message.clone())).await.unwrap();
+    let echoed = timeout(Duration::from_secs(10), connection.recv()).await.unwrap().unwrap();
+    assert_eq!(echoed, Some(Bytes::from(message)));
+}
+
+#[tokio::test]
+async fn test_many_connections_share_one_socket() {
+    let network = MemoryNetwork::new();
+    let listener = Arc::new(Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap());
+    echo(listener.clone());
+    let endpoint = ClientEndpoint::with_transport(network.bind(client_addr()).unwrap(), LinkConfig::default()).unwrap();
+    assert_eq!(endpoint.local_addr().unwrap(), client_addr());
+
+    let mut connecting = Vec::new();
+    for _ in 0..CONNECTIONS {
+        connecting.push(endpoint.connect(server_addr()));
+    }
+    let connections: Vec<Connection> = join_all(connecting).await.into_iter().map(Result::unwrap).collect();
+    assert_eq!(endpoint.connections(), CONNECTIONS);
+    timeout(Duration::from_secs(5), async {
+        while listener.stats().connections < CONNECTIONS {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    // 每条连接的消息只回到它自己
+    for round in 0..3 {
+        let exchanges = connections.iter().enumerate().map(|(i, connection)| round_trip(connection, format!("connection {} round {}", i, round)));
+        join_all(exchanges).await;
+    }
+
+    // 正常关闭一部分、丢弃一部分，其余照常收发
+    let mut connections = connections.into_iter();
+    let closing: Vec<_> = connections.by_ref().take(10).map(|connection| connection.close()).collect();
+    for result in join_all(closing).await {
+        result.unwrap();
+    }
+    drop(connections.by_ref().take(10).collect::<Vec<_>>());
+    let remaining: Vec<Connection> = connections.collect();
+    let exchanges = remaining.iter().enumerate().map(|(i, connection)| round_trip(connection, format!("survivor {}", i)));
+    join_all(exchanges).await;
+    timeout(Duration::from_secs(5), async {
+        while endpoint.connections() > remaining.len() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(endpoint.connections(), remaining.len());
+
+    // 新连接与留下的连接并存
+    let late = endpoint.connect(server_addr()).await.unwrap();
+    round_trip(&late, "late".to_string()).await;
+}
+
+#[tokio::test]
+async fn test_socket_is_released_after_the_last_connection() {
+    let network = MemoryNetwork::new();
+    let listener = Arc::new(Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap());
+    echo(listener.clone());
+    let endpoint = ClientEndpoint::with_transport(network.bind(client_addr()).unwrap(), LinkConfig::default()).unwrap();
+    let connection = endpoint.connect(server_addr()).await.unwrap();
+
+    // 句柄被丢弃后连接仍可用，套接字仍被占用
+    drop(endpoint);
+    round_trip(&connection, "still here".to_string()).await;
+    assert_eq!(network.bind(client_addr()).unwrap_err().kind(), io::ErrorKind::AddrInUse);
+
+    connection.close().await.unwrap();
+    timeout(Duration::from_secs(5), async {
+        while network.bind(client_addr()).is_err() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_shutdown_aborts_connections() {
+    let network = MemoryNetwork::new();
+    let listener = Arc::new(Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap());
+    echo(listener.clone());
+    let endpoint = ClientEndpoint::with_transport(network.bind(client_addr()).unwrap(), LinkConfig::default()).unwrap();
+    let connection = endpoint.connect(server_addr()).await.unwrap();
+    round_trip(&connection, "before".to_string()).await;
+
+    timeout(Duration::from_secs(5), endpoint.shutdown()).await.unwrap();
+    assert_eq!(endpoint.connections(), 0);
+    assert!(timeout(Duration::from_secs(5), connection.recv()).await.unwrap().is_err());
+    assert_eq!(endpoint.connect(server_addr()).await.unwrap_err(), LinkError::Closed);
+}
+
+#[tokio::test]
+async fn test_connect_to_a_silent_address_times_out() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { handshake_timeout: Duration::from_millis(300), ..LinkConfig::default() };
+    let endpoint = ClientEndpoint::with_transport(network.bind(client_addr()).unwrap(), config).unwrap();
+    assert!(matches!(endpoint.connect(server_addr()).await, Err(LinkError::ConnectTimeout { .. })));
+    assert_eq!(endpoint.connections(), 0);
+}
@@ -0,0 +1,44 @@
+//! 关闭码集成测试：应用以自己的码复位连接，对端的 `recv` 以携带同一个码的 `Application` 失败；
+//! 传输层的码不能由应用发出；监听器以 `SERVER_BUSY` 拒绝握手时客户端得到 `ServerBusy`，码可以读回
+
+mod common;
+
+use bytes::Bytes;
+use common::{pair, server_addr};
+use link_rs::close_codes::CloseCode;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::transport::MemoryNetwork;
+use std::time::Duration;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_application_code_reaches_the_peer() {
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair(&network, LinkConfig::default()).await;
+
+    // 传输层的码留给本 crate，连接不受影响
+    assert!(matches!(client.reset(CloseCode::PROTOCOL_ERROR).await, Err(LinkError::Config(_))));
+    client.send(Bytes::from_static(b"still open")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"still open")));
+
+    let code = CloseCode::application(0x2a).unwrap();
+    client.reset(code).await.unwrap();
+    let error = timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap_err();
+    assert_eq!(error, LinkError::Application(code));
+    assert_eq!(error.close_code(), Some(code));
+    assert_eq!(client.send(Bytes::from_static(b"late")).await, Err(LinkError::Application(code)));
+}
+
+#[tokio::test]
+async fn test_refused_handshake_reports_the_code() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { max_connections: Some(1), ..LinkConfig::default() };
+    let (_listener, _client, _server) = pair(&network, config.clone()).await;
+
+    let transport = network.bind("10.0.0.3:5000".parse().unwrap()).unwrap();
+    let error = Connection::connect_over(transport, server_addr(), config).await.unwrap_err();
+    assert_eq!(error, LinkError::ServerBusy);
+    assert_eq!(error.close_code(), Some(CloseCode::SERVER_BUSY));
+}
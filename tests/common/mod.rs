@@ -0,0 +1,61 @@
+//! 集成测试共用的夹具：`MemoryNetwork` 上监听器与客户端的地址、连接与接受、建立一对连接、接收一条消息，以及在数据报中查找内容。
+//! 每个测试文件以 `mod common;` 引入，只用到其中的一部分
+#![allow(dead_code)]
+
+use bytes::Bytes;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::transport::MemoryNetwork;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::time::timeout;
+
+/// 监听器绑定的地址
+pub fn server_addr() -> SocketAddr {
+    "10.0.0.1:7000".parse().unwrap()
+}
+
+/// 只有一个客户端时它的地址
+pub fn client_addr() -> SocketAddr {
+    "10.0.0.2:5000".parse().unwrap()
+}
+
+/// 10.0.0.`host`:`port`，多个客户端时各用一个
+pub fn addr(host: u8, port: u16) -> SocketAddr {
+    SocketAddr::new(IpAddr::from([10, 0, 0, host]), port)
+}
+
+/// 从 `from` 以 `config` 连接 `server_addr` 上的监听器
+pub async fn connect(network: &MemoryNetwork, from: SocketAddr, config: LinkConfig) -> Result<Connection, LinkError> {
+    Connection::connect_over(network.bind(from).unwrap(), server_addr(), config).await
+}
+
+/// 等待监听器接受下一个连接，至多 5 秒
+pub async fn accept(listener: &Listener) -> Connection {
+    timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap().0
+}
+
+/// 两端都以 `config` 在 `server_addr` 与 `client_addr` 之间建立一对连接：监听器、客户端与服务端
+pub async fn pair(network: &MemoryNetwork, config: LinkConfig) -> (Listener, Connection, Connection) {
+    pair_with(network, config.clone(), config).await
+}
+
+/// 同 `pair`，监听器以 `server` 接受、客户端以 `client` 连接
+pub async fn pair_with(network: &MemoryNetwork, server: LinkConfig, client: LinkConfig) -> (Listener, Connection, Connection) {
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), server).unwrap();
+    let client = connect(network, client_addr(), client).await.unwrap();
+    let server = accept(&listener).await;
+    (listener, client, server)
+}
+
+/// 等待连接交付下一条消息，至多 5 秒；连接出错或已结束时 panic
+pub async fn recv(connection: &Connection) -> Bytes {
+    timeout(Duration::from_secs(5), connection.recv()).await.unwrap().unwrap().unwrap()
+}
+
+/// `needle` 是否原样出现在数据报中
+pub fn contains(datagram: &[u8], needle: &[u8]) -> bool {
+    datagram.windows(needle.len()).any(|window| window == needle)
+}
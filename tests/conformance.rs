@@ -0,0 +1,102 @@
+//! 一致性检查集成测试：`conformance::run` 对本 crate 自己的回显服务器与回显客户端通过全部检查，
+//! 检查本身随实现一起演进；不回显的对端在第一项需要回显的检查上失败，报告附上对端发来的段
+use bytes::Bytes;
+use link_rs::config::LinkConfig;
+use link_rs::conformance::{self, Outcome, Peer, SuiteConfig};
+use link_rs::connection::Connection;
+use link_rs::server::{ConnectionHandle, EchoHandler, Handler, Server};
+use link_rs::transport::MemoryNetwork;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn peer_addr() -> SocketAddr {
+    "10.0.0.1:7000".parse().unwrap()
+}
+
+fn suite_addr() -> SocketAddr {
+    "10.0.0.2:5000".parse().unwrap()
+}
+
+// 在后台运行服务器，返回值存活期间一直服务
+fn serve(network: &MemoryNetwork, handler: impl Handler) -> Arc<Server> {
+    let server = Arc::new(Server::with_transport(network.bind(peer_addr()).unwrap(), LinkConfig::default(), handler).unwrap());
+    tokio::spawn({
+        let server = server.clone();
+        async move { server.run().await }
+    });
+    server
+}
+
+fn config() -> SuiteConfig {
+    SuiteConfig { step_timeout: Duration::from_secs(20), ..SuiteConfig::default() }
+}
+
+// 什么都不发回的服务器
+struct Silent;
+
+impl Handler for Silent {
+    async fn on_message(&self, _conn: &ConnectionHandle, _message: Bytes) {}
+}
+
+#[tokio::test]
+async fn test_suite_passes_against_our_server() {
+    let network = MemoryNetwork::new();
+    let _server = serve(&network, EchoHandler);
+    let report = conformance::run(network.bind(suite_addr()).unwrap(), Peer::Server(peer_addr()), config()).await;
+    assert!(report.passed(), "{}", report);
+    assert_eq!(report.checks.len(), 6);
+    assert!(report.to_string().ends_with("6/6 checks passed"));
+}
+
+#[tokio::test]
+async fn test_suite_passes_against_our_client() {
+    let network = MemoryNetwork::new();
+    let transport = network.bind(peer_addr()).unwrap();
+    // 对端连接过来并原样发回收到的数据，我们关闭后它也关闭
+    let client = tokio::spawn(async move {
+        let connection = Connection::connect_over(transport, suite_addr(), LinkConfig::default()).await.unwrap();
+        while let Some(data) = connection.recv().await.unwrap() {
+            connection.send(data).await.unwrap();
+        }
+        connection.close().await
+    });
+    let report = conformance::run(network.bind(suite_addr()).unwrap(), Peer::Client, config()).await;
+    assert!(report.passed(), "{}", report);
+    client.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_failures_carry_the_peer_segments() {
+    let network = MemoryNetwork::new();
+    let _server = serve(&network, Silent);
+    let config = SuiteConfig { messages: 10, lossy_messages: 10, step_timeout: Duration::from_millis(300), ..SuiteConfig::default() };
+    let report = conformance::run(network.bind(suite_addr()).unwrap(), Peer::Server(peer_addr()), config).await;
+    assert!(!report.passed());
+
+    // 握手照常通过，需要回显的检查各自失败，附上对端在这期间发来的确认
+    assert!(matches!(report.checks[0].outcome, Outcome::Passed), "{}", report);
+    let Outcome::Failed { reason, evidence } = &report.checks[1].outcome else {
+        panic!("{}", report);
+    };
+    assert!(reason.contains("0 of 1 messages echoed"), "{}", reason);
+    assert!(!evidence.is_empty() && evidence.iter().all(|segment| segment.peer == peer_addr()));
+    assert!(evidence.iter().any(|segment| segment.summary.starts_with("Ack")), "{:?}", evidence);
+    let names: Vec<_> = report.failed().map(|check| check.name).collect();
+    assert!(names.contains(&"in-order delivery") && names.contains(&"loss recovery"), "{:?}", names);
+
+    // 文本报告中每个附上的段之后是它的 hexdump
+    let text = report.to_string();
+    assert!(text.contains("FAIL version negotiation: "), "{}", text);
+    assert!(text.contains("\n00000000  "), "{}", text);
+}
+
+#[tokio::test]
+async fn test_handshake_failure_skips_the_rest() {
+    let network = MemoryNetwork::new();
+    let config = SuiteConfig { link: LinkConfig { handshake_timeout: Duration::from_millis(200), ..LinkConfig::default() }, ..config() };
+    let report = conformance::run(network.bind(suite_addr()).unwrap(), Peer::Server(peer_addr()), config).await;
+    assert!(matches!(report.checks[0].outcome, Outcome::Failed { .. }), "{}", report);
+    assert!(report.checks[1..].iter().all(|check| matches!(check.outcome, Outcome::Skipped(_))), "{}", report);
+    assert!(report.to_string().ends_with("0/6 checks passed"));
+}
@@ -0,0 +1,222 @@
+//! 客户端 connect 集成测试：真实监听器上的握手，超时、拒绝与错误确认三种失败，`ConnectOptions` 调整的 SYN 重传，两端同时互相连接，
+//! 绑定指定的源地址与网络接口，以及解析出多个地址时的依次尝试
+#![cfg(feature = "tokio")]
+
+use bytes::{Bytes, BytesMut};
+use link_rs::config::LinkConfig;
+use link_rs::connection::{CONNECT_STAGGER, ConnectOptions, Connection};
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::segment::{Segment, SegmentType};
+use link_rs::state::ConnState;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::{Instant, timeout};
+
+fn quick(handshake_timeout: Duration) -> LinkConfig {
+    LinkConfig { handshake_timeout, ..LinkConfig::default() }
+}
+
+// 假服务端：以 SYN 本身和它是第几个 SYN 调用 `reply` 生成回应
+async fn fake_server(reply: impl Fn(&Segment, usize) -> Option<Segment> + Send + 'static) -> (SocketAddr, tokio::task::JoinHandle<()>) {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    let task = tokio::spawn(async move {
+        let mut buf = vec![0u8; 65535];
+        let mut syns = 0;
+        loop {
+            let (len, from) = socket.recv_from(&mut buf).await.unwrap();
+            let mut datagram = BytesMut::from(&buf[..len]);
+            while let Ok(Some(segment)) = Segment::decode_from(&mut datagram) {
+                if segment.segment_type() != SegmentType::Syn {
+                    continue;
+                }
+                syns += 1;
+                if let Some(reply) = reply(&segment, syns) {
+                    socket.send_to(&reply.encode().unwrap(), from).await.unwrap();
+                }
+            }
+        }
+    });
+    (addr, task)
+}
+
+fn syn_ack(acking: link_rs::seq::SeqNum) -> Segment {
+    Segment::builder(SegmentType::Syn).data_seq(7u64).ack(acking).window(64).build().unwrap()
+}
+
+#[tokio::test]
+async fn test_connect_to_listener() {
+    let listener = Listener::bind("127.0.0.1:0").await.unwrap();
+    let server = listener.local_addr().unwrap();
+
+    let client = Connection::connect(server).await.unwrap();
+    assert_eq!(client.state(), ConnState::Established);
+    assert_eq!(client.peer_addr(), server);
+
+    let (accepted, peer) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    assert_eq!(accepted.state(), ConnState::Established);
+    assert_eq!(peer.port(), client.local_addr().unwrap().port());
+}
+
+#[tokio::test]
+async fn test_connect_times_out_against_dead_port() {
+    let dead = UdpSocket::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+
+    let started = Instant::now();
+    let result = Connection::connect_with(dead, quick(Duration::from_millis(300))).await;
+    // 第一次重传之前总超时就到期了
+    let Err(LinkError::ConnectTimeout { attempts: 1, elapsed }) = result else {
+        panic!("{:?}", result.map(|_| ()));
+    };
+    assert!(elapsed >= Duration::from_millis(300));
+    assert!(started.elapsed() >= Duration::from_millis(300));
+}
+
+#[tokio::test]
+async fn test_connect_options_override_the_syn_schedule() {
+    let dead = UdpSocket::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    let config = LinkConfig { syn_retry_jitter: 0.0, ..LinkConfig::default() };
+
+    // 重传用尽：SYN 在 0、50、150ms 发出，350ms 时放弃
+    let options = ConnectOptions { syn_retry_interval_initial: Some(Duration::from_millis(50)), syn_max_retries: Some(2), ..ConnectOptions::default() };
+    let result = Connection::connect_with_options(dead, config.clone(), options.clone()).await;
+    let Err(LinkError::ConnectTimeout { attempts: 3, elapsed }) = result else {
+        panic!("{:?}", result.map(|_| ()));
+    };
+    assert!(elapsed >= Duration::from_millis(350) && elapsed < Duration::from_secs(2), "{:?}", elapsed);
+
+    // 总超时先到期
+    let options = ConnectOptions { syn_max_retries: Some(10), overall_timeout: Some(Duration::from_millis(120)), ..options };
+    let result = Connection::connect_with_options(dead, config, options).await;
+    let Err(LinkError::ConnectTimeout { attempts: 2, elapsed }) = result else {
+        panic!("{:?}", result.map(|_| ()));
+    };
+    assert!(elapsed >= Duration::from_millis(120) && elapsed < Duration::from_secs(2), "{:?}", elapsed);
+}
+
+#[tokio::test]
+async fn test_rst_reply_is_refused() {
+    let (server, _task) = fake_server(|_, _| Some(Segment::builder(SegmentType::Rst).build().unwrap())).await;
+    let result = Connection::connect_with(server, quick(Duration::from_secs(5))).await;
+    assert_eq!(result.unwrap_err(), LinkError::Refused);
+}
+
+#[tokio::test]
+async fn test_wrong_ack_retries_once() {
+    // 第一个 SYN-ACK 确认错误的序列号，第二次握手正常
+    let (server, _task) = fake_server(|syn, n| Some(syn_ack(if n == 1 { syn.seq().wrapping_add(1) } else { syn.seq() }))).await;
+    let client = Connection::connect_with(server, quick(Duration::from_secs(5))).await.unwrap();
+    assert_eq!(client.state(), ConnState::Established);
+
+    // 每次都确认错误：重试一次后以协议错误失败
+    let (server, _task) = fake_server(|syn, _| Some(syn_ack(syn.seq().wrapping_add(1)))).await;
+    let result = Connection::connect_with(server, quick(Duration::from_secs(5))).await;
+    assert!(matches!(result, Err(LinkError::Protocol(_))));
+}
+
+#[tokio::test]
+async fn test_listener_resets_unknown_peers() {
+    let listener = Listener::bind("127.0.0.1:0").await.unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(listener.local_addr().unwrap()).await.unwrap();
+
+    let data = Segment::new(SegmentType::Data, 1, b"stray".to_vec());
+    socket.send(&data.encode().unwrap()).await.unwrap();
+    let mut buf = vec![0u8; 1024];
+    let len = timeout(Duration::from_secs(5), socket.recv(&mut buf)).await.unwrap().unwrap();
+    assert_eq!(Segment::decode(&buf[..len]).unwrap().segment_type(), SegmentType::Rst);
+}
+
+#[tokio::test]
+async fn test_simultaneous_connect() {
+    // 先取得两个空闲端口，再让两端同时连接对方
+    let free = || async { UdpSocket::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap() };
+    let (addr_a, addr_b) = (free().await, free().await);
+    let (a, b) = tokio::join!(
+        Connection::connect_from(addr_a, addr_b, quick(Duration::from_secs(5))),
+        Connection::connect_from(addr_b, addr_a, quick(Duration::from_secs(5))),
+    );
+    let (a, b) = (a.unwrap(), b.unwrap());
+    assert_eq!((a.state(), b.state()), (ConnState::Established, ConnState::Established));
+    assert_eq!(a.peer_addr(), addr_b);
+
+    a.send(Bytes::from_static(b"ping")).await.unwrap();
+    b.send(Bytes::from_static(b"pong")).await.unwrap();
+    let received = timeout(Duration::from_secs(5), b.recv()).await.unwrap().unwrap().unwrap();
+    assert_eq!(received, Bytes::from_static(b"ping"));
+    let received = timeout(Duration::from_secs(5), a.recv()).await.unwrap().unwrap().unwrap();
+    assert_eq!(received, Bytes::from_static(b"pong"));
+}
+
+#[tokio::test]
+async fn test_connect_from_chosen_source_address() {
+    // 127.0.0.2 只在整个 127/8 都是回环的平台上可用
+    let source: SocketAddr = "127.0.0.2:0".parse().unwrap();
+    if std::net::UdpSocket::bind(source).is_err() {
+        return;
+    }
+    let listener = Listener::bind("127.0.0.1:0").await.unwrap();
+    let server = listener.local_addr().unwrap();
+    let options = ConnectOptions { local_addr: Some(source), ..ConnectOptions::default() };
+    let client = Connection::connect_with_options(server, LinkConfig::default(), options).await.unwrap();
+    let (_accepted, peer) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    assert_eq!(peer.ip(), source.ip());
+    assert_eq!(client.stats().local_addr, Some(peer));
+}
+
+#[tokio::test]
+async fn test_connect_from_foreign_address_fails() {
+    // TEST-NET-1 的地址不会配置在测试机上
+    let foreign: SocketAddr = "192.0.2.1:0".parse().unwrap();
+    let options = ConnectOptions { local_addr: Some(foreign), ..ConnectOptions::default() };
+    let result = Connection::connect_with_options("127.0.0.1:9".parse().unwrap(), quick(Duration::from_millis(100)), options).await;
+    assert_eq!(result.unwrap_err(), LinkError::AddrNotLocal(foreign));
+
+    // 不存在的接口：支持绑定接口的平台报告接口名，其他平台报告不支持
+    let options = ConnectOptions { interface: Some("nosuchif0".to_string()), ..ConnectOptions::default() };
+    let error = Connection::connect_with_options("127.0.0.1:9".parse().unwrap(), quick(Duration::from_millis(100)), options).await.unwrap_err();
+    if cfg!(any(target_os = "linux", target_os = "android")) {
+        assert!(error.to_string().contains("nosuchif0"), "{}", error);
+    } else {
+        assert_eq!(error, LinkError::InterfaceUnsupported);
+    }
+}
+
+// 绑定后立即释放的端口：发往它的 SYN 得不到回应
+async fn dead_port(ip: &str) -> Option<SocketAddr> {
+    Some(UdpSocket::bind((ip, 0)).await.ok()?.local_addr().unwrap())
+}
+
+#[tokio::test]
+async fn test_resolved_addresses_fall_back_after_the_stagger() {
+    let listener = Listener::bind("127.0.0.1:0").await.unwrap();
+    let live = listener.local_addr().unwrap();
+    let dead = dead_port("127.0.0.1").await.unwrap();
+
+    // 第一个地址没有回应，过 CONNECT_STAGGER 后第二个地址的握手开始并完成
+    let started = Instant::now();
+    let client = Connection::connect_with(&[dead, live][..], quick(Duration::from_secs(5))).await.unwrap();
+    assert!(started.elapsed() >= CONNECT_STAGGER);
+    assert!(started.elapsed() < CONNECT_STAGGER + Duration::from_secs(1), "{:?}", started.elapsed());
+    assert_eq!(client.peer_addr(), live);
+
+    // 主机名同样解析后依次尝试，localhost 先解析出 ::1 时退回 127.0.0.1
+    let client = Connection::connect(format!("localhost:{}", live.port())).await.unwrap();
+    assert_eq!(client.peer_addr().ip().to_canonical(), live.ip());
+}
+
+#[tokio::test]
+async fn test_every_resolved_address_failing_is_reported() {
+    let mut dead = vec![dead_port("127.0.0.1").await.unwrap()];
+    // IPv6 地址与 IPv4 地址一样被尝试
+    dead.extend(dead_port("::1").await);
+    dead.push(dead_port("127.0.0.1").await.unwrap());
+    let result = Connection::connect_with(&dead[..], quick(Duration::from_millis(300))).await;
+    let Err(LinkError::ConnectFailed(attempts)) = result else {
+        panic!("{:?}", result.map(|_| ()));
+    };
+    assert_eq!(attempts.iter().map(|(addr, _)| *addr).collect::<Vec<_>>(), dead);
+    assert!(attempts.iter().all(|(_, e)| matches!(e, LinkError::ConnectTimeout { .. })), "{:?}", attempts);
+}
@@ -0,0 +1,237 @@
+//! 加密集成测试（`crypto` 特性）：同一密钥的两端正常收发（包括以 cookie 回应的握手），线上看不到明文；
+//! 握手段被篡改时被丢弃，密钥不同或只有一端配置了密钥时握手失败
+#![cfg(feature = "crypto")]
+
+mod common;
+
+use bytes::Bytes;
+use common::server_addr;
+use link_rs::checksum::ChecksumAlgorithm;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::cookie::SynCookies;
+use link_rs::crypto::PresharedKey;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::segment::Segment;
+use link_rs::session::SessionError;
+use link_rs::transport::{BoxFuture, MemoryNetwork, MemoryTransport, Transport};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::time::timeout;
+
+const SECRET: &[u8] = b"the eagle lands at midnight";
+
+fn keyed(psk: Option<PresharedKey>) -> LinkConfig {
+    LinkConfig { psk, handshake_timeout: Duration::from_millis(500), ..LinkConfig::default() }
+}
+
+// 记下客户端发出的每个数据报
+#[derive(Debug)]
+struct Recording {
+    inner: MemoryTransport,
+    sent: Mutex<Vec<Vec<u8>>>,
+}
+
+impl Transport for Recording {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        Box::pin(async move {
+            self.sent.lock().unwrap().push(buf.to_vec());
+            self.inner.send_to(buf, target).await
+        })
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        self.inner.recv_from(buf)
+    }
+}
+
+// 翻转客户端前 `remaining` 个数据报中握手 nonce 的一位；不校验校验和，篡改只能由认证发现
+#[derive(Debug)]
+struct Tampering {
+    inner: MemoryTransport,
+    remaining: AtomicUsize,
+}
+
+impl Transport for Tampering {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        Box::pin(async move {
+            let mut datagram = buf.to_vec();
+            if self.remaining.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1)).is_ok() {
+                let at = datagram.len() - Segment::AUTH_TRAILER_LEN;
+                datagram[at] ^= 1;
+            }
+            self.inner.send_to(&datagram, target).await
+        })
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        self.inner.recv_from(buf)
+    }
+}
+
+// 丢弃客户端发出的第 `index` 个数据报
+#[derive(Debug)]
+struct Dropping {
+    inner: MemoryTransport,
+    index: usize,
+    sent: AtomicUsize,
+}
+
+impl Transport for Dropping {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        Box::pin(async move {
+            if self.sent.fetch_add(1, Ordering::Relaxed) == self.index {
+                return Ok(buf.len());
+            }
+            self.inner.send_to(buf, target).await
+        })
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        self.inner.recv_from(buf)
+    }
+}
+
+#[tokio::test]
+async fn test_encrypted_echo() {
+    let psk = PresharedKey::random();
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), keyed(Some(psk.clone()))).unwrap();
+    let transport = std::sync::Arc::new(Recording { inner: network.bind("10.0.0.2:0".parse().unwrap()).unwrap(), sent: Mutex::new(Vec::new()) });
+    let client = Connection::connect_over(transport.clone(), server_addr(), keyed(Some(psk))).await.unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+
+    for _ in 0..3 {
+        client.send(Bytes::from_static(SECRET)).await.unwrap();
+        let message = server.recv().await.unwrap().unwrap();
+        assert_eq!(message, SECRET);
+        server.send(message).await.unwrap();
+        assert_eq!(client.recv().await.unwrap().unwrap(), SECRET);
+    }
+    let (closed, _) = tokio::join!(client.close(), async {
+        assert_eq!(server.recv().await.unwrap(), None);
+        server.close().await.unwrap();
+    });
+    closed.unwrap();
+    let sent = transport.sent.lock().unwrap();
+    assert!(sent.iter().all(|datagram| !datagram.windows(SECRET.len()).any(|w| w == SECRET)), "plaintext on the wire");
+    assert_eq!(listener.metrics().decode_errors.decrypt, 0);
+}
+
+#[tokio::test]
+async fn test_keyed_handshake_with_cookies() {
+    let psk = PresharedKey::random();
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { syn_cookies: SynCookies::Always, ..keyed(Some(psk.clone())) };
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config).unwrap();
+    let transport = network.bind("10.0.0.2:0".parse().unwrap()).unwrap();
+    let client = Connection::connect_over(transport, server_addr(), keyed(Some(psk))).await.unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+
+    client.send(Bytes::from_static(SECRET)).await.unwrap();
+    assert_eq!(server.recv().await.unwrap().unwrap(), SECRET);
+    server.send(Bytes::from_static(b"ack")).await.unwrap();
+    assert_eq!(client.recv().await.unwrap().unwrap(), "ack");
+}
+
+#[tokio::test]
+async fn test_lost_final_ack_is_resent() {
+    // 签名的最后确认丢失：未签名的数据不能完成握手，服务端重发 SYN-ACK，客户端重发签名的确认
+    let psk = PresharedKey::random();
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), keyed(Some(psk.clone()))).unwrap();
+    let transport = Dropping { inner: network.bind("10.0.0.2:0".parse().unwrap()).unwrap(), index: 1, sent: AtomicUsize::new(0) };
+    let client = Connection::connect_over(transport, server_addr(), keyed(Some(psk))).await.unwrap();
+    client.send(Bytes::from_static(SECRET)).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.expect("handshake never completed").unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap(), SECRET);
+}
+
+#[tokio::test]
+async fn test_tampered_nonce_fails_authentication() {
+    let psk = PresharedKey::random();
+    let unchecked = |psk| LinkConfig { checksums: vec![ChecksumAlgorithm::NoChecksum], handshake_timeout: Duration::from_secs(3), ..keyed(psk) };
+    // 只篡改第一个 SYN：它被丢弃，重传的 SYN 完成握手
+    for (tampered, connects) in [(1, true), (usize::MAX, false)] {
+        let network = MemoryNetwork::new();
+        let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), unchecked(Some(psk.clone()))).unwrap();
+        let transport = Tampering { inner: network.bind("10.0.0.2:0".parse().unwrap()).unwrap(), remaining: AtomicUsize::new(tampered) };
+        let result = Connection::connect_over(transport, server_addr(), unchecked(Some(psk.clone()))).await;
+        match connects {
+            true => assert!(result.is_ok(), "{:?}", result.err()),
+            false => assert!(matches!(result, Err(LinkError::ConnectTimeout { .. })), "{:?}", result.map(|_| ())),
+        }
+        assert!(listener.metrics().decode_errors.decrypt > 0);
+    }
+}
+
+#[tokio::test]
+async fn test_mismatched_keys_fail_the_handshake() {
+    // 密钥不同：SYN 无法通过认证
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), keyed(Some(PresharedKey::new([1; 32])))).unwrap();
+    let transport = network.bind("10.0.0.2:0".parse().unwrap()).unwrap();
+    let result = Connection::connect_over(transport, server_addr(), keyed(Some(PresharedKey::new([2; 32])))).await;
+    assert!(matches!(result, Err(LinkError::ConnectTimeout { .. })), "{:?}", result.map(|_| ()));
+    assert!(listener.metrics().decode_errors.decrypt > 0);
+
+    // 客户端没有密钥：未签名的 SYN 以 `AUTH_FAILED` 被拒绝
+    let transport = network.bind("10.0.0.2:0".parse().unwrap()).unwrap();
+    let result = Connection::connect_over(transport, server_addr(), keyed(None)).await;
+    assert_eq!(result.unwrap_err(), LinkError::AuthFailed);
+
+    // 服务端没有密钥：客户端忽略未签名的 SYN-ACK
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), keyed(None)).unwrap();
+    let transport = network.bind("10.0.0.2:0".parse().unwrap()).unwrap();
+    let result = Connection::connect_over(transport, server_addr(), keyed(Some(PresharedKey::new([2; 32])))).await;
+    assert!(matches!(result, Err(LinkError::ConnectTimeout { .. })), "{:?}", result.map(|_| ()));
+    assert!(timeout(Duration::from_millis(50), listener.accept()).await.is_err(), "server accepted a connection");
+}
+
+#[tokio::test]
+async fn test_handoff_rederives_the_session_keys() {
+    let psk = PresharedKey::random();
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), keyed(Some(psk.clone()))).unwrap();
+    let transport = network.bind("10.0.0.2:0".parse().unwrap()).unwrap();
+    let client = Connection::connect_over(transport, server_addr(), keyed(Some(psk.clone()))).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    client.send(Bytes::from_static(b"before")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap(), Bytes::from_static(b"before"));
+    let state = server.export_state().await.unwrap();
+    drop(listener);
+
+    // 旧监听器的套接字随它的任务退出后释放
+    let transport = loop {
+        match network.bind(server_addr()) {
+            Ok(transport) => break transport,
+            Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    };
+    let listener = Listener::with_transport(transport, keyed(Some(psk))).unwrap();
+    // 没有密钥的监听器无法接续加密的连接
+    let unkeyed = Listener::with_transport(network.bind("10.0.0.1:7001".parse().unwrap()).unwrap(), keyed(None)).unwrap();
+    assert!(matches!(unkeyed.adopt(state.clone()).await, Err(LinkError::Session(SessionError::KeyMismatch))));
+    let server = listener.adopt(state).await.unwrap();
+    client.send(Bytes::from_static(b"after")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap(), Bytes::from_static(b"after"));
+    server.send(Bytes::from_static(b"reply")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), client.recv()).await.unwrap().unwrap().unwrap(), Bytes::from_static(b"reply"));
+}
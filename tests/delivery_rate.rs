@@ -0,0 +1,99 @@
+//! 投递速率估计集成测试：客户端经过限速为 `RATE` 字节每秒的传输批量发送，
+//! `ConnectionStats::delivery_rate_bps` 收敛到链路的速率，`min_rtt` 接近没有排队时的往返时间
+
+mod common;
+
+use bytes::Bytes;
+use common::server_addr;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use link_rs::transport::{BoxFuture, MemoryNetwork, MemoryTransport, Transport};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{Instant, timeout};
+
+/// 链路速率（字节每秒）
+const RATE: u64 = 1_000_000;
+
+/// 速率为 `RATE` 的链路：每个数据报按长度占用 len / `RATE`，排在之前的数据报之后放出；发送立即返回。
+/// 放出时间按链路空闲的时刻累计，定时器的粒度不影响平均速率
+#[derive(Debug)]
+struct Throttled {
+    inner: Arc<MemoryTransport>,
+    free_at: StdMutex<Instant>,
+    link: mpsc::UnboundedSender<(Instant, Bytes, SocketAddr)>,
+}
+
+impl Throttled {
+    fn new(inner: MemoryTransport) -> Self {
+        let inner = Arc::new(inner);
+        let (link, mut queued) = mpsc::unbounded_channel::<(Instant, Bytes, SocketAddr)>();
+        tokio::spawn({
+            let inner = inner.clone();
+            async move {
+                while let Some((at, datagram, target)) = queued.recv().await {
+                    tokio::time::sleep_until(at).await;
+                    let _ = inner.send_to(&datagram, target).await;
+                }
+            }
+        });
+        Self { inner, free_at: StdMutex::new(Instant::now()), link }
+    }
+}
+
+impl Transport for Throttled {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        let mut free_at = self.free_at.lock().unwrap();
+        *free_at = (*free_at).max(Instant::now()) + Duration::from_nanos(buf.len() as u64 * 1_000_000_000 / RATE);
+        let _ = self.link.send((*free_at, Bytes::copy_from_slice(buf), target));
+        Box::pin(std::future::ready(Ok(buf.len())))
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        self.inner.recv_from(buf)
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_estimate_converges_to_the_link_rate() {
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap();
+    let throttled = Throttled::new(network.bind("10.0.0.2:5000".parse().unwrap()).unwrap());
+    let client = Connection::connect_over(throttled, server_addr(), LinkConfig::default()).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    let server = Arc::new(server);
+    let reader = tokio::spawn({
+        let server = server.clone();
+        async move { while let Ok(Some(_)) = server.recv().await {} }
+    });
+
+    // 约 2 秒的链路时间
+    for _ in 0..2000 {
+        client.send(Bytes::from(vec![7; 1000])).await.unwrap();
+    }
+    timeout(Duration::from_secs(10), async {
+        while client.stats().sender.bytes_acked < 2_000_000 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("transfer did not finish");
+
+    // 只计数据体：每个数据报的首部占去链路的一小部分
+    let stats = client.stats();
+    let measured = stats.delivery_rate_bps / 8;
+    assert!(measured > RATE * 8 / 10 && measured < RATE * 12 / 10, "{} bytes/s", measured);
+    // 一个数据报在链路上的时间约 1ms，排队使平均 RTT 远大于它
+    let min_rtt = stats.min_rtt.unwrap();
+    assert!(min_rtt < Duration::from_millis(5), "{:?}", min_rtt);
+    assert!(stats.srtt.unwrap() > min_rtt);
+    reader.abort();
+}
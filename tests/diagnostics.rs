@@ -0,0 +1,88 @@
+//! 启动自检集成测试：服务端经过 `FaultyTransport`，每种路径问题各得到对应的诊断；健康的路径诊断为正常，
+//! `Server::selftest` 经回环地址检查服务器自己
+#![cfg(feature = "tokio")]
+
+use link_rs::config::LinkConfig;
+use link_rs::diagnostics::{self, Diagnosis, SelftestOptions};
+use link_rs::fault::FaultConfig;
+use link_rs::server::{EchoHandler, Server};
+use link_rs::transport::MemoryNetwork;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+// 在内存网络上起一个服务端注入 `faults` 的回显服务器，从客户端做一次自检
+async fn selftest(faults: FaultConfig, options: SelftestOptions, client: LinkConfig) -> diagnostics::Report {
+    let network = MemoryNetwork::new();
+    let server_addr: SocketAddr = "10.0.0.1:7000".parse().unwrap();
+    let config = LinkConfig { faults: Some(faults), ..LinkConfig::default() };
+    let server = Arc::new(Server::with_transport(network.bind(server_addr).unwrap(), config, EchoHandler).unwrap());
+    let serving = tokio::spawn({
+        let server = server.clone();
+        async move { server.run().await }
+    });
+    let transport = network.bind("10.0.0.2:5000".parse().unwrap()).unwrap();
+    let report = diagnostics::selftest_over(transport, server_addr, &options, client).await.unwrap();
+    server.shutdown();
+    serving.await.unwrap().unwrap();
+    report
+}
+
+fn quick() -> SelftestOptions {
+    SelftestOptions { idle: Duration::ZERO, ..SelftestOptions::default() }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_healthy_path() {
+    let report = selftest(FaultConfig::default(), SelftestOptions { idle: Duration::from_secs(20), ..SelftestOptions::default() }, LinkConfig::default()).await;
+    assert_eq!(report.diagnosis, Diagnosis::Healthy);
+    assert!(report.handshake.is_some() && report.padded_rtt.is_some() && report.burst_rtt.is_some());
+    assert_eq!(report.loss, Some(0.0));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_no_connectivity() {
+    let report = selftest(FaultConfig { loss: 1.0, ..FaultConfig::default() }, quick(), LinkConfig::default()).await;
+    assert_eq!(report.diagnosis, Diagnosis::NoConnectivity);
+    assert_eq!(report.diagnosis.exit_code(), 3);
+    assert_eq!((report.handshake, report.loss), (None, None));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_small_path_mtu() {
+    let report = selftest(FaultConfig { mtu: Some(1100), ..FaultConfig::default() }, quick(), LinkConfig::default()).await;
+    assert_eq!(report.diagnosis, Diagnosis::PathMtu { below: 1200 });
+    assert_eq!((report.padded_rtt, report.loss), (None, Some(0.0)));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_high_loss() {
+    let report = selftest(FaultConfig { loss: 0.3, seed: 5, ..FaultConfig::default() }, quick(), LinkConfig::default()).await;
+    let Diagnosis::HighLoss { loss } = report.diagnosis else { panic!("unexpected {:?}", report) };
+    // 探测与 Pong 各自以 30% 丢失
+    assert!((0.3..0.8).contains(&loss), "loss {}", loss);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_nat_rebinding() {
+    // 映射 2s 后过期，客户端每 5s 才发出一次保活：第一次保活就从新端口到达
+    let client = LinkConfig { keepalive_interval: Duration::from_secs(5), path_keepalive_interval: Duration::from_secs(5), ..LinkConfig::default() };
+    let options = SelftestOptions { idle: Duration::from_secs(12), ..SelftestOptions::default() };
+    let report = selftest(FaultConfig { nat: Some(Duration::from_secs(2)), ..FaultConfig::default() }, options, client).await;
+    let Diagnosis::NatRebinding { after } = report.diagnosis else { panic!("unexpected {:?}", report) };
+    assert!(after >= Duration::from_secs(4) && after <= Duration::from_secs(6), "after {:?}", after);
+}
+
+#[tokio::test]
+async fn test_server_checks_itself_over_loopback() {
+    let server = Arc::new(Server::bind("0.0.0.0:0", LinkConfig::default(), EchoHandler).await.unwrap());
+    let serving = tokio::spawn({
+        let server = server.clone();
+        async move { server.run().await }
+    });
+    let options = SelftestOptions { burst: 10, idle: Duration::from_millis(200), ..SelftestOptions::default() };
+    let report = server.selftest(&options).await.unwrap();
+    assert_eq!(report.diagnosis, Diagnosis::Healthy, "{:?}", report);
+    server.shutdown();
+    serving.await.unwrap().unwrap();
+}
@@ -0,0 +1,64 @@
+//! 全双工集成测试：连接的两端在各自的任务里同时发送与接收（与 `examples/chat.rs` 相同的用法），
+//! 两个方向的消息交错进行，都按序完整到达，关闭写方向后对方的 `recv` 返回 None，整个过程不会死锁
+#![cfg(feature = "tokio")]
+
+use bytes::Bytes;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::timeout;
+
+const MESSAGES: usize = 500;
+
+// 在两个任务中同时发送 `MESSAGES` 条消息与读取对方的消息，返回读到的消息
+async fn chat(connection: Connection, name: &'static str) -> Vec<Bytes> {
+    let connection = Arc::new(connection);
+    let writer = tokio::spawn({
+        let connection = connection.clone();
+        async move {
+            for i in 0..MESSAGES {
+                connection.send(Bytes::from(format!("{} {}", name, i))).await.unwrap();
+                if i % 50 == 0 {
+                    tokio::task::yield_now().await;
+                }
+            }
+            connection.shutdown_write().await.unwrap();
+        }
+    });
+    let reader = tokio::spawn({
+        let connection = connection.clone();
+        async move {
+            let mut received = Vec::new();
+            while let Some(message) = connection.recv().await.unwrap() {
+                received.push(message);
+            }
+            received
+        }
+    });
+    let received = reader.await.unwrap();
+    writer.await.unwrap();
+    Arc::into_inner(connection).expect("both tasks have finished").close().await.unwrap();
+    received
+}
+
+fn expected(name: &str) -> Vec<Bytes> {
+    (0..MESSAGES).map(|i| Bytes::from(format!("{} {}", name, i))).collect()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_both_directions_at_once() {
+    let listener = Listener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = tokio::spawn(async move { chat(Connection::connect(addr).await.unwrap(), "client").await });
+    let server = tokio::spawn(async move {
+        let (connection, _) = listener.accept().await.unwrap();
+        chat(connection, "server").await
+    });
+
+    let (from_server, from_client) = timeout(Duration::from_secs(20), async { (client.await.unwrap(), server.await.unwrap()) })
+        .await
+        .expect("full-duplex exchange deadlocked");
+    assert_eq!(from_server, expected("server"));
+    assert_eq!(from_client, expected("client"));
+}
@@ -0,0 +1,145 @@
+//! 端到端集成测试：服务器与多个并发客户端跑在本机的真实 UDP 套接字上，握手完成、每条消息按序原样回显、
+//! 正常关闭，结束后服务端的连接表为空。每个场景都有两端经过故障注入（`LinkConfig::faults`）的丢包变体。
+//! 每一步都有宽松但有限的超时，超时时报告停在哪一步、各客户端的进度，而不是挂起
+#![cfg(feature = "tokio")]
+
+use bytes::Bytes;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::fault::FaultConfig;
+use link_rs::server::{Drained, EchoHandler, Server};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+// 丢包变体两个方向各丢 2%、乱序 1%；种子按端区分
+fn lossy(seed: u64) -> Option<FaultConfig> {
+    Some(FaultConfig { loss: 0.02, reorder: 0.01, seed, ..FaultConfig::default() })
+}
+
+fn config(faults: Option<FaultConfig>) -> LinkConfig {
+    LinkConfig { faults, ..LinkConfig::default() }
+}
+
+// 超时时带上步骤名与进度，便于定位
+async fn bounded<T>(step: &str, limit: Duration, progress: impl Fn() -> String, future: impl Future<Output = T>) -> T {
+    match timeout(limit, future).await {
+        Ok(value) => value,
+        Err(_) => panic!("{} did not finish within {:?}: {}", step, limit, progress()),
+    }
+}
+
+// 临时端口上的回显服务器
+struct TestServer {
+    server: Arc<Server>,
+    addr: SocketAddr,
+    running: JoinHandle<Result<Drained, link_rs::error::LinkError>>,
+}
+
+impl TestServer {
+    async fn spawn(faults: Option<FaultConfig>) -> TestServer {
+        let server = Arc::new(Server::bind("127.0.0.1:0", config(faults), EchoHandler).await.unwrap());
+        let addr = server.local_addr().unwrap();
+        let running = tokio::spawn({
+            let server = server.clone();
+            async move { server.run().await }
+        });
+        TestServer { server, addr, running }
+    }
+
+    // 等待连接表清空（已关闭的连接只剩墓碑），然后关闭服务器
+    async fn stop_when_empty(self) {
+        let listener = self.server.listener();
+        bounded("emptying the connection table", Duration::from_secs(10), || format!("{:?}", listener.stats()), async {
+            while listener.stats().connections > 0 || listener.stats().half_open > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+        self.server.shutdown();
+        let drained = bounded("server shutdown", Duration::from_secs(15), String::new, self.running).await.unwrap().unwrap();
+        assert_eq!(drained.aborted, 0, "{:?}", drained);
+    }
+}
+
+// 第 `client` 个客户端的第 `i` 条消息：带编号的前缀加上长度与内容都随编号变化的数据体
+fn message(client: usize, i: usize) -> Bytes {
+    let mut message = format!("{}:{}:", client, i).into_bytes();
+    message.extend((0..i % 512).map(|k| (k * 31 + i * 7 + client) as u8));
+    Bytes::from(message)
+}
+
+// `clients` 个客户端并发连接，各自边发边收 `messages` 条消息，全部按序原样回显后关闭
+async fn run_clients(addr: SocketAddr, clients: usize, messages: usize, faults: impl Fn(usize) -> Option<FaultConfig>, limit: Duration) {
+    let progress: Arc<Vec<AtomicUsize>> = Arc::new((0..clients).map(|_| AtomicUsize::new(0)).collect());
+    let tasks: Vec<_> = (0..clients)
+        .map(|client| {
+            let config = config(faults(client));
+            let progress = progress.clone();
+            tokio::spawn(async move {
+                let connection = Connection::connect_with(addr, config).await.unwrap_or_else(|e| panic!("client {} handshake: {}", client, e));
+                let send = async {
+                    for i in 0..messages {
+                        connection.send(message(client, i)).await.unwrap();
+                    }
+                };
+                let recv = async {
+                    for i in 0..messages {
+                        let echoed = connection.recv().await.unwrap().unwrap_or_else(|| panic!("client {} stream ended after {} messages", client, i));
+                        assert!(echoed == message(client, i), "client {} message {} came back altered or out of order", client, i);
+                        progress[client].fetch_add(1, Ordering::Relaxed);
+                    }
+                };
+                tokio::join!(send, recv);
+                connection.close().await.unwrap_or_else(|e| panic!("client {} close: {}", client, e));
+            })
+        })
+        .collect();
+
+    let report = || format!("messages echoed per client {:?}", progress.iter().map(|n| n.load(Ordering::Relaxed)).collect::<Vec<_>>());
+    bounded("clients", limit, report, async {
+        for task in tasks {
+            task.await.unwrap();
+        }
+    })
+    .await;
+}
+
+async fn exchange(faults: impl Fn(u64) -> Option<FaultConfig>, limit: Duration) {
+    let server = TestServer::spawn(faults(0)).await;
+    run_clients(server.addr, 4, 10_000, |client| faults(client as u64 + 1), limit).await;
+    server.stop_when_empty().await;
+}
+
+async fn short_lived(faults: impl Fn(u64) -> Option<FaultConfig>, limit: Duration) {
+    let server = TestServer::spawn(faults(0)).await;
+    // 一条消息就关闭的连接：握手与 FIN 交换占了大部分
+    for round in 0..4 {
+        run_clients(server.addr, 16, 1, |client| faults(100 * round + client as u64 + 1), limit).await;
+    }
+    server.stop_when_empty().await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_clients_exchange_in_order() {
+    exchange(|_| None, Duration::from_secs(60)).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_clients_exchange_in_order_under_loss() {
+    exchange(lossy, Duration::from_secs(120)).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_short_lived_clients_close_cleanly() {
+    short_lived(|_| None, Duration::from_secs(30)).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_short_lived_clients_close_cleanly_under_loss() {
+    short_lived(lossy, Duration::from_secs(60)).await;
+}
@@ -0,0 +1,115 @@
+//! 0-RTT 数据集成测试：打开 `accept_early_data` 的服务端在握手完成之前交付随 SYN 到达的数据，
+//! 关闭时在建立之后交付；SYN-ACK 丢失、以 Retry 验证地址时同样只交付一次；放不进握手数据报的数据被拒绝
+
+mod common;
+
+use bytes::Bytes;
+use common::{client_addr, server_addr};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::transport::MemoryNetwork;
+use std::time::Duration;
+use tokio::time::timeout;
+
+const REQUEST: &[u8] = b"GET /index.html";
+
+fn accepting() -> LinkConfig {
+    LinkConfig { accept_early_data: true, ..LinkConfig::default() }
+}
+
+// 0-RTT 数据只交付一次：之后的第一条消息是握手之后发出的
+async fn assert_delivered_once(client: &Connection, server: &Connection) {
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(REQUEST)));
+    client.send(Bytes::from_static(b"after")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"after")));
+}
+
+#[tokio::test]
+async fn test_early_data_arrives_before_the_handshake_completes() {
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), accepting()).unwrap();
+    // 只放行客户端的第一个数据报：SYN 与 0-RTT 数据
+    let mut sent = 0;
+    network.set_filter(move |_, _, to| {
+        if to == server_addr() {
+            sent += 1;
+            return sent == 1;
+        }
+        true
+    });
+    let transport = network.bind(client_addr()).unwrap();
+    let connecting = tokio::spawn(Connection::connect_over_with_data(transport, server_addr(), LinkConfig::default(), Bytes::from_static(REQUEST)));
+
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    assert!(server.accepted_early_data());
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(REQUEST)));
+
+    let client = timeout(Duration::from_secs(5), connecting).await.unwrap().unwrap().unwrap();
+    network.clear_filter();
+    client.send(Bytes::from_static(b"after")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"after")));
+}
+
+#[tokio::test]
+async fn test_ignored_early_data_is_sent_after_the_handshake() {
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap();
+    let transport = network.bind(client_addr()).unwrap();
+    let client = Connection::connect_over_with_data(transport, server_addr(), LinkConfig::default(), Bytes::from_static(REQUEST)).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+
+    assert!(!server.accepted_early_data());
+    assert_delivered_once(&client, &server).await;
+}
+
+#[tokio::test]
+async fn test_lost_syn_ack_is_resent_by_the_connection() {
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), accepting()).unwrap();
+    // 丢弃第一个 SYN-ACK：服务端的连接已由 0-RTT 数据建立，客户端重传的 SYN 由它回应
+    let mut replies = 0;
+    network.set_filter(move |_, _, to| {
+        if to == client_addr() {
+            replies += 1;
+            return replies > 1;
+        }
+        true
+    });
+    let transport = network.bind(client_addr()).unwrap();
+    let client = timeout(
+        Duration::from_secs(5),
+        Connection::connect_over_with_data(transport, server_addr(), LinkConfig::default(), Bytes::from_static(REQUEST)),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+
+    assert!(server.accepted_early_data());
+    assert_delivered_once(&client, &server).await;
+}
+
+#[tokio::test]
+async fn test_early_data_after_retry() {
+    let network = MemoryNetwork::new();
+    // 每个 SYN 都先以 Retry 验证地址：第一个数据报中的 0-RTT 数据被忽略，带回令牌的 SYN 之后的被接受
+    let config = LinkConfig { retry_threshold: Some(0), ..accepting() };
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config).unwrap();
+    let transport = network.bind(client_addr()).unwrap();
+    let client = Connection::connect_over_with_data(transport, server_addr(), LinkConfig::default(), Bytes::from_static(REQUEST)).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+
+    assert!(server.accepted_early_data());
+    assert_delivered_once(&client, &server).await;
+}
+
+#[tokio::test]
+async fn test_oversized_early_data_fails_before_sending() {
+    let network = MemoryNetwork::new();
+    let transport = network.bind(client_addr()).unwrap();
+    let data = Bytes::from(vec![0; LinkConfig::default().mss]);
+    let result = Connection::connect_over_with_data(transport, server_addr(), LinkConfig::default(), data).await;
+    assert!(matches!(result, Err(LinkError::MessageTooLarge { .. })), "{:?}", result);
+}
@@ -0,0 +1,58 @@
+//! 回显集成测试：50 个客户端并发连接同一个监听器，每个客户端的 1000 条消息都按序原样返回
+#![cfg(feature = "tokio")]
+
+use bytes::Bytes;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use std::time::Duration;
+use tokio::time::timeout;
+
+const CLIENTS: usize = 50;
+const MESSAGES: usize = 1000;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_clients_echo_in_order() {
+    let listener = Listener::bind("127.0.0.1:0").await.unwrap();
+    let server = listener.local_addr().unwrap();
+
+    // 每个连接一个回显任务
+    let acceptor = tokio::spawn(async move {
+        let mut echoes = Vec::new();
+        for _ in 0..CLIENTS {
+            let (connection, _) = listener.accept().await.unwrap();
+            echoes.push(tokio::spawn(async move {
+                while let Ok(Some(message)) = connection.recv().await {
+                    if connection.send(message).await.is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+        (listener, echoes)
+    });
+
+    let clients: Vec<_> = (0..CLIENTS)
+        .map(|id| {
+            tokio::spawn(async move {
+                let connection = Connection::connect(server).await.unwrap();
+                let send = async {
+                    for i in 0..MESSAGES {
+                        connection.send(Bytes::from(format!("{}-{}", id, i))).await.unwrap();
+                    }
+                };
+                let recv = async {
+                    for i in 0..MESSAGES {
+                        assert_eq!(connection.recv().await.unwrap().unwrap(), Bytes::from(format!("{}-{}", id, i)));
+                    }
+                };
+                tokio::join!(send, recv);
+            })
+        })
+        .collect();
+
+    for client in clients {
+        timeout(Duration::from_secs(60), client).await.expect("client timed out").unwrap();
+    }
+    let (listener, _echoes) = acceptor.await.unwrap();
+    assert_eq!(listener.stats().connections, CLIENTS);
+}
@@ -0,0 +1,116 @@
+//! 数据报过大集成测试：两端的传输都以 EMSGSIZE 拒绝超过 1400 字节的数据报，而配置的 MSS 是 1472。
+//! 被拒绝的数据报交还连接，有效 MSS 降到限制之下，其中的数据重新分片后发出：客户端的独占套接字与监听器的发送任务
+//! 两条路径上，分片消息与单段的消息都原样按序到达，一条不丢
+
+use bytes::Bytes;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::transport::{BoxFuture, MemoryNetwork, MemoryTransport, Transport};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::timeout;
+
+const LIMIT: usize = 1400;
+
+// 本机接口只允许 `LIMIT` 字节的传输：更大的数据报发送失败，计入 `rejected`
+#[derive(Debug)]
+struct Limited {
+    inner: MemoryTransport,
+    rejected: Arc<AtomicU64>,
+}
+
+impl Transport for Limited {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        if buf.len() > LIMIT {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            #[cfg(target_os = "linux")]
+            let e = io::Error::from_raw_os_error(90);
+            #[cfg(not(target_os = "linux"))]
+            let e = io::Error::from(LinkError::DatagramTooLarge { size: buf.len(), limit: LIMIT });
+            return Box::pin(async move { Err(e) });
+        }
+        self.inner.send_to(buf, target)
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        self.inner.recv_from(buf)
+    }
+}
+
+fn message(i: usize, len: usize) -> Bytes {
+    (0..len).map(|n| (n * 7 + i) as u8).collect()
+}
+
+#[tokio::test]
+async fn test_oversized_datagrams_are_refragmented() {
+    let network = MemoryNetwork::new();
+    let rejected = Arc::new(AtomicU64::new(0));
+    let limited = |addr: &str| Limited { inner: network.bind(addr.parse().unwrap()).unwrap(), rejected: rejected.clone() };
+    let config = LinkConfig { mss: 1472, nodelay: true, ..LinkConfig::default() };
+    let server_addr = "10.0.0.1:7000".parse().unwrap();
+    let listener = Listener::with_transport(limited("10.0.0.1:7000"), config.clone()).unwrap();
+    let client = Connection::connect_over(limited("10.0.0.2:5000"), server_addr, config).await.unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+    assert_eq!((client.mss(), server.mss()), (1472, 1472));
+
+    // 放得进 1472 字节、放不进 1400 字节的单段消息，与按 1472 字节分片的大消息交替；服务端原样回显
+    let sent: Vec<Bytes> = (0..40).map(|i| message(i, if i % 2 == 0 { 1420 } else { 6000 })).collect();
+    let echo = tokio::spawn(async move {
+        while let Ok(Some(message)) = server.recv().await {
+            server.send_msg(message).await.unwrap();
+        }
+        let mss = server.mss();
+        server.close().await.unwrap();
+        mss
+    });
+    let received = timeout(Duration::from_secs(20), async {
+        for (i, message) in sent.iter().enumerate() {
+            match i % 2 {
+                0 => client.send(message.clone()).await.unwrap(),
+                _ => client.send_msg(message.clone()).await.unwrap(),
+            }
+        }
+        let mut received = Vec::new();
+        while received.len() < sent.len() {
+            received.push(client.recv().await.unwrap().unwrap());
+        }
+        received
+    })
+    .await
+    .unwrap();
+    assert!(received == sent, "echoed messages differ");
+
+    // 两端都收敛到限制之下；被拒绝的数据不等重传就重新发出
+    assert!(rejected.load(Ordering::Relaxed) > 0);
+    assert!((LIMIT * 3 / 4..=LIMIT).contains(&client.mss()), "client mss {}", client.mss());
+    assert_eq!(client.stats().sender.segments_retransmitted, 0);
+    let (closed, mss) = tokio::join!(client.close(), echo);
+    closed.unwrap();
+    let mss = mss.unwrap();
+    assert!((LIMIT * 3 / 4..=LIMIT).contains(&mss), "server mss {}", mss);
+}
+
+#[tokio::test]
+async fn test_unshrinkable_datagram_fails_the_handshake() {
+    // 握手数据报本身放不下：连接建立失败，错误给出数据报的大小与配置的 MSS
+    let network = MemoryNetwork::new();
+    let rejected = Arc::new(AtomicU64::new(0));
+    let _listener = Listener::with_transport(network.bind("10.0.0.1:7000".parse().unwrap()).unwrap(), LinkConfig::default()).unwrap();
+    let transport = Limited { inner: network.bind("10.0.0.2:5000".parse().unwrap()).unwrap(), rejected: rejected.clone() };
+    let early = Bytes::from(vec![1u8; 1290]);
+    let config = LinkConfig { mss: 1472, ..LinkConfig::default() };
+    match Connection::connect_over_with_data(transport, "10.0.0.1:7000".parse().unwrap(), config, early).await {
+        Err(LinkError::DatagramTooLarge { size, limit }) => assert!(size > LIMIT && limit == 1472, "{} {}", size, limit),
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+    assert_eq!(rejected.load(Ordering::Relaxed), 1);
+}
@@ -0,0 +1,118 @@
+//! 故障切换集成测试：传输进行到一半时第一个后端失联，客户端在重传耗尽后切换到第二个后端，
+//! 重发未确认的消息，全部消息最终都到达（允许重发的部分重复），切换回调报告一次
+
+use bytes::Bytes;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::failover::{Connector, Failover, FailoverClient};
+use link_rs::listener::Listener;
+use link_rs::transport::{BoxFuture, MemoryNetwork};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::timeout;
+
+const MESSAGES: u32 = 300;
+
+fn primary() -> SocketAddr {
+    "10.0.0.1:7000".parse().unwrap()
+}
+
+fn backup() -> SocketAddr {
+    "10.0.0.2:7000".parse().unwrap()
+}
+
+// 很快判定失联：几次重传之后放弃
+fn config() -> LinkConfig {
+    LinkConfig {
+        min_rto: Duration::from_millis(50),
+        max_rto: Duration::from_millis(200),
+        max_retries: 3,
+        send_buffer: 8 * 1024,
+        handshake_timeout: Duration::from_secs(2),
+        ..LinkConfig::default()
+    }
+}
+
+struct MemoryConnector(MemoryNetwork);
+
+impl Connector for MemoryConnector {
+    fn connect(&self, remote: SocketAddr, config: LinkConfig) -> BoxFuture<'_, Result<Connection, LinkError>> {
+        Box::pin(async move {
+            let transport = self.0.bind("10.0.0.9:0".parse().unwrap()).map_err(LinkError::from)?;
+            Connection::connect_over(transport, remote, config).await
+        })
+    }
+}
+
+// 接受连接并按到达顺序记录消息中的序号；收到 `after` 条后通知一次
+fn serve(listener: Listener, received: Arc<Mutex<Vec<u32>>>, after: usize, reached: Arc<Notify>) {
+    tokio::spawn(async move {
+        while let Ok((connection, _)) = listener.accept().await {
+            let received = received.clone();
+            let reached = reached.clone();
+            tokio::spawn(async move {
+                while let Ok(Some(message)) = connection.recv().await {
+                    let mut received = received.lock().unwrap();
+                    received.push(u32::from_be_bytes(message[..4].try_into().unwrap()));
+                    if received.len() == after {
+                        reached.notify_one();
+                    }
+                }
+                let _ = connection.close().await;
+            });
+        }
+    });
+}
+
+fn message(id: u32) -> Bytes {
+    let mut data = id.to_be_bytes().to_vec();
+    data.resize(100, 0);
+    Bytes::from(data)
+}
+
+#[tokio::test]
+async fn test_transfer_completes_through_backup() {
+    let network = MemoryNetwork::new();
+    let on_primary = Arc::new(Mutex::new(Vec::new()));
+    let on_backup = Arc::new(Mutex::new(Vec::new()));
+    let killed = Arc::new(Notify::new());
+    serve(Listener::with_transport(network.bind(primary()).unwrap(), config()).unwrap(), on_primary.clone(), 100, killed.clone());
+    serve(Listener::with_transport(network.bind(backup()).unwrap(), config()).unwrap(), on_backup.clone(), 0, Arc::new(Notify::new()));
+
+    // 第一个后端收到 100 条后不再收发任何数据报
+    let blackhole = network.clone();
+    tokio::spawn(async move {
+        killed.notified().await;
+        blackhole.set_filter(|_, from, to| from != primary() && to != primary());
+    });
+
+    let failovers = Arc::new(Mutex::new(Vec::<Failover>::new()));
+    let recorded = failovers.clone();
+    let mut client = FailoverClient::with_connector(vec![primary(), backup()], config(), MemoryConnector(network.clone()))
+        .on_failover(move |failover| recorded.lock().unwrap().push(failover.clone()));
+    for id in 0..MESSAGES {
+        timeout(Duration::from_secs(20), client.send(message(id))).await.unwrap().unwrap();
+    }
+    assert_eq!(client.peer_addr(), Some(backup()));
+    timeout(Duration::from_secs(20), client.close()).await.unwrap().unwrap();
+
+    let failovers = failovers.lock().unwrap().clone();
+    assert_eq!(failovers.len(), 1, "{:?}", failovers);
+    assert_eq!((failovers[0].from, failovers[0].to), (primary(), backup()));
+    assert!(matches!(failovers[0].error, LinkError::PeerUnreachable { .. }), "{:?}", failovers[0].error);
+    assert!(failovers[0].replayed > 0);
+
+    // 第二个后端从重发的第一条起按顺序收到其余全部消息；与第一个后端重复的只在重发的范围内
+    let on_primary = on_primary.lock().unwrap().clone();
+    let on_backup = on_backup.lock().unwrap().clone();
+    assert!(on_primary.len() >= 100);
+    assert_eq!(on_primary, (0..on_primary.len() as u32).collect::<Vec<_>>());
+    let first = on_backup[0];
+    assert!(first <= on_primary.len() as u32);
+    assert_eq!(on_backup, (first..MESSAGES).collect::<Vec<_>>());
+    let duplicated = on_primary.len() - first as usize;
+    assert!(duplicated <= failovers[0].replayed, "{} duplicated, {} replayed", duplicated, failovers[0].replayed);
+}
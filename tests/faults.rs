@@ -0,0 +1,52 @@
+//! 故障注入集成测试：两端都经过 `FaultyTransport`，在丢包与乱序下传输 1MB，接收方按字节完整地收到
+#![cfg(feature = "tokio")]
+
+use bytes::{Bytes, BytesMut};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::fault::FaultConfig;
+use link_rs::listener::Listener;
+use std::time::Duration;
+use tokio::time::timeout;
+
+const TOTAL: usize = 1024 * 1024;
+const MESSAGE: usize = 16 * 1024;
+
+fn lossy(seed: u64) -> LinkConfig {
+    LinkConfig { faults: Some(FaultConfig { loss: 0.05, reorder: 0.02, seed, ..FaultConfig::default() }), ..LinkConfig::default() }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_transfer_survives_loss_and_reordering() {
+    let listener = Listener::bind_with("127.0.0.1:0", lossy(1)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let data: Bytes = (0..TOTAL).map(|i| (i * 31 % 251) as u8).collect();
+
+    let sender = tokio::spawn({
+        let data = data.clone();
+        async move {
+            let client = Connection::connect_with(addr, lossy(2)).await.unwrap();
+            for chunk in data.chunks(MESSAGE) {
+                client.send(data.slice_ref(chunk)).await.unwrap();
+            }
+            client.close().await.unwrap();
+        }
+    });
+
+    let received = timeout(Duration::from_secs(60), async {
+        let (server, _) = listener.accept().await.unwrap();
+        let mut received = BytesMut::new();
+        while let Some(message) = server.recv().await.unwrap() {
+            received.extend_from_slice(&message);
+        }
+        // 服务端的 FIN 与它的重传都丢失、客户端的 TIME_WAIT 先到期时 close 超时，已交付的数据不受影响
+        assert!(matches!(server.close().await, Ok(()) | Err(LinkError::CloseTimedOut)));
+        received
+    })
+    .await
+    .expect("transfer did not finish");
+    timeout(Duration::from_secs(10), sender).await.unwrap().unwrap();
+    assert_eq!(received.len(), TOTAL);
+    assert!(received == data, "received bytes differ");
+}
@@ -0,0 +1,64 @@
+//! futures 组合子集成测试（`futures` 特性）：内存网络上的连接作为 `Sink` 接收 `forward` 的消息，
+//! 在对端作为 `Stream` 以 `try_collect` 收齐；`poll_flush` 在数据全部被确认后完成，出错的消息作为一项交付
+#![cfg(feature = "futures")]
+
+mod common;
+
+use bytes::Bytes;
+use common::pair;
+use futures::{SinkExt, StreamExt, TryStreamExt};
+use link_rs::config::LinkConfig;
+use link_rs::error::LinkError;
+use link_rs::transport::MemoryNetwork;
+use std::time::Duration;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_forward_into_the_sink_and_collect_the_stream() {
+    let network = MemoryNetwork::new();
+    // 窗口很小，forward 必须等发送队列腾出空间；其中一条消息要分片
+    let config = LinkConfig { send_window: 4, send_buffer: 4096, ..LinkConfig::default() };
+    let (_listener, mut client, server) = pair(&network, config).await;
+    let mut messages: Vec<Bytes> = (0..100).map(|i| Bytes::from(format!("message {}", i))).collect();
+    messages.push(Bytes::from(vec![7; 3000]));
+
+    let collector = tokio::spawn(async move { server.try_collect::<Vec<_>>().await });
+    let stream = futures::stream::iter(messages.clone()).map(Ok::<_, LinkError>);
+    timeout(Duration::from_secs(10), stream.forward(&mut client)).await.unwrap().unwrap();
+    let received = timeout(Duration::from_secs(10), collector).await.unwrap().unwrap().unwrap();
+    assert_eq!(received, messages);
+    // forward 结束时关闭了写方向，读方向照常
+    assert!(client.send(Bytes::from_static(b"late")).await.is_err());
+}
+
+#[tokio::test]
+async fn test_flush_waits_for_acks() {
+    let network = MemoryNetwork::new();
+    let (_listener, mut client, mut server) = pair(&network, LinkConfig::default()).await;
+    for i in 0..10 {
+        client.feed(Bytes::from(format!("message {}", i))).await.unwrap();
+    }
+    timeout(Duration::from_secs(5), client.flush()).await.unwrap().unwrap();
+    assert!(client.unacknowledged().is_empty());
+
+    for i in 0..10 {
+        let message = timeout(Duration::from_secs(5), server.next()).await.unwrap().unwrap().unwrap();
+        assert_eq!(message, Bytes::from(format!("message {}", i)));
+    }
+    // 对端关闭后流结束
+    SinkExt::close(&mut client).await.unwrap();
+    assert!(timeout(Duration::from_secs(5), server.next()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_rejected_message_fails_the_send() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { max_message: 1000, ..LinkConfig::default() };
+    let (_listener, mut client, mut server) = pair(&network, config).await;
+    let result = client.send_all(&mut futures::stream::iter([Ok(Bytes::from(vec![0; 2000]))])).await;
+    assert!(matches!(result, Err(LinkError::MessageTooLarge { len: 2000, .. })), "{:?}", result);
+
+    // 被拒绝的消息不留在 Sink 中，之后的消息照常发送
+    SinkExt::send(&mut client, Bytes::from_static(b"small")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.next()).await.unwrap().unwrap().unwrap(), Bytes::from_static(b"small"));
+}
@@ -0,0 +1,127 @@
+//! 空洞事件集成测试：按脚本丢弃指定的消息，订阅者对每个空洞恰好看到一对事件——出现时的 `Opened`，
+//! 重传补齐后的 `Filled` 或过期被放弃后的 `Abandoned`；同一空洞之后的一连串乱序段只报告一次，按序交付不受影响
+mod common;
+
+use bytes::{Bytes, BytesMut};
+use common::pair_with;
+use link_rs::config::LinkConfig;
+use link_rs::gaps::{GapEvent, GapEvents};
+use link_rs::segment::{Segment, SegmentType};
+use link_rs::sender::SendOptions;
+use link_rs::seq::SeqNum;
+use link_rs::transport::MemoryNetwork;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::timeout;
+
+// 丢弃携带 `lost` 中消息的数据段：`always` 为 false 时只丢第一次，重传照常通过。记下被丢弃的消息的序列号
+fn drop_messages(network: &MemoryNetwork, lost: &'static [&'static [u8]], always: bool) -> Arc<Mutex<HashMap<&'static [u8], SeqNum>>> {
+    let dropped = Arc::new(Mutex::new(HashMap::new()));
+    let seen = dropped.clone();
+    network.set_filter(move |datagram, _, _| {
+        let mut datagram = BytesMut::from(datagram);
+        let mut pass = true;
+        while let Ok(Some(segment)) = Segment::decode_from(&mut datagram) {
+            if segment.segment_type() != SegmentType::Data {
+                continue;
+            }
+            if let Some(&message) = lost.iter().find(|message| segment.data().as_ref() == **message) {
+                let mut seen = seen.lock().unwrap();
+                if always || !seen.contains_key(message) {
+                    seen.insert(message, segment.seq());
+                    pass = false;
+                }
+            }
+        }
+        pass
+    });
+    dropped
+}
+
+async fn next(events: &mut GapEvents) -> GapEvent {
+    timeout(Duration::from_secs(5), events.next()).await.unwrap().unwrap()
+}
+
+// 比较时忽略时间：只看事件的种类与区间
+fn strip(event: GapEvent) -> (&'static str, SeqNum, SeqNum) {
+    let (start, end) = event.range();
+    match event {
+        GapEvent::Opened { .. } => ("opened", start, end),
+        GapEvent::Filled { .. } => ("filled", start, end),
+        GapEvent::Abandoned { .. } => ("abandoned", start, end),
+    }
+}
+
+#[tokio::test]
+async fn test_retransmitted_gaps_open_and_fill_once() {
+    const FIRST: &[u8] = b"first lost message";
+    const SECOND: &[u8] = b"second lost message";
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair_with(&network, LinkConfig::default(), LinkConfig { nodelay: true, ..LinkConfig::default() }).await;
+    let mut events = server.subscribe_gaps();
+    let dropped = drop_messages(&network, &[FIRST, SECOND], false);
+
+    // 每条消息之后让驱动任务发出，各占一个数据报：两个各自独立的空洞，之后各有一串乱序段
+    let messages: [&[u8]; 7] = [FIRST, b"a", b"b", SECOND, b"c", b"d", b"e"];
+    for message in messages {
+        client.send(Bytes::from_static(message)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    for message in messages {
+        assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap(), Bytes::from_static(message));
+    }
+
+    let (first, second) = {
+        let dropped = dropped.lock().unwrap();
+        (dropped[FIRST], dropped[SECOND])
+    };
+    let mut seen = Vec::new();
+    for _ in 0..4 {
+        seen.push(strip(next(&mut events).await));
+    }
+    // 每个空洞恰好一对事件；两个空洞的事件交错的方式取决于重传的时机
+    for lost in [first, second] {
+        let pair: Vec<_> = seen.iter().filter(|&&(_, start, _)| start == lost).collect();
+        assert_eq!(pair, [&("opened", lost, lost), &("filled", lost, lost)], "{:?}", seen);
+    }
+    assert!(events.try_next().is_none());
+
+    network.clear_filter();
+    let closing = tokio::spawn(async move { server.close().await });
+    client.close().await.unwrap();
+    closing.await.unwrap().unwrap();
+    // 连接结束后事件流结束
+    assert_eq!(timeout(Duration::from_secs(5), events.next()).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_expired_gap_is_abandoned() {
+    const STALE: &[u8] = b"sample that never gets through";
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair_with(&network, LinkConfig::default(), LinkConfig { nodelay: true, ..LinkConfig::default() }).await;
+    let mut events = server.subscribe_gaps();
+    let dropped = drop_messages(&network, &[STALE], true);
+
+    let ttl = SendOptions::ttl(Duration::from_millis(100));
+    client.send_with(Bytes::from_static(STALE), ttl).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    for i in 0..3 {
+        client.send_with(Bytes::from(format!("sample {}", i)), ttl).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    for i in 0..3 {
+        assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap(), Bytes::from(format!("sample {}", i)));
+    }
+
+    let stale = dropped.lock().unwrap()[STALE];
+    let opened = next(&mut events).await;
+    let abandoned = next(&mut events).await;
+    assert_eq!(strip(opened), ("opened", stale, stale));
+    assert_eq!(strip(abandoned), ("abandoned", stale, stale));
+    let (GapEvent::Opened { at: opened_at, .. }, GapEvent::Abandoned { at: abandoned_at, .. }) = (opened, abandoned) else {
+        unreachable!();
+    };
+    assert!(abandoned_at >= opened_at + Duration::from_millis(50), "{:?}", abandoned_at - opened_at);
+    assert!(events.try_next().is_none());
+}
@@ -0,0 +1,151 @@
+//! 处理函数集成测试：自定义的 `Handler` 按到达顺序看到每个连接的每条消息，在连接前后各被调用一次，
+//! 经 `ConnectionHandle` 发出的回应到达对端；处理函数 panic 时只有那一个连接以 Rst 中止，服务器与其他连接照常工作
+mod common;
+
+use bytes::Bytes;
+use common::{addr, server_addr};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::server::{ConnectionHandle, Drained, Handler, Server};
+use link_rs::transport::MemoryNetwork;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::timeout;
+
+fn serve(network: &MemoryNetwork, handler: impl Handler) -> Arc<Server> {
+    let server = Arc::new(Server::builder().handler(handler).with_transport(network.bind(server_addr()).unwrap()).unwrap());
+    tokio::spawn({
+        let server = server.clone();
+        async move { server.run().await }
+    });
+    server
+}
+
+async fn connect(network: &MemoryNetwork, i: u8) -> Connection {
+    let config = LinkConfig { nodelay: true, ..LinkConfig::default() };
+    common::connect(network, addr(10 + i, 5000), config).await.unwrap()
+}
+
+// 每个对端的生命周期事件与收到的消息，按发生顺序
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Seen {
+    Connected,
+    Message(Bytes),
+    Disconnected(bool),
+}
+
+#[derive(Clone, Default)]
+struct Recorder {
+    seen: Arc<Mutex<HashMap<SocketAddr, Vec<Seen>>>>,
+}
+
+impl Recorder {
+    fn record(&self, peer: SocketAddr, seen: Seen) {
+        self.seen.lock().unwrap().entry(peer).or_default().push(seen);
+    }
+}
+
+impl Handler for Recorder {
+    async fn on_connect(&self, conn: &ConnectionHandle) {
+        self.record(conn.peer_addr(), Seen::Connected);
+    }
+
+    async fn on_message(&self, conn: &ConnectionHandle, message: Bytes) {
+        // 让出一次，使两个连接的处理交错进行
+        tokio::task::yield_now().await;
+        self.record(conn.peer_addr(), Seen::Message(message.clone()));
+        conn.send(Bytes::from([b"ack ", &message[..]].concat())).await.unwrap();
+    }
+
+    async fn on_disconnect(&self, peer: SocketAddr, outcome: &Result<(), LinkError>) {
+        self.record(peer, Seen::Disconnected(outcome.is_ok()));
+    }
+}
+
+#[tokio::test]
+async fn test_custom_handler_sees_each_connection_in_order() {
+    let network = MemoryNetwork::new();
+    let recorder = Recorder::default();
+    let server = serve(&network, recorder.clone());
+
+    let clients = (0..2u8).map(|i| {
+        let network = network.clone();
+        tokio::spawn(async move {
+            let connection = connect(&network, i).await;
+            for n in 0..50 {
+                let message = format!("client {} message {}", i, n);
+                connection.send(Bytes::from(message.clone())).await.unwrap();
+                let reply = timeout(Duration::from_secs(5), connection.recv()).await.unwrap().unwrap().unwrap();
+                assert_eq!(reply, format!("ack {}", message).as_bytes());
+            }
+            connection.close().await.unwrap();
+        })
+    });
+    for client in clients.collect::<Vec<_>>() {
+        client.await.unwrap();
+    }
+
+    // 会话在对端关闭之后才调用 `on_disconnect`
+    timeout(Duration::from_secs(5), async {
+        while recorder.seen.lock().unwrap().values().filter(|seen| seen.last() == Some(&Seen::Disconnected(true))).count() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    let seen = recorder.seen.lock().unwrap();
+    for i in 0..2u8 {
+        let mut expected = vec![Seen::Connected];
+        expected.extend((0..50).map(|n| Seen::Message(Bytes::from(format!("client {} message {}", i, n)))));
+        expected.push(Seen::Disconnected(true));
+        assert_eq!(seen[&addr(10 + i, 5000)], expected);
+    }
+    drop(seen);
+    server.shutdown();
+}
+
+// 收到 "boom" 时 panic，其余消息原样发回
+struct Fragile;
+
+impl Handler for Fragile {
+    async fn on_message(&self, conn: &ConnectionHandle, message: Bytes) {
+        if message == "boom" {
+            panic!("handler exploded");
+        }
+        conn.send(message).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_panicking_handler_resets_only_its_connection() {
+    let network = MemoryNetwork::new();
+    let server = Arc::new(Server::builder().handler(Fragile).with_transport(network.bind(server_addr()).unwrap()).unwrap());
+    let run = tokio::spawn({
+        let server = server.clone();
+        async move { server.run().await }
+    });
+    let victim = connect(&network, 0).await;
+    let bystander = connect(&network, 1).await;
+
+    victim.send(Bytes::from_static(b"before")).await.unwrap();
+    assert_eq!(victim.recv().await.unwrap().unwrap(), "before");
+    victim.send(Bytes::from_static(b"boom")).await.unwrap();
+    assert!(matches!(timeout(Duration::from_secs(5), victim.recv()).await.unwrap(), Err(LinkError::Reset)));
+
+    // 另一个连接与之后的新连接不受影响，服务器关闭时它们正常完成 FIN 交换
+    let newcomer = connect(&network, 2).await;
+    for connection in [&bystander, &newcomer] {
+        connection.send(Bytes::from_static(b"still here")).await.unwrap();
+        assert_eq!(timeout(Duration::from_secs(5), connection.recv()).await.unwrap().unwrap().unwrap(), "still here");
+    }
+    server.shutdown();
+    for connection in [bystander, newcomer] {
+        assert_eq!(timeout(Duration::from_secs(5), connection.recv()).await.unwrap().unwrap(), None);
+        connection.close().await.unwrap();
+    }
+    let drained = timeout(Duration::from_secs(10), run).await.unwrap().unwrap().unwrap();
+    assert_eq!(drained, Drained { closed: 2, failed: 0, aborted: 0 });
+}
@@ -0,0 +1,88 @@
+//! 连接交接集成测试：传输进行到一半时导出服务端连接的状态，旧监听器退出，新监听器在同一地址上接续它，
+//! 两个方向的传输照常完成，消息不丢失、不重复、不乱序；有打开的流或已经关闭写端的连接不能导出
+mod common;
+
+use bytes::Bytes;
+use common::{pair_with, recv, server_addr};
+use link_rs::config::LinkConfig;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::session::{SessionError, SessionState};
+use link_rs::transport::MemoryNetwork;
+use std::time::Duration;
+use tokio::time::timeout;
+
+// 旧进程的套接字随它的任务退出后释放，之后新进程才能绑定
+async fn rebind(network: &MemoryNetwork) -> Listener {
+    for _ in 0..100 {
+        if let Ok(transport) = network.bind(server_addr()) {
+            return Listener::with_transport(transport, LinkConfig::default()).unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("the old listener never released its address");
+}
+
+#[tokio::test]
+async fn test_handoff_mid_transfer() {
+    let network = MemoryNetwork::new();
+    let (listener, client, server) = pair_with(&network, LinkConfig::default(), LinkConfig { nodelay: true, ..LinkConfig::default() }).await;
+    for i in 0..5 {
+        client.send(Bytes::from(format!("request {}", i))).await.unwrap();
+    }
+    // 服务端读了一部分，其余的到达了但还没读取
+    for i in 0..2 {
+        assert_eq!(recv(&server).await, Bytes::from(format!("request {}", i)));
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    // 交接期间链路中断：服务端发出的数据还在途、没有得到确认
+    network.set_filter(|_, _, _| false);
+    for i in 0..3 {
+        server.send(Bytes::from(format!("response {}", i))).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let state = timeout(Duration::from_secs(5), server.export_state()).await.unwrap().unwrap();
+    let encoded = state.encode();
+    drop(listener);
+    // 交接期间客户端继续写入，这些段无人确认，由客户端重传
+    for i in 5..8 {
+        client.send(Bytes::from(format!("request {}", i))).await.unwrap();
+    }
+    network.clear_filter();
+
+    let listener = rebind(&network).await;
+    let server = listener.adopt(SessionState::decode(&encoded).unwrap()).await.unwrap();
+    for i in 2..8 {
+        assert_eq!(recv(&server).await, Bytes::from(format!("request {}", i)));
+    }
+    for i in 0..3 {
+        assert_eq!(recv(&client).await, Bytes::from(format!("response {}", i)));
+    }
+    server.send(Bytes::from_static(b"after the handoff")).await.unwrap();
+    assert_eq!(recv(&client).await, Bytes::from_static(b"after the handoff"));
+
+    // 同一个状态不能被接续两次
+    let again = listener.adopt(SessionState::decode(&encoded).unwrap()).await;
+    assert!(matches!(again, Err(LinkError::Session(SessionError::InUse))));
+
+    let closing = tokio::spawn(async move { server.close().await });
+    client.close().await.unwrap();
+    closing.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_export_is_refused_when_not_exportable() {
+    let network = MemoryNetwork::new();
+    let (_listener, client, _server) = pair_with(&network, LinkConfig::default(), LinkConfig { nodelay: true, ..LinkConfig::default() }).await;
+    let _stream = client.open_stream().unwrap();
+    assert!(matches!(client.export_state().await, Err(LinkError::Session(SessionError::NotExportable(_)))));
+
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair_with(&network, LinkConfig::default(), LinkConfig { nodelay: true, ..LinkConfig::default() }).await;
+    client.shutdown_write().await.unwrap();
+    // 服务端已收到 FIN
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), None);
+    assert!(matches!(server.export_state().await, Err(LinkError::Session(SessionError::NotExportable(_)))));
+    assert!(matches!(client.export_state().await, Err(LinkError::Session(SessionError::NotExportable(_)))));
+}
@@ -0,0 +1,88 @@
+//! 离线解码集成测试：一次真实交换写出的 pcap 与记录文件经 `inspect` 解码，每个段都能解析，
+//! 握手在前、消息的数据段带着它的长度出现，按服务端端口标出的方向与地址一致
+#![cfg(feature = "tokio")]
+
+use bytes::Bytes;
+use link_rs::capture::{Capture, PcapWriter};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::inspect::{self, Entry};
+use link_rs::listener::Listener;
+use link_rs::replay::RecordWriter;
+use link_rs::segment::{Decoded, SegmentType};
+use link_rs::trace::Direction;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::timeout;
+
+// 一次握手与一来一回两条消息，返回服务端端口
+async fn exchange(config: LinkConfig) -> u16 {
+    let listener = Listener::bind_with("127.0.0.1:0", config.clone()).await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    timeout(Duration::from_secs(5), async {
+        let client = Connection::connect_with(listener.local_addr().unwrap(), config).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        client.send(Bytes::from_static(b"question")).await.unwrap();
+        assert_eq!(server.recv().await.unwrap().unwrap(), "question");
+        server.send(Bytes::from_static(b"answer!")).await.unwrap();
+        assert_eq!(client.recv().await.unwrap().unwrap(), "answer!");
+    })
+    .await
+    .unwrap();
+    port
+}
+
+fn kind(entry: &Entry) -> SegmentType {
+    match &entry.segment {
+        Ok(Decoded::Known(segment)) => segment.segment_type(),
+        other => panic!("{:?}", other),
+    }
+}
+
+fn data_lens(entries: &[Entry]) -> Vec<usize> {
+    entries
+        .iter()
+        .filter_map(|entry| match &entry.segment {
+            Ok(Decoded::Known(segment)) if segment.segment_type() == SegmentType::Data => Some(segment.data().len()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_inspects_a_captured_exchange() {
+    let path = std::env::temp_dir().join(format!("link-rs-inspect-{}.pcap", std::process::id()));
+    let writer = Arc::new(PcapWriter::create(&path).await.unwrap());
+    let port = exchange(LinkConfig { capture: Some(Capture::new(writer.clone())), ..Default::default() }).await;
+    writer.flush().await;
+    let file = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let entries = inspect::inspect(&file, Some(port)).unwrap();
+    assert!(entries.iter().all(|entry| entry.segment.is_ok()), "{}", entries.iter().map(|entry| format!("{}\n", entry)).collect::<String>());
+    assert_eq!((kind(&entries[0]), entries[0].direction), (SegmentType::Syn, Some(Direction::Inbound)));
+    for entry in &entries {
+        let inbound = entry.to.port() == port;
+        assert_eq!(entry.direction, Some(if inbound { Direction::Inbound } else { Direction::Outbound }));
+        assert!(entry.to_string().contains(if inbound { " in " } else { " out " }), "{}", entry);
+    }
+    // 两端各记录一次每个数据报
+    assert_eq!(data_lens(&entries), [8, 8, 7, 7]);
+    assert!(entries.windows(2).all(|pair| pair[0].at <= pair[1].at));
+    assert!(inspect::inspect(&file, Some(1)).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_inspects_a_record_file() {
+    let path = std::env::temp_dir().join(format!("link-rs-inspect-{}.rec", std::process::id()));
+    let writer = Arc::new(RecordWriter::create(&path).await.unwrap());
+    exchange(LinkConfig { capture: Some(Capture::new(writer.clone())), ..Default::default() }).await;
+    writer.flush().await;
+    let file = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let entries = inspect::inspect(&file, None).unwrap();
+    assert_eq!(kind(&entries[0]), SegmentType::Syn);
+    assert!(entries.iter().all(|entry| entry.segment.is_ok() && entry.direction == Some(Direction::Inbound)));
+    assert_eq!(data_lens(&entries), [8, 7]);
+}
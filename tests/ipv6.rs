@@ -0,0 +1,70 @@
+//! IPv6 集成测试：连接与回显在 `::1` 上运行；双栈监听器同时服务 IPv4 与 IPv4 映射地址的客户端。
+//! 没有 IPv6 的环境（或不允许双栈的平台）跳过
+#![cfg(feature = "tokio")]
+
+use bytes::Bytes;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::time::timeout;
+
+fn ipv6_available() -> bool {
+    std::net::UdpSocket::bind("[::1]:0").is_ok()
+}
+
+// 连接到 `server`，发送几条消息并等待回显
+async fn echo_through(listener: &Listener, server: SocketAddr) -> SocketAddr {
+    let client = Connection::connect(server).await.unwrap();
+    let (connection, peer) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    tokio::spawn(async move {
+        while let Ok(Some(message)) = connection.recv().await {
+            if connection.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+    for i in 0..10 {
+        client.send(Bytes::from(format!("m{}", i))).await.unwrap();
+        let reply = timeout(Duration::from_secs(5), client.recv()).await.unwrap().unwrap().unwrap();
+        assert_eq!(reply, Bytes::from(format!("m{}", i)));
+    }
+    assert_eq!(client.stats().peer_addr, Some(server));
+    peer
+}
+
+#[tokio::test]
+async fn test_echo_over_ipv6_loopback() {
+    if !ipv6_available() {
+        return;
+    }
+    let listener = Listener::bind("[::1]:0").await.unwrap();
+    let server = listener.local_addr().unwrap();
+    let peer = echo_through(&listener, server).await;
+    assert!(peer.is_ipv6());
+    assert_eq!(listener.stats().connections, 1);
+}
+
+#[tokio::test]
+async fn test_dual_stack_serves_both_families() {
+    if !ipv6_available() {
+        return;
+    }
+    let config = LinkConfig { dual_stack: true, ..Default::default() };
+    let Ok(listener) = Listener::bind_with("[::]:0", config).await else {
+        return;
+    };
+    let port = listener.local_addr().unwrap().port();
+
+    // IPv4 客户端在双栈套接字上以映射地址出现
+    let peer = echo_through(&listener, SocketAddr::from(([127, 0, 0, 1], port))).await;
+    assert!(peer.ip().to_canonical().is_ipv4());
+    // 直接连接映射地址
+    let mapped: SocketAddr = format!("[::ffff:127.0.0.1]:{}", port).parse().unwrap();
+    echo_through(&listener, mapped).await;
+    // 同一个监听器上的 IPv6 客户端
+    let peer = echo_through(&listener, SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], port))).await;
+    assert_eq!(peer.ip(), std::net::Ipv6Addr::LOCALHOST);
+    assert_eq!(listener.stats().connections, 3);
+}
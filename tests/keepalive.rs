@@ -0,0 +1,87 @@
+//! 保活集成测试（暂停的时钟）：单向的持续传输中发送方一直在发出数据、收到确认，两端都不发出任何探测；
+//! 对端停止确认之后发送方仍在发送，路径探测依旧不需要，存活探测耗尽后以 `KeepaliveTimeout` 判定失联。
+//! 空闲的连接只发出存活探测，两种探测同时到期时合并为一个
+mod common;
+
+use bytes::Bytes;
+use common::{pair, server_addr};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::transport::MemoryNetwork;
+use std::time::Duration;
+use tokio::time::{Instant, sleep};
+
+const INTERVAL: Duration = Duration::from_secs(1);
+
+fn config() -> LinkConfig {
+    LinkConfig { nodelay: true, keepalive_interval: INTERVAL, path_keepalive_interval: INTERVAL, keepalive_failures: 3, ..LinkConfig::default() }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_bulk_transfer_needs_no_pings_until_the_peer_stops_acking() {
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair(&network, config()).await;
+    let reader = tokio::spawn(async move {
+        while let Ok(Some(_)) = server.recv().await {}
+        server
+    });
+
+    // 十个保活间隔的单向传输：每 100ms 一条消息，服务端只回确认
+    let message = Bytes::from(vec![5u8; 1000]);
+    for _ in 0..100 {
+        client.send(message.clone()).await.unwrap();
+        sleep(Duration::from_millis(100)).await;
+    }
+    let stats = client.stats();
+    assert_eq!((stats.liveness_pings, stats.path_pings), (0, 0));
+
+    // 对端的确认全部丢失：发送方继续发送，只有存活探测，三个都没有回应后判定失联
+    network.set_filter(|_, from, _| from != server_addr());
+    let stalled = Instant::now();
+    let failure = loop {
+        match client.send(message.clone()).await {
+            Ok(()) => sleep(Duration::from_millis(100)).await,
+            Err(e) => break e,
+        }
+    };
+    assert_eq!(failure, LinkError::KeepaliveTimeout { unanswered: 3 });
+    let elapsed = stalled.elapsed();
+    assert!(elapsed >= INTERVAL * 4 && elapsed < INTERVAL * 5, "{:?}", elapsed);
+    let stats = client.stats();
+    assert_eq!((stats.liveness_pings, stats.path_pings), (3, 0));
+
+    // 服务端一直在回确认，路径探测同样不需要
+    let server = reader.await.unwrap();
+    assert_eq!(server.stats().path_pings, 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_idle_connection_sends_one_probe_per_interval() {
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair(&network, config()).await;
+
+    // 双方都空闲：各自的两种探测同时到期，只发出存活探测；对端的回应重置失败计数，连接一直保持
+    sleep(INTERVAL * 10 + Duration::from_millis(500)).await;
+    for connection in [&client, &server] {
+        let stats = connection.stats();
+        assert!(stats.liveness_pings >= 5, "{:?}", stats);
+        assert_eq!(stats.path_pings, 0);
+    }
+    client.send(Bytes::from_static(b"alive")).await.unwrap();
+    assert_eq!(server.recv().await.unwrap().unwrap(), "alive");
+
+    // 只关闭路径保活的一端仍照常探测对端是否存活
+    let (_listener, client, server) = {
+        let network = MemoryNetwork::new();
+        let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config()).unwrap();
+        let quiet = LinkConfig { path_keepalive_interval: Duration::ZERO, ..config() };
+        let client = Connection::connect_over(network.bind("10.0.0.2:5000".parse().unwrap()).unwrap(), server_addr(), quiet).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (listener, client, server)
+    };
+    sleep(INTERVAL * 4).await;
+    assert!(client.stats().liveness_pings > 0);
+    assert_eq!((client.stats().path_pings, server.stats().path_pings), (0, 0));
+}
@@ -0,0 +1,77 @@
+//! 连接数上限集成测试：已建立的连接达到 `max_connections`（或来自同一 IP 的达到 `per_ip_limit`）时，
+//! 新的握手以 `SERVER_BUSY` 的 Rst 拒绝（客户端得到 `ServerBusy`），已有的连接照常收发；连接结束后让出名额。
+//! 半开握手数的上限（`backlog`）见 `listener` 测试
+
+mod common;
+
+use bytes::Bytes;
+use common::{accept, addr, server_addr};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::transport::MemoryNetwork;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::time::timeout;
+
+async fn connect(network: &MemoryNetwork, from: SocketAddr) -> Result<Connection, LinkError> {
+    common::connect(network, from, LinkConfig { handshake_timeout: Duration::from_secs(2), ..LinkConfig::default() }).await
+}
+
+// 已有的连接不受拒绝的影响
+async fn still_works(client: &Connection, server: &Connection) {
+    client.send(Bytes::from_static(b"still here")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"still here")));
+}
+
+#[tokio::test]
+async fn test_max_connections_refuses_new_handshakes() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { max_connections: Some(2), ..LinkConfig::default() };
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config).unwrap();
+    let first = connect(&network, addr(2, 5000)).await.unwrap();
+    let first_server = accept(&listener).await;
+    let second = connect(&network, addr(3, 5000)).await.unwrap();
+    let _second_server = accept(&listener).await;
+
+    let refused = connect(&network, addr(4, 5000)).await;
+    assert!(matches!(refused, Err(LinkError::ServerBusy)), "{:?}", refused.err());
+    assert_eq!(listener.metrics().busy_refusals, 1);
+    assert_eq!((listener.metrics().active_connections, listener.stats().half_open), (2, 0));
+    still_works(&first, &first_server).await;
+
+    // 一个连接的句柄被丢弃、移出连接表后让出名额
+    drop(first_server);
+    drop(first);
+    timeout(Duration::from_secs(5), async {
+        while listener.occupancy(addr(2, 0).ip()) != (1, 0) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    let third = connect(&network, addr(4, 5001)).await.unwrap();
+    let third_server = accept(&listener).await;
+    still_works(&third, &third_server).await;
+    drop(second);
+}
+
+#[tokio::test]
+async fn test_per_ip_limit_counts_each_source_ip() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { per_ip_limit: Some(1), ..LinkConfig::default() };
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config).unwrap();
+    let client = connect(&network, addr(2, 5000)).await.unwrap();
+    let server = accept(&listener).await;
+
+    // 同一 IP 的另一个端口被拒绝，另一个 IP 不受影响
+    let refused = connect(&network, addr(2, 5001)).await;
+    assert!(matches!(refused, Err(LinkError::ServerBusy)), "{:?}", refused.err());
+    let other = connect(&network, addr(3, 5000)).await.unwrap();
+    let _other_server = accept(&listener).await;
+    assert_eq!(listener.occupancy(addr(2, 0).ip()), (2, 1));
+    assert_eq!(listener.metrics().busy_refusals, 1);
+    still_works(&client, &server).await;
+    drop(other);
+}
@@ -0,0 +1,411 @@
+//! 监听器集成测试：两个手写握手的客户端同时连接，各自的数据只出现在自己的连接上；
+//! 半开握手受 backlog 限制；不再发送任何段的客户端在空闲超时后被移出连接表；
+//! 正常关闭的连接留下墓碑，在 drain_timeout 内回应重传的 FIN、丢弃迟到的数据；
+//! 超出接收缓冲区的数据报被识别为截断并计数，不会被当作较短的段解码；
+//! 以 SYN cookie 握手时不登记任何半开状态，伪造的最后确认被拒绝；
+//! 要求地址验证时客户端带回 Retry 令牌后完成握手，过期或来自其他地址的令牌被丢弃；
+//! `max_connections` 在握手完成时再检查一次，SYN 之后才超出上限的握手以 `CloseCode::SERVER_BUSY` 的 Rst 拒绝
+#![cfg(feature = "tokio")]
+
+use bytes::{Bytes, BytesMut};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::cookie::SynCookies;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::close_codes::CloseCode;
+use link_rs::segment::{Segment, SegmentType};
+use link_rs::seq::SeqNum;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+const CLIENT_ISN: u64 = 100;
+
+async fn recv_segment(socket: &UdpSocket) -> Segment {
+    let mut buf = vec![0u8; 65535];
+    let len = timeout(Duration::from_secs(5), socket.recv(&mut buf)).await.unwrap().unwrap();
+    let mut datagram = BytesMut::from(&buf[..len]);
+    Segment::decode_from(&mut datagram).unwrap().unwrap()
+}
+
+// 手动完成三次握手
+async fn handshake(server: SocketAddr) -> UdpSocket {
+    open(server).await.0
+}
+
+// 手动完成三次握手，同时返回服务端的 SYN-ACK（携带分配的连接 ID）
+async fn open(server: SocketAddr) -> (UdpSocket, Segment) {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(server).await.unwrap();
+
+    let syn = Segment::builder(SegmentType::Syn).data_seq(CLIENT_ISN).build().unwrap();
+    socket.send(&syn.encode().unwrap()).await.unwrap();
+    let syn_ack = recv_segment(&socket).await;
+    assert_eq!(syn_ack.segment_type(), SegmentType::Syn);
+    assert_eq!(syn_ack.ack(), SeqNum::new(CLIENT_ISN));
+
+    socket.send(&final_ack(&syn_ack, CLIENT_ISN)).await.unwrap();
+    (socket, syn_ack)
+}
+
+// 完成握手的确认：与真实客户端一样在序列号上携带自己的 ISN
+fn final_ack(syn_ack: &Segment, isn: u64) -> Vec<u8> {
+    let ack = Segment::builder(SegmentType::Ack).conn_id(syn_ack.conn_id()).data_seq(isn).ack(syn_ack.seq()).window(64).build().unwrap();
+    ack.encode().unwrap().to_vec()
+}
+
+// 等待第一个指定类型的段
+async fn recv_type(socket: &UdpSocket, segment_type: SegmentType) -> Segment {
+    loop {
+        let segment = recv_segment(socket).await;
+        if segment.segment_type() == segment_type {
+            return segment;
+        }
+    }
+}
+
+// 握手后发送若干消息
+async fn client(server: SocketAddr, name: &'static str, messages: usize) -> SocketAddr {
+    let socket = handshake(server).await;
+
+    for i in 0..messages {
+        let data = Segment::builder(SegmentType::Data)
+            .data_seq(CLIENT_ISN + 1 + i as u64)
+            .payload(format!("{}-{}", name, i))
+            .build()
+            .unwrap();
+        socket.send(&data.encode().unwrap()).await.unwrap();
+    }
+    // 等到最后一个消息被确认，保证客户端套接字在此之前不被关闭
+    loop {
+        let segment = recv_segment(&socket).await;
+        if segment.segment_type() == SegmentType::Ack && segment.ack() == SeqNum::new(CLIENT_ISN + messages as u64) {
+            break;
+        }
+    }
+    socket.local_addr().unwrap()
+}
+
+#[tokio::test]
+async fn test_accept_routes_each_peer_to_its_connection() {
+    let listener = Listener::bind("127.0.0.1:0").await.unwrap();
+    let server = listener.local_addr().unwrap();
+
+    let a = tokio::spawn(client(server, "a", 5));
+    let b = tokio::spawn(client(server, "b", 5));
+
+    let mut by_peer = Vec::new();
+    for _ in 0..2 {
+        let (connection, peer) = listener.accept().await.unwrap();
+        assert_eq!(connection.peer_addr(), peer);
+        let mut messages = Vec::new();
+        for _ in 0..5 {
+            messages.push(timeout(Duration::from_secs(5), connection.recv()).await.unwrap().unwrap().unwrap());
+        }
+        // 连接句柄要保留到客户端收到最后的确认，丢弃句柄会停止连接的定时器
+        by_peer.push((peer, messages, connection));
+    }
+
+    let addr_a = a.await.unwrap();
+    let addr_b = b.await.unwrap();
+    for (peer, messages, _) in by_peer {
+        let name = if peer == addr_a {
+            "a"
+        } else {
+            assert_eq!(peer, addr_b);
+            "b"
+        };
+        let expected: Vec<Bytes> = (0..5).map(|i| Bytes::from(format!("{}-{}", name, i))).collect();
+        assert_eq!(messages, expected);
+    }
+}
+
+#[tokio::test]
+async fn test_backlog_bounds_half_open_handshakes() {
+    let config = LinkConfig { backlog: 1, ..Default::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let server = listener.local_addr().unwrap();
+
+    // 第一个 SYN 占满半开队列，第二个客户端得不到 SYN-ACK
+    let first = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let second = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let syn = Segment::builder(SegmentType::Syn).data_seq(CLIENT_ISN).build().unwrap().encode().unwrap();
+    first.send_to(&syn, server).await.unwrap();
+    first.connect(server).await.unwrap();
+    assert_eq!(recv_segment(&first).await.segment_type(), SegmentType::Syn);
+
+    second.send_to(&syn, server).await.unwrap();
+    let mut buf = [0u8; 64];
+    assert!(timeout(Duration::from_millis(200), second.recv_from(&mut buf)).await.is_err());
+}
+
+#[tokio::test]
+async fn test_idle_connections_are_evicted() {
+    // 保活间隔远大于空闲超时，连接只会因为空闲被回收
+    let config = LinkConfig { keepalive_interval: Duration::from_secs(3600), ..Default::default() };
+    let idle_timeout = config.idle_timeout;
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let server = listener.local_addr().unwrap();
+
+    // 客户端握手后不再发送任何段；套接字保留到测试结束，避免新客户端复用已关闭客户端的端口而撞上旧连接
+    let mut accepted = Vec::new();
+    let mut silent = Vec::new();
+    for _ in 0..100 {
+        silent.push(handshake(server).await);
+        accepted.push(listener.accept().await.unwrap().0);
+    }
+    while listener.stats().connections < 100 {
+        tokio::task::yield_now().await;
+    }
+
+    tokio::time::pause();
+    tokio::time::sleep(idle_timeout + Duration::from_secs(1)).await;
+    while listener.stats().connections > 0 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let stats = listener.stats();
+    assert_eq!(stats.evicted, 100);
+    assert_eq!(stats.half_open, 0);
+    for connection in accepted {
+        assert_eq!(connection.recv().await, Err(LinkError::IdleTimeout));
+    }
+}
+
+#[tokio::test]
+async fn test_closed_connection_absorbs_late_retransmissions() {
+    let config = LinkConfig { drain_timeout: Duration::from_millis(500), ..Default::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let (socket, syn_ack) = open(listener.local_addr().unwrap()).await;
+    let conn_id = syn_ack.conn_id();
+    let (connection, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+
+    // 客户端先关闭；服务端读到流结束后关闭，客户端确认服务端的 FIN
+    let fin = Segment::builder(SegmentType::Fin).conn_id(conn_id).data_seq(CLIENT_ISN + 1).build().unwrap().encode().unwrap();
+    socket.send(&fin).await.unwrap();
+    let fin_ack = recv_type(&socket, SegmentType::Ack).await;
+    assert_eq!(timeout(Duration::from_secs(5), connection.recv()).await.unwrap(), Ok(None));
+    let closing = tokio::spawn(connection.close());
+    let server_fin = recv_type(&socket, SegmentType::Fin).await;
+    let ack = Segment::builder(SegmentType::Ack).conn_id(conn_id).ack(server_fin.seq()).window(64).build().unwrap();
+    socket.send(&ack.encode().unwrap()).await.unwrap();
+    assert_eq!(closing.await.unwrap(), Ok(()));
+    while listener.stats().tombstones == 0 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert_eq!(listener.stats().connections, 0);
+
+    // 服务端的确认丢失、客户端重传 FIN：墓碑以同样的确认回应，而不是 Rst
+    socket.send(&fin).await.unwrap();
+    let reply = recv_segment(&socket).await;
+    assert_eq!(reply.segment_type(), SegmentType::Ack);
+    assert_eq!(reply.ack(), fin_ack.ack());
+
+    // 迟到的数据直接丢弃
+    let data = Segment::builder(SegmentType::Data).conn_id(conn_id).data_seq(CLIENT_ISN + 1).payload("late").build().unwrap();
+    socket.send(&data.encode().unwrap()).await.unwrap();
+    let mut buf = [0u8; 1024];
+    assert!(timeout(Duration::from_millis(100), socket.recv(&mut buf)).await.is_err());
+
+    // 墓碑到期后同一个段来自未知连接，以 Rst 回应
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    socket.send(&fin).await.unwrap();
+    assert_eq!(recv_segment(&socket).await.segment_type(), SegmentType::Rst);
+    assert_eq!(listener.stats().tombstones, 0);
+}
+
+#[tokio::test]
+async fn test_truncated_datagram_is_counted() {
+    let config = LinkConfig { recv_buffer: 1024, ..Default::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(listener.local_addr().unwrap()).await.unwrap();
+
+    // SYN 与 2KB 的数据段打包在一个数据报里：截断到 1KB 后开头的 SYN 仍能解析，但整个数据报都不可信，不会建立握手
+    let syn = Segment::builder(SegmentType::Syn).data_seq(CLIENT_ISN).build().unwrap();
+    let data = Segment::builder(SegmentType::Data).data_seq(CLIENT_ISN + 1).payload(vec![0u8; 2048]).build().unwrap();
+    socket.send(&[syn.encode().unwrap(), data.encode().unwrap()].concat()).await.unwrap();
+    let mut buf = [0u8; 1024];
+    assert!(timeout(Duration::from_millis(200), socket.recv(&mut buf)).await.is_err());
+    let stats = listener.stats();
+    assert_eq!((stats.truncated, stats.malformed, stats.half_open), (1, 0, 0));
+
+    // 放得下的段照常处理
+    handshake(listener.local_addr().unwrap()).await;
+    timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_syn_cookie_handshake() {
+    let config = LinkConfig { syn_cookies: SynCookies::Always, ..Default::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let server = listener.local_addr().unwrap();
+
+    // 真实客户端照常连接并收发
+    let echo = async {
+        let (connection, _) = listener.accept().await.unwrap();
+        let message = connection.recv().await.unwrap().unwrap();
+        connection.send(message).await.unwrap();
+        connection
+    };
+    let client = async {
+        let connection = Connection::connect(server).await.unwrap();
+        connection.send(Bytes::from_static(b"cookie")).await.unwrap();
+        assert_eq!(connection.recv().await.unwrap().unwrap(), Bytes::from_static(b"cookie"));
+        connection
+    };
+    let (_server_side, _client_side) = timeout(Duration::from_secs(5), async { tokio::join!(echo, client) }).await.unwrap();
+    assert_eq!(listener.stats().connections, 1);
+    assert_eq!(listener.stats().half_open, 0);
+}
+
+#[tokio::test]
+async fn test_forged_cookie_is_rejected() {
+    let config = LinkConfig { syn_cookies: SynCookies::Always, ..Default::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(listener.local_addr().unwrap()).await.unwrap();
+
+    let syn = Segment::builder(SegmentType::Syn).data_seq(CLIENT_ISN).build().unwrap();
+    socket.send(&syn.encode().unwrap()).await.unwrap();
+    let syn_ack = recv_segment(&socket).await;
+    assert_eq!(listener.stats().half_open, 0);
+
+    // 确认号改动一位、或带回别的 ISN，都得到 Rst
+    let mut forged = Segment::builder(SegmentType::Ack).conn_id(syn_ack.conn_id()).data_seq(CLIENT_ISN).ack(syn_ack.seq().wrapping_add(1)).window(64).build().unwrap();
+    socket.send(&forged.encode().unwrap()).await.unwrap();
+    assert_eq!(recv_segment(&socket).await.segment_type(), SegmentType::Rst);
+    socket.send(&final_ack(&syn_ack, CLIENT_ISN + 7)).await.unwrap();
+    assert_eq!(recv_segment(&socket).await.segment_type(), SegmentType::Rst);
+    forged.set_conn_id(syn_ack.conn_id() ^ 1);
+    socket.send(&forged.encode().unwrap()).await.unwrap();
+    assert_eq!(recv_segment(&socket).await.segment_type(), SegmentType::Rst);
+    assert!(timeout(Duration::from_millis(100), listener.accept()).await.is_err());
+
+    // 原样的 cookie 仍然有效
+    socket.send(&final_ack(&syn_ack, CLIENT_ISN)).await.unwrap();
+    timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_syn_flood_leaves_no_state() {
+    let config = LinkConfig { syn_cookies: SynCookies::Always, ..Default::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let server = listener.local_addr().unwrap();
+
+    // 来自 200 个地址、从不完成握手的 SYN：每个都得到 SYN-ACK，连接表保持为空
+    let mut flood = Vec::new();
+    for isn in 0..200u64 {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(server).await.unwrap();
+        let syn = Segment::builder(SegmentType::Syn).data_seq(isn).build().unwrap();
+        socket.send(&syn.encode().unwrap()).await.unwrap();
+        flood.push(socket);
+    }
+    for socket in &flood {
+        assert_eq!(recv_segment(socket).await.segment_type(), SegmentType::Syn);
+    }
+    let stats = listener.stats();
+    assert_eq!((stats.connections, stats.half_open), (0, 0));
+}
+
+#[tokio::test]
+async fn test_syn_cookies_on_backlog_overflow() {
+    let config = LinkConfig { syn_cookies: SynCookies::Overflow, backlog: 1, ..Default::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let server = listener.local_addr().unwrap();
+
+    // 第一个 SYN 占满半开队列，之后的客户端经 cookie 完成握手
+    let stalled = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    stalled.connect(server).await.unwrap();
+    stalled.send(&Segment::builder(SegmentType::Syn).data_seq(CLIENT_ISN).build().unwrap().encode().unwrap()).await.unwrap();
+    recv_segment(&stalled).await;
+    let _client = handshake(server).await;
+    timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    let stats = listener.stats();
+    assert_eq!((stats.connections, stats.half_open), (1, 1));
+}
+
+#[tokio::test]
+async fn test_max_connections_checked_when_the_handshake_completes() {
+    let config = LinkConfig { max_connections: Some(1), ..Default::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let server = listener.local_addr().unwrap();
+
+    // 两个握手都在连接数为 0 时收到 SYN-ACK，后完成的一个超出上限
+    let mut pending = Vec::new();
+    for _ in 0..2 {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(server).await.unwrap();
+        socket.send(&Segment::builder(SegmentType::Syn).data_seq(CLIENT_ISN).build().unwrap().encode().unwrap()).await.unwrap();
+        let syn_ack = recv_type(&socket, SegmentType::Syn).await;
+        pending.push((socket, syn_ack));
+    }
+    assert_eq!(listener.stats().half_open, 2);
+    for (socket, syn_ack) in &pending {
+        socket.send(&final_ack(syn_ack, CLIENT_ISN)).await.unwrap();
+    }
+    let _accepted = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    let rst = recv_type(&pending[1].0, SegmentType::Rst).await;
+    assert_eq!(rst.options().error(), Some(CloseCode::SERVER_BUSY));
+    assert!(timeout(Duration::from_millis(100), listener.accept()).await.is_err());
+    let stats = listener.stats();
+    assert_eq!((stats.connections, stats.half_open, listener.metrics().busy_refusals), (1, 0, 1));
+}
+
+#[tokio::test]
+async fn test_retry_handshake() {
+    let config = LinkConfig { retry_threshold: Some(0), ..Default::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let server = listener.local_addr().unwrap();
+
+    let client = async {
+        let connection = Connection::connect(server).await.unwrap();
+        connection.send(Bytes::from_static(b"validated")).await.unwrap();
+        connection
+    };
+    let (_client_side, accepted) = timeout(Duration::from_secs(5), async { tokio::join!(client, listener.accept()) }).await.unwrap();
+    let (connection, _) = accepted.unwrap();
+    assert_eq!(connection.recv().await.unwrap().unwrap(), Bytes::from_static(b"validated"));
+    assert_eq!((listener.metrics().retries, listener.metrics().invalid_tokens), (1, 0));
+}
+
+#[tokio::test]
+async fn test_invalid_retry_tokens_are_dropped() {
+    let config = LinkConfig { retry_threshold: Some(0), retry_token_lifetime: Duration::from_millis(300), ..Default::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let server = listener.local_addr().unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(server).await.unwrap();
+
+    let syn = Segment::builder(SegmentType::Syn).data_seq(CLIENT_ISN).build().unwrap();
+    socket.send(&syn.encode().unwrap()).await.unwrap();
+    let retry = recv_segment(&socket).await;
+    assert_eq!(retry.segment_type(), SegmentType::Retry);
+    assert_eq!(retry.ack(), SeqNum::new(CLIENT_ISN));
+    assert_eq!(listener.stats().half_open, 0);
+    let with_token = Segment::builder(SegmentType::Syn).data_seq(CLIENT_ISN).retry_token(retry.data()).build().unwrap().encode().unwrap();
+
+    // 另一个地址带回同一个令牌：丢弃，不回应
+    let other = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    other.connect(server).await.unwrap();
+    other.send(&with_token).await.unwrap();
+    let mut buf = [0u8; 1500];
+    assert!(timeout(Duration::from_millis(100), other.recv(&mut buf)).await.is_err(), "replayed token was answered");
+    assert_eq!(listener.metrics().invalid_tokens, 1);
+
+    // 过期后签发它的地址也不能使用
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    socket.send(&with_token).await.unwrap();
+    assert!(timeout(Duration::from_millis(100), socket.recv(&mut buf)).await.is_err(), "expired token was answered");
+    assert_eq!(listener.metrics().invalid_tokens, 2);
+
+    // 新签发的令牌立即带回，进入正常的握手
+    socket.send(&syn.encode().unwrap()).await.unwrap();
+    let retry = recv_segment(&socket).await;
+    let with_token = Segment::builder(SegmentType::Syn).data_seq(CLIENT_ISN).retry_token(retry.data()).build().unwrap();
+    socket.send(&with_token.encode().unwrap()).await.unwrap();
+    assert_eq!(recv_segment(&socket).await.segment_type(), SegmentType::Syn);
+    assert_eq!(listener.stats().half_open, 1);
+}
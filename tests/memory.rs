@@ -0,0 +1,254 @@
+//! 内存传输集成测试：监听器、服务端与连接都跑在 `MemoryNetwork` 上，不占用端口；
+//! 端到端的回显、丢包与乱序下的传输（`FaultyTransport` 包在内存端点外面）、只有拥塞标记时的降窗、
+//! 接收队列满时的丢弃与恢复、发送节奏下数据段的到达间隔，以及暂停时钟下 SYN 的重传时刻
+
+mod common;
+
+use bytes::{Bytes, BytesMut};
+use common::server_addr;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::fault::FaultConfig;
+use link_rs::listener::Listener;
+use link_rs::congestion::CongestionAlgorithm;
+use link_rs::segment::Segment;
+use link_rs::server::{EchoHandler, Server};
+use link_rs::transport::{MemoryNetwork, MemoryTransport};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::{Instant, timeout};
+
+fn client(network: &MemoryNetwork) -> MemoryTransport {
+    network.bind("10.0.0.2:0".parse().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_echo_server_in_memory() {
+    let network = MemoryNetwork::new();
+    let server = Arc::new(Server::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default(), EchoHandler).unwrap());
+    assert_eq!(server.local_addr().unwrap(), server_addr());
+    let running = tokio::spawn({
+        let server = server.clone();
+        async move { server.run().await }
+    });
+
+    let mut clients = Vec::new();
+    for n in 0..3 {
+        let transport = client(&network);
+        clients.push(tokio::spawn(async move {
+            let connection = Connection::connect_over(transport, server_addr(), LinkConfig::default()).await.unwrap();
+            for i in 0..20 {
+                let message = Bytes::from(format!("client {} message {}", n, i));
+                connection.send(message.clone()).await.unwrap();
+                assert_eq!(connection.recv().await.unwrap(), Some(message));
+            }
+            connection.close().await.unwrap();
+        }));
+    }
+    timeout(Duration::from_secs(10), async {
+        for client in clients {
+            client.await.unwrap();
+        }
+    })
+    .await
+    .expect("echo over memory did not finish");
+    server.shutdown();
+    running.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_lossy_transfer_in_memory() {
+    let lossy = |seed| LinkConfig {
+        faults: Some(FaultConfig { loss: 0.05, reorder: 0.02, seed, ..FaultConfig::default() }),
+        linger: Duration::from_secs(1),
+        ..LinkConfig::default()
+    };
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), lossy(1)).unwrap();
+    let data: Bytes = (0..1024 * 1024).map(|i| (i * 31 % 251) as u8).collect();
+
+    let sender = tokio::spawn({
+        let data = data.clone();
+        let transport = client(&network);
+        async move {
+            let connection = Connection::connect_over(transport, server_addr(), lossy(2)).await.unwrap();
+            for chunk in data.chunks(16 * 1024) {
+                connection.send(data.slice_ref(chunk)).await.unwrap();
+            }
+            // 最后的 FIN 交换可能全部丢失，已交付的数据不受影响
+            let _ = connection.close().await;
+        }
+    });
+    let received = timeout(Duration::from_secs(60), async {
+        let (connection, _) = listener.accept().await.unwrap();
+        let mut received = BytesMut::new();
+        while let Some(message) = connection.recv().await.unwrap() {
+            received.extend_from_slice(&message);
+        }
+        let _ = connection.close().await;
+        received
+    })
+    .await
+    .expect("transfer did not finish");
+    timeout(Duration::from_secs(15), sender).await.unwrap().unwrap();
+    assert!(received == data, "received bytes differ");
+}
+
+#[tokio::test]
+async fn test_ce_marks_reduce_window_without_loss() {
+    // 客户端发出的数据报有一成被标记 CE，内存网络本身不丢包
+    let marking = LinkConfig { faults: Some(FaultConfig { ce: 0.1, seed: 3, ..FaultConfig::default() }), ..LinkConfig::default() };
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap();
+    let transport = Arc::new(client(&network));
+    let connection = Connection::connect_over(transport.clone(), server_addr(), marking).await.unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+
+    let sending = async {
+        for i in 0..200 {
+            connection.send(Bytes::from(vec![i as u8; 1000])).await.unwrap();
+        }
+        connection.shutdown_write().await.unwrap();
+    };
+    let receiving = async {
+        let mut received = 0;
+        while let Some(message) = server.recv().await.unwrap() {
+            received += message.len();
+        }
+        received
+    };
+    let (_, received) = timeout(Duration::from_secs(10), async { tokio::join!(sending, receiving) }).await.expect("transfer did not finish");
+    assert_eq!(received, 200 * 1000);
+
+    // 窗口因回送的 ECE 减半，没有任何重传
+    let stats = connection.stats();
+    assert!(server.stats().receiver.ce_received > 0);
+    assert!(stats.sender.ecn_reductions > 0, "{:?}", stats.sender);
+    assert!(stats.ssthresh < usize::MAX);
+    assert_eq!((stats.sender.segments_retransmitted, stats.fast_retransmits, stats.timeouts), (0, 0, 0));
+    assert_eq!(transport.dropped(), 0);
+}
+
+#[tokio::test]
+async fn test_full_queue_drops_are_retransmitted() {
+    // 发往服务端的队列只有 4 个数据报，突发的数据段会被丢弃，由重传补齐
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind_with_capacity(server_addr(), 4).unwrap(), LinkConfig::default()).unwrap();
+    let transport = Arc::new(client(&network));
+    let connection = Connection::connect_over(transport.clone(), server_addr(), LinkConfig::default()).await.unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+
+    let messages: Vec<Bytes> = (0..200).map(|i| Bytes::from(vec![i as u8; 1000])).collect();
+    let sending = async {
+        for message in &messages {
+            connection.send(message.clone()).await.unwrap();
+        }
+        connection.close().await.unwrap();
+    };
+    let receiving = async {
+        let mut received = Vec::new();
+        while let Some(message) = server.recv().await.unwrap() {
+            received.push(message);
+        }
+        server.close().await.unwrap();
+        received
+    };
+    let (_, received) = timeout(Duration::from_secs(30), async { tokio::join!(sending, receiving) }).await.expect("transfer did not finish");
+    assert_eq!(received, messages);
+    assert!(transport.dropped() > 0, "the queue never filled");
+}
+
+#[tokio::test]
+async fn test_connect_to_unbound_address_fails() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { handshake_timeout: Duration::from_millis(300), ..LinkConfig::default() };
+    let result = Connection::connect_over(client(&network), server_addr(), config).await;
+    assert!(matches!(result, Err(LinkError::ConnectTimeout { .. })), "{:?}", result.map(|_| ()));
+}
+
+// 暂停的时钟下以给定的参数连接没有监听器的地址，返回各个 SYN 相对开始的发出时间与连接的结果
+async fn syn_schedule(config: LinkConfig) -> (Vec<Duration>, Result<Connection, LinkError>) {
+    let network = MemoryNetwork::new();
+    let start = Instant::now();
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    network.set_filter({
+        let sent = sent.clone();
+        move |_, _, _| {
+            sent.lock().unwrap().push(Instant::now() - start);
+            true
+        }
+    });
+    let result = Connection::connect_over(client(&network), server_addr(), config).await;
+    let sent = sent.lock().unwrap().clone();
+    (sent, result)
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_syn_retransmission_follows_the_backoff() {
+    let config = LinkConfig {
+        syn_retry_initial: Duration::from_millis(100),
+        syn_max_retries: 3,
+        syn_retry_jitter: 0.0,
+        ..LinkConfig::default()
+    };
+    let (sent, result) = syn_schedule(config.clone()).await;
+    assert_eq!(sent, [0, 100, 300, 700].map(Duration::from_millis));
+    // 最后一次重传之后再等一个间隔
+    assert_eq!(result.unwrap_err(), LinkError::ConnectTimeout { attempts: 4, elapsed: Duration::from_millis(1500) });
+
+    // 总超时在退避中途到期：不等下一次重传
+    let config = LinkConfig { syn_max_retries: 10, handshake_timeout: Duration::from_secs(1), ..config };
+    let (sent, result) = syn_schedule(config).await;
+    assert_eq!(sent, [0, 100, 300, 700].map(Duration::from_millis));
+    assert_eq!(result.unwrap_err(), LinkError::ConnectTimeout { attempts: 4, elapsed: Duration::from_secs(1) });
+}
+
+// 链路单程延迟 50ms、窗口固定为 10 段：空闲后一口气写入一整窗，返回服务端各消息的到达时间与当时的平滑 RTT
+async fn window_arrivals(pacing: bool) -> (Vec<Instant>, Duration) {
+    let delayed = FaultConfig { delay: Duration::from_millis(50), ..FaultConfig::default() };
+    let config = LinkConfig {
+        congestion: CongestionAlgorithm::NoCc { window: 10 },
+        nodelay: true,
+        pacing,
+        faults: Some(delayed),
+        ..LinkConfig::default()
+    };
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap();
+    let connection = Connection::connect_over(client(&network), server_addr(), config).await.unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+
+    // 先交换一条消息得到 RTT 样本，等确认全部回来
+    connection.send(Bytes::from_static(b"warm up")).await.unwrap();
+    server.recv().await.unwrap().unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let srtt = connection.stats().srtt.expect("rtt sampled");
+
+    for i in 0..10 {
+        connection.send(Bytes::from(vec![i as u8; 1000])).await.unwrap();
+    }
+    let mut arrivals = Vec::new();
+    for _ in 0..10 {
+        server.recv().await.unwrap().unwrap();
+        arrivals.push(Instant::now());
+    }
+    (arrivals, srtt)
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_pacing_spaces_a_window_of_segments() {
+    let (arrivals, srtt) = window_arrivals(true).await;
+    // 1.25 倍的 cwnd/SRTT：约 1000 字节的段之间隔 len × srtt / (1.25 × 10 × mss)
+    let len = (Segment::FIXED_HEADER_LEN + 1000) as f64;
+    let interval = srtt.mul_f64(len / (1.25 * 10.0 * LinkConfig::default().mss as f64));
+    let gaps: Vec<Duration> = arrivals.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    // 开头的一个放行量（两个 MSS 再透支一个段）一起到达，补齐透支后逐个间隔到达；定时器按毫秒取整
+    assert_eq!(gaps[..2], [Duration::ZERO; 2]);
+    assert!(gaps[2] <= interval, "gaps {:?}", gaps);
+    assert!(gaps[3..].iter().all(|gap| gap.abs_diff(interval) <= interval / 5), "gaps {:?}, expected about {:?}", gaps, interval);
+
+    // 关闭节奏时一整窗同时到达
+    let (arrivals, _) = window_arrivals(false).await;
+    assert_eq!(arrivals[9] - arrivals[0], Duration::ZERO);
+}
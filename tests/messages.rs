@@ -0,0 +1,72 @@
+//! 分片消息集成测试：有丢包与乱序的传输上，`send_msg` 发出的空消息、恰好一片、多一个字节与几 MB 的消息
+//! 都由 `recv_msg` 按发送顺序整条交付；超过 `max_message` 的消息在发送时被拒绝
+
+mod common;
+
+use bytes::Bytes;
+use common::server_addr;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::fault::FaultConfig;
+use link_rs::listener::Listener;
+use link_rs::options;
+use link_rs::segment::Segment;
+use link_rs::transport::MemoryNetwork;
+use std::time::Duration;
+use tokio::time::timeout;
+
+fn lossy(seed: u64) -> LinkConfig {
+    LinkConfig {
+        faults: Some(FaultConfig { loss: 0.02, reorder: 0.02, seed, ..FaultConfig::default() }),
+        min_rto: Duration::from_millis(50),
+        linger: Duration::from_secs(1),
+        ..LinkConfig::default()
+    }
+}
+
+fn message(len: usize, tag: u8) -> Bytes {
+    (0..len).map(|i| (i * 31 % 251) as u8 ^ tag).collect()
+}
+
+#[tokio::test]
+async fn test_message_boundaries_survive_loss() {
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), lossy(1)).unwrap();
+    let client = Connection::connect_over(network.bind("10.0.0.2:5000".parse().unwrap()).unwrap(), server_addr(), lossy(2)).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+
+    // 一片的大小：有效 MSS 减去段头与选项区
+    let fragment = client.mss() - Segment::FIXED_HEADER_LEN - options::MAX_LEN;
+    let lens = [0, fragment, fragment + 1, 1, 3 * 1024 * 1024, 0, 2 * fragment];
+    let messages: Vec<Bytes> = lens.iter().enumerate().map(|(i, &len)| message(len, i as u8)).collect();
+
+    let sending = tokio::spawn({
+        let messages = messages.clone();
+        async move {
+            for message in messages {
+                client.send_msg(message).await.unwrap();
+            }
+            let _ = client.close().await;
+        }
+    });
+    for (i, expected) in messages.iter().enumerate() {
+        let received = timeout(Duration::from_secs(60), server.recv_msg()).await.unwrap().unwrap();
+        assert!(received == expected, "message {} of {} bytes arrived as {} bytes", i, expected.len(), received.len());
+    }
+    assert_eq!(timeout(Duration::from_secs(10), server.recv_msg()).await.unwrap(), Err(LinkError::Closed));
+    timeout(Duration::from_secs(15), sending).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_oversized_message_fails_at_send() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { max_message: 10_000, ..LinkConfig::default() };
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap();
+    let client = Connection::connect_over(network.bind("10.0.0.2:5000".parse().unwrap()).unwrap(), server_addr(), config).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+
+    assert_eq!(client.send_msg(message(10_001, 0)).await, Err(LinkError::MessageTooLarge { len: 10_001, max: 10_000 }));
+    client.send_msg(message(10_000, 0)).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv_msg()).await.unwrap().unwrap(), message(10_000, 0));
+}
@@ -0,0 +1,211 @@
+//! 连接迁移集成测试：客户端与监听器之间的模拟 NAT 在传输中途换用新的出口套接字，
+//! 服务端按连接 ID 找到连接、新地址回应 PathChallenge 后把连接迁过去，所有消息按序到达；伪造的段不能劫持连接，
+//! 从另一个地址重放的真实段换来一个无人回应的 PathChallenge，验证期间与失败之后流量都留在原来的地址；
+//! 打开 `rotate_conn_id_on_migration` 时迁移之后连接换用新的 ID
+#![cfg(feature = "tokio")]
+
+use bytes::{Bytes, BytesMut};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use link_rs::segment::{Segment, SegmentType};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+const MESSAGES: usize = 500;
+
+// 模拟 NAT：客户端发往 `front` 的数据报经当前出口套接字转发给服务端，两个出口收到的回应都转回客户端
+struct Nat {
+    front: SocketAddr,
+    exits: [SocketAddr; 2],
+    rebound: Arc<AtomicBool>,
+    conn_id: Arc<AtomicU32>,    // 从服务端的段中读出的连接 ID
+    last_ack: Arc<Mutex<Option<Vec<u8>>>>,  // 客户端最近一个以 Ack 开头的数据报
+}
+
+async fn nat(server: SocketAddr) -> Nat {
+    let front = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+    let exits = [Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap()), Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap())];
+    let rebound = Arc::new(AtomicBool::new(false));
+    let conn_id = Arc::new(AtomicU32::new(0));
+    let last_ack = Arc::new(Mutex::new(None));
+    let client = Arc::new(Mutex::new(None::<SocketAddr>));
+    let nat = Nat {
+        front: front.local_addr().unwrap(),
+        exits: [exits[0].local_addr().unwrap(), exits[1].local_addr().unwrap()],
+        rebound: rebound.clone(),
+        conn_id: conn_id.clone(),
+        last_ack: last_ack.clone(),
+    };
+
+    let (outbound, exit_sockets, client_addr) = (front.clone(), exits.clone(), client.clone());
+    tokio::spawn(async move {
+        let mut buf = vec![0u8; 65535];
+        loop {
+            let (len, from) = outbound.recv_from(&mut buf).await.unwrap();
+            *client_addr.lock().unwrap() = Some(from);
+            if Segment::decode_from(&mut BytesMut::from(&buf[..len])).is_ok_and(|segment| segment.is_some_and(|segment| segment.segment_type() == SegmentType::Ack)) {
+                *last_ack.lock().unwrap() = Some(buf[..len].to_vec());
+            }
+            let exit = &exit_sockets[usize::from(rebound.load(Ordering::SeqCst))];
+            let _ = exit.send_to(&buf[..len], server).await;
+        }
+    });
+    for exit in exits {
+        let (front, client, conn_id) = (front.clone(), client.clone(), conn_id.clone());
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; 65535];
+            loop {
+                let len = exit.recv(&mut buf).await.unwrap();
+                if let Ok(Some(segment)) = Segment::decode_from(&mut BytesMut::from(&buf[..len])) {
+                    conn_id.store(segment.conn_id(), Ordering::SeqCst);
+                }
+                let to = client.lock().unwrap().expect("reply before any request");
+                let _ = front.send_to(&buf[..len], to).await;
+            }
+        });
+    }
+    nat
+}
+
+#[tokio::test]
+async fn test_connection_follows_rebound_client() {
+    let listener = Listener::bind("127.0.0.1:0").await.unwrap();
+    let nat = nat(listener.local_addr().unwrap()).await;
+
+    let client = Connection::connect(nat.front).await.unwrap();
+    let (server, peer) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    assert_eq!(peer, nat.exits[0]);
+    assert_ne!(nat.conn_id.load(Ordering::SeqCst), 0);
+
+    let send = async {
+        for i in 0..MESSAGES {
+            client.send(Bytes::from(format!("m{}", i))).await.unwrap();
+        }
+    };
+    let recv = async {
+        for i in 0..MESSAGES {
+            // 传输进行到一半时 NAT 换用新的出口
+            if i == MESSAGES / 2 {
+                nat.rebound.store(true, Ordering::SeqCst);
+            }
+            let message = timeout(Duration::from_secs(10), server.recv()).await.unwrap().unwrap().unwrap();
+            assert_eq!(message, Bytes::from(format!("m{}", i)));
+        }
+    };
+    tokio::join!(send, recv);
+
+    assert_eq!(server.peer_addr(), nat.exits[1]);
+    assert_eq!((listener.stats().migrated, listener.stats().unvalidated), (1, 0));
+    assert_eq!(listener.stats().connections, 1);
+
+    // 回应发往新地址，经 NAT 回到客户端
+    server.send(Bytes::from_static(b"after")).await.unwrap();
+    let reply = timeout(Duration::from_secs(5), client.recv()).await.unwrap().unwrap().unwrap();
+    assert_eq!(reply, Bytes::from_static(b"after"));
+}
+
+#[tokio::test]
+async fn test_forged_segment_does_not_migrate() {
+    let listener = Listener::bind("127.0.0.1:0").await.unwrap();
+    let nat = nat(listener.local_addr().unwrap()).await;
+    let client = Connection::connect(nat.front).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    client.send(Bytes::from_static(b"hello")).await.unwrap();
+    assert_eq!(server.recv().await.unwrap().unwrap(), Bytes::from_static(b"hello"));
+
+    // 连接 ID 正确但序列号远在接收窗口之外：作为陌生地址的段以 Rst 回应，连接不受影响
+    let attacker = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    attacker.connect(listener.local_addr().unwrap()).await.unwrap();
+    let forged = Segment::builder(SegmentType::Data)
+        .conn_id(nat.conn_id.load(Ordering::SeqCst))
+        .data_seq(u64::MAX / 2)
+        .payload("forged")
+        .build()
+        .unwrap();
+    attacker.send(&forged.encode().unwrap()).await.unwrap();
+    let mut buf = vec![0u8; 1024];
+    let len = timeout(Duration::from_secs(5), attacker.recv(&mut buf)).await.unwrap().unwrap();
+    assert_eq!(Segment::decode(&buf[..len]).unwrap().segment_type(), SegmentType::Rst);
+
+    assert_eq!(server.peer_addr(), nat.exits[0]);
+    assert_eq!(listener.stats().migrated, 0);
+    client.send(Bytes::from_static(b"still here")).await.unwrap();
+    assert_eq!(server.recv().await.unwrap().unwrap(), Bytes::from_static(b"still here"));
+}
+
+#[tokio::test]
+async fn test_replayed_segment_from_another_address_never_redirects() {
+    let config = LinkConfig { path_validation_timeout: Duration::from_millis(200), ..LinkConfig::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let nat = nat(listener.local_addr().unwrap()).await;
+    let client = Connection::connect(nat.front).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    server.send(Bytes::from_static(b"hello")).await.unwrap();
+    assert_eq!(client.recv().await.unwrap().unwrap(), Bytes::from_static(b"hello"));
+    let replayed = nat.last_ack.lock().unwrap().clone().expect("the client acknowledged");
+
+    // 真实的确认通过序列号校验，换来发往攻击者地址的 PathChallenge
+    let attacker = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    attacker.connect(listener.local_addr().unwrap()).await.unwrap();
+    attacker.send(&replayed).await.unwrap();
+    let mut buf = vec![0u8; 1024];
+    let len = timeout(Duration::from_secs(5), attacker.recv(&mut buf)).await.unwrap().unwrap();
+    let challenge = Segment::decode(&buf[..len]).unwrap();
+    assert_eq!(challenge.segment_type(), SegmentType::PathChallenge);
+    assert!(challenge.nonce().is_some());
+
+    // 验证期间与失败之后，双向的数据都经原来的地址
+    for i in 0..20 {
+        server.send(Bytes::from(format!("s{}", i))).await.unwrap();
+        client.send(Bytes::from(format!("c{}", i))).await.unwrap();
+        assert_eq!(timeout(Duration::from_secs(5), client.recv()).await.unwrap().unwrap().unwrap(), Bytes::from(format!("s{}", i)));
+        assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap(), Bytes::from(format!("c{}", i)));
+        assert_eq!(server.peer_addr(), nat.exits[0]);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!((listener.stats().migrated, listener.stats().unvalidated), (0, 1));
+    // 攻击者只收到过那一个 PathChallenge
+    assert!(timeout(Duration::from_millis(50), attacker.recv(&mut buf)).await.is_err());
+}
+
+#[tokio::test]
+async fn test_conn_id_rotates_with_the_migration() {
+    let config = LinkConfig { rotate_conn_id_on_migration: true, conn_id_grace: Duration::from_millis(100), ..LinkConfig::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let nat = nat(listener.local_addr().unwrap()).await;
+    let client = Connection::connect(nat.front).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    let before = server.conn_id();
+    assert_eq!(client.conn_id(), before);
+
+    for i in 0..100 {
+        if i == 50 {
+            nat.rebound.store(true, Ordering::SeqCst);
+        }
+        client.send(Bytes::from(format!("m{}", i))).await.unwrap();
+        let message = timeout(Duration::from_secs(10), server.recv()).await.unwrap().unwrap().unwrap();
+        assert_eq!(message, Bytes::from(format!("m{}", i)));
+    }
+    assert_eq!(server.peer_addr(), nat.exits[1]);
+    // 客户端确认 NewConnId 之后服务端换用新的 ID；客户端自己的 ID 不变
+    timeout(Duration::from_secs(5), async {
+        while server.conn_id() == before {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(client.conn_id(), before);
+
+    // 宽限期过后新的 ID 照常工作
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    client.send(Bytes::from_static(b"after")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap(), Bytes::from_static(b"after"));
+    assert_eq!(server.stats().stale_conn_id, 0);
+    assert_eq!(listener.stats().connections, 1);
+}
@@ -0,0 +1,41 @@
+//! MSS 协商集成测试：双方在握手中通告各自能接收的最大数据报（`recv_buffer`），每一端的有效 MSS
+//! 是本端的 `mss` 与双方通告中最小的一个；以 cookie 回应的握手从最后的确认中得知客户端的通告。
+//! 放不进对端通告的消息在发送时就被拒绝
+
+mod common;
+
+use bytes::Bytes;
+use common::pair_with;
+use link_rs::config::LinkConfig;
+use link_rs::cookie::SynCookies;
+use link_rs::error::LinkError;
+use link_rs::transport::MemoryNetwork;
+use std::time::Duration;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_asymmetric_advertisements_pick_the_minimum() {
+    for syn_cookies in [SynCookies::Never, SynCookies::Always] {
+        // 客户端只能接收 1100 字节的数据报；服务端本身的 mss 更小
+        let server = LinkConfig { mss: 900, syn_cookies, ..LinkConfig::default() };
+        let client = LinkConfig { mss: 1400, recv_buffer: 1100, ..LinkConfig::default() };
+        let (_listener, connection, accepted) = pair_with(&MemoryNetwork::new(), server, client).await;
+        assert_eq!((connection.mss(), accepted.mss()), (1100, 900), "{:?}", syn_cookies);
+
+        // 服务端发给客户端的消息受客户端的通告限制，反方向不受
+        assert!(matches!(accepted.send(Bytes::from(vec![0; 2000])).await, Err(LinkError::MessageTooLarge { len: 2000, .. })));
+        connection.send(Bytes::from(vec![7; 2000])).await.unwrap();
+        assert_eq!(timeout(Duration::from_secs(5), accepted.recv()).await.unwrap().unwrap(), Some(Bytes::from(vec![7; 2000])));
+        accepted.send(Bytes::from(vec![9; 900])).await.unwrap();
+        assert_eq!(timeout(Duration::from_secs(5), connection.recv()).await.unwrap().unwrap(), Some(Bytes::from(vec![9; 900])));
+    }
+}
+
+#[tokio::test]
+async fn test_default_advertisements_keep_the_configured_mss() {
+    let (_listener, connection, accepted) = pair_with(&MemoryNetwork::new(), LinkConfig::default(), LinkConfig { mss: 1400, ..LinkConfig::default() }).await;
+    assert_eq!((connection.mss(), accepted.mss()), (1400, LinkConfig::default().mss));
+    // 比 mss 大、但放得进对端接收缓冲的消息照常收发
+    connection.send(Bytes::from(vec![1; 16 * 1024])).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), accepted.recv()).await.unwrap().unwrap().map(|message| message.len()), Some(16 * 1024));
+}
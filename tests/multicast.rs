@@ -0,0 +1,51 @@
+//! 组播集成测试：同一主机上的两个接收端经回环接口加入同一个组，都收到发送方的每个段，重复的段只交付一次
+#![cfg(feature = "tokio")]
+
+use bytes::Bytes;
+use link_rs::config::LinkConfig;
+use link_rs::multicast::{MulticastConfig, MulticastSender};
+use link_rs::segment::{Segment, SegmentType};
+use link_rs::server::Server;
+use socket2::{Domain, Socket, Type};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::time::timeout;
+
+const GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 42, 7);
+const LOOPBACK: Ipv4Addr = Ipv4Addr::LOCALHOST;
+
+#[tokio::test]
+async fn test_two_receivers_get_every_segment() {
+    let mut first = Server::bind_multicast(GROUP, 0, LOOPBACK, LinkConfig::default()).unwrap();
+    let port = first.local_addr().unwrap().port();
+    let mut second = Server::bind_multicast(GROUP, port, LOOPBACK, LinkConfig::default()).unwrap();
+
+    let group = SocketAddr::from((GROUP, port));
+    let sender = MulticastSender::bind(group, &MulticastConfig { interface_v4: LOOPBACK, ..MulticastConfig::default() }).unwrap();
+    let messages: Vec<Bytes> = (0..10u8).map(|i| Bytes::from(vec![i; 100])).collect();
+    let mut seqs = Vec::new();
+    for message in &messages {
+        seqs.push(sender.send(message.clone()).await.unwrap());
+    }
+    // 另一个发送方把同一个段发两次：两个接收端都只交付一次
+    let raw = Socket::new(Domain::IPV4, Type::DGRAM, None).unwrap();
+    raw.set_multicast_if_v4(&LOOPBACK).unwrap();
+    let raw = std::net::UdpSocket::from(raw);
+    let repeated = Segment::builder(SegmentType::Data).data_seq(7u64).payload("again").build().unwrap().encode().unwrap();
+    raw.send_to(&repeated, group).unwrap();
+    raw.send_to(&repeated, group).unwrap();
+    sender.send(Bytes::from_static(b"last")).await.unwrap();
+
+    let from = sender.local_addr().unwrap().port();
+    for receiver in [&mut first, &mut second] {
+        for (message, seq) in messages.iter().zip(&seqs) {
+            let (segment, peer) = timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap();
+            assert_eq!((segment.data(), segment.seq(), peer.port()), (message, *seq, from));
+        }
+        let (segment, peer) = timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap();
+        assert_eq!((segment.data(), segment.seq().get(), peer.port()), (&Bytes::from_static(b"again"), 7, raw.local_addr().unwrap().port()));
+        let (segment, _) = timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap();
+        assert_eq!(segment.data(), &Bytes::from_static(b"last"));
+        assert_eq!(receiver.duplicates(), 1);
+    }
+}
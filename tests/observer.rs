@@ -0,0 +1,118 @@
+//! 观察者集成测试：有丢包的传输与优雅关闭中，注册的观察者按顺序收到建立、RTT 样本、重传、
+//! 关闭过程的状态迁移与结束
+
+mod common;
+
+use bytes::Bytes;
+use common::server_addr;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::observer::{ConnectionObserver, Observer};
+use link_rs::seq::SeqNum;
+use link_rs::state::ConnState;
+use link_rs::transport::MemoryNetwork;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::{Instant, timeout};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Recorded {
+    Established(SocketAddr),
+    Close(SocketAddr, Option<LinkError>),
+    State(ConnState, ConnState),
+    Retransmit(SeqNum, u32),
+    Rtt,
+}
+
+#[derive(Default)]
+struct Recorder(Mutex<Vec<Recorded>>);
+
+impl Recorder {
+    fn events(&self) -> Vec<Recorded> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn push(&self, event: Recorded) {
+        self.0.lock().unwrap().push(event);
+    }
+}
+
+impl ConnectionObserver for Recorder {
+    fn on_established(&self, peer: SocketAddr) {
+        self.push(Recorded::Established(peer));
+    }
+
+    fn on_close(&self, peer: SocketAddr, reason: Option<&LinkError>) {
+        self.push(Recorded::Close(peer, reason.cloned()));
+    }
+
+    fn on_state_change(&self, old: ConnState, new: ConnState) {
+        self.push(Recorded::State(old, new));
+    }
+
+    fn on_retransmit(&self, seq: SeqNum, attempt: u32) {
+        self.push(Recorded::Retransmit(seq, attempt));
+    }
+
+    fn on_rtt_sample(&self, _rtt: Duration) {
+        self.push(Recorded::Rtt);
+    }
+}
+
+#[tokio::test]
+async fn test_observer_sees_lossy_transfer_and_close() {
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap();
+    let recorder = Arc::new(Recorder::default());
+    let config = LinkConfig { nodelay: true, observer: Some(Observer::new(recorder.clone())), ..LinkConfig::default() };
+    let connection = Connection::connect_over(network.bind("10.0.0.2:0".parse().unwrap()).unwrap(), server_addr(), config).await.unwrap();
+    let (accepted, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+
+    // 丢弃客户端发出的每第 4 个数据报
+    let mut sent = 0;
+    network.set_filter(move |_, _, to| {
+        if to != server_addr() {
+            return true;
+        }
+        sent += 1;
+        sent % 4 != 0
+    });
+    for i in 0..30u8 {
+        connection.send(Bytes::from(vec![i; 100])).await.unwrap();
+    }
+    for i in 0..30u8 {
+        assert_eq!(timeout(Duration::from_secs(10), accepted.recv()).await.unwrap().unwrap(), Some(Bytes::from(vec![i; 100])));
+    }
+    network.clear_filter();
+
+    // 客户端先关闭，TIME_WAIT 到期后报告结束
+    let server = tokio::spawn(async move {
+        assert_eq!(timeout(Duration::from_secs(10), accepted.recv()).await.unwrap().unwrap(), None);
+        accepted.close().await.unwrap();
+    });
+    connection.close().await.unwrap();
+    server.await.unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !recorder.events().iter().any(|event| matches!(event, Recorded::Close(..))) {
+        assert!(Instant::now() < deadline, "no close reported: {:?}", recorder.events());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    let events = recorder.events();
+    assert_eq!(events.first(), Some(&Recorded::Established(server_addr())));
+    assert_eq!(events.last(), Some(&Recorded::Close(server_addr(), None)));
+    let position = |wanted: &Recorded| events.iter().position(|event| event == wanted).unwrap_or_else(|| panic!("{:?} missing: {:?}", wanted, events));
+    let retransmit = events.iter().rposition(|event| matches!(event, Recorded::Retransmit(_, attempt) if *attempt > 0));
+    let retransmit = retransmit.unwrap_or_else(|| panic!("no retransmit: {:?}", events));
+    assert!(events.contains(&Recorded::Rtt));
+
+    // 重传都发生在关闭之前，关闭按 FinWait、TimeWait、Closed 的顺序迁移
+    let fin_wait = position(&Recorded::State(ConnState::Established, ConnState::FinWait));
+    let time_wait = position(&Recorded::State(ConnState::FinWait, ConnState::TimeWait));
+    let closed = position(&Recorded::State(ConnState::TimeWait, ConnState::Closed));
+    assert!(retransmit < fin_wait && fin_wait < time_wait && time_wait < closed, "{:?}", events);
+    assert_eq!(events.iter().filter(|event| matches!(event, Recorded::Established(_))).count(), 1);
+}
@@ -0,0 +1,127 @@
+//! 握手参数协商集成测试：双方在 SYN 与 SYN-ACK 中提出各自的参数，每项取更保守的一个，两端得到同一组参数；
+//! 以 cookie 回应的握手从最后的确认中得知协商结果。监听器的 `ParamPolicy` 可以收紧客户端提出的参数，
+//! 或以 `PARAMETER_ERROR` 的 Rst 拒绝握手；不携带参数的旧版本客户端按监听器自己的参数建立连接
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use bytes::{Bytes, BytesMut};
+use common::{accept, addr, server_addr};
+use link_rs::checksum::ChecksumAlgorithm;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::cookie::SynCookies;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::params::TransportParameters;
+use link_rs::segment::{Segment, SegmentType};
+use link_rs::transport::MemoryNetwork;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+async fn connect(network: &MemoryNetwork, client: LinkConfig) -> Result<Connection, LinkError> {
+    common::connect(network, addr(2, 0), client).await
+}
+
+// 监听器一并返回：它被丢弃后接受的连接也收不到数据了
+async fn pair(listener: Listener, network: &MemoryNetwork, client: LinkConfig) -> (Listener, Connection, Connection) {
+    let connection = connect(network, client).await.unwrap();
+    let accepted = accept(&listener).await;
+    (listener, connection, accepted)
+}
+
+fn listener(network: &MemoryNetwork, config: LinkConfig) -> Listener {
+    Listener::with_transport(network.bind(server_addr()).unwrap(), config).unwrap()
+}
+
+async fn round_trip(connection: &Connection, accepted: &Connection) {
+    connection.send(Bytes::from_static(b"ping")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), accepted.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"ping")));
+}
+
+#[tokio::test]
+async fn test_default_negotiation_takes_the_conservative_side() {
+    for syn_cookies in [SynCookies::Never, SynCookies::Always] {
+        let network = MemoryNetwork::new();
+        let server = LinkConfig { ack_every_n_segments: 4, syn_cookies, ..LinkConfig::default() };
+        let client = LinkConfig { keepalive_interval: Duration::from_secs(5), ..LinkConfig::default() };
+        let (_listener, connection, accepted) = pair(listener(&network, server), &network, client).await;
+
+        let negotiated = connection.negotiated();
+        assert_eq!(accepted.negotiated(), negotiated, "{:?}", syn_cookies);
+        // 确认频率取客户端的 2，保活间隔取客户端的 5s；监听器默认不接受 0-RTT
+        let expected = TransportParameters {
+            ack_every: 2,
+            keepalive_interval: Duration::from_secs(5),
+            sack: true,
+            early_data: false,
+            checksum: connection.checksum(),
+        };
+        assert_eq!(negotiated, expected, "{:?}", syn_cookies);
+        round_trip(&connection, &accepted).await;
+    }
+}
+
+#[tokio::test]
+async fn test_policy_clamps_the_proposal() {
+    for syn_cookies in [SynCookies::Never, SynCookies::Always] {
+        let network = MemoryNetwork::new();
+        let listener = listener(&network, LinkConfig { syn_cookies, accept_early_data: true, ..LinkConfig::default() });
+        // 不允许 SACK，保活至多 2s；放宽确认频率的调整不生效
+        listener.set_param_policy(|_: SocketAddr, proposed: &TransportParameters| {
+            Some(TransportParameters { keepalive_interval: proposed.keepalive_interval.min(Duration::from_secs(2)), sack: false, ack_every: 16, ..*proposed })
+        });
+        let (_listener, connection, accepted) = pair(listener, &network, LinkConfig::default()).await;
+
+        let negotiated = connection.negotiated();
+        assert_eq!(accepted.negotiated(), negotiated, "{:?}", syn_cookies);
+        assert_eq!((negotiated.keepalive_interval, negotiated.sack), (Duration::from_secs(2), false));
+        assert_eq!((negotiated.ack_every, negotiated.early_data), (2, true));
+        round_trip(&connection, &accepted).await;
+    }
+}
+
+#[tokio::test]
+async fn test_policy_rejects_the_proposal() {
+    let network = MemoryNetwork::new();
+    let listener = listener(&network, LinkConfig { checksums: vec![ChecksumAlgorithm::Crc32c, ChecksumAlgorithm::NoChecksum], ..LinkConfig::default() });
+    // 拒绝最偏好不校验的客户端
+    listener.set_param_policy(|_: SocketAddr, proposed: &TransportParameters| (proposed.checksum != ChecksumAlgorithm::NoChecksum).then_some(*proposed));
+
+    let unchecked = LinkConfig { checksums: vec![ChecksumAlgorithm::NoChecksum, ChecksumAlgorithm::Crc32c], ..LinkConfig::default() };
+    let result = connect(&network, unchecked).await;
+    assert!(matches!(result, Err(LinkError::ParameterRejected)), "{:?}", result.map(|_| ()));
+
+    // 其他客户端不受影响
+    let (_listener, connection, accepted) = pair(listener, &network, LinkConfig::default()).await;
+    assert_eq!(connection.checksum(), ChecksumAlgorithm::Crc32c);
+    round_trip(&connection, &accepted).await;
+}
+
+#[tokio::test]
+async fn test_old_peer_without_params_gets_the_local_values() {
+    let config = LinkConfig { ack_every_n_segments: 3, keepalive_interval: Duration::from_secs(20), ..LinkConfig::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let server = listener.local_addr().unwrap();
+
+    // 旧版本的客户端：SYN 与确认都不带任何选项
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(server).await.unwrap();
+    let syn = Segment::builder(SegmentType::Syn).data_seq(100u64).build().unwrap();
+    socket.send(&syn.encode().unwrap()).await.unwrap();
+    let mut buf = vec![0u8; 65535];
+    let len = timeout(Duration::from_secs(5), socket.recv(&mut buf)).await.unwrap().unwrap();
+    let syn_ack = Segment::decode_from(&mut BytesMut::from(&buf[..len])).unwrap().unwrap();
+    // SYN-ACK 照常携带参数，旧版本跳过这个选项
+    let offered = syn_ack.options().params().unwrap();
+    assert_eq!((offered.ack_every, offered.keepalive_interval), (3, Duration::from_secs(20)));
+    let ack = Segment::builder(SegmentType::Ack).conn_id(syn_ack.conn_id()).data_seq(100u64).ack(syn_ack.seq()).window(64).build().unwrap();
+    socket.send(&ack.encode().unwrap()).await.unwrap();
+
+    let (accepted, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    let negotiated = accepted.negotiated();
+    assert_eq!((negotiated.ack_every, negotiated.keepalive_interval, negotiated.sack), (3, Duration::from_secs(20), true));
+    assert_eq!(negotiated, offered);
+}
@@ -0,0 +1,73 @@
+//! 往返时间探测集成测试：连接上的每个探测都得到 Pong；经过 `FaultyTransport` 丢包时丢失的探测各自超时，后续探测照常发出
+#![cfg(feature = "tokio")]
+
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::fault::FaultConfig;
+use link_rs::listener::Listener;
+use link_rs::ping::{PingEvent, PingOptions, Pinger};
+use std::time::Duration;
+
+fn lossy(seed: u64) -> LinkConfig {
+    LinkConfig {
+        faults: Some(FaultConfig { loss: 0.25, seed, ..FaultConfig::default() }),
+        linger: Duration::from_secs(1),
+        ..LinkConfig::default()
+    }
+}
+
+// 接受一个连接并保持到对端关闭
+async fn serve(listener: Listener) {
+    let (server, _) = listener.accept().await.unwrap();
+    while let Ok(Some(_)) = server.recv().await {}
+    let _ = server.close().await;
+}
+
+#[tokio::test]
+async fn test_every_probe_answered() {
+    let listener = Listener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let serving = tokio::spawn(serve(listener));
+
+    let client = Connection::connect(addr).await.unwrap();
+    let options = PingOptions { count: 5, interval: Duration::from_millis(20), timeout: Duration::from_millis(500) };
+    let mut replies = Vec::new();
+    let summary = Pinger::new(&client).run(&options, |event| replies.push(*event)).await.unwrap();
+    assert_eq!((summary.sent, summary.received, summary.late), (5, 5, 0));
+    assert_eq!(summary.loss(), 0.0);
+    assert!(replies.iter().enumerate().all(|(i, event)| matches!(event, PingEvent::Reply { seq, .. } if *seq == i)));
+    assert!(summary.min_rtt <= summary.mean_rtt && summary.mean_rtt <= summary.max_rtt);
+
+    client.close().await.unwrap();
+    serving.await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_lost_probes_time_out_individually() {
+    let listener = Listener::bind_with("127.0.0.1:0", lossy(1)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let serving = tokio::spawn(serve(listener));
+
+    let client = Connection::connect_with(addr, lossy(2)).await.unwrap();
+    let options = PingOptions { count: 40, interval: Duration::from_millis(10), timeout: Duration::from_millis(200) };
+    let (mut replies, mut timeouts) = (0, 0);
+    let started = tokio::time::Instant::now();
+    let summary = Pinger::new(&client)
+        .run(&options, |event| match event {
+            PingEvent::Reply { .. } => replies += 1,
+            PingEvent::Timeout { .. } => timeouts += 1,
+            PingEvent::Late { .. } => {}
+        })
+        .await
+        .unwrap();
+    assert_eq!(summary.sent, 40);
+    assert_eq!((summary.received, replies + timeouts), (replies, 40));
+    assert!(timeouts > 0 && replies > 0, "{} replies, {} timeouts", replies, timeouts);
+    assert!(summary.loss() > 0.0 && summary.loss() < 1.0);
+    // 超时的探测没有推迟后续的探测：总时长约为 40 个间隔加一个超时
+    assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+
+    // 丢包下的关闭可能等到 linger 超时，与探测的结果无关
+    let _ = client.close().await;
+    serving.abort();
+}
@@ -0,0 +1,66 @@
+//! 路径 MTU 探测集成测试：内存网络丢弃超过阈值的数据报，双方的有效 MSS 收敛到阈值之下，探测期间数据照常交付
+
+use bytes::Bytes;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use link_rs::pmtu::RESOLUTION;
+use link_rs::transport::MemoryNetwork;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::time::timeout;
+
+const PATH_MTU: usize = 4000;
+
+fn probing() -> LinkConfig {
+    LinkConfig { max_mss: Some(9000), ..LinkConfig::default() }
+}
+
+async fn converged(connection: &Connection) -> usize {
+    loop {
+        let mss = connection.mss();
+        if mss + RESOLUTION > PATH_MTU {
+            return mss;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_mss_converges_below_path_mtu() {
+    let network = MemoryNetwork::new();
+    network.set_mtu(Some(PATH_MTU));
+    let server_addr: SocketAddr = "10.0.0.1:7000".parse().unwrap();
+    let listener = Listener::with_transport(network.bind(server_addr).unwrap(), probing()).unwrap();
+    let transport = network.bind("10.0.0.2:0".parse().unwrap()).unwrap();
+    let client = Connection::connect_over(transport, server_addr, probing()).await.unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+    assert_eq!(client.mss(), LinkConfig::default().mss);
+
+    // 超过阈值的探测全部丢失，数据不受影响
+    let messages: Vec<Bytes> = (0..50).map(|i| Bytes::from(vec![i as u8; 600])).collect();
+    let exchange = async {
+        for message in &messages {
+            client.send(message.clone()).await.unwrap();
+            assert_eq!(server.recv().await.unwrap().as_ref(), Some(message));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    };
+    timeout(Duration::from_secs(20), exchange).await.expect("data stalled while probing");
+
+    let (client_mss, server_mss) = timeout(Duration::from_secs(20), async { tokio::join!(converged(&client), converged(&server)) })
+        .await
+        .expect("path mtu did not converge");
+    for mss in [client_mss, server_mss] {
+        assert!(mss <= PATH_MTU, "mss {} exceeds the path mtu", mss);
+    }
+
+    // 按新的大小合并的小写入照常送达
+    let burst: Vec<Bytes> = (0..20).map(|i| Bytes::from(vec![i as u8; 100])).collect();
+    for message in &burst {
+        client.send(message.clone()).await.unwrap();
+    }
+    for message in &burst {
+        assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().as_ref(), Some(message));
+    }
+}
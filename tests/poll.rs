@@ -0,0 +1,136 @@
+//! 轮询接口集成测试：不经 `.await`，以计数的 waker 直接轮询内存网络上的连接。
+//! `poll_recv` 在数据到达之前、`poll_send` 在确认腾出发送队列之前、`poll_close` 在对端的 FIN 到达之前保持 `Pending`，
+//! 只有最近一次轮询登记的 waker 被唤醒；虚假唤醒后的轮询再次返回 `Pending`
+
+mod common;
+
+use bytes::Bytes;
+use common::{client_addr, pair};
+use link_rs::config::LinkConfig;
+use link_rs::transport::MemoryNetwork;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+use tokio::time::timeout;
+
+/// 记录被唤醒次数的 waker
+#[derive(Default)]
+struct Counter(AtomicUsize);
+
+impl Wake for Counter {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Counter {
+    fn wakes(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+fn counting() -> (Arc<Counter>, Waker) {
+    let counter = Arc::new(Counter::default());
+    (counter.clone(), Waker::from(counter))
+}
+
+// 让驱动任务运行，直到 `counter` 被唤醒
+async fn woken(counter: &Counter) {
+    timeout(Duration::from_secs(5), async {
+        while counter.wakes() == 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    })
+    .await
+    .expect("waker was not woken");
+}
+
+#[tokio::test]
+async fn test_poll_recv_is_ready_when_data_arrives() {
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair(&network, LinkConfig::default()).await;
+    let (first, first_waker) = counting();
+    let (second, second_waker) = counting();
+
+    assert!(server.poll_recv(&mut Context::from_waker(&first_waker)).is_pending());
+    // 新的上下文取代之前登记的 waker
+    assert!(server.poll_recv(&mut Context::from_waker(&second_waker)).is_pending());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(server.poll_recv(&mut Context::from_waker(Waker::noop())).is_pending());
+    assert!(server.poll_recv(&mut Context::from_waker(&second_waker)).is_pending());
+    assert_eq!((first.wakes(), second.wakes()), (0, 0));
+
+    client.send(Bytes::from_static(b"hello")).await.unwrap();
+    woken(&second).await;
+    assert_eq!(first.wakes(), 0);
+    let mut cx = Context::from_waker(&second_waker);
+    assert!(matches!(server.poll_recv(&mut cx), Poll::Ready(Ok(Some(message))) if message == "hello"));
+    // 没有更多数据：再次轮询仍是 Pending
+    assert!(server.poll_recv(&mut cx).is_pending());
+
+    client.shutdown_write().await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), std::future::poll_fn(|cx| server.poll_recv(cx))).await.unwrap().unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_poll_send_is_ready_when_acks_free_the_queue() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { send_buffer: 1000, ..LinkConfig::default() };
+    let (_listener, client, server) = pair(&network, config).await;
+    // 丢弃发往客户端的确认，发送队列只出不进
+    network.set_filter(|_, _, to| to != client_addr());
+    let (first, first_waker) = counting();
+    let message = Bytes::from(vec![1; 100]);
+    let mut accepted = 0;
+    while client.poll_send(&mut Context::from_waker(&first_waker), &message).is_ready() {
+        accepted += 1;
+    }
+    assert!(accepted > 0 && accepted <= 10, "{}", accepted);
+
+    let (second, second_waker) = counting();
+    assert!(client.poll_send(&mut Context::from_waker(&second_waker), &message).is_pending());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!((first.wakes(), second.wakes()), (0, 0));
+
+    // 确认恢复后重传的段得到确认，队列腾出空间
+    network.clear_filter();
+    woken(&second).await;
+    assert_eq!(first.wakes(), 0);
+    assert!(client.poll_send(&mut Context::from_waker(&second_waker), &message).is_ready());
+
+    // 未就绪的轮询没有留下消息：对端恰好收到被接受的那些
+    client.shutdown_write().await.unwrap();
+    let mut received = 0;
+    while let Some(data) = timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap() {
+        assert_eq!(data, message);
+        received += 1;
+    }
+    assert_eq!(received, accepted + 1);
+}
+
+#[tokio::test]
+async fn test_poll_close_is_ready_after_the_peer_fin() {
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair(&network, LinkConfig::default()).await;
+    let (counter, waker) = counting();
+    let mut cx = Context::from_waker(&waker);
+
+    assert!(client.poll_close(&mut cx).is_pending());
+    // 本端的 FIN 被确认、而对端还没有关闭
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), None);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let before = counter.wakes();
+    assert!(client.poll_close(&mut cx).is_pending());
+
+    server.shutdown_write().await.unwrap();
+    timeout(Duration::from_secs(5), async {
+        while counter.wakes() == before {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert!(matches!(client.poll_close(&mut cx), Poll::Ready(Ok(()))));
+    assert!(client.send(Bytes::from_static(b"late")).await.is_err());
+}
@@ -0,0 +1,57 @@
+//! 缓冲池的分配计数：以计数的全局分配器比较打包发送时每个数据报新分配与复用池中缓冲的分配次数。
+//! 这个文件只有一个测试，计数不会混入并行执行的其他测试
+
+use link_rs::pool::BufferPool;
+use link_rs::segment::{self, Segment, SegmentType};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        // SAFETY: 原样转交系统分配器，调用方保证 layout 合法
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: ptr 由上面的 alloc 经系统分配器分配
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const ROUNDS: usize = 1000;
+
+// 每轮把一批段打包成数据报，数据报用过后交给 `release`；返回期间的分配次数
+fn count(mut pack: impl FnMut(&[Segment]) -> Vec<bytes::BytesMut>, mut release: impl FnMut(bytes::BytesMut), segments: &[Segment]) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..ROUNDS {
+        for datagram in pack(segments) {
+            release(datagram);
+        }
+    }
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+#[test]
+fn test_pool_reduces_allocations() {
+    // 每批 8 个 338 字节的段，打包成 3 个 1200 字节以内的数据报
+    let segments: Vec<_> = (0..8).map(|seq| Segment::new(SegmentType::Data, seq, vec![0xAB; 300])).collect();
+    let fresh = count(|segments| segment::pack_datagrams(segments, 1200).unwrap(), drop, &segments);
+
+    let pool = BufferPool::with_limits(1200, 64 * 1024);
+    let pooled = count(|segments| segment::pack_datagrams_with(segments, 1200, &pool).unwrap(), |datagram| pool.put(datagram), &segments);
+
+    // 新分配时每个数据报至少一次（追加时还可能扩容）；池化后只剩承载数据报列表的 Vec
+    assert!(fresh >= 3 * ROUNDS, "{} allocations without the pool", fresh);
+    assert!(pooled <= ROUNDS + 4, "{} allocations with the pool", pooled);
+    let stats = pool.stats();
+    assert_eq!(stats.hits + stats.misses, 3 * ROUNDS as u64);
+    assert!(stats.misses <= 3);
+}
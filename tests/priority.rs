@@ -0,0 +1,132 @@
+//! 控制段优先集成测试：客户端经过限速的传输（每个数据报占用链路 `PER_DATAGRAM`）批量发送，
+//! 协议核心中排着上百个数据的数据报；其间服务端的 Ping 得到的 Pong 插在排队的数据之前发出，往返时间不随队列增长；
+//! 另一个流上零星的消息与批量流轮流发出，不排在整窗数据之后
+
+mod common;
+
+use bytes::Bytes;
+use common::{client_addr, server_addr};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use link_rs::ping::{PingOptions, Pinger};
+use link_rs::transport::{BoxFuture, MemoryNetwork, MemoryTransport, Transport};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+
+const PER_DATAGRAM: Duration = Duration::from_millis(2);
+
+/// 一次只发一个数据报、每个占用 `PER_DATAGRAM` 的链路
+#[derive(Debug)]
+struct Throttled {
+    inner: MemoryTransport,
+    link: Mutex<()>,
+}
+
+impl Transport for Throttled {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        Box::pin(async move {
+            let _link = self.link.lock().await;
+            tokio::time::sleep(PER_DATAGRAM).await;
+            self.inner.send_to(buf, target).await
+        })
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        self.inner.recv_from(buf)
+    }
+}
+
+#[tokio::test]
+async fn test_pongs_overtake_queued_bulk_data() {
+    let network = MemoryNetwork::new();
+    // 整窗一次放出：客户端的协议核心中排着约 `initial_cwnd` 个数据报（约 400ms 的链路时间）
+    let config = LinkConfig { pacing: false, initial_cwnd: 200, send_window: 256, ..LinkConfig::default() };
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config.clone()).unwrap();
+    let throttled = Throttled { inner: network.bind(client_addr()).unwrap(), link: Mutex::new(()) };
+    let client = Connection::connect_over(throttled, server_addr(), config).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+
+    let bulk = tokio::spawn(async move {
+        for _ in 0..2000 {
+            client.send(Bytes::from(vec![7; 1000])).await.unwrap();
+        }
+    });
+    let first = timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap();
+    assert_eq!(first.len(), 1000);
+    // 服务端一直读，接收窗口不会让批量发送停下
+    let server = Arc::new(server);
+    let reader = tokio::spawn({
+        let server = server.clone();
+        async move { while let Ok(Some(_)) = server.recv().await {} }
+    });
+
+    let options = PingOptions { count: 5, interval: Duration::from_millis(20), timeout: Duration::from_secs(1) };
+    let summary = Pinger::new(&server).run(&options, |_| {}).await.unwrap();
+    assert_eq!(summary.received, 5);
+    // 至多等正在发送的一个数据报，远小于排队的数据所需的时间
+    let max_rtt = summary.max_rtt.unwrap();
+    assert!(max_rtt < Duration::from_millis(100), "{:?}", max_rtt);
+    bulk.abort();
+    reader.abort();
+}
+
+#[tokio::test]
+async fn test_trickle_stream_is_not_starved_by_a_bulk_stream() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { pacing: false, initial_cwnd: 200, send_window: 256, ..LinkConfig::default() };
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config.clone()).unwrap();
+    let throttled = Throttled { inner: network.bind(client_addr()).unwrap(), link: Mutex::new(()) };
+    let client = Arc::new(Connection::connect_over(throttled, server_addr(), config).await.unwrap());
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+
+    // 批量流一直有整窗的数据排队，服务端一直读
+    let bulk = client.open_stream().unwrap();
+    let trickle = client.open_stream().unwrap();
+    bulk.send(Bytes::from(vec![7; 1000])).await.unwrap();
+    let bulk_server = timeout(Duration::from_secs(5), server.accept_stream()).await.unwrap().unwrap();
+    let sending = tokio::spawn(async move {
+        loop {
+            bulk.send(Bytes::from(vec![7; 1000])).await.unwrap();
+        }
+    });
+    let received = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let reader = tokio::spawn({
+        let received = received.clone();
+        async move {
+            while let Ok(Some(data)) = bulk_server.recv().await {
+                received.fetch_add(data.len(), std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // 涓流的每条消息至多等批量流一轮的额度（一个数据报），远小于排队的整窗数据所需的时间
+    trickle.send(Bytes::from_static(b"first")).await.unwrap();
+    let trickle_server = timeout(Duration::from_secs(5), server.accept_stream()).await.unwrap().unwrap();
+    assert_eq!(trickle_server.recv().await.unwrap(), Some(Bytes::from_static(b"first")));
+    let before = received.load(std::sync::atomic::Ordering::Relaxed);
+    let started = tokio::time::Instant::now();
+    for i in 0..10u8 {
+        let sent = tokio::time::Instant::now();
+        trickle.send(Bytes::from(vec![i; 10])).await.unwrap();
+        assert_eq!(timeout(Duration::from_secs(5), trickle_server.recv()).await.unwrap().unwrap(), Some(Bytes::from(vec![i; 10])));
+        let latency = sent.elapsed();
+        assert!(latency < Duration::from_millis(50), "message {} took {:?}", i, latency);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    // 其间批量流照常用着余下的链路
+    let bulk_bytes = received.load(std::sync::atomic::Ordering::Relaxed) - before;
+    let capacity = (started.elapsed().as_millis() / PER_DATAGRAM.as_millis()) as usize * 1000;
+    assert!(bulk_bytes > capacity / 2, "{} of {}", bulk_bytes, capacity);
+    sending.abort();
+    reader.abort();
+}
@@ -0,0 +1,77 @@
+//! 反射流量集成测试：一面"镜子"把收到的每个数据报原样发回。监听器丢弃并计数被发回的自己的段，流量随之停止；
+//! 连向镜子的客户端不会与自己的 SYN 完成握手；同一进程中的客户端与监听器互不干扰
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use bytes::Bytes;
+use common::server_addr;
+use link_rs::checksum::ChecksumAlgorithm;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::segment::{Segment, SegmentType};
+use link_rs::transport::{MemoryNetwork, MemoryTransport, Transport};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::time::timeout;
+
+fn mirror_addr() -> SocketAddr {
+    "10.0.0.9:9000".parse().unwrap()
+}
+
+// 先把 `first`（如果有）发给监听器，之后把收到的每个数据报发回给发送方；返回发回的数据报数
+fn mirror(transport: MemoryTransport, first: Option<Segment>) -> Arc<AtomicUsize> {
+    let reflected = Arc::new(AtomicUsize::new(0));
+    let count = reflected.clone();
+    tokio::spawn(async move {
+        if let Some(first) = first {
+            transport.send_to(&first.encode().unwrap(), server_addr()).await.unwrap();
+        }
+        let mut buf = vec![0u8; 65536];
+        while let Ok((len, from)) = transport.recv_from(&mut buf).await {
+            count.fetch_add(1, Ordering::Relaxed);
+            let _ = transport.send_to(&buf[..len], from).await;
+        }
+    });
+    reflected
+}
+
+#[tokio::test]
+async fn test_listener_drops_its_reflected_segments() {
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap();
+    // 一面镜子发起握手，另一面发出不属于任何连接的数据段
+    let syn = Segment::builder(SegmentType::Syn).data_seq(1000u64).offer(&[ChecksumAlgorithm::Crc32c]).checksum(ChecksumAlgorithm::Crc32c).build().unwrap();
+    let stray = Segment::builder(SegmentType::Data).conn_id(77).data_seq(5u64).payload(Bytes::from_static(b"stray")).build().unwrap();
+    let syn_acks = mirror(network.bind(mirror_addr()).unwrap(), Some(syn));
+    let rsts = mirror(network.bind("10.0.0.8:8000".parse().unwrap()).unwrap(), Some(stray));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let reflected = || syn_acks.load(Ordering::Relaxed) + rsts.load(Ordering::Relaxed);
+
+    // SYN-ACK 与 Rst 各被发回一次，监听器丢弃它们，不再回应
+    assert_eq!((syn_acks.load(Ordering::Relaxed), rsts.load(Ordering::Relaxed)), (1, 1));
+    assert_eq!(listener.metrics().reflected, 2);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(reflected(), 2);
+
+    // 同一进程中的客户端照常连接，它的段不会被当作反射
+    let client = Connection::connect_over(network.bind("10.0.0.2:5000".parse().unwrap()).unwrap(), server_addr(), LinkConfig::default()).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    client.send(Bytes::from_static(b"hello")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap(), Bytes::from_static(b"hello"));
+    assert_eq!(listener.metrics().reflected, 2);
+}
+
+#[tokio::test]
+async fn test_client_ignores_its_reflected_handshake() {
+    let network = MemoryNetwork::new();
+    mirror(network.bind(mirror_addr()).unwrap(), None);
+    let config = LinkConfig { handshake_timeout: Duration::from_millis(300), ..LinkConfig::default() };
+    // 被发回的 SYN 看起来像对端同时打开，接受它就会与自己建立连接
+    let connected = Connection::connect_over(network.bind("10.0.0.2:5000".parse().unwrap()).unwrap(), mirror_addr(), config).await;
+    assert!(matches!(connected, Err(LinkError::ConnectTimeout { .. })), "{:?}", connected.map(|_| ()));
+}
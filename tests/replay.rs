@@ -0,0 +1,52 @@
+//! 记录与重放集成测试：内存网络上的一次会话由监听器的 `RecordWriter` 记录下来，
+//! 重放给新的监听器后交付同样的消息序列
+
+mod common;
+
+use bytes::Bytes;
+use common::{client_addr, server_addr};
+use link_rs::capture::Capture;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use link_rs::replay::{self, RecordWriter, ReplayEvent};
+use link_rs::transport::MemoryNetwork;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_replayed_session_delivers_the_same_messages() {
+    let path = std::env::temp_dir().join(format!("link-replay-{}.rec", std::process::id()));
+    let writer = Arc::new(RecordWriter::create(&path).await.unwrap());
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { capture: Some(Capture::new(writer.clone())), ..LinkConfig::default() };
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config).unwrap();
+    let client = Connection::connect_over(network.bind(client_addr()).unwrap(), server_addr(), LinkConfig::default()).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+
+    let messages: Vec<Bytes> = (0..5).map(|i| Bytes::from(format!("message {}", i).repeat(i + 1))).collect();
+    for message in &messages {
+        client.send(message.clone()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    client.shutdown_write().await.unwrap();
+    let mut received = Vec::new();
+    while let Some(message) = timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap() {
+        received.push(message);
+    }
+    assert_eq!(received, messages);
+    writer.flush().await;
+    assert_eq!(writer.dropped(), 0);
+
+    let records = replay::read(&path).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(records.iter().all(|record| record.from == client_addr() && record.to == server_addr()));
+    assert!(records.iter().map(|record| record.delay).sum::<Duration>() >= Duration::from_millis(100));
+
+    let events = replay::replay(&records, LinkConfig::default(), 4.0).await.unwrap();
+    let mut expected = vec![ReplayEvent::Accepted(client_addr())];
+    expected.extend(messages.iter().map(|message| ReplayEvent::Message(client_addr(), message.clone())));
+    expected.push(ReplayEvent::Finished(client_addr()));
+    assert_eq!(events, expected);
+}
@@ -0,0 +1,63 @@
+//! 重新同步集成测试：接收方的链路中断期间发送方积压了数据，恢复后接收方请求从最新位置开始，
+//! 发送方放弃积压、不再重传，接收方之后只读到新的消息；起点早于发送方仍保留的数据的请求被拒绝，连接不受影响
+mod common;
+
+use bytes::Bytes;
+use common::{pair_with, recv};
+use link_rs::config::LinkConfig;
+use link_rs::error::LinkError;
+use link_rs::transport::MemoryNetwork;
+use std::time::Duration;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_resync_to_latest_after_an_outage() {
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair_with(&network, LinkConfig::default(), LinkConfig { nodelay: true, ..LinkConfig::default() }).await;
+    server.send(Bytes::from_static(b"read")).await.unwrap();
+    assert_eq!(recv(&client).await, Bytes::from_static(b"read"));
+    // 到达了但还没读取
+    server.send(Bytes::from_static(b"unread")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // 链路中断期间发送方继续写入
+    network.set_filter(|_, _, _| false);
+    for i in 0..20 {
+        server.send(Bytes::from(format!("backlog {}", i))).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    network.clear_filter();
+
+    timeout(Duration::from_secs(5), client.resync(None)).await.unwrap().unwrap();
+    // 积压的数据已被放弃，不再重传
+    assert!(server.unacknowledged().is_empty());
+    server.send(Bytes::from_static(b"fresh")).await.unwrap();
+    assert_eq!(recv(&client).await, Bytes::from_static(b"fresh"));
+
+    let closing = tokio::spawn(async move { server.close().await });
+    client.close().await.unwrap();
+    closing.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_backwards_resync_is_refused() {
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair_with(&network, LinkConfig::default(), LinkConfig { nodelay: true, ..LinkConfig::default() }).await;
+    let head = client.resync(None).await.unwrap();
+    for i in 0..3 {
+        server.send(Bytes::from(format!("message {}", i))).await.unwrap();
+        assert_eq!(recv(&client).await, Bytes::from(format!("message {}", i)));
+    }
+    // 等确认到达发送方：之前的数据都不再保留
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(server.unacknowledged().is_empty());
+
+    let refused = client.resync(Some(head)).await;
+    assert_eq!(refused, Err(LinkError::ResyncRefused { oldest: head.wrapping_add(3) }));
+
+    // 连接照常工作
+    server.send(Bytes::from_static(b"still here")).await.unwrap();
+    assert_eq!(recv(&client).await, Bytes::from_static(b"still here"));
+    client.send(Bytes::from_static(b"reply")).await.unwrap();
+    assert_eq!(recv(&server).await, Bytes::from_static(b"reply"));
+}
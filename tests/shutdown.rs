@@ -0,0 +1,60 @@
+//! 关闭集成测试：客户端传输途中服务器 `shutdown`，每个客户端都看到对端正常关闭（`recv` 返回 `None`），
+//! 而不是等到超时；关闭之后的新连接被拒绝
+#![cfg(feature = "tokio")]
+
+use bytes::Bytes;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::server::{Drained, EchoHandler, Server};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::time::timeout;
+
+const CLIENTS: usize = 8;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_shutdown_closes_clients_cleanly() {
+    let server = Arc::new(Server::bind("127.0.0.1:0", LinkConfig::default(), EchoHandler).await.unwrap());
+    let addr = server.local_addr().unwrap();
+    let running = tokio::spawn({
+        let server = server.clone();
+        async move { server.run().await }
+    });
+
+    // 每个客户端不停地发送 16KB 的消息并等待回显，直到服务器关闭
+    let echoed = Arc::new(AtomicUsize::new(0));
+    let clients: Vec<_> = (0..CLIENTS)
+        .map(|id| {
+            let echoed = echoed.clone();
+            tokio::spawn(async move {
+                let connection = Connection::connect(addr).await.unwrap();
+                let message = Bytes::from(vec![id as u8; 16 * 1024]);
+                loop {
+                    connection.send(message.clone()).await.unwrap();
+                    match connection.recv().await.unwrap() {
+                        Some(reply) => {
+                            assert_eq!(reply, message);
+                            echoed.fetch_add(1, Ordering::Relaxed);
+                        }
+                        None => break,
+                    }
+                }
+                connection.close().await.unwrap();
+            })
+        })
+        .collect();
+
+    while echoed.load(Ordering::Relaxed) < CLIENTS * 10 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    server.shutdown();
+    for client in clients {
+        timeout(Duration::from_secs(10), client).await.expect("client did not observe the close").unwrap();
+    }
+    let drained = timeout(Duration::from_secs(10), running).await.unwrap().unwrap().unwrap();
+    assert_eq!(drained, Drained { closed: CLIENTS, failed: 0, aborted: 0 });
+
+    // 监听器仍在，但不再接受握手
+    assert!(timeout(Duration::from_secs(5), Connection::connect(addr)).await.unwrap().is_err());
+}
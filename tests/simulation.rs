@@ -0,0 +1,250 @@
+//! 确定性模拟测试：客户端与服务端跑在 `MemoryNetwork` 上，时间由 `tokio::time::pause` 控制，
+//! 按脚本丢弃指定的段，再从记录下的收发时间断言精确的定时行为：RTO 恰在计算出的时间到期、
+//! 第三个（而不是第二个）重复确认触发快速重传、保活恰好在 N 个探测后判定失联、超时重传的间隔逐次翻倍、
+//! 打开零窗口的窗口更新丢失后由坚持探测恢复传输、确认频率随 `ack_every_n_segments` 变化
+
+use bytes::{Bytes, BytesMut};
+use link_rs::config::LinkConfig;
+use link_rs::congestion::CongestionAlgorithm;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::segment::{Segment, SegmentType};
+use link_rs::transport::MemoryNetwork;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+const SERVER: &str = "10.0.0.1:7000";
+
+// 线路上出现过的一个段
+#[derive(Debug, Clone)]
+struct Sent {
+    at: Instant,
+    to_server: bool,
+    segment: Segment,
+    delivered: bool,
+}
+
+impl Sent {
+    fn is_data(&self) -> bool {
+        self.segment.segment_type() == SegmentType::Data && !self.segment.data().is_empty()
+    }
+}
+
+type Rule = Box<dyn FnMut(&Sent) -> bool + Send>;
+
+// 收发记录与丢包脚本：数据报中有任何一个段让脚本返回 true 时整个数据报被丢弃
+struct Wire {
+    log: Vec<Sent>,
+    drop: Rule,
+}
+
+struct Sim {
+    client: Connection,
+    server: Connection,
+    wire: Arc<Mutex<Wire>>,
+    _listener: Listener,
+}
+
+impl Sim {
+    async fn new(client: LinkConfig, server: LinkConfig) -> Sim {
+        let network = MemoryNetwork::new();
+        let server_addr: SocketAddr = SERVER.parse().unwrap();
+        let wire = Arc::new(Mutex::new(Wire { log: Vec::new(), drop: Box::new(|_| false) }));
+        network.set_filter({
+            let wire = wire.clone();
+            move |datagram, _, to| {
+                let mut wire = wire.lock().unwrap();
+                let mut buf = BytesMut::from(datagram);
+                let mut segments = Vec::new();
+                while let Ok(Some(segment)) = Segment::decode_from(&mut buf) {
+                    segments.push(Sent { at: Instant::now(), to_server: to == server_addr, segment, delivered: true });
+                }
+                let mut dropped = false;
+                for sent in &segments {
+                    dropped |= (wire.drop)(sent);
+                }
+                wire.log.extend(segments.into_iter().map(|sent| Sent { delivered: !dropped, ..sent }));
+                !dropped
+            }
+        });
+        let listener = Listener::with_transport(network.bind(server_addr).unwrap(), server).unwrap();
+        let transport = network.bind("10.0.0.2:0".parse().unwrap()).unwrap();
+        let client = Connection::connect_over(transport, server_addr, client).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        // 握手的余波（确认、窗口更新）全部结束
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        Sim { client, server, wire, _listener: listener }
+    }
+
+    // 替换丢包脚本
+    fn drop_when(&self, rule: impl FnMut(&Sent) -> bool + Send + 'static) {
+        self.wire.lock().unwrap().drop = Box::new(rule);
+    }
+
+    fn sent(&self, filter: impl Fn(&Sent) -> bool) -> Vec<Sent> {
+        self.wire.lock().unwrap().log.iter().filter(|sent| filter(sent)).cloned().collect()
+    }
+
+    // 客户端发出的某个数据段的每一次传输
+    fn transmissions(&self, seq: u64) -> Vec<Sent> {
+        self.sent(|sent| sent.to_server && sent.is_data() && sent.segment.seq().get() == seq)
+    }
+
+    // 客户端发出的第一个数据段的序列号
+    fn first_data_seq(&self) -> u64 {
+        self.sent(|sent| sent.to_server && sent.is_data())[0].segment.seq().get()
+    }
+}
+
+// 只丢弃客户端第一个数据段的第一次传输
+fn drop_first_data() -> impl FnMut(&Sent) -> bool + Send + 'static {
+    let mut dropped = false;
+    move |sent| sent.to_server && sent.is_data() && !std::mem::replace(&mut dropped, true)
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_rto_fires_at_computed_time() {
+    let sim = Sim::new(LinkConfig::default(), LinkConfig::default()).await;
+    let rto = sim.client.stats().rto;
+    sim.drop_when(drop_first_data());
+
+    sim.client.send(Bytes::from_static(b"lost once")).await.unwrap();
+    assert_eq!(sim.server.recv().await.unwrap(), Some(Bytes::from_static(b"lost once")));
+
+    let sends = sim.transmissions(sim.first_data_seq());
+    assert_eq!(sends.iter().map(|sent| sent.delivered).collect::<Vec<_>>(), vec![false, true]);
+    assert_eq!(sends[1].at - sends[0].at, rto);
+    assert_eq!((sim.client.stats().timeouts, sim.client.stats().fast_retransmits), (1, 0));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_third_duplicate_ack_triggers_fast_retransmit() {
+    // 丢掉 n 个段中的第一个：之后的 n - 1 个段各引出一个重复确认。每个段几乎占满 MSS，各自单独成一个数据报
+    for (messages, fast) in [(3, false), (4, true)] {
+        let config = LinkConfig { nodelay: true, ..LinkConfig::default() };
+        let sim = Sim::new(config.clone(), config).await;
+        let rto = sim.client.stats().rto;
+        sim.drop_when(drop_first_data());
+
+        for i in 0..messages {
+            sim.client.send(Bytes::from(vec![i; 1100])).await.unwrap();
+        }
+        for i in 0..messages {
+            assert_eq!(sim.server.recv().await.unwrap(), Some(Bytes::from(vec![i; 1100])));
+        }
+
+        let stats = sim.client.stats();
+        let sends = sim.transmissions(sim.first_data_seq());
+        assert_eq!(sends.len(), 2);
+        assert_eq!(stats.sender.duplicate_acks, messages as u64 - 1);
+        if fast {
+            // 第三个重复确认一到立即重传，不等 RTO
+            assert_eq!(sends[1].at, sends[0].at);
+            assert_eq!((stats.fast_retransmits, stats.timeouts), (1, 0));
+        } else {
+            // 只有两个重复确认：等到 RTO 才重传
+            assert_eq!(sends[1].at - sends[0].at, rto);
+            assert_eq!((stats.fast_retransmits, stats.timeouts), (0, 1));
+        }
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_keepalive_declares_peer_dead_after_exact_probes() {
+    let interval = Duration::from_secs(1);
+    let client = LinkConfig { keepalive_interval: interval, keepalive_failures: 3, ..LinkConfig::default() };
+    let server = LinkConfig { keepalive_interval: Duration::from_secs(3600), ..LinkConfig::default() };
+    let sim = Sim::new(client, server).await;
+
+    // 从此客户端再也收不到任何段
+    sim.drop_when(|sent| !sent.to_server);
+    let heard = sim.sent(|sent| !sent.to_server && sent.delivered).last().expect("handshake reached the client").at;
+    let error = sim.client.recv().await.unwrap_err();
+    let dead_at = Instant::now();
+
+    assert_eq!(error, LinkError::KeepaliveTimeout { unanswered: 3 });
+    let pings: Vec<_> = sim.sent(|sent| sent.to_server && sent.segment.segment_type() == SegmentType::Ping).iter().map(|sent| sent.at - heard).collect();
+    assert_eq!(pings, vec![interval, interval * 2, interval * 3]);
+    assert_eq!(dead_at - heard, interval * 4);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_retransmission_backoff_doubles() {
+    let config = LinkConfig { max_retries: 4, ..LinkConfig::default() };
+    let sim = Sim::new(config, LinkConfig::default()).await;
+    let rto = sim.client.stats().rto;
+    sim.drop_when(|sent| sent.to_server && sent.is_data());
+
+    sim.client.send(Bytes::from_static(b"never arrives")).await.unwrap();
+    let error = sim.client.recv().await.unwrap_err();
+    let failed_at = Instant::now();
+
+    assert!(matches!(error, LinkError::PeerUnreachable { attempts: 5, .. }), "{:?}", error);
+    let sends = sim.transmissions(sim.first_data_seq());
+    let gaps: Vec<_> = sends.windows(2).map(|pair| pair[1].at - pair[0].at).collect();
+    assert_eq!(gaps, vec![rto, rto * 2, rto * 4, rto * 8]);
+    // 第四次重传之后再等一个翻倍的间隔，判定对端不可达
+    assert_eq!(failed_at - sends[4].at, rto * 16);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_zero_window_probe_recovers_lost_window_update() {
+    let client = LinkConfig { nodelay: true, ..LinkConfig::default() };
+    let server = LinkConfig { recv_window: 2, ..LinkConfig::default() };
+    let sim = Sim::new(client, server).await;
+    // 服务端通告零窗口之后，丢弃第一个打开窗口的确认
+    let (mut closed, mut dropped) = (false, false);
+    sim.drop_when(move |sent| {
+        let ack = !sent.to_server && sent.segment.segment_type() == SegmentType::Ack;
+        closed |= ack && sent.segment.window() == 0;
+        ack && closed && sent.segment.window() > 0 && !std::mem::replace(&mut dropped, true)
+    });
+
+    // 前两条填满服务端的接收窗口，后两条在客户端得知零窗口后才写入，停在发送队列中
+    for i in 0..4u8 {
+        sim.client.send(Bytes::from(vec![i; 100])).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    for i in 0..4u8 {
+        assert_eq!(sim.server.recv().await.unwrap(), Some(Bytes::from(vec![i; 100])));
+    }
+
+    let updates = sim.sent(|sent| !sent.to_server && sent.segment.segment_type() == SegmentType::Ack && sent.segment.window() > 0 && !sent.delivered);
+    assert_eq!(updates.len(), 1);
+    let probes = sim.sent(|sent| sent.to_server && sent.segment.segment_type() == SegmentType::Data && sent.segment.data().is_empty());
+    // 零窗口期间探测一直在进行，更新丢失之后的下一个探测引出了打开的窗口
+    assert!(probes.iter().any(|probe| probe.at > updates[0].at), "no probe after the lost update: {:?}", probes);
+    // 探测不是丢包：没有超时重传，也没有快速重传
+    let stats = sim.client.stats();
+    assert_eq!((stats.timeouts, stats.fast_retransmits, stats.sender.segments_retransmitted), (0, 0, 0));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_ack_frequency_thins_acks_for_a_burst() {
+    // 一口气发出 64 个段；服务端在读取之前发出的确认数
+    async fn acks_for_burst(every: usize) -> usize {
+        // 确认频率在握手时协商，取两端中较小的一个
+        let client = LinkConfig { congestion: CongestionAlgorithm::NoCc { window: 64 }, nodelay: true, pacing: false, ack_every_n_segments: every, ..LinkConfig::default() };
+        let server = LinkConfig { ack_every_n_segments: every, recv_window: 128, ..LinkConfig::default() };
+        let sim = Sim::new(client, server).await;
+        let before = sim.sent(|sent| !sent.to_server).len();
+        for i in 0..64u8 {
+            sim.client.send(Bytes::from(vec![i; 100])).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(sim.client.stats().in_flight, 0);
+        let acks = sim.sent(|sent| !sent.to_server && sent.segment.segment_type() == SegmentType::Ack).len() - before;
+        for i in 0..64u8 {
+            assert_eq!(sim.server.recv().await.unwrap(), Some(Bytes::from(vec![i; 100])));
+        }
+        acks
+    }
+    let (thin, default) = (acks_for_burst(8).await, acks_for_burst(2).await);
+    // 每 8 个段一个确认（同一批到达的段产生的确认可能合并成一个）；默认每 2 个段一个
+    assert!((6..=9).contains(&thin), "{} acks for 64 segments", thin);
+    assert!(default >= 3 * thin, "{} acks by default, {} with every 8", default, thin);
+}
@@ -0,0 +1,71 @@
+//! 套接字选项集成测试：请求的缓冲区大小与 DSCP 在绑定时生效，经 `Server::socket_info` 读回；
+//! 内核缩小缓冲区时绑定照常成功，设置了 DSCP 的客户端与服务器照常通信
+#![cfg(feature = "tokio")]
+
+use bytes::Bytes;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::server::{EchoHandler, Server};
+use link_rs::transport::MemoryNetwork;
+use std::time::Duration;
+use tokio::time::timeout;
+
+// 支持读写 IP_TOS/IPV6_TCLASS 的平台
+const DSCP_SUPPORTED: bool = cfg!(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "freebsd"));
+
+fn tuned() -> LinkConfig {
+    LinkConfig { so_rcvbuf: Some(32 * 1024), so_sndbuf: Some(16 * 1024), dscp: Some(46), ..LinkConfig::default() }
+}
+
+#[tokio::test]
+async fn test_effective_options_are_reported() {
+    let server = Server::bind("127.0.0.1:0", tuned(), EchoHandler).await.unwrap();
+    let info = server.socket_info().unwrap();
+    // Linux 报告加倍后的值
+    let recv_buffer = info.recv_buffer.unwrap();
+    assert!((32 * 1024..=64 * 1024).contains(&recv_buffer), "{}", recv_buffer);
+    let send_buffer = info.send_buffer.unwrap();
+    assert!((16 * 1024..=32 * 1024).contains(&send_buffer), "{}", send_buffer);
+    if DSCP_SUPPORTED {
+        assert_eq!(info.dscp, Some(46));
+    }
+
+    // 没有设置时 DSCP 为 0，缓冲区为系统默认值
+    let plain = Server::bind("127.0.0.1:0", LinkConfig::default(), EchoHandler).await.unwrap();
+    if DSCP_SUPPORTED {
+        assert_eq!(plain.socket_info().unwrap().dscp, Some(0));
+    }
+
+    // 调用方提供的传输没有套接字选项
+    let network = MemoryNetwork::new();
+    let memory = Server::with_transport(network.bind("10.0.0.1:7000".parse().unwrap()).unwrap(), tuned(), EchoHandler).unwrap();
+    assert_eq!(memory.socket_info(), None);
+}
+
+#[tokio::test]
+async fn test_clamped_buffer_is_not_an_error() {
+    let config = LinkConfig { so_rcvbuf: Some(1 << 30), ..LinkConfig::default() };
+    let server = Server::bind("127.0.0.1:0", config, EchoHandler).await.unwrap();
+    assert!(server.socket_info().unwrap().recv_buffer.is_some());
+}
+
+#[tokio::test]
+async fn test_marked_connection_exchanges_data() {
+    let server = Server::bind("127.0.0.1:0", tuned(), EchoHandler).await.unwrap();
+    let addr = server.local_addr().unwrap();
+    let running = tokio::spawn(async move { server.run().await });
+
+    let connection = timeout(Duration::from_secs(5), Connection::connect_with(addr, tuned())).await.unwrap().unwrap();
+    connection.send(Bytes::from_static(b"marked")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), connection.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"marked")));
+    connection.close().await.unwrap();
+    running.abort();
+
+    // IPv6 套接字以 IPV6_TCLASS 标记
+    if std::net::UdpSocket::bind("[::1]:0").is_ok() {
+        let server = Server::bind("[::1]:0", tuned(), EchoHandler).await.unwrap();
+        if DSCP_SUPPORTED {
+            assert_eq!(server.socket_info().unwrap().dscp, Some(46));
+        }
+    }
+}
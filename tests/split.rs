@@ -0,0 +1,115 @@
+//! 读写两半集成测试：内存网络上的连接拆成 `RecvHalf` 与 `SendHalf` 后由两个任务同时收发；
+//! 丢弃 `SendHalf` 时对端收到 FIN 而读方向照常交付，丢弃 `RecvHalf` 不影响写方向；`reunite` 只接受同一个连接的两半
+
+mod common;
+
+use bytes::Bytes;
+use common::{accept, addr, connect, server_addr};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use link_rs::state::ConnState;
+use link_rs::transport::MemoryNetwork;
+use std::time::Duration;
+use tokio::time::timeout;
+
+const MESSAGES: usize = 200;
+
+async fn pair(network: &MemoryNetwork, listener: &Listener, port: u16) -> (Connection, Connection) {
+    let client = connect(network, addr(2, port), LinkConfig::default()).await.unwrap();
+    let server = accept(listener).await;
+    (client, server)
+}
+
+fn listen(network: &MemoryNetwork) -> Listener {
+    Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap()
+}
+
+#[tokio::test]
+async fn test_halves_are_used_from_two_tasks() {
+    let network = MemoryNetwork::new();
+    let listener = listen(&network);
+    let (client, server) = pair(&network, &listener, 5000).await;
+    // 服务端原样发回，客户端的两半同时发送与接收
+    let echo = tokio::spawn(async move {
+        while let Some(message) = server.recv().await.unwrap() {
+            server.send(message).await.unwrap();
+        }
+        server.close().await.unwrap();
+    });
+
+    let (recv, send) = client.split();
+    let writer = tokio::spawn(async move {
+        for i in 0..MESSAGES {
+            send.send(Bytes::from(format!("message {}", i))).await.unwrap();
+        }
+        send
+    });
+    let reader = tokio::spawn(async move {
+        for i in 0..MESSAGES {
+            assert_eq!(recv.recv().await.unwrap(), Some(Bytes::from(format!("message {}", i))));
+        }
+        recv
+    });
+    let send = timeout(Duration::from_secs(10), writer).await.unwrap().unwrap();
+    let recv = timeout(Duration::from_secs(10), reader).await.unwrap().unwrap();
+
+    let client = recv.reunite(send).unwrap();
+    assert_eq!(client.state(), ConnState::Established);
+    timeout(Duration::from_secs(10), client.close()).await.unwrap().unwrap();
+    timeout(Duration::from_secs(10), echo).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_dropping_the_send_half_sends_fin() {
+    let network = MemoryNetwork::new();
+    let listener = listen(&network);
+    let (client, server) = pair(&network, &listener, 5000).await;
+    let (recv, send) = client.split();
+
+    send.send(Bytes::from_static(b"last")).await.unwrap();
+    drop(send);
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"last")));
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), None);
+
+    // 读方向不受影响
+    server.send(Bytes::from_static(b"reply")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), recv.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"reply")));
+}
+
+#[tokio::test]
+async fn test_dropping_the_recv_half_keeps_writing() {
+    let network = MemoryNetwork::new();
+    let listener = listen(&network);
+    let (client, server) = pair(&network, &listener, 5000).await;
+    let (recv, send) = client.split();
+
+    drop(recv);
+    for i in 0..10 {
+        send.send(Bytes::from(format!("message {}", i))).await.unwrap();
+    }
+    for i in 0..10 {
+        assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), Some(Bytes::from(format!("message {}", i))));
+    }
+    send.shutdown_write().await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_reunite_rejects_halves_of_different_connections() {
+    let network = MemoryNetwork::new();
+    let listener = listen(&network);
+    let (first, _first_server) = pair(&network, &listener, 5000).await;
+    let (second, second_server) = pair(&network, &listener, 5001).await;
+    let (first_recv, first_send) = first.split();
+    let (second_recv, second_send) = second.split();
+
+    let error = first_recv.reunite(second_send).unwrap_err();
+    assert_eq!(error.to_string(), "tried to reunite halves of different connections");
+    // 交还的两半仍可使用，各自与原来的另一半合回连接
+    let (first_recv, second_send) = (error.0, error.1);
+    second_send.send(Bytes::from_static(b"still usable")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), second_server.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"still usable")));
+    assert!(first_recv.reunite(first_send).is_ok());
+    assert!(second_send.reunite(second_recv).is_ok());
+}
@@ -0,0 +1,52 @@
+//! 远程统计查询集成测试：`query_peer_stats` 以 StatsRequest 取回对端一侧的连接统计；
+//! 对端每条连接每秒至多回应一次，间隔内的查询超时
+
+mod common;
+
+use bytes::Bytes;
+use common::pair;
+use link_rs::config::LinkConfig;
+use link_rs::connection::STATS_QUERY_TIMEOUT;
+use link_rs::endpoint::STATS_REPLY_INTERVAL;
+use link_rs::error::LinkError;
+use link_rs::transport::MemoryNetwork;
+use std::time::Duration;
+use tokio::time::{Instant, timeout};
+
+#[tokio::test]
+async fn test_query_returns_the_peers_view() {
+    let (_listener, connection, accepted) = pair(&MemoryNetwork::new(), LinkConfig::default()).await;
+    for i in 0..10 {
+        connection.send(Bytes::from(vec![i; 100])).await.unwrap();
+        assert_eq!(timeout(Duration::from_secs(5), accepted.recv()).await.unwrap().unwrap().map(|message| message.len()), Some(100));
+    }
+    accepted.send(Bytes::from_static(b"reply")).await.unwrap();
+    timeout(Duration::from_secs(5), connection.recv()).await.unwrap().unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // 对端收到了 1000 字节、发出了 5 字节，连接建立不久，RTT 已有样本
+    let stats = connection.query_peer_stats().await.unwrap();
+    assert_eq!((stats.bytes_received, stats.bytes_sent, stats.segments_retransmitted), (1000, 5, 0));
+    assert!(stats.cwnd > 0);
+    assert!(stats.srtt.is_some_and(|srtt| srtt < Duration::from_secs(1)), "{:?}", stats.srtt);
+    assert!(stats.uptime > Duration::ZERO && stats.uptime < Duration::from_secs(5), "{:?}", stats.uptime);
+
+    // 反方向同样可以查询
+    let stats = accepted.query_peer_stats().await.unwrap();
+    assert_eq!((stats.bytes_received, stats.bytes_sent), (5, 1000));
+}
+
+#[tokio::test]
+async fn test_queries_within_the_interval_time_out() {
+    let (_listener, connection, _accepted) = pair(&MemoryNetwork::new(), LinkConfig::default()).await;
+    let first = Instant::now();
+    connection.query_peer_stats().await.unwrap();
+
+    let started = Instant::now();
+    assert_eq!(connection.query_peer_stats().await, Err(LinkError::StatsTimedOut));
+    assert!(started.elapsed() >= STATS_QUERY_TIMEOUT);
+
+    // 间隔过后对端再次回应
+    tokio::time::sleep_until(first + STATS_REPLY_INTERVAL).await;
+    connection.query_peer_stats().await.unwrap();
+}
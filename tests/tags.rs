@@ -0,0 +1,167 @@
+//! 消息标签集成测试：带标签与不带标签的消息交错发送，分片消息与丢失后重传的消息都连同各自的标签交付；
+//! 两个流上交错的消息只拿到自己的标签；把 tag 选项当作未识别类型的旧版本对端照常收到消息，只是没有标签
+
+mod common;
+
+use bytes::{Bytes, BytesMut};
+use common::{accept, client_addr, connect, contains, server_addr};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use link_rs::options::{Options, SegmentOption};
+use link_rs::segment::Segment;
+use link_rs::sender::SendOptions;
+use link_rs::transport::{BoxFuture, MemoryNetwork, MemoryTransport, Transport};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::time::timeout;
+
+// 旧版本的选项表里没有的类型
+const UNKNOWN: u8 = 99;
+
+// 监听器随返回值一起存活
+async fn pair(network: &MemoryNetwork, server: impl Transport, config: LinkConfig) -> (Listener, Connection, Connection) {
+    let listener = Listener::with_transport(server, config.clone()).unwrap();
+    let client = connect(network, client_addr(), config).await.unwrap();
+    let server = accept(&listener).await;
+    (listener, client, server)
+}
+
+#[tokio::test]
+async fn test_tags_survive_fragmentation_and_retransmission() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { nodelay: true, ..LinkConfig::default() };
+    let (_listener, client, server) = pair(&network, network.bind(server_addr()).unwrap(), config).await;
+
+    // 第一次发出的带标签的消息丢失，重传照常携带标签
+    const LOST: &[u8] = b"tagged message that is lost once";
+    let seen = Arc::new(AtomicUsize::new(0));
+    let counted = seen.clone();
+    network.set_filter(move |datagram, _, _| !contains(datagram, LOST) || counted.fetch_add(1, Ordering::Relaxed) > 0);
+
+    let large: Bytes = (0..10_000).map(|i| (i % 251) as u8).collect();
+    let sent = [
+        (Some(1), Bytes::from_static(LOST)),
+        (None, Bytes::from_static(b"untagged")),
+        (Some(u32::MAX), large.clone()),
+        (None, large),
+        (Some(0), Bytes::new()),
+        (Some(3), Bytes::from_static(b"last")),
+    ];
+    for (tag, data) in &sent {
+        let options = SendOptions { tag: *tag, ..SendOptions::default() };
+        client.send_msg_with(data.clone(), options).await.unwrap();
+    }
+    let mut received = Vec::new();
+    for _ in 0..sent.len() {
+        received.push(timeout(Duration::from_secs(5), server.recv_msg_tagged()).await.unwrap().unwrap());
+    }
+    assert!(received == sent, "{:?}", received.iter().map(|(tag, data)| (*tag, data.len())).collect::<Vec<_>>());
+    assert!(seen.load(Ordering::Relaxed) >= 2, "lost message was not retransmitted");
+
+    // 无序的带标签消息
+    client.send_with(Bytes::from_static(b"unordered"), SendOptions { ordered: false, ..SendOptions::tag(4) }).await.unwrap();
+    assert_eq!(server.recv_msg_tagged().await.unwrap(), (Some(4), Bytes::from_static(b"unordered")));
+
+    network.clear_filter();
+    let closing = tokio::spawn(async move { server.close().await });
+    client.close().await.unwrap();
+    closing.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_tags_stay_on_their_streams() {
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair(&network, network.bind(server_addr()).unwrap(), LinkConfig::default()).await;
+    let first = client.open_stream().unwrap();
+    let second = client.open_stream().unwrap();
+
+    // 两个流交错发送，各自的标签与另一个流错开
+    for i in 0..20u32 {
+        first.send_with(Bytes::from(format!("first {}", i)), SendOptions::tag(i)).await.unwrap();
+        let options = if i % 2 == 0 { SendOptions::tag(1000 + i) } else { SendOptions::default() };
+        second.send_with(Bytes::from(format!("second {}", i)), options).await.unwrap();
+    }
+    let accepted = [server.accept_stream().await.unwrap(), server.accept_stream().await.unwrap()];
+    for stream in &accepted {
+        for i in 0..20u32 {
+            let (tag, data) = timeout(Duration::from_secs(5), stream.recv_msg_tagged()).await.unwrap().unwrap();
+            match stream.id() == first.id() {
+                true => assert_eq!((tag, data), (Some(i), Bytes::from(format!("first {}", i)))),
+                false => assert_eq!((tag, data), ((i % 2 == 0).then_some(1000 + i), Bytes::from(format!("second {}", i)))),
+            }
+        }
+    }
+}
+
+/// 以旧版本的选项表解读收到的段：tag 选项的类型改成未识别的类型，其余原样
+#[derive(Debug)]
+struct OldOptionTable {
+    inner: MemoryTransport,
+    rewritten: Arc<AtomicUsize>,
+}
+
+impl OldOptionTable {
+    fn downgrade(&self, datagram: &[u8]) -> Option<BytesMut> {
+        let mut rest = Bytes::copy_from_slice(datagram);
+        let mut out = BytesMut::new();
+        while let Some(mut segment) = Segment::decode_bytes(&mut rest).ok()? {
+            let mut raw = segment.options().as_bytes().to_vec();
+            let mut at = 0;
+            while at + 1 < raw.len() {
+                if raw[at] == SegmentOption::Tag(0).kind() {
+                    raw[at] = UNKNOWN;
+                    self.rewritten.fetch_add(1, Ordering::Relaxed);
+                }
+                at += 2 + usize::from(raw[at + 1]);
+            }
+            segment.set_options(Options::decode(&raw).ok()?);
+            segment.encode_into(&mut out).ok()?;
+        }
+        Some(out)
+    }
+}
+
+impl Transport for OldOptionTable {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn send_to<'a>(&'a self, buf: &'a [u8], target: SocketAddr) -> BoxFuture<'a, io::Result<usize>> {
+        self.inner.send_to(buf, target)
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> BoxFuture<'a, io::Result<(usize, SocketAddr)>> {
+        Box::pin(async move {
+            let (len, from) = self.inner.recv_from(buf).await?;
+            if let Some(downgraded) = self.downgrade(&buf[..len]).filter(|downgraded| downgraded.len() == len) {
+                buf[..len].copy_from_slice(&downgraded);
+            }
+            Ok((len, from))
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_peer_with_an_older_option_table_ignores_tags() {
+    let network = MemoryNetwork::new();
+    let rewritten = Arc::new(AtomicUsize::new(0));
+    let old = OldOptionTable { inner: network.bind(server_addr()).unwrap(), rewritten: rewritten.clone() };
+    let (_listener, client, server) = pair(&network, old, LinkConfig::default()).await;
+
+    let large: Bytes = (0..5000).map(|i| (i % 13) as u8).collect();
+    client.send_with(Bytes::from_static(b"tagged"), SendOptions::tag(42)).await.unwrap();
+    client.send(Bytes::from_static(b"plain")).await.unwrap();
+    client.send_msg_with(large.clone(), SendOptions::tag(43)).await.unwrap();
+    // 消息照常交付，只是没有标签
+    for expected in [Bytes::from_static(b"tagged"), Bytes::from_static(b"plain"), large] {
+        assert_eq!(timeout(Duration::from_secs(5), server.recv_msg_tagged()).await.unwrap().unwrap(), (None, expected));
+    }
+    assert!(rewritten.load(Ordering::Relaxed) >= 2);
+    let closing = tokio::spawn(async move { server.close().await });
+    client.close().await.unwrap();
+    closing.await.unwrap().unwrap();
+}
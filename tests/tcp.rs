@@ -0,0 +1,219 @@
+//! TCP 回退集成测试：消息的用例在本机的 TCP 上重跑——分片消息（`messages.rs`）、有序与无序消息交错（`unordered.rs`）、
+//! 标签（`tags.rs`）与带 TTL 的消息（`ttl.rs`）；那些用例靠丢弃数据报制造空洞，TCP 上没有丢包，这里只检查消息语义不变。
+//! `TransportKind::Auto` 在 UDP 握手超时后改经 TCP 连接，服务端关闭流后客户端连接以 `NotConnected` 结束
+#![cfg(feature = "tokio")]
+
+use bytes::Bytes;
+use link_rs::config::LinkConfig;
+use link_rs::connection::{ConnectOptions, Connection, TransportKind};
+use link_rs::error::LinkError;
+use link_rs::listener::Listener;
+use link_rs::options;
+use link_rs::segment::Segment;
+use link_rs::sender::SendOptions;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::time::timeout;
+
+fn message(len: usize, tag: u8) -> Bytes {
+    (0..len).map(|i| (i * 31 % 251) as u8 ^ tag).collect()
+}
+
+fn tcp() -> ConnectOptions {
+    ConnectOptions { transport: TransportKind::Tcp, ..ConnectOptions::default() }
+}
+
+async fn listen(config: LinkConfig) -> (Listener, SocketAddr) {
+    let listener = Listener::bind_tcp("127.0.0.1:0", config).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    (listener, addr)
+}
+
+// 经 TCP 建立一对连接，监听器随返回值一起存活
+async fn pair(config: LinkConfig) -> (Listener, Connection, Connection) {
+    let (listener, addr) = listen(config.clone()).await;
+    let client = Connection::connect_with_options(addr, config, tcp()).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    (listener, client, server)
+}
+
+#[tokio::test]
+async fn test_message_boundaries_over_tcp() {
+    let (listener, addr) = listen(LinkConfig::default()).await;
+    let config = LinkConfig { linger: Duration::from_secs(1), ..LinkConfig::default() };
+    let client = Connection::connect_with_options(addr, config, tcp()).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+
+    let fragment = client.mss() - Segment::FIXED_HEADER_LEN - options::MAX_LEN;
+    let lens = [0, fragment, fragment + 1, 1, 3 * 1024 * 1024, 0, 2 * fragment];
+    let messages: Vec<Bytes> = lens.iter().enumerate().map(|(i, &len)| message(len, i as u8)).collect();
+
+    let sending = tokio::spawn({
+        let messages = messages.clone();
+        async move {
+            for message in messages {
+                client.send_msg(message).await.unwrap();
+            }
+            let stats = client.stats();
+            let _ = client.close().await;
+            stats
+        }
+    });
+    for (i, expected) in messages.iter().enumerate() {
+        let received = timeout(Duration::from_secs(60), server.recv_msg()).await.unwrap().unwrap();
+        assert!(received == expected, "message {} of {} bytes arrived as {} bytes", i, expected.len(), received.len());
+    }
+    assert_eq!(timeout(Duration::from_secs(10), server.recv_msg()).await.unwrap(), Err(LinkError::Closed));
+    let stats = timeout(Duration::from_secs(15), sending).await.unwrap().unwrap();
+    // TCP 不丢包，直通模式下不应有重传
+    assert_eq!((stats.timeouts, stats.fast_retransmits), (0, 0));
+}
+
+#[tokio::test]
+async fn test_oversized_message_fails_at_send_over_tcp() {
+    let (listener, addr) = listen(LinkConfig::default()).await;
+    let config = LinkConfig { max_message: 10_000, ..LinkConfig::default() };
+    let client = Connection::connect_with_options(addr, config, tcp()).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+
+    assert_eq!(client.send_msg(message(10_001, 0)).await, Err(LinkError::MessageTooLarge { len: 10_001, max: 10_000 }));
+    client.send_msg(message(10_000, 0)).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv_msg()).await.unwrap().unwrap(), message(10_000, 0));
+}
+
+#[tokio::test]
+async fn test_unordered_messages_over_tcp() {
+    let (_listener, client, server) = pair(LinkConfig { nodelay: true, ..LinkConfig::default() }).await;
+
+    // 没有空洞可越过：无序消息与有序消息一样按发送顺序到达
+    let unordered = SendOptions { ordered: false, ..SendOptions::default() };
+    let ordered = SendOptions::default();
+    let sent = [(&b"ordered 0"[..], ordered), (b"ordered 1", ordered), (b"unordered 1", unordered), (b"unordered 2", unordered), (b"ordered 2", ordered)];
+    for (message, options) in sent {
+        client.send_with(Bytes::from_static(message), options).await.unwrap();
+    }
+    for (message, _) in sent {
+        assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(message)));
+    }
+
+    let closing = tokio::spawn(async move { server.close().await });
+    client.close().await.unwrap();
+    closing.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_tags_over_tcp() {
+    let (_listener, client, server) = pair(LinkConfig::default()).await;
+
+    // 分片消息连同标签交付
+    let sent = [
+        (Some(1), Bytes::from_static(b"tagged")),
+        (None, Bytes::from_static(b"untagged")),
+        (Some(u32::MAX), message(10_000, 1)),
+        (None, message(10_000, 2)),
+        (Some(0), Bytes::new()),
+    ];
+    for (tag, data) in &sent {
+        client.send_msg_with(data.clone(), SendOptions { tag: *tag, ..SendOptions::default() }).await.unwrap();
+    }
+    for expected in &sent {
+        assert_eq!(&timeout(Duration::from_secs(5), server.recv_msg_tagged()).await.unwrap().unwrap(), expected);
+    }
+
+    // 两个流交错发送，各自只拿到自己的标签
+    let first = client.open_stream().unwrap();
+    let second = client.open_stream().unwrap();
+    for i in 0..10u32 {
+        first.send_with(Bytes::from(format!("first {}", i)), SendOptions::tag(i)).await.unwrap();
+        second.send_with(Bytes::from(format!("second {}", i)), SendOptions::tag(1000 + i)).await.unwrap();
+    }
+    let accepted = [server.accept_stream().await.unwrap(), server.accept_stream().await.unwrap()];
+    for stream in &accepted {
+        let (name, base) = if stream.id() == first.id() { ("first", 0) } else { ("second", 1000) };
+        for i in 0..10u32 {
+            let received = timeout(Duration::from_secs(5), stream.recv_msg_tagged()).await.unwrap().unwrap();
+            assert_eq!(received, (Some(base + i), Bytes::from(format!("{} {}", name, i))));
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_ttl_messages_arrive_over_tcp() {
+    const TTL: Duration = Duration::from_millis(500);
+    let (_listener, client, server) = pair(LinkConfig::default()).await;
+
+    // TCP 送达每个段，确认及时返回：设置了 TTL 的消息（包括分片的）一条也不会过期
+    for i in 0..5 {
+        client.send_with(Bytes::from(format!("sample {}", i)), SendOptions::ttl(TTL)).await.unwrap();
+    }
+    client.send_msg_with(message(4000, 3), SendOptions::ttl(TTL)).await.unwrap();
+    for i in 0..5 {
+        assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), Some(Bytes::from(format!("sample {}", i))));
+    }
+    assert_eq!(timeout(Duration::from_secs(5), server.recv_msg()).await.unwrap().unwrap(), message(4000, 3));
+
+    // 统计由驱动任务发布：往返一次，让两端的驱动任务再跑一轮
+    server.send(Bytes::from_static(b"ok")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), client.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"ok")));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!((client.stats().sender.expired, server.stats().receiver.skipped), (0, 0));
+}
+
+#[tokio::test]
+async fn test_many_clients_share_one_tcp_listener() {
+    let (listener, addr) = listen(LinkConfig::default()).await;
+    let mut clients = Vec::new();
+    for i in 0..4u8 {
+        let client = Connection::connect_with_options(addr, LinkConfig::default(), tcp()).await.unwrap();
+        client.send(Bytes::from(vec![i; 100])).await.unwrap();
+        clients.push(client);
+    }
+    let mut tags = Vec::new();
+    for _ in 0..4 {
+        let (server, peer) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+        let received = timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap();
+        let client = clients.iter().find(|client| client.local_addr().unwrap() == peer).expect("accepted an unknown peer");
+        server.send(received.clone()).await.unwrap();
+        assert_eq!(timeout(Duration::from_secs(5), client.recv()).await.unwrap().unwrap(), Some(received.clone()));
+        tags.push(received[0]);
+    }
+    tags.sort();
+    assert_eq!(tags, [0, 1, 2, 3]);
+}
+
+#[tokio::test]
+async fn test_auto_falls_back_to_tcp() {
+    // 只在 TCP 上监听：UDP 的 SYN 无人回应
+    let (listener, addr) = listen(LinkConfig::default()).await;
+    let options = ConnectOptions {
+        transport: TransportKind::Auto,
+        syn_retry_interval_initial: Some(Duration::from_millis(50)),
+        syn_max_retries: Some(1),
+        ..ConnectOptions::default()
+    };
+    let client = timeout(Duration::from_secs(5), Connection::connect_with_options(addr, LinkConfig::default(), options)).await.unwrap().unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    client.send(Bytes::from_static(b"fallback")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap(), Some(Bytes::from_static(b"fallback")));
+}
+
+#[tokio::test]
+async fn test_closed_stream_fails_the_connection() {
+    let (listener, addr) = listen(LinkConfig::default()).await;
+    let client = Connection::connect_with_options(addr, LinkConfig::default(), tcp()).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    drop(server);
+    drop(listener);
+
+    // 流被关闭后不等空闲超时，连接立即以传输错误结束
+    let result = timeout(Duration::from_secs(5), client.recv()).await.unwrap();
+    assert!(matches!(result, Err(LinkError::Io { kind: io::ErrorKind::NotConnected, .. })), "{:?}", result);
+}
+
+#[tokio::test]
+async fn test_tcp_connect_to_dead_port_is_an_io_error() {
+    let dead = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let result = Connection::connect_with_options(dead, LinkConfig::default(), tcp()).await;
+    assert!(matches!(result, Err(LinkError::Io { kind: io::ErrorKind::ConnectionRefused, .. })), "{:?}", result.map(|_| ()));
+}
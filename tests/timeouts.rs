@@ -0,0 +1,93 @@
+//! 限时收发集成测试（暂停的时钟）：`recv_timeout`/`send_timeout` 恰好在期限到达时返回 `Timeout`，
+//! 超时的发送没有入队，超时或被丢弃的接收没有取走消息——之后的读取按序得到每条消息，不丢也不重复
+mod common;
+
+use bytes::Bytes;
+use common::{pair_with, server_addr};
+use link_rs::config::LinkConfig;
+use link_rs::error::LinkError;
+use link_rs::transport::MemoryNetwork;
+use std::time::Duration;
+use tokio::time::{Instant, sleep, timeout};
+
+#[tokio::test(start_paused = true)]
+async fn test_recv_timeout_fires_at_the_deadline() {
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair_with(&network, LinkConfig::default(), LinkConfig { nodelay: true, ..LinkConfig::default() }).await;
+
+    let start = Instant::now();
+    assert_eq!(server.recv_timeout(Duration::from_millis(300)).await, Err(LinkError::Timeout));
+    assert_eq!(start.elapsed(), Duration::from_millis(300));
+    assert_eq!(server.recv_deadline(start + Duration::from_millis(500)).await, Err(LinkError::Timeout));
+    assert_eq!(start.elapsed(), Duration::from_millis(500));
+
+    // 连接照常工作；期限已过时仍交付已经到达的消息
+    client.send(Bytes::from_static(b"late")).await.unwrap();
+    sleep(Duration::from_millis(50)).await;
+    assert_eq!(server.recv_deadline(start).await.unwrap().unwrap(), "late");
+    client.send(Bytes::from_static(b"soon")).await.unwrap();
+    assert_eq!(server.recv_timeout(Duration::from_secs(1)).await.unwrap().unwrap(), "soon");
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_timed_out_send_leaves_no_trace() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { nodelay: true, send_buffer: 4096, ..LinkConfig::default() };
+    let (_listener, client, server) = pair_with(&network, LinkConfig::default(), config).await;
+
+    // 对端的确认全部丢失：队列填满之后的发送等待空间，到期时返回 `Timeout` 而队列不变
+    network.set_filter(|_, from, _| from != server_addr());
+    let message = Bytes::from(vec![1u8; 1000]);
+    let mut sent = 0;
+    while client.try_send(message.clone()).is_ok() {
+        sent += 1;
+    }
+    let queued = client.send_queue_len();
+    let start = Instant::now();
+    assert_eq!(client.send_timeout(message.clone(), Duration::from_millis(200)).await, Err(LinkError::Timeout));
+    assert_eq!(start.elapsed(), Duration::from_millis(200));
+    assert_eq!(client.send_queue_len(), queued);
+
+    // 恢复后重试的发送入队，对端收到的消息中没有超时的那一条
+    network.clear_filter();
+    client.send_timeout(Bytes::from_static(b"retried"), Duration::from_secs(10)).await.unwrap();
+    for _ in 0..sent {
+        assert_eq!(server.recv().await.unwrap().unwrap(), message);
+    }
+    assert_eq!(server.recv().await.unwrap().unwrap(), "retried");
+    assert_eq!(server.recv_timeout(Duration::from_secs(1)).await, Err(LinkError::Timeout));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_cancelled_recv_loses_nothing() {
+    const MESSAGES: u32 = 200;
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair_with(&network, LinkConfig::default(), LinkConfig { nodelay: true, ..LinkConfig::default() }).await;
+
+    // 被丢弃的 `recv` 之后发出的消息由下一次读取得到
+    assert!(timeout(Duration::from_millis(10), server.recv()).await.is_err());
+    client.send(Bytes::from_static(b"first")).await.unwrap();
+    assert_eq!(server.recv().await.unwrap().unwrap(), "first");
+
+    // 发送间隔与读取期限交错，许多读取在消息到达的同一时刻超时
+    let sender = tokio::spawn(async move {
+        for i in 0..MESSAGES {
+            client.send(Bytes::copy_from_slice(&i.to_be_bytes())).await.unwrap();
+            sleep(Duration::from_millis(u64::from(i % 7))).await;
+        }
+        client
+    });
+    let mut received = Vec::new();
+    let mut timeouts = 0;
+    while received.len() < MESSAGES as usize {
+        match server.recv_timeout(Duration::from_millis(3)).await {
+            Ok(Some(message)) => received.push(u32::from_be_bytes(message[..].try_into().unwrap())),
+            Err(LinkError::Timeout) => timeouts += 1,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+    assert_eq!(received, (0..MESSAGES).collect::<Vec<_>>());
+    assert!(timeouts > 0);
+    let _client = sender.await.unwrap();
+    assert_eq!(server.recv_timeout(Duration::from_millis(100)).await, Err(LinkError::Timeout));
+}
@@ -0,0 +1,115 @@
+//! 连接 span 集成测试：两条并发连接的事件分别落在各自的 `conn` span 中，带不同的 conn_id；
+//! 监听器分发任务的逐段事件以自身的 conn_id 字段区分连接
+
+mod common;
+
+use bytes::Bytes;
+use common::server_addr;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use link_rs::transport::MemoryNetwork;
+use std::collections::BTreeSet;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::timeout;
+use tracing_subscriber::fmt::MakeWriter;
+
+// 收集格式化后的日志输出
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Output {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap().lines().map(str::to_string).collect()
+    }
+}
+
+impl io::Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Output {
+    type Writer = Output;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+// `key=` 之后的数字
+fn field(text: &str, key: &str) -> Option<u32> {
+    let start = text.find(&format!("{}=", key))? + key.len() + 1;
+    text[start..].split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()
+}
+
+// 行首的 span 上下文中 `conn` span 记录的 conn_id；服务端连接的 span 在监听器的 span 之下
+fn span_conn_id(line: &str) -> Option<u32> {
+    let start = line.find(" conn{").or_else(|| line.find(":conn{"))? + 6;
+    let end = start + line[start..].find('}')?;
+    field(&line[start..end], "conn_id")
+}
+
+async fn exchange(network: &MemoryNetwork, listener: &Listener, local: &str) {
+    let connection = Connection::connect_over(network.bind(local.parse().unwrap()).unwrap(), server_addr(), LinkConfig::default()).await.unwrap();
+    let (accepted, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    for i in 0..5u8 {
+        connection.send(Bytes::from(vec![i; 64])).await.unwrap();
+        assert_eq!(timeout(Duration::from_secs(5), accepted.recv()).await.unwrap().unwrap().map(|message| message.len()), Some(64));
+    }
+    let server = tokio::spawn(async move {
+        assert_eq!(timeout(Duration::from_secs(5), accepted.recv()).await.unwrap().unwrap(), None);
+        accepted.close().await.unwrap();
+    });
+    connection.close().await.unwrap();
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_concurrent_connections_carry_distinct_conn_ids() {
+    let output = Output::default();
+    let subscriber = tracing_subscriber::fmt().with_max_level(tracing::Level::DEBUG).with_ansi(false).with_writer(output.clone()).finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap();
+    tokio::join!(exchange(&network, &listener, "10.0.0.2:5001"), exchange(&network, &listener, "10.0.0.3:5002"));
+
+    // 两个客户端的对端都是同一个服务端地址，只能按 span 中记录的 conn_id 区分
+    let lines = output.lines();
+    let spanned = |role: &str| -> BTreeSet<u32> {
+        lines
+            .iter()
+            .filter(|line| line.contains(&format!("role={}", role)) && line.contains("segment"))
+            .map(|line| span_conn_id(line).unwrap_or_else(|| panic!("no conn_id in {}", line)))
+            .collect()
+    };
+    let clients = spanned("client");
+    assert_eq!(clients.len(), 2, "{:?}", clients);
+    assert!(!clients.contains(&0));
+    assert_eq!(spanned("server"), clients);
+
+    // 握手与关闭阶段的事件在各自的子 span 中
+    for conn_id in &clients {
+        let within = |phase: &str| lines.iter().any(|line| span_conn_id(line) == Some(*conn_id) && line.contains(phase));
+        assert!(within("}:handshake:"), "no handshake span for {}", conn_id);
+        assert!(within("}:close:"), "no close span for {}", conn_id);
+    }
+
+    // 监听器分发的握手段不在连接的 span 中，但 ACK 带着分配给连接的 conn_id
+    let demuxed: BTreeSet<u32> = lines
+        .iter()
+        .filter(|line| line.contains("listener{") && span_conn_id(line).is_none() && line.contains("segment"))
+        .filter_map(|line| field(line, " conn_id"))
+        .filter(|conn_id| *conn_id != 0)
+        .collect();
+    assert_eq!(demuxed, clients);
+}
@@ -0,0 +1,90 @@
+//! 文件传输集成测试：两端都经过 `FaultyTransport`，在丢包与乱序下传输一个随机内容的多兆字节文件并比对哈希
+#![cfg(feature = "tokio")]
+
+use bytes::Bytes;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::fault::FaultConfig;
+use link_rs::listener::Listener;
+use link_rs::transfer::{self, FileHeader, TransferError};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::timeout;
+
+const SIZE: usize = 4 * 1024 * 1024 + 123;
+
+fn lossy(seed: u64) -> LinkConfig {
+    LinkConfig { faults: Some(FaultConfig { loss: 0.02, reorder: 0.01, seed, ..FaultConfig::default() }), ..LinkConfig::default() }
+}
+
+// 每个测试独占的临时目录
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("link-rs-transfer-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_lossy_file_transfer() {
+    let dir = scratch("lossy");
+    let (source, output) = (dir.join("source"), dir.join("output"));
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::create_dir_all(&output).unwrap();
+    let mut content = vec![0u8; SIZE];
+    getrandom::fill(&mut content).unwrap();
+    let file = source.join("random.bin");
+    std::fs::write(&file, &content).unwrap();
+
+    let listener = Listener::bind_with("127.0.0.1:0", lossy(1)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let sender = tokio::spawn(async move {
+        let client = Connection::connect_with(addr, lossy(2)).await.unwrap();
+        let sent = transfer::send_file(&client, &file, |_| {}).await.unwrap();
+        // 与 faults 测试相同：最后一次关闭在丢包下可能等不到对端，传输结果已经确定
+        let _ = client.close().await;
+        sent
+    });
+
+    let received = timeout(Duration::from_secs(120), async {
+        let (server, _) = listener.accept().await.unwrap();
+        let received = transfer::recv_file(&server, &output, |_| {}).await.unwrap();
+        assert_eq!(server.recv().await.unwrap(), None);
+        let _ = server.close().await;
+        received
+    })
+    .await
+    .expect("transfer did not finish");
+    let sent = timeout(Duration::from_secs(10), sender).await.unwrap().unwrap();
+
+    assert_eq!((sent.bytes, received.bytes), (SIZE as u64, SIZE as u64));
+    assert_eq!(received.path, output.join("random.bin"));
+    assert_eq!(transfer::hash_file(&received.path).await.unwrap(), transfer::hash_file(&sent.path).await.unwrap());
+    assert!(std::fs::read(&received.path).unwrap() == content, "received file differs");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_hash_mismatch_is_rejected() {
+    let dir = scratch("mismatch");
+    let listener = Listener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let sender = tokio::spawn(async move {
+        let client = Connection::connect(addr).await.unwrap();
+        let header = FileHeader { name: "bad.bin".to_string(), size: 5, sha256: [0; 32] };
+        client.send(header.encode()).await.unwrap();
+        client.send(Bytes::from_static(b"hello")).await.unwrap();
+        let verdict = client.recv().await.unwrap();
+        let _ = client.close().await;
+        verdict
+    });
+
+    let (server, _) = listener.accept().await.unwrap();
+    let result = transfer::recv_file(&server, &dir, |_| {}).await;
+    assert_eq!(result, Err(TransferError::HashMismatch));
+    assert_eq!(sender.await.unwrap(), Some(Bytes::from_static(b"BAD")));
+    let _ = server.close().await;
+    // 校验失败时既没有最终文件，也没有留下 .part
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,85 @@
+//! 同一组核心场景跑在每一种传输上：tokio 的 `UdpSocket`（`tokio` 特性）、`MemoryTransport`，
+//! 以及装箱成 `Box<dyn Transport>` / `Arc<dyn Transport>` 的传输（包括包在内存传输外面的 `FaultyTransport`）。
+//! 场景：握手、双向按序收发、附加流、FIN 交换后两端都关闭
+
+mod common;
+
+use bytes::Bytes;
+use common::{addr, server_addr};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::fault::{FaultConfig, FaultyTransport};
+use link_rs::listener::Listener;
+use link_rs::state::ConnState;
+use link_rs::transport::{MemoryNetwork, Transport};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::timeout;
+
+const STEP: Duration = Duration::from_secs(10);
+
+// 在 `server` 上监听、经 `client` 连接，跑完整个场景
+async fn exercise(server: impl Transport, client: impl Transport) {
+    let addr = server.local_addr().unwrap();
+    let listener = Listener::with_transport(server, LinkConfig::default()).unwrap();
+    let connection = timeout(STEP, Connection::connect_over(client, addr, LinkConfig::default())).await.expect("handshake").unwrap();
+    let (accepted, peer) = timeout(STEP, listener.accept()).await.expect("accept").unwrap();
+    assert_eq!(peer, connection.local_addr().unwrap());
+    assert_eq!((connection.state(), accepted.state()), (ConnState::Established, ConnState::Established));
+
+    for i in 0..20u32 {
+        connection.send(Bytes::from(format!("ping {}", i))).await.unwrap();
+    }
+    for i in 0..20u32 {
+        let message = timeout(STEP, accepted.recv()).await.expect("server recv").unwrap();
+        assert_eq!(message, Some(Bytes::from(format!("ping {}", i))));
+        accepted.send(Bytes::from(format!("pong {}", i))).await.unwrap();
+    }
+    for i in 0..20u32 {
+        assert_eq!(timeout(STEP, connection.recv()).await.expect("client recv").unwrap(), Some(Bytes::from(format!("pong {}", i))));
+    }
+
+    let stream = connection.open_stream().unwrap();
+    stream.send(Bytes::from_static(b"on a stream")).await.unwrap();
+    let remote = timeout(STEP, accepted.accept_stream()).await.expect("accept stream").unwrap();
+    assert_eq!(remote.id(), stream.id());
+    assert_eq!(timeout(STEP, remote.recv()).await.expect("stream recv").unwrap(), Some(Bytes::from_static(b"on a stream")));
+    drop((stream, remote));
+
+    let (closed, _) = tokio::join!(timeout(STEP, connection.close()), async {
+        assert_eq!(timeout(STEP, accepted.recv()).await.expect("peer fin").unwrap(), None);
+        timeout(STEP, accepted.close()).await.expect("server close").unwrap();
+    });
+    closed.expect("client close").unwrap();
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_udp_socket() {
+    let server = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let client = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    exercise(server, client).await;
+}
+
+#[tokio::test]
+async fn test_memory_transport() {
+    let network = MemoryNetwork::new();
+    exercise(network.bind(server_addr()).unwrap(), network.bind(addr(2, 0)).unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_boxed_transports() {
+    let network = MemoryNetwork::new();
+    let server: Box<dyn Transport> = Box::new(network.bind(server_addr()).unwrap());
+    let client: Arc<dyn Transport> = Arc::new(network.bind(addr(2, 0)).unwrap());
+    exercise(server, client).await;
+}
+
+#[tokio::test]
+async fn test_faulty_transport_over_memory() {
+    let network = MemoryNetwork::new();
+    let faults = FaultConfig { loss: 0.1, duplicate: 0.05, reorder: 0.1, seed: 7, ..FaultConfig::default() };
+    let server: Box<dyn Transport> = Box::new(FaultyTransport::new(network.bind(server_addr()).unwrap(), &faults, 1 << 20));
+    let client: Box<dyn Transport> = Box::new(FaultyTransport::new(network.bind(addr(2, 0)).unwrap(), &FaultConfig { seed: 8, ..faults }, 1 << 20));
+    exercise(server, client).await;
+}
@@ -0,0 +1,78 @@
+//! 部分可靠集成测试：设置了 TTL 的消息的每次发送都被丢弃，到期后发送方放弃它并发出 Skip，
+//! 之后的消息不再等它、及时按序交付，接收方只报告一次空缺；没有到期的消息照常可靠
+mod common;
+
+use bytes::Bytes;
+use common::{contains, server_addr};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use link_rs::sender::SendOptions;
+use link_rs::transport::MemoryNetwork;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+
+const STALE: &[u8] = b"telemetry sample that never gets through";
+const TTL: Duration = Duration::from_millis(200);
+
+#[tokio::test]
+async fn test_expired_message_is_skipped_and_later_ones_arrive() {
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap();
+    let config = LinkConfig { nodelay: true, ..LinkConfig::default() };
+    let client = Connection::connect_over(network.bind("10.0.0.2:5000".parse().unwrap()).unwrap(), server_addr(), config).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    network.set_filter(|datagram, _, _| !contains(datagram, STALE));
+
+    let started = Instant::now();
+    // 每条消息之后让驱动任务发出，各占一个数据报，丢失的只有过期的那条
+    client.send_with(Bytes::from_static(STALE), SendOptions::ttl(TTL)).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    for i in 0..5 {
+        client.send_with(Bytes::from(format!("sample {}", i)), SendOptions::ttl(TTL)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    for i in 0..5 {
+        let message = timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap();
+        assert_eq!(message, Bytes::from(format!("sample {}", i)));
+    }
+    // 只等 TTL 与一两次往返，不等丢失的消息的重传
+    assert!(started.elapsed() < TTL + Duration::from_millis(300), "{:?}", started.elapsed());
+
+    // 之后的消息照常交付，空缺只报告一次
+    for i in 5..10 {
+        client.send(Bytes::from(format!("sample {}", i))).await.unwrap();
+        assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap(), Bytes::from(format!("sample {}", i)));
+    }
+    assert_eq!(server.stats().receiver.skipped, 1);
+    assert_eq!(client.stats().sender.expired, 1);
+
+    network.clear_filter();
+    let closing = tokio::spawn(async move { server.close().await });
+    client.close().await.unwrap();
+    closing.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_expired_fragmented_message_is_skipped_whole() {
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap();
+    let client = Connection::connect_over(network.bind("10.0.0.2:5000".parse().unwrap()).unwrap(), server_addr(), LinkConfig::default()).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+
+    // 三片的消息只有中间一片一直丢失：首尾两片到达，整条消息仍被跳过
+    let mut message = vec![1u8; 4000];
+    message[1500..1500 + STALE.len()].copy_from_slice(STALE);
+    network.set_filter(|datagram, _, _| !contains(datagram, STALE));
+    client.send_msg_with(Bytes::from(message), SendOptions::ttl(TTL)).await.unwrap();
+    client.send(Bytes::from_static(b"after")).await.unwrap();
+
+    assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap(), Bytes::from_static(b"after"));
+    // 统计由驱动任务发布：让服务端的驱动任务再跑一轮
+    server.send(Bytes::from_static(b"ok")).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), client.recv()).await.unwrap().unwrap().unwrap(), Bytes::from_static(b"ok"));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(server.stats().receiver.skipped, 1);
+    assert_eq!(client.stats().sender.expired, 1);
+    network.clear_filter();
+}
@@ -0,0 +1,88 @@
+//! 运行期间调整参数的集成测试：传输中途打开 nodelay 后同样的小写入不再合并，发出的数据报明显变多；
+//! 调整握手决定的 MSS 被拒绝，连接照常收发
+
+mod common;
+
+use bytes::Bytes;
+use common::{client_addr, pair};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::error::LinkError;
+use link_rs::transport::MemoryNetwork;
+use link_rs::tuning::{ConnOption, OptionName};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::time::timeout;
+
+const WRITES: usize = 40;
+
+// 监听器随返回值一起存活
+// 间隔 1ms 写入 `WRITES` 条小消息，等对端全部收到，返回这期间客户端发出的数据报数
+async fn trickle(client: &Connection, server: &Connection, sent: &AtomicUsize, phase: &str) -> usize {
+    let before = sent.load(Ordering::Relaxed);
+    for i in 0..WRITES {
+        client.send(Bytes::from(format!("{} {}", phase, i))).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    for i in 0..WRITES {
+        let message = timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap();
+        assert_eq!(message, Some(Bytes::from(format!("{} {}", phase, i))));
+    }
+    sent.load(Ordering::Relaxed) - before
+}
+
+#[tokio::test]
+async fn test_nodelay_toggled_mid_transfer_stops_coalescing() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { nagle_delay: Duration::from_millis(50), ..LinkConfig::default() };
+    let (_listener, client, server) = pair(&network, config).await;
+    let sent = Arc::new(AtomicUsize::new(0));
+    let counted = sent.clone();
+    network.set_filter(move |_, from, _| {
+        if from == client_addr() {
+            counted.fetch_add(1, Ordering::Relaxed);
+        }
+        true
+    });
+
+    assert_eq!(client.get_option(OptionName::Nodelay), ConnOption::Nodelay(false));
+    let coalesced = trickle(&client, &server, &sent, "bulk").await;
+    client.set_option(ConnOption::Nodelay(true)).unwrap();
+    assert_eq!(client.get_option(OptionName::Nodelay), ConnOption::Nodelay(true));
+    let immediate = trickle(&client, &server, &sent, "interactive").await;
+    assert!(immediate >= WRITES && coalesced * 2 < immediate, "coalesced {} datagrams, then {} with nodelay", coalesced, immediate);
+    assert!(client.stats().nodelay);
+
+    // 关回去之后重新合并
+    client.set_option(ConnOption::Nodelay(false)).unwrap();
+    let again = trickle(&client, &server, &sent, "bulk again").await;
+    assert!(again * 2 < immediate, "{} datagrams after turning nodelay off", again);
+}
+
+#[tokio::test]
+async fn test_handshake_fixed_options_cannot_change() {
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair(&network, LinkConfig::default()).await;
+    let mss = client.mss();
+    assert_eq!(client.set_option(ConnOption::Mss(mss / 2)), Err(LinkError::NotAdjustable(OptionName::Mss)));
+    assert_eq!(client.get_option(OptionName::Mss), ConnOption::Mss(mss));
+    let checksum = client.checksum();
+    assert_eq!(client.set_option(ConnOption::Checksum(checksum)), Err(LinkError::NotAdjustable(OptionName::Checksum)));
+    assert!(matches!(client.set_option(ConnOption::KeepaliveInterval(Duration::ZERO)), Err(LinkError::Config(_))));
+
+    // 协商出的参数调整后反映在 `negotiated` 中，只影响本端
+    let peer = server.negotiated();
+    client.set_option(ConnOption::AckEvery(4)).unwrap();
+    client.set_option(ConnOption::KeepaliveInterval(Duration::from_secs(3))).unwrap();
+    assert_eq!((client.negotiated().ack_every, client.negotiated().keepalive_interval), (4, Duration::from_secs(3)));
+    assert_eq!(server.negotiated(), peer);
+
+    // 连接不受影响
+    let large: Bytes = (0..20_000).map(|i| (i % 251) as u8).collect();
+    client.send_msg(large.clone()).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), server.recv_msg()).await.unwrap().unwrap(), large);
+    let closing = tokio::spawn(async move { server.close().await });
+    client.close().await.unwrap();
+    closing.await.unwrap().unwrap();
+}
@@ -0,0 +1,55 @@
+//! 无序消息集成测试：较早的有序消息丢失时，之后发出的无序消息不等重传、先于它交付，
+//! 有序消息在重传到达后仍按发送顺序交付
+
+mod common;
+
+use bytes::Bytes;
+use common::{contains, server_addr};
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use link_rs::sender::SendOptions;
+use link_rs::transport::MemoryNetwork;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::time::timeout;
+
+const LOST: &[u8] = b"ordered message that is lost once";
+
+#[tokio::test]
+async fn test_unordered_messages_overtake_a_lost_one() {
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap();
+    let config = LinkConfig { nodelay: true, ..LinkConfig::default() };
+    let client = Connection::connect_over(network.bind("10.0.0.2:5000".parse().unwrap()).unwrap(), server_addr(), config).await.unwrap();
+    let (server, _) = timeout(Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+
+    // 丢弃第一次发出的 `LOST`，重传照常通过
+    let seen = Arc::new(AtomicUsize::new(0));
+    let counted = seen.clone();
+    network.set_filter(move |datagram, _, _| !contains(datagram, LOST) || counted.fetch_add(1, Ordering::Relaxed) > 0);
+
+    // 每条消息之后让驱动任务发出，各占一个数据报
+    let unordered = SendOptions { ordered: false, ..SendOptions::default() };
+    let ordered = SendOptions::default();
+    for (message, options) in [(LOST, ordered), (b"ordered 1", ordered), (b"unordered 1", unordered), (b"unordered 2", unordered), (b"ordered 2", ordered)] {
+        client.send_with(Bytes::from_static(message), options).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    let mut received = Vec::new();
+    for _ in 0..5 {
+        received.push(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap());
+    }
+    assert_eq!(
+        received,
+        [&b"unordered 1"[..], b"unordered 2", LOST, b"ordered 1", b"ordered 2"].map(Bytes::from_static)
+    );
+    assert!(seen.load(Ordering::Relaxed) >= 2, "lost message was not retransmitted");
+
+    network.clear_filter();
+    let closing = tokio::spawn(async move { server.close().await });
+    client.close().await.unwrap();
+    closing.await.unwrap().unwrap();
+}
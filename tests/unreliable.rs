@@ -0,0 +1,114 @@
+//! 不可靠消息集成测试：有丢包的传输上可靠消息全部按序到达、不可靠消息部分到达且不重复，
+//! 两者交替发送互不阻塞；只收到不可靠消息的一端不发出确认
+
+mod common;
+
+use bytes::Bytes;
+use common::{client_addr, pair_with, server_addr};
+use link_rs::config::LinkConfig;
+use link_rs::error::LinkError;
+use link_rs::transport::MemoryNetwork;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::time::timeout;
+
+const MESSAGES: u32 = 100;
+
+// 丢包时更快地重传
+fn config() -> LinkConfig {
+    LinkConfig { nodelay: true, min_rto: Duration::from_millis(50), max_rto: Duration::from_millis(400), ..LinkConfig::default() }
+}
+
+fn id(message: &Bytes) -> u32 {
+    u32::from_be_bytes(message[..4].try_into().unwrap())
+}
+
+#[tokio::test]
+async fn test_lossy_mix_of_reliable_and_unreliable() {
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair_with(&network, LinkConfig::default(), config()).await;
+
+    // 丢弃客户端发出的每第 4 个数据报
+    let mut sent = 0;
+    network.set_filter(move |_, _, to| {
+        if to != server_addr() {
+            return true;
+        }
+        sent += 1;
+        sent % 4 != 0
+    });
+
+    let server = Arc::new(server);
+    let unreliable = tokio::spawn({
+        let server = server.clone();
+        async move {
+            let mut ids = Vec::new();
+            while let Some(message) = server.recv_unreliable().await.unwrap() {
+                ids.push(id(&message));
+            }
+            ids
+        }
+    });
+    let reliable = tokio::spawn({
+        let server = server.clone();
+        async move {
+            let mut ids = Vec::new();
+            while let Some(message) = timeout(Duration::from_secs(10), server.recv()).await.unwrap().unwrap() {
+                ids.push(id(&message));
+            }
+            ids
+        }
+    });
+
+    let mut blocked = 0;
+    for i in 0..MESSAGES {
+        client.send(Bytes::from(i.to_be_bytes().to_vec())).await.unwrap();
+        match client.send_unreliable(Bytes::from(i.to_be_bytes().to_vec())) {
+            Ok(()) => {}
+            Err(LinkError::WouldBlock) => blocked += 1,
+            Err(e) => panic!("{}", e),
+        }
+        // 按间隔产生的采样：让驱动任务有机会发出
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    assert!(blocked < MESSAGES / 2, "{} blocked", blocked);
+    client.shutdown_write().await.unwrap();
+
+    assert_eq!(timeout(Duration::from_secs(10), reliable).await.unwrap().unwrap(), (0..MESSAGES).collect::<Vec<_>>());
+    // 读方向在对端的 FIN 之后结束
+    let unreliable = timeout(Duration::from_secs(10), unreliable).await.unwrap().unwrap();
+    assert!(!unreliable.is_empty() && unreliable.len() < (MESSAGES - blocked) as usize, "{} of {}", unreliable.len(), MESSAGES - blocked);
+    assert!(unreliable.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", unreliable);
+    network.clear_filter();
+    let server = Arc::into_inner(server).unwrap();
+    let closing = tokio::spawn(async move { server.close().await });
+    client.close().await.unwrap();
+    closing.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_unreliable_messages_are_not_acknowledged() {
+    let network = MemoryNetwork::new();
+    let (_listener, client, server) = pair_with(&network, LinkConfig::default(), config()).await;
+    // 握手完成之后，统计服务端发往客户端的数据报
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let replies = Arc::new(AtomicUsize::new(0));
+    let counted = replies.clone();
+    network.set_filter(move |_, _, to| {
+        if to == client_addr() {
+            counted.fetch_add(1, Ordering::Relaxed);
+        }
+        true
+    });
+
+    for i in 0..10u32 {
+        client.send_unreliable(Bytes::from(i.to_be_bytes().to_vec())).unwrap();
+        let message = timeout(Duration::from_secs(5), server.recv_unreliable()).await.unwrap().unwrap().unwrap();
+        assert_eq!(id(&message), i);
+    }
+    // 超过延迟确认的时间仍没有任何回复
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(replies.load(Ordering::Relaxed), 0);
+    assert_eq!(client.unacknowledged(), Vec::<Bytes>::new());
+}
@@ -0,0 +1,136 @@
+//! 线上格式的黄金测试向量：`tests/vectors/` 下每个 hex 文件是一个数据报，`manifest.toml` 记录期望的解码结果。
+//! 合法向量解码后逐字段比对清单，再重新编码，必须与文件逐字节相同；非法向量解码必须返回清单中的错误。
+//! 段头解析（`segment::parse_header`）在每个向量上与完整解码一致。
+//! 向量由 `cargo run --bin gen-vectors` 生成，这里的失败意味着线上格式变了：要么修正代码，要么有意重新生成
+
+use link_rs::segment::{self, Segment, SegmentFlags, SegmentType};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+const FLAGS: [(&str, SegmentFlags); 8] = [
+    ("ACK", SegmentFlags::ACK),
+    ("SACK", SegmentFlags::SACK),
+    ("SEALED", SegmentFlags::SEALED),
+    ("AUTH", SegmentFlags::AUTH),
+    ("TOKEN", SegmentFlags::TOKEN),
+    ("CE", SegmentFlags::CE),
+    ("ECE", SegmentFlags::ECE),
+    ("UNRELIABLE", SegmentFlags::UNRELIABLE),
+];
+
+fn dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/vectors")
+}
+
+fn manifest() -> Vec<Table> {
+    let text = std::fs::read_to_string(dir().join("manifest.toml")).unwrap();
+    let manifest: Table = text.parse().unwrap();
+    manifest["vector"].as_array().unwrap().iter().map(|vector| vector.as_table().unwrap().clone()).collect()
+}
+
+// 去掉 `#` 注释后按空白分隔的十六进制字节
+fn read_hex(file: &str) -> Vec<u8> {
+    let text = std::fs::read_to_string(dir().join(file)).unwrap_or_else(|e| panic!("{}: {}", file, e));
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(str::split_whitespace)
+        .map(|byte| u8::from_str_radix(byte, 16).unwrap_or_else(|_| panic!("{}: bad hex byte {:?}", file, byte)))
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn str<'a>(vector: &'a Table, key: &str) -> &'a str {
+    vector[key].as_str().unwrap_or_else(|| panic!("{}: {} is not a string", vector["name"], key))
+}
+
+fn int(vector: &Table, key: &str) -> u64 {
+    vector[key].as_integer().unwrap_or_else(|| panic!("{}: {} is not an integer", vector["name"], key)) as u64
+}
+
+fn strings(vector: &Table, key: &str) -> Vec<String> {
+    vector[key].as_array().unwrap().iter().map(|value| value.as_str().unwrap().to_string()).collect()
+}
+
+fn is_valid(vector: &Table) -> bool {
+    !vector.contains_key("error")
+}
+
+#[test]
+fn test_valid_vectors_decode_and_reencode_exactly() {
+    for vector in manifest().iter().filter(|vector| is_valid(vector)) {
+        let name = str(vector, "name");
+        let bytes = read_hex(str(vector, "file"));
+        let segment = Segment::decode(&bytes).unwrap_or_else(|e| panic!("{}: {:?}", name, e));
+
+        assert_eq!(format!("{:?}", segment.segment_type()), str(vector, "type"), "{}", name);
+        let flags: Vec<_> = FLAGS.iter().filter(|(_, flag)| segment.flags().contains(*flag)).map(|(flag, _)| flag.to_string()).collect();
+        assert_eq!(flags, strings(vector, "flags"), "{}", name);
+        assert_eq!(segment.stream_id() as u64, int(vector, "stream_id"), "{}", name);
+        assert_eq!(segment.conn_id() as u64, int(vector, "conn_id"), "{}", name);
+        assert_eq!(segment.seq().get(), int(vector, "seq"), "{}", name);
+        assert_eq!(segment.ack().get(), int(vector, "ack"), "{}", name);
+        assert_eq!(segment.window() as u64, int(vector, "window"), "{}", name);
+        assert_eq!(segment.checksum().to_string(), str(vector, "checksum"), "{}", name);
+        let options: Vec<_> = segment.options().iter().map(|option| format!("{:?}", option)).collect();
+        assert_eq!(options, strings(vector, "options"), "{}", name);
+        assert_eq!(hex(segment.options().as_bytes()), str(vector, "options_hex"), "{}", name);
+        assert_eq!(hex(segment.data()), str(vector, "payload"), "{}", name);
+
+        assert_eq!(hex(&segment.encode().unwrap()), hex(&bytes), "{} does not re-encode byte for byte", name);
+    }
+}
+
+#[test]
+fn test_invalid_vectors_fail_with_expected_error() {
+    for vector in manifest().iter().filter(|vector| !is_valid(vector)) {
+        let name = str(vector, "name");
+        let error = Segment::decode(&read_hex(str(vector, "file"))).expect_err(name);
+        assert_eq!(format!("{:?}", error), str(vector, "error"), "{}", name);
+    }
+}
+
+#[test]
+fn test_parse_header_agrees_on_every_vector() {
+    for vector in manifest() {
+        let name = str(&vector, "name");
+        let bytes = read_hex(str(&vector, "file"));
+        match (segment::parse_header(&bytes), Segment::decode(&bytes)) {
+            (Ok(header), Ok(segment)) => {
+                assert_eq!((header.total_len, header.segment_type, header.flags), (bytes.len(), segment.segment_type(), segment.flags()), "{}", name);
+                assert_eq!((header.stream_id, header.conn_id, header.seq), (segment.stream_id(), segment.conn_id(), segment.seq()), "{}", name);
+            }
+            // 段头之后的错误（校验和、选项区、数据体）只有完整解码能发现
+            (Ok(_), Err(_)) => {}
+            (Err(e), decoded) => assert_eq!(decoded.map(|_| ()), Err(e), "{}", name),
+        }
+    }
+}
+
+#[test]
+fn test_vectors_cover_every_type_option_and_file() {
+    let vectors = manifest();
+    let types: BTreeSet<_> = vectors.iter().filter(|vector| is_valid(vector)).map(|vector| str(vector, "type").to_string()).collect();
+    let expected: BTreeSet<_> = (0..=u8::MAX).map_while(SegmentType::from_id).map(|segment_type| format!("{:?}", segment_type)).collect();
+    assert_eq!(types, expected);
+
+    let options: Vec<_> = vectors.iter().filter(|vector| is_valid(vector)).flat_map(|vector| strings(vector, "options")).collect();
+    for kind in ["Timestamp", "Mss", "SackPermitted", "Cwr", "Error", "AckNow"] {
+        assert!(options.iter().any(|option| option.starts_with(kind)), "no vector carries a {} option", kind);
+    }
+    for flag in FLAGS.map(|(flag, _)| Value::from(flag)) {
+        assert!(vectors.iter().any(|vector| is_valid(vector) && vector["flags"].as_array().unwrap().contains(&flag)), "no vector sets {}", flag);
+    }
+
+    // 目录里没有清单之外的向量文件
+    let listed: BTreeSet<_> = vectors.iter().map(|vector| str(vector, "file").to_string()).collect();
+    let files: BTreeSet<_> = std::fs::read_dir(dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|file| file.ends_with(".hex"))
+        .collect();
+    assert_eq!(files, listed);
+}
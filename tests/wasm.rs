@@ -0,0 +1,38 @@
+//! 浏览器编解码测试：以 wasm-bindgen-test 在无头浏览器中运行，
+//!     wasm-pack test --headless --firefox --no-default-features --features wasm -- --test wasm
+//! 编码出的段解码后类型、序列号与数据体不变，损坏的输入得到错误而不是 panic
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use link_rs::segment::{Segment, SegmentFlags, SegmentType};
+use link_rs::wasm::{decode_segment, encode_segment};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen_test]
+fn test_roundtrip() {
+    let encoded = encode_segment("Data", 42, b"hello").unwrap();
+    let segment = decode_segment(&encoded).unwrap();
+    assert_eq!((segment.type_name().as_str(), segment.seq(), segment.flags()), ("Data", 42, 0));
+    assert_eq!(segment.payload(), b"hello");
+
+    // 与原生的编码逐字节相同
+    assert_eq!(encoded, Segment::new(SegmentType::Data, 42, &b"hello"[..]).encode().unwrap().to_vec());
+
+    let ack = Segment::builder(SegmentType::Ack).ack(7).window(16).flags(SegmentFlags::ECE).build().unwrap();
+    let segment = decode_segment(&ack.encode().unwrap()).unwrap();
+    assert_eq!(segment.type_name(), "Ack");
+    assert_eq!(segment.flags(), ack.flags().bits());
+    assert!(SegmentFlags::from_bits(segment.flags()).unwrap().contains(SegmentFlags::ECE));
+    assert_eq!(segment.segment(), &ack);
+}
+
+#[wasm_bindgen_test]
+fn test_invalid_input_is_an_error() {
+    let mut encoded = encode_segment("Ping", 1, &[0; 8]).unwrap();
+    assert!(decode_segment(&encoded[..encoded.len() - 1]).is_err());
+    let last = encoded.len() - 1;
+    encoded[last] ^= 0xFF;
+    assert!(decode_segment(&encoded).is_err());
+    assert!(encode_segment("Bogus", 1, b"").is_err());
+}
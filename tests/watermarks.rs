@@ -0,0 +1,96 @@
+//! 水位事件集成测试：对端停止确认时发送队列涨过高水位，恰好报告一次 `High`；对端恢复、队列排空后恰好报告一次 `Low`。
+//! 不读的接收端的重排缓冲区同样如此，应用读出之后回到低水位
+mod common;
+
+use bytes::Bytes;
+use common::{pair_with, server_addr};
+use link_rs::config::LinkConfig;
+use link_rs::transport::MemoryNetwork;
+use link_rs::watermarks::{Level, Queue, WatermarkEvent, WatermarkEvents, Watermarks};
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+async fn next(events: &mut WatermarkEvents) -> WatermarkEvent {
+    timeout(Duration::from_secs(10), events.next()).await.unwrap().unwrap()
+}
+
+#[tokio::test]
+async fn test_stalled_peer_crosses_each_send_watermark_once() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { nodelay: true, send_watermarks: Some(Watermarks::new(4096, 16384)), ..LinkConfig::default() };
+    let (_listener, client, server) = pair_with(&network, LinkConfig::default(), config).await;
+    let mut events = client.subscribe_watermarks();
+    assert_eq!(client.send_queue_len(), 0);
+
+    // 对端的确认全部丢失：已发送的数据留在队列中，一直写到越过高水位之后
+    network.set_filter(|_, from, _| from != server_addr());
+    let message = Bytes::from(vec![7u8; 1000]);
+    for _ in 0..24 {
+        client.send(message.clone()).await.unwrap();
+    }
+    assert!(client.send_queue_len() >= 16384, "{}", client.send_queue_len());
+    let high = next(&mut events).await;
+    assert_eq!((high.queue, high.level), (Queue::Send, Level::High));
+    assert!((16384..17384).contains(&high.len), "{:?}", high);
+    sleep(Duration::from_millis(200)).await;
+    assert!(events.try_next().is_none());
+
+    // 确认恢复后重传被确认，队列排空：只有一次 `Low`
+    network.clear_filter();
+    let low = next(&mut events).await;
+    assert_eq!((low.queue, low.level), (Queue::Send, Level::Low));
+    assert!(low.len <= 4096 && low.at > high.at, "{:?}", low);
+    let mut received = 0;
+    while received < 24 {
+        assert_eq!(timeout(Duration::from_secs(5), server.recv()).await.unwrap().unwrap().unwrap(), message);
+        received += 1;
+    }
+    assert_eq!(client.send_queue_len(), 0);
+    sleep(Duration::from_millis(200)).await;
+    assert!(events.try_next().is_none());
+
+    // 连接结束后事件流结束
+    let (closed, _) = tokio::join!(client.close(), async {
+        assert_eq!(server.recv().await.unwrap(), None);
+        server.close().await.unwrap();
+    });
+    closed.unwrap();
+    assert!(timeout(Duration::from_secs(5), events.next()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_unread_messages_cross_the_receive_watermarks() {
+    let network = MemoryNetwork::new();
+    let client = LinkConfig { nodelay: true, ..LinkConfig::default() };
+    let server = LinkConfig { recv_watermarks: Some(Watermarks::new(4, 16)), ..LinkConfig::default() };
+    let (_listener, client, server) = pair_with(&network, server, client).await;
+    let mut events = server.subscribe_watermarks();
+
+    // 服务端不读：已收到的消息积压在重排缓冲区中
+    for i in 0..20u32 {
+        client.send(Bytes::copy_from_slice(&i.to_be_bytes())).await.unwrap();
+    }
+    let high = next(&mut events).await;
+    assert_eq!((high.queue, high.level), (Queue::Receive, Level::High));
+    assert!(high.len >= 16, "{:?}", high);
+    timeout(Duration::from_secs(5), async {
+        while server.recv_buffer_len() < 20 {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert!(events.try_next().is_none());
+
+    // 读出到只剩 4 段时越过低水位，之后继续读不再有事件
+    for i in 0..16u32 {
+        assert_eq!(server.recv().await.unwrap().unwrap().as_ref(), i.to_be_bytes());
+    }
+    let low = next(&mut events).await;
+    assert_eq!((low.queue, low.level, low.len), (Queue::Receive, Level::Low, 4));
+    for i in 16..20u32 {
+        assert_eq!(server.recv().await.unwrap().unwrap().as_ref(), i.to_be_bytes());
+    }
+    assert_eq!(server.recv_buffer_len(), 0);
+    assert!(events.try_next().is_none());
+}
@@ -0,0 +1,115 @@
+//! 多接收套接字集成测试：监听器以 SO_REUSEPORT 绑定 4 个套接字，32 个客户端并发发送，
+//! 每个套接字都分到了连接，每个客户端的消息在服务端按序到达；连接在各个套接字上并发建立与关闭、其间换上新的
+//! 访问控制列表，每个分片的连接表最终一致。不支持的平台退回一个套接字
+#![cfg(feature = "tokio")]
+
+use bytes::Bytes;
+use link_rs::acl::Acl;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use link_rs::socket;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+const WORKERS: usize = 4;
+const CLIENTS: usize = 32;
+const MESSAGES: usize = 200;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_workers_share_load_and_keep_order() {
+    let config = LinkConfig { workers: WORKERS, ..Default::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let server = listener.local_addr().unwrap();
+    assert_eq!(listener.workers(), if socket::REUSE_PORT { WORKERS } else { 1 });
+
+    let clients: Vec<_> = (0..CLIENTS)
+        .map(|id| {
+            tokio::spawn(async move {
+                let connection = Connection::connect(server).await.unwrap();
+                connection.send(Bytes::from(id.to_string())).await.unwrap();
+                for i in 0..MESSAGES {
+                    connection.send(Bytes::from(format!("{}-{}", id, i))).await.unwrap();
+                }
+                // 等服务端读完再关闭
+                connection.recv().await.unwrap();
+            })
+        })
+        .collect();
+
+    let mut readers = Vec::new();
+    for _ in 0..CLIENTS {
+        let (connection, _) = timeout(Duration::from_secs(10), listener.accept()).await.unwrap().unwrap();
+        readers.push(tokio::spawn(async move {
+            let id = connection.recv().await.unwrap().unwrap();
+            let id = String::from_utf8(id.to_vec()).unwrap();
+            for i in 0..MESSAGES {
+                let message = connection.recv().await.unwrap().unwrap();
+                assert_eq!(message, Bytes::from(format!("{}-{}", id, i)));
+            }
+            connection.send(Bytes::from_static(b"done")).await.unwrap();
+            connection
+        }));
+    }
+    let mut connections = Vec::new();
+    for reader in readers {
+        connections.push(timeout(Duration::from_secs(60), reader).await.expect("reader timed out").unwrap());
+    }
+    for client in clients {
+        timeout(Duration::from_secs(60), client).await.expect("client timed out").unwrap();
+    }
+
+    // 每个套接字都分到了连接，统计之和覆盖所有连接
+    let per_worker = listener.worker_stats();
+    assert_eq!(per_worker.len(), listener.workers());
+    assert!(per_worker.iter().all(|stats| stats.connections > 0), "{:?}", per_worker);
+    assert_eq!(listener.stats().connections, CLIENTS);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_churn_keeps_every_shard_consistent() {
+    const ROUNDS: usize = 4;
+    let config = LinkConfig { workers: WORKERS, ..Default::default() };
+    let listener = Listener::bind_with("127.0.0.1:0", config).await.unwrap();
+    let server = listener.local_addr().unwrap();
+
+    // 每一轮的连接并发建立、交换一条消息后由两端同时关闭；轮与轮之间换上新的访问控制列表，已建立的连接不受影响
+    for round in 0..ROUNDS {
+        let clients: Vec<_> = (0..CLIENTS)
+            .map(|id| {
+                tokio::spawn(async move {
+                    let connection = Connection::connect(server).await.unwrap();
+                    connection.send(Bytes::from(format!("{}-{}", round, id))).await.unwrap();
+                    assert_eq!(connection.recv().await.unwrap(), None);
+                    connection.close().await.unwrap();
+                })
+            })
+            .collect();
+        let mut servers = Vec::new();
+        for _ in 0..CLIENTS {
+            let (connection, _) = timeout(Duration::from_secs(10), listener.accept()).await.unwrap().unwrap();
+            servers.push(tokio::spawn(async move {
+                assert!(connection.recv().await.unwrap().unwrap().starts_with(format!("{}-", round).as_bytes()));
+                connection.close().await.unwrap();
+            }));
+        }
+        listener.set_acl(Acl { evict: true, ..Acl::default() });
+        for task in clients.into_iter().chain(servers) {
+            timeout(Duration::from_secs(30), task).await.expect("connection timed out").unwrap();
+        }
+    }
+
+    // 每个分片都移出了它的全部连接，留下的墓碑之和正好是关闭的连接数
+    timeout(Duration::from_secs(10), async {
+        loop {
+            let per_worker = listener.worker_stats();
+            if per_worker.iter().all(|stats| stats.connections == 0 && stats.half_open == 0) && listener.stats().tombstones == ROUNDS * CLIENTS {
+                break;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("{:?}", listener.worker_stats()));
+    assert_eq!(listener.occupancy("127.0.0.1".parse().unwrap()), (0, 0));
+}
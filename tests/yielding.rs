@@ -0,0 +1,88 @@
+//! 协作让出集成测试：单线程运行时上一条连接持续灌入小数据报，另一条安静连接的 Ping 往返时间仍然有界；
+//! 每处理一个数据报就让出一次时，可靠传输照样完整按序
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use bytes::Bytes;
+use common::server_addr;
+use link_rs::config::LinkConfig;
+use link_rs::connection::Connection;
+use link_rs::listener::Listener;
+use link_rs::ping::{PingOptions, Pinger};
+use link_rs::transport::MemoryNetwork;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_flood_does_not_starve_a_quiet_connection() {
+    let network = MemoryNetwork::new();
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), LinkConfig::default()).unwrap();
+    let flooder = Connection::connect_over(network.bind("10.0.0.2:5000".parse().unwrap()).unwrap(), server_addr(), LinkConfig::default()).await.unwrap();
+    let (flooded, _) = listener.accept().await.unwrap();
+    let quiet = Connection::connect_over(network.bind("10.0.0.3:5000".parse().unwrap()).unwrap(), server_addr(), LinkConfig::default()).await.unwrap();
+    let (_quiet_server, _) = listener.accept().await.unwrap();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let received = Arc::new(AtomicU64::new(0));
+    let flooding = tokio::spawn({
+        let stop = stop.clone();
+        async move {
+            let payload = Bytes::from_static(&[7; 32]);
+            while !stop.load(Ordering::Relaxed) {
+                // 发到本端的队列满为止，再让出一次：灌入的一方自己不长期占着线程
+                while flooder.send_unreliable(payload.clone()).is_ok() {}
+                tokio::task::yield_now().await;
+            }
+        }
+    });
+    let draining = tokio::spawn({
+        let received = received.clone();
+        async move {
+            while let Ok(Some(_)) = flooded.recv_unreliable().await {
+                received.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    });
+
+    let options = PingOptions { count: 20, interval: Duration::from_millis(10), timeout: Duration::from_secs(1) };
+    let summary = timeout(Duration::from_secs(10), Pinger::new(&quiet).run(&options, |_| {})).await.unwrap().unwrap();
+    stop.store(true, Ordering::Relaxed);
+    flooding.await.unwrap();
+    draining.abort();
+
+    assert!(received.load(Ordering::Relaxed) > 1000, "only {} flooded messages arrived", received.load(Ordering::Relaxed));
+    assert_eq!(summary.received, 20, "{:?}", summary);
+    assert!(summary.max_rtt.unwrap() < Duration::from_millis(250), "{:?}", summary);
+}
+
+#[tokio::test]
+async fn test_batch_size_does_not_change_delivery() {
+    let network = MemoryNetwork::new();
+    let config = LinkConfig { recv_batch: 1, ..LinkConfig::default() };
+    let listener = Listener::with_transport(network.bind(server_addr()).unwrap(), config.clone()).unwrap();
+    let client = Connection::connect_over(network.bind("10.0.0.2:5000".parse().unwrap()).unwrap(), server_addr(), config).await.unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+
+    let sent: Vec<Bytes> = (0..500u32).map(|i| Bytes::from(i.to_be_bytes().repeat(64))).collect();
+    let received = timeout(Duration::from_secs(20), async {
+        let sending = async {
+            for message in &sent {
+                client.send(message.clone()).await.unwrap();
+            }
+        };
+        let receiving = async {
+            let mut received = Vec::new();
+            while received.len() < sent.len() {
+                received.push(server.recv().await.unwrap().unwrap());
+            }
+            received
+        };
+        tokio::join!(sending, receiving).1
+    })
+    .await
+    .unwrap();
+    assert!(received == sent, "messages differ");
+}